@@ -0,0 +1,309 @@
+//! `bolide run --cache`：按源码哈希缓存 AOT 编译出的可执行文件
+//!
+//! JIT 产物无法落盘，所以缓存走 AOT 路径。缓存键由编译器版本、目标平台、
+//! 运行时库（大小和修改时间）、主文件内容以及每个导入文件的路径和内容组成，
+//! 导入文件列表与编译器加载模块时相同（`bolide_compiler::import_files`）。
+//!
+//! 计算缓存键需要导入文件列表，解析大文件本身就要不少时间，所以每个主文件内容另存一份
+//! 导入列表（`<哈希>.imports`），命中时不再解析源码；导入路径是源码里的字面量，只取决于内容。
+//!
+//! 命中时以子进程运行缓存的可执行文件（程序崩溃不影响 bolide 本身）；
+//! 未命中时编译、链接、存入缓存再运行。缓存目录超过上限时按最近使用时间淘汰，
+//! 最近使用时间就是文件的修改时间，命中时刷新。
+//!
+//! 环境变量：`BOLIDE_CACHE_DIR` 覆盖缓存目录，`BOLIDE_CACHE_MAX_MB` 设置上限（默认 512），
+//! `BOLIDE_RUN_CACHE=1` 让不带 `--cache` 的 `bolide run` 也使用缓存（`--no-cache` 或只有 JIT
+//! 支持的选项时仍用 JIT）。缓存运行与 JIT 运行还有 `AOT_DIVERGENCES` 列出的差异，所以默认不开启。
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+
+/// 默认缓存上限（MB）
+const DEFAULT_MAX_MB: u64 = 512;
+
+/// 缓存运行与 `bolide run` 已知的行为差异（AOT 与 JIT 一致之前，未命中时打印）
+pub const AOT_DIVERGENCES: &[&str] = &[
+    "bool values print as true/false (the JIT prints 1/0)",
+    "no `Result:` line is printed; a failing program exits with its status code",
+    "programs the AOT compiler rejects (e.g. functions reading globals) run uncached with the JIT",
+];
+
+/// `BOLIDE_RUN_CACHE` 是否要求 `bolide run` 默认使用缓存
+pub fn enabled_by_env() -> bool {
+    is_enabled(std::env::var("BOLIDE_RUN_CACHE").ok().as_deref())
+}
+
+/// 开关型环境变量的取值：1 / true / yes / on（不区分大小写）为开
+fn is_enabled(value: Option<&str>) -> bool {
+    value.is_some_and(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+}
+
+/// 运行缓存目录
+pub struct RunCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl RunCache {
+    /// 按环境变量打开缓存（目录不存在时不创建）
+    pub fn open() -> Self {
+        let max_mb = std::env::var("BOLIDE_CACHE_MAX_MB")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_MAX_MB);
+        Self { dir: default_dir(), max_bytes: max_mb * 1024 * 1024 }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 缓存项路径
+    pub fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}{}", key, std::env::consts::EXE_SUFFIX))
+    }
+
+    /// 主文件 `source` 的导入列表，上次编译时记录；没有记录时返回 None
+    pub fn read_imports(&self, source: &str) -> Option<Vec<String>> {
+        let text = fs::read_to_string(self.imports_path(source)).ok()?;
+        Some(text.lines().map(str::to_string).collect())
+    }
+
+    /// 记录导入列表（先写临时文件再改名）；失败只是下次命中时要重新解析，忽略
+    pub fn write_imports(&self, source: &str, files: &[String]) {
+        let path = self.imports_path(source);
+        let temp = path.with_extension(format!("imports.{}.tmp", std::process::id()));
+        let text: String = files.iter().map(|file| format!("{}\n", file)).collect();
+        if fs::write(&temp, text).is_err() || fs::rename(&temp, &path).is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+    }
+
+    fn imports_path(&self, source: &str) -> PathBuf {
+        let mut buf = env!("CARGO_PKG_VERSION").as_bytes().to_vec();
+        buf.push(0);
+        buf.extend_from_slice(source.as_bytes());
+        self.dir.join(format!("{:016x}.imports", bolide_runtime::hash_bytes(&buf) as u64))
+    }
+
+    /// 写入中的临时路径（链接完成后改名为缓存项，避免其他进程看到半个文件）
+    pub fn temp_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}.tmp{}", key, std::process::id(), std::env::consts::EXE_SUFFIX))
+    }
+
+    /// 标记为最近使用；Windows 上正在运行的可执行文件无法写入，忽略失败
+    pub fn touch(&self, path: &Path) {
+        if let Ok(file) = fs::File::options().write(true).open(path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+    }
+
+    /// 超出上限时删除最久未使用的项（`keep` 除外）
+    ///
+    /// 删除失败的项（例如 Windows 上正在运行的可执行文件）跳过，下次再试。
+    pub fn evict(&self, keep: &Path) {
+        let mut entries = self.entries();
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, size, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            if path == keep {
+                continue;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total -= size;
+            }
+        }
+    }
+
+    /// 清空缓存，返回删除的程序数和总字节数（导入列表只计字节）
+    pub fn clear(&self) -> (usize, u64) {
+        let mut removed = 0;
+        let mut bytes = 0;
+        for (path, size, _) in self.entries() {
+            if fs::remove_file(&path).is_ok() {
+                if path.extension().is_none_or(|ext| ext != "imports") {
+                    removed += 1;
+                }
+                bytes += size;
+            }
+        }
+        (removed, bytes)
+    }
+
+    /// 缓存目录中的文件：(路径, 大小, 修改时间)
+    fn entries(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        let Ok(read_dir) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        read_dir
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let meta = e.metadata().ok()?;
+                if !meta.is_file() {
+                    return None;
+                }
+                Some((e.path(), meta.len(), meta.modified().unwrap_or(SystemTime::UNIX_EPOCH)))
+            })
+            .collect()
+    }
+}
+
+/// 用户缓存目录下的 bolide/run
+fn default_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("BOLIDE_CACHE_DIR") {
+        return PathBuf::from(dir);
+    }
+    #[cfg(target_os = "windows")]
+    let base = std::env::var_os("LOCALAPPDATA").map(PathBuf::from);
+    #[cfg(not(target_os = "windows"))]
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")));
+    base.unwrap_or_else(std::env::temp_dir).join("bolide").join("run")
+}
+
+/// 计算缓存键，`imports` 是 `bolide_compiler::import_files` 给出的导入文件
+///
+/// 哈希值本身不跨版本稳定，但版本号是键的一部分，旧版本的缓存项只会变成未命中。
+pub fn cache_key(source: &str, imports: &[String], runtime_lib: &Path) -> String {
+    let mut buf: Vec<u8> = Vec::with_capacity(source.len() + 256);
+    let mut field = |bytes: &[u8]| {
+        buf.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+        buf.extend_from_slice(bytes);
+    };
+
+    field(env!("CARGO_PKG_VERSION").as_bytes());
+    field(std::env::consts::OS.as_bytes());
+    field(std::env::consts::ARCH.as_bytes());
+
+    // 运行时库重新构建后缓存项作废
+    let lib_stamp = fs::metadata(runtime_lib)
+        .map(|m| {
+            let modified = m.modified().ok()
+                .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map(|d| d.as_nanos())
+                .unwrap_or(0);
+            format!("{}:{}", m.len(), modified)
+        })
+        .unwrap_or_default();
+    field(lib_stamp.as_bytes());
+
+    field(source.as_bytes());
+    for file in imports {
+        field(file.as_bytes());
+        match fs::read(file) {
+            Ok(content) => field(&content),
+            // 缺失的模块会在编译时报错，这里只需让键与存在时不同
+            Err(_) => field(b"\0missing"),
+        }
+    }
+
+    format!("{:016x}", bolide_runtime::hash_bytes(&buf) as u64)
+}
+
+/// 运行缓存的可执行文件；程序失败时以同样的状态码退出
+///
+/// `--overflow-checks` 和 `--executor` 通过环境变量传给子进程。
+pub fn run_executable(path: &Path) -> io::Result<()> {
+    let overflow_checks = if bolide_runtime::overflow_checks_enabled() { "1" } else { "0" };
+    let executor = if bolide_runtime::single_threaded() { "single" } else { "threaded" };
+    let status = std::process::Command::new(path)
+        .env("BOLIDE_OVERFLOW_CHECKS", overflow_checks)
+        .env("BOLIDE_EXECUTOR", executor)
+        .status()?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_switch_values() {
+        for on in ["1", "true", "YES", " on "] {
+            assert!(is_enabled(Some(on)), "{}", on);
+        }
+        for off in ["", "0", "false", "off", "2"] {
+            assert!(!is_enabled(Some(off)), "{}", off);
+        }
+        assert!(!is_enabled(None));
+    }
+
+    fn key_for(source: &str, lib: &Path) -> String {
+        let program = bolide_parser::parse_source(source).unwrap();
+        cache_key(source, &bolide_compiler::import_files(&program), lib)
+    }
+
+    #[test]
+    fn test_key_tracks_sources() {
+        let dir = std::env::temp_dir().join(format!("bolide-cache-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let module = dir.join("util.bl");
+        let lib = dir.join("libruntime.a");
+        fs::write(&module, "fn one() -> int { return 1; }\n").unwrap();
+        fs::write(&lib, "lib").unwrap();
+
+        let main = format!("import \"{}\";\nprint(util.one());\n", module.display());
+        let first = key_for(&main, &lib);
+        assert_eq!(first, key_for(&main, &lib), "unchanged inputs must give the same key");
+
+        // 修改主文件
+        let edited = format!("{}print(2);\n", main);
+        assert_ne!(first, key_for(&edited, &lib));
+
+        // 修改导入的文件
+        fs::write(&module, "fn one() -> int { return 11; }\n").unwrap();
+        assert_ne!(first, key_for(&main, &lib));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_evict_oldest_first() {
+        let dir = std::env::temp_dir().join(format!("bolide-cache-evict-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cache = RunCache { dir: dir.clone(), max_bytes: 20 };
+
+        let old = cache.entry_path("old");
+        let new = cache.entry_path("new");
+        fs::write(&old, [0u8; 16]).unwrap();
+        fs::File::options().write(true).open(&old).unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1)).unwrap();
+        fs::write(&new, [0u8; 16]).unwrap();
+
+        cache.evict(&new);
+        assert!(!old.exists());
+        assert!(new.exists());
+
+        assert_eq!(cache.clear(), (1, 16));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_imports_recorded_per_source() {
+        let dir = std::env::temp_dir().join(format!("bolide-cache-imports-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cache = RunCache { dir: dir.clone(), max_bytes: u64::MAX };
+
+        let source = "import \"a.bl\";\nimport \"lib/b.bl\";\n";
+        assert_eq!(cache.read_imports(source), None);
+        cache.write_imports(source, &["a.bl".to_string(), "lib/b.bl".to_string()]);
+        assert_eq!(cache.read_imports(source).unwrap(), ["a.bl", "lib/b.bl"]);
+        cache.write_imports("print(1);\n", &[]);
+        assert_eq!(cache.read_imports("print(1);\n").unwrap(), Vec::<String>::new());
+        assert_eq!(cache.read_imports("print(2);\n"), None);
+
+        // 导入列表不算缓存的程序
+        assert_eq!(cache.clear().0, 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,116 @@
+//! `bolide compile --incremental`：每个导入的模块单独编译成目标文件并缓存
+//!
+//! 目标文件放在输出文件旁的 `.bolide-build/` 目录，文件名为 `模块名-键.o`。键由编译器版本、
+//! 目标三元组、是否忽略 `@trace`、是否共享容器（`--share-containers`）、模块路径和内容组成，键不变的模块直接复用目标文件。主程序声明了所有模块的
+//! 符号（`AotCompiler::compile_main`），每次都重新编译，然后与模块目标文件一起链接。
+//!
+//! 模块的顶层语句和完整编译时一样不会执行，模块没有初始化代码，链接顺序不影响行为。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bolide_compiler::AotCompiler;
+use bolide_parser::Program;
+
+/// 一个模块的目标文件
+pub struct ModuleObject {
+    /// import 路径
+    pub file: String,
+    pub object: PathBuf,
+    /// 本次重新编译（之前没有这个键的目标文件）
+    pub rebuilt: bool,
+    /// 重新编译时的编译警告
+    pub warnings: Vec<String>,
+}
+
+/// 输出文件对应的构建目录
+pub fn build_dir(output: &Path) -> PathBuf {
+    output.parent().unwrap_or(Path::new(".")).join(".bolide-build")
+}
+
+/// 模块目标文件的缓存键
+///
+/// 哈希值本身不跨版本稳定，但版本号是键的一部分，旧版本的目标文件只会变成未命中。
+pub fn module_key(file: &str, content: &[u8], triple: &str, no_trace: bool, share_containers: bool, no_opt: bool) -> String {
+    let mut buf: Vec<u8> = Vec::with_capacity(content.len() + 128);
+    let mut field = |bytes: &[u8]| {
+        buf.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+        buf.extend_from_slice(bytes);
+    };
+    field(env!("CARGO_PKG_VERSION").as_bytes());
+    field(triple.as_bytes());
+    field(&[no_trace as u8, share_containers as u8, no_opt as u8]);
+    field(file.as_bytes());
+    field(content);
+    format!("{:016x}", bolide_runtime::hash_bytes(&buf) as u64)
+}
+
+/// 编译或复用程序导入的每个模块，按 import 顺序返回
+///
+/// 同一模块旧键的目标文件在写入新文件后删除。
+pub fn build_modules(program: &Program, triple: &str, dir: &Path, no_trace: bool, share_containers: bool, no_opt: bool) -> Result<Vec<ModuleObject>, String> {
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create build directory {}: {}", dir.display(), e))?;
+
+    let mut objects = Vec::new();
+    for file in bolide_compiler::import_files(program) {
+        let content = fs::read(&file)
+            .map_err(|e| format!("Failed to load module '{}': {}", file, e))?;
+        let stem = Path::new(&file).file_stem().and_then(|s| s.to_str()).unwrap_or("module").to_string();
+        let object = dir.join(format!("{}-{}.o", stem, module_key(&file, &content, triple, no_trace, share_containers, no_opt)));
+        if object.exists() {
+            objects.push(ModuleObject { file, object, rebuilt: false, warnings: Vec::new() });
+            continue;
+        }
+
+        let mut compiler = AotCompiler::new()?;
+        compiler.set_no_trace(no_trace);
+        compiler.set_share_containers(share_containers);
+        compiler.set_no_opt(no_opt);
+        let result = compiler.compile_module(&file)
+            .map_err(|e| format!("in module '{}': {}", file, e))?;
+        // 先写临时文件再改名，中断的构建不会留下半个目标文件
+        let temp = object.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temp, &result.object_code)
+            .and_then(|_| fs::rename(&temp, &object))
+            .map_err(|e| format!("Failed to write object file {}: {}", object.display(), e))?;
+        remove_stale(dir, &stem, &object);
+        objects.push(ModuleObject { file, object, rebuilt: true, warnings: result.warnings });
+    }
+    Ok(objects)
+}
+
+/// 删除同一模块其他键的目标文件
+fn remove_stale(dir: &Path, stem: &str, keep: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let is_stale = name.strip_prefix(stem)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .and_then(|rest| rest.strip_suffix(".o"))
+            .is_some_and(|key| key.len() == 16 && key.chars().all(|c| c.is_ascii_hexdigit()));
+        if is_stale && path != keep {
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_key() {
+        let key = module_key("util.bl", b"fn f() {}", "x86_64-unknown-linux-gnu", false, false, false);
+        assert_eq!(key.len(), 16);
+        assert_eq!(key, module_key("util.bl", b"fn f() {}", "x86_64-unknown-linux-gnu", false, false, false));
+        assert_ne!(key, module_key("util.bl", b"fn g() {}", "x86_64-unknown-linux-gnu", false, false, false));
+        assert_ne!(key, module_key("lib/util.bl", b"fn f() {}", "x86_64-unknown-linux-gnu", false, false, false));
+        assert_ne!(key, module_key("util.bl", b"fn f() {}", "aarch64-unknown-linux-gnu", false, false, false));
+        assert_ne!(key, module_key("util.bl", b"fn f() {}", "x86_64-unknown-linux-gnu", true, false, false));
+        assert_ne!(key, module_key("util.bl", b"fn f() {}", "x86_64-unknown-linux-gnu", false, true, false));
+        assert_ne!(key, module_key("util.bl", b"fn f() {}", "x86_64-unknown-linux-gnu", false, false, true));
+    }
+}
@@ -0,0 +1,1018 @@
+//! `bolide lsp`：基于 stdio 的语言服务器
+//!
+//! 支持的请求：
+//! - 打开/修改文档时发布诊断：预处理警告、语法错误，以及 `JitCompiler::validate` 报告的编译错误
+//! - `textDocument/definition`：函数调用、类名、函数内的变量和参数、`self.字段`，
+//!   以及 `模块.函数` 跳到导入的文件
+//! - `textDocument/documentSymbol`：大纲（函数、类及其字段和方法、全局变量）
+//!
+//! AST 不带位置信息，定义位置来自对源码的词法扫描（跳过字符串和注释）。
+//! 编译错误也没有位置，诊断落在错误信息中提到的名字上，找不到时落在第一行。
+//!
+//! 位置换算：LSP 的列按 UTF-16 码元计，编译器报告的列按字符计，内部统一用字节偏移。
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+/// 运行语言服务器，直到客户端发送 `exit`
+pub fn run() -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let stdout = io::stdout();
+    let mut output = stdout.lock();
+    let mut server = Server::default();
+
+    while let Some(message) = read_message(&mut input)? {
+        if message.get("method").and_then(Value::as_str) == Some("exit") {
+            std::process::exit(if server.shutdown { 0 } else { 1 });
+        }
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+    }
+    Ok(())
+}
+
+// ==================== 消息收发 ====================
+
+/// 读取一条消息（`Content-Length` 头 + JSON 正文），输入结束时返回 None
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header"))?;
+    let mut body = vec![0u8; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+// ==================== 请求处理 ====================
+
+#[derive(Default)]
+struct Server {
+    /// 打开的文档：URI -> 文本
+    documents: HashMap<String, String>,
+    /// 已收到 shutdown
+    shutdown: bool,
+}
+
+impl Server {
+    /// 处理一条消息，返回要发给客户端的消息（响应和通知）
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message.get("method").and_then(Value::as_str).unwrap_or("");
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let id = message.get("id").cloned();
+
+        match method {
+            "initialize" => vec![response(id, json!({
+                "capabilities": {
+                    "textDocumentSync": { "openClose": true, "change": 1 },
+                    "definitionProvider": true,
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "bolide", "version": env!("CARGO_PKG_VERSION") },
+            }))],
+            "shutdown" => {
+                self.shutdown = true;
+                vec![response(id, Value::Null)]
+            }
+            "textDocument/didOpen" => {
+                let uri = str_at(&params, &["textDocument", "uri"]);
+                let text = str_at(&params, &["textDocument", "text"]);
+                self.documents.insert(uri.to_string(), text.to_string());
+                vec![self.publish_diagnostics(uri)]
+            }
+            "textDocument/didChange" => {
+                // 全量同步：最后一次变更就是完整文本
+                let uri = str_at(&params, &["textDocument", "uri"]);
+                let changes = params.get("contentChanges").and_then(Value::as_array);
+                if let Some(text) = changes.and_then(|c| c.last()).and_then(|c| c.get("text")).and_then(Value::as_str) {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                }
+                vec![self.publish_diagnostics(uri)]
+            }
+            "textDocument/didClose" => {
+                let uri = str_at(&params, &["textDocument", "uri"]);
+                self.documents.remove(uri);
+                vec![notification("textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": [] }))]
+            }
+            "textDocument/definition" => {
+                let uri = str_at(&params, &["textDocument", "uri"]);
+                let line = params.pointer("/position/line").and_then(Value::as_u64).unwrap_or(0) as usize;
+                let character = params.pointer("/position/character").and_then(Value::as_u64).unwrap_or(0) as usize;
+                let result = self.definition(uri, line, character).unwrap_or(Value::Null);
+                vec![response(id, result)]
+            }
+            "textDocument/documentSymbol" => {
+                let uri = str_at(&params, &["textDocument", "uri"]);
+                let text = self.documents.get(uri).map(String::as_str).unwrap_or("");
+                vec![response(id, document_symbols(text))]
+            }
+            _ => match id {
+                // 未知请求必须回复；未知通知忽略
+                Some(id) => vec![json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32601, "message": format!("method not supported: {}", method) },
+                })],
+                None => Vec::new(),
+            },
+        }
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> Value {
+        let text = self.documents.get(uri).map(String::as_str).unwrap_or("");
+        let diagnostics: Vec<Value> = diagnose(text).iter().map(|d| d.to_json(text)).collect();
+        notification("textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": diagnostics }))
+    }
+
+    fn definition(&self, uri: &str, line: usize, character: usize) -> Option<Value> {
+        let text = self.documents.get(uri)?;
+        let offset = position_to_offset(text, line, character);
+        let index = Index::build(text);
+        let (token, qualifier) = index.token_at(offset)?;
+
+        // 模块.名字：跳到导入的文件
+        if let Some(module) = qualifier.filter(|q| index.symbol_named(q).is_none() && *q != "self") {
+            let path = module_file(text, module, uri_to_path(uri).as_deref())?;
+            let module_uri = path_to_uri(&path);
+            let module_text = match self.documents.get(&module_uri) {
+                Some(open) => open.clone(),
+                None => std::fs::read_to_string(&path).ok()?,
+            };
+            let module_index = Index::build(&module_text);
+            let symbol = module_index.top_level(&token.text)?;
+            return Some(location(&module_uri, &module_text, symbol.name_start, symbol.name_end));
+        }
+
+        let symbol = index.resolve(&token.text, qualifier, offset)?;
+        Some(location(uri, text, symbol.name_start, symbol.name_end))
+    }
+}
+
+fn response(id: Option<Value>, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id.unwrap_or(Value::Null), "result": result })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn str_at<'a>(value: &'a Value, path: &[&str]) -> &'a str {
+    path.iter()
+        .try_fold(value, |v, key| v.get(key))
+        .and_then(Value::as_str)
+        .unwrap_or("")
+}
+
+fn location(uri: &str, text: &str, start: usize, end: usize) -> Value {
+    json!({ "uri": uri, "range": range_json(text, start, end) })
+}
+
+fn range_json(text: &str, start: usize, end: usize) -> Value {
+    let (start_line, start_char) = offset_to_position(text, start);
+    let (end_line, end_char) = offset_to_position(text, end);
+    json!({
+        "start": { "line": start_line, "character": start_char },
+        "end": { "line": end_line, "character": end_char },
+    })
+}
+
+// ==================== 位置换算 ====================
+
+/// 字节偏移 -> LSP 位置（行号从 0 开始，列按 UTF-16 码元计）
+fn offset_to_position(text: &str, offset: usize) -> (usize, usize) {
+    let offset = floor_char_boundary(text, offset);
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character = text[line_start..offset].encode_utf16().count();
+    (line, character)
+}
+
+/// LSP 位置 -> 字节偏移（列超出行尾时取行尾）
+fn position_to_offset(text: &str, line: usize, character: usize) -> usize {
+    let Some(line_start) = line_start(text, line) else {
+        return text.len();
+    };
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if c == '\n' || units >= character {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+/// 编译器报告的行列（从 1 开始，列按字符计）-> 字节偏移
+fn line_col_to_offset(text: &str, line: usize, col: usize) -> usize {
+    let Some(mut start) = line_start(text, line.saturating_sub(1)) else {
+        return text.len();
+    };
+    // 预处理去掉了 BOM，第一行的列不含它
+    if start == 0 && text.starts_with('\u{FEFF}') {
+        start = '\u{FEFF}'.len_utf8();
+    }
+    text[start..]
+        .char_indices()
+        .take_while(|&(_, c)| c != '\n')
+        .nth(col.saturating_sub(1))
+        .map_or_else(|| start + text[start..].find('\n').unwrap_or(text.len() - start), |(i, _)| start + i)
+}
+
+/// 第 `line` 行（从 0 开始）的起始字节偏移
+fn line_start(text: &str, line: usize) -> Option<usize> {
+    if line == 0 {
+        return Some(0);
+    }
+    text.match_indices('\n').nth(line - 1).map(|(i, _)| i + 1)
+}
+
+fn floor_char_boundary(text: &str, mut offset: usize) -> usize {
+    offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+// ==================== 诊断 ====================
+
+#[derive(Debug, PartialEq)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug)]
+struct Diagnostic {
+    start: usize,
+    end: usize,
+    severity: Severity,
+    message: String,
+}
+
+impl Diagnostic {
+    fn to_json(&self, text: &str) -> Value {
+        json!({
+            "range": range_json(text, self.start, self.end),
+            "severity": if self.severity == Severity::Error { 1 } else { 2 },
+            "source": "bolide",
+            "message": self.message,
+        })
+    }
+}
+
+/// 检查文档：预处理、语法分析，然后走一遍编译
+fn diagnose(text: &str) -> Vec<Diagnostic> {
+    let pre = match bolide_parser::preprocess(text) {
+        Ok(pre) => pre,
+        Err(message) => return vec![positioned_error(text, &message)],
+    };
+    let mut diagnostics: Vec<Diagnostic> = pre.warnings.iter().map(|w| {
+        let mut d = positioned_error(text, w);
+        d.severity = Severity::Warning;
+        d
+    }).collect();
+
+    let program = match bolide_parser::parse_collecting_errors(&pre.text, &bolide_parser::CfgTarget::host()) {
+        Ok(program) => program,
+        Err(errors) => {
+            // 行列号在预处理前后不变，直接在原文上定位
+            for error in errors {
+                let start = line_col_to_offset(text, error.line, error.col);
+                let message = if error.expected.is_empty() {
+                    error.message
+                } else {
+                    format!("Parse error: {}", error.message)
+                };
+                diagnostics.push(Diagnostic { start, end: word_end(text, start), severity: Severity::Error, message });
+            }
+            return diagnostics;
+        }
+    };
+
+    let result = std::panic::catch_unwind(|| bolide_compiler::JitCompiler::new().validate(&program));
+    let message = match result {
+        Ok(Ok(())) => return diagnostics,
+        Ok(Err(message)) => message,
+        Err(_) => "internal compiler error (the compiler panicked)".to_string(),
+    };
+    let index = Index::build(text);
+    let (start, end) = index.locate_message(text, &message);
+    diagnostics.push(Diagnostic { start, end, severity: Severity::Error, message });
+    diagnostics
+}
+
+/// 带 `行:列` 的预处理错误和警告（`3:5: ...`）
+fn positioned_error(text: &str, message: &str) -> Diagnostic {
+    let mut parts = message.splitn(3, ':');
+    let position = match (parts.next(), parts.next()) {
+        (Some(line), Some(col)) => &message[..line.len() + 1 + col.len()],
+        _ => "",
+    };
+    let summary = message.get(position.len() + 1..).unwrap_or(message).trim().to_string();
+
+    let (line, col) = match position.trim().split_once(':') {
+        Some((l, c)) => (l.parse::<usize>().ok(), c.parse::<usize>().ok()),
+        None => (None, None),
+    };
+    let (start, end) = match (line, col) {
+        (Some(line), Some(col)) => {
+            let start = line_col_to_offset(text, line, col);
+            (start, word_end(text, start))
+        }
+        _ => (0, text.find('\n').unwrap_or(text.len())),
+    };
+    Diagnostic { start, end, severity: Severity::Error, message: summary }
+}
+
+/// 从 `start` 起的单词末尾（至少一个字符，不跨行）
+fn word_end(text: &str, start: usize) -> usize {
+    let rest = &text[start..];
+    let word = rest.find(|c: char| !is_ident_char(c)).unwrap_or(rest.len());
+    if word > 0 {
+        return start + word;
+    }
+    match rest.chars().next() {
+        Some(c) if c != '\n' => start + c.len_utf8(),
+        _ => start,
+    }
+}
+
+// ==================== 符号索引 ====================
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    text: String,
+    start: usize,
+    end: usize,
+    ident: bool,
+}
+
+/// 词法扫描：标识符和标点，跳过空白、注释、字符串和数字
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    // 下一个字符串是 r"..."，反斜杠不转义
+    let mut raw_next = false;
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c == '/' && matches!(chars.peek(), Some((_, '/'))) {
+            for (_, c) in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            }
+            continue;
+        }
+        if c == '/' && matches!(chars.peek(), Some((_, '*'))) {
+            chars.next();
+            let mut prev = ' ';
+            for (_, c) in chars.by_ref() {
+                if prev == '*' && c == '/' {
+                    break;
+                }
+                prev = c;
+            }
+            continue;
+        }
+        if c == '"' {
+            let raw = std::mem::take(&mut raw_next);
+            if text[start..].starts_with("\"\"\"") {
+                // 三引号字符串到下一个 """ 结束，里面的引号和换行都是内容
+                let close = text[start + 3..].find("\"\"\"").map_or(text.len(), |i| start + i + 6);
+                while chars.next_if(|&(i, _)| i < close).is_some() {}
+                continue;
+            }
+            while let Some((_, c)) = chars.next() {
+                match c {
+                    '\\' if !raw => { chars.next(); }
+                    '"' => break,
+                    _ => {}
+                }
+            }
+            continue;
+        }
+        if c.is_ascii_digit() {
+            while chars.next_if(|&(_, c)| is_ident_char(c) || c == '.').is_some() {}
+            continue;
+        }
+        if is_ident_start(c) {
+            let mut end = start + c.len_utf8();
+            while let Some((i, c)) = chars.next_if(|&(_, c)| is_ident_char(c)) {
+                end = i + c.len_utf8();
+            }
+            // 字符串前缀 r"..." / f"..." 不是标识符
+            let word = &text[start..end];
+            if (word == "r" || word == "f") && text[end..].starts_with('"') {
+                raw_next = word == "r";
+                continue;
+            }
+            tokens.push(Token { text: word.to_string(), start, end, ident: true });
+            continue;
+        }
+        tokens.push(Token { text: c.to_string(), start, end: start + c.len_utf8(), ident: false });
+    }
+    tokens
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SymbolKind {
+    Function,
+    Class,
+    Method,
+    Field,
+    Variable,
+    Parameter,
+}
+
+impl SymbolKind {
+    /// LSP SymbolKind 编号
+    fn lsp(self) -> u32 {
+        match self {
+            SymbolKind::Class => 5,
+            SymbolKind::Method => 6,
+            SymbolKind::Field => 8,
+            SymbolKind::Function => 12,
+            SymbolKind::Variable | SymbolKind::Parameter => 13,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Symbol {
+    name: String,
+    kind: SymbolKind,
+    /// 名字的字节范围
+    name_start: usize,
+    name_end: usize,
+    /// 整个声明的字节范围（函数和类包括函数体）
+    start: usize,
+    end: usize,
+    /// 所在的函数或类（`symbols` 中的下标）
+    container: Option<usize>,
+    /// 类的父类名
+    parent: Option<String>,
+}
+
+struct Index {
+    tokens: Vec<Token>,
+    symbols: Vec<Symbol>,
+}
+
+impl Index {
+    fn build(text: &str) -> Self {
+        let tokens = tokenize(text);
+        let matching = match_brackets(&tokens);
+        let mut symbols = Vec::new();
+
+        let mut i = 0;
+        while i < tokens.len() {
+            let next_ident = tokens.get(i + 1).filter(|t| t.ident);
+            match tokens[i].text.as_str() {
+                "class" if tokens[i].ident => if let Some(name) = next_ident {
+                    let open = (i + 2..tokens.len()).find(|&j| tokens[j].text == "{");
+                    let parent = tokens.get(i + 2)
+                        .filter(|t| t.text == ":")
+                        .and_then(|_| tokens.get(i + 3))
+                        .map(|t| t.text.clone());
+                    let end = open.and_then(|o| matching[o]).map_or(name.end, |c| tokens[c].end);
+                    symbols.push(Symbol::new(name, SymbolKind::Class, tokens[i].start, end, parent));
+                },
+                "fn" if tokens[i].ident => if let Some(name) = next_ident {
+                    let params_close = tokens.get(i + 2)
+                        .filter(|t| t.text == "(")
+                        .and_then(|_| matching[i + 2]);
+                    // 函数体：参数表后第一个 `{`，先遇到 `;` 时没有函数体（extern 声明）
+                    let body_close = params_close.and_then(|p| {
+                        (p + 1..tokens.len())
+                            .find(|&j| tokens[j].text == "{" || tokens[j].text == ";")
+                            .filter(|&j| tokens[j].text == "{")
+                            .and_then(|j| matching[j])
+                    });
+                    let end = body_close.or(params_close).map_or(name.end, |c| tokens[c].end);
+                    symbols.push(Symbol::new(name, SymbolKind::Function, tokens[i].start, end, None));
+                    if let Some(close) = params_close {
+                        for j in i + 3..close {
+                            let is_param = tokens[j].ident
+                                && tokens[j + 1].text == ":"
+                                && matches!(tokens[j - 1].text.as_str(), "(" | "," | "ref" | "owned");
+                            if is_param {
+                                let end = tokens[j + 1].end;
+                                symbols.push(Symbol::new(&tokens[j], SymbolKind::Parameter, tokens[j].start, end, None));
+                            }
+                        }
+                    }
+                },
+                "let" | "for" if tokens[i].ident => {
+                    let decl_end = (i + 1..tokens.len())
+                        .find(|&j| tokens[j].text == ";" || tokens[j].text == "{")
+                        .map_or(tokens[i].end, |j| tokens[j].end);
+                    // let (a, b) = ... / for (k, v) in ...
+                    let names: Vec<&Token> = match tokens.get(i + 1) {
+                        Some(t) if t.text == "(" => matching[i + 1]
+                            .map(|close| tokens[i + 2..close].iter().filter(|t| t.ident && t.text != "_").collect())
+                            .unwrap_or_default(),
+                        Some(t) if t.ident => vec![t],
+                        _ => Vec::new(),
+                    };
+                    for name in names {
+                        symbols.push(Symbol::new(name, SymbolKind::Variable, tokens[i].start, decl_end, None));
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+
+        // 字段：直接位于类体内、后面跟 `:` 的标识符
+        let classes: Vec<usize> = (0..symbols.len()).filter(|&s| symbols[s].kind == SymbolKind::Class).collect();
+        for &class in &classes {
+            let (body_start, body_end) = (symbols[class].name_end, symbols[class].end);
+            let inside_method = |offset: usize| symbols.iter().any(|s| {
+                s.kind == SymbolKind::Function && s.start > body_start && s.start <= offset && offset < s.end
+            });
+            let mut fields = Vec::new();
+            for (j, token) in tokens.iter().enumerate() {
+                if token.start <= body_start || token.start >= body_end || !token.ident || j == 0 {
+                    continue;
+                }
+                let is_field = tokens.get(j + 1).is_some_and(|t| t.text == ":")
+                    && matches!(tokens[j - 1].text.as_str(), "{" | ";" | "}")
+                    && !inside_method(token.start);
+                if is_field {
+                    let end = (j..tokens.len()).find(|&k| tokens[k].text == ";").map_or(token.end, |k| tokens[k].end);
+                    fields.push(Symbol::new(token, SymbolKind::Field, token.start, end, None));
+                }
+            }
+            symbols.extend(fields);
+        }
+
+        // 容器：包含声明的最内层函数或类
+        for s in 0..symbols.len() {
+            let container = (0..symbols.len())
+                .filter(|&c| c != s && matches!(symbols[c].kind, SymbolKind::Function | SymbolKind::Class))
+                .filter(|&c| symbols[c].start <= symbols[s].start && symbols[s].end <= symbols[c].end)
+                .min_by_key(|&c| symbols[c].end - symbols[c].start);
+            symbols[s].container = container;
+        }
+        for s in 0..symbols.len() {
+            if symbols[s].kind == SymbolKind::Function
+                && symbols[s].container.is_some_and(|c| symbols[c].kind == SymbolKind::Class)
+            {
+                symbols[s].kind = SymbolKind::Method;
+            }
+        }
+
+        Index { tokens, symbols }
+    }
+
+    /// 光标处的标识符，以及它前面 `x.` 中的 `x`
+    fn token_at(&self, offset: usize) -> Option<(&Token, Option<&str>)> {
+        let i = self.tokens.iter().position(|t| t.ident && t.start <= offset && offset <= t.end)?;
+        let qualifier = (i >= 2 && self.tokens[i - 1].text == ".")
+            .then(|| self.tokens[i - 2].text.as_str());
+        Some((&self.tokens[i], qualifier))
+    }
+
+    fn symbol_named(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| s.name == name)
+    }
+
+    /// 顶层的函数、类或全局变量
+    fn top_level(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| s.name == name && s.container.is_none())
+    }
+
+    /// 包含 `offset` 的最内层函数或方法
+    fn enclosing_function(&self, offset: usize) -> Option<usize> {
+        (0..self.symbols.len())
+            .filter(|&s| matches!(self.symbols[s].kind, SymbolKind::Function | SymbolKind::Method))
+            .filter(|&s| self.symbols[s].start <= offset && offset < self.symbols[s].end)
+            .min_by_key(|&s| self.symbols[s].end - self.symbols[s].start)
+    }
+
+    /// 类（及其父类）中的字段或方法
+    fn member(&self, class: usize, name: &str) -> Option<&Symbol> {
+        let mut current = Some(class);
+        let mut depth = 0;
+        while let Some(c) = current {
+            if let Some(member) = self.symbols.iter().find(|s| s.container == Some(c) && s.name == name
+                && matches!(s.kind, SymbolKind::Field | SymbolKind::Method))
+            {
+                return Some(member);
+            }
+            // 继承链有环时停下
+            depth += 1;
+            if depth > self.symbols.len() {
+                return None;
+            }
+            current = self.symbols[c].parent.as_deref().and_then(|p| {
+                self.symbols.iter().position(|s| s.kind == SymbolKind::Class && s.name == p)
+            });
+        }
+        None
+    }
+
+    /// 解析 `offset` 处名字的定义
+    fn resolve(&self, name: &str, qualifier: Option<&str>, offset: usize) -> Option<&Symbol> {
+        let function = self.enclosing_function(offset);
+
+        if let Some(qualifier) = qualifier {
+            // self.x：当前方法所在的类；其他 obj.x：任意类中同名的字段或方法
+            if qualifier == "self" {
+                let class = function.and_then(|f| self.symbols[f].container)?;
+                return self.member(class, name);
+            }
+            return self.symbols.iter().find(|s| s.name == name && matches!(s.kind, SymbolKind::Field | SymbolKind::Method));
+        }
+
+        // 函数内：声明在光标之前的最近一个同名变量或参数
+        if let Some(f) = function {
+            let local = self.symbols.iter()
+                .filter(|s| s.name == name && matches!(s.kind, SymbolKind::Variable | SymbolKind::Parameter))
+                .filter(|s| s.container == Some(f) && s.name_start <= offset)
+                .max_by_key(|s| s.name_start);
+            if local.is_some() {
+                return local;
+            }
+        }
+
+        self.symbols.iter()
+            .filter(|s| s.name == name && s.container.is_none())
+            .filter(|s| s.kind != SymbolKind::Variable || s.name_start <= offset || function.is_some())
+            .min_by_key(|s| s.name_start)
+            .or_else(|| self.symbol_named(name))
+    }
+
+    /// 编译错误信息中提到的名字在源码中的位置
+    ///
+    /// 依次尝试引号中的名字和最后一个 `: ` 之后的名字：优先落在定义上，其次是第一次出现的地方。
+    fn locate_message(&self, text: &str, message: &str) -> (usize, usize) {
+        let mut candidates: Vec<&str> = Vec::new();
+        for quote in ['\'', '`'] {
+            candidates.extend(message.split(quote).skip(1).step_by(2));
+        }
+        if let Some((_, tail)) = message.rsplit_once(": ") {
+            candidates.push(tail.trim());
+        }
+
+        for name in candidates {
+            if name.is_empty() || !name.chars().all(is_ident_char) {
+                continue;
+            }
+            if let Some(symbol) = self.symbol_named(name) {
+                return (symbol.name_start, symbol.name_end);
+            }
+            if let Some(token) = self.tokens.iter().find(|t| t.text == name) {
+                return (token.start, token.end);
+            }
+        }
+        (0, text.find('\n').unwrap_or(text.len()))
+    }
+}
+
+impl Symbol {
+    fn new(name: &Token, kind: SymbolKind, start: usize, end: usize, parent: Option<String>) -> Self {
+        Symbol {
+            name: name.text.clone(),
+            kind,
+            name_start: name.start,
+            name_end: name.end,
+            start,
+            end,
+            container: None,
+            parent,
+        }
+    }
+}
+
+/// 括号配对：`(`/`{` 的下标 -> 对应闭括号的下标
+fn match_brackets(tokens: &[Token]) -> Vec<Option<usize>> {
+    let mut matching = vec![None; tokens.len()];
+    let mut stack: Vec<usize> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        match token.text.as_str() {
+            "(" | "{" | "[" => stack.push(i),
+            ")" | "}" | "]" => {
+                if let Some(open) = stack.pop() {
+                    matching[open] = Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    matching
+}
+
+/// 大纲：顶层函数、类（子项为字段和方法）和全局变量
+fn document_symbols(text: &str) -> Value {
+    let index = Index::build(text);
+    let to_json = |s: &Symbol, children: Vec<Value>| json!({
+        "name": s.name,
+        "kind": s.kind.lsp(),
+        "range": range_json(text, s.start, s.end),
+        "selectionRange": range_json(text, s.name_start, s.name_end),
+        "children": children,
+    });
+    let symbols: Vec<Value> = (0..index.symbols.len())
+        .filter(|&s| index.symbols[s].container.is_none())
+        .map(|s| {
+            let symbol = &index.symbols[s];
+            let children = index.symbols.iter()
+                .filter(|c| symbol.kind == SymbolKind::Class && c.container == Some(s))
+                .map(|c| to_json(c, Vec::new()))
+                .collect();
+            to_json(symbol, children)
+        })
+        .collect();
+    Value::Array(symbols)
+}
+
+// ==================== 导入的文件 ====================
+
+/// `module` 对应的导入文件（模块名是文件名去掉扩展名，与编译器一致）
+///
+/// 编译器按当前工作目录解析导入路径；找不到时再按文档所在目录找。
+fn module_file(text: &str, module: &str, document: Option<&Path>) -> Option<PathBuf> {
+    let pre = bolide_parser::preprocess(text).ok()?;
+    let program = bolide_parser::parse(&pre.text).ok()?;
+    let file = bolide_compiler::import_files(&program)
+        .into_iter()
+        .find(|f| Path::new(f).file_stem().and_then(|s| s.to_str()) == Some(module))?;
+    let path = PathBuf::from(&file);
+    if path.is_absolute() || path.exists() {
+        return Some(path);
+    }
+    document.and_then(Path::parent).map(|dir| dir.join(&file)).filter(|p| p.exists())
+}
+
+/// `file://` URI -> 路径
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    let path = String::from_utf8(decoded).ok()?;
+    // file:///C:/x -> C:/x
+    let path = match path.strip_prefix('/') {
+        Some(rest) if rest.as_bytes().get(1) == Some(&b':') => rest.to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
+
+/// 路径 -> `file://` URI
+fn path_to_uri(path: &Path) -> String {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let text = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !text.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(server: &mut Server, uri: &str, text: &str) -> Value {
+        let message = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": uri, "languageId": "bolide", "version": 1, "text": text } },
+        });
+        server.handle(&message).remove(0)
+    }
+
+    fn definition(server: &mut Server, uri: &str, line: usize, character: usize) -> Value {
+        let message = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "textDocument/definition",
+            "params": { "textDocument": { "uri": uri }, "position": { "line": line, "character": character } },
+        });
+        server.handle(&message).remove(0)["result"].clone()
+    }
+
+    fn start_of(value: &Value) -> (u64, u64) {
+        let start = &value["range"]["start"];
+        (start["line"].as_u64().unwrap(), start["character"].as_u64().unwrap())
+    }
+
+    #[test]
+    fn test_utf16_positions() {
+        // 😀 是 4 字节、2 个 UTF-16 码元；中 是 3 字节、1 个码元
+        let text = "let a = \"😀中\"; let b = 1;\nx";
+        let b = text.find("b =").unwrap();
+        assert_eq!(offset_to_position(text, b), (0, 19));
+        assert_eq!(position_to_offset(text, 0, 19), b);
+        assert_eq!(position_to_offset(text, 1, 0), text.len() - 1);
+        // 超出行尾取行尾
+        assert_eq!(position_to_offset(text, 0, 999), text.find('\n').unwrap());
+
+        // 编译器的列按字符计：第 19 个字符是 `b`
+        assert_eq!(line_col_to_offset(text, 1, 19), b);
+        assert_eq!(line_col_to_offset("\u{FEFF}ab", 1, 2), "\u{FEFF}a".len());
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let text = "let s: str = \"😀😀\";\nlet y: int = 1 +;\n";
+        let diagnostics = diagnose(text);
+        assert_eq!(diagnostics.len(), 1);
+        let d = diagnostics[0].to_json(text);
+        assert_eq!(start_of(&d), (1, 16));
+        assert!(d["message"].as_str().unwrap().starts_with("Parse error: expected"), "{}", d["message"]);
+
+        // 预处理错误同样带位置；😀 之后的列按 UTF-16 计
+        let text = "let s = 1; /* 😀 */ let t\u{202E} = 2;";
+        let d = diagnose(text)[0].to_json(text);
+        assert_eq!(start_of(&d), (0, 25));
+    }
+
+    #[test]
+    fn test_every_parse_error_reported() {
+        let text = "fn a() {\n    let x: int = ;\n}\n\nfn b() {\n    print(1 +);\n}\n";
+        let starts: Vec<_> = diagnose(text).iter().map(|d| start_of(&d.to_json(text))).collect();
+        assert_eq!(starts, [(1, 17), (5, 13)]);
+    }
+
+    #[test]
+    fn test_compile_error_on_named_symbol() {
+        let text = "fn helper() -> int {\n    return missing_value;\n}\nprint(helper());\n";
+        let diagnostics = diagnose(text);
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        let d = diagnostics[0].to_json(text);
+        assert_eq!(start_of(&d), (1, 11));
+
+        assert!(diagnose("fn ok() -> int { return 1; }\nprint(ok());\n").is_empty());
+    }
+
+    #[test]
+    fn test_tokenize_skips_strings() {
+        let text = "let a = r\"C:\\\";\nlet b = f\"\"\"\n  \"x\" {a}\n\"\"\";\nlet c = 1;";
+        let idents: Vec<String> = tokenize(text).into_iter().filter(|t| t.ident).map(|t| t.text).collect();
+        assert_eq!(idents, ["let", "a", "let", "b", "let", "c"]);
+    }
+
+    #[test]
+    fn test_definition_in_file() {
+        let text = "\
+class Point {
+    x: int;
+    fn norm() -> int { return self.x * self.x; }
+}
+fn scale(p: Point, k: int) -> int {
+    let total: int = p.norm() * k;
+    return total;
+}
+let origin: Point = Point(0, 0);
+print(scale(origin, 2));
+";
+        let mut server = Server::default();
+        let uri = "file:///tmp/point.bl";
+        open(&mut server, uri, text);
+
+        // 调用 -> 函数定义
+        assert_eq!(start_of(&definition(&mut server, uri, 9, 7)), (4, 3));
+        // 类名 -> 类定义
+        assert_eq!(start_of(&definition(&mut server, uri, 8, 21)), (0, 6));
+        // 函数内的变量和参数
+        assert_eq!(start_of(&definition(&mut server, uri, 6, 12)), (5, 8));
+        assert_eq!(start_of(&definition(&mut server, uri, 5, 32)), (4, 19));
+        // self.x -> 字段；p.norm -> 方法
+        assert_eq!(start_of(&definition(&mut server, uri, 2, 35)), (1, 4));
+        assert_eq!(start_of(&definition(&mut server, uri, 5, 24)), (2, 7));
+        // 全局变量
+        assert_eq!(start_of(&definition(&mut server, uri, 9, 13)), (8, 4));
+        // 关键字上没有定义
+        assert!(definition(&mut server, uri, 6, 5).is_null());
+    }
+
+    #[test]
+    fn test_definition_across_import() {
+        let dir = std::env::temp_dir().join(format!("bolide-lsp-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let module = dir.join("geometry.bl");
+        std::fs::write(&module, "// 几何\nfn area(w: int, h: int) -> int {\n    return w * h;\n}\n").unwrap();
+
+        let text = format!("import \"{}\";\nprint(geometry.area(2, 3));\n", module.display());
+        let mut server = Server::default();
+        let uri = path_to_uri(&dir.join("main.bl"));
+        let published = open(&mut server, &uri, &text);
+        assert_eq!(published["params"]["diagnostics"], json!([]));
+
+        let target = definition(&mut server, &uri, 1, 17);
+        assert_eq!(target["uri"], json!(path_to_uri(&module)));
+        assert_eq!(start_of(&target), (1, 3));
+        assert_eq!(uri_to_path(target["uri"].as_str().unwrap()).unwrap(), std::fs::canonicalize(&module).unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_document_symbols() {
+        let text = "\
+let limit: int = 10;
+class Node: Base {
+    value: int;
+    fn get() -> int { let v: int = self.value; return v; }
+}
+extern \"libm.so\" {
+    fn cos(x: c_double) -> c_double;
+}
+fn main_loop() { }
+";
+        let symbols = document_symbols(text);
+        let names: Vec<(&str, u64)> = symbols.as_array().unwrap().iter()
+            .map(|s| (s["name"].as_str().unwrap(), s["kind"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(names, [("limit", 13), ("Node", 5), ("cos", 12), ("main_loop", 12)]);
+
+        let children: Vec<&str> = symbols[1]["children"].as_array().unwrap().iter()
+            .map(|c| c["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(children, ["get", "value"]);
+        assert_eq!(symbols[1]["range"]["end"]["line"], json!(4));
+    }
+
+    #[test]
+    fn test_message_framing() {
+        let body = r#"{"jsonrpc":"2.0","id":7,"method":"initialize","params":{}}"#;
+        let raw = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+        let mut input = io::Cursor::new(raw.into_bytes());
+        let message = read_message(&mut input).unwrap().unwrap();
+        assert_eq!(read_message(&mut input).unwrap(), None);
+
+        let mut server = Server::default();
+        let replies = server.handle(&message);
+        assert_eq!(replies[0]["id"], json!(7));
+        assert_eq!(replies[0]["result"]["capabilities"]["definitionProvider"], json!(true));
+
+        let mut out = Vec::new();
+        write_message(&mut out, &replies[0]).unwrap();
+        let written = String::from_utf8(out).unwrap();
+        let (header, json_body) = written.split_once("\r\n\r\n").unwrap();
+        assert_eq!(header, format!("Content-Length: {}", json_body.len()));
+
+        // 未知请求返回 MethodNotFound，未知通知不回复
+        let unknown = server.handle(&json!({ "jsonrpc": "2.0", "id": 8, "method": "textDocument/hover" }));
+        assert_eq!(unknown[0]["error"]["code"], json!(-32601));
+        assert!(server.handle(&json!({ "jsonrpc": "2.0", "method": "$/cancelRequest" })).is_empty());
+    }
+}
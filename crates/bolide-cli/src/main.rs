@@ -1,69 +1,58 @@
+mod cache;
+mod incremental;
+mod lsp;
+mod toolchain;
+
 use clap::{Parser, Subcommand};
 use std::path::{Path, PathBuf};
 use std::fs;
 use std::io::{self, Write};
 use std::process::Command;
 
-use bolide_parser::parse_source;
-use bolide_compiler::{JitCompiler, AotCompiler};
+use bolide_parser::{parse_source, parse_source_for_target, CfgTarget, ParseError};
+use bolide_compiler::{c_header, JitCompiler, AotCompiler, BUILTINS, BUILTIN_CATEGORIES};
 
-/// REPL 状态，维护累积的代码
+/// REPL 状态：一个长期存在的 JIT 编译器，每次输入只编译新的定义和包装顶层语句的入口函数
 struct ReplState {
-    /// 函数定义
+    compiler: JitCompiler,
+    /// 定义过的函数，按定义顺序（`:funcs`）
     functions: Vec<String>,
-    /// 全局变量声明
-    globals: Vec<String>,
+    /// 声明过的全局变量，按第一次声明的顺序（`:vars`）
+    variables: Vec<String>,
 }
 
 impl ReplState {
     fn new() -> Self {
+        let mut compiler = JitCompiler::new();
+        compiler.set_keep_ir(true);
         Self {
+            compiler,
             functions: Vec::new(),
-            globals: Vec::new(),
+            variables: Vec::new(),
         }
     }
 
-    /// 判断输入类型并添加到状态
-    fn add_input(&mut self, input: &str) -> InputType {
-        let trimmed = input.trim();
-
-        if trimmed.starts_with("fn ") {
-            self.functions.push(input.to_string());
-            InputType::FuncDef
-        } else if trimmed.starts_with("let ") {
-            self.globals.push(input.to_string());
-            InputType::VarDecl
-        } else if trimmed.starts_with("class ") {
-            self.functions.push(input.to_string());
-            InputType::ClassDef
-        } else {
-            InputType::Expr
+    /// 记下编译成功的输入里的函数和顶层变量
+    fn record(&mut self, program: &bolide_parser::Program) {
+        for stmt in &program.statements {
+            match stmt {
+                bolide_parser::Statement::FuncDef(func) => self.functions.push(func.name.clone()),
+                bolide_parser::Statement::VarDecl(decl) if !self.variables.contains(&decl.name) => {
+                    self.variables.push(decl.name.clone());
+                }
+                _ => {}
+            }
         }
     }
+}
 
-    /// 生成完整的程序代码
-    fn build_program(&self, expr: Option<&str>) -> String {
-        let mut code = String::new();
-
-        // 添加函数/类定义
-        for func in &self.functions {
-            code.push_str(func);
-            code.push('\n');
-        }
-
-        // 添加全局变量
-        for var in &self.globals {
-            code.push_str(var);
-            code.push('\n');
-        }
-
-        // 添加表达式/语句
-        if let Some(e) = expr {
-            code.push_str(e);
-            code.push('\n');
-        }
-
-        code
+/// 判断输入类型（只含一个定义时给出提示）
+fn input_type(program: &bolide_parser::Program) -> InputType {
+    match program.statements.as_slice() {
+        [bolide_parser::Statement::FuncDef(_)] => InputType::FuncDef,
+        [bolide_parser::Statement::VarDecl(_)] => InputType::VarDecl,
+        [bolide_parser::Statement::ClassDef(_)] => InputType::ClassDef,
+        _ => InputType::Expr,
     }
 }
 
@@ -90,6 +79,50 @@ enum Commands {
     Run {
         /// Source file path
         file: PathBuf,
+        /// Reuse an AOT-compiled executable cached by source hash (default when BOLIDE_RUN_CACHE=1)
+        #[arg(long)]
+        cache: bool,
+        /// Compile with the JIT even when BOLIDE_RUN_CACHE=1
+        #[arg(long, conflicts_with = "cache")]
+        no_cache: bool,
+        /// Make integer overflow in checked operations (e.g. sum) a runtime error
+        #[arg(long)]
+        overflow_checks: bool,
+        /// Coroutine scheduling: `threaded` runs coroutines in parallel, `single` runs one at a time and switches only at `await`
+        #[arg(long, value_parser = ["threaded", "single"])]
+        executor: Option<String>,
+        /// Track a shadow call stack and print the Bolide functions (`at foo (line 12)`) with runtime errors
+        #[arg(long, conflicts_with = "cache")]
+        debug: bool,
+        /// Ignore `@trace` attributes: traced functions compile exactly as if they were not annotated
+        #[arg(long, conflicts_with = "cache")]
+        no_trace: bool,
+        /// Bind existing lists and dicts by reference (`let b = a` shares `a`) instead of copying them
+        #[arg(long, conflicts_with = "cache")]
+        share_containers: bool,
+        /// Don't fold literal-only string expressions, evaluate `@pure` calls at compile time or remove cancelling clone/release pairs
+        #[arg(long, conflicts_with = "cache")]
+        no_opt: bool,
+        /// Optimization level: `-O1` (default) also folds constant expressions and removes constant branches and
+        /// unreachable statements before code generation; `-O0` is the same as `--no-opt`
+        #[arg(short = 'O', value_name = "LEVEL", default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..=1), conflicts_with = "cache")]
+        opt_level: u8,
+        /// Don't run: print each function's call graph, emitted retain/release/clone calls, RC variables and moves
+        #[arg(long)]
+        analyze: bool,
+        /// Print the `--analyze` report as JSON
+        #[arg(long, requires = "analyze")]
+        json: bool,
+        /// Run top-level code once, then call `tick` (or the `@hot_entry` function) repeatedly, replacing edited functions while it runs
+        #[arg(long, conflicts_with_all = ["cache", "analyze"])]
+        hot: bool,
+        /// Sample every thread's Bolide call stack at RATE (e.g. `997hz`) and write folded stacks for flamegraph
+        /// tools at exit or on SIGUSR1, with a self-time summary on stderr; implies the `--debug` shadow stack
+        #[arg(long, value_name = "RATE", value_parser = parse_sample_rate, conflicts_with_all = ["cache", "hot"])]
+        sample: Option<u32>,
+        /// Folded stacks file written by `--sample` (default: `<source name>.folded` in the current directory)
+        #[arg(long, value_name = "PATH", requires = "sample")]
+        sample_output: Option<PathBuf>,
     },
     /// Compile a Bolide source file to executable (AOT)
     Compile {
@@ -98,19 +131,195 @@ enum Commands {
         /// Output file path
         #[arg(short, long)]
         output: Option<PathBuf>,
+        /// Target triple (e.g. x86_64-pc-windows-msvc); selects `@cfg` items and the code generator.
+        /// For another OS or architecture only the object file is written, and a link command is printed
+        #[arg(long)]
+        target: Option<String>,
+        /// Output kind: `exe` links an executable; `obj` / `lib` write an object file / static library
+        /// without an entry point that exports the top-level functions, plus a C header
+        #[arg(long, value_parser = ["exe", "obj", "lib"], default_value = "exe")]
+        emit: String,
+        /// Compile each imported module to its own object file, cached in `.bolide-build/` next to the
+        /// output; only modules whose source changed are recompiled
+        #[arg(long, conflicts_with_all = ["target", "emit"])]
+        incremental: bool,
+        /// Ignore `@trace` attributes: traced functions compile exactly as if they were not annotated
+        #[arg(long)]
+        no_trace: bool,
+        /// Bind existing lists and dicts by reference (`let b = a` shares `a`) instead of copying them
+        #[arg(long)]
+        share_containers: bool,
+        /// Don't fold literal-only string expressions at compile time or remove cancelling clone/release pairs
+        #[arg(long)]
+        no_opt: bool,
+        /// Optimization level: `-O1` (default) also folds constant expressions and removes constant branches and
+        /// unreachable statements before code generation; `-O0` is the same as `--no-opt`
+        #[arg(short = 'O', value_name = "LEVEL", default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..=1))]
+        opt_level: u8,
+        /// Don't build: print each function's call graph, emitted retain/release/clone calls, RC variables and moves
+        #[arg(long)]
+        analyze: bool,
+        /// Print the `--analyze` report as JSON
+        #[arg(long, requires = "analyze")]
+        json: bool,
     },
+    /// Run the `test "name" { ... }` blocks of a source file, or of every .bl file under a directory (JIT)
+    Test {
+        /// Source file or directory
+        path: PathBuf,
+    },
+    /// List built-in functions, methods and special forms
+    Builtins {
+        /// Print as JSON (for editor tooling)
+        #[arg(long)]
+        json: bool,
+    },
+    /// Manage the `run --cache` program cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Run the language server over stdio (diagnostics, go-to-definition, outline)
+    Lsp,
+    /// Install prebuilt runtime libraries for AOT compilation and cross-compiling
+    Toolchain {
+        #[command(subcommand)]
+        action: ToolchainAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Remove all cached programs
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum ToolchainAction {
+    /// Download and verify the runtime library for a target triple (the only command that uses the network)
+    Install {
+        /// Target triple (e.g. aarch64-unknown-linux-gnu)
+        triple: String,
+        /// Download from this URL instead of the release (also `BOLIDE_TOOLCHAIN_URL`); http(s):// or file://
+        #[arg(long)]
+        base_url: Option<String>,
+        /// Check downloads against this manifest instead of the one built into bolide
+        #[arg(long)]
+        manifest: Option<PathBuf>,
+    },
+    /// List installed targets and their runtime versions
+    List,
+}
+
+/// 影响生成代码的编译开关，JIT 和 AOT 相同
+#[derive(Debug, Clone, Copy, Default)]
+struct CodegenFlags {
+    /// `--no-trace`
+    no_trace: bool,
+    /// `--share-containers`
+    share_containers: bool,
+    /// `--no-opt` / `-O0`
+    no_opt: bool,
+}
+
+impl CodegenFlags {
+    fn apply_jit(self, compiler: &mut JitCompiler) {
+        compiler.set_no_trace(self.no_trace);
+        compiler.set_share_containers(self.share_containers);
+        compiler.set_no_opt(self.no_opt);
+    }
+
+    fn apply_aot(self, compiler: &mut AotCompiler) {
+        compiler.set_no_trace(self.no_trace);
+        compiler.set_share_containers(self.share_containers);
+        compiler.set_no_opt(self.no_opt);
+    }
 }
 
 fn main() -> miette::Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Some(Commands::Run { file }) => {
-            run_file(&file)?;
+        Some(Commands::Run { file, analyze: true, json, no_trace, share_containers, no_opt, opt_level, .. }) => {
+            let no_opt = no_opt || opt_level == 0;
+            analyze_file(&file, None, json, CodegenFlags { no_trace, share_containers, no_opt })?;
         }
-        Some(Commands::Compile { file, output }) => {
-            let out = output.unwrap_or_else(|| file.with_extension("exe"));
-            compile_file(&file, &out)?;
+        Some(Commands::Run { file, cache, no_cache, overflow_checks, executor, debug, no_trace, share_containers, no_opt, opt_level, hot, sample, sample_output, .. }) => {
+            let no_opt = no_opt || opt_level == 0;
+            if overflow_checks {
+                bolide_runtime::bolide_set_overflow_checks(1);
+            }
+            if let Some(executor) = executor {
+                bolide_runtime::bolide_set_executor_single((executor == "single") as i64);
+            }
+            // BOLIDE_RUN_CACHE 只在没有 JIT 专用选项时生效（显式 --cache 与这些选项冲突，由 clap 报错）
+            let jit_only = debug || no_trace || share_containers || no_opt || hot || sample.is_some();
+            let sample = sample.map(|hz| Sampling { hz, output: sample_output.unwrap_or_else(|| Sampling::default_output(&file)) });
+            if hot {
+                run_hot(&file, debug, CodegenFlags { no_trace, share_containers, no_opt })?;
+            } else if cache || (!no_cache && !jit_only && cache::enabled_by_env()) {
+                run_file_cached(&file)?;
+            } else {
+                run_file(&file, debug, sample.as_ref(), CodegenFlags { no_trace, share_containers, no_opt })?;
+            }
+        }
+        Some(Commands::Compile { file, target, analyze: true, json, no_trace, share_containers, no_opt, opt_level, .. }) => {
+            let no_opt = no_opt || opt_level == 0;
+            analyze_file(&file, target.as_deref(), json, CodegenFlags { no_trace, share_containers, no_opt })?;
+        }
+        Some(Commands::Compile { file, output, target, emit, incremental, no_trace, share_containers, no_opt, opt_level, .. }) => {
+            let no_opt = no_opt || opt_level == 0;
+            compile_file(&file, output, target.as_deref(), &emit, incremental, CodegenFlags { no_trace, share_containers, no_opt })?;
+        }
+        Some(Commands::Test { path }) => {
+            let passed = run_tests(&path)?;
+            bolide_runtime::bolide_shutdown();
+            if !passed {
+                bolide_runtime::bolide_exit(1);
+            }
+        }
+        Some(Commands::Builtins { json }) => {
+            if json {
+                print_builtins_json();
+            } else {
+                print_builtins();
+            }
+        }
+        Some(Commands::Cache { action: CacheAction::Clear }) => {
+            let cache = cache::RunCache::open();
+            let (count, bytes) = cache.clear();
+            println!("Removed {} cached program(s), {} KB, from {}", count, bytes / 1024, cache.dir().display());
+        }
+        Some(Commands::Lsp) => {
+            lsp::run().map_err(|e| miette::miette!("Language server I/O error: {}", e))?;
+        }
+        Some(Commands::Toolchain { action: ToolchainAction::Install { triple, base_url, manifest } }) => {
+            let manifest_text = match manifest {
+                Some(path) => fs::read_to_string(&path)
+                    .map_err(|e| miette::miette!("Failed to read manifest {}: {}", path.display(), e))?,
+                None => toolchain::EMBEDDED_MANIFEST.to_string(),
+            };
+            let entries = toolchain::parse_manifest(&manifest_text).map_err(|e| miette::miette!("{}", e))?;
+            let base_url = base_url.unwrap_or_else(toolchain::default_base_url);
+            let toolchains = toolchain::Toolchains::open();
+            println!("Downloading runtime for {} from {}", triple, base_url);
+            let path = toolchains.install(&triple, &entries, &base_url).map_err(|e| miette::miette!("{}", e))?;
+            println!("Installed bolide runtime {} for {}: {}", env!("CARGO_PKG_VERSION"), triple, path.display());
+        }
+        Some(Commands::Toolchain { action: ToolchainAction::List }) => {
+            let toolchains = toolchain::Toolchains::open();
+            let installed = toolchains.installed();
+            if installed.is_empty() {
+                println!("No toolchains installed in {}", toolchains.dir().display());
+            }
+            for (triple, version) in installed {
+                let version = version.unwrap_or_else(|| "unknown".to_string());
+                if version == env!("CARGO_PKG_VERSION") {
+                    println!("{:<32} {}", triple, version);
+                } else {
+                    println!("{:<32} {} (needs {}; reinstall it)", triple, version, env!("CARGO_PKG_VERSION"));
+                }
+            }
         }
         None => {
             run_repl()?;
@@ -120,26 +329,431 @@ fn main() -> miette::Result<()> {
     Ok(())
 }
 
-fn run_file(file: &PathBuf) -> miette::Result<()> {
-    println!("Running: {}", file.display());
+/// 按分类打印内置列表
+fn print_builtins() {
+    for (category, title) in BUILTIN_CATEGORIES {
+        let items: Vec<_> = BUILTINS.iter().filter(|b| b.category == *category).collect();
+        if items.is_empty() {
+            continue;
+        }
+        println!("{}", title);
+        let width = items.iter().map(|b| b.signature.len()).max().unwrap_or(0);
+        for b in items {
+            let aliases = if b.aliases.is_empty() {
+                String::new()
+            } else {
+                format!(" (alias: {})", b.aliases.join(", "))
+            };
+            println!("  {:<width$}  {}{}", b.signature, b.summary, aliases, width = width);
+        }
+        println!();
+    }
+}
+
+/// JSON 字符串转义
+fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// 以 JSON 数组打印内置列表
+fn print_builtins_json() {
+    let entries: Vec<String> = BUILTINS.iter().map(|b| {
+        let aliases: Vec<String> = b.aliases.iter().map(|a| json_str(a)).collect();
+        format!(
+            "  {{\"name\": {}, \"aliases\": [{}], \"receiver\": {}, \"kind\": {}, \"category\": {}, \"signature\": {}, \"summary\": {}}}",
+            json_str(b.name),
+            aliases.join(", "),
+            if b.receiver.is_empty() { "null".to_string() } else { json_str(b.receiver) },
+            json_str(b.kind.as_str()),
+            json_str(b.category),
+            json_str(b.signature),
+            json_str(b.summary),
+        )
+    }).collect();
+    println!("[\n{}\n]", entries.join(",\n"));
+}
+
+/// 解析源文件（`@cfg` 对 `target` 求值），并把预处理警告打印到 stderr
+fn parse_with_warnings(file: &Path, source: &str, target: &CfgTarget) -> miette::Result<bolide_parser::Program> {
+    let (ast, warnings) = parse_source_for_target(source, target)
+        .map_err(|e| parse_report(&file.display().to_string(), source, *e))?;
+    for warning in warnings {
+        eprintln!("warning: {}:{}", file.display(), warning);
+    }
+    Ok(ast)
+}
+
+/// 解析错误的报告：标出出错的行并在出错位置下划线，多处错误依次列出
+fn parse_report(name: &str, source: &str, error: ParseError) -> miette::Report {
+    miette::Report::new(error.with_path(name))
+        .with_source_code(miette::NamedSource::new(name, source.to_string()))
+}
+
+/// 把编译器的警告（已弃用的用法等）打印到 stderr
+fn print_compile_warnings(file: &Path, warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("warning: {}: {}", file.display(), warning);
+    }
+}
+
+/// `bolide run --sample`：采样频率和折叠格式的输出文件
+struct Sampling {
+    hz: u32,
+    output: PathBuf,
+}
+
+impl Sampling {
+    /// 默认输出到当前目录下的 `<源文件名>.folded`
+    fn default_output(file: &Path) -> PathBuf {
+        let stem = file.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "bolide".to_string());
+        PathBuf::from(format!("{}.folded", stem))
+    }
+}
+
+/// 解析 `--sample` 的频率：`997hz` 或 `997`
+fn parse_sample_rate(text: &str) -> Result<u32, String> {
+    let lower = text.trim().to_ascii_lowercase();
+    let digits = lower.strip_suffix("hz").unwrap_or(&lower);
+    match digits.parse::<u32>() {
+        Ok(hz) if (1..=bolide_runtime::MAX_SAMPLE_HZ).contains(&hz) => Ok(hz),
+        Ok(_) => Err(format!("sample rate must be between 1hz and {}hz", bolide_runtime::MAX_SAMPLE_HZ)),
+        Err(_) => Err(format!("invalid sample rate '{}' (expected e.g. 997hz)", text)),
+    }
+}
+
+fn run_file(file: &PathBuf, debug: bool, sample: Option<&Sampling>, flags: CodegenFlags) -> miette::Result<()> {
+    // 程序输出经运行时的输出层：读端关闭（如接到 head）时安静退出
+    bolide_runtime::write_stdout(format_args!("Running: {}\n", file.display()));
+    bolide_runtime::flush_stdout();
     let source = fs::read_to_string(file)
         .map_err(|e| miette::miette!("Failed to read file: {}", e))?;
 
-    let ast = parse_source(&source)
-        .map_err(|e| miette::miette!("Parse error: {}", e))?;
+    let ast = parse_with_warnings(file, &source, &CfgTarget::host())?;
+    run_jit(file, &source, &ast, debug, sample, flags)
+}
 
+/// JIT 编译并运行已解析的程序
+///
+/// 采样需要影子调用栈，`sample` 非空时按调试模式编译；采样线程在编译之后启动，在分离的线程结束之后停止。
+fn run_jit(file: &Path, source: &str, ast: &bolide_parser::Program, debug: bool, sample: Option<&Sampling>, flags: CodegenFlags) -> miette::Result<()> {
     let mut compiler = JitCompiler::new();
+    compiler.set_source(&file.display().to_string(), source);
+    compiler.set_debug(debug || sample.is_some());
+    flags.apply_jit(&mut compiler);
+    let main_ptr = compiler.compile(ast)
+        .map_err(|e| miette::miette!("Compile error: {}", e))?;
+    print_compile_warnings(file, compiler.warnings());
+
+    if let Some(sample) = sample {
+        bolide_runtime::start_sampler(sample.hz, sample.output.clone());
+    }
+    let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+    let result = main_fn();
+    // 编译器要活到分离的线程结束之后，它们还在执行编译好的代码
+    bolide_runtime::bolide_shutdown();
+    // 采样到的函数名指向编译出的代码，在编译器释放之前写出
+    bolide_runtime::stop_sampler();
+    compiler.release_globals();
+    drop(compiler);
+    bolide_runtime::write_stdout(format_args!("Result: {}\n", result));
+    // 顶层代码的返回值作为进程退出状态（和编译出的可执行文件一致）
+    if bolide_runtime::exit_status(result) != 0 {
+        bolide_runtime::bolide_exit(result);
+    }
+    Ok(())
+}
+
+/// `--hot` 检查源文件是否修改的间隔
+const HOT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// `bolide run --hot`：顶层代码运行一次，然后反复调用入口函数，直到它返回 false（不返回值时一直运行）
+///
+/// 每隔 `HOT_POLL_INTERVAL` 检查源文件的修改时间，改变后重新解析并替换函数体改变的函数
+/// （见 `JitCompiler::hot_reload`），全局变量的值保留。解析或编译失败时打印错误，继续运行之前的版本。
+fn run_hot(file: &Path, debug: bool, flags: CodegenFlags) -> miette::Result<()> {
+    bolide_runtime::write_stdout(format_args!("Running: {}\n", file.display()));
+    bolide_runtime::flush_stdout();
+    let source = fs::read_to_string(file)
+        .map_err(|e| miette::miette!("Failed to read file: {}", e))?;
+    let ast = parse_with_warnings(file, &source, &CfgTarget::host())?;
+
+    let mut compiler = JitCompiler::new();
+    compiler.set_source(&file.display().to_string(), &source);
+    compiler.set_debug(debug);
+    flags.apply_jit(&mut compiler);
+    compiler.set_hot(true);
     let main_ptr = compiler.compile(&ast)
         .map_err(|e| miette::miette!("Compile error: {}", e))?;
+    print_compile_warnings(file, compiler.warnings());
+    let (entry, returns_bool) = compiler.hot_entry()
+        .map(|(name, returns_bool)| (name.to_string(), returns_bool))
+        .ok_or_else(|| miette::miette!("No hot entry function"))?;
 
     let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
     let result = main_fn();
-    println!("Result: {}", result);
+    let modified = |file: &Path| fs::metadata(file).and_then(|m| m.modified()).ok();
+    let mut last_modified = modified(file);
+    let mut last_check = std::time::Instant::now();
+    loop {
+        // 每次从分派槽取入口函数的当前版本
+        let Some(entry_ptr) = compiler.hot_function(&entry) else { break };
+        let keep_going = if returns_bool {
+            let tick: fn() -> i64 = unsafe { std::mem::transmute(entry_ptr) };
+            tick() != 0
+        } else {
+            let tick: fn() = unsafe { std::mem::transmute(entry_ptr) };
+            tick();
+            true
+        };
+        bolide_runtime::flush_stdout();
+        if !keep_going {
+            break;
+        }
+        if last_check.elapsed() < HOT_POLL_INTERVAL {
+            continue;
+        }
+        last_check = std::time::Instant::now();
+        let current = modified(file);
+        if current != last_modified {
+            last_modified = current;
+            hot_reload_file(&mut compiler, file);
+        }
+    }
+
+    bolide_runtime::bolide_shutdown();
+    compiler.release_globals();
+    drop(compiler);
+    bolide_runtime::write_stdout(format_args!("Result: {}\n", result));
+    Ok(())
+}
+
+/// 重新解析源文件并替换改变的函数，结果打印到 stderr
+fn hot_reload_file(compiler: &mut JitCompiler, file: &Path) {
+    let ast = fs::read_to_string(file)
+        .map_err(|e| format!("Failed to read file: {}", e))
+        .and_then(|source| parse_source_for_target(&source, &CfgTarget::host())
+            .map(|(ast, _)| ast)
+            .map_err(|e| e.with_path(&file.display().to_string()).to_string()));
+    match ast.and_then(|ast| compiler.hot_reload(&ast)) {
+        Ok(names) if names.is_empty() => eprintln!("hot reload: no function changed"),
+        Ok(names) => {
+            print_compile_warnings(file, compiler.warnings());
+            eprintln!("hot reload: replaced {}", names.join(", "));
+        }
+        Err(e) => eprintln!("hot reload failed: {}", e),
+    }
+}
+
+/// `bolide test`：逐个编译运行测试块并打印汇总，返回是否全部通过
+///
+/// 每个测试块用新的 JIT 编译器单独编译（见 `test_programs`），断言失败由运行时记录，
+/// 测试结束时取出；编译错误也算这个测试失败。
+fn run_tests(path: &Path) -> miette::Result<bool> {
+    let files = test_files(path)?;
+    let mut passed = 0;
+    let mut failures: Vec<(String, Vec<String>)> = Vec::new();
+    for file in &files {
+        let source = fs::read_to_string(file)
+            .map_err(|e| miette::miette!("Failed to read {}: {}", file.display(), e))?;
+        let ast = parse_with_warnings(file, &source, &CfgTarget::host())?;
+        for case in bolide_compiler::test_programs(&ast) {
+            let label = format!("{}:{} \"{}\"", file.display(), case.line, case.name);
+            let messages = run_test_case(file, &source, &case);
+            if messages.is_empty() {
+                passed += 1;
+                bolide_runtime::write_stdout(format_args!("test {} ... ok\n", label));
+            } else {
+                bolide_runtime::write_stdout(format_args!("test {} ... FAILED\n", label));
+                failures.push((label, messages));
+            }
+        }
+    }
+
+    if passed + failures.len() == 0 {
+        bolide_runtime::write_stdout(format_args!("No tests found in {}\n", path.display()));
+        return Ok(true);
+    }
+    if !failures.is_empty() {
+        bolide_runtime::write_stdout(format_args!("\nfailures:\n"));
+        for (label, messages) in &failures {
+            bolide_runtime::write_stdout(format_args!("  test {}\n", label));
+            for message in messages {
+                bolide_runtime::write_stdout(format_args!("    {}\n", message));
+            }
+        }
+    }
+    let status = if failures.is_empty() { "ok" } else { "FAILED" };
+    bolide_runtime::write_stdout(format_args!(
+        "\ntest result: {}. {} passed; {} failed\n", status, passed, failures.len()
+    ));
+    Ok(failures.is_empty())
+}
+
+/// 要测试的文件：给出文件时就是它，给出目录时是其中（含子目录）全部 .bl 文件，按路径排序
+fn test_files(path: &Path) -> miette::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = fs::read_dir(&dir)
+            .map_err(|e| miette::miette!("Failed to read directory {}: {}", dir.display(), e))?;
+        for entry in entries.flatten() {
+            let entry_path = entry.path();
+            if entry_path.is_dir() {
+                dirs.push(entry_path);
+            } else if entry_path.extension().is_some_and(|ext| ext == "bl") {
+                files.push(entry_path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// 编译运行一个测试块，返回失败信息（通过时为空）
+fn run_test_case(file: &Path, source: &str, case: &bolide_compiler::TestCase) -> Vec<String> {
+    let mut compiler = JitCompiler::new();
+    compiler.set_source(&file.display().to_string(), source);
+    let main_ptr = match compiler.compile(&case.program) {
+        Ok(ptr) => ptr,
+        Err(e) => return vec![format!("compile error: {}", e)],
+    };
+    print_compile_warnings(file, compiler.warnings());
+
+    bolide_runtime::bolide_error_clear();
+    bolide_runtime::test_begin(&case.name);
+    let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+    main_fn();
+    // 测试启动的线程里的断言也算在这个测试上
+    if !bolide_runtime::wait_for_threads(bolide_runtime::THREAD_EXIT_TIMEOUT) {
+        eprintln!("warning: {} thread(s) still running after test \"{}\"", bolide_runtime::live_threads(), case.name);
+    }
+    let failures = bolide_runtime::test_end();
+    compiler.release_globals();
+    failures.into_iter().map(|failure| failure.message).collect()
+}
+
+/// 通过运行缓存执行：命中时直接运行缓存的可执行文件，否则 AOT 编译后存入缓存
+///
+/// 记录过导入列表的源码先按记录计算缓存键，命中时不解析源码。
+fn run_file_cached(file: &PathBuf) -> miette::Result<()> {
+    let source = fs::read_to_string(file)
+        .map_err(|e| miette::miette!("Failed to read file: {}", e))?;
+    let runtime_lib = find_runtime_lib(None, false)?;
+    let cache = cache::RunCache::open();
+
+    if let Some(imports) = cache.read_imports(&source) {
+        let entry = cache.entry_path(&cache::cache_key(&source, &imports, Path::new(&runtime_lib)));
+        if entry.exists() {
+            return run_cache_entry(file, &cache, &entry);
+        }
+    }
+
+    let ast = parse_with_warnings(file, &source, &CfgTarget::host())?;
+    let imports = bolide_compiler::import_files(&ast);
+    let key = cache::cache_key(&source, &imports, Path::new(&runtime_lib));
+    let entry = cache.entry_path(&key);
+    if entry.exists() {
+        cache.write_imports(&source, &imports);
+        return run_cache_entry(file, &cache, &entry);
+    }
+
+    bolide_runtime::write_stdout(format_args!("Running: {} (compiling into cache)\n", file.display()));
+    bolide_runtime::flush_stdout();
+    eprintln!("note: cached runs use the AOT compiler; known differences from `bolide run`:");
+    for divergence in cache::AOT_DIVERGENCES {
+        eprintln!("  - {}", divergence);
+    }
+
+    fs::create_dir_all(cache.dir())
+        .map_err(|e| miette::miette!("Failed to create cache directory {}: {}", cache.dir().display(), e))?;
+    let temp = cache.temp_path(&key);
+    if let Err(e) = build_executable(file, &source, &ast, &temp, false, CodegenFlags::default()) {
+        let _ = fs::remove_file(&temp);
+        eprintln!("warning: cannot cache this program ({}); running with the JIT", e);
+        return run_jit(file, &source, &ast, false, None, CodegenFlags::default());
+    }
+
+    // 改名失败（Windows 上同一缓存项正被另一个进程运行）时直接运行临时文件
+    if fs::rename(&temp, &entry).is_ok() {
+        cache.write_imports(&source, &imports);
+        cache.evict(&entry);
+        cache::run_executable(&entry)
+    } else {
+        let result = cache::run_executable(&temp);
+        let _ = fs::remove_file(&temp);
+        result
+    }
+    .map_err(|e| miette::miette!("Failed to run compiled program: {}", e))
+}
+
+/// 运行命中的缓存项
+fn run_cache_entry(file: &Path, cache: &cache::RunCache, entry: &Path) -> miette::Result<()> {
+    bolide_runtime::write_stdout(format_args!("Running: {} (cached)\n", file.display()));
+    bolide_runtime::flush_stdout();
+    cache.touch(entry);
+    cache::run_executable(entry)
+        .map_err(|e| miette::miette!("Failed to run cached program {}: {}", entry.display(), e))
+}
+
+/// `--analyze`：编译但不运行，打印每个函数的调用图和 RC 操作统计
+///
+/// 统计来自 JIT 生成的代码；`target` 只影响 `@cfg` 选择的条目。
+fn analyze_file(file: &Path, target: Option<&str>, json: bool, flags: CodegenFlags) -> miette::Result<()> {
+    let cfg_target = match target {
+        Some(triple) => CfgTarget::from_triple(triple).map_err(|e| miette::miette!("{}", e))?,
+        None => CfgTarget::host(),
+    };
+    let source = fs::read_to_string(file)
+        .map_err(|e| miette::miette!("Failed to read file: {}", e))?;
+    let ast = parse_with_warnings(file, &source, &cfg_target)?;
+
+    let mut compiler = JitCompiler::new();
+    compiler.set_source(&file.display().to_string(), &source);
+    flags.apply_jit(&mut compiler);
+    let report = compiler.analyze(&ast)
+        .map_err(|e| miette::miette!("Compile error: {}", e))?;
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report.to_text());
+    }
     Ok(())
 }
 
 /// AOT 编译文件
-fn compile_file(file: &PathBuf, output: &PathBuf) -> miette::Result<()> {
+///
+/// `emit` 为 `exe`、`obj` 或 `lib`；没有给出输出路径时按它取默认名（`prog.exe`、`prog.o`、`libprog.a`）。
+/// `incremental` 只用于本机的可执行文件（命令行参数保证）。
+fn compile_file(file: &PathBuf, output: Option<PathBuf>, target: Option<&str>, emit: &str, incremental: bool, flags: CodegenFlags) -> miette::Result<()> {
+    let cfg_target = match target {
+        Some(triple) => CfgTarget::from_triple(triple).map_err(|e| miette::miette!("{}", e))?,
+        None => CfgTarget::host(),
+    };
+    let windows = cfg_target.os == "windows";
+    let output = output.unwrap_or_else(|| match emit {
+        "obj" => file.with_extension(if windows { "obj" } else { "o" }),
+        "lib" => {
+            let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+            file.with_file_name(if windows { format!("{}.lib", stem) } else { format!("lib{}.a", stem) })
+        }
+        _ => file.with_extension("exe"),
+    });
+    let output = &output;
     println!("Compiling: {} -> {}", file.display(), output.display());
 
     // 读取源文件
@@ -147,18 +761,40 @@ fn compile_file(file: &PathBuf, output: &PathBuf) -> miette::Result<()> {
         .map_err(|e| miette::miette!("Failed to read file: {}", e))?;
 
     // 解析
-    let ast = parse_source(&source)
-        .map_err(|e| miette::miette!("Parse error: {}", e))?;
+    let ast = parse_with_warnings(file, &source, &cfg_target)?;
 
+    if emit != "exe" {
+        return build_library(file, &source, &ast, target, output, emit == "lib", flags);
+    }
+    if let Some(triple) = target.filter(|_| cfg_target != CfgTarget::host()) {
+        return build_object(file, &source, &ast, triple, &cfg_target, output, flags);
+    }
+    if incremental {
+        build_incremental(file, &source, &ast, output, true, flags)?;
+    } else {
+        build_executable(file, &source, &ast, output, true, flags)?;
+    }
+
+    println!("Successfully compiled: {}", output.display());
+    Ok(())
+}
+
+/// AOT 编译已解析的程序并链接成可执行文件
+///
+/// `verbose` 为 false 时不打印进度（缓存运行时 stdout 只留给程序本身）。
+fn build_executable(file: &Path, source: &str, ast: &bolide_parser::Program, output: &Path, verbose: bool, flags: CodegenFlags) -> miette::Result<()> {
     // AOT 编译
-    let compiler = AotCompiler::new()
+    let mut compiler = AotCompiler::new()
         .map_err(|e| miette::miette!("Compiler init error: {}", e))?;
+    compiler.set_source(&file.display().to_string(), source);
+    flags.apply_aot(&mut compiler);
 
-    let result = compiler.compile(&ast)
+    let result = compiler.compile(ast)
         .map_err(|e| miette::miette!("Compile error: {}", e))?;
+    print_compile_warnings(file, &result.warnings);
 
     // 打印外部库信息
-    if !result.extern_libs.is_empty() {
+    if verbose && !result.extern_libs.is_empty() {
         println!("External libraries: {:?}", result.extern_libs);
     }
 
@@ -167,20 +803,217 @@ fn compile_file(file: &PathBuf, output: &PathBuf) -> miette::Result<()> {
     fs::write(&obj_path, &result.object_code)
         .map_err(|e| miette::miette!("Failed to write object file: {}", e))?;
 
-    println!("Generated object file: {}", obj_path.display());
+    if verbose {
+        println!("Generated object file: {}", obj_path.display());
+    }
 
     // 链接
-    link_executable(&obj_path, output, &result.extern_libs)?;
+    let linked = link_executable(std::slice::from_ref(&obj_path), output, &result.extern_libs, verbose);
 
     // 清理目标文件
     let _ = fs::remove_file(&obj_path);
+    linked
+}
 
-    println!("Successfully compiled: {}", output.display());
+/// 增量编译并链接：导入的模块按内容缓存目标文件（见 `incremental`），主程序每次重新编译
+///
+/// 返回各模块的目标文件，`rebuilt` 标出本次重新编译的模块。
+fn build_incremental(file: &Path, source: &str, ast: &bolide_parser::Program, output: &Path, verbose: bool, flags: CodegenFlags) -> miette::Result<Vec<incremental::ModuleObject>> {
+    let build_dir = incremental::build_dir(output);
+    let modules = incremental::build_modules(ast, &toolchain::host_triple(), &build_dir, flags.no_trace, flags.share_containers, flags.no_opt)
+        .map_err(|e| miette::miette!("Compile error: {}", e))?;
+    for module in &modules {
+        print_compile_warnings(Path::new(&module.file), &module.warnings);
+        if verbose {
+            let action = if module.rebuilt { "Compiling" } else { "Reusing" };
+            println!("{} module {} -> {}", action, module.file, module.object.display());
+        }
+    }
+
+    let mut compiler = AotCompiler::new()
+        .map_err(|e| miette::miette!("Compiler init error: {}", e))?;
+    compiler.set_source(&file.display().to_string(), source);
+    flags.apply_aot(&mut compiler);
+    let result = compiler.compile_main(ast)
+        .map_err(|e| miette::miette!("Compile error: {}", e))?;
+    print_compile_warnings(file, &result.warnings);
+
+    if verbose && !result.extern_libs.is_empty() {
+        println!("External libraries: {:?}", result.extern_libs);
+    }
+
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let main_obj = build_dir.join(format!("{}-main.o", stem));
+    fs::write(&main_obj, &result.object_code)
+        .map_err(|e| miette::miette!("Failed to write object file: {}", e))?;
+
+    let mut objects = vec![main_obj];
+    objects.extend(modules.iter().map(|m| m.object.clone()));
+    link_executable(&objects, output, &result.extern_libs, verbose)?;
+    Ok(modules)
+}
+
+/// 为其他平台编译：只写出目标文件，不调用本机链接器，而是打印建议的链接命令
+///
+/// 链接需要目标平台的工具链和运行时库；运行时库没有安装时命令里只写库名。
+fn build_object(file: &Path, source: &str, ast: &bolide_parser::Program, triple: &str, target: &CfgTarget, output: &Path, flags: CodegenFlags) -> miette::Result<()> {
+    let mut compiler = AotCompiler::with_target(Some(triple))
+        .map_err(|e| miette::miette!("Compiler init error: {}", e))?;
+    compiler.set_source(&file.display().to_string(), source);
+    flags.apply_aot(&mut compiler);
+
+    let result = compiler.compile(ast)
+        .map_err(|e| miette::miette!("Compile error: {}", e))?;
+    print_compile_warnings(file, &result.warnings);
+
+    if !result.extern_libs.is_empty() {
+        println!("External libraries: {:?}", result.extern_libs);
+    }
+
+    let obj_path = output.with_extension(if target.os == "windows" { "obj" } else { "o" });
+    fs::write(&obj_path, &result.object_code)
+        .map_err(|e| miette::miette!("Failed to write object file: {}", e))?;
+
+    println!("Generated object file: {} ({})", obj_path.display(), result.target_triple);
+    let runtime_lib = match find_runtime_lib(Some(triple), false) {
+        Ok(path) => path,
+        Err(_) => {
+            println!("note: the runtime library for {} is not installed; get it with `bolide toolchain install {}`", triple, triple);
+            toolchain::runtime_lib_name(triple).to_string()
+        }
+    };
+    println!("note: cross-compiling skips the host linker; link it with a {} toolchain:", triple);
+    println!("  {}", link_command(triple, &obj_path, output, &runtime_lib, &result.extern_libs));
     Ok(())
 }
 
+/// 为目标平台链接目标文件的命令（与本机链接时 `link_windows` / `link_unix` 的参数相同）
+fn link_command(triple: &str, object: &Path, output: &Path, runtime_lib: &str, extern_libs: &[String]) -> String {
+    let args: Vec<String> = if triple.ends_with("windows-msvc") {
+        let mut args = vec![
+            "lld-link".to_string(),
+            "/ENTRY:main".to_string(),
+            "/SUBSYSTEM:CONSOLE".to_string(),
+            format!("/OUT:{}", output.display()),
+            object.display().to_string(),
+            runtime_lib.to_string(),
+        ];
+        args.extend(WINDOWS_LINK_LIBS.iter().map(|lib| lib.to_string()));
+        args.extend(extern_libs.iter().map(|lib| windows_lib_arg(lib)));
+        args
+    } else {
+        let mut args = vec![
+            "clang".to_string(),
+            format!("--target={}", triple),
+            "-o".to_string(),
+            output.display().to_string(),
+            object.display().to_string(),
+            runtime_lib.to_string(),
+        ];
+        args.extend(UNIX_LINK_LIBS.iter().map(|lib| lib.to_string()));
+        args.extend(extern_libs.iter().map(|lib| unix_lib_arg(lib)));
+        args
+    };
+    args.join(" ")
+}
+
+/// 编译成不带入口的目标文件（`archive` 为 true 时打包成静态库），并在旁边写出 C 头文件
+///
+/// 头文件名取输出文件名去掉扩展名和 `lib` 前缀（`libmath.a` -> `math.h`）。
+fn build_library(file: &Path, source: &str, ast: &bolide_parser::Program, target: Option<&str>, output: &Path, archive: bool, flags: CodegenFlags) -> miette::Result<()> {
+    let mut compiler = AotCompiler::with_target(target).map_err(|e| miette::miette!("Compiler init error: {}", e))?;
+    compiler.set_source(&file.display().to_string(), source);
+    flags.apply_aot(&mut compiler);
+
+    let result = compiler.compile_library(ast)
+        .map_err(|e| miette::miette!("Compile error: {}", e))?;
+    print_compile_warnings(file, &result.warnings);
+
+    if !result.extern_libs.is_empty() {
+        println!("External libraries: {:?}", result.extern_libs);
+    }
+
+    if archive {
+        let obj_path = output.with_extension("o");
+        fs::write(&obj_path, &result.object_code)
+            .map_err(|e| miette::miette!("Failed to write object file: {}", e))?;
+        let archived = archive_object(&obj_path, output);
+        let _ = fs::remove_file(&obj_path);
+        archived?;
+    } else {
+        fs::write(output, &result.object_code)
+            .map_err(|e| miette::miette!("Failed to write object file: {}", e))?;
+    }
+
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = if archive { stem.strip_prefix("lib").unwrap_or(&stem) } else { &stem };
+    let header_path = output.with_file_name(format!("{}.h", name));
+    fs::write(&header_path, c_header(ast, name))
+        .map_err(|e| miette::miette!("Failed to write header: {}", e))?;
+
+    println!("Generated {}: {}", if archive { "static library" } else { "object file" }, output.display());
+    println!("Generated header: {}", header_path.display());
+    match find_runtime_lib(target, false) {
+        Ok(runtime_lib) => println!("note: link it together with the runtime library {}", runtime_lib),
+        Err(_) => println!("note: link it together with the Bolide runtime library"),
+    }
+    Ok(())
+}
+
+/// 把目标文件打包成静态库
+fn archive_object(obj_path: &Path, output: &Path) -> miette::Result<()> {
+    // ar 会向已有的库追加成员
+    let _ = fs::remove_file(output);
+
+    #[cfg(target_os = "windows")]
+    let status = Command::new("llvm-lib")
+        .arg(format!("/OUT:{}", output.display()))
+        .arg(obj_path)
+        .status();
+    #[cfg(not(target_os = "windows"))]
+    let status = Command::new("ar")
+        .arg("rcs")
+        .arg(output)
+        .arg(obj_path)
+        .status();
+
+    let status = status.map_err(|e| miette::miette!("Archiver not found: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(miette::miette!("Creating the static library failed"))
+    }
+}
+
 /// 查找运行时库路径
-fn find_runtime_lib() -> miette::Result<String> {
+///
+/// 本机目标先找随 CLI 构建的库（可执行文件旁、target/debug），再找 `bolide toolchain`
+/// 安装的库；其他目标只找安装的库，缺失时提示安装命令。
+fn find_runtime_lib(target: Option<&str>, verbose: bool) -> miette::Result<String> {
+    let host = toolchain::host_triple();
+    let triple = target.unwrap_or(&host);
+    if triple == host {
+        if let Some(path) = find_local_runtime_lib(verbose)? {
+            return Ok(path);
+        }
+    }
+
+    let toolchains = toolchain::Toolchains::open();
+    if let Some(path) = toolchains.find(triple).map_err(|e| miette::miette!("{}", e))? {
+        if verbose {
+            println!("Found runtime library: {}", path.display());
+        }
+        return Ok(path.display().to_string());
+    }
+
+    Err(miette::miette!(
+        "Runtime library for {} not found: {}; install it with `bolide toolchain install {}`",
+        triple, toolchain::runtime_lib_name(triple), triple
+    ))
+}
+
+/// 查找随 CLI 一起构建的本机运行时库
+fn find_local_runtime_lib(verbose: bool) -> miette::Result<Option<String>> {
     // 获取当前可执行文件路径
     let exe_path = std::env::current_exe()
         .map_err(|e| miette::miette!("Failed to get executable path: {}", e))?;
@@ -195,51 +1028,91 @@ fn find_runtime_lib() -> miette::Result<String> {
 
     let lib_path = exe_dir.join(lib_name);
     if lib_path.exists() {
-        println!("Found runtime library: {}", lib_path.display());
-        return Ok(lib_path.display().to_string());
+        if verbose {
+            println!("Found runtime library: {}", lib_path.display());
+        }
+        return Ok(Some(lib_path.display().to_string()));
     }
 
     // 尝试在 target/debug 目录下查找
     let debug_path = exe_dir.join("..").join(lib_name);
     if debug_path.exists() {
         let path = debug_path.canonicalize().unwrap();
-        println!("Found runtime library: {}", path.display());
-        return Ok(path.display().to_string());
+        if verbose {
+            println!("Found runtime library: {}", path.display());
+        }
+        return Ok(Some(path.display().to_string()));
     }
 
     // 尝试在当前工作目录的 target/debug 下查找
     let cwd_path = PathBuf::from("target/debug").join(lib_name);
     if cwd_path.exists() {
         let path = cwd_path.canonicalize().unwrap();
-        println!("Found runtime library: {}", path.display());
-        return Ok(path.display().to_string());
+        if verbose {
+            println!("Found runtime library: {}", path.display());
+        }
+        return Ok(Some(path.display().to_string()));
     }
 
-    Err(miette::miette!("Runtime library not found: {}", lib_name))
+    Ok(None)
+}
+
+/// Windows 上链接可执行文件需要的系统库
+const WINDOWS_LINK_LIBS: &[&str] = &[
+    "kernel32.lib", "msvcrt.lib", "ucrt.lib", "vcruntime.lib", "libcmt.lib", "ws2_32.lib",
+    "userenv.lib", "advapi32.lib", "bcrypt.lib", "ntdll.lib", "legacy_stdio_definitions.lib",
+];
+
+/// 类 Unix 系统上链接可执行文件需要的系统库
+const UNIX_LINK_LIBS: &[&str] = &["-lm", "-lpthread", "-ldl"];
+
+/// extern 块的库在 lld-link 命令行上的写法（`foo.dll` -> `foo.lib`）
+fn windows_lib_arg(lib: &str) -> String {
+    if lib.to_lowercase().ends_with(".dll") {
+        lib[..lib.len()-4].to_string() + ".lib"
+    } else {
+        lib.to_string()
+    }
+}
+
+/// extern 块的库在 cc 命令行上的写法
+fn unix_lib_arg(lib: &str) -> String {
+    if lib.starts_with("lib") && lib.ends_with(".so") {
+        // libfoo.so -> -lfoo
+        format!("-l{}", &lib[3..lib.len()-3])
+    } else if lib.ends_with(".so") || lib.contains(".so.") {
+        // foo.so -> -l:foo.so；带版本号的 libc.so.6 -> -l:libc.so.6（在库目录里按文件名查找）
+        format!("-l:{}", lib)
+    } else {
+        // 直接使用
+        lib.to_string()
+    }
 }
 
 /// 链接可执行文件
-fn link_executable(obj_path: &PathBuf, output: &PathBuf, extern_libs: &[String]) -> miette::Result<()> {
+fn link_executable(objects: &[PathBuf], output: &Path, extern_libs: &[String], verbose: bool) -> miette::Result<()> {
     #[cfg(target_os = "windows")]
     {
-        link_windows(obj_path, output, extern_libs)
+        link_windows(objects, output, extern_libs, verbose)
     }
 
     #[cfg(not(target_os = "windows"))]
     {
-        link_unix(obj_path, output, extern_libs)
+        link_unix(objects, output, extern_libs, verbose)
     }
 }
 
 #[cfg(target_os = "windows")]
-fn link_windows(obj_path: &PathBuf, output: &PathBuf, extern_libs: &[String]) -> miette::Result<()> {
+fn link_windows(objects: &[PathBuf], output: &Path, extern_libs: &[String], verbose: bool) -> miette::Result<()> {
     // 查找运行时库
-    let runtime_lib_path = PathBuf::from(find_runtime_lib()?);
+    let runtime_lib_path = PathBuf::from(find_runtime_lib(None, verbose)?);
     let runtime_lib_dir = runtime_lib_path.parent().unwrap().display().to_string();
     let runtime_lib_name = runtime_lib_path.file_name().unwrap().to_str().unwrap();
 
-    println!("Runtime lib dir: {}", runtime_lib_dir);
-    println!("Runtime lib name: {}", runtime_lib_name);
+    if verbose {
+        println!("Runtime lib dir: {}", runtime_lib_dir);
+        println!("Runtime lib name: {}", runtime_lib_name);
+    }
 
     // 构建链接参数
     let libpath_arg = format!("/LIBPATH:{}", runtime_lib_dir);
@@ -249,34 +1122,22 @@ fn link_windows(obj_path: &PathBuf, output: &PathBuf, extern_libs: &[String]) ->
         "/ENTRY:main".to_string(),
         "/SUBSYSTEM:CONSOLE".to_string(),
         out_arg,
-        obj_path.display().to_string(),
-        runtime_lib_name.to_string(),
-        libpath_arg,
-        "kernel32.lib".to_string(),
-        "msvcrt.lib".to_string(),
-        "ucrt.lib".to_string(),
-        "vcruntime.lib".to_string(),
-        "libcmt.lib".to_string(),
-        "ws2_32.lib".to_string(),
-        "userenv.lib".to_string(),
-        "advapi32.lib".to_string(),
-        "bcrypt.lib".to_string(),
-        "ntdll.lib".to_string(),
-        "legacy_stdio_definitions.lib".to_string(),
     ];
+    args.extend(objects.iter().map(|obj| obj.display().to_string()));
+    args.extend([runtime_lib_name.to_string(), libpath_arg]);
+    args.extend(WINDOWS_LINK_LIBS.iter().map(|lib| lib.to_string()));
 
-    // 添加外部库 (将 .dll 转换为 .lib)
     for lib in extern_libs {
-        let lib_name = if lib.to_lowercase().ends_with(".dll") {
-            lib[..lib.len()-4].to_string() + ".lib"
-        } else {
-            lib.clone()
-        };
-        println!("Adding external library: {}", lib_name);
+        let lib_name = windows_lib_arg(lib);
+        if verbose {
+            println!("Adding external library: {}", lib_name);
+        }
         args.push(lib_name);
     }
 
-    println!("Running lld-link...");
+    if verbose {
+        println!("Running lld-link...");
+    }
     let status = Command::new("lld-link")
         .args(&args)
         .status()
@@ -290,32 +1151,22 @@ fn link_windows(obj_path: &PathBuf, output: &PathBuf, extern_libs: &[String]) ->
 }
 
 #[cfg(not(target_os = "windows"))]
-fn link_unix(obj_path: &PathBuf, output: &PathBuf, extern_libs: &[String]) -> miette::Result<()> {
-    let runtime_lib = find_runtime_lib()?;
+fn link_unix(objects: &[PathBuf], output: &Path, extern_libs: &[String], verbose: bool) -> miette::Result<()> {
+    let runtime_lib = find_runtime_lib(None, verbose)?;
 
     let mut args = vec![
         "-o".to_string(),
         output.display().to_string(),
-        obj_path.display().to_string(),
-        runtime_lib,
-        "-lm".to_string(),
-        "-lpthread".to_string(),
-        "-ldl".to_string(),
     ];
+    args.extend(objects.iter().map(|obj| obj.display().to_string()));
+    args.push(runtime_lib);
+    args.extend(UNIX_LINK_LIBS.iter().map(|lib| lib.to_string()));
 
-    // 添加外部库 (将 .so 转换为 -l 参数)
     for lib in extern_libs {
-        let lib_name = if lib.starts_with("lib") && lib.ends_with(".so") {
-            // libfoo.so -> -lfoo
-            format!("-l{}", &lib[3..lib.len()-3])
-        } else if lib.ends_with(".so") {
-            // foo.so -> -l:foo.so
-            format!("-l:{}", lib)
-        } else {
-            // 直接使用
-            lib.clone()
-        };
-        println!("Adding external library: {}", lib_name);
+        let lib_name = unix_lib_arg(lib);
+        if verbose {
+            println!("Adding external library: {}", lib_name);
+        }
         args.push(lib_name);
     }
 
@@ -336,39 +1187,70 @@ fn run_repl() -> miette::Result<()> {
     println!("Type 'exit' or 'quit' to exit, 'help' for help.");
     println!();
 
-    let stdin = io::stdin();
+    repl_session(Box::new(io::BufReader::new(io::stdin())), &mut io::stdout());
+
+    bolide_runtime::bolide_shutdown();
+    println!("Goodbye!");
+    Ok(())
+}
+
+/// REPL 的行来源。程序里的 `input()` 也从这里取行（见 `repl_input`），
+/// 不会和 REPL 抢同一份输入，也不会留下读了一半的缓冲区
+static REPL_INPUT: std::sync::Mutex<Option<Box<dyn io::BufRead + Send>>> = std::sync::Mutex::new(None);
+
+/// 从 REPL 的行来源读一行（不含换行符），输入结束时返回 None
+fn repl_read_line() -> Option<String> {
+    let mut source = REPL_INPUT.lock().unwrap_or_else(|e| e.into_inner());
+    let mut line = String::new();
+    match source.as_mut()?.read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim_end_matches('\n').trim_end_matches('\r').to_string()),
+    }
+}
+
+/// REPL 里 `input()` 的读取函数：提示前加 `< `，和 REPL 自己的 `>>> ` 区分开
+extern "C" fn repl_input(prompt: *const bolide_runtime::BolideString) -> *mut bolide_runtime::BolideString {
+    let prompt = if prompt.is_null() { "" } else { unsafe { (*prompt).as_str() } };
+    bolide_runtime::write_stdout(format_args!("< {}", prompt));
+    bolide_runtime::flush_stdout();
+    bolide_runtime::BolideString::new(&repl_read_line().unwrap_or_default())
+}
+
+/// 运行一次 REPL 会话，直到 `exit` 或输入结束；提示符和结果写到 `out`，错误写到标准错误
+fn repl_session(input: Box<dyn io::BufRead + Send>, out: &mut dyn Write) {
+    *REPL_INPUT.lock().unwrap_or_else(|e| e.into_inner()) = Some(input);
+    bolide_runtime::bolide_set_input_handler(Some(repl_input));
+
     let mut state = ReplState::new();
     let mut input_buffer = String::new();
     let mut in_multiline = false;
 
     loop {
         if in_multiline {
-            print!("... ");
+            write!(out, "... ").unwrap();
         } else {
-            print!(">>> ");
+            write!(out, ">>> ").unwrap();
         }
-        io::stdout().flush().unwrap();
+        out.flush().unwrap();
 
-        let mut line = String::new();
-        if stdin.read_line(&mut line).is_err() {
+        let Some(line) = repl_read_line() else {
             break;
-        }
-
-        let line = line.trim_end_matches('\n').trim_end_matches('\r');
+        };
+        let line = line.as_str();
 
-        // 处理多行输入（函数/类定义）
+        // 处理多行输入（函数/类定义、match 等块语句）
         if in_multiline {
             input_buffer.push_str(line);
             input_buffer.push('\n');
 
-            // 检查是否结束（以 } 结尾）
-            if line.trim() == "}" {
+            // 所有 { 都闭合、三引号字符串也结束后才算完（嵌套块的 } 不算）
+            if !input_incomplete(&input_buffer) {
                 in_multiline = false;
                 let input = input_buffer.trim().to_string();
                 input_buffer.clear();
 
                 match eval_input(&mut state, &input) {
-                    Ok(msg) => println!("{}", msg),
+                    Ok(msg) => writeln!(out, "{}", msg).unwrap(),
                     Err(e) => eprintln!("Error: {}", e),
                 }
             }
@@ -380,6 +1262,14 @@ fn run_repl() -> miette::Result<()> {
             continue;
         }
 
+        if input.starts_with(':') {
+            match meta_command(&mut state, input) {
+                Ok(msg) => writeln!(out, "{}", msg).unwrap(),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+            continue;
+        }
+
         match input {
             "exit" | "quit" => break,
             "help" => {
@@ -388,14 +1278,14 @@ fn run_repl() -> miette::Result<()> {
             }
             "clear" => {
                 state = ReplState::new();
-                println!("State cleared.");
+                writeln!(out, "State cleared.").unwrap();
                 continue;
             }
             _ => {}
         }
 
-        // 检查是否是多行输入的开始（包含 { 但不以 } 结尾）
-        if input.contains('{') && !input.trim().ends_with('}') {
+        // 检查是否是多行输入的开始（有未闭合的 {，如 `fn f() {`、`match x {`，或者 `"""` 字符串）
+        if input_incomplete(input) {
             in_multiline = true;
             input_buffer = input.to_string();
             input_buffer.push('\n');
@@ -403,13 +1293,52 @@ fn run_repl() -> miette::Result<()> {
         }
 
         match eval_input(&mut state, input) {
-            Ok(msg) => println!("{}", msg),
+            Ok(msg) => writeln!(out, "{}", msg).unwrap(),
             Err(e) => eprintln!("Error: {}", e),
         }
     }
 
-    println!("Goodbye!");
-    Ok(())
+    bolide_runtime::bolide_set_input_handler(None);
+    *REPL_INPUT.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// 输入是否还没写完：有未闭合的 `{`，或者停在没有结束的三引号字符串里。
+/// 跳过字符串字面量和 `//` 注释；单引号字符串不跨行
+fn input_incomplete(text: &str) -> bool {
+    let mut depth = 0i64;
+    let mut in_string = false;
+    let mut in_triple = false;
+    let mut in_comment = false;
+    let mut chars = text.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        if in_triple {
+            if text[pos..].starts_with("\"\"\"") {
+                chars.nth(1);
+                in_triple = false;
+            }
+            continue;
+        }
+        if c == '\n' {
+            in_string = false;
+            in_comment = false;
+            continue;
+        }
+        if in_comment {
+            continue;
+        }
+        match c {
+            '"' if !in_string && text[pos..].starts_with("\"\"\"") => {
+                chars.nth(1);
+                in_triple = true;
+            }
+            '"' => in_string = !in_string,
+            '/' if !in_string && matches!(chars.peek(), Some((_, '/'))) => in_comment = true,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0 || in_triple
 }
 
 fn print_help() {
@@ -417,6 +1346,10 @@ fn print_help() {
     println!("  exit, quit  - Exit the REPL");
     println!("  help        - Show this help message");
     println!("  clear       - Clear all defined variables and functions");
+    println!("  :type EXPR  - Show the inferred type of an expression without running it");
+    println!("  :ir NAME    - Show the Cranelift IR of a defined function");
+    println!("  :funcs      - List defined functions with their signatures");
+    println!("  :vars       - List declared variables with their types");
     println!();
     println!("Enter Bolide code:");
     println!("  - Variables:   let x: int = 10;");
@@ -424,63 +1357,646 @@ fn print_help() {
 }
 
 fn eval_input(state: &mut ReplState, input: &str) -> Result<String, String> {
-    let input_type = state.add_input(input);
-
-    match input_type {
-        InputType::FuncDef => {
-            // 验证函数定义是否有效
-            let code = state.build_program(None);
-            let ast = parse_source(&code).map_err(|e| {
-                state.functions.pop();
-                e.to_string()
-            })?;
-            let mut compiler = JitCompiler::new();
-            compiler.compile(&ast).map_err(|e| {
-                state.functions.pop();
-                e.to_string()
-            })?;
-            Ok("Function defined.".to_string())
-        }
-        InputType::ClassDef => {
-            let code = state.build_program(None);
-            let ast = parse_source(&code).map_err(|e| {
-                state.functions.pop();
-                e.to_string()
-            })?;
-            let mut compiler = JitCompiler::new();
-            compiler.compile(&ast).map_err(|e| {
-                state.functions.pop();
-                e.to_string()
-            })?;
-            Ok("Class defined.".to_string())
-        }
-        InputType::VarDecl => {
-            // 验证变量声明是否有效
-            let code = state.build_program(None);
-            let ast = parse_source(&code).map_err(|e| {
-                state.globals.pop();
-                e.to_string()
-            })?;
-            let mut compiler = JitCompiler::new();
-            compiler.compile(&ast).map_err(|e| {
-                state.globals.pop();
-                e.to_string()
-            })?;
-            Ok("Variable declared.".to_string())
-        }
-        InputType::Expr => {
-            let code = state.build_program(Some(input));
-            let ast = parse_source(&code).map_err(|e| e.to_string())?;
-            let mut compiler = JitCompiler::new();
-            let main_ptr = compiler.compile(&ast).map_err(|e| e.to_string())?;
-            let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
-            let result = main_fn();
-            // 只有非零结果才显示（print等语句返回0）
-            if result != 0 {
-                Ok(result.to_string())
-            } else {
-                Ok(String::new())
+    // 多行输入以 `}` 结尾时可以省略语句末尾的 `;`（如 `let f = fn(x: int) -> int { ... }`）
+    let ast = match parse_source(input) {
+        Ok(ast) => ast,
+        Err(e) => match parse_source(&format!("{};", input)) {
+            Ok(ast) if input.ends_with('}') => ast,
+            _ => return Err(format!("{:?}", parse_report("<repl>", input, *e))),
+        },
+    };
+    state.compiler.set_source("<repl>", input);
+    let main_ptr = state.compiler.compile_incremental(&ast)?;
+    print_compile_warnings(Path::new("<repl>"), state.compiler.warnings());
+    state.record(&ast);
+
+    let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+    let result = main_fn();
+    match input_type(&ast) {
+        InputType::FuncDef => Ok("Function defined.".to_string()),
+        InputType::ClassDef => Ok("Class defined.".to_string()),
+        InputType::VarDecl => Ok("Variable declared.".to_string()),
+        // 只有非零结果才显示（print等语句返回0）
+        InputType::Expr if result != 0 => Ok(result.to_string()),
+        InputType::Expr => Ok(String::new()),
+    }
+}
+
+/// REPL 的 `:` 命令，在普通求值之前处理
+fn meta_command(state: &mut ReplState, input: &str) -> Result<String, String> {
+    let (command, arg) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    let arg = arg.trim();
+    match command {
+        ":type" if !arg.is_empty() => {
+            let source = format!("{};", arg.trim_end_matches(';'));
+            let ast = parse_source(&source).map_err(|e| format!("{:?}", parse_report("<repl>", &source, *e)))?;
+            match ast.statements.as_slice() {
+                [bolide_parser::Statement::Expr(expr)] => state.compiler.type_of(expr),
+                _ => Err(":type expects a single expression".to_string()),
             }
         }
+        ":ir" if !arg.is_empty() => state.compiler.function_ir(arg)
+            .map(|ir| ir.trim_end().to_string())
+            .ok_or_else(|| format!("no compiled function named '{}'", arg)),
+        ":funcs" if arg.is_empty() => Ok(state.functions.iter()
+            .filter_map(|name| state.compiler.function_signature(name))
+            .collect::<Vec<_>>()
+            .join("\n")),
+        ":vars" if arg.is_empty() => Ok(state.variables.iter()
+            .filter_map(|name| Some(format!("{}: {}", name, state.compiler.global_type(name)?)))
+            .collect::<Vec<_>>()
+            .join("\n")),
+        ":type" | ":ir" => Err(format!("usage: {} {}", command, if command == ":type" { "EXPR" } else { "NAME" })),
+        ":funcs" | ":vars" => Err(format!("{} takes no arguments", command)),
+        _ => Err(format!("unknown command '{}' (type help for the list)", command)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Output;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 本机运行时库的路径；需要先 cargo build 生成，没有时测试直接失败而不是跳过
+    fn runtime_lib() -> String {
+        find_runtime_lib(None, false).expect("runtime library not built; run `cargo build --workspace` first")
+    }
+
+    /// 把一段程序编译成可执行文件并运行，返回进程的输出
+    fn run_aot_output(source: &str, flags: CodegenFlags) -> Output {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        runtime_lib();
+        let dir = std::env::temp_dir().join(format!("bolide_aot_{}_{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed)));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.bl");
+        let output = dir.join("main");
+        let ast = parse_source(source).unwrap();
+        build_executable(&path, source, &ast, &output, false, flags).unwrap();
+        let result = Command::new(&output).output().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    /// 按给定选项编译运行，检查正常退出，返回标准输出
+    fn run_aot_with(source: &str, flags: CodegenFlags) -> String {
+        let result = run_aot_output(source, flags);
+        assert!(result.status.success(), "exit status {}: {}", result.status, String::from_utf8_lossy(&result.stderr));
+        String::from_utf8_lossy(&result.stdout).into_owned()
+    }
+
+    /// 按默认选项编译运行，返回标准输出
+    fn run_aot(source: &str) -> String {
+        run_aot_with(source, CodegenFlags::default())
+    }
+
+    #[test]
+    fn test_input_incomplete() {
+        assert!(input_incomplete("match x {"));
+        assert!(input_incomplete("match x { 1 => { print(1); }"));
+        assert!(input_incomplete("match x {\n    1 => {\n        print(\"}\");\n    }\n"));
+        assert!(!input_incomplete("match x {\n    _ => { } // }\n}"));
+        assert!(!input_incomplete("let d = {1: 2};"));
+        // 三引号字符串里的引号、花括号和 // 不算
+        assert!(input_incomplete("let s: str = \"\"\""));
+        assert!(input_incomplete("let s: str = \"\"\"\n    { \"a\" // }\n"));
+        assert!(!input_incomplete("let s: str = \"\"\"\n    { \"a\" // \n    \"\"\";"));
+        assert!(!input_incomplete("let s: str = \"\"\"\"\"\";"));
+    }
+
+    #[test]
+    fn test_parse_sample_rate() {
+        assert_eq!(parse_sample_rate("997hz"), Ok(997));
+        assert_eq!(parse_sample_rate("100Hz"), Ok(100));
+        assert_eq!(parse_sample_rate("50"), Ok(50));
+        assert!(parse_sample_rate("0hz").unwrap_err().contains("between 1hz"));
+        assert!(parse_sample_rate("fast").unwrap_err().contains("invalid sample rate 'fast'"));
+        assert_eq!(Sampling::default_output(Path::new("dir/app.bl")), PathBuf::from("app.folded"));
+    }
+
+    #[test]
+    fn test_repl_keeps_state() {
+        let mut state = ReplState::new();
+        assert_eq!(eval_input(&mut state, "let x = 5;").unwrap(), "Variable declared.");
+        assert_eq!(eval_input(&mut state, "fn scale(n: int) -> int {\n    return n * 8;\n}").unwrap(), "Function defined.");
+        eval_input(&mut state, "x = scale(x) + 2;").unwrap();
+        assert_eq!(eval_input(&mut state, "return x;").unwrap(), "42");
+        assert_eq!(eval_input(&mut state, "fn scale(n: int) -> int {\n    return n;\n}").unwrap_err(), "'scale' is already defined");
+        assert!(eval_input(&mut state, "return y;").is_err());
+        assert_eq!(eval_input(&mut state, "return scale(x);").unwrap(), "336");
+    }
+
+    #[test]
+    fn test_repl_meta_commands() {
+        let mut state = ReplState::new();
+        eval_input(&mut state, "fn f(x: int, ref n: int) -> int {\n    return x + n;\n}").unwrap();
+        eval_input(&mut state, "let total: float = 1.5;").unwrap();
+        eval_input(&mut state, "let names: list<str> = [\"a\"];").unwrap();
+
+        // 推断类型不执行表达式
+        assert_eq!(meta_command(&mut state, ":type f(1, 2) + 2.0").unwrap(), "float");
+        assert_eq!(meta_command(&mut state, ":type names[0] + \"!\"").unwrap(), "str");
+        assert_eq!(meta_command(&mut state, ":type f").unwrap(), "func(int, int) -> int");
+        assert_eq!(meta_command(&mut state, ":type nope + 1").unwrap_err(), "Undefined variable: nope");
+
+        let ir = meta_command(&mut state, ":ir f").unwrap();
+        assert!(ir.starts_with("function %f("), "{}", ir);
+        assert!(meta_command(&mut state, ":ir g").is_err());
+
+        assert_eq!(meta_command(&mut state, ":funcs").unwrap(), "fn f(x: int, ref n: int) -> int");
+        assert_eq!(meta_command(&mut state, ":vars").unwrap(), "total: float\nnames: list<str>");
+        assert!(eval_input(&mut state, "let broken: int = nope;").is_err());
+        eval_input(&mut state, "let count = 3;").unwrap();
+        assert_eq!(meta_command(&mut state, ":vars").unwrap(), "total: float\nnames: list<str>\ncount: int");
+        assert!(meta_command(&mut state, ":nope").unwrap_err().starts_with("unknown command ':nope'"));
+    }
+
+    #[test]
+    fn test_repl_lambdas() {
+        // 多行的 `fn(...) {` 在 `}` 处结束；之后输入的匿名函数不与之前的重名
+        let script = "let double = fn(x: int) -> int {\n\
+                          return x * 2;\n\
+                      }\n\
+                      let inc = fn(x: int) -> int { return x + 1; };\n\
+                      return double(inc(20));\n";
+        let mut out = Vec::new();
+        repl_session(Box::new(io::Cursor::new(script)), &mut out);
+        let out = String::from_utf8(out).unwrap();
+        let results: Vec<&str> = out.split(">>> ").map(str::trim).filter(|s| !s.is_empty()).collect();
+        assert_eq!(results, ["... ... Variable declared.", "Variable declared.", "42"]);
+    }
+
+    #[test]
+    fn test_repl_triple_quoted_string() {
+        // 没有结束的 """ 继续读下一行，内容按原样（包括缩进）交给解析器去缩进
+        let script = "let sql: str = \"\"\"\n\
+                      \x20   SELECT *\n\
+                      \x20     FROM t {\n\
+                      \x20   \"\"\";\n\
+                      return sql == \"SELECT *\\n  FROM t {\";\n";
+        let mut out = Vec::new();
+        repl_session(Box::new(io::Cursor::new(script)), &mut out);
+        let out = String::from_utf8(out).unwrap();
+        let results: Vec<&str> = out.split(">>> ").map(str::trim).filter(|s| !s.is_empty()).collect();
+        assert_eq!(results, ["... ... ... Variable declared.", "1"]);
+    }
+
+    #[test]
+    fn test_repl_input_reads_from_session() {
+        // 两次 input() 取走紧跟在调用后面的两行，之后的行仍按 REPL 命令处理
+        let script = "fn ask() -> str {\n\
+                          let a: str = input(\"name? \");\n\
+                          let b: str = input();\n\
+                          return a + \",\" + b;\n\
+                      }\n\
+                      let got: str = ask();\n\
+                      alpha\n\
+                      beta\n\
+                      return len(got);\n\
+                      return got == \"alpha,beta\";\n";
+        let mut out = Vec::new();
+        repl_session(Box::new(io::Cursor::new(script)), &mut out);
+        let out = String::from_utf8(out).unwrap();
+        let results: Vec<&str> = out.split(">>> ").map(str::trim).filter(|s| !s.is_empty()).collect();
+        assert_eq!(results, ["... ... ... ... Function defined.", "Variable declared.", "10", "1"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_library_links_with_c() {
+        let runtime_lib = runtime_lib();
+        let tests = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../tests/c_library");
+        let dir = std::env::temp_dir().join(format!("bolide_c_library_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let source_path = tests.join("calc.bl");
+        let source = fs::read_to_string(&source_path).unwrap();
+        let ast = parse_source(&source).unwrap();
+        let lib = dir.join("libcalc.a");
+        build_library(&source_path, &source, &ast, None, &lib, true, CodegenFlags::default()).unwrap();
+        assert!(fs::read_to_string(dir.join("calc.h")).unwrap().contains("int64_t fib(int64_t n);"));
+
+        let prog = dir.join("calc_test");
+        let status = Command::new("cc")
+            .arg(tests.join("main.c"))
+            .arg("-I").arg(&dir)
+            .arg(&lib)
+            .arg(&runtime_lib)
+            .args(["-lm", "-lpthread", "-ldl", "-o"])
+            .arg(&prog)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let output = Command::new(&prog).output().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "add: 42\nfib: 12586269025\nhypot2: 25\nis_even: 1 0\ngreeting length: 8\nhello, C\nname length: 1\nwords: 3\ntotal: 42\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_compiled_program_shares_globals() {
+        let source = "let counter: int = 0;\nlet log = \"\";\n\nfn inc() {\n    counter = counter + 1;\n    log = log + str(counter);\n}\n\ninc();\ninc();\ninc();\nprint(counter);\nprint(log);\n";
+        assert_eq!(run_aot(source), "3\n123\n");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_compiled_program_extern_c_types() {
+        let source = "extern \"libc.so.6\" {\n    fn strlen(s: cstr) -> u64;\n    fn strchr(s: cstr, c: i32) -> cstr;\n    fn strtoul(s: cstr, end: ptr, base: i32) -> u32;\n}\n\
+                      extern \"libm.so.6\" {\n    fn sqrtf(x: f32) -> f32;\n}\n\
+                      let key: str = \"key=value\";\nprint(strlen(key));\nprint(strchr(key, 61));\nprint(strchr(key, 122));\n\
+                      print(strtoul(\"4294967295\", 0, 10));\nprint(sqrtf(2.25));\nprint(sqrtf(16));\n";
+        assert_eq!(run_aot(source), "9\n=value\n\n4294967295\n1.5\n4\n");
+        assert_eq!(unix_lib_arg("libc.so.6"), "-l:libc.so.6");
+        assert_eq!(unix_lib_arg("libfoo.so"), "-lfoo");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_compiled_program_waits_for_threads() {
+        // tests/test_thread_outlives_main.bl：main 返回后等分离的线程打印完再退出
+        let source = include_str!("../../../tests/test_thread_outlives_main.bl");
+        let result = run_aot_output(source, CodegenFlags::default());
+        assert_eq!(String::from_utf8_lossy(&result.stdout), "1\n42\n");
+        assert!(String::from_utf8_lossy(&result.stderr).contains("1 thread(s) still running after main returned"));
+        assert!(result.status.success());
+    }
+
+    #[test]
+    fn test_cross_compile_writes_object() {
+        let dir = std::env::temp_dir().join(format!("bolide_cross_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("hello.bl");
+        fs::write(&path, "print(\"hi\");\n").unwrap();
+        let triple = if toolchain::host_triple().starts_with("aarch64-unknown-linux") {
+            "x86_64-unknown-linux-gnu"
+        } else {
+            "aarch64-unknown-linux-gnu"
+        };
+        compile_file(&path, Some(dir.join("hello")), Some(triple), "exe", false, CodegenFlags::default()).unwrap();
+        // 不调用本机链接器：只有目标文件
+        assert_eq!(&fs::read(dir.join("hello.o")).unwrap()[..4], b"\x7fELF");
+        assert!(!dir.join("hello").exists());
+
+        let libs = ["libfoo.so".to_string()];
+        assert_eq!(
+            link_command("aarch64-apple-darwin", Path::new("a.o"), Path::new("a"), "libbolide_runtime.a", &libs),
+            "clang --target=aarch64-apple-darwin -o a a.o libbolide_runtime.a -lm -lpthread -ldl -lfoo"
+        );
+        let command = link_command("x86_64-pc-windows-msvc", Path::new("a.obj"), Path::new("a.exe"), "bolide_runtime.lib", &["user32.dll".to_string()]);
+        assert!(command.starts_with("lld-link /ENTRY:main /SUBSYSTEM:CONSOLE /OUT:a.exe a.obj bolide_runtime.lib kernel32.lib"), "{}", command);
+        assert!(command.ends_with("legacy_stdio_definitions.lib user32.lib"), "{}", command);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_compiled_program_float_list() {
+        let source = "let xs: list<float> = [2.5, -0.25];\nxs.push(1);\nxs[0] = 4.5;\nxs.sort();\nlet total: float = 0.0;\nfor x in xs {\n    total = total + x;\n}\nprint(xs);\nprint(xs[2]);\nprint(total);\n";
+        assert_eq!(run_aot(source), "[-0.25, 1, 4.5]\n4.5\n5.25\n");
+
+        // tests/test_float_list.bl：first / pop / contains / index_of、空的 list<float> 和解构循环
+        let source = include_str!("../../../tests/test_float_list.bl");
+        assert_eq!(run_aot(source), "[2.5, -0.25, 1.125, 3.75, 2]\n1.125\n[0.5, 9.75, 1.125, 3.75, 2]\n\
+            [0.5, 1.125, 2, 3.75, 9.75]\n3.425\n0.5\n9.75\n1\n3\n[0, 0.5, 1, 1.5]\n4\n4\n");
+    }
+
+    #[test]
+    fn test_compiled_program_user_main() {
+        // 用户定义的 main 函数和进程入口 main 不冲突
+        // 字符串统计按 --no-opt 检查，str(<字面量>) 不折叠成驻留的字面量
+        let programs = [
+            ("hello", include_str!("../../../tests/hello.bl"), "42\n"),
+            ("test_channel_batch", include_str!("../../../tests/test_channel_batch.bl"),
+                "499500\n1000\n[1, 2]\n[3, 4, 5]\n0\n[6, 7]\n[]\n[\"1\", \"2\", \"3\"]\n[\"1\", \"2\", \"3\"]\n\
+                [String Stats] alloc: 4, free: 4, leak: 0\n"),
+        ];
+        for (name, source, expected) in programs {
+            assert_eq!(run_aot_with(source, CodegenFlags { no_opt: true, ..Default::default() }), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_compiled_program_pool_tasks() {
+        // 带参数的 spawn 经 trampoline 提交到线程池，join 取回各自类型的结果
+        let programs = [
+            ("test_pool_nested", include_str!("../../../tests/test_pool_nested.bl"), "3036989\ntrue\n0\n"),
+            ("test_pool_return", include_str!("../../../tests/test_pool_return.bl"), "1\n2\n2\n10\n11\n12\n3\n"),
+        ];
+        for (name, source, expected) in programs {
+            assert_eq!(run_aot(source), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_compiled_program_bind() {
+        // bind 的结果通过 func 变量调用、传给函数和 map；绑定 str 参数的目标经借用适配函数调用
+        let programs = [
+            ("test_bind", include_str!("../../../tests/test_bind.bl"), "15\n42\n2\nHello, Bolide\nHello, world\n123\n129\n456\n"),
+            ("test_map_filter_reduce", include_str!("../../../tests/test_map_filter_reduce.bl"),
+                "[2, 4, 6]\n[2, 4, 6]\n6\n[\"#1\", \"#2\", \"#3\"]\n>#1#2#3\n[0.5, 1, 1.5]\n[]\n42\n[11, 12, 13]\n12\n"),
+        ];
+        for (name, source, expected) in programs {
+            assert_eq!(run_aot(source), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_compiled_program_list_callbacks() {
+        // 列表方法的回调按借用方式接收元素；str、对象参数的回调经借用适配函数调用
+        // test_sort_by_key 最后对空列表调用 min_by，以状态码 1 退出
+        // 字符串统计按 --no-opt 检查，str(<字面量>) 不折叠成驻留的字面量
+        let programs = [
+            ("test_list_sort_by", include_str!("../../../tests/test_list_sort_by.bl"),
+                "[\"a\", \"b\", \"c\"]\n[1.25, 3.5, NaN]\n[9, 4, 3, 1, -1, -5]\n[\"fig\", \"yam\", \"kiwi\", \"pear\", \"plum\", \"apple\"]\n[false, true, true]\n", 0),
+            ("test_sort_by_key", include_str!("../../../tests/test_sort_by_key.bl"),
+                "[21, 11, 31, 42, 2, 13, 3]\n[-3, 0, 5, 8]\nann bo bo mia \n25\nbo bo mia ann \n19\nann\n21\n13\n[\"apple\", \"fig\", \"pear\", \"plum\"]\npear\n", 1),
+            // group_by 的字符串键由驻留表持有，统计里 leak 为 2
+            ("test_list_group", include_str!("../../../tests/test_list_group.bl"),
+                "[1, 2, 1, 3, 2]\n[1, 2, 3]\n[\"42\", \"7\", \"105\", \"3\", \"10\"]\n3\n[\"42\", \"42\", \"42\", \"10\"]\n[\"7\", \"7\", \"3\"]\n[\"105\"]\n[\"42\", \"42\", \"42\", \"10\"]\n[\"7\", \"105\", \"7\", \"3\"]\n[3, 5]\n[-2, -7, 0]\n[\"42\", \"7\", \"42\", \"105\", \"7\", \"3\", \"10\"]\n[String Stats] alloc: 50, free: 48, leak: 2\n", 0),
+        ];
+        for (name, source, expected, status) in programs {
+            let result = run_aot_output(source, CodegenFlags { no_opt: true, ..Default::default() });
+            assert_eq!(String::from_utf8_lossy(&result.stdout), expected, "{}", name);
+            assert_eq!(result.status.code(), Some(status), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_compiled_program_default_dict() {
+        // d[k].push(x) 修改字典里存放的列表；字符串统计检查嵌套容器和列表字面量都已释放
+        // 字符串统计按 --no-opt 检查，str(<字面量>) 不折叠成驻留的字面量
+        let source = include_str!("../../../tests/test_dict_container_values.bl");
+        assert_eq!(run_aot_with(source, CodegenFlags { no_opt: true, ..Default::default() }), "7\n1429\n1428\n3\n10\n[\"10\", \"11\", \"12\"]\n3\n[\"13\"]\n2\n[\"100\", \"105\", \"17\"]\n[\"21\", \"2\"]\n2\n2\n111\n220\n0\n0\n[String Stats] alloc: 24, free: 24, leak: 0\n");
+    }
+
+    #[test]
+    fn test_compiled_program_list_aggregate() {
+        // 同名的用户函数 max(a, b) 不影响对一个列表实参的 max(xs)
+        let source = include_str!("../../../tests/test_list_aggregate.bl");
+        assert_eq!(run_aot(source), "14\n1\n5\n2.8\n42\n42\n42\n42\n0\n3.75\n-2\n4.75\n1.25\n13000000000000\n-4\n10\n6.6\n2.2\n-9223372036854775808\n9\n5\n");
+    }
+
+    #[test]
+    fn test_compiled_program_compound_assign() {
+        // 列表字段、嵌套下标的复合赋值写回对象和外层列表里存放的那一份，下标表达式只求值一次
+        let source = include_str!("../../../tests/test_compound_assign.bl");
+        let stdout = run_aot(source);
+        let (printed, _stats) = stdout.split_once("[String Stats]").unwrap();
+        assert_eq!(printed, "3\n5\n101\n100\na012-end\n[1, 2, 3, 4, 5]\n[3, 3]\n[\"a\", \"b\", \"c\"]\n10\n0.25\nxy\n[7, 8]\n[10, 25, 60]\n1\n[\"a!\", \"b\"]\n2\n3\n1\n[1, 2, 9]\n[0, 4]\n4\n");
+    }
+
+    #[test]
+    fn test_compiled_program_empty_literals() {
+        // 空的和嵌套的列表、字典字面量按声明、参数、返回类型创建；bigint 元素随列表释放
+        let source = include_str!("../../../tests/test_empty_literal.bl");
+        assert_eq!(run_aot(source), "[\"alice\", \"bob\"]\n0\n1\n1\n[\"y\"]\n[\"x\"]\n[1, 2.5]\n[BigInt Stats] alloc: 0, free: 0, leak: 0\n[0, 1000000000000, 2000000000000]\n[]\n[BigInt Stats] alloc: 9, free: 9, leak: 0\n");
+    }
+
+    #[test]
+    fn test_compiled_program_channel_rc() {
+        // 接收到的值带上通道的元素类型：列表按列表打印，临时值在语句结束时释放
+        let source = include_str!("../../../tests/test_channel_rc.bl");
+        let output = run_aot(source);
+        let (printed, stats) = output.split_once("[String Stats]").expect("string stats line");
+        assert_eq!(printed, "1000\n499500\n999\n[0, 0]\n[1, 10]\n3\n[2, 20]\n");
+        assert!(stats.ends_with("leak: 2\n"), "{}", stats);
+    }
+
+    #[test]
+    fn test_compiled_program_lambdas() {
+        // 存进变量和列表的匿名函数、test_callback 回调、spawn 匿名函数变量
+        let source = include_str!("../../../tests/test_lambda.bl");
+        assert_eq!(run_aot(source), "49\n[2, 3, 4]\n[2, 3]\n24\n7\n25\n-5\n42\n[101, 102]\n");
+    }
+
+    #[test]
+    fn test_compiled_program_str_callbacks() {
+        // 参数是 str 的回调经借用适配函数调用，元素和累加值不被回调释放
+        let source = "fn label(x: int) -> str {\n    return \"#\" + str(x);\n}\nfn concat(acc: str, s: str) -> str {\n    return acc + s;\n}\n\
+                      fn long(s: str) -> bool {\n    return len(s) > 2;\n}\nfn size(s: str) -> int {\n    return len(s);\n}\n\
+                      let labels: list<str> = map(label, [1, 20, 300]);\nprint(labels);\nprint(reduce(concat, labels, \">\"));\n\
+                      print(filter(long, labels));\nprint(map(size, labels));\nprint(labels);\n";
+        assert_eq!(run_aot(source), "[\"#1\", \"#20\", \"#300\"]\n>#1#20#300\n[\"#20\", \"#300\"]\n[2, 3, 4]\n[\"#1\", \"#20\", \"#300\"]\n");
+    }
+
+    #[test]
+    fn test_compiled_program_fstring() {
+        let source = "class P {\n    x: int;\n}\nlet n: int = 41;\nlet b: bigint = 12B;\nlet xs: list<int> = [1, 2];\nlet d: dict<int, int> = {1: 2};\nlet p: P = P(5);\n\
+                      print(f\"n={n + 1} {2.5} {true} {b * b} {19.99D}\");\nprint(f\"{xs} {d} {p} {{x}}\");\nlet s: str = f\"{n}-{n}\";\nprint(s);\n";
+        assert_eq!(run_aot(source), "n=42 2.5 true 144 19.99\n[1, 2] {1: 2} P { x: 5 } {x}\n41-41\n");
+    }
+
+    #[test]
+    fn test_compiled_program_shares_containers() {
+        // 两个后端按同一套规则绑定容器：示例文件打印的长度和它开头注释里 `bolide run` 的输出一致；
+        // 同一个程序 JIT 返回、编译后打印同一个结果
+        let sample = include_str!("../../../tests/test_share_containers.bl");
+        let source = "class Box {\n    items: list<int>;\n}\nfn add(xs: list<int>) {\n    xs.push(0);\n}\nfn give(b: Box) -> list<int> {\n    return b.items;\n}\n\
+                      fn run() -> int {\n    let a: list<int> = [1];\n    let b = a;\n    b.push(2);\n    let c: list<int> = [];\n    c = a;\n    c.push(3);\n\
+                      let box = Box(a);\n    box.items.push(4);\n    give(box).push(5);\n    let t: (list<int>, int) = (a, 1);\n    let u, n = t;\n    u.push(6);\n\
+                      add(a);\n    let outer: list<list<int>> = [a];\n    outer[0].push(7);\n    let d: list<int> = a.copy();\n    d.push(8);\n    return len(a);\n}\n";
+        for (share, lens, expected) in [(false, "3 3 3 3 3 3 3 4 1 4 5", 3), (true, "4 5 6 7 8 9 10 11 2 11 12", 8)] {
+            let flags = CodegenFlags { share_containers: share, ..CodegenFlags::default() };
+            assert_eq!(run_aot_with(sample, flags).split_whitespace().collect::<Vec<_>>().join(" "), lens);
+            assert_eq!(run_aot_with(&format!("{}print(run());\n", source), flags), format!("{}\n", expected));
+            let program = bolide_parser::parse_source(&format!("{}return run();\n", source)).unwrap();
+            let mut compiler = JitCompiler::new();
+            compiler.set_share_containers(share);
+            let main_fn: fn() -> i64 = unsafe { std::mem::transmute(compiler.compile(&program).unwrap()) };
+            assert_eq!(main_fn(), expected, "share_containers = {}", share);
+            compiler.release_globals();
+        }
+    }
+
+    #[test]
+    fn test_compiled_program_opt_levels() {
+        // -O0 和 -O1 的输出相同：常量表达式、常量条件的分支和 return 之后的语句
+        let source = "fn f(n: int) -> int {\n    if 1 == 1 {\n        return n * (2 + 1);\n    }\n    print(99);\n    return 0;\n}\n\
+                      if 2 > 3 {\n    print(1);\n} elif true {\n    let z: int = 3 * 4;\n    print(z);\n} else {\n    print(5);\n}\n\
+                      print(f(14));\nprint(str(2 * 3) + \"x\");\nprint(-(2 - 10) % 3);\nprint(1.5 * 2.0 >= 3.0);\n";
+        for no_opt in [true, false] {
+            assert_eq!(run_aot_with(source, CodegenFlags { no_opt, ..CodegenFlags::default() }), "12\n42\n6x\n2\ntrue\n");
+        }
+    }
+
+    #[test]
+    fn test_compiled_program_printr_flushes_before_error() {
+        // 输出到管道时先进缓冲区；运行时错误退出前写出已打印的内容
+        let source = "let xs: list<int> = [1, 2];
+printr(1);
+printr(\" \");
+printr(true);
+printr(xs);
+print(\"\");
+\
+                      for i in range(3) {\n    printr(i);\n}\nflush();\nlet empty: list<int> = [];\nprintr(min(empty));\n";
+        let result = run_aot_output(source, CodegenFlags::default());
+        assert_eq!(String::from_utf8_lossy(&result.stdout), "1 true[1, 2]\n012");
+        assert_eq!(result.status.code(), Some(1));
+    }
+
+    #[test]
+    fn test_compiled_program_for_over_channel() {
+        let source = "fn total(n: int) -> int {\n    let ch: channel<int> = channel(n);\n    for i in range(n) {\n        ch <- i;\n    }\n    ch.close();\n\
+                      let sum: int = 0;\n    for v in ch {\n        sum = sum + v;\n    }\n    return sum;\n}\nprint(total(100));\n";
+        assert_eq!(run_aot(source), "4950\n");
+    }
+
+    #[test]
+    fn test_compiled_program_file_io() {
+        let dir = std::env::temp_dir().join(format!("bolide_file_io_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let data = dir.join("data.txt");
+        let source = format!("let path: str = \"{}\";\nprint(file_exists(path));\nwrite_file(path, \"x\\n\");\nappend_file(path, \"yz\\n\");\n\
+                              let lines: list<str> = read_lines(path);\nprint(len(lines));\nprint(lines[1]);\nprint(len(read_file(path).split(\"\\n\")));\n\
+                              print(len(read_lines(path + \".missing\")));\nprint(error());\nprint(file_exists(path));\n", data.display());
+        assert_eq!(run_aot(&source), "false\n2\nyz\n3\n0\n7\ntrue\n");
+        assert_eq!(fs::read_to_string(&data).unwrap(), "x\nyz\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_compiled_program_durations() {
+        let source = "fn wait(dl: deadline) -> int {\n    let ch: channel<int> = channel();\n    let t: int = timer_start();\n\
+                      select {\n        v <- ch => { t = 0; }\n        timeout(dl) => {}\n    }\n    return timer_elapsed_ns(t);\n}\n\
+                      let d: duration = 1.5s + 250ms;\nprint(d);\nprint(d * 2 - 1h);\nprint(d / 1ms);\nprint(str(30us) + \" \" + str(d / 4));\n\
+                      print(wait(deadline(50ms)) >= 40000000);\n";
+        assert_eq!(run_aot(source), "1.75s\n-3596.5s\n1750\n30us 437.5ms\ntrue\n");
+    }
+
+    #[test]
+    fn test_compiled_program_method_chaining() {
+        // 返回 self 的方法可以连着调用；链上的每个中间结果释放一次，不多不少
+        let source = "class Builder {\n    x: int;\n    y: int;\n    name: str;\n\
+                      fn set_x(v: int) -> Builder {\n        self.x = v;\n        return self;\n    }\n\
+                      fn set_y(v: int) -> Builder {\n        self.y = v;\n        return self;\n    }\n\
+                      fn set_name(n: str) -> Builder {\n        self.name = n;\n        return self;\n    }\n}\n\
+                      fn run() {\n    let b: Builder = Builder(0, 0, \"\");\n    b.set_x(1).set_y(2).set_name(\"pt\");\n\
+                      print(b.x * 10 + b.y);\n    let c: Builder = b.set_x(3).set_y(4);\n    print(c.x * 10 + c.y);\n\
+                      print(Builder(0, 0, \"\").set_x(5).set_name(\"q\").x);\n    print(b.name);\n}\n\
+                      object_debug_enable(true);\nrun();\nprint(object_live_count());\n";
+        assert_eq!(run_aot(source), "12\n34\n5\npt\n0\n");
+    }
+
+    #[test]
+    fn test_compiled_program_select_from() {
+        // 分支收到的索引是 channel 在列表中的位置；都关闭后执行 closed 分支
+        let source = "fn run() {\n    let chans: list<channel<int>> = [];\n    for i in range(3) {\n        let c: channel<int> = channel();\n        chans.push(c);\n    }\n\
+                      let last: channel<int> = chans[2];\n    last <- 7;\n    let first: channel<int> = chans[0];\n    first <- 5;\n\
+                      for round in range(3) {\n        select from chans {\n            v, i => { print(i * 10 + v); }\n            timeout(10ms) => { print(-1); }\n        }\n    }\n\
+                      for c in chans {\n        c.close();\n    }\n    let rounds: int = 0;\n    let open: int = 1;\n\
+                      while open == 1 {\n        rounds = rounds + 1;\n        select from chans {\n            v, i => { print(v); }\n            closed => { open = 0; }\n        }\n    }\n    print(rounds);\n}\nrun();\n";
+        assert_eq!(run_aot(source), "5\n27\n-1\n1\n");
+
+        // tests/test_select_from.bl：spawn 的 worker 发送、str 消息由分支释放
+        let source = include_str!("../../../tests/test_select_from.bl");
+        assert_eq!(run_aot(source), "0: 0\n1: 100\n2: 200\n3: 300\n4: 400\n1000\ntimeout\nclosed\n9\n\
+            [String Stats] alloc: 19, free: 16, leak: 3\n");
+    }
+
+    #[test]
+    fn test_compiled_program_decimal_rounding() {
+        let source = "let third: decimal = decimal(\"1\") / decimal(\"3\");\nprint(round(third, 10));\nprint(decimal(\"7.5\") % decimal(\"2\"));\n\
+                      let prev: int = set_div_precision(4);\nprint(2d / 3d);\nset_div_precision(prev);\n\
+                      print(floor(-7.5d));\nprint(ceil(2.25));\nprint(round(2.675, 2));\n";
+        assert_eq!(run_aot(source), "0.3333333333\n1.5\n0.6667\n-8\n3\n2.68\n");
+
+        // d.round(places, mode) 方法：按次指定的模式和线程模式
+        let source = include_str!("../../../tests/test_decimal_round.bl");
+        assert_eq!(run_aot(source), "2.34\n-2.34\n2.35\n-2.35\n2.34\n-2.34\n2\n2.345\n2.35\n3\n0.6666666666666666666666666666\n2\n0.6666666666666666666666666667\n0.38\n");
+    }
+
+    #[test]
+    fn test_compiled_program_threadlocal() {
+        let source = "threadlocal let hits: int = 10;\nthreadlocal let log: str = str(0);\nthreadlocal let scale = 1.5;\n\
+                      fn visit(n: int) {\n    hits = hits + n;\n    log = log + \",\" + str(n);\n}\n\
+                      visit(1);\nvisit(2);\nprint(hits);\nprint(log);\nprint(scale * 2.0);\n";
+        assert_eq!(run_aot(source), "13\n0,1,2\n3\n");
+    }
+
+    #[test]
+    fn test_compiled_program_await_scope_errors() {
+        // 没有被 await 的子任务的错误在作用域结束时一起报告
+        let source = "async fn good() -> int {\n    return parse_int(\"1\");\n}\nasync fn bad() -> int {\n    return parse_int(\"x\");\n}\n\
+                      await scope fail_fast {\n    let a = good();\n}\nprint(\"ok\");\n\
+                      await scope {\n    let b = bad();\n    let c = good();\n    let d = bad();\n}\nprint(\"unreachable\");\n";
+        let result = run_aot_output(source, CodegenFlags::default());
+        assert_eq!(String::from_utf8_lossy(&result.stdout), "ok\n");
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        assert!(stderr.contains("2 async tasks failed in await scope:\n  error in async task 'bad': "), "{}", stderr);
+        assert_eq!(result.status.code(), Some(1));
+    }
+
+    #[test]
+    fn test_compiled_program_frees_objects() {
+        // 函数没有 return 时也释放局部变量；嵌套对象和它的字符串字段随外层对象释放
+        let source = "class Child {\n    label: str;\n    parent: weak Parent;\n}\nclass Parent {\n    child: Child;\n}\n\
+                      fn build(n: int) {\n    let c: Child = Child(str(n), none);\n    let p: Parent = Parent(c);\n    c.parent = p;\n    let q: Parent = Parent(Child(str(n + 1), none));\n}\n\
+                      object_debug_enable(true);\nbuild(1);\nprint(object_live_count());\nstring_debug_stats();\n";
+        assert_eq!(run_aot(source), "0\n[String Stats] alloc: 2, free: 2, leak: 0\n");
+    }
+
+    #[test]
+    fn test_run_tests() {
+        let dir = std::env::temp_dir().join(format!("bolide_test_blocks_{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        let passing = "fn double(x: int) -> int {\n    return x * 2;\n}\n\
+                       test \"double\" {\n    assert_eq(double(4), 8);\n}\n";
+        fs::write(dir.join("nested/passing.bl"), passing).unwrap();
+        fs::write(dir.join("no_tests.bl"), "print(1);\n").unwrap();
+        assert!(run_tests(&dir).unwrap());
+
+        // 一个通过、一个失败：整体失败；编译错误也算失败
+        let failing = "let base: int = 1;\n\
+                       test \"ok\" {\n    assert(base == 1);\n}\n\
+                       test \"wrong\" {\n    assert_eq(base, 2);\n}\n\
+                       test \"broken\" {\n    assert_eq(base, \"1\");\n}\n";
+        fs::write(dir.join("failing.bl"), failing).unwrap();
+        assert!(!run_tests(&dir.join("failing.bl")).unwrap());
+        assert!(!run_tests(&dir).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_incremental_reuses_modules() {
+        runtime_lib();
+        let dir = std::env::temp_dir().join(format!("bolide_incremental_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let util = dir.join("util.bl");
+        fs::write(&util, "fn triple(x: int) -> int {\n    return x * 3;\n}\n").unwrap();
+        let main_path = dir.join("main.bl");
+        let output = dir.join("app");
+        let build = |main_src: &str| {
+            let source = format!("import \"{}\";\n{}", util.display(), main_src);
+            fs::write(&main_path, &source).unwrap();
+            let ast = parse_source(&source).unwrap();
+            let modules = build_incremental(&main_path, &source, &ast, &output, false, CodegenFlags::default()).unwrap();
+            let stdout = Command::new(&output).output().unwrap().stdout;
+            (modules.iter().map(|m| m.rebuilt).collect::<Vec<_>>(), String::from_utf8_lossy(&stdout).into_owned())
+        };
+
+        assert_eq!(build("print(util.triple(14));\n"), (vec![true], "42\n".to_string()));
+        let first = fs::read(&output).unwrap();
+        // 只改主程序：模块目标文件直接复用
+        assert_eq!(build("print(util.triple(5));\n"), (vec![false], "15\n".to_string()));
+        // 清空构建目录后重新编译，结果逐字节相同
+        fs::remove_dir_all(dir.join(".bolide-build")).unwrap();
+        assert_eq!(build("print(util.triple(14));\n"), (vec![true], "42\n".to_string()));
+        assert_eq!(fs::read(&output).unwrap(), first);
+        // 模块改变后重新编译，旧的目标文件被删除
+        fs::write(&util, "fn triple(x: int) -> int {\n    return x + x + x + 1;\n}\n").unwrap();
+        assert_eq!(build("print(util.triple(14));\n"), (vec![true], "43\n".to_string()));
+        let objects = fs::read_dir(dir.join(".bolide-build")).unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().starts_with("util-"))
+            .count();
+        assert_eq!(objects, 1);
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,355 @@
+//! `bolide toolchain`：安装预编译的运行时静态库
+//!
+//! AOT 编译出的目标文件要和同一目标平台的运行时库（`libbolide_runtime.a`，MSVC 目标为
+//! `bolide_runtime.lib`）链接。只装了 CLI 的机器和交叉编译都没法用 Rust 工具链构建它，
+//! `bolide toolchain install <triple>` 从发布地址下载对应的库，放到用户数据目录的
+//! `toolchains/<triple>/` 下，`find_runtime_lib` 按编译目标在那里查找。
+//!
+//! - 下载地址：`<base>/<清单中的文件名>`，base 默认是本版本的 GitHub release，可用 `--base-url`
+//!   或 `BOLIDE_TOOLCHAIN_URL` 覆盖；支持 `http(s)://`（调用 curl）和 `file://`。
+//! - 校验：构建时把 `toolchain-manifest.txt` 嵌入 CLI，每行 `<triple> <sha256> <文件名>`，
+//!   哈希不符的下载直接丢弃。自建镜像可用 `--manifest` 指定清单文件。
+//! - 版本：库里嵌有 `bolide-runtime-version:<版本>` 标记（`bolide_runtime::BOLIDE_RUNTIME_VERSION`），
+//!   与 CLI 版本不同的库不安装，也不用于链接。
+//!
+//! 只有 install 访问网络，编译和运行始终离线。
+//!
+//! 环境变量：`BOLIDE_TOOLCHAIN_DIR` 覆盖安装目录，`BOLIDE_TOOLCHAIN_URL` 覆盖下载地址。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use bolide_parser::CfgTarget;
+use sha2::{Digest, Sha256};
+
+/// 构建时嵌入的清单（开发构建中只有注释）
+pub const EMBEDDED_MANIFEST: &str = include_str!("../toolchain-manifest.txt");
+
+/// 清单中的一项
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub triple: String,
+    /// 小写十六进制
+    pub sha256: String,
+    /// 下载地址下的文件名
+    pub file: String,
+}
+
+/// 解析清单：每行 `<triple> <sha256> <文件名>`，空行和 `#` 开头的行忽略
+pub fn parse_manifest(text: &str) -> Result<Vec<ManifestEntry>, String> {
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [triple, sha256, file] = fields[..] else {
+            return Err(format!("Toolchain manifest line {}: expected `<triple> <sha256> <file>`", i + 1));
+        };
+        if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Toolchain manifest line {}: invalid sha256 '{}'", i + 1, sha256));
+        }
+        entries.push(ManifestEntry {
+            triple: triple.to_string(),
+            sha256: sha256.to_ascii_lowercase(),
+            file: file.to_string(),
+        });
+    }
+    Ok(entries)
+}
+
+/// 默认下载地址：本版本的 GitHub release
+pub fn default_base_url() -> String {
+    std::env::var("BOLIDE_TOOLCHAIN_URL").unwrap_or_else(|_| {
+        format!("https://github.com/streetartist/bolide/releases/download/v{}", env!("CARGO_PKG_VERSION"))
+    })
+}
+
+/// 本机的目标三元组
+pub fn host_triple() -> String {
+    target_lexicon::Triple::host().to_string()
+}
+
+/// 目标平台的运行时库文件名
+pub fn runtime_lib_name(triple: &str) -> &'static str {
+    if triple.ends_with("windows-msvc") {
+        "bolide_runtime.lib"
+    } else {
+        "libbolide_runtime.a"
+    }
+}
+
+/// 库文件中嵌入的运行时版本
+pub fn archive_version(bytes: &[u8]) -> Option<String> {
+    let prefix = bolide_runtime::VERSION_TAG_PREFIX.as_bytes();
+    let mut rest = bytes;
+    while let Some(pos) = rest.windows(prefix.len()).position(|w| w == prefix) {
+        let tail = &rest[pos + prefix.len()..];
+        // 标记以 NUL 结尾；前缀的其他出现（如单独的字符串常量）跳过
+        if let Some(end) = tail.iter().take(64).position(|&b| b == 0) {
+            let version = &tail[..end];
+            if !version.is_empty() && version.iter().all(|b| b.is_ascii_alphanumeric() || b"._-+".contains(b)) {
+                return Some(String::from_utf8_lossy(version).into_owned());
+            }
+        }
+        rest = tail;
+    }
+    None
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 安装目录：`BOLIDE_TOOLCHAIN_DIR`，否则为用户数据目录下的 bolide/toolchains
+fn default_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("BOLIDE_TOOLCHAIN_DIR") {
+        return PathBuf::from(dir);
+    }
+    #[cfg(target_os = "windows")]
+    let base = std::env::var_os("LOCALAPPDATA").map(PathBuf::from);
+    #[cfg(not(target_os = "windows"))]
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")));
+    base.unwrap_or_else(std::env::temp_dir).join("bolide").join("toolchains")
+}
+
+/// 下载到 `dest`；整个 CLI 只有这里访问网络
+fn download(url: &str, dest: &Path) -> Result<(), String> {
+    if let Some(path) = url.strip_prefix("file://") {
+        // file:///C:/dir/lib.a -> C:/dir/lib.a
+        let path = match path.as_bytes() {
+            [b'/', _, b':', ..] => &path[1..],
+            _ => path,
+        };
+        fs::copy(path, dest).map_err(|e| format!("Failed to copy {}: {}", path, e))?;
+        return Ok(());
+    }
+    if !(url.starts_with("https://") || url.starts_with("http://")) {
+        return Err(format!("Unsupported toolchain URL '{}' (expected https://, http:// or file://)", url));
+    }
+    let status = Command::new("curl")
+        .args(["--fail", "--location", "--silent", "--show-error", "--output"])
+        .arg(dest)
+        .arg(url)
+        .status()
+        .map_err(|e| format!("Failed to run curl, which is needed to download toolchains: {}", e))?;
+    if !status.success() {
+        return Err(format!("Download failed: {}", url));
+    }
+    Ok(())
+}
+
+/// 运行时库的安装目录
+pub struct Toolchains {
+    dir: PathBuf,
+}
+
+impl Toolchains {
+    /// 按环境变量打开（目录不存在时不创建）
+    pub fn open() -> Self {
+        Self { dir: default_dir() }
+    }
+
+    #[cfg(test)]
+    pub fn at(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn lib_path(&self, triple: &str) -> PathBuf {
+        self.dir.join(triple).join(runtime_lib_name(triple))
+    }
+
+    /// 目标平台已安装的运行时库；库的版本与 CLI 不同时报错
+    pub fn find(&self, triple: &str) -> Result<Option<PathBuf>, String> {
+        let path = self.lib_path(triple);
+        let Ok(bytes) = fs::read(&path) else {
+            return Ok(None);
+        };
+        let version = archive_version(&bytes).unwrap_or_else(|| "unknown".to_string());
+        if version != env!("CARGO_PKG_VERSION") {
+            return Err(format!(
+                "Runtime library {} is version {} but this bolide is {}; update it with `bolide toolchain install {}`",
+                path.display(), version, env!("CARGO_PKG_VERSION"), triple
+            ));
+        }
+        Ok(Some(path))
+    }
+
+    /// 已安装的目标和库版本，按三元组排序
+    pub fn installed(&self) -> Vec<(String, Option<String>)> {
+        let Ok(read_dir) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut targets: Vec<(String, Option<String>)> = read_dir
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let triple = e.file_name().to_str()?.to_string();
+                let bytes = fs::read(self.lib_path(&triple)).ok()?;
+                Some((triple, archive_version(&bytes)))
+            })
+            .collect();
+        targets.sort();
+        targets
+    }
+
+    /// 下载目标平台的运行时库，校验哈希和版本后安装，返回安装路径
+    pub fn install(&self, triple: &str, manifest: &[ManifestEntry], base_url: &str) -> Result<PathBuf, String> {
+        CfgTarget::from_triple(triple)?;
+        let entry = manifest.iter().find(|e| e.triple == triple).ok_or_else(|| {
+            if manifest.is_empty() {
+                "This bolide build has no toolchain manifest; pass one with --manifest <file>".to_string()
+            } else {
+                let available: Vec<&str> = manifest.iter().map(|e| e.triple.as_str()).collect();
+                format!("No prebuilt runtime for '{}' (available: {})", triple, available.join(", "))
+            }
+        })?;
+
+        let target_dir = self.dir.join(triple);
+        fs::create_dir_all(&target_dir)
+            .map_err(|e| format!("Failed to create {}: {}", target_dir.display(), e))?;
+        // 先下载到临时文件，校验通过后再改名，失败时不留下半个或错误的库
+        let temp = target_dir.join(format!("{}.{}.download", runtime_lib_name(triple), std::process::id()));
+        let url = format!("{}/{}", base_url.trim_end_matches('/'), entry.file);
+        let verified = download(&url, &temp).and_then(|()| verify(&temp, entry));
+        if let Err(e) = verified {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+
+        let path = self.lib_path(triple);
+        fs::rename(&temp, &path).map_err(|e| format!("Failed to install {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+/// 检查下载的库与清单中的哈希以及 CLI 版本一致
+fn verify(path: &Path, entry: &ManifestEntry) -> Result<(), String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let actual = sha256_hex(&bytes);
+    if actual != entry.sha256 {
+        return Err(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            entry.file, entry.sha256, actual
+        ));
+    }
+    match archive_version(&bytes) {
+        Some(version) if version == env!("CARGO_PKG_VERSION") => Ok(()),
+        Some(version) => Err(format!(
+            "{} is runtime version {} but this bolide is {}",
+            entry.file, version, env!("CARGO_PKG_VERSION")
+        )),
+        None => Err(format!("{} is not a bolide runtime library (no version tag)", entry.file)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIPLE: &str = "aarch64-unknown-linux-gnu";
+
+    /// 含版本标记的假库文件
+    fn fake_archive(version: &str) -> Vec<u8> {
+        let mut bytes = b"!<arch>\n".to_vec();
+        bytes.extend_from_slice(bolide_runtime::VERSION_TAG_PREFIX.as_bytes());
+        bytes.extend_from_slice(b"?");
+        bytes.extend_from_slice(format!("{}{}\0", bolide_runtime::VERSION_TAG_PREFIX, version).as_bytes());
+        bytes
+    }
+
+    /// 临时目录：mirror/ 存放“发布”的库，installed/ 是安装目录
+    fn setup(name: &str, archive: &[u8]) -> (PathBuf, String, Toolchains) {
+        let root = std::env::temp_dir().join(format!("bolide-toolchain-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let mirror = root.join("mirror");
+        fs::create_dir_all(&mirror).unwrap();
+        fs::write(mirror.join("runtime.a"), archive).unwrap();
+        let base_url = format!("file://{}", mirror.display());
+        (root.clone(), base_url, Toolchains::at(root.join("installed")))
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let hash = "AB".repeat(32);
+        let text = format!("# comment\n\n{} {} runtime.a\n", TRIPLE, hash);
+        let entries = parse_manifest(&text).unwrap();
+        assert_eq!(entries, [ManifestEntry { triple: TRIPLE.to_string(), sha256: "ab".repeat(32), file: "runtime.a".to_string() }]);
+
+        assert!(parse_manifest(EMBEDDED_MANIFEST).is_ok());
+        let err = parse_manifest(&format!("{} {}\n", TRIPLE, hash)).unwrap_err();
+        assert!(err.contains("line 1"), "{}", err);
+        let err = parse_manifest(&format!("{} abc runtime.a\n", TRIPLE)).unwrap_err();
+        assert!(err.contains("invalid sha256"), "{}", err);
+    }
+
+    #[test]
+    fn test_archive_version() {
+        assert_eq!(archive_version(&fake_archive("1.2.3")).as_deref(), Some("1.2.3"));
+        assert_eq!(archive_version(b"no tag here"), None);
+        // 本机构建的运行时库带有当前版本
+        let version = bolide_runtime::BOLIDE_RUNTIME_VERSION.as_bytes();
+        assert_eq!(archive_version(version).as_deref(), Some(env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn test_install_and_find() {
+        let archive = fake_archive(env!("CARGO_PKG_VERSION"));
+        let (root, base_url, toolchains) = setup("ok", &archive);
+        let manifest = vec![ManifestEntry { triple: TRIPLE.to_string(), sha256: sha256_hex(&archive), file: "runtime.a".to_string() }];
+
+        assert_eq!(toolchains.find(TRIPLE).unwrap(), None);
+        let path = toolchains.install(TRIPLE, &manifest, &base_url).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), archive);
+        assert_eq!(toolchains.find(TRIPLE).unwrap(), Some(path));
+        assert_eq!(toolchains.installed(), [(TRIPLE.to_string(), Some(env!("CARGO_PKG_VERSION").to_string()))]);
+
+        // 清单里没有的目标
+        let err = toolchains.install("x86_64-apple-darwin", &manifest, &base_url).unwrap_err();
+        assert!(err.contains("available: aarch64-unknown-linux-gnu"), "{}", err);
+        let err = toolchains.install(TRIPLE, &[], &base_url).unwrap_err();
+        assert!(err.contains("--manifest"), "{}", err);
+
+        // 安装的库与 CLI 版本不同时，查找报错并给出更新命令
+        fs::write(toolchains.lib_path(TRIPLE), fake_archive("0.0.1")).unwrap();
+        let err = toolchains.find(TRIPLE).unwrap_err();
+        assert!(err.contains("bolide toolchain install aarch64-unknown-linux-gnu"), "{}", err);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_install_rejects_bad_downloads() {
+        let archive = fake_archive(env!("CARGO_PKG_VERSION"));
+        let (root, base_url, toolchains) = setup("bad", &archive);
+
+        // 哈希不符：不安装，不留下临时文件
+        let manifest = vec![ManifestEntry { triple: TRIPLE.to_string(), sha256: "0".repeat(64), file: "runtime.a".to_string() }];
+        let err = toolchains.install(TRIPLE, &manifest, &base_url).unwrap_err();
+        assert!(err.contains("Checksum mismatch for runtime.a"), "{}", err);
+        assert_eq!(toolchains.find(TRIPLE).unwrap(), None);
+        assert_eq!(fs::read_dir(toolchains.dir().join(TRIPLE)).unwrap().count(), 0);
+
+        // 哈希正确但版本不同
+        let old = fake_archive("0.0.1");
+        fs::write(root.join("mirror").join("old.a"), &old).unwrap();
+        let manifest = vec![ManifestEntry { triple: TRIPLE.to_string(), sha256: sha256_hex(&old), file: "old.a".to_string() }];
+        let err = toolchains.install(TRIPLE, &manifest, &base_url).unwrap_err();
+        assert!(err.contains("runtime version 0.0.1"), "{}", err);
+        assert_eq!(toolchains.find(TRIPLE).unwrap(), None);
+
+        // 下载不到、不支持的地址
+        let manifest = vec![ManifestEntry { triple: TRIPLE.to_string(), sha256: sha256_hex(&archive), file: "missing.a".to_string() }];
+        assert!(toolchains.install(TRIPLE, &manifest, &base_url).unwrap_err().contains("Failed to copy"));
+        assert!(toolchains.install(TRIPLE, &manifest, "ftp://example.com").unwrap_err().contains("Unsupported toolchain URL"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
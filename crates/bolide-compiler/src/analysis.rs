@@ -0,0 +1,256 @@
+//! `--analyze` 报告：不运行程序，只统计编译结果
+//!
+//! 每个函数记录调用图的出边、生成代码里每个 retain/release/clone 运行时函数的调用次数、
+//! RC 变量及其类型、被 owned 参数移走的变量、登记过的临时 RC 值个数、是否使用生命周期模式，
+//! `@pure` 调用被编译期求值、复用或提到循环外的次数，以及窥孔优化删掉的和还能删掉的
+//! clone/release 对数。
+//! 调用和 RC 操作从函数的 Cranelift IR 中统计，是实际生成的代码而不是源码里的写法。
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bolide_parser::Type as BolideType;
+
+/// 调用图的边类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CallKind {
+    /// 直接调用用户函数（含类构造函数）
+    Call,
+    /// 调用类方法
+    Method,
+    /// spawn 到新线程或线程池
+    Spawn,
+    /// 调用 async 函数（启动协程）
+    Async,
+    /// 调用 extern 块声明的 C 函数
+    Extern,
+}
+
+impl CallKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CallKind::Call => "call",
+            CallKind::Method => "method",
+            CallKind::Spawn => "spawn",
+            CallKind::Async => "async",
+            CallKind::Extern => "extern",
+        }
+    }
+}
+
+/// 单个函数的统计
+#[derive(Debug, Clone, Default)]
+pub struct FunctionReport {
+    /// 调用图出边（去重）
+    pub calls: BTreeSet<(CallKind, String)>,
+    /// 间接调用次数（函数值、closure 等，编译期不知道目标）
+    pub indirect_calls: usize,
+    /// RC 运行时函数 -> 生成的调用次数，如 `string_clone` -> 2
+    pub rc_ops: BTreeMap<String, usize>,
+    /// 分配堆对象的运行时函数 -> 调用次数，如 `tuple_new` -> 1
+    pub allocs: BTreeMap<String, usize>,
+    /// 由函数负责释放的 RC 变量（按声明顺序）
+    pub rc_variables: Vec<(String, BolideType)>,
+    /// 被移交给 owned 参数的变量
+    pub moved_variables: BTreeSet<String>,
+    /// 登记为语句临时值的 RC 结果个数
+    pub temps_tracked: usize,
+    /// 使用生命周期模式（`from x`），不生成 ARC 代码
+    pub lifetime_mode: bool,
+    /// 编译期求值掉的 `@pure` 调用个数
+    pub pure_calls_folded: usize,
+    /// 复用同一语句里已算出结果的 `@pure` 调用个数
+    pub pure_calls_reused: usize,
+    /// 从 while 条件提到循环之前的 `@pure` 调用个数
+    pub pure_calls_hoisted: usize,
+    /// 窥孔优化删掉的互相抵消的 clone/release 对数
+    pub rc_pairs_removed: usize,
+    /// 生成的代码里还能删掉的 clone/release 对数（`--no-opt` 时不为 0）
+    pub rc_pairs_cancellable: usize,
+}
+
+impl FunctionReport {
+    fn count_suffix(&self, suffix: &str) -> usize {
+        self.rc_ops.iter()
+            .filter(|(name, _)| name.ends_with(suffix))
+            .map(|(_, n)| n)
+            .sum()
+    }
+
+    /// 所有 `*_retain` 调用次数
+    pub fn retains(&self) -> usize {
+        self.count_suffix("_retain")
+    }
+
+    /// 所有 `*_release` 调用次数
+    pub fn releases(&self) -> usize {
+        self.count_suffix("_release")
+    }
+
+    /// 所有 `*_clone` 调用次数
+    pub fn clones(&self) -> usize {
+        self.count_suffix("_clone")
+    }
+}
+
+/// 整个程序的统计，按函数名排序
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisReport {
+    pub functions: BTreeMap<String, FunctionReport>,
+}
+
+/// 运行时函数名是否是 RC 操作
+pub(crate) fn is_rc_op(name: &str) -> bool {
+    name.ends_with("_retain") || name.ends_with("_release") || name.ends_with("_clone")
+}
+
+/// 运行时函数名是否分配新对象（`list_new`、`tuple_new`、字符串拼接和转换等）
+pub(crate) fn is_alloc(name: &str) -> bool {
+    name.ends_with("_new") || name == "string_concat" || name.starts_with("string_from_")
+}
+
+impl AnalysisReport {
+    pub fn function(&self, name: &str) -> Option<&FunctionReport> {
+        self.functions.get(name)
+    }
+
+    /// 文本树形式
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for (name, f) in &self.functions {
+            out.push_str(name);
+            if f.lifetime_mode {
+                out.push_str(" (lifetime mode)");
+            }
+            out.push('\n');
+
+            let mut sections: Vec<(&str, Vec<String>)> = Vec::new();
+            let mut calls: Vec<String> = f.calls.iter()
+                .map(|(kind, target)| format!("{} {}", kind.as_str(), target))
+                .collect();
+            if f.indirect_calls > 0 {
+                calls.push(format!("indirect x{}", f.indirect_calls));
+            }
+            sections.push(("calls", calls));
+            sections.push(("rc ops", f.rc_ops.iter().map(|(op, n)| format!("{} x{}", op, n)).collect()));
+            sections.push(("allocations", f.allocs.iter().map(|(op, n)| format!("{} x{}", op, n)).collect()));
+            sections.push(("rc variables", f.rc_variables.iter()
+                .map(|(var, ty)| format!("{}: {}", var, type_name(ty)))
+                .collect()));
+            sections.push(("moved", f.moved_variables.iter().cloned().collect()));
+            sections.push(("pure calls", [
+                ("folded", f.pure_calls_folded),
+                ("reused", f.pure_calls_reused),
+                ("hoisted", f.pure_calls_hoisted),
+            ].iter().filter(|(_, n)| *n > 0).map(|(what, n)| format!("{} x{}", what, n)).collect()));
+            sections.push(("clone/release pairs", [
+                ("removed", f.rc_pairs_removed),
+                ("cancellable", f.rc_pairs_cancellable),
+            ].iter().filter(|(_, n)| *n > 0).map(|(what, n)| format!("{} x{}", what, n)).collect()));
+
+            for (title, items) in &sections {
+                if items.is_empty() {
+                    continue;
+                }
+                out.push_str(&format!("├─ {}\n", title));
+                for (i, item) in items.iter().enumerate() {
+                    let branch = if i + 1 == items.len() { "└─" } else { "├─" };
+                    out.push_str(&format!("│  {} {}\n", branch, item));
+                }
+            }
+            out.push_str(&format!(
+                "└─ retain {}, release {}, clone {}, temporaries {}\n",
+                f.retains(), f.releases(), f.clones(), f.temps_tracked
+            ));
+        }
+        out
+    }
+
+    /// JSON 形式（编辑器和脚本使用）
+    pub fn to_json(&self) -> String {
+        let functions: Vec<String> = self.functions.iter().map(|(name, f)| {
+            let calls: Vec<String> = f.calls.iter()
+                .map(|(kind, target)| format!("{{\"kind\": {}, \"target\": {}}}", json_str(kind.as_str()), json_str(target)))
+                .collect();
+            let rc_ops: Vec<String> = f.rc_ops.iter()
+                .map(|(op, n)| format!("{}: {}", json_str(op), n))
+                .collect();
+            let allocs: Vec<String> = f.allocs.iter()
+                .map(|(op, n)| format!("{}: {}", json_str(op), n))
+                .collect();
+            let rc_variables: Vec<String> = f.rc_variables.iter()
+                .map(|(var, ty)| format!("{{\"name\": {}, \"type\": {}}}", json_str(var), json_str(&type_name(ty))))
+                .collect();
+            let moved: Vec<String> = f.moved_variables.iter().map(|v| json_str(v)).collect();
+            format!(
+                "    {}: {{\"calls\": [{}], \"indirect_calls\": {}, \"rc_ops\": {{{}}}, \"allocs\": {{{}}}, \"retains\": {}, \"releases\": {}, \"clones\": {}, \"rc_variables\": [{}], \"moved_variables\": [{}], \"temps_tracked\": {}, \"lifetime_mode\": {}, \"pure_calls\": {{\"folded\": {}, \"reused\": {}, \"hoisted\": {}}}, \"rc_pairs\": {{\"removed\": {}, \"cancellable\": {}}}}}",
+                json_str(name),
+                calls.join(", "),
+                f.indirect_calls,
+                rc_ops.join(", "),
+                allocs.join(", "),
+                f.retains(),
+                f.releases(),
+                f.clones(),
+                rc_variables.join(", "),
+                moved.join(", "),
+                f.temps_tracked,
+                f.lifetime_mode,
+                f.pure_calls_folded,
+                f.pure_calls_reused,
+                f.pure_calls_hoisted,
+                f.rc_pairs_removed,
+                f.rc_pairs_cancellable,
+            )
+        }).collect();
+        format!("{{\n  \"functions\": {{\n{}\n  }}\n}}", functions.join(",\n"))
+    }
+}
+
+/// 按源码写法显示类型
+pub(crate) fn type_name(ty: &BolideType) -> String {
+    let list = |tys: &[BolideType]| tys.iter().map(type_name).collect::<Vec<_>>().join(", ");
+    match ty {
+        BolideType::Int => "int".to_string(),
+        BolideType::Float => "float".to_string(),
+        BolideType::Bool => "bool".to_string(),
+        BolideType::Str => "str".to_string(),
+        BolideType::BigInt => "bigint".to_string(),
+        BolideType::Decimal => "decimal".to_string(),
+        BolideType::Duration => "duration".to_string(),
+        BolideType::Deadline => "deadline".to_string(),
+        BolideType::Dynamic => "dynamic".to_string(),
+        BolideType::Ptr => "ptr".to_string(),
+        BolideType::Channel(t) => format!("channel<{}>", type_name(t)),
+        BolideType::Future => "future".to_string(),
+        BolideType::Func => "func".to_string(),
+        BolideType::FuncSig(params, ret) => match ret {
+            Some(ret) => format!("func({}) -> {}", list(params), type_name(ret)),
+            None => format!("func({})", list(params)),
+        },
+        BolideType::List(t) => format!("list<{}>", type_name(t)),
+        BolideType::Dict(k, v) => format!("dict<{}, {}>", type_name(k), type_name(v)),
+        BolideType::WeakDict(k, v) => format!("weak_dict<{}, {}>", type_name(k), type_name(v)),
+        BolideType::Tuple(tys) => format!("({})", list(tys)),
+        BolideType::Custom(name) => name.clone(),
+        BolideType::Weak(t) => format!("weak {}", type_name(t)),
+        BolideType::Unowned(t) => format!("unowned {}", type_name(t)),
+        BolideType::PackedList(t) => format!("list<packed {}>", type_name(t)),
+        BolideType::PackedRef(t) => format!("packed {}", type_name(t)),
+    }
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -7,9 +7,33 @@ use cranelift::prelude::isa::{TargetIsa, CallConv};
 use cranelift_object::{ObjectBuilder, ObjectModule};
 use cranelift_module::{DataDescription, Linkage, Module, FuncId, DataId};
 use cranelift_codegen::ir::{FuncRef, StackSlotData, StackSlotKind};
+use cranelift_frontend::Switch;
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
-use bolide_parser::{Program, Statement, Expr, Type as BolideType, FuncDef, Param, ParamMode, ExternBlock, ExternDecl, CType, BinOp, UnaryOp};
+use std::str::FromStr;
+use target_lexicon::BinaryFormat;
+use bolide_parser::{CfgTarget, Program, Statement, Expr, Type as BolideType, FuncDef, Param, ParamMode, ExternBlock, ExternDecl, CType, BinOp, UnaryOp, MatchPattern};
+use crate::formatter;
+use crate::call_trace;
+use crate::lambda;
+use crate::class_graph;
+use crate::equality;
+use crate::list_literal;
+use crate::ice::{self, IceReport, IceSource};
+use crate::layout;
+use crate::purity;
+use crate::bind;
+use crate::handles;
+use crate::optimize;
+use crate::string_fold;
+use crate::rc_peephole;
+use crate::compound;
+use crate::call_args;
+use crate::features;
+use crate::overflow;
+use crate::duration;
+use crate::test_blocks;
+use crate::modules::{self, ImportedModule, Member};
 
 /// AOT 编译结果
 #[derive(Debug)]
@@ -18,8 +42,21 @@ pub struct AotCompileResult {
     pub object_code: Vec<u8>,
     /// 外部库列表 (库路径)
     pub extern_libs: Vec<String>,
+    /// 编译警告（例如已弃用的用法），每条形如 `in function 'f': ...`
+    pub warnings: Vec<String>,
+    /// 目标文件对应的目标三元组
+    pub target_triple: String,
 }
 
+/// 可以交叉编译的目标三元组（`for_target` 出错时列出）
+pub const SUPPORTED_TARGETS: &[&str] = &[
+    "x86_64-unknown-linux-gnu",
+    "aarch64-unknown-linux-gnu",
+    "x86_64-apple-darwin",
+    "aarch64-apple-darwin",
+    "x86_64-pc-windows-msvc",
+];
+
 /// Trampoline 信息
 struct TrampolineInfo {
     func_id: FuncId,
@@ -33,6 +70,8 @@ struct FieldInfo {
     name: String,
     ty: BolideType,
     offset: usize,
+    /// 字段初始值，构造时省略该字段则使用
+    default: Option<Expr>,
 }
 
 /// 类信息
@@ -45,6 +84,61 @@ struct ClassInfo {
     size: usize,
 }
 
+/// 正在编译的 while/for 循环（break/continue 的跳转目标）
+struct LoopFrame {
+    /// loop_count() 计数器
+    counter: Variable,
+    /// 循环条件或循环体用到了 loop_count()
+    counter_used: bool,
+    /// continue 的目标：推进循环变量后回到循环头
+    continue_block: Block,
+    /// break 的目标
+    exit_block: Block,
+    /// 有 continue 跳到 continue_block
+    continued: bool,
+    /// 循环体作用域在 rc_variables 中的起点，跳出循环体时释放其后的变量
+    scope_start: usize,
+    /// 进入循环时 measure_stack 的深度，跳出循环体时结束更深的 measure 块
+    measure_depth: usize,
+    /// 进入循环时 pool_stack 的深度，跳出循环体时拆除更深的 pool 块
+    pool_depth: usize,
+}
+
+/// 程序结束时释放 RC 全局变量的函数，由入口 `main` 在顶层代码返回后调用
+const GLOBALS_RELEASE: &str = "__globals_release";
+
+/// threadlocal 全局变量的初始化函数和析构函数的名字前缀（见 `bolide_runtime` 的 tls 模块）
+const TLS_INIT_PREFIX: &str = "__tls_init_";
+const TLS_DROP_PREFIX: &str = "__tls_drop_";
+
+/// threadlocal 变量 `name` 的初始化函数和析构函数（参数类型为 `ty`，返回前释放）
+fn thread_local_funcs(name: &str, ty: BolideType) -> (FuncDef, FuncDef) {
+    let init_func = FuncDef {
+        name: format!("{}{}", TLS_INIT_PREFIX, name),
+        is_async: false,
+        params: vec![],
+        return_type: None,
+        lifetime_deps: None,
+        body: vec![],
+        pure: false,
+        trace: None,
+        hot_entry: false,
+        line: 0,
+    };
+    let drop_func = FuncDef {
+        name: format!("{}{}", TLS_DROP_PREFIX, name),
+        params: vec![Param { name: "value".to_string(), ty, mode: ParamMode::Owned, default: None }],
+        ..init_func.clone()
+    };
+    (init_func, drop_func)
+}
+
+/// packed 列表只有 JIT 实现
+const PACKED_LIST_UNSUPPORTED: &str = "list<packed T> is only supported by the JIT (bolide run)";
+
+/// join_ok 只有 JIT 实现
+const JOIN_OK_UNSUPPORTED: &str = "join_ok is only supported by the JIT (bolide run)";
+
 /// AOT 编译器
 pub struct AotCompiler {
     module: ObjectModule,
@@ -60,6 +154,8 @@ pub struct AotCompiler {
     trampolines: HashMap<String, TrampolineInfo>,
     /// trampoline 计数器
     trampoline_counter: usize,
+    /// 有引用计数参数、作为 map / filter / reduce 回调的函数，各自有一个借用适配函数
+    borrow_adapters: Vec<String>,
     /// 指针类型
     ptr_type: types::Type,
     /// 类名 -> 类信息 映射
@@ -68,12 +164,33 @@ pub struct AotCompiler {
     async_funcs: HashSet<String>,
     /// extern 函数信息: 函数名 -> (库路径, 函数声明)
     extern_funcs: HashMap<String, (String, bolide_parser::ExternFunc)>,
-    /// 模块名映射: 模块名 -> 文件路径
-    modules: HashMap<String, String>,
+    /// 导入的模块: 模块名 -> 路径和导出的名字
+    modules: HashMap<String, ImportedModule>,
     /// 使用生命周期模式的函数集合
     lifetime_funcs: HashSet<String>,
     /// 字符串常量数据
     string_data: HashMap<String, DataId>,
+    /// 源文件（ICE 复现包使用）
+    source: Option<IceSource>,
+    /// 编译目标，导入的模块按它求值 `@cfg`
+    cfg_target: CfgTarget,
+    /// 编译警告，随编译结果返回
+    warnings: Vec<String>,
+    /// `--no-trace`：忽略 `@trace` 标注，不生成调用日志
+    no_trace: bool,
+    /// 优化级别（`-O0` / `-O1`）：0 时不做常量折叠和死分支消除、不折叠字面量字符串表达式，
+    /// 不删除互相抵消的 clone/release
+    opt_level: u8,
+    /// `--share-containers`：绑定已有的列表、字典时共享同一个容器，不再复制
+    share_containers: bool,
+    /// 导入模块的函数和类（`@模块_名字`）在各自的目标文件中定义，这里只声明（增量编译的主程序）
+    external_modules: bool,
+    /// 全局变量（顶层 `let`）-> 数据段
+    global_data_ids: HashMap<String, DataId>,
+    /// 全局变量的类型；没有标注类型的在编译 `__main__` 里的声明时推断
+    global_var_types: HashMap<String, BolideType>,
+    /// `threadlocal let` 声明，数据段里存的是运行时的 TLS 槽号
+    thread_locals: Vec<bolide_parser::VarDecl>,
 }
 
 /// 运行时符号列表
@@ -81,8 +198,13 @@ pub const RUNTIME_SYMBOLS: &[&str] = &[
     // 基本类型打印
     "print_int", "print_float", "print_bool", "print_bigint",
     "print_decimal", "print_string", "print_dynamic",
+    "print_int_inline", "print_float_inline", "print_bool_inline", "print_bigint_inline",
+    "print_decimal_inline", "print_string_inline", "print_dynamic_inline", "print_flush",
     // 用户输入
-    "input", "input_prompt",
+    "input", "input_prompt", "input_async", "file_read", "file_read_async", "io_await", "set_io_workers",
+    "file_write_lines", "file_write", "file_append", "file_exists", "file_lines", "file_remove", "temp_dir", "format_row",
+    // 测试断言
+    "assert", "assert_eq_int", "assert_eq_float", "assert_eq_str", "assert_eq_list",
     // BigInt
     "bigint_from_i64", "bigint_from_str", "bigint_add", "bigint_sub",
     "bigint_mul", "bigint_div", "bigint_rem", "bigint_neg",
@@ -90,9 +212,10 @@ pub const RUNTIME_SYMBOLS: &[&str] = &[
     "bigint_to_i64", "bigint_clone", "bigint_debug_stats",
     // Decimal
     "decimal_from_i64", "decimal_from_f64", "decimal_from_str",
-    "decimal_add", "decimal_sub", "decimal_mul", "decimal_div",
-    "decimal_neg", "decimal_eq", "decimal_lt", "decimal_to_i64",
-    "decimal_to_f64", "decimal_clone",
+    "decimal_add", "decimal_sub", "decimal_mul", "decimal_div", "decimal_rem",
+    "decimal_neg", "decimal_eq", "decimal_lt", "decimal_le", "decimal_gt", "decimal_ge", "decimal_to_i64",
+    "decimal_to_f64", "decimal_clone", "decimal_from_string",
+    "decimal_round", "decimal_round_dp_mode", "decimal_floor", "decimal_ceil", "decimal_set_rounding", "decimal_set_div_precision", "float_round",
     // Dynamic
     "dynamic_from_int", "dynamic_from_float", "dynamic_from_bool",
     "dynamic_from_string", "dynamic_from_list", "dynamic_from_bigint",
@@ -100,34 +223,42 @@ pub const RUNTIME_SYMBOLS: &[&str] = &[
     "dynamic_div", "dynamic_neg", "dynamic_eq", "dynamic_lt", "dynamic_clone",
     // String
     "string_from_slice", "string_literal", "string_as_cstr", "string_concat",
-    "string_eq", "string_from_int", "string_from_float", "string_from_bool",
-    "string_from_bigint", "string_from_decimal", "string_to_int", "string_to_float",
+    "string_eq", "string_char_at", "string_slice", "string_split", "string_trim", "string_upper", "string_lower",
+    "string_contains", "string_replace", "string_encode", "bytes_decode", "bytes_decode_latin1", "string_len", "string_intern", "string_intern_stats", "string_intern_clear", "string_cmp", "string_from_int", "string_from_float", "string_from_bool",
+    "string_from_bigint", "string_from_decimal", "string_from_duration", "string_to_int", "string_to_float",
+    "string_builder_new", "string_builder_append_str", "string_builder_append_int", "string_builder_append_float",
+    "string_builder_append_bool", "string_builder_append_bigint", "string_builder_append_decimal",
+    "string_builder_append_dynamic", "string_builder_append_list", "string_builder_append_dict",
+    "string_builder_append_tuple", "string_builder_finish",
+    "string_parse_int", "string_parse_float",
+    "string_debug_stats",
     // Memory
     "bolide_alloc", "bolide_free",
     // Object
-    "object_alloc", "object_retain", "object_release", "object_clone",
+    "object_alloc", "object_retain", "object_release", "object_refcount", "object_clone", "object_to_string", "object_equals",
     // Thread
     "thread_spawn_int", "thread_spawn_float", "thread_spawn_ptr",
     "thread_spawn_int_with_env", "thread_spawn_float_with_env", "thread_spawn_ptr_with_env",
     "thread_join_int", "thread_join_float", "thread_join_ptr",
-    "thread_handle_free", "thread_cancel", "thread_is_cancelled",
+    "thread_handle_free", "thread_cancel", "thread_is_cancelled", "thread_set_name", "thread_take_error",
     // Pool
-    "pool_create", "pool_enter", "pool_exit", "pool_is_active",
+    "pool_create", "pool_enter", "pool_exit", "pool_exit_checked", "pool_is_active",
     "pool_spawn_int", "pool_spawn_float", "pool_spawn_ptr",
     "pool_spawn_int_with_env", "pool_spawn_float_with_env", "pool_spawn_ptr_with_env",
-    "pool_join_int", "pool_join_float", "pool_join_ptr",
-    "pool_handle_free", "pool_destroy",
+    "pool_join_int", "pool_join_float", "pool_join_ptr", "pool_set_name", "pool_take_error",
+    "pool_handle_free", "pool_destroy", "thread_id", "live_threads", "tls_alloc", "tls_get", "tls_thread_exit", "platform", "version", "version_at_least", "has_feature", "register_features", "error_code", "error_message", "error_clear", "exit", "shutdown",
     // Channel
-    "channel_create", "channel_create_buffered", "channel_send",
-    "channel_recv", "channel_close", "channel_free", "channel_select",
+    "channel_create", "channel_create_buffered", "channel_send", "channel_send_ptr",
+    "channel_recv", "channel_recv_opt", "channel_close", "channel_free", "channel_select", "channel_select_list",
+    "channel_send_all", "channel_recv_n", "channel_drain",
     // Coroutine
     "coroutine_spawn_int", "coroutine_spawn_float", "coroutine_spawn_ptr",
     "coroutine_await_int", "coroutine_await_float", "coroutine_await_ptr",
-    "coroutine_cancel", "coroutine_free",
+    "coroutine_cancel", "coroutine_free", "coroutine_set_workers", "coroutine_set_name", "coroutine_cancelled",
     "coroutine_spawn_int_with_env", "coroutine_spawn_float_with_env", "coroutine_spawn_ptr_with_env",
-    "scope_enter", "scope_register", "scope_exit",
+    "scope_enter", "scope_enter_fail_fast", "scope_register", "scope_exit",
     // Select
-    "select_wait_first",
+    "select_wait_first", "deadline", "deadline_remaining",
     // Tuple
     "tuple_new", "tuple_free", "tuple_set", "tuple_get", "tuple_len", "print_tuple",
     // FFI
@@ -137,24 +268,63 @@ pub const RUNTIME_SYMBOLS: &[&str] = &[
     "bigint_retain", "bigint_release",
     "decimal_retain", "decimal_release",
     "list_retain", "list_release", "list_clone",
-    "list_new", "list_push", "list_pop", "list_len", "list_get", "list_set",
+    "list_new", "list_from_const_array", "list_push", "list_pop", "list_len", "list_get", "list_set",
+    "list_push_f64", "list_get_f64", "list_set_f64",
     "list_insert", "list_remove", "list_clear", "list_reverse", "list_extend",
     "list_contains", "list_index_of", "list_count", "list_sort", "list_slice",
-    "list_is_empty", "list_first", "list_last", "print_list",
+    "list_is_empty", "list_first", "list_last", "list_dedup", "list_unique", "print_list",
+    "list_map", "list_filter", "list_reduce", "list_sort_by",
+    "list_sort_by_key", "list_min_by", "list_max_by", "list_group_by", "bind", "closure_call", "func_retain", "func_release",
+    "list_sum_int", "list_sum_float", "list_min_int", "list_max_int",
+    "list_min_float", "list_max_float", "list_mean", "list_mean_float", "list_mean_bigint",
+    "list_sum_bigint", "list_min_bigint", "list_max_bigint",
+    "list_sum_decimal", "list_min_decimal", "list_max_decimal", "list_mean_decimal",
     // Dict
-    "dict_new", "dict_retain", "dict_release", "dict_clone",
+    "dict_new", "dict_new_default", "dict_get_or_insert_empty", "dict_retain", "dict_release", "dict_clone",
     "dict_set", "dict_get", "dict_contains", "dict_remove",
     "dict_len", "dict_is_empty", "dict_clear", "dict_keys", "dict_values",
     "dict_iter", "print_dict",
+    "weakdict_new", "weakdict_release", "weakdict_clone", "weakdict_set", "weakdict_get",
+    "weakdict_contains", "weakdict_remove", "weakdict_len", "weakdict_is_empty", "weakdict_compact",
+    "weak_debug_stats", "object_debug_enable", "object_live_count", "object_dump_live",
     "dynamic_retain", "dynamic_release",
 ];
 
 impl AotCompiler {
-    /// 创建新的 AOT 编译器
+    /// 创建新的 AOT 编译器（目标为本机）
     pub fn new() -> Result<Self, String> {
         let isa_builder = cranelift_native::builder()
             .map_err(|e| format!("Failed to create ISA builder: {}", e))?;
+        Self::with_isa_builder(isa_builder, CfgTarget::host())
+    }
+
+    /// 为目标三元组（如 `x86_64-pc-windows-msvc`）创建 AOT 编译器
+    ///
+    /// 目标文件格式跟随三元组：Linux 为 ELF，macOS 为 Mach-O，Windows 为 COFF。
+    pub fn for_target(triple: &str) -> Result<Self, String> {
+        let unsupported = |reason: String| {
+            format!("Unsupported target '{}': {} (supported targets: {})", triple, reason, SUPPORTED_TARGETS.join(", "))
+        };
+        let cfg_target = CfgTarget::from_triple(triple)?;
+        let parsed = target_lexicon::Triple::from_str(triple)
+            .map_err(|e| format!("Invalid target '{}': {}", triple, e))?;
+        if !matches!(parsed.binary_format, BinaryFormat::Elf | BinaryFormat::Macho | BinaryFormat::Coff) {
+            return Err(unsupported(format!("no object file format for {}", parsed.binary_format)));
+        }
+        let isa_builder = cranelift_codegen::isa::lookup(parsed)
+            .map_err(|e| unsupported(e.to_string()))?;
+        Self::with_isa_builder(isa_builder, cfg_target)
+    }
 
+    /// `target` 为 None 时同 `new`，否则同 `for_target`
+    pub fn with_target(target: Option<&str>) -> Result<Self, String> {
+        match target {
+            Some(triple) => Self::for_target(triple),
+            None => Self::new(),
+        }
+    }
+
+    fn with_isa_builder(isa_builder: isa::Builder, cfg_target: CfgTarget) -> Result<Self, String> {
         let flag_builder = settings::builder();
         let flags = settings::Flags::new(flag_builder);
         let isa = isa_builder.finish(flags)
@@ -180,6 +350,7 @@ impl AotCompiler {
             func_params: HashMap::new(),
             trampolines: HashMap::new(),
             trampoline_counter: 0,
+            borrow_adapters: Vec::new(),
             ptr_type,
             classes: HashMap::new(),
             async_funcs: HashSet::new(),
@@ -187,9 +358,47 @@ impl AotCompiler {
             modules: HashMap::new(),
             lifetime_funcs: HashSet::new(),
             string_data: HashMap::new(),
+            source: None,
+            cfg_target,
+            warnings: Vec::new(),
+            no_trace: false,
+            opt_level: 1,
+            share_containers: false,
+            external_modules: false,
+            global_data_ids: HashMap::new(),
+            global_var_types: HashMap::new(),
+            thread_locals: Vec::new(),
         })
     }
 
+    /// 设置源文件，内部编译器错误时会写入复现包
+    pub fn set_source(&mut self, path: &str, text: &str) {
+        self.source = Some(IceSource {
+            path: path.to_string(),
+            text: text.to_string(),
+        });
+    }
+
+    /// 忽略 `@trace` 标注（`--no-trace`），生成的代码与没有标注时相同
+    pub fn set_no_trace(&mut self, no_trace: bool) {
+        self.no_trace = no_trace;
+    }
+
+    /// 字面量字符串表达式照常在运行时计算、保留互相抵消的 clone/release（`--no-opt`），与 JIT 的同名开关一致
+    pub fn set_no_opt(&mut self, no_opt: bool) {
+        self.opt_level = if no_opt { 0 } else { 1 };
+    }
+
+    /// 优化级别（`-O0` / `-O1`，默认 1），与 JIT 的同名开关一致
+    pub fn set_opt_level(&mut self, level: u8) {
+        self.opt_level = level.min(1);
+    }
+
+    /// 绑定已有的列表、字典时共享同一个容器（`--share-containers`），与 JIT 的同名开关一致
+    pub fn set_share_containers(&mut self, share: bool) {
+        self.share_containers = share;
+    }
+
     /// Get or create a data object for a string literal
     fn get_or_create_string_data(&mut self, s: &str) -> Result<DataId, String> {
         if let Some(&data_id) = self.string_data.get(s) {
@@ -215,11 +424,16 @@ impl AotCompiler {
     }
 
     /// Collect all string literals from statements
+    ///
+    /// 参数默认值在调用处编译，所以也收集所有参数默认值里的字符串。
     fn collect_strings_from_stmts(&self, stmts: &[Statement]) -> HashSet<String> {
         let mut strings = HashSet::new();
         for stmt in stmts {
             self.collect_strings_from_stmt(stmt, &mut strings);
         }
+        for default in self.func_params.values().flatten().filter_map(|p| p.default.as_ref()) {
+            self.collect_strings_from_expr(default, &mut strings);
+        }
         strings
     }
 
@@ -235,6 +449,7 @@ impl AotCompiler {
                 self.collect_strings_from_expr(&a.target, strings);
                 self.collect_strings_from_expr(&a.value, strings);
             }
+            Statement::Destructure(d) => self.collect_strings_from_expr(&d.value, strings),
             Statement::If(if_stmt) => {
                 self.collect_strings_from_expr(&if_stmt.condition, strings);
                 for s in &if_stmt.then_body { self.collect_strings_from_stmt(s, strings); }
@@ -254,6 +469,48 @@ impl AotCompiler {
                 self.collect_strings_from_expr(&for_stmt.iter, strings);
                 for s in &for_stmt.body { self.collect_strings_from_stmt(s, strings); }
             }
+            Statement::Measure(measure_stmt) => {
+                strings.insert(measure_stmt.label.clone());
+                for s in &measure_stmt.body { self.collect_strings_from_stmt(s, strings); }
+            }
+            Statement::Pool(pool_stmt) => {
+                self.collect_strings_from_expr(&pool_stmt.size, strings);
+                for s in &pool_stmt.body { self.collect_strings_from_stmt(s, strings); }
+            }
+            Statement::Match(match_stmt) => {
+                self.collect_strings_from_expr(&match_stmt.subject, strings);
+                for arm in &match_stmt.arms {
+                    for pattern in &arm.patterns {
+                        if let MatchPattern::Str(text) = pattern { strings.insert(text.clone()); }
+                    }
+                    for s in &arm.body { self.collect_strings_from_stmt(s, strings); }
+                }
+                if let Some(ref default) = match_stmt.default {
+                    for s in default { self.collect_strings_from_stmt(s, strings); }
+                }
+            }
+            Statement::Select(select_stmt) => {
+                use bolide_parser::SelectBranch;
+                if let Some(ref channels) = select_stmt.channels {
+                    self.collect_strings_from_expr(channels, strings);
+                }
+                for branch in &select_stmt.branches {
+                    let body = match branch {
+                        SelectBranch::Recv { body, .. } => body,
+                        SelectBranch::Timeout { duration, body } => {
+                            self.collect_strings_from_expr(duration, strings);
+                            body
+                        }
+                        SelectBranch::RecvAny { body, .. }
+                        | SelectBranch::Closed { body }
+                        | SelectBranch::Default { body } => body,
+                    };
+                    for s in body { self.collect_strings_from_stmt(s, strings); }
+                }
+            }
+            Statement::AwaitScope(scope_stmt) => {
+                for s in &scope_stmt.body { self.collect_strings_from_stmt(s, strings); }
+            }
             Statement::Return(Some(e)) => self.collect_strings_from_expr(e, strings),
             _ => {}
         }
@@ -263,23 +520,36 @@ impl AotCompiler {
         match expr {
             Expr::String(s) => { strings.insert(s.clone()); }
             Expr::Call(callee, args) => {
+                // async 调用把函数名交给协程，报告错误时使用
+                if let Expr::Ident(name) = callee.as_ref() {
+                    if self.async_funcs.contains(name) { strings.insert(name.clone()); }
+                }
                 self.collect_strings_from_expr(callee, strings);
                 for a in args { self.collect_strings_from_expr(a, strings); }
             }
+            Expr::Await(e) => self.collect_strings_from_expr(e, strings),
             Expr::BinOp(l, _, r) => {
                 self.collect_strings_from_expr(l, strings);
                 self.collect_strings_from_expr(r, strings);
             }
-            Expr::UnaryOp(_, e) => self.collect_strings_from_expr(e, strings),
+            Expr::CompareChain(first, rest) => {
+                self.collect_strings_from_expr(first, strings);
+                for (_, e) in rest { self.collect_strings_from_expr(e, strings); }
+            }
+            Expr::UnaryOp(_, e) | Expr::NamedArg(_, e) => self.collect_strings_from_expr(e, strings),
             Expr::Index(b, i) => {
                 self.collect_strings_from_expr(b, strings);
                 self.collect_strings_from_expr(i, strings);
             }
+            Expr::Slice(b, start, end) => {
+                self.collect_strings_from_expr(b, strings);
+                for e in [start, end].into_iter().flatten() { self.collect_strings_from_expr(e, strings); }
+            }
             Expr::Member(b, _) => self.collect_strings_from_expr(b, strings),
             Expr::List(items) => {
                 for i in items { self.collect_strings_from_expr(i, strings); }
             }
-            Expr::Tuple(items) => {
+            Expr::Tuple(items) | Expr::FString(items) => {
                 for i in items { self.collect_strings_from_expr(i, strings); }
             }
             Expr::Dict(entries) => {
@@ -293,9 +563,72 @@ impl AotCompiler {
     }
 
     /// 编译程序并返回目标文件字节
-    pub fn compile(mut self, program: &Program) -> Result<AotCompileResult, String> {
+    pub fn compile(self, program: &Program) -> Result<AotCompileResult, String> {
+        self.compile_program(program, true)
+    }
+
+    /// 编译成库：不生成入口 `main` 和顶层代码 `__main__`，只导出函数（C 调用约定）
+    ///
+    /// 程序只能包含定义（函数、类、extern 块、import），其他顶层语句报错。
+    pub fn compile_library(self, program: &Program) -> Result<AotCompileResult, String> {
+        self.compile_program(program, false)
+    }
+
+    /// 增量编译的主程序：与 `compile` 相同，但导入模块的函数和类只声明为外部符号，
+    /// 由 `compile_module` 编译出的目标文件在链接时提供
+    pub fn compile_main(mut self, program: &Program) -> Result<AotCompileResult, String> {
+        self.external_modules = true;
+        self.compile_program(program, true)
+    }
+
+    /// 增量编译的单个模块：`file_path` 是 import 的路径，函数和类按导入时的规则命名为
+    /// `@模块_名字`，与 `compile_main` 声明的符号一致。模块只能使用自己的函数和类。
+    pub fn compile_module(mut self, file_path: &str) -> Result<AotCompileResult, String> {
+        let module_name = Self::extract_module_name(file_path);
+        let imported = self.load_module(file_path)?;
+        self.modules.insert(module_name.clone(), ImportedModule::new(file_path, &imported));
+        let statements = modules::module_items(imported, &module_name, false);
+        self.compile_program(&Program { statements }, false)
+    }
+
+    /// 类名按字典序排列，保证每次编译的符号和布局顺序相同
+    fn class_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.classes.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// 名字属于导入模块，并且在其他目标文件中定义
+    fn is_external(&self, name: &str) -> bool {
+        self.external_modules && name.starts_with('@')
+    }
+
+    fn linkage_for(&self, name: &str) -> Linkage {
+        if self.is_external(name) { Linkage::Import } else { Linkage::Export }
+    }
+
+    /// 目标文件里的符号名：GNU ld 把 `@` 当作符号版本分隔符，导入模块的 `@模块_名字`
+    /// 写成 `__bolide_模块_名字`；`main` 是进程入口，用户定义的 `main` 函数写成 `__bolide_main`
+    fn symbol_name(name: &str) -> String {
+        match name.strip_prefix('@') {
+            Some(rest) => format!("__bolide_{}", rest),
+            None if name == "main" => "__bolide_main".to_string(),
+            None => name.to_string(),
+        }
+    }
+
+    fn compile_program(mut self, program: &Program, entry: bool) -> Result<AotCompileResult, String> {
         // 预处理 import 语句
-        let program = self.process_imports(program)?;
+        let mut program = self.process_imports(program)?;
+        // 常量折叠、死分支消除，只由字面量组成的字符串表达式换成结果字面量
+        if self.opt_level > 0 {
+            program = optimize::fold_program(&program);
+            string_fold::fold_program(&mut program);
+        }
+
+        // 验证 @pure 标注；编译期求值和调用提升只在 JIT 中进行
+        purity::check_program(&program)?;
+        handles::check_program(&program)?;
 
         // 注册内置函数
         self.register_builtins()?;
@@ -310,6 +643,19 @@ impl AotCompiler {
         // 收集类定义
         self.collect_classes(&program)?;
 
+        // 为每个类合成默认的字符串表示方法
+        formatter::add_default_formatters(&mut program, &self.modules, |name| {
+            self.classes[name].fields.iter().map(|f| (f.name.clone(), f.ty.clone())).collect()
+        });
+        // 以及默认的逐字段相等比较
+        equality::add_default_comparators(&mut program, |name| {
+            self.classes[name].fields.iter().map(|f| (f.name.clone(), f.ty.clone())).collect()
+        });
+        // `@trace` 函数的调用日志
+        if !self.no_trace {
+            call_trace::add_call_tracing(&mut program, &self.modules);
+        }
+
         // 第一遍：收集函数声明
         for stmt in &program.statements {
             if let Statement::FuncDef(func) = stmt {
@@ -321,7 +667,7 @@ impl AotCompiler {
         }
 
         // 声明类构造函数和方法
-        for class_name in self.classes.keys().cloned().collect::<Vec<_>>() {
+        for class_name in self.class_names() {
             self.declare_class_constructor(&class_name)?;
         }
         self.declare_class_methods(&program)?;
@@ -329,20 +675,14 @@ impl AotCompiler {
         // 生成 trampolines
         let spawn_targets = self.collect_spawn_targets(&program);
         self.generate_trampolines(&spawn_targets)?;
-
-        // 编译类
-        for class_name in self.classes.keys().cloned().collect::<Vec<_>>() {
-            self.compile_class_constructor(&class_name)?;
-        }
-        self.compile_class_methods(&program)?;
+        self.declare_borrow_adapters(&program)?;
 
         // 第二遍：编译函数
+        let mut funcs = Vec::new();
         let mut toplevel_stmts = Vec::new();
         for stmt in &program.statements {
             match stmt {
-                Statement::FuncDef(func) => {
-                    self.compile_function(func)?;
-                }
+                Statement::FuncDef(func) => funcs.push(func),
                 Statement::ClassDef(_) => {}
                 _ => {
                     toplevel_stmts.push(stmt.clone());
@@ -350,18 +690,137 @@ impl AotCompiler {
             }
         }
 
-        // 包装顶层代码为 main 函数
+        if !entry {
+            let code = toplevel_stmts.iter()
+                .find(|stmt| !matches!(stmt, Statement::Import(_) | Statement::ExternBlock(_)));
+            if let Some(stmt) = code {
+                let what = match stmt {
+                    Statement::VarDecl(decl) => format!("`let {}`", decl.name),
+                    Statement::Expr(expr) => format!("`{}`", purity::expr_text(expr)),
+                    _ => "a top-level statement".to_string(),
+                };
+                return Err(format!("a library has no entry point to run top-level code; move {} into a function", what));
+            }
+
+            self.compile_classes(&program)?;
+            for name in layout::call_graph_order(&funcs, &[]) {
+                if let Some(func) = funcs.iter().find(|f| f.name == name && !self.is_external(&f.name)) {
+                    self.compile_function(func)?;
+                }
+            }
+            self.define_trampolines()?;
+            self.define_borrow_adapters()?;
+            return self.finish();
+        }
+
+        // 顶层 let 是全局变量，所有函数都可以读写
+        self.collect_global_variables(&toplevel_stmts)?;
+
+        // 包装顶层代码为 __main__ 函数，由入口 main 调用
         let main_func = FuncDef {
-            name: "main".to_string(),
+            name: "__main__".to_string(),
             is_async: false,
             params: vec![],
             return_type: Some(BolideType::Int),
             lifetime_deps: None,
             body: toplevel_stmts,
+            pure: false,
+            trace: None,
+            hot_entry: false,
+            line: 0,
         };
         self.declare_function(&main_func)?;
+        let release_func = FuncDef {
+            name: GLOBALS_RELEASE.to_string(),
+            is_async: false,
+            params: vec![],
+            return_type: None,
+            lifetime_deps: None,
+            body: vec![],
+            pure: false,
+            trace: None,
+            hot_entry: false,
+            line: 0,
+        };
+        self.declare_function(&release_func)?;
+        // threadlocal 变量的初始化函数（函数体是初始化赋值）和析构函数；析构函数的参数类型在
+        // __main__ 推断出变量类型后才确定，RC 类型和 int 的签名相同
+        for decl in self.thread_locals.clone() {
+            let (init_func, drop_func) = thread_local_funcs(&decl.name, BolideType::Int);
+            self.declare_function(&init_func)?;
+            self.declare_function(&drop_func)?;
+        }
+
+        // 代码段布局：main，然后是类和按调用图排列的函数，最后是 trampolines
+        // __main__ 最先编译：没有标注类型的全局变量在这里确定类型
+        self.define_entry_stub()?;
         self.compile_function(&main_func)?;
+        self.compile_classes(&program)?;
+        for name in layout::call_graph_order(&funcs, &main_func.body) {
+            if let Some(func) = funcs.iter().find(|f| f.name == name && !self.is_external(&f.name)) {
+                self.compile_function(func)?;
+            }
+        }
+        for decl in self.thread_locals.clone() {
+            let ty = self.global_var_types.get(&decl.name)
+                .filter(|ty| AotCompileContext::is_rc_type(ty))
+                .cloned()
+                .unwrap_or(BolideType::Int);
+            let (init_func, drop_func) = thread_local_funcs(&decl.name, ty);
+            self.compile_function(&init_func)?;
+            self.compile_function(&drop_func)?;
+        }
+        self.compile_function(&release_func)?;
+        self.define_trampolines()?;
+        self.define_borrow_adapters()?;
+        self.finish()
+    }
+
+    /// 编译类的构造函数和方法
+    fn compile_classes(&mut self, program: &Program) -> Result<(), String> {
+        for class_name in self.class_names() {
+            if !self.is_external(&class_name) {
+                self.compile_class_constructor(&class_name)?;
+            }
+        }
+        self.compile_class_methods(program)
+    }
+
+    /// 为顶层的 `let` 和 `let a, b = ...` 声明全局变量（8 字节，初始为 0）
+    fn collect_global_variables(&mut self, toplevel: &[Statement]) -> Result<(), String> {
+        for stmt in toplevel {
+            let names: Vec<&String> = match stmt {
+                Statement::VarDecl(decl) => vec![&decl.name],
+                Statement::Destructure(destructure) if destructure.declare => destructure.targets.iter().flatten().collect(),
+                _ => continue,
+            };
+            for name in names {
+                if self.global_data_ids.contains_key(name) {
+                    continue;
+                }
+                let data_id = self.module.declare_anonymous_data(true, false)
+                    .map_err(|e| format!("Failed to declare global '{}': {}", name, e))?;
+                self.data_desc.clear();
+                self.data_desc.define_zeroinit(8);
+                self.module.define_data(data_id, &self.data_desc)
+                    .map_err(|e| format!("Failed to define global '{}': {}", name, e))?;
+                self.data_desc.clear();
+                self.global_data_ids.insert(name.clone(), data_id);
+            }
+            if let Statement::VarDecl(bolide_parser::VarDecl { name, ty: Some(ty), .. }) = stmt {
+                self.global_var_types.insert(name.clone(), ty.clone());
+            }
+            if let Statement::VarDecl(decl) = stmt {
+                if decl.thread_local {
+                    self.thread_locals.push(decl.clone());
+                }
+            }
+        }
+        Ok(())
+    }
 
+    /// 生成目标文件
+    fn finish(self) -> Result<AotCompileResult, String> {
         // 收集外部库列表 (去重)
         let extern_libs: Vec<String> = self.extern_funcs.values()
             .map(|(lib_path, _)| lib_path.clone())
@@ -370,12 +829,15 @@ impl AotCompiler {
             .collect();
 
         // 生成目标文件
+        let target_triple = self.module.isa().triple().to_string();
         let product = self.module.finish();
         let object_code = product.emit().map_err(|e| format!("Emit error: {}", e))?;
 
         Ok(AotCompileResult {
             object_code,
             extern_libs,
+            warnings: self.warnings,
+            target_triple,
         })
     }
 
@@ -385,6 +847,7 @@ impl AotCompiler {
             BolideType::Int => types::I64,
             BolideType::Float => types::F64,
             BolideType::Bool => types::I64,
+            BolideType::Duration | BolideType::Deadline => types::I64,
             BolideType::Str => self.ptr_type,
             BolideType::BigInt => self.ptr_type,
             BolideType::Decimal => self.ptr_type,
@@ -396,54 +859,35 @@ impl AotCompiler {
             BolideType::FuncSig(_, _) => self.ptr_type,
             BolideType::List(_) => self.ptr_type,
             BolideType::Dict(_, _) => self.ptr_type,
+            BolideType::WeakDict(_, _) => self.ptr_type,
             BolideType::Tuple(_) => self.ptr_type,
             BolideType::Custom(_) => self.ptr_type,
             BolideType::Weak(_) => self.ptr_type,
             BolideType::Unowned(_) => self.ptr_type,
+            BolideType::PackedList(_) | BolideType::PackedRef(_) => self.ptr_type,
         }
     }
 
     /// 处理 import 语句
     fn process_imports(&mut self, program: &Program) -> Result<Program, String> {
         let mut merged_statements = Vec::new();
-        let mut imported_files: HashSet<String> = HashSet::new();
-
-        for stmt in &program.statements {
-            if let Statement::Import(import) = stmt {
-                if let Some(ref file_path) = import.file_path {
-                    if imported_files.contains(file_path) {
-                        continue;
-                    }
-                    imported_files.insert(file_path.clone());
 
-                    let module_name = Self::extract_module_name(file_path);
-                    self.modules.insert(module_name.clone(), file_path.clone());
-
-                    let imported = self.load_module(file_path)?;
-
-                    for imp_stmt in imported.statements {
-                        match imp_stmt {
-                            Statement::FuncDef(mut func) => {
-                                func.name = format!("@{}_{}", module_name, func.name);
-                                merged_statements.push(Statement::FuncDef(func));
-                            }
-                            Statement::ClassDef(mut class) => {
-                                class.name = format!("@{}_{}", module_name, class.name);
-                                merged_statements.push(Statement::ClassDef(class));
-                            }
-                            Statement::ExternBlock(ext) => {
-                                merged_statements.push(Statement::ExternBlock(ext));
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-            }
+        for file_path in &crate::modules::import_files(program) {
+            let module_name = Self::extract_module_name(file_path);
+            let mut imported = self.load_module(file_path)?;
+            self.modules.insert(module_name.clone(), ImportedModule::new(file_path, &imported));
+            // 匿名函数提升后随模块的其他函数一起加上前缀
+            lambda::lift_lambdas(&mut imported, 0)?;
+            merged_statements.extend(modules::module_items(imported, &module_name, true));
         }
 
-        for stmt in &program.statements {
-            merged_statements.push(stmt.clone());
-        }
+        // 匿名函数提升为命名函数；测试块不编译进程序
+        let mut main = test_blocks::without_tests(program);
+        lambda::lift_lambdas(&mut main, 0)?;
+        // 主程序里 `模块.类` 形式的类型标注换成导入后的类名
+        let mut statements = main.statements;
+        modules::resolve_qualified_types(&mut statements, &self.modules)?;
+        merged_statements.extend(statements);
 
         Ok(Program { statements: merged_statements })
     }
@@ -459,8 +903,9 @@ impl AotCompiler {
     fn load_module(&self, file_path: &str) -> Result<Program, String> {
         let content = std::fs::read_to_string(file_path)
             .map_err(|e| format!("Failed to load module '{}': {}", file_path, e))?;
-        bolide_parser::parse_source(&content)
-            .map_err(|e| format!("Failed to parse module '{}': {}", file_path, e))
+        bolide_parser::parse_source_for_target(&content, &self.cfg_target)
+            .map(|(program, _warnings)| program)
+            .map_err(|e| modules::parse_error(file_path, *e))
     }
 
     /// 注册内置函数
@@ -509,6 +954,30 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("print_string".to_string(), id);
 
+        // printr 的不换行打印：bolide_print_*_inline(value) -> void
+        let inline_prints: [(&str, types::Type); 7] = [
+            ("print_int_inline", types::I64),
+            ("print_float_inline", types::F64),
+            ("print_bool_inline", types::I64),
+            ("print_bigint_inline", ptr),
+            ("print_decimal_inline", ptr),
+            ("print_string_inline", ptr),
+            ("print_dynamic_inline", ptr),
+        ];
+        for (name, param) in inline_prints {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(param));
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // bolide_print_flush() -> void
+        let sig = self.module.make_signature();
+        let id = self.module.declare_function("bolide_print_flush", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("print_flush".to_string(), id);
+
         self.register_more_builtins()
     }
 
@@ -530,6 +999,79 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("input_prompt".to_string(), id);
 
+        // async 函数里的 input() / read_file()：在 I/O 线程上读取，io_await 挂起等待结果
+        for (name, internal) in [("bolide_input_async", "input_async"), ("bolide_file_read", "file_read"),
+            ("bolide_file_read_async", "file_read_async"), ("bolide_io_await", "io_await")] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ptr));
+            let id = self.module.declare_function(name, Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(internal.to_string(), id);
+        }
+
+        // bolide_file_write_lines(path, list)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_file_write_lines", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("file_write_lines".to_string(), id);
+
+        // bolide_file_write / bolide_file_append(path, contents) -> i64
+        for (name, internal) in [("bolide_file_write", "file_write"), ("bolide_file_append", "file_append")] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(name, Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(internal.to_string(), id);
+        }
+
+        // bolide_file_exists(path) -> i64、bolide_file_lines(path) -> list、bolide_file_remove(path) -> i64
+        for (name, internal, ret) in [("bolide_file_exists", "file_exists", types::I64), ("bolide_file_lines", "file_lines", ptr), ("bolide_file_remove", "file_remove", types::I64)] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ret));
+            let id = self.module.declare_function(name, Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(internal.to_string(), id);
+        }
+
+        // bolide_format_row(list, sep) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_format_row", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("format_row".to_string(), id);
+
+        // bolide_assert(i64, message)、bolide_assert_eq_*(a, b)
+        let assert_sigs = [
+            ("assert", types::I64, ptr),
+            ("assert_eq_int", types::I64, types::I64),
+            ("assert_eq_float", types::F64, types::F64),
+            ("assert_eq_str", ptr, ptr),
+            ("assert_eq_list", ptr, ptr),
+        ];
+        for (name, a, b) in assert_sigs {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(a));
+            sig.params.push(AbiParam::new(b));
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // bolide_set_io_workers(i64)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_set_io_workers", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("set_io_workers".to_string(), id);
+
         // bolide_string_from_int(i64) -> ptr
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(types::I64));
@@ -570,20 +1112,84 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("string_from_decimal".to_string(), id);
 
+        // ===== f-string 拼接缓冲 =====
+        // bolide_string_builder_new(i64) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_string_builder_new", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_builder_new".to_string(), id);
+
+        // bolide_string_builder_append_*(ptr, value)
+        let append_params = [
+            ("string_builder_append_str", ptr),
+            ("string_builder_append_int", types::I64),
+            ("string_builder_append_float", types::F64),
+            ("string_builder_append_bool", types::I64),
+            ("string_builder_append_bigint", ptr),
+            ("string_builder_append_decimal", ptr),
+            ("string_builder_append_dynamic", ptr),
+            ("string_builder_append_list", ptr),
+            ("string_builder_append_dict", ptr),
+            ("string_builder_append_tuple", ptr),
+        ];
+        for (name, param) in append_params {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.params.push(AbiParam::new(param));
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // bolide_string_builder_finish(ptr) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_string_builder_finish", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_builder_finish".to_string(), id);
+
+        // 回调测试：test_callback(callback, a, b) -> i64, map_int(callback, value) -> i64
+        for (name, ints) in [("test_callback", 2), ("map_int", 1)] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            for _ in 0..ints {
+                sig.params.push(AbiParam::new(types::I64));
+            }
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
         self.register_string_builtins()
     }
 
     fn register_string_builtins(&mut self) -> Result<(), String> {
         let ptr = self.ptr_type;
 
-        // bolide_string_literal(ptr, len) -> ptr
+        // bolide_string_literal(ptr, len) -> ptr（驻留）/ bolide_string_from_slice(ptr, len) -> ptr（新分配）
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
         sig.params.push(AbiParam::new(types::I64));
         sig.returns.push(AbiParam::new(ptr));
-        let id = self.module.declare_function("bolide_string_literal", Linkage::Import, &sig)
-            .map_err(|e| format!("{}", e))?;
-        self.functions.insert("string_literal".to_string(), id);
+        for name in ["string_literal", "string_from_slice"] {
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // bolide_string_as_cstr(ptr) -> char*（extern 的 cstr 参数）/ bolide_string_new(char*) -> ptr（cstr 返回值）
+        for (symbol, name) in [("bolide_string_as_cstr", "string_as_cstr"), ("bolide_string_new", "string_from_cstr")] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ptr));
+            let id = self.module.declare_function(symbol, Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
 
         // bolide_string_concat(ptr, ptr) -> ptr
         let mut sig = self.module.make_signature();
@@ -603,6 +1209,113 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("string_eq".to_string(), id);
 
+        // bolide_string_char_at(ptr, i64) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_string_char_at", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_char_at".to_string(), id);
+
+        // bolide_string_slice(ptr, i64, i64) -> ptr
+        // bolide_list_slice(ptr, i64, i64) -> ptr
+        for (name, internal) in [("bolide_string_slice", "string_slice"), ("bolide_list_slice", "list_slice")] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.params.push(AbiParam::new(types::I64));
+            sig.params.push(AbiParam::new(types::I64));
+            sig.returns.push(AbiParam::new(ptr));
+            let id = self.module.declare_function(name, Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(internal.to_string(), id);
+        }
+
+        // bolide_string_split(ptr, ptr) -> ptr
+        // bolide_string_contains(ptr, ptr) -> i64
+        // bolide_string_encode(ptr, ptr) -> ptr
+        for (name, internal, ret) in [("bolide_string_split", "string_split", ptr), ("bolide_string_contains", "string_contains", types::I64), ("bolide_string_encode", "string_encode", ptr)] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ret));
+            let id = self.module.declare_function(name, Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(internal.to_string(), id);
+        }
+
+        // bolide_string_trim / bolide_string_upper / bolide_string_lower(ptr) -> ptr
+        for (name, internal) in [("bolide_string_trim", "string_trim"), ("bolide_string_upper", "string_upper"), ("bolide_string_lower", "string_lower")] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ptr));
+            let id = self.module.declare_function(name, Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(internal.to_string(), id);
+        }
+
+        // bolide_string_replace(ptr, ptr, ptr) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_string_replace", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_replace".to_string(), id);
+
+        // bolide_bytes_decode(list, ptr, ptr) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_bytes_decode", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("bytes_decode".to_string(), id);
+
+        // bolide_bytes_decode_latin1(list) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_bytes_decode_latin1", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("bytes_decode_latin1".to_string(), id);
+
+        // bolide_string_len(ptr) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_string_len", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_len".to_string(), id);
+
+        // bolide_string_intern(ptr) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_string_intern", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_intern".to_string(), id);
+
+        // bolide_string_intern_stats() / bolide_string_intern_clear() -> i64
+        for name in ["string_intern_stats", "string_intern_clear"] {
+            let mut sig = self.module.make_signature();
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // bolide_string_cmp(ptr, ptr) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_string_cmp", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_cmp".to_string(), id);
+
         // bolide_string_release(ptr) -> void
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -634,11 +1347,28 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("string_to_float".to_string(), id);
 
-        self.register_bigint_builtins()
-    }
-
-    fn register_bigint_builtins(&mut self) -> Result<(), String> {
-        let ptr = self.ptr_type;
+        // bolide_string_parse_int(ptr, radix: i64) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_string_parse_int", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_parse_int".to_string(), id);
+
+        // bolide_string_parse_float(ptr) -> f64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::F64));
+        let id = self.module.declare_function("bolide_string_parse_float", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_parse_float".to_string(), id);
+
+        self.register_bigint_builtins()
+    }
+
+    fn register_bigint_builtins(&mut self) -> Result<(), String> {
+        let ptr = self.ptr_type;
 
         // bolide_bigint_from_i64(i64) -> ptr
         let mut sig = self.module.make_signature();
@@ -717,6 +1447,16 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("list_new".to_string(), id);
 
+        // bolide_list_from_const_array(elem_type: i8, data: ptr, count: i64) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I8));
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_list_from_const_array", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_from_const_array".to_string(), id);
+
         // bolide_list_push(ptr, i64) -> void
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -725,6 +1465,14 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("list_push".to_string(), id);
 
+        // bolide_list_extend(ptr, ptr) -> void
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_list_extend", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_extend".to_string(), id);
+
         // bolide_list_len(ptr) -> i64
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -751,6 +1499,99 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("list_set".to_string(), id);
 
+        // float 元素按 f64 传值，排序和打印按列表的元素类型解释槽位
+        let f64_ty = types::F64;
+        let list_functions: [(&str, &[Type], Option<Type>); 5] = [
+            ("list_push_f64", &[ptr, f64_ty], None),
+            ("list_get_f64", &[ptr, types::I64], Some(f64_ty)),
+            ("list_set_f64", &[ptr, types::I64, f64_ty], Some(types::I64)),
+            ("list_sort", &[ptr], None),
+            ("print_list", &[ptr], None),
+        ];
+        for (name, params, ret) in list_functions {
+            let mut sig = self.module.make_signature();
+            for &param in params {
+                sig.params.push(AbiParam::new(param));
+            }
+            if let Some(ret) = ret {
+                sig.returns.push(AbiParam::new(ret));
+            }
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // 其余列表方法：元素按 i64 槽位传递，float 值由调用方 bitcast
+        let slot_functions: [(&str, &[Type], Option<Type>); 13] = [
+            ("list_pop", &[ptr], Some(types::I64)),
+            ("list_insert", &[ptr, types::I64, types::I64], None),
+            ("list_remove", &[ptr, types::I64], Some(types::I64)),
+            ("list_clear", &[ptr], None),
+            ("list_reverse", &[ptr], None),
+            ("list_contains", &[ptr, types::I64], Some(types::I64)),
+            ("list_index_of", &[ptr, types::I64], Some(types::I64)),
+            ("list_count", &[ptr, types::I64], Some(types::I64)),
+            ("list_is_empty", &[ptr], Some(types::I64)),
+            ("list_first", &[ptr], Some(types::I64)),
+            ("list_last", &[ptr], Some(types::I64)),
+            ("list_dedup", &[ptr], None),
+            ("list_unique", &[ptr], Some(ptr)),
+        ];
+        for (name, params, ret) in slot_functions {
+            let mut sig = self.module.make_signature();
+            for &param in params {
+                sig.params.push(AbiParam::new(param));
+            }
+            if let Some(ret) = ret {
+                sig.returns.push(AbiParam::new(ret));
+            }
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // map / filter / reduce、sort_by 等：函数值按 i64 传入，由运行时通过 bolide_closure_call 回调
+        let higher_order: [(&str, &[Type], Option<Type>); 8] = [
+            ("list_map", &[ptr, types::I64, types::I8], Some(ptr)),
+            ("list_filter", &[ptr, types::I64], Some(ptr)),
+            ("list_reduce", &[ptr, types::I64, types::I64, types::I8], Some(types::I64)),
+            ("list_sort_by", &[ptr, types::I64], None),
+            ("list_sort_by_key", &[ptr, types::I64, types::I8], None),
+            ("list_min_by", &[ptr, types::I64, types::I8], Some(types::I64)),
+            ("list_max_by", &[ptr, types::I64, types::I8], Some(types::I64)),
+            ("list_group_by", &[ptr, types::I64, types::I8], Some(ptr)),
+        ];
+        for (name, params, ret) in higher_order {
+            let mut sig = self.module.make_signature();
+            for &param in params {
+                sig.params.push(AbiParam::new(param));
+            }
+            if let Some(ret) = ret {
+                sig.returns.push(AbiParam::new(ret));
+            }
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // 函数值：bind 生成的绑定函数有引用计数，普通函数地址 retain / release 时不变
+        let closures: [(&str, &[Type], Option<Type>); 4] = [
+            ("bind", &[types::I64, ptr, ptr, types::I64], Some(types::I64)),
+            ("closure_call", &[types::I64, ptr, types::I64], Some(types::I64)),
+            ("func_retain", &[types::I64], Some(types::I64)),
+            ("func_release", &[types::I64], None),
+        ];
+        for (name, params, ret) in closures {
+            let mut sig = self.module.make_signature();
+            for &param in params {
+                sig.params.push(AbiParam::new(param));
+            }
+            sig.returns.extend(ret.map(AbiParam::new));
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
         // bolide_list_release(ptr) -> void
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -758,6 +1599,22 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("list_release".to_string(), id);
 
+        // bolide_list_clone(ptr) -> ptr（与 JIT 相同，复制变量时整表拷贝）
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_list_clone", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_clone".to_string(), id);
+
+        // bolide_list_retain(ptr) -> ptr（`--share-containers` 时绑定列表只增加引用计数）
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_list_retain", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_retain".to_string(), id);
+
         self.register_memory_builtins()
     }
 
@@ -795,6 +1652,41 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("object_release".to_string(), id);
 
+        // bolide_object_refcount(ptr) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_object_refcount", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("object_refcount".to_string(), id);
+
+        // object_clone(ptr) -> ptr  (retain 后返回同一对象)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("object_clone", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("object_clone".to_string(), id);
+
+        // object_to_string(ptr, formatter) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("object_to_string", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("object_to_string".to_string(), id);
+
+        // object_equals(a, b, eq) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("object_equals", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("object_equals".to_string(), id);
+
         self.register_tuple_builtins()
     }
 
@@ -827,12 +1719,22 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("tuple_get".to_string(), id);
 
-        // bolide_tuple_free(ptr) -> void
+        // bolide_tuple_len(ptr) -> i64
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
-        let id = self.module.declare_function("bolide_tuple_free", Linkage::Import, &sig)
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_tuple_len", Linkage::Import, &sig)
             .map_err(|e| format!("{}", e))?;
-        self.functions.insert("tuple_free".to_string(), id);
+        self.functions.insert("tuple_len".to_string(), id);
+
+        // bolide_tuple_free(ptr) / bolide_print_tuple(ptr) -> void
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        for name in ["tuple_free", "print_tuple"] {
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
 
         // bolide_tuple_debug_stats() -> void
         let mut sig = self.module.make_signature();
@@ -840,6 +1742,142 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("tuple_debug_stats".to_string(), id);
 
+        // bolide_string_debug_stats() / bolide_weak_debug_stats() -> void
+        for name in ["string_debug_stats", "weak_debug_stats"] {
+            let sig = self.module.make_signature();
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // bolide_object_debug_enable(i64) -> i64 / bolide_object_live_count() -> i64 / bolide_object_dump_live()
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_object_debug_enable", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("object_debug_enable".to_string(), id);
+        let mut sig = self.module.make_signature();
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_object_live_count", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("object_live_count".to_string(), id);
+        let sig = self.module.make_signature();
+        let id = self.module.declare_function("bolide_object_dump_live", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("object_dump_live".to_string(), id);
+
+        // ===== 计时 =====
+        // bolide_timer_start() / bolide_alloc_snapshot() / bolide_measure_begin() -> i64
+        for name in ["timer_start", "alloc_snapshot", "measure_begin"] {
+            let mut sig = self.module.make_signature();
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // bolide_timer_elapsed_ns(i64) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_timer_elapsed_ns", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("timer_elapsed_ns".to_string(), id);
+
+        // bolide_timer_elapsed_ms(i64) -> f64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::F64));
+        let id = self.module.declare_function("bolide_timer_elapsed_ms", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("timer_elapsed_ms".to_string(), id);
+
+        // bolide_deadline(i64) / bolide_deadline_remaining(i64) -> i64：duration 和时间点都是纳秒
+        for name in ["deadline", "deadline_remaining"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(types::I64));
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // bolide_string_from_duration(i64) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_string_from_duration", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_from_duration".to_string(), id);
+
+        // bolide_measure_end(label_ptr, label_len, start, alloc_start, early) -> void
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        for _ in 0..4 {
+            sig.params.push(AbiParam::new(types::I64));
+        }
+        let id = self.module.declare_function("bolide_measure_end", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("measure_end".to_string(), id);
+
+        // ===== 哈希 =====
+        // bolide_hash_int(i64) / bolide_hash_float(f64) / bolide_hash_string(ptr) / ... -> i64
+        let hash_params = [
+            ("hash_int", types::I64),
+            ("hash_float", types::F64),
+            ("hash_string", ptr),
+            ("hash_bigint", ptr),
+            ("hash_decimal", ptr),
+            ("hash_dynamic", ptr),
+        ];
+        for (name, param) in hash_params {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(param));
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // bolide_hash_combine(i64, i64) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_hash_combine", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("hash_combine".to_string(), id);
+
+        // ===== 列表聚合 =====
+        // bolide_list_sum_int(list) -> i64, bolide_list_sum_float(list) -> f64, ...
+        let aggregate_returns = [
+            ("list_sum_int", types::I64),
+            ("list_min_int", types::I64),
+            ("list_max_int", types::I64),
+            ("list_sum_float", types::F64),
+            ("list_min_float", types::F64),
+            ("list_max_float", types::F64),
+            ("list_mean", types::F64),
+            ("list_mean_float", types::F64),
+            ("list_mean_bigint", types::F64),
+            ("list_sum_bigint", ptr),
+            ("list_min_bigint", ptr),
+            ("list_max_bigint", ptr),
+            ("list_sum_decimal", ptr),
+            ("list_min_decimal", ptr),
+            ("list_max_decimal", ptr),
+            ("list_mean_decimal", ptr),
+        ];
+        for (name, ret) in aggregate_returns {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ret));
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
         self.register_dict_builtins()
     }
 
@@ -873,6 +1911,57 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("dict_get".to_string(), id);
 
+        // bolide_dict_len(ptr) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_dict_len", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("dict_len".to_string(), id);
+
+        // 其余字典函数：(名字, 参数, 返回值)，运行时符号为 bolide_ 前缀
+        let i64_ty = types::I64;
+        let dict_functions: [(&str, &[Type], Option<Type>); 13] = [
+            ("dict_new_default", &[types::I8, types::I8], Some(ptr)),
+            ("dict_get_or_insert_empty", &[ptr, i64_ty, types::I8], Some(i64_ty)),
+            ("dict_retain", &[ptr], None),
+            ("dict_release", &[ptr], None),
+            ("dict_clone", &[ptr], Some(ptr)),
+            ("dict_contains", &[ptr, i64_ty], Some(i64_ty)),
+            ("dict_remove", &[ptr, i64_ty], Some(i64_ty)),
+            ("dict_is_empty", &[ptr], Some(i64_ty)),
+            ("dict_clear", &[ptr], None),
+            ("dict_keys", &[ptr], Some(ptr)),
+            ("dict_values", &[ptr], Some(ptr)),
+            ("dict_iter", &[ptr], Some(ptr)),
+            ("print_dict", &[ptr], None),
+        ];
+        // 弱键字典：键是对象指针
+        let weak_dict_functions: [(&str, &[Type], Option<Type>); 10] = [
+            ("weakdict_new", &[types::I8], Some(ptr)),
+            ("weakdict_release", &[ptr], None),
+            ("weakdict_clone", &[ptr], Some(ptr)),
+            ("weakdict_set", &[ptr, ptr, i64_ty], None),
+            ("weakdict_get", &[ptr, ptr], Some(i64_ty)),
+            ("weakdict_contains", &[ptr, ptr], Some(i64_ty)),
+            ("weakdict_remove", &[ptr, ptr], Some(i64_ty)),
+            ("weakdict_len", &[ptr], Some(i64_ty)),
+            ("weakdict_is_empty", &[ptr], Some(i64_ty)),
+            ("weakdict_compact", &[ptr], Some(i64_ty)),
+        ];
+        for (name, params, ret) in dict_functions.into_iter().chain(weak_dict_functions) {
+            let mut sig = self.module.make_signature();
+            for &param in params {
+                sig.params.push(AbiParam::new(param));
+            }
+            if let Some(ret) = ret {
+                sig.returns.push(AbiParam::new(ret));
+            }
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
         self.register_decimal_builtins()
     }
 
@@ -887,6 +1976,14 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("decimal_from_f64".to_string(), id);
 
+        // bolide_decimal_from_i64(i64) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_decimal_from_i64", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("decimal_from_i64".to_string(), id);
+
         // decimal 二元运算: add, sub, mul, div, rem
         for op in &["add", "sub", "mul", "div", "rem"] {
             let mut sig = self.module.make_signature();
@@ -900,6 +1997,60 @@ impl AotCompiler {
             self.functions.insert(internal_name, id);
         }
 
+        // decimal 取整和取负: round(ptr, i64) -> ptr, floor / ceil / neg(ptr) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_decimal_round", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("decimal_round".to_string(), id);
+
+        // bolide_decimal_round_dp_mode(ptr, places: i64, mode: i64) -> ptr（d.round(places, mode)，mode 为 -1 时用线程模式）
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_decimal_round_dp_mode", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("decimal_round_dp_mode".to_string(), id);
+        for op in &["floor", "ceil", "neg"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ptr));
+            let id = self.module.declare_function(&format!("bolide_decimal_{}", op), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(format!("decimal_{}", op), id);
+        }
+
+        // bolide_decimal_from_string(ptr) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_decimal_from_string", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("decimal_from_string".to_string(), id);
+
+        // bolide_decimal_set_rounding / bolide_decimal_set_div_precision(i64) -> i64
+        for op in &["set_rounding", "set_div_precision"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(types::I64));
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(&format!("bolide_decimal_{}", op), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(format!("decimal_{}", op), id);
+        }
+
+        // bolide_float_round(f64, i64) -> f64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::F64));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::F64));
+        let id = self.module.declare_function("bolide_float_round", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("float_round".to_string(), id);
+
         // decimal 比较运算: eq, lt, le, gt, ge
         for op in &["eq", "lt", "le", "gt", "ge"] {
             let mut sig = self.module.make_signature();
@@ -919,21 +2070,65 @@ impl AotCompiler {
     fn register_async_builtins(&mut self) -> Result<(), String> {
         let ptr = self.ptr_type;
 
-        // bolide_coroutine_spawn_int(fn_ptr) -> ptr
+        // bolide_coroutine_spawn_{int,float,ptr}(fn_ptr) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        for suffix in ["int", "float", "ptr"] {
+            let id = self.module.declare_function(&format!("bolide_coroutine_spawn_{}", suffix), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(format!("coroutine_spawn_{}", suffix), id);
+        }
+
+        // bolide_coroutine_spawn_{int,float,ptr}_with_env(fn_ptr, env) -> ptr
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
         sig.returns.push(AbiParam::new(ptr));
-        let id = self.module.declare_function("bolide_coroutine_spawn_int", Linkage::Import, &sig)
+        for suffix in ["int", "float", "ptr"] {
+            let id = self.module.declare_function(&format!("bolide_coroutine_spawn_{}_with_env", suffix), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(format!("coroutine_spawn_{}_with_env", suffix), id);
+        }
+
+        // bolide_coroutine_await_{int,float,ptr}(ptr) -> i64 / f64 / ptr
+        for (suffix, ret) in [("int", types::I64), ("float", types::F64), ("ptr", ptr)] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ret));
+            let id = self.module.declare_function(&format!("bolide_coroutine_await_{}", suffix), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(format!("coroutine_await_{}", suffix), id);
+        }
+
+        // bolide_coroutine_free(ptr)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_coroutine_free", Linkage::Import, &sig)
             .map_err(|e| format!("{}", e))?;
-        self.functions.insert("coroutine_spawn_int".to_string(), id);
+        self.functions.insert("coroutine_free".to_string(), id);
 
-        // bolide_coroutine_await_int(ptr) -> i64
+        // bolide_coroutine_set_name(ptr, ptr)
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_coroutine_set_name", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("coroutine_set_name".to_string(), id);
+
+        // bolide_coroutine_cancelled() -> i64
+        let mut sig = self.module.make_signature();
         sig.returns.push(AbiParam::new(types::I64));
-        let id = self.module.declare_function("bolide_coroutine_await_int", Linkage::Import, &sig)
+        let id = self.module.declare_function("bolide_coroutine_cancelled", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("coroutine_cancelled".to_string(), id);
+
+        // bolide_coroutine_set_workers(i64)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_coroutine_set_workers", Linkage::Import, &sig)
             .map_err(|e| format!("{}", e))?;
-        self.functions.insert("coroutine_await_int".to_string(), id);
+        self.functions.insert("coroutine_set_workers".to_string(), id);
 
         self.register_channel_builtins()
     }
@@ -956,6 +2151,15 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("channel_recv".to_string(), id);
 
+        // bolide_channel_recv_opt(ptr, out_ptr) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_channel_recv_opt", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("channel_recv_opt".to_string(), id);
+
         // bolide_channel_send(ptr, i64) -> void
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -964,51 +2168,246 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("channel_send".to_string(), id);
 
-        // bolide_channel_select(ptr, i64, i64, ptr) -> i64
+        // bolide_channel_send_ptr(ptr, i64, tag: u8) -> i64：RC 值入队前按 tag retain 一份
         let mut sig = self.module.make_signature();
-        sig.params.push(AbiParam::new(ptr));   // channels array
-        sig.params.push(AbiParam::new(types::I64)); // count
-        sig.params.push(AbiParam::new(types::I64)); // timeout
-        sig.params.push(AbiParam::new(ptr));   // value out ptr
-        sig.returns.push(AbiParam::new(types::I64)); // selected index
-        let id = self.module.declare_function("bolide_channel_select", Linkage::Import, &sig)
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I8));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_channel_send_ptr", Linkage::Import, &sig)
             .map_err(|e| format!("{}", e))?;
-        self.functions.insert("channel_select".to_string(), id);
-
-        self.register_pool_builtins()
-    }
-
-    fn register_pool_builtins(&mut self) -> Result<(), String> {
-        let ptr = self.ptr_type;
+        self.functions.insert("channel_send_ptr".to_string(), id);
 
-        // bolide_pool_create(i64) -> ptr
+        // bolide_channel_create_buffered(i64) -> ptr
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(types::I64));
         sig.returns.push(AbiParam::new(ptr));
-        let id = self.module.declare_function("bolide_pool_create", Linkage::Import, &sig)
+        let id = self.module.declare_function("bolide_channel_create_buffered", Linkage::Import, &sig)
             .map_err(|e| format!("{}", e))?;
-        self.functions.insert("pool_create".to_string(), id);
+        self.functions.insert("channel_create_buffered".to_string(), id);
 
-        // bolide_pool_enter(ptr) -> void
+        // bolide_channel_send_all(ptr, list) -> i64
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
-        let id = self.module.declare_function("bolide_pool_enter", Linkage::Import, &sig)
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_channel_send_all", Linkage::Import, &sig)
             .map_err(|e| format!("{}", e))?;
-        self.functions.insert("pool_enter".to_string(), id);
+        self.functions.insert("channel_send_all".to_string(), id);
 
-        // bolide_pool_exit() -> void
+        // bolide_channel_recv_n(ptr, i64, list) -> i64
         let mut sig = self.module.make_signature();
-        let id = self.module.declare_function("bolide_pool_exit", Linkage::Import, &sig)
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_channel_recv_n", Linkage::Import, &sig)
             .map_err(|e| format!("{}", e))?;
-        self.functions.insert("pool_exit".to_string(), id);
+        self.functions.insert("channel_recv_n".to_string(), id);
 
-        // bolide_pool_destroy(ptr) -> void
+        // bolide_channel_drain(ptr, i8) -> list
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
-        let id = self.module.declare_function("bolide_pool_destroy", Linkage::Import, &sig)
-            .map_err(|e| format!("{}", e))?;
+        sig.params.push(AbiParam::new(types::I8));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_channel_drain", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("channel_drain".to_string(), id);
+
+        // bolide_channel_close(ptr)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_channel_close", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("channel_close".to_string(), id);
+
+        // bolide_channel_select(ptr, i64, i64, ptr) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));   // channels array
+        sig.params.push(AbiParam::new(types::I64)); // count
+        sig.params.push(AbiParam::new(types::I64)); // timeout
+        sig.params.push(AbiParam::new(ptr));   // value out ptr
+        sig.returns.push(AbiParam::new(types::I64)); // selected index
+        let id = self.module.declare_function("bolide_channel_select", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("channel_select".to_string(), id);
+
+        // bolide_channel_select_list(list, timeout, value_ptr) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_channel_select_list", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("channel_select_list".to_string(), id);
+
+        self.register_pool_builtins()
+    }
+
+    fn register_pool_builtins(&mut self) -> Result<(), String> {
+        let ptr = self.ptr_type;
+
+        // bolide_pool_create(i64) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_pool_create", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("pool_create".to_string(), id);
+
+        // bolide_pool_enter(ptr) -> void
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_pool_enter", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("pool_enter".to_string(), id);
+
+        // bolide_pool_exit_checked(ptr) -> void
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_pool_exit_checked", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("pool_exit".to_string(), id);
+
+        // bolide_pool_destroy(ptr) -> void
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_pool_destroy", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
         self.functions.insert("pool_destroy".to_string(), id);
 
+        // spawn / join：按目标函数的返回值选 _int / _float / _ptr，pool 块里走线程池（同 JIT）
+        for (suffix, ret) in [("int", types::I64), ("float", types::F64), ("ptr", ptr)] {
+            for kind in ["thread", "pool"] {
+                let signatures: [(String, &[Type], Type); 3] = [
+                    (format!("{}_spawn_{}", kind, suffix), &[ptr], ptr),
+                    (format!("{}_spawn_{}_with_env", kind, suffix), &[ptr, ptr], ptr),
+                    (format!("{}_join_{}", kind, suffix), &[ptr], ret),
+                ];
+                for (name, params, ret) in signatures {
+                    let mut sig = self.module.make_signature();
+                    for &param in params {
+                        sig.params.push(AbiParam::new(param));
+                    }
+                    sig.returns.push(AbiParam::new(ret));
+                    let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                        .map_err(|e| format!("{}", e))?;
+                    self.functions.insert(name, id);
+                }
+            }
+        }
+
+        // bolide_thread_set_name / bolide_pool_set_name(handle, name)：名字的所有权交给运行时
+        for name in ["thread_set_name", "pool_set_name"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.params.push(AbiParam::new(ptr));
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // bolide_pool_is_active() -> i64
+        let mut sig = self.module.make_signature();
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_pool_is_active", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("pool_is_active".to_string(), id);
+
+        // bolide_thread_id() -> i64
+        let mut sig = self.module.make_signature();
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_thread_id", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("thread_id".to_string(), id);
+
+        // bolide_live_threads() -> i64
+        let mut sig = self.module.make_signature();
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_live_threads", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("live_threads".to_string(), id);
+
+        // bolide_tls_alloc(init: ptr, dtor: ptr) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_tls_alloc", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("tls_alloc".to_string(), id);
+
+        // bolide_tls_get(key: i64) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_tls_get", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("tls_get".to_string(), id);
+
+        // bolide_tls_thread_exit()
+        let sig = self.module.make_signature();
+        let id = self.module.declare_function("bolide_tls_thread_exit", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("tls_thread_exit".to_string(), id);
+
+        // bolide_platform() -> ptr、bolide_temp_dir() -> ptr
+        let mut sig = self.module.make_signature();
+        sig.returns.push(AbiParam::new(ptr));
+        for name in ["platform", "temp_dir"] {
+            let id = self.module.declare_function(&format!("bolide_{}", name), Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // bolide_version() -> ptr
+        let mut sig = self.module.make_signature();
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_version", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("version".to_string(), id);
+
+        // bolide_version_at_least(ptr) -> i64 / bolide_has_feature(ptr) -> i64
+        for (name, internal) in [("bolide_version_at_least", "version_at_least"), ("bolide_has_feature", "has_feature")] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(name, Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(internal.to_string(), id);
+        }
+
+        // bolide_register_features(names: ptr, len: usize)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bolide_register_features", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("register_features".to_string(), id);
+
+        // bolide_error_code() -> i64 / bolide_error_message() -> ptr / bolide_error_clear()
+        for (name, internal, ret) in [("bolide_error_code", "error_code", Some(types::I64)), ("bolide_error_message", "error_message", Some(ptr)), ("bolide_error_clear", "error_clear", None)] {
+            let mut sig = self.module.make_signature();
+            sig.returns.extend(ret.map(AbiParam::new));
+            let id = self.module.declare_function(name, Linkage::Import, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(internal.to_string(), id);
+        }
+
+        // bolide_exit(code: i64)，不返回
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bolide_exit", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("exit".to_string(), id);
+
+        // bolide_shutdown()：顶层代码返回后等待分离线程（有超时），只在入口 main 里调用
+        let sig = self.module.make_signature();
+        let id = self.module.declare_function("bolide_shutdown", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("__shutdown".to_string(), id);
+
         self.register_scope_builtins()
     }
 
@@ -1019,6 +2418,18 @@ impl AotCompiler {
             .map_err(|e| format!("{}", e))?;
         self.functions.insert("scope_enter".to_string(), id);
 
+        // bolide_scope_enter_fail_fast() -> void
+        let id = self.module.declare_function("bolide_scope_enter_fail_fast", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("scope_enter_fail_fast".to_string(), id);
+
+        // bolide_scope_register(ptr) -> void
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(self.ptr_type));
+        let id = self.module.declare_function("bolide_scope_register", Linkage::Import, &sig)
+            .map_err(|e| format!("{}", e))?;
+        self.functions.insert("scope_register".to_string(), id);
+
         // bolide_scope_exit() -> void
         let mut sig = self.module.make_signature();
         let id = self.module.declare_function("bolide_scope_exit", Linkage::Import, &sig)
@@ -1034,10 +2445,12 @@ impl AotCompiler {
             if let ExternDecl::Function(func) = decl {
                 let mut sig = self.module.make_signature();
                 for param in &func.params {
-                    sig.params.push(AbiParam::new(self.ctype_to_cranelift(&param.ty)));
+                    sig.params.push(AbiParam::new(Self::ctype_to_cranelift(&param.ty, self.ptr_type)));
                 }
                 if let Some(ref ret_ty) = func.return_type {
-                    sig.returns.push(AbiParam::new(self.ctype_to_cranelift(ret_ty)));
+                    if !matches!(ret_ty, CType::Void) {
+                        sig.returns.push(AbiParam::new(Self::ctype_to_cranelift(ret_ty, self.ptr_type)));
+                    }
                 }
                 let id = self.module.declare_function(&func.name, Linkage::Import, &sig)
                     .map_err(|e| format!("{}", e))?;
@@ -1049,7 +2462,7 @@ impl AotCompiler {
     }
 
     /// CType 转换为 Cranelift 类型
-    fn ctype_to_cranelift(&self, ty: &CType) -> types::Type {
+    fn ctype_to_cranelift(ty: &CType, ptr_type: types::Type) -> types::Type {
         match ty {
             CType::Void => types::I64,
             CType::Char | CType::UChar | CType::I8 | CType::U8 => types::I8,
@@ -1060,13 +2473,21 @@ impl AotCompiler {
             CType::Float => types::F32,
             CType::Double => types::F64,
             CType::Bool => types::I8,
-            CType::Ptr(_) | CType::Array(_, _) | CType::FuncPtr { .. } => self.ptr_type,
-            CType::Struct(_) => self.ptr_type,
+            CType::Ptr(_) | CType::Array(_, _) | CType::FuncPtr { .. } => ptr_type,
+            CType::Struct(_) => ptr_type,
         }
     }
 
     /// 收集类定义
     fn collect_classes(&mut self, program: &Program) -> Result<(), String> {
+        let defs: Vec<&bolide_parser::ClassDef> = program.statements.iter()
+            .filter_map(|stmt| match stmt {
+                Statement::ClassDef(class) => Some(class),
+                _ => None,
+            })
+            .collect();
+        class_graph::check_classes(&defs)?;
+
         for stmt in &program.statements {
             if let Statement::ClassDef(class) = stmt {
                 let mut fields = Vec::new();
@@ -1082,13 +2503,13 @@ impl AotCompiler {
 
                 // 添加本类字段
                 for field in &class.fields {
-                    let size = 8; // 所有类型都是 8 字节
                     fields.push(FieldInfo {
                         name: field.name.clone(),
                         ty: field.ty.clone(),
                         offset,
+                        default: field.default_value.clone(),
                     });
-                    offset += size;
+                    offset += bolide_runtime::OBJECT_FIELD_SIZE;
                 }
 
                 let methods: Vec<String> = class.methods.iter()
@@ -1109,6 +2530,10 @@ impl AotCompiler {
 
     /// 声明函数
     fn declare_function(&mut self, func: &FuncDef) -> Result<(), String> {
+        let packed = |ty: &BolideType| matches!(ty, BolideType::PackedList(_));
+        if func.params.iter().any(|p| packed(&p.ty)) || func.return_type.as_ref().is_some_and(packed) {
+            return Err(PACKED_LIST_UNSUPPORTED.to_string());
+        }
         let mut sig = self.module.make_signature();
 
         for param in &func.params {
@@ -1121,7 +2546,7 @@ impl AotCompiler {
         }
 
         let func_id = self.module
-            .declare_function(&func.name, Linkage::Export, &sig)
+            .declare_function(&Self::symbol_name(&func.name), self.linkage_for(&func.name), &sig)
             .map_err(|e| format!("Declare function error: {}", e))?;
 
         self.functions.insert(func.name.clone(), func_id);
@@ -1149,11 +2574,16 @@ impl AotCompiler {
         sig.returns.push(AbiParam::new(self.ptr_type));
 
         let func_id = self.module
-            .declare_function(class_name, Linkage::Export, &sig)
+            .declare_function(&Self::symbol_name(class_name), self.linkage_for(class_name), &sig)
             .map_err(|e| format!("Declare constructor error: {}", e))?;
 
         self.functions.insert(class_name.to_string(), func_id);
         self.func_return_types.insert(class_name.to_string(), Some(BolideType::Custom(class_name.to_string())));
+        // 构造函数参数：字段的初始值就是参数默认值
+        let params = class_info.fields.iter()
+            .map(|f| Param { name: f.name.clone(), ty: f.ty.clone(), mode: ParamMode::Borrow, default: f.default.clone() })
+            .collect();
+        self.func_params.insert(class_name.to_string(), params);
         Ok(())
     }
 
@@ -1174,11 +2604,13 @@ impl AotCompiler {
                     }
 
                     let func_id = self.module
-                        .declare_function(&method_name, Linkage::Export, &sig)
+                        .declare_function(&Self::symbol_name(&method_name), self.linkage_for(&method_name), &sig)
                         .map_err(|e| format!("Declare method error: {}", e))?;
 
                     self.functions.insert(method_name.clone(), func_id);
                     self.func_return_types.insert(method_name.clone(), method.return_type.clone());
+                    // 不含隐式 self
+                    self.func_params.insert(method_name, method.params.clone());
                 }
             }
         }
@@ -1207,9 +2639,14 @@ impl AotCompiler {
                 }
             }
             Statement::Assign(a) => self.collect_spawn_in_expr(&a.value, targets),
+            Statement::Destructure(d) => self.collect_spawn_in_expr(&d.value, targets),
             Statement::If(i) => {
                 self.collect_spawn_in_expr(&i.condition, targets);
                 self.collect_spawn_in_stmts(&i.then_body, targets);
+                for (cond, body) in &i.elif_branches {
+                    self.collect_spawn_in_expr(cond, targets);
+                    self.collect_spawn_in_stmts(body, targets);
+                }
                 if let Some(ref else_body) = i.else_body {
                     self.collect_spawn_in_stmts(else_body, targets);
                 }
@@ -1219,8 +2656,39 @@ impl AotCompiler {
                 self.collect_spawn_in_stmts(&w.body, targets);
             }
             Statement::For(f) => {
+                self.collect_spawn_in_expr(&f.iter, targets);
                 self.collect_spawn_in_stmts(&f.body, targets);
             }
+            Statement::Pool(p) => {
+                self.collect_spawn_in_expr(&p.size, targets);
+                self.collect_spawn_in_stmts(&p.body, targets);
+            }
+            Statement::Measure(m) => {
+                self.collect_spawn_in_stmts(&m.body, targets);
+            }
+            Statement::AwaitScope(scope) => {
+                self.collect_spawn_in_stmts(&scope.body, targets);
+            }
+            Statement::Send(send) => self.collect_spawn_in_expr(&send.value, targets),
+            Statement::Match(m) => {
+                self.collect_spawn_in_expr(&m.subject, targets);
+                for arm in &m.arms {
+                    self.collect_spawn_in_stmts(&arm.body, targets);
+                }
+                if let Some(ref default) = m.default {
+                    self.collect_spawn_in_stmts(default, targets);
+                }
+            }
+            Statement::AsyncSelect(select) => {
+                for branch in &select.branches {
+                    let (expr, body) = match branch {
+                        bolide_parser::AsyncSelectBranch::Bind { expr, body, .. } => (expr, body),
+                        bolide_parser::AsyncSelectBranch::Expr { expr, body } => (expr, body),
+                    };
+                    self.collect_spawn_in_expr(expr, targets);
+                    self.collect_spawn_in_stmts(body, targets);
+                }
+            }
             Statement::FuncDef(f) => {
                 self.collect_spawn_in_stmts(&f.body, targets);
             }
@@ -1231,66 +2699,287 @@ impl AotCompiler {
 
     fn collect_spawn_in_expr(&self, expr: &Expr, targets: &mut HashSet<String>) {
         match expr {
-            Expr::Spawn(name, args) if !args.is_empty() => {
-                targets.insert(name.clone());
+            Expr::Spawn(name, args) => {
+                if !args.is_empty() {
+                    targets.insert(name.clone());
+                }
+                for arg in args {
+                    self.collect_spawn_in_expr(arg, targets);
+                }
             }
             Expr::BinOp(l, _, r) => {
                 self.collect_spawn_in_expr(l, targets);
                 self.collect_spawn_in_expr(r, targets);
             }
+            Expr::CompareChain(first, rest) => {
+                self.collect_spawn_in_expr(first, targets);
+                for (_, operand) in rest {
+                    self.collect_spawn_in_expr(operand, targets);
+                }
+            }
+            Expr::UnaryOp(_, operand) | Expr::NamedArg(_, operand) | Expr::Await(operand) | Expr::Member(operand, _) => {
+                self.collect_spawn_in_expr(operand, targets);
+            }
             Expr::Call(callee, args) => {
+                // 带参数的 async 调用也经 trampoline 启动协程
+                if let Expr::Ident(name) = callee.as_ref() {
+                    if self.async_funcs.contains(name) && !args.is_empty() {
+                        targets.insert(name.clone());
+                    }
+                }
                 self.collect_spawn_in_expr(callee, targets);
                 for arg in args {
                     self.collect_spawn_in_expr(arg, targets);
                 }
             }
+            Expr::Index(base, idx) => {
+                self.collect_spawn_in_expr(base, targets);
+                self.collect_spawn_in_expr(idx, targets);
+            }
+            Expr::List(items) | Expr::Tuple(items) | Expr::FString(items) | Expr::AwaitAll(items) => {
+                for item in items {
+                    self.collect_spawn_in_expr(item, targets);
+                }
+            }
+            Expr::Dict(pairs) => {
+                for (key, value) in pairs {
+                    self.collect_spawn_in_expr(key, targets);
+                    self.collect_spawn_in_expr(value, targets);
+                }
+            }
             _ => {}
         }
     }
 
-    /// 生成 trampolines
+    /// 声明 trampolines（函数体在所有函数之后定义，见 `define_trampolines`）
     fn generate_trampolines(&mut self, targets: &HashSet<String>) -> Result<(), String> {
+        // 排序保证 trampoline 名称和布局在每次编译中一致
+        let mut targets: Vec<&String> = targets.iter().collect();
+        targets.sort();
         for func_name in targets {
             if let Some(params) = self.func_params.get(func_name).cloned() {
                 if params.is_empty() {
                     continue;
                 }
-                self.create_trampoline(func_name, &params)?;
+                self.declare_trampoline(func_name, &params)?;
             }
         }
         Ok(())
     }
 
-    /// 创建单个 trampoline 函数
-    fn create_trampoline(&mut self, func_name: &str, params: &[Param]) -> Result<(), String> {
-        let trampoline_name = format!("__trampoline_{}_{}", func_name, self.trampoline_counter);
-        self.trampoline_counter += 1;
-
-        let env_size = (params.len() * 8) as i64;
-        let param_types: Vec<BolideType> = params.iter().map(|p| p.ty.clone()).collect();
-
-        // 声明 trampoline 函数
+    /// trampoline 签名：fn(env) -> 目标函数的返回值
+    fn trampoline_signature(&self, func_name: &str) -> Signature {
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(self.ptr_type));
         if let Some(Some(ret_ty)) = self.func_return_types.get(func_name) {
             sig.returns.push(AbiParam::new(self.bolide_type_to_cranelift(ret_ty)));
         }
+        sig
+    }
+
+    /// 声明单个 trampoline 函数
+    fn declare_trampoline(&mut self, func_name: &str, params: &[Param]) -> Result<(), String> {
+        let trampoline_name = format!("__trampoline_{}_{}", func_name, self.trampoline_counter);
+        self.trampoline_counter += 1;
 
+        let sig = self.trampoline_signature(func_name);
+        // 只在本目标文件内取地址；增量编译时各目标文件的 trampoline 可能重名
         let trampoline_id = self.module
-            .declare_function(&trampoline_name, Linkage::Export, &sig)
+            .declare_function(&trampoline_name, Linkage::Local, &sig)
+            .map_err(|e| format!("{}", e))?;
+
+        self.trampolines.insert(func_name.to_string(), TrampolineInfo {
+            func_id: trampoline_id,
+            param_types: params.iter().map(|p| p.ty.clone()).collect(),
+            env_size: (params.len() * 8) as i64,
+        });
+        self.functions.insert(trampoline_name, trampoline_id);
+        Ok(())
+    }
+
+    /// 定义所有 trampoline，集中放在代码段末尾
+    fn define_trampolines(&mut self) -> Result<(), String> {
+        let mut targets: Vec<String> = self.trampolines.keys().cloned().collect();
+        targets.sort();
+        for func_name in targets {
+            self.define_trampoline(&func_name)?;
+        }
+        Ok(())
+    }
+
+    /// 定义进程入口 `main`：注册特性表，调用顶层代码 `__main__`，释放全局变量，以 `__main__` 的返回值作为退出状态
+    ///
+    /// `__main__` 返回后先刷新输出缓冲区（释放全局变量出错时已打印的输出不丢），
+    /// 再和 `bolide run` 一样等待仍在运行的线程（`bolide_shutdown`）；
+    /// 经过 `bolide_exit` 退出，和 `bolide run` 一样按 `exit_status` 换算状态。
+    fn define_entry_stub(&mut self) -> Result<(), String> {
+        let mut sig = self.module.make_signature();
+        sig.returns.push(AbiParam::new(types::I32));
+        let entry_id = self.module.declare_function("main", Linkage::Export, &sig)
             .map_err(|e| format!("{}", e))?;
+        let main_id = *self.functions.get("__main__").ok_or("__main__ not declared")?;
+        let release_id = *self.functions.get(GLOBALS_RELEASE).ok_or("__globals_release not declared")?;
+        let exit_id = *self.functions.get("exit").ok_or("exit not declared")?;
+        let flush_id = *self.functions.get("print_flush").ok_or("print_flush not declared")?;
+        let shutdown_id = *self.functions.get("__shutdown").ok_or("__shutdown not declared")?;
+        let register_id = *self.functions.get("register_features").ok_or("register_features not declared")?;
+
+        // 特性表：以 \n 分隔的特性名
+        let features = features::feature_blob();
+        let features_id = self.module.declare_data("__bolide_features", Linkage::Local, false, false)
+            .map_err(|e| format!("Failed to declare feature table: {}", e))?;
+        self.data_desc.clear();
+        self.data_desc.define(features.as_bytes().to_vec().into_boxed_slice());
+        self.module.define_data(features_id, &self.data_desc)
+            .map_err(|e| format!("Failed to define feature table: {}", e))?;
+
+        self.ctx.func.signature = sig;
+        let mut fbc = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut self.ctx.func, &mut fbc);
+        let entry = builder.create_block();
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
+
+        let main_ref = self.module.declare_func_in_func(main_id, builder.func);
+        let release_ref = self.module.declare_func_in_func(release_id, builder.func);
+        let exit_ref = self.module.declare_func_in_func(exit_id, builder.func);
+        let flush_ref = self.module.declare_func_in_func(flush_id, builder.func);
+        let shutdown_ref = self.module.declare_func_in_func(shutdown_id, builder.func);
+        let register_ref = self.module.declare_func_in_func(register_id, builder.func);
+        let features_gv = self.module.declare_data_in_func(features_id, builder.func);
+        let features_ptr = builder.ins().symbol_value(self.ptr_type, features_gv);
+        let features_len = builder.ins().iconst(self.ptr_type, features.len() as i64);
+        builder.ins().call(register_ref, &[features_ptr, features_len]);
+        let call = builder.ins().call(main_ref, &[]);
+        let result = builder.inst_results(call)[0];
+        builder.ins().call(flush_ref, &[]);
+        builder.ins().call(shutdown_ref, &[]);
+        builder.ins().call(release_ref, &[]);
+        builder.ins().call(exit_ref, &[result]);
+        // bolide_exit 不返回
+        let zero = builder.ins().iconst(types::I32, 0);
+        builder.ins().return_(&[zero]);
+        builder.finalize();
+
+        self.module.define_function(entry_id, &mut self.ctx)
+            .map_err(|e| format!("Define entry error: {}", e))?;
+        self.module.clear_context(&mut self.ctx);
+        Ok(())
+    }
+
+    /// 声明 map / filter / reduce、列表 sort_by 等方法的回调和 bind 目标的借用适配函数 `__borrow_<函数名>`
+    ///
+    /// 运行时按借用方式传入元素、累加值和绑定参数，而 AOT 函数释放自己的参数；适配函数先 retain
+    /// 引用计数参数再调用目标函数。只有直接以函数名作为回调或 bind 的目标时才生成，
+    /// 与这些内置函数同名的用户函数不算。
+    fn declare_borrow_adapters(&mut self, program: &Program) -> Result<(), String> {
+        let builtins: Vec<&str> = ["map", "filter", "reduce", "bind"].into_iter()
+            .filter(|name| !self.func_params.contains_key(*name))
+            .collect();
+        let methods = ["sort_by", "sort_by_key", "min_by", "max_by", "group_by"];
+        let mut targets = Vec::new();
+        let mut visit = |expr: &Expr| {
+            if let Expr::Call(callee, args) = expr {
+                let takes_callback = match callee.as_ref() {
+                    Expr::Ident(builtin) => builtins.contains(&builtin.as_str()),
+                    Expr::Member(_, method) => methods.contains(&method.as_str()),
+                    _ => false,
+                };
+                if let (true, Some(Expr::Ident(target))) = (takes_callback, args.first()) {
+                    let rc_params = self.func_params.get(target)
+                        .is_some_and(|params| params.iter().any(|p| AotCompileContext::is_rc_type(&p.ty)));
+                    if rc_params && !targets.contains(target) {
+                        targets.push(target.clone());
+                    }
+                }
+            }
+        };
+        for stmt in &program.statements {
+            match stmt {
+                Statement::FuncDef(func) => purity::for_each_expr(&func.body, &mut visit),
+                Statement::ClassDef(class) => {
+                    for method in &class.methods {
+                        purity::for_each_expr(&method.body, &mut visit);
+                    }
+                }
+                other => purity::for_each_expr(std::slice::from_ref(other), &mut visit),
+            }
+        }
+        targets.sort();
+        for target in targets {
+            let target_id = self.functions[&target];
+            let sig = self.module.declarations().get_function_decl(target_id).signature.clone();
+            let adapter_id = self.module
+                .declare_function(&format!("__borrow_{}", target), Linkage::Local, &sig)
+                .map_err(|e| format!("{}", e))?;
+            self.functions.insert(format!("__borrow_{}", target), adapter_id);
+            self.borrow_adapters.push(target);
+        }
+        Ok(())
+    }
+
+    /// 定义借用适配函数：retain 引用计数参数后调用目标函数，目标函数返回时释放它们
+    fn define_borrow_adapters(&mut self) -> Result<(), String> {
+        for target in self.borrow_adapters.clone() {
+            let adapter_id = self.functions[&format!("__borrow_{}", target)];
+            let target_id = self.functions[&target];
+            let param_types: Vec<BolideType> = self.func_params[&target].iter().map(|p| p.ty.clone()).collect();
+            let mut clone_ids = Vec::new();
+            for ty in &param_types {
+                let clone_id = match AotCompileContext::get_clone_func_name(ty) {
+                    Some(name) => Some(*self.functions.get(name).ok_or_else(|| format!("{} not declared", name))?),
+                    None => None,
+                };
+                clone_ids.push(clone_id);
+            }
+
+            self.ctx.func.signature = self.module.declarations().get_function_decl(target_id).signature.clone();
+            let mut fbc = FunctionBuilderContext::new();
+            let mut builder = FunctionBuilder::new(&mut self.ctx.func, &mut fbc);
+            let entry = builder.create_block();
+            builder.append_block_params_for_function_params(entry);
+            builder.switch_to_block(entry);
+            builder.seal_block(entry);
+
+            let params = builder.block_params(entry).to_vec();
+            let mut call_args = Vec::new();
+            for (param, clone_id) in params.into_iter().zip(clone_ids) {
+                match clone_id {
+                    Some(clone_id) => {
+                        let clone_ref = self.module.declare_func_in_func(clone_id, builder.func);
+                        let call = builder.ins().call(clone_ref, &[param]);
+                        call_args.push(builder.inst_results(call)[0]);
+                    }
+                    None => call_args.push(param),
+                }
+            }
+            let target_ref = self.module.declare_func_in_func(target_id, builder.func);
+            let call = builder.ins().call(target_ref, &call_args);
+            let results = builder.inst_results(call).to_vec();
+            builder.ins().return_(&results);
+            builder.finalize();
+
+            self.module.define_function(adapter_id, &mut self.ctx)
+                .map_err(|e| format!("Define adapter error: {}", e))?;
+            self.module.clear_context(&mut self.ctx);
+        }
+        Ok(())
+    }
+
+    /// 定义单个 trampoline：从 env 取出参数并调用目标函数
+    fn define_trampoline(&mut self, func_name: &str) -> Result<(), String> {
+        let info = &self.trampolines[func_name];
+        let trampoline_id = info.func_id;
+        let cranelift_types: Vec<types::Type> = info.param_types.iter()
+            .map(|ty| self.bolide_type_to_cranelift(ty))
+            .collect();
 
         // 获取目标函数 ID
         let target_func_id = *self.functions.get(func_name)
             .ok_or_else(|| format!("Target function {} not declared", func_name))?;
 
-        // 预计算参数类型
-        let cranelift_types: Vec<types::Type> = params.iter()
-            .map(|p| self.bolide_type_to_cranelift(&p.ty))
-            .collect();
-
         // 构建函数体
-        self.ctx.func.signature = sig;
+        self.ctx.func.signature = self.trampoline_signature(func_name);
         let mut fbc = FunctionBuilderContext::new();
         let mut builder = FunctionBuilder::new(&mut self.ctx.func, &mut fbc);
 
@@ -1328,14 +3017,6 @@ impl AotCompiler {
         self.module.define_function(trampoline_id, &mut self.ctx)
             .map_err(|e| format!("Define trampoline error: {}", e))?;
         self.module.clear_context(&mut self.ctx);
-
-        self.trampolines.insert(func_name.to_string(), TrampolineInfo {
-            func_id: trampoline_id,
-            param_types,
-            env_size,
-        });
-        self.functions.insert(trampoline_name, trampoline_id);
-
         Ok(())
     }
 
@@ -1390,6 +3071,9 @@ impl AotCompiler {
     fn compile_class_methods(&mut self, program: &Program) -> Result<(), String> {
         for stmt in &program.statements {
             if let Statement::ClassDef(class) = stmt {
+                if self.is_external(&class.name) {
+                    continue;
+                }
                 for method in &class.methods {
                     self.compile_class_method(&class.name, method)?;
                 }
@@ -1429,13 +3113,6 @@ impl AotCompiler {
         builder.switch_to_block(entry);
         builder.seal_block(entry);
 
-        // 导入函数引用
-        let mut func_refs = HashMap::new();
-        for (name, &fid) in &self.functions {
-            let fref = self.module.declare_func_in_func(fid, builder.func);
-            func_refs.insert(name.clone(), fref);
-        }
-
         // Declare string data in function and create GlobalValues
         let mut string_globals = HashMap::new();
         for (s, data_id) in &string_data_ids {
@@ -1444,17 +3121,26 @@ impl AotCompiler {
         }
 
         // 使用作用域来确保 ctx 在 finalize 之前被释放
+        let list_chunks;
         {
             let mut ctx = AotCompileContext::new(
                 &mut builder,
-                func_refs,
+                &mut self.module,
+                &self.functions,
+                &self.func_params,
+                &self.global_data_ids,
+                &mut self.global_var_types,
                 self.ptr_type,
                 self.classes.clone(),
                 self.async_funcs.clone(),
                 self.func_return_types.clone(),
                 string_globals,
                 self.modules.clone(),
+                method_name.clone(),
             );
+            ctx.share_containers = self.share_containers;
+            ctx.thread_locals = self.thread_locals.iter().map(|decl| decl.name.clone()).collect();
+            ctx.extern_funcs = self.extern_funcs.clone();
 
             // 设置 self 参数
             let params: Vec<_> = ctx.builder.block_params(entry).to_vec();
@@ -1468,7 +3154,10 @@ impl AotCompiler {
                 let var = ctx.declare_variable(&param.name, ty);
                 ctx.builder.def_var(var, params[i + 1]); // +1 因为 self 是第一个参数
                 ctx.var_types.insert(param.name.clone(), param.ty.clone());
-                ctx.track_rc_variable(&param.name, &param.ty);
+                // 合成的比较方法由 object_equals 以借用方式调用，参数不归它释放
+                if method.name != equality::DEFAULT_EQ {
+                    ctx.track_rc_variable(&param.name, &param.ty);
+                }
             }
 
             // 编译方法体
@@ -1489,13 +3178,15 @@ impl AotCompiler {
                     ctx.builder.ins().return_(&[]);
                 }
             }
+            list_chunks = std::mem::take(&mut ctx.list_chunks);
+            self.warnings.append(&mut ctx.warnings);
         }
 
         builder.finalize();
         self.module.define_function(func_id, &mut self.ctx)
             .map_err(|e| format!("Define method error: {}", e))?;
         self.module.clear_context(&mut self.ctx);
-        Ok(())
+        self.compile_list_chunks(list_chunks)
     }
 
     /// 编译函数
@@ -1503,8 +3194,16 @@ impl AotCompiler {
         let func_id = *self.functions.get(&func.name)
             .ok_or_else(|| format!("Function {} not declared", func.name))?;
 
+        // threadlocal 变量的初始化函数：函数体是变量声明里的初始化
+        let tls_init = func.name.strip_prefix(TLS_INIT_PREFIX)
+            .and_then(|name| self.thread_locals.iter().find(|decl| decl.name == name))
+            .cloned();
+
         // Collect string literals and create data objects
-        let strings = self.collect_strings_from_stmts(&func.body);
+        let mut strings = self.collect_strings_from_stmts(&func.body);
+        if let Some(ref decl) = tls_init {
+            strings.extend(self.collect_strings_from_stmts(&[Statement::VarDecl(decl.clone())]));
+        }
         let mut string_data_ids: HashMap<String, DataId> = HashMap::new();
         for s in &strings {
             let data_id = self.get_or_create_string_data(s)?;
@@ -1528,13 +3227,6 @@ impl AotCompiler {
         builder.switch_to_block(entry);
         builder.seal_block(entry);
 
-        // 导入函数引用
-        let mut func_refs = HashMap::new();
-        for (name, &fid) in &self.functions {
-            let fref = self.module.declare_func_in_func(fid, builder.func);
-            func_refs.insert(name.clone(), fref);
-        }
-
         // Declare string data in function and create GlobalValues
         let mut string_globals = HashMap::new();
         for (s, data_id) in &string_data_ids {
@@ -1543,17 +3235,26 @@ impl AotCompiler {
         }
 
         // 使用作用域来确保 ctx 在 finalize 之前被释放
+        let list_chunks;
         {
             let mut ctx = AotCompileContext::new(
                 &mut builder,
-                func_refs,
+                &mut self.module,
+                &self.functions,
+                &self.func_params,
+                &self.global_data_ids,
+                &mut self.global_var_types,
                 self.ptr_type,
                 self.classes.clone(),
                 self.async_funcs.clone(),
                 self.func_return_types.clone(),
                 string_globals,
                 self.modules.clone(),
+                func.name.clone(),
             );
+            ctx.share_containers = self.share_containers;
+            ctx.thread_locals = self.thread_locals.iter().map(|decl| decl.name.clone()).collect();
+            ctx.extern_funcs = self.extern_funcs.clone();
 
             // 设置参数变量
             let params: Vec<_> = ctx.builder.block_params(entry).to_vec();
@@ -1565,6 +3266,13 @@ impl AotCompiler {
                 ctx.track_rc_variable(&param.name, &param.ty);
             }
 
+            if func.name == "__main__" {
+                ctx.emit_thread_local_setup(&self.thread_locals)?;
+            }
+            if let Some(ref decl) = tls_init {
+                ctx.compile_global_decl(decl)?;
+            }
+
             // 编译函数体
             let mut returned = false;
             for stmt in &func.body {
@@ -1573,9 +3281,14 @@ impl AotCompiler {
                     break;
                 }
             }
+            if func.name == GLOBALS_RELEASE {
+                ctx.emit_globals_release()?;
+            }
 
-            // 如果没有显式返回，添加默认返回
+            // 如果没有显式返回，释放 RC 变量后添加默认返回
             if !returned {
+                ctx.release_temp_rc_values();
+                ctx.emit_rc_cleanup();
                 if func.return_type.is_some() {
                     let zero = ctx.builder.ins().iconst(types::I64, 0);
                     ctx.builder.ins().return_(&[zero]);
@@ -1583,25 +3296,58 @@ impl AotCompiler {
                     ctx.builder.ins().return_(&[]);
                 }
             }
+            list_chunks = std::mem::take(&mut ctx.list_chunks);
+            self.warnings.append(&mut ctx.warnings);
         } // ctx 在这里被释放
 
         builder.finalize();
+        if self.opt_level > 0 {
+            rc_peephole::remove_cancelled_clones(&mut self.ctx.func, &self.functions, &self.func_params);
+        }
         // println!("Compiling Aot function: {}", func.name);
-        if let Err(e) = self.ctx.verify_if(&*self.module.isa()) {
-            println!("Verify Error for {}: {:?}", func.name, e);
-            println!("{}", self.ctx.func.display());
+        if let Err(e) = self.module.define_function(func_id, &mut self.ctx) {
+            let msg = if ice::is_verifier_error(&e) {
+                let report = IceReport {
+                    func_name: &func.name,
+                    detail: ice::error_detail(&e, &self.ctx.func),
+                    clif: self.ctx.func.display().to_string(),
+                    ast: format!("{:#?}", func),
+                };
+                ice::report(&report, self.source.as_ref())
+            } else {
+                format!("Define function error in {}: {}", func.name, e)
+            };
+            self.module.clear_context(&mut self.ctx);
+            return Err(msg);
         }
-
-        self.module.define_function(func_id, &mut self.ctx)
-            .map_err(|e| format!("Define function error in {}: {}", func.name, e))?;
         self.module.clear_context(&mut self.ctx);
-        Ok(())
+        self.compile_list_chunks(list_chunks)
+    }
+
+    /// 编译大列表字面量合成的辅助函数（已在字面量处声明）
+    fn compile_list_chunks(&mut self, chunks: Vec<(FuncDef, FuncId)>) -> Result<(), String> {
+        for (chunk, chunk_id) in chunks {
+            self.functions.insert(chunk.name.clone(), chunk_id);
+            self.func_return_types.insert(chunk.name.clone(), chunk.return_type.clone());
+            self.compile_function(&chunk)?;
+        }
+        Ok(())
     }
 }
 
 /// AOT 编译上下文
 struct AotCompileContext<'a, 'b> {
     builder: &'a mut FunctionBuilder<'b>,
+    module: &'a mut ObjectModule,
+    /// 函数符号表：名称 -> FuncId（引用在首次使用时才声明到当前函数）
+    functions: &'a HashMap<String, FuncId>,
+    /// 函数和构造函数的参数表，用于整理关键字实参和默认值
+    func_params: &'a HashMap<String, Vec<Param>>,
+    /// 全局变量 -> 数据段
+    global_data_ids: &'a HashMap<String, DataId>,
+    /// 全局变量的类型，`__main__` 编译没有标注类型的声明时补上
+    global_var_types: &'a mut HashMap<String, BolideType>,
+    /// 当前函数已声明的函数引用
     func_refs: HashMap<String, FuncRef>,
     variables: HashMap<String, Variable>,
     var_types: HashMap<String, BolideType>,
@@ -1613,27 +3359,61 @@ struct AotCompileContext<'a, 'b> {
     /// String data global values (string content -> GlobalValue)
     string_globals: HashMap<String, (cranelift_codegen::ir::GlobalValue, usize)>,
     /// 模块名映射
-    modules: HashMap<String, String>,
+    modules: HashMap<String, ImportedModule>,
     /// RC variables to be released at scope exit/return
     rc_variables: Vec<(Variable, BolideType)>,
     /// Temporary RC values from expressions (to be released at statement end)
     temp_rc_values: Vec<(Value, BolideType)>,
+    /// 正在编译的 measure 块（标签、开始时间、开始时的分配计数），由外到内
+    measure_stack: Vec<(String, Value, Value)>,
+    /// 正在编译的 pool 块的线程池，由外到内；提前离开块时退出并销毁
+    pool_stack: Vec<Value>,
+    /// 正在编译的循环，由外到内
+    loops: Vec<LoopFrame>,
+    /// 下标小于它的循环在 select / await scope 块之外，break/continue 不能跳过去
+    loop_fence: usize,
+    /// 当前函数名（用于辅助函数命名）
+    current_func_name: String,
+    /// 已分块编译的大列表字面量个数
+    chunked_lists: usize,
+    /// 大列表字面量合成的辅助函数，当前函数编译完后再编译
+    list_chunks: Vec<(FuncDef, FuncId)>,
+    /// 编译警告（已带上所在函数），函数编译完后交给 AotCompiler
+    warnings: Vec<String>,
+    /// 绑定已有的列表/字典时共享而不复制（`--share-containers`）
+    share_containers: bool,
+    /// threadlocal 全局变量，读写经由 `tls_get` 取得当前线程的单元
+    thread_locals: HashSet<String>,
+    /// extern 函数：函数名 -> (库路径, 声明)
+    extern_funcs: HashMap<String, (String, bolide_parser::ExternFunc)>,
+    /// 保存 spawn 句柄的变量 -> 被 spawn 的函数，join 按函数的返回类型取结果
+    spawn_funcs: HashMap<String, String>,
 }
 
 impl<'a, 'b> AotCompileContext<'a, 'b> {
     fn new(
         builder: &'a mut FunctionBuilder<'b>,
-        func_refs: HashMap<String, FuncRef>,
+        module: &'a mut ObjectModule,
+        functions: &'a HashMap<String, FuncId>,
+        func_params: &'a HashMap<String, Vec<Param>>,
+        global_data_ids: &'a HashMap<String, DataId>,
+        global_var_types: &'a mut HashMap<String, BolideType>,
         ptr_type: types::Type,
         classes: HashMap<String, ClassInfo>,
         async_funcs: HashSet<String>,
         func_return_types: HashMap<String, Option<BolideType>>,
         string_globals: HashMap<String, (cranelift_codegen::ir::GlobalValue, usize)>,
-        modules: HashMap<String, String>,
+        modules: HashMap<String, ImportedModule>,
+        current_func_name: String,
     ) -> Self {
         Self {
             builder,
-            func_refs,
+            module,
+            functions,
+            func_params,
+            global_data_ids,
+            global_var_types,
+            func_refs: HashMap::new(),
             variables: HashMap::new(),
             var_types: HashMap::new(),
             var_counter: 0,
@@ -1645,7 +3425,44 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             modules,
             rc_variables: Vec::new(),
             temp_rc_values: Vec::new(),
+            measure_stack: Vec::new(),
+            pool_stack: Vec::new(),
+            loops: Vec::new(),
+            loop_fence: 0,
+            current_func_name,
+            chunked_lists: 0,
+            list_chunks: Vec::new(),
+            warnings: Vec::new(),
+            share_containers: false,
+            thread_locals: HashSet::new(),
+            extern_funcs: HashMap::new(),
+            spawn_funcs: HashMap::new(),
+        }
+    }
+
+    /// 记录编译警告；同一函数里相同的警告只记一次
+    fn warn(&mut self, message: &str) {
+        let location = if self.current_func_name == "__main__" {
+            "top-level code".to_string()
+        } else {
+            format!("function '{}'", self.current_func_name)
+        };
+        let warning = format!("in {}: {}", location, message);
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// 取得函数引用：首次使用时声明到当前函数并缓存
+    fn get_func_ref(&mut self, name: &str) -> Result<FuncRef, String> {
+        if let Some(&func_ref) = self.func_refs.get(name) {
+            return Ok(func_ref);
         }
+        let func_id = *self.functions.get(name)
+            .ok_or_else(|| format!("{} not found", name))?;
+        let func_ref = self.module.declare_func_in_func(func_id, self.builder.func);
+        self.func_refs.insert(name.to_string(), func_ref);
+        Ok(func_ref)
     }
 
     fn enter_scope(&self) -> usize {
@@ -1677,6 +3494,7 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             BolideType::Int => types::I64,
             BolideType::Float => types::F64,
             BolideType::Bool => types::I64,
+            BolideType::Duration | BolideType::Deadline => types::I64,
             BolideType::Str => self.ptr_type,
             BolideType::BigInt => self.ptr_type,
             BolideType::Decimal => self.ptr_type,
@@ -1688,10 +3506,12 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             BolideType::FuncSig(_, _) => self.ptr_type,
             BolideType::List(_) => self.ptr_type,
             BolideType::Dict(_, _) => self.ptr_type,
+            BolideType::WeakDict(_, _) => self.ptr_type,
             BolideType::Tuple(_) => self.ptr_type,
             BolideType::Custom(_) => self.ptr_type,
             BolideType::Weak(_) => self.ptr_type,
             BolideType::Unowned(_) => self.ptr_type,
+            BolideType::PackedList(_) | BolideType::PackedRef(_) => self.ptr_type,
         }
     }
 
@@ -1705,9 +3525,12 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                 BolideType::Decimal |
                 BolideType::List(_) |
                 BolideType::Dict(_, _) |
+                BolideType::WeakDict(_, _) |
                 BolideType::Dynamic |
                 BolideType::Custom(_) |
-                BolideType::Tuple(_)
+                BolideType::Tuple(_) |
+                BolideType::Func |
+                BolideType::FuncSig(_, _)
             )
         }
     }
@@ -1720,9 +3543,11 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             BolideType::Decimal => Some("decimal_release"),
             BolideType::List(_) => Some("list_release"),
             BolideType::Dict(_, _) => Some("dict_release"),
+            BolideType::WeakDict(_, _) => Some("weakdict_release"),
             BolideType::Dynamic => Some("dynamic_release"),
             BolideType::Custom(_) => Some("object_release"),
             BolideType::Tuple(_) => Some("tuple_free"),
+            BolideType::Func | BolideType::FuncSig(_, _) => Some("func_release"),
             _ => None,
         }
     }
@@ -1751,7 +3576,7 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
     fn emit_release(&mut self, val: Value, ty: &BolideType) {
         if let BolideType::Tuple(inner_types) = ty {
             // 元组需要先释放元素
-            if let Some(&get_func) = self.func_refs.get("tuple_get") {
+            if let Ok(get_func) = self.get_func_ref("tuple_get") {
                 for (i, elem_ty) in inner_types.iter().enumerate() {
                     if Self::is_rc_type(elem_ty) {
                         let idx_val = self.builder.ins().iconst(types::I64, i as i64);
@@ -1766,58 +3591,85 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                 }
             }
             // 最后释放元组本身
-            if let Some(&free_func) = self.func_refs.get("tuple_free") {
+            if let Ok(free_func) = self.get_func_ref("tuple_free") {
                 self.builder.ins().call(free_func, &[val]);
             }
         } else if let BolideType::Custom(ref class_name) = ty {
-            // 对于 Custom 类型，需要先检查是否为 null
-            // 因为 emit_object_fields_cleanup 会访问对象字段，如果对象是 null 会导致问题
-            let null_val = self.builder.ins().iconst(self.ptr_type, 0);
-            let is_null = self.builder.ins().icmp(IntCC::Equal, val, null_val);
-            
-            let release_block = self.builder.create_block();
-            let continue_block = self.builder.create_block();
-            
-            self.builder.ins().brif(is_null, continue_block, &[], release_block, &[]);
-            
-            // release_block: 对象不为 null，进行释放
-            self.builder.switch_to_block(release_block);
-            self.builder.seal_block(release_block);
-            
-            self.emit_object_fields_cleanup(val, class_name);
-            if let Some(&release_func) = self.func_refs.get("object_release") {
-                self.builder.ins().call(release_func, &[val]);
-            }
-            
-            self.builder.ins().jump(continue_block, &[]);
-            
-            // continue_block: 继续执行
-            self.builder.switch_to_block(continue_block);
-            self.builder.seal_block(continue_block);
+            self.emit_object_release(val, class_name, &mut Vec::new());
         } else {
             if let Some(func_name) = Self::get_release_func_name(ty) {
-                if let Some(&func_ref) = self.func_refs.get(func_name) {
+                if let Ok(func_ref) = self.get_func_ref(func_name) {
                     self.builder.ins().call(func_ref, &[val]);
                 }
             }
         }
     }
 
+    /// 释放类实例：nil 跳过（字段释放会访问对象），这是最后一个引用时先释放它的 RC 字段
+    ///
+    /// `expanding` 是正在展开字段释放代码的类（由外到内），避免自引用的类无限展开。
+    fn emit_object_release(&mut self, val: Value, class_name: &str, expanding: &mut Vec<String>) {
+        let (Ok(refcount_func), Ok(release_func)) = (self.get_func_ref("object_refcount"), self.get_func_ref("object_release")) else {
+            return;
+        };
+        let live_block = self.builder.create_block();
+        let cleanup_block = self.builder.create_block();
+        let release_block = self.builder.create_block();
+        let done_block = self.builder.create_block();
+
+        let null_val = self.builder.ins().iconst(self.ptr_type, 0);
+        let is_null = self.builder.ins().icmp(IntCC::Equal, val, null_val);
+        self.builder.ins().brif(is_null, done_block, &[], live_block, &[]);
+
+        self.builder.switch_to_block(live_block);
+        self.builder.seal_block(live_block);
+        let call = self.builder.ins().call(refcount_func, &[val]);
+        let refcount = self.builder.inst_results(call)[0];
+        let is_last = self.builder.ins().icmp_imm(IntCC::Equal, refcount, 1);
+        self.builder.ins().brif(is_last, cleanup_block, &[], release_block, &[]);
+
+        self.builder.switch_to_block(cleanup_block);
+        self.builder.seal_block(cleanup_block);
+        self.emit_object_fields_cleanup(val, class_name, expanding);
+        self.builder.ins().jump(release_block, &[]);
+
+        self.builder.switch_to_block(release_block);
+        self.builder.seal_block(release_block);
+        self.builder.ins().call(release_func, &[val]);
+        self.builder.ins().jump(done_block, &[]);
+
+        self.builder.switch_to_block(done_block);
+        self.builder.seal_block(done_block);
+    }
+
     /// 释放对象内部的 RC 字段
-    fn emit_object_fields_cleanup(&mut self, obj_ptr: Value, class_name: &str) {
-        if let Some(class_info) = self.classes.get(class_name).cloned() {
-            for field in &class_info.fields {
-                if Self::is_rc_type(&field.ty) {
-                    if let Some(func_name) = Self::get_release_func_name(&field.ty) {
-                        if let Some(&func_ref) = self.func_refs.get(func_name) {
-                            let field_ptr = self.builder.ins().iadd_imm(obj_ptr, field.offset as i64);
-                            let field_val = self.builder.ins().load(types::I64, MemFlags::new(), field_ptr, 0);
-                            self.builder.ins().call(func_ref, &[field_val]);
-                        }
+    ///
+    /// weak/unowned 字段不持有引用，跳过；类实例字段按 `emit_object_release` 递归释放，
+    /// 字段的类正在展开时（链表节点的 `next` 等）只释放字段对象本身。
+    fn emit_object_fields_cleanup(&mut self, obj_ptr: Value, class_name: &str, expanding: &mut Vec<String>) {
+        let Some(class_info) = self.classes.get(class_name).cloned() else {
+            return;
+        };
+        expanding.push(class_name.to_string());
+        for field in &class_info.fields {
+            if !Self::is_rc_type(&field.ty) {
+                continue;
+            }
+            let field_ptr = self.builder.ins().iadd_imm(obj_ptr, field.offset as i64);
+            let field_val = self.builder.ins().load(types::I64, MemFlags::new(), field_ptr, 0);
+            match &field.ty {
+                BolideType::Custom(inner) if !expanding.contains(inner) => {
+                    self.emit_object_release(field_val, inner, expanding);
+                }
+                BolideType::Custom(_) => {
+                    if let Ok(func_ref) = self.get_func_ref("object_release") {
+                        self.builder.ins().call(func_ref, &[field_val]);
                     }
                 }
+                ty => self.emit_release(field_val, ty),
             }
         }
+        expanding.pop();
     }
 
     /// 编译表达式
@@ -1829,54 +3681,101 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             Expr::String(s) => self.compile_string_literal(s),
             Expr::BigInt(s) => self.compile_bigint_literal(s),
             Expr::Decimal(s) => self.compile_decimal_literal(s),
+            Expr::Duration(ns) => Ok(self.builder.ins().iconst(types::I64, *ns)),
             Expr::Ident(name) => self.compile_ident(name),
             Expr::BinOp(left, op, right) => self.compile_binop(left, op, right),
+            Expr::CompareChain(first, rest) => self.compile_compare_chain(first, rest),
             Expr::UnaryOp(op, operand) => self.compile_unary(op, operand),
             Expr::Call(callee, args) => self.compile_call(callee, args),
             Expr::None => Ok(self.builder.ins().iconst(types::I64, 0)),
             Expr::Index(base, index) => self.compile_index(base, index),
+            Expr::Slice(base, start, end) => self.compile_slice(base, start.as_deref(), end.as_deref()),
             Expr::Member(base, member) => self.compile_member(base, member),
-            Expr::List(items) => self.compile_list(items),
+            // 字面量是新建的容器：作为临时值跟踪，借用它的地方（如 d[k] = [...]）用完后释放
+            Expr::List(items) => {
+                let list = self.compile_list(items, None)?;
+                let ty = self.infer_expr_type(expr).unwrap_or(BolideType::List(Box::new(BolideType::Int)));
+                self.track_temp_rc_value(list, &ty);
+                Ok(list)
+            }
+            Expr::Dict(entries) => {
+                let dict = self.compile_dict(entries, None)?;
+                let ty = self.dict_literal_type(entries);
+                self.track_temp_rc_value(dict, &ty);
+                Ok(dict)
+            }
             Expr::Tuple(items) => self.compile_tuple(items),
-            Expr::Dict(entries) => self.compile_dict(entries),
+            Expr::FString(parts) => self.compile_fstring(parts),
             Expr::Spawn(name, args) => self.compile_spawn(name, args),
             Expr::Await(inner) => self.compile_await(inner),
             Expr::Recv(channel) => self.compile_recv_channel(channel),
             Expr::AwaitAll(exprs) => self.compile_await_all(exprs),
+            Expr::NamedArg(name, _) => Err(format!("keyword argument '{}' is only allowed in calls to user-defined functions", name)),
+            Expr::Lambda(..) => Err("lambdas can only appear inside function bodies and top-level statements".to_string()),
         }
     }
 
     /// 编译字符串字面量
     fn compile_string_literal(&mut self, s: &str) -> Result<Value, String> {
-        let func_ref = *self.func_refs.get("string_literal")
-            .ok_or("string_literal not found")?;
+        let result = self.load_string_literal(s)?;
+        self.track_temp_rc_value(result, &BolideType::Str);
+        Ok(result)
+    }
+
+    /// 取字符串字面量的驻留实例，不记为临时值（驻留实例永不释放）
+    fn load_string_literal(&mut self, s: &str) -> Result<Value, String> {
+        let func_ref = self.get_func_ref("string_literal")?;
 
         // Get the GlobalValue for this string from string_globals
-        let (gv, len) = *self.string_globals.get(s)
-            .ok_or_else(|| format!("String data not found for: {}", s))?;
+        let (gv, len) = match self.string_globals.get(s) {
+            Some(&entry) => entry,
+            None => self.declare_string_data(s)?,
+        };
 
         // Get the address of the data at runtime
         let ptr_val = self.builder.ins().global_value(self.ptr_type, gv);
         let len_val = self.builder.ins().iconst(types::I64, len as i64);
 
         let call = self.builder.ins().call(func_ref, &[ptr_val, len_val]);
-        let result = self.builder.inst_results(call)[0];
-        self.track_temp_rc_value(result, &BolideType::Str);
-        Ok(result)
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// 任务名：新分配（不驻留）的字符串，交给 `*_set_name` 后由运行时释放
+    fn compile_task_name(&mut self, func_name: &str) -> Result<Value, String> {
+        let (gv, len) = match self.string_globals.get(func_name) {
+            Some(&entry) => entry,
+            None => self.declare_string_data(func_name)?,
+        };
+        let from_slice = self.get_func_ref("string_from_slice")?;
+        let ptr_val = self.builder.ins().global_value(self.ptr_type, gv);
+        let len_val = self.builder.ins().iconst(types::I64, len as i64);
+        let call = self.builder.ins().call(from_slice, &[ptr_val, len_val]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// 预先收集时没有见到的字符串（如 async 调用记下的函数名），在当前函数里补上数据段
+    fn declare_string_data(&mut self, s: &str) -> Result<(cranelift_codegen::ir::GlobalValue, usize), String> {
+        let data_id = self.module.declare_anonymous_data(false, false)
+            .map_err(|e| format!("Failed to declare string data: {}", e))?;
+        let mut desc = DataDescription::new();
+        desc.define(s.as_bytes().to_vec().into_boxed_slice());
+        self.module.define_data(data_id, &desc)
+            .map_err(|e| format!("Failed to define string data: {}", e))?;
+        let gv = self.module.declare_data_in_func(data_id, self.builder.func);
+        self.string_globals.insert(s.to_string(), (gv, s.len()));
+        Ok((gv, s.len()))
     }
 
     /// 编译 BigInt 字面量
     fn compile_bigint_literal(&mut self, s: &str) -> Result<Value, String> {
         let val;
         if let Ok(n) = s.parse::<i64>() {
-            let func_ref = *self.func_refs.get("bigint_from_i64")
-                .ok_or("bigint_from_i64 not found")?;
+            let func_ref = self.get_func_ref("bigint_from_i64")?;
             let arg = self.builder.ins().iconst(types::I64, n);
             let call = self.builder.ins().call(func_ref, &[arg]);
             val = self.builder.inst_results(call)[0];
         } else {
-            let func_ref = *self.func_refs.get("bigint_from_str")
-                .ok_or("bigint_from_str not found")?;
+            let func_ref = self.get_func_ref("bigint_from_str")?;
             let bytes: Box<[u8]> = s.as_bytes().into();
             let ptr = Box::leak(bytes).as_ptr();
             let len = s.len();
@@ -1893,15 +3792,13 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
     fn compile_decimal_literal(&mut self, s: &str) -> Result<Value, String> {
         let val;
         if let Ok(f) = s.parse::<f64>() {
-            let func_ref = *self.func_refs.get("decimal_from_f64")
-                .ok_or("decimal_from_f64 not found")?;
+            let func_ref = self.get_func_ref("decimal_from_f64")?;
             let arg = self.builder.ins().f64const(f);
             let call = self.builder.ins().call(func_ref, &[arg]);
             val = self.builder.inst_results(call)[0];
         } else {
             // Fallback to parsing from string
-            let func_ref = *self.func_refs.get("decimal_from_str")
-                 .ok_or("decimal_from_str not found")?;
+            let func_ref = self.get_func_ref("decimal_from_str")?;
              let bytes: Box<[u8]> = s.as_bytes().into();
              let ptr = Box::leak(bytes).as_ptr();
              let len = s.len();
@@ -1945,23 +3842,27 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             BolideType::Decimal => Some("decimal_clone"),
             BolideType::List(_) => Some("list_clone"),
             BolideType::Dict(_, _) => Some("dict_clone"),
+            BolideType::WeakDict(_, _) => Some("weakdict_clone"),
             BolideType::Dynamic => Some("dynamic_clone"),
             BolideType::Custom(_) => Some("object_clone"),
+            BolideType::Func | BolideType::FuncSig(_, _) => Some("func_retain"),
              _ => None,
         }
     }
 
-    /// 统一的 retain (clone) 辅助函数
+    /// 统一的 retain (clone) 辅助函数：列表和字典只增加引用计数，读取得到的是同一个容器
+    ///
+    /// 绑定到变量、字段等位置时是否复制由 `take_binding_value` 决定。
     fn emit_retain(&mut self, val: Value, ty: &BolideType) -> Value {
         if let BolideType::Tuple(inner_types) = ty {
              // Tuple Deep Copy: create new tuple and clone elements
-             if let Some(&new_func) = self.func_refs.get("tuple_new") {
+             if let Ok(new_func) = self.get_func_ref("tuple_new") {
                  let len = self.builder.ins().iconst(types::I64, inner_types.len() as i64);
                  let call = self.builder.ins().call(new_func, &[len]);
                  let new_tuple = self.builder.inst_results(call)[0];
 
-                 if let Some(&get_func) = self.func_refs.get("tuple_get") {
-                     if let Some(&set_func) = self.func_refs.get("tuple_set") {
+                 if let Ok(get_func) = self.get_func_ref("tuple_get") {
+                     if let Ok(set_func) = self.get_func_ref("tuple_set") {
                          for (i, elem_ty) in inner_types.iter().enumerate() {
                              let idx_val = self.builder.ins().iconst(types::I64, i as i64);
                              // Get from old tuple
@@ -1985,8 +3886,17 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
              // Fallback if functions missing (should not happen)
              return val; 
         } else {
+            let retain = match ty {
+                BolideType::List(_) => Some("list_retain"),
+                BolideType::Dict(_, _) => Some("dict_retain"),
+                _ => None,
+            };
+            if let Some(func_ref) = retain.and_then(|name| self.get_func_ref(name).ok()) {
+                self.builder.ins().call(func_ref, &[val]);
+                return val;
+            }
             if let Some(func_name) = Self::get_clone_func_name(ty) {
-                if let Some(&func_ref) = self.func_refs.get(func_name) {
+                if let Ok(func_ref) = self.get_func_ref(func_name) {
                     let call = self.builder.ins().call(func_ref, &[val]);
                     return self.builder.inst_results(call)[0];
                 }
@@ -1996,6 +3906,26 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         }
     }
 
+    /// 绑定位置（声明、赋值、字段、构造参数、返回值、元组元素）接管的值（规则同 JIT 的 `emit_binding_copy`）
+    ///
+    /// 从变量、字段或元素读取的列表和字典默认复制一份，读取时 retain 的那份照常在语句结束时释放；
+    /// `share_containers` 时直接接管这份引用。调用结果、字面量等临时值原样接管。
+    fn take_binding_value(&mut self, expr: &Expr, val: Value) -> Value {
+        if !self.share_containers && matches!(expr, Expr::Ident(_) | Expr::Member(..) | Expr::Index(..)) {
+            let clone = match self.infer_expr_type(expr) {
+                Some(BolideType::List(_)) => Some("list_clone"),
+                Some(BolideType::Dict(_, _)) => Some("dict_clone"),
+                _ => None,
+            };
+            if let Some(func_ref) = clone.and_then(|name| self.get_func_ref(name).ok()) {
+                let call = self.builder.ins().call(func_ref, &[val]);
+                return self.builder.inst_results(call)[0];
+            }
+        }
+        self.remove_temp_rc_value(val);
+        val
+    }
+
     /// 编译标识符
     fn compile_ident(&mut self, name: &str) -> Result<Value, String> {
         if let Some(&var) = self.variables.get(name) {
@@ -2010,17 +3940,169 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             }
             return Ok(val);
         }
-        if let Some(&func_ref) = self.func_refs.get(name) {
+        if let Some(val) = self.load_global(name) {
+            // 与局部变量一样，RC 值 retain 后作为临时值返回
+            if let Some(ty) = self.global_var_types.get(name).cloned() {
+                if Self::is_rc_type(&ty) {
+                    let new_val = self.emit_retain(val, &ty);
+                    self.track_temp_rc_value(new_val, &ty);
+                    return Ok(new_val);
+                }
+            }
+            return Ok(val);
+        }
+        if let Ok(func_ref) = self.get_func_ref(name) {
             return Ok(self.builder.ins().func_addr(self.ptr_type, func_ref));
         }
         Err(format!("Undefined variable: {}", name))
     }
 
+    /// 全局变量的地址，不是全局变量时返回 None；threadlocal 变量是当前线程的单元
+    fn global_addr(&mut self, name: &str) -> Option<Value> {
+        let data_id = *self.global_data_ids.get(name)?;
+        let gv = self.module.declare_data_in_func(data_id, self.builder.func);
+        let addr = self.builder.ins().global_value(self.ptr_type, gv);
+        if !self.thread_locals.contains(name) {
+            return Some(addr);
+        }
+        let key = self.builder.ins().load(types::I64, MemFlags::new(), addr, 0);
+        let tls_get = self.get_func_ref("tls_get").ok()?;
+        let call = self.builder.ins().call(tls_get, &[key]);
+        Some(self.builder.inst_results(call)[0])
+    }
+
+    /// `__main__` 开头为 threadlocal 变量分配槽，槽号存进变量的数据段
+    fn emit_thread_local_setup(&mut self, decls: &[bolide_parser::VarDecl]) -> Result<(), String> {
+        for decl in decls {
+            let init = self.get_func_ref(&format!("{}{}", TLS_INIT_PREFIX, decl.name))?;
+            let init_addr = self.builder.ins().func_addr(self.ptr_type, init);
+            let dtor = self.get_func_ref(&format!("{}{}", TLS_DROP_PREFIX, decl.name))?;
+            let dtor_addr = self.builder.ins().func_addr(self.ptr_type, dtor);
+            let alloc = self.get_func_ref("tls_alloc")?;
+            let call = self.builder.ins().call(alloc, &[init_addr, dtor_addr]);
+            let key = self.builder.inst_results(call)[0];
+            let data_id = self.global_data_ids[&decl.name];
+            let gv = self.module.declare_data_in_func(data_id, self.builder.func);
+            let addr = self.builder.ins().global_value(self.ptr_type, gv);
+            self.builder.ins().store(MemFlags::new(), key, addr, 0);
+        }
+        Ok(())
+    }
+
+    /// 读取全局变量（借用，不 retain）；float 全局变量按 f64 读取
+    fn load_global(&mut self, name: &str) -> Option<Value> {
+        let addr = self.global_addr(name)?;
+        let ty = self.global_var_types.get(name).map_or(types::I64, |ty| self.bolide_type_to_cranelift(ty));
+        Some(self.builder.ins().load(ty, MemFlags::new(), addr, 0))
+    }
+
+    /// 写入全局变量，接管 `val` 的所有权；`init` 为 false 时先释放旧值
+    fn store_global(&mut self, name: &str, val: Value, init: bool) -> Result<(), String> {
+        let addr = self.global_addr(name).ok_or_else(|| format!("Undefined variable: {}", name))?;
+        if let Some(ty) = self.global_var_types.get(name).cloned() {
+            if Self::is_rc_type(&ty) {
+                self.remove_temp_rc_value(val);
+                if !init {
+                    let old_val = self.builder.ins().load(types::I64, MemFlags::new(), addr, 0);
+                    self.emit_release(old_val, &ty);
+                }
+            }
+        }
+        self.builder.ins().store(MemFlags::new(), val, addr, 0);
+        Ok(())
+    }
+
+    /// 顶层代码里的名字是否指全局变量（函数里的局部变量会遮蔽同名的全局变量）
+    fn is_global(&self, name: &str) -> bool {
+        !self.variables.contains_key(name) && self.global_data_ids.contains_key(name)
+    }
+
+    /// `__globals_release` 的函数体：释放 RC 全局变量并置零
+    ///
+    /// 还有用户线程在运行时跳过（它们可能还在使用全局变量），由进程退出回收；
+    /// 没有执行到初始化的全局变量仍是 0，跳过。
+    fn emit_globals_release(&mut self) -> Result<(), String> {
+        let mut globals: Vec<(String, BolideType)> = self.global_var_types.iter()
+            .filter(|(name, ty)| Self::is_rc_type(ty) && !self.thread_locals.contains(*name))
+            .map(|(name, ty)| (name.clone(), ty.clone()))
+            .collect();
+        if globals.is_empty() && self.thread_locals.is_empty() {
+            return Ok(());
+        }
+        globals.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let live_threads = self.get_func_ref("live_threads")?;
+        let call = self.builder.ins().call(live_threads, &[]);
+        let live = self.builder.inst_results(call)[0];
+        let release_block = self.builder.create_block();
+        let done_block = self.builder.create_block();
+        self.builder.ins().brif(live, done_block, &[], release_block, &[]);
+        self.builder.switch_to_block(release_block);
+        self.builder.seal_block(release_block);
+
+        for (name, ty) in globals {
+            let addr = self.global_addr(&name).ok_or_else(|| format!("Undefined variable: {}", name))?;
+            let val = self.builder.ins().load(types::I64, MemFlags::new(), addr, 0);
+            let set_block = self.builder.create_block();
+            let next_block = self.builder.create_block();
+            self.builder.ins().brif(val, set_block, &[], next_block, &[]);
+            self.builder.switch_to_block(set_block);
+            self.builder.seal_block(set_block);
+            self.emit_release(val, &ty);
+            let zero = self.builder.ins().iconst(types::I64, 0);
+            self.builder.ins().store(MemFlags::new(), zero, addr, 0);
+            self.builder.ins().jump(next_block, &[]);
+            self.builder.switch_to_block(next_block);
+            self.builder.seal_block(next_block);
+        }
+        // 主线程的 threadlocal 值
+        if !self.thread_locals.is_empty() {
+            let thread_exit = self.get_func_ref("tls_thread_exit")?;
+            self.builder.ins().call(thread_exit, &[]);
+        }
+        self.builder.ins().jump(done_block, &[]);
+        self.builder.switch_to_block(done_block);
+        self.builder.seal_block(done_block);
+        Ok(())
+    }
+
     /// 编译二元运算
     fn compile_binop(&mut self, left: &Expr, op: &BinOp, right: &Expr) -> Result<Value, String> {
-        // 检查操作数类型以决定使用整数还是浮点运算
         let left_type = self.infer_expr_type(left);
         let right_type = self.infer_expr_type(right);
+        if matches!(op, BinOp::Is | BinOp::IsNot) {
+            equality::check_identity_operand(left, left_type.as_ref())?;
+            equality::check_identity_operand(right, right_type.as_ref())?;
+        }
+        // 类实例的 == / !=
+        if let Some(BolideType::Custom(class_name)) = &left_type {
+            if matches!(op, BinOp::Eq | BinOp::Ne) {
+                return self.compile_object_equals(left, op, right, class_name, &right_type);
+            }
+        }
+        let lhs = self.compile_expr(left)?;
+        let rhs = self.compile_expr(right)?;
+        self.compile_binop_values(lhs, &left_type, op, rhs, &right_type)
+    }
+
+    /// 对已求值的操作数做二元运算
+    fn compile_binop_values(&mut self, lhs: Value, left_type: &Option<BolideType>, op: &BinOp, rhs: Value, right_type: &Option<BolideType>) -> Result<Value, String> {
+        // 引用同一性：直接比较指针（操作数类型已在 compile_binop 中检查）
+        if matches!(op, BinOp::Is | BinOp::IsNot) {
+            let cc = if matches!(op, BinOp::Is) { IntCC::Equal } else { IntCC::NotEqual };
+            let cmp = self.builder.ins().icmp(cc, lhs, rhs);
+            return Ok(self.builder.ins().uextend(types::I64, cmp));
+        }
+
+        // duration / deadline：按单位检查后做 i64 运算
+        let left_ty = left_type.clone().unwrap_or(BolideType::Int);
+        let right_ty = right_type.clone().unwrap_or(BolideType::Int);
+        if let Some(result) = duration::binop_type(&left_ty, op, &right_ty) {
+            result?;
+            return Ok(duration::emit_binop(&mut self.builder, &left_ty, op, lhs, &right_ty, rhs));
+        }
+
+        // 检查操作数类型以决定使用整数还是浮点运算
         let is_float = matches!(left_type, Some(BolideType::Float))
             || matches!(right_type, Some(BolideType::Float));
         let is_string = matches!(left_type, Some(BolideType::Str))
@@ -2032,26 +4114,19 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
 
         // 字符串操作
         if is_string {
-            return self.compile_string_binop(left, op, right);
+            return self.compile_string_binop(lhs, op, rhs);
         }
 
         // BigInt 操作
         if is_bigint {
-            let lhs = self.compile_expr(left)?;
-            let rhs = self.compile_expr(right)?;
             return self.compile_bigint_binop(lhs, op, rhs);
         }
 
         // Decimal 操作
         if is_decimal {
-            let lhs = self.compile_expr(left)?;
-            let rhs = self.compile_expr(right)?;
             return self.compile_decimal_binop(lhs, op, rhs);
         }
 
-        let lhs = self.compile_expr(left)?;
-        let rhs = self.compile_expr(right)?;
-
         if is_float {
             // 浮点运算
             match op {
@@ -2093,6 +4168,7 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                 BinOp::And | BinOp::Or => {
                     Err("Logical operations not supported for floats".to_string())
                 }
+                BinOp::Is | BinOp::IsNot => unreachable!("identity comparison is compiled before dispatching on operand types"),
             }
         } else {
             // 整数运算
@@ -2128,20 +4204,17 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                 }
                 BinOp::And => Ok(self.builder.ins().band(lhs, rhs)),
                 BinOp::Or => Ok(self.builder.ins().bor(lhs, rhs)),
+                BinOp::Is | BinOp::IsNot => unreachable!("identity comparison is compiled before dispatching on operand types"),
             }
         }
     }
 
     /// 编译字符串二元运算
-    fn compile_string_binop(&mut self, left: &Expr, op: &BinOp, right: &Expr) -> Result<Value, String> {
-        let lhs = self.compile_expr(left)?;
-        let rhs = self.compile_expr(right)?;
-
+    fn compile_string_binop(&mut self, lhs: Value, op: &BinOp, rhs: Value) -> Result<Value, String> {
         match op {
             BinOp::Add => {
                 // 字符串连接
-                let func_ref = *self.func_refs.get("string_concat")
-                    .ok_or("string_concat not found")?;
+                let func_ref = self.get_func_ref("string_concat")?;
                 let call = self.builder.ins().call(func_ref, &[lhs, rhs]);
                 let result = self.builder.inst_results(call)[0];
                 self.track_temp_rc_value(result, &BolideType::Str);
@@ -2149,15 +4222,13 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             }
             BinOp::Eq => {
                 // 字符串相等比较
-                let func_ref = *self.func_refs.get("string_eq")
-                    .ok_or("string_eq not found")?;
+                let func_ref = self.get_func_ref("string_eq")?;
                 let call = self.builder.ins().call(func_ref, &[lhs, rhs]);
                 Ok(self.builder.inst_results(call)[0])
             }
             BinOp::Ne => {
                 // 字符串不等比较
-                let func_ref = *self.func_refs.get("string_eq")
-                    .ok_or("string_eq not found")?;
+                let func_ref = self.get_func_ref("string_eq")?;
                 let call = self.builder.ins().call(func_ref, &[lhs, rhs]);
                 let eq_result = self.builder.inst_results(call)[0];
                 // 取反
@@ -2165,10 +4236,75 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                 let cmp = self.builder.ins().icmp(IntCC::Equal, eq_result, zero);
                 Ok(self.builder.ins().uextend(types::I64, cmp))
             }
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                // 字典序比较: string_cmp 返回 -1 / 0 / 1
+                let cc = match op {
+                    BinOp::Lt => IntCC::SignedLessThan,
+                    BinOp::Le => IntCC::SignedLessThanOrEqual,
+                    BinOp::Gt => IntCC::SignedGreaterThan,
+                    _ => IntCC::SignedGreaterThanOrEqual,
+                };
+                let func_ref = self.get_func_ref("string_cmp")?;
+                let call = self.builder.ins().call(func_ref, &[lhs, rhs]);
+                let ord = self.builder.inst_results(call)[0];
+                let cmp = self.builder.ins().icmp_imm(cc, ord, 0);
+                Ok(self.builder.ins().uextend(types::I64, cmp))
+            }
             _ => Err(format!("Unsupported string operation: {:?}", op)),
         }
     }
 
+    /// 编译链式比较 a < b < c（与 JIT 相同：每个操作数只求值一次，短路）
+    fn compile_compare_chain(&mut self, first: &Expr, rest: &[(BinOp, Expr)]) -> Result<Value, String> {
+        let merge_block = self.builder.create_block();
+        self.builder.append_block_param(merge_block, types::I64);
+
+        let mut left_ty = self.infer_expr_type(first);
+        let mut left = self.compile_expr(first)?;
+        // 左操作数产生的临时值在 temp_rc_values 中的起始位置（第一个操作数的留到语句结束）
+        let mut left_temps: Option<usize> = None;
+
+        for (i, (op, right_expr)) in rest.iter().enumerate() {
+            let right_ty = self.infer_expr_type(right_expr);
+            let mark = self.temp_rc_values.len();
+            let right = self.compile_expr(right_expr)?;
+            let cmp = self.compile_binop_values(left, &left_ty, op, right, &right_ty)?;
+
+            // 左操作数已用完
+            let right_temps = match left_temps {
+                Some(start) => {
+                    let done: Vec<_> = self.temp_rc_values.drain(start..mark).collect();
+                    for (val, ty) in done {
+                        self.emit_release(val, &ty);
+                    }
+                    start
+                }
+                None => mark,
+            };
+
+            if i + 1 == rest.len() {
+                let done: Vec<_> = self.temp_rc_values.drain(right_temps..).collect();
+                for (val, ty) in done {
+                    self.emit_release(val, &ty);
+                }
+                self.builder.ins().jump(merge_block, &[cmp]);
+            } else {
+                let next_block = self.builder.create_block();
+                self.builder.ins().brif(cmp, next_block, &[], merge_block, &[cmp]);
+                self.builder.switch_to_block(next_block);
+                self.builder.seal_block(next_block);
+            }
+
+            left = right;
+            left_ty = right_ty;
+            left_temps = Some(right_temps);
+        }
+
+        self.builder.switch_to_block(merge_block);
+        self.builder.seal_block(merge_block);
+        Ok(self.builder.block_params(merge_block)[0])
+    }
+
     /// 编译 BigInt 二元运算
     fn compile_bigint_binop(&mut self, lhs: Value, op: &BinOp, rhs: Value) -> Result<Value, String> {
         let func_name = match op {
@@ -2180,8 +4316,7 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             BinOp::Eq => "bigint_eq",
             BinOp::Ne => {
                 // ne = !eq
-                let eq_ref = *self.func_refs.get("bigint_eq")
-                    .ok_or("bigint_eq not found")?;
+                let eq_ref = self.get_func_ref("bigint_eq")?;
                 let call = self.builder.ins().call(eq_ref, &[lhs, rhs]);
                 let eq_result = self.builder.inst_results(call)[0];
                 let one = self.builder.ins().iconst(types::I64, 1);
@@ -2194,10 +4329,10 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             BinOp::And | BinOp::Or => {
                 return Err("Logical operations not supported for BigInt".to_string());
             }
+            BinOp::Is | BinOp::IsNot => unreachable!("identity comparison is compiled before dispatching on operand types"),
         };
 
-        let func_ref = *self.func_refs.get(func_name)
-            .ok_or_else(|| format!("{} not found", func_name))?;
+        let func_ref = self.get_func_ref(func_name)?;
         let call = self.builder.ins().call(func_ref, &[lhs, rhs]);
         let result = self.builder.inst_results(call)[0];
         
@@ -2220,8 +4355,7 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             BinOp::Eq => "decimal_eq",
             BinOp::Ne => {
                 // ne = !eq
-                let eq_ref = *self.func_refs.get("decimal_eq")
-                    .ok_or("decimal_eq not found")?;
+                let eq_ref = self.get_func_ref("decimal_eq")?;
                 let call = self.builder.ins().call(eq_ref, &[lhs, rhs]);
                 let eq_result = self.builder.inst_results(call)[0];
                 let one = self.builder.ins().iconst(types::I64, 1);
@@ -2234,10 +4368,10 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             BinOp::And | BinOp::Or => {
                 return Err("Logical operations not supported for Decimal".to_string());
             }
+            BinOp::Is | BinOp::IsNot => unreachable!("identity comparison is compiled before dispatching on operand types"),
         };
 
-        let func_ref = *self.func_refs.get(func_name)
-            .ok_or_else(|| format!("{} not found", func_name))?;
+        let func_ref = self.get_func_ref(func_name)?;
         let call = self.builder.ins().call(func_ref, &[lhs, rhs]);
         let result = self.builder.inst_results(call)[0];
 
@@ -2259,16 +4393,14 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                 match operand_type {
                     Some(BolideType::Float) => Ok(self.builder.ins().fneg(val)),
                     Some(BolideType::BigInt) => {
-                        let func_ref = *self.func_refs.get("bigint_neg")
-                            .ok_or("bigint_neg not found")?;
+                        let func_ref = self.get_func_ref("bigint_neg")?;
                         let call = self.builder.ins().call(func_ref, &[val]);
                         let result = self.builder.inst_results(call)[0];
                         self.track_temp_rc_value(result, &BolideType::BigInt);
                         Ok(result)
                     },
                     Some(BolideType::Decimal) => {
-                        let func_ref = *self.func_refs.get("decimal_neg")
-                            .ok_or("decimal_neg not found")?;
+                        let func_ref = self.get_func_ref("decimal_neg")?;
                         let call = self.builder.ins().call(func_ref, &[val]);
                         let result = self.builder.inst_results(call)[0];
                         self.track_temp_rc_value(result, &BolideType::Decimal);
@@ -2290,13 +4422,15 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         match callee {
             Expr::Ident(name) => self.compile_named_call(name, args),
             Expr::Member(base, method_name) => {
-                // 先检查是否是模块调用
-                if let Expr::Ident(module_name) = base.as_ref() {
-                    if self.modules.contains_key(module_name) {
-                        // 模块调用: module.func() -> @module_func()
-                        let func_name = format!("@{}_{}", module_name, method_name);
-                        return self.compile_named_call(&func_name, args);
-                    }
+                // 模块调用: module.f(...) 按 @module_f(...) 编译
+                if let Some(resolved) = modules::resolve_member(&self.modules, base, method_name) {
+                    let name = match resolved? {
+                        Member::Global(name) if !matches!(self.global_var_types.get(&name), Some(BolideType::Func | BolideType::FuncSig(..))) => {
+                            return Err(format!("'{}' is not a function", modules::qualified_name(base, method_name)));
+                        }
+                        Member::Function(name) | Member::Class(name) | Member::Global(name) => name,
+                    };
+                    return self.compile_named_call(&name, args);
                 }
                 // 不是模块调用，是方法调用
                 self.compile_method_call(base, method_name, args)
@@ -2319,15 +4453,39 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             return self.compile_string_method(base, method_name, args);
         }
 
+        // decimal 方法（基础表达式可以是算术结果，如 (a / b).round(2)）
+        if let Some(BolideType::Decimal) = &base_type {
+            return self.compile_decimal_method(base, method_name, args);
+        }
+
+        // 处理通道方法
+        if let Some(BolideType::Channel(elem)) = &base_type {
+            return self.compile_channel_method(base, elem, method_name, args);
+        }
+
+        // 处理字典方法
+        if let Some(BolideType::Dict(key_ty, val_ty)) = &base_type {
+            return self.compile_dict_method(base, key_ty, val_ty, method_name, args);
+        }
+
+        // 处理弱键字典方法
+        if let Some(BolideType::WeakDict(key_ty, val_ty)) = &base_type {
+            return self.compile_weak_dict_method(base, key_ty, val_ty, method_name, args);
+        }
+
         // 处理类方法
         if let Some(BolideType::Custom(class_name)) = base_type {
-            let base_val = self.compile_expr(base)?;
             let method_full_name = format!("{}_{}", class_name, method_name);
+            if let Some(params) = self.func_params.get(&method_full_name) {
+                if let Some(args) = call_args::positional_args(method_name, params, args)? {
+                    return self.compile_method_call(base, method_name, &args);
+                }
+            }
+            let base_val = self.compile_expr(base)?;
 
-            if let Some(&func_ref) = self.func_refs.get(&method_full_name) {
-                // Self is passed as first argument and ownership is transferred
-                self.remove_temp_rc_value(base_val);
-                
+            if let Ok(func_ref) = self.get_func_ref(&method_full_name) {
+                // 方法借用 self（方法体不释放它），base 的临时值仍在语句结束时释放；
+                // 链式调用 b.set_x(1).set_y(2) 中 set_x 返回的那一份也由此释放
                 let mut arg_vals = vec![base_val]; // self 作为第一个参数
                 for arg in args {
                     let val = self.compile_expr(arg)?;
@@ -2353,69 +4511,751 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         Err(format!("Unknown method: {}", method_name))
     }
 
+    /// 编译通道方法（批量收发，语义同 JIT）
+    fn compile_channel_method(&mut self, base: &Expr, elem_ty: &BolideType, method_name: &str, args: &[Expr]) -> Result<Value, String> {
+        let channel_val = self.compile_expr(base)?;
+        // 接收到的列表和 compile_list 一样按 `list_tag` 标记元素类型
+        let tag = Self::list_tag(elem_ty);
+        let list_ty = BolideType::List(Box::new(elem_ty.clone()));
+
+        match method_name {
+            "send_all" => {
+                if args.len() != 1 {
+                    return Err("send_all expects 1 argument".to_string());
+                }
+                if let Some(BolideType::List(list_elem)) = self.infer_expr_type(&args[0]) {
+                    if *list_elem != *elem_ty {
+                        return Err(format!("send_all on channel<{:?}> expects list<{:?}>, got list<{:?}>",
+                            elem_ty, elem_ty, list_elem));
+                    }
+                }
+                let list_val = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("channel_send_all")?;
+                let call = self.builder.ins().call(func_ref, &[channel_val, list_val]);
+                Ok(self.builder.inst_results(call)[0])
+            }
+            "recv_n" => {
+                if args.len() != 1 {
+                    return Err("recv_n expects 1 argument".to_string());
+                }
+                let n = self.compile_expr(&args[0])?;
+                let list_new = self.get_func_ref("list_new")?;
+                let tag = self.builder.ins().iconst(types::I8, tag);
+                let call = self.builder.ins().call(list_new, &[tag]);
+                let list_val = self.builder.inst_results(call)[0];
+                let func_ref = self.get_func_ref("channel_recv_n")?;
+                self.builder.ins().call(func_ref, &[channel_val, n, list_val]);
+                self.track_temp_rc_value(list_val, &list_ty);
+                Ok(list_val)
+            }
+            "drain" => {
+                let func_ref = self.get_func_ref("channel_drain")?;
+                let tag = self.builder.ins().iconst(types::I8, tag);
+                let call = self.builder.ins().call(func_ref, &[channel_val, tag]);
+                let list_val = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(list_val, &list_ty);
+                Ok(list_val)
+            }
+            "close" => {
+                let func_ref = self.get_func_ref("channel_close")?;
+                self.builder.ins().call(func_ref, &[channel_val]);
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            _ => Err(format!("Unknown channel method: {}", method_name)),
+        }
+    }
+
     /// 编译列表方法
     fn compile_list_method(&mut self, base: &Expr, method_name: &str, args: &[Expr]) -> Result<Value, String> {
-        let list_val = self.compile_expr(base)?;
+        if method_name == "decode" || method_name == "decode_latin1" {
+            return self.compile_bytes_decode(base, method_name, args);
+        }
+        let elem_ty = match self.infer_expr_type(base) {
+            Some(BolideType::List(elem)) => *elem,
+            _ => BolideType::Int,
+        };
+        // 借用变量里的列表：push / set 修改的是变量本身，而不是它的副本
+        let list_val = self.compile_dict_ref(base)?;
 
         match method_name {
             "len" => {
-                let func_ref = *self.func_refs.get("list_len").ok_or("list_len not found")?;
+                let func_ref = self.get_func_ref("list_len")?;
                 let call = self.builder.ins().call(func_ref, &[list_val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             "push" => {
-                let func_ref = *self.func_refs.get("list_push").ok_or("list_push not found")?;
-                let val = self.compile_expr(&args[0])?;
-                // Consume value ownership
-                self.remove_temp_rc_value(val);
-                self.builder.ins().call(func_ref, &[list_val, val]);
+                let val = self.compile_list_elem_arg(&args[0], &elem_ty)?;
+                // str 列表自己 retain，其他元素的所有权移交给列表
+                if !Self::list_retains(&elem_ty) {
+                    self.remove_temp_rc_value(val);
+                }
+                self.emit_list_push(list_val, val)?;
                 Ok(self.builder.ins().iconst(types::I64, 0))
             }
             "get" => {
-                let func_ref = *self.func_refs.get("list_get").ok_or("list_get not found")?;
                 let idx = self.compile_expr(&args[0])?;
-                let call = self.builder.ins().call(func_ref, &[list_val, idx]);
-                Ok(self.builder.inst_results(call)[0])
+                self.emit_list_get(list_val, idx, &elem_ty)
             }
             "set" => {
-                let func_ref = *self.func_refs.get("list_set").ok_or("list_set not found")?;
                 let idx = self.compile_expr(&args[0])?;
-                let val = self.compile_expr(&args[1])?;
-                // Consume value ownership
-                self.remove_temp_rc_value(val);
+                let val = self.compile_list_elem_arg(&args[1], &elem_ty)?;
+                // str 列表自己 retain，其他元素的所有权移交给列表
+                if !Self::list_retains(&elem_ty) {
+                    self.remove_temp_rc_value(val);
+                }
+                self.emit_list_set(list_val, idx, val)?;
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            "sort" => {
+                let func_ref = self.get_func_ref("list_sort")?;
+                self.builder.ins().call(func_ref, &[list_val]);
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            // sort_by(cmp_fn) -> void：比较函数 fn(a: T, b: T) -> int，稳定排序（规则同 JIT）
+            "sort_by" => {
+                if args.len() != 1 {
+                    return Err("sort_by expects 1 argument".to_string());
+                }
+                // 运行时按 i64 传递元素，与 bind 相同不支持 float
+                if elem_ty == BolideType::Float {
+                    return Err("sort_by does not support float lists".to_string());
+                }
+                match self.func_value_sig(&args[0]) {
+                    Some((params, _)) if params.len() != 2 => {
+                        return Err(format!("sort_by comparator must take 2 arguments, got {}", params.len()));
+                    }
+                    Some((_, Some(BolideType::Int))) => {}
+                    Some((_, ret)) => {
+                        let ret = ret.as_ref().map_or("nothing".to_string(), crate::analysis::type_name);
+                        return Err(format!("sort_by comparator must return int, got {}", ret));
+                    }
+                    None => return Err("sort_by comparator needs a declared signature, e.g. func(T, T) -> int".to_string()),
+                }
+                let cmp_fn = self.compile_callback("sort_by", &args[0])?;
+                let func_ref = self.get_func_ref("list_sort_by")?;
+                self.builder.ins().call(func_ref, &[list_val, cmp_fn]);
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            // dedup() -> void：原地合并相邻的重复元素
+            "dedup" => {
+                if !args.is_empty() {
+                    return Err("dedup expects no arguments".to_string());
+                }
+                let func_ref = self.get_func_ref("list_dedup")?;
+                self.builder.ins().call(func_ref, &[list_val]);
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            // unique() -> list：每个值第一次出现的元素
+            "unique" => {
+                if !args.is_empty() {
+                    return Err("unique expects no arguments".to_string());
+                }
+                let func_ref = self.get_func_ref("list_unique")?;
+                let call = self.builder.ins().call(func_ref, &[list_val]);
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::List(Box::new(elem_ty)));
+                Ok(result)
+            }
+            // group_by(key_fn) -> dict<K, list<T>>：AOT 不装箱 dynamic，键函数须声明返回 int、bool 或 str
+            "group_by" => {
+                if args.len() != 1 {
+                    return Err("group_by expects 1 argument".to_string());
+                }
+                // 运行时按 i64 传递元素，与 bind 相同不支持 float
+                if elem_ty == BolideType::Float {
+                    return Err("group_by does not support float lists".to_string());
+                }
+                let key_ty = self.group_by_key_type(&args[0])?;
+                let key_fn = self.compile_callback("group_by", &args[0])?;
+                let key_tag = self.builder.ins().iconst(types::I8, Self::list_tag(&key_ty));
+                let func_ref = self.get_func_ref("list_group_by")?;
+                let call = self.builder.ins().call(func_ref, &[list_val, key_fn, key_tag]);
+                let result = self.builder.inst_results(call)[0];
+                let dict_ty = BolideType::Dict(Box::new(key_ty), Box::new(BolideType::List(Box::new(elem_ty))));
+                self.track_temp_rc_value(result, &dict_ty);
+                Ok(result)
+            }
+            // sort_by_key(key_fn) -> void（稳定排序）/ min_by(key_fn) / max_by(key_fn) -> value
+            "sort_by_key" | "min_by" | "max_by" => {
+                if args.len() != 1 {
+                    return Err(format!("{} expects 1 argument", method_name));
+                }
+                // 运行时按 i64 传递元素，与 bind 相同不支持 float
+                if elem_ty == BolideType::Float {
+                    return Err(format!("{} does not support float lists", method_name));
+                }
+                let key_ty = match self.func_value_sig(&args[0]) {
+                    Some((params, _)) if params.len() != 1 => {
+                        return Err(format!("{} key function must take 1 argument, got {}", method_name, params.len()));
+                    }
+                    Some((_, Some(ty @ (BolideType::Int | BolideType::Bool | BolideType::Float | BolideType::Str)))) => ty,
+                    Some((_, other)) => {
+                        return Err(format!("{} key function must return int, float or str, got {:?}", method_name, other));
+                    }
+                    None => return Err(format!("{} key function needs a declared signature, e.g. func(T) -> int", method_name)),
+                };
+                let key_fn = self.compile_callback(method_name, &args[0])?;
+                let key_tag = self.builder.ins().iconst(types::I8, Self::list_tag(&key_ty));
+                let func_ref = self.get_func_ref(&format!("list_{}", method_name))?;
+                let call = self.builder.ins().call(func_ref, &[list_val, key_fn, key_tag]);
+                if method_name == "sort_by_key" {
+                    return Ok(self.builder.ins().iconst(types::I64, 0));
+                }
+                // 列表仍持有元素，与 first() 相同地 retain 一份
+                let slot = self.builder.inst_results(call)[0];
+                if Self::is_rc_type(&elem_ty) {
+                    let retained = self.emit_retain(slot, &elem_ty);
+                    self.track_temp_rc_value(retained, &elem_ty);
+                    return Ok(retained);
+                }
+                Ok(slot)
+            }
+            // pop() / remove(index)：元素从列表移出，所有权交给调用方
+            "pop" | "remove" => {
+                let mut call_args = vec![list_val];
+                if method_name == "remove" {
+                    if args.len() != 1 {
+                        return Err("remove expects 1 argument".to_string());
+                    }
+                    call_args.push(self.compile_expr(&args[0])?);
+                }
+                let func_ref = self.get_func_ref(&format!("list_{}", method_name))?;
+                let call = self.builder.ins().call(func_ref, &call_args);
+                let slot = self.builder.inst_results(call)[0];
+                if Self::is_rc_type(&elem_ty) {
+                    self.track_temp_rc_value(slot, &elem_ty);
+                }
+                Ok(self.slot_to_elem(slot, &elem_ty))
+            }
+            // first() / last()：列表仍持有元素，与下标读取相同地 retain 一份
+            "first" | "last" => {
+                let func_ref = self.get_func_ref(&format!("list_{}", method_name))?;
+                let call = self.builder.ins().call(func_ref, &[list_val]);
+                let slot = self.builder.inst_results(call)[0];
+                if Self::is_rc_type(&elem_ty) {
+                    let retained = self.emit_retain(slot, &elem_ty);
+                    self.track_temp_rc_value(retained, &elem_ty);
+                    return Ok(retained);
+                }
+                Ok(self.slot_to_elem(slot, &elem_ty))
+            }
+            "insert" => {
+                if args.len() != 2 {
+                    return Err("insert expects 2 arguments".to_string());
+                }
+                let idx = self.compile_expr(&args[0])?;
+                let val = self.compile_list_elem_arg(&args[1], &elem_ty)?;
+                // str 列表自己 retain，其他元素的所有权移交给列表
+                if !Self::list_retains(&elem_ty) {
+                    self.remove_temp_rc_value(val);
+                }
+                let val = self.elem_to_slot(val);
+                let func_ref = self.get_func_ref("list_insert")?;
                 self.builder.ins().call(func_ref, &[list_val, idx, val]);
                 Ok(self.builder.ins().iconst(types::I64, 0))
             }
+            "contains" | "index_of" | "count" => {
+                if args.len() != 1 {
+                    return Err(format!("{} expects 1 argument", method_name));
+                }
+                let val = self.compile_list_elem_arg(&args[0], &elem_ty)?;
+                let val = self.elem_to_slot(val);
+                let func_ref = self.get_func_ref(&format!("list_{}", method_name))?;
+                let call = self.builder.ins().call(func_ref, &[list_val, val]);
+                Ok(self.builder.inst_results(call)[0])
+            }
+            "clear" | "reverse" => {
+                let func_ref = self.get_func_ref(&format!("list_{}", method_name))?;
+                self.builder.ins().call(func_ref, &[list_val]);
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            "is_empty" => {
+                let func_ref = self.get_func_ref("list_is_empty")?;
+                let call = self.builder.ins().call(func_ref, &[list_val]);
+                Ok(self.builder.inst_results(call)[0])
+            }
+            // copy() / clone() -> list（与 JIT 相同，独立的一份）
+            "copy" | "clone" => {
+                let func_ref = self.get_func_ref("list_clone")?;
+                let call = self.builder.ins().call(func_ref, &[list_val]);
+                let copy = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(copy, &BolideType::List(Box::new(elem_ty)));
+                Ok(copy)
+            }
             _ => Err(format!("Unknown list method: {}", method_name)),
         }
     }
 
-    /// 编译字符串方法
-    fn compile_string_method(&mut self, base: &Expr, method_name: &str, _args: &[Expr]) -> Result<Value, String> {
-        let _str_val = self.compile_expr(base)?;
+    /// 编译 `b.decode(encoding, mode)` / `b.decode_latin1()`，`list<int>` 作为字节序列
+    fn compile_bytes_decode(&mut self, base: &Expr, method_name: &str, args: &[Expr]) -> Result<Value, String> {
+        if let Some(BolideType::List(elem)) = self.infer_expr_type(base) {
+            if *elem != BolideType::Int {
+                return Err(format!("{} expects list<int> of bytes, got list<{:?}>", method_name, elem));
+            }
+        }
+        let (func_name, max_args) = if method_name == "decode" { ("bytes_decode", 2) } else { ("bytes_decode_latin1", 0) };
+        if args.len() > max_args || (method_name == "decode" && args.is_empty()) {
+            return Err(format!("{} expects {} argument(s)", method_name, if max_args == 0 { "0" } else { "1 or 2" }));
+        }
+        let mut arg_vals = vec![self.compile_expr(base)?];
+        for arg in args {
+            arg_vals.push(self.compile_expr(arg)?);
+        }
+        // 省略 mode 时为 strict
+        if method_name == "decode" && args.len() == 1 {
+            arg_vals.push(self.builder.ins().iconst(self.ptr_type, 0));
+        }
+        let func_ref = self.get_func_ref(func_name)?;
+        let call = self.builder.ins().call(func_ref, &arg_vals);
+        let result = self.builder.inst_results(call)[0];
+        self.track_temp_rc_value(result, &BolideType::Str);
+        Ok(result)
+    }
 
-        match method_name {
-            // 可以添加更多字符串方法
-            _ => Err(format!("Unknown string method: {}", method_name)),
+    /// 编译字符串方法
+    fn compile_string_method(&mut self, base: &Expr, method_name: &str, args: &[Expr]) -> Result<Value, String> {
+        let (func_name, arity, result_ty) = match method_name {
+            "split" => ("string_split", 1, Some(BolideType::List(Box::new(BolideType::Str)))),
+            "trim" | "strip" => ("string_trim", 0, Some(BolideType::Str)),
+            "to_upper" | "upper" => ("string_upper", 0, Some(BolideType::Str)),
+            "to_lower" | "lower" => ("string_lower", 0, Some(BolideType::Str)),
+            "contains" => ("string_contains", 1, None),
+            "replace" => ("string_replace", 2, Some(BolideType::Str)),
+            "encode" => ("string_encode", 1, Some(BolideType::List(Box::new(BolideType::Int)))),
+            _ => return Err(format!("Unknown string method: {}", method_name)),
+        };
+        // 不带参数的 split() 按空白拆分
+        let optional = method_name == "split" && args.is_empty();
+        if args.len() != arity && !optional {
+            return Err(format!("{} expects {} argument(s)", method_name, arity));
+        }
+        let mut arg_vals = vec![self.compile_expr(base)?];
+        for arg in args {
+            arg_vals.push(self.compile_expr(arg)?);
+        }
+        if optional {
+            arg_vals.push(self.builder.ins().iconst(self.ptr_type, 0));
+        }
+        let func_ref = self.get_func_ref(func_name)?;
+        let call = self.builder.ins().call(func_ref, &arg_vals);
+        let result = self.builder.inst_results(call)[0];
+        if let Some(ty) = result_ty {
+            self.track_temp_rc_value(result, &ty);
         }
+        Ok(result)
     }
 
     /// 编译命名函数调用
     fn compile_named_call(&mut self, name: &str, args: &[Expr]) -> Result<Value, String> {
-        // 处理 print 函数
-        if name == "print" && args.len() == 1 {
+        // 间接调用：通过函数类型的变量调用
+        match self.var_types.get(name).or_else(|| self.global_var_types.get(name)).cloned() {
+            Some(BolideType::Func) => return self.compile_indirect_call(name, args, None),
+            Some(BolideType::FuncSig(params, ret)) => {
+                return self.compile_indirect_call(name, args, Some((params, ret.map(|ret| *ret))));
+            }
+            _ => {}
+        }
+
+        // 关键字实参和省略的默认参数：按参数顺序整理好再编译
+        if let Some(params) = self.func_params.get(name).filter(|_| !self.is_list_aggregate_call(name, args)) {
+            if let Some(args) = call_args::positional_args(name, params, args)? {
+                return self.compile_named_call(name, &args);
+            }
+        }
+
+        // 处理 print 函数
+        if name == "print" && args.len() == 1 {
             return self.compile_print(&args[0]);
         }
+        if name == "printr" {
+            if args.len() != 1 {
+                return Err("printr expects 1 argument".to_string());
+            }
+            return self.compile_print_inline(&args[0]);
+        }
+        if name == "flush" {
+            if !args.is_empty() {
+                return Err("flush expects no arguments".to_string());
+            }
+            let func_ref = self.get_func_ref("print_flush")?;
+            self.builder.ins().call(func_ref, &[]);
+            return Ok(self.builder.ins().iconst(types::I64, 0));
+        }
 
         // 处理类型转换和内置函数
         match name {
+            "packed_list" => return Err(PACKED_LIST_UNSUPPORTED.to_string()),
             "int" => return self.compile_to_int(args),
             "float" => return self.compile_to_float(args),
+            "parse_int" => return self.compile_parse_int(args),
+            "parse_float" => return self.compile_parse_float(args),
             "str" => return self.compile_to_str(args),
             "bigint" => return self.compile_to_bigint(args),
             "decimal" => return self.compile_to_decimal(args),
             "input" => return self.compile_input(args),
+            "read_file" => {
+                if args.len() != 1 {
+                    return Err("read_file expects 1 argument".to_string());
+                }
+                match self.infer_expr_type(&args[0]) {
+                    Some(BolideType::Str) | None => {}
+                    Some(ty) => return Err(format!("read_file expects a str argument, got {:?}", ty)),
+                }
+                let path = self.compile_expr(&args[0])?;
+                let result = self.compile_io_read("file_read", "file_read_async", path)?;
+                self.track_temp_rc_value(result, &BolideType::Str);
+                return Ok(result);
+            }
+            "write_lines" => {
+                if args.len() != 2 {
+                    return Err("write_lines expects 2 arguments".to_string());
+                }
+                match self.infer_expr_type(&args[0]) {
+                    Some(BolideType::Str) | None => {}
+                    Some(ty) => return Err(format!("write_lines expects a str path, got {:?}", ty)),
+                }
+                match self.infer_expr_type(&args[1]) {
+                    Some(BolideType::List(elem)) if *elem == BolideType::Str => {}
+                    None => {}
+                    Some(ty) => return Err(format!("write_lines expects a list<str>, got {:?}", ty)),
+                }
+                let path = self.compile_expr(&args[0])?;
+                let lines = self.compile_expr(&args[1])?;
+                let func_ref = self.get_func_ref("file_write_lines")?;
+                self.builder.ins().call(func_ref, &[path, lines]);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+            "write_file" | "append_file" => {
+                if args.len() != 2 {
+                    return Err(format!("{} expects 2 arguments", name));
+                }
+                for (arg, what) in args.iter().zip(["path", "contents"]) {
+                    match self.infer_expr_type(arg) {
+                        Some(BolideType::Str) | None => {}
+                        Some(ty) => return Err(format!("{} expects a str {}, got {:?}", name, what, ty)),
+                    }
+                }
+                let path = self.compile_expr(&args[0])?;
+                let contents = self.compile_expr(&args[1])?;
+                let runtime = if name == "write_file" { "file_write" } else { "file_append" };
+                let func_ref = self.get_func_ref(runtime)?;
+                let call = self.builder.ins().call(func_ref, &[path, contents]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            "file_exists" | "read_lines" | "remove_file" => {
+                if args.len() != 1 {
+                    return Err(format!("{} expects 1 argument", name));
+                }
+                match self.infer_expr_type(&args[0]) {
+                    Some(BolideType::Str) | None => {}
+                    Some(ty) => return Err(format!("{} expects a str argument, got {:?}", name, ty)),
+                }
+                let path = self.compile_expr(&args[0])?;
+                let runtime = match name {
+                    "file_exists" => "file_exists",
+                    "remove_file" => "file_remove",
+                    _ => "file_lines",
+                };
+                let func_ref = self.get_func_ref(runtime)?;
+                let call = self.builder.ins().call(func_ref, &[path]);
+                let result = self.builder.inst_results(call)[0];
+                if name == "read_lines" {
+                    self.track_temp_rc_value(result, &BolideType::List(Box::new(BolideType::Str)));
+                }
+                return Ok(result);
+            }
+            "format_row" => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err("format_row expects 1 or 2 arguments".to_string());
+                }
+                match self.infer_expr_type(&args[0]) {
+                    Some(BolideType::List(elem)) if matches!(*elem,
+                        BolideType::Int | BolideType::Float | BolideType::Bool | BolideType::Str
+                        | BolideType::BigInt | BolideType::Decimal | BolideType::Dynamic) => {}
+                    None => {}
+                    Some(ty) => return Err(format!("format_row expects a list of int, float, bool, str, bigint, decimal or dynamic, got {:?}", ty)),
+                }
+                if let Some(sep) = args.get(1) {
+                    match self.infer_expr_type(sep) {
+                        Some(BolideType::Str) | None => {}
+                        Some(ty) => return Err(format!("format_row expects a str separator, got {:?}", ty)),
+                    }
+                }
+                let fields = self.compile_expr(&args[0])?;
+                let sep = match args.get(1) {
+                    Some(sep) => self.compile_expr(sep)?,
+                    None => self.builder.ins().iconst(self.ptr_type, 0),
+                };
+                let func_ref = self.get_func_ref("format_row")?;
+                let call = self.builder.ins().call(func_ref, &[fields, sep]);
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::Str);
+                return Ok(result);
+            }
+            "assert" => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err("assert expects 1 or 2 arguments".to_string());
+                }
+                match self.infer_expr_type(&args[0]) {
+                    Some(BolideType::Bool | BolideType::Int) | None => {}
+                    Some(ty) => return Err(format!("assert expects a bool condition, got {:?}", ty)),
+                }
+                if let Some(message) = args.get(1) {
+                    match self.infer_expr_type(message) {
+                        Some(BolideType::Str) | None => {}
+                        Some(ty) => return Err(format!("assert expects a str message, got {:?}", ty)),
+                    }
+                }
+                let cond = self.compile_expr(&args[0])?;
+                let message = match args.get(1) {
+                    Some(message) => self.compile_expr(message)?,
+                    None => self.builder.ins().iconst(self.ptr_type, 0),
+                };
+                let func_ref = self.get_func_ref("assert")?;
+                self.builder.ins().call(func_ref, &[cond, message]);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+            "assert_eq" => {
+                if args.len() != 2 {
+                    return Err("assert_eq expects 2 arguments".to_string());
+                }
+                let left_ty = self.infer_expr_type(&args[0]);
+                let right_ty = self.infer_expr_type(&args[1]);
+                let func_name = match (&left_ty, &right_ty) {
+                    (Some(BolideType::Int), Some(BolideType::Int)) | (Some(BolideType::Bool), Some(BolideType::Bool)) => "assert_eq_int",
+                    (Some(BolideType::Float), Some(BolideType::Float | BolideType::Int))
+                    | (Some(BolideType::Int), Some(BolideType::Float)) => "assert_eq_float",
+                    (Some(BolideType::Str), Some(BolideType::Str)) => "assert_eq_str",
+                    (Some(BolideType::List(a)), Some(BolideType::List(b))) if a == b => "assert_eq_list",
+                    _ => return Err(format!(
+                        "assert_eq compares two int, bool, float, str or list values of the same type, got {:?} and {:?}",
+                        left_ty, right_ty
+                    )),
+                };
+                let mut values = Vec::with_capacity(2);
+                for arg in args {
+                    let val = self.compile_expr(arg)?;
+                    let is_int = self.builder.func.dfg.value_type(val) != types::F64;
+                    values.push(if func_name == "assert_eq_float" && is_int {
+                        self.builder.ins().fcvt_from_sint(types::F64, val)
+                    } else {
+                        val
+                    });
+                }
+                let func_ref = self.get_func_ref(func_name)?;
+                self.builder.ins().call(func_ref, &values);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+            "set_io_workers" => {
+                if args.len() != 1 {
+                    return Err("set_io_workers expects 1 argument".to_string());
+                }
+                let n = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("set_io_workers")?;
+                self.builder.ins().call(func_ref, &[n]);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+            "set_coroutine_workers" => {
+                if args.len() != 1 {
+                    return Err("set_coroutine_workers expects 1 argument".to_string());
+                }
+                let n = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("coroutine_set_workers")?;
+                self.builder.ins().call(func_ref, &[n]);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+            "timer_start" | "timer_elapsed_ns" | "timer_elapsed_ms" => return self.compile_timer_call(name, args),
+            // deadline(d)：从现在起 d 之后的时间点；remaining(dl)：距离时间点还剩多久（不小于 0）
+            "deadline" | "remaining" => {
+                let expected = if name == "deadline" { BolideType::Duration } else { BolideType::Deadline };
+                if args.len() != 1 || self.infer_expr_type(&args[0]).as_ref() != Some(&expected) {
+                    return Err(format!("{} expects 1 {} argument", name, crate::analysis::type_name(&expected)));
+                }
+                let val = self.compile_expr(&args[0])?;
+                let runtime = if name == "deadline" { "deadline" } else { "deadline_remaining" };
+                let func_ref = self.get_func_ref(runtime)?;
+                let call = self.builder.ins().call(func_ref, &[val]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            "hash" => return self.compile_hash(args),
+            "len" => return self.compile_len(args),
+            "weak_dict" => {
+                return Err("weak_dict() needs a declared type, e.g. let cache: weak_dict<Node, int> = weak_dict()".to_string());
+            }
+            "default_dict" => {
+                return Err("default_dict() needs a declared dict type, e.g. let d: dict<int, list<int>> = default_dict()".to_string());
+            }
+            // saturating_* / wrapping_* / checked_*：显式指定溢出行为的整数运算（见 overflow 模块）
+            name if overflow::lookup(name).is_some() => {
+                let (op, value, overflowed) = self.compile_int_intrinsic(name, args)?;
+                return match overflowed {
+                    Some(overflowed) if op.mode == overflow::Mode::Checked => self.emit_checked_tuple(value, overflowed),
+                    _ => Ok(value),
+                };
+            }
+            "intern" => return self.compile_intern(args),
+            // set_rounding(mode) - 设置当前线程的 decimal 舍入模式，返回之前的模式
+            "set_rounding" => {
+                if args.len() != 1 {
+                    return Err("set_rounding expects 1 argument".to_string());
+                }
+                let mode = self.compile_rounding_mode(&args[0])?;
+                let func_ref = self.get_func_ref("decimal_set_rounding")?;
+                let call = self.builder.ins().call(func_ref, &[mode]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            // set_div_precision(digits) - 设置当前线程 decimal 除法保留的小数位数（负数恢复默认），返回之前的设置
+            "set_div_precision" => {
+                if args.len() != 1 {
+                    return Err("set_div_precision expects 1 argument".to_string());
+                }
+                let digits = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("decimal_set_div_precision")?;
+                let call = self.builder.ins().call(func_ref, &[digits]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            // round(x, digits) / floor(x) / ceil(x)；同名的用户函数优先
+            "round" | "floor" | "ceil" if !self.func_params.contains_key(name) => {
+                return self.compile_rounding_call(name, args);
+            }
+            "intern_stats" | "intern_clear" => {
+                if !args.is_empty() {
+                    return Err(format!("{} expects no arguments", name));
+                }
+                let func_ref = self.get_func_ref(&format!("string_{}", name))?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            // error() -> int / error_message() -> str / clear_error()：当前线程最近一次可恢复的运行时错误
+            "error" | "error_message" | "clear_error" => {
+                if !args.is_empty() {
+                    return Err(format!("{} expects no arguments", name));
+                }
+                let runtime_name = match name {
+                    "error" => "error_code",
+                    "error_message" => "error_message",
+                    _ => "error_clear",
+                };
+                let func_ref = self.get_func_ref(runtime_name)?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                return match name {
+                    "error" => Ok(self.builder.inst_results(call)[0]),
+                    "error_message" => {
+                        let result = self.builder.inst_results(call)[0];
+                        self.track_temp_rc_value(result, &BolideType::Str);
+                        Ok(result)
+                    }
+                    _ => Ok(self.builder.ins().iconst(types::I64, 0)),
+                };
+            }
+            // exit(code)：刷新标准输出后立即结束进程
+            "exit" => {
+                if args.len() != 1 {
+                    return Err("exit expects 1 argument".to_string());
+                }
+                let code_ty = self.infer_expr_type(&args[0]);
+                if code_ty != Some(BolideType::Int) {
+                    return Err(format!("exit expects an int status code, got {:?}", code_ty));
+                }
+                let code = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("exit")?;
+                self.builder.ins().call(func_ref, &[code]);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+            "thread_id" => {
+                if !args.is_empty() {
+                    return Err("thread_id expects no arguments".to_string());
+                }
+                let func_ref = self.get_func_ref("thread_id")?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            "loop_count" => {
+                if !args.is_empty() {
+                    return Err("loop_count expects no arguments".to_string());
+                }
+                let Some(frame) = self.loops.last_mut() else {
+                    return Err("loop_count() can only be used inside a while or for loop".to_string());
+                };
+                frame.counter_used = true;
+                let var = frame.counter;
+                return Ok(self.builder.use_var(var));
+            }
+            "platform" | "temp_dir" => {
+                if !args.is_empty() {
+                    return Err(format!("{} expects no arguments", name));
+                }
+                let func_ref = self.get_func_ref(name)?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::Str);
+                return Ok(result);
+            }
+            "version" => {
+                if !args.is_empty() {
+                    return Err("version expects no arguments".to_string());
+                }
+                let func_ref = self.get_func_ref("version")?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::Str);
+                return Ok(result);
+            }
+            "version_at_least" | "has_feature" => {
+                if args.len() != 1 {
+                    return Err(format!("{} expects 1 argument", name));
+                }
+                match self.infer_expr_type(&args[0]) {
+                    Some(BolideType::Str) | None => {}
+                    Some(ty) => return Err(format!("{} expects a str argument, got {:?}", name, ty)),
+                }
+                let val = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref(name)?;
+                let call = self.builder.ins().call(func_ref, &[val]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            "object_debug_enable" => {
+                if args.len() != 1 {
+                    return Err("object_debug_enable expects 1 argument".to_string());
+                }
+                match self.infer_expr_type(&args[0]) {
+                    Some(BolideType::Bool | BolideType::Int) | None => {}
+                    Some(ty) => return Err(format!("object_debug_enable expects a bool argument, got {:?}", ty)),
+                }
+                let on = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("object_debug_enable")?;
+                let call = self.builder.ins().call(func_ref, &[on]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            "object_live_count" | "object_dump_live" => {
+                if !args.is_empty() {
+                    return Err(format!("{} expects no arguments", name));
+                }
+                let func_ref = self.get_func_ref(name)?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                return Ok(match self.builder.inst_results(call).first() {
+                    Some(&count) => count,
+                    None => self.builder.ins().iconst(types::I64, 0),
+                });
+            }
+            name if self.is_list_aggregate_call(name, args) => {
+                return self.compile_list_aggregate(name, &args[0]);
+            }
+            // map(f, xs) / filter(f, xs) / reduce(f, xs, init)；同名的用户函数优先
+            "map" | "filter" | "reduce" if !self.func_params.contains_key(name) => {
+                return self.compile_higher_order(name, args);
+            }
+            "bind" if !self.func_params.contains_key(name) => return self.compile_bind(args),
             "join" => return self.compile_join(args),
+            "join_ok" => return Err(JOIN_OK_UNSUPPORTED.to_string()),
+            "cancelled" => {
+                if !args.is_empty() {
+                    return Err("cancelled expects no arguments".to_string());
+                }
+                let func_ref = self.get_func_ref("coroutine_cancelled")?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
             "channel" => return self.compile_channel_create(args),
             _ => {}
         }
@@ -2425,16 +5265,28 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             return self.compile_async_call(name, args);
         }
 
+        if let Some((_, extern_func)) = self.extern_funcs.get(name).cloned() {
+            return self.compile_extern_call(&extern_func, args);
+        }
+
         // 查找函数引用
-        let func_ref = *self.func_refs.get(name)
-            .ok_or_else(|| format!("Function not found: {}", name))?;
+        let func_ref = self.get_func_ref(name).map_err(|_| format!("Function not found: {}", name))?;
 
         // 编译参数
+        let param_types: Vec<BolideType> = self.func_params.get(name)
+            .map(|params| params.iter().map(|p| p.ty.clone()).collect())
+            .unwrap_or_default();
+        let is_constructor = self.classes.contains_key(name);
         let mut arg_vals = Vec::new();
-        for arg in args {
-            let val = self.compile_expr(arg)?;
-            // Consume temp RC value (pass ownership to callee)
-            self.remove_temp_rc_value(val);
+        for (i, arg) in args.iter().enumerate() {
+            let val = self.compile_expr_expecting(arg, param_types.get(i))?;
+            // 参数的所有权交给被调函数；构造函数把参数存进字段，按绑定位置处理
+            let val = if is_constructor {
+                self.take_binding_value(arg, val)
+            } else {
+                self.remove_temp_rc_value(val);
+                val
+            };
             arg_vals.push(val);
         }
 
@@ -2455,30 +5307,266 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         }
     }
 
+    /// 编译间接函数调用（通过函数类型的变量，规则同 JIT）
+    ///
+    /// 最高位为 1 的是 bind 生成的函数值，经 `bolide_closure_call` 分发，运行时借用实参，调用后由这里释放；
+    /// 普通函数地址直接调用，和具名调用一样把实参交给被调函数。
+    fn compile_indirect_call(&mut self, var_name: &str, args: &[Expr], func_sig: Option<(Vec<BolideType>, Option<BolideType>)>) -> Result<Value, String> {
+        let func_ptr = self.compile_ident(var_name)?;
+
+        let mut arg_vals = Vec::new();
+        let mut arg_types = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            let ty = match &func_sig {
+                Some((params, _)) => params.get(i).cloned(),
+                None => self.infer_expr_type(arg),
+            }.unwrap_or(BolideType::Int);
+            let val = self.compile_expr(arg)?;
+            self.remove_temp_rc_value(val);
+            arg_vals.push(val);
+            arg_types.push(ty);
+        }
+
+        let mut sig = self.module.make_signature();
+        for ty in &arg_types {
+            sig.params.push(AbiParam::new(self.bolide_type_to_cranelift(ty)));
+        }
+        let ret_ty = func_sig.and_then(|(_, ret)| ret);
+        let ret_cl_type = ret_ty.as_ref().map_or(types::I64, |ty| self.bolide_type_to_cranelift(ty));
+        sig.returns.push(AbiParam::new(ret_cl_type));
+        let sig_ref = self.builder.import_signature(sig);
+
+        let is_bound = self.builder.ins().icmp_imm(IntCC::SignedLessThan, func_ptr, 0);
+        let direct_block = self.builder.create_block();
+        let bound_block = self.builder.create_block();
+        let merge_block = self.builder.create_block();
+        self.builder.append_block_param(merge_block, ret_cl_type);
+        self.builder.ins().brif(is_bound, bound_block, &[], direct_block, &[]);
+
+        // 普通函数地址：被调函数取得实参的所有权
+        self.builder.switch_to_block(direct_block);
+        self.builder.seal_block(direct_block);
+        let call = self.builder.ins().call_indirect(sig_ref, func_ptr, &arg_vals);
+        let direct_result = self.builder.inst_results(call)[0];
+        self.builder.ins().jump(merge_block, &[direct_result]);
+
+        // 绑定函数：实参写入栈上数组，交给 closure_call，返回后释放
+        self.builder.switch_to_block(bound_block);
+        self.builder.seal_block(bound_block);
+        let slot = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            (arg_vals.len().max(1) * 8) as u32,
+            0,
+        ));
+        let array_ptr = self.builder.ins().stack_addr(self.ptr_type, slot, 0);
+        for (i, &val) in arg_vals.iter().enumerate() {
+            let val = if self.builder.func.dfg.value_type(val) == types::F64 {
+                self.builder.ins().bitcast(types::I64, MemFlags::new(), val)
+            } else {
+                val
+            };
+            self.builder.ins().store(MemFlags::new(), val, array_ptr, (i * 8) as i32);
+        }
+        let closure_call = self.get_func_ref("closure_call")?;
+        let argc = self.builder.ins().iconst(types::I64, arg_vals.len() as i64);
+        let call = self.builder.ins().call(closure_call, &[func_ptr, array_ptr, argc]);
+        let bound_result = self.builder.inst_results(call)[0];
+        for (&val, ty) in arg_vals.iter().zip(&arg_types) {
+            if Self::is_rc_type(ty) {
+                self.emit_release(val, ty);
+            }
+        }
+        let bound_result = if ret_cl_type == types::F64 {
+            self.builder.ins().bitcast(types::F64, MemFlags::new(), bound_result)
+        } else {
+            bound_result
+        };
+        self.builder.ins().jump(merge_block, &[bound_result]);
+
+        self.builder.switch_to_block(merge_block);
+        self.builder.seal_block(merge_block);
+        let result = self.builder.block_params(merge_block)[0];
+        if let Some(ty) = ret_ty.filter(Self::is_rc_type) {
+            self.track_temp_rc_value(result, &ty);
+        }
+        Ok(result)
+    }
+
+    /// 编译 extern 函数调用（和 JIT 的 `compile_extern_call` 相同的转换）
+    ///
+    /// `cstr`（`*char`）参数传 str 的字节，str 仍归调用者，语句结束时释放；`cstr` 返回值复制成新的 str。
+    fn compile_extern_call(&mut self, extern_func: &bolide_parser::ExternFunc, args: &[Expr]) -> Result<Value, String> {
+        let func_ref = self.get_func_ref(&extern_func.name)?;
+        let mut arg_vals = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            let param = extern_func.params.get(i);
+            // 函数指针参数（回调）传函数名时取函数地址
+            if let (Some(param), Expr::Ident(name)) = (param, arg) {
+                if matches!(param.ty, CType::FuncPtr { .. }) {
+                    if let Ok(callback) = self.get_func_ref(name) {
+                        arg_vals.push(self.builder.ins().func_addr(self.ptr_type, callback));
+                        continue;
+                    }
+                }
+            }
+            let val = self.compile_expr(arg)?;
+            let val = match param {
+                Some(param) if matches!(&param.ty, CType::Ptr(inner) if matches!(inner.as_ref(), CType::Char)) => {
+                    let as_cstr = self.get_func_ref("string_as_cstr")?;
+                    let call = self.builder.ins().call(as_cstr, &[val]);
+                    self.builder.inst_results(call)[0]
+                }
+                Some(param) => self.marshal_extern_arg(val, &param.ty)
+                    .map_err(|e| format!("extern function '{}' parameter '{}': {}", extern_func.name, param.name, e))?,
+                None => val,
+            };
+            arg_vals.push(val);
+        }
+
+        let call = self.builder.ins().call(func_ref, &arg_vals);
+        let Some(&result) = self.builder.inst_results(call).first() else {
+            return Ok(self.builder.ins().iconst(types::I64, 0));
+        };
+        let ret_ty = extern_func.return_type.as_ref().unwrap_or(&CType::Void);
+        let result_ty = self.builder.func.dfg.value_type(result);
+        if matches!(ret_ty, CType::Ptr(inner) if matches!(inner.as_ref(), CType::Char)) {
+            let string_new = self.get_func_ref("string_from_cstr")?;
+            let call = self.builder.ins().call(string_new, &[result]);
+            let string = self.builder.inst_results(call)[0];
+            self.track_temp_rc_value(string, &BolideType::Str);
+            Ok(string)
+        } else if result_ty == types::I8 || result_ty == types::I16 || result_ty == types::I32 {
+            // 无符号类型零扩展，有符号类型符号扩展
+            if Self::ctype_is_unsigned(ret_ty) {
+                Ok(self.builder.ins().uextend(types::I64, result))
+            } else {
+                Ok(self.builder.ins().sextend(types::I64, result))
+            }
+        } else if result_ty == types::F32 {
+            Ok(self.builder.ins().fpromote(types::F64, result))
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// Bolide 值转换为 C 参数类型：整数截断或扩展到参数宽度，float 按参数宽度 fdemote，int 传给浮点参数时转换
+    fn marshal_extern_arg(&mut self, val: Value, ctype: &CType) -> Result<Value, String> {
+        let expected = AotCompiler::ctype_to_cranelift(ctype, self.ptr_type);
+        let actual = self.builder.func.dfg.value_type(val);
+        if actual == expected {
+            return Ok(val);
+        }
+        Ok(match (actual.is_int(), expected.is_int()) {
+            (true, true) if actual.bits() > expected.bits() => self.builder.ins().ireduce(expected, val),
+            (true, true) if Self::ctype_is_unsigned(ctype) => self.builder.ins().uextend(expected, val),
+            (true, true) => self.builder.ins().sextend(expected, val),
+            (true, false) => self.builder.ins().fcvt_from_sint(expected, val),
+            (false, false) if expected == types::F32 => self.builder.ins().fdemote(types::F32, val),
+            (false, false) => self.builder.ins().fpromote(types::F64, val),
+            (false, true) => return Err("expects an integer, got float".to_string()),
+        })
+    }
+
+    /// 返回值零扩展的 C 整数类型
+    fn ctype_is_unsigned(ctype: &CType) -> bool {
+        matches!(ctype, CType::UChar | CType::U8 | CType::UShort | CType::U16 | CType::UInt | CType::U32 | CType::Bool)
+    }
+
+    /// extern 函数调用的 Bolide 类型：浮点返回 float，`cstr`（`*char`）返回 str，其余为 int
+    fn extern_return_type(func: &bolide_parser::ExternFunc) -> BolideType {
+        match &func.return_type {
+            Some(CType::Float | CType::Double) => BolideType::Float,
+            Some(CType::Ptr(inner)) if matches!(inner.as_ref(), CType::Char) => BolideType::Str,
+            _ => BolideType::Int,
+        }
+    }
+
     /// 编译 async 函数调用 - 启动协程并返回 Future
+    ///
+    /// 带参数时参数存进 env，经 trampoline 调用；参数的所有权和普通调用一样交给被调函数。
     fn compile_async_call(&mut self, func_name: &str, args: &[Expr]) -> Result<Value, String> {
-        // 获取函数地址
-        let target_func_ref = *self.func_refs.get(func_name)
-            .ok_or_else(|| format!("Undefined async function: {}", func_name))?;
-        let func_addr = self.builder.ins().func_addr(self.ptr_type, target_func_ref);
-
-        // 调用 coroutine_spawn_int 启动协程
-        let spawn_ref = *self.func_refs.get("coroutine_spawn_int")
-            .ok_or("coroutine_spawn_int not found")?;
-        let call = self.builder.ins().call(spawn_ref, &[func_addr]);
-        Ok(self.builder.inst_results(call)[0])
+        let type_suffix = match self.func_return_types.get(func_name).cloned().flatten() {
+            Some(BolideType::Float) => "_float",
+            Some(BolideType::Str) | Some(BolideType::BigInt) | Some(BolideType::Decimal)
+            | Some(BolideType::Dynamic) | Some(BolideType::Ptr)
+            | Some(BolideType::List(_)) | Some(BolideType::Custom(_)) => "_ptr",
+            _ => "_int",
+        };
+
+        let call = if args.is_empty() {
+            let target_func_ref = self.get_func_ref(func_name).map_err(|_| format!("Undefined async function: {}", func_name))?;
+            let func_addr = self.builder.ins().func_addr(self.ptr_type, target_func_ref);
+            let spawn_ref = self.get_func_ref(&format!("coroutine_spawn{}", type_suffix))?;
+            self.builder.ins().call(spawn_ref, &[func_addr])
+        } else {
+            let env_size = (args.len() * 8) as i64;
+            let alloc_ref = self.get_func_ref("bolide_alloc")?;
+            let size_val = self.builder.ins().iconst(types::I64, env_size);
+            let alloc_call = self.builder.ins().call(alloc_ref, &[size_val]);
+            let env_ptr = self.builder.inst_results(alloc_call)[0];
+            for (i, arg) in args.iter().enumerate() {
+                let val = self.compile_expr(arg)?;
+                self.remove_temp_rc_value(val);
+                self.builder.ins().store(MemFlags::trusted(), val, env_ptr, (i * 8) as i32);
+            }
+
+            let trampoline_name = self.get_trampoline_name(func_name);
+            let trampoline_ref = self.get_func_ref(&trampoline_name).map_err(|_| format!("Trampoline not found: {}", trampoline_name))?;
+            let func_addr = self.builder.ins().func_addr(self.ptr_type, trampoline_ref);
+            let spawn_ref = self.get_func_ref(&format!("coroutine_spawn{}_with_env", type_suffix))?;
+            self.builder.ins().call(spawn_ref, &[func_addr, env_ptr])
+        };
+        let future = self.builder.inst_results(call)[0];
+
+        // 记下函数名（报告协程的错误时使用），注册到当前的 await 作用域
+        let name = self.compile_task_name(func_name)?;
+        let set_name_ref = self.get_func_ref("coroutine_set_name")?;
+        self.builder.ins().call(set_name_ref, &[future, name]);
+        let register_ref = self.get_func_ref("scope_register")?;
+        self.builder.ins().call(register_ref, &[future]);
+        Ok(future)
     }
 
     /// 编译 print 函数
     fn compile_print(&mut self, arg: &Expr) -> Result<Value, String> {
-        let val = self.compile_expr(arg)?;
-
         // 使用类型推断来选择正确的打印函数
         let inferred_type = self.infer_expr_type(arg);
+
+        // 类实例和 duration 先转成字符串
+        if inferred_type.as_ref().and_then(Self::object_class).is_some() || inferred_type == Some(BolideType::Duration) {
+            let text = self.compile_to_str(std::slice::from_ref(arg))?;
+            let func_ref = self.get_func_ref("print_string")?;
+            self.builder.ins().call(func_ref, &[text]);
+            return Ok(self.builder.ins().iconst(types::I64, 0));
+        }
+
+        let val = self.compile_expr(arg)?;
         let func_name = self.get_print_func_name(&inferred_type);
 
-        let func_ref = *self.func_refs.get(func_name)
-            .ok_or_else(|| format!("{} not found", func_name))?;
+        let func_ref = self.get_func_ref(func_name)?;
+        self.builder.ins().call(func_ref, &[val]);
+        Ok(self.builder.ins().iconst(types::I64, 0))
+    }
+
+    /// printr(x)：和 print 的格式相同，不加换行；容器和类实例先格式化成字符串
+    fn compile_print_inline(&mut self, arg: &Expr) -> Result<Value, String> {
+        let func_name = match self.infer_expr_type(arg) {
+            Some(BolideType::Int) | None => "print_int_inline",
+            Some(BolideType::Float) => "print_float_inline",
+            Some(BolideType::Bool) => "print_bool_inline",
+            Some(BolideType::BigInt) => "print_bigint_inline",
+            Some(BolideType::Decimal) => "print_decimal_inline",
+            Some(BolideType::Str) => "print_string_inline",
+            Some(BolideType::Dynamic) => "print_dynamic_inline",
+            Some(_) => {
+                let text = self.compile_fstring(std::slice::from_ref(arg))?;
+                let func_ref = self.get_func_ref("print_string_inline")?;
+                self.builder.ins().call(func_ref, &[text]);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+        };
+        let val = self.compile_expr(arg)?;
+        let func_ref = self.get_func_ref(func_name)?;
         self.builder.ins().call(func_ref, &[val]);
         Ok(self.builder.ins().iconst(types::I64, 0))
     }
@@ -2500,6 +5588,53 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         }
     }
 
+    /// f"..."：先求出全部片段，再按 `print` 的格式追加到拼接缓冲，只分配一次结果字符串
+    fn compile_fstring(&mut self, parts: &[Expr]) -> Result<Value, String> {
+        let mut pieces = Vec::with_capacity(parts.len());
+        let mut capacity = 0;
+        for part in parts {
+            if let Expr::String(text) = part {
+                capacity += text.len();
+            }
+            let ty = self.infer_expr_type(part);
+            // 类实例和 duration 先转成字符串
+            if ty.as_ref().and_then(Self::object_class).is_some() || ty == Some(BolideType::Duration) {
+                let text = self.compile_to_str(std::slice::from_ref(part))?;
+                pieces.push((text, "string_builder_append_str"));
+                continue;
+            }
+            let append = match ty {
+                Some(BolideType::Str) => "string_builder_append_str",
+                Some(BolideType::Float) => "string_builder_append_float",
+                Some(BolideType::Bool) => "string_builder_append_bool",
+                Some(BolideType::BigInt) => "string_builder_append_bigint",
+                Some(BolideType::Decimal) => "string_builder_append_decimal",
+                Some(BolideType::Dynamic) => "string_builder_append_dynamic",
+                Some(BolideType::List(_)) => "string_builder_append_list",
+                Some(BolideType::Dict(_, _)) => "string_builder_append_dict",
+                Some(BolideType::Tuple(_)) => "string_builder_append_tuple",
+                Some(BolideType::Int) | None => "string_builder_append_int",
+                Some(other) => return Err(format!("f-string cannot format a value of type {:?}", other)),
+            };
+            let val = self.compile_expr(part)?;
+            pieces.push((val, append));
+        }
+
+        let new_ref = self.get_func_ref("string_builder_new")?;
+        let capacity = self.builder.ins().iconst(types::I64, capacity as i64);
+        let call = self.builder.ins().call(new_ref, &[capacity]);
+        let string_builder = self.builder.inst_results(call)[0];
+        for (val, append) in pieces {
+            let append_ref = self.get_func_ref(append)?;
+            self.builder.ins().call(append_ref, &[string_builder, val]);
+        }
+        let finish_ref = self.get_func_ref("string_builder_finish")?;
+        let call = self.builder.ins().call(finish_ref, &[string_builder]);
+        let result = self.builder.inst_results(call)[0];
+        self.track_temp_rc_value(result, &BolideType::Str);
+        Ok(result)
+    }
+
     /// 编译 int() 类型转换
     fn compile_to_int(&mut self, args: &[Expr]) -> Result<Value, String> {
         if args.len() != 1 {
@@ -2514,20 +5649,18 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                 Ok(self.builder.ins().fcvt_to_sint(types::I64, val))
             }
             Some(BolideType::Str) => {
-                let func_ref = *self.func_refs.get("string_to_int")
-                    .ok_or("string_to_int not found")?;
+                self.warn("int() on a str is deprecated; use parse_int(s), which also rejects out-of-range input");
+                let func_ref = self.get_func_ref("string_to_int")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             Some(BolideType::BigInt) => {
-                let func_ref = *self.func_refs.get("bigint_to_i64")
-                    .ok_or("bigint_to_i64 not found")?;
+                let func_ref = self.get_func_ref("bigint_to_i64")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             Some(BolideType::Decimal) => {
-                let func_ref = *self.func_refs.get("decimal_to_i64")
-                    .ok_or("decimal_to_i64 not found")?;
+                let func_ref = self.get_func_ref("decimal_to_i64")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
@@ -2549,14 +5682,13 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                 Ok(self.builder.ins().fcvt_from_sint(types::F64, val))
             }
             Some(BolideType::Str) => {
-                let func_ref = *self.func_refs.get("string_to_float")
-                    .ok_or("string_to_float not found")?;
+                self.warn("float() on a str is deprecated; use parse_float(s), which also rejects out-of-range input");
+                let func_ref = self.get_func_ref("string_to_float")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             Some(BolideType::Decimal) => {
-                let func_ref = *self.func_refs.get("decimal_to_f64")
-                    .ok_or("decimal_to_f64 not found")?;
+                let func_ref = self.get_func_ref("decimal_to_f64")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
@@ -2564,6 +5696,40 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         }
     }
 
+    /// 编译 parse_int(s) / parse_int(s, radix)
+    fn compile_parse_int(&mut self, args: &[Expr]) -> Result<Value, String> {
+        if args.is_empty() || args.len() > 2 {
+            return Err(format!("parse_int() expects 1 or 2 arguments, got {}", args.len()));
+        }
+        let arg_type = self.infer_expr_type(&args[0]);
+        if arg_type != Some(BolideType::Str) {
+            return Err(format!("parse_int() expects a str, got {:?}", arg_type));
+        }
+        let text = self.compile_expr(&args[0])?;
+        let radix = match args.get(1) {
+            Some(arg) => self.compile_expr(arg)?,
+            None => self.builder.ins().iconst(types::I64, 10),
+        };
+        let func_ref = self.get_func_ref("string_parse_int")?;
+        let call = self.builder.ins().call(func_ref, &[text, radix]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// 编译 parse_float(s)
+    fn compile_parse_float(&mut self, args: &[Expr]) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err(format!("parse_float() expects 1 argument, got {}", args.len()));
+        }
+        let arg_type = self.infer_expr_type(&args[0]);
+        if arg_type != Some(BolideType::Str) {
+            return Err(format!("parse_float() expects a str, got {:?}", arg_type));
+        }
+        let text = self.compile_expr(&args[0])?;
+        let func_ref = self.get_func_ref("string_parse_float")?;
+        let call = self.builder.ins().call(func_ref, &[text]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
     /// 编译 str() 类型转换
     fn compile_to_str(&mut self, args: &[Expr]) -> Result<Value, String> {
         if args.len() != 1 {
@@ -2572,41 +5738,47 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         let arg_type = self.infer_expr_type(&args[0]);
         let val = self.compile_expr(&args[0])?;
 
+        if let Some(class_name) = arg_type.as_ref().and_then(Self::object_class) {
+            let class_name = class_name.to_string();
+            let result = self.compile_object_to_string(&class_name, val)?;
+            self.track_temp_rc_value(result, &BolideType::Str);
+            return Ok(result);
+        }
+
         let val = match arg_type {
             Some(BolideType::Str) => Ok::<Value, String>(val),
             Some(BolideType::Int) => {
-                let func_ref = *self.func_refs.get("string_from_int")
-                    .ok_or("string_from_int not found")?;
+                let func_ref = self.get_func_ref("string_from_int")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             Some(BolideType::Float) => {
-                let func_ref = *self.func_refs.get("string_from_float")
-                    .ok_or("string_from_float not found")?;
+                let func_ref = self.get_func_ref("string_from_float")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             Some(BolideType::Bool) => {
-                let func_ref = *self.func_refs.get("string_from_bool")
-                    .ok_or("string_from_bool not found")?;
+                let func_ref = self.get_func_ref("string_from_bool")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             Some(BolideType::BigInt) => {
-                let func_ref = *self.func_refs.get("string_from_bigint")
-                    .ok_or("string_from_bigint not found")?;
+                let func_ref = self.get_func_ref("string_from_bigint")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             Some(BolideType::Decimal) => {
-                let func_ref = *self.func_refs.get("string_from_decimal")
-                    .ok_or("string_from_decimal not found")?;
+                let func_ref = self.get_func_ref("string_from_decimal")?;
+                let call = self.builder.ins().call(func_ref, &[val]);
+                Ok(self.builder.inst_results(call)[0])
+            }
+            Some(BolideType::Duration) => {
+                let func_ref = self.get_func_ref("string_from_duration")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             _ => {
-                let func_ref = *self.func_refs.get("string_from_int")
-                    .ok_or("string_from_int not found")?;
+                let func_ref = self.get_func_ref("string_from_int")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
@@ -2623,6 +5795,82 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         Ok(val)
     }
 
+    /// 类实例（含 weak/unowned 引用）的类名
+    fn object_class(ty: &BolideType) -> Option<&str> {
+        match ty {
+            BolideType::Custom(name) => Some(name),
+            BolideType::Weak(inner) | BolideType::Unowned(inner) => match inner.as_ref() {
+                BolideType::Custom(name) => Some(name),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// 类（或父类）中定义的方法的全名
+    fn class_method(&self, class_name: &str, method: &str) -> Option<String> {
+        let mut current = Some(class_name.to_string());
+        while let Some(name) = current {
+            let full_name = format!("{}_{}", name, method);
+            if self.functions.contains_key(&full_name) {
+                return Some(full_name);
+            }
+            current = self.classes.get(&name).and_then(|c| c.parent.clone());
+        }
+        None
+    }
+
+    /// 类实例的 `==` / `!=`：优先用类（或父类）的 `__eq__`（`!=` 先找 `__ne__`，没有时取 `__eq__` 的反），
+    /// 否则经 `object_equals` 调用合成的逐字段比较
+    fn compile_object_equals(&mut self, left: &Expr, op: &BinOp, right: &Expr, class_name: &str, right_ty: &Option<BolideType>) -> Result<Value, String> {
+        let user_ne = if matches!(op, BinOp::Ne) { self.class_method(class_name, "__ne__") } else { None };
+        let negate = matches!(op, BinOp::Ne) && user_ne.is_none();
+        let result = match user_ne.or_else(|| self.class_method(class_name, equality::USER_EQ)) {
+            Some(method) => {
+                // self 不归方法所有（语句结束时释放），参数的所有权转移给方法
+                let func_ref = self.get_func_ref(&method)?;
+                let lhs = self.compile_expr(left)?;
+                let rhs = self.compile_expr(right)?;
+                self.remove_temp_rc_value(rhs);
+                let call = self.builder.ins().call(func_ref, &[lhs, rhs]);
+                self.builder.inst_results(call)[0]
+            }
+            None => {
+                let class = match right_ty.as_ref().and_then(Self::object_class) {
+                    Some(right_class) => equality::common_class(class_name, right_class, |c| {
+                        self.classes.get(c).and_then(|info| info.parent.clone())
+                    }).ok_or_else(|| format!("Cannot compare `{}` with `{}`: the classes are unrelated", class_name, right_class))?,
+                    None if matches!(right, Expr::None) => class_name.to_string(),
+                    None => return Err(format!("Cannot compare `{}` with {:?}", class_name, right_ty)),
+                };
+                let comparator = format!("{}_{}", class, equality::DEFAULT_EQ);
+                let comparator_ref = self.get_func_ref(&comparator)?;
+                let comparator_addr = self.builder.ins().func_addr(self.ptr_type, comparator_ref);
+                let lhs = self.compile_expr(left)?;
+                let rhs = self.compile_expr(right)?;
+                let func_ref = self.get_func_ref("object_equals")?;
+                let call = self.builder.ins().call(func_ref, &[lhs, rhs, comparator_addr]);
+                self.builder.inst_results(call)[0]
+            }
+        };
+        if negate {
+            let one = self.builder.ins().iconst(types::I64, 1);
+            return Ok(self.builder.ins().isub(one, result));
+        }
+        Ok(result)
+    }
+
+    /// 类实例转字符串：优先用类（或父类）的 `__str__`，否则用合成的默认格式化
+    fn compile_object_to_string(&mut self, class_name: &str, obj: Value) -> Result<Value, String> {
+        let formatter = self.class_method(class_name, formatter::USER_STR)
+            .unwrap_or_else(|| format!("{}_{}", class_name, formatter::DEFAULT_STR));
+        let formatter_ref = self.get_func_ref(&formatter)?;
+        let formatter_addr = self.builder.ins().func_addr(self.ptr_type, formatter_ref);
+        let func_ref = self.get_func_ref("object_to_string")?;
+        let call = self.builder.ins().call(func_ref, &[obj, formatter_addr]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
     /// 编译 bigint() 类型转换
     fn compile_to_bigint(&mut self, args: &[Expr]) -> Result<Value, String> {
         if args.len() != 1 {
@@ -2634,20 +5882,17 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         let val = match arg_type {
             Some(BolideType::BigInt) => Ok::<Value, String>(val),
             Some(BolideType::Int) => {
-                let func_ref = *self.func_refs.get("bigint_from_i64")
-                    .ok_or("bigint_from_i64 not found")?;
+                let func_ref = self.get_func_ref("bigint_from_i64")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             Some(BolideType::Str) => {
-                let func_ref = *self.func_refs.get("bigint_from_str")
-                    .ok_or("bigint_from_str not found")?;
+                let func_ref = self.get_func_ref("bigint_from_str")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             _ => {
-                let func_ref = *self.func_refs.get("bigint_from_i64")
-                    .ok_or("bigint_from_i64 not found")?;
+                let func_ref = self.get_func_ref("bigint_from_i64")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
@@ -2659,6 +5904,87 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         Ok(val)
     }
 
+    /// round(x, digits = 0) / floor(x) / ceil(x)：结果与 x 同类型（与 JIT 相同）
+    fn compile_rounding_call(&mut self, func_name: &str, args: &[Expr]) -> Result<Value, String> {
+        let max_args = if func_name == "round" { 2 } else { 1 };
+        if args.is_empty() || args.len() > max_args {
+            return Err(format!("{} expects {} argument(s)", func_name, if max_args == 2 { "1 or 2" } else { "1" }));
+        }
+        let ty = self.infer_expr_type(&args[0]);
+        let value = self.compile_expr(&args[0])?;
+        let digits = match args.get(1) {
+            Some(arg) => {
+                let digits_ty = self.infer_expr_type(arg);
+                if !matches!(digits_ty, Some(BolideType::Int)) {
+                    return Err(format!("round expects an int digit count, got {:?}", digits_ty));
+                }
+                self.compile_expr(arg)?
+            }
+            None => self.builder.ins().iconst(types::I64, 0),
+        };
+        match (&ty, func_name) {
+            (Some(BolideType::Decimal), _) => {
+                let func_ref = self.get_func_ref(&format!("decimal_{}", func_name))?;
+                let call = if func_name == "round" {
+                    self.builder.ins().call(func_ref, &[value, digits])
+                } else {
+                    self.builder.ins().call(func_ref, &[value])
+                };
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::Decimal);
+                Ok(result)
+            }
+            (Some(BolideType::Float), "round") => {
+                let func_ref = self.get_func_ref("float_round")?;
+                let call = self.builder.ins().call(func_ref, &[value, digits]);
+                Ok(self.builder.inst_results(call)[0])
+            }
+            (Some(BolideType::Float), "floor") => Ok(self.builder.ins().floor(value)),
+            (Some(BolideType::Float), _) => Ok(self.builder.ins().ceil(value)),
+            (Some(BolideType::Int), _) => Ok(value),
+            _ => Err(format!("{} expects an int, float or decimal argument, got {:?}", func_name, ty)),
+        }
+    }
+
+    /// 编译 decimal 方法：round(places = 0, mode = 当前线程模式) -> decimal
+    fn compile_decimal_method(&mut self, base: &Expr, method_name: &str, args: &[Expr]) -> Result<Value, String> {
+        if method_name != "round" {
+            return Err(format!("Unknown decimal method: {}", method_name));
+        }
+        if args.len() > 2 {
+            return Err("round expects at most 2 arguments".to_string());
+        }
+        let value = self.compile_expr(base)?;
+        let places = match args.first() {
+            Some(arg) => self.compile_expr(arg)?,
+            None => self.builder.ins().iconst(types::I64, 0),
+        };
+        let mode = match args.get(1) {
+            Some(arg) => self.compile_rounding_mode(arg)?,
+            None => self.builder.ins().iconst(types::I64, -1),
+        };
+        let func_ref = self.get_func_ref("decimal_round_dp_mode")?;
+        let call = self.builder.ins().call(func_ref, &[value, places, mode]);
+        let result = self.builder.inst_results(call)[0];
+        self.track_temp_rc_value(result, &BolideType::Decimal);
+        Ok(result)
+    }
+
+    /// 编译舍入模式参数：字符串字面量在编译期解析为模式常量，其他表达式按 int 传递
+    fn compile_rounding_mode(&mut self, expr: &Expr) -> Result<Value, String> {
+        if let Expr::String(name) = expr {
+            let mode = bolide_runtime::rounding_mode_from_name(name).ok_or_else(|| format!(
+                "Unknown rounding mode \"{}\" (expected half_up, half_even, down, up, floor or ceiling)",
+                name
+            ))?;
+            return Ok(self.builder.ins().iconst(types::I64, mode));
+        }
+        if !matches!(self.infer_expr_type(expr), Some(BolideType::Int)) {
+            return Err("Rounding mode must be a string literal or an int".to_string());
+        }
+        self.compile_expr(expr)
+    }
+
     /// 编译 decimal() 类型转换
     fn compile_to_decimal(&mut self, args: &[Expr]) -> Result<Value, String> {
         if args.len() != 1 {
@@ -2670,26 +5996,22 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         let val = match arg_type {
             Some(BolideType::Decimal) => Ok::<Value, String>(val),
             Some(BolideType::Int) => {
-                let func_ref = *self.func_refs.get("decimal_from_i64")
-                    .ok_or("decimal_from_i64 not found")?;
+                let func_ref = self.get_func_ref("decimal_from_i64")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             Some(BolideType::Float) => {
-                let func_ref = *self.func_refs.get("decimal_from_f64")
-                    .ok_or("decimal_from_f64 not found")?;
+                let func_ref = self.get_func_ref("decimal_from_f64")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             Some(BolideType::Str) => {
-                let func_ref = *self.func_refs.get("decimal_from_str")
-                    .ok_or("decimal_from_str not found")?;
+                let func_ref = self.get_func_ref("decimal_from_string")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             _ => {
-                let func_ref = *self.func_refs.get("decimal_from_f64")
-                    .ok_or("decimal_from_f64 not found")?;
+                let func_ref = self.get_func_ref("decimal_from_f64")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
@@ -2703,17 +6025,26 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
 
     /// 编译 input() 函数
     fn compile_input(&mut self, args: &[Expr]) -> Result<Value, String> {
-        if args.is_empty() {
-            let func_ref = *self.func_refs.get("input")
-                .ok_or("input not found")?;
+        if self.async_funcs.contains(&self.current_func_name) {
+            if args.len() > 1 {
+                return Err("input() expects 0 or 1 argument".to_string());
+            }
+            let prompt = match args.first() {
+                Some(prompt) => self.compile_expr(prompt)?,
+                None => self.builder.ins().iconst(self.ptr_type, 0),
+            };
+            let result = self.compile_io_read("input_prompt", "input_async", prompt)?;
+            self.track_temp_rc_value(result, &BolideType::Str);
+            Ok(result)
+        } else if args.is_empty() {
+            let func_ref = self.get_func_ref("input")?;
             let call = self.builder.ins().call(func_ref, &[]);
             let result = self.builder.inst_results(call)[0];
             self.track_temp_rc_value(result, &BolideType::Str);
             Ok(result)
         } else if args.len() == 1 {
             let prompt = self.compile_expr(&args[0])?;
-            let func_ref = *self.func_refs.get("input_prompt")
-                .ok_or("input_prompt not found")?;
+            let func_ref = self.get_func_ref("input_prompt")?;
             let call = self.builder.ins().call(func_ref, &[prompt]);
             let result = self.builder.inst_results(call)[0];
             self.track_temp_rc_value(result, &BolideType::Str);
@@ -2723,29 +6054,78 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         }
     }
 
+    /// 阻塞读取：普通函数里直接调用 `blocking`；async 函数里调用 `nonblocking` 得到 future，
+    /// 再用 `io_await` 挂起等待，读取期间其他协程可以执行
+    fn compile_io_read(&mut self, blocking: &str, nonblocking: &str, arg: Value) -> Result<Value, String> {
+        let call = if self.async_funcs.contains(&self.current_func_name) {
+            let start = self.get_func_ref(nonblocking)?;
+            let call = self.builder.ins().call(start, &[arg]);
+            let future = self.builder.inst_results(call)[0];
+            let await_ref = self.get_func_ref("io_await")?;
+            self.builder.ins().call(await_ref, &[future])
+        } else {
+            let func_ref = self.get_func_ref(blocking)?;
+            self.builder.ins().call(func_ref, &[arg])
+        };
+        Ok(self.builder.inst_results(call)[0])
+    }
+
     /// 编译 join() 函数
     fn compile_join(&mut self, args: &[Expr]) -> Result<Value, String> {
         if args.len() != 1 {
             return Err("join() expects 1 argument".to_string());
         }
         let handle = self.compile_expr(&args[0])?;
-        let func_ref = *self.func_refs.get("thread_join_int")
-            .ok_or("thread_join_int not found")?;
-        let call = self.builder.ins().call(func_ref, &[handle]);
-        Ok(self.builder.inst_results(call)[0])
+        // 句柄变量记录了被 spawn 的函数，按它的返回类型取结果
+        let return_type = match &args[0] {
+            Expr::Ident(var) => self.spawn_funcs.get(var)
+                .and_then(|func| self.func_return_types.get(func).cloned().flatten()),
+            _ => None,
+        };
+        let type_suffix = Self::spawn_type_suffix(return_type.as_ref());
+        let result_type = match type_suffix {
+            "_float" => types::F64,
+            "_ptr" => self.ptr_type,
+            _ => types::I64,
+        };
+
+        let pool_is_active = self.get_func_ref("pool_is_active")?;
+        let call = self.builder.ins().call(pool_is_active, &[]);
+        let is_active = self.builder.inst_results(call)[0];
+
+        let pool_block = self.builder.create_block();
+        let thread_block = self.builder.create_block();
+        let merge_block = self.builder.create_block();
+        self.builder.append_block_param(merge_block, result_type);
+        self.builder.ins().brif(is_active, pool_block, &[], thread_block, &[]);
+
+        for (block, kind) in [(pool_block, "pool"), (thread_block, "thread")] {
+            self.builder.switch_to_block(block);
+            self.builder.seal_block(block);
+            let join_ref = self.get_func_ref(&format!("{}_join{}", kind, type_suffix))?;
+            let call = self.builder.ins().call(join_ref, &[handle]);
+            let result = self.builder.inst_results(call)[0];
+            self.builder.ins().jump(merge_block, &[result]);
+        }
+
+        self.builder.switch_to_block(merge_block);
+        self.builder.seal_block(merge_block);
+        let result = self.builder.block_params(merge_block)[0];
+        if let Some(ty) = return_type.filter(Self::is_rc_type) {
+            self.track_temp_rc_value(result, &ty);
+        }
+        Ok(result)
     }
 
     /// 编译 channel() 函数
     fn compile_channel_create(&mut self, args: &[Expr]) -> Result<Value, String> {
-        let func_ref = *self.func_refs.get("channel_create")
-            .ok_or("channel_create not found")?;
+        let func_ref = self.get_func_ref("channel_create")?;
         if args.is_empty() {
             let call = self.builder.ins().call(func_ref, &[]);
             Ok(self.builder.inst_results(call)[0])
         } else if args.len() == 1 {
             let size = self.compile_expr(&args[0])?;
-            let buffered_ref = *self.func_refs.get("channel_create_buffered")
-                .ok_or("channel_create_buffered not found")?;
+            let buffered_ref = self.get_func_ref("channel_create_buffered")?;
             let call = self.builder.ins().call(buffered_ref, &[size]);
             Ok(self.builder.inst_results(call)[0])
         } else {
@@ -2754,19 +6134,52 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
     }
 
     /// 编译索引访问
-    fn compile_index(&mut self, base: &Expr, index: &Expr) -> Result<Value, String> {
+    /// 编译切片 s[a:b] / xs[a:b]：结果是新值，登记为临时值
+    fn compile_slice(&mut self, base: &Expr, start: Option<&Expr>, end: Option<&Expr>) -> Result<Value, String> {
         let base_type = self.infer_expr_type(base);
+        let func_name = match &base_type {
+            Some(BolideType::Str) => "string_slice",
+            Some(BolideType::List(_)) => "list_slice",
+            other => return Err(format!("Slicing is not supported for {:?}", other)),
+        };
         let base_val = self.compile_expr(base)?;
+        let start_val = match start {
+            Some(e) => self.compile_expr(e)?,
+            None => self.builder.ins().iconst(types::I64, 0),
+        };
+        let end_val = match end {
+            Some(e) => self.compile_expr(e)?,
+            None => self.builder.ins().iconst(types::I64, i64::MAX),
+        };
+        let func_ref = self.get_func_ref(func_name)?;
+        let call = self.builder.ins().call(func_ref, &[base_val, start_val, end_val]);
+        let result = self.builder.inst_results(call)[0];
+        if let Some(ty) = base_type {
+            self.track_temp_rc_value(result, &ty);
+        }
+        Ok(result)
+    }
+
+    fn compile_index(&mut self, base: &Expr, index: &Expr) -> Result<Value, String> {
+        let base_type = self.infer_expr_type(base);
+        if let Some(BolideType::Dict(_, val_ty)) = &base_type {
+            if let Some(stored) = self.compile_stored_container(base, index, val_ty)? {
+                let retained = self.emit_retain(stored, val_ty);
+                self.track_temp_rc_value(retained, val_ty);
+                return Ok(retained);
+            }
+            let dict = self.compile_dict_ref(base)?;
+            let key = self.compile_expr(index)?;
+            return self.emit_dict_get(dict, val_ty, key);
+        }
+        // 变量里的列表、字符串直接借用，读一个元素不复制整个列表
+        let base_val = self.compile_dict_ref(base)?;
         let index_val = self.compile_expr(index)?;
 
-        // 根据类型选择不同的索引函数
         // 根据类型选择不同的索引函数
         match base_type {
-            Some(BolideType::List(elem_ty)) => {
-                let func_ref = *self.func_refs.get("list_get")
-                    .ok_or("list_get not found")?;
-                let call = self.builder.ins().call(func_ref, &[base_val, index_val]);
-                let val = self.builder.inst_results(call)[0];
+            Some(BolideType::List(elem_ty)) => {
+                let val = self.emit_list_get(base_val, index_val, &elem_ty)?;
                 if Self::is_rc_type(&elem_ty) {
                     let retained = self.emit_retain(val, &elem_ty);
                     self.track_temp_rc_value(retained, &elem_ty);
@@ -2775,22 +6188,14 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                     Ok(val)
                 }
             }
-            Some(BolideType::Dict(_, val_ty)) => {
-                let func_ref = *self.func_refs.get("dict_get")
-                    .ok_or("dict_get not found")?;
+            // s[i] -> int：字符码点，越界 -1
+            Some(BolideType::Str) => {
+                let func_ref = self.get_func_ref("string_char_at")?;
                 let call = self.builder.ins().call(func_ref, &[base_val, index_val]);
-                let val = self.builder.inst_results(call)[0];
-                if Self::is_rc_type(&val_ty) {
-                    let retained = self.emit_retain(val, &val_ty);
-                    self.track_temp_rc_value(retained, &val_ty);
-                    Ok(retained)
-                } else {
-                    Ok(val)
-                }
+                Ok(self.builder.inst_results(call)[0])
             }
             Some(BolideType::Tuple(inner_types)) => {
-                let func_ref = *self.func_refs.get("tuple_get")
-                    .ok_or("tuple_get not found")?;
+                let func_ref = self.get_func_ref("tuple_get")?;
                 let call = self.builder.ins().call(func_ref, &[base_val, index_val]);
                 let val = self.builder.inst_results(call)[0];
                 
@@ -2813,8 +6218,7 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             }
             _ => {
                 // If type unknown, assume tuple or dynamic
-                let func_ref = *self.func_refs.get("tuple_get")
-                    .ok_or("tuple_get not found")?;
+                let func_ref = self.get_func_ref("tuple_get")?;
                 let call = self.builder.ins().call(func_ref, &[base_val, index_val]);
                 let val = self.builder.inst_results(call)[0];
                 
@@ -2827,6 +6231,13 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
 
     /// 编译成员访问
     fn compile_member(&mut self, base: &Expr, member: &str) -> Result<Value, String> {
+        // 模块成员：全局变量读取当前值，函数当作函数值
+        if let Some(resolved) = modules::resolve_member(&self.modules, base, member) {
+            return match resolved? {
+                Member::Global(name) | Member::Function(name) => self.compile_expr(&Expr::Ident(name)),
+                Member::Class(_) => Err(format!("'{}' is a class; call it to construct an instance", modules::qualified_name(base, member))),
+            };
+        }
         let base_val = self.compile_expr(base)?;
 
         // 尝试获取基础表达式的类型
@@ -2883,14 +6294,34 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
 
     /// 推断表达式类型
     fn infer_expr_type(&self, expr: &Expr) -> Option<BolideType> {
+        // 模块成员按导入后的名字推断：`m.f(...)` 同 `@m_f(...)`，`m.X` 同 `@m_X`
+        match expr {
+            Expr::Call(callee, args) => if let Expr::Member(base, member) = callee.as_ref() {
+                match modules::resolve_member(&self.modules, base, member) {
+                    Some(Ok(Member::Class(name))) => return Some(BolideType::Custom(name)),
+                    Some(Ok(Member::Function(name) | Member::Global(name))) => {
+                        return self.infer_expr_type(&Expr::Call(Box::new(Expr::Ident(name)), args.clone()));
+                    }
+                    _ => {}
+                }
+            },
+            Expr::Member(base, member) => if let Some(Ok(Member::Global(name))) = modules::resolve_member(&self.modules, base, member) {
+                return self.infer_expr_type(&Expr::Ident(name));
+            },
+            _ => {}
+        }
         match expr {
-            Expr::Ident(name) => self.var_types.get(name).cloned(),
+            // 函数名（包括提升后的匿名函数 __lambdaN）作为值时是带签名的 func(...)
+            Expr::Ident(name) => self.var_types.get(name).or_else(|| self.global_var_types.get(name)).cloned()
+                .or_else(|| self.func_value_sig(expr).map(|(params, ret)| BolideType::FuncSig(params, ret.map(Box::new)))),
             Expr::Int(_) => Some(BolideType::Int),
             Expr::Float(_) => Some(BolideType::Float),
             Expr::Bool(_) => Some(BolideType::Bool),
-            Expr::String(_) => Some(BolideType::Str),
+            Expr::String(_) | Expr::FString(_) => Some(BolideType::Str),
             Expr::BigInt(_) => Some(BolideType::BigInt),
             Expr::Decimal(_) => Some(BolideType::Decimal),
+            Expr::Duration(_) => Some(BolideType::Duration),
+            Expr::Recv(channel) => self.channel_elem_type(channel),
             Expr::List(items) => {
                 if let Some(first) = items.first() {
                     let elem_ty = self.infer_expr_type(first).unwrap_or(BolideType::Dynamic);
@@ -2899,7 +6330,7 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                     Some(BolideType::List(Box::new(BolideType::Dynamic)))
                 }
             }
-            Expr::Dict(_) => Some(BolideType::Dict(Box::new(BolideType::Dynamic), Box::new(BolideType::Dynamic))),
+            Expr::Dict(entries) => Some(self.dict_literal_type(entries)),
             Expr::Tuple(exprs) => {
                 let elem_types: Vec<BolideType> = exprs.iter()
                     .map(|e| self.infer_expr_type(e).unwrap_or(BolideType::Dynamic))
@@ -2919,30 +6350,117 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                     }
                     BolideType::List(elem_ty) => Some(*elem_ty),
                     BolideType::Dict(_, val_ty) => Some(*val_ty),
+                    BolideType::Str => Some(BolideType::Int),
                     _ => Some(BolideType::Dynamic),
                 }
             }
-            Expr::Call(callee, _args) => {
+            Expr::Slice(base, _, _) => self.infer_expr_type(base),
+            Expr::Call(callee, args) => {
                 if let Expr::Ident(name) = callee.as_ref() {
+                    // 通过函数类型的变量调用：签名里的返回类型
+                    match self.var_types.get(name).or_else(|| self.global_var_types.get(name)) {
+                        Some(BolideType::FuncSig(_, ret)) => return ret.as_deref().cloned(),
+                        Some(BolideType::Func) => return Some(BolideType::Int),
+                        _ => {}
+                    }
                     match name.as_str() {
                         "bigint" => Some(BolideType::BigInt),
                         "decimal" => Some(BolideType::Decimal),
-                        "int" => Some(BolideType::Int),
-                        "float" => Some(BolideType::Float),
+                        "int" | "parse_int" => Some(BolideType::Int),
+                        "float" | "parse_float" => Some(BolideType::Float),
                         "str" => Some(BolideType::Str),
                         "input" => Some(BolideType::Str),
+                        "read_file" | "format_row" => Some(BolideType::Str),
+                        "intern" | "platform" | "temp_dir" | "version" | "error_message" => Some(BolideType::Str),
+                        "version_at_least" | "has_feature" | "object_debug_enable" | "cancelled" => Some(BolideType::Bool),
+                        "write_file" | "append_file" | "file_exists" | "remove_file" => Some(BolideType::Bool),
+                        "read_lines" => Some(BolideType::List(Box::new(BolideType::Str))),
+                        "map" | "filter" | "reduce" if !self.func_params.contains_key(name) => {
+                            self.higher_order_type(name, args).ok()
+                        }
+                        // 剩余参数构成新函数的签名
+                        "bind" if !self.func_params.contains_key(name) => Some(match args.first() {
+                            Some(f) => bind::result_type(self.bind_target_type(f), args.len() - 1),
+                            None => BolideType::Func,
+                        }),
+                        "timer_start" | "timer_elapsed_ns" | "thread_id" | "loop_count" | "error" => Some(BolideType::Int),
+                        "deadline" => Some(BolideType::Deadline),
+                        "remaining" => Some(BolideType::Duration),
+                        "timer_elapsed_ms" => Some(BolideType::Float),
+                        "hash" | "len" | "set_rounding" | "set_div_precision" => Some(BolideType::Int),
+                        "round" | "floor" | "ceil" if !self.func_params.contains_key(name) && !args.is_empty() => {
+                            match self.infer_expr_type(&args[0]) {
+                                Some(ty @ (BolideType::Float | BolideType::Decimal)) => Some(ty),
+                                _ => Some(BolideType::Int),
+                            }
+                        }
+                        name if overflow::lookup(name).is_some() => overflow::lookup(name).map(overflow::result_type),
+                        "sum" | "min" | "max" | "avg" if args.len() == 1 => {
+                            match self.infer_expr_type(&args[0]) {
+                                Some(list_ty @ BolideType::List(_)) => Self::list_aggregate(name, &list_ty).map(|(_, ty)| ty),
+                                _ => self.func_return_types.get(name.as_str()).cloned().flatten(),
+                            }
+                        }
+                        _ if self.extern_funcs.contains_key(name.as_str()) => {
+                            Some(Self::extern_return_type(&self.extern_funcs[name.as_str()].1))
+                        }
                         _ => {
                             // Check user-defined function return types
                             self.func_return_types.get(name.as_str()).cloned().flatten()
                         }
                     }
+                } else if let Expr::Member(base, method) = callee.as_ref() {
+                    match (self.infer_expr_type(base), method.as_str()) {
+                        (Some(BolideType::Channel(elem)), "recv_n" | "drain") => Some(BolideType::List(elem)),
+                        (Some(BolideType::Channel(_)), "send_all") => Some(BolideType::Int),
+                        (Some(BolideType::Str), "split") => Some(BolideType::List(Box::new(BolideType::Str))),
+                        (Some(BolideType::Str), "encode") => Some(BolideType::List(Box::new(BolideType::Int))),
+                        (Some(BolideType::List(_)), "decode" | "decode_latin1") => Some(BolideType::Str),
+                        (Some(BolideType::List(elem)), "get" | "pop" | "remove" | "first" | "last" | "min_by" | "max_by") => Some(*elem),
+                        (Some(BolideType::List(_)), "len" | "index_of" | "count") => Some(BolideType::Int),
+                        (Some(list @ BolideType::List(_)), "copy" | "clone" | "unique") => Some(list),
+                        (Some(BolideType::List(elem)), "group_by") => match args.first().map(|f| self.group_by_key_type(f)) {
+                            Some(Ok(key_ty)) => Some(BolideType::Dict(Box::new(key_ty), Box::new(BolideType::List(elem)))),
+                            _ => None,
+                        },
+                        (Some(BolideType::Str), "contains") => Some(BolideType::Bool),
+                        (Some(BolideType::Decimal), "round") => Some(BolideType::Decimal),
+                        (Some(BolideType::Dict(k, _)), "keys") => Some(BolideType::List(k)),
+                        (Some(BolideType::Dict(_, v)), "values") => Some(BolideType::List(v)),
+                        (Some(BolideType::Dict(_, v)), "get" | "remove") => Some(*v),
+                        (Some(dict @ BolideType::Dict(..)), "clone") => Some(dict),
+                        (Some(BolideType::Dict(..)), "len") => Some(BolideType::Int),
+                        (Some(BolideType::Dict(..)), "contains" | "is_empty") => Some(BolideType::Bool),
+                        (Some(BolideType::WeakDict(_, v)), "get") => Some(*v),
+                        (Some(BolideType::WeakDict(..)), "len" | "compact") => Some(BolideType::Int),
+                        (Some(BolideType::WeakDict(..)), "contains" | "remove" | "is_empty") => Some(BolideType::Bool),
+                        (Some(BolideType::Str), "trim" | "strip" | "to_upper" | "upper" | "to_lower" | "lower" | "replace") => Some(BolideType::Str),
+                        // 方法的返回类型，如 b.set_x(1).set_y(2)
+                        (Some(BolideType::Custom(class)), method) => {
+                            self.func_return_types.get(&format!("{}_{}", class, method)).cloned().flatten()
+                        }
+                        _ => None,
+                    }
                 } else {
                     None
                 }
             }
+            Expr::CompareChain(_, _) => Some(BolideType::Bool),
+            Expr::UnaryOp(op, operand) => match op {
+                UnaryOp::Not => Some(BolideType::Bool),
+                UnaryOp::Neg => self.infer_expr_type(operand),
+            },
             Expr::BinOp(left, op, right) => {
                 let left_ty = self.infer_expr_type(left);
                 let right_ty = self.infer_expr_type(right);
+                // 比较的结果总是 bool
+                if matches!(op, BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge | BinOp::Is | BinOp::IsNot) {
+                    return Some(BolideType::Bool);
+                }
+                let (l, r) = (left_ty.clone().unwrap_or(BolideType::Int), right_ty.clone().unwrap_or(BolideType::Int));
+                if let Some(Ok(ty)) = duration::binop_type(&l, op, &r) {
+                    return Some(ty);
+                }
                 match (&left_ty, &right_ty) {
                     (Some(BolideType::Str), Some(BolideType::Str)) => {
                         match op {
@@ -3008,37 +6526,217 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
     }
 
     /// 编译列表字面量
-    fn compile_list(&mut self, items: &[Expr]) -> Result<Value, String> {
-        let func_ref = *self.func_refs.get("list_new")
-            .ok_or("list_new not found")?;
-        let elem_type = self.builder.ins().iconst(types::I8, 0);
+    /// 编译列表字面量，`expected` 为声明的元素类型（`let xs: list<float> = []` 的空列表也带上元素类型）
+    fn compile_list(&mut self, items: &[Expr], expected: Option<&BolideType>) -> Result<Value, String> {
+        if items.len() > list_literal::CHUNK_SIZE {
+            if let Some(list) = self.compile_large_list(items)? {
+                return Ok(list);
+            }
+        }
+
+        let elem_ty = match expected {
+            Some(ty) => ty.clone(),
+            None => items.first().and_then(|item| self.infer_expr_type(item)).unwrap_or(BolideType::Int),
+        };
+        let tag = Self::list_tag(&elem_ty);
+        let func_ref = self.get_func_ref("list_new")?;
+        let elem_type = self.builder.ins().iconst(types::I8, tag);
         let call = self.builder.ins().call(func_ref, &[elem_type]);
         let list_ptr = self.builder.inst_results(call)[0];
 
-        let push_ref = *self.func_refs.get("list_push")
-            .ok_or("list_push not found")?;
         for item in items {
-            let val = self.compile_expr(item)?;
-            self.remove_temp_rc_value(val); // Consume value
-            self.builder.ins().call(push_ref, &[list_ptr, val]);
+            let val = self.compile_list_elem_arg(item, &elem_ty)?;
+            // str 列表的 push 自己 retain，临时字符串照常在语句结束时释放
+            if !Self::list_retains(&elem_ty) {
+                self.remove_temp_rc_value(val); // Consume value
+            }
+            self.emit_list_push(list_ptr, val)?;
         }
 
         Ok(list_ptr)
     }
 
+    /// 按期望的类型编译值：空的或嵌套的列表、字典字面量用声明的元素类型创建（同 JIT），
+    /// 结果和其他字面量一样作为临时值跟踪
+    fn compile_expr_expecting(&mut self, expr: &Expr, expected: Option<&BolideType>) -> Result<Value, String> {
+        let (val, ty) = match (expr, expected) {
+            (Expr::List(items), Some(ty @ BolideType::List(elem))) => (self.compile_list(items, Some(elem))?, ty),
+            (Expr::Dict(entries), Some(ty @ BolideType::Dict(..))) => (self.compile_dict(entries, Some(ty))?, ty),
+            _ => return self.compile_expr(expr),
+        };
+        self.track_temp_rc_value(val, ty);
+        Ok(val)
+    }
+
+    /// AOT 列表的运行时元素类型标签
+    ///
+    /// 标量元素带上运行时元素类型（打印、排序和比较按它解释槽位）；
+    /// str、bigint、decimal 元素带上各自的标签，列表 retain 存入的值、释放时一起释放；
+    /// 其余引用计数元素的所有权由 push 直接转交给列表，仍按 int 标记
+    fn list_tag(elem_ty: &BolideType) -> i64 {
+        match elem_ty {
+            BolideType::Float => 1,
+            BolideType::Bool => 2,
+            BolideType::Str => 3,
+            BolideType::BigInt => 4,
+            BolideType::Decimal => 5,
+            _ => 0,
+        }
+    }
+
+    /// 列表 retain / release 这种元素（push、set、insert 按借用传入，否则所有权直接移交给列表）
+    fn list_retains(elem_ty: &BolideType) -> bool {
+        matches!(Self::list_tag(elem_ty), 3..=5)
+    }
+
+    /// 编译要写入列表的值：float 列表里的 int 值转成 float，容器字面量按元素类型创建
+    fn compile_list_elem_arg(&mut self, expr: &Expr, elem_ty: &BolideType) -> Result<Value, String> {
+        let val = self.compile_expr_expecting(expr, Some(elem_ty))?;
+        if *elem_ty == BolideType::Float && self.builder.func.dfg.value_type(val) == types::I64
+            && self.infer_expr_type(expr) == Some(BolideType::Int)
+        {
+            return Ok(self.builder.ins().fcvt_from_sint(types::F64, val));
+        }
+        Ok(val)
+    }
+
+    /// f64 元素按位转成运行时的 i64 槽位
+    fn elem_to_slot(&mut self, val: Value) -> Value {
+        if self.builder.func.dfg.value_type(val) == types::F64 {
+            self.builder.ins().bitcast(types::I64, MemFlags::new(), val)
+        } else {
+            val
+        }
+    }
+
+    /// 运行时按 i64 返回的槽位还原成元素值
+    fn slot_to_elem(&mut self, slot: Value, elem_ty: &BolideType) -> Value {
+        if *elem_ty == BolideType::Float {
+            self.builder.ins().bitcast(types::F64, MemFlags::new(), slot)
+        } else {
+            slot
+        }
+    }
+
+    /// 追加元素：f64 值走 `list_push_f64`
+    fn emit_list_push(&mut self, list: Value, val: Value) -> Result<(), String> {
+        let func_name = if self.builder.func.dfg.value_type(val) == types::F64 { "list_push_f64" } else { "list_push" };
+        let func_ref = self.get_func_ref(func_name)?;
+        self.builder.ins().call(func_ref, &[list, val]);
+        Ok(())
+    }
+
+    /// 读取元素：float 列表走 `list_get_f64`
+    fn emit_list_get(&mut self, list: Value, index: Value, elem_ty: &BolideType) -> Result<Value, String> {
+        let func_name = if *elem_ty == BolideType::Float { "list_get_f64" } else { "list_get" };
+        let func_ref = self.get_func_ref(func_name)?;
+        let call = self.builder.ins().call(func_ref, &[list, index]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// 写入元素：f64 值走 `list_set_f64`
+    fn emit_list_set(&mut self, list: Value, index: Value, val: Value) -> Result<(), String> {
+        let func_name = if self.builder.func.dfg.value_type(val) == types::F64 { "list_set_f64" } else { "list_set" };
+        let func_ref = self.get_func_ref(func_name)?;
+        self.builder.ins().call(func_ref, &[list, index, val]);
+        Ok(())
+    }
+
+    /// 编译超过 `CHUNK_SIZE` 个元素的列表字面量（见 `list_literal`）
+    ///
+    /// 元素引用了局部变量、不能搬进辅助函数时返回 None，由调用方逐个 push。
+    fn compile_large_list(&mut self, items: &[Expr]) -> Result<Option<Value>, String> {
+        let elem = self.infer_expr_type(&items[0]).unwrap_or(BolideType::Int);
+        if let Some(array) = list_literal::const_array(items, &elem) {
+            let data_id = self.module.declare_anonymous_data(false, false)
+                .map_err(|e| format!("Failed to declare list data: {}", e))?;
+            let mut desc = DataDescription::new();
+            desc.set_align(8);
+            desc.define(array.bytes.into_boxed_slice());
+            self.module.define_data(data_id, &desc)
+                .map_err(|e| format!("Failed to define list data: {}", e))?;
+            let gv = self.module.declare_data_in_func(data_id, self.builder.func);
+
+            let from_const = self.get_func_ref("list_from_const_array")?;
+            let elem_type = self.builder.ins().iconst(types::I8, array.elem_type as i64);
+            let data = self.builder.ins().global_value(self.ptr_type, gv);
+            let count = self.builder.ins().iconst(types::I64, items.len() as i64);
+            let call = self.builder.ins().call(from_const, &[elem_type, data, count]);
+            return Ok(Some(self.builder.inst_results(call)[0]));
+        }
+
+        let variables = &self.variables;
+        if !items.iter().all(|e| list_literal::is_closed(e, &|name| variables.contains_key(name))) {
+            return Ok(None);
+        }
+
+        let list_new = self.get_func_ref("list_new")?;
+        let elem_type = self.builder.ins().iconst(types::I8, 0);
+        let call = self.builder.ins().call(list_new, &[elem_type]);
+        let list_ptr = self.builder.inst_results(call)[0];
+
+        // 每个辅助函数返回一段子列表，追加后释放（子列表不拥有元素）
+        let prefix = format!("__list_chunk_{}_{}", self.current_func_name, self.chunked_lists);
+        self.chunked_lists += 1;
+        let list_extend = self.get_func_ref("list_extend")?;
+        let list_release = self.get_func_ref("list_release")?;
+        for chunk in list_literal::chunk_functions(&prefix, items, &elem) {
+            let mut sig = self.module.make_signature();
+            sig.returns.push(AbiParam::new(self.ptr_type));
+            let chunk_id = self.module.declare_function(&chunk.name, Linkage::Local, &sig)
+                .map_err(|e| format!("{}", e))?;
+            let chunk_ref = self.module.declare_func_in_func(chunk_id, self.builder.func);
+            let call = self.builder.ins().call(chunk_ref, &[]);
+            let part = self.builder.inst_results(call)[0];
+            self.builder.ins().call(list_extend, &[list_ptr, part]);
+            self.builder.ins().call(list_release, &[part]);
+            self.list_chunks.push((chunk, chunk_id));
+        }
+        Ok(Some(list_ptr))
+    }
+
+    /// 编译溢出控制的整数内置函数，返回 (运算, 结果, 溢出标志)
+    fn compile_int_intrinsic(&mut self, name: &str, args: &[Expr]) -> Result<(overflow::IntOp, Value, Option<Value>), String> {
+        let op = overflow::lookup(name).ok_or_else(|| format!("Unknown function: {}", name))?;
+        let arg_types: Vec<_> = args.iter().map(|a| self.infer_expr_type(a)).collect();
+        overflow::check_args(name, &arg_types)?;
+        let a = self.compile_expr(&args[0])?;
+        let b = self.compile_expr(&args[1])?;
+        let (value, overflowed) = overflow::emit(&mut self.builder, op, a, b);
+        Ok((op, value, overflowed))
+    }
+
+    /// `checked_*` 的结果当作值使用时分配 `(ok, value)` 元组
+    fn emit_checked_tuple(&mut self, value: Value, overflowed: Value) -> Result<Value, String> {
+        let ok = self.builder.ins().icmp_imm(IntCC::Equal, overflowed, 0);
+        let ok = self.builder.ins().uextend(types::I64, ok);
+        let func_ref = self.get_func_ref("tuple_new")?;
+        let len = self.builder.ins().iconst(types::I64, 2);
+        let call = self.builder.ins().call(func_ref, &[len]);
+        let tuple_ptr = self.builder.inst_results(call)[0];
+        let set_ref = self.get_func_ref("tuple_set")?;
+        for (i, slot) in [ok, value].into_iter().enumerate() {
+            let idx = self.builder.ins().iconst(types::I64, i as i64);
+            self.builder.ins().call(set_ref, &[tuple_ptr, idx, slot]);
+        }
+        Ok(tuple_ptr)
+    }
+
     /// 编译 Tuple 字面量
     fn compile_tuple(&mut self, items: &[Expr]) -> Result<Value, String> {
-        let func_ref = *self.func_refs.get("tuple_new")
-            .ok_or("tuple_new not found")?;
+        let func_ref = self.get_func_ref("tuple_new")?;
         let len = self.builder.ins().iconst(types::I64, items.len() as i64);
         let call = self.builder.ins().call(func_ref, &[len]);
         let tuple_ptr = self.builder.inst_results(call)[0];
 
-        let set_ref = *self.func_refs.get("tuple_set")
-            .ok_or("tuple_set not found")?;
+        let set_ref = self.get_func_ref("tuple_set")?;
         for (i, item) in items.iter().enumerate() {
             let val = self.compile_expr(item)?;
-            self.remove_temp_rc_value(val); // Consume value
+            let mut val = self.take_binding_value(item, val);
+            // 元组槽位是 i64，float 以位模式存储
+            if self.builder.func.dfg.value_type(val) == types::F64 {
+                val = self.builder.ins().bitcast(types::I64, MemFlags::new(), val);
+            }
             let idx = self.builder.ins().iconst(types::I64, i as i64);
             self.builder.ins().call(set_ref, &[tuple_ptr, idx, val]);
         }
@@ -3046,79 +6744,624 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
     }
 
     /// 编译 Dict 字面量
-    fn compile_dict(&mut self, entries: &[(Expr, Expr)]) -> Result<Value, String> {
-        let func_ref = *self.func_refs.get("dict_new")
-            .ok_or("dict_new not found")?;
-        let key_type = self.builder.ins().iconst(types::I8, 0);
-        let val_type = self.builder.ins().iconst(types::I8, 0);
+    ///
+    /// 键值的运行时类型标签取自声明的类型（`let d: dict<str, int> = {}`），否则取自各项一致的类型
+    /// （见 `dict_literal_type`）。
+    fn compile_dict(&mut self, entries: &[(Expr, Expr)], declared: Option<&BolideType>) -> Result<Value, String> {
+        let (key_ty, val_ty) = match declared.cloned().unwrap_or_else(|| self.dict_literal_type(entries)) {
+            BolideType::Dict(k, v) => (*k, *v),
+            _ => (BolideType::Dynamic, BolideType::Dynamic),
+        };
+        let func_ref = self.get_func_ref("dict_new")?;
+        let key_type = self.builder.ins().iconst(types::I8, Self::dict_tag(&key_ty));
+        let val_type = self.builder.ins().iconst(types::I8, Self::dict_tag(&val_ty));
         let call = self.builder.ins().call(func_ref, &[key_type, val_type]);
         let dict_ptr = self.builder.inst_results(call)[0];
 
-        let set_ref = *self.func_refs.get("dict_set")
-            .ok_or("dict_set not found")?;
         for (key, value) in entries {
-            let k = self.compile_expr(key)?;
-            let v = self.compile_expr(value)?;
-            self.remove_temp_rc_value(k); // Consume key
-            self.remove_temp_rc_value(v); // Consume value
-            self.builder.ins().call(set_ref, &[dict_ptr, k, v]);
+            self.emit_dict_set(dict_ptr, &key_ty, &val_ty, key, value)?;
+        }
+
+        Ok(dict_ptr)
+    }
+
+    /// 字典字面量的类型：所有键（值）类型一致时取该类型，否则（包括空字面量）为 dynamic
+    fn dict_literal_type(&self, entries: &[(Expr, Expr)]) -> BolideType {
+        let uniform = |types: Vec<Option<BolideType>>| -> BolideType {
+            match types.first() {
+                Some(Some(first)) if types.iter().all(|t| t.as_ref() == Some(first)) => first.clone(),
+                _ => BolideType::Dynamic,
+            }
+        };
+        let key_ty = uniform(entries.iter().map(|(k, _)| self.infer_expr_type(k)).collect());
+        let val_ty = uniform(entries.iter().map(|(_, v)| self.infer_expr_type(v)).collect());
+        BolideType::Dict(Box::new(key_ty), Box::new(val_ty))
+    }
+
+    /// 函数值的签名：函数名取声明的参数和返回类型，变量取声明的 func(...) 类型，
+    /// 调用（如 `bind(f, 1)`）取推断的结果类型
+    fn func_value_sig(&self, expr: &Expr) -> Option<(Vec<BolideType>, Option<BolideType>)> {
+        let name = match expr {
+            Expr::Ident(name) => name,
+            Expr::Call(_, _) => match self.infer_expr_type(expr)? {
+                BolideType::FuncSig(params, ret) => return Some((params, ret.map(|ret| *ret))),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        match self.var_types.get(name).or_else(|| self.global_var_types.get(name)) {
+            Some(BolideType::FuncSig(params, ret)) => Some((params.clone(), ret.as_deref().cloned())),
+            Some(_) => None,
+            None => {
+                let params = self.func_params.get(name)?;
+                let ret = self.func_return_types.get(name).cloned().flatten();
+                Some((params.iter().map(|p| p.ty.clone()).collect(), ret))
+            }
+        }
+    }
+
+    /// group_by 的键类型：键函数须声明签名，接收 1 个参数、返回 int、bool 或 str
+    fn group_by_key_type(&self, key_fn: &Expr) -> Result<BolideType, String> {
+        match self.func_value_sig(key_fn) {
+            Some((params, _)) if params.len() != 1 => {
+                Err(format!("group_by key function must take 1 argument, got {}", params.len()))
+            }
+            Some((_, Some(ty @ (BolideType::Int | BolideType::Bool | BolideType::Str)))) => Ok(ty),
+            Some((_, other)) => Err(format!("group_by key function must return int, bool or str, got {:?}", other)),
+            None => Err("group_by key function needs a declared signature in AOT builds, e.g. func(T) -> int".to_string()),
+        }
+    }
+
+    /// bind 的目标函数的类型：函数名取声明的签名，变量取声明的类型，其他为 func
+    fn bind_target_type(&self, expr: &Expr) -> BolideType {
+        match self.func_value_sig(expr) {
+            Some((params, ret)) => BolideType::FuncSig(params, ret.map(Box::new)),
+            None => BolideType::Func,
+        }
+    }
+
+    /// bind(f, a, ...)：绑定参数和类型标签写入栈上数组，由 `bolide_bind` 生成绑定函数（规则同 JIT，见 `bind`）
+    ///
+    /// 运行时按借用方式传入绑定参数和调用实参：以函数名绑定、有引用计数参数的目标经借用适配函数调用
+    /// （见 `declare_borrow_adapters`）；绑定函数值变量时它的参数不能是引用计数类型。
+    /// AOT 不把值装箱为 dynamic，dynamic 参数按 int 绑定。
+    fn compile_bind(&mut self, args: &[Expr]) -> Result<Value, String> {
+        if args.len() < 2 {
+            return Err("bind expects a function and at least 1 argument".to_string());
+        }
+        let bound_args = &args[1..];
+        let target_ty = self.bind_target_type(&args[0]);
+        bind::check_target(&target_ty, bound_args.len())?;
+
+        let adapter = match &args[0] {
+            Expr::Ident(func_name) if !self.variables.contains_key(func_name) && !self.is_global(func_name) => {
+                Some(format!("__borrow_{}", func_name)).filter(|adapter| self.functions.contains_key(adapter))
+            }
+            _ => None,
+        };
+        if let BolideType::FuncSig(params, _) = &target_ty {
+            if let Some(ty) = params.iter().find(|ty| Self::is_rc_type(ty)).filter(|_| adapter.is_none()) {
+                return Err(format!("bind: parameters of type {} are only supported in AOT builds when the bound function is a named function",
+                    crate::analysis::type_name(ty)));
+            }
+        }
+        // 绑定参数由记录持有，不能交给 owned/ref 参数
+        if let Expr::Ident(name) = &args[0] {
+            if let Some(params) = self.func_params.get(name) {
+                if params.iter().any(|p| p.mode != ParamMode::Borrow) {
+                    return Err(format!("bind: function '{}' has owned or ref parameters", name));
+                }
+            }
+        }
+
+        let func_val = match adapter {
+            Some(adapter) => {
+                let func_ref = self.get_func_ref(&adapter)?;
+                self.builder.ins().func_addr(self.ptr_type, func_ref)
+            }
+            None => self.compile_expr(&args[0])?,
+        };
+
+        let count = bound_args.len();
+        let args_slot = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            (count * 8) as u32,
+            0,
+        ));
+        let tags_slot = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            count as u32,
+            0,
+        ));
+        let args_ptr = self.builder.ins().stack_addr(self.ptr_type, args_slot, 0);
+        let tags_ptr = self.builder.ins().stack_addr(self.ptr_type, tags_slot, 0);
+
+        for (i, arg) in bound_args.iter().enumerate() {
+            let tag = match self.infer_expr_type(arg) {
+                Some(BolideType::Dynamic) | None => bind::arg_tag(&BolideType::Int)?,
+                Some(ty) => bind::arg_tag(&ty)?,
+            };
+            let val = self.compile_expr(arg)?;
+            self.builder.ins().store(MemFlags::new(), val, args_ptr, (i * 8) as i32);
+            let tag_val = self.builder.ins().iconst(types::I8, tag as i64);
+            self.builder.ins().store(MemFlags::new(), tag_val, tags_ptr, i as i32);
+        }
+
+        // 运行时会 retain 绑定的 RC 参数，临时值照常在语句结束时释放
+        let bind_ref = self.get_func_ref("bind")?;
+        let count_val = self.builder.ins().iconst(types::I64, count as i64);
+        let call = self.builder.ins().call(bind_ref, &[func_val, args_ptr, tags_ptr, count_val]);
+        let result = self.builder.inst_results(call)[0];
+        self.track_temp_rc_value(result, &BolideType::Func);
+        Ok(result)
+    }
+
+    /// map(f, xs) / filter(f, xs) / reduce(f, xs, init) 的结果类型（规则同 JIT）
+    fn higher_order_type(&self, name: &str, args: &[Expr]) -> Result<BolideType, String> {
+        let arity = if name == "reduce" { 2 } else { 1 };
+        if args.len() != arity + 1 {
+            return Err(format!("{} expects {} arguments", name, arity + 1));
+        }
+        let elem_ty = match self.infer_expr_type(&args[1]) {
+            Some(BolideType::List(elem)) => *elem,
+            other => return Err(format!("{} expects a list as its second argument, got {:?}", name, other)),
+        };
+        if elem_ty == BolideType::Float {
+            return Err(format!("{} does not support float lists", name));
+        }
+        let (params, ret) = self.func_value_sig(&args[0])
+            .ok_or_else(|| format!("{} needs a function with a declared signature, e.g. func(int) -> int", name))?;
+        if params.len() != arity {
+            return Err(format!("{} function must take {} argument(s), got {}", name, arity, params.len()));
+        }
+        match (name, ret) {
+            ("filter", Some(BolideType::Bool)) => Ok(BolideType::List(Box::new(elem_ty))),
+            ("filter", other) => Err(format!("filter function must return bool, got {:?}", other)),
+            ("reduce", Some(BolideType::Float)) => Err("reduce does not support float accumulators".to_string()),
+            (_, None) => Err(format!("{} function must return a value", name)),
+            ("reduce", Some(acc_ty)) => Ok(acc_ty),
+            (_, Some(ret_ty)) => Ok(BolideType::List(Box::new(ret_ty))),
+        }
+    }
+
+    /// map / filter / reduce：结果列表和引用计数的累加值是临时值
+    ///
+    /// 与列表字面量相同，结果列表给标量和 str 元素带上类型标签，其余引用计数元素按 int 标记。
+    /// AOT 函数释放自己的参数，而运行时按借用方式传参：以函数名传入、有引用计数参数的回调
+    /// 经借用适配函数调用（见 `declare_borrow_adapters`）；函数值变量的参数不能是引用计数类型。
+    fn compile_higher_order(&mut self, name: &str, args: &[Expr]) -> Result<Value, String> {
+        let result_ty = self.higher_order_type(name, args)?;
+        let func = self.compile_callback(name, &args[0])?;
+        let list = self.compile_expr(&args[1])?;
+        let result = match (name, &result_ty) {
+            ("map", BolideType::List(ret_ty)) => {
+                let tag = self.builder.ins().iconst(types::I8, Self::list_tag(ret_ty));
+                let func_ref = self.get_func_ref("list_map")?;
+                let call = self.builder.ins().call(func_ref, &[list, func, tag]);
+                self.builder.inst_results(call)[0]
+            }
+            ("filter", _) => {
+                let func_ref = self.get_func_ref("list_filter")?;
+                let call = self.builder.ins().call(func_ref, &[list, func]);
+                self.builder.inst_results(call)[0]
+            }
+            _ => {
+                let init = self.compile_expr(&args[2])?;
+                let tag = self.builder.ins().iconst(types::I8, Self::dict_tag(&result_ty));
+                let func_ref = self.get_func_ref("list_reduce")?;
+                let call = self.builder.ins().call(func_ref, &[list, func, init, tag]);
+                self.builder.inst_results(call)[0]
+            }
+        };
+        self.track_temp_rc_value(result, &result_ty);
+        Ok(result)
+    }
+
+    /// 运行时回调的函数值（map / filter / reduce、sort_by 等）
+    ///
+    /// 运行时按借用方式传入元素：以函数名传入、有引用计数参数的函数换成它的借用适配函数
+    /// （见 `declare_borrow_adapters`），函数值变量的参数不能是引用计数类型。
+    fn compile_callback(&mut self, name: &str, func: &Expr) -> Result<Value, String> {
+        let adapter = match func {
+            Expr::Ident(func_name) if !self.variables.contains_key(func_name) => {
+                Some(format!("__borrow_{}", func_name)).filter(|adapter| self.functions.contains_key(adapter))
+            }
+            _ => None,
+        };
+        if let Some((params, _)) = self.func_value_sig(func) {
+            if let Some(ty) = params.iter().find(|ty| Self::is_rc_type(ty)).filter(|_| adapter.is_none()) {
+                return Err(format!("{}: callback parameters of type {} are only supported in AOT builds when the callback is a named function",
+                    name, crate::analysis::type_name(ty)));
+            }
+        }
+        if let Expr::Ident(func_name) = func {
+            if let Some(params) = self.func_params.get(func_name) {
+                if params.iter().any(|p| p.mode != ParamMode::Borrow) {
+                    return Err(format!("{}: function '{}' has owned or ref parameters", name, func_name));
+                }
+            }
+        }
+        match adapter {
+            Some(adapter) => {
+                let func_ref = self.get_func_ref(&adapter)?;
+                Ok(self.builder.ins().func_addr(self.ptr_type, func_ref))
+            }
+            None => self.compile_expr(func),
+        }
+    }
+
+    /// 字典键值的运行时类型标签（与运行时 ElementType 一致）
+    ///
+    /// AOT 不把值装箱为 dynamic：dynamic 和其他类型按 int 存放，字典不对它们做引用计数。
+    fn dict_tag(ty: &BolideType) -> i64 {
+        match ty {
+            BolideType::Float => 1,
+            BolideType::Bool => 2,
+            BolideType::Str => 3,
+            BolideType::BigInt => 4,
+            BolideType::Decimal => 5,
+            BolideType::List(_) => 6,
+            BolideType::Dict(_, _) => 8,
+            _ => 0,
+        }
+    }
+
+    /// 这个类型的键或值由字典 retain / release（否则存入时所有权直接移交给字典）
+    fn dict_retains(ty: &BolideType) -> bool {
+        Self::dict_tag(ty) >= 3
+    }
+
+    /// d[key] = value：字典 retain 的键值按借用传入，临时值在语句结束时照常释放
+    fn emit_dict_set(&mut self, dict: Value, key_ty: &BolideType, val_ty: &BolideType, key: &Expr, value: &Expr) -> Result<(), String> {
+        let k = self.compile_expr(key)?;
+        let mut v = self.compile_expr_expecting(value, Some(val_ty))?;
+        if !Self::dict_retains(key_ty) {
+            self.remove_temp_rc_value(k);
+        }
+        if !Self::dict_retains(val_ty) {
+            self.remove_temp_rc_value(v);
+        }
+        // 值是 i64，float 以位模式存储
+        if self.builder.func.dfg.value_type(v) == types::F64 {
+            v = self.builder.ins().bitcast(types::I64, MemFlags::new(), v);
+        }
+        let set_ref = self.get_func_ref("dict_set")?;
+        self.builder.ins().call(set_ref, &[dict, k, v]);
+        Ok(())
+    }
+
+    /// 字典/列表变量直接借用，不经过 compile_ident 的整表拷贝（字典操作、列表的 `+=`）；其他表达式照常编译
+    ///
+    /// 容器类型的字段和列表元素同样借用存放的那一份：`c.items.push(x)`、`grid[i][j] = v`、
+    /// `c.items += ys` 修改的是对象和外层列表里的容器。借用只在当前语句内有效。
+    fn compile_dict_ref(&mut self, expr: &Expr) -> Result<Value, String> {
+        match expr {
+            Expr::Ident(name) if self.variables.contains_key(name) => {
+                let var = self.variables[name];
+                Ok(self.builder.use_var(var))
+            }
+            Expr::Ident(name) if self.global_data_ids.contains_key(name) => {
+                self.load_global(name).ok_or_else(|| format!("Undefined variable: {}", name))
+            }
+            Expr::Index(base, index) => match self.infer_expr_type(base) {
+                Some(BolideType::Dict(_, val_ty)) => match self.compile_stored_container(base, index, &val_ty)? {
+                    Some(stored) => Ok(stored),
+                    None => self.compile_expr(expr),
+                },
+                Some(BolideType::List(elem_ty)) if Self::empty_container_tag(&elem_ty).is_some() => {
+                    let list = self.compile_dict_ref(base)?;
+                    let index = self.compile_expr(index)?;
+                    self.emit_list_get(list, index, &elem_ty)
+                }
+                _ => self.compile_expr(expr),
+            },
+            Expr::Member(base, member) => match self.container_field_offset(base, member) {
+                Some(offset) => {
+                    let object = self.compile_dict_ref(base)?;
+                    Ok(self.builder.ins().load(types::I64, MemFlags::new(), object, offset))
+                }
+                None => self.compile_expr(expr),
+            },
+            _ => self.compile_expr(expr),
+        }
+    }
+
+    /// `base.member` 是对象的列表/字典字段时返回字段偏移
+    fn container_field_offset(&self, base: &Expr, member: &str) -> Option<i32> {
+        if modules::resolve_member(&self.modules, base, member).is_some() {
+            return None;
+        }
+        let class_name = match self.infer_expr_type(base)? {
+            BolideType::Custom(name) => name,
+            BolideType::Weak(inner) | BolideType::Unowned(inner) => match *inner {
+                BolideType::Custom(name) => name,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let field = self.classes.get(&class_name)?.fields.iter().find(|field| field.name == member)?;
+        Self::empty_container_tag(&field.ty).map(|_| field.offset as i32)
+    }
+
+    /// 读取字典值（借用），RC 值 retain 后作为临时值返回
+    fn emit_dict_get(&mut self, dict: Value, val_ty: &BolideType, key: Value) -> Result<Value, String> {
+        let func_ref = self.get_func_ref("dict_get")?;
+        let call = self.builder.ins().call(func_ref, &[dict, key]);
+        let val = self.builder.inst_results(call)[0];
+        if Self::dict_retains(val_ty) {
+            let retained = self.emit_retain(val, val_ty);
+            self.track_temp_rc_value(retained, val_ty);
+            return Ok(retained);
+        }
+        if *val_ty == BolideType::Float {
+            return Ok(self.builder.ins().bitcast(types::F64, MemFlags::new(), val));
+        }
+        Ok(val)
+    }
+
+    /// 编译字典方法调用
+    fn compile_dict_method(&mut self, base: &Expr, key_ty: &BolideType, val_ty: &BolideType, method_name: &str, args: &[Expr]) -> Result<Value, String> {
+        let arity = match method_name {
+            "len" | "is_empty" | "clear" | "keys" | "values" | "clone" => 0,
+            "contains" | "get" | "remove" => 1,
+            "set" => 2,
+            _ => return Err(format!("Unknown dictionary method: {}", method_name)),
+        };
+        if args.len() != arity {
+            return Err(format!("{} expects {} argument(s)", method_name, arity));
+        }
+        let dict = self.compile_dict_ref(base)?;
+
+        match method_name {
+            "set" => {
+                self.emit_dict_set(dict, key_ty, val_ty, &args[0], &args[1])?;
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            "get" => {
+                let key = self.compile_expr(&args[0])?;
+                self.emit_dict_get(dict, val_ty, key)
+            }
+            "remove" => {
+                // 移除的值归调用者所有
+                let key = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("dict_remove")?;
+                let call = self.builder.ins().call(func_ref, &[dict, key]);
+                let val = self.builder.inst_results(call)[0];
+                if Self::dict_retains(val_ty) {
+                    self.track_temp_rc_value(val, val_ty);
+                }
+                Ok(val)
+            }
+            "contains" => {
+                let key = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("dict_contains")?;
+                let call = self.builder.ins().call(func_ref, &[dict, key]);
+                Ok(self.builder.inst_results(call)[0])
+            }
+            "clear" => {
+                let func_ref = self.get_func_ref("dict_clear")?;
+                self.builder.ins().call(func_ref, &[dict]);
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            _ => {
+                let (func_name, result_ty) = match method_name {
+                    "len" => ("dict_len", None),
+                    "is_empty" => ("dict_is_empty", None),
+                    "keys" => ("dict_keys", Some(BolideType::List(Box::new(key_ty.clone())))),
+                    "values" => ("dict_values", Some(BolideType::List(Box::new(val_ty.clone())))),
+                    _ => ("dict_clone", Some(BolideType::Dict(Box::new(key_ty.clone()), Box::new(val_ty.clone())))),
+                };
+                let func_ref = self.get_func_ref(func_name)?;
+                let call = self.builder.ins().call(func_ref, &[dict]);
+                let result = self.builder.inst_results(call)[0];
+                if let Some(ty) = result_ty {
+                    self.track_temp_rc_value(result, &ty);
+                }
+                Ok(result)
+            }
+        }
+    }
+
+    /// 表达式是不带参数的 `weak_dict()` 调用
+    fn is_default_dict_call(expr: &Expr) -> bool {
+        matches!(expr, Expr::Call(callee, args)
+            if args.is_empty() && matches!(callee.as_ref(), Expr::Ident(name) if name == "default_dict"))
+    }
+
+    /// 空容器的运行时类型标签：列表取元素标签，字典取键、值标签（值在高 4 位）；其他类型为 None
+    fn empty_container_tag(ty: &BolideType) -> Option<i64> {
+        match ty {
+            BolideType::List(elem) => Some(Self::list_tag(elem)),
+            BolideType::Dict(k, v) => Some(Self::dict_tag(k) | Self::dict_tag(v) << 4),
+            _ => None,
+        }
+    }
+
+    /// 编译 `default_dict()`：键不存在时读取 d[k] 插入值类型的空容器
+    fn compile_default_dict(&mut self, key_ty: &BolideType, val_ty: &BolideType) -> Result<Value, String> {
+        if Self::empty_container_tag(val_ty).is_none() {
+            return Err(format!("default_dict() values must be lists or dicts, got {:?}", val_ty));
+        }
+        let func_ref = self.get_func_ref("dict_new_default")?;
+        let key_type = self.builder.ins().iconst(types::I8, Self::dict_tag(key_ty));
+        let val_type = self.builder.ins().iconst(types::I8, Self::dict_tag(val_ty));
+        let call = self.builder.ins().call(func_ref, &[key_type, val_type]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// 容器值的 `d[k]`：字典里存放的那一份（借用），`d[k].push(x)` 修改的就是它；
+    /// 键不存在时报错，`default_dict()` 创建的字典则插入空容器
+    fn compile_stored_container(&mut self, dict: &Expr, key: &Expr, val_ty: &BolideType) -> Result<Option<Value>, String> {
+        let Some(tag) = Self::empty_container_tag(val_ty) else { return Ok(None) };
+        let dict = self.compile_dict_ref(dict)?;
+        let key = self.compile_expr(key)?;
+        let tag = self.builder.ins().iconst(types::I8, tag);
+        let func_ref = self.get_func_ref("dict_get_or_insert_empty")?;
+        let call = self.builder.ins().call(func_ref, &[dict, key, tag]);
+        Ok(Some(self.builder.inst_results(call)[0]))
+    }
+
+    fn is_weak_dict_call(expr: &Expr) -> bool {
+        matches!(expr, Expr::Call(callee, args)
+            if args.is_empty() && matches!(callee.as_ref(), Expr::Ident(name) if name == "weak_dict"))
+    }
+
+    /// 编译 `weak_dict()`：值的运行时类型标签取自声明的类型
+    fn compile_weak_dict(&mut self, val_ty: &BolideType) -> Result<Value, String> {
+        let func_ref = self.get_func_ref("weakdict_new")?;
+        let val_type = self.builder.ins().iconst(types::I8, Self::dict_tag(val_ty));
+        let call = self.builder.ins().call(func_ref, &[val_type]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// 编译弱键字典方法调用：键是对象指针，字典只持有它的弱句柄
+    fn compile_weak_dict_method(&mut self, base: &Expr, key_ty: &BolideType, val_ty: &BolideType, method_name: &str, args: &[Expr]) -> Result<Value, String> {
+        let arity = match method_name {
+            "len" | "is_empty" | "compact" => 0,
+            "contains" | "get" | "remove" => 1,
+            "set" => 2,
+            _ => return Err(format!("Unknown weak_dict method: {}", method_name)),
+        };
+        if args.len() != arity {
+            return Err(format!("weak_dict.{} expects {} argument(s), got {}", method_name, arity, args.len()));
+        }
+        // 键必须是类实例（子类实例也可以）
+        if let Some(key) = args.first() {
+            match self.infer_expr_type(key) {
+                Some(BolideType::Custom(_)) => {}
+                other => {
+                    let found = other.map_or("unknown".to_string(), |ty| crate::analysis::type_name(&ty));
+                    return Err(format!(
+                        "weak_dict.{} expects a {} key, found {}",
+                        method_name, crate::analysis::type_name(key_ty), found
+                    ));
+                }
+            }
+        }
+        let dict = self.compile_dict_ref(base)?;
+        let mut call_args = vec![dict];
+        // 键只借用：变量直接读取，构造出的临时对象在语句结束时释放
+        if let Some(key) = args.first() {
+            call_args.push(self.compile_dict_ref(key)?);
+        }
+        if method_name == "set" {
+            // 字典 retain 的值按借用传入，其余值的所有权移交给字典（同 emit_dict_set）
+            let mut v = self.compile_expr(&args[1])?;
+            if !Self::dict_retains(val_ty) {
+                self.remove_temp_rc_value(v);
+            }
+            if *val_ty == BolideType::Float && self.builder.func.dfg.value_type(v) == types::I64 {
+                v = self.builder.ins().fcvt_from_sint(types::F64, v);
+            }
+            if self.builder.func.dfg.value_type(v) == types::F64 {
+                v = self.builder.ins().bitcast(types::I64, MemFlags::new(), v);
+            }
+            call_args.push(v);
+        }
+        let func_ref = self.get_func_ref(&format!("weakdict_{}", method_name))?;
+        let call = self.builder.ins().call(func_ref, &call_args);
+        match method_name {
+            "set" => Ok(self.builder.ins().iconst(types::I64, 0)),
+            "get" => {
+                let val = self.builder.inst_results(call)[0];
+                if Self::dict_retains(val_ty) {
+                    let retained = self.emit_retain(val, val_ty);
+                    self.track_temp_rc_value(retained, val_ty);
+                    return Ok(retained);
+                }
+                if *val_ty == BolideType::Float {
+                    return Ok(self.builder.ins().bitcast(types::F64, MemFlags::new(), val));
+                }
+                Ok(val)
+            }
+            _ => Ok(self.builder.inst_results(call)[0]),
+        }
+    }
+
+    /// spawn / join 按返回类型选择的运行时函数后缀（与 JIT 相同，只有 _int / _float / _ptr 三种）
+    fn spawn_type_suffix(return_type: Option<&BolideType>) -> &'static str {
+        match return_type {
+            Some(BolideType::Float) => "_float",
+            Some(BolideType::Str) | Some(BolideType::BigInt) | Some(BolideType::Decimal)
+            | Some(BolideType::Dynamic) | Some(BolideType::Ptr)
+            | Some(BolideType::List(_)) | Some(BolideType::Custom(_)) => "_ptr",
+            _ => "_int",
         }
+    }
+
+    /// 编译 Spawn 表达式：在 pool 块里提交到线程池，否则启动新线程（同 JIT），返回句柄
+    ///
+    /// 有参数时经 trampoline 调用，参数存进 env；被 spawn 的函数取得参数的所有权。
+    fn compile_spawn(&mut self, name: &str, args: &[Expr]) -> Result<Value, String> {
+        let return_type = self.func_return_types.get(name).cloned().flatten();
+        let type_suffix = Self::spawn_type_suffix(return_type.as_ref());
+
+        let (func_addr, env_ptr) = if args.is_empty() {
+            let target_ref = self.get_func_ref(name).map_err(|_| format!("Undefined function: {}", name))?;
+            (self.builder.ins().func_addr(self.ptr_type, target_ref), None)
+        } else {
+            let env_size = (args.len() * 8) as i64;
+            let alloc_ref = self.get_func_ref("bolide_alloc")?;
+            let size_val = self.builder.ins().iconst(types::I64, env_size);
+            let call = self.builder.ins().call(alloc_ref, &[size_val]);
+            let env_ptr = self.builder.inst_results(call)[0];
+
+            for (i, arg) in args.iter().enumerate() {
+                let mut val = self.compile_expr(arg)?;
+                // 引用计数不是原子的，新线程总是拿到一份独立的列表/字典；
+                // 读取时共享的那一份照常作为临时值在语句结束时释放
+                let clone = match self.infer_expr_type(arg) {
+                    Some(BolideType::List(_)) => Some("list_clone"),
+                    Some(BolideType::Dict(_, _)) => Some("dict_clone"),
+                    _ => None,
+                };
+                if let Some(clone) = clone {
+                    let func_ref = self.get_func_ref(clone)?;
+                    let call = self.builder.ins().call(func_ref, &[val]);
+                    val = self.builder.inst_results(call)[0];
+                } else {
+                    self.remove_temp_rc_value(val);
+                }
+                self.builder.ins().store(MemFlags::trusted(), val, env_ptr, (i * 8) as i32);
+            }
 
-        Ok(dict_ptr)
-    }
+            let trampoline_name = self.get_trampoline_name(name);
+            let trampoline_ref = self.get_func_ref(&trampoline_name).map_err(|_| format!("Trampoline not found: {}", trampoline_name))?;
+            (self.builder.ins().func_addr(self.ptr_type, trampoline_ref), Some(env_ptr))
+        };
+        let spawn_suffix = if env_ptr.is_some() { format!("{}_with_env", type_suffix) } else { type_suffix.to_string() };
+        let spawn_args: Vec<Value> = std::iter::once(func_addr).chain(env_ptr).collect();
 
-    /// 编译 Spawn 表达式
-    fn compile_spawn(&mut self, name: &str, args: &[Expr]) -> Result<Value, String> {
-        if args.is_empty() {
-            // 无参数：直接 spawn
-            let func_ref = *self.func_refs.get("coroutine_spawn_int")
-                .ok_or("coroutine_spawn_int not found")?;
-            if let Some(&target_ref) = self.func_refs.get(name) {
-                let fn_ptr = self.builder.ins().func_addr(self.ptr_type, target_ref);
-                let null_env = self.builder.ins().iconst(self.ptr_type, 0);
-                let call = self.builder.ins().call(func_ref, &[fn_ptr, null_env]);
-                return Ok(self.builder.inst_results(call)[0]);
-            }
-        } else {
-            // 有参数：使用 trampoline
-            return self.compile_spawn_with_args(name, args);
-        }
-        Ok(self.builder.ins().iconst(types::I64, 0))
-    }
+        let pool_is_active = self.get_func_ref("pool_is_active")?;
+        let call = self.builder.ins().call(pool_is_active, &[]);
+        let is_active = self.builder.inst_results(call)[0];
 
-    /// 编译带参数的 Spawn
-    fn compile_spawn_with_args(&mut self, name: &str, args: &[Expr]) -> Result<Value, String> {
-        // 分配 env 内存
-        let env_size = (args.len() * 8) as i64;
-        let alloc_ref = *self.func_refs.get("bolide_alloc")
-            .ok_or("bolide_alloc not found")?;
-        let size_val = self.builder.ins().iconst(types::I64, env_size);
-        let call = self.builder.ins().call(alloc_ref, &[size_val]);
-        let env_ptr = self.builder.inst_results(call)[0];
+        let pool_block = self.builder.create_block();
+        let thread_block = self.builder.create_block();
+        let merge_block = self.builder.create_block();
+        self.builder.append_block_param(merge_block, self.ptr_type);
+        self.builder.ins().brif(is_active, pool_block, &[], thread_block, &[]);
 
-        // 将参数存入 env
-        for (i, arg) in args.iter().enumerate() {
-            let val = self.compile_expr(arg)?;
-            let offset = (i * 8) as i32;
-            self.builder.ins().store(MemFlags::new(), val, env_ptr, offset);
+        for (block, kind) in [(pool_block, "pool"), (thread_block, "thread")] {
+            self.builder.switch_to_block(block);
+            self.builder.seal_block(block);
+            let spawn_ref = self.get_func_ref(&format!("{}_spawn{}", kind, spawn_suffix))?;
+            let call = self.builder.ins().call(spawn_ref, &spawn_args);
+            let handle = self.builder.inst_results(call)[0];
+            // 函数名，报告任务的错误时使用
+            let task_name = self.compile_task_name(name)?;
+            let set_name = self.get_func_ref(&format!("{}_set_name", kind))?;
+            self.builder.ins().call(set_name, &[handle, task_name]);
+            self.builder.ins().jump(merge_block, &[handle]);
         }
 
-        // 获取 trampoline 函数地址
-        let trampoline_name = self.get_trampoline_name(name);
-        let trampoline_ref = *self.func_refs.get(&trampoline_name)
-            .ok_or_else(|| format!("Trampoline not found: {}", trampoline_name))?;
-        let fn_ptr = self.builder.ins().func_addr(self.ptr_type, trampoline_ref);
-
-        // 调用 spawn
-        let spawn_ref = *self.func_refs.get("coroutine_spawn_int")
-            .ok_or("coroutine_spawn_int not found")?;
-        let call = self.builder.ins().call(spawn_ref, &[fn_ptr, env_ptr]);
-        Ok(self.builder.inst_results(call)[0])
+        self.builder.switch_to_block(merge_block);
+        self.builder.seal_block(merge_block);
+        Ok(self.builder.block_params(merge_block)[0])
     }
 
-    /// 获取 trampoline 函数名
     fn get_trampoline_name(&self, func_name: &str) -> String {
-        for name in self.func_refs.keys() {
+        for name in self.functions.keys() {
             if name.starts_with(&format!("__trampoline_{}_", func_name)) {
                 return name.clone();
             }
@@ -3129,24 +7372,69 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
     /// 编译 Await 表达式
     fn compile_await(&mut self, inner: &Expr) -> Result<Value, String> {
         let future = self.compile_expr(inner)?;
-        let func_ref = *self.func_refs.get("coroutine_await_int")
-            .ok_or("coroutine_await_int not found")?;
+        let result_type = self.infer_expr_type(&Expr::Await(Box::new(inner.clone())));
+        let await_func_name = match &result_type {
+            Some(BolideType::Float) => "coroutine_await_float",
+            Some(BolideType::Str) | Some(BolideType::BigInt) | Some(BolideType::Decimal)
+            | Some(BolideType::List(_)) | Some(BolideType::Custom(_)) => "coroutine_await_ptr",
+            _ => "coroutine_await_int",
+        };
+        let func_ref = self.get_func_ref(await_func_name)?;
         let call = self.builder.ins().call(func_ref, &[future]);
-        Ok(self.builder.inst_results(call)[0])
+        let result = self.builder.inst_results(call)[0];
+
+        // 释放 Future；变量置空，再次 await 它时运行时记录错误而不是访问释放了的 Future
+        let free_ref = self.get_func_ref("coroutine_free")?;
+        self.builder.ins().call(free_ref, &[future]);
+        if let Expr::Ident(name) = inner {
+            let null_val = self.builder.ins().iconst(self.ptr_type, 0);
+            if let Some(&var) = self.variables.get(name) {
+                self.builder.def_var(var, null_val);
+            } else if let Some(addr) = self.global_addr(name) {
+                self.builder.ins().store(MemFlags::new(), null_val, addr, 0);
+            }
+        }
+
+        if let Some(ty) = result_type {
+            if Self::is_rc_type(&ty) {
+                self.track_temp_rc_value(result, &ty);
+            }
+        }
+        Ok(result)
+    }
+
+    /// 通道变量的值：局部变量，或顶层声明的全局变量（借用）
+    fn channel_value(&mut self, name: &str) -> Result<Value, String> {
+        if let Some(&var) = self.variables.get(name) {
+            return Ok(self.builder.use_var(var));
+        }
+        self.load_global(name).ok_or_else(|| format!("Channel not found: {}", name))
     }
 
     /// 编译 Recv 表达式 (从通道接收)
     fn compile_recv_channel(&mut self, channel_name: &str) -> Result<Value, String> {
         // 获取通道变量
-        let ch = if let Some(&var) = self.variables.get(channel_name) {
-            self.builder.use_var(var)
-        } else {
-            return Err(format!("Channel not found: {}", channel_name));
-        };
-        let func_ref = *self.func_refs.get("channel_recv")
-            .ok_or("channel_recv not found")?;
+        let ch = self.channel_value(channel_name)?;
+        let func_ref = self.get_func_ref("channel_recv")?;
         let call = self.builder.ins().call(func_ref, &[ch]);
-        Ok(self.builder.inst_results(call)[0])
+        let val = self.builder.inst_results(call)[0];
+        // 按引用搬运的元素由通道交给接收方一份引用，作为临时值持有；float 从槽位按位转回
+        match self.channel_elem_type(channel_name) {
+            Some(BolideType::Float) => Ok(self.builder.ins().bitcast(types::F64, MemFlags::new(), val)),
+            Some(elem) if Self::channel_payload_tag(&elem).is_some() => {
+                self.track_temp_rc_value(val, &elem);
+                Ok(val)
+            }
+            _ => Ok(val),
+        }
+    }
+
+    /// 通道变量声明的元素类型
+    fn channel_elem_type(&self, name: &str) -> Option<BolideType> {
+        match self.var_types.get(name).or_else(|| self.global_var_types.get(name)) {
+            Some(BolideType::Channel(elem)) => Some((**elem).clone()),
+            _ => None,
+        }
     }
 
     /// 编译 AwaitAll 表达式
@@ -3169,8 +7457,7 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                 _ => "coroutine_await_int",
             };
 
-            let await_ref = *self.func_refs.get(await_func_name)
-                .ok_or_else(|| format!("{} not found", await_func_name))?;
+            let await_ref = self.get_func_ref(await_func_name)?;
 
             let call = self.builder.ins().call(await_ref, &[*future_ptr]);
             let result = self.builder.inst_results(call)[0];
@@ -3184,14 +7471,12 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             Ok(results[0])
         } else {
             // 使用运行时元组存储所有结果
-            let tuple_new = *self.func_refs.get("tuple_new")
-                .ok_or("tuple_new not found")?;
+            let tuple_new = self.get_func_ref("tuple_new")?;
             let len = self.builder.ins().iconst(types::I64, results.len() as i64);
             let call = self.builder.ins().call(tuple_new, &[len]);
             let tuple_ptr = self.builder.inst_results(call)[0];
 
-            let tuple_set = *self.func_refs.get("tuple_set")
-                .ok_or("tuple_set not found")?;
+            let tuple_set = self.get_func_ref("tuple_set")?;
             for (i, result) in results.iter().enumerate() {
                 let idx = self.builder.ins().iconst(types::I64, i as i64);
                 self.builder.ins().call(tuple_set, &[tuple_ptr, idx, *result]);
@@ -3212,10 +7497,18 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                 self.compile_assign(assign)?;
                 false
             }
+            Statement::Destructure(destructure) => {
+                self.compile_destructure(destructure)?;
+                false
+            }
             Statement::Return(expr) => {
                 self.compile_return(expr.as_ref())?;
                 true
             }
+            Statement::Break | Statement::Continue => {
+                self.compile_loop_jump(matches!(stmt, Statement::Break))?;
+                true
+            }
             Statement::Expr(e) => {
                 self.compile_expr(e)?;
                 false
@@ -3237,10 +7530,7 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                 // 这些语句在顶层处理，函数体内忽略
                 false
             }
-            Statement::Pool(pool_stmt) => {
-                self.compile_pool(pool_stmt)?;
-                false
-            }
+            Statement::Pool(pool_stmt) => self.compile_pool(pool_stmt)?,
             Statement::Select(select_stmt) => {
                 self.compile_select(select_stmt)?;
                 false
@@ -3249,10 +7539,13 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                 self.compile_await_scope(scope_stmt)?;
                 false
             }
+            Statement::Measure(measure_stmt) => self.compile_measure(measure_stmt)?,
+            Statement::Match(match_stmt) => self.compile_match(match_stmt)?,
             Statement::AsyncSelect(async_select) => {
                 self.compile_async_select(async_select)?;
                 false
             }
+            Statement::Test(test) => return Err(format!("test \"{}\" only runs under `bolide test`", test.name)),
         };
 
         if !is_terminator {
@@ -3265,53 +7558,105 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
 
     /// 编译 Send 语句
     fn compile_send(&mut self, send_stmt: &bolide_parser::SendStmt) -> Result<(), String> {
-        let ch = if let Some(&var) = self.variables.get(&send_stmt.channel) {
-            self.builder.use_var(var)
-        } else {
-            return Err(format!("Channel not found: {}", send_stmt.channel));
-        };
+        let ch = self.channel_value(&send_stmt.channel)?;
         let val = self.compile_expr(&send_stmt.value)?;
-        let func_ref = *self.func_refs.get("channel_send")
-            .ok_or("channel_send not found")?;
-        self.builder.ins().call(func_ref, &[ch, val]);
+        // RC 元素由通道 retain 一份交给接收方，发送方的变量/临时值照常释放
+        match self.channel_elem_type(&send_stmt.channel).as_ref().and_then(Self::channel_payload_tag) {
+            Some(tag) => {
+                let tag_val = self.builder.ins().iconst(types::I8, tag as i64);
+                let func_ref = self.get_func_ref("channel_send_ptr")?;
+                self.builder.ins().call(func_ref, &[ch, val, tag_val]);
+            }
+            None => {
+                // 通道的槽位是 i64，float 按位转换
+                let val = if self.builder.func.dfg.value_type(val) == types::F64 {
+                    self.builder.ins().bitcast(types::I64, MemFlags::new(), val)
+                } else {
+                    val
+                };
+                let func_ref = self.get_func_ref("channel_send")?;
+                self.builder.ins().call(func_ref, &[ch, val]);
+            }
+        }
         Ok(())
     }
 
-    /// 编译 Pool 语句
-    fn compile_pool(&mut self, pool_stmt: &bolide_parser::PoolStmt) -> Result<(), String> {
+    /// 通道按引用搬运的元素类型的运行时标签（见 `bolide_channel_send_ptr`）
+    fn channel_payload_tag(ty: &BolideType) -> Option<u8> {
+        match ty {
+            BolideType::Func | BolideType::FuncSig(_, _) => Some(bolide_runtime::ARG_TAG_FUNC),
+            ty if Self::dict_retains(ty) => Some(Self::dict_tag(ty) as u8),
+            _ => None,
+        }
+    }
+
+    /// 编译 Pool 语句；块内的 return 和跳出块的 break/continue 由 `emit_pool_early_exits` 拆除线程池
+    fn compile_pool(&mut self, pool_stmt: &bolide_parser::PoolStmt) -> Result<bool, String> {
         let size = self.compile_expr(&pool_stmt.size)?;
 
         // 创建线程池
-        let pool_create_ref = *self.func_refs.get("pool_create")
-            .ok_or("pool_create not found")?;
+        let pool_create_ref = self.get_func_ref("pool_create")?;
         let call = self.builder.ins().call(pool_create_ref, &[size]);
         let pool_ptr = self.builder.inst_results(call)[0];
 
         // 进入线程池上下文
-        let pool_enter_ref = *self.func_refs.get("pool_enter")
-            .ok_or("pool_enter not found")?;
+        let pool_enter_ref = self.get_func_ref("pool_enter")?;
         self.builder.ins().call(pool_enter_ref, &[pool_ptr]);
 
         // 编译 pool 块内的语句
+        self.pool_stack.push(pool_ptr);
+        let mut terminated = false;
         for stmt in &pool_stmt.body {
-            self.compile_stmt(stmt)?;
+            if terminated { break; }
+            terminated = self.compile_stmt(stmt)?;
         }
+        self.pool_stack.pop();
 
-        // 退出线程池上下文
-        let pool_exit_ref = *self.func_refs.get("pool_exit")
-            .ok_or("pool_exit not found")?;
-        self.builder.ins().call(pool_exit_ref, &[]);
+        if !terminated {
+            self.emit_pool_teardown(pool_ptr)?;
+        }
+        Ok(terminated)
+    }
 
-        // 销毁线程池
-        let pool_destroy_ref = *self.func_refs.get("pool_destroy")
-            .ok_or("pool_destroy not found")?;
+    /// 拆除线程池：退出上下文，再销毁（等待任务完成）
+    fn emit_pool_teardown(&mut self, pool_ptr: Value) -> Result<(), String> {
+        let pool_exit_ref = self.get_func_ref("pool_exit")?;
+        self.builder.ins().call(pool_exit_ref, &[pool_ptr]);
+        let pool_destroy_ref = self.get_func_ref("pool_destroy")?;
         self.builder.ins().call(pool_destroy_ref, &[pool_ptr]);
+        Ok(())
+    }
 
+    /// 提前离开时拆除 pool_stack 中 `depth` 之后的 pool 块，由内到外；在释放 RC 变量之前调用
+    fn emit_pool_early_exits(&mut self, depth: usize) -> Result<(), String> {
+        let crossed: Vec<Value> = self.pool_stack[depth..].iter().rev().copied().collect();
+        for pool_ptr in crossed {
+            self.emit_pool_teardown(pool_ptr)?;
+        }
         Ok(())
     }
 
+    /// 编译超时参数，得到运行时要的纳秒数（见 `duration::emit_timeout_ns`）
+    fn compile_timeout(&mut self, expr: &Expr) -> Result<Value, String> {
+        let kind = duration::timeout_kind(self.infer_expr_type(expr).as_ref())?;
+        if kind == duration::Timeout::Millis {
+            self.warn(duration::BARE_INT_TIMEOUT);
+        }
+        let value = self.compile_expr(expr)?;
+        let remaining = match kind {
+            duration::Timeout::Deadline => Some(self.get_func_ref("deadline_remaining")?),
+            _ => None,
+        };
+        Ok(duration::emit_timeout_ns(&mut self.builder, kind, value, remaining))
+    }
+
     /// 编译 Select 语句
     fn compile_select(&mut self, select_stmt: &bolide_parser::SelectStmt) -> Result<(), String> {
+        if let Some(channels) = &select_stmt.channels {
+            return self.compile_select_from(channels, &select_stmt.branches);
+        }
+        // 块内的 break/continue 不能跳到块外的循环
+        let outer_fence = std::mem::replace(&mut self.loop_fence, self.loops.len());
         use bolide_parser::SelectBranch;
 
         let mut recv_branches: Vec<(&str, &str, &Vec<Statement>)> = Vec::new();
@@ -3329,6 +7674,9 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                 SelectBranch::Default { body } => {
                     default_branch = Some(body);
                 }
+                SelectBranch::RecvAny { .. } | SelectBranch::Closed { .. } => {
+                    return Err("`value, index =>` and `closed =>` branches need `select from`".to_string());
+                }
             }
         }
 
@@ -3338,6 +7686,7 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                     self.compile_stmt(stmt)?;
                 }
             }
+            self.loop_fence = outer_fence;
             return Ok(());
         }
 
@@ -3346,17 +7695,14 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
 
         // 分配 channel 数组
         let array_size = (channel_count * 8) as i64;
-        let alloc_ref = *self.func_refs.get("bolide_alloc")
-            .ok_or("bolide_alloc not found")?;
+        let alloc_ref = self.get_func_ref("bolide_alloc")?;
         let size_val = self.builder.ins().iconst(types::I64, array_size);
         let call = self.builder.ins().call(alloc_ref, &[size_val]);
         let array_ptr = self.builder.inst_results(call)[0];
 
         // 填充 channel 数组
         for (i, (_, channel_name, _)) in recv_branches.iter().enumerate() {
-            let ch_var = *self.variables.get(*channel_name)
-                .ok_or_else(|| format!("Undefined channel: {}", channel_name))?;
-            let ch_ptr = self.builder.use_var(ch_var);
+            let ch_ptr = self.channel_value(channel_name)?;
             let offset = (i * 8) as i32;
             self.builder.ins().store(MemFlags::new(), ch_ptr, array_ptr, offset);
         }
@@ -3370,14 +7716,13 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         let timeout_val = if default_branch.is_some() {
             self.builder.ins().iconst(types::I64, -2) // has default
         } else if let Some((duration_expr, _)) = &timeout_branch {
-            self.compile_expr(duration_expr)?
+            self.compile_timeout(duration_expr)?
         } else {
             self.builder.ins().iconst(types::I64, -1) // no timeout
         };
 
         // 调用 channel_select
-        let select_ref = *self.func_refs.get("channel_select")
-            .ok_or("channel_select not found")?;
+        let select_ref = self.get_func_ref("channel_select")?;
         let count_val = self.builder.ins().iconst(types::I64, channel_count as i64);
         let call = self.builder.ins().call(select_ref, &[array_ptr, count_val, timeout_val, value_ptr]);
         let selected_idx = self.builder.inst_results(call)[0];
@@ -3427,51 +7772,159 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         }
 
         // 编译各 recv 分支
-        for (i, (var_name, _, body)) in recv_branches.iter().enumerate() {
+        for (i, (var_name, channel_name, body)) in recv_branches.iter().enumerate() {
             self.builder.switch_to_block(branch_blocks[i]);
             self.builder.seal_block(branch_blocks[i]);
 
             let recv_val = self.builder.ins().load(types::I64, MemFlags::new(), value_ptr, 0);
-            let var = self.declare_variable(var_name, types::I64);
-            self.builder.def_var(var, recv_val);
-
-            for stmt in *body {
-                self.compile_stmt(stmt)?;
-            }
-            self.builder.ins().jump(exit_block, &[]);
+            let elem = match self.var_types.get(*channel_name).or_else(|| self.global_var_types.get(*channel_name)) {
+                Some(BolideType::Channel(elem)) => (**elem).clone(),
+                _ => BolideType::Int,
+            };
+            self.compile_select_body(body, &[(var_name, recv_val, elem)], exit_block)?;
         }
 
         // 编译 timeout 分支
         if let (Some(block), Some((_, body))) = (timeout_block, &timeout_branch) {
             self.builder.switch_to_block(block);
             self.builder.seal_block(block);
-            for stmt in *body {
-                self.compile_stmt(stmt)?;
-            }
-            self.builder.ins().jump(exit_block, &[]);
+            self.compile_select_body(body, &[], exit_block)?;
         }
 
         // 编译 default 分支
         if let (Some(block), Some(body)) = (default_block_opt, default_branch) {
             self.builder.switch_to_block(block);
             self.builder.seal_block(block);
-            for stmt in body {
-                self.compile_stmt(stmt)?;
-            }
-            self.builder.ins().jump(exit_block, &[]);
+            self.compile_select_body(body, &[], exit_block)?;
+        }
+
+        self.builder.switch_to_block(exit_block);
+        self.builder.seal_block(exit_block);
+
+        self.loop_fence = outer_fence;
+        Ok(())
+    }
+
+    /// 编译 `select from chans { v, i => { ... } }`：每次执行时按列表当前的元素等待，
+    /// 分支绑定收到的值和 channel 在列表中的索引；所有 channel 都已关闭（或列表为空）时执行 `closed` 分支
+    fn compile_select_from(&mut self, channels: &Expr, branches: &[bolide_parser::SelectBranch]) -> Result<(), String> {
+        use bolide_parser::SelectBranch;
+
+        let elem = match self.infer_expr_type(channels) {
+            Some(BolideType::List(inner)) => match *inner {
+                BolideType::Channel(elem) => *elem,
+                other => return Err(format!("`select from` expects a list of channels, got list<{:?}>", other)),
+            },
+            other => return Err(format!("`select from` expects a list of channels, got {:?}", other)),
+        };
+
+        let mut recv = None;
+        let mut closed_body = None;
+        let mut timeout_branch = None;
+        let mut default_body = None;
+        for branch in branches {
+            match branch {
+                SelectBranch::RecvAny { var, index, body } => recv = Some((var.as_str(), index.as_str(), body)),
+                SelectBranch::Closed { body } => closed_body = Some(body),
+                SelectBranch::Timeout { duration, body } => timeout_branch = Some((duration, body)),
+                SelectBranch::Default { body } => default_body = Some(body),
+                SelectBranch::Recv { .. } => return Err("`select from` branches receive as `value, index => { ... }`".to_string()),
+            }
+        }
+        let (var_name, index_name, recv_body) = recv.ok_or("`select from` needs a `value, index => { ... }` branch")?;
+
+        let list = self.compile_expr(channels)?;
+        let value_slot = self.builder.create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8, 0));
+        let value_ptr = self.builder.ins().stack_addr(self.ptr_type, value_slot, 0);
+        let timeout_val = if default_body.is_some() {
+            self.builder.ins().iconst(types::I64, -2)
+        } else if let Some((duration, _)) = timeout_branch {
+            self.compile_timeout(duration)?
+        } else {
+            self.builder.ins().iconst(types::I64, -1)
+        };
+        let select_ref = self.get_func_ref("channel_select_list")?;
+        let call = self.builder.ins().call(select_ref, &[list, timeout_val, value_ptr]);
+        let selected_idx = self.builder.inst_results(call)[0];
+
+        // 块内的 break/continue 不能跳到块外的循环
+        let outer_fence = std::mem::replace(&mut self.loop_fence, self.loops.len());
+        let exit_block = self.builder.create_block();
+        let recv_block = self.builder.create_block();
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        let received = self.builder.ins().icmp(IntCC::SignedGreaterThanOrEqual, selected_idx, zero);
+        let not_received = self.builder.create_block();
+        self.builder.ins().brif(received, recv_block, &[], not_received, &[]);
+        self.builder.switch_to_block(not_received);
+        self.builder.seal_block(not_received);
+
+        // 其余结果：-1 超时，-2 default，SELECT_ALL_CLOSED
+        let others = [
+            (-1, timeout_branch.map(|(_, body)| body)),
+            (-2, default_body),
+            (bolide_runtime::SELECT_ALL_CLOSED, closed_body),
+        ];
+        let mut bodies = Vec::new();
+        for (code, body) in others {
+            let Some(body) = body else { continue };
+            let block = self.builder.create_block();
+            let code_val = self.builder.ins().iconst(types::I64, code);
+            let is_match = self.builder.ins().icmp(IntCC::Equal, selected_idx, code_val);
+            let next_block = self.builder.create_block();
+            self.builder.ins().brif(is_match, block, &[], next_block, &[]);
+            self.builder.switch_to_block(next_block);
+            self.builder.seal_block(next_block);
+            bodies.push((block, body));
+        }
+        self.builder.ins().jump(exit_block, &[]);
+
+        self.builder.switch_to_block(recv_block);
+        self.builder.seal_block(recv_block);
+        let received_val = self.builder.ins().load(types::I64, MemFlags::new(), value_ptr, 0);
+        let bindings = [(var_name, received_val, elem), (index_name, selected_idx, BolideType::Int)];
+        self.compile_select_body(recv_body, &bindings, exit_block)?;
+
+        for (block, body) in bodies {
+            self.builder.switch_to_block(block);
+            self.builder.seal_block(block);
+            self.compile_select_body(body, &[], exit_block)?;
         }
 
         self.builder.switch_to_block(exit_block);
         self.builder.seal_block(exit_block);
+        self.loop_fence = outer_fence;
+        Ok(())
+    }
 
+    /// 编译 select 的一个分支：绑定收到的值（RC 值归分支所有，分支结束时释放），
+    /// 分支没有 return 时跳到 `exit_block`
+    fn compile_select_body(&mut self, body: &[Statement], bindings: &[(&str, Value, BolideType)], exit_block: Block) -> Result<(), String> {
+        let scope_idx = self.enter_scope();
+        for (name, val, ty) in bindings {
+            let var_ty = self.bolide_type_to_cranelift(ty);
+            let var = self.declare_variable(name, var_ty);
+            // 收到的值存在 i64 槽位里，float 按位转换
+            let val = if var_ty == types::F64 { self.builder.ins().bitcast(types::F64, MemFlags::new(), *val) } else { *val };
+            self.builder.def_var(var, val);
+            self.var_types.insert(name.to_string(), ty.clone());
+            self.track_rc_variable(name, ty);
+        }
+        for stmt in body {
+            if self.compile_stmt(stmt)? {
+                return Ok(());
+            }
+        }
+        self.leave_scope(scope_idx);
+        self.builder.ins().jump(exit_block, &[]);
         Ok(())
     }
 
     /// 编译 AwaitScope 语句
     fn compile_await_scope(&mut self, scope_stmt: &bolide_parser::AwaitScopeStmt) -> Result<(), String> {
+        // 块内的 break/continue 不能跳到块外的循环
+        let outer_fence = std::mem::replace(&mut self.loop_fence, self.loops.len());
         // 进入作用域
-        let scope_enter_ref = *self.func_refs.get("scope_enter")
-            .ok_or("scope_enter not found")?;
+        let scope_enter_ref = self.get_func_ref(if scope_stmt.fail_fast { "scope_enter_fail_fast" } else { "scope_enter" })?;
         self.builder.ins().call(scope_enter_ref, &[]);
 
         // 编译作用域内的语句
@@ -3479,14 +7932,224 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             self.compile_stmt(stmt)?;
         }
 
-        // 退出作用域
-        let scope_exit_ref = *self.func_refs.get("scope_exit")
-            .ok_or("scope_exit not found")?;
+        // 退出作用域（等待子任务，报告它们的错误）
+        let scope_exit_ref = self.get_func_ref("scope_exit")?;
         self.builder.ins().call(scope_exit_ref, &[]);
 
+        self.loop_fence = outer_fence;
+        Ok(())
+    }
+
+    /// 编译 measure 块：块前取时间和分配计数，块结束时打印
+    fn compile_measure(&mut self, measure_stmt: &bolide_parser::MeasureStmt) -> Result<bool, String> {
+        let begin_ref = self.get_func_ref("measure_begin")?;
+        let call = self.builder.ins().call(begin_ref, &[]);
+        let start = self.builder.inst_results(call)[0];
+        let snapshot_ref = self.get_func_ref("alloc_snapshot")?;
+        let call = self.builder.ins().call(snapshot_ref, &[]);
+        let alloc_start = self.builder.inst_results(call)[0];
+
+        self.measure_stack.push((measure_stmt.label.clone(), start, alloc_start));
+        let scope_idx = self.enter_scope();
+        let mut returned = false;
+        for stmt in &measure_stmt.body {
+            if self.compile_stmt(stmt)? {
+                returned = true;
+                break;
+            }
+        }
+        self.measure_stack.pop();
+
+        if !returned {
+            self.leave_scope(scope_idx);
+            self.emit_measure_end(&measure_stmt.label, start, alloc_start, false)?;
+        }
+        Ok(returned)
+    }
+
+    /// 调用 measure_end 打印一个 measure 块的结果
+    fn emit_measure_end(&mut self, label: &str, start: Value, alloc_start: Value, early: bool) -> Result<(), String> {
+        let (gv, len) = *self.string_globals.get(label)
+            .ok_or_else(|| format!("String data not found for: {}", label))?;
+        let ptr_val = self.builder.ins().global_value(self.ptr_type, gv);
+        let len_val = self.builder.ins().iconst(types::I64, len as i64);
+        let early_val = self.builder.ins().iconst(types::I64, early as i64);
+        let end_ref = self.get_func_ref("measure_end")?;
+        self.builder.ins().call(end_ref, &[ptr_val, len_val, start, alloc_start, early_val]);
+        Ok(())
+    }
+
+    /// return 穿过的 measure 块（由内到外）打印结果，标记为提前返回
+    fn emit_measure_early_exits(&mut self) -> Result<(), String> {
+        let active: Vec<(String, Value, Value)> = self.measure_stack.iter().rev().cloned().collect();
+        for (label, start, alloc_start) in active {
+            self.emit_measure_end(&label, start, alloc_start, true)?;
+        }
         Ok(())
     }
 
+    /// 编译计时器内置函数
+    fn compile_timer_call(&mut self, name: &str, args: &[Expr]) -> Result<Value, String> {
+        let arg_vals = if name == "timer_start" {
+            if !args.is_empty() {
+                return Err("timer_start expects no arguments".to_string());
+            }
+            Vec::new()
+        } else {
+            if args.len() != 1 {
+                return Err(format!("{} expects 1 argument", name));
+            }
+            vec![self.compile_expr(&args[0])?]
+        };
+        let func_ref = self.get_func_ref(name)?;
+        let call = self.builder.ins().call(func_ref, &arg_vals);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// 列表聚合的运行时函数和结果类型：sum/min/max 返回元素类型，
+    /// avg 对 int/float/bigint 返回 float，对 decimal 返回 decimal
+    fn list_aggregate(func: &str, list_ty: &BolideType) -> Option<(&'static str, BolideType)> {
+        let BolideType::List(elem) = list_ty else {
+            return None;
+        };
+        let entry = match (func, elem.as_ref()) {
+            ("sum", BolideType::Int) => ("list_sum_int", BolideType::Int),
+            ("min", BolideType::Int) => ("list_min_int", BolideType::Int),
+            ("max", BolideType::Int) => ("list_max_int", BolideType::Int),
+            ("sum", BolideType::Float) => ("list_sum_float", BolideType::Float),
+            ("min", BolideType::Float) => ("list_min_float", BolideType::Float),
+            ("max", BolideType::Float) => ("list_max_float", BolideType::Float),
+            ("avg", BolideType::Int) => ("list_mean", BolideType::Float),
+            ("avg", BolideType::Float) => ("list_mean_float", BolideType::Float),
+            ("avg", BolideType::BigInt) => ("list_mean_bigint", BolideType::Float),
+            ("sum", BolideType::BigInt) => ("list_sum_bigint", BolideType::BigInt),
+            ("min", BolideType::BigInt) => ("list_min_bigint", BolideType::BigInt),
+            ("max", BolideType::BigInt) => ("list_max_bigint", BolideType::BigInt),
+            ("sum", BolideType::Decimal) => ("list_sum_decimal", BolideType::Decimal),
+            ("min", BolideType::Decimal) => ("list_min_decimal", BolideType::Decimal),
+            ("max", BolideType::Decimal) => ("list_max_decimal", BolideType::Decimal),
+            ("avg", BolideType::Decimal) => ("list_mean_decimal", BolideType::Decimal),
+            _ => return None,
+        };
+        Some(entry)
+    }
+
+    /// 是否为内置的列表聚合 sum/min/max/avg（恰好一个列表实参）
+    fn is_list_aggregate_call(&self, name: &str, args: &[Expr]) -> bool {
+        matches!(name, "sum" | "min" | "max" | "avg")
+            && args.len() == 1
+            && matches!(self.infer_expr_type(&args[0]), Some(BolideType::List(_)))
+    }
+
+    /// 编译 sum(xs) / min(xs) / max(xs) / avg(xs)
+    fn compile_list_aggregate(&mut self, func: &str, arg: &Expr) -> Result<Value, String> {
+        let list_ty = self.infer_expr_type(arg).unwrap_or(BolideType::Int);
+        let (runtime_name, result_ty) = Self::list_aggregate(func, &list_ty).ok_or_else(|| {
+            format!("{}() is not supported for {:?}; element type must be int, float, bigint or decimal", func, list_ty)
+        })?;
+        // 列表变量直接借用，不经过 compile_ident 的整表拷贝
+        let list = match arg {
+            Expr::Ident(name) if self.variables.contains_key(name) => {
+                let var = self.variables[name];
+                self.builder.use_var(var)
+            }
+            _ => self.compile_expr(arg)?,
+        };
+        let func_ref = self.get_func_ref(runtime_name)?;
+        let call = self.builder.ins().call(func_ref, &[list]);
+        let result = self.builder.inst_results(call)[0];
+        self.track_temp_rc_value(result, &result_ty);
+        Ok(result)
+    }
+
+    /// 编译 hash(x)
+    fn compile_hash(&mut self, args: &[Expr]) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err("hash expects 1 argument".to_string());
+        }
+        let val = self.compile_expr(&args[0])?;
+        // 推断不出类型时按值的机器类型区分 int / float
+        let ty = self.infer_expr_type(&args[0]).unwrap_or_else(|| {
+            if self.builder.func.dfg.value_type(val) == types::F64 { BolideType::Float } else { BolideType::Int }
+        });
+        self.emit_hash(val, &ty)
+    }
+
+    /// 编译 len(x)：字符串的字符数，列表/字典/元组的元素数
+    fn compile_len(&mut self, args: &[Expr]) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err("len expects 1 argument".to_string());
+        }
+        let len_func = match self.infer_expr_type(&args[0]) {
+            Some(BolideType::Str) => "string_len",
+            Some(BolideType::List(_)) => "list_len",
+            Some(BolideType::Dict(_, _)) => "dict_len",
+            Some(BolideType::WeakDict(_, _)) => "weakdict_len",
+            Some(BolideType::Tuple(_)) => "tuple_len",
+            other => return Err(format!("len() is not supported for {:?}", other)),
+        };
+        let val = self.compile_dict_ref(&args[0])?;
+        let func_ref = self.get_func_ref(len_func)?;
+        let call = self.builder.ins().call(func_ref, &[val]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// 编译 intern(s)：返回驻留实例
+    fn compile_intern(&mut self, args: &[Expr]) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err("intern expects 1 argument".to_string());
+        }
+        match self.infer_expr_type(&args[0]) {
+            Some(BolideType::Str) | None => {}
+            Some(ty) => return Err(format!("intern expects a str argument, got {:?}", ty)),
+        }
+        let val = self.compile_expr(&args[0])?;
+        let func_ref = self.get_func_ref("string_intern")?;
+        let call = self.builder.ins().call(func_ref, &[val]);
+        let result = self.builder.inst_results(call)[0];
+        self.track_temp_rc_value(result, &BolideType::Str);
+        Ok(result)
+    }
+
+    /// 按静态类型哈希一个值；元组逐槽哈希后依次合并
+    fn emit_hash(&mut self, val: Value, ty: &BolideType) -> Result<Value, String> {
+        let func_name = match ty {
+            BolideType::Int | BolideType::Bool => "hash_int",
+            BolideType::Float => "hash_float",
+            BolideType::Str => "hash_string",
+            BolideType::BigInt => "hash_bigint",
+            BolideType::Decimal => "hash_decimal",
+            BolideType::Dynamic => "hash_dynamic",
+            BolideType::Tuple(elem_types) => {
+                let tuple_get = self.get_func_ref("tuple_get")?;
+                let combine = self.get_func_ref("hash_combine")?;
+                let mut acc = self.builder.ins().iconst(types::I64, bolide_runtime::TUPLE_HASH_SEED);
+                for (i, elem_ty) in elem_types.iter().enumerate() {
+                    let idx = self.builder.ins().iconst(types::I64, i as i64);
+                    let call = self.builder.ins().call(tuple_get, &[val, idx]);
+                    let mut slot = self.builder.inst_results(call)[0];
+                    // 元组中的 float 以位模式存储
+                    if *elem_ty == BolideType::Float {
+                        slot = self.builder.ins().bitcast(types::F64, MemFlags::new(), slot);
+                    }
+                    let slot_hash = self.emit_hash(slot, elem_ty)?;
+                    let call = self.builder.ins().call(combine, &[acc, slot_hash]);
+                    acc = self.builder.inst_results(call)[0];
+                }
+                return Ok(acc);
+            }
+            other => {
+                return Err(format!(
+                    "hash() cannot hash a value of type {:?}; hashable types are int, float, bool, str, bigint, decimal, dynamic and tuples of these",
+                    other
+                ));
+            }
+        };
+        let func_ref = self.get_func_ref(func_name)?;
+        let call = self.builder.ins().call(func_ref, &[val]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
     /// 编译 AsyncSelect 语句
     fn compile_async_select(&mut self, async_select: &bolide_parser::AsyncSelectStmt) -> Result<(), String> {
         use bolide_parser::AsyncSelectBranch;
@@ -3526,14 +8189,15 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         }
 
         // 4. 调用 select_wait_first 获取第一个完成的索引
-        let select_wait_first = *self.func_refs.get("select_wait_first")
-            .ok_or("select_wait_first not found")?;
+        let select_wait_first = self.get_func_ref("select_wait_first")?;
         let count = self.builder.ins().iconst(types::I64, branch_count as i64);
         let call = self.builder.ins().call(select_wait_first, &[array_ptr, count]);
         let winner_idx = self.builder.inst_results(call)[0];
 
-        // 5. 根据获胜索引执行对应分支
+        // 5. 根据获胜索引执行对应分支；分支内的 break/continue 不能跳到块外的循环
+        let outer_fence = std::mem::replace(&mut self.loop_fence, self.loops.len());
         self.compile_async_select_branches(async_select, &futures, winner_idx)?;
+        self.loop_fence = outer_fence;
 
         Ok(())
     }
@@ -3565,8 +8229,7 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             match branch {
                 AsyncSelectBranch::Bind { var, body, .. } => {
                     // await 获取结果并绑定变量
-                    let await_int = *self.func_refs.get("coroutine_await_int")
-                        .ok_or("coroutine_await_int not found")?;
+                    let await_int = self.get_func_ref("coroutine_await_int")?;
                     let call = self.builder.ins().call(await_int, &[futures[i]]);
                     let result = self.builder.inst_results(call)[0];
 
@@ -3599,29 +8262,34 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
 
     /// 编译变量声明
     fn compile_var_decl(&mut self, decl: &bolide_parser::VarDecl) -> Result<(), String> {
-        let ty = if let Some(ref t) = decl.ty {
-            self.bolide_type_to_cranelift(t)
-        } else {
-            types::I64
-        };
+        if let Some(BolideType::PackedList(_)) = decl.ty {
+            return Err(PACKED_LIST_UNSUPPORTED.to_string());
+        }
+        if self.current_func_name == "__main__" && self.is_global(&decl.name) {
+            return self.compile_global_decl(decl);
+        }
+        // 没有标注类型时按值推断，`let a = 0.0;` 需要 F64 变量
+        let bolide_ty = decl.ty.clone().or_else(|| decl.value.as_ref().and_then(|value| self.infer_expr_type(value)));
+        let ty = bolide_ty.as_ref().map_or(types::I64, |t| self.bolide_type_to_cranelift(t));
         let var = self.declare_variable(&decl.name, ty);
+        if let Some(Expr::Spawn(func_name, _)) = &decl.value {
+            self.spawn_funcs.insert(decl.name.clone(), func_name.clone());
+        }
 
         // Store the type in var_types
-        if let Some(ref t) = decl.ty {
-            self.var_types.insert(decl.name.clone(), t.clone());
-        } else if let Some(ref value) = decl.value {
-            // Infer type from value expression
-            if let Some(inferred_ty) = self.infer_expr_type(value) {
-                self.var_types.insert(decl.name.clone(), inferred_ty);
-            }
+        if let Some(t) = bolide_ty {
+            self.var_types.insert(decl.name.clone(), t);
         }
 
         if let Some(ref value) = decl.value {
-            let val = self.compile_expr(value)?;
-            
-            // Take ownership if it's a temp RC value
-            self.remove_temp_rc_value(val);
-            
+            let val = match (value, &decl.ty) {
+                (Expr::Dict(entries), Some(ty @ BolideType::Dict(..))) => self.compile_dict(entries, Some(ty))?,
+                (Expr::List(items), Some(BolideType::List(elem))) => self.compile_list(items, Some(elem))?,
+                (value, Some(BolideType::WeakDict(_, val_ty))) if Self::is_weak_dict_call(value) => self.compile_weak_dict(val_ty)?,
+                (value, Some(BolideType::Dict(key_ty, val_ty))) if Self::is_default_dict_call(value) => self.compile_default_dict(key_ty, val_ty)?,
+                _ => self.compile_expr(value)?,
+            };
+            let val = self.take_binding_value(value, val);
             self.builder.def_var(var, val);
         } else {
             let zero = self.builder.ins().iconst(types::I64, 0);
@@ -3636,22 +8304,61 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         Ok(())
     }
 
+    /// 顶层的 `let`：初始化全局变量，没有标注类型时按初始值推断（也是 threadlocal 变量的初始化函数体）
+    fn compile_global_decl(&mut self, decl: &bolide_parser::VarDecl) -> Result<(), String> {
+        let ty = match (&decl.ty, &decl.value) {
+            (Some(ty), _) => ty.clone(),
+            (None, Some(value)) => self.infer_expr_type(value).unwrap_or(BolideType::Int),
+            (None, None) => BolideType::Int,
+        };
+        self.global_var_types.insert(decl.name.clone(), ty);
+        if let Some(Expr::Spawn(func_name, _)) = &decl.value {
+            self.spawn_funcs.insert(decl.name.clone(), func_name.clone());
+        }
+        // threadlocal 变量在每个线程第一次访问时由 __tls_init_* 初始化
+        if decl.thread_local && self.current_func_name == "__main__" {
+            return Ok(());
+        }
+        let val = match (&decl.value, &decl.ty) {
+            (Some(Expr::Dict(entries)), Some(ty @ BolideType::Dict(..))) => self.compile_dict(entries, Some(ty))?,
+            (Some(Expr::List(items)), Some(BolideType::List(elem))) => self.compile_list(items, Some(elem))?,
+            (Some(value), Some(BolideType::WeakDict(_, val_ty))) if Self::is_weak_dict_call(value) => self.compile_weak_dict(val_ty)?,
+            (Some(value), Some(BolideType::Dict(key_ty, val_ty))) if Self::is_default_dict_call(value) => self.compile_default_dict(key_ty, val_ty)?,
+            (Some(value), _) => {
+                let val = self.compile_expr(value)?;
+                self.take_binding_value(value, val)
+            }
+            (None, _) => self.builder.ins().iconst(types::I64, 0),
+        };
+        self.store_global(&decl.name, val, true)
+    }
+
     /// 编译赋值语句
     fn compile_assign(&mut self, assign: &bolide_parser::Assign) -> Result<(), String> {
+        if let Some(op) = assign.op {
+            return self.compile_compound_assign(&assign.target, op, &assign.value);
+        }
         match &assign.target {
+            Expr::Ident(var_name) if self.is_global(var_name) => {
+                let ty = self.global_var_types.get(var_name).cloned();
+                let val = self.compile_expr_expecting(&assign.value, ty.as_ref())?;
+                let val = self.take_binding_value(&assign.value, val);
+                self.store_global(var_name, val, false)?;
+            }
             Expr::Ident(var_name) => {
                 let var = *self.variables.get(var_name)
                     .ok_or_else(|| format!("Undefined variable: {}", var_name))?;
-                let val = self.compile_expr(&assign.value)?;
+                let ty = self.var_types.get(var_name).cloned();
+                let val = self.compile_expr_expecting(&assign.value, ty.as_ref())?;
                 
                 // Release old value if RC type
                 if let Some(ty) = self.var_types.get(var_name).cloned() {
                     if Self::is_rc_type(&ty) {
                         let old_val = self.builder.use_var(var);
                         self.emit_release(old_val, &ty);
-                        
-                        // Take ownership of new value if it's a temp
-                        self.remove_temp_rc_value(val);
+                        let val = self.take_binding_value(&assign.value, val);
+                        self.builder.def_var(var, val);
+                        return Ok(());
                     }
                 }
                 
@@ -3663,15 +8370,165 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             Expr::Index(base, index) => {
                 self.compile_index_assign(base, index, &assign.value)?;
             }
-            _ => return Err("Unsupported assignment target".to_string()),
+            _ => return Err("Unsupported assignment target".to_string()),
+        }
+        Ok(())
+    }
+
+    /// 编译复合赋值 `target op= value`（见 compound 模块）
+    fn compile_compound_assign(&mut self, target: &Expr, op: BinOp, value: &Expr) -> Result<(), String> {
+        let target_ty = self.infer_expr_type(target);
+        if let (Some(target_ty), Some(value_ty)) = (&target_ty, self.infer_expr_type(value)) {
+            compound::check_operands(op, target_ty, value, &value_ty)?;
+        }
+
+        let mut hidden = Vec::new();
+        let stable = compound::stable_target(target, &mut |expr| {
+            let ty = self.infer_expr_type(expr)
+                .ok_or_else(|| format!("Cannot infer the type of `{}` in a compound assignment", purity::expr_text(expr)))?;
+            let val = self.compile_expr(expr)?;
+            let name = format!("__compound_{}", self.var_counter);
+            let var = self.declare_variable(&name, self.bolide_type_to_cranelift(&ty));
+            self.builder.def_var(var, val);
+            self.var_types.insert(name.clone(), ty);
+            hidden.push(name.clone());
+            Ok(Expr::Ident(name))
+        });
+
+        let result = stable.and_then(|stable| {
+            if target_ty.as_ref().is_some_and(|ty| compound::is_list_extend(op, ty)) {
+                let list = self.compile_dict_ref(&stable)?;
+                let other = self.compile_expr_expecting(value, target_ty.as_ref())?;
+                let list_extend = self.get_func_ref("list_extend")?;
+                self.builder.ins().call(list_extend, &[list, other]);
+                return Ok(());
+            }
+            let value = Expr::BinOp(Box::new(stable.clone()), op, Box::new(value.clone()));
+            self.compile_assign(&bolide_parser::Assign { target: stable, op: None, value })
+        });
+
+        for name in hidden {
+            self.variables.remove(&name);
+            self.var_types.remove(&name);
+        }
+        result
+    }
+
+    /// 编译解构语句: let a, b = expr; / a, b = expr;
+    ///
+    /// 右侧只求值一次，得到的元组归这里所有：槽位值直接移交给目标变量，
+    /// `_` 丢弃的 RC 槽位立即释放，最后只释放元组本身。
+    fn compile_destructure(&mut self, destructure: &bolide_parser::Destructure) -> Result<(), String> {
+        if let Some((name, args)) = overflow::checked_call(&destructure.value) {
+            if destructure.targets.len() == 2 {
+                return self.compile_checked_destructure(destructure, name, args);
+            }
+        }
+        if let Expr::Call(callee, _) = &destructure.value {
+            if matches!(callee.as_ref(), Expr::Ident(name) if name == "join_ok") {
+                return Err(JOIN_OK_UNSUPPORTED.to_string());
+            }
+        }
+        let slot_types = match self.infer_expr_type(&destructure.value) {
+            Some(BolideType::Tuple(types)) => types,
+            Some(other) => return Err(format!("Cannot destructure a value of type {:?}", other)),
+            None => return Err("Cannot destructure a value of unknown type".to_string()),
+        };
+        if slot_types.len() != destructure.targets.len() {
+            return Err(format!(
+                "Cannot destructure a {}-tuple into {} names",
+                slot_types.len(),
+                destructure.targets.len()
+            ));
+        }
+
+        let tuple_val = self.compile_expr(&destructure.value)?;
+        self.remove_temp_rc_value(tuple_val);
+
+        let tuple_get = self.get_func_ref("tuple_get")?;
+        for (i, (target, ty)) in destructure.targets.iter().zip(&slot_types).enumerate() {
+            let idx = self.builder.ins().iconst(types::I64, i as i64);
+            let call = self.builder.ins().call(tuple_get, &[tuple_val, idx]);
+            let mut val = self.builder.inst_results(call)[0];
+            // 元组槽位是 i64，float 以位模式存储
+            if *ty == BolideType::Float {
+                val = self.builder.ins().bitcast(types::F64, MemFlags::new(), val);
+            }
+
+            match target {
+                None => {
+                    if Self::is_rc_type(ty) {
+                        self.emit_release(val, ty);
+                    }
+                }
+                Some(name) if self.is_global(name) && (!destructure.declare || self.current_func_name == "__main__") => {
+                    if destructure.declare {
+                        self.global_var_types.insert(name.clone(), ty.clone());
+                    }
+                    self.store_global(name, val, destructure.declare)?;
+                }
+                Some(name) if destructure.declare => {
+                    let var = self.declare_variable(name, self.bolide_type_to_cranelift(ty));
+                    self.var_types.insert(name.clone(), ty.clone());
+                    self.builder.def_var(var, val);
+                    self.track_rc_variable(name, ty);
+                }
+                Some(name) => {
+                    let var = *self.variables.get(name)
+                        .ok_or_else(|| format!("Undefined variable: {}", name))?;
+                    if let Some(old_ty) = self.var_types.get(name).cloned() {
+                        if Self::is_rc_type(&old_ty) {
+                            let old_val = self.builder.use_var(var);
+                            self.emit_release(old_val, &old_ty);
+                        }
+                    }
+                    self.builder.def_var(var, val);
+                }
+            }
+        }
+
+        // 槽位已全部移交或释放，只释放元组本身
+        let tuple_free = self.get_func_ref("tuple_free")?;
+        self.builder.ins().call(tuple_free, &[tuple_val]);
+        Ok(())
+    }
+
+    /// `let ok, v = checked_add(a, b);`：不分配元组，两个结果直接绑定到目标（都不是 RC 类型）
+    fn compile_checked_destructure(&mut self, destructure: &bolide_parser::Destructure, name: &str, args: &[Expr]) -> Result<(), String> {
+        let (_, value, overflowed) = self.compile_int_intrinsic(name, args)?;
+        let overflowed = overflowed.ok_or_else(|| format!("{} reports no overflow flag", name))?;
+        let ok = self.builder.ins().icmp_imm(IntCC::Equal, overflowed, 0);
+        let ok = self.builder.ins().uextend(types::I64, ok);
+
+        for (target, (val, ty)) in destructure.targets.iter().zip([(ok, BolideType::Bool), (value, BolideType::Int)]) {
+            let Some(target) = target else { continue };
+            if self.is_global(target) && (!destructure.declare || self.current_func_name == "__main__") {
+                if destructure.declare {
+                    self.global_var_types.insert(target.clone(), ty);
+                }
+                self.store_global(target, val, destructure.declare)?;
+            } else if destructure.declare {
+                let var = self.declare_variable(target, self.bolide_type_to_cranelift(&ty));
+                self.var_types.insert(target.clone(), ty);
+                self.builder.def_var(var, val);
+            } else {
+                let var = *self.variables.get(target)
+                    .ok_or_else(|| format!("Undefined variable: {}", target))?;
+                self.builder.def_var(var, val);
+            }
         }
         Ok(())
     }
 
     /// 编译成员赋值
     fn compile_member_assign(&mut self, base: &Expr, member: &str, value: &Expr) -> Result<(), String> {
+        if let Some(resolved) = modules::resolve_member(&self.modules, base, member) {
+            resolved?;
+            return Err(modules::global_write_error(base, member));
+        }
         let base_val = self.compile_expr(base)?;
-        let val = self.compile_expr(value)?;
+        let field_ty = self.infer_expr_type(&Expr::Member(Box::new(base.clone()), member.to_string()));
+        let val = self.compile_expr_expecting(value, field_ty.as_ref())?;
 
         let base_type = self.infer_expr_type(base);
         
@@ -3706,9 +8563,9 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                             let field_ptr = self.builder.ins().iadd_imm(base_val, offset as i64);
                             let old_val = self.builder.ins().load(types::I64, MemFlags::new(), field_ptr, 0);
                             self.emit_release(old_val, &field.ty);
-                            
-                            // Take ownership of new value if it's a temp
-                            self.remove_temp_rc_value(val);
+                            let val = self.take_binding_value(value, val);
+                            self.builder.ins().store(MemFlags::new(), val, base_val, offset);
+                            return Ok(());
                         }
                         
                         self.builder.ins().store(MemFlags::new(), val, base_val, offset);
@@ -3723,39 +8580,52 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
 
     /// 编译索引赋值
     fn compile_index_assign(&mut self, base: &Expr, index: &Expr, value: &Expr) -> Result<(), String> {
-        let base_val = self.compile_expr(base)?;
+        if let Some(BolideType::Dict(key_ty, val_ty)) = self.infer_expr_type(base) {
+            let dict = self.compile_dict_ref(base)?;
+            return self.emit_dict_set(dict, &key_ty, &val_ty, index, value);
+        }
+        let elem_ty = match self.infer_expr_type(base) {
+            Some(BolideType::List(elem)) => *elem,
+            _ => BolideType::Int,
+        };
+        // 借用变量里的列表，写入的是变量本身而不是副本
+        let base_val = self.compile_dict_ref(base)?;
         let index_val = self.compile_expr(index)?;
-        let val = self.compile_expr(value)?;
+        let val = self.compile_list_elem_arg(value, &elem_ty)?;
 
-        // Consume value ownership
-        self.remove_temp_rc_value(val);
+        // str 列表自己 retain，其他元素的所有权移交给列表
+        if !Self::list_retains(&elem_ty) {
+            self.remove_temp_rc_value(val);
+        }
 
-        let func_ref = *self.func_refs.get("list_set")
-            .ok_or("list_set not found")?;
-        self.builder.ins().call(func_ref, &[base_val, index_val, val]);
+        self.emit_list_set(base_val, index_val, val)?;
         Ok(())
     }
 
     /// 编译返回语句
     fn compile_return(&mut self, expr: Option<&Expr>) -> Result<(), String> {
         if let Some(e) = expr {
-            let val = self.compile_expr(e)?;
+            let ret_ty = self.func_return_types.get(&self.current_func_name).cloned().flatten();
+            let val = self.compile_expr_expecting(e, ret_ty.as_ref())?;
             
-            // If val is in temp_rc_values, remove it so it's not released here
-            // (Function return transfers ownership of +1 ref count)
-            self.remove_temp_rc_value(val);
+            // 返回值的 +1 引用交给调用方（返回变量或字段时按绑定位置复制）
+            let val = self.take_binding_value(e, val);
+            self.emit_pool_early_exits(0)?;
             
             // Release other temporary values
             self.release_temp_rc_values();
             
             // Cleanup variables before returning
             self.emit_rc_cleanup();
+            self.emit_measure_early_exits()?;
             self.builder.ins().return_(&[val]);
         } else {
+            self.emit_pool_early_exits(0)?;
             // Release temporary values
             self.release_temp_rc_values();
             
             self.emit_rc_cleanup();
+            self.emit_measure_early_exits()?;
             self.builder.ins().return_(&[]);
         }
         Ok(())
@@ -3763,6 +8633,21 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
 
     /// 编译 if 语句
     fn compile_if(&mut self, if_stmt: &bolide_parser::IfStmt) -> Result<bool, String> {
+        // elif 链等价于 else 分支里嵌套的 if
+        if let Some(((cond, body), rest)) = if_stmt.elif_branches.split_first() {
+            let nested = bolide_parser::IfStmt {
+                condition: cond.clone(),
+                then_body: body.clone(),
+                elif_branches: rest.to_vec(),
+                else_body: if_stmt.else_body.clone(),
+            };
+            return self.compile_if(&bolide_parser::IfStmt {
+                condition: if_stmt.condition.clone(),
+                then_body: if_stmt.then_body.clone(),
+                elif_branches: Vec::new(),
+                else_body: Some(vec![Statement::If(nested)]),
+            });
+        }
         let cond = self.compile_expr(&if_stmt.condition)?;
 
         let then_block = self.builder.create_block();
@@ -3820,12 +8705,213 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         Ok(then_returned && else_returned)
     }
 
+    /// 编译 match 语句
+    ///
+    /// 整数用 `Switch` 分派：连续的分支值生成 br_table，稀疏的生成二分比较。
+    /// 字符串依次和各个字面量 string_eq，命中后把分支序号传给 dispatch 块再分派。
+    fn compile_match(&mut self, match_stmt: &bolide_parser::MatchStmt) -> Result<bool, String> {
+        let subject_ty = self.infer_expr_type(&match_stmt.subject);
+        let is_str = match subject_ty {
+            Some(BolideType::Int) => false,
+            Some(BolideType::Str) => true,
+            ref other => return Err(format!("match subject must be int or str, got {:?}", other)),
+        };
+        for arm in &match_stmt.arms {
+            for pattern in &arm.patterns {
+                if matches!(pattern, MatchPattern::Str(_)) != is_str {
+                    return Err(format!("match on {:?} cannot have pattern {:?}", subject_ty, pattern));
+                }
+            }
+        }
+
+        let subject = self.compile_expr(&match_stmt.subject)?;
+        let arm_blocks: Vec<Block> = match_stmt.arms.iter().map(|_| self.builder.create_block()).collect();
+        let default_block = self.builder.create_block();
+        let merge_block = self.builder.create_block();
+
+        let mut switch = Switch::new();
+        let discriminant = if is_str {
+            // 比较期间 subject 必须存活，临时值在 dispatch 块（所有比较路径的汇合点）释放
+            let dispatch_block = self.builder.create_block();
+            self.builder.append_block_param(dispatch_block, types::I64);
+            let eq_ref = self.get_func_ref("string_eq")?;
+            for (index, arm) in match_stmt.arms.iter().enumerate() {
+                for pattern in &arm.patterns {
+                    let MatchPattern::Str(text) = pattern else { unreachable!() };
+                    let literal = self.load_string_literal(text)?;
+                    let call = self.builder.ins().call(eq_ref, &[subject, literal]);
+                    let eq = self.builder.inst_results(call)[0];
+                    let arm_index = self.builder.ins().iconst(types::I64, index as i64);
+                    let next_block = self.builder.create_block();
+                    self.builder.ins().brif(eq, dispatch_block, &[arm_index], next_block, &[]);
+                    self.builder.switch_to_block(next_block);
+                    self.builder.seal_block(next_block);
+                }
+            }
+            let no_match = self.builder.ins().iconst(types::I64, match_stmt.arms.len() as i64);
+            self.builder.ins().jump(dispatch_block, &[no_match]);
+            self.builder.switch_to_block(dispatch_block);
+            self.builder.seal_block(dispatch_block);
+            for (index, &block) in arm_blocks.iter().enumerate() {
+                switch.set_entry(index as u128, block);
+            }
+            self.builder.block_params(dispatch_block)[0]
+        } else {
+            for (arm, &block) in match_stmt.arms.iter().zip(&arm_blocks) {
+                for pattern in &arm.patterns {
+                    let MatchPattern::Int(value) = pattern else { unreachable!() };
+                    switch.set_entry(*value as u64 as u128, block);
+                }
+            }
+            subject
+        };
+        self.release_temp_rc_values();
+        switch.emit(&mut self.builder, discriminant, default_block);
+
+        let mut all_returned = true;
+        let bodies = match_stmt.arms.iter().map(|arm| &arm.body).zip(arm_blocks)
+            .chain(match_stmt.default.iter().zip([default_block]));
+        for (body, block) in bodies {
+            self.builder.switch_to_block(block);
+            self.builder.seal_block(block);
+            let scope_idx = self.enter_scope();
+            let mut returned = false;
+            for stmt in body {
+                if self.compile_stmt(stmt)? {
+                    returned = true;
+                    break;
+                }
+            }
+            if returned {
+                // 已经在 return/break 处释放，下一个分支不能再释放这些变量
+                self.rc_variables.truncate(scope_idx);
+            } else {
+                self.leave_scope(scope_idx);
+                self.builder.ins().jump(merge_block, &[]);
+            }
+            all_returned &= returned;
+        }
+        if match_stmt.default.is_none() {
+            // 没有 `_` 分支：不匹配时什么都不执行
+            self.builder.switch_to_block(default_block);
+            self.builder.seal_block(default_block);
+            self.builder.ins().jump(merge_block, &[]);
+            all_returned = false;
+        }
+
+        self.builder.switch_to_block(merge_block);
+        self.builder.seal_block(merge_block);
+
+        Ok(all_returned)
+    }
+
+    /// 进入循环（跳到循环头之前）：记录 break/continue 的目标，为 loop_count() 准备初值为 0 的隐藏计数器
+    fn push_loop(&mut self, continue_block: Block, exit_block: Block) {
+        let counter = Variable::new(self.var_counter);
+        self.var_counter += 1;
+        self.builder.declare_var(counter, types::I64);
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        self.builder.def_var(counter, zero);
+        self.loops.push(LoopFrame {
+            counter,
+            counter_used: false,
+            continue_block,
+            exit_block,
+            continued: false,
+            scope_start: self.rc_variables.len(),
+            measure_depth: self.measure_stack.len(),
+            pool_depth: self.pool_stack.len(),
+        });
+    }
+
+    /// 进入循环体作用域，break/continue 释放从这里开始声明的 RC 变量
+    fn enter_loop_body(&mut self) -> usize {
+        let scope_idx = self.enter_scope();
+        if let Some(frame) = self.loops.last_mut() {
+            frame.scope_start = scope_idx;
+        }
+        scope_idx
+    }
+
+    /// 离开循环体作用域：走到末尾时释放本次迭代的变量；以 return/break/continue 结束时
+    /// 各出口已经释放过，只需把它们移出作用域
+    fn leave_loop_body(&mut self, scope_idx: usize, body_terminated: bool) {
+        if body_terminated {
+            self.rc_variables.truncate(scope_idx);
+        } else {
+            self.leave_scope(scope_idx);
+        }
+    }
+
+    /// 循环体编译完后切换到 continue 块；循环体正常走到末尾或有 continue 时返回 true，
+    /// 调用者接着推进循环变量并调用 `emit_loop_back_edge`
+    fn enter_loop_latch(&mut self, body_terminated: bool) -> bool {
+        let frame = self.loops.last().expect("enter_loop_latch outside a loop");
+        let latch = frame.continue_block;
+        let reachable = !body_terminated || frame.continued;
+        if !body_terminated {
+            self.builder.ins().jump(latch, &[]);
+        }
+        self.builder.seal_block(latch);
+        if reachable {
+            self.builder.switch_to_block(latch);
+        }
+        reachable
+    }
+
+    /// 回边：只有用到 loop_count() 的循环才递增计数器，然后回到循环头
+    fn emit_loop_back_edge(&mut self, header_block: Block) {
+        if let Some(&LoopFrame { counter, counter_used: true, .. }) = self.loops.last() {
+            let current = self.builder.use_var(counter);
+            let next = self.builder.ins().iadd_imm(current, 1);
+            self.builder.def_var(counter, next);
+        }
+        self.builder.ins().jump(header_block, &[]);
+    }
+
+    /// 编译 break / continue：释放循环体内已声明的 RC 变量、结束循环体内的 measure 块，
+    /// 跳到最内层循环的出口或 continue 块
+    fn compile_loop_jump(&mut self, is_break: bool) -> Result<(), String> {
+        let keyword = if is_break { "break" } else { "continue" };
+        if self.loops.len() <= self.loop_fence {
+            return Err(if self.loops.is_empty() {
+                format!("'{}' can only be used inside a while or for loop", keyword)
+            } else {
+                format!("'{}' cannot jump out of a select or await scope block", keyword)
+            });
+        }
+        let frame = self.loops.last_mut().expect("checked above");
+        let target = if is_break {
+            frame.exit_block
+        } else {
+            frame.continued = true;
+            frame.continue_block
+        };
+        let (scope_start, measure_depth, pool_depth) = (frame.scope_start, frame.measure_depth, frame.pool_depth);
+
+        self.emit_pool_early_exits(pool_depth)?;
+        self.release_temp_rc_values();
+        for i in (scope_start..self.rc_variables.len()).rev() {
+            let (var, ty) = self.rc_variables[i].clone();
+            let val = self.builder.use_var(var);
+            self.emit_release(val, &ty);
+        }
+        let crossed: Vec<(String, Value, Value)> = self.measure_stack[measure_depth..].iter().rev().cloned().collect();
+        for (label, start, alloc_start) in crossed {
+            self.emit_measure_end(&label, start, alloc_start, true)?;
+        }
+        self.builder.ins().jump(target, &[]);
+        Ok(())
+    }
+
     /// 编译 while 语句
     fn compile_while(&mut self, while_stmt: &bolide_parser::WhileStmt) -> Result<(), String> {
         let header_block = self.builder.create_block();
         let body_block = self.builder.create_block();
         let exit_block = self.builder.create_block();
+        let latch_block = self.builder.create_block();
 
+        self.push_loop(latch_block, exit_block);
         self.builder.ins().jump(header_block, &[]);
 
         // 条件检查
@@ -3843,7 +8929,7 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         self.builder.switch_to_block(body_block);
         self.builder.seal_block(body_block);
         
-        let scope_idx = self.enter_scope();
+        let scope_idx = self.enter_loop_body();
         let mut body_returned = false;
         for stmt in &while_stmt.body {
             if self.compile_stmt(stmt)? {
@@ -3852,10 +8938,11 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             }
         }
         
-        if !body_returned {
-             self.leave_scope(scope_idx);
-             self.builder.ins().jump(header_block, &[]);
+        self.leave_loop_body(scope_idx, body_returned);
+        if self.enter_loop_latch(body_returned) {
+            self.emit_loop_back_edge(header_block);
         }
+        self.loops.pop();
 
         // 现在所有 header_block 的前驱都已添加，可以 seal 了
         self.builder.seal_block(header_block);
@@ -3876,59 +8963,111 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
                 }
             }
         }
+        if for_stmt.var_type.is_some() {
+            return Err("Loop variable type annotations are only supported on range() loops".to_string());
+        }
+
+        if let Some(BolideType::Dict(key_ty, val_ty)) = self.infer_expr_type(&for_stmt.iter) {
+            return self.compile_dict_for(for_stmt, &key_ty, &val_ty);
+        }
+        if let Some(BolideType::Channel(elem_ty)) = self.infer_expr_type(&for_stmt.iter) {
+            return self.compile_channel_for(for_stmt, &elem_ty);
+        }
 
         // 列表迭代
         self.compile_list_for(for_stmt)
     }
 
     /// 编译 range for 循环
+    ///
+    /// 任一参数是 float（或循环变量标注为 float）时是 float range，见 `compile_float_range_bounds`。
     fn compile_range_for(&mut self, for_stmt: &bolide_parser::ForStmt, args: &[Expr]) -> Result<(), String> {
+        let var_name = for_stmt.vars.first()
+            .ok_or("For loop requires at least one variable")?;
+        if args.is_empty() || args.len() > 3 {
+            return Err("range() requires 1-3 arguments".to_string());
+        }
+        let is_float = match &for_stmt.var_type {
+            Some(BolideType::Float) => true,
+            Some(BolideType::Int) | None => args.iter().any(|arg| self.infer_expr_type(arg) == Some(BolideType::Float)),
+            Some(other) => return Err(format!("range() loop variable '{}' must be int or float, not {:?}", var_name, other)),
+        };
+        if is_float && for_stmt.var_type == Some(BolideType::Int) {
+            return Err(format!(
+                "range() with float bounds cannot bind int loop variable '{}'; annotate it as float or convert the bounds with int()",
+                var_name
+            ));
+        }
+
+        // float range：整数计数器 (计数器变量, start, step)，end 是迭代次数
+        let mut float_range = None;
         // 解析 range 参数: range(end) 或 range(start, end) 或 range(start, end, step)
-        let (start, end, step) = match args.len() {
-            1 => {
-                let end = self.compile_expr(&args[0])?;
-                let start = self.builder.ins().iconst(types::I64, 0);
-                let step = self.builder.ins().iconst(types::I64, 1);
-                (start, end, step)
-            }
-            2 => {
-                let start = self.compile_expr(&args[0])?;
-                let end = self.compile_expr(&args[1])?;
-                let step = self.builder.ins().iconst(types::I64, 1);
-                (start, end, step)
-            }
-            3 => {
-                let start = self.compile_expr(&args[0])?;
-                let end = self.compile_expr(&args[1])?;
-                let step = self.compile_expr(&args[2])?;
-                (start, end, step)
+        let (start, end, step) = if is_float {
+            let (start, count, step) = self.compile_float_range_bounds(args)?;
+            let counter = self.declare_variable(&format!("__range_count_{}", var_name), types::I64);
+            let zero = self.builder.ins().iconst(types::I64, 0);
+            self.builder.def_var(counter, zero);
+            float_range = Some((counter, start, step));
+            (start, count, step)
+        } else {
+            match args.len() {
+                1 => {
+                    let end = self.compile_expr(&args[0])?;
+                    let start = self.builder.ins().iconst(types::I64, 0);
+                    let step = self.builder.ins().iconst(types::I64, 1);
+                    (start, end, step)
+                }
+                2 => {
+                    let start = self.compile_expr(&args[0])?;
+                    let end = self.compile_expr(&args[1])?;
+                    let step = self.builder.ins().iconst(types::I64, 1);
+                    (start, end, step)
+                }
+                _ => {
+                    let start = self.compile_expr(&args[0])?;
+                    let end = self.compile_expr(&args[1])?;
+                    let step = self.compile_expr(&args[2])?;
+                    (start, end, step)
+                }
             }
-            _ => return Err("range() requires 1-3 arguments".to_string()),
         };
 
         // 创建循环变量
-        let var_name = for_stmt.vars.first()
-            .ok_or("For loop requires at least one variable")?;
-        let loop_var = self.declare_variable(var_name, types::I64);
+        let (loop_ty, loop_bolide_ty) = if is_float { (types::F64, BolideType::Float) } else { (types::I64, BolideType::Int) };
+        let loop_var = self.declare_variable(var_name, loop_ty);
         self.builder.def_var(loop_var, start);
+        self.var_types.insert(var_name.clone(), loop_bolide_ty);
 
         let header_block = self.builder.create_block();
         let body_block = self.builder.create_block();
         let exit_block = self.builder.create_block();
+        let latch_block = self.builder.create_block();
 
+        self.push_loop(latch_block, exit_block);
         self.builder.ins().jump(header_block, &[]);
 
         // 条件检查
         self.builder.switch_to_block(header_block);
-        let idx = self.builder.use_var(loop_var);
+        let idx = match float_range {
+            Some((counter, ..)) => self.builder.use_var(counter),
+            None => self.builder.use_var(loop_var),
+        };
         let cond = self.builder.ins().icmp(IntCC::SignedLessThan, idx, end);
         self.builder.ins().brif(cond, body_block, &[], exit_block, &[]);
 
         // 循环体
         self.builder.switch_to_block(body_block);
         self.builder.seal_block(body_block);
+        if let Some((counter, start, step)) = float_range {
+            // t = start + i * step
+            let i = self.builder.use_var(counter);
+            let i = self.builder.ins().fcvt_from_sint(types::F64, i);
+            let offset = self.builder.ins().fmul(i, step);
+            let t = self.builder.ins().fadd(start, offset);
+            self.builder.def_var(loop_var, t);
+        }
 
-        let scope_idx = self.enter_scope();
+        let scope_idx = self.enter_loop_body();
         let mut body_returned = false;
         for stmt in &for_stmt.body {
             if self.compile_stmt(stmt)? {
@@ -3937,16 +9076,22 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             }
         }
         
-        if !body_returned {
-             self.leave_scope(scope_idx);
-
-             // 递增索引
-             let idx = self.builder.use_var(loop_var);
-             let new_idx = self.builder.ins().iadd(idx, step);
-             self.builder.def_var(loop_var, new_idx);
+        self.leave_loop_body(scope_idx, body_returned);
+        if self.enter_loop_latch(body_returned) {
+            if let Some((counter, ..)) = float_range {
+                let i = self.builder.use_var(counter);
+                let next = self.builder.ins().iadd_imm(i, 1);
+                self.builder.def_var(counter, next);
+            } else {
+                // 递增索引
+                let idx = self.builder.use_var(loop_var);
+                let new_idx = self.builder.ins().iadd(idx, step);
+                self.builder.def_var(loop_var, new_idx);
+            }
 
-             self.builder.ins().jump(header_block, &[]);
+            self.emit_loop_back_edge(header_block);
         }
+        self.loops.pop();
 
         self.builder.seal_block(header_block);
 
@@ -3956,10 +9101,110 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         Ok(())
     }
 
+    /// float range 的 (start, 迭代次数, step)，int 参数先转成 float
+    ///
+    /// 和 JIT 一样预先算出迭代次数 `ceil((end - start) / step)`，步长为 0 或参数含 NaN 时不迭代。
+    fn compile_float_range_bounds(&mut self, args: &[Expr]) -> Result<(Value, Value, Value), String> {
+        let mut bounds = Vec::with_capacity(3);
+        for arg in args {
+            let val = self.compile_expr(arg)?;
+            let val = if self.builder.func.dfg.value_type(val) == types::F64 {
+                val
+            } else {
+                self.builder.ins().fcvt_from_sint(types::F64, val)
+            };
+            bounds.push(val);
+        }
+        let (start, end, step) = match bounds[..] {
+            [end] => (self.builder.ins().f64const(0.0), end, self.builder.ins().f64const(1.0)),
+            [start, end] => (start, end, self.builder.ins().f64const(1.0)),
+            [start, end, step] => (start, end, step),
+            _ => return Err("range() requires 1-3 arguments".to_string()),
+        };
+        let span = self.builder.ins().fsub(end, start);
+        let trips = self.builder.ins().fdiv(span, step);
+        let trips = self.builder.ins().ceil(trips);
+        // NaN 转成 0；负数在循环条件里等同于 0
+        let count = self.builder.ins().fcvt_to_sint_sat(types::I64, trips);
+        let zero_f = self.builder.ins().f64const(0.0);
+        let zero_step = self.builder.ins().fcmp(FloatCC::Equal, step, zero_f);
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        let count = self.builder.ins().select(zero_step, zero, count);
+        Ok((start, count, step))
+    }
+
     /// 编译列表 for 循环
+    /// 声明 `for a, b in xs` 解构出的变量：元素是列表时都取内层元素类型，是元组时按位置取类型
+    fn declare_unpacked_loop_vars(&mut self, vars: &[String], elem_type: &BolideType) -> Result<Vec<(Variable, BolideType)>, String> {
+        let mut unpacked = Vec::new();
+        for (i, name) in vars.iter().enumerate() {
+            let ty = match elem_type {
+                BolideType::List(inner) => (**inner).clone(),
+                BolideType::Tuple(inner) => inner.get(i).cloned().unwrap_or(BolideType::Int),
+                other => return Err(format!("Cannot unpack type {:?} in for loop", other)),
+            };
+            let cl_type = self.bolide_type_to_cranelift(&ty);
+            let var = self.declare_variable(name, cl_type);
+            let init = if cl_type == types::F64 {
+                self.builder.ins().f64const(0.0)
+            } else {
+                self.builder.ins().iconst(cl_type, 0)
+            };
+            self.builder.def_var(var, init);
+            self.var_types.insert(name.clone(), ty.clone());
+            unpacked.push((var, ty));
+        }
+        Ok(unpacked)
+    }
+
+    /// 每次迭代把元素的各个位置取到解构变量里；RC 值 retain 一份，随循环变量在迭代结束时释放
+    fn unpack_loop_elem(&mut self, elem: Value, elem_type: &BolideType, unpacked: &[(Variable, BolideType)]) -> Result<(), String> {
+        for (i, (var, ty)) in unpacked.iter().enumerate() {
+            let idx = self.builder.ins().iconst(types::I64, i as i64);
+            let val = if let BolideType::List(inner) = elem_type {
+                self.emit_list_get(elem, idx, inner)?
+            } else {
+                let func_ref = self.get_func_ref("tuple_get")?;
+                let call = self.builder.ins().call(func_ref, &[elem, idx]);
+                let slot = self.builder.inst_results(call)[0];
+                self.slot_to_elem(slot, ty)
+            };
+            let val = if Self::is_rc_type(ty) {
+                self.rc_variables.push((*var, ty.clone()));
+                self.emit_retain(val, ty)
+            } else {
+                val
+            };
+            self.builder.def_var(*var, val);
+        }
+        Ok(())
+    }
+
     fn compile_list_for(&mut self, for_stmt: &bolide_parser::ForStmt) -> Result<(), String> {
-        // 编译迭代器
-        let iter_val = self.compile_expr(&for_stmt.iter)?;
+        // 编译迭代器；临时列表由循环持有到结束，循环体语句释放临时值时不能释放它
+        // 遍历变量时借用它的列表（不复制）并 retain 一份：循环体里的 push / pop 作用在同一个列表上，
+        // 给变量重新赋值也不会释放正在遍历的列表
+        let (iter_val, held) = if matches!(for_stmt.iter, Expr::Ident(_)) {
+            let list = self.compile_dict_ref(&for_stmt.iter)?;
+            let list_ty = self.infer_expr_type(&for_stmt.iter).unwrap_or(BolideType::List(Box::new(BolideType::Int)));
+            let func_ref = self.get_func_ref("list_retain")?;
+            self.builder.ins().call(func_ref, &[list]);
+            (list, Some((list, list_ty)))
+        } else {
+            let iter_val = self.compile_expr(&for_stmt.iter)?;
+            let held = self.temp_rc_values.iter().position(|(v, _)| *v == iter_val)
+                .map(|pos| self.temp_rc_values.remove(pos));
+            // 求值时的其他临时值（如字面量里的字符串）在进入循环前释放，不能留给循环体的每条语句
+            self.release_temp_rc_values();
+            (iter_val, held)
+        };
+        // 持有的列表放进隐藏变量，提前 return 时随其他 RC 变量释放
+        let held_scope = self.enter_scope();
+        if let Some((list, ty)) = &held {
+            let held_var = self.declare_variable("__for_list", self.ptr_type);
+            self.builder.def_var(held_var, *list);
+            self.rc_variables.push((held_var, ty.clone()));
+        }
         
         // Infer element type
         let elem_type = match self.infer_expr_type(&for_stmt.iter) {
@@ -3968,8 +9213,7 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         };
 
         // 获取列表长度
-        let len_ref = *self.func_refs.get("list_len")
-            .ok_or("list_len not found")?;
+        let len_ref = self.get_func_ref("list_len")?;
         let call = self.builder.ins().call(len_ref, &[iter_val]);
         let len = self.builder.inst_results(call)[0];
 
@@ -3978,23 +9222,40 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         let zero = self.builder.ins().iconst(types::I64, 0);
         self.builder.def_var(idx_var, zero);
 
-        // 创建循环变量
-        let var_name = for_stmt.vars.first()
-            .ok_or("For loop requires at least one variable")?;
-        let loop_var = self.declare_variable(var_name, types::I64);
-        self.builder.def_var(loop_var, zero);
+        // 创建循环变量；`for a, b in xs` 时元素放在隐藏变量里，再解构到各个变量
+        if for_stmt.vars.is_empty() {
+            return Err("For loop requires at least one variable".to_string());
+        }
+        let destructure = for_stmt.vars.len() > 1;
+        let var_name = if destructure { "__for_elem".to_string() } else { for_stmt.vars[0].clone() };
+        let var_name = &var_name;
+        // float 元素的循环变量是 f64
+        let elem_cl_type = self.bolide_type_to_cranelift(&elem_type);
+        let loop_var = self.declare_variable(var_name, elem_cl_type);
+        let init = if elem_cl_type == types::F64 { self.builder.ins().f64const(0.0) } else { zero };
+        self.builder.def_var(loop_var, init);
         
         self.var_types.insert(var_name.clone(), elem_type.clone());
+        let unpacked = if destructure {
+            self.declare_unpacked_loop_vars(&for_stmt.vars, &elem_type)?
+        } else {
+            Vec::new()
+        };
 
         let header_block = self.builder.create_block();
         let body_block = self.builder.create_block();
         let exit_block = self.builder.create_block();
+        let latch_block = self.builder.create_block();
 
+        self.push_loop(latch_block, exit_block);
         self.builder.ins().jump(header_block, &[]);
 
-        // 条件检查
+        // 条件检查：与 JIT 的迭代器相同，追加的元素不遍历，删除后超出当前长度时结束
         self.builder.switch_to_block(header_block);
         let idx = self.builder.use_var(idx_var);
+        let call = self.builder.ins().call(len_ref, &[iter_val]);
+        let current_len = self.builder.inst_results(call)[0];
+        let len = self.builder.ins().smin(len, current_len);
         let cond = self.builder.ins().icmp(IntCC::SignedLessThan, idx, len);
         self.builder.ins().brif(cond, body_block, &[], exit_block, &[]);
 
@@ -4002,16 +9263,13 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
         self.builder.switch_to_block(body_block);
         self.builder.seal_block(body_block);
         
-        let scope_idx = self.enter_scope();
+        let scope_idx = self.enter_loop_body();
         if Self::is_rc_type(&elem_type) {
             self.track_rc_variable(var_name, &elem_type);
         }
 
-        let get_ref = *self.func_refs.get("list_get")
-            .ok_or("list_get not found")?;
         let idx = self.builder.use_var(idx_var);
-        let call = self.builder.ins().call(get_ref, &[iter_val, idx]);
-        let elem = self.builder.inst_results(call)[0];
+        let elem = self.emit_list_get(iter_val, idx, &elem_type)?;
         
         let elem = if Self::is_rc_type(&elem_type) {
              self.emit_retain(elem, &elem_type)
@@ -4019,6 +9277,9 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
              elem
         };
         self.builder.def_var(loop_var, elem);
+        if destructure {
+            self.unpack_loop_elem(elem, &elem_type, &unpacked)?;
+        }
 
         let mut body_returned = false;
         for stmt in &for_stmt.body {
@@ -4028,23 +9289,359 @@ impl<'a, 'b> AotCompileContext<'a, 'b> {
             }
         }
         
-        if !body_returned {
-            self.leave_scope(scope_idx);
-
+        self.leave_loop_body(scope_idx, body_returned);
+        if self.enter_loop_latch(body_returned) {
             // 递增索引
             let idx = self.builder.use_var(idx_var);
             let one = self.builder.ins().iconst(types::I64, 1);
             let new_idx = self.builder.ins().iadd(idx, one);
             self.builder.def_var(idx_var, new_idx);
 
-            self.builder.ins().jump(header_block, &[]);
+            self.emit_loop_back_edge(header_block);
+        }
+        self.loops.pop();
+
+        self.builder.seal_block(header_block);
+
+        self.builder.switch_to_block(exit_block);
+        self.builder.seal_block(exit_block);
+
+        self.leave_scope(held_scope);
+        Ok(())
+    }
+
+    /// 编译 for msg in ch：每次迭代接收一个值（和 `<- ch` 一样按原始值传递），通道关闭并取空后结束
+    fn compile_channel_for(&mut self, for_stmt: &bolide_parser::ForStmt, elem_type: &BolideType) -> Result<(), String> {
+        let [var_name] = for_stmt.vars.as_slice() else {
+            return Err("for over a channel takes one variable".to_string());
+        };
+        let channel = self.compile_expr(&for_stmt.iter)?;
+
+        let elem_cl_type = self.bolide_type_to_cranelift(elem_type);
+        let loop_var = self.declare_variable(var_name, elem_cl_type);
+        let init = if elem_cl_type == types::F64 {
+            self.builder.ins().f64const(0.0)
+        } else {
+            self.builder.ins().iconst(elem_cl_type, 0)
+        };
+        self.builder.def_var(loop_var, init);
+        self.var_types.insert(var_name.clone(), elem_type.clone());
+
+        let slot = self.builder.create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8, 0));
+        let header_block = self.builder.create_block();
+        let body_block = self.builder.create_block();
+        let exit_block = self.builder.create_block();
+        let latch_block = self.builder.create_block();
+
+        self.push_loop(latch_block, exit_block);
+        self.builder.ins().jump(header_block, &[]);
+
+        // 条件检查：channel_recv_opt 返回 0 表示通道已关闭并取空
+        self.builder.switch_to_block(header_block);
+        let out_ptr = self.builder.ins().stack_addr(self.ptr_type, slot, 0);
+        let recv_ref = self.get_func_ref("channel_recv_opt")?;
+        let call = self.builder.ins().call(recv_ref, &[channel, out_ptr]);
+        let received = self.builder.inst_results(call)[0];
+        self.builder.ins().brif(received, body_block, &[], exit_block, &[]);
+
+        // 循环体
+        self.builder.switch_to_block(body_block);
+        self.builder.seal_block(body_block);
+        let value = self.builder.ins().stack_load(types::I64, slot, 0);
+        let value = if elem_cl_type == types::F64 {
+            self.builder.ins().bitcast(types::F64, MemFlags::new(), value)
+        } else {
+            value
+        };
+        self.builder.def_var(loop_var, value);
+
+        let scope_idx = self.enter_loop_body();
+        let mut body_returned = false;
+        for stmt in &for_stmt.body {
+            if self.compile_stmt(stmt)? {
+                body_returned = true;
+                break;
+            }
+        }
+        self.leave_loop_body(scope_idx, body_returned);
+        if self.enter_loop_latch(body_returned) {
+            self.emit_loop_back_edge(header_block);
         }
+        self.loops.pop();
 
         self.builder.seal_block(header_block);
+        self.builder.switch_to_block(exit_block);
+        self.builder.seal_block(exit_block);
+        Ok(())
+    }
+
+    /// 编译 for k in d / for k, v in d
+    ///
+    /// 遍历 `dict_iter` 返回的键列表（列表持有字符串键的引用），`v` 按键读取。
+    /// 被遍历的临时字典和键列表由循环持有，循环结束时释放。
+    fn compile_dict_for(&mut self, for_stmt: &bolide_parser::ForStmt, key_ty: &BolideType, val_ty: &BolideType) -> Result<(), String> {
+        let (key_name, val_name) = match for_stmt.vars.as_slice() {
+            [k] => (k, None),
+            [k, v] => (k, Some(v)),
+            _ => return Err("for over a dict takes one or two variables".to_string()),
+        };
+        // 只借用变量里的字典：字段、元素的借用会随下面释放的临时对象失效
+        let dict = if matches!(for_stmt.iter, Expr::Ident(_)) {
+            self.compile_dict_ref(&for_stmt.iter)?
+        } else {
+            self.compile_expr(&for_stmt.iter)?
+        };
+        let held = self.temp_rc_values.iter().position(|(v, _)| *v == dict)
+            .map(|pos| self.temp_rc_values.remove(pos));
+        self.release_temp_rc_values();
+
+        let iter_ref = self.get_func_ref("dict_iter")?;
+        let call = self.builder.ins().call(iter_ref, &[dict]);
+        let keys = self.builder.inst_results(call)[0];
+        let len_ref = self.get_func_ref("list_len")?;
+        let call = self.builder.ins().call(len_ref, &[keys]);
+        let len = self.builder.inst_results(call)[0];
+
+        let idx_var = self.declare_variable("__for_idx", types::I64);
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        self.builder.def_var(idx_var, zero);
+        let key_var = self.declare_variable(key_name, types::I64);
+        self.builder.def_var(key_var, zero);
+        self.var_types.insert(key_name.clone(), key_ty.clone());
+        let val_var = match val_name {
+            Some(name) => {
+                let ty = self.bolide_type_to_cranelift(val_ty);
+                let var = self.declare_variable(name, ty);
+                let init = if ty == types::F64 { self.builder.ins().f64const(0.0) } else { zero };
+                self.builder.def_var(var, init);
+                self.var_types.insert(name.clone(), val_ty.clone());
+                Some(var)
+            }
+            None => None,
+        };
+
+        let header_block = self.builder.create_block();
+        let body_block = self.builder.create_block();
+        let exit_block = self.builder.create_block();
+        let latch_block = self.builder.create_block();
+
+        self.push_loop(latch_block, exit_block);
+        self.builder.ins().jump(header_block, &[]);
+
+        self.builder.switch_to_block(header_block);
+        let idx = self.builder.use_var(idx_var);
+        let cond = self.builder.ins().icmp(IntCC::SignedLessThan, idx, len);
+        self.builder.ins().brif(cond, body_block, &[], exit_block, &[]);
+
+        self.builder.switch_to_block(body_block);
+        self.builder.seal_block(body_block);
+
+        let scope_idx = self.enter_loop_body();
+        let get_ref = self.get_func_ref("list_get")?;
+        let idx = self.builder.use_var(idx_var);
+        let call = self.builder.ins().call(get_ref, &[keys, idx]);
+        let key = self.builder.inst_results(call)[0];
+        let key_val = if Self::dict_retains(key_ty) {
+            self.track_rc_variable(key_name, key_ty);
+            self.emit_retain(key, key_ty)
+        } else {
+            key
+        };
+        self.builder.def_var(key_var, key_val);
+        if let (Some(name), Some(var)) = (val_name, val_var) {
+            let value = self.emit_dict_get(dict, val_ty, key)?;
+            // 读取的值由循环变量接管，本次迭代结束时释放
+            self.remove_temp_rc_value(value);
+            if Self::dict_retains(val_ty) {
+                self.track_rc_variable(name, val_ty);
+            }
+            self.builder.def_var(var, value);
+        }
+
+        let mut body_returned = false;
+        for stmt in &for_stmt.body {
+            if self.compile_stmt(stmt)? {
+                body_returned = true;
+                break;
+            }
+        }
+
+        self.leave_loop_body(scope_idx, body_returned);
+        if self.enter_loop_latch(body_returned) {
+            let idx = self.builder.use_var(idx_var);
+            let new_idx = self.builder.ins().iadd_imm(idx, 1);
+            self.builder.def_var(idx_var, new_idx);
+            self.emit_loop_back_edge(header_block);
+        }
+        self.loops.pop();
 
+        self.builder.seal_block(header_block);
         self.builder.switch_to_block(exit_block);
         self.builder.seal_block(exit_block);
 
+        let release_ref = self.get_func_ref("list_release")?;
+        self.builder.ins().call(release_ref, &[keys]);
+        if let Some((dict, ty)) = held {
+            self.emit_release(dict, &ty);
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cfg_follows_target() {
+        // 导入的模块也按目标过滤
+        let dir = std::env::temp_dir().join(format!("bolide_aot_cfg_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let module = dir.join("clock.bl");
+        std::fs::write(&module, r#"
+@cfg(windows)
+extern "kernel32.dll" {
+    fn GetTickCount64() -> u64;
+}
+@cfg(unix)
+extern "libc.so.6" {
+    fn time(t: ptr) -> i64;
+}
+"#).unwrap();
+
+        let source = format!(r#"
+import "{}";
+@cfg(windows)
+extern "msvcrt.dll" {{
+    fn fabs(x: c_double) -> c_double;
+}}
+@cfg(unix)
+extern "libm.so.6" {{
+    fn fabs(x: c_double) -> c_double;
+}}
+// 只在 Windows 上声明的函数，其他目标上整个函数被丢弃
+@cfg(windows)
+fn uptime() -> int {{
+    return GetTickCount64();
+}}
+let x: float = 1.5;
+"#, module.display().to_string().replace('\\', "/"));
+
+        let extern_libs = |os: &str| {
+            let triple = format!("{}-{}", std::env::consts::ARCH, os);
+            let target = CfgTarget::from_triple(&triple).unwrap();
+            let (program, _) = bolide_parser::parse_source_for_target(&source, &target).unwrap();
+            let mut compiler = AotCompiler::for_target(&triple).unwrap();
+            let mut libs = compiler.compile(&program).unwrap().extern_libs;
+            libs.sort();
+            libs
+        };
+        assert_eq!(extern_libs("pc-windows-msvc"), ["kernel32.dll", "msvcrt.dll"]);
+        assert_eq!(extern_libs("unknown-linux-gnu"), ["libc.so.6", "libm.so.6"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cross_target_object_format() {
+        let program = bolide_parser::parse_source("fn add(a: int, b: int) -> int { return a + b; }\nprint(add(1, 2));").unwrap();
+        let compile = |triple: &str| {
+            let result = AotCompiler::with_target(Some(triple)).unwrap().compile(&program).unwrap();
+            assert_eq!(result.target_triple, triple);
+            result.object_code
+        };
+
+        // ELF: 7f 'E' 'L' 'F'，e_machine 在偏移 18（x86_64 = 62，aarch64 = 183）
+        let elf = compile("x86_64-unknown-linux-gnu");
+        assert_eq!(&elf[..4], b"\x7fELF");
+        assert_eq!(u16::from_le_bytes([elf[18], elf[19]]), 62);
+        let elf = compile("aarch64-unknown-linux-gnu");
+        assert_eq!(&elf[..4], b"\x7fELF");
+        assert_eq!(u16::from_le_bytes([elf[18], elf[19]]), 183);
+
+        // Mach-O 64 位：MH_MAGIC_64，CPU 类型 arm64 = 0x0100000c
+        let macho = compile("aarch64-apple-darwin");
+        assert_eq!(u32::from_le_bytes(macho[..4].try_into().unwrap()), 0xfeedfacf);
+        assert_eq!(u32::from_le_bytes(macho[4..8].try_into().unwrap()), 0x0100000c);
+
+        // COFF：开头就是机器类型 AMD64 = 0x8664
+        let coff = compile("x86_64-pc-windows-msvc");
+        assert_eq!(u16::from_le_bytes([coff[0], coff[1]]), 0x8664);
+
+        let err = AotCompiler::for_target("wasm32-unknown-unknown").err().unwrap();
+        assert!(err.starts_with("Unsupported target 'wasm32-unknown-unknown'"), "{}", err);
+        assert!(err.contains("supported targets: x86_64-unknown-linux-gnu, aarch64-unknown-linux-gnu"), "{}", err);
+    }
+
+    #[test]
+    fn test_compile_async_calls() {
+        let compile = |src: &str| {
+            let program = bolide_parser::parse_source(src).unwrap();
+            AotCompiler::new().unwrap().compile(&program).map(|_| ())
+        };
+        // 带参数的 async 调用经 trampoline 启动；任务名不在预先收集的字符串里时按需生成数据
+        compile(include_str!("../../../tests/test_async_loop.bl")).unwrap();
+        compile(include_str!("../../../tests/test_async_fanout.bl")).unwrap();
+        compile(include_str!("../../../tests/test_await_all.bl")).unwrap();
+        compile(include_str!("../../../tests/test_async_io.bl")).unwrap();
+        let err = compile(include_str!("../../../tests/test_task_errors.bl")).unwrap_err();
+        assert_eq!(err, JOIN_OK_UNSUPPORTED);
+    }
+
+    #[test]
+    fn test_compile_untyped_float_locals() {
+        let compile = |src: &str| {
+            let program = bolide_parser::parse_source(src).unwrap();
+            AotCompiler::new().unwrap().compile(&program).map(|_| ())
+        };
+        // 函数里没有标注类型的 float 变量按推断的类型声明
+        compile("fn f() -> float {\n    let a = 0.0;\n    a = a + 1.5;\n    return a;\n}\nprint(f());\n").unwrap();
+        // packed 列表报告诊断而不是在代码生成里崩溃
+        assert_eq!(compile(include_str!("../../../tests/test_packed_list.bl")).unwrap_err(), PACKED_LIST_UNSUPPORTED);
+        assert_eq!(compile(include_str!("../../../tests/bench_packed_list.bl")).unwrap_err(), PACKED_LIST_UNSUPPORTED);
+    }
+
+    #[test]
+    fn test_compile_library() {
+        let symbols = |src: &str| -> Result<Vec<u8>, String> {
+            let program = bolide_parser::parse_source(src).unwrap();
+            AotCompiler::new().unwrap().compile_library(&program).map(|r| r.object_code)
+        };
+        let object = symbols("fn add(a: int, b: int) -> int {\n    return a + b;\n}\n").unwrap();
+        let contains = |name: &[u8]| object.windows(name.len()).any(|w| w == name);
+        assert!(contains(b"add\0"));
+        // 没有入口和顶层代码
+        assert!(!contains(b"__main__"));
+        assert!(!contains(b"main\0"));
+
+        let err = symbols("fn f() -> int {\n    return 1;\n}\nprint(f());\n").unwrap_err();
+        assert_eq!(err, "a library has no entry point to run top-level code; move `print(f())` into a function");
+        let err = symbols("let limit: int = 3;\n").unwrap_err();
+        assert!(err.contains("move `let limit` into a function"), "{}", err);
+    }
+
+    #[test]
+    fn test_compile_module_and_main() {
+        let path = std::env::temp_dir().join(format!("bolide_aot_module_{}.bl", std::process::id()));
+        // 函数体要足够大，不会被段对齐的填充抵消
+        std::fs::write(&path, "fn twice(x: int) -> int {\n    let y: int = x * 2;\n    print(\"twice\");\n    return y;\n}\nprint(1);\n").unwrap();
+        let module_name = format!("bolide_aot_module_{}", std::process::id());
+        let symbol = format!("__bolide_{}_twice\0", module_name);
+        let contains = |object: &[u8], name: &str| object.windows(name.len()).any(|w| w == name.as_bytes());
+
+        // 模块只含自己的函数，顶层语句和完整编译时一样被忽略
+        let module = AotCompiler::new().unwrap().compile_module(&path.display().to_string()).unwrap().object_code;
+        assert!(contains(&module, &symbol));
+        assert!(!contains(&module, "__main__"));
+
+        let src = format!("import \"{}\";\nprint({}.twice(21));\n", path.display(), module_name);
+        let program = bolide_parser::parse_source(&src).unwrap();
+        let main = AotCompiler::new().unwrap().compile_main(&program).unwrap().object_code;
+        let full = AotCompiler::new().unwrap().compile(&program).unwrap().object_code;
+        // 两者都引用模块函数，只有完整编译包含它的函数体
+        assert!(contains(&main, &symbol) && contains(&full, &symbol));
+        assert!(main.len() < full.len());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,79 @@
+//! `bind(f, a, ...)`：偏函数应用（JIT 和 AOT 共用的检查和类型标签）
+//!
+//! 运行时（`bolide_bind`）把绑定参数和它们的类型标签存进绑定函数记录，retain 其中的引用计数值；
+//! 调用时按 i64 转发全部实参，所以 float 参数和返回值不能经过绑定函数。
+//! 结果的类型是目标函数去掉前面已绑定的参数后的签名。
+
+use bolide_parser::Type as BolideType;
+use bolide_runtime::{ElementType, ARG_TAG_FUNC, ARG_TAG_OBJECT};
+
+/// 按目标函数的签名检查绑定参数的个数和 float 参数/返回值（签名未知时不检查）
+pub(crate) fn check_target(target: &BolideType, bound: usize) -> Result<(), String> {
+    if let BolideType::FuncSig(params, ret) = target {
+        if bound > params.len() {
+            return Err(format!("bind: {} arguments bound but function takes {}", bound, params.len()));
+        }
+        if params.iter().any(|p| matches!(p, BolideType::Float))
+            || matches!(ret.as_deref(), Some(BolideType::Float))
+        {
+            return Err("bind does not support functions with float parameters or return values".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// 绑定了前 `bound` 个参数后的函数类型
+pub(crate) fn result_type(target: BolideType, bound: usize) -> BolideType {
+    match target {
+        BolideType::FuncSig(params, ret) if params.len() >= bound => {
+            BolideType::FuncSig(params[bound..].to_vec(), ret)
+        }
+        _ => BolideType::Func,
+    }
+}
+
+/// 绑定参数的运行时类型标签，决定绑定函数记录 retain / release 哪些参数
+pub(crate) fn arg_tag(ty: &BolideType) -> Result<u8, String> {
+    let tag = match ty {
+        BolideType::Float => return Err("bind does not support float arguments".to_string()),
+        BolideType::Func | BolideType::FuncSig(_, _) => return Ok(ARG_TAG_FUNC),
+        BolideType::Custom(_) => return Ok(ARG_TAG_OBJECT),
+        BolideType::Int => ElementType::Int,
+        BolideType::Bool => ElementType::Bool,
+        BolideType::Str => ElementType::String,
+        BolideType::BigInt => ElementType::BigInt,
+        BolideType::Decimal => ElementType::Decimal,
+        BolideType::List(_) => ElementType::List,
+        BolideType::Dict(_, _) => ElementType::Dict,
+        BolideType::Dynamic => ElementType::Dynamic,
+        _ => ElementType::Ptr,
+    };
+    Ok(tag as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arg_tags_match_runtime() {
+        assert_eq!(arg_tag(&BolideType::Int), Ok(0));
+        assert_eq!(arg_tag(&BolideType::Str), Ok(3));
+        assert_eq!(arg_tag(&BolideType::Dict(Box::new(BolideType::Str), Box::new(BolideType::Int))), Ok(8));
+        assert_eq!(arg_tag(&BolideType::Func), Ok(ARG_TAG_FUNC));
+        assert_eq!(arg_tag(&BolideType::Custom("Point".to_string())), Ok(ARG_TAG_OBJECT));
+        assert_eq!(arg_tag(&BolideType::Ptr), Ok(7));
+        assert!(arg_tag(&BolideType::Float).is_err());
+    }
+
+    #[test]
+    fn test_result_type_drops_bound_params() {
+        let target = BolideType::FuncSig(vec![BolideType::Int, BolideType::Str], Some(Box::new(BolideType::Str)));
+        assert!(check_target(&target, 2).is_ok());
+        assert!(check_target(&target, 3).is_err());
+        assert_eq!(result_type(target, 1), BolideType::FuncSig(vec![BolideType::Str], Some(Box::new(BolideType::Str))));
+        assert_eq!(result_type(BolideType::Func, 1), BolideType::Func);
+        let float_target = BolideType::FuncSig(vec![BolideType::Float], None);
+        assert!(check_target(&float_target, 1).is_err());
+    }
+}
@@ -0,0 +1,338 @@
+//! 内置函数与方法的用户文档表
+//!
+//! `bolide builtins` 从这里生成列表。编译器 `compile_call` / `compile_method_call`
+//! 中分发的每个名字都必须在表中有条目（见底部测试），新增内置时记得同步。
+//! 签名使用 Bolide 语法，而不是运行时的 C ABI。
+
+/// 内置项的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinKind {
+    /// 普通函数调用：`name(args)`
+    Function,
+    /// 方法调用：`value.name(args)`
+    Method,
+    /// 语法形式（不是函数，但由编译器特殊处理）
+    SpecialForm,
+}
+
+impl BuiltinKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BuiltinKind::Function => "function",
+            BuiltinKind::Method => "method",
+            BuiltinKind::SpecialForm => "special_form",
+        }
+    }
+}
+
+/// 一个内置项
+#[derive(Debug, Clone, Copy)]
+pub struct BuiltinDoc {
+    /// 名字（方法不含接收者）
+    pub name: &'static str,
+    /// 同义名
+    pub aliases: &'static [&'static str],
+    /// 方法的接收者类型（list / packed_list / dict / weak_dict / decimal / channel / future），函数为空
+    pub receiver: &'static str,
+    pub kind: BuiltinKind,
+    /// 分类：io / string / list / dict / math / concurrency / conversion / function / time / testing / system / debug
+    pub category: &'static str,
+    /// Bolide 语法的签名
+    pub signature: &'static str,
+    /// 一句话说明
+    pub summary: &'static str,
+}
+
+/// 分类的显示顺序和标题
+pub const BUILTIN_CATEGORIES: &[(&str, &str)] = &[
+    ("io", "I/O"),
+    ("string", "String"),
+    ("list", "List"),
+    ("dict", "Dict"),
+    ("math", "Math"),
+    ("concurrency", "Concurrency"),
+    ("conversion", "Conversion"),
+    ("function", "Functions"),
+    ("time", "Timing"),
+    ("testing", "Testing"),
+    ("system", "System"),
+    ("debug", "Debug"),
+];
+
+const fn function(name: &'static str, category: &'static str, signature: &'static str, summary: &'static str) -> BuiltinDoc {
+    BuiltinDoc { name, aliases: &[], receiver: "", kind: BuiltinKind::Function, category, signature, summary }
+}
+
+const fn method(receiver: &'static str, name: &'static str, aliases: &'static [&'static str], signature: &'static str, summary: &'static str) -> BuiltinDoc {
+    let category = match receiver.as_bytes() {
+        b"list" | b"packed_list" => "list",
+        b"dict" | b"weak_dict" => "dict",
+        b"decimal" => "math",
+        _ => "concurrency",
+    };
+    BuiltinDoc { name, aliases, receiver, kind: BuiltinKind::Method, category, signature, summary }
+}
+
+const fn special(name: &'static str, category: &'static str, signature: &'static str, summary: &'static str) -> BuiltinDoc {
+    BuiltinDoc { name, aliases: &[], receiver: "", kind: BuiltinKind::SpecialForm, category, signature, summary }
+}
+
+/// 全部内置项
+pub const BUILTINS: &[BuiltinDoc] = &[
+    // I/O
+    function("print", "io", "print(value: T)", "Print any value followed by a newline"),
+    function("printr", "io", "printr(value: T)", "Print any value without a trailing newline"),
+    function("flush", "io", "flush()", "Write buffered output to stdout now (output to a file or pipe is buffered; a terminal gets each line)"),
+    function("input", "io", "input(prompt: str = \"\") -> str", "Read a line from stdin, optionally showing a prompt (inside an async fn the coroutine suspends while waiting)"),
+    function("read_file", "io", "read_file(path: str) -> str", "Read a whole text file; \"\" and error code 7 on failure (inside an async fn the coroutine suspends while reading)"),
+    function("read_lines", "io", "read_lines(path: str) -> list<str>", "Read a text file as a list of lines without their line endings; [] and error code 7 on failure"),
+    function("write_file", "io", "write_file(path: str, contents: str) -> bool", "Create or truncate a file and write the string; false and error code 7 on failure"),
+    function("append_file", "io", "append_file(path: str, contents: str) -> bool", "Append the string to a file, creating it if missing; false and error code 7 on failure"),
+    function("file_exists", "io", "file_exists(path: str) -> bool", "Whether a file or directory exists at the path"),
+    function("remove_file", "io", "remove_file(path: str) -> bool", "Delete a file; false and error code 7 on failure"),
+    function("temp_dir", "io", "temp_dir() -> str", "The system temporary directory, without a trailing separator"),
+    function("write_lines", "io", "write_lines(path: str, lines: list<str>)", "Write each string followed by a newline to a file through one buffer; error code 7 on failure"),
+    function("format_row", "io", "format_row(fields: list<T>, sep: str = \",\") -> str", "Join int/float/bool/str/bigint/decimal/dynamic fields into one CSV row in a single allocation, quoting fields per RFC 4180"),
+    function("set_io_workers", "io", "set_io_workers(n: int)", "Maximum number of threads serving input() / read_file() calls made inside async functions (default 2)"),
+
+    // String
+    special("+", "string", "a: str + b: str -> str", "Concatenate two strings"),
+    special("<", "string", "a: str < b: str -> bool", "Compare strings lexicographically by bytes (also <=, >, >=)"),
+    special("[]", "string", "s: str[i: int] -> int", "Code point of the i-th character (negative counts from the end, -1 when out of range)"),
+    special("[:]", "string", "s: str[a: int : b: int] -> str", "Characters a..b as a new string (bounds optional, negative counts from the end, clamped)"),
+    method("string", "split", &[], "s.split(sep: str) -> list<str>", "Split on sep like Python (\"\" gives [\"\"], trailing separators give empty strings); split() splits on whitespace"),
+    method("string", "trim", &["strip"], "s.trim() -> str", "Copy without leading and trailing whitespace"),
+    method("string", "to_upper", &["upper"], "s.to_upper() -> str", "Uppercase copy"),
+    method("string", "to_lower", &["lower"], "s.to_lower() -> str", "Lowercase copy"),
+    method("string", "contains", &[], "s.contains(needle: str) -> bool", "Whether needle occurs in s"),
+    method("string", "replace", &[], "s.replace(from: str, to: str) -> str", "Copy with every occurrence of from replaced by to"),
+    method("string", "encode", &[], "s.encode(encoding: str) -> list<int>", "Bytes of s in utf-8, utf-16le or latin-1 (characters latin-1 cannot represent are a runtime error)"),
+    function("len", "string", "len(x: str | list<T> | list<packed T> | dict<K, V> | tuple) -> int", "Number of characters (not bytes) in a string, or elements in a list, dict or tuple"),
+    function("intern", "string", "intern(s: str) -> str", "Canonical shared instance of s (kept until intern_clear()); interned strings compare and look up dict keys by address"),
+    function("intern_clear", "string", "intern_clear() -> int", "Free interned strings (literals included) that nothing but the intern table references; returns how many"),
+
+    // Conversion
+    function("int", "conversion", "int(value: float | str | bigint | decimal) -> int", "Convert to int (floats are truncated; str arguments are deprecated, use parse_int)"),
+    function("float", "conversion", "float(value: int | str | bigint | decimal) -> float", "Convert to float (str arguments are deprecated, use parse_float)"),
+    function("parse_int", "conversion", "parse_int(s: str, radix: int = 10) -> int", "Parse an integer in base 2-36 (surrounding whitespace and one sign allowed); on bad or out-of-range input sets error() and returns 0"),
+    function("parse_float", "conversion", "parse_float(s: str) -> float", "Parse a float (surrounding whitespace allowed); on bad or out-of-range input sets error() and returns 0.0"),
+    function("str", "conversion", "str(value: T) -> str", "Convert any value to its string form; class instances use `__str__` or list their fields"),
+    function("bigint", "conversion", "bigint(value: int | float | str | decimal) -> bigint", "Convert to arbitrary-precision integer"),
+    function("decimal", "conversion", "decimal(value: int | float | str | bigint) -> decimal", "Convert to exact decimal"),
+
+    // Math
+    function("set_rounding", "math", "set_rounding(mode: str | int) -> int", "Set this thread's decimal rounding mode, returning the previous one"),
+    function("set_div_precision", "math", "set_div_precision(digits: int) -> int", "Limit decimal division on this thread to `digits` decimals (negative restores the default of up to 28), returning the previous setting"),
+    function("round", "math", "round(x: int | float | decimal, digits: int = 0) -> T", "Round to `digits` decimals with this thread's rounding mode (floats are rounded by their decimal value)"),
+    function("floor", "math", "floor(x: int | float | decimal) -> T", "Largest integral value not greater than x"),
+    function("ceil", "math", "ceil(x: int | float | decimal) -> T", "Smallest integral value not less than x"),
+    function("hash", "math", "hash(value: int | float | bool | str | bigint | decimal | dynamic | tuple) -> int", "Hash a value (stable within a process, not across versions)"),
+    function("saturating_add", "math", "saturating_add(a: int, b: int) -> int", "a + b, clamped to the int range on overflow"),
+    function("saturating_sub", "math", "saturating_sub(a: int, b: int) -> int", "a - b, clamped to the int range on overflow"),
+    function("saturating_mul", "math", "saturating_mul(a: int, b: int) -> int", "a * b, clamped to the int range on overflow"),
+    function("wrapping_add", "math", "wrapping_add(a: int, b: int) -> int", "a + b with two's-complement wraparound, even under --overflow-checks"),
+    function("wrapping_sub", "math", "wrapping_sub(a: int, b: int) -> int", "a - b with two's-complement wraparound, even under --overflow-checks"),
+    function("wrapping_mul", "math", "wrapping_mul(a: int, b: int) -> int", "a * b with two's-complement wraparound, even under --overflow-checks"),
+    function("checked_add", "math", "checked_add(a: int, b: int) -> (bool, int)", "(ok, a + b); ok is false on overflow and the value wraps. `let ok, v = checked_add(a, b);` allocates no tuple"),
+    function("checked_sub", "math", "checked_sub(a: int, b: int) -> (bool, int)", "(ok, a - b); ok is false on overflow and the value wraps"),
+    function("checked_mul", "math", "checked_mul(a: int, b: int) -> (bool, int)", "(ok, a * b); ok is false on overflow and the value wraps"),
+    method("decimal", "round", &[], "d.round(places: int = 0, mode: str = <thread mode>) -> decimal", "Round to `places` decimals (half_up, half_even, down, up, floor, ceiling)"),
+
+    // List
+    function("sum", "list", "sum(xs: list<int | float | bigint | decimal>) -> T", "Sum of the elements (0 for an empty list; int sums wrap unless --overflow-checks)"),
+    function("min", "list", "min(xs: list<int | float | bigint | decimal>) -> T", "Smallest element (runtime error on an empty list)"),
+    function("max", "list", "max(xs: list<int | float | bigint | decimal>) -> T", "Largest element (runtime error on an empty list)"),
+    function("avg", "list", "avg(xs: list<int | float | bigint | decimal>) -> float | decimal", "Mean of the elements; decimal lists give a decimal (runtime error on an empty list)"),
+    method("list", "push", &["append"], "xs.push(value: T)", "Append an element"),
+    method("list", "pop", &[], "xs.pop() -> T", "Remove and return the last element"),
+    method("list", "len", &["length", "size"], "xs.len() -> int", "Number of elements"),
+    method("list", "get", &[], "xs.get(index: int) -> T", "Element at index"),
+    method("list", "set", &[], "xs.set(index: int, value: T) -> bool", "Replace the element at index"),
+    method("list", "insert", &[], "xs.insert(index: int, value: T)", "Insert before index"),
+    method("list", "remove", &[], "xs.remove(index: int) -> T", "Remove and return the element at index"),
+    method("list", "clear", &[], "xs.clear()", "Remove all elements"),
+    method("list", "reverse", &[], "xs.reverse()", "Reverse in place"),
+    method("list", "extend", &[], "xs.extend(other: list<T>)", "Append all elements of another list"),
+    method("list", "contains", &["includes"], "xs.contains(value: T) -> bool", "Whether the list contains value"),
+    method("list", "index_of", &["index", "find"], "xs.index_of(value: T) -> int", "Index of value, or -1"),
+    method("list", "count", &[], "xs.count(value: T) -> int", "Number of occurrences of value"),
+    method("list", "sort", &[], "xs.sort()", "Sort an int, bool, float or str list in place (ascending, stable; NaN last, strings by content)"),
+    method("list", "sort_by", &[], "xs.sort_by(cmp: func(T, T) -> int)", "Stable in-place sort with a comparator: negative keeps a first, positive puts b first"),
+    method("list", "sort_by_key", &[], "xs.sort_by_key(key: func(T) -> K)", "Stable in-place sort by an int, float or str key; the key function runs once per element"),
+    method("list", "min_by", &[], "xs.min_by(key: func(T) -> K) -> T", "Element with the smallest key (first one on ties); runtime error on an empty list"),
+    method("list", "max_by", &[], "xs.max_by(key: func(T) -> K) -> T", "Element with the largest key (first one on ties); runtime error on an empty list"),
+    method("list", "slice", &[], "xs.slice(start: int, end: int) -> list<T>", "Copy of elements in [start, end)"),
+    special("[:]", "list", "xs: list<T>[a: int : b: int] -> list<T>", "Same as slice, with optional and negative bounds"),
+    method("list", "dedup", &[], "xs.dedup()", "Remove adjacent equal elements in place"),
+    method("list", "unique", &[], "xs.unique() -> list<T>", "First occurrence of each distinct value, in order"),
+    method("list", "group_by", &[], "xs.group_by(key: func(T) -> K) -> dict<K, list<T>>", "Group elements by an int or str key (K is dynamic when the key function has no declared signature)"),
+    method("list", "is_empty", &["empty"], "xs.is_empty() -> bool", "Whether the list has no elements"),
+    method("list", "first", &[], "xs.first() -> T", "First element"),
+    method("list", "last", &[], "xs.last() -> T", "Last element"),
+    method("list", "copy", &["clone"], "xs.copy() -> list<T>", "Shallow copy"),
+    method("list", "decode", &[], "b.decode(encoding: str, mode: str) -> str", "Decode a list<int> of bytes (utf-8, utf-16le, latin-1); mode \"strict\" (default) fails at the byte offset of the first invalid sequence, \"lossy\" substitutes U+FFFD"),
+    method("list", "decode_latin1", &[], "b.decode_latin1() -> str", "Decode a list<int> of bytes as latin-1 (every byte maps to one character)"),
+    function("packed_list", "list", "packed_list(T, n: int) -> list<packed T>", "List of n zeroed instances of class T stored inline (T may only hold int, float, bool and packable class fields)"),
+    method("packed_list", "len", &[], "ps.len() -> int", "Number of elements"),
+    method("packed_list", "push", &[], "ps.push(value: T)", "Copy the fields of value into a new element at the end"),
+    method("packed_list", "resize", &[], "ps.resize(n: int)", "Grow with zeroed elements or truncate to n elements"),
+    method("packed_list", "swap", &[], "ps.swap(i: int, j: int)", "Swap the contents of two elements"),
+
+    // Dict
+    function("default_dict", "dict", "default_dict() -> dict<K, list<T> | dict<K2, V2>>", "Dict whose d[k] inserts an empty container for a missing key; K and the value type come from the declaration"),
+    special("[]", "dict", "d: dict<K, V>[key: K] -> V", "Value for key; list and dict values are the stored container (d[k].push(x) mutates it), and a missing key is an error unless the dict came from default_dict()"),
+    method("dict", "set", &[], "d.set(key: K, value: V)", "Insert or replace an entry"),
+    method("dict", "get", &[], "d.get(key: K) -> V", "Value for key"),
+    method("dict", "contains", &[], "d.contains(key: K) -> bool", "Whether key is present"),
+    method("dict", "remove", &[], "d.remove(key: K) -> V", "Remove an entry and return its value"),
+    method("dict", "len", &[], "d.len() -> int", "Number of entries"),
+    method("dict", "is_empty", &[], "d.is_empty() -> bool", "Whether the dict has no entries"),
+    method("dict", "clear", &[], "d.clear()", "Remove all entries"),
+    method("dict", "keys", &[], "d.keys() -> list<K>", "All keys"),
+    method("dict", "values", &[], "d.values() -> list<V>", "All values"),
+    method("dict", "clone", &[], "d.clone() -> dict<K, V>", "Shallow copy"),
+    function("weak_dict", "dict", "weak_dict() -> weak_dict<K, V>", "Dict keyed by class instances that doesn't keep its keys alive; an entry vanishes once its key is released. K and V come from the declaration"),
+    method("weak_dict", "set", &[], "w.set(key: K, value: V)", "Insert or replace an entry; only a weak handle to key is stored"),
+    method("weak_dict", "get", &[], "w.get(key: K) -> V", "Value for a live key"),
+    method("weak_dict", "contains", &[], "w.contains(key: K) -> bool", "Whether key has a live entry"),
+    method("weak_dict", "remove", &[], "w.remove(key: K) -> bool", "Remove an entry; returns whether it was present"),
+    method("weak_dict", "len", &[], "w.len() -> int", "Number of live entries (drops entries whose key was released)"),
+    method("weak_dict", "is_empty", &[], "w.is_empty() -> bool", "Whether there are no live entries"),
+    method("weak_dict", "compact", &[], "w.compact() -> int", "Drop entries whose key was released; returns how many"),
+
+    // Concurrency
+    special("spawn", "concurrency", "spawn f(args) -> future", "Run a function on a new thread"),
+    function("join", "concurrency", "join(handle: future) -> T", "Wait for a spawned thread and return its result; an error the task left behind ends the program"),
+    function("join_ok", "concurrency", "join_ok(handle: future) -> (bool, T)", "Wait for a spawned thread; on failure returns false and records the task's error for error() / error_message()"),
+    function("cancelled", "concurrency", "cancelled() -> bool", "Whether the current async task was cancelled, e.g. by a failing sibling in await scope fail_fast"),
+    special("threadlocal", "concurrency", "threadlocal let x: T = init;", "Top-level global with one copy per thread, initialized on first access"),
+    function("channel", "concurrency", "channel() -> channel<T>", "Create a channel (element type comes from the declaration)"),
+    special("send", "concurrency", "ch <- value", "Send a value into a channel"),
+    special("recv", "concurrency", "<- ch -> T", "Receive a value from a channel"),
+    special("select", "concurrency", "select { x <- ch => { ... } timeout(d) => { ... } default => { ... } }", "Wait on several channels"),
+    special("select from", "concurrency", "select from chans { v, i => { ... } closed => { ... } timeout(d) => { ... } }", "Wait on a list of channels; i is the index of the channel that delivered v"),
+    special("pool", "concurrency", "pool(n) { ... }", "Run spawns inside the block (and inside its tasks) on a thread pool of size n"),
+    function("thread_id", "concurrency", "thread_id() -> int", "Small sequential id of the current OS thread"),
+    special("await", "concurrency", "await f(args) -> T", "Wait for an async function call"),
+    special("await all", "concurrency", "await all { f(a), g(b) } -> (T, U)", "Wait for several async calls concurrently"),
+    special("await scope", "concurrency", "await scope { ... } / await scope fail_fast { ... }", "Wait for the async calls started in the block and report their errors; fail_fast cancels the others on the first error"),
+    function("set_coroutine_workers", "concurrency", "set_coroutine_workers(n: int)", "Number of worker threads running async calls (default: CPU count)"),
+    method("channel", "send_all", &[], "ch.send_all(xs: list<T>) -> int", "Send every element under one lock, blocking while the buffer is full; returns how many were sent (fewer means the channel closed)"),
+    method("channel", "recv_n", &[], "ch.recv_n(n: int) -> list<T>", "Wait for at least one message, then take up to n at once; empty means closed"),
+    method("channel", "drain", &[], "ch.drain() -> list<T>", "Receive until the channel is closed and empty"),
+    method("channel", "close", &[], "ch.close()", "Close the channel; queued messages can still be received"),
+    method("future", "cancel", &["close"], "handle.cancel()", "Request cancellation of a spawned task"),
+    method("future", "is_cancelled", &[], "handle.is_cancelled() -> bool", "Whether cancellation was requested"),
+
+    // Functions
+    function("bind", "function", "bind(f: func(A, B...) -> R, a: A, ...) -> func(B...) -> R", "Partially apply leading arguments"),
+    function("map", "function", "map(f: func(T) -> U, xs: list<T>) -> list<U>", "New list of f applied to each element (f needs a declared signature; T cannot be float)"),
+    function("filter", "function", "filter(f: func(T) -> bool, xs: list<T>) -> list<T>", "New list of the elements for which f returns true, in order"),
+    function("reduce", "function", "reduce(f: func(R, T) -> R, xs: list<T>, init: R) -> R", "Fold the elements left to right starting from init (init for an empty list; R cannot be float)"),
+    special("range", "function", "for i in range(end) / range(start, end) / range(start, end, step)", "Integer range, or a float range when any argument is float; only valid as a for-loop source"),
+    function("loop_count", "function", "loop_count() -> int", "Iterations the innermost enclosing while/for loop has completed (0 during the first)"),
+
+    // Timing
+    special("measure", "time", "measure \"label\" { ... }", "Print the block's elapsed time and allocation count when it ends"),
+    function("timer_start", "time", "timer_start() -> int", "Read the monotonic clock (nanoseconds)"),
+    function("timer_elapsed_ns", "time", "timer_elapsed_ns(start: int) -> int", "Nanoseconds since timer_start()"),
+    function("timer_elapsed_ms", "time", "timer_elapsed_ms(start: int) -> float", "Milliseconds since timer_start()"),
+    function("deadline", "time", "deadline(d: duration) -> deadline", "Monotonic point in time d from now; accepted wherever a timeout is"),
+    function("remaining", "time", "remaining(dl: deadline) -> duration", "Time left until dl, 0s once it has passed"),
+
+    // Testing
+    special("test", "testing", "test \"name\" { ... }", "Top-level test block: skipped by bolide run, compiled and run on its own by bolide test"),
+    function("assert", "testing", "assert(cond: bool, message: str = \"\")", "Record a failure when cond is false; execution continues (bolide test reports it)"),
+    function("assert_eq", "testing", "assert_eq(a: T, b: T)", "Record a failure when int/bool/str/list values differ, or floats differ by more than a relative 1e-9"),
+
+    // System
+    function("platform", "system", "platform() -> str", "Operating system the program is running on: \"windows\", \"linux\", \"macos\", ..."),
+    function("version", "system", "version() -> str", "Semantic version of the running bolide runtime, e.g. \"0.8.2\""),
+    function("version_at_least", "system", "version_at_least(v: str) -> bool", "Whether the runtime version is at least v by semver precedence (pre-releases sort before the release); an invalid version sets error() and returns false"),
+    function("has_feature", "system", "has_feature(name: str) -> bool", "Whether this bolide build supports the language feature name, e.g. \"typed-channels\"; unknown names return false"),
+    function("error", "system", "error() -> int", "Code of this thread's last recoverable runtime error (0 none, 1 invalid value, 2 index, 3 division by zero, 4 overflow, 5 internal); not reset by successful calls"),
+    function("error_message", "system", "error_message() -> str", "Message of this thread's last recoverable runtime error (\"\" when there is none)"),
+    function("clear_error", "system", "clear_error()", "Reset error() to 0"),
+    function("exit", "system", "exit(code: int)", "Flush stdout and end the process immediately with the given status (Unix keeps the low 8 bits, a nonzero code never becomes 0)"),
+
+    // Debug
+    function("bigint_debug_stats", "debug", "bigint_debug_stats()", "Print bigint allocation counters"),
+    function("tuple_debug_stats", "debug", "tuple_debug_stats()", "Print tuple allocation counters"),
+    function("intern_stats", "debug", "intern_stats() -> int", "Number of entries in the string intern table"),
+    function("string_debug_stats", "debug", "string_debug_stats()", "Print string allocation counters"),
+    function("weak_debug_stats", "debug", "weak_debug_stats()", "Print weak handle allocation counters"),
+    function("object_debug_enable", "debug", "object_debug_enable(on: bool) -> bool", "Start (or stop and forget) tracking class instances allocated by this thread; returns the previous setting"),
+    function("object_live_count", "debug", "object_live_count() -> int", "Number of tracked class instances that have not been freed"),
+    function("object_dump_live", "debug", "object_dump_live()", "Print the address, size and refcount of every tracked class instance still alive"),
+];
+
+/// 按名字（含同义名）和接收者查找
+#[cfg(test)]
+fn find_builtin(receiver: &str, name: &str) -> Option<&'static BuiltinDoc> {
+    BUILTINS.iter().find(|b| b.receiver == receiver && (b.name == name || b.aliases.contains(&name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 从 jit.rs 的某个函数中取出 match 分支上的字符串字面量
+    fn dispatched_names(fn_name: &str) -> Vec<String> {
+        let src = include_str!("jit.rs");
+        let start = src.find(&format!("fn {}(", fn_name))
+            .unwrap_or_else(|| panic!("{} not found in jit.rs", fn_name));
+        let end = start + src[start..].find("\n    }\n").unwrap();
+        let mut names = Vec::new();
+        for line in src[start..end].lines() {
+            let line = line.trim_start();
+            if !line.starts_with('"') || !line.contains("=>") {
+                continue;
+            }
+            let arm = line.split("=>").next().unwrap();
+            let pattern = arm.split(" if ").next().unwrap();
+            for part in pattern.split('|') {
+                let part = part.trim();
+                if let Some(name) = part.strip_prefix('"').and_then(|p| p.strip_suffix('"')) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        assert!(!names.is_empty(), "no dispatched names found in {}", fn_name);
+        names
+    }
+
+    #[test]
+    fn test_every_call_builtin_documented() {
+        for name in dispatched_names("compile_call") {
+            assert!(find_builtin("", &name).is_some(), "builtin `{}` is missing from BUILTINS", name);
+        }
+    }
+
+    #[test]
+    fn test_every_method_documented() {
+        let receivers = [
+            ("compile_list_method_call", "list"),
+            ("compile_dict_method_call", "dict"),
+            ("compile_weak_dict_method_call", "weak_dict"),
+            ("compile_string_method_call", "string"),
+            ("compile_decimal_method_call", "decimal"),
+            ("compile_channel_method_call", "channel"),
+            ("compile_packed_list_method_call", "packed_list"),
+            ("compile_method_call", "future"),
+        ];
+        for (fn_name, receiver) in receivers {
+            for name in dispatched_names(fn_name) {
+                assert!(find_builtin(receiver, &name).is_some(),
+                    "{} method `{}` is missing from BUILTINS", receiver, name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_categories_known() {
+        for b in BUILTINS {
+            assert!(BUILTIN_CATEGORIES.iter().any(|(c, _)| *c == b.category),
+                "unknown category `{}` for `{}`", b.category, b.name);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! `bolide compile --emit obj|lib` 附带的 C 头文件
+//!
+//! 每个顶层函数（`main` 除外）一条原型，类型按 AOT 生成的调用约定映射：int / bool / duration / deadline -> `int64_t`
+//! （bool 为 0 或 1，duration 和 deadline 为纳秒），float -> `double`，其余类型（str、list、dict、对象等）都是运行时
+//! 对象指针 `void *`。AOT 编译的函数拥有传入的对象、返回时释放，返回的对象归调用者所有。
+//! AOT 里 ref 参数和普通参数一样按值传递。async 函数返回协程句柄，不出现在头文件里。
+
+use bolide_parser::{FuncDef, Program, Statement, Type as BolideType};
+
+/// 与 C 关键字同名的参数加上 `_` 后缀
+const C_KEYWORDS: &[&str] = &[
+    "auto", "case", "char", "const", "default", "do", "double", "enum", "extern", "float",
+    "goto", "inline", "int", "long", "register", "restrict", "short", "signed", "sizeof",
+    "static", "switch", "typedef", "union", "unsigned", "void", "volatile",
+];
+
+/// 为程序的顶层函数生成 C 头文件，`name` 用于注释和 include guard
+pub fn c_header(program: &Program, name: &str) -> String {
+    let funcs: Vec<&FuncDef> = program.statements.iter()
+        .filter_map(|stmt| match stmt {
+            // 用户的 main 函数不以 `main` 导出（见 `AotCompiler::symbol_name`）
+            Statement::FuncDef(func) if !func.is_async && func.name != "main" => Some(func),
+            _ => None,
+        })
+        .collect();
+    let guard: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    let guard = format!("BOLIDE_{}_H", guard);
+
+    let mut out = String::new();
+    out.push_str(&format!("/* {}.h: generated by `bolide compile`, do not edit */\n", name));
+    out.push_str(&format!("#ifndef {}\n#define {}\n\n#include <stdint.h>\n#include <stddef.h>\n\n", guard, guard));
+    out.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+
+    // 用到字符串时声明构造和释放字符串的运行时函数
+    let uses_str = funcs.iter().any(|f| {
+        f.params.iter().any(|p| p.ty == BolideType::Str) || f.return_type == Some(BolideType::Str)
+    });
+    if uses_str {
+        out.push_str("/* str values: create from a NUL-terminated UTF-8 string, release when done */\n");
+        out.push_str("void *bolide_string_new(const char *s);\n");
+        out.push_str("size_t bolide_string_len(const void *s);\n");
+        out.push_str("void *bolide_string_retain(void *s);\n");
+        out.push_str("void bolide_string_release(void *s);\n\n");
+    }
+    // Bolide 的输出不是终端时有缓冲，和 C 的 stdio 交替写标准输出前要先刷新
+    out.push_str("/* Bolide output is buffered unless stdout is a terminal: flush it before writing to stdout from C */\n");
+    out.push_str("void bolide_print_flush(void);\n\n");
+    out.push_str("/* Object arguments (void *) are released by the callee: retain a value you keep using.\n");
+    out.push_str("   Returned objects belong to the caller. */\n");
+
+    for func in &funcs {
+        let params: Vec<String> = func.params.iter()
+            .map(|p| {
+                let name = if C_KEYWORDS.contains(&p.name.as_str()) { format!("{}_", p.name) } else { p.name.clone() };
+                declarator(c_type(&p.ty), &name)
+            })
+            .collect();
+        let params = if params.is_empty() { "void".to_string() } else { params.join(", ") };
+        let ret = func.return_type.as_ref().map_or("void", c_type);
+        out.push_str(&format!("{};\n", declarator(ret, &format!("{}({})", func.name, params))));
+    }
+
+    out.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+    out.push_str(&format!("#endif /* {} */\n", guard));
+    out
+}
+
+fn c_type(ty: &BolideType) -> &'static str {
+    match ty {
+        BolideType::Int | BolideType::Bool | BolideType::Duration | BolideType::Deadline => "int64_t",
+        BolideType::Float => "double",
+        _ => "void *",
+    }
+}
+
+/// 类型后接名字：指针类型的 `*` 紧贴名字
+fn declarator(ty: &str, name: &str) -> String {
+    if ty.ends_with('*') {
+        format!("{}{}", ty, name)
+    } else {
+        format!("{} {}", ty, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prototypes() {
+        let src = "fn add(a: int, b: int) -> int {\n    return a + b;\n}\nfn scale(x: float, on: bool) -> float {\n    return x;\n}\nfn greet(name: str) -> str {\n    return name;\n}\nfn bump(ref n: int, ref s: str) {\n    n = n + 1;\n}\nfn first(xs: list<int>, char: int) -> int {\n    return xs[char];\n}\nfn tick() {\n}\nfn main() -> int {\n    return 0;\n}\nasync fn later() -> int {\n    return 1;\n}\n";
+        let program = bolide_parser::parse_source(src).unwrap();
+        let header = c_header(&program, "my-lib");
+        assert!(header.starts_with("/* my-lib.h: generated by `bolide compile`, do not edit */\n#ifndef BOLIDE_MY_LIB_H\n"), "{}", header);
+        let prototypes: Vec<&str> = header.lines()
+            .filter(|line| line.ends_with(");") && !line.contains(" bolide_") && !line.contains("*bolide_"))
+            .collect();
+        assert_eq!(prototypes, [
+            "int64_t add(int64_t a, int64_t b);",
+            "double scale(double x, int64_t on);",
+            "void *greet(void *name);",
+            "void bump(int64_t n, void *s);",
+            "int64_t first(void *xs, int64_t char_);",
+            "void tick(void);",
+        ]);
+        assert!(header.contains("void *bolide_string_new(const char *s);"));
+        assert!(header.contains("void bolide_print_flush(void);"));
+        assert!(header.trim_end().ends_with("#endif /* BOLIDE_MY_LIB_H */"));
+    }
+}
@@ -0,0 +1,95 @@
+//! 关键字实参和参数默认值
+//!
+//! 调用处的实参先按参数顺序排好，再照常编译：`name: value` 放到同名参数的位置，
+//! 省略的参数用默认值表达式补上。默认值在调用处求值，和写在实参里一样，
+//! 产生的临时值由调用者在语句结束时释放。
+
+use bolide_parser::{Expr, Param};
+
+/// 把 `args` 整理成 `params` 顺序的位置实参
+///
+/// 只有位置实参、也没有省略参数时返回 `None`，调用方直接用原来的实参。
+/// 关键字实参之后又出现位置实参、参数名不存在或重复、省略了没有默认值的参数时报错。
+pub(crate) fn positional_args(func_name: &str, params: &[Param], args: &[Expr]) -> Result<Option<Vec<Expr>>, String> {
+    let has_named = args.iter().any(|arg| matches!(arg, Expr::NamedArg(..)));
+    if !has_named && args.len() >= params.len() {
+        return Ok(None);
+    }
+
+    let mut slots: Vec<Option<Expr>> = vec![None; params.len()];
+    let mut keyword_seen = false;
+    for (i, arg) in args.iter().enumerate() {
+        match arg {
+            Expr::NamedArg(name, value) => {
+                keyword_seen = true;
+                let index = params.iter().position(|p| p.name == *name)
+                    .ok_or_else(|| format!("'{}' has no parameter named '{}'", func_name, name))?;
+                if slots[index].is_some() {
+                    return Err(format!("argument '{}' of '{}' is given more than once", name, func_name));
+                }
+                slots[index] = Some(value.as_ref().clone());
+            }
+            _ if keyword_seen => {
+                return Err(format!("positional argument after keyword arguments in call to '{}'", func_name));
+            }
+            _ if i >= params.len() => {
+                return Err(format!("'{}' takes {} argument(s) but {} were given", func_name, params.len(), args.len()));
+            }
+            _ => slots[i] = Some(arg.clone()),
+        }
+    }
+
+    slots.into_iter().zip(params)
+        .map(|(slot, param)| {
+            slot.or_else(|| param.default.clone())
+                .ok_or_else(|| format!("missing argument '{}' in call to '{}'", param.name, func_name))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bolide_parser::{ParamMode, Type};
+
+    fn param(name: &str, default: Option<Expr>) -> Param {
+        Param { name: name.to_string(), ty: Type::Int, mode: ParamMode::Borrow, default }
+    }
+
+    fn named(name: &str, value: i64) -> Expr {
+        Expr::NamedArg(name.to_string(), Box::new(Expr::Int(value)))
+    }
+
+    fn ints(args: &[Expr]) -> Vec<i64> {
+        args.iter().map(|arg| match arg {
+            Expr::Int(n) => *n,
+            other => panic!("unexpected argument {:?}", other),
+        }).collect()
+    }
+
+    #[test]
+    fn test_keywords_reorder_and_defaults_fill_in() {
+        let params = [param("a", None), param("b", Some(Expr::Int(20))), param("c", Some(Expr::Int(30)))];
+
+        assert!(positional_args("f", &params, &[Expr::Int(1), Expr::Int(2), Expr::Int(3)]).unwrap().is_none());
+        let args = positional_args("f", &params, &[Expr::Int(1)]).unwrap().unwrap();
+        assert_eq!(ints(&args), [1, 20, 30]);
+        let args = positional_args("f", &params, &[named("c", 3), named("a", 1)]).unwrap().unwrap();
+        assert_eq!(ints(&args), [1, 20, 3]);
+        let args = positional_args("f", &params, &[Expr::Int(1), named("c", 3)]).unwrap().unwrap();
+        assert_eq!(ints(&args), [1, 20, 3]);
+    }
+
+    #[test]
+    fn test_invalid_keyword_calls() {
+        let params = [param("a", None), param("b", Some(Expr::Int(20)))];
+        let err = |args: &[Expr]| positional_args("f", &params, args).unwrap_err();
+
+        assert!(err(&[named("a", 1), Expr::Int(2)]).contains("positional argument after keyword"));
+        assert!(err(&[named("z", 1)]).contains("no parameter named 'z'"));
+        assert!(err(&[Expr::Int(1), named("a", 1)]).contains("given more than once"));
+        assert!(err(&[named("b", 2)]).contains("missing argument 'a'"));
+        assert!(err(&[]).contains("missing argument 'a'"));
+    }
+}
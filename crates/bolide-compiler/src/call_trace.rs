@@ -0,0 +1,177 @@
+//! `@trace` 函数的调用日志
+//!
+//! 标注了 `@trace` 的函数（包括方法）在入口打印一行 `[trace] f(x=1, name=ada)`；
+//! `@trace(ret)` 还在每条返回路径上打印 `[trace] f -> 3`，没有返回值的函数打印 `[trace] f returned`。
+//! 和 `__default_str__` 一样在 AST 上改写：参数和返回值按 `str()` 的规则转换（`str()` 不支持的类型
+//! 输出 `<list>` 这样的占位），整行经 `print` 输出，拼接产生的临时字符串由语句结束时的释放逻辑回收。
+//! ref 参数打印的是调用时的当前值。
+//!
+//! 返回值先存进隐藏的局部变量，打印后再返回它，返回表达式只求值一次。
+//! `--no-trace`（`set_no_trace`）时不改写，生成的代码和没有标注时完全相同。
+
+use std::collections::HashMap;
+
+use bolide_parser::{
+    AsyncSelectBranch, BinOp, Expr, FuncDef, Param, Program, SelectBranch, Statement, TraceMode, Type as BolideType, VarDecl,
+};
+
+use crate::formatter;
+use crate::modules::ImportedModule;
+
+/// 日志行的前缀
+const TAG: &str = "[trace] ";
+/// 保存返回值的隐藏变量
+const RET_VAR: &str = "__trace_ret";
+
+/// 改写所有标注了 `@trace` 的函数和方法
+pub(crate) fn add_call_tracing(program: &mut Program, modules: &HashMap<String, ImportedModule>) {
+    for stmt in &mut program.statements {
+        match stmt {
+            Statement::FuncDef(func) => {
+                let label = formatter::display_name(&func.name, modules);
+                instrument(func, &label);
+            }
+            Statement::ClassDef(class) => {
+                let class_name = formatter::display_name(&class.name, modules);
+                for method in &mut class.methods {
+                    let label = format!("{}.{}", class_name, method.name);
+                    instrument(method, &label);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn instrument(func: &mut FuncDef, label: &str) {
+    let Some(mode) = func.trace else { return };
+    if mode == TraceMode::Returns {
+        log_returns(&mut func.body, label, func.return_type.as_ref());
+        if func.return_type.is_none() && !matches!(func.body.last(), Some(Statement::Return(_))) {
+            func.body.push(log_line(vec![Expr::String(format!("{}{} returned", TAG, label))]));
+        }
+    }
+    func.body.insert(0, entry_line(label, &func.params));
+}
+
+/// `print("[trace] f(a=" + str(a) + ", b=" + str(b) + ")")`
+fn entry_line(label: &str, params: &[Param]) -> Statement {
+    let mut text = format!("{}{}(", TAG, label);
+    let mut parts = Vec::new();
+    for (i, param) in params.iter().enumerate() {
+        if i > 0 {
+            text.push_str(", ");
+        }
+        text.push_str(&param.name);
+        text.push('=');
+        push_value(&mut text, &mut parts, Expr::Ident(param.name.clone()), &param.ty);
+    }
+    text.push(')');
+    parts.push(Expr::String(text));
+    log_line(parts)
+}
+
+/// 把 `return e;` 改成先存下返回值、打印后再返回
+fn log_returns(body: &mut Vec<Statement>, label: &str, ret_ty: Option<&BolideType>) {
+    let mut i = 0;
+    while i < body.len() {
+        let replacement = match &mut body[i] {
+            Statement::Return(value) => {
+                let mut text = format!("{}{}", TAG, label);
+                let mut parts = Vec::new();
+                let mut stmts = Vec::new();
+                let ret = match (value.take(), ret_ty) {
+                    (Some(value), Some(ty)) => {
+                        stmts.push(Statement::VarDecl(VarDecl {
+                            name: RET_VAR.to_string(),
+                            ty: Some(ty.clone()),
+                            value: Some(value),
+                            thread_local: false,
+                        }));
+                        text.push_str(" -> ");
+                        push_value(&mut text, &mut parts, Expr::Ident(RET_VAR.to_string()), ty);
+                        Some(Expr::Ident(RET_VAR.to_string()))
+                    }
+                    // 没有声明返回类型却返回了值：原样保留，由编译器报告
+                    (Some(value), None) => Some(value),
+                    (None, _) => {
+                        text.push_str(" returned");
+                        None
+                    }
+                };
+                parts.push(Expr::String(text));
+                stmts.push(log_line(parts));
+                stmts.push(Statement::Return(ret));
+                Some(stmts)
+            }
+            stmt => {
+                for nested in nested_bodies(stmt) {
+                    log_returns(nested, label, ret_ty);
+                }
+                None
+            }
+        };
+        match replacement {
+            Some(stmts) => {
+                let len = stmts.len();
+                body.splice(i..i + 1, stmts);
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+}
+
+/// 语句里直接包含的代码块（不进入嵌套的函数定义）
+fn nested_bodies(stmt: &mut Statement) -> Vec<&mut Vec<Statement>> {
+    match stmt {
+        Statement::If(s) => {
+            let mut bodies = vec![&mut s.then_body];
+            bodies.extend(s.elif_branches.iter_mut().map(|(_, body)| body));
+            bodies.extend(s.else_body.as_mut());
+            bodies
+        }
+        Statement::While(s) => vec![&mut s.body],
+        Statement::For(s) => vec![&mut s.body],
+        Statement::Pool(s) => vec![&mut s.body],
+        Statement::AwaitScope(s) => vec![&mut s.body],
+        Statement::Measure(s) => vec![&mut s.body],
+        Statement::Match(s) => {
+            let mut bodies: Vec<_> = s.arms.iter_mut().map(|arm| &mut arm.body).collect();
+            bodies.extend(s.default.as_mut());
+            bodies
+        }
+        Statement::Select(s) => s.branches.iter_mut().map(|branch| match branch {
+            SelectBranch::Recv { body, .. } | SelectBranch::RecvAny { body, .. } | SelectBranch::Closed { body }
+            | SelectBranch::Timeout { body, .. } | SelectBranch::Default { body } => body,
+        }).collect(),
+        Statement::AsyncSelect(s) => s.branches.iter_mut().map(|branch| match branch {
+            AsyncSelectBranch::Bind { body, .. } | AsyncSelectBranch::Expr { body, .. } => body,
+        }).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 追加一个值：`str()` 支持的类型拼接 `str(value)`，其他类型写占位
+fn push_value(text: &mut String, parts: &mut Vec<Expr>, value: Expr, ty: &BolideType) {
+    match formatter::str_expr(value, ty) {
+        Some(expr) => {
+            parts.push(Expr::String(std::mem::take(text)));
+            parts.push(expr);
+        }
+        None => {
+            text.push('<');
+            text.push_str(formatter::type_label(ty));
+            text.push('>');
+        }
+    }
+}
+
+/// `print(a + b + ...)`
+fn log_line(parts: Vec<Expr>) -> Statement {
+    let line = parts.into_iter()
+        .filter(|part| !matches!(part, Expr::String(s) if s.is_empty()))
+        .reduce(|acc, part| Expr::BinOp(Box::new(acc), BinOp::Add, Box::new(part)))
+        .unwrap_or_else(|| Expr::String(String::new()));
+    Statement::Expr(Expr::Call(Box::new(Expr::Ident("print".to_string())), vec![line]))
+}
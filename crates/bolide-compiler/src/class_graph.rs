@@ -0,0 +1,237 @@
+//! 类之间的依赖检查
+//!
+//! 类按继承顺序处理（父类先于子类），在此之前先检查两种会无限展开的依赖：
+//!
+//! - 继承链成环：`class A: B` 且 `class B: A`
+//! - 构造时按值的依赖成环：字段默认值在调用处求值，默认值里构造的类如果又省略了
+//!   带默认值的字段，那些默认值接着求值。`class Node { next: Node = Node(0); }`
+//!   这样的默认值会无限展开，报告环上的字段路径
+//!
+//! 只是字段类型指向自己（链表、树）的类不受影响：没有默认值，或者默认值是 `none`
+//! 的字段不产生依赖。图上的环按强连通分量找出。
+
+use std::collections::{HashMap, VecDeque};
+
+use bolide_parser::{ClassDef, ClassField, Expr};
+
+use crate::purity;
+
+/// 检查类定义中的继承环和字段默认值的构造环
+pub(crate) fn check_classes(classes: &[&ClassDef]) -> Result<(), String> {
+    check_inheritance(classes)?;
+    check_field_defaults(classes)
+}
+
+/// 继承链不能成环
+fn check_inheritance(classes: &[&ClassDef]) -> Result<(), String> {
+    let index: HashMap<&str, usize> = classes.iter().enumerate().map(|(i, c)| (c.name.as_str(), i)).collect();
+    let edges: Vec<Vec<usize>> = classes.iter()
+        .map(|c| c.parent.as_deref().and_then(|p| index.get(p)).copied().into_iter().collect())
+        .collect();
+    match cycles(&edges).first() {
+        Some(cycle) => {
+            let names: Vec<&str> = cycle.iter().map(|&i| classes[i].name.as_str()).collect();
+            Err(format!("class inheritance cycle: {}", names.join(" -> ")))
+        }
+        None => Ok(()),
+    }
+}
+
+/// 字段默认值不能直接或间接地构造正在定义的类（且又用到同一个默认值）
+fn check_field_defaults(classes: &[&ClassDef]) -> Result<(), String> {
+    let by_name: HashMap<&str, &ClassDef> = classes.iter().map(|c| (c.name.as_str(), *c)).collect();
+    let layouts: HashMap<&str, Vec<&ClassField>> = classes.iter()
+        .map(|c| (c.name.as_str(), fields_of(c, &by_name)))
+        .collect();
+
+    // 节点：每个类（含继承的字段）里带默认值的字段
+    let mut nodes: Vec<(&str, &str, &Expr)> = Vec::new();
+    let mut node_of: HashMap<(&str, &str), usize> = HashMap::new();
+    for class in classes {
+        for field in &layouts[class.name.as_str()] {
+            if let Some(default) = &field.default_value {
+                node_of.insert((class.name.as_str(), field.name.as_str()), nodes.len());
+                nodes.push((class.name.as_str(), field.name.as_str(), default));
+            }
+        }
+    }
+
+    // 边：默认值里的构造调用省略了的、带默认值的字段（与 call_args 的整理规则一致）
+    let edges: Vec<Vec<usize>> = nodes.iter().map(|&(_, _, default)| {
+        let mut targets = Vec::new();
+        purity::visit_expr(default, &mut |expr| {
+            let Expr::Call(callee, args) = expr else { return };
+            let Expr::Ident(name) = callee.as_ref() else { return };
+            let Some(fields) = layouts.get(name.as_str()) else { return };
+            let positional = args.iter().take_while(|a| !matches!(a, Expr::NamedArg(..))).count();
+            let named: Vec<&str> = args.iter()
+                .filter_map(|a| match a {
+                    Expr::NamedArg(n, _) => Some(n.as_str()),
+                    _ => None,
+                })
+                .collect();
+            for (i, field) in fields.iter().enumerate() {
+                if i >= positional && !named.contains(&field.name.as_str()) {
+                    targets.extend(node_of.get(&(name.as_str(), field.name.as_str())));
+                }
+            }
+        });
+        targets
+    }).collect();
+
+    match cycles(&edges).first() {
+        Some(cycle) => {
+            let path: Vec<String> = cycle.iter().map(|&i| format!("{}.{}", nodes[i].0, nodes[i].1)).collect();
+            Err(format!("field defaults construct each other without end: {}", path.join(" -> ")))
+        }
+        None => Ok(()),
+    }
+}
+
+/// 类的全部字段（含继承的），与构造函数参数顺序一致；不在本次程序里的父类略过
+fn fields_of<'c>(class: &'c ClassDef, by_name: &HashMap<&str, &'c ClassDef>) -> Vec<&'c ClassField> {
+    let mut chain = vec![class];
+    while let Some(parent) = chain.last().unwrap().parent.as_deref().and_then(|p| by_name.get(p)) {
+        chain.push(parent);
+    }
+    chain.iter().rev().flat_map(|c| &c.fields).collect()
+}
+
+/// 图中每个成环的强连通分量给出一条环路（首尾是同一个节点）
+///
+/// 强连通分量用 Tarjan 算法求出；分量只有一个节点时，有自环才算成环。
+fn cycles(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut tarjan = Tarjan {
+        edges,
+        index: vec![None; edges.len()],
+        low: vec![0; edges.len()],
+        on_stack: vec![false; edges.len()],
+        stack: Vec::new(),
+        next: 0,
+        components: Vec::new(),
+    };
+    for node in 0..edges.len() {
+        if tarjan.index[node].is_none() {
+            tarjan.connect(node);
+        }
+    }
+
+    let mut components = tarjan.components;
+    components.iter_mut().for_each(|c| c.sort_unstable());
+    components.sort_unstable();
+    components.into_iter()
+        .filter(|c| c.len() > 1 || edges[c[0]].contains(&c[0]))
+        .map(|c| cycle_through(edges, &c))
+        .collect()
+}
+
+struct Tarjan<'a> {
+    edges: &'a [Vec<usize>],
+    index: Vec<Option<usize>>,
+    low: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next: usize,
+    components: Vec<Vec<usize>>,
+}
+
+impl Tarjan<'_> {
+    fn connect(&mut self, node: usize) {
+        self.index[node] = Some(self.next);
+        self.low[node] = self.next;
+        self.next += 1;
+        self.stack.push(node);
+        self.on_stack[node] = true;
+
+        for &next in &self.edges[node] {
+            match self.index[next] {
+                None => {
+                    self.connect(next);
+                    self.low[node] = self.low[node].min(self.low[next]);
+                }
+                Some(index) if self.on_stack[next] => self.low[node] = self.low[node].min(index),
+                Some(_) => {}
+            }
+        }
+
+        if Some(self.low[node]) == self.index[node] {
+            let mut component = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack[member] = false;
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+/// 分量内从编号最小的节点出发、回到它的最短环路
+fn cycle_through(edges: &[Vec<usize>], component: &[usize]) -> Vec<usize> {
+    let start = component[0];
+    let mut came_from: HashMap<usize, usize> = HashMap::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(node) = queue.pop_front() {
+        for &next in &edges[node] {
+            if next == start {
+                let mut path = vec![start, node];
+                let mut cur = node;
+                while cur != start {
+                    cur = came_from[&cur];
+                    path.push(cur);
+                }
+                path.reverse();
+                return path;
+            }
+            if component.contains(&next) && !came_from.contains_key(&next) {
+                came_from.insert(next, node);
+                queue.push_back(next);
+            }
+        }
+    }
+    vec![start]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bolide_parser::Statement;
+
+    fn check(src: &str) -> Result<(), String> {
+        let program = bolide_parser::parse_source(src).unwrap();
+        let classes: Vec<&ClassDef> = program.statements.iter()
+            .filter_map(|stmt| match stmt {
+                Statement::ClassDef(class) => Some(class),
+                _ => None,
+            })
+            .collect();
+        check_classes(&classes)
+    }
+
+    #[test]
+    fn test_default_construction_cycles() {
+        let err = check("class Node {\n    value: int;\n    next: Node = Node(0);\n}\n").unwrap_err();
+        assert!(err.ends_with("Node.next -> Node.next"), "{}", err);
+
+        let err = check("class A {\n    b: B = B();\n}\nclass B {\n    n: int = 1;\n    a: A = A();\n}\n").unwrap_err();
+        assert!(err.ends_with("A.b -> B.a -> A.b"), "{}", err);
+
+        // 继承的默认值也在子类的构造函数调用里求值
+        let err = check("class Base {\n    child: Leaf = Leaf();\n}\nclass Leaf: Base {\n}\n").unwrap_err();
+        assert!(err.ends_with("Leaf.child -> Leaf.child"), "{}", err);
+
+        let err = check("class A: B {\n}\nclass B: A {\n}\n").unwrap_err();
+        assert_eq!(err, "class inheritance cycle: A -> B -> A");
+    }
+
+    #[test]
+    fn test_pointer_recursive_classes_allowed() {
+        // 字段类型指向自己、默认值为 none 或构造时给出了该字段，都不会无限展开
+        check("class Node {\n    value: int;\n    next: Node = none;\n    prev: weak Node;\n}\n").unwrap();
+        check("class Node {\n    value: int;\n    next: Node = Node(0, none);\n}\n").unwrap();
+        check("class Node {\n    value: int = 0;\n    next: Node = Node(next: none);\n}\n").unwrap();
+        check("class Tree {\n    left: Tree;\n    right: Tree;\n}\nclass Forest {\n    root: Tree = Tree(none, none);\n}\n").unwrap();
+    }
+}
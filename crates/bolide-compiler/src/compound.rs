@@ -0,0 +1,125 @@
+//! 复合赋值 `target op= value`
+//!
+//! 按 `target = target op value` 编译，但目标只求值一次：目标里的索引表达式（以及不是变量的基础表达式）
+//! 先求值存入隐藏变量，读和写都通过只含变量、字面量、成员访问和索引的路径进行，
+//! 所以 `xs[next()] += 1` 只调用一次 `next()`。
+//!
+//! 列表的 `+=` 不构造新列表，而是 `list_extend` 到原列表；字符串的 `+=` 由普通赋值接管
+//! 拼接结果，旧值释放一次。
+
+use bolide_parser::{BinOp, Expr, Type as BolideType};
+
+/// 运算符的源码写法，用于错误信息
+pub(crate) fn op_text(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+=",
+        BinOp::Sub => "-=",
+        BinOp::Mul => "*=",
+        BinOp::Div => "/=",
+        BinOp::Mod => "%=",
+        _ => "=",
+    }
+}
+
+/// 复合赋值是否原地扩展列表（`xs += ys`）
+pub(crate) fn is_list_extend(op: BinOp, target_ty: &BolideType) -> bool {
+    matches!(op, BinOp::Add) && matches!(target_ty, BolideType::List(_))
+}
+
+/// 检查目标类型是否支持该复合赋值
+///
+/// 数值、字符串和类（运算符重载）交给二元运算的规则；容器只支持列表的 `+=`，
+/// 且右侧必须是同类型的列表（列表字面量按目标的元素类型编译）。
+pub(crate) fn check_operands(op: BinOp, target_ty: &BolideType, value: &Expr, value_ty: &BolideType) -> Result<(), String> {
+    match target_ty {
+        BolideType::List(_) if matches!(op, BinOp::Add) => {
+            if value_ty == target_ty || matches!(value, Expr::List(_)) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "`+=` on a {} needs a {} on the right, got {}",
+                    crate::analysis::type_name(target_ty),
+                    crate::analysis::type_name(target_ty),
+                    crate::analysis::type_name(value_ty)
+                ))
+            }
+        }
+        BolideType::Str if !matches!(op, BinOp::Add) => {
+            Err(format!("`{}` is not supported for str; only `+=` is", op_text(op)))
+        }
+        BolideType::List(_) | BolideType::PackedList(_) | BolideType::Dict(_, _) | BolideType::WeakDict(_, _) | BolideType::Tuple(_)
+        | BolideType::Channel(_) | BolideType::Future | BolideType::Func | BolideType::FuncSig(_, _) => {
+            Err(format!("`{}` is not supported for {}", op_text(op), crate::analysis::type_name(target_ty)))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 把目标中需要只求值一次的子表达式换成 `hoist` 的结果（隐藏变量）
+///
+/// 变量和字面量索引保持原样；其余索引表达式和不是变量的基础表达式按从左到右的顺序交给 `hoist`。
+pub(crate) fn stable_target(target: &Expr, hoist: &mut dyn FnMut(&Expr) -> Result<Expr, String>) -> Result<Expr, String> {
+    Ok(match target {
+        Expr::Ident(_) => target.clone(),
+        Expr::Member(base, field) => Expr::Member(Box::new(stable_target(base, hoist)?), field.clone()),
+        Expr::Index(base, index) => {
+            let base = stable_target(base, hoist)?;
+            let index = if is_trivial(index) { index.as_ref().clone() } else { hoist(index)? };
+            Expr::Index(Box::new(base), Box::new(index))
+        }
+        _ => hoist(target)?,
+    })
+}
+
+fn is_trivial(expr: &Expr) -> bool {
+    matches!(expr, Expr::Ident(_) | Expr::Int(_) | Expr::Float(_) | Expr::Bool(_) | Expr::String(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ident(name: &str) -> Expr {
+        Expr::Ident(name.to_string())
+    }
+
+    fn index(base: Expr, idx: Expr) -> Expr {
+        Expr::Index(Box::new(base), Box::new(idx))
+    }
+
+    #[test]
+    fn test_stable_target_hoists_each_index_once() {
+        // grid[next()][j].count
+        let call = Expr::Call(Box::new(ident("next")), vec![]);
+        let target = Expr::Member(Box::new(index(index(ident("grid"), call), ident("j"))), "count".to_string());
+
+        let mut hoisted = Vec::new();
+        let stable = stable_target(&target, &mut |e| {
+            hoisted.push(format!("{:?}", e));
+            Ok(ident(&format!("__h{}", hoisted.len())))
+        })
+        .unwrap();
+
+        assert_eq!(hoisted.len(), 1);
+        assert!(hoisted[0].contains("next"), "{}", hoisted[0]);
+        let expected = Expr::Member(Box::new(index(index(ident("grid"), ident("__h1")), ident("j"))), "count".to_string());
+        assert_eq!(format!("{:?}", stable), format!("{:?}", expected));
+    }
+
+    #[test]
+    fn test_check_operands() {
+        let ints = BolideType::List(Box::new(BolideType::Int));
+        let floats = BolideType::List(Box::new(BolideType::Float));
+        let value = ident("v");
+        assert!(check_operands(BinOp::Add, &ints, &value, &ints).is_ok());
+        assert!(check_operands(BinOp::Add, &floats, &Expr::List(vec![Expr::Int(1)]), &ints).is_ok());
+        assert!(check_operands(BinOp::Add, &BolideType::Str, &value, &BolideType::Str).is_ok());
+        assert!(check_operands(BinOp::Mod, &BolideType::Float, &value, &BolideType::Float).is_ok());
+
+        let err = check_operands(BinOp::Add, &ints, &value, &BolideType::Int).unwrap_err();
+        assert!(err.contains("needs a list<int> on the right, got int"), "{}", err);
+        let err = check_operands(BinOp::Sub, &ints, &value, &ints).unwrap_err();
+        assert!(err.contains("`-=` is not supported for list<int>"), "{}", err);
+        assert!(check_operands(BinOp::Mul, &BolideType::Str, &value, &BolideType::Int).unwrap_err().contains("only `+=`"));
+    }
+}
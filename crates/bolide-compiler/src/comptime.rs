@@ -0,0 +1,371 @@
+//! 编译期求值：实参都是常量的 `@pure` 函数调用
+//!
+//! 按 JIT 生成代码的语义解释函数体（int 运算回绕、float 取模为 `a - floor(a / b) * b`、
+//! 比较结果为 bool、`range` 的步长只有字面量负数才倒数）。只支持 int / float / bool 值和
+//! let、赋值、if、while、for range、match、return、break、continue；遇到其他写法、
+//! 运行时会出错的运算（除以零、float 转 int 越界）或超出步数上限时放弃，照常生成调用。
+
+use std::collections::HashMap;
+
+use bolide_parser::{BinOp, Expr, FuncDef, MatchPattern, Statement, Type as BolideType, UnaryOp};
+
+use crate::overflow;
+
+/// 编译期常量
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Const {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+/// 单次求值最多执行的语句数（含循环迭代），超出后放弃
+const STEP_LIMIT: usize = 100_000;
+/// 最深的调用嵌套
+const DEPTH_LIMIT: usize = 64;
+
+impl Const {
+    /// 字面量（含负数字面量）的值
+    pub(crate) fn from_literal(expr: &Expr) -> Option<Const> {
+        match expr {
+            Expr::Int(n) => Some(Const::Int(*n)),
+            Expr::Float(f) => Some(Const::Float(*f)),
+            Expr::Bool(b) => Some(Const::Bool(*b)),
+            Expr::UnaryOp(UnaryOp::Neg, inner) => match Const::from_literal(inner)? {
+                Const::Int(n) => Some(Const::Int(n.wrapping_neg())),
+                Const::Float(f) => Some(Const::Float(-f)),
+                Const::Bool(_) => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn has_type(self, ty: &BolideType) -> bool {
+        matches!(
+            (self, ty),
+            (Const::Int(_), BolideType::Int) | (Const::Float(_), BolideType::Float) | (Const::Bool(_), BolideType::Bool)
+        )
+    }
+
+    fn zero(ty: &BolideType) -> Option<Const> {
+        match ty {
+            BolideType::Int => Some(Const::Int(0)),
+            BolideType::Float => Some(Const::Float(0.0)),
+            BolideType::Bool => Some(Const::Bool(false)),
+            _ => None,
+        }
+    }
+}
+
+/// 在编译期调用 `funcs` 里的 `name`；无法求值时返回 None
+pub(crate) fn eval_call(funcs: &HashMap<String, FuncDef>, name: &str, args: &[Const]) -> Option<Const> {
+    let mut eval = Evaluator { funcs, steps: 0, depth: 0 };
+    eval.call(name, args)
+}
+
+struct Evaluator<'a> {
+    funcs: &'a HashMap<String, FuncDef>,
+    steps: usize,
+    depth: usize,
+}
+
+/// 语句执行后的控制流
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    Return(Option<Const>),
+}
+
+type Env = HashMap<String, Const>;
+
+impl Evaluator<'_> {
+    fn call(&mut self, name: &str, args: &[Const]) -> Option<Const> {
+        let func = self.funcs.get(name)?;
+        let ret_ty = func.return_type.as_ref()?;
+        if func.params.len() != args.len() || self.depth >= DEPTH_LIMIT {
+            return None;
+        }
+        let mut env = Env::new();
+        for (param, arg) in func.params.iter().zip(args) {
+            if !arg.has_type(&param.ty) {
+                return None;
+            }
+            env.insert(param.name.clone(), *arg);
+        }
+        self.depth += 1;
+        let flow = self.exec_block(&func.body, &mut env);
+        self.depth -= 1;
+        let value = match flow? {
+            Flow::Return(value) => value?,
+            // 没有 return 时 JIT 返回零值
+            Flow::Next => Const::zero(ret_ty)?,
+            Flow::Break | Flow::Continue => return None,
+        };
+        value.has_type(ret_ty).then_some(value)
+    }
+
+    fn exec_block(&mut self, body: &[Statement], env: &mut Env) -> Option<Flow> {
+        for stmt in body {
+            match self.exec(stmt, env)? {
+                Flow::Next => {}
+                flow => return Some(flow),
+            }
+        }
+        Some(Flow::Next)
+    }
+
+    fn exec(&mut self, stmt: &Statement, env: &mut Env) -> Option<Flow> {
+        self.steps += 1;
+        if self.steps > STEP_LIMIT {
+            return None;
+        }
+        match stmt {
+            Statement::VarDecl(decl) => {
+                let value = self.eval(decl.value.as_ref()?, env)?;
+                if decl.ty.as_ref().is_some_and(|ty| !value.has_type(ty)) {
+                    return None;
+                }
+                env.insert(decl.name.clone(), value);
+            }
+            Statement::Assign(assign) => {
+                let Expr::Ident(name) = &assign.target else { return None };
+                let value = self.eval(&assign.value, env)?;
+                let old = env.get(name)?;
+                let value = match assign.op {
+                    Some(op) => binop(*old, &op, value)?,
+                    None => value,
+                };
+                if std::mem::discriminant(old) != std::mem::discriminant(&value) {
+                    return None;
+                }
+                env.insert(name.clone(), value);
+            }
+            Statement::If(s) => {
+                if self.eval_bool(&s.condition, env)? {
+                    return self.exec_block(&s.then_body, env);
+                }
+                for (cond, body) in &s.elif_branches {
+                    if self.eval_bool(cond, env)? {
+                        return self.exec_block(body, env);
+                    }
+                }
+                if let Some(body) = &s.else_body {
+                    return self.exec_block(body, env);
+                }
+            }
+            Statement::While(s) => {
+                while self.eval_bool(&s.condition, env)? {
+                    match self.exec_block(&s.body, env)? {
+                        Flow::Next | Flow::Continue => {}
+                        Flow::Break => break,
+                        flow @ Flow::Return(_) => return Some(flow),
+                    }
+                    self.tick()?;
+                }
+            }
+            Statement::For(s) => return self.exec_for_range(s, env),
+            Statement::Match(s) => {
+                let Const::Int(subject) = self.eval(&s.subject, env)? else { return None };
+                for arm in &s.arms {
+                    if arm.patterns.iter().any(|p| *p == MatchPattern::Int(subject)) {
+                        return self.exec_block(&arm.body, env);
+                    }
+                }
+                if let Some(body) = &s.default {
+                    return self.exec_block(body, env);
+                }
+            }
+            Statement::Return(value) => {
+                let value = match value {
+                    Some(expr) => Some(self.eval(expr, env)?),
+                    None => None,
+                };
+                return Some(Flow::Return(value));
+            }
+            Statement::Break => return Some(Flow::Break),
+            Statement::Continue => return Some(Flow::Continue),
+            Statement::Expr(expr) => {
+                self.eval(expr, env)?;
+            }
+            _ => return None,
+        }
+        Some(Flow::Next)
+    }
+
+    /// `for i in range(...)`：和 JIT 一样，循环变量就是普通变量，每次迭代末尾加上步长
+    fn exec_for_range(&mut self, s: &bolide_parser::ForStmt, env: &mut Env) -> Option<Flow> {
+        let Expr::Call(callee, args) = &s.iter else { return None };
+        if !matches!(callee.as_ref(), Expr::Ident(name) if name == "range") || s.vars.len() != 1 {
+            return None;
+        }
+        // `for t: float in range(...)` 的循环变量是 float，不在编译期求值
+        if matches!(s.var_type, Some(ref ty) if *ty != bolide_parser::Type::Int) {
+            return None;
+        }
+        let mut ints = Vec::new();
+        for arg in args {
+            let Const::Int(n) = self.eval(arg, env)? else { return None };
+            ints.push(n);
+        }
+        let (start, end, step) = match ints[..] {
+            [end] => (0, end, 1),
+            [start, end] => (start, end, 1),
+            [start, end, step] => (start, end, step),
+            _ => return None,
+        };
+        let descending = matches!(args.get(2), Some(Expr::Int(n)) if *n < 0);
+        let var = &s.vars[0];
+        env.insert(var.clone(), Const::Int(start));
+        loop {
+            let Some(&Const::Int(i)) = env.get(var) else { return None };
+            if (descending && i <= end) || (!descending && i >= end) {
+                break;
+            }
+            match self.exec_block(&s.body, env)? {
+                Flow::Next | Flow::Continue => {}
+                Flow::Break => break,
+                flow @ Flow::Return(_) => return Some(flow),
+            }
+            let Some(&Const::Int(i)) = env.get(var) else { return None };
+            env.insert(var.clone(), Const::Int(i.wrapping_add(step)));
+            self.tick()?;
+        }
+        Some(Flow::Next)
+    }
+
+    fn tick(&mut self) -> Option<()> {
+        self.steps += 1;
+        (self.steps <= STEP_LIMIT).then_some(())
+    }
+
+    fn eval_bool(&mut self, expr: &Expr, env: &Env) -> Option<bool> {
+        match self.eval(expr, env)? {
+            Const::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr, env: &Env) -> Option<Const> {
+        match expr {
+            Expr::Int(_) | Expr::Float(_) | Expr::Bool(_) => Const::from_literal(expr),
+            Expr::Ident(name) => env.get(name).copied(),
+            Expr::UnaryOp(op, inner) => match (op, self.eval(inner, env)?) {
+                (UnaryOp::Neg, Const::Int(n)) => Some(Const::Int(n.wrapping_neg())),
+                (UnaryOp::Neg, Const::Float(f)) => Some(Const::Float(-f)),
+                (UnaryOp::Not, Const::Bool(b)) => Some(Const::Bool(!b)),
+                _ => None,
+            },
+            Expr::BinOp(l, op, r) => {
+                let l = self.eval(l, env)?;
+                let r = self.eval(r, env)?;
+                binop(l, op, r)
+            }
+            Expr::CompareChain(first, rest) => {
+                let mut left = self.eval(first, env)?;
+                for (op, expr) in rest {
+                    let right = self.eval(expr, env)?;
+                    if binop(left, op, right)? != Const::Bool(true) {
+                        return Some(Const::Bool(false));
+                    }
+                    left = right;
+                }
+                Some(Const::Bool(true))
+            }
+            Expr::Call(callee, args) => {
+                let Expr::Ident(name) = callee.as_ref() else { return None };
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(self.eval(arg, env)?);
+                }
+                match (name.as_str(), &values[..]) {
+                    ("int", [Const::Int(n)]) => Some(Const::Int(*n)),
+                    // fcvt_to_sint 对 NaN 和越界的值会 trap
+                    ("int", [Const::Float(f)]) if f.is_finite() && f.trunc() >= i64::MIN as f64 && f.trunc() < i64::MAX as f64 => {
+                        Some(Const::Int(*f as i64))
+                    }
+                    ("float", [Const::Int(n)]) => Some(Const::Float(*n as f64)),
+                    ("float", [Const::Float(f)]) => Some(Const::Float(*f)),
+                    // checked_* 返回元组，不在编译期求值
+                    (name, [Const::Int(a), Const::Int(b)]) if overflow::lookup(name).is_some() => {
+                        let op = overflow::lookup(name)?;
+                        (op.mode != overflow::Mode::Checked).then(|| Const::Int(overflow::fold(op, *a, *b).0))
+                    }
+                    _ => self.call(name, &values),
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn binop(l: Const, op: &BinOp, r: Const) -> Option<Const> {
+    use Const::*;
+    Some(match (l, r) {
+        (Int(a), Int(b)) => match op {
+            BinOp::Add => Int(a.wrapping_add(b)),
+            BinOp::Sub => Int(a.wrapping_sub(b)),
+            BinOp::Mul => Int(a.wrapping_mul(b)),
+            // sdiv / srem 在除以零和 MIN / -1 时 trap
+            BinOp::Div => Int(a.checked_div(b)?),
+            BinOp::Mod => Int(a.checked_rem(b)?),
+            BinOp::Eq => Bool(a == b),
+            BinOp::Ne => Bool(a != b),
+            BinOp::Lt => Bool(a < b),
+            BinOp::Le => Bool(a <= b),
+            BinOp::Gt => Bool(a > b),
+            BinOp::Ge => Bool(a >= b),
+            _ => return None,
+        },
+        (Float(a), Float(b)) => match op {
+            BinOp::Add => Float(a + b),
+            BinOp::Sub => Float(a - b),
+            BinOp::Mul => Float(a * b),
+            BinOp::Div => Float(a / b),
+            BinOp::Mod => Float(a - (a / b).floor() * b),
+            BinOp::Eq => Bool(a == b),
+            BinOp::Ne => Bool(a != b),
+            BinOp::Lt => Bool(a < b),
+            BinOp::Le => Bool(a <= b),
+            BinOp::Gt => Bool(a > b),
+            BinOp::Ge => Bool(a >= b),
+            _ => return None,
+        },
+        (Bool(a), Bool(b)) => match op {
+            BinOp::And => Bool(a && b),
+            BinOp::Or => Bool(a || b),
+            BinOp::Eq => Bool(a == b),
+            BinOp::Ne => Bool(a != b),
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn funcs(src: &str) -> HashMap<String, FuncDef> {
+        bolide_parser::parse_source(src).unwrap().statements.into_iter()
+            .filter_map(|stmt| match stmt {
+                Statement::FuncDef(func) => Some((func.name.clone(), func)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_eval_call() {
+        let funcs = funcs("fn tri(n: int) -> int {\n    let t: int = 0;\n    for i in range(1, n + 1) {\n        t = t + i;\n    }\n    return t;\n}\nfn div(a: int, b: int) -> int {\n    return a / b;\n}\nfn fmod(a: float, b: float) -> float {\n    return a % b;\n}\nfn spin(n: int) -> int {\n    while true {\n        n = n + 1;\n    }\n    return n;\n}\nfn twice(n: int) -> int {\n    if n > 0 {\n        return tri(n) * 2;\n    }\n}\n");
+        assert_eq!(eval_call(&funcs, "tri", &[Const::Int(10)]), Some(Const::Int(55)));
+        assert_eq!(eval_call(&funcs, "twice", &[Const::Int(3)]), Some(Const::Int(12)));
+        assert_eq!(eval_call(&funcs, "twice", &[Const::Int(-3)]), Some(Const::Int(0)));
+        assert_eq!(eval_call(&funcs, "fmod", &[Const::Float(-1.0), Const::Float(3.0)]), Some(Const::Float(2.0)));
+        // 运行时会 trap 的运算、超出步数上限和类型不符都留到运行时
+        assert_eq!(eval_call(&funcs, "div", &[Const::Int(1), Const::Int(0)]), None);
+        assert_eq!(eval_call(&funcs, "div", &[Const::Int(i64::MIN), Const::Int(-1)]), None);
+        assert_eq!(eval_call(&funcs, "spin", &[Const::Int(0)]), None);
+        assert_eq!(eval_call(&funcs, "tri", &[Const::Float(1.0)]), None);
+    }
+}
@@ -0,0 +1,169 @@
+//! `duration` 和 `deadline`
+//!
+//! 两者都用 i64 纳秒表示：`duration` 是一段时间（`100ms`、`1.5s`），`deadline` 是单调时钟上的
+//! 时间点（`deadline(500ms)`），运算只是把 i64 运算按单位检查一遍：
+//!
+//! - `d + d`、`d - d`、`d % d`、`-d` 得到 duration；`d / d` 得到 float（`d / 1ms` 即毫秒数）
+//! - `d * n`、`n * d`、`d / n`：n 为 int 或 float，float 时结果四舍五入到纳秒
+//! - `dl + d`、`d + dl`、`dl - d` 得到 deadline；`dl - dl` 得到 duration
+//! - 比较只在同类之间进行
+//!
+//! duration 和不带单位的 int 相加、比较是错误：单位不明正是要避免的问题。
+//! 超时参数（`select` 的 `timeout(...)`）接受 duration 或 deadline；
+//! 不带单位的 int 仍按毫秒处理，但会给出弃用警告。
+
+use bolide_parser::{BinOp, Type as BolideType};
+use cranelift::prelude::*;
+
+use crate::analysis::type_name;
+
+/// 不带单位的超时参数的弃用警告
+pub(crate) const BARE_INT_TIMEOUT: &str =
+    "bare int timeout is deprecated; it is interpreted as milliseconds, write a duration such as 100ms instead";
+
+/// 超时参数的种类，决定怎样换算成运行时要的纳秒数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Timeout {
+    Duration,
+    Deadline,
+    /// 不带单位的 int：按毫秒处理（已弃用）
+    Millis,
+}
+
+/// 按参数类型确定超时参数的种类
+pub(crate) fn timeout_kind(ty: Option<&BolideType>) -> Result<Timeout, String> {
+    match ty {
+        Some(BolideType::Duration) => Ok(Timeout::Duration),
+        Some(BolideType::Deadline) => Ok(Timeout::Deadline),
+        Some(BolideType::Int) | None => Ok(Timeout::Millis),
+        Some(other) => Err(format!("timeout expects a duration or a deadline, got {}", type_name(other))),
+    }
+}
+
+fn is_time(ty: &BolideType) -> bool {
+    matches!(ty, BolideType::Duration | BolideType::Deadline)
+}
+
+fn is_number(ty: &BolideType) -> bool {
+    matches!(ty, BolideType::Int | BolideType::Float)
+}
+
+/// 二元运算的结果类型；两边都不是 duration / deadline 时返回 None，交给普通的数值运算
+pub(crate) fn binop_type(left: &BolideType, op: &BinOp, right: &BolideType) -> Option<Result<BolideType, String>> {
+    use BolideType::{Deadline, Duration, Float, Int};
+    if !is_time(left) && !is_time(right) {
+        return None;
+    }
+    let result = match (left, op, right) {
+        (_, BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge, _) if left == right => Some(BolideType::Bool),
+        (Duration, BinOp::Add | BinOp::Sub | BinOp::Mod, Duration) => Some(Duration),
+        (Duration, BinOp::Div, Duration) => Some(Float),
+        (Duration, BinOp::Mul, Int | Float) | (Int | Float, BinOp::Mul, Duration) => Some(Duration),
+        (Duration, BinOp::Div, Int | Float) => Some(Duration),
+        (Deadline, BinOp::Add | BinOp::Sub, Duration) | (Duration, BinOp::Add, Deadline) => Some(Deadline),
+        (Deadline, BinOp::Sub, Deadline) => Some(Duration),
+        _ => None,
+    };
+    Some(result.ok_or_else(|| {
+        let mut message = format!(
+            "unsupported operation: {} {} {}",
+            type_name(left), crate::purity::binop_text(op), type_name(right)
+        );
+        if (is_time(left) && is_number(right)) || (is_number(left) && is_time(right)) {
+            message.push_str("; give the number a unit, e.g. 5ms");
+        }
+        message
+    }))
+}
+
+/// 生成 duration / deadline 的二元运算，类型已由 `binop_type` 检查过
+pub(crate) fn emit_binop(
+    builder: &mut FunctionBuilder,
+    left_ty: &BolideType,
+    op: &BinOp,
+    a: Value,
+    right_ty: &BolideType,
+    b: Value,
+) -> Value {
+    let cmp = |builder: &mut FunctionBuilder, cc| {
+        let flag = builder.ins().icmp(cc, a, b);
+        builder.ins().uextend(types::I64, flag)
+    };
+    match op {
+        BinOp::Eq => cmp(builder, IntCC::Equal),
+        BinOp::Ne => cmp(builder, IntCC::NotEqual),
+        BinOp::Lt => cmp(builder, IntCC::SignedLessThan),
+        BinOp::Le => cmp(builder, IntCC::SignedLessThanOrEqual),
+        BinOp::Gt => cmp(builder, IntCC::SignedGreaterThan),
+        BinOp::Ge => cmp(builder, IntCC::SignedGreaterThanOrEqual),
+        BinOp::Add => builder.ins().iadd(a, b),
+        BinOp::Sub => builder.ins().isub(a, b),
+        BinOp::Mod => builder.ins().srem(a, b),
+        BinOp::Div if is_time(right_ty) => {
+            let a = builder.ins().fcvt_from_sint(types::F64, a);
+            let b = builder.ins().fcvt_from_sint(types::F64, b);
+            builder.ins().fdiv(a, b)
+        }
+        BinOp::Mul | BinOp::Div => {
+            // 让 duration 在左边：乘法可交换，除法的 duration 一定在左边
+            let (duration, factor, factor_ty) = if is_time(left_ty) { (a, b, right_ty) } else { (b, a, left_ty) };
+            if matches!(factor_ty, BolideType::Float) {
+                let ns = builder.ins().fcvt_from_sint(types::F64, duration);
+                let scaled = if matches!(op, BinOp::Mul) {
+                    builder.ins().fmul(ns, factor)
+                } else {
+                    builder.ins().fdiv(ns, factor)
+                };
+                let rounded = builder.ins().nearest(scaled);
+                builder.ins().fcvt_to_sint_sat(types::I64, rounded)
+            } else if matches!(op, BinOp::Mul) {
+                builder.ins().imul(duration, factor)
+            } else {
+                builder.ins().sdiv(duration, factor)
+            }
+        }
+        BinOp::And | BinOp::Or | BinOp::Is | BinOp::IsNot => {
+            unreachable!("binop_type rejects {:?} on durations", op)
+        }
+    }
+}
+
+/// 把超时参数换算成纳秒；`remaining` 是 `deadline_remaining` 的函数引用，只在 deadline 时用到
+///
+/// 负的 duration 当作 0（立即超时），运行时的 -1 / -2 留给“无超时”和 default。
+pub(crate) fn emit_timeout_ns(builder: &mut FunctionBuilder, kind: Timeout, value: Value, remaining: Option<codegen::ir::FuncRef>) -> Value {
+    let ns = match kind {
+        Timeout::Duration => value,
+        Timeout::Deadline => {
+            let remaining = remaining.expect("deadline timeout needs deadline_remaining");
+            let call = builder.ins().call(remaining, &[value]);
+            return builder.inst_results(call)[0];
+        }
+        Timeout::Millis => builder.ins().imul_imm(value, 1_000_000),
+    };
+    let zero = builder.ins().iconst(types::I64, 0);
+    builder.ins().smax(ns, zero)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bolide_parser::Type::{Bool, Deadline, Duration, Float, Int};
+
+    #[test]
+    fn test_binop_types() {
+        assert_eq!(binop_type(&Int, &BinOp::Add, &Int), None);
+        assert_eq!(binop_type(&Duration, &BinOp::Add, &Duration), Some(Ok(Duration)));
+        assert_eq!(binop_type(&Float, &BinOp::Mul, &Duration), Some(Ok(Duration)));
+        assert_eq!(binop_type(&Duration, &BinOp::Div, &Duration), Some(Ok(Float)));
+        assert_eq!(binop_type(&Duration, &BinOp::Lt, &Duration), Some(Ok(Bool)));
+        assert_eq!(binop_type(&Deadline, &BinOp::Sub, &Deadline), Some(Ok(Duration)));
+        assert_eq!(binop_type(&Duration, &BinOp::Add, &Deadline), Some(Ok(Deadline)));
+
+        let err = binop_type(&Duration, &BinOp::Add, &Int).unwrap().unwrap_err();
+        assert!(err.contains("duration + int") && err.contains("5ms"), "{}", err);
+        assert!(binop_type(&Int, &BinOp::Div, &Duration).unwrap().is_err());
+        assert!(binop_type(&Deadline, &BinOp::Add, &Deadline).unwrap().is_err());
+        assert!(binop_type(&Duration, &BinOp::Lt, &Int).unwrap().is_err());
+    }
+}
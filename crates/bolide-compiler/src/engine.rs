@@ -0,0 +1,308 @@
+//! 嵌入 API：在宿主程序里编译 Bolide 程序并限时调用其中的函数
+//!
+//! 引擎编译时开启中断检查（见 `bolide_runtime::InterruptState`）：每条循环回边递减一个计数器，
+//! 用完才读一次时钟；超过预算或收到取消请求后，正在执行的函数做 `return` 同样的清理后返回，
+//! 调用链逐层返回到宿主，得到 `BolideError::Timeout` / `BolideError::Cancelled`。
+//!
+//! 中止的调用释放各层函数的 RC 变量、语句临时值和列表迭代器，以下情况不清理：
+//! - 正在构造的容器字面量（元素已求值、字面量还没完成时被中断）
+//! - `pool` / `await scope` 块：线程池不会退出，已启动的任务继续运行
+//! - 被中止的脚本 spawn 出的线程和协程：它们运行同一份代码，也会在下一次检查时返回
+//!
+//! 每次中止泄漏的对象数因此不超过中断点所在语句里正在构造的容器个数；
+//! 提前返回的函数帧数记在 `frames_unwound()`。阻塞在运行时函数里（`sleep`、通道接收、`input`）
+//! 以及不含循环的无限递归不会被中断。
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use bolide_parser::{ParamMode, Program, Type as BolideType};
+use bolide_runtime::{InterruptState, INTERRUPT_CANCELLED, INTERRUPT_TIMEOUT};
+
+use crate::JitCompiler;
+
+/// 嵌入 API 的错误
+#[derive(Debug, thiserror::Error)]
+pub enum BolideError {
+    #[error("compile error: {0}")]
+    Compile(String),
+    #[error("unknown function: {0}")]
+    UnknownFunction(String),
+    /// 函数签名不能从宿主直接调用（目前只支持 int/bool 参数和 int/bool/无返回值）
+    #[error("cannot call {0} from the host: {1}")]
+    Unsupported(String, String),
+    /// 超过了 `call_with_budget` 的时间预算
+    #[error("script exceeded its time budget")]
+    Timeout,
+    /// 收到了 `request_cancel`
+    #[error("script was cancelled")]
+    Cancelled,
+    /// `version_at_least` 的参数不是合法的语义化版本
+    #[error("invalid version: {0}")]
+    InvalidVersion(String),
+}
+
+/// 可以交给其他线程的取消句柄
+#[derive(Clone)]
+pub struct CancelHandle(Arc<InterruptState>);
+
+impl CancelHandle {
+    /// 中断引擎上正在进行的调用；没有调用在进行时不产生效果
+    pub fn request_cancel(&self) {
+        self.0.request_cancel();
+    }
+}
+
+/// 编译好的 Bolide 程序
+pub struct BolideEngine {
+    jit: JitCompiler,
+    interrupt: Arc<InterruptState>,
+}
+
+impl BolideEngine {
+    /// 开启中断检查编译程序；顶层代码不会执行，需要时调用 `run_with_budget`
+    pub fn new(program: &Program) -> Result<Self, BolideError> {
+        let interrupt = Arc::new(InterruptState::new());
+        let mut jit = JitCompiler::new();
+        jit.enable_interrupt_checks(interrupt.clone());
+        jit.compile(program).map_err(BolideError::Compile)?;
+        Ok(Self { jit, interrupt })
+    }
+
+    /// 限时执行顶层代码（初始化全局变量等），返回它的结果
+    pub fn run_with_budget(&self, budget: Duration) -> Result<i64, BolideError> {
+        self.invoke("__main__", &[], Some(budget))
+    }
+
+    /// 限时调用函数 `func`
+    pub fn call_with_budget(&self, func: &str, args: &[i64], budget: Duration) -> Result<i64, BolideError> {
+        self.invoke(func, args, Some(budget))
+    }
+
+    /// 不限时调用函数 `func`（仍然可以被 `request_cancel` 中断）
+    pub fn call(&self, func: &str, args: &[i64]) -> Result<i64, BolideError> {
+        self.invoke(func, args, None)
+    }
+
+    /// 中断正在进行的调用；从其他线程取消请使用 `cancel_handle()`
+    pub fn request_cancel(&self) {
+        self.interrupt.request_cancel();
+    }
+
+    pub fn cancel_handle(&self) -> CancelHandle {
+        CancelHandle(self.interrupt.clone())
+    }
+
+    /// 累计因中断提前返回的函数帧数
+    pub fn frames_unwound(&self) -> u64 {
+        self.interrupt.frames_unwound()
+    }
+
+    /// 运行时的语义化版本，与脚本里的 `version()` 相同
+    pub fn version(&self) -> &'static str {
+        bolide_runtime::VERSION
+    }
+
+    /// 运行时版本是否不低于 `required`（按 SemVer 优先级比较），与 `version_at_least()` 相同
+    pub fn version_at_least(&self, required: &str) -> Result<bool, BolideError> {
+        bolide_runtime::version_at_least(required)
+            .ok_or_else(|| BolideError::InvalidVersion(required.to_string()))
+    }
+
+    /// 编译器是否支持特性 `name`，与 `has_feature()` 相同
+    pub fn has_feature(&self, name: &str) -> bool {
+        bolide_runtime::has_feature(name)
+    }
+
+    fn invoke(&self, func: &str, args: &[i64], budget: Option<Duration>) -> Result<i64, BolideError> {
+        let (ptr, params, ret) = self.jit.entry_point(func)
+            .ok_or_else(|| BolideError::UnknownFunction(func.to_string()))?;
+        let unsupported = |why: String| BolideError::Unsupported(func.to_string(), why);
+        if params.len() != args.len() {
+            return Err(unsupported(format!("expects {} argument(s), got {}", params.len(), args.len())));
+        }
+        if let Some(p) = params.iter().find(|p| p.mode != ParamMode::Borrow || !matches!(p.ty, BolideType::Int | BolideType::Bool)) {
+            return Err(unsupported(format!("parameter '{}' is not a plain int or bool", p.name)));
+        }
+        let returns = match ret {
+            None => false,
+            Some(BolideType::Int | BolideType::Bool) => true,
+            Some(other) => return Err(unsupported(format!("return type {} is not int or bool", crate::analysis::type_name(other)))),
+        };
+
+        self.interrupt.arm(budget);
+        // 参数和返回值都是 i64，按参数个数选择函数指针类型
+        let result = unsafe {
+            macro_rules! call {
+                ($($arg:expr),*) => {{
+                    if returns {
+                        let f: extern "C" fn($(call!(@ty $arg)),*) -> i64 = std::mem::transmute(ptr);
+                        f($($arg),*)
+                    } else {
+                        let f: extern "C" fn($(call!(@ty $arg)),*) = std::mem::transmute(ptr);
+                        f($($arg),*);
+                        0
+                    }
+                }};
+                (@ty $arg:expr) => { i64 };
+            }
+            match *args {
+                [] => call!(),
+                [a] => call!(a),
+                [a, b] => call!(a, b),
+                [a, b, c] => call!(a, b, c),
+                [a, b, c, d] => call!(a, b, c, d),
+                _ => return Err(unsupported("more than 4 arguments".to_string())),
+            }
+        };
+        match self.interrupt.pending() {
+            INTERRUPT_TIMEOUT => Err(BolideError::Timeout),
+            INTERRUPT_CANCELLED => Err(BolideError::Cancelled),
+            _ => Ok(result),
+        }
+    }
+}
+
+impl Drop for BolideEngine {
+    /// 释放顶层代码初始化的 RC 全局变量
+    fn drop(&mut self) {
+        self.jit.release_globals();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    const SCRIPT: &str = r#"
+let calls: int = 0;
+
+fn spin(n: int) -> int {
+    let label: str = str(n);
+    let xs: list<str> = [label, str(n + 1)];
+    let i: int = 0;
+    while true {
+        let t: str = str(i) + "x";
+        i = i + 1;
+    }
+    return xs.len();
+}
+
+fn nested(n: int) -> int {
+    let s: str = str(n);
+    for x in [str(1), str(2)] {
+        return spin(n) + len(s) + len(x);
+    }
+    return 0;
+}
+
+fn sum_to(n: int) -> int {
+    let total: int = 0;
+    for i in range(n) {
+        total = (total + i * i) % 1000000007;
+    }
+    return total;
+}
+"#;
+
+    fn engine() -> BolideEngine {
+        let program = bolide_parser::parse_source(SCRIPT).unwrap();
+        BolideEngine::new(&program).unwrap()
+    }
+
+    #[test]
+    fn test_infinite_loop_times_out() {
+        let engine = engine();
+        let budget = Duration::from_millis(100);
+        let start = Instant::now();
+        let result = engine.call_with_budget("spin", &[1], budget);
+        let elapsed = start.elapsed();
+        assert!(matches!(result, Err(BolideError::Timeout)), "{:?}", result);
+        assert!(elapsed >= budget && elapsed < budget * 2, "cancelled after {:?}", elapsed);
+        assert_eq!(engine.frames_unwound(), 1);
+
+        // 中止后引擎仍然可用
+        assert_eq!(engine.call_with_budget("sum_to", &[10], budget).unwrap(), 285);
+    }
+
+    #[test]
+    fn test_cancel_from_another_thread() {
+        let engine = engine();
+        let handle = engine.cancel_handle();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            handle.request_cancel();
+        });
+        let start = Instant::now();
+        let result = engine.call("nested", &[3]);
+        canceller.join().unwrap();
+        assert!(matches!(result, Err(BolideError::Cancelled)), "{:?}", result);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        // spin 和 nested 各提前返回一次
+        assert_eq!(engine.frames_unwound(), 2);
+    }
+
+    #[test]
+    fn test_repeated_cancellation_does_not_leak() {
+        let engine = engine();
+        // 先运行一次，让字面量 "x" 驻留（驻留字符串不释放）
+        let _ = engine.call_with_budget("nested", &[7], Duration::from_millis(5));
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        for _ in 0..20 {
+            let result = engine.call_with_budget("nested", &[7], Duration::from_millis(5));
+            assert!(matches!(result, Err(BolideError::Timeout)), "{:?}", result);
+        }
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        // 各层的 RC 变量、循环里的临时值和列表迭代器都已释放
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+        assert_eq!(engine.frames_unwound(), 42);
+    }
+
+    #[test]
+    fn test_well_behaved_script_unaffected() {
+        let engine = engine();
+        assert_eq!(engine.call_with_budget("sum_to", &[1000], Duration::from_secs(10)).unwrap(), 332833500);
+        assert_eq!(engine.run_with_budget(Duration::from_secs(10)).unwrap(), 0);
+        assert_eq!(engine.frames_unwound(), 0);
+
+        assert!(matches!(engine.call("missing", &[]), Err(BolideError::UnknownFunction(_))));
+        assert!(matches!(engine.call("sum_to", &[]), Err(BolideError::Unsupported(..))));
+    }
+
+    #[test]
+    fn test_version_and_features() {
+        let program = bolide_parser::parse_source(r#"
+fn knows_typed_channels() -> bool {
+    return has_feature("typed-channels");
+}
+
+fn knows_unknown_feature() -> bool {
+    return has_feature("no-such-feature");
+}
+
+fn at_least_current() -> bool {
+    return version_at_least(version());
+}
+
+fn at_least_next_major() -> bool {
+    return version_at_least("999.0.0");
+}
+"#).unwrap();
+        let engine = BolideEngine::new(&program).unwrap();
+
+        // 脚本里的内置函数和引擎 API 看到同一份版本和特性表
+        assert_eq!(engine.call("knows_typed_channels", &[]).unwrap(), 1);
+        assert_eq!(engine.call("knows_unknown_feature", &[]).unwrap(), 0);
+        assert_eq!(engine.call("at_least_current", &[]).unwrap(), 1);
+        assert_eq!(engine.call("at_least_next_major", &[]).unwrap(), 0);
+        assert!(engine.has_feature("typed-channels"));
+        assert!(!engine.has_feature("no-such-feature"));
+
+        assert_eq!(engine.version(), env!("CARGO_PKG_VERSION"));
+        assert!(engine.version_at_least(engine.version()).unwrap());
+        assert!(engine.version_at_least("0.1.0-alpha").unwrap());
+        assert!(!engine.version_at_least("999.0.0-rc.1").unwrap());
+        assert!(matches!(engine.version_at_least("1.0"), Err(BolideError::InvalidVersion(_))));
+    }
+}
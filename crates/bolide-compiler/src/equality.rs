@@ -0,0 +1,156 @@
+//! 类实例的相等比较
+//!
+//! `a is b` 比较引用（指针相等，可与 `none` 比较）。`a == b` 比较值：类（或其父类）定义了
+//! `__eq__` 时调用它（`!=` 没有 `__ne__` 时取它的反），否则经运行时的
+//! `object_equals(a, b, eq)` 调用为每个类合成的 `__default_eq__` 方法逐字段比较。
+//! 运行时先处理 nil 和同一对象，再负责循环引用和嵌套深度（见 `object_equals`）。
+//!
+//! 字段的比较方式：int/float/bool/str/bigint/decimal 按值，类实例字段递归用 `==`，
+//! weak/unowned 字段和列表、字典等其余类型按引用（`is`）。
+//! 两边是不同的类时按最近的共同父类的字段比较，没有共同父类时报错。
+
+use bolide_parser::{BinOp, Expr, FuncDef, IfStmt, Param, ParamMode, Program, Statement, Type as BolideType};
+
+/// 用户定义的相等方法名
+pub(crate) const USER_EQ: &str = "__eq__";
+/// 合成的逐字段比较方法名
+pub(crate) const DEFAULT_EQ: &str = "__default_eq__";
+
+/// 为每个类追加 `__default_eq__` 方法
+///
+/// `fields_of` 返回类的全部字段（含继承的），与对象布局顺序一致。
+pub(crate) fn add_default_comparators(program: &mut Program, fields_of: impl Fn(&str) -> Vec<(String, BolideType)>) {
+    for stmt in &mut program.statements {
+        if let Statement::ClassDef(class) = stmt {
+            let fields = fields_of(&class.name);
+            let method = default_eq_method(&class.name, &fields);
+            class.methods.push(method);
+        }
+    }
+}
+
+/// 合成 `fn __default_eq__(other: Class) -> bool`：逐字段比较，第一个不同的字段处返回 false
+fn default_eq_method(class_name: &str, fields: &[(String, BolideType)]) -> FuncDef {
+    let mut body: Vec<Statement> = fields.iter().map(|(name, ty)| {
+        let op = if compares_by_value(ty) { BinOp::Ne } else { BinOp::IsNot };
+        let field_of = |obj: &str| Expr::Member(Box::new(Expr::Ident(obj.to_string())), name.clone());
+        Statement::If(IfStmt {
+            condition: Expr::BinOp(Box::new(field_of("self")), op, Box::new(field_of("other"))),
+            then_body: vec![Statement::Return(Some(Expr::Bool(false)))],
+            elif_branches: vec![],
+            else_body: None,
+        })
+    }).collect();
+    body.push(Statement::Return(Some(Expr::Bool(true))));
+
+    FuncDef {
+        name: DEFAULT_EQ.to_string(),
+        is_async: false,
+        params: vec![Param {
+            name: "other".to_string(),
+            ty: BolideType::Custom(class_name.to_string()),
+            mode: ParamMode::Borrow,
+            default: None,
+        }],
+        return_type: Some(BolideType::Bool),
+        lifetime_deps: None,
+        body,
+        pure: false,
+        trace: None,
+        hot_entry: false,
+        line: 0,
+    }
+}
+
+/// 字段按值（`==`）比较；其余按引用（`is`）
+fn compares_by_value(ty: &BolideType) -> bool {
+    matches!(ty,
+        BolideType::Int | BolideType::Float | BolideType::Bool | BolideType::Str
+        | BolideType::BigInt | BolideType::Decimal | BolideType::Custom(_))
+}
+
+/// `is` 的操作数必须是引用（`none` 字面量除外）
+pub(crate) fn check_identity_operand(expr: &Expr, ty: Option<&BolideType>) -> Result<(), String> {
+    if matches!(expr, Expr::None) {
+        return Ok(());
+    }
+    match ty {
+        Some(ty @ (BolideType::Int | BolideType::Float | BolideType::Bool)) => Err(format!(
+            "`is` compares references (class instances, lists, dicts, strings); use == to compare {:?} values",
+            ty
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// 两个类最近的共同父类（包括它们自己）
+pub(crate) fn common_class(left: &str, right: &str, parent_of: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let mut ancestors = vec![left.to_string()];
+    while let Some(parent) = parent_of(ancestors.last().unwrap()) {
+        // 继承链有环时停下
+        if ancestors.contains(&parent) {
+            break;
+        }
+        ancestors.push(parent);
+    }
+    let mut visited: Vec<String> = Vec::new();
+    let mut current = Some(right.to_string());
+    while let Some(name) = current {
+        if ancestors.contains(&name) {
+            return Some(name);
+        }
+        if visited.contains(&name) {
+            break;
+        }
+        current = parent_of(&name);
+        visited.push(name);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_eq_layout() {
+        let fields = vec![
+            ("x".to_string(), BolideType::Float),
+            ("next".to_string(), BolideType::Custom("Node".to_string())),
+            ("prev".to_string(), BolideType::Weak(Box::new(BolideType::Custom("Node".to_string())))),
+            ("tags".to_string(), BolideType::List(Box::new(BolideType::Str))),
+        ];
+        let method = default_eq_method("Node", &fields);
+        assert!(matches!(&method.params[0].ty, BolideType::Custom(c) if c == "Node"));
+
+        let checks: Vec<(String, &str)> = method.body.iter().filter_map(|stmt| match stmt {
+            Statement::If(if_stmt) => match &if_stmt.condition {
+                Expr::BinOp(left, op, _) => match left.as_ref() {
+                    Expr::Member(_, field) => Some((field.clone(), if matches!(op, BinOp::Ne) { "!=" } else { "is not" })),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        }).collect();
+        assert_eq!(checks, [
+            ("x".to_string(), "!="),
+            ("next".to_string(), "!="),
+            ("prev".to_string(), "is not"),
+            ("tags".to_string(), "is not"),
+        ]);
+        assert!(matches!(method.body.last(), Some(Statement::Return(Some(Expr::Bool(true))))));
+    }
+
+    #[test]
+    fn test_common_class() {
+        let parent_of = |name: &str| match name {
+            "Dog" | "Cat" => Some("Animal".to_string()),
+            "Puppy" => Some("Dog".to_string()),
+            _ => None,
+        };
+        assert_eq!(common_class("Puppy", "Cat", parent_of).as_deref(), Some("Animal"));
+        assert_eq!(common_class("Dog", "Puppy", parent_of).as_deref(), Some("Dog"));
+        assert_eq!(common_class("Dog", "Point", parent_of), None);
+    }
+}
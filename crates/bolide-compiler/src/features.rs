@@ -0,0 +1,55 @@
+//! 编译器支持的语言特性表（`has_feature()`）
+//!
+//! 新特性合入时在这里加上它的名字，旧版本的 `bolide` 没有这一项，
+//! 脚本和测试套件可以据此跳过当前版本还不支持的功能。
+//! JIT 创建时直接注册到运行时；AOT 把表写进数据段，由入口 `main` 注册。
+
+/// 特性名，小写、用 `-` 连接
+pub const FEATURES: &[&str] = &[
+    "async-io",
+    "break-continue",
+    "chained-comparisons",
+    "compound-assignment",
+    "coroutine-workers",
+    "default-args",
+    "destructuring",
+    "exit",
+    "float-list",
+    "float-range",
+    "fstrings",
+    "intern",
+    "keyword-args",
+    "lambdas",
+    "map-filter-reduce",
+    "match",
+    "packed-list",
+    "pure-functions",
+    "recoverable-errors",
+    "slicing",
+    "trace-attribute",
+    "typed-channels",
+    "version-detection",
+    "weak-dict",
+];
+
+/// AOT 数据段里的特性表：以 `\n` 分隔的特性名
+pub(crate) fn feature_blob() -> String {
+    FEATURES.join("\n")
+}
+
+/// 把特性表注册到运行时
+pub(crate) fn register() {
+    bolide_runtime::register_features(FEATURES.iter().copied());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feature_names() {
+        assert!(FEATURES.windows(2).all(|pair| pair[0] < pair[1]), "keep FEATURES sorted and unique");
+        assert!(FEATURES.iter().all(|name| !name.is_empty()
+            && name.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')));
+    }
+}
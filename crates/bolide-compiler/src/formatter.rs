@@ -0,0 +1,165 @@
+//! 类实例的字符串表示
+//!
+//! `print(p)` / `str(p)` 调用运行时的 `object_to_string(obj, fmt)`，`fmt` 在编译期选出：
+//! 类（或其父类）定义了 `__str__` 方法时用它，否则用为每个类合成的
+//! `__default_str__` 方法（全名 `ClassName___default_str__`），输出 `Point { x: 3, y: 4 }`。
+//! 合成方法和普通方法一样声明、编译，字段按 `str()` 的规则转换，
+//! 拼接产生的临时字符串由语句结束时的释放逻辑回收。
+//!
+//! 对象头部没有类型信息，格式化函数只能按静态类型选择：子类实例存在父类类型的变量里时，
+//! 按父类格式化。运行时负责 nil、循环引用和嵌套深度（见 `object_to_string`）。
+
+use std::collections::HashMap;
+
+use bolide_parser::{BinOp, Expr, FuncDef, Program, Statement, Type as BolideType};
+
+use crate::modules::ImportedModule;
+
+/// 用户定义的格式化方法名
+pub(crate) const USER_STR: &str = "__str__";
+/// 合成的默认格式化方法名
+pub(crate) const DEFAULT_STR: &str = "__default_str__";
+
+/// 为每个类追加 `__default_str__` 方法
+///
+/// `fields_of` 返回类的全部字段（含继承的），与对象布局顺序一致。
+pub(crate) fn add_default_formatters(
+    program: &mut Program,
+    modules: &HashMap<String, ImportedModule>,
+    fields_of: impl Fn(&str) -> Vec<(String, BolideType)>,
+) {
+    for stmt in &mut program.statements {
+        if let Statement::ClassDef(class) = stmt {
+            let fields = fields_of(&class.name);
+            let method = default_str_method(&display_name(&class.name, modules), &fields);
+            class.methods.push(method);
+        }
+    }
+}
+
+/// 输出中的类名：导入模块的类 `@util_Point` 显示为 `util.Point`
+pub(crate) fn display_name(class_name: &str, modules: &HashMap<String, ImportedModule>) -> String {
+    if let Some(rest) = class_name.strip_prefix('@') {
+        for module in modules.keys() {
+            if let Some(name) = rest.strip_prefix(module.as_str()).and_then(|r| r.strip_prefix('_')) {
+                return format!("{}.{}", module, name);
+            }
+        }
+    }
+    class_name.to_string()
+}
+
+/// 合成 `fn __default_str__() -> str { return "Name { a: " + str(self.a) + ", ... }"; }`
+fn default_str_method(display_name: &str, fields: &[(String, BolideType)]) -> FuncDef {
+    let mut text = format!("{} {{", display_name);
+    let mut parts: Vec<Expr> = Vec::new();
+    for (i, (name, ty)) in fields.iter().enumerate() {
+        text.push_str(if i == 0 { " " } else { ", " });
+        text.push_str(name);
+        text.push_str(": ");
+        match field_str(name, ty) {
+            Some(expr) => {
+                parts.push(Expr::String(std::mem::take(&mut text)));
+                parts.push(expr);
+            }
+            None => {
+                text.push('<');
+                text.push_str(type_label(ty));
+                text.push('>');
+            }
+        }
+    }
+    text.push_str(if fields.is_empty() { "}" } else { " }" });
+    parts.push(Expr::String(text));
+
+    let body = parts.into_iter()
+        .reduce(|acc, part| Expr::BinOp(Box::new(acc), BinOp::Add, Box::new(part)))
+        .unwrap_or_else(|| Expr::String(String::new()));
+
+    FuncDef {
+        name: DEFAULT_STR.to_string(),
+        is_async: false,
+        params: vec![],
+        return_type: Some(BolideType::Str),
+        lifetime_deps: None,
+        body: vec![Statement::Return(Some(body))],
+        pure: false,
+        trace: None,
+        hot_entry: false,
+        line: 0,
+    }
+}
+
+/// 字段的 `str(self.field)`；`str()` 不支持的类型返回 None，输出类型占位
+fn field_str(name: &str, ty: &BolideType) -> Option<Expr> {
+    let field = Expr::Member(Box::new(Expr::Ident("self".to_string())), name.to_string());
+    str_expr(field, ty)
+}
+
+/// `ty` 类型的值 `value` 的 `str(value)`；`str()` 不支持的类型返回 None
+pub(crate) fn str_expr(value: Expr, ty: &BolideType) -> Option<Expr> {
+    let supported = match ty {
+        BolideType::Int | BolideType::Float | BolideType::Bool | BolideType::Str
+        | BolideType::BigInt | BolideType::Decimal | BolideType::Custom(_) => true,
+        BolideType::Weak(inner) | BolideType::Unowned(inner) => matches!(inner.as_ref(), BolideType::Custom(_)),
+        _ => false,
+    };
+    if !supported {
+        return None;
+    }
+    Some(Expr::Call(Box::new(Expr::Ident("str".to_string())), vec![value]))
+}
+
+/// 占位里的类型名，如 `<list>`
+pub(crate) fn type_label(ty: &BolideType) -> &'static str {
+    match ty {
+        BolideType::List(_) => "list",
+        BolideType::Dict(_, _) => "dict",
+        BolideType::WeakDict(_, _) => "weak_dict",
+        BolideType::Tuple(_) => "tuple",
+        BolideType::Dynamic => "dynamic",
+        BolideType::Channel(_) => "channel",
+        BolideType::Future => "future",
+        BolideType::Func | BolideType::FuncSig(_, _) => "func",
+        _ => "ptr",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_str_layout() {
+        let fields = vec![
+            ("x".to_string(), BolideType::Int),
+            ("tags".to_string(), BolideType::List(Box::new(BolideType::Str))),
+            ("next".to_string(), BolideType::Weak(Box::new(BolideType::Custom("Node".to_string())))),
+        ];
+        let method = default_str_method("Node", &fields);
+        let Statement::Return(Some(body)) = &method.body[0] else { panic!("expected return") };
+
+        // 展开左结合的加法链
+        let mut parts = Vec::new();
+        let mut cur = body;
+        while let Expr::BinOp(left, BinOp::Add, right) = cur {
+            parts.push(right.as_ref());
+            cur = left;
+        }
+        parts.push(cur);
+        parts.reverse();
+
+        let rendered: Vec<String> = parts.iter().map(|p| match p {
+            Expr::String(s) => s.clone(),
+            Expr::Call(_, args) => match &args[0] {
+                Expr::Member(_, field) => format!("str({})", field),
+                other => panic!("unexpected argument {:?}", other),
+            },
+            other => panic!("unexpected part {:?}", other),
+        }).collect();
+        assert_eq!(rendered, ["Node { x: ", "str(x)", ", tags: <list>, next: ", "str(next)", " }"]);
+
+        let empty = default_str_method("Empty", &[]);
+        assert!(matches!(&empty.body[0], Statement::Return(Some(Expr::String(s))) if s == "Empty {}"));
+    }
+}
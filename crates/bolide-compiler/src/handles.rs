@@ -0,0 +1,170 @@
+//! 同一个句柄被 join / await 两次的编译期检查
+//!
+//! `join(t)` 把线程结果的所有权交给调用方，`await f` 之后 Future 已经释放；
+//! 同一个语句块里对同一个变量再 `join` / `join_ok` / `await` 一次几乎一定是错误，编译时报告。
+//! 只看同一个语句块里按顺序执行的语句：嵌套块（分支、循环体）单独检查，
+//! 变量重新赋值或声明之后重新计数。运行时遇到的重复 join / await 见
+//! `bolide_thread_join_ptr`、`bolide_coroutine_await_ptr`。
+
+use std::collections::HashMap;
+
+use bolide_parser::{AsyncSelectBranch, Expr, FuncDef, Program, SelectBranch, Statement};
+
+use crate::purity::visit_expr;
+
+/// 检查整个程序（顶层语句、函数、方法、测试块）
+pub(crate) fn check_program(program: &Program) -> Result<(), String> {
+    check_block(&program.statements, None)
+}
+
+fn check_func(func: &FuncDef) -> Result<(), String> {
+    check_block(&func.body, Some(&func.name))
+}
+
+/// 检查一个语句块，嵌套的块各自从头计数
+fn check_block(body: &[Statement], func: Option<&str>) -> Result<(), String> {
+    // 变量名 → 第一次消耗它的操作
+    let mut consumed: HashMap<String, &'static str> = HashMap::new();
+    for stmt in body {
+        for expr in direct_exprs(stmt) {
+            let mut error = None;
+            visit_expr(expr, &mut |e| {
+                let Some((var, op)) = consumption(e) else { return };
+                match consumed.get(var) {
+                    Some(prev) if error.is_none() => error = Some(twice_error(func, var, prev, op)),
+                    Some(_) => {}
+                    None => {
+                        consumed.insert(var.to_string(), op);
+                    }
+                }
+            });
+            if let Some(error) = error {
+                return Err(error);
+            }
+        }
+        // 重新赋值后是另一个句柄
+        for var in assigned_vars(stmt) {
+            consumed.remove(var);
+        }
+        for block in nested_blocks(stmt) {
+            check_block(block, func)?;
+        }
+        match stmt {
+            Statement::FuncDef(f) => check_func(f)?,
+            Statement::ClassDef(class) => {
+                for method in &class.methods {
+                    check_func(method)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// 表达式直接 join / await 的变量，以及操作名（`join(t)`、`join_ok(t)`、`await f`）
+fn consumption(expr: &Expr) -> Option<(&str, &'static str)> {
+    match expr {
+        Expr::Call(callee, args) => match (callee.as_ref(), args.as_slice()) {
+            (Expr::Ident(name), [Expr::Ident(var)]) if name == "join" || name == "join_ok" => Some((var, "joined")),
+            _ => None,
+        },
+        Expr::Await(inner) => match inner.as_ref() {
+            Expr::Ident(var) => Some((var, "awaited")),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn twice_error(func: Option<&str>, var: &str, prev: &str, op: &str) -> String {
+    let what = if op == "awaited" { "future" } else { "handle" };
+    let again = if op == "awaited" { "awaited" } else { "joined" };
+    let location = func.map(|f| format!("in function '{}': ", f)).unwrap_or_default();
+    format!(
+        "{}{} '{}' was already {} in this block and cannot be {} again; keep the first result in a variable",
+        location, what, var, prev, again
+    )
+}
+
+/// 语句本身（不含嵌套块）求值的表达式，按执行顺序
+fn direct_exprs(stmt: &Statement) -> Vec<&Expr> {
+    match stmt {
+        Statement::VarDecl(decl) => decl.value.iter().collect(),
+        Statement::Assign(assign) => vec![&assign.value, &assign.target],
+        Statement::Destructure(d) => vec![&d.value],
+        Statement::If(s) => std::iter::once(&s.condition).chain(s.elif_branches.iter().map(|(c, _)| c)).collect(),
+        Statement::While(s) => vec![&s.condition],
+        Statement::For(s) => vec![&s.iter],
+        Statement::Pool(s) => vec![&s.size],
+        Statement::Match(s) => vec![&s.subject],
+        Statement::Send(s) => vec![&s.value],
+        Statement::Return(Some(value)) | Statement::Expr(value) => vec![value],
+        _ => Vec::new(),
+    }
+}
+
+/// 语句重新绑定的变量
+fn assigned_vars(stmt: &Statement) -> Vec<&str> {
+    match stmt {
+        Statement::VarDecl(decl) => vec![&decl.name],
+        Statement::Assign(assign) => match &assign.target {
+            Expr::Ident(name) => vec![name],
+            _ => Vec::new(),
+        },
+        Statement::Destructure(d) => d.targets.iter().flatten().map(String::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 语句里嵌套的语句块
+fn nested_blocks(stmt: &Statement) -> Vec<&[Statement]> {
+    match stmt {
+        Statement::If(s) => std::iter::once(s.then_body.as_slice())
+            .chain(s.elif_branches.iter().map(|(_, body)| body.as_slice()))
+            .chain(s.else_body.as_deref())
+            .collect(),
+        Statement::While(s) => vec![&s.body],
+        Statement::For(s) => vec![&s.body],
+        Statement::Pool(s) => vec![&s.body],
+        Statement::AwaitScope(s) => vec![&s.body],
+        Statement::Measure(s) => vec![&s.body],
+        Statement::Test(test) => vec![&test.body],
+        Statement::Match(s) => s.arms.iter().map(|arm| arm.body.as_slice()).chain(s.default.as_deref()).collect(),
+        Statement::Select(s) => s.branches.iter().map(|branch| match branch {
+            SelectBranch::Recv { body, .. } | SelectBranch::RecvAny { body, .. } | SelectBranch::Closed { body }
+            | SelectBranch::Default { body } | SelectBranch::Timeout { body, .. } => body.as_slice(),
+        }).collect(),
+        Statement::AsyncSelect(s) => s.branches.iter().map(|branch| match branch {
+            AsyncSelectBranch::Bind { body, .. } | AsyncSelectBranch::Expr { body, .. } => body.as_slice(),
+        }).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(source: &str) -> Result<(), String> {
+        check_program(&bolide_parser::parse_source(source).unwrap())
+    }
+
+    #[test]
+    fn test_second_join_in_same_block() {
+        let err = check("fn work() -> int { return 1; }\nfn main() { let t = spawn work(); let a = join(t); let b = join(t); }").unwrap_err();
+        assert_eq!(
+            err,
+            "in function 'main': handle 't' was already joined in this block and cannot be joined again; keep the first result in a variable"
+        );
+        let err = check("async fn work() -> int { return 1; }\nlet f = work();\nlet a = await f;\nlet b = await f;").unwrap_err();
+        assert!(err.starts_with("future 'f' was already awaited"), "{}", err);
+    }
+
+    #[test]
+    fn test_separate_blocks_and_reassignment() {
+        // 分支里各自 join 一次、重新赋值之后再 join 都不报告
+        check("fn work() -> int { return 1; }\nlet t = spawn work();\nif true { join(t); } else { join(t); }\nt = spawn work();\njoin(t);").unwrap();
+        check("fn work() -> int { return 1; }\nlet t = spawn work();\njoin(t);\nt = spawn work();\njoin(t);").unwrap();
+    }
+}
@@ -0,0 +1,289 @@
+//! 热重载：`bolide run --hot`
+//!
+//! 热重载模式下每个用户函数有一个分派槽（8 字节的可写数据段），存着函数当前版本的地址。
+//! 按名字的直接调用、把函数当作值取地址、不带参数的 spawn 和 spawn 的 trampoline 都先从槽里
+//! 读出地址再间接调用，每次调用多一次 load；不在热重载模式时照常直接调用。
+//!
+//! 重新载入时比较每个函数定义（不含行号）的哈希，把函数体改变的函数编译成新的匿名函数，
+//! 全部编译成功后依次把新地址写入槽：之后的调用进入新代码，正在执行的调用跑完旧代码。
+//! 全局变量的数据段不变，值在替换前后保留。
+//!
+//! 只有签名不变的函数可以替换。类（包括方法）、extern 块、全局变量、import、async 函数、
+//! 入口函数的改变，以及增删函数都需要重新启动；顶层语句只在启动时运行一次，修改后不会再运行。
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bolide_parser::{FuncDef, Program, Statement};
+use cranelift::prelude::*;
+use cranelift_codegen::ir::{FuncRef, Inst};
+use cranelift_jit::JITModule;
+use cranelift_module::{DataId, Module};
+
+/// 没有函数标注 `@hot_entry` 时作为入口的函数名
+pub(crate) const DEFAULT_ENTRY: &str = "tick";
+
+/// 可以替换的函数
+pub(crate) struct HotFunction {
+    /// 分派槽
+    pub slot: DataId,
+    /// 定义的哈希，用于判断函数体是否改变
+    hash: i64,
+    /// 参数（名字、类型、传递方式、默认值）、返回类型和生命周期依赖
+    signature: String,
+}
+
+/// 热重载模式的状态
+#[derive(Default)]
+pub(crate) struct HotState {
+    /// 函数名 -> 可以替换的函数
+    pub functions: HashMap<String, HotFunction>,
+    /// 不能替换的定义：描述（如 `class 'Point'`）-> 内容的哈希
+    fixed: HashMap<String, i64>,
+    /// 入口函数，返回值是否为 bool（为 false 时停止）
+    pub entry: Option<(String, bool)>,
+    /// 尝试过的重新载入次数；非零时新版本的函数声明为匿名符号
+    pub reloads: usize,
+}
+
+impl HotState {
+    /// 记录启动时的定义；`slot` 为每个可以替换的函数分配分派槽
+    pub fn record(
+        &mut self,
+        program: &Program,
+        mut slot: impl FnMut(&str) -> Result<DataId, String>,
+    ) -> Result<(), String> {
+        self.entry = Some(find_entry(program)?);
+        self.fixed = fixed_items(program);
+        for func in hot_functions(program) {
+            let slot = slot(&func.name)?;
+            self.functions.insert(func.name.clone(), HotFunction {
+                slot,
+                hash: definition_hash(func),
+                signature: signature(func),
+            });
+        }
+        Ok(())
+    }
+
+    /// 函数名 -> 分派槽（编译函数时使用）
+    pub fn slots(&self) -> HashMap<String, DataId> {
+        self.functions.iter().map(|(name, func)| (name.clone(), func.slot)).collect()
+    }
+
+    /// 新程序里函数体改变的函数，按定义顺序；有不能替换的改变时返回错误并列出全部改变
+    pub fn changed_functions<'p>(&self, program: &'p Program) -> Result<Vec<&'p FuncDef>, String> {
+        let mut problems = Vec::new();
+        if self.entry.as_ref().map(|(name, _)| name) != find_entry(program).ok().as_ref().map(|(name, _)| name) {
+            problems.push("the entry function changed".to_string());
+        }
+
+        let fixed = fixed_items(program);
+        let mut items: Vec<&String> = self.fixed.keys().chain(fixed.keys()).collect();
+        items.sort();
+        items.dedup();
+        for item in items {
+            match (self.fixed.get(item), fixed.get(item)) {
+                (Some(old), Some(new)) if old != new => problems.push(format!("{} changed", item)),
+                (Some(_), None) => problems.push(format!("{} removed", item)),
+                (None, Some(_)) => problems.push(format!("{} added", item)),
+                _ => {}
+            }
+        }
+
+        let mut changed = Vec::new();
+        let functions = hot_functions(program);
+        for func in &functions {
+            match self.functions.get(&func.name) {
+                None => problems.push(format!("function '{}' added", func.name)),
+                Some(old) if old.signature != signature(func) => {
+                    problems.push(format!("function '{}' changed its signature", func.name));
+                }
+                Some(old) if old.hash != definition_hash(func) => changed.push(*func),
+                Some(_) => {}
+            }
+        }
+        let mut removed: Vec<&String> = self.functions.keys()
+            .filter(|name| !functions.iter().any(|func| func.name == **name))
+            .collect();
+        removed.sort();
+        problems.extend(removed.into_iter().map(|name| format!("function '{}' removed", name)));
+
+        if problems.is_empty() {
+            Ok(changed)
+        } else {
+            Err(format!("{}; restart to apply these changes", problems.join(", ")))
+        }
+    }
+
+    /// 记下替换后的定义
+    pub fn update(&mut self, func: &FuncDef) {
+        if let Some(hot) = self.functions.get_mut(&func.name) {
+            hot.hash = definition_hash(func);
+        }
+    }
+}
+
+/// 可以替换的函数：顶层的非 async 函数（包括导入模块的函数和提升后的匿名函数）
+fn hot_functions(program: &Program) -> Vec<&FuncDef> {
+    program.statements.iter()
+        .filter_map(|stmt| match stmt {
+            Statement::FuncDef(func) if !func.is_async => Some(func),
+            _ => None,
+        })
+        .collect()
+}
+
+/// 入口函数：标注 `@hot_entry` 的函数，没有时是 `tick`；不带参数，不返回值或返回 bool
+fn find_entry(program: &Program) -> Result<(String, bool), String> {
+    let funcs: Vec<&FuncDef> = hot_functions(program);
+    let marked: Vec<&&FuncDef> = funcs.iter().filter(|func| func.hot_entry).collect();
+    let entry = match marked.as_slice() {
+        [] => funcs.iter().find(|func| func.name == DEFAULT_ENTRY)
+            .ok_or_else(|| format!("--hot needs a function named '{}' or marked @hot_entry", DEFAULT_ENTRY))?,
+        [func] => *func,
+        [first, second, ..] => {
+            return Err(format!("only one function can be marked @hot_entry ('{}' and '{}' are)", first.name, second.name));
+        }
+    };
+    let returns_bool = match &entry.return_type {
+        None => false,
+        Some(bolide_parser::Type::Bool) => true,
+        Some(_) => return Err(format!("hot entry '{}' must return nothing or bool", entry.name)),
+    };
+    if !entry.params.is_empty() {
+        return Err(format!("hot entry '{}' cannot take parameters", entry.name));
+    }
+    Ok((entry.name.clone(), returns_bool))
+}
+
+/// 不能替换的定义
+fn fixed_items(program: &Program) -> HashMap<String, i64> {
+    let mut items = HashMap::new();
+    let mut externs = 0;
+    for stmt in &program.statements {
+        let item = match stmt {
+            Statement::ClassDef(class) => format!("class '{}'", class.name),
+            Statement::VarDecl(decl) => format!("global '{}'", decl.name),
+            Statement::FuncDef(func) if func.is_async => format!("async function '{}'", func.name),
+            Statement::ExternBlock(block) => {
+                externs += 1;
+                format!("extern block #{} (\"{}\")", externs, block.lib_path)
+            }
+            Statement::Import(import) => format!("import of '{}'", import.path.join(".")),
+            _ => continue,
+        };
+        items.insert(item, text_hash(&format!("{:?}", stmt)));
+    }
+    items
+}
+
+fn text_hash(text: &str) -> i64 {
+    bolide_runtime::hash_bytes(text.as_bytes())
+}
+
+/// 定义的哈希；行号不算在内，在函数前面插入几行不会让它显得改变
+fn definition_hash(func: &FuncDef) -> i64 {
+    text_hash(&format!("{:?}", FuncDef { line: 0, ..func.clone() }))
+}
+
+fn signature(func: &FuncDef) -> String {
+    format!("{:?} -> {:?} {:?}", func.params, func.return_type, func.lifetime_deps)
+}
+
+/// 读出分派槽里函数当前版本的地址
+pub(crate) fn emit_slot_load(builder: &mut FunctionBuilder, module: &mut JITModule, slot: DataId) -> Value {
+    let ptr_type = module.target_config().pointer_type();
+    let gv = module.declare_data_in_func(slot, builder.func);
+    let addr = builder.ins().global_value(ptr_type, gv);
+    builder.ins().load(ptr_type, MemFlags::trusted(), addr, 0)
+}
+
+/// 经分派槽调用函数；签名取自 `callee` 在当前函数里的声明
+pub(crate) fn emit_slot_call(
+    builder: &mut FunctionBuilder,
+    module: &mut JITModule,
+    slot: DataId,
+    callee: FuncRef,
+    args: &[Value],
+) -> Inst {
+    let sig = builder.func.dfg.ext_funcs[callee].signature;
+    let target = emit_slot_load(builder, module, slot);
+    builder.ins().call_indirect(sig, target, args)
+}
+
+/// 分派槽在内存里的单元（`finalize_definitions` 之后才有效）
+fn slot_cell(module: &JITModule, slot: DataId) -> &AtomicUsize {
+    let (ptr, size) = module.get_finalized_data(slot);
+    debug_assert_eq!(size, 8);
+    // 槽是按 8 字节对齐的可写数据段，只通过原子操作访问
+    unsafe { &*(ptr as *const AtomicUsize) }
+}
+
+/// 把函数地址写入分派槽
+pub(crate) fn store_slot(module: &JITModule, slot: DataId, code: *const u8) {
+    slot_cell(module, slot).store(code as usize, Ordering::Release);
+}
+
+/// 分派槽里函数当前版本的地址
+pub(crate) fn load_slot(module: &JITModule, slot: DataId) -> *const u8 {
+    slot_cell(module, slot).load(Ordering::Acquire) as *const u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn functions(source: &str) -> HotState {
+        let mut state = HotState::default();
+        let mut next = 0;
+        state.record(&bolide_parser::parse_source(source).unwrap(), |_| {
+            next += 1;
+            Ok(DataId::from_u32(next))
+        }).unwrap();
+        state
+    }
+
+    fn changes(state: &HotState, source: &str) -> Result<Vec<String>, String> {
+        let program = bolide_parser::parse_source(source).unwrap();
+        state.changed_functions(&program).map(|funcs| funcs.iter().map(|func| func.name.clone()).collect())
+    }
+
+    #[test]
+    fn test_changed_functions() {
+        let base = "let n: int = 0;\nfn step() -> int {\n    return 1;\n}\nfn tick() -> bool {\n    n = n + step();\n    return true;\n}\n";
+        let state = functions(base);
+        assert_eq!(state.entry, Some(("tick".to_string(), true)));
+        assert_eq!(changes(&state, base), Ok(vec![]));
+        // 只挪动行号不算改变；顶层语句的改变不影响
+        assert_eq!(changes(&state, &format!("\n\nprint(1);\n{}", base)), Ok(vec![]));
+        assert_eq!(changes(&state, &base.replace("return 1;", "return 2;")), Ok(vec!["step".to_string()]));
+
+        for (edited, expected) in [
+            (base.replace("fn step() -> int", "fn step(k: int) -> int"), "function 'step' changed its signature"),
+            (base.replace("let n: int = 0;", "let n: int = 5;"), "global 'n' changed"),
+            (format!("{}fn helper() {{}}\n", base), "function 'helper' added"),
+            (format!("class P {{ x: int; }}\n{}", base), "class 'P' added"),
+            (base.replace("fn tick() -> bool", "@hot_entry\nfn tick() -> bool"), ""),
+            (base.replace("fn tick()", "fn frame()"), "the entry function changed"),
+        ] {
+            match changes(&state, &edited) {
+                Err(err) if !expected.is_empty() => {
+                    assert!(err.contains(expected) && err.ends_with("restart to apply these changes"), "{}", err);
+                }
+                result => assert_eq!(result, Ok(vec!["tick".to_string()]), "{}", edited),
+            }
+        }
+    }
+
+    #[test]
+    fn test_entry() {
+        let entry = |source: &str| find_entry(&bolide_parser::parse_source(source).unwrap());
+        assert_eq!(entry("fn tick() {}\n"), Ok(("tick".to_string(), false)));
+        assert_eq!(entry("fn tick() {}\n@hot_entry\nfn frame() -> bool { return true; }\n"), Ok(("frame".to_string(), true)));
+        assert!(entry("fn main() {}\n").unwrap_err().contains("named 'tick' or marked @hot_entry"));
+        assert!(entry("fn tick(dt: float) {}\n").unwrap_err().contains("cannot take parameters"));
+        assert!(entry("fn tick() -> int { return 0; }\n").unwrap_err().contains("must return nothing or bool"));
+        assert!(entry("@hot_entry\nfn a() {}\n@hot_entry\nfn b() {}\n").unwrap_err().contains("only one function"));
+    }
+}
@@ -0,0 +1,114 @@
+//! 内部编译器错误 (ICE) 处理
+//!
+//! Cranelift 校验失败或编译器自身 panic 时，不把整页 IR 直接打到终端，
+//! 而是把复现材料写入临时目录，只给用户一条简短提示。
+//! 设置 `BOLIDE_ICE_FULL=1` 可恢复完整的内联输出，便于开发调试。
+
+use std::any::Any;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use cranelift_codegen::CodegenError;
+use cranelift_codegen::ir::Function;
+use cranelift_module::ModuleError;
+
+/// 问题反馈地址
+const ISSUE_URL: &str = "https://github.com/streetartist/bolide/issues";
+
+/// 被编译的源文件（写入复现包）
+#[derive(Clone)]
+pub(crate) struct IceSource {
+    pub path: String,
+    pub text: String,
+}
+
+/// 一次 ICE 的全部材料
+pub(crate) struct IceReport<'a> {
+    /// 出错的函数名
+    pub func_name: &'a str,
+    /// 错误详情（校验器输出或 panic 信息）
+    pub detail: String,
+    /// 出错函数的 CLIF
+    pub clif: String,
+    /// 出错函数的 AST
+    pub ast: String,
+}
+
+/// 是否为校验器类错误
+pub(crate) fn is_verifier_error(err: &ModuleError) -> bool {
+    matches!(err, ModuleError::Compilation(CodegenError::Verifier(_)))
+}
+
+/// 生成错误详情，校验器错误会附带标注过的 IR
+pub(crate) fn error_detail(err: &ModuleError, func: &Function) -> String {
+    match err {
+        ModuleError::Compilation(CodegenError::Verifier(errors)) => {
+            cranelift_codegen::print_errors::pretty_verifier_error(func, None, errors.clone())
+        }
+        _ => format!("{}", err),
+    }
+}
+
+/// 从 panic 载荷中取出消息
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// 是否要求完整内联输出
+fn full_dump_requested() -> bool {
+    std::env::var("BOLIDE_ICE_FULL").map(|v| v == "1").unwrap_or(false)
+}
+
+/// 写入复现包，返回目录路径
+fn write_bundle(report: &IceReport, source: Option<&IceSource>) -> std::io::Result<PathBuf> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let dir = std::env::temp_dir().join(format!("bolide-ice-{}-{}", std::process::id(), stamp));
+    fs::create_dir_all(&dir)?;
+
+    let source_text = match source {
+        Some(src) => format!("// {}\n{}", src.path, src.text),
+        None => "// source not available\n".to_string(),
+    };
+    fs::write(dir.join("source.bl"), source_text)?;
+    fs::write(dir.join("function.clif"), &report.clif)?;
+    fs::write(dir.join("ast.txt"), &report.ast)?;
+    fs::write(dir.join("error.txt"), format!("function: {}\n\n{}\n", report.func_name, report.detail))?;
+    fs::write(dir.join("version.txt"), format!("bolide-compiler {}\n", env!("CARGO_PKG_VERSION")))?;
+
+    Ok(dir)
+}
+
+/// 处理一次 ICE：写入复现包并返回给用户看的错误消息
+pub(crate) fn report(report: &IceReport, source: Option<&IceSource>) -> String {
+    let header = format!("internal compiler error in function `{}`", report.func_name);
+
+    match write_bundle(report, source) {
+        Ok(dir) => {
+            let mut msg = format!(
+                "{}\n  reproduction bundle: {}\n  please file an issue at {} and attach the bundle\n  (set BOLIDE_ICE_FULL=1 to print the full dump inline)",
+                header,
+                dir.display(),
+                ISSUE_URL,
+            );
+            if full_dump_requested() {
+                msg.push_str(&format!("\n\n{}\n\n{}", report.detail, report.clif));
+            }
+            msg
+        }
+        // 写不了复现包时退回到内联输出，至少不丢信息
+        Err(e) => format!(
+            "{}\n  failed to write reproduction bundle: {}\n\n{}\n\n{}",
+            header, e, report.detail, report.clif
+        ),
+    }
+}
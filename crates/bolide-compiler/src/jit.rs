@@ -5,12 +5,40 @@
 use cranelift::prelude::*;
 use cranelift::prelude::isa::{TargetIsa, CallConv};
 use cranelift_jit::{JITBuilder, JITModule};
-use cranelift_module::{DataDescription, Linkage, Module, FuncId};
-use cranelift_codegen::ir::{FuncRef, StackSlotData, StackSlotKind};
+use cranelift_module::{DataDescription, Linkage, Module, ModuleError, ModuleResult, FuncId};
+use cranelift_codegen::ir::{FuncRef, Inst, StackSlotData, StackSlotKind};
+use cranelift_frontend::Switch;
 use std::collections::{HashMap, HashSet};
-use bolide_parser::{Program, Statement, Expr, BinOp, UnaryOp, Type as BolideType, FuncDef, VarDecl, Assign, Param, ParamMode, ClassDef, ClassField, ExternBlock};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use bolide_parser::{Program, Statement, Expr, BinOp, UnaryOp, Type as BolideType, FuncDef, VarDecl, Assign, Destructure, Param, ParamMode, ClassDef, ClassField, ExternBlock, MatchPattern};
+use crate::formatter;
+use crate::call_trace;
+use crate::lambda;
+use crate::equality;
+use crate::class_graph;
+use crate::list_literal;
+use crate::packed::{self, PackedLayout};
+use crate::ice::{self, IceReport, IceSource};
+use crate::analysis::{self, AnalysisReport, CallKind, FunctionReport};
+use crate::purity;
+use crate::bind;
+use crate::handles;
+use crate::optimize;
+use crate::string_fold;
+use crate::rc_peephole;
+use crate::comptime::{self, Const};
+use crate::compound;
+use crate::call_args;
+use crate::features;
+use crate::overflow;
+use crate::duration;
+use crate::hot::{self, HotState};
+use crate::test_blocks;
+use crate::modules::{self, ImportedModule, Member};
 
 /// Trampoline 信息
+#[derive(Clone)]
 struct TrampolineInfo {
     func_id: FuncId,
     param_types: Vec<BolideType>,
@@ -22,7 +50,8 @@ struct TrampolineInfo {
 struct FieldInfo {
     name: String,
     ty: BolideType,
-    offset: usize,  // 字段在对象中的偏移（字节）
+    offset: usize,  // 字段相对对象指针（数据部分起点，对象头之后）的偏移（字节）
+    default: Option<Expr>,  // 字段初始值，构造时省略该字段则使用
 }
 
 /// 类信息
@@ -32,11 +61,43 @@ struct ClassInfo {
     parent: Option<String>,
     fields: Vec<FieldInfo>,
     methods: Vec<String>,  // 方法名列表
-    size: usize,  // 对象数据大小（字节，不含头部）
+    size: usize,  // 对象数据大小（字节，不含头部），即 object_alloc 的参数
+}
+
+/// 程序结束时释放 RC 全局变量的函数（见 `JitCompiler::release_globals`）
+const GLOBALS_RELEASE: &str = "__globals_release";
+
+/// threadlocal 全局变量的初始化函数和析构函数的名字前缀（见 `bolide_runtime` 的 tls 模块）
+const TLS_PREFIX: &str = "__tls_";
+const TLS_INIT_PREFIX: &str = "__tls_init_";
+const TLS_DROP_PREFIX: &str = "__tls_drop_";
+
+/// 正在编译的 while/for 循环（break/continue 的跳转目标）
+struct LoopFrame {
+    /// loop_count() 计数器
+    counter: Variable,
+    /// 循环条件或循环体用到了 loop_count()
+    counter_used: bool,
+    /// continue 的目标：推进循环变量后回到循环头
+    continue_block: Block,
+    /// break 的目标
+    exit_block: Block,
+    /// 有 continue 跳到 continue_block
+    continued: bool,
+    /// 进入循环时 measure_stack 的深度，跳出循环体时结束更深的 measure 块
+    measure_depth: usize,
+    /// 进入循环时 pool_stack 的深度，跳出循环体时拆除更深的 pool 块
+    pool_depth: usize,
+    /// 进入循环时的作用域深度
+    scope_depth: usize,
+    /// 循环里用过的借用变量（packed 列表在循环里被修改时检查）
+    borrows_used: HashSet<String>,
 }
 
 /// JIT 编译器
 pub struct JitCompiler {
+    /// 编译器 drop 时 cranelift-jit 不释放代码内存（除非显式调用 `free_memory`，
+    /// 这里从不调用），所以编译器释放后仍在运行的线程可以继续执行编译好的代码
     module: JITModule,
     ctx: codegen::Context,
     data_desc: DataDescription,
@@ -58,16 +119,48 @@ pub struct JitCompiler {
     async_funcs: HashSet<String>,
     /// extern 函数信息: 函数名 -> (库路径, 函数声明)
     extern_funcs: HashMap<String, (String, bolide_parser::ExternFunc)>,
-    /// 已加载的动态库
-    loaded_libs: HashMap<String, libloading::Library>,
-    /// 模块名映射: 模块名 -> 文件路径
-    modules: HashMap<String, String>,
+    /// 导入的模块: 模块名 -> 路径和导出的名字
+    modules: HashMap<String, ImportedModule>,
     /// 使用生命周期模式的函数集合（返回借用而非拥有的值）
     lifetime_funcs: HashSet<String>,
     /// 全局变量名 -> 数据ID 映射
     global_data_ids: HashMap<String, cranelift_module::DataId>,
     /// 全局变量类型映射
     global_var_types: HashMap<String, BolideType>,
+    /// `threadlocal let` 声明的全局变量，数据段里存的是运行时的 TLS 槽号
+    thread_locals: HashSet<String>,
+    /// 本次编译新声明的 threadlocal 变量，`__main__` 开头为它们分配槽
+    pending_thread_locals: Vec<VarDecl>,
+    /// 源文件（ICE 复现包使用）
+    source: Option<IceSource>,
+    /// `--analyze` 报告，启用时每个函数编译后填入
+    analysis: Option<AnalysisReport>,
+    /// 中断状态（嵌入时的时间预算和取消），设置后在循环回边和用户函数调用后生成检查
+    interrupt: Option<Arc<bolide_runtime::InterruptState>>,
+    /// 调试模式（`bolide run --debug`）：函数入口和每条返回路径上维护影子调用栈（见 `bolide_runtime::trace_lines`）
+    debug: bool,
+    /// `--no-trace`：忽略 `@trace` 标注，不生成调用日志
+    no_trace: bool,
+    /// 优化级别（`-O0` / `-O1`）：0 时不做常量折叠和死分支消除、不折叠字面量字符串表达式，
+    /// 也不对 `@pure` 调用做编译期求值、复用和提升
+    opt_level: u8,
+    /// `--share-containers`：把已有的列表、字典绑定到新位置时共享同一个容器，不再复制（见 `emit_binding_copy`）
+    share_containers: bool,
+    /// 编译警告（例如已弃用的用法），`compile` 之后由调用者打印
+    warnings: Vec<String>,
+    /// 验证通过的 `@pure` 函数：函数名 -> 定义（编译期求值用）
+    pure_funcs: HashMap<String, FuncDef>,
+    /// `compile_incremental` 编译过的输入数；非零时用户函数和全局变量声明为匿名符号
+    repl_chunks: usize,
+    /// 之前的输入里的定义（函数、类、extern 块、全局变量），只用于检查新输入的 `@pure` 函数
+    repl_definitions: Vec<Statement>,
+    /// 函数名 -> 最后一次编译出的 CLIF 文本；`set_keep_ir` 开启时才记录（REPL 的 `:ir`）
+    function_ir: Option<HashMap<String, String>>,
+    /// 热重载模式（`bolide run --hot`）：用户函数经分派槽调用，可以用 `hot_reload` 替换
+    hot: Option<HotState>,
+    /// 测试钩子：故意破坏该函数的签名以触发校验错误
+    #[cfg(test)]
+    bad_signature_for: Option<String>,
 }
 
 impl JitCompiler {
@@ -83,10 +176,37 @@ impl JitCompiler {
         builder.symbol("print_decimal", bolide_runtime::bolide_print_decimal as *const u8);
         builder.symbol("print_string", bolide_runtime::bolide_print_string as *const u8);
         builder.symbol("print_dynamic", bolide_runtime::bolide_print_dynamic as *const u8);
+        // printr：不换行的打印
+        builder.symbol("print_int_inline", bolide_runtime::bolide_print_int_inline as *const u8);
+        builder.symbol("print_float_inline", bolide_runtime::bolide_print_float_inline as *const u8);
+        builder.symbol("print_bool_inline", bolide_runtime::bolide_print_bool_inline as *const u8);
+        builder.symbol("print_bigint_inline", bolide_runtime::bolide_print_bigint_inline as *const u8);
+        builder.symbol("print_decimal_inline", bolide_runtime::bolide_print_decimal_inline as *const u8);
+        builder.symbol("print_string_inline", bolide_runtime::bolide_print_string_inline as *const u8);
+        builder.symbol("print_dynamic_inline", bolide_runtime::bolide_print_dynamic_inline as *const u8);
+        builder.symbol("print_flush", bolide_runtime::bolide_print_flush as *const u8);
 
         // 注册运行时函数 - 用户输入
         builder.symbol("input", bolide_runtime::bolide_input as *const u8);
         builder.symbol("input_prompt", bolide_runtime::bolide_input_prompt as *const u8);
+        builder.symbol("input_async", bolide_runtime::bolide_input_async as *const u8);
+        builder.symbol("file_read", bolide_runtime::bolide_file_read as *const u8);
+        builder.symbol("file_read_async", bolide_runtime::bolide_file_read_async as *const u8);
+        builder.symbol("file_write_lines", bolide_runtime::bolide_file_write_lines as *const u8);
+        builder.symbol("file_write", bolide_runtime::bolide_file_write as *const u8);
+        builder.symbol("file_append", bolide_runtime::bolide_file_append as *const u8);
+        builder.symbol("file_exists", bolide_runtime::bolide_file_exists as *const u8);
+        builder.symbol("file_lines", bolide_runtime::bolide_file_lines as *const u8);
+        builder.symbol("file_remove", bolide_runtime::bolide_file_remove as *const u8);
+        builder.symbol("temp_dir", bolide_runtime::bolide_temp_dir as *const u8);
+        builder.symbol("format_row", bolide_runtime::bolide_format_row as *const u8);
+        builder.symbol("assert", bolide_runtime::bolide_assert as *const u8);
+        builder.symbol("assert_eq_int", bolide_runtime::bolide_assert_eq_int as *const u8);
+        builder.symbol("assert_eq_float", bolide_runtime::bolide_assert_eq_float as *const u8);
+        builder.symbol("assert_eq_str", bolide_runtime::bolide_assert_eq_str as *const u8);
+        builder.symbol("assert_eq_list", bolide_runtime::bolide_assert_eq_list as *const u8);
+        builder.symbol("io_await", bolide_runtime::bolide_io_await as *const u8);
+        builder.symbol("set_io_workers", bolide_runtime::bolide_set_io_workers as *const u8);
 
         // 注册运行时函数 - BigInt
         builder.symbol("bigint_from_i64", bolide_runtime::bolide_bigint_from_i64 as *const u8);
@@ -97,6 +217,12 @@ impl JitCompiler {
         builder.symbol("bigint_div", bolide_runtime::bolide_bigint_div as *const u8);
         builder.symbol("bigint_rem", bolide_runtime::bolide_bigint_rem as *const u8);
         builder.symbol("bigint_neg", bolide_runtime::bolide_bigint_neg as *const u8);
+        builder.symbol("bigint_add_assign", bolide_runtime::bolide_bigint_add_assign as *const u8);
+        builder.symbol("bigint_add_assign_i64", bolide_runtime::bolide_bigint_add_assign_i64 as *const u8);
+        builder.symbol("bigint_sub_assign", bolide_runtime::bolide_bigint_sub_assign as *const u8);
+        builder.symbol("bigint_sub_assign_i64", bolide_runtime::bolide_bigint_sub_assign_i64 as *const u8);
+        builder.symbol("bigint_mul_assign", bolide_runtime::bolide_bigint_mul_assign as *const u8);
+        builder.symbol("bigint_mul_assign_i64", bolide_runtime::bolide_bigint_mul_assign_i64 as *const u8);
         builder.symbol("bigint_eq", bolide_runtime::bolide_bigint_eq as *const u8);
         builder.symbol("bigint_lt", bolide_runtime::bolide_bigint_lt as *const u8);
         builder.symbol("bigint_le", bolide_runtime::bolide_bigint_le as *const u8);
@@ -120,6 +246,24 @@ impl JitCompiler {
         builder.symbol("decimal_to_i64", bolide_runtime::bolide_decimal_to_i64 as *const u8);
         builder.symbol("decimal_to_f64", bolide_runtime::bolide_decimal_to_f64 as *const u8);
         builder.symbol("decimal_clone", bolide_runtime::bolide_decimal_clone as *const u8);
+        builder.symbol("decimal_round_dp_mode", bolide_runtime::bolide_decimal_round_dp_mode as *const u8);
+        builder.symbol("decimal_set_rounding", bolide_runtime::bolide_decimal_set_rounding as *const u8);
+        builder.symbol("decimal_set_div_precision", bolide_runtime::bolide_decimal_set_div_precision as *const u8);
+        builder.symbol("decimal_rem", bolide_runtime::bolide_decimal_rem as *const u8);
+        builder.symbol("decimal_le", bolide_runtime::bolide_decimal_le as *const u8);
+        builder.symbol("decimal_gt", bolide_runtime::bolide_decimal_gt as *const u8);
+        builder.symbol("decimal_ge", bolide_runtime::bolide_decimal_ge as *const u8);
+        builder.symbol("decimal_round", bolide_runtime::bolide_decimal_round as *const u8);
+        builder.symbol("decimal_floor", bolide_runtime::bolide_decimal_floor as *const u8);
+        builder.symbol("decimal_ceil", bolide_runtime::bolide_decimal_ceil as *const u8);
+        builder.symbol("decimal_from_string", bolide_runtime::bolide_decimal_from_string as *const u8);
+        builder.symbol("float_round", bolide_runtime::bolide_float_round as *const u8);
+        builder.symbol("decimal_add_assign", bolide_runtime::bolide_decimal_add_assign as *const u8);
+        builder.symbol("decimal_add_assign_i64", bolide_runtime::bolide_decimal_add_assign_i64 as *const u8);
+        builder.symbol("decimal_sub_assign", bolide_runtime::bolide_decimal_sub_assign as *const u8);
+        builder.symbol("decimal_sub_assign_i64", bolide_runtime::bolide_decimal_sub_assign_i64 as *const u8);
+        builder.symbol("decimal_mul_assign", bolide_runtime::bolide_decimal_mul_assign as *const u8);
+        builder.symbol("decimal_mul_assign_i64", bolide_runtime::bolide_decimal_mul_assign_i64 as *const u8);
 
         // 注册运行时函数 - Dynamic
         builder.symbol("dynamic_from_int", bolide_runtime::bolide_dynamic_from_int as *const u8);
@@ -145,6 +289,22 @@ impl JitCompiler {
         builder.symbol("string_as_cstr", bolide_runtime::bolide_string_as_cstr as *const u8);
         builder.symbol("string_concat", bolide_runtime::bolide_string_concat as *const u8);
         builder.symbol("string_eq", bolide_runtime::bolide_string_eq as *const u8);
+        builder.symbol("string_char_at", bolide_runtime::bolide_string_char_at as *const u8);
+        builder.symbol("string_slice", bolide_runtime::bolide_string_slice as *const u8);
+        builder.symbol("string_split", bolide_runtime::bolide_string_split as *const u8);
+        builder.symbol("string_trim", bolide_runtime::bolide_string_trim as *const u8);
+        builder.symbol("string_upper", bolide_runtime::bolide_string_upper as *const u8);
+        builder.symbol("string_lower", bolide_runtime::bolide_string_lower as *const u8);
+        builder.symbol("string_contains", bolide_runtime::bolide_string_contains as *const u8);
+        builder.symbol("string_replace", bolide_runtime::bolide_string_replace as *const u8);
+        builder.symbol("string_encode", bolide_runtime::bolide_string_encode as *const u8);
+        builder.symbol("bytes_decode", bolide_runtime::bolide_bytes_decode as *const u8);
+        builder.symbol("bytes_decode_latin1", bolide_runtime::bolide_bytes_decode_latin1 as *const u8);
+        builder.symbol("string_len", bolide_runtime::bolide_string_len as *const u8);
+        builder.symbol("string_intern", bolide_runtime::bolide_string_intern as *const u8);
+        builder.symbol("string_intern_stats", bolide_runtime::bolide_string_intern_stats as *const u8);
+        builder.symbol("string_intern_clear", bolide_runtime::bolide_string_intern_clear as *const u8);
+        builder.symbol("string_cmp", bolide_runtime::bolide_string_cmp as *const u8);
 
         // 注册类型转换函数
         builder.symbol("string_from_int", bolide_runtime::bolide_string_from_int as *const u8);
@@ -152,8 +312,23 @@ impl JitCompiler {
         builder.symbol("string_from_bool", bolide_runtime::bolide_string_from_bool as *const u8);
         builder.symbol("string_from_bigint", bolide_runtime::bolide_string_from_bigint as *const u8);
         builder.symbol("string_from_decimal", bolide_runtime::bolide_string_from_decimal as *const u8);
+        builder.symbol("string_builder_new", bolide_runtime::bolide_string_builder_new as *const u8);
+        builder.symbol("string_builder_append_str", bolide_runtime::bolide_string_builder_append_str as *const u8);
+        builder.symbol("string_builder_append_int", bolide_runtime::bolide_string_builder_append_int as *const u8);
+        builder.symbol("string_builder_append_float", bolide_runtime::bolide_string_builder_append_float as *const u8);
+        builder.symbol("string_builder_append_bool", bolide_runtime::bolide_string_builder_append_bool as *const u8);
+        builder.symbol("string_builder_append_bigint", bolide_runtime::bolide_string_builder_append_bigint as *const u8);
+        builder.symbol("string_builder_append_decimal", bolide_runtime::bolide_string_builder_append_decimal as *const u8);
+        builder.symbol("string_builder_append_dynamic", bolide_runtime::bolide_string_builder_append_dynamic as *const u8);
+        builder.symbol("string_builder_append_list", bolide_runtime::bolide_string_builder_append_list as *const u8);
+        builder.symbol("string_builder_append_dict", bolide_runtime::bolide_string_builder_append_dict as *const u8);
+        builder.symbol("string_builder_append_tuple", bolide_runtime::bolide_string_builder_append_tuple as *const u8);
+        builder.symbol("string_builder_append_packed_list", bolide_runtime::bolide_string_builder_append_packed_list as *const u8);
+        builder.symbol("string_builder_finish", bolide_runtime::bolide_string_builder_finish as *const u8);
         builder.symbol("string_to_int", bolide_runtime::bolide_string_to_int as *const u8);
         builder.symbol("string_to_float", bolide_runtime::bolide_string_to_float as *const u8);
+        builder.symbol("string_parse_int", bolide_runtime::bolide_string_parse_int as *const u8);
+        builder.symbol("string_parse_float", bolide_runtime::bolide_string_parse_float as *const u8);
 
         // 注册内存分配函数
         builder.symbol("bolide_alloc", bolide_runtime::bolide_alloc as *const u8);
@@ -163,7 +338,10 @@ impl JitCompiler {
         builder.symbol("object_alloc", bolide_runtime::object_alloc as *const u8);
         builder.symbol("object_retain", bolide_runtime::object_retain as *const u8);
         builder.symbol("object_release", bolide_runtime::object_release as *const u8);
+        builder.symbol("object_refcount", bolide_runtime::bolide_object_refcount as *const u8);
         builder.symbol("object_clone", bolide_runtime::object_clone as *const u8);
+        builder.symbol("object_to_string", bolide_runtime::object_to_string as *const u8);
+        builder.symbol("object_equals", bolide_runtime::object_equals as *const u8);
 
         // 注册运行时函数 - 线程（无参版本）
         builder.symbol("thread_spawn_int", bolide_runtime::bolide_thread_spawn_int as *const u8);
@@ -179,12 +357,35 @@ impl JitCompiler {
         builder.symbol("thread_handle_free", bolide_runtime::bolide_thread_handle_free as *const u8);
         builder.symbol("thread_cancel", bolide_runtime::bolide_thread_cancel as *const u8);
         builder.symbol("thread_is_cancelled", bolide_runtime::bolide_thread_is_cancelled as *const u8);
+        builder.symbol("thread_set_name", bolide_runtime::bolide_thread_set_name as *const u8);
+        builder.symbol("thread_take_error", bolide_runtime::bolide_thread_take_error as *const u8);
 
         // 注册运行时函数 - 线程池（无参版本）
         builder.symbol("pool_create", bolide_runtime::bolide_pool_create as *const u8);
         builder.symbol("pool_enter", bolide_runtime::bolide_pool_enter as *const u8);
-        builder.symbol("pool_exit", bolide_runtime::bolide_pool_exit as *const u8);
+        builder.symbol("pool_exit", bolide_runtime::bolide_pool_exit_checked as *const u8);
+
+        // 注册运行时函数 - 嵌入时的中断检查
+        builder.symbol("interrupt_poll", bolide_runtime::bolide_interrupt_poll as *const u8);
+        builder.symbol("interrupt_unwind", bolide_runtime::bolide_interrupt_unwind as *const u8);
+
+        // 注册运行时函数 - 调试模式的影子调用栈
+        builder.symbol("trace_push", bolide_runtime::bolide_trace_push as *const u8);
+        builder.symbol("trace_pop", bolide_runtime::bolide_trace_pop as *const u8);
         builder.symbol("pool_is_active", bolide_runtime::bolide_pool_is_active as *const u8);
+        builder.symbol("thread_id", bolide_runtime::bolide_thread_id as *const u8);
+        builder.symbol("live_threads", bolide_runtime::bolide_live_threads as *const u8);
+        builder.symbol("tls_alloc", bolide_runtime::bolide_tls_alloc as *const u8);
+        builder.symbol("tls_get", bolide_runtime::bolide_tls_get as *const u8);
+        builder.symbol("tls_thread_exit", bolide_runtime::bolide_tls_thread_exit as *const u8);
+        builder.symbol("platform", bolide_runtime::bolide_platform as *const u8);
+        builder.symbol("version", bolide_runtime::bolide_version as *const u8);
+        builder.symbol("version_at_least", bolide_runtime::bolide_version_at_least as *const u8);
+        builder.symbol("has_feature", bolide_runtime::bolide_has_feature as *const u8);
+        builder.symbol("error_code", bolide_runtime::bolide_error_code as *const u8);
+        builder.symbol("error_message", bolide_runtime::bolide_error_message as *const u8);
+        builder.symbol("error_clear", bolide_runtime::bolide_error_clear as *const u8);
+        builder.symbol("exit", bolide_runtime::bolide_exit as *const u8);
         builder.symbol("pool_spawn_int", bolide_runtime::bolide_pool_spawn_int as *const u8);
         builder.symbol("pool_spawn_float", bolide_runtime::bolide_pool_spawn_float as *const u8);
         builder.symbol("pool_spawn_ptr", bolide_runtime::bolide_pool_spawn_ptr as *const u8);
@@ -196,6 +397,8 @@ impl JitCompiler {
         builder.symbol("pool_join_float", bolide_runtime::bolide_pool_join_float as *const u8);
         builder.symbol("pool_join_ptr", bolide_runtime::bolide_pool_join_ptr as *const u8);
         builder.symbol("pool_handle_free", bolide_runtime::bolide_pool_handle_free as *const u8);
+        builder.symbol("pool_set_name", bolide_runtime::bolide_pool_set_name as *const u8);
+        builder.symbol("pool_take_error", bolide_runtime::bolide_pool_take_error as *const u8);
         builder.symbol("pool_destroy", bolide_runtime::bolide_pool_destroy as *const u8);
 
         // 注册运行时函数 - 通道
@@ -203,9 +406,17 @@ impl JitCompiler {
         builder.symbol("channel_create_buffered", bolide_runtime::bolide_channel_create_buffered as *const u8);
         builder.symbol("channel_send", bolide_runtime::bolide_channel_send as *const u8);
         builder.symbol("channel_recv", bolide_runtime::bolide_channel_recv as *const u8);
+        builder.symbol("channel_send_ptr", bolide_runtime::bolide_channel_send_ptr as *const u8);
+        builder.symbol("channel_recv_ptr", bolide_runtime::bolide_channel_recv_ptr as *const u8);
+        builder.symbol("channel_recv_opt", bolide_runtime::bolide_channel_recv_opt as *const u8);
+        builder.symbol("channel_recv_opt_ptr", bolide_runtime::bolide_channel_recv_opt_ptr as *const u8);
+        builder.symbol("channel_send_all", bolide_runtime::bolide_channel_send_all as *const u8);
+        builder.symbol("channel_recv_n", bolide_runtime::bolide_channel_recv_n as *const u8);
+        builder.symbol("channel_drain", bolide_runtime::bolide_channel_drain as *const u8);
         builder.symbol("channel_close", bolide_runtime::bolide_channel_close as *const u8);
         builder.symbol("channel_free", bolide_runtime::bolide_channel_free as *const u8);
         builder.symbol("channel_select", bolide_runtime::bolide_channel_select as *const u8);
+        builder.symbol("channel_select_list", bolide_runtime::bolide_channel_select_list as *const u8);
 
         // 注册运行时函数 - 协程
         builder.symbol("coroutine_spawn_int", bolide_runtime::bolide_coroutine_spawn_int as *const u8);
@@ -216,10 +427,14 @@ impl JitCompiler {
         builder.symbol("coroutine_await_ptr", bolide_runtime::bolide_coroutine_await_ptr as *const u8);
         builder.symbol("coroutine_cancel", bolide_runtime::bolide_coroutine_cancel as *const u8);
         builder.symbol("coroutine_free", bolide_runtime::bolide_coroutine_free as *const u8);
+        builder.symbol("coroutine_set_name", bolide_runtime::bolide_coroutine_set_name as *const u8);
+        builder.symbol("coroutine_cancelled", bolide_runtime::bolide_coroutine_cancelled as *const u8);
+        builder.symbol("coroutine_set_workers", bolide_runtime::bolide_coroutine_set_workers as *const u8);
         builder.symbol("coroutine_spawn_int_with_env", bolide_runtime::bolide_coroutine_spawn_int_with_env as *const u8);
         builder.symbol("coroutine_spawn_float_with_env", bolide_runtime::bolide_coroutine_spawn_float_with_env as *const u8);
         builder.symbol("coroutine_spawn_ptr_with_env", bolide_runtime::bolide_coroutine_spawn_ptr_with_env as *const u8);
         builder.symbol("scope_enter", bolide_runtime::bolide_scope_enter as *const u8);
+        builder.symbol("scope_enter_fail_fast", bolide_runtime::bolide_scope_enter_fail_fast as *const u8);
         builder.symbol("scope_register", bolide_runtime::bolide_scope_register as *const u8);
         builder.symbol("scope_exit", bolide_runtime::bolide_scope_exit as *const u8);
 
@@ -233,7 +448,28 @@ impl JitCompiler {
         builder.symbol("tuple_get", bolide_runtime::bolide_tuple_get as *const u8);
         builder.symbol("tuple_len", bolide_runtime::bolide_tuple_len as *const u8);
         builder.symbol("tuple_debug_stats", bolide_runtime::bolide_tuple_debug_stats as *const u8);
+        builder.symbol("string_debug_stats", bolide_runtime::bolide_string_debug_stats as *const u8);
+        builder.symbol("weak_debug_stats", bolide_runtime::bolide_weak_debug_stats as *const u8);
+        builder.symbol("object_debug_enable", bolide_runtime::bolide_object_debug_enable as *const u8);
+        builder.symbol("object_live_count", bolide_runtime::bolide_object_live_count as *const u8);
+        builder.symbol("object_dump_live", bolide_runtime::bolide_object_dump_live as *const u8);
+        builder.symbol("timer_start", bolide_runtime::bolide_timer_start as *const u8);
+        builder.symbol("timer_elapsed_ns", bolide_runtime::bolide_timer_elapsed_ns as *const u8);
+        builder.symbol("timer_elapsed_ms", bolide_runtime::bolide_timer_elapsed_ms as *const u8);
+        builder.symbol("deadline", bolide_runtime::bolide_deadline as *const u8);
+        builder.symbol("deadline_remaining", bolide_runtime::bolide_deadline_remaining as *const u8);
+        builder.symbol("string_from_duration", bolide_runtime::bolide_string_from_duration as *const u8);
+        builder.symbol("alloc_snapshot", bolide_runtime::bolide_alloc_snapshot as *const u8);
+        builder.symbol("measure_begin", bolide_runtime::bolide_measure_begin as *const u8);
+        builder.symbol("measure_end", bolide_runtime::bolide_measure_end as *const u8);
         builder.symbol("print_tuple", bolide_runtime::bolide_print_tuple as *const u8);
+        builder.symbol("hash_int", bolide_runtime::bolide_hash_int as *const u8);
+        builder.symbol("hash_float", bolide_runtime::bolide_hash_float as *const u8);
+        builder.symbol("hash_string", bolide_runtime::bolide_hash_string as *const u8);
+        builder.symbol("hash_bigint", bolide_runtime::bolide_hash_bigint as *const u8);
+        builder.symbol("hash_decimal", bolide_runtime::bolide_hash_decimal as *const u8);
+        builder.symbol("hash_dynamic", bolide_runtime::bolide_hash_dynamic as *const u8);
+        builder.symbol("hash_combine", bolide_runtime::bolide_hash_combine as *const u8);
 
         // FFI 运行时函数
         builder.symbol("ffi_load_library", bolide_runtime::bolide_ffi_load_library as *const u8);
@@ -254,11 +490,18 @@ impl JitCompiler {
         builder.symbol("list_release", bolide_runtime::bolide_list_release as *const u8);
         builder.symbol("list_clone", bolide_runtime::bolide_list_clone as *const u8);
         builder.symbol("list_new", bolide_runtime::bolide_list_new as *const u8);
+        builder.symbol("list_from_const_array", bolide_runtime::bolide_list_from_const_array as *const u8);
         builder.symbol("list_push", bolide_runtime::bolide_list_push as *const u8);
         builder.symbol("list_pop", bolide_runtime::bolide_list_pop as *const u8);
         builder.symbol("list_len", bolide_runtime::bolide_list_len as *const u8);
         builder.symbol("list_get", bolide_runtime::bolide_list_get as *const u8);
+        builder.symbol("list_iter_new", bolide_runtime::bolide_list_iter_new as *const u8);
+        builder.symbol("list_iter_next", bolide_runtime::bolide_list_iter_next as *const u8);
+        builder.symbol("list_iter_free", bolide_runtime::bolide_list_iter_free as *const u8);
         builder.symbol("list_set", bolide_runtime::bolide_list_set as *const u8);
+        builder.symbol("list_push_f64", bolide_runtime::bolide_list_push_f64 as *const u8);
+        builder.symbol("list_get_f64", bolide_runtime::bolide_list_get_f64 as *const u8);
+        builder.symbol("list_set_f64", bolide_runtime::bolide_list_set_f64 as *const u8);
         builder.symbol("list_insert", bolide_runtime::bolide_list_insert as *const u8);
         builder.symbol("list_remove", bolide_runtime::bolide_list_remove as *const u8);
         builder.symbol("list_clear", bolide_runtime::bolide_list_clear as *const u8);
@@ -268,11 +511,48 @@ impl JitCompiler {
         builder.symbol("list_index_of", bolide_runtime::bolide_list_index_of as *const u8);
         builder.symbol("list_count", bolide_runtime::bolide_list_count as *const u8);
         builder.symbol("list_sort", bolide_runtime::bolide_list_sort as *const u8);
+        builder.symbol("list_dedup", bolide_runtime::bolide_list_dedup as *const u8);
+        builder.symbol("list_unique", bolide_runtime::bolide_list_unique as *const u8);
+        builder.symbol("list_group_by", bolide_runtime::bolide_list_group_by as *const u8);
+        builder.symbol("list_sort_by", bolide_runtime::bolide_list_sort_by as *const u8);
+        builder.symbol("list_sort_by_key", bolide_runtime::bolide_list_sort_by_key as *const u8);
+        builder.symbol("list_min_by", bolide_runtime::bolide_list_min_by as *const u8);
+        builder.symbol("list_map", bolide_runtime::bolide_list_map as *const u8);
+        builder.symbol("list_filter", bolide_runtime::bolide_list_filter as *const u8);
+        builder.symbol("list_reduce", bolide_runtime::bolide_list_reduce as *const u8);
+        builder.symbol("list_max_by", bolide_runtime::bolide_list_max_by as *const u8);
         builder.symbol("list_slice", bolide_runtime::bolide_list_slice as *const u8);
         builder.symbol("list_is_empty", bolide_runtime::bolide_list_is_empty as *const u8);
         builder.symbol("list_first", bolide_runtime::bolide_list_first as *const u8);
         builder.symbol("list_last", bolide_runtime::bolide_list_last as *const u8);
+        builder.symbol("list_sum_int", bolide_runtime::bolide_list_sum_int as *const u8);
+        builder.symbol("list_sum_float", bolide_runtime::bolide_list_sum_float as *const u8);
+        builder.symbol("list_min_int", bolide_runtime::bolide_list_min_int as *const u8);
+        builder.symbol("list_max_int", bolide_runtime::bolide_list_max_int as *const u8);
+        builder.symbol("list_min_float", bolide_runtime::bolide_list_min_float as *const u8);
+        builder.symbol("list_max_float", bolide_runtime::bolide_list_max_float as *const u8);
+        builder.symbol("list_mean", bolide_runtime::bolide_list_mean as *const u8);
+        builder.symbol("list_mean_float", bolide_runtime::bolide_list_mean_float as *const u8);
+        builder.symbol("list_mean_bigint", bolide_runtime::bolide_list_mean_bigint as *const u8);
+        builder.symbol("list_sum_bigint", bolide_runtime::bolide_list_sum_bigint as *const u8);
+        builder.symbol("list_min_bigint", bolide_runtime::bolide_list_min_bigint as *const u8);
+        builder.symbol("list_max_bigint", bolide_runtime::bolide_list_max_bigint as *const u8);
+        builder.symbol("list_sum_decimal", bolide_runtime::bolide_list_sum_decimal as *const u8);
+        builder.symbol("list_min_decimal", bolide_runtime::bolide_list_min_decimal as *const u8);
+        builder.symbol("list_max_decimal", bolide_runtime::bolide_list_max_decimal as *const u8);
+        builder.symbol("list_mean_decimal", bolide_runtime::bolide_list_mean_decimal as *const u8);
         builder.symbol("print_list", bolide_runtime::bolide_print_list as *const u8);
+        // Packed list symbols
+        builder.symbol("packed_list_new", bolide_runtime::bolide_packed_list_new as *const u8);
+        builder.symbol("packed_list_release", bolide_runtime::bolide_packed_list_release as *const u8);
+        builder.symbol("packed_list_clone", bolide_runtime::bolide_packed_list_clone as *const u8);
+        builder.symbol("packed_list_len", bolide_runtime::bolide_packed_list_len as *const u8);
+        builder.symbol("packed_list_at", bolide_runtime::bolide_packed_list_at as *const u8);
+        builder.symbol("packed_list_push", bolide_runtime::bolide_packed_list_push as *const u8);
+        builder.symbol("packed_list_resize", bolide_runtime::bolide_packed_list_resize as *const u8);
+        builder.symbol("packed_list_swap", bolide_runtime::bolide_packed_list_swap as *const u8);
+        builder.symbol("print_packed_list", bolide_runtime::bolide_print_packed_list as *const u8);
+        builder.symbol("packed_to_string", bolide_runtime::bolide_packed_to_string as *const u8);
         // Dict symbols
         builder.symbol("dict_new", bolide_runtime::bolide_dict_new as *const u8);
         builder.symbol("dict_retain", bolide_runtime::bolide_dict_retain as *const u8);
@@ -280,6 +560,8 @@ impl JitCompiler {
         builder.symbol("dict_clone", bolide_runtime::bolide_dict_clone as *const u8);
         builder.symbol("dict_set", bolide_runtime::bolide_dict_set as *const u8);
         builder.symbol("dict_get", bolide_runtime::bolide_dict_get as *const u8);
+        builder.symbol("dict_new_default", bolide_runtime::bolide_dict_new_default as *const u8);
+        builder.symbol("dict_get_or_insert_empty", bolide_runtime::bolide_dict_get_or_insert_empty as *const u8);
         builder.symbol("dict_contains", bolide_runtime::bolide_dict_contains as *const u8);
         builder.symbol("dict_remove", bolide_runtime::bolide_dict_remove as *const u8);
         builder.symbol("dict_len", bolide_runtime::bolide_dict_len as *const u8);
@@ -289,10 +571,29 @@ impl JitCompiler {
         builder.symbol("dict_values", bolide_runtime::bolide_dict_values as *const u8);
         builder.symbol("dict_iter", bolide_runtime::bolide_dict_iter as *const u8);
         builder.symbol("print_dict", bolide_runtime::bolide_print_dict as *const u8);
+        // weak_dict symbols
+        builder.symbol("weakdict_new", bolide_runtime::bolide_weakdict_new as *const u8);
+        builder.symbol("weakdict_release", bolide_runtime::bolide_weakdict_release as *const u8);
+        builder.symbol("weakdict_clone", bolide_runtime::bolide_weakdict_clone as *const u8);
+        builder.symbol("weakdict_set", bolide_runtime::bolide_weakdict_set as *const u8);
+        builder.symbol("weakdict_get", bolide_runtime::bolide_weakdict_get as *const u8);
+        builder.symbol("weakdict_contains", bolide_runtime::bolide_weakdict_contains as *const u8);
+        builder.symbol("weakdict_remove", bolide_runtime::bolide_weakdict_remove as *const u8);
+        builder.symbol("weakdict_len", bolide_runtime::bolide_weakdict_len as *const u8);
+        builder.symbol("weakdict_is_empty", bolide_runtime::bolide_weakdict_is_empty as *const u8);
+        builder.symbol("weakdict_compact", bolide_runtime::bolide_weakdict_compact as *const u8);
         builder.symbol("dynamic_retain", bolide_runtime::bolide_dynamic_retain as *const u8);
         builder.symbol("dynamic_release", bolide_runtime::bolide_dynamic_release as *const u8);
         builder.symbol("print_dynamic", bolide_runtime::bolide_print_dynamic as *const u8);
 
+        // 注册运行时函数 - 绑定函数
+        builder.symbol("bind", bolide_runtime::bolide_bind as *const u8);
+        builder.symbol("closure_call", bolide_runtime::bolide_closure_call as *const u8);
+        builder.symbol("func_retain", bolide_runtime::bolide_func_retain as *const u8);
+        builder.symbol("func_release", bolide_runtime::bolide_func_release as *const u8);
+
+        // JIT 代码和编译器在同一进程里运行，特性表直接注册到运行时
+        features::register();
 
         let module = JITModule::new(builder);
         let ptr_type = module.target_config().pointer_type();
@@ -312,12 +613,41 @@ impl JitCompiler {
             classes: HashMap::new(),
             async_funcs: HashSet::new(),
             extern_funcs: HashMap::new(),
-            loaded_libs: HashMap::new(),
             modules: HashMap::new(),
             lifetime_funcs: HashSet::new(),
             global_data_ids: HashMap::new(),
             global_var_types: HashMap::new(),
-        }
+            thread_locals: HashSet::new(),
+            pending_thread_locals: Vec::new(),
+            source: None,
+            analysis: None,
+            interrupt: None,
+            debug: false,
+            no_trace: false,
+            opt_level: 1,
+            share_containers: false,
+            warnings: Vec::new(),
+            pure_funcs: HashMap::new(),
+            repl_chunks: 0,
+            repl_definitions: Vec::new(),
+            function_ir: None,
+            hot: None,
+            #[cfg(test)]
+            bad_signature_for: None,
+        }
+    }
+
+    /// 设置源文件，内部编译器错误时会写入复现包
+    pub fn set_source(&mut self, path: &str, text: &str) {
+        self.source = Some(IceSource {
+            path: path.to_string(),
+            text: text.to_string(),
+        });
+    }
+
+    /// 编译时产生的警告，每条形如 `in function 'f': ...`
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
     }
 
     /// 编译程序并返回入口函数指针
@@ -325,9 +655,169 @@ impl JitCompiler {
         // 预处理 import 语句，加载并合并导入的模块
         let program = self.process_imports(program)?;
 
+        // 验证 @pure 标注
+        self.pure_funcs = purity::check_program(&program)?;
+        handles::check_program(&program)?;
+        // 热重载模式下被调用的函数可能改变，不在编译期求值 @pure 调用
+        if self.opt_level == 0 || self.hot.is_some() {
+            self.pure_funcs.clear();
+        }
+
         // 注册内置函数
         self.register_builtins()?;
 
+        self.compile_program(program)
+    }
+
+    /// REPL 用的增量编译：在同一个 JIT 模块里只编译 `program` 新增的定义，
+    /// 顶层语句包装成新的入口函数，返回它的指针
+    ///
+    /// 之前编译的函数、类和全局变量直接复用，顶层的 `let` 是持续存在的全局变量，
+    /// 再次 `let` 同名同类型的变量等同于赋值。重新定义已有的函数或类报错。
+    /// 出错时这次输入的声明全部撤销，已经生成的代码替换为陷阱，编译器可以继续使用。
+    pub fn compile_incremental(&mut self, program: &Program) -> Result<*const u8, String> {
+        self.repl_chunks += 1;
+        if self.repl_chunks == 1 {
+            self.register_builtins()?;
+        }
+        self.warnings.clear();
+
+        let snapshot = self.snapshot_definitions();
+        let first_func = self.module.declarations().get_functions().count();
+        let result = self.compile_chunk(program);
+        if result.is_err() {
+            self.discard_functions_from(first_func);
+            self.restore_definitions(snapshot);
+        }
+        result
+    }
+
+    fn compile_chunk(&mut self, program: &Program) -> Result<*const u8, String> {
+        // 已经导入过的模块不再加载
+        let statements = program.statements.iter()
+            .filter(|stmt| !matches!(stmt, Statement::Import(import)
+                if import.file_path.as_ref().is_some_and(|path| self.modules.values().any(|m| m.path == *path))))
+            .cloned()
+            .collect();
+        let program = self.process_imports(&Program { statements })?;
+
+        for stmt in &program.statements {
+            let name = match stmt {
+                Statement::FuncDef(func) => &func.name,
+                Statement::ClassDef(class) => &class.name,
+                _ => continue,
+            };
+            if self.functions.contains_key(name) || self.classes.contains_key(name) {
+                return Err(format!("'{}' is already defined", name));
+            }
+        }
+
+        // 新函数可能调用之前定义的函数，纯度检查需要看到全部定义
+        let mut known = self.repl_definitions.clone();
+        known.extend(program.statements.iter().cloned());
+        self.pure_funcs = purity::check_program(&Program { statements: known })?;
+        handles::check_program(&program)?;
+
+        let definitions: Vec<Statement> = program.statements.iter()
+            .filter(|stmt| matches!(stmt, Statement::FuncDef(_) | Statement::ClassDef(_)
+                | Statement::ExternBlock(_) | Statement::VarDecl(_)))
+            .cloned()
+            .collect();
+        let main_ptr = self.compile_program(program)?;
+        self.repl_definitions.extend(definitions);
+        Ok(main_ptr)
+    }
+
+    /// 增量编译出错时恢复的名字表
+    fn snapshot_definitions(&self) -> Definitions {
+        Definitions {
+            functions: self.functions.clone(),
+            func_return_types: self.func_return_types.clone(),
+            func_params: self.func_params.clone(),
+            trampolines: self.trampolines.clone(),
+            classes: self.classes.clone(),
+            async_funcs: self.async_funcs.clone(),
+            extern_funcs: self.extern_funcs.clone(),
+            modules: self.modules.clone(),
+            lifetime_funcs: self.lifetime_funcs.clone(),
+            global_data_ids: self.global_data_ids.clone(),
+            global_var_types: self.global_var_types.clone(),
+            thread_locals: self.thread_locals.clone(),
+            pure_funcs: self.pure_funcs.clone(),
+        }
+    }
+
+    fn restore_definitions(&mut self, saved: Definitions) {
+        self.functions = saved.functions;
+        self.func_return_types = saved.func_return_types;
+        self.func_params = saved.func_params;
+        self.trampolines = saved.trampolines;
+        self.classes = saved.classes;
+        self.async_funcs = saved.async_funcs;
+        self.extern_funcs = saved.extern_funcs;
+        self.modules = saved.modules;
+        self.lifetime_funcs = saved.lifetime_funcs;
+        self.global_data_ids = saved.global_data_ids;
+        self.global_var_types = saved.global_var_types;
+        self.thread_locals = saved.thread_locals;
+        self.pending_thread_locals.clear();
+        self.pure_funcs = saved.pure_funcs;
+    }
+
+    /// 撤销出错的输入：从第 `first` 个声明起，还没有定义的函数定义为陷阱
+    ///
+    /// 已经定义的函数可能调用出错前声明、但没来得及定义的函数，不补上定义的话
+    /// 下一次 `finalize_definitions` 会因为找不到符号而失败。这些函数不会再被调用。
+    fn discard_functions_from(&mut self, first: usize) {
+        self.module.clear_context(&mut self.ctx);
+        let declared: Vec<(FuncId, Signature)> = self.module.declarations().get_functions()
+            .skip(first)
+            .filter(|(_, decl)| decl.linkage != Linkage::Import)
+            .map(|(id, decl)| (id, decl.signature.clone()))
+            .collect();
+        for (func_id, sig) in declared {
+            self.ctx.func.signature = sig;
+            self.ctx.func.name = cranelift_codegen::ir::UserFuncName::user(0, func_id.as_u32());
+            let mut builder_ctx = FunctionBuilderContext::new();
+            let mut builder = FunctionBuilder::new(&mut self.ctx.func, &mut builder_ctx);
+            let block = builder.create_block();
+            builder.append_block_params_for_function_params(block);
+            builder.switch_to_block(block);
+            builder.seal_block(block);
+            builder.ins().trap(TrapCode::unwrap_user(1));
+            builder.finalize();
+            // 已经定义过的函数返回 DuplicateDefinition，保留原来的代码
+            if let Err(e) = self.module.define_function(func_id, &mut self.ctx) {
+                debug_assert!(matches!(e, ModuleError::DuplicateDefinition(_)), "{}", e);
+            }
+            self.module.clear_context(&mut self.ctx);
+        }
+        let _ = self.module.finalize_definitions();
+    }
+
+    /// 声明用户代码生成的函数（函数、构造函数、方法）
+    ///
+    /// 增量编译时声明为匿名函数：撤销的输入留下的符号不会和之后的同名定义冲突；
+    /// 热重载编译的新版本同样是匿名函数。
+    fn declare_user_function(&mut self, name: &str, sig: &Signature) -> ModuleResult<FuncId> {
+        if self.repl_chunks > 0 || self.hot.as_ref().is_some_and(|hot| hot.reloads > 0) {
+            self.module.declare_anonymous_function(sig)
+        } else {
+            self.module.declare_function(name, Linkage::Export, sig)
+        }
+    }
+
+    /// 编译合并了导入模块的程序：声明并编译所有定义，顶层语句包装成 `__main__`
+    fn compile_program(&mut self, mut program: Program) -> Result<*const u8, String> {
+        // 热重载比较的是经过同样变换的定义（见 `hot_view`）
+        let hot_view = self.hot.is_some().then(|| self.hot_view(program.clone()));
+
+        // 常量折叠、死分支消除，只由字面量组成的字符串表达式换成结果字面量
+        if self.opt_level > 0 {
+            program = optimize::fold_program(&program);
+            string_fold::fold_program(&mut program);
+        }
+
         // 先处理所有 extern 块（必须在函数声明之前）
         for stmt in &program.statements {
             if let Statement::ExternBlock(eb) = stmt {
@@ -338,6 +828,19 @@ impl JitCompiler {
         // 收集所有类定义
         self.collect_classes(&program)?;
 
+        // 为每个类合成默认的字符串表示方法
+        formatter::add_default_formatters(&mut program, &self.modules, |name| {
+            self.classes[name].fields.iter().map(|f| (f.name.clone(), f.ty.clone())).collect()
+        });
+        // 以及默认的逐字段相等比较
+        equality::add_default_comparators(&mut program, |name| {
+            self.classes[name].fields.iter().map(|f| (f.name.clone(), f.ty.clone())).collect()
+        });
+        // `@trace` 函数的调用日志
+        if !self.no_trace {
+            call_trace::add_call_tracing(&mut program, &self.modules);
+        }
+
         // 第一遍：收集所有函数声明（包括类构造函数）
         for stmt in &program.statements {
             if let Statement::FuncDef(func) = stmt {
@@ -349,9 +852,19 @@ impl JitCompiler {
             }
         }
 
+        if let Some(view) = &hot_view {
+            self.declare_hot_slots(view)?;
+        }
+
         // 声明类构造函数
-        for class_name in self.classes.keys().cloned().collect::<Vec<_>>() {
-            self.declare_class_constructor(&class_name)?;
+        let class_names: Vec<String> = program.statements.iter()
+            .filter_map(|stmt| match stmt {
+                Statement::ClassDef(class) => Some(class.name.clone()),
+                _ => None,
+            })
+            .collect();
+        for class_name in &class_names {
+            self.declare_class_constructor(class_name)?;
         }
 
         // 声明类方法
@@ -365,8 +878,8 @@ impl JitCompiler {
         self.collect_global_variables(&program)?;
 
         // 编译类构造函数
-        for class_name in self.classes.keys().cloned().collect::<Vec<_>>() {
-            self.compile_class_constructor(&class_name)?;
+        for class_name in &class_names {
+            self.compile_class_constructor(class_name)?;
         }
 
         // 编译类方法
@@ -377,7 +890,7 @@ impl JitCompiler {
         for stmt in &program.statements {
             match stmt {
                 Statement::FuncDef(func) => {
-                    self.compile_function(func)?;
+                    self.compile_function_guarded(func)?;
                 }
                 Statement::ClassDef(_) => {
                     // 类定义已经在 collect_classes 中处理
@@ -388,6 +901,37 @@ impl JitCompiler {
             }
         }
 
+        // threadlocal 全局变量的初始化函数（函数体由 emit_thread_local_init 生成）和 RC 值的析构函数
+        for decl in self.pending_thread_locals.clone() {
+            let init_func = FuncDef {
+                name: format!("{}{}", TLS_INIT_PREFIX, decl.name),
+                is_async: false,
+                params: vec![],
+                return_type: None,
+                lifetime_deps: None,
+                body: vec![],
+                pure: false,
+                trace: None,
+                hot_entry: false,
+                line: 0,
+            };
+            self.declare_function(&init_func)?;
+            self.compile_function_guarded(&init_func)?;
+
+            let ty = self.global_var_types[&decl.name].clone();
+            if CompileContext::is_rc_type(&ty) {
+                // owned 参数在函数返回前释放
+                let drop_func = FuncDef {
+                    name: format!("{}{}", TLS_DROP_PREFIX, decl.name),
+                    params: vec![Param { name: "value".to_string(), ty, mode: ParamMode::Owned, default: None }],
+                    body: vec![],
+                    ..init_func
+                };
+                self.declare_function(&drop_func)?;
+                self.compile_function_guarded(&drop_func)?;
+            }
+        }
+
         // 将顶层代码包装成 __main__ 函数
         let main_func = FuncDef {
             name: "__main__".to_string(),
@@ -396,12 +940,47 @@ impl JitCompiler {
             return_type: Some(BolideType::Int),
             lifetime_deps: None,
             body: toplevel_stmts,
+            pure: false,
+            trace: None,
+            hot_entry: false,
+            line: 0,
         };
         self.declare_function(&main_func)?;
-        self.compile_function(&main_func)?;
+        self.compile_function_guarded(&main_func)?;
+        self.pending_thread_locals.clear();
+
+        // REPL 的全局变量在输入之间保留，不生成释放函数
+        if self.repl_chunks == 0 && self.global_var_types.values().any(CompileContext::is_rc_type) {
+            let release_func = FuncDef {
+                name: GLOBALS_RELEASE.to_string(),
+                is_async: false,
+                params: vec![],
+                return_type: None,
+                lifetime_deps: None,
+                body: vec![],
+                pure: false,
+                trace: None,
+                hot_entry: false,
+                line: 0,
+            };
+            self.declare_function(&release_func)?;
+            self.compile_function_guarded(&release_func)?;
+        }
 
-        self.module.finalize_definitions()
-            .map_err(|e| format!("Finalize error: {}", e))?;
+        if let Err(e) = self.module.finalize_definitions() {
+            if ice::is_verifier_error(&e) {
+                let report = IceReport {
+                    func_name: "<finalize>",
+                    detail: format!("{}", e),
+                    clif: String::new(),
+                    ast: String::new(),
+                };
+                return Err(ice::report(&report, self.source.as_ref()));
+            }
+            return Err(format!("Finalize error: {}", e));
+        }
+
+        self.fill_hot_slots();
 
         // 获取 __main__ 函数
         let func_id = self.functions.get("__main__")
@@ -410,6 +989,343 @@ impl JitCompiler {
         Ok(main_ptr)
     }
 
+    /// 开启热重载模式（`bolide run --hot`），在 `compile` 之前调用
+    ///
+    /// 程序需要一个入口函数：标注 `@hot_entry` 的函数，没有时是名为 `tick` 的函数。
+    /// 用户函数的调用经过分派槽（见 `hot` 模块），之后可以用 `hot_reload` 替换函数体。
+    pub fn set_hot(&mut self, hot: bool) {
+        self.hot = hot.then(HotState::default);
+    }
+
+    /// 热重载模式的入口函数：名字，以及它是否返回 bool（返回 false 时停止调用）
+    pub fn hot_entry(&self) -> Option<(&str, bool)> {
+        let (name, returns_bool) = self.hot.as_ref()?.entry.as_ref()?;
+        Some((name.as_str(), *returns_bool))
+    }
+
+    /// 热重载模式下函数当前版本的地址
+    pub fn hot_function(&self, name: &str) -> Option<*const u8> {
+        let func = self.hot.as_ref()?.functions.get(name)?;
+        Some(hot::load_slot(&self.module, func.slot))
+    }
+
+    /// 用修改后的程序替换函数体改变的函数，返回替换的函数名（按定义顺序）
+    ///
+    /// 签名、类、全局变量等不能替换的改变返回错误，这时没有函数被替换；编译出错时同样如此。
+    /// 全部编译成功后才写入分派槽。在入口函数的两次调用之间调用：其他线程里正在运行的
+    /// 代码可能先后看到新旧两个版本。
+    pub fn hot_reload(&mut self, program: &Program) -> Result<Vec<String>, String> {
+        let Some(state) = self.hot.as_mut() else {
+            return Err("hot_reload needs set_hot(true) before compile".to_string());
+        };
+        state.reloads += 1;
+        self.warnings.clear();
+
+        let snapshot = self.snapshot_definitions();
+        let first_func = self.module.declarations().get_functions().count();
+        let result = self.compile_hot_versions(program);
+        if result.is_err() {
+            self.discard_functions_from(first_func);
+            self.restore_definitions(snapshot);
+        }
+        result
+    }
+
+    fn compile_hot_versions(&mut self, program: &Program) -> Result<Vec<String>, String> {
+        let program = self.merge_imports(program, 0)?;
+        purity::check_program(&program)?;
+        handles::check_program(&program)?;
+        let view = self.hot_view(program);
+        let changed: Vec<FuncDef> = self.hot.as_ref()
+            .map_or(Ok(Vec::new()), |state| state.changed_functions(&view))?
+            .into_iter()
+            .cloned()
+            .collect();
+        if changed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // 新版本可能 spawn 之前没有 spawn 过的函数
+        let statements = changed.iter().cloned().map(Statement::FuncDef).collect();
+        let mut targets = self.collect_spawn_targets(&Program { statements });
+        targets.retain(|target| !self.trampolines.contains_key(target));
+        for func in &changed {
+            self.declare_function(func)?;
+        }
+        self.generate_trampolines(&targets)?;
+        for func in &changed {
+            self.compile_function_guarded(func)?;
+        }
+        self.module.finalize_definitions()
+            .map_err(|e| format!("Finalize error: {}", e))?;
+
+        let mut names = Vec::new();
+        if let Some(state) = self.hot.as_mut() {
+            for func in &changed {
+                let code = self.module.get_finalized_function(self.functions[&func.name]);
+                hot::store_slot(&self.module, state.functions[&func.name].slot, code);
+                state.update(func);
+                names.push(func.name.clone());
+            }
+        }
+        Ok(names)
+    }
+
+    /// 热重载比较的定义：`compile_program` 对函数做的源码级变换（常量折叠、字符串折叠、`@trace`）之后
+    fn hot_view(&self, mut program: Program) -> Program {
+        if self.opt_level > 0 {
+            program = optimize::fold_program(&program);
+            string_fold::fold_program(&mut program);
+        }
+        if !self.no_trace {
+            call_trace::add_call_tracing(&mut program, &self.modules);
+        }
+        program
+    }
+
+    /// 为可以替换的函数分配分派槽，记录启动时的定义
+    fn declare_hot_slots(&mut self, view: &Program) -> Result<(), String> {
+        let Some(mut state) = self.hot.take() else {
+            return Ok(());
+        };
+        let result = state.record(view, |name| {
+            let slot = self.module.declare_anonymous_data(true, false)
+                .map_err(|e| format!("Failed to declare dispatch slot for '{}': {}", name, e))?;
+            self.data_desc.define_zeroinit(8);
+            self.data_desc.set_align(8);
+            let defined = self.module.define_data(slot, &self.data_desc)
+                .map_err(|e| format!("Failed to define dispatch slot for '{}': {}", name, e));
+            self.data_desc.clear();
+            defined.map(|_| slot)
+        });
+        self.hot = Some(state);
+        result
+    }
+
+    /// 启动时把每个函数的地址写入它的分派槽
+    fn fill_hot_slots(&self) {
+        let Some(state) = &self.hot else {
+            return;
+        };
+        for (name, func) in &state.functions {
+            if let Some(&id) = self.functions.get(name) {
+                hot::store_slot(&self.module, func.slot, self.module.get_finalized_function(id));
+            }
+        }
+    }
+
+    /// 顶层代码运行完后释放 RC 全局变量（`bolide run` 在用户线程结束后调用）
+    ///
+    /// 只对 `compile` 编译的程序有效；还有用户线程在运行时不释放。释放后全局变量置零，
+    /// 再次运行顶层代码会重新初始化它们。
+    pub fn release_globals(&self) {
+        if let Some(&id) = self.functions.get(GLOBALS_RELEASE) {
+            let release: extern "C" fn() = unsafe { std::mem::transmute(self.module.get_finalized_function(id)) };
+            release();
+        }
+    }
+
+    /// 在 `compile` 之前调用：生成的代码在循环回边和用户函数调用后检查这个中断状态（嵌入 API 用）
+    pub fn enable_interrupt_checks(&mut self, state: Arc<bolide_runtime::InterruptState>) {
+        self.interrupt = Some(state);
+    }
+
+    /// 在 `compile` 之前调用：开启后生成的函数维护影子调用栈，运行时错误附带 `at foo (line 12)` 调用栈
+    ///
+    /// 关闭时（默认）不生成任何调用。
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    /// 在 `compile` 之前调用：开启后忽略 `@trace` 标注（`--no-trace`），生成的代码与没有标注时相同
+    pub fn set_no_trace(&mut self, no_trace: bool) {
+        self.no_trace = no_trace;
+    }
+
+    /// 在 `compile` 之前调用：开启后（`--no-opt`）字面量字符串表达式照常在运行时计算，
+    /// `@pure` 调用只做验证、照常生成调用，互相抵消的 clone/release 保留在生成的代码里；
+    /// 与 `set_opt_level(0)` 相同
+    pub fn set_no_opt(&mut self, no_opt: bool) {
+        self.opt_level = if no_opt { 0 } else { 1 };
+    }
+
+    /// 在 `compile` 之前调用：设置优化级别（`-O0` / `-O1`，默认 1）。0 关闭全部优化，
+    /// 1 还在代码生成前折叠常量表达式、去掉条件为常量的分支和不可达的语句（见 `optimize`）
+    pub fn set_opt_level(&mut self, level: u8) {
+        self.opt_level = level.min(1);
+    }
+
+    /// 在 `compile` 之前调用：开启后 `let b = a`、赋值、字段、元组和返回值绑定已有的列表/字典时
+    /// 共享同一个容器（增加引用计数），和下标赋值、容器字面量、传参一致；关闭时（默认）这些位置复制一份
+    pub fn set_share_containers(&mut self, share: bool) {
+        self.share_containers = share;
+    }
+
+    /// 开启后保留每个函数最后一次编译出的 CLIF（REPL 用，多占内存），由 `function_ir` 取出
+    pub fn set_keep_ir(&mut self, keep: bool) {
+        self.function_ir = keep.then(HashMap::new);
+    }
+
+    /// 函数 `name` 最后一次编译出的 CLIF，首行的函数名为源码里的名字；没有开启 `set_keep_ir` 时为 None
+    pub fn function_ir(&self, name: &str) -> Option<&str> {
+        // 出错撤销的输入编译过的函数不算
+        self.functions.get(name)?;
+        self.function_ir.as_ref()?.get(name).map(String::as_str)
+    }
+
+    /// 在已编译的定义（函数、类、全局变量）之上推断表达式的类型，按源码写法返回；不生成代码
+    pub fn type_of(&mut self, expr: &Expr) -> Result<String, String> {
+        // 用到的名字须已定义；被调用的名字可能是内置函数，交给推断
+        let (mut names, mut callees) = (HashSet::new(), HashSet::new());
+        let mut lambda = false;
+        purity::for_each_expr(&[Statement::Expr(expr.clone())], &mut |e| match e {
+            Expr::Ident(name) | Expr::Recv(name) => {
+                names.insert(name.clone());
+            }
+            Expr::Call(callee, _) => if let Expr::Ident(name) = callee.as_ref() {
+                callees.insert(name.clone());
+            },
+            Expr::Lambda(..) => lambda = true,
+            _ => {}
+        });
+        if lambda {
+            return Err("cannot infer the type of a lambda outside a statement".to_string());
+        }
+        let mut names: Vec<String> = names.difference(&callees).cloned().collect();
+        names.sort();
+        if let Some(name) = names.iter().find(|name| !self.global_var_types.contains_key(*name)
+            && !self.functions.contains_key(*name) && !self.classes.contains_key(*name) && !self.modules.contains_key(*name))
+        {
+            return Err(format!("Undefined variable: {}", name));
+        }
+
+        // 只借用 CompileContext 的推断，builder 不会生成指令
+        let mut func = codegen::ir::Function::new();
+        let mut builder_ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut func, &mut builder_ctx);
+        let compile_ctx = CompileContext::new(
+            &mut builder,
+            &mut self.module,
+            &self.global_data_ids,
+            &self.global_var_types,
+            &self.functions,
+            self.func_return_types.clone(),
+            self.func_params.clone(),
+            &self.trampolines,
+            self.ptr_type,
+            self.classes.clone(),
+            self.async_funcs.clone(),
+            self.extern_funcs.clone(),
+            self.modules.clone(),
+            None,
+            "<type>".to_string(),
+            self.lifetime_funcs.clone(),
+            &self.pure_funcs,
+        );
+        Ok(analysis::type_name(&compile_ctx.infer_expr_type(expr)))
+    }
+
+    /// 用户函数的签名，形如 `fn f(x: int, ref n: int) -> int`；不是已编译的函数时为 None
+    pub fn function_signature(&self, name: &str) -> Option<String> {
+        let params = self.func_params.get(name)?;
+        let params: Vec<String> = params.iter()
+            .map(|p| {
+                let mode = match p.mode {
+                    ParamMode::Borrow => "",
+                    ParamMode::Owned => "owned ",
+                    ParamMode::Ref => "ref ",
+                };
+                format!("{}{}: {}", mode, p.name, analysis::type_name(&p.ty))
+            })
+            .collect();
+        Some(match self.func_return_types.get(name).cloned().flatten() {
+            Some(ret) => format!("fn {}({}) -> {}", name, params.join(", "), analysis::type_name(&ret)),
+            None => format!("fn {}({})", name, params.join(", ")),
+        })
+    }
+
+    /// 全局变量的类型（按源码写法）；没有这个全局变量时为 None
+    pub fn global_type(&self, name: &str) -> Option<String> {
+        self.global_var_types.get(name).map(analysis::type_name)
+    }
+
+    /// 编译后按名字取用户函数的入口、参数和返回类型
+    pub(crate) fn entry_point(&self, name: &str) -> Option<(*const u8, &[Param], Option<&BolideType>)> {
+        let id = *self.functions.get(name)?;
+        let params = self.func_params.get(name)?;
+        let ret = self.func_return_types.get(name)?.as_ref();
+        Some((self.module.get_finalized_function(id), params.as_slice(), ret))
+    }
+
+    /// 只检查程序能否编译（编辑器诊断用）：走完整的编译流程但不运行，结束后释放生成的代码
+    pub fn validate(mut self, program: &Program) -> Result<(), String> {
+        let result = self.compile(program).map(|_| ());
+        // 没有执行过生成的代码，可以安全释放
+        unsafe { self.module.free_memory(); }
+        result
+    }
+
+    /// 只编译不运行，返回每个函数的调用图和 RC 操作统计（`--analyze`）
+    pub fn analyze(mut self, program: &Program) -> Result<AnalysisReport, String> {
+        self.analysis = Some(AnalysisReport::default());
+        let result = self.compile(program).map(|_| ());
+        // 没有执行过生成的代码，可以安全释放
+        unsafe { self.module.free_memory(); }
+        result?;
+        Ok(self.analysis.take().unwrap_or_default())
+    }
+
+    /// 从刚生成的 IR 统计调用和 RC 操作，合并编译时记录的信息后写入报告
+    ///
+    /// `edges` 是编译时记录的 spawn/async/extern 调用，每次调用一条；它们在 IR 里
+    /// 只是 func_addr 或间接调用，看不出目标。
+    fn record_analysis(&mut self, name: &str, edges: Vec<(CallKind, String)>, mut report: FunctionReport) {
+        if self.analysis.is_none() {
+            return;
+        }
+        let names: HashMap<u32, &str> = self.functions.iter()
+            .map(|(name, id)| (id.as_u32(), name.as_str()))
+            .collect();
+        let func = &self.ctx.func;
+        for block in func.layout.blocks() {
+            for inst in func.layout.block_insts(block) {
+                match func.dfg.insts[inst] {
+                    cranelift_codegen::ir::InstructionData::Call { func_ref, .. } => {
+                        let target = match &func.dfg.ext_funcs[func_ref].name {
+                            cranelift_codegen::ir::ExternalName::User(r) => {
+                                names.get(&func.params.user_named_funcs()[*r].index)
+                            }
+                            _ => None,
+                        };
+                        let Some(&target) = target else { continue };
+                        if analysis::is_rc_op(target) {
+                            *report.rc_ops.entry(target.to_string()).or_insert(0) += 1;
+                        } else if self.func_params.contains_key(target) || self.classes.contains_key(target) {
+                            let is_method = self.classes.values()
+                                .any(|c| c.methods.iter().any(|m| target == format!("{}_{}", c.name, m)));
+                            let kind = if is_method { CallKind::Method } else { CallKind::Call };
+                            report.calls.insert((kind, target.to_string()));
+                        } else if analysis::is_alloc(target) {
+                            *report.allocs.entry(target.to_string()).or_insert(0) += 1;
+                        }
+                    }
+                    cranelift_codegen::ir::InstructionData::CallIndirect { .. } => {
+                        report.indirect_calls += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        // extern 调用经 ffi_get_symbol 取得地址后间接调用，不算作间接调用
+        let extern_calls = edges.iter().filter(|(kind, _)| *kind == CallKind::Extern).count();
+        report.indirect_calls = report.indirect_calls.saturating_sub(extern_calls);
+        report.calls.extend(edges);
+        report.lifetime_mode |= self.lifetime_funcs.contains(name);
+        if let Some(analysis) = self.analysis.as_mut() {
+            analysis.functions.insert(name.to_string(), report);
+        }
+    }
+
     /// 声明函数（第一遍）
     fn declare_function(&mut self, func: &FuncDef) -> Result<(), String> {
         let mut sig = self.module.make_signature();
@@ -425,8 +1341,7 @@ impl JitCompiler {
             sig.returns.push(AbiParam::new(self.bolide_type_to_cranelift(ret_ty)));
         }
 
-        let func_id = self.module
-            .declare_function(&func.name, Linkage::Export, &sig)
+        let func_id = self.declare_user_function(&func.name, &sig)
             .map_err(|e| format!("Declare function error: {}", e))?;
 
         self.functions.insert(func.name.clone(), func_id);
@@ -443,314 +1358,202 @@ impl JitCompiler {
 
     /// 处理 import 语句，加载并合并导入的模块
     fn process_imports(&mut self, program: &Program) -> Result<Program, String> {
-        let mut merged_statements = Vec::new();
-        let mut imported_files: std::collections::HashSet<String> = std::collections::HashSet::new();
+        // 匿名函数提升为命名函数；REPL 接着之前输入的编号
+        let lambda_base = self.functions.keys().filter(|name| name.starts_with(lambda::LAMBDA_PREFIX)).count();
+        self.merge_imports(program, lambda_base)
+    }
 
-        // 先处理所有 import 语句
-        for stmt in &program.statements {
-            if let Statement::Import(import) = stmt {
-                if let Some(ref file_path) = import.file_path {
-                    // 避免重复导入
-                    if imported_files.contains(file_path) {
-                        continue;
-                    }
-                    imported_files.insert(file_path.clone());
+    /// 合并导入的模块，主程序的匿名函数从 `lambda_base` 开始编号
+    fn merge_imports(&mut self, program: &Program, lambda_base: usize) -> Result<Program, String> {
+        let mut merged_statements = Vec::new();
 
-                    // 从文件名提取模块名
-                    let module_name = Self::extract_module_name(file_path);
-                    self.modules.insert(module_name.clone(), file_path.clone());
+        // 先处理所有 import 语句：合并导入的定义，添加模块前缀
+        for file_path in &crate::modules::import_files(program) {
+            // 从文件名提取模块名
+            let module_name = Self::extract_module_name(file_path);
+            let mut imported = self.load_module(file_path)?;
+            self.modules.insert(module_name.clone(), ImportedModule::new(file_path, &imported));
+            // 匿名函数提升后随模块的其他函数一起加上前缀
+            lambda::lift_lambdas(&mut imported, 0)?;
+            merged_statements.extend(modules::module_items(imported, &module_name, true));
+        }
+
+        // 测试块只由 `bolide test` 运行（见 `test_programs`）
+        let mut main = test_blocks::without_tests(program);
+        lambda::lift_lambdas(&mut main, lambda_base)?;
+        // 添加原程序的所有语句，`模块.类` 形式的类型标注换成导入后的类名
+        let mut statements = main.statements;
+        modules::resolve_qualified_types(&mut statements, &self.modules)?;
+        merged_statements.extend(statements);
 
-                    // 加载并解析文件
-                    let imported = self.load_module(file_path)?;
+        Ok(Program { statements: merged_statements })
+    }
 
-                    // 先收集模块中定义的类名
-                    let mut class_names: HashSet<String> = HashSet::new();
-                    for imp_stmt in &imported.statements {
-                        if let Statement::ClassDef(class) = imp_stmt {
-                            class_names.insert(class.name.clone());
+    /// 收集并声明全局变量
+    fn collect_global_variables(&mut self, program: &Program) -> Result<(), String> {
+        let mut globals = Vec::new();
+        for stmt in &program.statements {
+            match stmt {
+                Statement::VarDecl(decl) => {
+                    // 推断类型
+                    let earlier = match &decl.value {
+                        Some(Expr::Ident(source)) => globals.iter().rev()
+                            .find(|(name, _)| name == source)
+                            .map(|(_, ty): &(String, BolideType)| ty.clone())
+                            .or_else(|| self.global_var_types.get(source).cloned()),
+                        _ => None,
+                    };
+                    let var_type = if let Some(ref ty) = decl.ty {
+                        self.normalize_bolide_type(ty)
+                    } else if let Some(ty) = earlier {
+                        // `let b = a;`：取前面全局变量的类型
+                        ty
+                    } else if let Some(ref val) = decl.value {
+                        self.normalize_bolide_type(&self.infer_expr_type_static(val))
+                    } else {
+                        BolideType::Int
+                    };
+                    // 增量编译时已有的全局变量：同类型的再次 let 等同于赋值
+                    if let Some(existing) = self.global_var_types.get(&decl.name) {
+                        if *existing != var_type {
+                            return Err(format!("'{}' is already declared as {}", decl.name, analysis::type_name(existing)));
                         }
+                        if decl.thread_local != self.thread_locals.contains(&decl.name) {
+                            return Err(format!("'{}' is already declared as a {}global", decl.name,
+                                if decl.thread_local { "" } else { "threadlocal " }));
+                        }
+                        continue;
                     }
-
-                    // 合并导入的定义，添加模块前缀
-                    for imp_stmt in imported.statements {
-                        match imp_stmt {
-                            Statement::FuncDef(mut func) => {
-                                // 重命名函数: func -> @module_func
-                                func.name = format!("@{}_{}", module_name, func.name);
-                                // 重写函数内部的类型引用
-                                Self::rewrite_func_class_refs(&mut func, &module_name, &class_names);
-                                merged_statements.push(Statement::FuncDef(func));
-                            }
-                            Statement::ClassDef(mut class) => {
-                                // 重命名类: Class -> @module_Class
-                                let old_name = class.name.clone();
-                                class.name = format!("@{}_{}", module_name, old_name);
-                                // 重写方法内部的类型引用
-                                for method in &mut class.methods {
-                                    Self::rewrite_func_class_refs(method, &module_name, &class_names);
+                    if decl.thread_local {
+                        self.thread_locals.insert(decl.name.clone());
+                        self.pending_thread_locals.push(decl.clone());
+                    }
+                    globals.push((decl.name.clone(), var_type));
+                }
+                Statement::Destructure(destructure) if destructure.declare => {
+                    // 槽位类型无法静态推断时不登记，由编译阶段报错
+                    if let BolideType::Tuple(slot_types) = self.infer_expr_type_static(&destructure.value) {
+                        if slot_types.len() == destructure.targets.len() {
+                            for (target, ty) in destructure.targets.iter().zip(slot_types) {
+                                if let Some(name) = target {
+                                    globals.push((name.clone(), self.normalize_bolide_type(&ty)));
                                 }
-                                merged_statements.push(Statement::ClassDef(class));
-                            }
-                            Statement::ExternBlock(ext) => {
-                                // 保留 extern 声明（不添加前缀，C函数名必须保持不变）
-                                merged_statements.push(Statement::ExternBlock(ext));
                             }
-                            Statement::VarDecl(mut decl) => {
-                                // 重命名模块级变量
-                                decl.name = format!("@{}_{}", module_name, decl.name);
-                                // 处理模块级变量声明
-                                Self::rewrite_var_decl_class_refs(&mut decl, &module_name, &class_names);
-                                merged_statements.push(Statement::VarDecl(decl));
-                            }
-                            _ => {} // 忽略其他顶层代码
                         }
                     }
                 }
+                _ => {}
             }
         }
 
-        // 添加原程序的所有语句
-        for stmt in &program.statements {
-            merged_statements.push(stmt.clone());
-        }
-
-        Ok(Program { statements: merged_statements })
-    }
-
-    /// 重写函数内部的类型引用，将模块内部类名转换为 @module_ClassName
-    fn rewrite_func_class_refs(func: &mut FuncDef, module_name: &str, class_names: &HashSet<String>) {
-        // 重写返回类型
-        if let Some(ref mut ret_ty) = func.return_type {
-            Self::rewrite_type_class_refs(ret_ty, module_name, class_names);
-        }
-        // 重写参数类型
-        for param in &mut func.params {
-            Self::rewrite_type_class_refs(&mut param.ty, module_name, class_names);
-        }
-        // 重写函数体内的语句
-        for stmt in &mut func.body {
-            Self::rewrite_stmt_class_refs(stmt, module_name, class_names);
-        }
-    }
+        for (name, var_type) in globals {
+            // 为全局变量创建数据段（8 字节用于存储值）
+            let data_id = if self.repl_chunks > 0 {
+                self.module.declare_anonymous_data(true, false)
+            } else {
+                self.module.declare_data(&name, Linkage::Local, true, false)
+            };
+            let data_id = data_id.map_err(|e| format!("Failed to declare global '{}': {}", name, e))?;
 
-    /// 重写类型中的类引用
-    fn rewrite_type_class_refs(ty: &mut BolideType, module_name: &str, class_names: &HashSet<String>) {
-        match ty {
-            BolideType::Custom(name) => {
-                if class_names.contains(name) {
-                    *name = format!("@{}_{}", module_name, name);
-                }
-            }
-            BolideType::List(inner) => Self::rewrite_type_class_refs(inner, module_name, class_names),
-            BolideType::Dict(k, v) => {
-                Self::rewrite_type_class_refs(k, module_name, class_names);
-                Self::rewrite_type_class_refs(v, module_name, class_names);
-            }
-            BolideType::Channel(inner) => Self::rewrite_type_class_refs(inner, module_name, class_names),
-            BolideType::Tuple(types) => {
-                for t in types {
-                    Self::rewrite_type_class_refs(t, module_name, class_names);
-                }
-            }
-            BolideType::Weak(inner) | BolideType::Unowned(inner) => {
-                Self::rewrite_type_class_refs(inner, module_name, class_names);
-            }
-            BolideType::FuncSig(params, ret) => {
-                for p in params {
-                    Self::rewrite_type_class_refs(p, module_name, class_names);
-                }
-                if let Some(r) = ret {
-                    Self::rewrite_type_class_refs(r, module_name, class_names);
-                }
-            }
-            _ => {}
-        }
-    }
+            // 初始化数据段为 0
+            self.data_desc.define_zeroinit(8);
+            self.module.define_data(data_id, &self.data_desc)
+                .map_err(|e| format!("Failed to define global '{}': {}", name, e))?;
+            self.data_desc.clear();
 
-    /// 重写变量声明中的类引用
-    fn rewrite_var_decl_class_refs(decl: &mut VarDecl, module_name: &str, class_names: &HashSet<String>) {
-        if let Some(ref mut ty) = decl.ty {
-            Self::rewrite_type_class_refs(ty, module_name, class_names);
-        }
-        if let Some(ref mut val) = decl.value {
-            Self::rewrite_expr_class_refs(val, module_name, class_names);
+            // 记录全局变量
+            self.global_data_ids.insert(name.clone(), data_id);
+            self.global_var_types.insert(name, var_type);
         }
+        Ok(())
     }
 
-    /// 重写语句中的类引用
-    fn rewrite_stmt_class_refs(stmt: &mut Statement, module_name: &str, class_names: &HashSet<String>) {
-        match stmt {
-            Statement::VarDecl(decl) => {
-                Self::rewrite_var_decl_class_refs(decl, module_name, class_names);
-            }
-            Statement::Assign(assign) => {
-                Self::rewrite_expr_class_refs(&mut assign.value, module_name, class_names);
+    /// 静态推断表达式类型（用于全局变量收集阶段）
+    fn infer_expr_type_static(&self, expr: &Expr) -> BolideType {
+        match expr {
+            Expr::Int(_) => BolideType::Int,
+            Expr::Float(_) => BolideType::Float,
+            Expr::Bool(_) => BolideType::Bool,
+            Expr::String(_) | Expr::FString(_) => BolideType::Str,
+            Expr::BigInt(_) => BolideType::BigInt,
+            Expr::Decimal(_) => BolideType::Decimal,
+            Expr::Duration(_) => BolideType::Duration,
+            Expr::None => BolideType::Int,
+            // 函数名作为值（包括提升后的匿名函数）
+            Expr::Ident(name) if self.func_params.contains_key(name) => BolideType::FuncSig(
+                self.func_params[name].iter().map(|p| p.ty.clone()).collect(),
+                self.func_return_types.get(name).cloned().flatten().map(Box::new),
+            ),
+            // 与 compile_list / compile_dict 的推断一致：列表看第一个元素，字典不一致时为 Dynamic，空字面量为 int
+            Expr::List(items) => {
+                let elem = items.first().map(|e| self.infer_expr_type_static(e)).unwrap_or(BolideType::Int);
+                BolideType::List(Box::new(elem))
             }
-            Statement::Expr(expr) => {
-                Self::rewrite_expr_class_refs(expr, module_name, class_names);
+            Expr::Dict(entries) => {
+                let unify = |types: Vec<BolideType>| -> BolideType {
+                    match types.split_first() {
+                        None => BolideType::Int,
+                        Some((first, rest)) if rest.iter().all(|t| t == first) => first.clone(),
+                        Some(_) => BolideType::Dynamic,
+                    }
+                };
+                let k = unify(entries.iter().map(|(k, _)| self.infer_expr_type_static(k)).collect());
+                let v = unify(entries.iter().map(|(_, v)| self.infer_expr_type_static(v)).collect());
+                BolideType::Dict(Box::new(k), Box::new(v))
             }
-            Statement::Return(Some(expr)) => {
-                Self::rewrite_expr_class_refs(expr, module_name, class_names);
+            Expr::Tuple(exprs) => {
+                let types: Vec<BolideType> = exprs.iter()
+                    .map(|e| self.infer_expr_type_static(e))
+                    .collect();
+                BolideType::Tuple(types)
             }
-            Statement::If(if_stmt) => {
-                Self::rewrite_expr_class_refs(&mut if_stmt.condition, module_name, class_names);
-                for s in &mut if_stmt.then_body {
-                    Self::rewrite_stmt_class_refs(s, module_name, class_names);
-                }
-                for (cond, body) in &mut if_stmt.elif_branches {
-                    Self::rewrite_expr_class_refs(cond, module_name, class_names);
-                    for s in body {
-                        Self::rewrite_stmt_class_refs(s, module_name, class_names);
-                    }
-                }
-                if let Some(else_body) = &mut if_stmt.else_body {
-                    for s in else_body {
-                        Self::rewrite_stmt_class_refs(s, module_name, class_names);
-                    }
-                }
-            }
-            Statement::While(while_stmt) => {
-                Self::rewrite_expr_class_refs(&mut while_stmt.condition, module_name, class_names);
-                for s in &mut while_stmt.body {
-                    Self::rewrite_stmt_class_refs(s, module_name, class_names);
-                }
-            }
-            Statement::For(for_stmt) => {
-                Self::rewrite_expr_class_refs(&mut for_stmt.iter, module_name, class_names);
-                for s in &mut for_stmt.body {
-                    Self::rewrite_stmt_class_refs(s, module_name, class_names);
-                }
-            }
-            _ => {}
-        }
-    }
-
-    /// 重写表达式中的类引用（主要是构造函数调用）
-    fn rewrite_expr_class_refs(expr: &mut Expr, module_name: &str, class_names: &HashSet<String>) {
-        match expr {
-            Expr::Call(callee, args) => {
-                // 检查是否是类构造函数调用: ClassName(args)
-                if let Expr::Ident(name) = callee.as_mut() {
-                    if class_names.contains(name.as_str()) {
-                        *name = format!("@{}_{}", module_name, name);
-                    }
-                }
-                Self::rewrite_expr_class_refs(callee, module_name, class_names);
-                for arg in args {
-                    Self::rewrite_expr_class_refs(arg, module_name, class_names);
-                }
-            }
-            Expr::BinOp(left, _, right) => {
-                Self::rewrite_expr_class_refs(left, module_name, class_names);
-                Self::rewrite_expr_class_refs(right, module_name, class_names);
-            }
-            Expr::UnaryOp(_, operand) => {
-                Self::rewrite_expr_class_refs(operand, module_name, class_names);
-            }
-            Expr::Index(base, idx) => {
-                Self::rewrite_expr_class_refs(base, module_name, class_names);
-                Self::rewrite_expr_class_refs(idx, module_name, class_names);
-            }
-            Expr::Member(base, _) => {
-                Self::rewrite_expr_class_refs(base, module_name, class_names);
-            }
-            Expr::List(items) => {
-                for item in items {
-                    Self::rewrite_expr_class_refs(item, module_name, class_names);
-                }
-            }
-            Expr::Dict(entries) => {
-                for (k, v) in entries {
-                    Self::rewrite_expr_class_refs(k, module_name, class_names);
-                    Self::rewrite_expr_class_refs(v, module_name, class_names);
-                }
-            }
-            Expr::Tuple(items) => {
-                for item in items {
-                    Self::rewrite_expr_class_refs(item, module_name, class_names);
-                }
-            }
-            Expr::Await(inner) => {
-                Self::rewrite_expr_class_refs(inner, module_name, class_names);
-            }
-            Expr::AwaitAll(exprs) => {
-                for e in exprs {
-                    Self::rewrite_expr_class_refs(e, module_name, class_names);
-                }
-            }
-            _ => {}
-        }
-    }
-
-    /// 收集并声明全局变量
-    fn collect_global_variables(&mut self, program: &Program) -> Result<(), String> {
-        for stmt in &program.statements {
-            if let Statement::VarDecl(decl) = stmt {
-                // 推断类型
-                let var_type = if let Some(ref ty) = decl.ty {
-                    self.normalize_bolide_type(ty)
-                } else if let Some(ref val) = decl.value {
-                    self.normalize_bolide_type(&self.infer_expr_type_static(val))
-                } else {
-                    BolideType::Int
-                };
-
-                // 为全局变量创建数据段（8 字节用于存储值）
-                let data_id = self.module
-                    .declare_data(&decl.name, Linkage::Local, true, false)
-                    .map_err(|e| format!("Failed to declare global '{}': {}", decl.name, e))?;
-
-                // 初始化数据段为 0
-                self.data_desc.define_zeroinit(8);
-                self.module.define_data(data_id, &self.data_desc)
-                    .map_err(|e| format!("Failed to define global '{}': {}", decl.name, e))?;
-                self.data_desc.clear();
-
-                // 记录全局变量
-                self.global_data_ids.insert(decl.name.clone(), data_id);
-                self.global_var_types.insert(decl.name.clone(), var_type);
-            }
-        }
-        Ok(())
-    }
-
-    /// 静态推断表达式类型（用于全局变量收集阶段）
-    fn infer_expr_type_static(&self, expr: &Expr) -> BolideType {
-        match expr {
-            Expr::Int(_) => BolideType::Int,
-            Expr::Float(_) => BolideType::Float,
-            Expr::Bool(_) => BolideType::Bool,
-            Expr::String(_) => BolideType::Str,
-            Expr::BigInt(_) => BolideType::BigInt,
-            Expr::Decimal(_) => BolideType::Decimal,
-            Expr::None => BolideType::Int,
-            Expr::List(_) => BolideType::List(Box::new(BolideType::Dynamic)),
-            Expr::Dict(_) => BolideType::Dict(Box::new(BolideType::Dynamic), Box::new(BolideType::Dynamic)),
-            Expr::Tuple(exprs) => {
-                let types: Vec<BolideType> = exprs.iter()
-                    .map(|e| self.infer_expr_type_static(e))
-                    .collect();
-                BolideType::Tuple(types)
-            }
-            Expr::Member(base, member) => {
-                // 处理模块成员访问，如 gui.COLOR_BLUE
-                if let Expr::Ident(module_name) = base.as_ref() {
-                    if self.modules.contains_key(module_name) {
-                        // 模块常量访问，检查全局变量类型
-                        let global_name = format!("@{}_{}", module_name, member);
-                        if let Some(ty) = self.global_var_types.get(&global_name) {
-                            return ty.clone();
-                        }
+            Expr::Member(base, member) => {
+                // 处理模块成员访问，如 gui.COLOR_BLUE
+                if let Expr::Ident(module_name) = base.as_ref() {
+                    if self.modules.contains_key(module_name) {
+                        // 模块常量访问，检查全局变量类型
+                        let global_name = format!("@{}_{}", module_name, member);
+                        if let Some(ty) = self.global_var_types.get(&global_name) {
+                            return ty.clone();
+                        }
                     }
                 }
                 BolideType::Int
             }
-            Expr::Call(callee, _) => {
-                // 检查是否是类构造函数或模块函数
+            Expr::Call(callee, args) => {
+                // 检查是否是类构造函数、模块函数或已声明返回类型的函数
                 if let Expr::Ident(name) = callee.as_ref() {
                     if self.classes.contains_key(name) {
                         return BolideType::Custom(name.clone());
                     }
+                    if let (Some(Expr::Ident(class_name)), "packed_list") = (args.first(), name.as_str()) {
+                        return BolideType::PackedList(Box::new(
+                            self.normalize_bolide_type(&BolideType::Custom(class_name.clone()))
+                        ));
+                    }
+                    if self.async_funcs.contains(name) {
+                        return BolideType::Future;
+                    }
+                    if let Some(Some(ret_ty)) = self.func_return_types.get(name) {
+                        return ret_ty.clone();
+                    }
+                    // 返回类型固定的内置函数
+                    match name.as_str() {
+                        "str" | "input" | "read_file" | "format_row" | "intern" | "platform" | "temp_dir" | "version" | "error_message" => return BolideType::Str,
+                        "version_at_least" | "has_feature" | "object_debug_enable" | "cancelled" => return BolideType::Bool,
+                        "write_file" | "append_file" | "file_exists" | "remove_file" => return BolideType::Bool,
+                        "read_lines" => return BolideType::List(Box::new(BolideType::Str)),
+                        "float" | "parse_float" | "timer_elapsed_ms" => return BolideType::Float,
+                        "bigint" => return BolideType::BigInt,
+                        "decimal" => return BolideType::Decimal,
+                        "deadline" => return BolideType::Deadline,
+                        "remaining" => return BolideType::Duration,
+                        "bind" => return match args.first() {
+                            Some(f) => bind::result_type(self.infer_expr_type_static(f), args.len() - 1),
+                            None => BolideType::Func,
+                        },
+                        _ => {}
+                    }
                 }
                 if let Expr::Member(base, member) = callee.as_ref() {
                     if let Expr::Ident(module_name) = base.as_ref() {
@@ -788,7 +1591,7 @@ impl JitCompiler {
             .map_err(|e| format!("Failed to load module '{}': {}", file_path, e))?;
 
         bolide_parser::parse_source(&content)
-            .map_err(|e| format!("Failed to parse module '{}': {}", file_path, e))
+            .map_err(|e| modules::parse_error(file_path, *e))
     }
 
     /// 规范化类型名称
@@ -818,6 +1621,10 @@ impl JitCompiler {
                 Box::new(self.normalize_bolide_type(k)),
                 Box::new(self.normalize_bolide_type(v)),
             ),
+            BolideType::WeakDict(k, v) => BolideType::WeakDict(
+                Box::new(self.normalize_bolide_type(k)),
+                Box::new(self.normalize_bolide_type(v)),
+            ),
             BolideType::Tuple(types) => BolideType::Tuple(
                 types.iter().map(|t| self.normalize_bolide_type(t)).collect()
             ),
@@ -828,6 +1635,8 @@ impl JitCompiler {
             BolideType::Weak(inner) => BolideType::Weak(Box::new(self.normalize_bolide_type(inner))),
             BolideType::Unowned(inner) => BolideType::Unowned(Box::new(self.normalize_bolide_type(inner))),
             BolideType::Channel(inner) => BolideType::Channel(Box::new(self.normalize_bolide_type(inner))),
+            BolideType::PackedList(inner) => BolideType::PackedList(Box::new(self.normalize_bolide_type(inner))),
+            BolideType::PackedRef(inner) => BolideType::PackedRef(Box::new(self.normalize_bolide_type(inner))),
             _ => ty.clone(),
         }
     }
@@ -888,6 +1697,28 @@ impl JitCompiler {
         let id = self.module.declare_function("print_string", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("print_string".to_string(), id);
 
+        // printr 的不换行打印：print_*_inline(value) -> void
+        let inline_prints: [(&str, types::Type); 7] = [
+            ("print_int_inline", types::I64),
+            ("print_float_inline", types::F64),
+            ("print_bool_inline", types::I64),
+            ("print_bigint_inline", ptr),
+            ("print_decimal_inline", ptr),
+            ("print_string_inline", ptr),
+            ("print_dynamic_inline", ptr),
+        ];
+        for (name, param) in inline_prints {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(param));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // print_flush() -> void
+        let sig = self.module.make_signature();
+        let id = self.module.declare_function("print_flush", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("print_flush".to_string(), id);
+
         // ===== 用户输入函数 =====
         // input() -> ptr
         let mut sig = self.module.make_signature();
@@ -902,6 +1733,72 @@ impl JitCompiler {
         let id = self.module.declare_function("input_prompt", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("input_prompt".to_string(), id);
 
+        // async 函数里的 input() / read_file()：在 I/O 线程上读取，io_await 挂起等待结果
+        // input_async(ptr) -> ptr, file_read(ptr) -> ptr, file_read_async(ptr) -> ptr, io_await(ptr) -> ptr
+        for name in ["input_async", "file_read", "file_read_async", "io_await"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ptr));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // file_write_lines(path, list)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("file_write_lines", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("file_write_lines".to_string(), id);
+
+        // file_write(path, contents) -> i64, file_append(path, contents) -> i64
+        for name in ["file_write", "file_append"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // file_exists(path) -> i64, file_lines(path) -> list, file_remove(path) -> i64
+        for (name, ret) in [("file_exists", types::I64), ("file_lines", ptr), ("file_remove", types::I64)] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ret));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // format_row(list, sep) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("format_row", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("format_row".to_string(), id);
+
+        // assert(i64, message)、assert_eq_*(a, b)
+        let assert_sigs = [
+            ("assert", types::I64, ptr),
+            ("assert_eq_int", types::I64, types::I64),
+            ("assert_eq_float", types::F64, types::F64),
+            ("assert_eq_str", ptr, ptr),
+            ("assert_eq_list", ptr, ptr),
+        ];
+        for (name, a, b) in assert_sigs {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(a));
+            sig.params.push(AbiParam::new(b));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // set_io_workers(i64)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("set_io_workers", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("set_io_workers".to_string(), id);
+
         // ===== 类型转换函数 =====
         // string_from_int(i64) -> ptr
         let mut sig = self.module.make_signature();
@@ -938,6 +1835,43 @@ impl JitCompiler {
         let id = self.module.declare_function("string_from_decimal", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("string_from_decimal".to_string(), id);
 
+        // ===== f-string 拼接缓冲 =====
+        // string_builder_new(capacity) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("string_builder_new", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_builder_new".to_string(), id);
+
+        // string_builder_append_*(builder, value)
+        let append_params = [
+            ("string_builder_append_str", ptr),
+            ("string_builder_append_int", types::I64),
+            ("string_builder_append_float", types::F64),
+            ("string_builder_append_bool", types::I64),
+            ("string_builder_append_bigint", ptr),
+            ("string_builder_append_decimal", ptr),
+            ("string_builder_append_dynamic", ptr),
+            ("string_builder_append_list", ptr),
+            ("string_builder_append_dict", ptr),
+            ("string_builder_append_tuple", ptr),
+            ("string_builder_append_packed_list", ptr),
+        ];
+        for (name, param) in append_params {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.params.push(AbiParam::new(param));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // string_builder_finish(builder) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("string_builder_finish", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_builder_finish".to_string(), id);
+
         // string_to_int(ptr) -> i64
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -952,6 +1886,21 @@ impl JitCompiler {
         let id = self.module.declare_function("string_to_float", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("string_to_float".to_string(), id);
 
+        // string_parse_int(ptr, radix: i64) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("string_parse_int", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_parse_int".to_string(), id);
+
+        // string_parse_float(ptr) -> f64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::F64));
+        let id = self.module.declare_function("string_parse_float", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_parse_float".to_string(), id);
+
         // ===== RC Release 函数 =====
         // string_release(ptr) -> void
         let mut sig = self.module.make_signature();
@@ -1005,12 +1954,89 @@ impl JitCompiler {
         let id = self.module.declare_function("decimal_clone", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("decimal_clone".to_string(), id);
 
-        // list_clone(ptr) -> ptr
+        // decimal_round_dp_mode(ptr, i64, i64) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("decimal_round_dp_mode", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("decimal_round_dp_mode".to_string(), id);
+
+        // decimal_neg(ptr) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("decimal_neg", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("decimal_neg".to_string(), id);
+
+        // decimal_set_rounding(i64) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("decimal_set_rounding", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("decimal_set_rounding".to_string(), id);
+
+        // decimal_set_div_precision(i64) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("decimal_set_div_precision", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("decimal_set_div_precision".to_string(), id);
+
+        // decimal_round(ptr, i64) -> ptr
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
         sig.returns.push(AbiParam::new(ptr));
-        let id = self.module.declare_function("list_clone", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
-        self.functions.insert("list_clone".to_string(), id);
+        let id = self.module.declare_function("decimal_round", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("decimal_round".to_string(), id);
+
+        // decimal_floor / decimal_ceil / decimal_from_string(ptr) -> ptr
+        for name in ["decimal_floor", "decimal_ceil", "decimal_from_string"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ptr));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // float_round(f64, i64) -> f64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::F64));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::F64));
+        let id = self.module.declare_function("float_round", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("float_round".to_string(), id);
+
+        // bigint/decimal 原地运算: <type>_<op>_assign(ptr, ptr) -> ptr
+        for name in ["bigint_add_assign", "bigint_sub_assign", "bigint_mul_assign",
+                     "decimal_add_assign", "decimal_sub_assign", "decimal_mul_assign"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ptr));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+
+            // <type>_<op>_assign_i64(ptr, i64) -> ptr
+            let name_i64 = format!("{}_i64", name);
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.params.push(AbiParam::new(types::I64));
+            sig.returns.push(AbiParam::new(ptr));
+            let id = self.module.declare_function(&name_i64, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name_i64, id);
+        }
+
+        // list_clone(ptr) -> ptr、list_retain(ptr) -> ptr
+        for name in ["list_clone", "list_retain"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ptr));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
 
         // list_new(elem_type: u8) -> ptr
         let mut sig = self.module.make_signature();
@@ -1019,6 +2045,15 @@ impl JitCompiler {
         let id = self.module.declare_function("list_new", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("list_new".to_string(), id);
 
+        // list_from_const_array(elem_type: u8, data: ptr, count: i64) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I8));
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("list_from_const_array", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_from_const_array".to_string(), id);
+
         // list_push(list: ptr, value: i64) -> void
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -1041,6 +2076,27 @@ impl JitCompiler {
         let id = self.module.declare_function("list_get", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("list_get".to_string(), id);
 
+        // list_iter_new(list: ptr) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("list_iter_new", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_iter_new".to_string(), id);
+
+        // list_iter_next(iter: ptr, out: ptr) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("list_iter_next", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_iter_next".to_string(), id);
+
+        // list_iter_free(iter: ptr)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("list_iter_free", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_iter_free".to_string(), id);
+
         // list_set(list: ptr, index: i64, value: i64) -> i64
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -1050,6 +2106,29 @@ impl JitCompiler {
         let id = self.module.declare_function("list_set", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("list_set".to_string(), id);
 
+        // float 元素按 f64 传值：list_push_f64(list, f64) / list_get_f64(list, i64) -> f64 /
+        // list_set_f64(list, i64, f64) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::F64));
+        let id = self.module.declare_function("list_push_f64", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_push_f64".to_string(), id);
+
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::F64));
+        let id = self.module.declare_function("list_get_f64", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_get_f64".to_string(), id);
+
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::F64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("list_set_f64", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_set_f64".to_string(), id);
+
         // list_pop(list: ptr) -> i64
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -1122,6 +2201,81 @@ impl JitCompiler {
         let id = self.module.declare_function("list_sort", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("list_sort".to_string(), id);
 
+        // list_dedup(list: ptr) -> void
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("list_dedup", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_dedup".to_string(), id);
+
+        // list_unique(list: ptr) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("list_unique", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_unique".to_string(), id);
+
+        // list_group_by(list: ptr, key_fn: i64, key_type: u8) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I8));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("list_group_by", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_group_by".to_string(), id);
+
+        // list_sort_by(list: ptr, cmp_fn: i64)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("list_sort_by", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_sort_by".to_string(), id);
+
+        // list_sort_by_key(list: ptr, key_fn: i64, key_type: u8)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I8));
+        let id = self.module.declare_function("list_sort_by_key", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_sort_by_key".to_string(), id);
+
+        // list_min_by / list_max_by(list: ptr, key_fn: i64, key_type: u8) -> i64
+        for name in ["list_min_by", "list_max_by"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.params.push(AbiParam::new(types::I64));
+            sig.params.push(AbiParam::new(types::I8));
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // list_map(list: ptr, func: i64, result_type: u8) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I8));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("list_map", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_map".to_string(), id);
+
+        // list_filter(list: ptr, func: i64) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("list_filter", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_filter".to_string(), id);
+
+        // list_reduce(list: ptr, func: i64, init: i64, acc_type: u8) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I8));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("list_reduce", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("list_reduce".to_string(), id);
+
         // list_slice(list: ptr, start: i64, end: i64) -> ptr
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -1152,12 +2306,62 @@ impl JitCompiler {
         let id = self.module.declare_function("list_last", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("list_last".to_string(), id);
 
+        // ===== 列表聚合 =====
+        // list_sum_int(list) -> i64, list_sum_float(list) -> f64, list_sum_bigint(list) -> ptr, ...
+        let aggregate_returns = [
+            ("list_sum_int", types::I64),
+            ("list_min_int", types::I64),
+            ("list_max_int", types::I64),
+            ("list_sum_float", types::F64),
+            ("list_min_float", types::F64),
+            ("list_max_float", types::F64),
+            ("list_mean", types::F64),
+            ("list_mean_float", types::F64),
+            ("list_mean_bigint", types::F64),
+            ("list_sum_bigint", ptr),
+            ("list_min_bigint", ptr),
+            ("list_max_bigint", ptr),
+            ("list_sum_decimal", ptr),
+            ("list_min_decimal", ptr),
+            ("list_max_decimal", ptr),
+            ("list_mean_decimal", ptr),
+        ];
+        for (name, ret) in aggregate_returns {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ret));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
         // print_list(list: ptr) -> void
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
         let id = self.module.declare_function("print_list", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("print_list".to_string(), id);
 
+        // ===== Packed 列表函数 =====
+        // packed_list_new(elem_size, len, desc, desc_len) -> ptr; packed_list_at / push 返回元素地址
+        let packed_list_funcs: [(&str, &[types::Type], Option<types::Type>); 10] = [
+            ("packed_list_new", &[types::I64, types::I64, ptr, types::I64], Some(ptr)),
+            ("packed_list_release", &[ptr], None),
+            ("packed_list_clone", &[ptr], Some(ptr)),
+            ("packed_list_len", &[ptr], Some(types::I64)),
+            ("packed_list_at", &[ptr, types::I64], Some(ptr)),
+            ("packed_list_push", &[ptr], Some(ptr)),
+            ("packed_list_resize", &[ptr, types::I64], None),
+            ("packed_list_swap", &[ptr, types::I64, types::I64], None),
+            ("print_packed_list", &[ptr], None),
+            ("packed_to_string", &[ptr, ptr, types::I64], Some(ptr)),
+        ];
+        for (name, params, ret) in packed_list_funcs {
+            let mut sig = self.module.make_signature();
+            sig.params.extend(params.iter().map(|&t| AbiParam::new(t)));
+            sig.returns.extend(ret.map(AbiParam::new));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
         // ===== Dict 函数 =====
         // dict_new(key_type: u8, value_type: u8) -> ptr
         let mut sig = self.module.make_signature();
@@ -1202,6 +2406,23 @@ impl JitCompiler {
         let id = self.module.declare_function("dict_get", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("dict_get".to_string(), id);
 
+        // dict_new_default(key_type: i8, value_type: i8) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I8));
+        sig.params.push(AbiParam::new(types::I8));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("dict_new_default", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("dict_new_default".to_string(), id);
+
+        // dict_get_or_insert_empty(dict: ptr, key: i64, elem_tag: i8) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I8));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("dict_get_or_insert_empty", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("dict_get_or_insert_empty".to_string(), id);
+
         // dict_contains(dict: ptr, key: i64) -> i64
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -1265,6 +2486,28 @@ impl JitCompiler {
         let id = self.module.declare_function("print_dict", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("print_dict".to_string(), id);
 
+        // ===== weak_dict 函数 =====
+        // weakdict_new(value_type: u8) -> ptr；键是对象指针，值按 i64 存储
+        let weak_dict_funcs: [(&str, &[types::Type], Option<types::Type>); 10] = [
+            ("weakdict_new", &[types::I8], Some(ptr)),
+            ("weakdict_release", &[ptr], None),
+            ("weakdict_clone", &[ptr], Some(ptr)),
+            ("weakdict_set", &[ptr, ptr, types::I64], None),
+            ("weakdict_get", &[ptr, ptr], Some(types::I64)),
+            ("weakdict_contains", &[ptr, ptr], Some(types::I64)),
+            ("weakdict_remove", &[ptr, ptr], Some(types::I64)),
+            ("weakdict_len", &[ptr], Some(types::I64)),
+            ("weakdict_is_empty", &[ptr], Some(types::I64)),
+            ("weakdict_compact", &[ptr], Some(types::I64)),
+        ];
+        for (name, params, ret) in weak_dict_funcs {
+            let mut sig = self.module.make_signature();
+            sig.params.extend(params.iter().map(|&t| AbiParam::new(t)));
+            sig.returns.extend(ret.map(AbiParam::new));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
         // dynamic_clone(ptr) -> ptr
 
 
@@ -1413,21 +2656,31 @@ impl JitCompiler {
         let id = self.module.declare_function("decimal_div", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("decimal_div".to_string(), id);
 
-        // decimal_eq(ptr, ptr) -> i64
+        // decimal_rem(ptr, ptr) -> ptr
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
         sig.params.push(AbiParam::new(ptr));
-        sig.returns.push(AbiParam::new(types::I64));
-        let id = self.module.declare_function("decimal_eq", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
-        self.functions.insert("decimal_eq".to_string(), id);
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("decimal_rem", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("decimal_rem".to_string(), id);
 
-        // decimal_lt(ptr, ptr) -> i64
+        // decimal_eq(ptr, ptr) -> i64
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
         sig.params.push(AbiParam::new(ptr));
         sig.returns.push(AbiParam::new(types::I64));
-        let id = self.module.declare_function("decimal_lt", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
-        self.functions.insert("decimal_lt".to_string(), id);
+        let id = self.module.declare_function("decimal_eq", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("decimal_eq".to_string(), id);
+
+        // decimal_lt / le / gt / ge(ptr, ptr) -> i64
+        for name in ["decimal_lt", "decimal_le", "decimal_gt", "decimal_ge"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
 
         // decimal_to_i64(ptr) -> i64
         let mut sig = self.module.make_signature();
@@ -1564,6 +2817,99 @@ impl JitCompiler {
         let id = self.module.declare_function("string_eq", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("string_eq".to_string(), id);
 
+        // string_char_at(ptr, i64) -> i64  (s[i]，字符码点，越界 -1)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("string_char_at", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_char_at".to_string(), id);
+
+        // string_slice(ptr, start: i64, end: i64) -> ptr  (s[a:b]，新字符串)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("string_slice", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_slice".to_string(), id);
+
+        // string_split(ptr, sep: ptr) -> ptr  (list<str>，sep 为空指针时按空白拆分)
+        // string_contains(ptr, needle: ptr) -> i64
+        // string_encode(ptr, encoding: ptr) -> ptr  (list<int> 字节)
+        for (name, ret) in [("string_split", ptr), ("string_contains", types::I64), ("string_encode", ptr)] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ret));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // string_trim / string_upper / string_lower(ptr) -> ptr  (新字符串)
+        for name in ["string_trim", "string_upper", "string_lower"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(ptr));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // string_replace(ptr, from: ptr, to: ptr) -> ptr  (新字符串)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("string_replace", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_replace".to_string(), id);
+
+        // bytes_decode(list, encoding: ptr, mode: ptr) -> ptr  (新字符串，mode 为空指针时为 strict)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bytes_decode", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("bytes_decode".to_string(), id);
+
+        // bytes_decode_latin1(list) -> ptr  (新字符串)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("bytes_decode_latin1", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("bytes_decode_latin1".to_string(), id);
+
+        // string_len(ptr) -> i64  (字符个数)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("string_len", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_len".to_string(), id);
+
+        // string_intern(ptr) -> ptr  (驻留实例)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("string_intern", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_intern".to_string(), id);
+
+        // string_intern_stats() / string_intern_clear() -> i64  (驻留表条目数 / 清理的条目数)
+        for name in ["string_intern_stats", "string_intern_clear"] {
+            let mut sig = self.module.make_signature();
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // string_cmp(ptr, ptr) -> i64  (-1 / 0 / 1)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("string_cmp", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_cmp".to_string(), id);
+
         // ===== 内存分配函数 =====
         // bolide_alloc(i64) -> ptr
         let mut sig = self.module.make_signature();
@@ -1619,6 +2965,21 @@ impl JitCompiler {
         let id = self.module.declare_function("thread_join_int", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("thread_join_int".to_string(), id);
 
+        // thread_take_error(ptr) -> i64, pool_take_error(ptr) -> i64
+        let id = self.module.declare_function("thread_take_error", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("thread_take_error".to_string(), id);
+        let id = self.module.declare_function("pool_take_error", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("pool_take_error".to_string(), id);
+
+        // thread_set_name / pool_set_name / coroutine_set_name(handle, name)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        for name in ["thread_set_name", "pool_set_name", "coroutine_set_name"] {
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
         // thread_join_float(ptr) -> f64
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -1666,17 +3027,115 @@ impl JitCompiler {
         let id = self.module.declare_function("pool_enter", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("pool_enter".to_string(), id);
 
-        // pool_exit()
+        // pool_exit(ptr)
         let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
         let id = self.module.declare_function("pool_exit", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("pool_exit".to_string(), id);
 
+        // interrupt_poll(state: ptr) -> i64  (回边的 fuel 用完时调用，1 = 中断)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("interrupt_poll", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("interrupt_poll".to_string(), id);
+
+        // interrupt_unwind(state: ptr)  (中断后每个提前返回的函数帧调用一次)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("interrupt_unwind", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("interrupt_unwind".to_string(), id);
+
         // pool_is_active() -> i64
         let mut sig = self.module.make_signature();
         sig.returns.push(AbiParam::new(types::I64));
         let id = self.module.declare_function("pool_is_active", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("pool_is_active".to_string(), id);
 
+        // thread_id() -> i64
+        let mut sig = self.module.make_signature();
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("thread_id", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("thread_id".to_string(), id);
+
+        // coroutine_cancelled() -> i64
+        let id = self.module.declare_function("coroutine_cancelled", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("coroutine_cancelled".to_string(), id);
+
+        // live_threads() -> i64
+        let mut sig = self.module.make_signature();
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("live_threads", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("live_threads".to_string(), id);
+
+        // tls_alloc(init: ptr, dtor: ptr) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("tls_alloc", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("tls_alloc".to_string(), id);
+
+        // tls_get(key: i64) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("tls_get", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("tls_get".to_string(), id);
+
+        // tls_thread_exit()
+        let sig = self.module.make_signature();
+        let id = self.module.declare_function("tls_thread_exit", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("tls_thread_exit".to_string(), id);
+
+        // platform() -> ptr, temp_dir() -> ptr
+        let mut sig = self.module.make_signature();
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("platform", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("platform".to_string(), id);
+        let id = self.module.declare_function("temp_dir", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("temp_dir".to_string(), id);
+
+        // trace_push(name: ptr, len: usize, line: i64) / trace_pop()
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("trace_push", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("trace_push".to_string(), id);
+        let sig = self.module.make_signature();
+        let id = self.module.declare_function("trace_pop", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("trace_pop".to_string(), id);
+
+        // version() -> ptr
+        let mut sig = self.module.make_signature();
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("version", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("version".to_string(), id);
+
+        // version_at_least(ptr) -> i64 / has_feature(ptr) -> i64
+        for name in ["version_at_least", "has_feature"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // error_code() -> i64 / error_message() -> ptr / error_clear()
+        for (name, ret) in [("error_code", Some(types::I64)), ("error_message", Some(ptr)), ("error_clear", None)] {
+            let mut sig = self.module.make_signature();
+            sig.returns.extend(ret.map(AbiParam::new));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // exit(code: i64)，不返回
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("exit", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("exit".to_string(), id);
+
         // pool_spawn_int(fn() -> i64) -> ptr
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));  // 函数指针
@@ -1771,6 +3230,57 @@ impl JitCompiler {
         let id = self.module.declare_function("channel_recv", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("channel_recv".to_string(), id);
 
+        // channel_send_ptr(ptr, i64, tag: u8) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I8));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("channel_send_ptr", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("channel_send_ptr".to_string(), id);
+
+        // channel_recv_ptr(ptr) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("channel_recv_ptr", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("channel_recv_ptr".to_string(), id);
+
+        // channel_recv_opt(ptr, out_ptr) -> i64 / channel_recv_opt_ptr
+        for name in ["channel_recv_opt", "channel_recv_opt_ptr"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(ptr));
+            sig.params.push(AbiParam::new(ptr));
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // channel_send_all(ptr, list) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("channel_send_all", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("channel_send_all".to_string(), id);
+
+        // channel_recv_n(ptr, i64, list) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("channel_recv_n", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("channel_recv_n".to_string(), id);
+
+        // channel_drain(ptr, u8) -> list
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I8));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("channel_drain", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("channel_drain".to_string(), id);
+
         // channel_close(ptr)
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -1783,16 +3293,25 @@ impl JitCompiler {
         let id = self.module.declare_function("channel_free", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("channel_free".to_string(), id);
 
-        // channel_select(channels_ptr, count, timeout_ms, value_ptr) -> i64
+        // channel_select(channels_ptr, count, timeout_ns, value_ptr) -> i64
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));       // channels array pointer
         sig.params.push(AbiParam::new(types::I64)); // count
-        sig.params.push(AbiParam::new(types::I64)); // timeout_ms
+        sig.params.push(AbiParam::new(types::I64)); // timeout_ns
         sig.params.push(AbiParam::new(ptr));       // value output pointer
         sig.returns.push(AbiParam::new(types::I64)); // selected index
         let id = self.module.declare_function("channel_select", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("channel_select".to_string(), id);
 
+        // channel_select_list(list, timeout_ns, value_ptr) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("channel_select_list", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("channel_select_list".to_string(), id);
+
         // ===== 协程函数 =====
         // coroutine_spawn_int(func_ptr) -> ptr
         let mut sig = self.module.make_signature();
@@ -1825,6 +3344,8 @@ impl JitCompiler {
         let mut sig = self.module.make_signature();
         let id = self.module.declare_function("scope_enter", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("scope_enter".to_string(), id);
+        let id = self.module.declare_function("scope_enter_fail_fast", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("scope_enter_fail_fast".to_string(), id);
 
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -1874,6 +3395,12 @@ impl JitCompiler {
         let id = self.module.declare_function("coroutine_free", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("coroutine_free".to_string(), id);
 
+        // coroutine_set_workers(i64)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("coroutine_set_workers", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("coroutine_set_workers".to_string(), id);
+
         // ===== Tuple 函数 =====
         // tuple_new(len) -> ptr
         let mut sig = self.module.make_signature();
@@ -1922,6 +3449,101 @@ impl JitCompiler {
         let id = self.module.declare_function("tuple_debug_stats", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("tuple_debug_stats".to_string(), id);
 
+        // string_debug_stats() / weak_debug_stats()
+        for name in ["string_debug_stats", "weak_debug_stats"] {
+            let sig = self.module.make_signature();
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // object_debug_enable(i64) -> i64 / object_live_count() -> i64 / object_dump_live()
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("object_debug_enable", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("object_debug_enable".to_string(), id);
+        let mut sig = self.module.make_signature();
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("object_live_count", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("object_live_count".to_string(), id);
+        let sig = self.module.make_signature();
+        let id = self.module.declare_function("object_dump_live", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("object_dump_live".to_string(), id);
+
+        // ===== 计时 =====
+        // timer_start() / alloc_snapshot() / measure_begin() -> i64
+        for name in ["timer_start", "alloc_snapshot", "measure_begin"] {
+            let mut sig = self.module.make_signature();
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // timer_elapsed_ns(i64) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("timer_elapsed_ns", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("timer_elapsed_ns".to_string(), id);
+
+        // timer_elapsed_ms(i64) -> f64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::F64));
+        let id = self.module.declare_function("timer_elapsed_ms", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("timer_elapsed_ms".to_string(), id);
+
+        // deadline(i64) / deadline_remaining(i64) -> i64：duration 和时间点都是纳秒
+        for name in ["deadline", "deadline_remaining"] {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(types::I64));
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // string_from_duration(i64) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("string_from_duration", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("string_from_duration".to_string(), id);
+
+        // measure_end(label_ptr, label_len, start, alloc_start, early)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        for _ in 0..4 {
+            sig.params.push(AbiParam::new(types::I64));
+        }
+        let id = self.module.declare_function("measure_end", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("measure_end".to_string(), id);
+
+        // ===== 哈希 =====
+        // hash_int(i64) / hash_float(f64) / hash_string(ptr) / ... -> i64
+        let hash_params = [
+            ("hash_int", types::I64),
+            ("hash_float", types::F64),
+            ("hash_string", ptr),
+            ("hash_bigint", ptr),
+            ("hash_decimal", ptr),
+            ("hash_dynamic", ptr),
+        ];
+        for (name, param) in hash_params {
+            let mut sig = self.module.make_signature();
+            sig.params.push(AbiParam::new(param));
+            sig.returns.push(AbiParam::new(types::I64));
+            let id = self.module.declare_function(name, Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+            self.functions.insert(name.to_string(), id);
+        }
+
+        // hash_combine(seed, value) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("hash_combine", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("hash_combine".to_string(), id);
+
         // ===== FFI 函数 =====
         // ffi_load_library(path_ptr) -> i64
         let mut sig = self.module.make_signature();
@@ -1969,6 +3591,13 @@ impl JitCompiler {
         let id = self.module.declare_function("object_release", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("object_release".to_string(), id);
 
+        // object_refcount(ptr) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("object_refcount", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("object_refcount".to_string(), id);
+
         // object_retain(ptr)
         let mut sig = self.module.make_signature();
         sig.params.push(AbiParam::new(ptr));
@@ -1982,6 +3611,56 @@ impl JitCompiler {
         let id = self.module.declare_function("object_clone", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
         self.functions.insert("object_clone".to_string(), id);
 
+        // object_to_string(ptr, formatter) -> ptr
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(ptr));
+        let id = self.module.declare_function("object_to_string", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("object_to_string".to_string(), id);
+
+        // object_equals(a, b, eq) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("object_equals", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("object_equals".to_string(), id);
+
+        // ===== 绑定函数 =====
+        // bind(func: i64, args: ptr, tags: ptr, count: i64) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("bind", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("bind".to_string(), id);
+
+        // closure_call(func: i64, args: ptr, argc: i64) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("closure_call", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("closure_call".to_string(), id);
+
+        // func_retain(func: i64) -> i64
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("func_retain", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("func_retain".to_string(), id);
+
+        // func_release(func: i64)
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(types::I64));
+        let id = self.module.declare_function("func_release", Linkage::Import, &sig).map_err(|e| format!("{}", e))?;
+        self.functions.insert("func_release".to_string(), id);
+
         Ok(())
     }
 
@@ -2007,6 +3686,11 @@ impl JitCompiler {
         self.ctx.func.signature = sig;
         self.ctx.func.name = cranelift_codegen::ir::UserFuncName::user(0, func_id.as_u32());
 
+        #[cfg(test)]
+        if self.bad_signature_for.as_deref() == Some(func.name.as_str()) {
+            self.ctx.func.signature.returns.push(AbiParam::new(types::I64));
+        }
+
         // 创建函数构建器
         let mut builder_ctx = FunctionBuilderContext::new();
         let mut builder = FunctionBuilder::new(&mut self.ctx.func, &mut builder_ctx);
@@ -2017,24 +3701,6 @@ impl JitCompiler {
         builder.switch_to_block(entry_block);
         builder.seal_block(entry_block);
 
-        // 预先收集函数引用
-        let mut func_refs = HashMap::new();
-        for (name, id) in &self.functions {
-            let func_ref = self.module.declare_func_in_func(*id, builder.func);
-            func_refs.insert(name.clone(), func_ref);
-        }
-
-        // 收集 trampoline 引用
-        let mut trampoline_refs = HashMap::new();
-        let mut trampoline_param_types = HashMap::new();
-        let mut trampoline_env_sizes = HashMap::new();
-        for (target_func, info) in &self.trampolines {
-            let func_ref = self.module.declare_func_in_func(info.func_id, builder.func);
-            trampoline_refs.insert(target_func.clone(), func_ref);
-            trampoline_param_types.insert(target_func.clone(), info.param_types.clone());
-            trampoline_env_sizes.insert(target_func.clone(), info.env_size);
-        }
-
         let ptr_type = self.ptr_type;
         let func_return_types = self.func_return_types.clone();
         let func_params = self.func_params.clone();
@@ -2051,12 +3717,10 @@ impl JitCompiler {
             &mut self.module,
             &self.global_data_ids,
             &self.global_var_types,
-            func_refs,
+            &self.functions,
             func_return_types,
             func_params,
-            trampoline_refs,
-            trampoline_param_types,
-            trampoline_env_sizes,
+            &self.trampolines,
             ptr_type,
             classes,
             async_funcs,
@@ -2065,7 +3729,16 @@ impl JitCompiler {
             func.lifetime_deps.clone(),
             func.name.clone(),
             lifetime_funcs,
+            &self.pure_funcs,
         );
+        compile_ctx.interrupt_state = self.interrupt.as_ref().map(|s| Arc::as_ptr(s) as i64);
+        compile_ctx.share_containers = self.share_containers;
+        compile_ctx.thread_locals = self.thread_locals.clone();
+        compile_ctx.hot_slots = self.hot.as_ref().map(HotState::slots).unwrap_or_default();
+        if self.debug && func.name != GLOBALS_RELEASE && !func.name.starts_with(TLS_PREFIX) {
+            let name = if func.name == "__main__" { "<main>" } else { func.name.as_str() };
+            compile_ctx.emit_trace_push(name, func.line)?;
+        }
 
         // 绑定参数到变量
         let params = compile_ctx.builder.block_params(entry_block).to_vec();
@@ -2087,20 +3760,29 @@ impl JitCompiler {
                     // 对于需要 RC 管理的类型，注册到 rc_variables
                     if CompileContext::is_rc_type(&param.ty) {
                         compile_ctx.rc_variables.push((param.name.clone(), param.ty.clone()));
+                        compile_ctx.note_rc_variable(&param.name, &param.ty);
                     }
                 }
                 ParamMode::Ref => {
-                    // Ref 参数：参数是指针地址，需要解引用
+                    // Ref 参数：参数是调用者变量的地址，值缓存在变量中，每次赋值写穿到该地址
                     let ptr_addr = params[i];
                     let val = compile_ctx.builder.ins().load(ptr_type, MemFlags::new(), ptr_addr, 0);
                     let var = compile_ctx.declare_variable(&param.name, param_types[i]);
                     compile_ctx.builder.def_var(var, val);
-                    // 记录 Ref 参数，以便在函数返回前写回
                     compile_ctx.ref_params.push((param.name.clone(), var, ptr_addr));
                 }
             }
         }
 
+        if func.name == "__main__" {
+            compile_ctx.emit_thread_local_setup(&self.pending_thread_locals)?;
+        }
+        if let Some(decl) = func.name.strip_prefix(TLS_INIT_PREFIX)
+            .and_then(|name| self.pending_thread_locals.iter().find(|decl| decl.name == name))
+        {
+            compile_ctx.emit_thread_local_init(decl)?;
+        }
+
         // 编译函数体
         let mut terminated = false;
         for stmt in &func.body {
@@ -2109,6 +3791,9 @@ impl JitCompiler {
             }
             terminated = compile_ctx.compile_stmt(stmt)?;
         }
+        if func.name == GLOBALS_RELEASE {
+            compile_ctx.emit_globals_release()?;
+        }
 
         // 如果没有显式 return，返回默认值或空
         if !terminated {
@@ -2117,9 +3802,8 @@ impl JitCompiler {
                 // 在隐式返回之前释放所有 RC 变量
                 compile_ctx.emit_rc_cleanup();
             }
-
-            // 写回 Ref 参数
-            compile_ctx.write_back_ref_params();
+            compile_ctx.emit_trace_pop()?;
+            compile_ctx.emit_main_flush()?;
 
             if let Some(ref ret_ty) = func.return_type {
                 let zero = match ret_ty {
@@ -2131,17 +3815,84 @@ impl JitCompiler {
                 compile_ctx.builder.ins().return_(&[]);
             }
         }
+        let list_chunks = std::mem::take(&mut compile_ctx.list_chunks);
+        let call_edges = std::mem::take(&mut compile_ctx.call_edges);
+        self.warnings.append(&mut compile_ctx.warnings);
+        let mut report = FunctionReport {
+            rc_variables: std::mem::take(&mut compile_ctx.rc_variables_seen),
+            moved_variables: compile_ctx.moved_variables.iter().cloned().collect(),
+            temps_tracked: compile_ctx.temps_tracked,
+            lifetime_mode: func.lifetime_deps.is_some(),
+            pure_calls_folded: compile_ctx.pure_folded,
+            pure_calls_reused: compile_ctx.pure_reused,
+            pure_calls_hoisted: compile_ctx.pure_hoisted,
+            ..FunctionReport::default()
+        };
 
         builder.finalize();
+        if self.opt_level > 0 {
+            report.rc_pairs_removed = rc_peephole::remove_cancelled_clones(&mut self.ctx.func, &self.functions, &self.func_params);
+        }
+        if self.analysis.is_some() {
+            report.rc_pairs_cancellable = rc_peephole::cancelled_clones(&self.ctx.func, &self.functions, &self.func_params);
+        }
+        self.record_analysis(&func.name, call_edges, report);
+        // REPL 的函数声明为匿名符号，首行换成源码里的函数名
+        let clif = self.function_ir.is_some().then(|| {
+            let header = format!("function {}", self.ctx.func.name);
+            self.ctx.func.display().to_string().replacen(&header, &format!("function %{}", func.name), 1)
+        });
 
         // 定义函数
-        self.module.define_function(func_id, &mut self.ctx)
-            .map_err(|e| format!("Define function error: {}", e))?;
+        if let Err(e) = self.module.define_function(func_id, &mut self.ctx) {
+            let msg = if ice::is_verifier_error(&e) {
+                let detail = ice::error_detail(&e, &self.ctx.func);
+                self.internal_compiler_error(&func.name, detail, format!("{:#?}", func))
+            } else {
+                format!("Define function error: {}", e)
+            };
+            self.module.clear_context(&mut self.ctx);
+            return Err(msg);
+        }
         self.module.clear_context(&mut self.ctx);
+        if let (Some(ir), Some(clif)) = (&mut self.function_ir, clif) {
+            ir.insert(func.name.clone(), clif);
+        }
+
+        // 大列表字面量的辅助函数（已在字面量处声明）
+        for (chunk, chunk_id) in list_chunks {
+            self.functions.insert(chunk.name.clone(), chunk_id);
+            self.func_return_types.insert(chunk.name.clone(), chunk.return_type.clone());
+            self.compile_function_guarded(&chunk)?;
+        }
 
         Ok(())
     }
 
+    /// 编译函数，并把编译器自身的 panic 转为指明函数名的 ICE
+    fn compile_function_guarded(&mut self, func: &FuncDef) -> Result<(), String> {
+        match panic::catch_unwind(AssertUnwindSafe(|| self.compile_function(func))) {
+            Ok(result) => result,
+            Err(payload) => {
+                let detail = format!("compiler panicked: {}", ice::panic_message(payload.as_ref()));
+                let msg = self.internal_compiler_error(&func.name, detail, format!("{:#?}", func));
+                self.module.clear_context(&mut self.ctx);
+                Err(msg)
+            }
+        }
+    }
+
+    /// 以当前上下文中的函数 IR 生成 ICE 报告
+    fn internal_compiler_error(&self, func_name: &str, detail: String, ast: String) -> String {
+        let report = IceReport {
+            func_name,
+            detail,
+            clif: self.ctx.func.display().to_string(),
+            ast,
+        };
+        ice::report(&report, self.source.as_ref())
+    }
+
     /// 收集需要 trampoline 的 spawn 目标函数
     fn collect_spawn_targets(&self, program: &Program) -> Vec<String> {
         let mut targets = Vec::new();
@@ -2183,6 +3934,12 @@ impl JitCompiler {
                     self.collect_spawn_targets_in_stmt(s, targets);
                 }
             }
+            Statement::For(for_stmt) => {
+                self.collect_spawn_targets_in_expr(&for_stmt.iter, targets);
+                for s in &for_stmt.body {
+                    self.collect_spawn_targets_in_stmt(s, targets);
+                }
+            }
             Statement::Pool(pool_stmt) => {
                 self.collect_spawn_targets_in_expr(&pool_stmt.size, targets);
                 for s in &pool_stmt.body {
@@ -2197,6 +3954,9 @@ impl JitCompiler {
             Statement::Assign(assign) => {
                 self.collect_spawn_targets_in_expr(&assign.value, targets);
             }
+            Statement::Destructure(destructure) => {
+                self.collect_spawn_targets_in_expr(&destructure.value, targets);
+            }
             Statement::Expr(expr) => {
                 self.collect_spawn_targets_in_expr(expr, targets);
             }
@@ -2211,6 +3971,24 @@ impl JitCompiler {
                     self.collect_spawn_targets_in_stmt(s, targets);
                 }
             }
+            Statement::Measure(measure_stmt) => {
+                for s in &measure_stmt.body {
+                    self.collect_spawn_targets_in_stmt(s, targets);
+                }
+            }
+            Statement::Match(match_stmt) => {
+                self.collect_spawn_targets_in_expr(&match_stmt.subject, targets);
+                for arm in &match_stmt.arms {
+                    for s in &arm.body {
+                        self.collect_spawn_targets_in_stmt(s, targets);
+                    }
+                }
+                if let Some(ref default) = match_stmt.default {
+                    for s in default {
+                        self.collect_spawn_targets_in_stmt(s, targets);
+                    }
+                }
+            }
             Statement::AsyncSelect(select_stmt) => {
                 for branch in &select_stmt.branches {
                     let (expr, body) = match branch {
@@ -2245,7 +4023,13 @@ impl JitCompiler {
                 self.collect_spawn_targets_in_expr(left, targets);
                 self.collect_spawn_targets_in_expr(right, targets);
             }
-            Expr::UnaryOp(_, operand) => {
+            Expr::CompareChain(first, rest) => {
+                self.collect_spawn_targets_in_expr(first, targets);
+                for (_, operand) in rest {
+                    self.collect_spawn_targets_in_expr(operand, targets);
+                }
+            }
+            Expr::UnaryOp(_, operand) | Expr::NamedArg(_, operand) => {
                 self.collect_spawn_targets_in_expr(operand, targets);
             }
             Expr::Call(callee, args) => {
@@ -2266,21 +4050,39 @@ impl JitCompiler {
                 self.collect_spawn_targets_in_expr(base, targets);
                 self.collect_spawn_targets_in_expr(idx, targets);
             }
+            Expr::Slice(base, start, end) => {
+                self.collect_spawn_targets_in_expr(base, targets);
+                for bound in [start, end].into_iter().flatten() {
+                    self.collect_spawn_targets_in_expr(bound, targets);
+                }
+            }
             Expr::Member(base, _) => {
                 self.collect_spawn_targets_in_expr(base, targets);
             }
-            Expr::List(items) => {
+            Expr::List(items) | Expr::Tuple(items) | Expr::FString(items) | Expr::AwaitAll(items) => {
                 for item in items {
                     self.collect_spawn_targets_in_expr(item, targets);
                 }
             }
-            _ => {}
+            Expr::Dict(pairs) => {
+                for (key, value) in pairs {
+                    self.collect_spawn_targets_in_expr(key, targets);
+                    self.collect_spawn_targets_in_expr(value, targets);
+                }
+            }
+            Expr::Await(inner) => {
+                self.collect_spawn_targets_in_expr(inner, targets);
+            }
+            _ => {}
         }
     }
 
     /// 为目标函数生成 trampoline
     fn generate_trampolines(&mut self, targets: &[String]) -> Result<(), String> {
         for func_name in targets {
+            if self.trampolines.contains_key(func_name) {
+                continue;
+            }
             self.create_trampoline(func_name)?;
         }
         Ok(())
@@ -2349,8 +4151,12 @@ impl JitCompiler {
             call_args.push(val);
         }
 
-        // 调用目标函数
-        let call = builder.ins().call(target_func_ref, &call_args);
+        // 调用目标函数（热重载模式下经分派槽）
+        let hot_slot = self.hot.as_ref().and_then(|state| state.functions.get(target_func_name)).map(|func| func.slot);
+        let call = match hot_slot {
+            Some(slot) => hot::emit_slot_call(&mut builder, &mut self.module, slot, target_func_ref, &call_args),
+            None => builder.ins().call(target_func_ref, &call_args),
+        };
 
         // 返回结果（先复制结果值以避免借用冲突）
         let result_val = {
@@ -2365,7 +4171,9 @@ impl JitCompiler {
                 BolideType::BigInt => Some("bigint_release"),
                 BolideType::Decimal => Some("decimal_release"),
                 BolideType::List(_) => Some("list_release"),
+                BolideType::PackedList(_) => Some("packed_list_release"),
                 BolideType::Dynamic => Some("dynamic_release"),
+                BolideType::Func | BolideType::FuncSig(_, _) => Some("func_release"),
                 _ => None,
             };
             if let Some(release_name) = release_func {
@@ -2385,8 +4193,16 @@ impl JitCompiler {
         builder.finalize();
 
         // 定义 trampoline 函数
-        self.module.define_function(trampoline_id, &mut self.ctx)
-            .map_err(|e| format!("Define trampoline error: {}", e))?;
+        if let Err(e) = self.module.define_function(trampoline_id, &mut self.ctx) {
+            let msg = if ice::is_verifier_error(&e) {
+                let detail = ice::error_detail(&e, &self.ctx.func);
+                self.internal_compiler_error(&trampoline_name, detail, format!("trampoline for `{}`", target_func_name))
+            } else {
+                format!("Define trampoline error: {}", e)
+            };
+            self.module.clear_context(&mut self.ctx);
+            return Err(msg);
+        }
         self.module.clear_context(&mut self.ctx);
 
         // 存储 trampoline 信息
@@ -2406,6 +4222,7 @@ impl JitCompiler {
             BolideType::Int => types::I64,
             BolideType::Float => types::F64,
             BolideType::Bool => types::I64,
+            BolideType::Duration | BolideType::Deadline => types::I64,
             BolideType::Str => self.ptr_type,
             BolideType::BigInt => self.ptr_type,
             BolideType::Decimal => self.ptr_type,
@@ -2417,11 +4234,13 @@ impl JitCompiler {
             BolideType::FuncSig(_, _) => self.ptr_type,  // 带签名的函数指针
             BolideType::List(_) => self.ptr_type,
             BolideType::Dict(_, _) => self.ptr_type,  // 字典作为指针
+            BolideType::WeakDict(_, _) => self.ptr_type,
             BolideType::Tuple(_) => self.ptr_type,  // 元组作为指针
 
             BolideType::Custom(_) => self.ptr_type,
             BolideType::Weak(inner) => self.bolide_type_to_cranelift(inner),
             BolideType::Unowned(inner) => self.bolide_type_to_cranelift(inner),
+            BolideType::PackedList(_) | BolideType::PackedRef(_) => self.ptr_type,
         }
     }
 
@@ -2435,6 +4254,15 @@ impl JitCompiler {
             }
         }
 
+        // 继承环和字段默认值的构造环会让下面的处理和构造函数调用无限展开
+        let defs: Vec<&ClassDef> = program.statements.iter()
+            .filter_map(|stmt| match stmt {
+                Statement::ClassDef(class) => Some(class),
+                _ => None,
+            })
+            .collect();
+        class_graph::check_classes(&defs)?;
+
         // 按继承顺序处理（父类先于子类）
         let mut processed: HashSet<String> = HashSet::new();
         let names: Vec<String> = class_defs.keys().cloned().collect();
@@ -2459,9 +4287,11 @@ impl JitCompiler {
         let class_def = class_defs.get(name)
             .ok_or_else(|| format!("Class not found: {}", name))?;
 
-        // 先处理父类
+        // 先处理父类（增量编译时父类可能已经定义过）
         if let Some(ref parent) = class_def.parent {
-            self.process_class_with_deps(class_defs, processed, parent)?;
+            if class_defs.contains_key(parent) || !self.classes.contains_key(parent) {
+                self.process_class_with_deps(class_defs, processed, parent)?;
+            }
         }
 
         // 构建并存储类信息
@@ -2494,8 +4324,9 @@ impl JitCompiler {
                 name: field.name.clone(),
                 ty: field.ty.clone(),
                 offset,
+                default: field.default_value.clone(),
             });
-            offset += 8;
+            offset += bolide_runtime::OBJECT_FIELD_SIZE;
         }
 
         let methods: Vec<String> = class_def.methods.iter()
@@ -2528,8 +4359,7 @@ impl JitCompiler {
         sig.returns.push(AbiParam::new(self.ptr_type));
 
         let func_name = class_name.to_string();
-        let func_id = self.module
-            .declare_function(&func_name, Linkage::Export, &sig)
+        let func_id = self.declare_user_function(&func_name, &sig)
             .map_err(|e| format!("Declare constructor error: {}", e))?;
 
         self.functions.insert(func_name.clone(), func_id);
@@ -2541,6 +4371,7 @@ impl JitCompiler {
                 name: f.name.clone(),
                 ty: f.ty.clone(),
                 mode: ParamMode::Borrow,
+                default: f.default.clone(),
             })
             .collect();
         self.func_params.insert(func_name, params);
@@ -2608,8 +4439,16 @@ impl JitCompiler {
         builder.finalize();
 
         // 编译函数
-        self.module.define_function(func_id, &mut self.ctx)
-            .map_err(|e| format!("Define constructor error: {}", e))?;
+        if let Err(e) = self.module.define_function(func_id, &mut self.ctx) {
+            let msg = if ice::is_verifier_error(&e) {
+                let detail = ice::error_detail(&e, &self.ctx.func);
+                self.internal_compiler_error(class_name, detail, format!("constructor of class `{}`", class_name))
+            } else {
+                format!("Define constructor error: {}", e)
+            };
+            self.module.clear_context(&mut self.ctx);
+            return Err(msg);
+        }
         self.module.clear_context(&mut self.ctx);
 
         Ok(())
@@ -2636,8 +4475,7 @@ impl JitCompiler {
                         sig.returns.push(AbiParam::new(self.bolide_type_to_cranelift(ret_ty)));
                     }
 
-                    let func_id = self.module
-                        .declare_function(&method_name, Linkage::Export, &sig)
+                    let func_id = self.declare_user_function(&method_name, &sig)
                         .map_err(|e| format!("Declare method error: {}", e))?;
 
                     self.functions.insert(method_name.clone(), func_id);
@@ -2648,6 +4486,7 @@ impl JitCompiler {
                         name: "self".to_string(),
                         ty: BolideType::Custom(class_def.name.clone()),
                         mode: ParamMode::Borrow,
+                        default: None,
                     }];
                     params_with_self.extend(method.params.clone());
                     self.func_params.insert(method_name, params_with_self);
@@ -2671,9 +4510,10 @@ impl JitCompiler {
                         name: "self".to_string(),
                         ty: BolideType::Custom(class_def.name.clone()),
                         mode: ParamMode::Borrow,
+                        default: None,
                     });
 
-                    self.compile_function(&method_with_self)?;
+                    self.compile_function_guarded(&method_with_self)?;
                 }
             }
         }
@@ -2695,6 +4535,23 @@ impl JitCompiler {
     }
 }
 
+/// `JitCompiler` 的名字表，增量编译出错时恢复
+struct Definitions {
+    functions: HashMap<String, FuncId>,
+    func_return_types: HashMap<String, Option<BolideType>>,
+    func_params: HashMap<String, Vec<Param>>,
+    trampolines: HashMap<String, TrampolineInfo>,
+    classes: HashMap<String, ClassInfo>,
+    async_funcs: HashSet<String>,
+    extern_funcs: HashMap<String, (String, bolide_parser::ExternFunc)>,
+    modules: HashMap<String, ImportedModule>,
+    lifetime_funcs: HashSet<String>,
+    global_data_ids: HashMap<String, cranelift_module::DataId>,
+    global_var_types: HashMap<String, BolideType>,
+    thread_locals: HashSet<String>,
+    pure_funcs: HashMap<String, FuncDef>,
+}
+
 impl Default for JitCompiler {
     fn default() -> Self {
         Self::new()
@@ -2707,6 +4564,9 @@ struct CompileContext<'a, 'b> {
     module: &'a mut JITModule,
     global_data_ids: &'a HashMap<String, cranelift_module::DataId>,
     global_var_types: &'a HashMap<String, BolideType>,
+    /// 函数符号表：名称 -> FuncId（引用在首次使用时才声明到当前函数）
+    functions: &'a HashMap<String, FuncId>,
+    /// 当前函数已声明的函数引用
     func_refs: HashMap<String, FuncRef>,
     variables: HashMap<String, Variable>,
     /// 变量的 Bolide 类型（用于类型推断）
@@ -2717,22 +4577,31 @@ struct CompileContext<'a, 'b> {
     func_params: HashMap<String, Vec<Param>>,
     /// spawn 变量对应的函数名（用于 join 时获取返回类型）
     spawn_func_map: HashMap<String, String>,
-    /// trampoline 函数引用
+    /// trampoline 表：目标函数名 -> trampoline 信息
+    trampolines: &'a HashMap<String, TrampolineInfo>,
+    /// 当前函数已声明的 trampoline 引用
     trampoline_refs: HashMap<String, FuncRef>,
-    /// trampoline 参数类型
-    trampoline_param_types: HashMap<String, Vec<BolideType>>,
-    /// trampoline env 大小
-    trampoline_env_sizes: HashMap<String, i64>,
     /// 需要在作用域结束时释放的 RC 变量（变量名 -> 类型）
     rc_variables: Vec<(String, BolideType)>,
     /// 当前语句中产生的临时 RC 值（值 -> 类型）
     temp_rc_values: Vec<(Value, BolideType)>,
+    /// 正在编译的 measure 块（标签、开始时间、开始时的分配计数），由外到内
+    measure_stack: Vec<(String, Value, Value)>,
+    /// 正在编译的 pool 块的线程池，由外到内；提前离开块时退出并销毁
+    pool_stack: Vec<Value>,
+    /// 正在遍历的列表迭代器，由外到内；提前 return 时释放
+    list_iter_stack: Vec<Value>,
+    /// 每层 for 循环持有的临时值（被遍历的临时容器及求值它产生的临时值），由外到内；
+    /// 循环结束或提前 return 时释放
+    loop_temps: Vec<Vec<(Value, BolideType)>>,
+    /// 正在编译的循环，由外到内
+    loops: Vec<LoopFrame>,
+    /// 下标小于它的循环在 select / await scope 块之外，break/continue 不能跳过去
+    loop_fence: usize,
     /// 已移动的变量（Owned 传递后）
     moved_variables: HashSet<String>,
-    /// Ref 参数信息（变量名, 变量, 指针地址）- 函数返回前需要写回
+    /// Ref 参数信息（变量名, 变量, 调用者变量的地址），赋值时写穿到该地址
     ref_params: Vec<(String, Variable, Value)>,
-    /// Ref 参数已被重新赋值（首次赋值后加入此集合）
-    ref_params_reassigned: HashSet<String>,
     var_counter: usize,
     ptr_type: types::Type,
     /// 类信息
@@ -2742,7 +4611,7 @@ struct CompileContext<'a, 'b> {
     /// extern 函数信息
     extern_funcs: HashMap<String, (String, bolide_parser::ExternFunc)>,
     /// 模块名映射
-    modules: HashMap<String, String>,
+    modules: HashMap<String, ImportedModule>,
     /// 生命周期依赖参数（from x, y 中的参数名）
     /// 当指定时，跳过 ARC 并执行生命周期检查
     lifetime_deps: Option<Vec<String>>,
@@ -2758,8 +4627,42 @@ struct CompileContext<'a, 'b> {
     var_scope_depth: HashMap<String, usize>,
     /// 借用变量追踪：变量名 -> (来源变量名, 来源作用域深度)
     borrowed_vars: HashMap<String, (String, usize)>,
+    /// 借用了 packed 列表元素、之后列表被修改过的变量 -> 修改列表的操作；再使用时报错
+    invalidated_borrows: HashMap<String, String>,
     /// weak 引用变量集合（访问时需要检查是否为 nil）
     weak_variables: HashSet<String>,
+    /// 已分块编译的大列表字面量个数（用于辅助函数命名）
+    chunked_lists: usize,
+    /// 大列表字面量合成的辅助函数，当前函数编译完后再编译
+    list_chunks: Vec<(FuncDef, FuncId)>,
+    /// spawn/async/extern 调用（`--analyze` 调用图用），每次调用一条
+    call_edges: Vec<(CallKind, String)>,
+    /// 登记过的 RC 变量，作用域结束后也保留（`--analyze` 用）
+    rc_variables_seen: Vec<(String, BolideType)>,
+    /// 登记过的临时 RC 值个数（`--analyze` 用）
+    temps_tracked: usize,
+    /// 编译警告（已带上所在函数），函数编译完后交给 JitCompiler
+    warnings: Vec<String>,
+    /// 验证通过的 `@pure` 函数
+    pure_funcs: &'a HashMap<String, FuncDef>,
+    /// 当前语句里已经调用过的 `@pure` 函数：(函数名, 实参, 所在块, 结果)
+    pure_calls: Vec<(String, Vec<Value>, Block, Value)>,
+    /// 提到外层循环之前的 `@pure` 调用，由外到内：调用的源码 -> (结果, 是否已被用过)
+    hoisted_pure_calls: Vec<HashMap<String, (Value, bool)>>,
+    /// 编译期求值 / 复用 / 提到循环外的 `@pure` 调用个数（`--analyze` 用）
+    pure_folded: usize,
+    pure_reused: usize,
+    pure_hoisted: usize,
+    /// 中断状态的地址，开启中断检查时才有
+    interrupt_state: Option<i64>,
+    /// 调试模式下入口压入了影子调用栈帧，每条返回路径都要弹出
+    trace_frame: bool,
+    /// 绑定已有的列表/字典时共享而不复制（`--share-containers`）
+    share_containers: bool,
+    /// threadlocal 全局变量，读写经由 `tls_get` 取得当前线程的单元
+    thread_locals: HashSet<String>,
+    /// 热重载模式下用户函数的分派槽：调用和取地址先从槽里读出当前版本
+    hot_slots: HashMap<String, cranelift_module::DataId>,
 }
 
 impl<'a, 'b> CompileContext<'a, 'b> {
@@ -2768,40 +4671,44 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         module: &'a mut JITModule,
         global_data_ids: &'a HashMap<String, cranelift_module::DataId>,
         global_var_types: &'a HashMap<String, BolideType>,
-        func_refs: HashMap<String, FuncRef>,
+        functions: &'a HashMap<String, FuncId>,
         func_return_types: HashMap<String, Option<BolideType>>,
         func_params: HashMap<String, Vec<Param>>,
-        trampoline_refs: HashMap<String, FuncRef>,
-        trampoline_param_types: HashMap<String, Vec<BolideType>>,
-        trampoline_env_sizes: HashMap<String, i64>,
+        trampolines: &'a HashMap<String, TrampolineInfo>,
         ptr_type: types::Type,
         classes: HashMap<String, ClassInfo>,
         async_funcs: HashSet<String>,
         extern_funcs: HashMap<String, (String, bolide_parser::ExternFunc)>,
-        modules: HashMap<String, String>,
+        modules: HashMap<String, ImportedModule>,
         lifetime_deps: Option<Vec<String>>,
         current_func_name: String,
         lifetime_funcs: HashSet<String>,
+        pure_funcs: &'a HashMap<String, FuncDef>,
     ) -> Self {
         Self {
             builder,
             module,
             global_data_ids,
             global_var_types,
-            func_refs,
+            functions,
+            func_refs: HashMap::new(),
             variables: HashMap::new(),
             var_types: HashMap::new(),
             func_return_types,
             func_params,
             spawn_func_map: HashMap::new(),
-            trampoline_refs,
-            trampoline_param_types,
-            trampoline_env_sizes,
+            trampolines,
+            trampoline_refs: HashMap::new(),
             rc_variables: Vec::new(),
             temp_rc_values: Vec::new(),
+            measure_stack: Vec::new(),
+            pool_stack: Vec::new(),
+            list_iter_stack: Vec::new(),
+            loop_temps: Vec::new(),
+            loops: Vec::new(),
+            loop_fence: 0,
             moved_variables: HashSet::new(),
             ref_params: Vec::new(),
-            ref_params_reassigned: HashSet::new(),
             var_counter: 0,
             ptr_type,
             classes,
@@ -2815,8 +4722,80 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             scope_depth: 0,
             var_scope_depth: HashMap::new(),
             borrowed_vars: HashMap::new(),
+            invalidated_borrows: HashMap::new(),
             weak_variables: HashSet::new(),
+            chunked_lists: 0,
+            list_chunks: Vec::new(),
+            interrupt_state: None,
+            trace_frame: false,
+            share_containers: false,
+            thread_locals: HashSet::new(),
+            hot_slots: HashMap::new(),
+            call_edges: Vec::new(),
+            rc_variables_seen: Vec::new(),
+            temps_tracked: 0,
+            warnings: Vec::new(),
+            pure_funcs,
+            pure_calls: Vec::new(),
+            hoisted_pure_calls: Vec::new(),
+            pure_folded: 0,
+            pure_reused: 0,
+            pure_hoisted: 0,
+        }
+    }
+
+    /// 调用用户函数；热重载模式下经分派槽调用当前版本
+    fn emit_user_call(&mut self, name: &str, func_ref: FuncRef, args: &[Value]) -> Inst {
+        match self.hot_slots.get(name) {
+            Some(&slot) => hot::emit_slot_call(self.builder, self.module, slot, func_ref, args),
+            None => self.builder.ins().call(func_ref, args),
+        }
+    }
+
+    /// 用户函数的地址；热重载模式下是分派槽里的当前版本
+    fn user_func_addr(&mut self, name: &str, func_ref: FuncRef) -> Value {
+        match self.hot_slots.get(name) {
+            Some(&slot) => hot::emit_slot_load(self.builder, self.module, slot),
+            None => self.builder.ins().func_addr(self.ptr_type, func_ref),
+        }
+    }
+
+    /// 记录编译警告；同一函数里相同的警告只记一次
+    fn warn(&mut self, message: &str) {
+        let location = if self.current_func_name == "__main__" {
+            "top-level code".to_string()
+        } else {
+            format!("function '{}'", self.current_func_name)
+        };
+        let warning = format!("in {}: {}", location, message);
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// 取得函数引用：首次使用时声明到当前函数并缓存
+    ///
+    /// 只声明实际调用到的函数，外部名表不会随程序和运行时的函数总数增长。
+    fn get_func_ref(&mut self, name: &str) -> Result<FuncRef, String> {
+        if let Some(&func_ref) = self.func_refs.get(name) {
+            return Ok(func_ref);
+        }
+        let func_id = *self.functions.get(name)
+            .ok_or_else(|| format!("{} not found", name))?;
+        let func_ref = self.module.declare_func_in_func(func_id, self.builder.func);
+        self.func_refs.insert(name.to_string(), func_ref);
+        Ok(func_ref)
+    }
+
+    /// 取得目标函数的 trampoline 引用（首次使用时声明）
+    fn get_trampoline_ref(&mut self, target: &str) -> Option<FuncRef> {
+        if let Some(&func_ref) = self.trampoline_refs.get(target) {
+            return Some(func_ref);
         }
+        let func_id = self.trampolines.get(target)?.func_id;
+        let func_ref = self.module.declare_func_in_func(func_id, self.builder.func);
+        self.trampoline_refs.insert(target.to_string(), func_ref);
+        Some(func_ref)
     }
 
     /// 规范化类型名称
@@ -2843,6 +4822,10 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 Box::new(self.normalize_bolide_type(k)),
                 Box::new(self.normalize_bolide_type(v)),
             ),
+            BolideType::WeakDict(k, v) => BolideType::WeakDict(
+                Box::new(self.normalize_bolide_type(k)),
+                Box::new(self.normalize_bolide_type(v)),
+            ),
             BolideType::Tuple(types) => BolideType::Tuple(
                 types.iter().map(|t| self.normalize_bolide_type(t)).collect()
             ),
@@ -2853,10 +4836,17 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             BolideType::Weak(inner) => BolideType::Weak(Box::new(self.normalize_bolide_type(inner))),
             BolideType::Unowned(inner) => BolideType::Unowned(Box::new(self.normalize_bolide_type(inner))),
             BolideType::Channel(inner) => BolideType::Channel(Box::new(self.normalize_bolide_type(inner))),
+            BolideType::PackedList(inner) => BolideType::PackedList(Box::new(self.normalize_bolide_type(inner))),
+            BolideType::PackedRef(inner) => BolideType::PackedRef(Box::new(self.normalize_bolide_type(inner))),
             _ => ty.clone(),
         }
     }
 
+    /// 获取类信息，自动处理模块限定名
+    fn get_class(&self, name: &str) -> Option<&ClassInfo> {
+        self.classes.get(name).or_else(|| self.classes.get(&self.normalize_type_name(name)))
+    }
+
     /// 检查表达式是否来源于生命周期依赖参数
     /// 返回 Some(param_name) 如果表达式来自某个生命周期参数（直接或间接）
     fn check_lifetime_source(&self, expr: &Expr) -> Option<String> {
@@ -2872,6 +4862,18 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 if let Some(source) = self.var_lifetime_source.get(name) {
                     return Some(source.clone());
                 }
+                // 3. 借用变量沿借用链追溯到来源（链长不超过借用变量数，避免环）
+                let mut current = name;
+                for _ in 0..self.borrowed_vars.len() {
+                    let Some((source, _)) = self.borrowed_vars.get(current) else { break };
+                    if self.lifetime_deps.as_ref().is_some_and(|deps| deps.contains(source)) {
+                        return Some(source.clone());
+                    }
+                    if let Some(root) = self.var_lifetime_source.get(source) {
+                        return Some(root.clone());
+                    }
+                    current = source;
+                }
                 None
             }
             Expr::Member(base, _) => {
@@ -2956,6 +4958,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         for var in &vars_in_scope {
             self.var_scope_depth.remove(var);
             self.borrowed_vars.remove(var);
+            self.invalidated_borrows.remove(var);
         }
 
         self.scope_depth -= 1;
@@ -2997,6 +5000,90 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         None
     }
 
+    /// 表达式中的借用值：返回 (借用者, 来源变量)
+    ///
+    /// 沿成员访问、索引和元组字面量向内查找，直接调用生命周期函数的结果也算借用。
+    /// packed 列表的元素（`ps[i]`）借用列表本身，从中读出的 int/float/bool 字段是复制。
+    fn borrow_in_expr(&self, expr: &Expr) -> Option<(String, String)> {
+        match expr {
+            Expr::Ident(name) => self.borrowed_vars.get(name)
+                .map(|(source, _)| (name.clone(), source.clone())),
+            Expr::Index(base, _) if matches!(self.infer_expr_type(base), BolideType::PackedList(_)) => {
+                if let Some(borrow) = self.borrow_in_expr(base) {
+                    return Some(borrow);
+                }
+                let root = Self::root_var(base)?;
+                Some((format!("{}[...]", root), root.to_string()))
+            }
+            Expr::Member(base, _)
+                if matches!(self.infer_expr_type(base), BolideType::PackedRef(_))
+                    && !matches!(self.infer_expr_type(expr), BolideType::PackedRef(_)) => None,
+            Expr::Member(base, _) | Expr::Index(base, _) => self.borrow_in_expr(base),
+            Expr::Tuple(items) => items.iter().find_map(|item| self.borrow_in_expr(item)),
+            Expr::Call(callee, _) => {
+                let Expr::Ident(func_name) = callee.as_ref() else { return None };
+                if !self.is_lifetime_func(func_name) {
+                    return None;
+                }
+                let source = self.get_lifetime_call_source(expr)?;
+                Some((format!("{}(...)", func_name), source))
+            }
+            _ => None,
+        }
+    }
+
+    /// 变量的声明位置（用于生命周期错误信息）
+    fn describe_decl_site(&self, name: &str) -> String {
+        let is_param = self.func_params.get(&self.current_func_name)
+            .is_some_and(|params| params.iter().any(|p| p.name == name));
+        if is_param {
+            format!("parameter of '{}'", self.current_func_name)
+        } else if self.variables.contains_key(name) {
+            format!("local variable in '{}'", self.current_func_name)
+        } else if self.global_data_ids.contains_key(name) {
+            "global variable".to_string()
+        } else {
+            format!("declared in '{}'", self.current_func_name)
+        }
+    }
+
+    /// 借用值不能逃逸到比来源活得更久的位置（字段、全局变量、通道、spawn 环境、Owned 参数）
+    fn check_borrow_escape(&self, value: &Expr, destination: &str) -> Result<(), String> {
+        if let Some((borrower, source)) = self.borrow_in_expr(value) {
+            return Err(format!(
+                "Lifetime error in function '{}': '{}' borrows from '{}' ({}) and cannot be {}",
+                self.current_func_name, borrower, source, self.describe_decl_site(&source), destination
+            ));
+        }
+        Ok(())
+    }
+
+    /// 返回借用值：当前函数必须声明 from，且来源可追溯到其中某个参数
+    fn validate_borrowed_return(&self, borrower: &str, source: &str) -> Result<(), String> {
+        if let (Some(deps), Some(root)) = (&self.lifetime_deps, self.check_lifetime_source(&Expr::Ident(source.to_string()))) {
+            if deps.contains(&root) {
+                return Ok(());
+            }
+        }
+        Err(format!(
+            "Lifetime error in function '{}': cannot return '{}', which borrows from '{}' ({}); \
+             '{}' must declare `from` on a parameter that owns '{}'",
+            self.current_func_name, borrower, source, self.describe_decl_site(source),
+            self.current_func_name, source
+        ))
+    }
+
+    /// 声明或赋值后记录（或清除）目标变量的借用关系
+    fn track_borrow(&mut self, var_name: &str, value: &Expr) {
+        self.invalidated_borrows.remove(var_name);
+        match self.borrow_in_expr(value) {
+            Some((_, source)) => self.record_borrow(var_name, &source),
+            None => {
+                self.borrowed_vars.remove(var_name);
+            }
+        }
+    }
+
     /// 收集语句列表中的 RC 变量声明（用于循环预初始化）
     fn collect_rc_var_decls(&self, stmts: &[Statement]) -> Vec<(String, BolideType)> {
         let mut result = Vec::new();
@@ -3014,6 +5101,17 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                         result.push((decl.name.clone(), ty));
                     }
                 }
+                Statement::Destructure(destructure) if destructure.declare => {
+                    if let BolideType::Tuple(slot_types) = self.infer_expr_type(&destructure.value) {
+                        for (target, ty) in destructure.targets.iter().zip(slot_types) {
+                            if let Some(name) = target {
+                                if Self::is_rc_type(&ty) {
+                                    result.push((name.clone(), ty));
+                                }
+                            }
+                        }
+                    }
+                }
                 Statement::If(if_stmt) => {
                     // 递归收集 if/else 分支中的变量
                     result.extend(self.collect_rc_var_decls(&if_stmt.then_body));
@@ -3028,6 +5126,17 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                     // 递归收集嵌套循环中的变量
                     result.extend(self.collect_rc_var_decls(&while_stmt.body));
                 }
+                Statement::Measure(measure_stmt) => {
+                    result.extend(self.collect_rc_var_decls(&measure_stmt.body));
+                }
+                Statement::Match(match_stmt) => {
+                    for arm in &match_stmt.arms {
+                        result.extend(self.collect_rc_var_decls(&arm.body));
+                    }
+                    if let Some(ref default) = match_stmt.default {
+                        result.extend(self.collect_rc_var_decls(default));
+                    }
+                }
                 _ => {}
             }
         }
@@ -3044,10 +5153,14 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 BolideType::BigInt |
                 BolideType::Decimal |
                 BolideType::List(_) |
+                BolideType::PackedList(_) |
                 BolideType::Dict(_, _) |
+                BolideType::WeakDict(_, _) |
                 BolideType::Dynamic |
                 BolideType::Custom(_) |
-                BolideType::Tuple(_)
+                BolideType::Tuple(_) |
+                BolideType::Func |
+                BolideType::FuncSig(_, _)
             )
         }
     }
@@ -3059,10 +5172,14 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             BolideType::BigInt => Some("bigint_release"),
             BolideType::Decimal => Some("decimal_release"),
             BolideType::List(_) => Some("list_release"),
+            BolideType::PackedList(_) => Some("packed_list_release"),
             BolideType::Dict(_, _) => Some("dict_release"),
+            BolideType::WeakDict(_, _) => Some("weakdict_release"),
             BolideType::Dynamic => Some("dynamic_release"),
             BolideType::Custom(_) => Some("object_release"),
             BolideType::Tuple(_) => Some("tuple_free"),
+            // 普通函数地址的 release 是空操作，只有 bind 生成的函数值持有资源
+            BolideType::Func | BolideType::FuncSig(_, _) => Some("func_release"),
             _ => None,
         }
     }
@@ -3074,10 +5191,13 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             BolideType::BigInt => Some("bigint_clone"),
             BolideType::Decimal => Some("decimal_clone"),
             BolideType::List(_) => Some("list_clone"),
+            BolideType::PackedList(_) => Some("packed_list_clone"),
             BolideType::Dict(_, _) => Some("dict_clone"),
+            BolideType::WeakDict(_, _) => Some("weakdict_clone"),
             BolideType::Dynamic => Some("dynamic_clone"),
             BolideType::Dynamic => Some("dynamic_clone"),
             BolideType::Custom(_) => Some("object_clone"),
+            BolideType::Func | BolideType::FuncSig(_, _) => Some("func_retain"),
             // Tuple clone handled specially (deep copy needed?) or ref count?
             // Actually tuple is immutable structure, maybe just retain? 
             // But we don't have tuple_retain exported.
@@ -3101,11 +5221,127 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         }
     }
 
+    /// 把已有的 RC 值绑定到新位置（变量、字段、元组槽位、返回值）时得到的新引用
+    ///
+    /// 默认按类型复制（列表、字典整表复制）；`share_containers` 时列表和字典只增加引用计数，
+    /// 两个位置共享同一个容器。没有复制函数的类型原样返回。
+    fn emit_binding_copy(&mut self, val: Value, ty: &BolideType) -> Value {
+        if self.share_containers {
+            let retain = match ty {
+                BolideType::List(_) => Some("list_retain"),
+                BolideType::Dict(_, _) => Some("dict_retain"),
+                _ => None,
+            };
+            if let Some(func_ref) = retain.and_then(|name| self.get_func_ref(name).ok()) {
+                self.builder.ins().call(func_ref, &[val]);
+                return val;
+            }
+        }
+        match Self::get_clone_func_name(ty).and_then(|name| self.get_func_ref(name).ok()) {
+            Some(func_ref) => {
+                let call = self.builder.ins().call(func_ref, &[val]);
+                self.builder.inst_results(call)[0]
+            }
+            None => val,
+        }
+    }
+
     /// 为所有 RC 变量生成 release 调用
     fn emit_rc_cleanup(&mut self) {
         self.emit_rc_cleanup_except(None);
     }
 
+    /// 全局变量的存储地址：普通全局变量是数据段，threadlocal 变量是当前线程的单元
+    fn global_addr(&mut self, name: &str, data_id: cranelift_module::DataId) -> Result<Value, String> {
+        let gv = self.module.declare_data_in_func(data_id, self.builder.func);
+        let addr = self.builder.ins().global_value(self.ptr_type, gv);
+        if !self.thread_locals.contains(name) {
+            return Ok(addr);
+        }
+        let key = self.builder.ins().load(types::I64, MemFlags::new(), addr, 0);
+        let tls_get = self.get_func_ref("tls_get")?;
+        let call = self.builder.ins().call(tls_get, &[key]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// `__main__` 开头为本次编译新声明的 threadlocal 变量分配槽，槽号存进变量的数据段
+    fn emit_thread_local_setup(&mut self, decls: &[VarDecl]) -> Result<(), String> {
+        for decl in decls {
+            let init = self.get_func_ref(&format!("{}{}", TLS_INIT_PREFIX, decl.name))?;
+            let init_addr = self.builder.ins().func_addr(self.ptr_type, init);
+            let drop_name = format!("{}{}", TLS_DROP_PREFIX, decl.name);
+            let dtor_addr = if self.functions.contains_key(&drop_name) {
+                let dtor = self.get_func_ref(&drop_name)?;
+                self.builder.ins().func_addr(self.ptr_type, dtor)
+            } else {
+                self.builder.ins().iconst(self.ptr_type, 0)
+            };
+            let alloc = self.get_func_ref("tls_alloc")?;
+            let call = self.builder.ins().call(alloc, &[init_addr, dtor_addr]);
+            let key = self.builder.inst_results(call)[0];
+            let gv = self.module.declare_data_in_func(self.global_data_ids[&decl.name], self.builder.func);
+            let addr = self.builder.ins().global_value(self.ptr_type, gv);
+            self.builder.ins().store(MemFlags::new(), key, addr, 0);
+        }
+        Ok(())
+    }
+
+    /// `__tls_init_<name>` 的函数体：把初始化表达式赋给当前线程刚创建的单元（初值 0）
+    fn emit_thread_local_init(&mut self, decl: &VarDecl) -> Result<(), String> {
+        if let Some(ref val) = decl.value {
+            self.compile_var_assign_impl(&decl.name, val, decl.ty.is_some())?;
+        }
+        Ok(())
+    }
+
+    /// `__globals_release` 的函数体：释放 RC 全局变量并置零
+    ///
+    /// 还有用户线程在运行时跳过（它们可能还在使用全局变量）；
+    /// 没有执行到初始化的全局变量仍是 0，跳过。
+    fn emit_globals_release(&mut self) -> Result<(), String> {
+        let mut globals: Vec<(String, BolideType)> = self.global_var_types.iter()
+            .filter(|(name, ty)| Self::is_rc_type(ty) && !self.thread_locals.contains(*name))
+            .map(|(name, ty)| (name.clone(), ty.clone()))
+            .collect();
+        globals.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let live_threads = self.get_func_ref("live_threads")?;
+        let call = self.builder.ins().call(live_threads, &[]);
+        let live = self.builder.inst_results(call)[0];
+        let release_block = self.builder.create_block();
+        let done_block = self.builder.create_block();
+        self.builder.ins().brif(live, done_block, &[], release_block, &[]);
+        self.builder.switch_to_block(release_block);
+        self.builder.seal_block(release_block);
+
+        for (name, ty) in globals {
+            let data_id = self.global_data_ids[&name];
+            let gv = self.module.declare_data_in_func(data_id, self.builder.func);
+            let addr = self.builder.ins().global_value(self.ptr_type, gv);
+            let val = self.builder.ins().load(self.ptr_type, MemFlags::new(), addr, 0);
+            let set_block = self.builder.create_block();
+            let next_block = self.builder.create_block();
+            self.builder.ins().brif(val, set_block, &[], next_block, &[]);
+            self.builder.switch_to_block(set_block);
+            self.builder.seal_block(set_block);
+            self.emit_release(val, &ty);
+            let zero = self.builder.ins().iconst(self.ptr_type, 0);
+            self.builder.ins().store(MemFlags::new(), zero, addr, 0);
+            self.builder.ins().jump(next_block, &[]);
+            self.builder.switch_to_block(next_block);
+            self.builder.seal_block(next_block);
+        }
+        // 主线程的 threadlocal 值
+        if !self.thread_locals.is_empty() {
+            let thread_exit = self.get_func_ref("tls_thread_exit")?;
+            self.builder.ins().call(thread_exit, &[]);
+        }
+        self.builder.ins().jump(done_block, &[]);
+        self.builder.switch_to_block(done_block);
+        self.builder.seal_block(done_block);
+        Ok(())
+    }
+
     /// 为所有 RC 变量生成 release 调用，可以排除指定变量
     fn emit_rc_cleanup_except(&mut self, except_var: Option<&str>) {
         // 收集需要释放的变量（避免借用冲突）
@@ -3135,7 +5371,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
     fn emit_release(&mut self, val: Value, ty: &BolideType) {
         if let BolideType::Tuple(inner_types) = ty {
             // 元组需要先释放元素
-            if let Some(&get_func) = self.func_refs.get("tuple_get") {
+            if let Ok(get_func) = self.get_func_ref("tuple_get") {
                 for (i, elem_ty) in inner_types.iter().enumerate() {
                     if Self::is_rc_type(elem_ty) {
                         let idx_val = self.builder.ins().iconst(types::I64, i as i64);
@@ -3147,46 +5383,101 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 }
             }
             // 最后释放元组本身
-            if let Some(&free_func) = self.func_refs.get("tuple_free") {
+            if let Ok(free_func) = self.get_func_ref("tuple_free") {
                 self.builder.ins().call(free_func, &[val]);
             }
         } else if let BolideType::Custom(ref class_name) = ty {
             // 自定义类型（Class）
-            self.emit_object_fields_cleanup(val, class_name);
-            if let Some(&release_func) = self.func_refs.get("object_release") {
-                self.builder.ins().call(release_func, &[val]);
-            }
+            self.emit_object_release(val, class_name, &mut Vec::new());
         } else {
             // 其他基本 RC 类型
             if let Some(func_name) = Self::get_release_func_name(ty) {
-                if let Some(&func_ref) = self.func_refs.get(func_name) {
+                if let Ok(func_ref) = self.get_func_ref(func_name) {
                     self.builder.ins().call(func_ref, &[val]);
                 }
             }
         }
     }
 
+    /// 释放类实例：nil 跳过，这是最后一个引用时先释放它的 RC 字段
+    ///
+    /// `expanding` 是正在展开字段释放代码的类（由外到内），避免自引用的类无限展开。
+    fn emit_object_release(&mut self, val: Value, class_name: &str, expanding: &mut Vec<String>) {
+        let (Ok(refcount_func), Ok(release_func)) = (self.get_func_ref("object_refcount"), self.get_func_ref("object_release")) else {
+            return;
+        };
+        let live_block = self.builder.create_block();
+        let cleanup_block = self.builder.create_block();
+        let release_block = self.builder.create_block();
+        let done_block = self.builder.create_block();
+
+        let null_val = self.builder.ins().iconst(self.ptr_type, 0);
+        let is_null = self.builder.ins().icmp(IntCC::Equal, val, null_val);
+        self.builder.ins().brif(is_null, done_block, &[], live_block, &[]);
+
+        self.builder.switch_to_block(live_block);
+        self.builder.seal_block(live_block);
+        let call = self.builder.ins().call(refcount_func, &[val]);
+        let refcount = self.builder.inst_results(call)[0];
+        let is_last = self.builder.ins().icmp_imm(IntCC::Equal, refcount, 1);
+        self.builder.ins().brif(is_last, cleanup_block, &[], release_block, &[]);
+
+        self.builder.switch_to_block(cleanup_block);
+        self.builder.seal_block(cleanup_block);
+        self.emit_object_fields_cleanup(val, class_name, expanding);
+        self.builder.ins().jump(release_block, &[]);
+
+        self.builder.switch_to_block(release_block);
+        self.builder.seal_block(release_block);
+        self.builder.ins().call(release_func, &[val]);
+        self.builder.ins().jump(done_block, &[]);
+
+        self.builder.switch_to_block(done_block);
+        self.builder.seal_block(done_block);
+    }
+
     /// 释放对象内部的 RC 字段
-    fn emit_object_fields_cleanup(&mut self, obj_ptr: Value, class_name: &str) {
-        if let Some(class_info) = self.classes.get(class_name).cloned() {
-            for field in &class_info.fields {
-                if Self::is_rc_type(&field.ty) {
-                    if let Some(func_name) = Self::get_release_func_name(&field.ty) {
-                        if let Some(&func_ref) = self.func_refs.get(func_name) {
-                            let field_ptr = self.builder.ins().iadd_imm(obj_ptr, field.offset as i64);
-                            let field_val = self.builder.ins().load(types::I64, MemFlags::new(), field_ptr, 0);
-                            self.builder.ins().call(func_ref, &[field_val]);
-                        }
+    ///
+    /// weak/unowned 字段不持有引用，跳过；类实例字段按 `emit_object_release` 递归释放，
+    /// 字段的类正在展开时（链表节点的 `next` 等）只释放字段对象本身。
+    fn emit_object_fields_cleanup(&mut self, obj_ptr: Value, class_name: &str, expanding: &mut Vec<String>) {
+        let Some(class_info) = self.classes.get(class_name).cloned() else {
+            return;
+        };
+        expanding.push(class_name.to_string());
+        for field in &class_info.fields {
+            if !Self::is_rc_type(&field.ty) {
+                continue;
+            }
+            let field_ptr = self.builder.ins().iadd_imm(obj_ptr, field.offset as i64);
+            let field_val = self.builder.ins().load(types::I64, MemFlags::new(), field_ptr, 0);
+            match &field.ty {
+                BolideType::Custom(inner) if !expanding.contains(inner) => {
+                    self.emit_object_release(field_val, inner, expanding);
+                }
+                BolideType::Custom(_) => {
+                    if let Ok(func_ref) = self.get_func_ref("object_release") {
+                        self.builder.ins().call(func_ref, &[field_val]);
                     }
                 }
+                ty => self.emit_release(field_val, ty),
             }
         }
+        expanding.pop();
     }
 
     /// 记录 RC 变量
     fn track_rc_variable(&mut self, name: &str, ty: &BolideType) {
         if Self::is_rc_type(ty) {
             self.rc_variables.push((name.to_string(), ty.clone()));
+            self.note_rc_variable(name, ty);
+        }
+    }
+
+    /// 记入分析报告的 RC 变量列表（同名同类型只记一次）
+    fn note_rc_variable(&mut self, name: &str, ty: &BolideType) {
+        if !self.rc_variables_seen.iter().any(|(n, t)| n == name && t == ty) {
+            self.rc_variables_seen.push((name.to_string(), ty.clone()));
         }
     }
 
@@ -3194,6 +5485,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
     fn track_temp_rc_value(&mut self, val: Value, ty: &BolideType) {
         if Self::is_rc_type(ty) {
             self.temp_rc_values.push((val, ty.clone()));
+            self.temps_tracked += 1;
         }
     }
 
@@ -3234,6 +5526,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
     /// 编译语句，返回是否已终止当前块
     fn compile_stmt(&mut self, stmt: &Statement) -> Result<bool, String> {
+        self.pure_calls.clear();
         let result = match stmt {
             Statement::VarDecl(decl) => {
                 self.compile_var_decl(decl)?;
@@ -3243,10 +5536,18 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 self.compile_assign(assign)?;
                 Ok(false)
             }
+            Statement::Destructure(destructure) => {
+                self.compile_destructure(destructure)?;
+                Ok(false)
+            }
             Statement::Return(expr) => {
                 self.compile_return(expr.as_ref())?;
                 Ok(true)
             }
+            Statement::Break | Statement::Continue => {
+                self.compile_loop_jump(matches!(stmt, Statement::Break))?;
+                Ok(true)
+            }
             Statement::Expr(e) => {
                 self.compile_expr(e)?;
                 Ok(false)
@@ -3260,10 +5561,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 self.compile_for(for_stmt)?;
                 Ok(false)
             }
-            Statement::Pool(pool_stmt) => {
-                self.compile_pool(pool_stmt)?;
-                Ok(false)
-            }
+            Statement::Pool(pool_stmt) => self.compile_pool(pool_stmt),
             Statement::Send(send_stmt) => {
                 self.compile_send(send_stmt)?;
                 Ok(false)
@@ -3276,6 +5574,8 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 self.compile_await_scope(scope_stmt)?;
                 Ok(false)
             }
+            Statement::Measure(measure_stmt) => self.compile_measure(measure_stmt),
+            Statement::Match(match_stmt) => self.compile_match(match_stmt),
             Statement::AsyncSelect(select_stmt) => {
                 self.compile_async_select(select_stmt)?;
                 Ok(false)
@@ -3287,6 +5587,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 self.register_extern_block(eb)?;
                 Ok(false)
             }
+            Statement::Test(test) => Err(format!("test \"{}\" only runs under `bolide test`", test.name)),
         };
 
         // 在每条语句执行后释放临时 RC 值
@@ -3297,6 +5598,9 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
     /// 编译赋值语句
     fn compile_assign(&mut self, assign: &Assign) -> Result<(), String> {
+        if let Some(op) = assign.op {
+            return self.compile_compound_assign(&assign.target, op, &assign.value);
+        }
         // 根据 target 类型分派
         match &assign.target {
             Expr::Ident(var_name) => self.compile_var_assign(var_name, &assign.value),
@@ -3306,30 +5610,152 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         }
     }
 
+    /// 编译复合赋值 `target op= value`（见 compound 模块）
+    ///
+    /// 目标里的索引表达式先求值存入隐藏变量，之后按 `target = target op value` 编译；
+    /// 列表的 `+=` 直接 `list_extend` 到原列表。
+    fn compile_compound_assign(&mut self, target: &Expr, op: BinOp, value: &Expr) -> Result<(), String> {
+        let target_ty = self.infer_expr_type(target);
+        compound::check_operands(op, &target_ty, value, &self.infer_expr_type(value))?;
+
+        let mut hidden = Vec::new();
+        let stable = compound::stable_target(target, &mut |expr| {
+            let ty = self.normalize_bolide_type(&self.infer_expr_type(expr));
+            let val = self.compile_expr(expr)?;
+            let name = format!("__compound_{}", self.var_counter);
+            let var = self.declare_variable(&name, self.bolide_type_to_cranelift(&ty));
+            self.builder.def_var(var, val);
+            self.var_types.insert(name.clone(), ty);
+            hidden.push(name.clone());
+            Ok(Expr::Ident(name))
+        });
+
+        let result = stable.and_then(|stable| {
+            if compound::is_list_extend(op, &target_ty) {
+                let list = self.compile_expr(&stable)?;
+                let other = self.compile_owned_expr(value, Some(&target_ty))?;
+                let list_extend = self.get_func_ref("list_extend")?;
+                self.builder.ins().call(list_extend, &[list, other]);
+                return Ok(());
+            }
+            let value = Expr::BinOp(Box::new(stable.clone()), op, Box::new(value.clone()));
+            self.compile_assign(&Assign { target: stable, op: None, value })
+        });
+
+        for name in hidden {
+            self.variables.remove(&name);
+            self.var_types.remove(&name);
+        }
+        result
+    }
+
+    /// 编译解构语句: let a, b = expr; / a, b = expr;
+    ///
+    /// 右侧只求值一次并存入隐藏变量，每个槽位按普通声明/赋值从 `隐藏变量[i]` 取值，
+    /// RC 槽位照常 clone。右侧是临时元组时随语句的临时值一起释放，`_` 丢弃的槽位也在这时释放。
+    fn compile_destructure(&mut self, destructure: &Destructure) -> Result<(), String> {
+        if let Some((name, args)) = overflow::checked_call(&destructure.value) {
+            if destructure.targets.len() == 2 {
+                return self.compile_checked_destructure(destructure, name, args);
+            }
+        }
+        let slot_types = match self.infer_expr_type(&destructure.value) {
+            BolideType::Tuple(types) => types,
+            other => return Err(format!("Cannot destructure a value of type {:?}", other)),
+        };
+        if slot_types.len() != destructure.targets.len() {
+            return Err(format!(
+                "Cannot destructure a {}-tuple into {} names",
+                slot_types.len(),
+                destructure.targets.len()
+            ));
+        }
+
+        let tuple_val = self.compile_expr(&destructure.value)?;
+        let hidden = format!("__destructure_{}", self.var_counter);
+        let var = self.declare_variable(&hidden, self.ptr_type);
+        self.builder.def_var(var, tuple_val);
+        self.var_types.insert(hidden.clone(), BolideType::Tuple(slot_types));
+
+        for (i, target) in destructure.targets.iter().enumerate() {
+            let Some(name) = target else { continue };
+            let slot = Expr::Index(Box::new(Expr::Ident(hidden.clone())), Box::new(Expr::Int(i as i64)));
+            if destructure.declare {
+                self.compile_var_decl(&VarDecl { name: name.clone(), ty: None, value: Some(slot), thread_local: false })?;
+            } else {
+                self.compile_var_assign(name, &slot)?;
+            }
+        }
+
+        // 借用关系按槽位传给解构出的变量（右侧不是元组字面量时每个槽位都算借用）
+        for (i, target) in destructure.targets.iter().enumerate() {
+            let Some(name) = target else { continue };
+            let slot_value = match &destructure.value {
+                Expr::Tuple(items) => &items[i],
+                value => value,
+            };
+            self.track_borrow(name, slot_value);
+        }
+
+        self.variables.remove(&hidden);
+        self.var_types.remove(&hidden);
+        Ok(())
+    }
+
+    /// `let ok, v = checked_add(a, b);`：不分配元组，两个结果经隐藏变量直接绑定到目标
+    fn compile_checked_destructure(&mut self, destructure: &Destructure, name: &str, args: &[Expr]) -> Result<(), String> {
+        let (_, value, overflowed) = self.compile_int_intrinsic(name, args)?;
+        let overflowed = overflowed.ok_or_else(|| format!("{} reports no overflow flag", name))?;
+        let ok = self.builder.ins().icmp_imm(IntCC::Equal, overflowed, 0);
+        let ok = self.builder.ins().uextend(types::I64, ok);
+
+        for (target, (val, ty)) in destructure.targets.iter().zip([(ok, BolideType::Bool), (value, BolideType::Int)]) {
+            let Some(target) = target else { continue };
+            let hidden = format!("__checked_{}", self.var_counter);
+            let var = self.declare_variable(&hidden, self.bolide_type_to_cranelift(&ty));
+            self.builder.def_var(var, val);
+            self.var_types.insert(hidden.clone(), ty);
+            let slot = Expr::Ident(hidden.clone());
+            if destructure.declare {
+                self.compile_var_decl(&VarDecl { name: target.clone(), ty: None, value: Some(slot), thread_local: false })?;
+            } else {
+                self.compile_var_assign(target, &slot)?;
+            }
+            self.variables.remove(&hidden);
+            self.var_types.remove(&hidden);
+        }
+        Ok(())
+    }
+
     /// 编译索引赋值 (list[i] = value)
     fn compile_index_assign(&mut self, base: &Expr, index: &Expr, value: &Expr) -> Result<(), String> {
         let base_type = self.infer_expr_type(base);
+        if let BolideType::PackedList(elem) = &base_type {
+            let elem = self.normalize_bolide_type(elem);
+            return self.compile_packed_index_assign(base, &elem, index, value);
+        }
         let base_val = self.compile_expr(base)?;
         let index_val = self.compile_expr(index)?;
-        let value_val = self.compile_expr(value)?;
+        // 容器 set 时 retain；字面量按元素类型编译并登记为临时值，语句结束释放创建时的那一份
+        let value_val = match &base_type {
+            BolideType::List(elem) | BolideType::Dict(_, elem) => self.compile_owned_expr(value, Some(elem))?,
+            _ => self.compile_expr(value)?,
+        };
 
         match base_type {
-            BolideType::List(_) => {
-                let list_set = *self.func_refs.get("list_set")
-                    .ok_or("list_set not found")?;
-                self.builder.ins().call(list_set, &[base_val, index_val, value_val]);
+            BolideType::List(elem_ty) => {
+                let value_val = self.coerce_list_elem(value_val, value, &elem_ty);
+                self.emit_list_set(base_val, index_val, value_val)?;
                 Ok(())
             }
             BolideType::Dict(_, _) => {
-                let dict_set = *self.func_refs.get("dict_set")
-                    .ok_or("dict_set not found")?;
+                let dict_set = self.get_func_ref("dict_set")?;
                 self.builder.ins().call(dict_set, &[base_val, index_val, value_val]);
                 Ok(())
             }
 
             BolideType::Tuple(_) => {
-                let tuple_set = *self.func_refs.get("tuple_set")
-                    .ok_or("tuple_set not found")?;
+                let tuple_set = self.get_func_ref("tuple_set")?;
                 self.builder.ins().call(tuple_set, &[base_val, index_val, value_val]);
                 Ok(())
             }
@@ -3340,81 +5766,85 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
     /// 编译变量赋值
     fn compile_var_assign(&mut self, var_name: &str, value: &Expr) -> Result<(), String> {
-        // 首先检查是否是局部变量
-        if let Some(&var) = self.variables.get(var_name) {
-            // 局部变量赋值（原有逻辑）
-            // 检查是否是 Ref 参数
-            let is_ref_param = self.ref_params.iter().any(|(name, _, _)| name == var_name);
-            // 检查 Ref 参数是否已经被重新赋值过
-            let was_reassigned = self.ref_params_reassigned.contains(var_name);
+        self.compile_var_assign_impl(var_name, value, true)
+    }
 
-            // 决定是否释放旧值
-            let should_release = !is_ref_param || was_reassigned;
+    /// 变量赋值；`global_ty_declared` 为 false 表示全局变量的记录类型来自静态推断，
+    /// 不能当作字面量的声明类型（未标注类型的全局变量初始化）
+    fn compile_var_assign_impl(&mut self, var_name: &str, value: &Expr, global_ty_declared: bool) -> Result<(), String> {
+        // 累加模式 v = v <op> rhs 走原地运算
+        if self.try_compile_assign_in_place(var_name, value)? {
+            return Ok(());
+        }
 
+        // 首先检查是否是局部变量
+        if let Some(&var) = self.variables.get(var_name) {
+            // 局部变量赋值；Ref 参数的旧值同样由槽位拥有，替换时释放（见 ParamMode::Ref）
             let var_ty = self.var_types.get(var_name).cloned();
-            if let Some(ref ty) = var_ty {
-                if Self::is_rc_type(ty) && should_release {
-                    let old_val = self.builder.use_var(var);
-                    self.emit_release(old_val, ty);
-                }
-            }
 
-            // 如果是 Ref 参数的首次赋值，标记为已重新赋值
-            if is_ref_param && !was_reassigned {
-                self.ref_params_reassigned.insert(var_name.to_string());
+            // 先编译新值（右侧可能读取旧值，例如 s = s + "x"），再释放旧值
+            let val = self.compile_owned_expr(value, var_ty.as_ref())?;
+            if let Some(BolideType::PackedList(_)) = var_ty {
+                self.invalidate_packed_borrows(&Expr::Ident(var_name.to_string()), &format!("assigning to '{}'", var_name))?;
             }
 
-            let val = self.compile_expr(value)?;
-
             // 如果是 RC 类型，需要处理引用计数
-            if let Some(ref ty) = var_ty {
-                if Self::is_rc_type(ty) {
+            let new_val = match var_ty {
+                Some(ref ty) if Self::is_rc_type(ty) => {
                     let is_temp = self.temp_rc_values.iter().any(|(v, _)| *v == val);
                     if is_temp {
                         self.remove_temp_rc_value(val);
-                        self.builder.def_var(var, val);
+                        val
                     } else {
-                        let clone_func_name = Self::get_clone_func_name(ty);
-                        if let Some(func_name) = clone_func_name {
-                            if let Some(&func_ref) = self.func_refs.get(func_name) {
-                                let call = self.builder.ins().call(func_ref, &[val]);
-                                let cloned_val = self.builder.inst_results(call)[0];
-                                self.builder.def_var(var, cloned_val);
-                            } else {
-                                self.builder.def_var(var, val);
-                            }
-                        } else {
-                            self.builder.def_var(var, val);
-                        }
+                        self.emit_binding_copy(val, ty)
                     }
-                } else {
-                    self.builder.def_var(var, val);
                 }
-            } else {
-                self.builder.def_var(var, val);
-            }
+                _ => val,
+            };
 
-            // 调用者端借用检查：记录借用关系
-            if self.is_lifetime_func_call(value) {
-                if let Some(source_var) = self.get_lifetime_call_source(value) {
-                    self.record_borrow(var_name, &source_var);
+            if let Some(ref ty) = var_ty {
+                if Self::is_rc_type(ty) {
+                    let old_val = self.builder.use_var(var);
+                    self.emit_release(old_val, ty);
                 }
             }
+            self.builder.def_var(var, new_val);
+            self.store_through_ref_param(var_name, new_val);
+
+            // 调用者端借用检查：记录借用关系
+            self.track_borrow(var_name, value);
 
             return Ok(());
         }
 
         // 检查是否是全局变量
         if let Some(&data_id) = self.global_data_ids.get(var_name) {
+            // 任何函数都可能修改全局的 packed 列表，元素的借用不能存进全局变量
+            if let BolideType::PackedRef(class) = self.infer_expr_type(value) {
+                return Err(format!(
+                    "An element of a packed list ({}) cannot be stored in global '{}'; keep its index instead",
+                    analysis::type_name(&class), var_name
+                ));
+            }
+            // 借用值只有在来源也是全局变量时才能存进全局变量
+            if let Some((_, source)) = self.borrow_in_expr(value) {
+                if self.variables.contains_key(&source) || !self.global_data_ids.contains_key(&source) {
+                    self.check_borrow_escape(value, &format!("stored in global '{}'", var_name))?;
+                }
+            }
+
             // 获取全局变量的类型
             let global_ty = self.global_var_types.get(var_name).cloned();
             
             // 获取全局变量的地址
-            let gv = self.module.declare_data_in_func(data_id, self.builder.func);
-            let addr = self.builder.ins().global_value(self.ptr_type, gv);
+            let addr = self.global_addr(var_name, data_id)?;
             
             // 先编译新值表达式(这样可以正确读取旧值, 例如 expr = expr + "1")
-            let val = self.compile_expr(value)?;
+            let expected = if global_ty_declared { global_ty.as_ref() } else { None };
+            let val = self.compile_owned_expr(value, expected)?;
+            if let Some(BolideType::PackedList(_)) = global_ty {
+                self.invalidate_packed_borrows(&Expr::Ident(var_name.to_string()), &format!("assigning to '{}'", var_name))?;
+            }
             
             // 如果是 RC 类型，需要处理引用计数
             if let Some(ref ty) = global_ty {
@@ -3428,24 +5858,16 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                         self.emit_release(old_val, ty);
                         // 存储新值
                         self.builder.ins().store(MemFlags::new(), val, addr, 0);
+                    } else if Self::get_clone_func_name(ty).is_some() {
+                        // 值来自另一个变量，复制（或共享）一份
+                        let copied = self.emit_binding_copy(val, ty);
+                        // 释放旧值
+                        let old_val = self.builder.ins().load(self.ptr_type, MemFlags::new(), addr, 0);
+                        self.emit_release(old_val, ty);
+                        // 存储新值
+                        self.builder.ins().store(MemFlags::new(), copied, addr, 0);
                     } else {
-                        // 值来自另一个变量，需要 clone
-                        let clone_func_name = Self::get_clone_func_name(ty);
-                        if let Some(func_name) = clone_func_name {
-                            if let Some(&func_ref) = self.func_refs.get(func_name) {
-                                let call = self.builder.ins().call(func_ref, &[val]);
-                                let cloned_val = self.builder.inst_results(call)[0];
-                                // 释放旧值
-                                let old_val = self.builder.ins().load(self.ptr_type, MemFlags::new(), addr, 0);
-                                self.emit_release(old_val, ty);
-                                // 存储新值
-                                self.builder.ins().store(MemFlags::new(), cloned_val, addr, 0);
-                            } else {
-                                self.builder.ins().store(MemFlags::new(), val, addr, 0);
-                            }
-                        } else {
-                            self.builder.ins().store(MemFlags::new(), val, addr, 0);
-                        }
+                        self.builder.ins().store(MemFlags::new(), val, addr, 0);
                     }
                 } else {
                     self.builder.ins().store(MemFlags::new(), val, addr, 0);
@@ -3459,21 +5881,101 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
         Err(format!("Undefined variable: {}", var_name))
     }
-    fn compile_member_assign(&mut self, base: &Expr, member: &str, value: &Expr) -> Result<(), String> {
-        // 获取基础表达式的类型
-        let class_name = self.get_expr_type(base)?;
-        let class_name = match class_name {
-            BolideType::Custom(name) => name,
-            _ => return Err(format!("Member assign on non-class type: {:?}", class_name)),
+    /// 把 `v = v <op> rhs`（v 为 bigint/decimal，op 为 + - *）编译为原地运算
+    ///
+    /// 运行时在 v 独占时直接修改，共享时复制，语义与分配新对象相同。
+    /// 只处理持有所有权的变量（let 局部变量、owned 参数、全局变量），返回是否已处理。
+    fn try_compile_assign_in_place(&mut self, var_name: &str, value: &Expr) -> Result<bool, String> {
+        let Expr::BinOp(lhs, op, rhs) = value else {
+            return Ok(false);
+        };
+        if !matches!(lhs.as_ref(), Expr::Ident(name) if name == var_name) {
+            return Ok(false);
+        }
+        let op_name = match op {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            _ => return Ok(false),
         };
 
-        // 获取类信息
-        let class_info = self.classes.get(&class_name)
-            .ok_or_else(|| format!("Class not found: {}", class_name))?
-            .clone();
+        let local_var = self.variables.get(var_name).copied();
+        let var_ty = if local_var.is_some() {
+            // 借用参数和 ref 参数不持有值，不能原地修改
+            let owned = self.rc_variables.iter().any(|(name, _)| name == var_name);
+            let is_ref_param = self.ref_params.iter().any(|(name, _, _)| name == var_name);
+            if !owned || is_ref_param {
+                return Ok(false);
+            }
+            self.var_types.get(var_name).cloned()
+        } else {
+            self.global_var_types.get(var_name).cloned()
+        };
+        let prefix = match var_ty {
+            Some(BolideType::BigInt) => "bigint",
+            Some(BolideType::Decimal) => "decimal",
+            _ => return Ok(false),
+        };
 
-        // 查找字段
-        let field = class_info.fields.iter()
+        // 右值是 bigint(x)/decimal(x) 且 x 为 int 时直接用 _i64 版本，不构造临时对象
+        let int_arg = match rhs.as_ref() {
+            Expr::Call(callee, args)
+                if args.len() == 1
+                    && matches!(callee.as_ref(), Expr::Ident(f) if f == prefix)
+                    && matches!(self.infer_expr_type(&args[0]), BolideType::Int) => Some(&args[0]),
+            _ => None,
+        };
+        if int_arg.is_none() && Some(self.infer_expr_type(rhs)) != var_ty {
+            return Ok(false);
+        }
+
+        let (rhs_val, func_name) = match int_arg {
+            Some(arg) => (self.compile_expr(arg)?, format!("{}_{}_assign_i64", prefix, op_name)),
+            None => (self.compile_expr(rhs)?, format!("{}_{}_assign", prefix, op_name)),
+        };
+        let func_ref = self.get_func_ref(func_name.as_str())?;
+
+        // 右值求值之后再读取 v
+        let old_val = self.compile_ident(var_name)?;
+        let call = self.builder.ins().call(func_ref, &[old_val, rhs_val]);
+        let new_val = self.builder.inst_results(call)[0];
+
+        match local_var {
+            Some(var) => self.builder.def_var(var, new_val),
+            None => {
+                let data_id = *self.global_data_ids.get(var_name)
+                    .ok_or_else(|| format!("Undefined variable: {}", var_name))?;
+                let addr = self.global_addr(var_name, data_id)?;
+                self.builder.ins().store(MemFlags::new(), new_val, addr, 0);
+            }
+        }
+        Ok(true)
+    }
+
+    fn compile_member_assign(&mut self, base: &Expr, member: &str, value: &Expr) -> Result<(), String> {
+        if let Some(resolved) = modules::resolve_member(&self.modules, base, member) {
+            resolved?;
+            return Err(modules::global_write_error(base, member));
+        }
+        if let BolideType::PackedRef(class) = self.infer_expr_type(base) {
+            return self.compile_packed_member_assign(base, &class, member, value);
+        }
+        self.check_borrow_escape(value, &format!("stored in field '{}'", member))?;
+
+        // 获取基础表达式的类型
+        let class_name = self.get_expr_type(base)?;
+        let class_name = match class_name {
+            BolideType::Custom(name) => name,
+            _ => return Err(format!("Member assign on non-class type: {:?}", class_name)),
+        };
+
+        // 获取类信息
+        let class_info = self.classes.get(&class_name)
+            .ok_or_else(|| format!("Class not found: {}", class_name))?
+            .clone();
+
+        // 查找字段
+        let field = class_info.fields.iter()
             .find(|f| f.name == member)
             .ok_or_else(|| format!("Field '{}' not found in class '{}'", member, class_name))?;
 
@@ -3484,39 +5986,29 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         let obj_ptr = self.compile_expr(base)?;
 
         // 编译值表达式
-        let val = self.compile_expr(value)?;
+        let val = self.compile_owned_expr(value, Some(&field_ty))?;
 
         // 计算字段地址
         let field_ptr = self.builder.ins().iadd_imm(obj_ptr, field_offset as i64);
 
-        // 如果字段是 RC 类型，需要处理引用计数
-        if Self::is_rc_type(&field_ty) {
-            let is_temp = self.temp_rc_values.iter().any(|(v, _)| *v == val);
-            if is_temp {
-                // 值是临时的，移除临时标记，字段接管所有权
-                self.remove_temp_rc_value(val);
-                self.builder.ins().store(MemFlags::new(), val, field_ptr, 0);
-            } else {
-                // 值来自另一个变量，需要 clone
-                if let Some(func_name) = Self::get_clone_func_name(&field_ty) {
-                    if let Some(&func_ref) = self.func_refs.get(func_name) {
-                        let call = self.builder.ins().call(func_ref, &[val]);
-                        let cloned = self.builder.inst_results(call)[0];
-                        self.builder.ins().store(MemFlags::new(), cloned, field_ptr, 0);
-                    } else {
-                        self.builder.ins().store(MemFlags::new(), val, field_ptr, 0);
-                    }
-                } else {
-                    self.builder.ins().store(MemFlags::new(), val, field_ptr, 0);
-                }
-            }
-        } else {
-            self.builder.ins().store(MemFlags::new(), val, field_ptr, 0);
-        }
+        let val = self.take_field_value(val, &field_ty)?;
+        self.builder.ins().store(MemFlags::new(), val, field_ptr, 0);
 
         Ok(())
     }
 
+    /// 要存进字段的值：字段接管临时值的所有权，来自变量的 RC 值复制（或共享）一份
+    fn take_field_value(&mut self, val: Value, field_ty: &BolideType) -> Result<Value, String> {
+        if !Self::is_rc_type(field_ty) {
+            return Ok(val);
+        }
+        if self.temp_rc_values.iter().any(|(v, _)| *v == val) {
+            self.remove_temp_rc_value(val);
+            return Ok(val);
+        }
+        Ok(self.emit_binding_copy(val, field_ty))
+    }
+
     /// 编译变量声明
     fn compile_var_decl(&mut self, decl: &VarDecl) -> Result<(), String> {
         // 确定 Bolide 类型
@@ -3529,22 +6021,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             BolideType::Int
         };
 
-        // 检查是否是全局变量
-        if self.global_data_ids.contains_key(&decl.name) {
-            // 全局变量不需要创建局部变量，直接编译初始化赋值
-            if let Some(ref val) = decl.value {
-                self.compile_var_assign(&decl.name, val)?;
-            }
-            return Ok(());
-        }
-
-        // 记录局部变量的 Bolide 类型（需要规范化类型名称）
-        self.var_types.insert(decl.name.clone(), self.normalize_bolide_type(&bolide_ty));
-
-        // 记录变量的作用域深度
-        self.record_var_scope(&decl.name);
-
-        // 如果是 spawn 或异步函数调用，记录变量名 -> 函数名的映射
+        // 如果是 spawn 或异步函数调用，记录变量名 -> 函数名的映射（全局变量也要记录，顶层的 join 按它确定结果类型）
         if let Some(ref value) = decl.value {
             match value {
                 Expr::Spawn(func_name, _) => {
@@ -3562,6 +6039,25 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             }
         }
 
+        // 顶层代码里的 let 初始化全局变量；函数里的 let 声明遮蔽同名全局变量的局部变量
+        if self.current_func_name == "__main__" && self.global_data_ids.contains_key(&decl.name) {
+            // threadlocal 变量在每个线程第一次访问时由 __tls_init_* 初始化
+            if decl.thread_local {
+                return Ok(());
+            }
+            // 全局变量不需要创建局部变量，直接编译初始化赋值
+            if let Some(ref val) = decl.value {
+                self.compile_var_assign_impl(&decl.name, val, decl.ty.is_some())?;
+            }
+            return Ok(());
+        }
+
+        // 记录局部变量的 Bolide 类型（需要规范化类型名称）
+        self.var_types.insert(decl.name.clone(), self.normalize_bolide_type(&bolide_ty));
+
+        // 记录变量的作用域深度
+        self.record_var_scope(&decl.name);
+
         // 转换为 Cranelift 类型
         let ty = self.bolide_type_to_cranelift(&bolide_ty);
 
@@ -3601,7 +6097,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         };
 
         if let Some(ref value) = decl.value {
-            let val = self.compile_expr(value)?;
+            let val = self.compile_owned_expr(value, decl.ty.as_ref())?;
 
             // 检查值是否来自生命周期函数调用（返回借用而非拥有的值）
             let is_from_lifetime_func = self.is_lifetime_func_call(value);
@@ -3616,31 +6112,22 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                     self.remove_temp_rc_value(val);
                     self.builder.def_var(var, val);
                 } else {
-                    // 值来自另一个变量，需要 clone（retain RC）
-                    let clone_func_name = Self::get_clone_func_name(&bolide_ty);
-                    if let Some(func_name) = clone_func_name {
-                        if let Some(&func_ref) = self.func_refs.get(func_name) {
-                            let call = self.builder.ins().call(func_ref, &[val]);
-                            let results = self.builder.inst_results(call);
-                            let cloned_val = results[0];
-                            self.builder.def_var(var, cloned_val);
-                        } else {
-                            // 没有 clone 函数，直接使用值
-                            self.builder.def_var(var, val);
-                        }
-                    } else {
-                        // 没有 clone 函数，直接使用值
-                        self.builder.def_var(var, val);
-                    }
+                    // 值来自另一个变量，复制（或共享）一份
+                    let copied = self.emit_binding_copy(val, &bolide_ty);
+                    self.builder.def_var(var, copied);
                 }
             } else {
                 // 非 RC 类型或来自生命周期函数，直接使用值
                 self.builder.def_var(var, val);
             }
         } else {
-            // 根据类型初始化默认值
+            // 根据类型初始化默认值；packed 列表默认为空列表
             let zero = if matches!(bolide_ty, BolideType::Float) {
                 self.builder.ins().f64const(0.0)
+            } else if let BolideType::PackedList(elem) = &bolide_ty {
+                let elem = self.normalize_bolide_type(elem);
+                let len = self.builder.ins().iconst(types::I64, 0);
+                self.emit_packed_list_new(&elem, len)?
             } else {
                 self.builder.ins().iconst(ty, 0)
             };
@@ -3662,13 +6149,9 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             .map(|v| self.is_lifetime_func_call(v))
             .unwrap_or(false);
 
-        // 调用者端借用检查：记录借用关系
-        if is_from_lifetime_func {
-            if let Some(ref value) = decl.value {
-                if let Some(source_var) = self.get_lifetime_call_source(value) {
-                    self.record_borrow(&decl.name, &source_var);
-                }
-            }
+        // 调用者端借用检查：记录借用关系（经过变量、索引和元组传递的借用同样记录）
+        if let Some(ref value) = decl.value {
+            self.track_borrow(&decl.name, value);
         }
 
         if existing_var.is_none()
@@ -3689,14 +6172,19 @@ impl<'a, 'b> CompileContext<'a, 'b> {
     /// 编译 return 语句
     fn compile_return(&mut self, expr: Option<&Expr>) -> Result<(), String> {
         if let Some(e) = expr {
-            // 生命周期模式：验证返回值来源
-            if self.uses_lifetime_mode() {
+            // 返回借用值需要当前函数也声明 from；生命周期模式下验证返回值来源
+            if let Some((borrower, source)) = self.borrow_in_expr(e) {
+                self.validate_borrowed_return(&borrower, &source)?;
+            } else if self.uses_lifetime_mode() {
                 self.validate_lifetime_return(e)?;
             }
 
             // 先编译返回表达式
-            let val = self.compile_expr(e)?;
+            let ret_ty = self.func_return_types.get(&self.current_func_name).cloned().flatten();
+            let val = self.compile_owned_expr(e, ret_ty.as_ref())?;
             let val_ty = self.infer_expr_type(e);
+            // 穿过的 pool 块先拆除（等任务完成），之后才释放变量
+            self.emit_pool_early_exits(0)?;
             
             // 最终使用的返回值（可能会因为 retain 而改变指针）
             let mut final_val = val;
@@ -3718,11 +6206,14 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                          self.remove_temp_rc_value(val);
                      } else {
                          // 如果不是临时值
-                         if return_var_name.is_some() {
-                             // 如果是变量 (Ident)，cleanup_except 会跳过它，所以不需要 retain (count 不变)
+                         let returns_owned_var = return_var_name.as_ref()
+                             .is_some_and(|name| self.rc_variables.iter().any(|(n, _)| n == name)
+                                 && !self.ref_params.iter().any(|(n, _, _)| n == name));
+                         if returns_owned_var {
+                             // 如果是本函数持有的变量，cleanup_except 会跳过它，所以不需要 retain (count 不变)
                          } else {
                              // 如果是其他表达式 (如 Index, Member)，是从某个容器借用的
-                             // cleanup 会释放容器，导致该值也被释放
+                             // cleanup 会释放容器，导致该值也被释放；借用参数、ref 参数和全局变量的值仍归调用者所有
                              // 所以这里必须 retain (clone) 一份，使 count +1
                              if let Some(new_val) = self.emit_retain(val, &val_ty) {
                                  final_val = new_val;
@@ -3738,11 +6229,13 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 self.emit_rc_cleanup_except(return_var_name.as_deref());
             }
 
-            // 写回 Ref 参数
-            self.write_back_ref_params();
-
+            self.emit_measure_early_exits()?;
+            self.emit_list_iter_early_exits()?;
+            self.emit_trace_pop()?;
+            self.emit_main_flush()?;
             self.builder.ins().return_(&[final_val]);
         } else {
+            self.emit_pool_early_exits(0)?;
             // 生命周期模式下跳过 ARC 操作
             if !self.uses_lifetime_mode() {
                 // 释放所有临时 RC 值
@@ -3752,9 +6245,10 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 self.emit_rc_cleanup();
             }
 
-            // 写回 Ref 参数
-            self.write_back_ref_params();
-
+            self.emit_measure_early_exits()?;
+            self.emit_list_iter_early_exits()?;
+            self.emit_trace_pop()?;
+            self.emit_main_flush()?;
             self.builder.ins().return_(&[]);
         }
         Ok(())
@@ -3762,23 +6256,47 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
     /// 统一的 retain 辅助函数
     fn emit_retain(&mut self, val: Value, ty: &BolideType) -> Option<Value> {
-        if let Some(clone_func) = Self::get_clone_func_name(ty) {
-             if let Some(&func_ref) = self.func_refs.get(clone_func) {
-                 let call = self.builder.ins().call(func_ref, &[val]);
-                 Some(self.builder.inst_results(call)[0])
-             } else {
-                 None
-             }
-        } else {
-            None
+        Self::get_clone_func_name(ty)?;
+        Some(self.emit_binding_copy(val, ty))
+    }
+
+    /// 按 ref 传递的实参必须拥有自己的值，且不能在同一次调用中再作为其他实参（见 ParamMode::Ref）
+    fn check_ref_argument(&self, func_name: &str, index: usize, var_name: &str, args: &[Expr]) -> Result<(), String> {
+        let param_name = self.func_params.get(func_name)
+            .and_then(|params| params.get(index))
+            .map(|p| p.name.clone())
+            .unwrap_or_default();
+        let ty = self.var_types.get(var_name)
+            .or_else(|| self.global_var_types.get(var_name));
+        if !ty.is_some_and(Self::is_rc_type) {
+            return Ok(());
+        }
+
+        let owns_value = !self.variables.contains_key(var_name)
+            || self.rc_variables.iter().any(|(name, _)| name == var_name)
+            || self.ref_params.iter().any(|(name, _, _)| name == var_name);
+        if !owns_value {
+            return Err(format!(
+                "'{}' is borrowed and cannot be passed to ref parameter '{}' of '{}'; declare it as a ref or owned parameter",
+                var_name, param_name, func_name
+            ));
+        }
+
+        for (j, other) in args.iter().enumerate() {
+            if j != index && Self::root_var(other) == Some(var_name) && Self::is_rc_type(&self.infer_expr_type(other)) {
+                return Err(format!(
+                    "'{}' is passed to ref parameter '{}' of '{}' and cannot be used in another argument of the same call",
+                    var_name, param_name, func_name
+                ));
+            }
         }
+        Ok(())
     }
 
-    /// 写回所有 Ref 参数的值
-    fn write_back_ref_params(&mut self) {
-        for (_, var, ptr_addr) in &self.ref_params.clone() {
-            let current_val = self.builder.use_var(*var);
-            self.builder.ins().store(MemFlags::new(), current_val, *ptr_addr, 0);
+    /// Ref 参数被赋值后立即写入调用者的变量
+    fn store_through_ref_param(&mut self, var_name: &str, val: Value) {
+        if let Some(&(_, _, ptr_addr)) = self.ref_params.iter().find(|(name, _, _)| name == var_name) {
+            self.builder.ins().store(MemFlags::new(), val, ptr_addr, 0);
         }
     }
 
@@ -3885,11 +6403,107 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         Ok(then_terminated && else_terminated)
     }
 
+    /// 编译 match 语句
+    ///
+    /// 整数用 `Switch` 分派：连续的分支值生成 br_table，稀疏的生成二分比较。
+    /// 字符串依次和各个字面量 string_eq，命中后把分支序号传给 dispatch 块再分派。
+    fn compile_match(&mut self, match_stmt: &bolide_parser::MatchStmt) -> Result<bool, String> {
+        let subject_ty = self.infer_expr_type(&match_stmt.subject);
+        let is_str = match subject_ty {
+            BolideType::Int => false,
+            BolideType::Str => true,
+            ref other => return Err(format!("match subject must be int or str, got {:?}", other)),
+        };
+        for arm in &match_stmt.arms {
+            for pattern in &arm.patterns {
+                if matches!(pattern, MatchPattern::Str(_)) != is_str {
+                    return Err(format!("match on {:?} cannot have pattern {:?}", subject_ty, pattern));
+                }
+            }
+        }
+
+        let subject = self.compile_expr(&match_stmt.subject)?;
+        let arm_blocks: Vec<Block> = match_stmt.arms.iter().map(|_| self.builder.create_block()).collect();
+        let default_block = self.builder.create_block();
+        let merge_block = self.builder.create_block();
+
+        let mut switch = Switch::new();
+        let discriminant = if is_str {
+            // 比较期间 subject 必须存活，临时值在 dispatch 块（所有比较路径的汇合点）释放
+            let dispatch_block = self.builder.create_block();
+            self.builder.append_block_param(dispatch_block, types::I64);
+            let eq_ref = self.get_func_ref("string_eq")?;
+            for (index, arm) in match_stmt.arms.iter().enumerate() {
+                for pattern in &arm.patterns {
+                    let MatchPattern::Str(text) = pattern else { unreachable!() };
+                    let literal = self.compile_expr(&Expr::String(text.clone()))?;
+                    let call = self.builder.ins().call(eq_ref, &[subject, literal]);
+                    let eq = self.builder.inst_results(call)[0];
+                    let arm_index = self.builder.ins().iconst(types::I64, index as i64);
+                    let next_block = self.builder.create_block();
+                    self.builder.ins().brif(eq, dispatch_block, &[arm_index], next_block, &[]);
+                    self.builder.switch_to_block(next_block);
+                    self.builder.seal_block(next_block);
+                }
+            }
+            let no_match = self.builder.ins().iconst(types::I64, match_stmt.arms.len() as i64);
+            self.builder.ins().jump(dispatch_block, &[no_match]);
+            self.builder.switch_to_block(dispatch_block);
+            self.builder.seal_block(dispatch_block);
+            for (index, &block) in arm_blocks.iter().enumerate() {
+                switch.set_entry(index as u128, block);
+            }
+            self.builder.block_params(dispatch_block)[0]
+        } else {
+            for (arm, &block) in match_stmt.arms.iter().zip(&arm_blocks) {
+                for pattern in &arm.patterns {
+                    let MatchPattern::Int(value) = pattern else { unreachable!() };
+                    switch.set_entry(*value as u64 as u128, block);
+                }
+            }
+            subject
+        };
+        self.release_temp_rc_values();
+        switch.emit(&mut self.builder, discriminant, default_block);
+
+        let mut all_terminated = true;
+        let bodies = match_stmt.arms.iter().map(|arm| &arm.body).zip(arm_blocks)
+            .chain(match_stmt.default.iter().zip([default_block]));
+        for (body, block) in bodies {
+            self.builder.switch_to_block(block);
+            self.builder.seal_block(block);
+            self.enter_scope();
+            let mut terminated = false;
+            for stmt in body {
+                if terminated { break; }
+                terminated = self.compile_stmt(stmt)?;
+            }
+            self.leave_scope()?;
+            if !terminated {
+                self.builder.ins().jump(merge_block, &[]);
+            }
+            all_terminated &= terminated;
+        }
+        if match_stmt.default.is_none() {
+            // 没有 `_` 分支：不匹配时什么都不执行
+            self.builder.switch_to_block(default_block);
+            self.builder.seal_block(default_block);
+            self.builder.ins().jump(merge_block, &[]);
+            all_terminated = false;
+        }
+
+        self.builder.switch_to_block(merge_block);
+        self.builder.seal_block(merge_block);
+
+        Ok(all_terminated)
+    }
+
     /// 编译 while 语句
     fn compile_while(&mut self, while_stmt: &bolide_parser::WhileStmt) -> Result<(), String> {
         let header_block = self.builder.create_block();
         let body_block = self.builder.create_block();
         let exit_block = self.builder.create_block();
+        let latch_block = self.builder.create_block();
 
         // 第一遍：收集循环体内的 RC 变量声明
         let loop_rc_vars = self.collect_rc_var_decls(&while_stmt.body);
@@ -3910,6 +6524,11 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             self.track_rc_variable(var_name, var_ty);
         }
 
+        // 条件里实参不随循环变化的 @pure 调用在进入循环前算一次
+        let hoisted = self.hoist_pure_calls(&while_stmt.condition, &while_stmt.body)?;
+        self.hoisted_pure_calls.push(hoisted);
+
+        self.push_loop(latch_block, exit_block);
         self.builder.ins().jump(header_block, &[]);
 
         self.builder.switch_to_block(header_block);
@@ -3926,9 +6545,11 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             terminated = self.compile_stmt(stmt)?;
         }
         self.leave_scope()?;  // 离开循环体作用域
-        if !terminated {
-            self.builder.ins().jump(header_block, &[]);
+        if self.enter_loop_latch(terminated) {
+            self.emit_loop_back_edge(header_block)?;
         }
+        self.loops.pop();
+        self.hoisted_pure_calls.pop();
 
         self.builder.seal_block(header_block);
 
@@ -3955,11 +6576,23 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                     if vars.len() != 1 {
                         return Err("range() loop only supports single variable".to_string());
                     }
-                    return self.compile_for_range(&vars[0], args, &for_stmt.body);
+                    return self.compile_for_range(&vars[0], for_stmt.var_type.as_ref(), args, &for_stmt.body);
                 }
             }
         }
+        if for_stmt.var_type.is_some() {
+            return Err("Loop variable type annotations are only supported on range() loops".to_string());
+        }
         
+        if let BolideType::PackedList(_) = self.infer_expr_type(&for_stmt.iter) {
+            return self.compile_for_packed(vars, &for_stmt.iter, &for_stmt.body);
+        }
+
+        if let BolideType::Channel(elem) = self.infer_expr_type(&for_stmt.iter) {
+            let elem = self.normalize_bolide_type(&elem);
+            return self.compile_for_channel(vars, &for_stmt.iter, elem, &for_stmt.body);
+        }
+
         // 检查是否是字典迭代
         if let BolideType::Dict(_, _) = self.infer_expr_type(&for_stmt.iter) {
              return self.compile_for_dict(vars, &for_stmt.iter, &for_stmt.body);
@@ -3975,42 +6608,70 @@ impl<'a, 'b> CompileContext<'a, 'b> {
     /// - range(end): 0 到 end-1
     /// - range(start, end): start 到 end-1
     /// - range(start, end, step): start 到 end-1，步长为 step
-    fn compile_for_range(&mut self, var_name: &str, args: &[Expr], body: &[Statement]) -> Result<(), String> {
-        // 解析 range 参数
-        let (start_val, end_val, step_val, is_negative_step) = match args.len() {
-            1 => {
-                let end = self.compile_expr(&args[0])?;
-                let start = self.builder.ins().iconst(types::I64, 0);
-                let step = self.builder.ins().iconst(types::I64, 1);
-                (start, end, step, false)
-            }
-            2 => {
-                let start = self.compile_expr(&args[0])?;
-                let end = self.compile_expr(&args[1])?;
-                let step = self.builder.ins().iconst(types::I64, 1);
-                (start, end, step, false)
-            }
-            3 => {
-                let start = self.compile_expr(&args[0])?;
-                let end = self.compile_expr(&args[1])?;
-                let step = self.compile_expr(&args[2])?;
-                // 检查是否可能是负步长 (编译时无法确定，运行时处理)
-                // 对于常量步长，可以优化
-                let is_neg = if let Expr::Int(n) = &args[2] { *n < 0 } else { false };
-                (start, end, step, is_neg)
+    ///
+    /// 任一参数是 float（或循环变量标注为 float）时是 float range，见 `compile_float_range_bounds`。
+    fn compile_for_range(&mut self, var_name: &str, var_type: Option<&BolideType>, args: &[Expr], body: &[Statement]) -> Result<(), String> {
+        if args.is_empty() || args.len() > 3 {
+            return Err("range() expects 1, 2, or 3 arguments".to_string());
+        }
+        let is_float = match var_type {
+            Some(BolideType::Float) => true,
+            Some(BolideType::Int) | None => args.iter().any(|arg| self.infer_expr_type(arg) == BolideType::Float),
+            Some(other) => return Err(format!("range() loop variable '{}' must be int or float, not {:?}", var_name, other)),
+        };
+        if is_float && var_type == Some(&BolideType::Int) {
+            return Err(format!(
+                "range() with float bounds cannot bind int loop variable '{}'; annotate it as float or convert the bounds with int()",
+                var_name
+            ));
+        }
+
+        // float range：整数计数器 (计数器变量, 迭代次数, start, step)
+        let mut float_range = None;
+        let (start_val, end_val, step_val, is_negative_step) = if is_float {
+            let (start, count, step) = self.compile_float_range_bounds(args)?;
+            let counter = self.declare_variable(&format!("__range_count_{}", var_name), types::I64);
+            let zero = self.builder.ins().iconst(types::I64, 0);
+            self.builder.def_var(counter, zero);
+            float_range = Some((counter, count, start, step));
+            (start, count, step, false)
+        } else {
+            match args.len() {
+                1 => {
+                    let end = self.compile_expr(&args[0])?;
+                    let start = self.builder.ins().iconst(types::I64, 0);
+                    let step = self.builder.ins().iconst(types::I64, 1);
+                    (start, end, step, false)
+                }
+                2 => {
+                    let start = self.compile_expr(&args[0])?;
+                    let end = self.compile_expr(&args[1])?;
+                    let step = self.builder.ins().iconst(types::I64, 1);
+                    (start, end, step, false)
+                }
+                _ => {
+                    let start = self.compile_expr(&args[0])?;
+                    let end = self.compile_expr(&args[1])?;
+                    let step = self.compile_expr(&args[2])?;
+                    // 检查是否可能是负步长 (编译时无法确定，运行时处理)
+                    // 对于常量步长，可以优化
+                    let is_neg = if let Expr::Int(n) = &args[2] { *n < 0 } else { false };
+                    (start, end, step, is_neg)
+                }
             }
-            _ => return Err("range() expects 1, 2, or 3 arguments".to_string()),
         };
 
         // 创建循环变量
-        let loop_var = self.declare_variable(var_name, types::I64);
+        let (loop_ty, loop_bolide_ty) = if is_float { (types::F64, BolideType::Float) } else { (types::I64, BolideType::Int) };
+        let loop_var = self.declare_variable(var_name, loop_ty);
         self.builder.def_var(loop_var, start_val);
-        self.var_types.insert(var_name.to_string(), BolideType::Int);
+        self.var_types.insert(var_name.to_string(), loop_bolide_ty);
 
         // 创建基本块
         let header_block = self.builder.create_block();
         let body_block = self.builder.create_block();
         let exit_block = self.builder.create_block();
+        let latch_block = self.builder.create_block();
 
         // 收集循环体内的 RC 变量声明
         let loop_rc_vars = self.collect_rc_var_decls(body);
@@ -4027,11 +6688,15 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         }
 
         // 跳转到循环头
+        self.push_loop(latch_block, exit_block);
         self.builder.ins().jump(header_block, &[]);
 
         // 循环头: 检查条件
         self.builder.switch_to_block(header_block);
-        let current_val = self.builder.use_var(loop_var);
+        let current_val = match float_range {
+            Some((counter, ..)) => self.builder.use_var(counter),
+            None => self.builder.use_var(loop_var),
+        };
         
         // 根据步长方向选择比较条件
         let cond = if is_negative_step {
@@ -4046,6 +6711,14 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         // 循环体
         self.builder.switch_to_block(body_block);
         self.builder.seal_block(body_block);
+        if let Some((counter, _, start, step)) = float_range {
+            // t = start + i * step
+            let i = self.builder.use_var(counter);
+            let i = self.builder.ins().fcvt_from_sint(types::F64, i);
+            let offset = self.builder.ins().fmul(i, step);
+            let t = self.builder.ins().fadd(start, offset);
+            self.builder.def_var(loop_var, t);
+        }
         self.enter_scope();
         let mut terminated = false;
         for stmt in body {
@@ -4054,13 +6727,20 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         }
         self.leave_scope()?;
         
-        if !terminated {
-            // 递增/递减循环变量: i = i + step
-            let current = self.builder.use_var(loop_var);
-            let next = self.builder.ins().iadd(current, step_val);
-            self.builder.def_var(loop_var, next);
-            self.builder.ins().jump(header_block, &[]);
+        if self.enter_loop_latch(terminated) {
+            if let Some((counter, ..)) = float_range {
+                let current = self.builder.use_var(counter);
+                let next = self.builder.ins().iadd_imm(current, 1);
+                self.builder.def_var(counter, next);
+            } else {
+                // 递增/递减循环变量: i = i + step
+                let current = self.builder.use_var(loop_var);
+                let next = self.builder.ins().iadd(current, step_val);
+                self.builder.def_var(loop_var, next);
+            }
+            self.emit_loop_back_edge(header_block)?;
         }
+        self.loops.pop();
 
         self.builder.seal_block(header_block);
         self.builder.switch_to_block(exit_block);
@@ -4069,20 +6749,67 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         Ok(())
     }
 
+    /// float range 的 (start, 迭代次数, step)，int 参数先转成 float
+    ///
+    /// 迭代次数预先算成 `ceil((end - start) / step)`，循环用整数计数器 i 驱动、每次取 `start + i * step`，
+    /// 累加步长的舍入误差不会让循环多跑或少跑一次。步长为 0 或参数含 NaN 时不迭代。
+    fn compile_float_range_bounds(&mut self, args: &[Expr]) -> Result<(Value, Value, Value), String> {
+        let mut bounds = Vec::with_capacity(3);
+        for arg in args {
+            let val = self.compile_expr(arg)?;
+            let val = if self.builder.func.dfg.value_type(val) == types::F64 {
+                val
+            } else {
+                self.builder.ins().fcvt_from_sint(types::F64, val)
+            };
+            bounds.push(val);
+        }
+        let (start, end, step) = match bounds[..] {
+            [end] => (self.builder.ins().f64const(0.0), end, self.builder.ins().f64const(1.0)),
+            [start, end] => (start, end, self.builder.ins().f64const(1.0)),
+            [start, end, step] => (start, end, step),
+            _ => return Err("range() expects 1, 2, or 3 arguments".to_string()),
+        };
+        let span = self.builder.ins().fsub(end, start);
+        let trips = self.builder.ins().fdiv(span, step);
+        let trips = self.builder.ins().ceil(trips);
+        // NaN 转成 0；负数在循环条件里等同于 0
+        let count = self.builder.ins().fcvt_to_sint_sat(types::I64, trips);
+        let zero_f = self.builder.ins().f64const(0.0);
+        let zero_step = self.builder.ins().fcmp(FloatCC::Equal, step, zero_f);
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        let count = self.builder.ins().select(zero_step, zero, count);
+        Ok((start, count, step))
+    }
+
     /// 编译 for item in list { ... }
     /// 编译列表迭代逻辑 (通用)
+    ///
+    /// `use_iterator` 时通过运行时迭代器取元素（每个元素一次 list_iter_next，没有下标检查），
+    /// 迭代器持有列表，循环体给变量重新赋值也安全；否则按下标 list_get。
     fn compile_list_iteration_loop(
         &mut self, 
         vars: &[String], 
         list_ptr: Value, 
         elem_type: BolideType, 
-        body: &[Statement]
+        body: &[Statement],
+        use_iterator: bool,
     ) -> Result<(), String> {
-        // 获取列表长度: list_len(list_ptr)
-        let list_len_ref = *self.func_refs.get("list_len")
-            .ok_or("list_len not found")?;
-        let len_call = self.builder.ins().call(list_len_ref, &[list_ptr]);
-        let list_length = self.builder.inst_results(len_call)[0];
+        // 迭代器和接收元素的栈空间
+        let iter_state = if use_iterator {
+            let iter_new_ref = self.get_func_ref("list_iter_new")?;
+            let call = self.builder.ins().call(iter_new_ref, &[list_ptr]);
+            let iter = self.builder.inst_results(call)[0];
+            let slot = self.builder.create_sized_stack_slot(StackSlotData::new(
+                StackSlotKind::ExplicitSlot,
+                8,
+                0,
+            ));
+            self.list_iter_stack.push(iter);
+            Some((iter, slot))
+        } else {
+            None
+        };
 
         // 使用第一个变量名作为索引变量后缀
         let loop_base_name = if !vars.is_empty() { &vars[0] } else { "loop" };
@@ -4095,15 +6822,11 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
         // 创建循环变量 (如果是单个变量)
         let loop_var = if vars.len() == 1 {
-            let v = self.declare_variable(&vars[0], types::I64); // 注意: declare_variable 需要具体类型吗? 这里的declare 是JIT internal mapping.
-            // Wait: declare_variable in jit.rs assigns Slot.
-            // Previous code:
-            // let loop_var = self.declare_variable(var_name, types::I64); -- TYPE I64?
-            // Element can be Ptr or I64.
-            // If elem_type is Ptr, we should declare valid Cranelift type.
-            // Step 630 line 3414: types::I64. (Maybe everything is I64/Ptr=I64 in current impl).
-            // I'll stick to I64.
-            self.builder.def_var(v, zero);
+            // float 元素的循环变量是 f64，其余元素（值或指针）都是 i64
+            let elem_cl_type = self.bolide_type_to_cranelift(&elem_type);
+            let v = self.declare_variable(&vars[0], elem_cl_type);
+            let init = if elem_cl_type == types::F64 { self.builder.ins().f64const(0.0) } else { zero };
+            self.builder.def_var(v, init);
             // 注册类型
             self.var_types.insert(vars[0].to_string(), elem_type.clone());
             Some(v)
@@ -4115,40 +6838,48 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         let header_block = self.builder.create_block();
         let body_block = self.builder.create_block();
         let exit_block = self.builder.create_block();
+        let latch_block = self.builder.create_block();
 
-        // 收集循环体内的 RC 变量声明
-        let loop_rc_vars = self.collect_rc_var_decls(body);
-        for (rc_var_name, var_ty) in &loop_rc_vars {
-            if self.variables.contains_key(rc_var_name) {
-                continue;
-            }
-            let ty = self.bolide_type_to_cranelift(var_ty);
-            let var = self.declare_variable(rc_var_name, ty);
-            let null_val = self.builder.ins().iconst(self.ptr_type, 0);
-            self.builder.def_var(var, null_val);
-            self.var_types.insert(rc_var_name.clone(), var_ty.clone());
-            self.track_rc_variable(rc_var_name, var_ty);
-        }
+        self.declare_loop_rc_vars(body);
+
+        // 获取列表长度: list_len(list_ptr)（迭代器自己记录长度）
+        let list_length = if iter_state.is_none() {
+            let list_len_ref = self.get_func_ref("list_len")?;
+            let len_call = self.builder.ins().call(list_len_ref, &[list_ptr]);
+            self.builder.inst_results(len_call)[0]
+        } else {
+            zero
+        };
 
         // 跳转到循环头
+        self.push_loop(latch_block, exit_block);
         self.builder.ins().jump(header_block, &[]);
 
-        // 循环头: 检查条件 (idx < length)
+        // 循环头: 检查条件 (idx < length，或迭代器还有元素)
         self.builder.switch_to_block(header_block);
-        let current_idx = self.builder.use_var(idx_var);
-        let cond = self.builder.ins().icmp(IntCC::SignedLessThan, current_idx, list_length);
+        let cond = if let Some((iter, slot)) = iter_state {
+            let out_ptr = self.builder.ins().stack_addr(self.ptr_type, slot, 0);
+            let iter_next_ref = self.get_func_ref("list_iter_next")?;
+            let call = self.builder.ins().call(iter_next_ref, &[iter, out_ptr]);
+            self.builder.inst_results(call)[0]
+        } else {
+            let current_idx = self.builder.use_var(idx_var);
+            self.builder.ins().icmp(IntCC::SignedLessThan, current_idx, list_length)
+        };
         self.builder.ins().brif(cond, body_block, &[], exit_block, &[]);
 
         // 循环体
         self.builder.switch_to_block(body_block);
         self.builder.seal_block(body_block);
 
-        // 获取当前元素: list_get(list_ptr, idx)
-        let list_get_ref = *self.func_refs.get("list_get")
-            .ok_or("list_get not found")?;
-        let idx_val = self.builder.use_var(idx_var);
-        let get_call = self.builder.ins().call(list_get_ref, &[list_ptr, idx_val]);
-        let elem_val = self.builder.inst_results(get_call)[0];
+        // 获取当前元素: 迭代器写入的值，或 list_get(list_ptr, idx)
+        let elem_val = if let Some((_, slot)) = iter_state {
+            let slot_val = self.builder.ins().stack_load(types::I64, slot, 0);
+            self.slot_to_elem(slot_val, &elem_type)
+        } else {
+            let idx_val = self.builder.use_var(idx_var);
+            self.emit_list_get(list_ptr, idx_val, &elem_type)?
+        };
         
         if vars.len() == 1 {
              if let Some(v) = loop_var {
@@ -4158,16 +6889,14 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             // 解构 (Destructuring)
             match elem_type {
                 BolideType::List(inner_type) => { // List unpacking
-                    let list_get_ref = *self.func_refs.get("list_get").ok_or("list_get not found")?;
                     for (i, var_name) in vars.iter().enumerate() {
                         let idx_const = self.builder.ins().iconst(types::I64, i as i64);
-                        let call = self.builder.ins().call(list_get_ref, &[elem_val, idx_const]);
-                        let val = self.builder.inst_results(call)[0];
+                        let val = self.emit_list_get(elem_val, idx_const, &inner_type)?;
                         self.define_variable(var_name, val, *inner_type.clone())?;
                     }
                 }
                 BolideType::Tuple(inner_types) => { // Tuple unpacking
-                    let tuple_get_ref = *self.func_refs.get("tuple_get").ok_or("tuple_get not found")?;
+                    let tuple_get_ref = self.get_func_ref("tuple_get")?;
                     // Ensure vars count matches tuple size? or min?
                     for (i, var_name) in vars.iter().enumerate() {
                          let idx_const = self.builder.ins().iconst(types::I64, i as i64);
@@ -4175,6 +6904,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                          let val = self.builder.inst_results(call)[0];
                          
                          let ty = if i < inner_types.len() { inner_types[i].clone() } else { BolideType::Int }; // Fallback
+                         let val = self.slot_to_elem(val, &ty);
                          self.define_variable(var_name, val, ty)?;
                     }
                 }
@@ -4190,21 +6920,43 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         }
         self.leave_scope()?;
         
-        if !terminated {
+        if self.enter_loop_latch(terminated) {
             // 递增索引: idx = idx + 1
             let current = self.builder.use_var(idx_var);
             let next = self.builder.ins().iadd_imm(current, 1);
             self.builder.def_var(idx_var, next);
-            self.builder.ins().jump(header_block, &[]);
+            self.emit_loop_back_edge(header_block)?;
         }
+        self.loops.pop();
 
         self.builder.seal_block(header_block);
         self.builder.switch_to_block(exit_block);
         self.builder.seal_block(exit_block);
 
+        if let Some((iter, _)) = iter_state {
+            self.list_iter_stack.pop();
+            let iter_free_ref = self.get_func_ref("list_iter_free")?;
+            self.builder.ins().call(iter_free_ref, &[iter]);
+        }
+
         Ok(())
     }
 
+    /// 在循环外预先声明循环体内的 RC 变量（初始为 null），每次迭代的 let 释放上一次的值
+    fn declare_loop_rc_vars(&mut self, body: &[Statement]) {
+        for (rc_var_name, var_ty) in self.collect_rc_var_decls(body) {
+            if self.variables.contains_key(&rc_var_name) {
+                continue;
+            }
+            let ty = self.bolide_type_to_cranelift(&var_ty);
+            let var = self.declare_variable(&rc_var_name, ty);
+            let null_val = self.builder.ins().iconst(self.ptr_type, 0);
+            self.builder.def_var(var, null_val);
+            self.var_types.insert(rc_var_name.clone(), var_ty.clone());
+            self.track_rc_variable(&rc_var_name, &var_ty);
+        }
+    }
+
     /// 编译 for item in list { ... }
     fn compile_for_list(&mut self, vars: &[String], iter_expr: &Expr, body: &[Statement]) -> Result<(), String> {
         let list_ptr = self.compile_expr(iter_expr)?;
@@ -4212,14 +6964,117 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             BolideType::List(inner) => *inner,
             _ => BolideType::Int,
         };
-        self.compile_list_iteration_loop(vars, list_ptr, elem_type, body)
+        // 遍历变量时用迭代器；临时列表（函数结果等）在循环结束时释放，按下标遍历
+        let use_iterator = matches!(iter_expr, Expr::Ident(_));
+        self.hold_loop_temps(iter_expr, list_ptr);
+        self.compile_list_iteration_loop(vars, list_ptr, elem_type, body, use_iterator)?;
+        self.release_loop_temps();
+        Ok(())
+    }
+
+    /// for 语句的临时值（被遍历的函数结果、字面量及其元素）改由循环持有到循环结束：
+    /// 循环体每条语句结束时释放临时值的代码每次迭代都会执行，不能释放它们
+    fn hold_loop_temps(&mut self, iter_expr: &Expr, container: Value) {
+        let mut held = std::mem::take(&mut self.temp_rc_values);
+        // 表达式中的列表/字典字面量不登记为临时值
+        if matches!(iter_expr, Expr::List(_) | Expr::Dict(_)) && !held.iter().any(|(v, _)| *v == container) {
+            held.push((container, self.infer_expr_type(iter_expr)));
+        }
+        self.loop_temps.push(held);
+    }
+
+    /// 循环结束（包括 break）：释放最内层循环持有的临时值
+    fn release_loop_temps(&mut self) {
+        for (val, ty) in self.loop_temps.pop().unwrap_or_default().into_iter().rev() {
+            self.emit_release(val, &ty);
+        }
+    }
+
+    /// 编译 for msg in ch { ... }：每次迭代接收一个值，通道关闭并取空后结束循环
+    ///
+    /// RC 元素由循环变量持有：接收下一个值前释放上一个，最后一个随函数里的其他 RC 变量释放。
+    fn compile_for_channel(&mut self, vars: &[String], iter_expr: &Expr, elem: BolideType, body: &[Statement]) -> Result<(), String> {
+        let [var_name] = vars else {
+            return Err("channel loop only supports single variable".to_string());
+        };
+        let channel = self.compile_expr(iter_expr)?;
+        let payload = Self::channel_payload_tag(&elem).is_some();
+
+        // 外层已有同名同类型的 RC 变量时复用它（和循环体里的 let 一样）
+        let reuse = payload && self.rc_variables.iter().any(|(name, ty)| name == var_name && *ty == elem);
+        let var = match self.variables.get(var_name) {
+            Some(&var) if reuse => var,
+            _ => {
+                let elem_cl_type = self.bolide_type_to_cranelift(&elem);
+                let var = self.declare_variable(var_name, elem_cl_type);
+                let init = if elem_cl_type == types::F64 {
+                    self.builder.ins().f64const(0.0)
+                } else {
+                    self.builder.ins().iconst(elem_cl_type, 0)
+                };
+                self.builder.def_var(var, init);
+                if payload {
+                    self.track_rc_variable(var_name, &elem);
+                }
+                var
+            }
+        };
+        self.var_types.insert(var_name.clone(), elem.clone());
+
+        let slot = self.builder.create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8, 0));
+        let header_block = self.builder.create_block();
+        let body_block = self.builder.create_block();
+        let exit_block = self.builder.create_block();
+        let latch_block = self.builder.create_block();
+
+        self.declare_loop_rc_vars(body);
+
+        self.push_loop(latch_block, exit_block);
+        self.builder.ins().jump(header_block, &[]);
+
+        // 循环头: channel_recv_opt 返回 0 表示通道已关闭并取空
+        self.builder.switch_to_block(header_block);
+        let out_ptr = self.builder.ins().stack_addr(self.ptr_type, slot, 0);
+        let recv_name = if payload { "channel_recv_opt_ptr" } else { "channel_recv_opt" };
+        let recv_ref = self.get_func_ref(recv_name)?;
+        let call = self.builder.ins().call(recv_ref, &[channel, out_ptr]);
+        let received = self.builder.inst_results(call)[0];
+        self.builder.ins().brif(received, body_block, &[], exit_block, &[]);
+
+        self.builder.switch_to_block(body_block);
+        self.builder.seal_block(body_block);
+        let raw = self.builder.ins().stack_load(types::I64, slot, 0);
+        let value = self.slot_to_elem(raw, &elem);
+        if payload {
+            let old = self.builder.use_var(var);
+            self.emit_release(old, &elem);
+        }
+        self.builder.def_var(var, value);
+
+        self.enter_scope();
+        let mut terminated = false;
+        for stmt in body {
+            if terminated { break; }
+            terminated = self.compile_stmt(stmt)?;
+        }
+        self.leave_scope()?;
+        if self.enter_loop_latch(terminated) {
+            self.emit_loop_back_edge(header_block)?;
+        }
+        self.loops.pop();
+
+        self.builder.seal_block(header_block);
+        self.builder.switch_to_block(exit_block);
+        self.builder.seal_block(exit_block);
+        Ok(())
     }
 
     /// 编译 for key in dict { ... }
     fn compile_for_dict(&mut self, vars: &[String], iter_expr: &Expr, body: &[Statement]) -> Result<(), String> {
         let dict_ptr = self.compile_expr(iter_expr)?;
-        
-        let dict_iter = *self.func_refs.get("dict_iter").ok_or("dict_iter not found")?;
+        self.hold_loop_temps(iter_expr, dict_ptr);
+
+        let dict_iter = self.get_func_ref("dict_iter")?;
         let call = self.builder.ins().call(dict_iter, &[dict_ptr]);
         let keys_list_ptr = self.builder.inst_results(call)[0];
         
@@ -4238,7 +7093,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             // 更简单的方法是: 手动编写 loop 逻辑 (inline)
             
             // 1. 获取 length (keys list)
-            let list_len_ref = *self.func_refs.get("list_len").ok_or("list_len not found")?;
+            let list_len_ref = self.get_func_ref("list_len")?;
             let len_call = self.builder.ins().call(list_len_ref, &[keys_list_ptr]);
             let list_length = self.builder.inst_results(len_call)[0];
 
@@ -4249,7 +7104,11 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             let header_block = self.builder.create_block();
             let body_block = self.builder.create_block();
             let exit_block = self.builder.create_block();
+            let latch_block = self.builder.create_block();
 
+            self.declare_loop_rc_vars(body);
+
+            self.push_loop(latch_block, exit_block);
             self.builder.ins().jump(header_block, &[]);
 
             // Header
@@ -4263,14 +7122,14 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             self.builder.seal_block(body_block);
 
             // Get Key
-            let list_get_ref = *self.func_refs.get("list_get").ok_or("list_get not found")?;
+            let list_get_ref = self.get_func_ref("list_get")?;
             let get_key_call = self.builder.ins().call(list_get_ref, &[keys_list_ptr, current_idx]);
             let key_val = self.builder.inst_results(get_key_call)[0];
             
             self.define_variable(&vars[0], key_val, key_type.clone())?;
 
             // Get Value: val = dict_get(dict_ptr, key)
-            let dict_get_ref = *self.func_refs.get("dict_get").ok_or("dict_get not found")?;
+            let dict_get_ref = self.get_func_ref("dict_get")?;
             let get_val_call = self.builder.ins().call(dict_get_ref, &[dict_ptr, key_val]);
             let val_val = self.builder.inst_results(get_val_call)[0];
             
@@ -4285,12 +7144,13 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             }
             self.leave_scope()?;
 
-            if !terminated {
+            if self.enter_loop_latch(terminated) {
                  let current = self.builder.use_var(idx_var);
                  let next = self.builder.ins().iadd_imm(current, 1);
                  self.builder.def_var(idx_var, next);
-                 self.builder.ins().jump(header_block, &[]);
+                 self.emit_loop_back_edge(header_block)?;
             }
+            self.loops.pop();
 
             self.builder.seal_block(header_block);
             self.builder.switch_to_block(exit_block);
@@ -4298,12 +7158,13 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
         } else {
             // 单变量迭代 (Keys)
-            self.compile_list_iteration_loop(vars, keys_list_ptr, key_type, body)?;
+            self.compile_list_iteration_loop(vars, keys_list_ptr, key_type, body, false)?;
         }
 
         // Release keys list
-        let release_fn = *self.func_refs.get("list_release").ok_or("list_release not found")?;
+        let release_fn = self.get_func_ref("list_release")?;
         self.builder.ins().call(release_fn, &[keys_list_ptr]);
+        self.release_loop_temps();
 
         Ok(())
     }
@@ -4323,8 +7184,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 let len = s.len();
 
                 // 获取 string_literal 函数引用 (Uses interning)
-                let func_ref = *self.func_refs.get("string_literal")
-                    .ok_or("string_literal not found")?;
+                let func_ref = self.get_func_ref("string_literal")?;
 
                 // 创建指针和长度的立即数
                 let ptr_val = self.builder.ins().iconst(self.ptr_type, ptr as i64);
@@ -4337,11 +7197,14 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             }
             Expr::BigInt(s) => self.compile_bigint_literal(s),
             Expr::Decimal(s) => self.compile_decimal_literal(s),
+            Expr::Duration(ns) => Ok(self.builder.ins().iconst(types::I64, *ns)),
             Expr::Ident(name) => self.compile_ident(name),
             Expr::BinOp(left, op, right) => self.compile_binop(left, op, right),
+            Expr::CompareChain(first, rest) => self.compile_compare_chain(first, rest),
             Expr::UnaryOp(op, operand) => self.compile_unary(op, operand),
             Expr::Call(callee, args) => self.compile_call(callee, args),
             Expr::Index(base, index) => self.compile_index(base, index),
+            Expr::Slice(base, start, end) => self.compile_slice(base, start.as_deref(), end.as_deref()),
             Expr::Member(base, member) => self.compile_member_access(base, member),
             Expr::List(items) => self.compile_list(items),
             Expr::Spawn(func_name, args) => self.compile_spawn(func_name, args),
@@ -4350,7 +7213,10 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             Expr::Await(inner_expr) => self.compile_await(inner_expr),
             Expr::AwaitAll(exprs) => self.compile_await_all(exprs),
             Expr::Tuple(exprs) => self.compile_tuple(exprs),
+            Expr::FString(parts) => self.compile_fstring(parts),
             Expr::Dict(entries) => self.compile_dict(entries),
+            Expr::NamedArg(name, _) => Err(format!("keyword argument '{}' is only allowed in calls to user-defined functions", name)),
+            Expr::Lambda(..) => Err("lambdas can only appear inside function bodies and top-level statements".to_string()),
         }
     }
 
@@ -4359,16 +7225,14 @@ impl<'a, 'b> CompileContext<'a, 'b> {
     fn compile_bigint_literal(&mut self, s: &str) -> Result<Value, String> {
         // 尝试作为 i64 解析，如果成功则用 bigint_from_i64
         let result = if let Ok(n) = s.parse::<i64>() {
-            let func_ref = *self.func_refs.get("bigint_from_i64")
-                .ok_or("bigint_from_i64 not found")?;
+            let func_ref = self.get_func_ref("bigint_from_i64")?;
             let val = self.builder.ins().iconst(types::I64, n);
             let call = self.builder.ins().call(func_ref, &[val]);
             let results = self.builder.inst_results(call);
             results[0]
         } else {
             // 用字符串方式创建 BigInt（超出 i64 范围的大数）
-            let func_ref = *self.func_refs.get("bigint_from_str")
-                .ok_or("bigint_from_str not found")?;
+            let func_ref = self.get_func_ref("bigint_from_str")?;
 
             // 将字符串字面量泄露到堆上，确保在程序生命周期内有效
             let bytes: Box<[u8]> = s.as_bytes().into();
@@ -4391,8 +7255,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
     fn compile_decimal_literal(&mut self, s: &str) -> Result<Value, String> {
         // 尝试作为 f64 解析
         if let Ok(f) = s.parse::<f64>() {
-            let func_ref = *self.func_refs.get("decimal_from_f64")
-                .ok_or("decimal_from_f64 not found")?;
+            let func_ref = self.get_func_ref("decimal_from_f64")?;
             let val = self.builder.ins().f64const(f);
             let call = self.builder.ins().call(func_ref, &[val]);
             let results = self.builder.inst_results(call);
@@ -4411,6 +7274,19 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         if self.moved_variables.contains(name) {
             return Err(format!("Variable '{}' has been moved and cannot be used", name));
         }
+        if let Some(action) = self.invalidated_borrows.get(name) {
+            let source = self.borrowed_vars.get(name).map(|(source, _)| source.as_str()).unwrap_or("a list");
+            return Err(format!(
+                "Lifetime error in function '{}': '{}' borrows an element of '{}' and cannot be used after {}, \
+                 which may move or free the list's elements",
+                self.current_func_name, name, source, action
+            ));
+        }
+        if self.borrowed_vars.contains_key(name) {
+            for frame in &mut self.loops {
+                frame.borrows_used.insert(name.to_string());
+            }
+        }
 
         // 先查找变量
         if let Some(&var) = self.variables.get(name) {
@@ -4457,17 +7333,17 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         // 检查是否是全局变量
         if let Some(&data_id) = self.global_data_ids.get(name) {
             // 获取全局变量的地址
-            let gv = self.module.declare_data_in_func(data_id, self.builder.func);
-            let addr = self.builder.ins().global_value(self.ptr_type, gv);
-            // 从地址加载值
-            let val = self.builder.ins().load(self.ptr_type, MemFlags::new(), addr, 0);
+            let addr = self.global_addr(name, data_id)?;
+            // 从地址加载值（float 全局变量按 f64 读取）
+            let ty = self.global_var_types.get(name).map_or(self.ptr_type, |ty| self.bolide_type_to_cranelift(ty));
+            let val = self.builder.ins().load(ty, MemFlags::new(), addr, 0);
             return Ok(val);
         }
 
         // 如果不是变量，检查是否是函数名（支持函数作为值）
-        if let Some(&func_ref) = self.func_refs.get(name) {
+        if let Ok(func_ref) = self.get_func_ref(name) {
             // 返回函数指针
-            return Ok(self.builder.ins().func_addr(self.ptr_type, func_ref));
+            return Ok(self.user_func_addr(name, func_ref));
         }
 
         Err(format!("Undefined variable or function: {}", name))
@@ -4479,15 +7355,40 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         let left_ty = self.infer_expr_type(left);
         let right_ty = self.infer_expr_type(right);
 
+        if matches!(op, BinOp::Is | BinOp::IsNot) {
+            equality::check_identity_operand(left, Some(&left_ty))?;
+            equality::check_identity_operand(right, Some(&right_ty))?;
+        }
+
         // 类类型运算符重载
         if let BolideType::Custom(ref class_name) = left_ty {
             if let Some(result) = self.try_operator_overload(left, op, right, class_name)? {
                 return Ok(result);
             }
+            if matches!(op, BinOp::Eq | BinOp::Ne) {
+                return self.compile_object_equals(left, op, right, class_name, &right_ty);
+            }
         }
 
         let lhs = self.compile_expr(left)?;
         let rhs = self.compile_expr(right)?;
+        self.compile_binop_values(lhs, &left_ty, op, rhs, &right_ty)
+    }
+
+    /// 对已求值的操作数做二元运算
+    fn compile_binop_values(&mut self, lhs: Value, left_ty: &BolideType, op: &BinOp, rhs: Value, right_ty: &BolideType) -> Result<Value, String> {
+        // 引用同一性：直接比较指针（操作数类型已在 compile_binop 中检查）
+        if matches!(op, BinOp::Is | BinOp::IsNot) {
+            let cc = if matches!(op, BinOp::Is) { IntCC::Equal } else { IntCC::NotEqual };
+            let cmp = self.builder.ins().icmp(cc, lhs, rhs);
+            return Ok(self.builder.ins().uextend(types::I64, cmp));
+        }
+
+        // duration / deadline：按单位检查后做 i64 运算
+        if let Some(result) = duration::binop_type(left_ty, op, right_ty) {
+            result?;
+            return Ok(duration::emit_binop(&mut self.builder, left_ty, op, lhs, right_ty, rhs));
+        }
 
         // BigInt 运算
         if matches!(left_ty, BolideType::BigInt) || matches!(right_ty, BolideType::BigInt) {
@@ -4502,24 +7403,28 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         // 字符串拼接
         if matches!(left_ty, BolideType::Str) && matches!(right_ty, BolideType::Str) {
             if matches!(op, BinOp::Add) {
-                let func_ref = *self.func_refs.get("string_concat")
-                    .ok_or("string_concat not found")?;
+                let func_ref = self.get_func_ref("string_concat")?;
                 let call = self.builder.ins().call(func_ref, &[lhs, rhs]);
                 let result = self.builder.inst_results(call)[0];
                 self.track_temp_rc_value(result, &BolideType::Str);
                 return Ok(result);
             } else if matches!(op, BinOp::Eq) {
-                let func_ref = *self.func_refs.get("string_eq")
-                    .ok_or("string_eq not found")?;
+                let func_ref = self.get_func_ref("string_eq")?;
                 let call = self.builder.ins().call(func_ref, &[lhs, rhs]);
                 return Ok(self.builder.inst_results(call)[0]);
             } else if matches!(op, BinOp::Ne) {
-                let func_ref = *self.func_refs.get("string_eq")
-                    .ok_or("string_eq not found")?;
+                let func_ref = self.get_func_ref("string_eq")?;
                 let call = self.builder.ins().call(func_ref, &[lhs, rhs]);
                 let eq_result = self.builder.inst_results(call)[0];
                 let one = self.builder.ins().iconst(types::I64, 1);
                 return Ok(self.builder.ins().isub(one, eq_result));
+            } else if let Some(cc) = Self::ordering_cc(op) {
+                // 字典序比较: string_cmp 返回 -1 / 0 / 1
+                let func_ref = self.get_func_ref("string_cmp")?;
+                let call = self.builder.ins().call(func_ref, &[lhs, rhs]);
+                let ord = self.builder.inst_results(call)[0];
+                let cmp = self.builder.ins().icmp_imm(cc, ord, 0);
+                return Ok(self.builder.ins().uextend(types::I64, cmp));
             } else {
                 return Err(format!("Unsupported string operation: {:?}", op));
             }
@@ -4568,6 +7473,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 BinOp::And | BinOp::Or => {
                     return Err("Logical operations not supported for float".to_string());
                 }
+                BinOp::Is | BinOp::IsNot => unreachable!("identity comparison is compiled before dispatching on operand types"),
             }
         } else {
             // Int 运算
@@ -4605,12 +7511,85 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
                 BinOp::And => self.builder.ins().band(lhs, rhs),
                 BinOp::Or => self.builder.ins().bor(lhs, rhs),
+                BinOp::Is | BinOp::IsNot => unreachable!("identity comparison is compiled before dispatching on operand types"),
             }
         };
 
         Ok(result)
     }
 
+    /// 排序比较运算符对应的有符号整数条件码
+    fn ordering_cc(op: &BinOp) -> Option<IntCC> {
+        match op {
+            BinOp::Lt => Some(IntCC::SignedLessThan),
+            BinOp::Le => Some(IntCC::SignedLessThanOrEqual),
+            BinOp::Gt => Some(IntCC::SignedGreaterThan),
+            BinOp::Ge => Some(IntCC::SignedGreaterThanOrEqual),
+            _ => None,
+        }
+    }
+
+    /// 编译链式比较 a < b < c
+    ///
+    /// 与 `a < b and b < c` 相同，但每个操作数只求值一次；某一段为假时直接得到 false，
+    /// 后面的操作数不再求值。除第一个外的操作数可能在条件块中求值，
+    /// 它们产生的临时值在最后一次使用后立即释放，不留到语句结束。
+    fn compile_compare_chain(&mut self, first: &Expr, rest: &[(BinOp, Expr)]) -> Result<Value, String> {
+        let merge_block = self.builder.create_block();
+        self.builder.append_block_param(merge_block, types::I64);
+
+        let mut left_ty = self.infer_expr_type(first);
+        if let BolideType::Custom(name) = &left_ty {
+            return Err(format!("Chained comparison is not supported for class `{}`; join the comparisons with `and`", name));
+        }
+        let mut left = self.compile_expr(first)?;
+        // 左操作数产生的临时值在 temp_rc_values 中的起始位置（第一个操作数的留到语句结束）
+        let mut left_temps: Option<usize> = None;
+
+        for (i, (op, right_expr)) in rest.iter().enumerate() {
+            let right_ty = self.infer_expr_type(right_expr);
+            if let BolideType::Custom(name) = &right_ty {
+                return Err(format!("Chained comparison is not supported for class `{}`; join the comparisons with `and`", name));
+            }
+            let mark = self.temp_rc_values.len();
+            let right = self.compile_expr(right_expr)?;
+            let cmp = self.compile_binop_values(left, &left_ty, op, right, &right_ty)?;
+
+            // 左操作数已用完
+            let right_temps = match left_temps {
+                Some(start) => {
+                    let done: Vec<_> = self.temp_rc_values.drain(start..mark).collect();
+                    for (val, ty) in done {
+                        self.emit_release(val, &ty);
+                    }
+                    start
+                }
+                None => mark,
+            };
+
+            if i + 1 == rest.len() {
+                let done: Vec<_> = self.temp_rc_values.drain(right_temps..).collect();
+                for (val, ty) in done {
+                    self.emit_release(val, &ty);
+                }
+                self.builder.ins().jump(merge_block, &[cmp]);
+            } else {
+                let next_block = self.builder.create_block();
+                self.builder.ins().brif(cmp, next_block, &[], merge_block, &[cmp]);
+                self.builder.switch_to_block(next_block);
+                self.builder.seal_block(next_block);
+            }
+
+            left = right;
+            left_ty = right_ty;
+            left_temps = Some(right_temps);
+        }
+
+        self.builder.switch_to_block(merge_block);
+        self.builder.seal_block(merge_block);
+        Ok(self.builder.block_params(merge_block)[0])
+    }
+
     /// 编译 BigInt 二元操作
     fn compile_bigint_binop(&mut self, lhs: Value, op: &BinOp, rhs: Value) -> Result<Value, String> {
         // 算术运算返回新的 BigInt，需要跟踪为临时值
@@ -4625,8 +7604,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             BinOp::Eq => "bigint_eq",
             BinOp::Ne => {
                 // ne = !eq
-                let eq_ref = *self.func_refs.get("bigint_eq")
-                    .ok_or("bigint_eq not found")?;
+                let eq_ref = self.get_func_ref("bigint_eq")?;
                 let call = self.builder.ins().call(eq_ref, &[lhs, rhs]);
                 let eq_result = self.builder.inst_results(call)[0];
                 let one = self.builder.ins().iconst(types::I64, 1);
@@ -4639,10 +7617,10 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             BinOp::And | BinOp::Or => {
                 return Err("Logical operations not supported for BigInt".to_string());
             }
+            BinOp::Is | BinOp::IsNot => unreachable!("identity comparison is compiled before dispatching on operand types"),
         };
 
-        let func_ref = *self.func_refs.get(func_name)
-            .ok_or_else(|| format!("{} not found", func_name))?;
+        let func_ref = self.get_func_ref(func_name)?;
         let call = self.builder.ins().call(func_ref, &[lhs, rhs]);
         let result = self.builder.inst_results(call)[0];
 
@@ -4668,8 +7646,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             BinOp::Eq => "decimal_eq",
             BinOp::Ne => {
                 // ne = !eq
-                let eq_ref = *self.func_refs.get("decimal_eq")
-                    .ok_or("decimal_eq not found")?;
+                let eq_ref = self.get_func_ref("decimal_eq")?;
                 let call = self.builder.ins().call(eq_ref, &[lhs, rhs]);
                 let eq_result = self.builder.inst_results(call)[0];
                 let one = self.builder.ins().iconst(types::I64, 1);
@@ -4682,10 +7659,10 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             BinOp::And | BinOp::Or => {
                 return Err("Logical operations not supported for Decimal".to_string());
             }
+            BinOp::Is | BinOp::IsNot => unreachable!("identity comparison is compiled before dispatching on operand types"),
         };
 
-        let func_ref = *self.func_refs.get(func_name)
-            .ok_or_else(|| format!("{} not found", func_name))?;
+        let func_ref = self.get_func_ref(func_name)?;
         let call = self.builder.ins().call(func_ref, &[lhs, rhs]);
         let result = self.builder.inst_results(call)[0];
 
@@ -4707,6 +7684,12 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             UnaryOp::Neg => {
                 if is_float {
                     self.builder.ins().fneg(val)
+                } else if matches!(operand_ty, BolideType::Decimal) {
+                    let func_ref = self.get_func_ref("decimal_neg")?;
+                    let call = self.builder.ins().call(func_ref, &[val]);
+                    let result = self.builder.inst_results(call)[0];
+                    self.track_temp_rc_value(result, &BolideType::Decimal);
+                    result
                 } else {
                     self.builder.ins().ineg(val)
                 }
@@ -4728,10 +7711,8 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         args: &[Expr],
         func_sig: Option<(Vec<BolideType>, Option<Box<BolideType>>)>
     ) -> Result<Value, String> {
-        // 获取函数指针
-        let var = *self.variables.get(var_name)
-            .ok_or_else(|| format!("Undefined function variable: {}", var_name))?;
-        let func_ptr = self.builder.use_var(var);
+        // 获取函数指针（局部或全局变量）
+        let func_ptr = self.compile_ident(var_name)?;
 
         // 编译参数
         let mut arg_values = Vec::new();
@@ -4767,9 +7748,56 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             sig.returns.push(AbiParam::new(types::I64));
         }
 
+        let ret_cl_type = sig.returns[0].value_type;
         let sig_ref = self.builder.import_signature(sig);
+
+        // 最高位为 1 的是 bind 生成的函数值，需要经运行时分发
+        let tag = self.builder.ins().icmp_imm(IntCC::SignedLessThan, func_ptr, 0);
+        let direct_block = self.builder.create_block();
+        let bound_block = self.builder.create_block();
+        let merge_block = self.builder.create_block();
+        self.builder.append_block_param(merge_block, ret_cl_type);
+        self.builder.ins().brif(tag, bound_block, &[], direct_block, &[]);
+
+        // 普通函数指针：直接调用
+        self.builder.switch_to_block(direct_block);
+        self.builder.seal_block(direct_block);
         let call = self.builder.ins().call_indirect(sig_ref, func_ptr, &arg_values);
-        let result = self.builder.inst_results(call)[0];
+        let direct_result = self.builder.inst_results(call)[0];
+        self.builder.ins().jump(merge_block, &[direct_result]);
+
+        // 绑定函数：实参写入栈上数组，交给 closure_call
+        self.builder.switch_to_block(bound_block);
+        self.builder.seal_block(bound_block);
+        let slot = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            (arg_values.len().max(1) * 8) as u32,
+            0,
+        ));
+        let array_ptr = self.builder.ins().stack_addr(self.ptr_type, slot, 0);
+        for (i, val) in arg_values.iter().enumerate() {
+            let val = if self.builder.func.dfg.value_type(*val) == types::F64 {
+                self.builder.ins().bitcast(types::I64, MemFlags::new(), *val)
+            } else {
+                *val
+            };
+            self.builder.ins().store(MemFlags::new(), val, array_ptr, (i * 8) as i32);
+        }
+        let closure_call = self.get_func_ref("closure_call")?;
+        let argc = self.builder.ins().iconst(types::I64, arg_values.len() as i64);
+        let call = self.builder.ins().call(closure_call, &[func_ptr, array_ptr, argc]);
+        let bound_result = self.builder.inst_results(call)[0];
+        let bound_result = if ret_cl_type == types::F64 {
+            self.builder.ins().bitcast(types::F64, MemFlags::new(), bound_result)
+        } else {
+            bound_result
+        };
+        self.builder.ins().jump(merge_block, &[bound_result]);
+
+        self.builder.switch_to_block(merge_block);
+        self.builder.seal_block(merge_block);
+        let result = self.builder.block_params(merge_block)[0];
+        self.emit_interrupt_check_after_call()?;
 
         // 如果返回类型是 RC 类型，track 为临时值
         if let Some((_, Some(ret_type))) = &func_sig {
@@ -4787,8 +7815,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         if let Expr::Ident(name) = callee {
             if name == "print" && args.len() == 1 {
                 if self.infer_expr_type(&args[0]) == BolideType::Dynamic {
-                    let func = *self.func_refs.get("print_dynamic")
-                        .ok_or("print_dynamic not found")?;
+                    let func = self.get_func_ref("print_dynamic")?;
                     let val = self.compile_expr(&args[0])?;
                     self.builder.ins().call(func, &[val]);
                     return Ok(self.builder.ins().iconst(types::I64, 0));
@@ -4798,13 +7825,16 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
         // 检查是否是模块调用或方法调用 (obj.method(args))
         if let Expr::Member(base, member_name) = callee {
-            // 先检查是否是模块调用
-            if let Expr::Ident(module_name) = base.as_ref() {
-                if self.modules.contains_key(module_name) {
-                    // 模块调用: module.func() -> @module_func()
-                    let func_name = format!("@{}_{}", module_name, member_name);
-                    return self.compile_module_call(&func_name, args);
-                }
+            // 模块调用: module.f(...) 按 @module_f(...) 编译，函数、构造函数和函数类型的全局变量
+            // 与模块内部的直接调用走同一条路径（关键字实参、默认参数、所有权都一样）
+            if let Some(resolved) = modules::resolve_member(&self.modules, base, member_name) {
+                let name = match resolved? {
+                    Member::Global(name) if !matches!(self.global_var_types.get(&name), Some(BolideType::Func | BolideType::FuncSig(..))) => {
+                        return Err(format!("'{}' is not a function", modules::qualified_name(base, member_name)));
+                    }
+                    Member::Function(name) | Member::Class(name) | Member::Global(name) => name,
+                };
+                return self.compile_call(&Expr::Ident(name), args);
             }
             // 不是模块调用，是方法调用
             return self.compile_method_call(base, member_name, args);
@@ -4813,7 +7843,10 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         // 检查是否是间接调用（通过函数类型变量调用）
         if let Expr::Ident(name) = callee {
             // 检查是否是 func 类型的变量
-            if let Some(var_type) = self.var_types.get(name).cloned() {
+            let var_type = self.var_types.get(name)
+                .or_else(|| self.global_var_types.get(name))
+                .cloned();
+            if let Some(var_type) = var_type {
                 match &var_type {
                     BolideType::Func => return self.compile_indirect_call(name, args, None),
                     BolideType::FuncSig(param_types, ret_type) => {
@@ -4824,22 +7857,18 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             }
         }
 
-        let func_name = match callee {
-            Expr::Ident(name) => name.clone(),
-            Expr::Member(base, member) => {
-                // 检查是否是模块调用: module.func()
-                if let Expr::Ident(module_name) = base.as_ref() {
-                    if self.modules.contains_key(module_name) {
-                        // 转换为 @module_func
-                        format!("@{}_{}", module_name, member)
-                    } else {
-                        // 不是模块，是方法调用
-                        return self.compile_method_call(base, member, args);
-                    }
-                } else {
-                    return self.compile_method_call(base, member, args);
+        // 关键字实参和省略的默认参数：按参数顺序整理好再编译
+        // （sum/min/max/avg 只有一个列表实参时是内置聚合，同名的用户函数不参与）
+        if let Expr::Ident(name) = callee {
+            if let Some(params) = self.func_params.get(name).filter(|_| !self.is_list_aggregate_call(name, args)) {
+                if let Some(args) = call_args::positional_args(name, params, args)? {
+                    return self.compile_call(callee, &args);
                 }
             }
+        }
+
+        let func_name = match callee {
+            Expr::Ident(name) => name.clone(),
             _ => return Err("Only direct function calls are supported".to_string()),
         };
 
@@ -4847,6 +7876,8 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         match func_name.as_str() {
             "int" => return self.compile_type_conversion_to_int(args),
             "float" => return self.compile_type_conversion_to_float(args),
+            "parse_int" => return self.compile_parse_int(args),
+            "parse_float" => return self.compile_parse_float(args),
             "str" => return self.compile_type_conversion_to_str(args),
             "bigint" => return self.compile_type_conversion_to_bigint(args),
             "decimal" => return self.compile_type_conversion_to_decimal(args),
@@ -4858,6 +7889,22 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 }
                 return self.compile_print(&args[0]);
             }
+            // printr(x) - 打印不换行
+            "printr" => {
+                if args.len() != 1 {
+                    return Err("printr expects 1 argument".to_string());
+                }
+                return self.compile_print_inline(&args[0]);
+            }
+            // flush() - 把缓冲的输出写到标准输出
+            "flush" => {
+                if !args.is_empty() {
+                    return Err("flush expects no arguments".to_string());
+                }
+                let func_ref = self.get_func_ref("print_flush")?;
+                self.builder.ins().call(func_ref, &[]);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
             // join 函数 - 等待线程/任务完成
             "join" => {
                 if args.len() != 1 {
@@ -4865,28 +7912,484 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 }
                 return self.compile_join(&args[0]);
             }
+            // join_ok 函数 - 等待任务完成，任务的错误交给调用方处理
+            "join_ok" => {
+                if args.len() != 1 {
+                    return Err("join_ok expects 1 argument".to_string());
+                }
+                return self.compile_join_ok(&args[0]);
+            }
             // channel 函数 - 创建通道
             "channel" => {
                 return self.compile_channel_create(args);
             }
+            // packed_list(T, n) - n 个零值元素的 list<packed T>
+            "packed_list" => {
+                return self.compile_packed_list_new(args);
+            }
             // bigint_debug_stats - 调试用
             "bigint_debug_stats" => {
-                let func_ref = *self.func_refs.get("bigint_debug_stats")
-                    .ok_or("bigint_debug_stats not found")?;
+                let func_ref = self.get_func_ref("bigint_debug_stats")?;
                 self.builder.ins().call(func_ref, &[]);
                 return Ok(self.builder.ins().iconst(types::I64, 0));
             }
             // tuple_debug_stats - 调试用
             "tuple_debug_stats" => {
-                let func_ref = *self.func_refs.get("tuple_debug_stats")
-                    .ok_or("tuple_debug_stats not found")?;
+                let func_ref = self.get_func_ref("tuple_debug_stats")?;
+                self.builder.ins().call(func_ref, &[]);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+            // string_debug_stats / weak_debug_stats - 调试用
+            "string_debug_stats" | "weak_debug_stats" => {
+                let func_ref = self.get_func_ref(func_name.as_str())?;
                 self.builder.ins().call(func_ref, &[]);
                 return Ok(self.builder.ins().iconst(types::I64, 0));
             }
+            // object_debug_enable(on) -> bool：开关当前线程的存活对象登记，返回之前的状态
+            "object_debug_enable" => {
+                if args.len() != 1 {
+                    return Err("object_debug_enable expects 1 argument".to_string());
+                }
+                let ty = self.infer_expr_type(&args[0]);
+                if !matches!(ty, BolideType::Bool | BolideType::Int) {
+                    return Err(format!("object_debug_enable expects a bool argument, got {:?}", ty));
+                }
+                let on = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("object_debug_enable")?;
+                let call = self.builder.ins().call(func_ref, &[on]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            // object_live_count() -> int / object_dump_live()：登记的存活对象
+            "object_live_count" | "object_dump_live" => {
+                if !args.is_empty() {
+                    return Err(format!("{} expects no arguments", func_name));
+                }
+                let func_ref = self.get_func_ref(func_name.as_str())?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                return Ok(match self.builder.inst_results(call).first() {
+                    Some(&count) => count,
+                    None => self.builder.ins().iconst(types::I64, 0),
+                });
+            }
             // input 函数 - 读取用户输入
             "input" => {
                 return self.compile_input(args);
             }
+            // read_file(path) - 读取整个文本文件
+            "read_file" => {
+                if args.len() != 1 {
+                    return Err("read_file expects 1 argument".to_string());
+                }
+                let ty = self.infer_expr_type(&args[0]);
+                if ty != BolideType::Str {
+                    return Err(format!("read_file expects a str argument, got {:?}", ty));
+                }
+                let path = self.compile_expr(&args[0])?;
+                let result = self.compile_io_read("file_read", "file_read_async", path)?;
+                self.track_temp_rc_value(result, &BolideType::Str);
+                return Ok(result);
+            }
+            // write_lines(path, lines) - 每个字符串一行写入文件（覆盖已有内容）
+            "write_lines" => {
+                if args.len() != 2 {
+                    return Err("write_lines expects 2 arguments".to_string());
+                }
+                let path_ty = self.infer_expr_type(&args[0]);
+                if path_ty != BolideType::Str {
+                    return Err(format!("write_lines expects a str path, got {:?}", path_ty));
+                }
+                let lines_ty = self.infer_expr_type(&args[1]);
+                if !matches!(&lines_ty, BolideType::List(elem) if **elem == BolideType::Str) {
+                    return Err(format!("write_lines expects a list<str>, got {:?}", lines_ty));
+                }
+                let path = self.compile_expr(&args[0])?;
+                let lines = self.compile_expr(&args[1])?;
+                let func_ref = self.get_func_ref("file_write_lines")?;
+                self.builder.ins().call(func_ref, &[path, lines]);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+            // write_file(path, contents) / append_file(path, contents) -> bool：覆盖或追加写入
+            "write_file" | "append_file" => {
+                if args.len() != 2 {
+                    return Err(format!("{} expects 2 arguments", func_name));
+                }
+                for (arg, what) in args.iter().zip(["path", "contents"]) {
+                    let ty = self.infer_expr_type(arg);
+                    if ty != BolideType::Str {
+                        return Err(format!("{} expects a str {}, got {:?}", func_name, what, ty));
+                    }
+                }
+                let path = self.compile_expr(&args[0])?;
+                let contents = self.compile_expr(&args[1])?;
+                let runtime = if func_name == "write_file" { "file_write" } else { "file_append" };
+                let func_ref = self.get_func_ref(runtime)?;
+                let call = self.builder.ins().call(func_ref, &[path, contents]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            // file_exists(path) -> bool / read_lines(path) -> list<str> / remove_file(path) -> bool
+            "file_exists" | "read_lines" | "remove_file" => {
+                if args.len() != 1 {
+                    return Err(format!("{} expects 1 argument", func_name));
+                }
+                let ty = self.infer_expr_type(&args[0]);
+                if ty != BolideType::Str {
+                    return Err(format!("{} expects a str argument, got {:?}", func_name, ty));
+                }
+                let path = self.compile_expr(&args[0])?;
+                let runtime = match func_name.as_str() {
+                    "file_exists" => "file_exists",
+                    "remove_file" => "file_remove",
+                    _ => "file_lines",
+                };
+                let func_ref = self.get_func_ref(runtime)?;
+                let call = self.builder.ins().call(func_ref, &[path]);
+                let result = self.builder.inst_results(call)[0];
+                if func_name == "read_lines" {
+                    self.track_temp_rc_value(result, &BolideType::List(Box::new(BolideType::Str)));
+                }
+                return Ok(result);
+            }
+            // format_row(fields, sep = ",") -> str：把列表连接成 CSV/TSV 的一行
+            "format_row" => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err("format_row expects 1 or 2 arguments".to_string());
+                }
+                let fields_ty = self.infer_expr_type(&args[0]);
+                let is_row = matches!(&fields_ty, BolideType::List(elem) if matches!(**elem,
+                    BolideType::Int | BolideType::Float | BolideType::Bool | BolideType::Str
+                    | BolideType::BigInt | BolideType::Decimal | BolideType::Dynamic));
+                if !is_row {
+                    return Err(format!("format_row expects a list of int, float, bool, str, bigint, decimal or dynamic, got {:?}", fields_ty));
+                }
+                if let Some(sep) = args.get(1) {
+                    let sep_ty = self.infer_expr_type(sep);
+                    if sep_ty != BolideType::Str {
+                        return Err(format!("format_row expects a str separator, got {:?}", sep_ty));
+                    }
+                }
+                let fields = self.compile_expr(&args[0])?;
+                let sep = match args.get(1) {
+                    Some(sep) => self.compile_expr(sep)?,
+                    None => self.builder.ins().iconst(self.ptr_type, 0),
+                };
+                let func_ref = self.get_func_ref("format_row")?;
+                let call = self.builder.ins().call(func_ref, &[fields, sep]);
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::Str);
+                return Ok(result);
+            }
+            // assert(cond, message = "") - 断言失败时记录（`bolide test` 汇总），不中止
+            "assert" => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err("assert expects 1 or 2 arguments".to_string());
+                }
+                let cond_ty = self.infer_expr_type(&args[0]);
+                if !matches!(cond_ty, BolideType::Bool | BolideType::Int) {
+                    return Err(format!("assert expects a bool condition, got {:?}", cond_ty));
+                }
+                if let Some(message) = args.get(1) {
+                    let message_ty = self.infer_expr_type(message);
+                    if message_ty != BolideType::Str {
+                        return Err(format!("assert expects a str message, got {:?}", message_ty));
+                    }
+                }
+                let cond = self.compile_expr(&args[0])?;
+                let message = match args.get(1) {
+                    Some(message) => self.compile_expr(message)?,
+                    None => self.builder.ins().iconst(self.ptr_type, 0),
+                };
+                let func_ref = self.get_func_ref("assert")?;
+                self.builder.ins().call(func_ref, &[cond, message]);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+            // assert_eq(a, b) - int/bool 按值，float 允许相对误差，str 和列表按内容
+            "assert_eq" => {
+                if args.len() != 2 {
+                    return Err("assert_eq expects 2 arguments".to_string());
+                }
+                let left_ty = self.infer_expr_type(&args[0]);
+                let right_ty = self.infer_expr_type(&args[1]);
+                let func_name = match (&left_ty, &right_ty) {
+                    (BolideType::Int, BolideType::Int) | (BolideType::Bool, BolideType::Bool) => "assert_eq_int",
+                    (BolideType::Float, BolideType::Float | BolideType::Int) | (BolideType::Int, BolideType::Float) => "assert_eq_float",
+                    (BolideType::Str, BolideType::Str) => "assert_eq_str",
+                    (BolideType::List(a), BolideType::List(b)) if a == b => "assert_eq_list",
+                    _ => return Err(format!(
+                        "assert_eq compares two int, bool, float, str or list values of the same type, got {:?} and {:?}",
+                        left_ty, right_ty
+                    )),
+                };
+                let mut values = Vec::with_capacity(2);
+                for arg in args {
+                    let val = self.compile_expr(arg)?;
+                    let is_int = self.builder.func.dfg.value_type(val) != types::F64;
+                    values.push(if func_name == "assert_eq_float" && is_int {
+                        self.builder.ins().fcvt_from_sint(types::F64, val)
+                    } else {
+                        val
+                    });
+                }
+                let func_ref = self.get_func_ref(func_name)?;
+                self.builder.ins().call(func_ref, &values);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+            // set_io_workers(n) - async 函数里 input() / read_file() 使用的 I/O 线程数上限
+            "set_io_workers" => {
+                if args.len() != 1 {
+                    return Err("set_io_workers expects 1 argument".to_string());
+                }
+                let n = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("set_io_workers")?;
+                self.builder.ins().call(func_ref, &[n]);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+            // set_coroutine_workers(n) - 同时运行的协程数上限（协程工作线程数）
+            "set_coroutine_workers" => {
+                if args.len() != 1 {
+                    return Err("set_coroutine_workers expects 1 argument".to_string());
+                }
+                let n = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("coroutine_set_workers")?;
+                self.builder.ins().call(func_ref, &[n]);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+            // 计时器: timer_start() -> int, timer_elapsed_ns(t) -> int, timer_elapsed_ms(t) -> float
+            "timer_start" => {
+                if !args.is_empty() {
+                    return Err("timer_start expects no arguments".to_string());
+                }
+                let func_ref = self.get_func_ref("timer_start")?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            // thread_id() -> int：当前 OS 线程的编号
+            // error() -> int / error_message() -> str / clear_error()：当前线程最近一次可恢复的运行时错误
+            "error" | "error_message" | "clear_error" => {
+                if !args.is_empty() {
+                    return Err(format!("{} expects no arguments", func_name));
+                }
+                let runtime_name = match func_name.as_str() {
+                    "error" => "error_code",
+                    "error_message" => "error_message",
+                    _ => "error_clear",
+                };
+                let func_ref = self.get_func_ref(runtime_name)?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                return match func_name.as_str() {
+                    "error" => Ok(self.builder.inst_results(call)[0]),
+                    "error_message" => {
+                        let result = self.builder.inst_results(call)[0];
+                        self.track_temp_rc_value(result, &BolideType::Str);
+                        Ok(result)
+                    }
+                    _ => Ok(self.builder.ins().iconst(types::I64, 0)),
+                };
+            }
+            // exit(code)：刷新标准输出后立即结束进程
+            "exit" => {
+                if args.len() != 1 {
+                    return Err("exit expects 1 argument".to_string());
+                }
+                let code_ty = self.infer_expr_type(&args[0]);
+                if code_ty != BolideType::Int {
+                    return Err(format!("exit expects an int status code, got {:?}", code_ty));
+                }
+                let code = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("exit")?;
+                self.builder.ins().call(func_ref, &[code]);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+            "thread_id" => {
+                if !args.is_empty() {
+                    return Err("thread_id expects no arguments".to_string());
+                }
+                let func_ref = self.get_func_ref("thread_id")?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            // cancelled()：当前协程是否已被取消（不在协程里时为 false）
+            "cancelled" => {
+                if !args.is_empty() {
+                    return Err("cancelled expects no arguments".to_string());
+                }
+                let func_ref = self.get_func_ref("coroutine_cancelled")?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            // loop_count() -> int：最内层 while/for 已完成的迭代次数（第一次迭代中为 0）
+            "loop_count" => {
+                if !args.is_empty() {
+                    return Err("loop_count expects no arguments".to_string());
+                }
+                let Some(frame) = self.loops.last_mut() else {
+                    return Err("loop_count() can only be used inside a while or for loop".to_string());
+                };
+                frame.counter_used = true;
+                let var = frame.counter;
+                return Ok(self.builder.use_var(var));
+            }
+            // platform() -> str：运行程序的操作系统
+            "platform" | "temp_dir" => {
+                if !args.is_empty() {
+                    return Err(format!("{} expects no arguments", func_name));
+                }
+                let func_ref = self.get_func_ref(&func_name)?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::Str);
+                return Ok(result);
+            }
+            // version() -> str：运行时的语义化版本
+            "version" => {
+                if !args.is_empty() {
+                    return Err("version expects no arguments".to_string());
+                }
+                let func_ref = self.get_func_ref("version")?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::Str);
+                return Ok(result);
+            }
+            // version_at_least(v) -> bool / has_feature(name) -> bool
+            "version_at_least" | "has_feature" => {
+                if args.len() != 1 {
+                    return Err(format!("{} expects 1 argument", func_name));
+                }
+                let ty = self.infer_expr_type(&args[0]);
+                if ty != BolideType::Str {
+                    return Err(format!("{} expects a str argument, got {:?}", func_name, ty));
+                }
+                let val = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref(func_name.as_str())?;
+                let call = self.builder.ins().call(func_ref, &[val]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            // deadline(d)：从现在起 d 之后的时间点；remaining(dl)：距离时间点还剩多久（不小于 0）
+            "deadline" | "remaining" => {
+                let expected = if func_name == "deadline" { BolideType::Duration } else { BolideType::Deadline };
+                if args.len() != 1 || self.infer_expr_type(&args[0]) != expected {
+                    return Err(format!("{} expects 1 {} argument", func_name, analysis::type_name(&expected)));
+                }
+                let val = self.compile_expr(&args[0])?;
+                let runtime = if func_name == "deadline" { "deadline" } else { "deadline_remaining" };
+                let func_ref = self.get_func_ref(runtime)?;
+                let call = self.builder.ins().call(func_ref, &[val]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            "timer_elapsed_ns" | "timer_elapsed_ms" => {
+                if args.len() != 1 {
+                    return Err(format!("{} expects 1 argument", func_name));
+                }
+                let start = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref(func_name.as_str())?;
+                let call = self.builder.ins().call(func_ref, &[start]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            // sum(xs) / min(xs) / max(xs) / avg(xs)：参数为列表时按元素类型调用运行时聚合
+            name if self.is_list_aggregate_call(name, args) => {
+                return self.compile_list_aggregate(&func_name, &args[0]);
+            }
+            // saturating_* / wrapping_* / checked_*：显式指定溢出行为的整数运算（见 overflow 模块）
+            name if overflow::lookup(name).is_some() => {
+                let (op, value, overflowed) = self.compile_int_intrinsic(name, args)?;
+                return match overflowed {
+                    Some(overflowed) if op.mode == overflow::Mode::Checked => self.emit_checked_tuple(value, overflowed),
+                    _ => Ok(value),
+                };
+            }
+            // len(x) -> int：字符串的字符数，列表/字典/元组的元素数
+            "len" => {
+                if args.len() != 1 {
+                    return Err("len expects 1 argument".to_string());
+                }
+                let len_func = match self.infer_expr_type(&args[0]) {
+                    BolideType::Str => "string_len",
+                    BolideType::List(_) => "list_len",
+                    BolideType::Dict(_, _) => "dict_len",
+                    BolideType::WeakDict(_, _) => "weakdict_len",
+                    BolideType::Tuple(_) => "tuple_len",
+                    BolideType::PackedList(_) => "packed_list_len",
+                    other => return Err(format!("len() is not supported for {:?}", other)),
+                };
+                let val = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref(len_func)?;
+                let call = self.builder.ins().call(func_ref, &[val]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            // default_dict()：类型取自声明，见 compile_expr_expecting
+            "default_dict" => {
+                return Err("default_dict() needs a declared dict type, e.g. let d: dict<int, list<int>> = default_dict()".to_string());
+            }
+            // weak_dict()：同样取声明的类型
+            "weak_dict" => {
+                return Err("weak_dict() needs a declared type, e.g. let cache: weak_dict<Node, int> = weak_dict()".to_string());
+            }
+            // hash(x) -> int
+            "hash" => {
+                if args.len() != 1 {
+                    return Err("hash expects 1 argument".to_string());
+                }
+                let ty = self.infer_expr_type(&args[0]);
+                let val = self.compile_expr(&args[0])?;
+                return self.emit_hash(val, &ty);
+            }
+            // intern(s) -> str：驻留实例
+            "intern" => {
+                if args.len() != 1 {
+                    return Err("intern expects 1 argument".to_string());
+                }
+                let ty = self.infer_expr_type(&args[0]);
+                if ty != BolideType::Str {
+                    return Err(format!("intern expects a str argument, got {:?}", ty));
+                }
+                let val = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("string_intern")?;
+                let call = self.builder.ins().call(func_ref, &[val]);
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::Str);
+                return Ok(result);
+            }
+            // intern_stats() -> int：驻留表的条目数；intern_clear() -> int：清理只被表引用的条目
+            "intern_stats" | "intern_clear" => {
+                if !args.is_empty() {
+                    return Err(format!("{} expects no arguments", func_name));
+                }
+                let func_ref = self.get_func_ref(&format!("string_{}", func_name))?;
+                let call = self.builder.ins().call(func_ref, &[]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            // bind 函数 - 偏函数应用
+            "bind" => {
+                return self.compile_bind(args);
+            }
+            // map(f, xs) / filter(f, xs) / reduce(f, xs, init)；同名的用户函数优先
+            "map" | "filter" | "reduce" if !self.func_params.contains_key(&func_name) => {
+                return self.compile_higher_order(&func_name, args);
+            }
+            // set_rounding(mode) - 设置当前线程的 decimal 舍入模式，返回之前的模式
+            "set_rounding" => {
+                if args.len() != 1 {
+                    return Err("set_rounding expects 1 argument".to_string());
+                }
+                let mode = self.compile_rounding_mode(&args[0])?;
+                let func_ref = self.get_func_ref("decimal_set_rounding")?;
+                let call = self.builder.ins().call(func_ref, &[mode]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            // set_div_precision(digits) - 设置当前线程 decimal 除法保留的小数位数（负数恢复默认），返回之前的设置
+            "set_div_precision" => {
+                if args.len() != 1 {
+                    return Err("set_div_precision expects 1 argument".to_string());
+                }
+                let digits = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("decimal_set_div_precision")?;
+                let call = self.builder.ins().call(func_ref, &[digits]);
+                return Ok(self.builder.inst_results(call)[0]);
+            }
+            // round(x, digits) / floor(x) / ceil(x)；同名的用户函数优先
+            "round" | "floor" | "ceil" if !self.func_params.contains_key(&func_name) => {
+                return self.compile_rounding_call(&func_name, args);
+            }
             _ => {}
 
         }
@@ -4898,16 +8401,34 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
         // 检查是否是 extern 函数
         if let Some((lib_path, extern_func)) = self.extern_funcs.get(&func_name).cloned() {
+            self.call_edges.push((CallKind::Extern, func_name.clone()));
             return self.compile_extern_call(&lib_path, &extern_func, args);
         }
 
-        let func_ref = *self.func_refs.get(&func_name)
-            .ok_or_else(|| format!("Undefined function: {}", func_name))?;
+        if let Some(value) = self.compile_pure_call(&func_name, args)? {
+            return Ok(value);
+        }
+
+        let func_ref = self.get_func_ref(&func_name).map_err(|_| format!("Undefined function: {}", func_name))?;
+        self.check_packed_call_args(args, &func_name)?;
 
         // 获取函数参数信息
         let param_modes: Vec<ParamMode> = self.func_params.get(&func_name)
             .map(|params| params.iter().map(|p| p.mode).collect())
             .unwrap_or_else(|| vec![ParamMode::Borrow; args.len()]);
+        let param_types: Vec<BolideType> = self.func_params.get(&func_name)
+            .map(|params| params.iter().map(|p| p.ty.clone()).collect())
+            .unwrap_or_default();
+
+        // 构造函数的参数直接存进对象字段
+        let is_constructor = self.classes.contains_key(&func_name);
+        if is_constructor {
+            if let Some(params) = self.func_params.get(&func_name) {
+                for (arg, param) in args.iter().zip(params) {
+                    self.check_borrow_escape(arg, &format!("stored in field '{}' of '{}'", param.name, func_name))?;
+                }
+            }
+        }
 
         let mut arg_values = Vec::new();
         for (i, arg) in args.iter().enumerate() {
@@ -4915,16 +8436,33 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
             match mode {
                 ParamMode::Borrow => {
-                    // 直接传值
-                    arg_values.push(self.compile_expr(arg)?);
+                    // 直接传值；构造函数的实参由字段接管（同字段赋值）
+                    let val = self.compile_expr_expecting(arg, param_types.get(i))?;
+                    let val = match param_types.get(i) {
+                        Some(ty) if is_constructor => self.take_field_value(val, ty)?,
+                        _ => val,
+                    };
+                    arg_values.push(val);
                 }
                 ParamMode::Owned => {
+                    let param_name = self.func_params.get(&func_name)
+                        .and_then(|params| params.get(i))
+                        .map(|p| p.name.clone())
+                        .unwrap_or_default();
+                    self.check_borrow_escape(arg, &format!("passed to owned parameter '{}' of '{}'", param_name, func_name))?;
+
                     // 传值，然后标记变量为已移动
-                    let val = self.compile_expr(arg)?;
+                    let val = self.compile_owned_expr(arg, param_types.get(i))?;
                     arg_values.push(val);
 
                     // 如果参数是变量，标记为已移动并置空
                     if let Expr::Ident(var_name) = arg {
+                        if self.ref_params.iter().any(|(name, _, _)| name == var_name) {
+                            return Err(format!(
+                                "ref parameter '{}' cannot be moved into owned parameter '{}' of '{}'; the caller's variable still owns it",
+                                var_name, param_name, func_name
+                            ));
+                        }
                         self.moved_variables.insert(var_name.clone());
                         // 置空变量（设为 null）
                         if let Some(&var) = self.variables.get(var_name) {
@@ -4939,72 +8477,54 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                     }
                 }
                 ParamMode::Ref => {
-                    // 传递变量的栈地址
-                    if let Expr::Ident(var_name) = arg {
-                        // 需要在栈上分配空间，存储变量值，然后传递地址
-                        let var = *self.variables.get(var_name)
-                            .ok_or_else(|| format!("Undefined variable for ref: {}", var_name))?;
+                    // 传递变量的地址，所有权约定见 ParamMode::Ref
+                    let Expr::Ident(var_name) = arg else {
+                        return Err("ref parameter must be a variable".to_string());
+                    };
+                    self.check_ref_argument(&func_name, i, var_name, args)?;
+                    if let Some(&var) = self.variables.get(var_name) {
+                        // 局部变量：存进栈槽，调用后读回
                         let current_val = self.builder.use_var(var);
-
-                        // 创建栈槽存储变量值
                         let slot = self.builder.create_sized_stack_slot(StackSlotData::new(
                             StackSlotKind::ExplicitSlot,
                             8,  // 指针大小
                             0,
                         ));
                         let slot_addr = self.builder.ins().stack_addr(self.ptr_type, slot, 0);
-
-                        // 将当前值存入栈槽
                         self.builder.ins().store(MemFlags::new(), current_val, slot_addr, 0);
-
                         arg_values.push(slot_addr);
-
-                        // 注意：函数返回后需要从栈槽读回新值
-                        // 这需要在 call 之后处理
+                    } else if let Some(&data_id) = self.global_data_ids.get(var_name) {
+                        // 全局变量：直接传数据段地址（threadlocal 变量传当前线程的单元）
+                        let addr = self.global_addr(var_name, data_id)?;
+                        arg_values.push(addr);
                     } else {
-                        return Err("ref parameter must be a variable".to_string());
+                        return Err(format!("Undefined variable for ref: {}", var_name));
                     }
                 }
             }
         }
 
-        let call = self.builder.ins().call(func_ref, &arg_values);
+        let call = self.emit_user_call(&func_name, func_ref, &arg_values);
 
         // 检查是否是生命周期函数
         let is_lifetime_func = self.lifetime_funcs.contains(&func_name);
 
-        // 处理 Ref 参数：从栈槽读回新值
-        // 对于生命周期函数，跳过释放旧值（因为返回值可能就是参数本身）
+        // Ref 参数：从栈槽读回当前值；被替换的旧值已由被调用者释放
         for (i, arg) in args.iter().enumerate() {
-            let mode = param_modes.get(i).copied().unwrap_or(ParamMode::Borrow);
-            if mode == ParamMode::Ref {
-                if let Expr::Ident(var_name) = arg {
-                    // arg_values[i] 是栈槽地址，从中读取新值
-                    let slot_addr = arg_values[i];
-                    let new_val = self.builder.ins().load(self.ptr_type, MemFlags::new(), slot_addr, 0);
-
-                    if let Some(&var) = self.variables.get(var_name) {
-                        // 释放旧值（调用者原本拥有的对象）
-                        // 但对于生命周期函数，跳过释放（返回值可能就是参数本身）
-                        if !is_lifetime_func {
-                            if let Some(var_ty) = self.var_types.get(var_name).cloned() {
-                                if Self::is_rc_type(&var_ty) {
-                                    if let Some(func_name) = Self::get_release_func_name(&var_ty) {
-                                        if let Some(&func_ref) = self.func_refs.get(func_name) {
-                                            let old_val = self.builder.use_var(var);
-                                            self.builder.ins().call(func_ref, &[old_val]);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        // 更新为新值
-                        self.builder.def_var(var, new_val);
-                    }
+            if param_modes.get(i) != Some(&ParamMode::Ref) {
+                continue;
+            }
+            if let Expr::Ident(var_name) = arg {
+                if let Some(&var) = self.variables.get(var_name) {
+                    let new_val = self.builder.ins().load(self.ptr_type, MemFlags::new(), arg_values[i], 0);
+                    self.builder.def_var(var, new_val);
+                    self.store_through_ref_param(var_name, new_val);
                 }
             }
         }
 
+        self.emit_interrupt_check_after_call()?;
+
         let results = self.builder.inst_results(call);
         if results.is_empty() {
             Ok(self.builder.ins().iconst(types::I64, 0))
@@ -5041,22 +8561,20 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             }
             BolideType::Str => {
                 // str -> int: 调用 string_to_int
-                let func_ref = *self.func_refs.get("string_to_int")
-                    .ok_or("string_to_int not found")?;
+                self.warn("int() on a str is deprecated; use parse_int(s), which also rejects out-of-range input");
+                let func_ref = self.get_func_ref("string_to_int")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             BolideType::BigInt => {
                 // bigint -> int: 调用 bigint_to_i64
-                let func_ref = *self.func_refs.get("bigint_to_i64")
-                    .ok_or("bigint_to_i64 not found")?;
+                let func_ref = self.get_func_ref("bigint_to_i64")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             BolideType::Decimal => {
                 // decimal -> int: 调用 decimal_to_i64
-                let func_ref = *self.func_refs.get("decimal_to_i64")
-                    .ok_or("decimal_to_i64 not found")?;
+                let func_ref = self.get_func_ref("decimal_to_i64")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
@@ -5080,15 +8598,14 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             }
             BolideType::Str => {
                 // str -> float: 调用 string_to_float
-                let func_ref = *self.func_refs.get("string_to_float")
-                    .ok_or("string_to_float not found")?;
+                self.warn("float() on a str is deprecated; use parse_float(s), which also rejects out-of-range input");
+                let func_ref = self.get_func_ref("string_to_float")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
             BolideType::Decimal => {
                 // decimal -> float: 调用 decimal_to_f64
-                let func_ref = *self.func_refs.get("decimal_to_f64")
-                    .ok_or("decimal_to_f64 not found")?;
+                let func_ref = self.get_func_ref("decimal_to_f64")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 Ok(self.builder.inst_results(call)[0])
             }
@@ -5096,43 +8613,93 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         }
     }
 
-    /// 类型转换: str(x) - 支持 int, float, bool, str, bigint, decimal
+    /// `parse_int(s)` / `parse_int(s, radix)`：失败时记录错误（见 `error()`）并返回 0
+    fn compile_parse_int(&mut self, args: &[Expr]) -> Result<Value, String> {
+        if args.is_empty() || args.len() > 2 {
+            return Err(format!("parse_int() expects 1 or 2 arguments, got {}", args.len()));
+        }
+        let arg_type = self.infer_expr_type(&args[0]);
+        if arg_type != BolideType::Str {
+            return Err(format!("parse_int() expects a str, got {:?}", arg_type));
+        }
+        let text = self.compile_expr(&args[0])?;
+        let radix = match args.get(1) {
+            Some(arg) => {
+                let radix_type = self.infer_expr_type(arg);
+                if radix_type != BolideType::Int {
+                    return Err(format!("parse_int() radix must be an int, got {:?}", radix_type));
+                }
+                self.compile_expr(arg)?
+            }
+            None => self.builder.ins().iconst(types::I64, 10),
+        };
+        let func_ref = self.get_func_ref("string_parse_int")?;
+        let call = self.builder.ins().call(func_ref, &[text, radix]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// `parse_float(s)`：失败时记录错误（见 `error()`）并返回 0.0
+    fn compile_parse_float(&mut self, args: &[Expr]) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err(format!("parse_float() expects 1 argument, got {}", args.len()));
+        }
+        let arg_type = self.infer_expr_type(&args[0]);
+        if arg_type != BolideType::Str {
+            return Err(format!("parse_float() expects a str, got {:?}", arg_type));
+        }
+        let text = self.compile_expr(&args[0])?;
+        let func_ref = self.get_func_ref("string_parse_float")?;
+        let call = self.builder.ins().call(func_ref, &[text]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// 类型转换: str(x) - 支持 int, float, bool, str, bigint, decimal, duration
     fn compile_type_conversion_to_str(&mut self, args: &[Expr]) -> Result<Value, String> {
         if args.len() != 1 {
             return Err("str() expects 1 argument".to_string());
         }
         let arg_type = self.infer_expr_type(&args[0]);
+        if let BolideType::PackedRef(class) = &arg_type {
+            return self.compile_packed_to_string(&args[0], class);
+        }
         let val = self.compile_expr(&args[0])?;
 
+        if let Some(class_name) = Self::object_class(&arg_type) {
+            let class_name = class_name.to_string();
+            let result = self.compile_object_to_string(&class_name, val)?;
+            self.track_temp_rc_value(result, &BolideType::Str);
+            return Ok(result);
+        }
+
         let result = match arg_type {
             BolideType::Str => return Ok(val),  // 恒等转换
             BolideType::Int => {
-                let func_ref = *self.func_refs.get("string_from_int")
-                    .ok_or("string_from_int not found")?;
+                let func_ref = self.get_func_ref("string_from_int")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 self.builder.inst_results(call)[0]
             }
             BolideType::Float => {
-                let func_ref = *self.func_refs.get("string_from_float")
-                    .ok_or("string_from_float not found")?;
+                let func_ref = self.get_func_ref("string_from_float")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 self.builder.inst_results(call)[0]
             }
             BolideType::Bool => {
-                let func_ref = *self.func_refs.get("string_from_bool")
-                    .ok_or("string_from_bool not found")?;
+                let func_ref = self.get_func_ref("string_from_bool")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 self.builder.inst_results(call)[0]
             }
             BolideType::BigInt => {
-                let func_ref = *self.func_refs.get("string_from_bigint")
-                    .ok_or("string_from_bigint not found")?;
+                let func_ref = self.get_func_ref("string_from_bigint")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 self.builder.inst_results(call)[0]
             }
             BolideType::Decimal => {
-                let func_ref = *self.func_refs.get("string_from_decimal")
-                    .ok_or("string_from_decimal not found")?;
+                let func_ref = self.get_func_ref("string_from_decimal")?;
+                let call = self.builder.ins().call(func_ref, &[val]);
+                self.builder.inst_results(call)[0]
+            }
+            BolideType::Duration => {
+                let func_ref = self.get_func_ref("string_from_duration")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 self.builder.inst_results(call)[0]
             }
@@ -5144,6 +8711,106 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         Ok(result)
     }
 
+    /// f"..."：先求出全部片段，再按 `print` 的格式追加到拼接缓冲，只分配一次结果字符串
+    fn compile_fstring(&mut self, parts: &[Expr]) -> Result<Value, String> {
+        let mut pieces = Vec::with_capacity(parts.len());
+        let mut capacity = 0;
+        for part in parts {
+            if let Expr::String(text) = part {
+                capacity += text.len();
+            }
+            let ty = self.infer_expr_type(part);
+            // 类实例、packed 元素和 duration 先转成字符串
+            if Self::object_class(&ty).is_some() || matches!(ty, BolideType::PackedRef(_) | BolideType::Duration) {
+                let text = self.compile_type_conversion_to_str(std::slice::from_ref(part))?;
+                pieces.push((text, "string_builder_append_str"));
+                continue;
+            }
+            let append = match ty {
+                BolideType::Str => "string_builder_append_str",
+                BolideType::Int => "string_builder_append_int",
+                BolideType::Float => "string_builder_append_float",
+                BolideType::Bool => "string_builder_append_bool",
+                BolideType::BigInt => "string_builder_append_bigint",
+                BolideType::Decimal => "string_builder_append_decimal",
+                BolideType::Dynamic => "string_builder_append_dynamic",
+                BolideType::List(_) => "string_builder_append_list",
+                BolideType::Dict(_, _) => "string_builder_append_dict",
+                BolideType::Tuple(_) => "string_builder_append_tuple",
+                BolideType::PackedList(_) => "string_builder_append_packed_list",
+                other => return Err(format!("f-string cannot format a value of type {:?}", other)),
+            };
+            let val = self.compile_expr(part)?;
+            pieces.push((val, append));
+        }
+
+        let new_ref = self.get_func_ref("string_builder_new")?;
+        let capacity = self.builder.ins().iconst(types::I64, capacity as i64);
+        let call = self.builder.ins().call(new_ref, &[capacity]);
+        let string_builder = self.builder.inst_results(call)[0];
+        for (val, append) in pieces {
+            let append_ref = self.get_func_ref(append)?;
+            self.builder.ins().call(append_ref, &[string_builder, val]);
+        }
+        let finish_ref = self.get_func_ref("string_builder_finish")?;
+        let call = self.builder.ins().call(finish_ref, &[string_builder]);
+        let result = self.builder.inst_results(call)[0];
+        self.track_temp_rc_value(result, &BolideType::Str);
+        Ok(result)
+    }
+
+    /// 类实例（含 weak/unowned 引用）的类名
+    fn object_class(ty: &BolideType) -> Option<&str> {
+        match ty {
+            BolideType::Custom(name) => Some(name),
+            BolideType::Weak(inner) | BolideType::Unowned(inner) => match inner.as_ref() {
+                BolideType::Custom(name) => Some(name),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// 类实例的 `==` / `!=`（没有对应的运算符方法时）：`!=` 取 `__eq__` 的反，
+    /// 类没有 `__eq__` 时经 `object_equals` 调用合成的逐字段比较
+    fn compile_object_equals(&mut self, left: &Expr, op: &BinOp, right: &Expr, class_name: &str, right_ty: &BolideType) -> Result<Value, String> {
+        let result = if self.find_method(class_name, equality::USER_EQ).is_ok() {
+            self.compile_method_call(left, equality::USER_EQ, &[right.clone()])?
+        } else {
+            let class = match Self::object_class(right_ty) {
+                Some(right_class) => equality::common_class(class_name, right_class, |c| {
+                    self.classes.get(c).and_then(|info| info.parent.clone())
+                }).ok_or_else(|| format!("Cannot compare `{}` with `{}`: the classes are unrelated", class_name, right_class))?,
+                None if matches!(right, Expr::None) => class_name.to_string(),
+                None => return Err(format!("Cannot compare `{}` with {:?}", class_name, right_ty)),
+            };
+            let comparator = self.find_method(&class, equality::DEFAULT_EQ)?;
+            let comparator_ref = self.get_func_ref(&comparator)?;
+            let comparator_addr = self.builder.ins().func_addr(self.ptr_type, comparator_ref);
+            let lhs = self.compile_expr(left)?;
+            let rhs = self.compile_expr(right)?;
+            let func_ref = self.get_func_ref("object_equals")?;
+            let call = self.builder.ins().call(func_ref, &[lhs, rhs, comparator_addr]);
+            self.builder.inst_results(call)[0]
+        };
+        if matches!(op, BinOp::Ne) {
+            let one = self.builder.ins().iconst(types::I64, 1);
+            return Ok(self.builder.ins().isub(one, result));
+        }
+        Ok(result)
+    }
+
+    /// 类实例转字符串：优先用类（或父类）的 `__str__`，否则用合成的默认格式化
+    fn compile_object_to_string(&mut self, class_name: &str, obj: Value) -> Result<Value, String> {
+        let formatter = self.find_method(class_name, formatter::USER_STR)
+            .or_else(|_| self.find_method(class_name, formatter::DEFAULT_STR))?;
+        let formatter_ref = self.get_func_ref(&formatter)?;
+        let formatter_addr = self.builder.ins().func_addr(self.ptr_type, formatter_ref);
+        let func_ref = self.get_func_ref("object_to_string")?;
+        let call = self.builder.ins().call(func_ref, &[obj, formatter_addr]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
     /// 类型转换: bigint(x) - 支持 int
     fn compile_type_conversion_to_bigint(&mut self, args: &[Expr]) -> Result<Value, String> {
         if args.len() != 1 {
@@ -5155,8 +8822,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         match arg_type {
             BolideType::BigInt => Ok(val),  // 恒等转换
             BolideType::Int => {
-                let func_ref = *self.func_refs.get("bigint_from_i64")
-                    .ok_or("bigint_from_i64 not found")?;
+                let func_ref = self.get_func_ref("bigint_from_i64")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 let result = self.builder.inst_results(call)[0];
                 self.track_temp_rc_value(result, &BolideType::BigInt);
@@ -5166,7 +8832,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         }
     }
 
-    /// 类型转换: decimal(x) - 支持 int, float
+    /// 类型转换: decimal(x) - 支持 int, float, str
     fn compile_type_conversion_to_decimal(&mut self, args: &[Expr]) -> Result<Value, String> {
         if args.len() != 1 {
             return Err("decimal() expects 1 argument".to_string());
@@ -5177,16 +8843,21 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         match arg_type {
             BolideType::Decimal => Ok(val),  // 恒等转换
             BolideType::Int => {
-                let func_ref = *self.func_refs.get("decimal_from_i64")
-                    .ok_or("decimal_from_i64 not found")?;
+                let func_ref = self.get_func_ref("decimal_from_i64")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 let result = self.builder.inst_results(call)[0];
                 self.track_temp_rc_value(result, &BolideType::Decimal);
                 Ok(result)
             }
             BolideType::Float => {
-                let func_ref = *self.func_refs.get("decimal_from_f64")
-                    .ok_or("decimal_from_f64 not found")?;
+                let func_ref = self.get_func_ref("decimal_from_f64")?;
+                let call = self.builder.ins().call(func_ref, &[val]);
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::Decimal);
+                Ok(result)
+            }
+            BolideType::Str => {
+                let func_ref = self.get_func_ref("decimal_from_string")?;
                 let call = self.builder.ins().call(func_ref, &[val]);
                 let result = self.builder.inst_results(call)[0];
                 self.track_temp_rc_value(result, &BolideType::Decimal);
@@ -5199,6 +8870,15 @@ impl<'a, 'b> CompileContext<'a, 'b> {
     /// 编译通用 print 函数 - 根据表达式类型自动选择打印函数
     fn compile_print(&mut self, expr: &Expr) -> Result<Value, String> {
         let expr_type = self.infer_expr_type(expr);
+
+        // 类实例、packed 元素和 duration 先转成字符串
+        if Self::object_class(&expr_type).is_some() || matches!(expr_type, BolideType::PackedRef(_) | BolideType::Duration) {
+            let text = self.compile_type_conversion_to_str(std::slice::from_ref(expr))?;
+            let func_ref = self.get_func_ref("print_string")?;
+            self.builder.ins().call(func_ref, &[text]);
+            return Ok(self.builder.ins().iconst(types::I64, 0));
+        }
+
         let val = self.compile_expr(expr)?;
 
         let func_name = match expr_type {
@@ -5212,30 +8892,61 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             BolideType::Tuple(_) => "print_tuple",
             BolideType::List(_) => "print_list",
             BolideType::Dict(_, _) => "print_dict",
+            BolideType::PackedList(_) => "print_packed_list",
 
             _ => "print_int",  // 默认用 int 打印
         };
 
 
-        let func_ref = *self.func_refs.get(func_name)
-            .ok_or_else(|| format!("{} not found", func_name))?;
+        let func_ref = self.get_func_ref(func_name)?;
+        self.builder.ins().call(func_ref, &[val]);
+        Ok(self.builder.ins().iconst(types::I64, 0))
+    }
+
+    /// printr(x)：和 print 的格式相同，不加换行；容器和类实例先格式化成字符串
+    fn compile_print_inline(&mut self, expr: &Expr) -> Result<Value, String> {
+        let expr_type = self.infer_expr_type(expr);
+        let func_name = match expr_type {
+            BolideType::Int => "print_int_inline",
+            BolideType::Float => "print_float_inline",
+            BolideType::Bool => "print_bool_inline",
+            BolideType::BigInt => "print_bigint_inline",
+            BolideType::Decimal => "print_decimal_inline",
+            BolideType::Str => "print_string_inline",
+            BolideType::Dynamic => "print_dynamic_inline",
+            _ => {
+                let text = self.compile_fstring(std::slice::from_ref(expr))?;
+                let func_ref = self.get_func_ref("print_string_inline")?;
+                self.builder.ins().call(func_ref, &[text]);
+                return Ok(self.builder.ins().iconst(types::I64, 0));
+            }
+        };
+        let val = self.compile_expr(expr)?;
+        let func_ref = self.get_func_ref(func_name)?;
         self.builder.ins().call(func_ref, &[val]);
         Ok(self.builder.ins().iconst(types::I64, 0))
     }
 
     /// 编译 input 函数 - 读取用户输入
     fn compile_input(&mut self, args: &[Expr]) -> Result<Value, String> {
-        let result = if args.is_empty() {
+        let result = if self.async_funcs.contains(&self.current_func_name) {
+            if args.len() > 1 {
+                return Err("input expects 0 or 1 argument".to_string());
+            }
+            let prompt = match args.first() {
+                Some(prompt) => self.compile_expr(prompt)?,
+                None => self.builder.ins().iconst(self.ptr_type, 0),
+            };
+            self.compile_io_read("input_prompt", "input_async", prompt)?
+        } else if args.is_empty() {
             // 无参数版本: input()
-            let func_ref = *self.func_refs.get("input")
-                .ok_or("input not found")?;
+            let func_ref = self.get_func_ref("input")?;
             let call = self.builder.ins().call(func_ref, &[]);
             self.builder.inst_results(call)[0]
         } else if args.len() == 1 {
             // 带提示版本: input("prompt")
             let prompt = self.compile_expr(&args[0])?;
-            let func_ref = *self.func_refs.get("input_prompt")
-                .ok_or("input_prompt not found")?;
+            let func_ref = self.get_func_ref("input_prompt")?;
             let call = self.builder.ins().call(func_ref, &[prompt]);
             self.builder.inst_results(call)[0]
         } else {
@@ -5247,18 +8958,317 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         Ok(result)
     }
 
+    /// 阻塞读取：普通函数里直接调用 `blocking`；async 函数里调用 `nonblocking` 得到 future，
+    /// 再用 `io_await` 挂起等待，读取期间其他协程可以执行
+    fn compile_io_read(&mut self, blocking: &str, nonblocking: &str, arg: Value) -> Result<Value, String> {
+        let call = if self.async_funcs.contains(&self.current_func_name) {
+            let start = self.get_func_ref(nonblocking)?;
+            let call = self.builder.ins().call(start, &[arg]);
+            let future = self.builder.inst_results(call)[0];
+            let await_ref = self.get_func_ref("io_await")?;
+            self.builder.ins().call(await_ref, &[future])
+        } else {
+            let func_ref = self.get_func_ref(blocking)?;
+            self.builder.ins().call(func_ref, &[arg])
+        };
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// 列表聚合的运行时函数和结果类型：sum/min/max 返回元素类型，
+    /// avg 对 int/float/bigint 返回 float，对 decimal 返回 decimal
+    fn list_aggregate(func: &str, list_ty: &BolideType) -> Option<(&'static str, BolideType)> {
+        let BolideType::List(elem) = list_ty else {
+            return None;
+        };
+        let entry = match (func, elem.as_ref()) {
+            ("sum", BolideType::Int) => ("list_sum_int", BolideType::Int),
+            ("min", BolideType::Int) => ("list_min_int", BolideType::Int),
+            ("max", BolideType::Int) => ("list_max_int", BolideType::Int),
+            ("sum", BolideType::Float) => ("list_sum_float", BolideType::Float),
+            ("min", BolideType::Float) => ("list_min_float", BolideType::Float),
+            ("max", BolideType::Float) => ("list_max_float", BolideType::Float),
+            ("avg", BolideType::Int) => ("list_mean", BolideType::Float),
+            ("avg", BolideType::Float) => ("list_mean_float", BolideType::Float),
+            ("avg", BolideType::BigInt) => ("list_mean_bigint", BolideType::Float),
+            ("sum", BolideType::BigInt) => ("list_sum_bigint", BolideType::BigInt),
+            ("min", BolideType::BigInt) => ("list_min_bigint", BolideType::BigInt),
+            ("max", BolideType::BigInt) => ("list_max_bigint", BolideType::BigInt),
+            ("sum", BolideType::Decimal) => ("list_sum_decimal", BolideType::Decimal),
+            ("min", BolideType::Decimal) => ("list_min_decimal", BolideType::Decimal),
+            ("max", BolideType::Decimal) => ("list_max_decimal", BolideType::Decimal),
+            ("avg", BolideType::Decimal) => ("list_mean_decimal", BolideType::Decimal),
+            _ => return None,
+        };
+        Some(entry)
+    }
+
+    /// 是否为内置的列表聚合 sum/min/max/avg（恰好一个列表实参）
+    fn is_list_aggregate_call(&self, name: &str, args: &[Expr]) -> bool {
+        matches!(name, "sum" | "min" | "max" | "avg")
+            && args.len() == 1
+            && matches!(self.infer_expr_type(&args[0]), BolideType::List(_))
+    }
+
+    /// sum(xs) / min(xs) / max(xs) / avg(xs)
+    fn compile_list_aggregate(&mut self, func: &str, arg: &Expr) -> Result<Value, String> {
+        let list_ty = self.infer_expr_type(arg);
+        let (runtime_name, result_ty) = Self::list_aggregate(func, &list_ty).ok_or_else(|| {
+            format!("{}() is not supported for {:?}; element type must be int, float, bigint or decimal", func, list_ty)
+        })?;
+        let list = self.compile_expr(arg)?;
+        let func_ref = self.get_func_ref(runtime_name)?;
+        let call = self.builder.ins().call(func_ref, &[list]);
+        let result = self.builder.inst_results(call)[0];
+        self.track_temp_rc_value(result, &result_ty);
+        Ok(result)
+    }
+
+
+    /// 按静态类型哈希一个值；元组逐槽哈希后依次合并
+    fn emit_hash(&mut self, val: Value, ty: &BolideType) -> Result<Value, String> {
+        let func_name = match ty {
+            BolideType::Int | BolideType::Bool => "hash_int",
+            BolideType::Float => "hash_float",
+            BolideType::Str => "hash_string",
+            BolideType::BigInt => "hash_bigint",
+            BolideType::Decimal => "hash_decimal",
+            BolideType::Dynamic => "hash_dynamic",
+            BolideType::Tuple(elem_types) => {
+                let tuple_get = self.get_func_ref("tuple_get")?;
+                let combine = self.get_func_ref("hash_combine")?;
+                let mut acc = self.builder.ins().iconst(types::I64, bolide_runtime::TUPLE_HASH_SEED);
+                for (i, elem_ty) in elem_types.iter().enumerate() {
+                    let idx = self.builder.ins().iconst(types::I64, i as i64);
+                    let call = self.builder.ins().call(tuple_get, &[val, idx]);
+                    let mut slot = self.builder.inst_results(call)[0];
+                    // 元组中的 float 以位模式存储
+                    if *elem_ty == BolideType::Float {
+                        slot = self.builder.ins().bitcast(types::F64, MemFlags::new(), slot);
+                    }
+                    let slot_hash = self.emit_hash(slot, elem_ty)?;
+                    let call = self.builder.ins().call(combine, &[acc, slot_hash]);
+                    acc = self.builder.inst_results(call)[0];
+                }
+                return Ok(acc);
+            }
+            other => {
+                return Err(format!(
+                    "hash() cannot hash a value of type {:?}; hashable types are int, float, bool, str, bigint, decimal, dynamic and tuples of these",
+                    other
+                ));
+            }
+        };
+        let func_ref = self.get_func_ref(func_name)?;
+        let call = self.builder.ins().call(func_ref, &[val]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// 编译 bind(f, a, ...) - 绑定前导参数，返回新的函数值
+    fn compile_bind(&mut self, args: &[Expr]) -> Result<Value, String> {
+        if args.len() < 2 {
+            return Err("bind expects a function and at least 1 argument".to_string());
+        }
+        let bound_args = &args[1..];
+
+        // 运行时按 i64 传递全部实参，浮点参数/返回值无法正确转发
+        bind::check_target(&self.infer_bind_target_type(&args[0]), bound_args.len())?;
+
+        // 绑定参数由记录持有，不能交给 owned/ref 参数
+        if let Expr::Ident(name) = &args[0] {
+            if let Some(params) = self.func_params.get(name) {
+                if params.iter().any(|p| p.mode != ParamMode::Borrow) {
+                    return Err(format!("bind: function '{}' has owned or ref parameters", name));
+                }
+            }
+        }
+
+        let func_val = self.compile_expr(&args[0])?;
+
+        // 绑定参数和类型标签写入栈上数组
+        let count = bound_args.len();
+        let args_slot = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            (count * 8) as u32,
+            0,
+        ));
+        let tags_slot = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            count as u32,
+            0,
+        ));
+        let args_ptr = self.builder.ins().stack_addr(self.ptr_type, args_slot, 0);
+        let tags_ptr = self.builder.ins().stack_addr(self.ptr_type, tags_slot, 0);
+
+        for (i, arg) in bound_args.iter().enumerate() {
+            let tag = bind::arg_tag(&self.infer_expr_type(arg))?;
+            let val = self.compile_expr(arg)?;
+            self.builder.ins().store(MemFlags::new(), val, args_ptr, (i * 8) as i32);
+            let tag_val = self.builder.ins().iconst(types::I8, tag as i64);
+            self.builder.ins().store(MemFlags::new(), tag_val, tags_ptr, i as i32);
+        }
+
+        // 运行时会 retain 绑定的 RC 参数，临时值照常在语句结束时释放
+        let bind_ref = self.get_func_ref("bind")?;
+        let count_val = self.builder.ins().iconst(types::I64, count as i64);
+        let call = self.builder.ins().call(bind_ref, &[func_val, args_ptr, tags_ptr, count_val]);
+        let result = self.builder.inst_results(call)[0];
+
+        self.track_temp_rc_value(result, &BolideType::Func);
+        Ok(result)
+    }
+
+    /// group_by 的键类型：取自键函数签名的返回类型（int / bool / str / dynamic），
+    /// 签名未知时为 dynamic（键函数须返回 dynamic 值）
+    fn group_by_key_type(&self, key_fn: &Expr) -> Result<BolideType, String> {
+        match self.infer_bind_target_type(key_fn) {
+            BolideType::FuncSig(params, ret) => {
+                if params.len() != 1 {
+                    return Err(format!("group_by key function must take 1 argument, got {}", params.len()));
+                }
+                match ret.as_deref() {
+                    Some(ty @ (BolideType::Int | BolideType::Bool | BolideType::Str | BolideType::Dynamic)) => Ok(ty.clone()),
+                    other => Err(format!("group_by key function must return int or str, got {:?}", other)),
+                }
+            }
+            _ => Ok(BolideType::Dynamic),
+        }
+    }
+
+    /// map(f, xs) / filter(f, xs) / reduce(f, xs, init) 的结果类型
+    ///
+    /// `f` 必须有声明的签名：参数个数在编译时检查（reduce 为 2，其余为 1），结果类型取自返回类型。
+    /// 运行时按 i64 传递元素和累加值，与 bind 相同不支持 float 元素和 float 累加值。
+    fn higher_order_type(&self, name: &str, args: &[Expr]) -> Result<BolideType, String> {
+        let arity = if name == "reduce" { 2 } else { 1 };
+        if args.len() != arity + 1 {
+            return Err(format!("{} expects {} arguments", name, arity + 1));
+        }
+        let elem_ty = match self.infer_expr_type(&args[1]) {
+            BolideType::List(elem) => *elem,
+            other => return Err(format!("{} expects a list as its second argument, got {:?}", name, other)),
+        };
+        if elem_ty == BolideType::Float {
+            return Err(format!("{} does not support float lists", name));
+        }
+        let (params, ret) = match self.infer_bind_target_type(&args[0]) {
+            BolideType::FuncSig(params, ret) => (params, ret),
+            _ => return Err(format!("{} needs a function with a declared signature, e.g. func(int) -> int", name)),
+        };
+        if params.len() != arity {
+            return Err(format!("{} function must take {} argument(s), got {}", name, arity, params.len()));
+        }
+        match (name, ret.map(|r| *r)) {
+            ("filter", Some(BolideType::Bool)) => Ok(BolideType::List(Box::new(elem_ty))),
+            ("filter", other) => Err(format!("filter function must return bool, got {:?}", other)),
+            ("reduce", Some(BolideType::Float)) => Err("reduce does not support float accumulators".to_string()),
+            (_, None) => Err(format!("{} function must return a value", name)),
+            ("reduce", Some(acc_ty)) => Ok(acc_ty),
+            (_, Some(ret_ty)) => Ok(BolideType::List(Box::new(ret_ty))),
+        }
+    }
+
+    /// map / filter / reduce：回调通过函数值 ABI（`bolide_closure_call`）调用，结果是 RC 临时值
+    fn compile_higher_order(&mut self, name: &str, args: &[Expr]) -> Result<Value, String> {
+        let result_ty = self.higher_order_type(name, args)?;
+        if let Expr::Ident(func_name) = &args[0] {
+            if let Some(params) = self.func_params.get(func_name) {
+                if params.iter().any(|p| p.mode != ParamMode::Borrow) {
+                    return Err(format!("{}: function '{}' has owned or ref parameters", name, func_name));
+                }
+            }
+        }
+        let func = self.compile_expr(&args[0])?;
+        let list = self.compile_expr(&args[1])?;
+        let result = match (name, &result_ty) {
+            ("map", BolideType::List(ret_ty)) => {
+                let tag = self.builder.ins().iconst(types::I8, Self::elem_type_tag(ret_ty) as i64);
+                let func_ref = self.get_func_ref("list_map")?;
+                let call = self.builder.ins().call(func_ref, &[list, func, tag]);
+                self.builder.inst_results(call)[0]
+            }
+            ("filter", _) => {
+                let func_ref = self.get_func_ref("list_filter")?;
+                let call = self.builder.ins().call(func_ref, &[list, func]);
+                self.builder.inst_results(call)[0]
+            }
+            _ => {
+                let init = self.compile_expr_expecting(&args[2], Some(&result_ty))?;
+                let tag = self.builder.ins().iconst(types::I8, Self::elem_type_tag(&result_ty) as i64);
+                let func_ref = self.get_func_ref("list_reduce")?;
+                let call = self.builder.ins().call(func_ref, &[list, func, init, tag]);
+                self.builder.inst_results(call)[0]
+            }
+        };
+        self.track_temp_rc_value(result, &result_ty);
+        Ok(result)
+    }
+
+    /// sort_by_key / min_by / max_by 的键类型：取自键函数签名的返回类型（int / bool / float / str）
+    fn sort_key_type(&self, method: &str, key_fn: &Expr) -> Result<BolideType, String> {
+        match self.infer_bind_target_type(key_fn) {
+            BolideType::FuncSig(params, ret) => {
+                if params.len() != 1 {
+                    return Err(format!("{} key function must take 1 argument, got {}", method, params.len()));
+                }
+                match ret.as_deref() {
+                    Some(ty @ (BolideType::Int | BolideType::Bool | BolideType::Float | BolideType::Str)) => Ok(ty.clone()),
+                    other => Err(format!("{} key function must return int, float or str, got {:?}", method, other)),
+                }
+            }
+            _ => Err(format!("{} key function needs a declared signature, e.g. func(T) -> int", method)),
+        }
+    }
+
+    /// 推断 bind 目标的函数类型（未知签名时返回 Func）
+    fn infer_bind_target_type(&self, expr: &Expr) -> BolideType {
+        match expr {
+            Expr::Ident(name) => {
+                if let Some(ty) = self.var_types.get(name).or_else(|| self.global_var_types.get(name)) {
+                    return ty.clone();
+                }
+                if let Some(params) = self.func_params.get(name) {
+                    let ret = self.func_return_types.get(name).cloned().flatten();
+                    return BolideType::FuncSig(
+                        params.iter().map(|p| p.ty.clone()).collect(),
+                        ret.map(Box::new),
+                    );
+                }
+                BolideType::Func
+            }
+            Expr::Call(_, _) => self.infer_expr_type(expr),
+            _ => BolideType::Func,
+        }
+    }
+
     /// 推断表达式类型
     fn infer_expr_type(&self, expr: &Expr) -> BolideType {
+        // 模块成员按导入后的名字推断：`m.f(...)` 同 `@m_f(...)`，`m.X` 同 `@m_X`
+        match expr {
+            Expr::Call(callee, args) => if let Expr::Member(base, member) = callee.as_ref() {
+                match modules::resolve_member(&self.modules, base, member) {
+                    Some(Ok(Member::Class(name))) => return BolideType::Custom(name),
+                    Some(Ok(Member::Function(name) | Member::Global(name))) => {
+                        return self.infer_expr_type(&Expr::Call(Box::new(Expr::Ident(name)), args.clone()));
+                    }
+                    _ => {}
+                }
+            },
+            Expr::Member(base, member) => if let Some(Ok(Member::Global(name))) = modules::resolve_member(&self.modules, base, member) {
+                return self.infer_expr_type(&Expr::Ident(name));
+            },
+            _ => {}
+        }
         match expr {
             Expr::Int(_) => BolideType::Int,
             Expr::Float(_) => BolideType::Float,
             Expr::Bool(_) => BolideType::Bool,
-            Expr::String(_) => BolideType::Str,
+            Expr::String(_) | Expr::FString(_) => BolideType::Str,
             Expr::BigInt(_) => BolideType::BigInt,
             Expr::Decimal(_) => BolideType::Decimal,
+            Expr::Duration(_) => BolideType::Duration,
             Expr::None => BolideType::Int,
             Expr::Spawn(_, _) => BolideType::Future,
-            Expr::Recv(_) => BolideType::Int,
+            Expr::Recv(channel) => self.channel_elem_type(channel).unwrap_or(BolideType::Int),
             Expr::Ident(name) => {
                 // 查找局部变量类型
                 if let Some(ty) = self.var_types.get(name) {
@@ -5268,11 +9278,25 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 if let Some(ty) = self.global_var_types.get(name) {
                     return ty.clone();
                 }
+                // 函数名作为值（包括提升后的匿名函数）
+                if self.func_params.contains_key(name) {
+                    return self.infer_bind_target_type(expr);
+                }
                 BolideType::Int
             }
+            Expr::CompareChain(_, _) => BolideType::Bool,
             Expr::BinOp(left, op, right) => {
                 let left_ty = self.infer_expr_type(left);
                 let right_ty = self.infer_expr_type(right);
+                // 比较的结果总是 bool（类的 == / != 不论是 __eq__ 还是逐字段比较）
+                if matches!(op, BinOp::Is | BinOp::IsNot | BinOp::Eq | BinOp::Ne)
+                    || (matches!(op, BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge) && !matches!(left_ty, BolideType::Custom(_)))
+                {
+                    return BolideType::Bool;
+                }
+                if let Some(Ok(ty)) = duration::binop_type(&left_ty, op, &right_ty) {
+                    return ty;
+                }
                 // 类型提升规则
                 match (&left_ty, &right_ty) {
                     (BolideType::Str, BolideType::Str) => {
@@ -5304,11 +9328,50 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                     match name.as_str() {
                         "bigint" => BolideType::BigInt,
                         "decimal" => BolideType::Decimal,
-                        "int" => BolideType::Int,
-                        "float" => BolideType::Float,
+                        "int" | "parse_int" => BolideType::Int,
+                        "float" | "parse_float" => BolideType::Float,
                         "str" => BolideType::Str,  // str 函数返回字符串
                         "channel" => BolideType::Channel(Box::new(BolideType::Int)),  // 默认 int，实际类型从声明获取
                         "input" => BolideType::Str,  // input 函数返回字符串
+                        "read_file" | "format_row" => BolideType::Str,
+                        "intern" | "platform" | "temp_dir" | "version" | "error_message" => BolideType::Str,
+                        "version_at_least" | "has_feature" | "object_debug_enable" | "cancelled" => BolideType::Bool,
+                        "write_file" | "append_file" | "file_exists" | "remove_file" => BolideType::Bool,
+                        "read_lines" => BolideType::List(Box::new(BolideType::Str)),
+                        "map" | "filter" | "reduce" if !self.func_params.contains_key(name) => {
+                            self.higher_order_type(name, args).unwrap_or(BolideType::Int)
+                        }
+                        "timer_start" | "timer_elapsed_ns" | "thread_id" | "error" => BolideType::Int,
+                        "deadline" => BolideType::Deadline,
+                        "remaining" => BolideType::Duration,
+                        "timer_elapsed_ms" => BolideType::Float,
+                        "hash" | "len" | "set_div_precision" => BolideType::Int,
+                        "round" | "floor" | "ceil" if !self.func_params.contains_key(name) && !args.is_empty() => {
+                            match self.infer_expr_type(&args[0]) {
+                                ty @ (BolideType::Float | BolideType::Decimal) => ty,
+                                _ => BolideType::Int,
+                            }
+                        }
+                        name if overflow::lookup(name).is_some() => overflow::result_type(overflow::lookup(name).unwrap()),
+                        "packed_list" => match args.first() {
+                            Some(Expr::Ident(class_name)) => BolideType::PackedList(Box::new(
+                                self.normalize_bolide_type(&BolideType::Custom(class_name.clone()))
+                            )),
+                            _ => BolideType::Int,
+                        },
+                        "sum" | "min" | "max" | "avg" if args.len() == 1
+                            && matches!(self.infer_expr_type(&args[0]), BolideType::List(_)) => {
+                            Self::list_aggregate(name, &self.infer_expr_type(&args[0]))
+                                .map(|(_, ty)| ty)
+                                .unwrap_or(BolideType::Int)
+                        }
+                        "bind" => {
+                            // 剩余参数构成新函数的签名
+                            match args.first() {
+                                Some(f) => bind::result_type(self.infer_bind_target_type(f), args.len() - 1),
+                                None => BolideType::Func,
+                            }
+                        }
                         "join" => {
                             // 从 spawn_func_map 获取原函数的返回类型
                             if args.len() == 1 {
@@ -5322,7 +9385,24 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                             }
                             BolideType::Int // 默认
                         }
+                        "join_ok" => {
+                            let join = Expr::Call(Box::new(Expr::Ident("join".to_string())), args.clone());
+                            BolideType::Tuple(vec![BolideType::Bool, self.infer_expr_type(&join)])
+                        }
                         _ => {
+                            // 通过函数类型变量间接调用
+                            let var_ty = self.var_types.get(name.as_str())
+                                .or_else(|| self.global_var_types.get(name.as_str()));
+                            if let Some(BolideType::FuncSig(_, ret)) = var_ty {
+                                return ret.as_deref().cloned().unwrap_or(BolideType::Int);
+                            }
+                            if let Some((_, extern_func)) = self.extern_funcs.get(name.as_str()) {
+                                return Self::extern_return_type(extern_func);
+                            }
+                            // 调用 async 函数得到 Future，返回类型是 await 它的结果
+                            if self.async_funcs.contains(name.as_str()) {
+                                return BolideType::Future;
+                            }
                             // 查找用户定义函数的返回类型
                             if let Some(Some(ret_ty)) = self.func_return_types.get(name.as_str()) {
                                 ret_ty.clone()
@@ -5344,14 +9424,41 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                                  _ => BolideType::Int,
                              }
                         }
+                        BolideType::WeakDict(_, v) if method == "get" => *v,
                         BolideType::List(elem) => {
                              match method.as_str() {
-                                 "pop" | "get" | "first" | "last" => *elem,
-                                 "slice" | "copy" | "clone" => BolideType::List(elem),
+                                 "pop" | "get" | "remove" | "first" | "last" | "min_by" | "max_by" => *elem,
+                                 "slice" | "copy" | "clone" | "unique" => BolideType::List(elem),
+                                 "decode" | "decode_latin1" => BolideType::Str,
+                                 "group_by" => {
+                                     let key_ty = args.first()
+                                         .and_then(|f| self.group_by_key_type(f).ok())
+                                         .unwrap_or(BolideType::Dynamic);
+                                     BolideType::Dict(Box::new(key_ty), Box::new(BolideType::List(elem)))
+                                 }
                                  "len" | "index_of" | "count" | "is_empty" => BolideType::Int,
                                  _ => BolideType::Int
                              }
                         }
+                        BolideType::Channel(elem) => {
+                             match method.as_str() {
+                                 "recv_n" | "drain" => BolideType::List(elem),
+                                 _ => BolideType::Int
+                             }
+                        }
+                        BolideType::Decimal if method == "round" => BolideType::Decimal,
+                        BolideType::PackedList(_) => BolideType::Int,
+                        BolideType::Str => match method.as_str() {
+                            "split" => BolideType::List(Box::new(BolideType::Str)),
+                            "encode" => BolideType::List(Box::new(BolideType::Int)),
+                            "contains" => BolideType::Bool,
+                            "trim" | "strip" | "to_upper" | "upper" | "to_lower" | "lower" | "replace" => BolideType::Str,
+                            _ => BolideType::Int,
+                        },
+                        // 方法的返回类型（沿继承链查找），如 b.set_x(1).set_y(2)
+                        BolideType::Custom(class) => self.find_method(&class, method).ok()
+                            .and_then(|full_name| self.func_return_types.get(&full_name).cloned().flatten())
+                            .unwrap_or(BolideType::Int),
                         _ => BolideType::Int
                     }
                 } else {
@@ -5361,6 +9468,20 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             Expr::Member(base, member) => {
                 // 获取基础表达式的类型，然后查找字段类型
                 let base_ty = self.infer_expr_type(base);
+                // packed 元素的类字段仍是 packed 元素
+                if let BolideType::PackedRef(class) = &base_ty {
+                    let field_ty = match class.as_ref() {
+                        BolideType::Custom(name) => self.get_class(name)
+                            .and_then(|info| info.fields.iter().find(|f| f.name == *member))
+                            .map(|f| f.ty.clone()),
+                        _ => None,
+                    };
+                    return match field_ty {
+                        Some(ty @ BolideType::Custom(_)) => BolideType::PackedRef(Box::new(self.normalize_bolide_type(&ty))),
+                        Some(ty) => ty,
+                        None => BolideType::Int,
+                    };
+                }
                 // 处理 Weak/Unowned 类型，提取内部的 Custom 类型
                 let class_name = match &base_ty {
                     BolideType::Custom(name) => Some(name.clone()),
@@ -5409,13 +9530,27 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                         }
                     }
                     BolideType::List(elem_ty) => *elem_ty,
+                    BolideType::PackedList(elem_ty) => BolideType::PackedRef(elem_ty),
                     BolideType::Dict(_, val_ty) => *val_ty,
+                    BolideType::Str => BolideType::Int,
                     _ => BolideType::Int,
                 }
             }
+            // 切片与原值同类型
+            Expr::Slice(base, _, _) => self.infer_expr_type(base),
             Expr::Await(inner_expr) => {
                 // await 表达式返回协程的返回类型
-                if let Expr::Ident(var_name) = inner_expr.as_ref() {
+                if let Expr::Call(callee, _) = inner_expr.as_ref() {
+                    // 直接 await 一次 async 调用
+                    match callee.as_ref() {
+                        Expr::Ident(func_name) if self.async_funcs.contains(func_name) => self.func_return_types
+                            .get(func_name)
+                            .cloned()
+                            .flatten()
+                            .unwrap_or(BolideType::Int),
+                        _ => BolideType::Int,
+                    }
+                } else if let Expr::Ident(var_name) = inner_expr.as_ref() {
                     // 从 spawn_func_map 查找对应的函数名
                     if let Some(func_name) = self.spawn_func_map.get(var_name) {
                         // 从 func_return_types 获取返回类型
@@ -5470,6 +9605,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             BolideType::Int => types::I64,
             BolideType::Float => types::F64,
             BolideType::Bool => types::I64,
+            BolideType::Duration | BolideType::Deadline => types::I64,
             BolideType::Str => self.ptr_type,
             BolideType::BigInt => self.ptr_type,
             BolideType::Decimal => self.ptr_type,
@@ -5481,45 +9617,64 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             BolideType::FuncSig(_, _) => self.ptr_type,  // 带签名的函数指针
             BolideType::List(_) => self.ptr_type,
             BolideType::Dict(_, _) => self.ptr_type,
+            BolideType::WeakDict(_, _) => self.ptr_type,
             BolideType::Tuple(_) => self.ptr_type,  // 元组作为指针
 
             BolideType::Custom(_) => self.ptr_type,
             BolideType::Weak(inner) => self.bolide_type_to_cranelift(inner),
             BolideType::Unowned(inner) => self.bolide_type_to_cranelift(inner),
+            BolideType::PackedList(_) | BolideType::PackedRef(_) => self.ptr_type,
         }
     }
 
     /// 编译 pool 语句
-    fn compile_pool(&mut self, pool_stmt: &bolide_parser::PoolStmt) -> Result<(), String> {
+    ///
+    /// 离开块的每条路径都拆除线程池一次：正常走到末尾在这里拆除，块内的 return、
+    /// 跳出块的 break/continue 和中断返回由 `emit_pool_early_exits` 拆除。
+    /// 拆除在释放 RC 变量之前：`pool_destroy` 先等块内提交的任务全部完成，任务可能还在使用这些值。
+    fn compile_pool(&mut self, pool_stmt: &bolide_parser::PoolStmt) -> Result<bool, String> {
         // 计算线程池大小
         let size = self.compile_expr(&pool_stmt.size)?;
 
         // 创建线程池: pool_create(size) -> ptr
-        let pool_create_ref = *self.func_refs.get("pool_create")
-            .ok_or("pool_create not found")?;
+        let pool_create_ref = self.get_func_ref("pool_create")?;
         let call = self.builder.ins().call(pool_create_ref, &[size]);
         let pool_ptr = self.builder.inst_results(call)[0];
 
         // 进入线程池上下文: pool_enter(pool)
-        let pool_enter_ref = *self.func_refs.get("pool_enter")
-            .ok_or("pool_enter not found")?;
+        let pool_enter_ref = self.get_func_ref("pool_enter")?;
         self.builder.ins().call(pool_enter_ref, &[pool_ptr]);
 
         // 编译 pool 块内的语句
+        self.pool_stack.push(pool_ptr);
+        let mut terminated = false;
         for stmt in &pool_stmt.body {
-            self.compile_stmt(stmt)?;
+            if terminated { break; }
+            terminated = self.compile_stmt(stmt)?;
         }
+        self.pool_stack.pop();
 
-        // 退出线程池上下文: pool_exit()
-        let pool_exit_ref = *self.func_refs.get("pool_exit")
-            .ok_or("pool_exit not found")?;
-        self.builder.ins().call(pool_exit_ref, &[]);
+        if !terminated {
+            self.emit_pool_teardown(pool_ptr)?;
+        }
+        Ok(terminated)
+    }
 
-        // 销毁线程池: pool_destroy(pool)
-        let pool_destroy_ref = *self.func_refs.get("pool_destroy")
-            .ok_or("pool_destroy not found")?;
+    /// 拆除线程池：退出上下文 pool_exit(pool)，再销毁 pool_destroy(pool)（等待任务完成）
+    fn emit_pool_teardown(&mut self, pool_ptr: Value) -> Result<(), String> {
+        let pool_exit_ref = self.get_func_ref("pool_exit")?;
+        self.builder.ins().call(pool_exit_ref, &[pool_ptr]);
+        let pool_destroy_ref = self.get_func_ref("pool_destroy")?;
         self.builder.ins().call(pool_destroy_ref, &[pool_ptr]);
+        Ok(())
+    }
 
+    /// 提前离开时拆除 pool_stack 中 `depth` 之后的 pool 块，由内到外
+    fn emit_pool_early_exits(&mut self, depth: usize) -> Result<(), String> {
+        let crossed: Vec<Value> = self.pool_stack[depth..].iter().rev().copied().collect();
+        for pool_ptr in crossed {
+            self.emit_pool_teardown(pool_ptr)?;
+        }
         Ok(())
     }
 
@@ -5530,19 +9685,47 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             .ok_or_else(|| format!("Undefined channel: {}", send_stmt.channel))?;
         let channel_ptr = self.builder.use_var(channel_var);
 
+        self.check_borrow_escape(&send_stmt.value, &format!("sent over channel '{}'", send_stmt.channel))?;
+
         // 编译要发送的值
         let value = self.compile_expr(&send_stmt.value)?;
 
-        // 调用 channel_send(channel, value)
-        let channel_send_ref = *self.func_refs.get("channel_send")
-            .ok_or("channel_send not found")?;
-        self.builder.ins().call(channel_send_ref, &[channel_ptr, value]);
+        // RC 元素由通道 retain 一份，发送方的变量/临时值照常释放
+        let payload = self.channel_elem_type(&send_stmt.channel).as_ref().and_then(Self::channel_payload_tag);
+        if let Some(tag) = payload {
+            let tag_val = self.builder.ins().iconst(types::I8, tag as i64);
+            let channel_send_ref = self.get_func_ref("channel_send_ptr")?;
+            self.builder.ins().call(channel_send_ref, &[channel_ptr, value, tag_val]);
+        } else {
+            // 调用 channel_send(channel, value)
+            let channel_send_ref = self.get_func_ref("channel_send")?;
+            self.builder.ins().call(channel_send_ref, &[channel_ptr, value]);
+        }
 
         Ok(())
     }
 
+    /// 编译超时参数，得到运行时要的纳秒数（见 `duration::emit_timeout_ns`）
+    fn compile_timeout(&mut self, expr: &Expr) -> Result<Value, String> {
+        let kind = duration::timeout_kind(Some(&self.infer_expr_type(expr)))?;
+        if kind == duration::Timeout::Millis {
+            self.warn(duration::BARE_INT_TIMEOUT);
+        }
+        let value = self.compile_expr(expr)?;
+        let remaining = match kind {
+            duration::Timeout::Deadline => Some(self.get_func_ref("deadline_remaining")?),
+            _ => None,
+        };
+        Ok(duration::emit_timeout_ns(&mut self.builder, kind, value, remaining))
+    }
+
     /// 编译 select 语句
     fn compile_select(&mut self, select_stmt: &bolide_parser::SelectStmt) -> Result<(), String> {
+        if let Some(channels) = &select_stmt.channels {
+            return self.compile_select_from(channels, &select_stmt.branches);
+        }
+        // 块内的 break/continue 不能跳到块外的循环
+        let outer_fence = std::mem::replace(&mut self.loop_fence, self.loops.len());
         use bolide_parser::SelectBranch;
 
         // 收集 recv 分支的 channel 和相关信息
@@ -5561,6 +9744,9 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 SelectBranch::Default { body } => {
                     default_branch = Some(body);
                 }
+                SelectBranch::RecvAny { .. } | SelectBranch::Closed { .. } => {
+                    return Err("`value, index =>` and `closed =>` branches need `select from`".to_string());
+                }
             }
         }
 
@@ -5572,6 +9758,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                     self.compile_stmt(stmt)?;
                 }
             }
+            self.loop_fence = outer_fence;
             return Ok(());
         }
 
@@ -5605,14 +9792,13 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         let timeout_val = if default_branch.is_some() {
             self.builder.ins().iconst(types::I64, -2)  // has default
         } else if let Some((duration_expr, _)) = &timeout_branch {
-            self.compile_expr(duration_expr)?
+            self.compile_timeout(duration_expr)?
         } else {
             self.builder.ins().iconst(types::I64, -1)  // no timeout
         };
 
         // 调用 bolide_channel_select
-        let select_ref = *self.func_refs.get("channel_select")
-            .ok_or("channel_select not found")?;
+        let select_ref = self.get_func_ref("channel_select")?;
         let count_val = self.builder.ins().iconst(types::I64, channel_count as i64);
         let call = self.builder.ins().call(select_ref, &[array_ptr, count_val, timeout_val, value_ptr]);
         let results = self.builder.inst_results(call);
@@ -5645,28 +9831,30 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         )?;
 
         // 编译各 recv 分支
-        for (i, (var_name, _, body)) in recv_branches.iter().enumerate() {
+        for (i, (var_name, channel_name, body)) in recv_branches.iter().enumerate() {
             self.builder.switch_to_block(branch_blocks[i]);
             self.builder.seal_block(branch_blocks[i]);
 
             // 从栈上读取接收到的值
             let received_val = self.builder.ins().load(types::I64, MemFlags::new(), value_ptr, 0);
 
-            // 声明或获取变量
-            let var = if let Some(&existing) = self.variables.get(*var_name) {
-                self.builder.def_var(existing, received_val);
-                existing
-            } else {
-                let new_var = self.declare_variable(var_name, types::I64);
-                self.builder.def_var(new_var, received_val);
-                new_var
-            };
-            let _ = var;
+            let var = self.define_select_var(var_name, received_val);
+
+            // RC 元素：分支变量持有通道交出的引用，分支结束时释放
+            let payload = self.channel_elem_type(channel_name)
+                .filter(|ty| Self::channel_payload_tag(ty).is_some());
+            if let Some(elem) = &payload {
+                self.var_types.insert(var_name.to_string(), elem.clone());
+            }
 
             // 编译分支体
             for stmt in *body {
                 self.compile_stmt(stmt)?;
             }
+            if let Some(elem) = &payload {
+                let value = self.builder.use_var(var);
+                self.emit_release(value, elem);
+            }
             self.builder.ins().jump(exit_block, &[]);
         }
 
@@ -5693,23 +9881,144 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         self.builder.switch_to_block(exit_block);
         self.builder.seal_block(exit_block);
 
+        self.loop_fence = outer_fence;
         Ok(())
     }
 
-    /// 生成 select 分支跳转逻辑
-    fn compile_select_dispatch(
-        &mut self,
-        selected_idx: Value,
-        branch_blocks: &[Block],
-        timeout_block: Option<Block>,
-        default_block: Option<Block>,
-        exit_block: Block,
-    ) -> Result<(), String> {
-        // 逐个比较索引并跳转
-        for (i, &block) in branch_blocks.iter().enumerate() {
-            let idx_val = self.builder.ins().iconst(types::I64, i as i64);
-            let is_match = self.builder.ins().icmp(IntCC::Equal, selected_idx, idx_val);
-            let next_block = self.builder.create_block();
+    /// select 分支绑定的变量：已有同名变量时赋值，否则声明
+    fn define_select_var(&mut self, name: &str, value: Value) -> Variable {
+        let var = match self.variables.get(name) {
+            Some(&existing) => existing,
+            None => self.declare_variable(name, types::I64),
+        };
+        self.builder.def_var(var, value);
+        var
+    }
+
+    /// 编译 `select from chans { v, i => { ... } }`：每次执行时按列表当前的元素等待
+    /// （两次 select 之间可以增删 channel），分支绑定收到的值和 channel 在列表中的索引；
+    /// 所有 channel 都已关闭（或列表为空）时执行 `closed` 分支，没有 `closed` 分支时什么也不做
+    fn compile_select_from(&mut self, channels: &Expr, branches: &[bolide_parser::SelectBranch]) -> Result<(), String> {
+        use bolide_parser::SelectBranch;
+
+        let list_ty = self.infer_expr_type(channels);
+        let elem = match &list_ty {
+            BolideType::List(inner) => match self.normalize_bolide_type(inner) {
+                BolideType::Channel(elem) => self.normalize_bolide_type(&elem),
+                _ => return Err(format!("`select from` expects a list of channels, got {:?}", list_ty)),
+            },
+            _ => return Err(format!("`select from` expects a list of channels, got {:?}", list_ty)),
+        };
+
+        let mut recv = None;
+        let mut closed_body = None;
+        let mut timeout_branch = None;
+        let mut default_body = None;
+        for branch in branches {
+            match branch {
+                SelectBranch::RecvAny { var, index, body } => recv = Some((var.as_str(), index.as_str(), body)),
+                SelectBranch::Closed { body } => closed_body = Some(body),
+                SelectBranch::Timeout { duration, body } => timeout_branch = Some((duration, body)),
+                SelectBranch::Default { body } => default_body = Some(body),
+                SelectBranch::Recv { .. } => return Err("`select from` branches receive as `value, index => { ... }`".to_string()),
+            }
+        }
+        let (var_name, index_name, recv_body) = recv.ok_or("`select from` needs a `value, index => { ... }` branch")?;
+
+        let list = self.compile_expr(channels)?;
+        let value_slot = self.builder.create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8, 0));
+        let value_ptr = self.builder.ins().stack_addr(self.ptr_type, value_slot, 0);
+        let timeout_val = if default_body.is_some() {
+            self.builder.ins().iconst(types::I64, -2)
+        } else if let Some((duration, _)) = timeout_branch {
+            self.compile_timeout(duration)?
+        } else {
+            self.builder.ins().iconst(types::I64, -1)
+        };
+        let select_ref = self.get_func_ref("channel_select_list")?;
+        let call = self.builder.ins().call(select_ref, &[list, timeout_val, value_ptr]);
+        let selected_idx = self.builder.inst_results(call)[0];
+
+        // 块内的 break/continue 不能跳到块外的循环
+        let outer_fence = std::mem::replace(&mut self.loop_fence, self.loops.len());
+        let exit_block = self.builder.create_block();
+        let recv_block = self.builder.create_block();
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        let received = self.builder.ins().icmp(IntCC::SignedGreaterThanOrEqual, selected_idx, zero);
+        let not_received = self.builder.create_block();
+        self.builder.ins().brif(received, recv_block, &[], not_received, &[]);
+        self.builder.switch_to_block(not_received);
+        self.builder.seal_block(not_received);
+
+        // 其余结果：-1 超时，-2 default，SELECT_ALL_CLOSED
+        let others = [
+            (-1, timeout_branch.map(|(_, body)| body)),
+            (-2, default_body),
+            (bolide_runtime::SELECT_ALL_CLOSED, closed_body),
+        ];
+        let mut bodies = Vec::new();
+        for (code, body) in others {
+            let Some(body) = body else { continue };
+            let block = self.builder.create_block();
+            let code_val = self.builder.ins().iconst(types::I64, code);
+            let is_match = self.builder.ins().icmp(IntCC::Equal, selected_idx, code_val);
+            let next_block = self.builder.create_block();
+            self.builder.ins().brif(is_match, block, &[], next_block, &[]);
+            self.builder.switch_to_block(next_block);
+            self.builder.seal_block(next_block);
+            bodies.push((block, body));
+        }
+        self.builder.ins().jump(exit_block, &[]);
+
+        // 接收分支：值和索引绑定到分支变量，RC 元素由分支变量持有，分支结束时释放
+        self.builder.switch_to_block(recv_block);
+        self.builder.seal_block(recv_block);
+        let received_val = self.builder.ins().load(types::I64, MemFlags::new(), value_ptr, 0);
+        let var = self.define_select_var(var_name, received_val);
+        self.define_select_var(index_name, selected_idx);
+        self.var_types.insert(index_name.to_string(), BolideType::Int);
+        let payload = Some(elem).filter(|ty| Self::channel_payload_tag(ty).is_some());
+        if let Some(elem) = &payload {
+            self.var_types.insert(var_name.to_string(), elem.clone());
+        }
+        for stmt in recv_body {
+            self.compile_stmt(stmt)?;
+        }
+        if let Some(elem) = &payload {
+            let value = self.builder.use_var(var);
+            self.emit_release(value, elem);
+        }
+        self.builder.ins().jump(exit_block, &[]);
+
+        for (block, body) in bodies {
+            self.builder.switch_to_block(block);
+            self.builder.seal_block(block);
+            for stmt in body {
+                self.compile_stmt(stmt)?;
+            }
+            self.builder.ins().jump(exit_block, &[]);
+        }
+
+        self.builder.switch_to_block(exit_block);
+        self.builder.seal_block(exit_block);
+        self.loop_fence = outer_fence;
+        Ok(())
+    }
+
+    /// 生成 select 分支跳转逻辑
+    fn compile_select_dispatch(
+        &mut self,
+        selected_idx: Value,
+        branch_blocks: &[Block],
+        timeout_block: Option<Block>,
+        default_block: Option<Block>,
+        exit_block: Block,
+    ) -> Result<(), String> {
+        // 逐个比较索引并跳转
+        for (i, &block) in branch_blocks.iter().enumerate() {
+            let idx_val = self.builder.ins().iconst(types::I64, i as i64);
+            let is_match = self.builder.ins().icmp(IntCC::Equal, selected_idx, idx_val);
+            let next_block = self.builder.create_block();
             self.builder.ins().brif(is_match, block, &[], next_block, &[]);
             self.builder.switch_to_block(next_block);
             self.builder.seal_block(next_block);
@@ -5739,6 +10048,20 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
     /// 编译 spawn 表达式
     fn compile_spawn(&mut self, func_name: &str, args: &[Expr]) -> Result<Value, String> {
+        if !self.func_params.contains_key(func_name)
+            && matches!(self.infer_expr_type(&Expr::Ident(func_name.to_string())), BolideType::Func | BolideType::FuncSig(..))
+        {
+            return Err(format!(
+                "cannot spawn '{}': spawn needs a function name, not a function value \
+                 (a variable bound once with `let {} = fn(...) {{ ... }};` can be spawned)",
+                func_name, func_name
+            ));
+        }
+        self.call_edges.push((CallKind::Spawn, func_name.to_string()));
+        for arg in args {
+            self.check_borrow_escape(arg, &format!("captured by spawn of '{}'", func_name))?;
+        }
+
         // 获取目标函数的返回类型，确定 spawn 函数后缀
         let return_type = self.func_return_types.get(func_name).cloned().unwrap_or(None);
         let type_suffix = match &return_type {
@@ -5752,24 +10075,19 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         // 根据是否有参数选择不同的路径
         let (func_addr, env_ptr) = if args.is_empty() {
             // 无参数：直接使用目标函数
-            let target_func_ref = *self.func_refs.get(func_name)
-                .ok_or_else(|| format!("Undefined function: {}", func_name))?;
-            let func_addr = self.builder.ins().func_addr(self.ptr_type, target_func_ref);
+            let target_func_ref = self.get_func_ref(func_name).map_err(|_| format!("Undefined function: {}", func_name))?;
+            let func_addr = self.user_func_addr(func_name, target_func_ref);
             let null_ptr = self.builder.ins().iconst(self.ptr_type, 0);
             (func_addr, null_ptr)
         } else {
             // 有参数：使用 trampoline
-            let trampoline_ref = *self.trampoline_refs.get(func_name)
+            let trampoline_ref = self.get_trampoline_ref(func_name)
                 .ok_or_else(|| format!("No trampoline for function: {}", func_name))?;
-            let param_types = self.trampoline_param_types.get(func_name)
-                .ok_or_else(|| format!("No param types for trampoline: {}", func_name))?
-                .clone();
-            let env_size = *self.trampoline_env_sizes.get(func_name)
-                .ok_or_else(|| format!("No env size for trampoline: {}", func_name))?;
+            let param_types = self.trampolines[func_name].param_types.clone();
+            let env_size = self.trampolines[func_name].env_size;
 
             // 分配 env 内存
-            let alloc_ref = *self.func_refs.get("bolide_alloc")
-                .ok_or("bolide_alloc not found")?;
+            let alloc_ref = self.get_func_ref("bolide_alloc")?;
             let size_val = self.builder.ins().iconst(types::I64, env_size);
             let alloc_call = self.builder.ins().call(alloc_ref, &[size_val]);
             let env_ptr = self.builder.inst_results(alloc_call)[0];
@@ -5784,8 +10102,8 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 // 对 RC 类型进行 clone
                 let val_to_store = if Self::is_rc_type(bolide_type) {
                     if let Some(clone_func) = Self::get_clone_func_name(bolide_type) {
-                        if let Some(clone_ref) = self.func_refs.get(clone_func) {
-                            let call = self.builder.ins().call(*clone_ref, &[val]);
+                        if let Ok(clone_ref) = self.get_func_ref(clone_func) {
+                            let call = self.builder.ins().call(clone_ref, &[val]);
                             self.builder.inst_results(call)[0]
                         } else {
                             val // 没有 clone 函数引用，直接使用
@@ -5805,9 +10123,11 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             (func_addr, env_ptr)
         };
 
+        // 函数名，报告任务的错误时使用
+        let name = self.compile_task_name(func_name)?;
+
         // 检查是否在线程池上下文中
-        let pool_is_active_ref = *self.func_refs.get("pool_is_active")
-            .ok_or("pool_is_active not found")?;
+        let pool_is_active_ref = self.get_func_ref("pool_is_active")?;
         let is_active_call = self.builder.ins().call(pool_is_active_ref, &[]);
         let is_active = self.builder.inst_results(is_active_call)[0];
 
@@ -5830,28 +10150,30 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         self.builder.switch_to_block(pool_block);
         self.builder.seal_block(pool_block);
         let pool_spawn_name = format!("pool_spawn{}", spawn_suffix);
-        let pool_spawn_ref = *self.func_refs.get(&pool_spawn_name)
-            .ok_or_else(|| format!("{} not found", pool_spawn_name))?;
+        let pool_spawn_ref = self.get_func_ref(&pool_spawn_name)?;
         let pool_call = if args.is_empty() {
             self.builder.ins().call(pool_spawn_ref, &[func_addr])
         } else {
             self.builder.ins().call(pool_spawn_ref, &[func_addr, env_ptr])
         };
         let pool_handle = self.builder.inst_results(pool_call)[0];
+        let set_name = self.get_func_ref("pool_set_name")?;
+        self.builder.ins().call(set_name, &[pool_handle, name]);
         self.builder.ins().jump(merge_block, &[pool_handle]);
 
         // 普通线程分支
         self.builder.switch_to_block(thread_block);
         self.builder.seal_block(thread_block);
         let thread_spawn_name = format!("thread_spawn{}", spawn_suffix);
-        let thread_spawn_ref = *self.func_refs.get(&thread_spawn_name)
-            .ok_or_else(|| format!("{} not found", thread_spawn_name))?;
+        let thread_spawn_ref = self.get_func_ref(&thread_spawn_name)?;
         let thread_call = if args.is_empty() {
             self.builder.ins().call(thread_spawn_ref, &[func_addr])
         } else {
             self.builder.ins().call(thread_spawn_ref, &[func_addr, env_ptr])
         };
         let thread_handle = self.builder.inst_results(thread_call)[0];
+        let set_name = self.get_func_ref("thread_set_name")?;
+        self.builder.ins().call(set_name, &[thread_handle, name]);
         self.builder.ins().jump(merge_block, &[thread_handle]);
 
         // 合并块
@@ -5869,17 +10191,61 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             .ok_or_else(|| format!("Undefined channel: {}", channel_name))?;
         let channel_ptr = self.builder.use_var(channel_var);
 
+        // RC 元素：接收方取得通道持有的引用，作为临时值交给使用者
+        let elem = self.channel_elem_type(channel_name);
+        if let Some(elem) = elem.filter(|ty| Self::channel_payload_tag(ty).is_some()) {
+            let channel_recv_ref = self.get_func_ref("channel_recv_ptr")?;
+            let call = self.builder.ins().call(channel_recv_ref, &[channel_ptr]);
+            let value = self.builder.inst_results(call)[0];
+            self.track_temp_rc_value(value, &elem);
+            return Ok(value);
+        }
+
         // 调用 channel_recv(channel) -> i64
-        let channel_recv_ref = *self.func_refs.get("channel_recv")
-            .ok_or("channel_recv not found")?;
+        let channel_recv_ref = self.get_func_ref("channel_recv")?;
         let call = self.builder.ins().call(channel_recv_ref, &[channel_ptr]);
         let value = self.builder.inst_results(call)[0];
 
         Ok(value)
     }
 
+    /// 通道变量的元素类型（`channel<T>` 中的 T）
+    fn channel_elem_type(&self, channel_name: &str) -> Option<BolideType> {
+        match self.var_types.get(channel_name).or_else(|| self.global_var_types.get(channel_name)) {
+            Some(BolideType::Channel(elem)) => Some(self.normalize_bolide_type(elem)),
+            _ => None,
+        }
+    }
+
+    /// 通道按引用搬运的元素类型的运行时标签（见 `bolide_channel_send_ptr`）；
+    /// 对象和元组的释放需要编译器生成的字段清理，仍按原始值传递
+    fn channel_payload_tag(ty: &BolideType) -> Option<u8> {
+        match ty {
+            BolideType::Str | BolideType::BigInt | BolideType::Decimal | BolideType::List(_)
+            | BolideType::Dict(_, _) | BolideType::Dynamic => Some(Self::elem_type_tag(ty)),
+            BolideType::Func | BolideType::FuncSig(_, _) => Some(bolide_runtime::ARG_TAG_FUNC),
+            _ => None,
+        }
+    }
+
+    /// 任务名：新分配（不驻留）的字符串，交给 `*_set_name` 后由运行时释放
+    fn compile_task_name(&mut self, func_name: &str) -> Result<Value, String> {
+        let bytes: Box<[u8]> = func_name.as_bytes().into();
+        let ptr = Box::leak(bytes).as_ptr();
+        let from_slice = self.get_func_ref("string_from_slice")?;
+        let ptr_val = self.builder.ins().iconst(self.ptr_type, ptr as i64);
+        let len_val = self.builder.ins().iconst(types::I64, func_name.len() as i64);
+        let call = self.builder.ins().call(from_slice, &[ptr_val, len_val]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
     /// 编译 async 函数调用 - 启动协程并返回 Future
     fn compile_async_call(&mut self, func_name: &str, args: &[Expr]) -> Result<Value, String> {
+        self.call_edges.push((CallKind::Async, func_name.to_string()));
+        for arg in args {
+            self.check_borrow_escape(arg, &format!("captured by async call to '{}'", func_name))?;
+        }
+
         // 获取返回类型确定 spawn 函数后缀
         let return_type = self.func_return_types.get(func_name).cloned().unwrap_or(None);
         let type_suffix = match &return_type {
@@ -5892,33 +10258,36 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
         // 获取函数地址和环境指针
         let (func_addr, env_ptr) = if args.is_empty() {
-            let target_func_ref = *self.func_refs.get(func_name)
-                .ok_or_else(|| format!("Undefined async function: {}", func_name))?;
+            let target_func_ref = self.get_func_ref(func_name).map_err(|_| format!("Undefined async function: {}", func_name))?;
             let func_addr = self.builder.ins().func_addr(self.ptr_type, target_func_ref);
             let null_ptr = self.builder.ins().iconst(self.ptr_type, 0);
             (func_addr, null_ptr)
         } else {
             // 有参数：使用 trampoline
-            let trampoline_ref = *self.trampoline_refs.get(func_name)
+            let trampoline_ref = self.get_trampoline_ref(func_name)
                 .ok_or_else(|| format!("No trampoline for async function: {}", func_name))?;
-            let param_types = self.trampoline_param_types.get(func_name)
-                .ok_or_else(|| format!("No param types for trampoline: {}", func_name))?
-                .clone();
-            let env_size = *self.trampoline_env_sizes.get(func_name)
-                .ok_or_else(|| format!("No env size for trampoline: {}", func_name))?;
+            let param_types = self.trampolines[func_name].param_types.clone();
+            let env_size = self.trampolines[func_name].env_size;
 
             // 分配 env 内存
-            let alloc_ref = *self.func_refs.get("bolide_alloc")
-                .ok_or("bolide_alloc not found")?;
+            let alloc_ref = self.get_func_ref("bolide_alloc")?;
             let size_val = self.builder.ins().iconst(types::I64, env_size);
             let alloc_call = self.builder.ins().call(alloc_ref, &[size_val]);
             let env_ptr = self.builder.inst_results(alloc_call)[0];
 
-            // 存储参数到 env
+            // 存储参数到 env；trampoline 调用结束后释放 RC 参数，这里存 clone 的副本
             for (i, arg) in args.iter().enumerate() {
                 let val = self.compile_expr(arg)?;
                 let offset = (i * 8) as i32;
-                self.builder.ins().store(MemFlags::trusted(), val, env_ptr, offset);
+                let val_to_store = match Self::get_clone_func_name(&param_types[i]) {
+                    Some(clone_func) if Self::is_rc_type(&param_types[i]) => {
+                        let clone_ref = self.get_func_ref(clone_func)?;
+                        let call = self.builder.ins().call(clone_ref, &[val]);
+                        self.builder.inst_results(call)[0]
+                    }
+                    _ => val,
+                };
+                self.builder.ins().store(MemFlags::trusted(), val_to_store, env_ptr, offset);
             }
 
             let func_addr = self.builder.ins().func_addr(self.ptr_type, trampoline_ref);
@@ -5928,23 +10297,25 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         // 调用 coroutine_spawn_* 启动协程
         let (spawn_func_name, call) = if args.is_empty() {
             let spawn_func_name = format!("coroutine_spawn{}", type_suffix);
-            let spawn_ref = *self.func_refs.get(&spawn_func_name)
-                .ok_or_else(|| format!("{} not found", spawn_func_name))?;
+            let spawn_ref = self.get_func_ref(&spawn_func_name)?;
             let call = self.builder.ins().call(spawn_ref, &[func_addr]);
             (spawn_func_name, call)
         } else {
             let spawn_func_name = format!("coroutine_spawn{}_with_env", type_suffix);
-            let spawn_ref = *self.func_refs.get(&spawn_func_name)
-                .ok_or_else(|| format!("{} not found", spawn_func_name))?;
+            let spawn_ref = self.get_func_ref(&spawn_func_name)?;
             let call = self.builder.ins().call(spawn_ref, &[func_addr, env_ptr]);
             (spawn_func_name, call)
         };
         let _ = spawn_func_name; // 避免警告
         let future_ptr = self.builder.inst_results(call)[0];
 
+        // 记下函数名，报告协程的错误时使用
+        let name = self.compile_task_name(func_name)?;
+        let set_name = self.get_func_ref("coroutine_set_name")?;
+        self.builder.ins().call(set_name, &[future_ptr, name]);
+
         // 注册 Future 到当前 scope（如果在 scope 内）
-        let scope_register = *self.func_refs.get("scope_register")
-            .ok_or("scope_register not found")?;
+        let scope_register = self.get_func_ref("scope_register")?;
         self.builder.ins().call(scope_register, &[future_ptr]);
 
         Ok(future_ptr)
@@ -5966,16 +10337,23 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             _ => "coroutine_await_int",
         };
 
-        let await_ref = *self.func_refs.get(await_func_name)
-            .ok_or_else(|| format!("{} not found", await_func_name))?;
+        let await_ref = self.get_func_ref(await_func_name)?;
 
         let call = self.builder.ins().call(await_ref, &[future_ptr]);
         let result = self.builder.inst_results(call)[0];
 
-        // 释放 Future
-        let free_ref = *self.func_refs.get("coroutine_free")
-            .ok_or("coroutine_free not found")?;
+        // 释放 Future；变量置空，再次 await 它时运行时记录错误而不是访问释放了的 Future
+        let free_ref = self.get_func_ref("coroutine_free")?;
         self.builder.ins().call(free_ref, &[future_ptr]);
+        if let Expr::Ident(name) = inner_expr {
+            let null_val = self.builder.ins().iconst(self.ptr_type, 0);
+            if let Some(&var) = self.variables.get(name) {
+                self.builder.def_var(var, null_val);
+            } else if let Some(&data_id) = self.global_data_ids.get(name) {
+                let addr = self.global_addr(name, data_id)?;
+                self.builder.ins().store(MemFlags::new(), null_val, addr, 0);
+            }
+        }
 
         // 标记结果为临时 RC 值（调用者接管所有权）
         self.track_temp_rc_value(result, &expr_type);
@@ -5983,6 +10361,34 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         Ok(result)
     }
 
+    /// 编译溢出控制的整数内置函数，返回 (运算, 结果, 溢出标志)
+    fn compile_int_intrinsic(&mut self, name: &str, args: &[Expr]) -> Result<(overflow::IntOp, Value, Option<Value>), String> {
+        let op = overflow::lookup(name).ok_or_else(|| format!("Unknown function: {}", name))?;
+        let arg_types: Vec<_> = args.iter().map(|a| Some(self.infer_expr_type(a))).collect();
+        overflow::check_args(name, &arg_types)?;
+        let a = self.compile_expr(&args[0])?;
+        let b = self.compile_expr(&args[1])?;
+        let (value, overflowed) = overflow::emit(&mut self.builder, op, a, b);
+        Ok((op, value, overflowed))
+    }
+
+    /// `checked_*` 的结果当作值使用时分配 `(ok, value)` 元组
+    fn emit_checked_tuple(&mut self, value: Value, overflowed: Value) -> Result<Value, String> {
+        let ok = self.builder.ins().icmp_imm(IntCC::Equal, overflowed, 0);
+        let ok = self.builder.ins().uextend(types::I64, ok);
+        let tuple_new = self.get_func_ref("tuple_new")?;
+        let len = self.builder.ins().iconst(types::I64, 2);
+        let call = self.builder.ins().call(tuple_new, &[len]);
+        let tuple_ptr = self.builder.inst_results(call)[0];
+        let tuple_set = self.get_func_ref("tuple_set")?;
+        for (i, slot) in [ok, value].into_iter().enumerate() {
+            let idx = self.builder.ins().iconst(types::I64, i as i64);
+            self.builder.ins().call(tuple_set, &[tuple_ptr, idx, slot]);
+        }
+        self.track_temp_rc_value(tuple_ptr, &BolideType::Tuple(vec![BolideType::Bool, BolideType::Int]));
+        Ok(tuple_ptr)
+    }
+
     /// 编译元组字面量
     fn compile_tuple(&mut self, exprs: &[Expr]) -> Result<Value, String> {
         if exprs.is_empty() {
@@ -5997,15 +10403,13 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         let tuple_type = BolideType::Tuple(elem_types);
 
         // 调用 tuple_new 创建元组
-        let tuple_new = *self.func_refs.get("tuple_new")
-            .ok_or("tuple_new not found")?;
+        let tuple_new = self.get_func_ref("tuple_new")?;
         let len = self.builder.ins().iconst(types::I64, exprs.len() as i64);
         let call = self.builder.ins().call(tuple_new, &[len]);
         let tuple_ptr = self.builder.inst_results(call)[0];
 
         // 编译并设置每个元素
-        let tuple_set = *self.func_refs.get("tuple_set")
-            .ok_or("tuple_set not found")?;
+        let tuple_set = self.get_func_ref("tuple_set")?;
         for (i, expr) in exprs.iter().enumerate() {
             let val = self.compile_expr(expr)?;
             let ty = self.infer_expr_type(expr);
@@ -6023,18 +10427,12 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                     self.remove_temp_rc_value(val);
                     val
                 } else {
-                    // 是变量，需要 clone (retain)
-                    if let Some(clone_func) = Self::get_clone_func_name(&ty) {
-                        if let Some(&clone_ref) = self.func_refs.get(clone_func) {
-                            let call = self.builder.ins().call(clone_ref, &[val]);
-                            self.builder.inst_results(call)[0]
-                        } else {
-                            val
-                        }
-                    } else {
-                        val
-                    }
+                    // 是变量，需要复制（或共享）一份
+                    self.emit_binding_copy(val, &ty)
                 }
+            } else if ty == BolideType::Float {
+                // 元组槽位是 i64，float 以位模式存储
+                self.builder.ins().bitcast(types::I64, MemFlags::new(), val)
             } else {
                 val
             };
@@ -6051,40 +10449,304 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
     /// 编译列表字面量 [a, b, c]
     fn compile_list(&mut self, items: &[Expr]) -> Result<Value, String> {
-        // 确定元素类型（默认 int = 0）
-        let elem_type = if items.is_empty() {
-            0u8 // int
+        self.compile_list_typed(items, None)
+    }
+
+    /// 按声明类型编译表达式
+    ///
+    /// 列表/字典字面量的元素标签取自声明类型（`let xs: list<str> = []` 得到字符串列表），
+    /// 其他表达式照常编译。
+    fn compile_expr_expecting(&mut self, expr: &Expr, expected: Option<&BolideType>) -> Result<Value, String> {
+        let expected = expected.map(|t| self.normalize_bolide_type(t));
+        match (expr, expected) {
+            (Expr::List(items), Some(BolideType::List(elem))) => self.compile_list_typed(items, Some(&elem)),
+            (Expr::Dict(entries), Some(BolideType::Dict(k, v))) => self.compile_dict_typed(entries, Some((&k, &v))),
+            (Expr::List(items), Some(BolideType::PackedList(elem))) => self.compile_packed_list_literal(items, &elem),
+            (Expr::Call(callee, args), Some(BolideType::Dict(k, v)))
+                if args.is_empty() && matches!(callee.as_ref(), Expr::Ident(name) if name == "default_dict") =>
+            {
+                self.compile_default_dict(&k, &v)
+            }
+            (Expr::Call(callee, args), Some(BolideType::WeakDict(k, v)))
+                if args.is_empty() && matches!(callee.as_ref(), Expr::Ident(name) if name == "weak_dict") =>
+            {
+                self.compile_weak_dict(&k, &v)
+            }
+            _ => self.compile_expr(expr),
+        }
+    }
+
+    /// 编译要被接管所有权的值（变量初始化、赋值、字段赋值、返回值、owned 实参）
+    ///
+    /// 列表/字典字面量的结果登记为临时值，接收方直接接管，不再 clone 一份。
+    fn compile_owned_expr(&mut self, expr: &Expr, expected: Option<&BolideType>) -> Result<Value, String> {
+        if expected.is_some_and(|t| !matches!(t, BolideType::PackedRef(_))) {
+            self.check_not_packed_elem(expr)?;
+        }
+        let val = self.compile_expr_expecting(expr, expected)?;
+        if matches!(expr, Expr::List(_) | Expr::Dict(_)) {
+            let ty = match expected {
+                Some(t) => self.normalize_bolide_type(t),
+                None => self.infer_expr_type(expr),
+            };
+            self.track_temp_rc_value(val, &ty);
+        }
+        Ok(val)
+    }
+
+    /// packed 元素没有对象头，只能存进 packed 列表或另一个元素引用变量
+    fn check_not_packed_elem(&self, expr: &Expr) -> Result<(), String> {
+        if let BolideType::PackedRef(class) = self.infer_expr_type(expr) {
+            return Err(format!(
+                "An element of a packed list ({0}) is not a {0} object and cannot be stored here; \
+                 construct a {0} from its fields instead",
+                analysis::type_name(&class)
+            ));
+        }
+        Ok(())
+    }
+
+    /// 列表/字典元素类型对应的运行时标签（与运行时 ElementType 一致）
+    fn elem_type_tag(ty: &BolideType) -> u8 {
+        match ty {
+            BolideType::Int => 0,
+            BolideType::Float => 1,
+            BolideType::Bool => 2,
+            BolideType::Str => 3,
+            BolideType::BigInt => 4,
+            BolideType::Decimal => 5,
+            BolideType::List(_) => 6,
+            BolideType::Ptr => 7,
+            BolideType::Dict(_, _) => 8,
+            BolideType::Dynamic => 9,
+            _ => 0, // fallback integer
+        }
+    }
+
+    /// 容器值类型描述空容器的运行时标签（见 `bolide_dict_get_or_insert_empty`）；
+    /// 列表是元素类型，字典是键类型 | 值类型 << 4，其他类型为 None
+    fn empty_container_tag(ty: &BolideType) -> Option<u8> {
+        match ty {
+            BolideType::List(elem) => Some(Self::elem_type_tag(elem)),
+            BolideType::Dict(k, v) => Some(Self::elem_type_tag(k) | Self::elem_type_tag(v) << 4),
+            _ => None,
+        }
+    }
+
+    /// 编译 default_dict()：键不存在时读取 d[k] 插入值类型的空容器
+    fn compile_default_dict(&mut self, key_ty: &BolideType, val_ty: &BolideType) -> Result<Value, String> {
+        if Self::empty_container_tag(val_ty).is_none() {
+            return Err(format!("default_dict() values must be lists or dicts, got {:?}", val_ty));
+        }
+        let new_ref = self.get_func_ref("dict_new_default")?;
+        let k_tag = self.builder.ins().iconst(types::I8, Self::elem_type_tag(key_ty) as i64);
+        let v_tag = self.builder.ins().iconst(types::I8, Self::elem_type_tag(val_ty) as i64);
+        let call = self.builder.ins().call(new_ref, &[k_tag, v_tag]);
+        let dict = self.builder.inst_results(call)[0];
+        self.track_temp_rc_value(dict, &BolideType::Dict(Box::new(key_ty.clone()), Box::new(val_ty.clone())));
+        Ok(dict)
+    }
+
+    /// 编译 weak_dict()：值按元素类型标签 retain / release
+    fn compile_weak_dict(&mut self, key_ty: &BolideType, val_ty: &BolideType) -> Result<Value, String> {
+        let new_ref = self.get_func_ref("weakdict_new")?;
+        let v_tag = self.builder.ins().iconst(types::I8, Self::elem_type_tag(val_ty) as i64);
+        let call = self.builder.ins().call(new_ref, &[v_tag]);
+        let dict = self.builder.inst_results(call)[0];
+        self.track_temp_rc_value(dict, &BolideType::WeakDict(Box::new(key_ty.clone()), Box::new(val_ty.clone())));
+        Ok(dict)
+    }
+
+    /// 按声明的元素类型编译字面量中的一个元素
+    ///
+    /// 嵌套的列表/字典字面量使用内层声明类型；int 提升为 float，其他类型装箱为 dynamic，
+    /// 其余不一致报错。返回存入容器的 i64 值，以及存入后需要释放的嵌套字面量类型
+    /// （容器存入时会 retain，创建时的那一份引用由这里负责释放）。
+    fn compile_literal_element(&mut self, expr: &Expr, expected: &BolideType, what: &str)
+        -> Result<(Value, Option<BolideType>), String>
+    {
+        if matches!((expr, expected), (Expr::List(_), BolideType::List(_)) | (Expr::Dict(_), BolideType::Dict(_, _))) {
+            let val = self.compile_expr_expecting(expr, Some(expected))?;
+            return Ok((val, Some(expected.clone())));
+        }
+
+        let actual = self.normalize_bolide_type(&self.infer_expr_type(expr));
+        let val = self.compile_expr(expr)?;
+        let val = match (expected, &actual) {
+            (e, a) if e == a => val,
+            (BolideType::Dynamic, a) => self.convert_to_dynamic(val, a)?,
+            (BolideType::Float, BolideType::Int) => self.builder.ins().fcvt_from_sint(types::F64, val),
+            (e, a) => {
+                return Err(format!(
+                    "Type mismatch in {}: expected {:?}, found {:?}",
+                    what, e, a
+                ));
+            }
+        };
+
+        // 容器按 i64 存储，float 存位模式
+        let val = if self.builder.func.dfg.value_type(val) == types::F64 {
+            self.builder.ins().bitcast(types::I64, MemFlags::new(), val)
         } else {
-            match self.infer_expr_type(&items[0]) {
-                BolideType::Int => 0,
-                BolideType::Float => 1,
-                BolideType::Bool => 2,
-                BolideType::Str => 3,
-                BolideType::BigInt => 4,
-                BolideType::Decimal => 5,
-                _ => 0, // default to int
+            val
+        };
+        Ok((val, None))
+    }
+
+    /// 编译列表字面量，`expected` 为声明的元素类型
+    fn compile_list_typed(&mut self, items: &[Expr], expected: Option<&BolideType>) -> Result<Value, String> {
+        for item in items {
+            self.check_not_packed_elem(item)?;
+        }
+        if items.len() > list_literal::CHUNK_SIZE {
+            let elem = match expected {
+                Some(ty) => ty.clone(),
+                None => self.normalize_bolide_type(&self.infer_expr_type(&items[0])),
+            };
+            if let Some(list) = self.compile_large_list(items, &elem)? {
+                return Ok(list);
             }
+        }
+
+        // 确定元素类型：优先用声明类型，否则按第一个元素推断（空列表默认 int）
+        let elem_type = match expected {
+            Some(ty) => Self::elem_type_tag(ty),
+            None if items.is_empty() => 0u8,
+            None => Self::elem_type_tag(&self.infer_expr_type(&items[0])),
         };
 
         // 调用 list_new(elem_type) 创建列表
-        let list_new = *self.func_refs.get("list_new")
-            .ok_or("list_new not found")?;
+        let list_new = self.get_func_ref("list_new")?;
         let elem_type_val = self.builder.ins().iconst(types::I8, elem_type as i64);
         let call = self.builder.ins().call(list_new, &[elem_type_val]);
         let list_ptr = self.builder.inst_results(call)[0];
 
         // 编译并添加每个元素
-        let list_push = *self.func_refs.get("list_push")
-            .ok_or("list_push not found")?;
-        for expr in items {
-            let val = self.compile_expr(expr)?;
-            self.builder.ins().call(list_push, &[list_ptr, val]);
+        for (i, expr) in items.iter().enumerate() {
+            let (val, nested) = match expected {
+                Some(ty) => self.compile_literal_element(expr, ty, &format!("list literal element {}", i))?,
+                None => (self.compile_expr(expr)?, None),
+            };
+            self.emit_list_push(list_ptr, val)?;
+            if let Some(ty) = nested {
+                self.emit_release(val, &ty);
+            }
         }
 
         Ok(list_ptr)
     }
 
+    /// 编译要写入列表的值
+    fn compile_list_elem_arg(&mut self, expr: &Expr, elem_ty: &BolideType) -> Result<Value, String> {
+        let val = self.compile_expr(expr)?;
+        Ok(self.coerce_list_elem(val, expr, elem_ty))
+    }
+
+    /// float 列表里写入的 int 值转成 float
+    fn coerce_list_elem(&mut self, val: Value, expr: &Expr, elem_ty: &BolideType) -> Value {
+        if *elem_ty == BolideType::Float && self.builder.func.dfg.value_type(val) == types::I64
+            && self.infer_expr_type(expr) == BolideType::Int
+        {
+            self.builder.ins().fcvt_from_sint(types::F64, val)
+        } else {
+            val
+        }
+    }
+
+    /// 列表槽位是 i64，float 元素存位模式
+    fn elem_to_slot(&mut self, val: Value) -> Value {
+        if self.builder.func.dfg.value_type(val) == types::F64 {
+            self.builder.ins().bitcast(types::I64, MemFlags::new(), val)
+        } else {
+            val
+        }
+    }
+
+    /// 运行时按 i64 返回的槽位还原成元素值
+    fn slot_to_elem(&mut self, slot: Value, elem_ty: &BolideType) -> Value {
+        if *elem_ty == BolideType::Float {
+            self.builder.ins().bitcast(types::F64, MemFlags::new(), slot)
+        } else {
+            slot
+        }
+    }
+
+    /// 追加元素：f64 值走 `list_push_f64`
+    fn emit_list_push(&mut self, list: Value, val: Value) -> Result<(), String> {
+        let func_name = if self.builder.func.dfg.value_type(val) == types::F64 { "list_push_f64" } else { "list_push" };
+        let func_ref = self.get_func_ref(func_name)?;
+        self.builder.ins().call(func_ref, &[list, val]);
+        Ok(())
+    }
+
+    /// 读取元素：float 列表走 `list_get_f64`
+    fn emit_list_get(&mut self, list: Value, index: Value, elem_ty: &BolideType) -> Result<Value, String> {
+        let func_name = if *elem_ty == BolideType::Float { "list_get_f64" } else { "list_get" };
+        let func_ref = self.get_func_ref(func_name)?;
+        let call = self.builder.ins().call(func_ref, &[list, index]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// 写入元素，返回是否成功：f64 值走 `list_set_f64`
+    fn emit_list_set(&mut self, list: Value, index: Value, val: Value) -> Result<Value, String> {
+        let func_name = if self.builder.func.dfg.value_type(val) == types::F64 { "list_set_f64" } else { "list_set" };
+        let func_ref = self.get_func_ref(func_name)?;
+        let call = self.builder.ins().call(func_ref, &[list, index, val]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// 编译超过 `CHUNK_SIZE` 个元素的列表字面量（见 `list_literal`）
+    ///
+    /// 元素引用了局部变量、不能搬进辅助函数时返回 None，由调用方逐个 push。
+    fn compile_large_list(&mut self, items: &[Expr], elem: &BolideType) -> Result<Option<Value>, String> {
+        if let Some(array) = list_literal::const_array(items, elem) {
+            let data_id = self.module.declare_anonymous_data(false, false)
+                .map_err(|e| format!("Failed to declare list data: {}", e))?;
+            let mut desc = DataDescription::new();
+            desc.set_align(8);
+            desc.define(array.bytes.into_boxed_slice());
+            self.module.define_data(data_id, &desc)
+                .map_err(|e| format!("Failed to define list data: {}", e))?;
+            let gv = self.module.declare_data_in_func(data_id, self.builder.func);
+
+            let from_const = self.get_func_ref("list_from_const_array")?;
+            let elem_type = self.builder.ins().iconst(types::I8, array.elem_type as i64);
+            let data = self.builder.ins().global_value(self.ptr_type, gv);
+            let count = self.builder.ins().iconst(types::I64, items.len() as i64);
+            let call = self.builder.ins().call(from_const, &[elem_type, data, count]);
+            return Ok(Some(self.builder.inst_results(call)[0]));
+        }
+
+        let variables = &self.variables;
+        if !items.iter().all(|e| list_literal::is_closed(e, &|name| variables.contains_key(name))) {
+            return Ok(None);
+        }
+
+        let list_new = self.get_func_ref("list_new")?;
+        let elem_type = self.builder.ins().iconst(types::I8, Self::elem_type_tag(elem) as i64);
+        let call = self.builder.ins().call(list_new, &[elem_type]);
+        let list_ptr = self.builder.inst_results(call)[0];
 
+        // 每个辅助函数返回一段子列表，追加后释放
+        let prefix = format!("__list_chunk_{}_{}", self.current_func_name, self.chunked_lists);
+        self.chunked_lists += 1;
+        let list_extend = self.get_func_ref("list_extend")?;
+        let chunk_ty = BolideType::List(Box::new(elem.clone()));
+        for chunk in list_literal::chunk_functions(&prefix, items, elem) {
+            let mut sig = self.module.make_signature();
+            sig.returns.push(AbiParam::new(self.ptr_type));
+            // 匿名：REPL 的每次输入都有一个 `__main__`，辅助函数名会重复
+            let chunk_id = self.module.declare_anonymous_function(&sig)
+                .map_err(|e| format!("{}", e))?;
+            let chunk_ref = self.module.declare_func_in_func(chunk_id, self.builder.func);
+            let call = self.builder.ins().call(chunk_ref, &[]);
+            let part = self.builder.inst_results(call)[0];
+            self.builder.ins().call(list_extend, &[list_ptr, part]);
+            self.emit_release(part, &chunk_ty);
+            self.list_chunks.push((chunk, chunk_id));
+        }
+        Ok(Some(list_ptr))
+    }
 
     /// 将值转换为 Dynamic 类型 (Boxing)
     fn convert_to_dynamic(&mut self, val: Value, ty: &BolideType) -> Result<Value, String> {
@@ -6100,8 +10762,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             BolideType::Dynamic => return Ok(val), // Already dynamic
             _ => return Err(format!("Cannot convert {:?} to dynamic", ty)),
         };
-        let func = *self.func_refs.get(func_name)
-            .ok_or_else(|| format!("{} not found", func_name))?;
+        let func = self.get_func_ref(func_name)?;
         let call = self.builder.ins().call(func, &[val]);
         let res = self.builder.inst_results(call)[0];
         self.track_temp_rc_value(res, &BolideType::Dynamic);
@@ -6110,8 +10771,19 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
     /// 编译字典字面量 {k: v, ...}
     fn compile_dict(&mut self, entries: &[(Expr, Expr)]) -> Result<Value, String> {
-        // 确定键和值类型 (需扫描所有元素以处理 Dynamic)
-        let (key_type_tag, val_type_tag) = if entries.is_empty() {
+        self.compile_dict_typed(entries, None)
+    }
+
+    /// 编译字典字面量，`expected` 为声明的键和值类型
+    fn compile_dict_typed(&mut self, entries: &[(Expr, Expr)], expected: Option<(&BolideType, &BolideType)>) -> Result<Value, String> {
+        for (k, v) in entries {
+            self.check_not_packed_elem(k)?;
+            self.check_not_packed_elem(v)?;
+        }
+        // 确定键和值类型：优先用声明类型，否则扫描所有元素（不一致时为 Dynamic）
+        let (key_type_tag, val_type_tag) = if let Some((k_ty, v_ty)) = expected {
+             (Self::elem_type_tag(k_ty), Self::elem_type_tag(v_ty))
+        } else if entries.is_empty() {
              (0u8, 0u8) // default int: int
         } else {
              // 第一次扫描：推断统一类型 (Dynamic or specific)
@@ -6126,36 +10798,34 @@ impl<'a, 'b> CompileContext<'a, 'b> {
              }
 
              // 映射到 type tag
-             let map_tag = |ty: &BolideType| -> u8 {
-                 match ty {
-                    BolideType::Int => 0,
-                    BolideType::Float => 1,
-                    BolideType::Bool => 2,
-                    BolideType::Str => 3,
-                    BolideType::BigInt => 4,
-                    BolideType::Decimal => 5,
-                    BolideType::List(_) => 6,
-                    BolideType::Ptr => 7,
-                    BolideType::Dict(_, _) => 8,
-                    BolideType::Dynamic => 9,
-                    _ => 0 // fallback integer
-                 }
-             };
-             (map_tag(&k_final_ty), map_tag(&v_final_ty))
+             (Self::elem_type_tag(&k_final_ty), Self::elem_type_tag(&v_final_ty))
         };
 
         // 创建字典
-        let dict_new = *self.func_refs.get("dict_new")
-             .ok_or("dict_new not found")?;
+        let dict_new = self.get_func_ref("dict_new")?;
         let k_type_val = self.builder.ins().iconst(types::I8, key_type_tag as i64);
         let v_type_val = self.builder.ins().iconst(types::I8, val_type_tag as i64);
         let call = self.builder.ins().call(dict_new, &[k_type_val, v_type_val]);
         let dict_ptr = self.builder.inst_results(call)[0];
 
         // 设置元素
-        let dict_set = *self.func_refs.get("dict_set")
-             .ok_or("dict_set not found")?;
+        let dict_set = self.get_func_ref("dict_set")?;
         
+        if let Some((k_ty, v_ty)) = expected {
+            for (i, (key, val)) in entries.iter().enumerate() {
+                let (k_val, k_nested) = self.compile_literal_element(key, k_ty, &format!("dict literal key {}", i))?;
+                let (v_val, v_nested) = self.compile_literal_element(val, v_ty, &format!("dict literal value {}", i))?;
+                self.builder.ins().call(dict_set, &[dict_ptr, k_val, v_val]);
+                if let Some(ty) = k_nested {
+                    self.emit_release(k_val, &ty);
+                }
+                if let Some(ty) = v_nested {
+                    self.emit_release(v_val, &ty);
+                }
+            }
+            return Ok(dict_ptr);
+        }
+
         for (key, val) in entries {
             let mut k_val = self.compile_expr(key)?;
             let mut v_val = self.compile_expr(val)?;
@@ -6185,104 +10855,842 @@ impl<'a, 'b> CompileContext<'a, 'b> {
     /// 编译索引访问 (元组或列表)
     fn compile_index(&mut self, base: &Expr, index: &Expr) -> Result<Value, String> {
         let base_type = self.infer_expr_type(base);
+        if let BolideType::PackedList(elem) = &base_type {
+            let layout = self.packed_layout(elem)?;
+            let list = self.compile_expr(base)?;
+            let index = self.compile_expr(index)?;
+            return self.emit_packed_elem_ptr(list, index, layout.size);
+        }
         let base_val = self.compile_expr(base)?;
         let index_val = self.compile_expr(index)?;
 
         // 根据类型选择不同的索引函数
         match base_type {
-            BolideType::List(_) => {
-                let list_get = *self.func_refs.get("list_get")
-                    .ok_or("list_get not found")?;
-                let call = self.builder.ins().call(list_get, &[base_val, index_val]);
+            BolideType::List(elem_ty) => self.emit_list_get(base_val, index_val, &elem_ty),
+            BolideType::Dict(_, val_ty) => {
+                // 容器值返回字典里存放的那一份（借用），d[k].push(x) 修改的就是它；
+                // 键不存在时报错，default_dict() 创建的字典则插入空容器
+                if let Some(tag) = Self::empty_container_tag(&self.normalize_bolide_type(&val_ty)) {
+                    let get_ref = self.get_func_ref("dict_get_or_insert_empty")?;
+                    let tag_val = self.builder.ins().iconst(types::I8, tag as i64);
+                    let call = self.builder.ins().call(get_ref, &[base_val, index_val, tag_val]);
+                    return Ok(self.builder.inst_results(call)[0]);
+                }
+                let dict_get = self.get_func_ref("dict_get")?;
+                let call = self.builder.ins().call(dict_get, &[base_val, index_val]);
                 Ok(self.builder.inst_results(call)[0])
             }
-            BolideType::Dict(_, _) => {
-                let dict_get = *self.func_refs.get("dict_get")
-                    .ok_or("dict_get not found")?;
-                let call = self.builder.ins().call(dict_get, &[base_val, index_val]);
+            // s[i] -> int：字符码点
+            BolideType::Str => {
+                let char_at = self.get_func_ref("string_char_at")?;
+                let call = self.builder.ins().call(char_at, &[base_val, index_val]);
                 Ok(self.builder.inst_results(call)[0])
             }
 
             _ => {
                 // 默认使用元组索引
-                let tuple_get = *self.func_refs.get("tuple_get")
-                    .ok_or("tuple_get not found")?;
+                let tuple_get = self.get_func_ref("tuple_get")?;
                 let call = self.builder.ins().call(tuple_get, &[base_val, index_val]);
-                Ok(self.builder.inst_results(call)[0])
+                let val = self.builder.inst_results(call)[0];
+                if self.infer_expr_type(&Expr::Index(Box::new(base.clone()), Box::new(index.clone()))) == BolideType::Float {
+                    return Ok(self.builder.ins().bitcast(types::F64, MemFlags::new(), val));
+                }
+                Ok(val)
             }
         }
     }
 
 
-    /// 编译 await all 表达式
-    fn compile_await_all(&mut self, exprs: &[Expr]) -> Result<Value, String> {
-        // 先启动所有协程，收集 Future 指针
-        let mut futures = Vec::new();
-        for expr in exprs {
-            let future_ptr = self.compile_expr(expr)?;
-            futures.push(future_ptr);
+    // ===== packed 列表（list<packed T>） =====
+
+    /// packed 列表元素的布局；元素类型必须是只含 int/float/bool 和可 packed 类字段的类
+    fn packed_layout(&self, elem: &BolideType) -> Result<PackedLayout, String> {
+        let BolideType::Custom(class_name) = self.normalize_bolide_type(elem) else {
+            return Err(format!("list<packed T> needs a class element type, got {}", analysis::type_name(elem)));
+        };
+        packed::packed_layout(
+            &class_name,
+            &|name| self.get_class(name).map(|info| info.fields.iter().map(|f| (f.name.clone(), f.ty.clone())).collect()),
+            &|name| formatter::display_name(name, &self.modules),
+        )
+    }
+
+    /// packed 元素的字段（按 packed 布局的偏移）
+    fn packed_field(&self, class: &BolideType, member: &str) -> Result<packed::PackedField, String> {
+        self.packed_layout(class)?.field(member).cloned()
+            .ok_or_else(|| format!("Field '{}' not found in class '{}'", member, analysis::type_name(class)))
+    }
+
+    /// 元素布局描述的地址和长度，和字符串字面量一样泄漏到程序结束
+    fn emit_packed_descriptor(&mut self, layout: &PackedLayout) -> (Value, Value) {
+        let bytes: Box<[u8]> = layout.descriptor.as_bytes().into();
+        let ptr = Box::leak(bytes).as_ptr();
+        let ptr_val = self.builder.ins().iconst(self.ptr_type, ptr as i64);
+        let len_val = self.builder.ins().iconst(types::I64, layout.descriptor.len() as i64);
+        (ptr_val, len_val)
+    }
+
+    /// 新建 `len` 个零值元素的 packed 列表（调用者负责登记所有权）
+    fn emit_packed_list_new(&mut self, elem: &BolideType, len: Value) -> Result<Value, String> {
+        let layout = self.packed_layout(elem)?;
+        let elem_size = self.builder.ins().iconst(types::I64, layout.size as i64);
+        let (desc, desc_len) = self.emit_packed_descriptor(&layout);
+        let new_ref = self.get_func_ref("packed_list_new")?;
+        let call = self.builder.ins().call(new_ref, &[elem_size, len, desc, desc_len]);
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    /// packed_list(T, n)：n 个零值元素的 list<packed T>
+    fn compile_packed_list_new(&mut self, args: &[Expr]) -> Result<Value, String> {
+        let [Expr::Ident(class_name), len] = args else {
+            return Err("packed_list expects a class name and a length, e.g. packed_list(Vec2, 100)".to_string());
+        };
+        let len_ty = self.infer_expr_type(len);
+        if len_ty != BolideType::Int {
+            return Err(format!("packed_list length must be an int, got {}", analysis::type_name(&len_ty)));
         }
+        let elem = self.normalize_bolide_type(&BolideType::Custom(class_name.clone()));
+        let len = self.compile_expr(len)?;
+        let list = self.emit_packed_list_new(&elem, len)?;
+        self.track_temp_rc_value(list, &BolideType::PackedList(Box::new(elem)));
+        Ok(list)
+    }
 
-        // 依次等待所有 Future（简单实现）
-        let mut results = Vec::new();
-        for (i, future_ptr) in futures.iter().enumerate() {
-            let expr_type = self.infer_expr_type(&exprs[i]);
-            let await_func_name = match &expr_type {
-                BolideType::Float => "coroutine_await_float",
-                BolideType::Str | BolideType::BigInt | BolideType::Decimal
-                | BolideType::List(_) | BolideType::Custom(_) => "coroutine_await_ptr",
-                _ => "coroutine_await_int",
-            };
+    /// list<packed T> 的列表字面量：元素逐个复制进缓冲区
+    fn compile_packed_list_literal(&mut self, items: &[Expr], elem: &BolideType) -> Result<Value, String> {
+        let layout = self.packed_layout(elem)?;
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        let list = self.emit_packed_list_new(elem, zero)?;
+        let push_ref = self.get_func_ref("packed_list_push")?;
+        for item in items {
+            let record = self.compile_packed_record(item, elem, &layout)?;
+            let call = self.builder.ins().call(push_ref, &[list]);
+            let slot = self.builder.inst_results(call)[0];
+            self.emit_copy_record(record, slot, layout.size);
+        }
+        Ok(list)
+    }
+
+    /// 第 `index` 个元素的地址：直接读列表头里的 `len` 和 `data` 计算，
+    /// 越界时交给运行时（记录错误并返回一条零值记录）
+    fn emit_packed_elem_ptr(&mut self, list: Value, index: Value, elem_size: usize) -> Result<Value, String> {
+        let len = self.builder.ins().load(types::I64, MemFlags::trusted(), list, bolide_runtime::PACKED_LIST_LEN_OFFSET);
+        let in_range = self.builder.ins().icmp(IntCC::UnsignedLessThan, index, len);
+        let fast_block = self.builder.create_block();
+        let slow_block = self.builder.create_block();
+        let merge_block = self.builder.create_block();
+        self.builder.append_block_param(merge_block, self.ptr_type);
+        self.builder.ins().brif(in_range, fast_block, &[], slow_block, &[]);
+
+        self.builder.switch_to_block(fast_block);
+        self.builder.seal_block(fast_block);
+        let data = self.builder.ins().load(self.ptr_type, MemFlags::trusted(), list, bolide_runtime::PACKED_LIST_DATA_OFFSET);
+        let offset = self.builder.ins().imul_imm(index, elem_size as i64);
+        let addr = self.builder.ins().iadd(data, offset);
+        self.builder.ins().jump(merge_block, &[addr]);
+
+        self.builder.switch_to_block(slow_block);
+        self.builder.seal_block(slow_block);
+        let at_ref = self.get_func_ref("packed_list_at")?;
+        let call = self.builder.ins().call(at_ref, &[list, index]);
+        let addr = self.builder.inst_results(call)[0];
+        self.builder.ins().jump(merge_block, &[addr]);
 
-            let await_ref = *self.func_refs.get(await_func_name)
-                .ok_or_else(|| format!("{} not found", await_func_name))?;
+        self.builder.switch_to_block(merge_block);
+        self.builder.seal_block(merge_block);
+        Ok(self.builder.block_params(merge_block)[0])
+    }
 
-            let call = self.builder.ins().call(await_ref, &[*future_ptr]);
-            let result = self.builder.inst_results(call)[0];
-            results.push(result);
+    /// 按 8 字节逐个复制一条记录
+    fn emit_copy_record(&mut self, src: Value, dst: Value, size: usize) {
+        for offset in (0..size).step_by(8) {
+            let word = self.builder.ins().load(types::I64, MemFlags::new(), src, offset as i32);
+            self.builder.ins().store(MemFlags::new(), word, dst, offset as i32);
         }
+    }
 
-        // 将结果存储到元组中
-        if results.is_empty() {
-            Ok(self.builder.ins().iconst(types::I64, 0))
-        } else if results.len() == 1 {
-            Ok(results[0])
-        } else {
-            // 使用运行时元组存储所有结果
-            let tuple_new = *self.func_refs.get("tuple_new")
-                .ok_or("tuple_new not found")?;
-            let len = self.builder.ins().iconst(types::I64, results.len() as i64);
-            let call = self.builder.ins().call(tuple_new, &[len]);
-            let tuple_ptr = self.builder.inst_results(call)[0];
-
-            let tuple_set = *self.func_refs.get("tuple_set")
-                .ok_or("tuple_set not found")?;
-            for (i, result) in results.iter().enumerate() {
-                let idx = self.builder.ins().iconst(types::I64, i as i64);
-                self.builder.ins().call(tuple_set, &[tuple_ptr, idx, *result]);
+    /// 对象的字段按 packed 布局写到 `dst`，类字段从子对象递归展开
+    fn emit_pack_object(&mut self, obj: Value, dst: Value, class: &BolideType, layout: &PackedLayout) -> Result<(), String> {
+        let BolideType::Custom(class_name) = class else {
+            return Err(format!("Cannot pack a value of type {}", analysis::type_name(class)));
+        };
+        let info = self.get_class(class_name)
+            .ok_or_else(|| format!("Class not found: {}", class_name))?
+            .clone();
+        for field in &layout.fields {
+            let boxed_offset = info.fields.iter()
+                .find(|f| f.name == field.name)
+                .map(|f| f.offset)
+                .ok_or_else(|| format!("Field '{}' not found in class '{}'", field.name, class_name))?;
+            let value = self.builder.ins().load(types::I64, MemFlags::new(), obj, boxed_offset as i32);
+            if let BolideType::Custom(_) = field.ty {
+                let inner_class = self.normalize_bolide_type(&field.ty);
+                let inner_layout = self.packed_layout(&inner_class)?;
+                let inner_dst = self.builder.ins().iadd_imm(dst, field.offset as i64);
+                self.emit_pack_object(value, inner_dst, &inner_class, &inner_layout)?;
+            } else {
+                self.builder.ins().store(MemFlags::new(), value, dst, field.offset as i32);
             }
-
-            Ok(tuple_ptr)
         }
+        Ok(())
     }
 
-    /// 编译 await scope 语句
-    fn compile_await_scope(&mut self, scope_stmt: &bolide_parser::AwaitScopeStmt) -> Result<(), String> {
-        // 进入 scope
-        let scope_enter = *self.func_refs.get("scope_enter")
-            .ok_or("scope_enter not found")?;
-        self.builder.ins().call(scope_enter, &[]);
-
-        // 执行 scope 内的语句
-        for stmt in &scope_stmt.body {
-            self.compile_stmt(stmt)?;
+    /// 要存进 packed 列表的值先整理成栈上的一条记录，返回记录地址：
+    /// T 的实例逐字段复制，另一个 packed 元素整条复制（存入时列表可能重新分配，不能直接引用源元素）
+    fn compile_packed_record(&mut self, value: &Expr, elem: &BolideType, layout: &PackedLayout) -> Result<Value, String> {
+        let class = self.normalize_bolide_type(elem);
+        let value_ty = self.normalize_bolide_type(&self.infer_expr_type(value));
+        let slot = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            layout.size.max(8) as u32,
+            3,
+        ));
+        let record = self.builder.ins().stack_addr(self.ptr_type, slot, 0);
+        match value_ty {
+            BolideType::PackedRef(inner) if *inner == class => {
+                let src = self.compile_expr(value)?;
+                self.emit_copy_record(src, record, layout.size);
+            }
+            ref ty if *ty == class => {
+                let obj = self.compile_expr(value)?;
+                self.emit_pack_object(obj, record, &class, layout)?;
+            }
+            other => {
+                return Err(format!(
+                    "Cannot store a value of type {} in list<packed {}>",
+                    analysis::type_name(&other), analysis::type_name(&class)
+                ));
+            }
         }
+        Ok(record)
+    }
 
-        // 退出 scope（等待所有未完成的 Future）
-        let scope_exit = *self.func_refs.get("scope_exit")
-            .ok_or("scope_exit not found")?;
-        self.builder.ins().call(scope_exit, &[]);
-
+    /// ps[i] = value：整条记录写入元素
+    fn compile_packed_index_assign(&mut self, base: &Expr, elem: &BolideType, index: &Expr, value: &Expr) -> Result<(), String> {
+        let layout = self.packed_layout(elem)?;
+        // 先求值右侧：它可能向列表追加元素，之后才能计算元素地址
+        let record = self.compile_packed_record(value, elem, &layout)?;
+        let list = self.compile_expr(base)?;
+        let index = self.compile_expr(index)?;
+        let dst = self.emit_packed_elem_ptr(list, index, layout.size)?;
+        self.emit_copy_record(record, dst, layout.size);
+        Ok(())
+    }
+
+    /// packed 元素的字段：int/float/bool 按偏移直接读取，类字段得到指向它的元素引用
+    fn compile_packed_member_access(&mut self, base: &Expr, class: &BolideType, member: &str) -> Result<Value, String> {
+        let field = self.packed_field(class, member)?;
+        let elem = self.compile_expr(base)?;
+        if let BolideType::Custom(_) = field.ty {
+            return Ok(self.builder.ins().iadd_imm(elem, field.offset as i64));
+        }
+        let field_ty = self.bolide_type_to_cranelift(&field.ty);
+        Ok(self.builder.ins().load(field_ty, MemFlags::new(), elem, field.offset as i32))
+    }
+
+    /// 写 packed 元素的字段：类字段整条记录写入
+    fn compile_packed_member_assign(&mut self, base: &Expr, class: &BolideType, member: &str, value: &Expr) -> Result<(), String> {
+        let field = self.packed_field(class, member)?;
+        if let BolideType::Custom(_) = field.ty {
+            let inner_class = self.normalize_bolide_type(&field.ty);
+            let layout = self.packed_layout(&inner_class)?;
+            let record = self.compile_packed_record(value, &inner_class, &layout)?;
+            let elem = self.compile_expr(base)?;
+            let dst = self.builder.ins().iadd_imm(elem, field.offset as i64);
+            self.emit_copy_record(record, dst, layout.size);
+        } else {
+            let val = self.compile_expr(value)?;
+            let elem = self.compile_expr(base)?;
+            self.builder.ins().store(MemFlags::new(), val, elem, field.offset as i32);
+        }
+        Ok(())
+    }
+
+    /// 单个 packed 元素转字符串，格式和打印 packed 列表一致
+    fn compile_packed_to_string(&mut self, elem_expr: &Expr, class: &BolideType) -> Result<Value, String> {
+        let layout = self.packed_layout(class)?;
+        let elem = self.compile_expr(elem_expr)?;
+        let (desc, desc_len) = self.emit_packed_descriptor(&layout);
+        let func_ref = self.get_func_ref("packed_to_string")?;
+        let call = self.builder.ins().call(func_ref, &[elem, desc, desc_len]);
+        let result = self.builder.inst_results(call)[0];
+        self.track_temp_rc_value(result, &BolideType::Str);
+        Ok(result)
+    }
+
+    /// packed 列表的方法：len / push / resize / swap
+    ///
+    /// push/resize 可能重新分配缓冲区，swap 交换元素内容，三者都会作废指向列表元素的借用。
+    fn compile_packed_list_method_call(&mut self, base: &Expr, elem: &BolideType, method: &str, args: &[Expr]) -> Result<Value, String> {
+        let arity = match method {
+            "len" => 0,
+            "push" | "resize" => 1,
+            "swap" => 2,
+            _ => return Err(format!("Unknown packed list method: {}; packed lists support len, push, resize and swap", method)),
+        };
+        if args.len() != arity {
+            return Err(format!("{}() expects {} argument(s), got {}", method, arity, args.len()));
+        }
+        let list_name = Self::root_var(base).unwrap_or("list").to_string();
+        match method {
+            "len" => {
+                let list = self.compile_expr(base)?;
+                let len_ref = self.get_func_ref("packed_list_len")?;
+                let call = self.builder.ins().call(len_ref, &[list]);
+                Ok(self.builder.inst_results(call)[0])
+            }
+            "push" => {
+                let layout = self.packed_layout(elem)?;
+                let record = self.compile_packed_record(&args[0], elem, &layout)?;
+                self.invalidate_packed_borrows(base, &format!("{}.push(...)", list_name))?;
+                let list = self.compile_expr(base)?;
+                let push_ref = self.get_func_ref("packed_list_push")?;
+                let call = self.builder.ins().call(push_ref, &[list]);
+                let slot = self.builder.inst_results(call)[0];
+                self.emit_copy_record(record, slot, layout.size);
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            _ => {
+                let mut values = Vec::new();
+                for arg in args {
+                    let arg_ty = self.infer_expr_type(arg);
+                    if arg_ty != BolideType::Int {
+                        return Err(format!("{}() expects int arguments, got {}", method, analysis::type_name(&arg_ty)));
+                    }
+                    values.push(self.compile_expr(arg)?);
+                }
+                self.invalidate_packed_borrows(base, &format!("{}.{}(...)", list_name, method))?;
+                let list = self.compile_expr(base)?;
+                values.insert(0, list);
+                let func_ref = self.get_func_ref(&format!("packed_list_{}", method))?;
+                self.builder.ins().call(func_ref, &values);
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+        }
+    }
+
+    /// for p in ps：按下标遍历，p 是每个元素的借用（循环开始时的长度）
+    fn compile_for_packed(&mut self, vars: &[String], iter: &Expr, body: &[Statement]) -> Result<(), String> {
+        let [var] = vars else {
+            return Err("A for loop over a packed list takes exactly one variable".to_string());
+        };
+        if !matches!(iter, Expr::Ident(_)) {
+            return Err("A for loop over a packed list needs a variable, e.g. for p in particles".to_string());
+        }
+        let index = format!("__packed_index_{}", self.var_counter);
+        let elem = Expr::Index(Box::new(iter.clone()), Box::new(Expr::Ident(index.clone())));
+        let mut loop_body = vec![Statement::VarDecl(VarDecl { name: var.clone(), ty: None, value: Some(elem), thread_local: false })];
+        loop_body.extend_from_slice(body);
+        let len = Expr::Call(Box::new(Expr::Ident("len".to_string())), vec![iter.clone()]);
+        self.compile_for_range(&index, None, &[len], &loop_body)
+    }
+
+    /// 可能移动 packed 列表元素的操作之后，指向其元素的借用不能再使用
+    ///
+    /// 借用在循环外声明、又在当前循环里用过时，下一次迭代就会用到失效的引用，直接报错。
+    fn invalidate_packed_borrows(&mut self, list: &Expr, action: &str) -> Result<(), String> {
+        let Some(root) = Self::root_var(list) else { return Ok(()) };
+        let mut borrowers: Vec<String> = self.borrowed_vars.iter()
+            .filter(|(borrower, (source, _))| {
+                source == root && matches!(self.var_types.get(*borrower), Some(BolideType::PackedRef(_)))
+            })
+            .map(|(borrower, _)| borrower.clone())
+            .collect();
+        borrowers.sort();
+        for borrower in borrowers {
+            let depth = self.var_scope_depth.get(&borrower).copied().unwrap_or(0);
+            if self.loops.iter().any(|frame| depth <= frame.scope_depth && frame.borrows_used.contains(&borrower)) {
+                return Err(format!(
+                    "Lifetime error in function '{}': '{}' borrows an element of '{}' and is used in a loop that calls {}; \
+                     the next iteration would use it after the list's elements moved",
+                    self.current_func_name, borrower, root, action
+                ));
+            }
+            self.invalidated_borrows.insert(borrower, action.to_string());
+        }
+        Ok(())
+    }
+
+    /// 调用函数或方法的实参：packed 元素不是对象，不能传给函数；
+    /// 传入 packed 列表后被调用者可能修改它，作废指向其元素的借用
+    fn check_packed_call_args(&mut self, args: &[Expr], callee: &str) -> Result<(), String> {
+        for arg in args {
+            match self.infer_expr_type(arg) {
+                BolideType::PackedRef(class) => {
+                    return Err(format!(
+                        "An element of a packed list ({}) cannot be passed to '{}'; pass its fields instead",
+                        analysis::type_name(&class), callee
+                    ));
+                }
+                BolideType::PackedList(_) => {
+                    let name = Self::root_var(arg).unwrap_or("list");
+                    self.invalidate_packed_borrows(arg, &format!("passing '{}' to '{}'", name, callee))?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// 成员访问 / 索引链最左边的变量
+    fn root_var(expr: &Expr) -> Option<&str> {
+        match expr {
+            Expr::Ident(name) => Some(name),
+            Expr::Member(base, _) | Expr::Index(base, _) => Self::root_var(base),
+            _ => None,
+        }
+    }
+
+    /// 编译切片 s[a:b] / xs[a:b]：结果是新值，登记为临时值；省略的 start 为 0，省略的 end 到末尾
+    fn compile_slice(&mut self, base: &Expr, start: Option<&Expr>, end: Option<&Expr>) -> Result<Value, String> {
+        let base_type = self.infer_expr_type(base);
+        let func_name = match &base_type {
+            BolideType::Str => "string_slice",
+            BolideType::List(_) => "list_slice",
+            other => return Err(format!("Slicing is not supported for {:?}", other)),
+        };
+        let base_val = self.compile_expr(base)?;
+        let start_val = match start {
+            Some(e) => self.compile_expr(e)?,
+            None => self.builder.ins().iconst(types::I64, 0),
+        };
+        let end_val = match end {
+            Some(e) => self.compile_expr(e)?,
+            None => self.builder.ins().iconst(types::I64, i64::MAX),
+        };
+        let func_ref = self.get_func_ref(func_name)?;
+        let call = self.builder.ins().call(func_ref, &[base_val, start_val, end_val]);
+        let result = self.builder.inst_results(call)[0];
+        self.track_temp_rc_value(result, &self.normalize_bolide_type(&base_type));
+        Ok(result)
+    }
+
+    /// 编译 await all 表达式
+    fn compile_await_all(&mut self, exprs: &[Expr]) -> Result<Value, String> {
+        // 先启动所有协程，收集 Future 指针
+        let mut futures = Vec::new();
+        for expr in exprs {
+            let future_ptr = self.compile_expr(expr)?;
+            futures.push(future_ptr);
+        }
+
+        // 依次等待所有 Future（简单实现）
+        let mut results = Vec::new();
+        for (i, future_ptr) in futures.iter().enumerate() {
+            let expr_type = self.infer_expr_type(&exprs[i]);
+            let await_func_name = match &expr_type {
+                BolideType::Float => "coroutine_await_float",
+                BolideType::Str | BolideType::BigInt | BolideType::Decimal
+                | BolideType::List(_) | BolideType::Custom(_) => "coroutine_await_ptr",
+                _ => "coroutine_await_int",
+            };
+
+            let await_ref = self.get_func_ref(await_func_name)?;
+
+            let call = self.builder.ins().call(await_ref, &[*future_ptr]);
+            let result = self.builder.inst_results(call)[0];
+            results.push(result);
+        }
+
+        // 将结果存储到元组中
+        if results.is_empty() {
+            Ok(self.builder.ins().iconst(types::I64, 0))
+        } else if results.len() == 1 {
+            Ok(results[0])
+        } else {
+            // 使用运行时元组存储所有结果
+            let tuple_new = self.get_func_ref("tuple_new")?;
+            let len = self.builder.ins().iconst(types::I64, results.len() as i64);
+            let call = self.builder.ins().call(tuple_new, &[len]);
+            let tuple_ptr = self.builder.inst_results(call)[0];
+
+            let tuple_set = self.get_func_ref("tuple_set")?;
+            for (i, result) in results.iter().enumerate() {
+                let idx = self.builder.ins().iconst(types::I64, i as i64);
+                self.builder.ins().call(tuple_set, &[tuple_ptr, idx, *result]);
+            }
+
+            Ok(tuple_ptr)
+        }
+    }
+
+    /// 编译 await scope 语句
+    fn compile_await_scope(&mut self, scope_stmt: &bolide_parser::AwaitScopeStmt) -> Result<(), String> {
+        // 块内的 break/continue 不能跳到块外的循环
+        let outer_fence = std::mem::replace(&mut self.loop_fence, self.loops.len());
+        // 进入 scope
+        let scope_enter = self.get_func_ref(if scope_stmt.fail_fast { "scope_enter_fail_fast" } else { "scope_enter" })?;
+        self.builder.ins().call(scope_enter, &[]);
+
+        // 执行 scope 内的语句
+        for stmt in &scope_stmt.body {
+            self.compile_stmt(stmt)?;
+        }
+
+        // 退出 scope（等待所有未完成的 Future，报告子任务的错误）
+        let scope_exit = self.get_func_ref("scope_exit")?;
+        self.builder.ins().call(scope_exit, &[]);
+
+        self.loop_fence = outer_fence;
+        Ok(())
+    }
+
+    /// 编译 measure 块：块前取时间和分配计数，块结束时打印
+    ///
+    /// 块内的 return 由 compile_return 调用 emit_measure_early_exits 补上输出。
+    fn compile_measure(&mut self, measure_stmt: &bolide_parser::MeasureStmt) -> Result<bool, String> {
+        let measure_begin = self.get_func_ref("measure_begin")?;
+        let call = self.builder.ins().call(measure_begin, &[]);
+        let start = self.builder.inst_results(call)[0];
+        let alloc_snapshot = self.get_func_ref("alloc_snapshot")?;
+        let call = self.builder.ins().call(alloc_snapshot, &[]);
+        let alloc_start = self.builder.inst_results(call)[0];
+
+        self.measure_stack.push((measure_stmt.label.clone(), start, alloc_start));
+        self.enter_scope();
+        let mut terminated = false;
+        for stmt in &measure_stmt.body {
+            if terminated { break; }
+            terminated = self.compile_stmt(stmt)?;
+        }
+        self.leave_scope()?;
+        self.measure_stack.pop();
+
+        if !terminated {
+            self.emit_measure_end(&measure_stmt.label, start, alloc_start, false)?;
+        }
+        Ok(terminated)
+    }
+
+    /// 调用 measure_end 打印一个 measure 块的结果
+    fn emit_measure_end(&mut self, label: &str, start: Value, alloc_start: Value, early: bool) -> Result<(), String> {
+        // 标签和字符串字面量一样泄露到堆上，在程序生命周期内有效
+        let bytes: Box<[u8]> = label.as_bytes().into();
+        let ptr = Box::leak(bytes).as_ptr();
+        let ptr_val = self.builder.ins().iconst(self.ptr_type, ptr as i64);
+        let len_val = self.builder.ins().iconst(types::I64, label.len() as i64);
+        let early_val = self.builder.ins().iconst(types::I64, early as i64);
+        let measure_end = self.get_func_ref("measure_end")?;
+        self.builder.ins().call(measure_end, &[ptr_val, len_val, start, alloc_start, early_val]);
+        Ok(())
+    }
+
+    /// return 穿过的 measure 块（由内到外）打印结果，标记为提前返回
+    fn emit_measure_early_exits(&mut self) -> Result<(), String> {
+        let active: Vec<(String, Value, Value)> = self.measure_stack.iter().rev().cloned().collect();
+        for (label, start, alloc_start) in active {
+            self.emit_measure_end(&label, start, alloc_start, true)?;
+        }
+        Ok(())
+    }
+
+    /// 进入循环（跳到循环头之前）：记录 break/continue 的目标，为 loop_count() 准备初值为 0 的隐藏计数器
+    fn push_loop(&mut self, continue_block: Block, exit_block: Block) {
+        let counter = Variable::new(self.var_counter);
+        self.var_counter += 1;
+        self.builder.declare_var(counter, types::I64);
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        self.builder.def_var(counter, zero);
+        self.loops.push(LoopFrame {
+            counter,
+            counter_used: false,
+            continue_block,
+            exit_block,
+            continued: false,
+            measure_depth: self.measure_stack.len(),
+            pool_depth: self.pool_stack.len(),
+            scope_depth: self.scope_depth,
+            borrows_used: HashSet::new(),
+        });
+    }
+
+    /// 循环体编译完后切换到 continue 块；循环体正常走到末尾或有 continue 时返回 true，
+    /// 调用者接着推进循环变量并调用 `emit_loop_back_edge`
+    fn enter_loop_latch(&mut self, body_terminated: bool) -> bool {
+        let frame = self.loops.last().expect("enter_loop_latch outside a loop");
+        let latch = frame.continue_block;
+        let reachable = !body_terminated || frame.continued;
+        if !body_terminated {
+            self.builder.ins().jump(latch, &[]);
+        }
+        self.builder.seal_block(latch);
+        if reachable {
+            self.builder.switch_to_block(latch);
+        }
+        reachable
+    }
+
+    /// 回边：用过 loop_count() 的循环计数加一（没用到的不产生任何指令），然后回到循环头
+    ///
+    /// 开启中断检查时先把 fuel 减一，用完才调用 interrupt_poll，需要中断就提前返回。
+    fn emit_loop_back_edge(&mut self, header_block: Block) -> Result<(), String> {
+        if let Some(&LoopFrame { counter, counter_used: true, .. }) = self.loops.last() {
+            let current = self.builder.use_var(counter);
+            let next = self.builder.ins().iadd_imm(current, 1);
+            self.builder.def_var(counter, next);
+        }
+        if let Some(addr) = self.interrupt_state {
+            let state = self.builder.ins().iconst(self.ptr_type, addr);
+            let fuel = self.builder.ins().load(types::I64, MemFlags::trusted(), state, bolide_runtime::INTERRUPT_FUEL_OFFSET);
+            let fuel = self.builder.ins().iadd_imm(fuel, -1);
+            self.builder.ins().store(MemFlags::trusted(), fuel, state, bolide_runtime::INTERRUPT_FUEL_OFFSET);
+            let poll_block = self.builder.create_block();
+            let abort_block = self.builder.create_block();
+            let exhausted = self.builder.ins().icmp_imm(IntCC::SignedLessThanOrEqual, fuel, 0);
+            self.builder.ins().brif(exhausted, poll_block, &[], header_block, &[]);
+
+            self.builder.switch_to_block(poll_block);
+            self.builder.seal_block(poll_block);
+            let poll = self.get_func_ref("interrupt_poll")?;
+            let call = self.builder.ins().call(poll, &[state]);
+            let interrupted = self.builder.inst_results(call)[0];
+            self.builder.ins().brif(interrupted, abort_block, &[], header_block, &[]);
+
+            self.builder.switch_to_block(abort_block);
+            self.builder.seal_block(abort_block);
+            return self.emit_interrupt_return(state);
+        }
+        self.builder.ins().jump(header_block, &[]);
+        Ok(())
+    }
+
+    /// 可以在编译期求值、复用或提出循环的 `@pure` 函数：参数和返回值都是 int / float / bool，
+    /// 参数都按借用传递
+    fn is_scalar_pure_func(&self, name: &str, argc: usize) -> bool {
+        let Some(func) = self.pure_funcs.get(name) else {
+            return false;
+        };
+        let scalar = |ty: &BolideType| matches!(ty, BolideType::Int | BolideType::Float | BolideType::Bool);
+        func.params.len() == argc
+            && func.params.iter().all(|p| p.mode == ParamMode::Borrow && scalar(&p.ty))
+            && func.return_type.as_ref().is_some_and(scalar)
+    }
+
+    /// 调用 `@pure` 函数：实参都是字面量时在编译期求值；已经提到循环外的调用直接用结果；
+    /// 同一语句里相同实参的调用只生成一次。不适用时返回 None，照常生成调用
+    fn compile_pure_call(&mut self, name: &str, args: &[Expr]) -> Result<Option<Value>, String> {
+        if !self.is_scalar_pure_func(name, args.len()) {
+            return Ok(None);
+        }
+
+        let consts: Option<Vec<Const>> = args.iter().map(Const::from_literal).collect();
+        if let Some(value) = consts.and_then(|consts| comptime::eval_call(self.pure_funcs, name, &consts)) {
+            self.pure_folded += 1;
+            return Ok(Some(match value {
+                Const::Int(n) => self.builder.ins().iconst(types::I64, n),
+                Const::Float(f) => self.builder.ins().f64const(f),
+                Const::Bool(b) => self.builder.ins().iconst(types::I64, b as i64),
+            }));
+        }
+
+        let key = Self::pure_call_key(name, args);
+        for hoisted in self.hoisted_pure_calls.iter_mut().rev() {
+            if let Some((value, used)) = hoisted.get_mut(&key) {
+                if *used {
+                    self.pure_reused += 1;
+                }
+                *used = true;
+                return Ok(Some(*value));
+            }
+        }
+
+        self.emit_pure_call(name, args).map(Some)
+    }
+
+    /// 生成 `@pure` 函数调用；当前块里已经用相同实参调用过时复用那次的结果
+    fn emit_pure_call(&mut self, name: &str, args: &[Expr]) -> Result<Value, String> {
+        let param_types: Vec<BolideType> = self.pure_funcs[name].params.iter().map(|p| p.ty.clone()).collect();
+        let mut arg_values = Vec::with_capacity(args.len());
+        for (arg, ty) in args.iter().zip(&param_types) {
+            arg_values.push(self.compile_expr_expecting(arg, Some(ty))?);
+        }
+
+        let block = self.builder.current_block();
+        let previous = self.pure_calls.iter()
+            .find(|(callee, values, call_block, _)| callee == name && *values == arg_values && Some(*call_block) == block);
+        if let Some(&(_, _, _, value)) = previous {
+            self.pure_reused += 1;
+            return Ok(value);
+        }
+
+        let func_ref = self.get_func_ref(name)?;
+        let call = self.builder.ins().call(func_ref, &arg_values);
+        let result = self.builder.inst_results(call)[0];
+        self.emit_interrupt_check_after_call()?;
+        // 中断检查之后的块只能经由这次调用到达，块里后面的代码都可以使用结果
+        if let Some(block) = self.builder.current_block() {
+            self.pure_calls.push((name.to_string(), arg_values, block, result));
+        }
+        Ok(result)
+    }
+
+    fn pure_call_key(name: &str, args: &[Expr]) -> String {
+        let args: Vec<String> = args.iter().map(purity::expr_text).collect();
+        format!("{}({})", name, args.join(", "))
+    }
+
+    /// 在进入 while 循环之前调用条件里不随循环变化的 `@pure` 函数
+    ///
+    /// 只考虑每次求值条件都会执行的调用，实参必须是字面量或者循环里没有写入的局部变量
+    /// （赋值、声明、for 变量、传给 ref / owned 参数或方法调用都算写入）。
+    fn hoist_pure_calls(&mut self, cond: &Expr, body: &[Statement]) -> Result<HashMap<String, (Value, bool)>, String> {
+        let mut hoisted = HashMap::new();
+        let mut calls = Vec::new();
+        purity::unconditional_calls(cond, &mut calls);
+        calls.retain(|(name, args)| self.is_scalar_pure_func(name, args.len()));
+        if calls.is_empty() {
+            return Ok(hoisted);
+        }
+
+        let func_params = &self.func_params;
+        let written = purity::loop_written_vars(cond, body, |func, i| {
+            func_params.get(func).and_then(|params| params.get(i)).is_some_and(|p| p.mode != ParamMode::Borrow)
+        });
+        for (name, args) in calls {
+            let invariant = args.iter().all(|arg| match arg {
+                Expr::Ident(var) => self.variables.contains_key(var) && !written.contains(var),
+                _ => Const::from_literal(arg).is_some(),
+            });
+            // 实参全是字面量的调用在编译期求值
+            let constant = args.iter().all(|arg| Const::from_literal(arg).is_some());
+            let key = Self::pure_call_key(name, args);
+            if !invariant || constant || hoisted.contains_key(&key) {
+                continue;
+            }
+            let value = self.emit_pure_call(name, args)?;
+            hoisted.insert(key, (value, false));
+            self.pure_hoisted += 1;
+        }
+        Ok(hoisted)
+    }
+
+    /// 调用用户函数之后：被调用者因中断提前返回时，当前函数也提前返回
+    fn emit_interrupt_check_after_call(&mut self) -> Result<(), String> {
+        let Some(addr) = self.interrupt_state else {
+            return Ok(());
+        };
+        let state = self.builder.ins().iconst(self.ptr_type, addr);
+        let pending = self.builder.ins().load(types::I64, MemFlags::trusted(), state, bolide_runtime::INTERRUPT_PENDING_OFFSET);
+        let abort_block = self.builder.create_block();
+        let continue_block = self.builder.create_block();
+        self.builder.ins().brif(pending, abort_block, &[], continue_block, &[]);
+
+        self.builder.switch_to_block(abort_block);
+        self.builder.seal_block(abort_block);
+        self.emit_interrupt_return(state)?;
+
+        self.builder.switch_to_block(continue_block);
+        self.builder.seal_block(continue_block);
+        Ok(())
+    }
+
+    /// 中断时的提前返回：与不带值的 return 做同样的清理，然后返回零值
+    ///
+    /// 语句临时值在这条分支上释放但仍留在列表里，正常路径照常处理它们。
+    fn emit_interrupt_return(&mut self, state: Value) -> Result<(), String> {
+        let unwind = self.get_func_ref("interrupt_unwind")?;
+        self.builder.ins().call(unwind, &[state]);
+        self.emit_pool_early_exits(0)?;
+        if !self.uses_lifetime_mode() {
+            for (val, ty) in self.temp_rc_values.clone() {
+                self.emit_release(val, &ty);
+            }
+            self.emit_rc_cleanup();
+        }
+        self.emit_measure_early_exits()?;
+        self.emit_list_iter_early_exits()?;
+        self.emit_trace_pop()?;
+        self.emit_main_flush()?;
+        let returns: Vec<types::Type> = self.builder.func.signature.returns.iter().map(|r| r.value_type).collect();
+        let zeros: Vec<Value> = returns.into_iter().map(|ty| match ty {
+            types::F64 => self.builder.ins().f64const(0.0),
+            ty => self.builder.ins().iconst(ty, 0),
+        }).collect();
+        self.builder.ins().return_(&zeros);
+        Ok(())
+    }
+
+    /// 编译 break / continue：拆除循环体内的 pool 块、结束 measure 块，跳到最内层循环的出口或 continue 块
+    ///
+    /// 循环体内声明的 RC 变量在进入循环前就初始化并登记，由下一次赋值或函数结束释放，
+    /// 这里只需释放当前语句的临时值；遍历中的列表迭代器在循环出口释放。
+    fn compile_loop_jump(&mut self, is_break: bool) -> Result<(), String> {
+        let keyword = if is_break { "break" } else { "continue" };
+        if self.loops.len() <= self.loop_fence {
+            return Err(if self.loops.is_empty() {
+                format!("'{}' can only be used inside a while or for loop", keyword)
+            } else {
+                format!("'{}' cannot jump out of a select or await scope block", keyword)
+            });
+        }
+        let frame = self.loops.last_mut().expect("checked above");
+        let target = if is_break {
+            frame.exit_block
+        } else {
+            frame.continued = true;
+            frame.continue_block
+        };
+        let (measure_depth, pool_depth) = (frame.measure_depth, frame.pool_depth);
+
+        self.emit_pool_early_exits(pool_depth)?;
+        self.release_temp_rc_values();
+        let crossed: Vec<(String, Value, Value)> = self.measure_stack[measure_depth..].iter().rev().cloned().collect();
+        for (label, start, alloc_start) in crossed {
+            self.emit_measure_end(&label, start, alloc_start, true)?;
+        }
+        self.builder.ins().jump(target, &[]);
+        Ok(())
+    }
+
+    /// 函数入口：把函数名和定义所在的行压入影子调用栈（调试模式）
+    ///
+    /// 函数名放在只读数据里，和生成的代码活得一样久。
+    fn emit_trace_push(&mut self, name: &str, line: usize) -> Result<(), String> {
+        let data_id = self.module.declare_anonymous_data(false, false)
+            .map_err(|e| format!("Failed to declare trace name: {}", e))?;
+        let mut desc = DataDescription::new();
+        desc.define(name.as_bytes().to_vec().into_boxed_slice());
+        self.module.define_data(data_id, &desc)
+            .map_err(|e| format!("Failed to define trace name: {}", e))?;
+        let gv = self.module.declare_data_in_func(data_id, self.builder.func);
+
+        let push = self.get_func_ref("trace_push")?;
+        let name_ptr = self.builder.ins().global_value(self.ptr_type, gv);
+        let name_len = self.builder.ins().iconst(self.ptr_type, name.len() as i64);
+        let line = self.builder.ins().iconst(types::I64, line as i64);
+        self.builder.ins().call(push, &[name_ptr, name_len, line]);
+        self.trace_frame = true;
+        Ok(())
+    }
+
+    /// 返回前弹出入口压入的影子调用栈帧；没有压入时不生成调用
+    fn emit_trace_pop(&mut self) -> Result<(), String> {
+        if self.trace_frame {
+            let pop = self.get_func_ref("trace_pop")?;
+            self.builder.ins().call(pop, &[]);
+        }
+        Ok(())
+    }
+
+    /// `__main__` 返回前刷新输出缓冲区：嵌入方直接调用编译出的入口时，返回后也能看到全部输出
+    fn emit_main_flush(&mut self) -> Result<(), String> {
+        if self.current_func_name == "__main__" {
+            let flush = self.get_func_ref("print_flush")?;
+            self.builder.ins().call(flush, &[]);
+        }
+        Ok(())
+    }
+
+    /// 提前 return 时释放所有正在使用的列表迭代器和循环持有的临时容器
+    fn emit_list_iter_early_exits(&mut self) -> Result<(), String> {
+        let active: Vec<Value> = self.list_iter_stack.iter().rev().copied().collect();
+        for iter in active {
+            let free_ref = self.get_func_ref("list_iter_free")?;
+            self.builder.ins().call(free_ref, &[iter]);
+        }
+        for (val, ty) in self.loop_temps.concat().into_iter().rev() {
+            self.emit_release(val, &ty);
+        }
         Ok(())
     }
 
@@ -6293,6 +11701,8 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         if select_stmt.branches.is_empty() {
             return Ok(());
         }
+        // 块内的 break/continue 不能跳到块外的循环
+        let outer_fence = std::mem::replace(&mut self.loop_fence, self.loops.len());
 
         let branch_count = select_stmt.branches.len();
 
@@ -6323,8 +11733,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         }
 
         // 4. 调用 select_wait_first 获取第一个完成的索引
-        let select_wait_first = *self.func_refs.get("select_wait_first")
-            .ok_or("select_wait_first not found")?;
+        let select_wait_first = self.get_func_ref("select_wait_first")?;
         let count = self.builder.ins().iconst(types::I64, branch_count as i64);
         let call = self.builder.ins().call(select_wait_first, &[array_ptr, count]);
         let winner_idx = self.builder.inst_results(call)[0];
@@ -6332,6 +11741,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         // 5. 根据获胜索引执行对应分支
         self.compile_select_branches(select_stmt, &futures, winner_idx)?;
 
+        self.loop_fence = outer_fence;
         Ok(())
     }
 
@@ -6361,8 +11771,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             match branch {
                 AsyncSelectBranch::Bind { var, body, .. } => {
                     // await 获取结果并绑定变量
-                    let await_int = *self.func_refs.get("coroutine_await_int")
-                        .ok_or("coroutine_await_int not found")?;
+                    let await_int = self.get_func_ref("coroutine_await_int")?;
                     let call = self.builder.ins().call(await_int, &[futures[i]]);
                     let result = self.builder.inst_results(call)[0];
 
@@ -6425,8 +11834,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         };
 
         // 先检查是否在线程池上下文
-        let pool_is_active_ref = *self.func_refs.get("pool_is_active")
-            .ok_or("pool_is_active not found")?;
+        let pool_is_active_ref = self.get_func_ref("pool_is_active")?;
         let is_active_call = self.builder.ins().call(pool_is_active_ref, &[]);
         let is_active = self.builder.inst_results(is_active_call)[0];
 
@@ -6445,8 +11853,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         self.builder.switch_to_block(pool_block);
         self.builder.seal_block(pool_block);
         let pool_join_name = format!("pool_join{}", type_suffix);
-        let pool_join_ref = *self.func_refs.get(&pool_join_name)
-            .ok_or(format!("{} not found", pool_join_name))?;
+        let pool_join_ref = self.get_func_ref(&pool_join_name).map_err(|_| format!("{} not found", pool_join_name))?;
         let pool_call = self.builder.ins().call(pool_join_ref, &[handle]);
         let pool_result = self.builder.inst_results(pool_call)[0];
         self.builder.ins().jump(merge_block, &[pool_result]);
@@ -6455,8 +11862,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         self.builder.switch_to_block(thread_block);
         self.builder.seal_block(thread_block);
         let thread_join_name = format!("thread_join{}", type_suffix);
-        let thread_join_ref = *self.func_refs.get(&thread_join_name)
-            .ok_or(format!("{} not found", thread_join_name))?;
+        let thread_join_ref = self.get_func_ref(&thread_join_name).map_err(|_| format!("{} not found", thread_join_name))?;
         let thread_call = self.builder.ins().call(thread_join_ref, &[handle]);
         let thread_result = self.builder.inst_results(thread_call)[0];
         self.builder.ins().jump(merge_block, &[thread_result]);
@@ -6476,20 +11882,81 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         Ok(result)
     }
 
+    /// 编译 join_ok(h)：等待任务结束，返回 (ok, 结果)
+    ///
+    /// 任务留下了错误时 ok 为 false，错误记录为当前线程的错误（`error()` / `error_message()`），
+    /// 不像 join 那样结束程序。
+    fn compile_join_ok(&mut self, handle_expr: &Expr) -> Result<Value, String> {
+        if !matches!(handle_expr, Expr::Ident(_)) {
+            return Err("join_ok expects a variable holding a spawn handle".to_string());
+        }
+        let handle = self.compile_expr(handle_expr)?;
+
+        // 和 join 一样按是否在线程池上下文选择句柄的种类
+        let pool_is_active_ref = self.get_func_ref("pool_is_active")?;
+        let is_active_call = self.builder.ins().call(pool_is_active_ref, &[]);
+        let is_active = self.builder.inst_results(is_active_call)[0];
+
+        let pool_block = self.builder.create_block();
+        let thread_block = self.builder.create_block();
+        let merge_block = self.builder.create_block();
+        self.builder.append_block_param(merge_block, types::I64);
+        self.builder.ins().brif(is_active, pool_block, &[], thread_block, &[]);
+
+        for (block, take_error) in [(pool_block, "pool_take_error"), (thread_block, "thread_take_error")] {
+            self.builder.switch_to_block(block);
+            self.builder.seal_block(block);
+            let take_error_ref = self.get_func_ref(take_error)?;
+            let call = self.builder.ins().call(take_error_ref, &[handle]);
+            let failed = self.builder.inst_results(call)[0];
+            self.builder.ins().jump(merge_block, &[failed]);
+        }
+
+        self.builder.switch_to_block(merge_block);
+        self.builder.seal_block(merge_block);
+        let failed = self.builder.block_params(merge_block)[0];
+        let ok = self.builder.ins().icmp_imm(IntCC::Equal, failed, 0);
+        let ok = self.builder.ins().uextend(types::I64, ok);
+
+        // 错误已经取走，join 只取结果
+        let value = self.compile_join(handle_expr)?;
+        let join = Expr::Call(Box::new(Expr::Ident("join".to_string())), vec![handle_expr.clone()]);
+        let value_ty = self.infer_expr_type(&join);
+        let value = if Self::is_rc_type(&value_ty) {
+            // 元组接管结果
+            self.remove_temp_rc_value(value);
+            value
+        } else if value_ty == BolideType::Float {
+            self.builder.ins().bitcast(types::I64, MemFlags::new(), value)
+        } else {
+            value
+        };
+
+        let tuple_new = self.get_func_ref("tuple_new")?;
+        let len = self.builder.ins().iconst(types::I64, 2);
+        let call = self.builder.ins().call(tuple_new, &[len]);
+        let tuple_ptr = self.builder.inst_results(call)[0];
+        let tuple_set = self.get_func_ref("tuple_set")?;
+        for (i, slot) in [ok, value].into_iter().enumerate() {
+            let idx = self.builder.ins().iconst(types::I64, i as i64);
+            self.builder.ins().call(tuple_set, &[tuple_ptr, idx, slot]);
+        }
+        self.track_temp_rc_value(tuple_ptr, &BolideType::Tuple(vec![BolideType::Bool, value_ty]));
+        Ok(tuple_ptr)
+    }
+
     /// 编译 channel 函数 - 创建通道
     fn compile_channel_create(&mut self, args: &[Expr]) -> Result<Value, String> {
         if args.is_empty() {
             // 无缓冲通道: channel_create()
-            let channel_create_ref = *self.func_refs.get("channel_create")
-                .ok_or("channel_create not found")?;
+            let channel_create_ref = self.get_func_ref("channel_create")?;
             let call = self.builder.ins().call(channel_create_ref, &[]);
             let channel_ptr = self.builder.inst_results(call)[0];
             Ok(channel_ptr)
         } else if args.len() == 1 {
             // 带缓冲通道: channel_create_buffered(capacity)
             let capacity = self.compile_expr(&args[0])?;
-            let channel_create_buffered_ref = *self.func_refs.get("channel_create_buffered")
-                .ok_or("channel_create_buffered not found")?;
+            let channel_create_buffered_ref = self.get_func_ref("channel_create_buffered")?;
             let call = self.builder.ins().call(channel_create_buffered_ref, &[capacity]);
             let channel_ptr = self.builder.inst_results(call)[0];
             Ok(channel_ptr)
@@ -6500,25 +11967,19 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
     /// 编译成员访问 (obj.field)
     fn compile_member_access(&mut self, base: &Expr, member: &str) -> Result<Value, String> {
-        // 特殊处理模块成员访问
-        if let Expr::Ident(name) = base {
-            // 检查是否是模块名
-            if self.modules.contains_key(name) {
-                let global_name = format!("@{}_{}", name, member);
-                if let Some(&data_id) = self.global_data_ids.get(&global_name) {
-                    // 获取全局变量的地址
-                    let gv = self.module.declare_data_in_func(data_id, self.builder.func);
-                    let addr = self.builder.ins().global_value(self.ptr_type, gv);
-                    // 从地址加载值
-                    let val = self.builder.ins().load(self.ptr_type, MemFlags::new(), addr, 0);
-                    return Ok(val);
-                } else {
-                     return Err(format!("DEBUG: Global not found: {}, in module: {}, keys: {:?}, modules: {:?}", global_name, name, self.global_data_ids.keys().take(10).collect::<Vec<_>>(), self.modules.keys().collect::<Vec<_>>()));
-                }
-            }
+        // 模块成员：全局变量读取当前值，函数当作函数值
+        if let Some(resolved) = modules::resolve_member(&self.modules, base, member) {
+            return match resolved? {
+                Member::Global(name) | Member::Function(name) => self.compile_expr(&Expr::Ident(name)),
+                Member::Class(_) => Err(format!("'{}' is a class; call it to construct an instance", modules::qualified_name(base, member))),
+            };
         }
 
         let base_type = self.get_expr_type(base)?;
+        if let BolideType::PackedRef(class) = &base_type {
+            let class = self.normalize_bolide_type(class);
+            return self.compile_packed_member_access(base, &class, member);
+        }
         // 处理 Weak/Unowned 类型，提取内部的 Custom 类型
         let class_name = match &base_type {
             BolideType::Custom(name) => name.clone(),
@@ -6548,15 +12009,30 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             .ok_or_else(|| format!("Field '{}' not found in class '{}'", member, class_name))?;
 
         let field_offset = field.offset;
+        // float 字段按 f64 存储，其余都是 8 字节整数或指针
+        let field_ty = self.bolide_type_to_cranelift(&field.ty);
         let obj_ptr = self.compile_expr(base)?;
         let field_ptr = self.builder.ins().iadd_imm(obj_ptr, field_offset as i64);
-        let value = self.builder.ins().load(types::I64, MemFlags::new(), field_ptr, 0);
+        let value = self.builder.ins().load(field_ty, MemFlags::new(), field_ptr, 0);
 
         Ok(value)
     }
 
     /// 获取表达式的类型
     fn get_expr_type(&self, expr: &Expr) -> Result<BolideType, String> {
+        // 模块成员按导入后的名字处理
+        if let Expr::Call(callee, args) = expr {
+            if let Expr::Member(base, member) = callee.as_ref() {
+                if let Some(resolved) = modules::resolve_member(&self.modules, base, member) {
+                    return match resolved? {
+                        Member::Class(name) => Ok(BolideType::Custom(name)),
+                        Member::Function(name) | Member::Global(name) => {
+                            self.get_expr_type(&Expr::Call(Box::new(Expr::Ident(name)), args.clone()))
+                        }
+                    };
+                }
+            }
+        }
         match expr {
             Expr::Ident(name) => {
                 if let Some(ty) = self.var_types.get(name) {
@@ -6576,23 +12052,29 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                         .cloned()
                         .flatten()
                         .ok_or_else(|| format!("Unknown function return type: {}", func_name))
+                } else if matches!(callee.as_ref(), Expr::Member(..)) {
+                    // 方法调用的结果，如 s.split(",").len()
+                    match self.infer_expr_type(expr) {
+                        BolideType::Int => Err("Cannot determine type of method call result".to_string()),
+                        ty => Ok(ty),
+                    }
                 } else {
                     Err("Cannot determine type of indirect call".to_string())
                 }
             }
             Expr::Member(base, member) => {
-                // 特殊处理模块成员访问
-                if let Expr::Ident(name) = base.as_ref() {
-                    // 检查是否是模块名
-                    if self.modules.contains_key(name) {
-                        let global_name = format!("@{}_{}", name, member);
-                        if let Some(ty) = self.global_var_types.get(&global_name) {
-                            return Ok(ty.clone());
-                        }
-                    }
+                // 模块成员：全局变量的类型
+                if let Some(resolved) = modules::resolve_member(&self.modules, base, member) {
+                    return match resolved? {
+                        Member::Global(name) => self.get_expr_type(&Expr::Ident(name)),
+                        _ => Err(format!("Cannot determine type of '{}'", member)),
+                    };
                 }
 
                 let base_type = self.get_expr_type(base)?;
+                if let BolideType::PackedRef(_) = base_type {
+                    return Ok(self.infer_expr_type(expr));
+                }
                 // 处理 Weak/Unowned 类型，提取内部的 Custom 类型
                 let class_name = match &base_type {
                     BolideType::Custom(name) => name.clone(),
@@ -6619,34 +12101,31 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                     .ok_or_else(|| format!("Field '{}' not found in class '{}'", member, class_name))?;
                 Ok(field.ty.clone())
             }
+            // d[k].push(x)、xs[i].method()：元素类型
+            Expr::Index(base, _) => match self.get_expr_type(base)? {
+                BolideType::List(elem) => Ok(*elem),
+                BolideType::PackedList(elem) => Ok(BolideType::PackedRef(elem)),
+                BolideType::Dict(_, val) => Ok(*val),
+                other => Err(format!("Cannot call a method on an element of {:?}", other)),
+            },
             _ => Err("Cannot determine expression type".to_string()),
         }
     }
 
-    /// 编译模块函数调用 (module.func())
-    fn compile_module_call(&mut self, func_name: &str, args: &[Expr]) -> Result<Value, String> {
-        let func_ref = *self.func_refs.get(func_name)
-            .ok_or_else(|| format!("Undefined function: {}", func_name))?;
-
-        // 编译参数
-        let mut arg_values = Vec::new();
-        for arg in args {
-            arg_values.push(self.compile_expr(arg)?);
+    /// 编译方法调用 (obj.method(args))
+    fn compile_method_call(&mut self, base: &Expr, method_name: &str, args: &[Expr]) -> Result<Value, String> {
+        // Decimal 方法（基础表达式可以是算术结果，如 (a / b).round(2)）
+        if matches!(self.infer_expr_type(base), BolideType::Decimal) {
+            let value = self.compile_expr(base)?;
+            return self.compile_decimal_method_call(value, method_name, args);
         }
 
-        // 调用函数
-        let call = self.builder.ins().call(func_ref, &arg_values);
-        let results = self.builder.inst_results(call);
-
-        if results.is_empty() {
-            Ok(self.builder.ins().iconst(types::I64, 0))
-        } else {
-            Ok(results[0])
+        // 字符串方法（基础表达式可以是字面量或另一个字符串方法的结果，如 s.trim().split(",")）
+        if matches!(self.infer_expr_type(base), BolideType::Str) {
+            let value = self.compile_expr(base)?;
+            return self.compile_string_method_call(value, method_name, args);
         }
-    }
 
-    /// 编译方法调用 (obj.method(args))
-    fn compile_method_call(&mut self, base: &Expr, method_name: &str, args: &[Expr]) -> Result<Value, String> {
         // 获取对象类型
         let class_name = self.get_expr_type(base)?;
 
@@ -6656,15 +12135,13 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             match method_name {
                 "close" | "cancel" => {
                     // 调用 thread_cancel
-                    let cancel_ref = *self.func_refs.get("thread_cancel")
-                        .ok_or("thread_cancel not found")?;
+                    let cancel_ref = self.get_func_ref("thread_cancel")?;
                     self.builder.ins().call(cancel_ref, &[handle]);
                     return Ok(self.builder.ins().iconst(types::I64, 0));
                 }
                 "is_cancelled" => {
                     // 调用 thread_is_cancelled
-                    let is_cancelled_ref = *self.func_refs.get("thread_is_cancelled")
-                        .ok_or("thread_is_cancelled not found")?;
+                    let is_cancelled_ref = self.get_func_ref("thread_is_cancelled")?;
                     let call = self.builder.ins().call(is_cancelled_ref, &[handle]);
                     return Ok(self.builder.inst_results(call)[0]);
                 }
@@ -6672,18 +12149,43 @@ impl<'a, 'b> CompileContext<'a, 'b> {
             }
         }
 
+        // 检查是否是 Channel 类型的方法调用
+        if let BolideType::Channel(elem) = &class_name {
+            let elem = self.normalize_bolide_type(elem);
+            let channel_ptr = self.compile_expr(base)?;
+            return self.compile_channel_method_call(channel_ptr, &elem, method_name, args);
+        }
+
         // 检查是否是 List 类型的方法调用
-        if matches!(class_name, BolideType::List(_)) {
+        if let BolideType::List(elem) = &class_name {
+            let elem = self.normalize_bolide_type(elem);
             let list_ptr = self.compile_expr(base)?;
-            return self.compile_list_method_call(list_ptr, method_name, args);
+            return self.compile_list_method_call(list_ptr, &elem, method_name, args);
+        }
+
+        if let BolideType::PackedList(elem) = &class_name {
+            let elem = self.normalize_bolide_type(elem);
+            return self.compile_packed_list_method_call(base, &elem, method_name, args);
+        }
+        if let BolideType::PackedRef(elem) = &class_name {
+            return Err(format!(
+                "Cannot call method '{}' on an element of a packed list ({}); read or write its fields instead",
+                method_name, analysis::type_name(elem)
+            ));
         }
 
         // 检查是否是 Dict 类型的方法调用
-        if matches!(class_name, BolideType::Dict(_, _)) {
+        if let BolideType::Dict(..) = &class_name {
+            let dict_ty = self.normalize_bolide_type(&class_name);
             let dict_ptr = self.compile_expr(base)?;
-            return self.compile_dict_method_call(dict_ptr, method_name, args);
+            return self.compile_dict_method_call(dict_ptr, &dict_ty, method_name, args);
         }
 
+        if let BolideType::WeakDict(..) = &class_name {
+            let dict_ty = self.normalize_bolide_type(&class_name);
+            let dict_ptr = self.compile_expr(base)?;
+            return self.compile_weak_dict_method_call(dict_ptr, &dict_ty, method_name, args);
+        }
 
         let class_name = match class_name {
             BolideType::Custom(name) => name,
@@ -6693,10 +12195,16 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
         // 查找方法（支持继承链）
         let full_method_name = self.find_method(&class_name, method_name)?;
+        // 参数表的第一个是隐式 self
+        if let Some(params) = self.func_params.get(&full_method_name) {
+            if let Some(args) = call_args::positional_args(method_name, &params[1..], args)? {
+                return self.compile_method_call(base, method_name, &args);
+            }
+        }
+        self.check_packed_call_args(args, &full_method_name)?;
 
         // 获取方法引用
-        let func_ref = *self.func_refs.get(&full_method_name)
-            .ok_or_else(|| format!("Method '{}' not found", full_method_name))?;
+        let func_ref = self.get_func_ref(&full_method_name).map_err(|_| format!("Method '{}' not found", full_method_name))?;
 
         // 编译 self 参数（对象指针）
         let self_val = self.compile_expr(base)?;
@@ -6709,37 +12217,168 @@ impl<'a, 'b> CompileContext<'a, 'b> {
 
         // 调用方法
         let call = self.builder.ins().call(func_ref, &arg_values);
+        self.emit_interrupt_check_after_call()?;
         let results = self.builder.inst_results(call);
 
         if results.is_empty() {
             Ok(self.builder.ins().iconst(types::I64, 0))
         } else {
-            Ok(results[0])
+            let result = results[0];
+            // 返回 RC 类型的方法（包括 return self）交出一份所有权，和函数调用一样跟踪为临时值
+            if !self.is_lifetime_func(&full_method_name) {
+                if let Some(Some(ret_ty)) = self.func_return_types.get(&full_method_name).cloned() {
+                    if Self::is_rc_type(&ret_ty) {
+                        self.track_temp_rc_value(result, &ret_ty);
+                    }
+                }
+            }
+            Ok(result)
+        }
+    }
+
+    /// 编译字符串方法调用，返回的字符串和列表都是新值
+    fn compile_string_method_call(&mut self, value: Value, method_name: &str, args: &[Expr]) -> Result<Value, String> {
+        let (func_name, arity, result_ty) = match method_name {
+            // split(sep) -> list<str>；不带参数时按空白拆分
+            "split" => ("string_split", 1, Some(BolideType::List(Box::new(BolideType::Str)))),
+            "trim" | "strip" => ("string_trim", 0, Some(BolideType::Str)),
+            "to_upper" | "upper" => ("string_upper", 0, Some(BolideType::Str)),
+            "to_lower" | "lower" => ("string_lower", 0, Some(BolideType::Str)),
+            "contains" => ("string_contains", 1, None),
+            "replace" => ("string_replace", 2, Some(BolideType::Str)),
+            // encode(encoding) -> list<int>
+            "encode" => ("string_encode", 1, Some(BolideType::List(Box::new(BolideType::Int)))),
+            _ => return Err(format!("Unknown string method: {}", method_name)),
+        };
+        let optional = method_name == "split" && args.is_empty();
+        if args.len() != arity && !optional {
+            return Err(format!("{} expects {} argument(s)", method_name, arity));
+        }
+        let mut arg_values = vec![value];
+        for arg in args {
+            arg_values.push(self.compile_expr(arg)?);
+        }
+        if optional {
+            arg_values.push(self.builder.ins().iconst(self.ptr_type, 0));
+        }
+        let func_ref = self.get_func_ref(func_name)?;
+        let call = self.builder.ins().call(func_ref, &arg_values);
+        let result = self.builder.inst_results(call)[0];
+        if let Some(ty) = result_ty {
+            self.track_temp_rc_value(result, &ty);
         }
+        Ok(result)
+    }
+
+    /// round(x, digits = 0) / floor(x) / ceil(x)：结果与 x 同类型
+    ///
+    /// decimal 调用运行时函数，float 的 floor / ceil 用 Cranelift 指令、round 按 decimal 的值舍入，
+    /// int 原样返回。round 使用当前线程的舍入模式。
+    fn compile_rounding_call(&mut self, func_name: &str, args: &[Expr]) -> Result<Value, String> {
+        let max_args = if func_name == "round" { 2 } else { 1 };
+        if args.is_empty() || args.len() > max_args {
+            return Err(format!("{} expects {} argument(s)", func_name, if max_args == 2 { "1 or 2" } else { "1" }));
+        }
+        let ty = self.infer_expr_type(&args[0]);
+        let value = self.compile_expr(&args[0])?;
+        let digits = match args.get(1) {
+            Some(arg) => {
+                let digits_ty = self.infer_expr_type(arg);
+                if digits_ty != BolideType::Int {
+                    return Err(format!("round expects an int digit count, got {:?}", digits_ty));
+                }
+                self.compile_expr(arg)?
+            }
+            None => self.builder.ins().iconst(types::I64, 0),
+        };
+        match (&ty, func_name) {
+            (BolideType::Decimal, _) => {
+                let func_ref = self.get_func_ref(&format!("decimal_{}", func_name))?;
+                let call = if func_name == "round" {
+                    self.builder.ins().call(func_ref, &[value, digits])
+                } else {
+                    self.builder.ins().call(func_ref, &[value])
+                };
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::Decimal);
+                Ok(result)
+            }
+            (BolideType::Float, "round") => {
+                let func_ref = self.get_func_ref("float_round")?;
+                let call = self.builder.ins().call(func_ref, &[value, digits]);
+                Ok(self.builder.inst_results(call)[0])
+            }
+            (BolideType::Float, "floor") => Ok(self.builder.ins().floor(value)),
+            (BolideType::Float, _) => Ok(self.builder.ins().ceil(value)),
+            (BolideType::Int, _) => Ok(value),
+            _ => Err(format!("{} expects an int, float or decimal argument, got {:?}", func_name, ty)),
+        }
+    }
+
+    /// 编译 Decimal 方法调用
+    fn compile_decimal_method_call(&mut self, value: Value, method_name: &str, args: &[Expr]) -> Result<Value, String> {
+        match method_name {
+            // round(places = 0, mode = 当前线程模式) -> decimal
+            "round" => {
+                if args.len() > 2 {
+                    return Err("round expects at most 2 arguments".to_string());
+                }
+                let places = match args.first() {
+                    Some(arg) => self.compile_expr(arg)?,
+                    None => self.builder.ins().iconst(types::I64, 0),
+                };
+                let mode = match args.get(1) {
+                    Some(arg) => self.compile_rounding_mode(arg)?,
+                    None => self.builder.ins().iconst(types::I64, -1),
+                };
+                let func_ref = self.get_func_ref("decimal_round_dp_mode")?;
+                let call = self.builder.ins().call(func_ref, &[value, places, mode]);
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::Decimal);
+                Ok(result)
+            }
+            _ => Err(format!("Unknown decimal method: {}", method_name)),
+        }
+    }
+
+    /// 编译舍入模式参数：字符串字面量在编译期解析为模式常量，其他表达式按 int 传递
+    fn compile_rounding_mode(&mut self, expr: &Expr) -> Result<Value, String> {
+        if let Expr::String(name) = expr {
+            let mode = bolide_runtime::rounding_mode_from_name(name).ok_or_else(|| format!(
+                "Unknown rounding mode \"{}\" (expected half_up, half_even, down, up, floor or ceiling)",
+                name
+            ))?;
+            return Ok(self.builder.ins().iconst(types::I64, mode));
+        }
+        if !matches!(self.infer_expr_type(expr), BolideType::Int) {
+            return Err("Rounding mode must be a string literal or an int".to_string());
+        }
+        self.compile_expr(expr)
     }
 
     /// 编译列表方法调用
-    fn compile_list_method_call(&mut self, list_ptr: Value, method_name: &str, args: &[Expr]) -> Result<Value, String> {
+    fn compile_list_method_call(&mut self, list_ptr: Value, elem_ty: &BolideType, method_name: &str, args: &[Expr]) -> Result<Value, String> {
         match method_name {
             // push(value) -> void
             "push" | "append" => {
                 if args.len() != 1 {
                     return Err(format!("{} expects 1 argument", method_name));
                 }
-                let value = self.compile_expr(&args[0])?;
-                let func_ref = *self.func_refs.get("list_push").ok_or("list_push not found")?;
-                self.builder.ins().call(func_ref, &[list_ptr, value]);
+                self.check_not_packed_elem(&args[0])?;
+                let value = self.compile_list_elem_arg(&args[0], elem_ty)?;
+                self.emit_list_push(list_ptr, value)?;
                 Ok(self.builder.ins().iconst(types::I64, 0))
             }
             // pop() -> value
             "pop" => {
-                let func_ref = *self.func_refs.get("list_pop").ok_or("list_pop not found")?;
+                let func_ref = self.get_func_ref("list_pop")?;
                 let call = self.builder.ins().call(func_ref, &[list_ptr]);
-                Ok(self.builder.inst_results(call)[0])
+                let slot = self.builder.inst_results(call)[0];
+                Ok(self.slot_to_elem(slot, elem_ty))
             }
             // len() -> int
             "len" | "length" | "size" => {
-                let func_ref = *self.func_refs.get("list_len").ok_or("list_len not found")?;
+                let func_ref = self.get_func_ref("list_len")?;
                 let call = self.builder.ins().call(func_ref, &[list_ptr]);
                 Ok(self.builder.inst_results(call)[0])
             }
@@ -6749,9 +12388,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                     return Err("get expects 1 argument".to_string());
                 }
                 let index = self.compile_expr(&args[0])?;
-                let func_ref = *self.func_refs.get("list_get").ok_or("list_get not found")?;
-                let call = self.builder.ins().call(func_ref, &[list_ptr, index]);
-                Ok(self.builder.inst_results(call)[0])
+                self.emit_list_get(list_ptr, index, elem_ty)
             }
             // set(index, value) -> bool
             "set" => {
@@ -6759,10 +12396,8 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                     return Err("set expects 2 arguments".to_string());
                 }
                 let index = self.compile_expr(&args[0])?;
-                let value = self.compile_expr(&args[1])?;
-                let func_ref = *self.func_refs.get("list_set").ok_or("list_set not found")?;
-                let call = self.builder.ins().call(func_ref, &[list_ptr, index, value]);
-                Ok(self.builder.inst_results(call)[0])
+                let value = self.compile_list_elem_arg(&args[1], elem_ty)?;
+                self.emit_list_set(list_ptr, index, value)
             }
             // insert(index, value) -> void
             "insert" => {
@@ -6770,8 +12405,9 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                     return Err("insert expects 2 arguments".to_string());
                 }
                 let index = self.compile_expr(&args[0])?;
-                let value = self.compile_expr(&args[1])?;
-                let func_ref = *self.func_refs.get("list_insert").ok_or("list_insert not found")?;
+                let value = self.compile_list_elem_arg(&args[1], elem_ty)?;
+                let value = self.elem_to_slot(value);
+                let func_ref = self.get_func_ref("list_insert")?;
                 self.builder.ins().call(func_ref, &[list_ptr, index, value]);
                 Ok(self.builder.ins().iconst(types::I64, 0))
             }
@@ -6781,19 +12417,20 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                     return Err("remove expects 1 argument".to_string());
                 }
                 let index = self.compile_expr(&args[0])?;
-                let func_ref = *self.func_refs.get("list_remove").ok_or("list_remove not found")?;
+                let func_ref = self.get_func_ref("list_remove")?;
                 let call = self.builder.ins().call(func_ref, &[list_ptr, index]);
-                Ok(self.builder.inst_results(call)[0])
+                let slot = self.builder.inst_results(call)[0];
+                Ok(self.slot_to_elem(slot, elem_ty))
             }
             // clear() -> void
             "clear" => {
-                let func_ref = *self.func_refs.get("list_clear").ok_or("list_clear not found")?;
+                let func_ref = self.get_func_ref("list_clear")?;
                 self.builder.ins().call(func_ref, &[list_ptr]);
                 Ok(self.builder.ins().iconst(types::I64, 0))
             }
             // reverse() -> void
             "reverse" => {
-                let func_ref = *self.func_refs.get("list_reverse").ok_or("list_reverse not found")?;
+                let func_ref = self.get_func_ref("list_reverse")?;
                 self.builder.ins().call(func_ref, &[list_ptr]);
                 Ok(self.builder.ins().iconst(types::I64, 0))
             }
@@ -6803,7 +12440,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                     return Err("extend expects 1 argument".to_string());
                 }
                 let other = self.compile_expr(&args[0])?;
-                let func_ref = *self.func_refs.get("list_extend").ok_or("list_extend not found")?;
+                let func_ref = self.get_func_ref("list_extend")?;
                 self.builder.ins().call(func_ref, &[list_ptr, other]);
                 Ok(self.builder.ins().iconst(types::I64, 0))
             }
@@ -6812,8 +12449,9 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 if args.len() != 1 {
                     return Err(format!("{} expects 1 argument", method_name));
                 }
-                let value = self.compile_expr(&args[0])?;
-                let func_ref = *self.func_refs.get("list_contains").ok_or("list_contains not found")?;
+                let value = self.compile_list_elem_arg(&args[0], elem_ty)?;
+                let value = self.elem_to_slot(value);
+                let func_ref = self.get_func_ref("list_contains")?;
                 let call = self.builder.ins().call(func_ref, &[list_ptr, value]);
                 Ok(self.builder.inst_results(call)[0])
             }
@@ -6822,8 +12460,9 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 if args.len() != 1 {
                     return Err(format!("{} expects 1 argument", method_name));
                 }
-                let value = self.compile_expr(&args[0])?;
-                let func_ref = *self.func_refs.get("list_index_of").ok_or("list_index_of not found")?;
+                let value = self.compile_list_elem_arg(&args[0], elem_ty)?;
+                let value = self.elem_to_slot(value);
+                let func_ref = self.get_func_ref("list_index_of")?;
                 let call = self.builder.ins().call(func_ref, &[list_ptr, value]);
                 Ok(self.builder.inst_results(call)[0])
             }
@@ -6832,17 +12471,129 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 if args.len() != 1 {
                     return Err("count expects 1 argument".to_string());
                 }
-                let value = self.compile_expr(&args[0])?;
-                let func_ref = *self.func_refs.get("list_count").ok_or("list_count not found")?;
+                let value = self.compile_list_elem_arg(&args[0], elem_ty)?;
+                let value = self.elem_to_slot(value);
+                let func_ref = self.get_func_ref("list_count")?;
                 let call = self.builder.ins().call(func_ref, &[list_ptr, value]);
                 Ok(self.builder.inst_results(call)[0])
             }
-            // sort() -> void
+            // sort() -> void：int / bool / float / str 列表升序（稳定）
             "sort" => {
-                let func_ref = *self.func_refs.get("list_sort").ok_or("list_sort not found")?;
+                if !args.is_empty() {
+                    return Err("sort expects no arguments; use sort_by(cmp) or sort_by_key(key) to customize the order".to_string());
+                }
+                if !matches!(elem_ty, BolideType::Int | BolideType::Bool | BolideType::Float | BolideType::Str) {
+                    return Err(format!("sort() only supports int, bool, float and str lists, got list<{}>; use sort_by(cmp)", analysis::type_name(elem_ty)));
+                }
+                let func_ref = self.get_func_ref("list_sort")?;
+                self.builder.ins().call(func_ref, &[list_ptr]);
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            // sort_by(cmp_fn) -> void：比较函数 fn(a: T, b: T) -> int，稳定排序
+            "sort_by" => {
+                if args.len() != 1 {
+                    return Err("sort_by expects 1 argument".to_string());
+                }
+                // 运行时按 i64 传递元素，与 bind 相同不支持 float
+                if matches!(elem_ty, BolideType::Float) {
+                    return Err("sort_by does not support float lists".to_string());
+                }
+                match self.infer_bind_target_type(&args[0]) {
+                    BolideType::FuncSig(params, ret) => {
+                        if params.len() != 2 {
+                            return Err(format!("sort_by comparator must take 2 arguments, got {}", params.len()));
+                        }
+                        if !matches!(ret.as_deref(), Some(BolideType::Int)) {
+                            let ret = ret.as_deref().map_or("nothing".to_string(), analysis::type_name);
+                            return Err(format!("sort_by comparator must return int, got {}", ret));
+                        }
+                    }
+                    _ => return Err("sort_by comparator needs a declared signature, e.g. func(T, T) -> int".to_string()),
+                }
+                if let Expr::Ident(name) = &args[0] {
+                    if let Some(params) = self.func_params.get(name) {
+                        if params.iter().any(|p| p.mode != ParamMode::Borrow) {
+                            return Err(format!("sort_by: function '{}' has owned or ref parameters", name));
+                        }
+                    }
+                }
+                let cmp_fn = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("list_sort_by")?;
+                self.builder.ins().call(func_ref, &[list_ptr, cmp_fn]);
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            // dedup() -> void：原地合并相邻的重复元素
+            "dedup" => {
+                if !args.is_empty() {
+                    return Err("dedup expects no arguments".to_string());
+                }
+                let func_ref = self.get_func_ref("list_dedup")?;
                 self.builder.ins().call(func_ref, &[list_ptr]);
                 Ok(self.builder.ins().iconst(types::I64, 0))
             }
+            // unique() -> list：每个值第一次出现的元素
+            "unique" => {
+                if !args.is_empty() {
+                    return Err("unique expects no arguments".to_string());
+                }
+                let func_ref = self.get_func_ref("list_unique")?;
+                let call = self.builder.ins().call(func_ref, &[list_ptr]);
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::List(Box::new(elem_ty.clone())));
+                Ok(result)
+            }
+            // group_by(key_fn) -> dict<K, list<T>>
+            "group_by" => {
+                if args.len() != 1 {
+                    return Err("group_by expects 1 argument".to_string());
+                }
+                // 运行时按 i64 传递元素，与 bind 相同不支持 float
+                if matches!(elem_ty, BolideType::Float) {
+                    return Err("group_by does not support float lists".to_string());
+                }
+                let key_ty = self.group_by_key_type(&args[0])?;
+                if let Expr::Ident(name) = &args[0] {
+                    if let Some(params) = self.func_params.get(name) {
+                        if params.iter().any(|p| p.mode != ParamMode::Borrow) {
+                            return Err(format!("group_by: function '{}' has owned or ref parameters", name));
+                        }
+                    }
+                }
+                let key_fn = self.compile_expr(&args[0])?;
+                let key_tag = self.builder.ins().iconst(types::I8, Self::elem_type_tag(&key_ty) as i64);
+                let func_ref = self.get_func_ref("list_group_by")?;
+                let call = self.builder.ins().call(func_ref, &[list_ptr, key_fn, key_tag]);
+                let result = self.builder.inst_results(call)[0];
+                let dict_ty = BolideType::Dict(Box::new(key_ty), Box::new(BolideType::List(Box::new(elem_ty.clone()))));
+                self.track_temp_rc_value(result, &dict_ty);
+                Ok(result)
+            }
+            // sort_by_key(key_fn) -> void（稳定排序）/ min_by(key_fn) / max_by(key_fn) -> value
+            "sort_by_key" | "min_by" | "max_by" => {
+                if args.len() != 1 {
+                    return Err(format!("{} expects 1 argument", method_name));
+                }
+                // 运行时按 i64 传递元素，与 bind 相同不支持 float
+                if matches!(elem_ty, BolideType::Float) {
+                    return Err(format!("{} does not support float lists", method_name));
+                }
+                let key_ty = self.sort_key_type(method_name, &args[0])?;
+                if let Expr::Ident(name) = &args[0] {
+                    if let Some(params) = self.func_params.get(name) {
+                        if params.iter().any(|p| p.mode != ParamMode::Borrow) {
+                            return Err(format!("{}: function '{}' has owned or ref parameters", method_name, name));
+                        }
+                    }
+                }
+                let key_fn = self.compile_expr(&args[0])?;
+                let key_tag = self.builder.ins().iconst(types::I8, Self::elem_type_tag(&key_ty) as i64);
+                let func_ref = self.get_func_ref(&format!("list_{}", method_name))?;
+                let call = self.builder.ins().call(func_ref, &[list_ptr, key_fn, key_tag]);
+                if method_name == "sort_by_key" {
+                    return Ok(self.builder.ins().iconst(types::I64, 0));
+                }
+                Ok(self.builder.inst_results(call)[0])
+            }
             // slice(start, end) -> list
             "slice" => {
                 if args.len() != 2 {
@@ -6850,100 +12601,244 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 }
                 let start = self.compile_expr(&args[0])?;
                 let end = self.compile_expr(&args[1])?;
-                let func_ref = *self.func_refs.get("list_slice").ok_or("list_slice not found")?;
+                let func_ref = self.get_func_ref("list_slice")?;
                 let call = self.builder.ins().call(func_ref, &[list_ptr, start, end]);
                 Ok(self.builder.inst_results(call)[0])
             }
             // is_empty() -> bool
             "is_empty" | "empty" => {
-                let func_ref = *self.func_refs.get("list_is_empty").ok_or("list_is_empty not found")?;
+                let func_ref = self.get_func_ref("list_is_empty")?;
                 let call = self.builder.ins().call(func_ref, &[list_ptr]);
                 Ok(self.builder.inst_results(call)[0])
             }
             // first() -> value
             "first" => {
-                let func_ref = *self.func_refs.get("list_first").ok_or("list_first not found")?;
+                let func_ref = self.get_func_ref("list_first")?;
                 let call = self.builder.ins().call(func_ref, &[list_ptr]);
-                Ok(self.builder.inst_results(call)[0])
+                let slot = self.builder.inst_results(call)[0];
+                Ok(self.slot_to_elem(slot, elem_ty))
             }
             // last() -> value
             "last" => {
-                let func_ref = *self.func_refs.get("list_last").ok_or("list_last not found")?;
+                let func_ref = self.get_func_ref("list_last")?;
                 let call = self.builder.ins().call(func_ref, &[list_ptr]);
-                Ok(self.builder.inst_results(call)[0])
+                let slot = self.builder.inst_results(call)[0];
+                Ok(self.slot_to_elem(slot, elem_ty))
             }
-            // copy() -> list (shallow copy, same as clone)
+            // copy() -> list (shallow copy, same as clone)；新列表作为临时值，绑定到变量时不再复制
             "copy" | "clone" => {
-                let func_ref = *self.func_refs.get("list_clone").ok_or("list_clone not found")?;
+                let func_ref = self.get_func_ref("list_clone")?;
                 let call = self.builder.ins().call(func_ref, &[list_ptr]);
-                Ok(self.builder.inst_results(call)[0])
+                let copy = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(copy, &BolideType::List(Box::new(elem_ty.clone())));
+                Ok(copy)
+            }
+            // decode(encoding, mode = "strict") / decode_latin1() -> str：list<int> 作为字节序列
+            "decode" | "decode_latin1" => {
+                if *elem_ty != BolideType::Int {
+                    return Err(format!("{} expects list<int> of bytes, got list<{:?}>", method_name, elem_ty));
+                }
+                let mut arg_values = vec![list_ptr];
+                let func_name = if method_name == "decode" {
+                    if args.is_empty() || args.len() > 2 {
+                        return Err("decode expects 1 or 2 arguments".to_string());
+                    }
+                    for arg in args {
+                        arg_values.push(self.compile_expr(arg)?);
+                    }
+                    if args.len() == 1 {
+                        arg_values.push(self.builder.ins().iconst(self.ptr_type, 0));
+                    }
+                    "bytes_decode"
+                } else {
+                    if !args.is_empty() {
+                        return Err("decode_latin1 expects 0 arguments".to_string());
+                    }
+                    "bytes_decode_latin1"
+                };
+                let func_ref = self.get_func_ref(func_name)?;
+                let call = self.builder.ins().call(func_ref, &arg_values);
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::Str);
+                Ok(result)
             }
             _ => Err(format!("Unknown list method: {}", method_name)),
         }
     }
 
+    /// 编译通道方法调用（批量收发）
+    ///
+    /// 消息按 i64 位搬运；`send_all` 的列表元素类型须与通道声明的元素类型一致，
+    /// `recv_n` / `drain` 返回该元素类型的新列表。
+    fn compile_channel_method_call(&mut self, channel_ptr: Value, elem_ty: &BolideType, method_name: &str, args: &[Expr]) -> Result<Value, String> {
+        match method_name {
+            // send_all(xs) -> int：入队的数量，小于 len(xs) 表示通道中途关闭
+            "send_all" => {
+                if args.len() != 1 {
+                    return Err("send_all expects 1 argument".to_string());
+                }
+                let list_ty = self.infer_expr_type(&args[0]);
+                let list_elem = match &list_ty {
+                    BolideType::List(e) => self.normalize_bolide_type(e),
+                    other => return Err(format!("send_all expects a list, got {:?}", other)),
+                };
+                if list_elem != *elem_ty {
+                    return Err(format!("send_all on channel<{:?}> expects list<{:?}>, got list<{:?}>",
+                        elem_ty, elem_ty, list_elem));
+                }
+                let list_ptr = self.compile_expr(&args[0])?;
+                let func_ref = self.get_func_ref("channel_send_all")?;
+                let call = self.builder.ins().call(func_ref, &[channel_ptr, list_ptr]);
+                Ok(self.builder.inst_results(call)[0])
+            }
+            // recv_n(n) -> list<T>：至少一条（阻塞），最多 n 条；空列表表示通道已关闭
+            "recv_n" => {
+                if args.len() != 1 {
+                    return Err("recv_n expects 1 argument".to_string());
+                }
+                let n = self.compile_expr(&args[0])?;
+                let tag = self.builder.ins().iconst(types::I8, Self::elem_type_tag(elem_ty) as i64);
+                let list_new = self.get_func_ref("list_new")?;
+                let call = self.builder.ins().call(list_new, &[tag]);
+                let list_ptr = self.builder.inst_results(call)[0];
+                let func_ref = self.get_func_ref("channel_recv_n")?;
+                self.builder.ins().call(func_ref, &[channel_ptr, n, list_ptr]);
+                self.track_temp_rc_value(list_ptr, &BolideType::List(Box::new(elem_ty.clone())));
+                Ok(list_ptr)
+            }
+            // drain() -> list<T>：接收直到通道关闭
+            "drain" => {
+                let tag = self.builder.ins().iconst(types::I8, Self::elem_type_tag(elem_ty) as i64);
+                let func_ref = self.get_func_ref("channel_drain")?;
+                let call = self.builder.ins().call(func_ref, &[channel_ptr, tag]);
+                let list_ptr = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(list_ptr, &BolideType::List(Box::new(elem_ty.clone())));
+                Ok(list_ptr)
+            }
+            "close" => {
+                let func_ref = self.get_func_ref("channel_close")?;
+                self.builder.ins().call(func_ref, &[channel_ptr]);
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            _ => Err(format!("Unknown channel method: {}", method_name)),
+        }
+    }
+
     /// 编译字典方法调用
-    fn compile_dict_method_call(&mut self, dict_ptr: Value, method_name: &str, args: &[Expr]) -> Result<Value, String> {
+    fn compile_dict_method_call(&mut self, dict_ptr: Value, dict_ty: &BolideType, method_name: &str, args: &[Expr]) -> Result<Value, String> {
+        let (key_ty, val_ty) = match dict_ty {
+            BolideType::Dict(k, v) => ((**k).clone(), (**v).clone()),
+            _ => (BolideType::Int, BolideType::Int),
+        };
         match method_name {
             "set" => {
-                 let set_fn = *self.func_refs.get("dict_set").ok_or("dict_set failed")?;
+                 let set_fn = self.get_func_ref("dict_set").map_err(|_| "dict_set failed")?;
                  let k = self.compile_expr(&args[0])?;
                  let v = self.compile_expr(&args[1])?;
                  self.builder.ins().call(set_fn, &[dict_ptr, k, v]);
                  Ok(self.builder.ins().iconst(types::I64, 0))
             }
             "get" => {
-                let get_fn = *self.func_refs.get("dict_get").ok_or("dict_get failed")?;
+                let get_fn = self.get_func_ref("dict_get").map_err(|_| "dict_get failed")?;
                 let k = self.compile_expr(&args[0])?;
                 let call = self.builder.ins().call(get_fn, &[dict_ptr, k]);
                 Ok(self.builder.inst_results(call)[0])
             }
             "contains" => {
-                let contains_fn = *self.func_refs.get("dict_contains").ok_or("dict_contains failed")?;
+                let contains_fn = self.get_func_ref("dict_contains").map_err(|_| "dict_contains failed")?;
                 let k = self.compile_expr(&args[0])?;
                 let call = self.builder.ins().call(contains_fn, &[dict_ptr, k]);
                 Ok(self.builder.inst_results(call)[0])
             }
             "remove" => {
-                let remove_fn = *self.func_refs.get("dict_remove").ok_or("dict_remove failed")?;
+                let remove_fn = self.get_func_ref("dict_remove").map_err(|_| "dict_remove failed")?;
                 let k = self.compile_expr(&args[0])?;
                 let call = self.builder.ins().call(remove_fn, &[dict_ptr, k]);
                 Ok(self.builder.inst_results(call)[0])
             }
              "len" => {
-                let len_fn = *self.func_refs.get("dict_len").ok_or("dict_len failed")?;
+                let len_fn = self.get_func_ref("dict_len").map_err(|_| "dict_len failed")?;
                 let call = self.builder.ins().call(len_fn, &[dict_ptr]);
                 Ok(self.builder.inst_results(call)[0])
             }
              "is_empty" => {
-                let is_empty_fn = *self.func_refs.get("dict_is_empty").ok_or("dict_is_empty failed")?;
+                let is_empty_fn = self.get_func_ref("dict_is_empty").map_err(|_| "dict_is_empty failed")?;
                 let call = self.builder.ins().call(is_empty_fn, &[dict_ptr]);
                 Ok(self.builder.inst_results(call)[0])
             }
             "clear" => {
-                let clear_fn = *self.func_refs.get("dict_clear").ok_or("dict_clear failed")?;
+                let clear_fn = self.get_func_ref("dict_clear").map_err(|_| "dict_clear failed")?;
                 self.builder.ins().call(clear_fn, &[dict_ptr]);
                 Ok(self.builder.ins().iconst(types::I64, 0))
             }
              "keys" => {
-                let keys_fn = *self.func_refs.get("dict_keys").ok_or("dict_keys failed")?;
+                let keys_fn = self.get_func_ref("dict_keys").map_err(|_| "dict_keys failed")?;
                 let call = self.builder.ins().call(keys_fn, &[dict_ptr]);
-                Ok(self.builder.inst_results(call)[0])
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::List(Box::new(key_ty)));
+                Ok(result)
             }
              "values" => {
-                let values_fn = *self.func_refs.get("dict_values").ok_or("dict_values failed")?;
+                let values_fn = self.get_func_ref("dict_values").map_err(|_| "dict_values failed")?;
                 let call = self.builder.ins().call(values_fn, &[dict_ptr]);
-                Ok(self.builder.inst_results(call)[0])
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, &BolideType::List(Box::new(val_ty)));
+                Ok(result)
             }
              "clone" => {
-                let clone_fn = *self.func_refs.get("dict_clone").ok_or("dict_clone failed")?;
+                let clone_fn = self.get_func_ref("dict_clone").map_err(|_| "dict_clone failed")?;
                 let call = self.builder.ins().call(clone_fn, &[dict_ptr]);
-                Ok(self.builder.inst_results(call)[0])
+                let result = self.builder.inst_results(call)[0];
+                self.track_temp_rc_value(result, dict_ty);
+                Ok(result)
             }
             _ => Err(format!("Unknown dictionary method: {}", method_name)),
         }
     }
 
+    /// 编译弱键字典方法调用；键是类实例，字典只持有它的弱句柄
+    fn compile_weak_dict_method_call(&mut self, dict_ptr: Value, dict_ty: &BolideType, method_name: &str, args: &[Expr]) -> Result<Value, String> {
+        let BolideType::WeakDict(key_ty, val_ty) = dict_ty else {
+            return Err(format!("Expected weak_dict, found {}", analysis::type_name(dict_ty)));
+        };
+        let expected_args = match method_name {
+            "set" => 2,
+            "get" | "contains" | "remove" => 1,
+            "len" | "is_empty" | "compact" => 0,
+            _ => return Err(format!("Unknown weak_dict method: {}", method_name)),
+        };
+        if args.len() != expected_args {
+            return Err(format!("weak_dict.{} expects {} argument(s), got {}", method_name, expected_args, args.len()));
+        }
+        let mut call_args = vec![dict_ptr];
+        if let Some(key) = args.first() {
+            // 键必须是类实例（子类实例也可以）
+            let found = self.infer_expr_type(key);
+            if !matches!(found, BolideType::Custom(_)) {
+                return Err(format!(
+                    "weak_dict.{} expects a {} key, found {}",
+                    method_name, analysis::type_name(key_ty), analysis::type_name(&found)
+                ));
+            }
+            call_args.push(self.compile_expr(key)?);
+        }
+        if method_name == "set" {
+            let value = self.compile_list_elem_arg(&args[1], val_ty)?;
+            call_args.push(self.elem_to_slot(value));
+        }
+        let func_ref = self.get_func_ref(&format!("weakdict_{}", method_name))?;
+        let call = self.builder.ins().call(func_ref, &call_args);
+        match method_name {
+            "set" => Ok(self.builder.ins().iconst(types::I64, 0)),
+            // 值是借用的，和 dict.get 一样
+            "get" => {
+                let slot = self.builder.inst_results(call)[0];
+                Ok(self.slot_to_elem(slot, val_ty))
+            }
+            _ => Ok(self.builder.inst_results(call)[0]),
+        }
+    }
+
 
     /// 在继承链中查找方法
 
@@ -6951,7 +12846,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         let mut current = self.normalize_type_name(class_name);
         loop {
             let full_name = format!("{}_{}", current, method_name);
-            if self.func_refs.contains_key(&full_name) {
+            if self.functions.contains_key(&full_name) {
                 return Ok(full_name);
             }
             // 查找父类
@@ -7058,16 +12953,14 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         let lib_path_ptr = self.create_string_constant(lib_path)?;
 
         // 2. 加载库
-        let load_lib_ref = *self.func_refs.get("ffi_load_library")
-            .ok_or("ffi_load_library not found")?;
+        let load_lib_ref = self.get_func_ref("ffi_load_library")?;
         self.builder.ins().call(load_lib_ref, &[lib_path_ptr]);
 
         // 3. 创建函数名字符串常量
         let func_name_ptr = self.create_string_constant(&extern_func.name)?;
 
         // 4. 获取函数指针
-        let get_symbol_ref = *self.func_refs.get("ffi_get_symbol")
-            .ok_or("ffi_get_symbol not found")?;
+        let get_symbol_ref = self.get_func_ref("ffi_get_symbol")?;
         let call = self.builder.ins().call(get_symbol_ref, &[lib_path_ptr, func_name_ptr]);
         let func_ptr = self.builder.inst_results(call)[0];
 
@@ -7080,7 +12973,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                     // 参数是函数指针类型，检查是否传递了函数名
                     if let Expr::Ident(func_name) = arg {
                         // 获取函数地址
-                        if let Some(&func_ref) = self.func_refs.get(func_name) {
+                        if let Ok(func_ref) = self.get_func_ref(func_name) {
                             let func_addr = self.builder.ins().func_addr(self.ptr_type, func_ref);
                             arg_values.push(func_addr);
                             continue;
@@ -7096,8 +12989,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 if let bolide_parser::CType::Ptr(inner) = &param.ty {
                     if matches!(inner.as_ref(), bolide_parser::CType::Char) {
                         // 参数类型是 *char，需要转换 BolideString* -> char*
-                        let as_cstr_ref = *self.func_refs.get("string_as_cstr")
-                            .ok_or("string_as_cstr not found")?;
+                        let as_cstr_ref = self.get_func_ref("string_as_cstr")?;
                         let call = self.builder.ins().call(as_cstr_ref, &[val]);
                         let cstr_ptr = self.builder.inst_results(call)[0];
                         arg_values.push(cstr_ptr);
@@ -7106,26 +12998,13 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 }
             }
 
-            // 获取期望的 C 类型
-            if let Some(param) = extern_func.params.get(i) {
-                let expected_ty = self.ctype_to_cranelift(&param.ty);
-                let actual_ty = self.builder.func.dfg.value_type(val);
-
-                // 类型转换
-                let converted = if actual_ty == types::I64 && expected_ty == types::I32 {
-                    self.builder.ins().ireduce(types::I32, val)
-                } else if actual_ty == types::I64 && expected_ty == types::I16 {
-                    self.builder.ins().ireduce(types::I16, val)
-                } else if actual_ty == types::I64 && expected_ty == types::I8 {
-                    self.builder.ins().ireduce(types::I8, val)
-                } else if actual_ty == types::F64 && expected_ty == types::F32 {
-                    self.builder.ins().fdemote(types::F32, val)
-                } else {
-                    val
-                };
-                arg_values.push(converted);
-            } else {
-                arg_values.push(val);
+            match extern_func.params.get(i) {
+                Some(param) => {
+                    let converted = self.marshal_extern_arg(val, &param.ty)
+                        .map_err(|e| format!("extern function '{}' parameter '{}': {}", extern_func.name, param.name, e))?;
+                    arg_values.push(converted);
+                }
+                None => arg_values.push(val),
             }
         }
 
@@ -7149,8 +13028,7 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 if let bolide_parser::CType::Ptr(inner) = ret_ty {
                     if matches!(inner.as_ref(), bolide_parser::CType::Char) {
                         // 返回类型是 *char，需要转换为 BolideString*
-                        let string_new_ref = *self.func_refs.get("bolide_string_new")
-                            .ok_or("bolide_string_new not found")?;
+                        let string_new_ref = self.get_func_ref("bolide_string_new")?;
                         let call = self.builder.ins().call(string_new_ref, &[result]);
                         let bolide_string = self.builder.inst_results(call)[0];
                         return Ok(bolide_string);
@@ -7158,11 +13036,14 @@ impl<'a, 'b> CompileContext<'a, 'b> {
                 }
             }
 
-            // 如果是 I32，扩展到 I64
-            if result_ty == types::I32 {
-                Ok(self.builder.ins().sextend(types::I64, result))
-            } else if result_ty == types::I8 || result_ty == types::I16 {
-                Ok(self.builder.ins().sextend(types::I64, result))
+            let ret_ty = extern_func.return_type.as_ref().unwrap_or(&bolide_parser::CType::Void);
+            if result_ty == types::I8 || result_ty == types::I16 || result_ty == types::I32 {
+                // 无符号类型零扩展，有符号类型符号扩展
+                if Self::ctype_is_unsigned(ret_ty) {
+                    Ok(self.builder.ins().uextend(types::I64, result))
+                } else {
+                    Ok(self.builder.ins().sextend(types::I64, result))
+                }
             } else if result_ty == types::F32 {
                 Ok(self.builder.ins().fpromote(types::F64, result))
             } else {
@@ -7171,6 +13052,40 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         }
     }
 
+    /// Bolide 值转换为 C 参数类型：整数截断或扩展到参数宽度，float 按参数宽度 fdemote，int 传给浮点参数时转换
+    fn marshal_extern_arg(&mut self, val: Value, ctype: &bolide_parser::CType) -> Result<Value, String> {
+        let expected = self.ctype_to_cranelift(ctype);
+        let actual = self.builder.func.dfg.value_type(val);
+        if actual == expected {
+            return Ok(val);
+        }
+        Ok(match (actual.is_int(), expected.is_int()) {
+            (true, true) if actual.bits() > expected.bits() => self.builder.ins().ireduce(expected, val),
+            (true, true) if Self::ctype_is_unsigned(ctype) => self.builder.ins().uextend(expected, val),
+            (true, true) => self.builder.ins().sextend(expected, val),
+            (true, false) => self.builder.ins().fcvt_from_sint(expected, val),
+            (false, false) if expected == types::F32 => self.builder.ins().fdemote(types::F32, val),
+            (false, false) => self.builder.ins().fpromote(types::F64, val),
+            (false, true) => return Err("expects an integer, got float".to_string()),
+        })
+    }
+
+    /// 返回值零扩展的 C 整数类型
+    fn ctype_is_unsigned(ctype: &bolide_parser::CType) -> bool {
+        use bolide_parser::CType;
+        matches!(ctype, CType::UChar | CType::U8 | CType::UShort | CType::U16 | CType::UInt | CType::U32 | CType::Bool)
+    }
+
+    /// extern 函数调用的 Bolide 类型：浮点返回 float，`cstr`（`*char`）返回 str，其余为 int
+    fn extern_return_type(func: &bolide_parser::ExternFunc) -> BolideType {
+        use bolide_parser::CType;
+        match &func.return_type {
+            Some(CType::Float | CType::Double) => BolideType::Float,
+            Some(CType::Ptr(inner)) if matches!(inner.as_ref(), CType::Char) => BolideType::Str,
+            _ => BolideType::Int,
+        }
+    }
+
     /// 构建 extern 函数签名
     fn build_extern_signature(&self, func: &bolide_parser::ExternFunc) -> Result<Signature, String> {
         use cranelift_codegen::isa::CallConv;
@@ -7224,3 +13139,2615 @@ impl<'a, 'b> CompileContext<'a, 'b> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verifier_error_writes_ice_bundle() {
+        let source = "fn broken() -> int {\n    return 1;\n}\nbroken();\n";
+        let program = bolide_parser::parse_source(source).unwrap();
+
+        let mut compiler = JitCompiler::new();
+        compiler.set_source("broken.bl", source);
+        compiler.bad_signature_for = Some("broken".to_string());
+
+        let err = compiler.compile(&program).err().expect("malformed signature should fail");
+        assert!(err.starts_with("internal compiler error in function `broken`"));
+        // 不应把整页 IR 打到终端
+        assert!(err.lines().count() <= 4, "ICE message too long:\n{}", err);
+
+        let dir = err.lines()
+            .find_map(|l| l.trim().strip_prefix("reproduction bundle: "))
+            .expect("bundle path in message");
+        let dir = std::path::Path::new(dir);
+        for file in ["source.bl", "function.clif", "ast.txt", "error.txt", "version.txt"] {
+            assert!(dir.join(file).exists(), "missing {}", file);
+        }
+        let saved = std::fs::read_to_string(dir.join("source.bl")).unwrap();
+        assert!(saved.contains("fn broken()"));
+        let clif = std::fs::read_to_string(dir.join("function.clif")).unwrap();
+        assert!(clif.contains("function"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_annotated_literal_type_mismatch() {
+        let source = "let xs: list<int> = [1, \"two\"];\n";
+        let program = bolide_parser::parse_source(source).unwrap();
+        let err = JitCompiler::new().compile(&program).err().expect("mismatched element should fail");
+        assert!(err.contains("list literal element 1"), "{}", err);
+
+        let source = "let d: dict<str, list<str>> = {\"a\": [1]};\n";
+        let program = bolide_parser::parse_source(source).unwrap();
+        let err = JitCompiler::new().compile(&program).err().expect("mismatched nested element should fail");
+        assert!(err.contains("list literal element 0"), "{}", err);
+    }
+
+    #[test]
+    fn test_large_list_literals() {
+        let ints: Vec<String> = (0..50_000).map(|i| i.to_string()).collect();
+        let calls: Vec<String> = (0..600).map(|i| format!("sq({})", i)).collect();
+        let source = format!(
+            "fn sq(n: int) -> int {{\n    return n * n;\n}}\nlet xs: list<int> = [{}];\nlet ys: list<int> = [{}];\n",
+            ints.join(", "), calls.join(", "),
+        );
+        let program = bolide_parser::parse_source(&source).unwrap();
+        let mut compiler = JitCompiler::new();
+        compiler.compile(&program).expect("large literals should compile");
+
+        // 常量元素不生成辅助函数；600 个调用分成 3 段
+        let chunks: Vec<&String> = compiler.functions.keys().filter(|n| n.starts_with("__list_chunk_")).collect();
+        assert_eq!(chunks.len(), 3, "{:?}", chunks);
+        assert!(compiler.functions.contains_key("__list_chunk___main___0_2"));
+    }
+
+    #[test]
+    fn test_destructure_arity_mismatch() {
+        let source = "let t: (int, int, int) = (1, 2, 3);\nlet a, b = t;\n";
+        let program = bolide_parser::parse_source(source).unwrap();
+        let err = JitCompiler::new().compile(&program).err().expect("arity mismatch should fail");
+        assert!(err.contains("3-tuple into 2 names"), "{}", err);
+
+        let source = "let n: int = 1;\nlet a, b = n;\n";
+        let program = bolide_parser::parse_source(source).unwrap();
+        let err = JitCompiler::new().compile(&program).err().expect("non-tuple should fail");
+        assert!(err.contains("Cannot destructure"), "{}", err);
+
+        let err = bolide_parser::parse_source("let a, a = (1, 2);\n").err().expect("duplicate name");
+        assert!(err.message.contains("Duplicate name 'a'"), "{}", err);
+    }
+
+    const LIFETIME_PRELUDE: &str = "\
+fn first(ref xs: list<int>) -> int from xs {
+    return xs[0];
+}
+class Holder {
+    v: int;
+}
+fn consume(owned v: int) {
+    print(v);
+}
+fn worker(v: int) -> int {
+    return v;
+}
+let g: int = 0;
+";
+
+    fn compile_with_prelude(body: &str) -> Result<*const u8, String> {
+        let source = format!("{}{}", LIFETIME_PRELUDE, body);
+        let program = bolide_parser::parse_source(&source).unwrap();
+        JitCompiler::new().compile(&program)
+    }
+
+    #[test]
+    fn test_borrow_escapes_rejected() {
+        let borrow = "    let xs: list<int> = [1];\n    let b: int = first(xs);\n";
+        let cases = [
+            ("let h: Holder = Holder(0);\n    h.v = b;", "'b' borrows from 'xs'", "stored in field 'v'"),
+            ("let h: Holder = Holder(b);", "'b' borrows from 'xs'", "stored in field 'v' of 'Holder'"),
+            ("g = b;", "'b' borrows from 'xs'", "stored in global 'g'"),
+            ("let ch: channel<int> = channel(1);\n    ch <- b;", "'b' borrows from 'xs'", "sent over channel 'ch'"),
+            ("let h = spawn worker(b);", "'b' borrows from 'xs'", "captured by spawn of 'worker'"),
+            // 经过元组打包和解构后仍然追踪
+            ("let t: (int, int) = (b, 2);\n    let a, c = t;\n    consume(a);", "'a' borrows from 'xs'", "passed to owned parameter 'v' of 'consume'"),
+            // 经过赋值和索引链后仍然追踪
+            ("let p: (int, int) = (0, 0);\n    p = (b, 1);\n    consume(p[0]);", "'p' borrows from 'xs'", "passed to owned parameter 'v' of 'consume'"),
+        ];
+        for (escape, names, destination) in cases {
+            let body = format!("fn f() {{\n{}    {}\n}}\n", borrow, escape);
+            let err = compile_with_prelude(&body).err().unwrap_or_else(|| panic!("escape accepted: {}", escape));
+            assert!(err.contains(names) && err.contains(destination), "{}", err);
+            assert!(err.contains("(local variable in 'f')"), "{}", err);
+        }
+
+        // 返回借用值但调用者没有声明 from
+        let body = "fn f(ref ys: list<int>) -> int {\n    let b: int = first(ys);\n    let e: int = b;\n    return e;\n}\n";
+        let err = compile_with_prelude(body).err().expect("undeclared borrowed return should fail");
+        assert!(err.contains("cannot return 'e', which borrows from 'ys' (parameter of 'f')"), "{}", err);
+
+        // 声明了 from，但来源是局部变量
+        let body = "fn f(ref ys: list<int>) -> int from ys {\n    let xs: list<int> = [1];\n    return first(xs);\n}\n";
+        let err = compile_with_prelude(body).err().expect("borrow of a local should fail");
+        assert!(err.contains("cannot return 'first(...)', which borrows from 'xs'"), "{}", err);
+
+        // 外层变量借用内层作用域的值
+        let body = "fn f() {\n    let r: int = 0;\n    if true {\n        let xs: list<int> = [1];\n        let b: int = first(xs);\n        r = b;\n    }\n}\n";
+        let err = compile_with_prelude(body).err().expect("dangling borrow should fail");
+        assert!(err.contains("'r' borrows from 'xs' which goes out of scope"), "{}", err);
+    }
+
+    #[test]
+    fn test_borrow_pass_through_accepted() {
+        let body = "\
+fn head(ref ys: list<int>) -> int from ys {
+    let b: int = first(ys);
+    let e: int = b;
+    return e;
+}
+fn head_direct(ref ys: list<int>) -> int from ys {
+    return first(ys);
+}
+fn head_tuple(ref ys: list<int>) -> int from ys {
+    let a, c = first(ys), 1;
+    return a;
+}
+fn use_locally() {
+    let xs: list<int> = [1];
+    let b: int = first(xs);
+    print(b + worker(b));
+}
+";
+        compile_with_prelude(body).expect("pass-through borrows should compile");
+    }
+
+    #[test]
+    fn test_packed_list_borrows() {
+        let prelude = "class Vec2 { x: float; y: float; }\n\
+class Named { id: int; name: str; }\n\
+fn grow(ps: list<packed Vec2>) {\n    ps.resize(10);\n}\n";
+        let compile = |body: &str| {
+            let source = format!("{}fn f() {{\n    let ps = packed_list(Vec2, 2);\n{}\n}}\n", prelude, body);
+            let program = bolide_parser::parse_source(&source).unwrap();
+            JitCompiler::new().compile(&program)
+        };
+        let cases = [
+            // 修改列表后不能再使用指向其元素的借用
+            ("    let p = ps[0];\n    ps.push(Vec2(1.0, 2.0));\n    print(p.x);",
+             "'p' borrows an element of 'ps' and cannot be used after ps.push(...)"),
+            ("    let p = ps[0];\n    grow(ps);\n    p.x = 1.0;",
+             "cannot be used after passing 'ps' to 'grow'"),
+            // 循环外的借用在循环里使用，下一次迭代时已经失效
+            ("    let p = ps[0];\n    for i in range(3) {\n        p.y = p.y + 1.0;\n        ps.swap(0, 1);\n    }",
+             "'p' borrows an element of 'ps' and is used in a loop that calls ps.swap(...)"),
+            // 元素不是对象
+            ("    let v: Vec2 = ps[0];", "An element of a packed list (Vec2) is not a Vec2 object"),
+            ("    ps[0].eq(ps[1]);", "Cannot call method 'eq' on an element of a packed list (Vec2)"),
+            ("    let ns = packed_list(Named, 1);",
+             "Class 'Named' cannot be packed: field 'name' has type str"),
+        ];
+        for (body, expected) in cases {
+            let err = compile(body).err().unwrap_or_else(|| panic!("accepted: {}", body));
+            assert!(err.contains(expected), "{}", err);
+        }
+
+        // 重新借用、只复制标量字段、在循环里声明的借用都可以
+        let body = "    let p = ps[0];\n    let x = ps[1].x;\n    ps.push(Vec2(1.0, 2.0));\n    p = ps[2];\n    print(p.x + x);\n\
+    for q in ps {\n        q.x = q.y;\n    }\n    for i in range(len(ps)) {\n        let q = ps[i];\n        ps.swap(0, i);\n    }";
+        compile(body).expect("valid packed borrows should compile");
+    }
+
+    #[test]
+    fn test_ref_param_ownership() {
+        let body = "fn take(owned v: list<int>) {\n    print(v);\n}\n\
+fn grow(ref xs: list<int>) {\n    xs.push(1);\n}\n\
+fn both(ref xs: list<int>, ys: list<int>) {\n    xs = ys;\n}\n";
+        let cases = [
+            // ref 参数不能移交给 owned 参数
+            ("fn f(ref xs: list<int>) {\n    take(xs);\n}\n", "ref parameter 'xs' cannot be moved into owned parameter 'v' of 'take'"),
+            // 借用参数不拥有值
+            ("fn f(xs: list<int>) {\n    grow(xs);\n}\n", "'xs' is borrowed and cannot be passed to ref parameter 'xs' of 'grow'"),
+            // 同一变量既按 ref 传递又作为其他实参
+            ("fn f() {\n    let xs: list<int> = [1];\n    both(xs, xs);\n}\n", "cannot be used in another argument of the same call"),
+        ];
+        for (case, expected) in cases {
+            let source = format!("{}{}", body, case);
+            let program = bolide_parser::parse_source(&source).unwrap();
+            let err = JitCompiler::new().compile(&program).err().unwrap_or_else(|| panic!("accepted: {}", case));
+            assert!(err.contains(expected), "{}", err);
+        }
+    }
+
+    /// 随机组合 ref 参数上的操作（原地修改、替换、条件替换、提前返回、循环、嵌套 ref 调用、
+    /// 返回 ref 参数），运行后对照模拟结果，并检查字符串没有泄漏或重复释放。
+    /// 列表元素都是运行时字符串，列表泄漏或重复释放也会反映在字符串计数上。
+    #[test]
+    fn test_ref_param_ownership_fuzz() {
+        struct Sim {
+            xs: Vec<String>,
+            s: String,
+        }
+
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % bound
+        };
+
+        for round in 0..150 {
+            let flags = [next(4), next(4)];
+            let mut ops: Vec<(String, Box<dyn Fn(&mut Sim, u64) -> bool>)> = Vec::new();
+            for k in 0..1 + next(6) {
+                let (n, m, t) = (next(100), next(100), next(5));
+                let op: (String, Box<dyn Fn(&mut Sim, u64) -> bool>) = match next(11) {
+                    0 => (format!("xs.push(str({}));", n), Box::new(move |sim, _| { sim.xs.push(n.to_string()); true })),
+                    1 => (format!("xs = [str({}), str({})];", n, m), Box::new(move |sim, _| { sim.xs = vec![n.to_string(), m.to_string()]; true })),
+                    2 => (format!("s = str({});", n), Box::new(move |sim, _| { sim.s = n.to_string(); true })),
+                    3 => (format!("s = s + str({});", n % 10), Box::new(move |sim, _| { sim.s.push_str(&(n % 10).to_string()); true })),
+                    4 => ("xs.push(s);".to_string(), Box::new(|sim, _| { sim.xs.push(sim.s.clone()); true })),
+                    5 => (format!("if flag > {} {{ xs = [str({})]; s = str({}); }}", t, n, m), Box::new(move |sim, flag| {
+                        if flag > t { sim.xs = vec![n.to_string()]; sim.s = m.to_string(); }
+                        true
+                    })),
+                    6 => (format!("if flag == {} {{ return; }}", t), Box::new(move |_, flag| flag != t)),
+                    7 => ("inner(xs, s);".to_string(), Box::new(|sim, _| { sim.xs = vec![sim.s.clone()]; sim.s = "7".to_string(); true })),
+                    8 => (format!("let i{k}: int = 0;\n    while i{k} < 3 {{ s = str(i{k}); xs.push(s); i{k} = i{k} + 1; }}"), Box::new(|sim, _| {
+                        for i in 0..3 { sim.s = i.to_string(); sim.xs.push(sim.s.clone()); }
+                        true
+                    })),
+                    9 => (format!("let t{k}: str = echo(s);\n    s = str({n});\n    xs.push(t{k});"), Box::new(move |sim, _| {
+                        let t = std::mem::replace(&mut sim.s, n.to_string());
+                        sim.xs.push(t);
+                        true
+                    })),
+                    _ => (format!("let j{k}: int = 0;\n    while j{k} < 5 {{ xs = [str(j{k})]; if j{k} == {t} {{ return; }} j{k} = j{k} + 1; }}"), Box::new(move |sim, _| {
+                        for j in 0..5 {
+                            sim.xs = vec![j.to_string()];
+                            if j == t { return false; }
+                        }
+                        true
+                    })),
+                };
+                ops.push(op);
+            }
+
+            let source = format!("\
+fn inner(ref xs: list<str>, ref s: str) {{
+    xs = [s];
+    s = str(7);
+}}
+fn echo(ref s: str) -> str {{
+    return s;
+}}
+fn callee(ref xs: list<str>, ref s: str, flag: int) {{
+    {}
+}}
+fn run() -> int {{
+    let xs: list<str> = [str(1)];
+    let s: str = str(2);
+    callee(xs, s, {});
+    callee(xs, s, {});
+    let total: int = 0;
+    let i: int = 0;
+    while i < xs.len() {{
+        total = (total * 31 + int(xs[i])) % 1000003;
+        i = i + 1;
+    }}
+    return ((total * 1000 + xs.len()) * 1000003 + int(s) % 1000003) % 2305843009213693951;
+}}
+return run();
+", ops.iter().map(|(code, _)| code.as_str()).collect::<Vec<_>>().join("\n    "), flags[0], flags[1]);
+
+            let mut sim = Sim { xs: vec!["1".to_string()], s: "2".to_string() };
+            for flag in flags {
+                for (_, op) in &ops {
+                    if !op(&mut sim, flag) {
+                        break;
+                    }
+                }
+            }
+            let total = sim.xs.iter().fold(0i64, |acc, x| (acc * 31 + x.parse::<i64>().unwrap()) % 1000003);
+            let len = sim.xs.len() as i64;
+            let expected = ((total * 1000 + len) * 1000003 + sim.s.parse::<i64>().unwrap() % 1000003) % 2305843009213693951;
+
+            let program = bolide_parser::parse_source(&source).unwrap();
+            // 测试用 str(<字面量>) 得到运行时新建的字符串，不折叠成驻留的字面量
+            let mut compiler = JitCompiler::new();
+            compiler.set_no_opt(true);
+            let main_ptr = compiler.compile(&program).unwrap_or_else(|e| panic!("{}\n{}", e, source));
+            let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+
+            let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+            let result = main_fn();
+            let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+            assert_eq!(result, expected, "round {}: wrong result\n{}", round, source);
+            assert_eq!(alloc_after - alloc_before, free_after - free_before, "round {}: strings leaked or freed twice\n{}", round, source);
+        }
+    }
+
+    #[test]
+    fn test_cfg_excluded_items_not_compiled() {
+        // 条件不成立的语句引用了不存在的库和函数，不会进入编译器
+        let source = r#"
+@cfg(os = "none")
+extern "no_such_library.so" {
+    fn missing_c() -> c_int;
+}
+@cfg(os = "none")
+fn broken() -> int {
+    return missing_c() + undefined_function();
+}
+let os = platform();
+print(os);
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        assert_eq!(program.statements.len(), 2);
+        let mut compiler = JitCompiler::new();
+        compiler.compile(&program).expect("excluded items should not be compiled");
+    }
+
+    #[test]
+    fn test_for_over_temporary_container() {
+        // 可迭代对象是语句临时值时，循环体里的语句不能提前释放它
+        let source = r#"
+fn first_long(n: int) -> int {
+    for x in [str(n), str(n * 100)] {
+        if len(x) > 2 {
+            return len(x);
+        }
+    }
+    return 0;
+}
+fn run() -> int {
+    let total: int = 0;
+    for x in [str(1), str(22)] {
+        let y: str = x + x;
+        total = total + len(y);
+    }
+    for k, v in {3: str(4444)} {
+        total = total + k * 10 + len(v);
+    }
+    return total * 1000 + first_long(7);
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        // 测试用 str(<字面量>) 得到运行时新建的字符串，不折叠成驻留的字面量
+        let mut compiler = JitCompiler::new();
+        compiler.set_no_opt(true);
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        assert_eq!(main_fn(), 40003);
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+    }
+
+    #[test]
+    fn test_weak_dict() {
+        let source = r#"
+class Node { id: int; }
+fn remember(cache: weak_dict<Node, float>, id: int) {
+    let temp: Node = Node(id);
+    cache.set(temp, id);
+}
+fn run() -> int {
+    let cache: weak_dict<Node, float> = weak_dict();
+    let a: Node = Node(1);
+    cache.set(a, 2.5);
+    remember(cache, 2);
+    let before: int = cache.len();
+    let dropped: int = cache.compact();
+    let doubled: float = cache.get(a) * 2.0;
+    return before * 100 + dropped * 10 + int(doubled);
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        // remember 的局部对象释放后表项失效：len() 先清理，compact() 没有可清理的
+        assert_eq!(main_fn(), 105);
+
+        let cases = [
+            ("let cache: weak_dict<Node, int> = weak_dict();\ncache.set(1, 2);\n", "weak_dict.set expects a Node key, found int"),
+            ("let cache: weak_dict<Node, int> = weak_dict();\ncache.get();\n", "weak_dict.get expects 1 argument(s), got 0"),
+            ("let cache: weak_dict<Node, int> = weak_dict();\ncache.keys();\n", "Unknown weak_dict method: keys"),
+            ("let cache: int = weak_dict();\n", "weak_dict() needs a declared type"),
+        ];
+        for (body, expected) in cases {
+            let source = format!("class Node {{ id: int; }}\n{}", body);
+            let program = bolide_parser::parse_source(&source).unwrap();
+            let err = JitCompiler::new().compile(&program).err().expect(body);
+            assert!(err.contains(expected), "{}: {}", body, err);
+        }
+    }
+
+    #[test]
+    fn test_object_live_registry() {
+        let source = r#"
+class Child {
+    label: str;
+    parent: weak Parent;
+}
+class Parent {
+    name: str;
+    child: Child;
+}
+class Ring {
+    next: Ring;
+}
+fn family() {
+    let c: Child = Child(str(1), none);
+    let p: Parent = Parent(str(2), c);
+    c.parent = p;
+}
+fn nested() {
+    let p: Parent = Parent(str(3), Child(str(4), none));
+    let orphan: Parent = Parent(str(5), none);
+}
+fn shared() {
+    let a: Child = Child(str(6), none);
+    let b: Child = a;
+}
+fn cycle() {
+    let a: Ring = Ring(none);
+    let b: Ring = Ring(a);
+    a.next = b;
+}
+fn run() -> int {
+    let was_on: bool = object_debug_enable(true);
+    family();
+    let after_family: int = object_live_count();
+    nested();
+    shared();
+    let after_shared: int = object_live_count();
+    cycle();
+    let after_cycle: int = object_live_count();
+    let still_on: bool = object_debug_enable(false);
+    let after_off: int = object_live_count();
+    let flags: int = 0;
+    if was_on {
+        flags = flags + 10000;
+    }
+    if still_on {
+        flags = flags + 1;
+    }
+    return flags + after_family * 1000 + after_shared * 100 + after_cycle * 10 + after_off;
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        // 测试用 str(<字面量>) 得到运行时新建的字符串，不折叠成驻留的字面量
+        let mut compiler = JitCompiler::new();
+        compiler.set_no_opt(true);
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        // 嵌套对象递归释放，weak 字段不释放；强引用环留下两个对象
+        assert_eq!(main_fn(), 21);
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+
+        let cases = [
+            ("object_live_count(1);\n", "object_live_count expects no arguments"),
+            ("object_debug_enable();\n", "object_debug_enable expects 1 argument"),
+            ("object_debug_enable(\"on\");\n", "object_debug_enable expects a bool argument, got Str"),
+        ];
+        for (source, expected) in cases {
+            let program = bolide_parser::parse_source(source).unwrap();
+            let err = JitCompiler::new().compile(&program).expect_err(source);
+            assert!(err.contains(expected), "{}: {}", source, err);
+        }
+    }
+
+    #[test]
+    fn test_match_statement() {
+        // 字符串 subject 是临时值；分支里 return 和循环里 break 前都要释放作用域内的字符串
+        let source = r#"
+fn code(s: str) -> int {
+    match s + "" {
+        "a" => { return 1; }
+        "ab", "abc" => {
+            let t: str = s + s;
+            return len(t);
+        }
+    }
+    return 0;
+}
+fn run() -> int {
+    let total: int = 0;
+    let i: int = -2;
+    while i < 1000 {
+        i = i + 1;
+        match i {
+            -1 => { total = total + 1; }
+            0, 2 => { total = total + 10; }
+            500 => {
+                let s: str = str(i);
+                total = total + len(s) * 100;
+                break;
+            }
+        }
+    }
+    return total * 100 + code("a") + code("ab") + code("abc") + code("b");
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+
+        // 第一次运行驻留字符串字面量（驻留实例不释放），第二次运行才统计
+        assert_eq!(main_fn(), 32100 + 1 + 4 + 6);
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        assert_eq!(main_fn(), 32100 + 1 + 4 + 6);
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+
+        for (bad, message) in [
+            ("match 1 {\n    _ => { }\n    1 => { }\n}\n", "'_' must be the last match arm"),
+            ("match 1 {\n    1, 2 => { }\n    2 => { }\n}\n", "duplicate match pattern 2"),
+            ("match 1 {\n    1 => { }\n    \"1\" => { }\n}\n", "all int or all str"),
+        ] {
+            let err = bolide_parser::parse_source(bad).err().expect(bad);
+            assert!(err.message.contains(message), "{}", err);
+        }
+        let program = bolide_parser::parse_source("match \"x\" {\n    1 => { }\n}\n").unwrap();
+        let err = JitCompiler::new().compile(&program).err().expect("int pattern on str");
+        assert!(err.contains("cannot have pattern Int(1)"), "{}", err);
+    }
+
+    #[test]
+    fn test_object_layout_matches_runtime() {
+        // 字段偏移和对象大小由运行时的常量推出，两边不一致时这里失败而不是运行时踩内存
+        let source = r#"
+class Base {
+    a: int;
+    b: float;
+}
+class Derived: Base {
+    c: str;
+    d: int;
+}
+fn run() -> int {
+    let x: Derived = Derived(1, 2.5, "s", 40);
+    return x.a + x.d + len(x.c);
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 42);
+
+        assert_eq!(compiler.ptr_type.bytes() as usize, bolide_runtime::OBJECT_FIELD_SIZE);
+        assert!(bolide_runtime::OBJECT_ALIGN >= bolide_runtime::OBJECT_FIELD_SIZE);
+        let derived = &compiler.classes["Derived"];
+        let offsets: Vec<usize> = derived.fields.iter().map(|f| f.offset).collect();
+        let field = bolide_runtime::OBJECT_FIELD_SIZE;
+        assert_eq!(offsets, [0, field, 2 * field, 3 * field]);
+        assert_eq!(derived.size, 4 * field);
+        assert_eq!(compiler.classes["Base"].size, 2 * field);
+
+        let obj = bolide_runtime::object_alloc(derived.size);
+        assert_eq!(bolide_runtime::bolide_object_size(obj), derived.size);
+        assert_eq!(obj as usize % bolide_runtime::OBJECT_ALIGN, 0);
+        bolide_runtime::object_release(obj);
+    }
+
+    #[test]
+    fn test_exit_from_nested_call() {
+        const CHILD_ENV: &str = "BOLIDE_EXIT_TEST_CHILD";
+        if std::env::var_os(CHILD_ENV).is_some() {
+            // 子进程：exit 在第 20 层调用里结束进程，之后的代码不执行
+            let source = "fn dive(n: int) -> int {\n    if n == 0 {\n        print(\"before\");\n        exit(7);\n        print(\"after\");\n    }\n    return dive(n - 1);\n}\nreturn dive(20);\n";
+            let program = bolide_parser::parse_source(source).unwrap();
+            let mut compiler = JitCompiler::new();
+            let main_ptr = compiler.compile(&program).unwrap();
+            let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+            main_fn();
+            std::process::exit(0);
+        }
+
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["jit::tests::test_exit_from_nested_call", "--exact", "--nocapture", "--test-threads=1"])
+            .env(CHILD_ENV, "1")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(output.status.code(), Some(7), "{}", stdout);
+        assert!(stdout.contains("before") && !stdout.contains("after"), "{}", stdout);
+
+        if cfg!(unix) {
+            assert_eq!(bolide_runtime::exit_status(3), 3);
+            assert_eq!(bolide_runtime::exit_status(-1), 255);
+            assert_eq!(bolide_runtime::exit_status(256), 255);
+            assert_eq!(bolide_runtime::exit_status(258), 2);
+        }
+        assert_eq!(bolide_runtime::exit_status(0), 0);
+
+        let program = bolide_parser::parse_source("exit(\"1\");\n").unwrap();
+        let err = JitCompiler::new().compile(&program).err().expect("str exit code");
+        assert!(err.contains("exit expects an int status code"), "{}", err);
+    }
+
+    #[test]
+    fn test_channel_send_all_element_type() {
+        let ok = "fn f() {\n    let c: channel<str> = channel();\n    let xs: list<str> = [\"a\"];\n    c.send_all(xs);\n    let ys: list<str> = c.recv_n(1);\n}\n";
+        let program = bolide_parser::parse_source(ok).unwrap();
+        JitCompiler::new().compile(&program).expect("matching element types");
+
+        let bad = ok.replace("list<str> = [\"a\"]", "list<int> = [1]");
+        let program = bolide_parser::parse_source(&bad).unwrap();
+        let err = JitCompiler::new().compile(&program).err().expect("mismatched element types");
+        assert!(err.contains("send_all on channel<Str> expects list<Str>, got list<Int>"), "{}", err);
+    }
+
+    #[test]
+    fn test_for_over_channel() {
+        let source = r#"
+fn produce(ch: channel<int>, n: int) -> int {
+    for i in range(n) {
+        ch <- i;
+    }
+    ch.close();
+    return n;
+}
+
+fn words(ch: channel<str>, n: int) -> int {
+    for i in range(n) {
+        ch <- "w" + str(i);
+    }
+    ch.close();
+    return n;
+}
+
+fn run() -> int {
+    let ch: channel<int> = channel();
+    let t: future = spawn produce(ch, 100);
+    let sum: int = 0;
+    for v in ch {
+        sum = sum + v;
+    }
+    join(t);
+
+    let sc: channel<str> = channel(2);
+    let w: future = spawn words(sc, 10);
+    let chars: int = 0;
+    for s in sc {
+        chars = chars + len(s);
+    }
+    join(w);
+    return sum * 100 + chars;
+}
+
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+
+        // 第一次运行驻留字符串字面量，第二次运行才统计：接收的字符串都由循环变量释放
+        assert_eq!(main_fn(), 4950 * 100 + 20);
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        assert_eq!(main_fn(), 4950 * 100 + 20);
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+    }
+
+    #[test]
+    fn test_sort_by_key_needs_typed_key() {
+        let ok = "fn key(s: str) -> float {\n    return 1.5;\n}\nfn f(xs: list<str>) -> str {\n    xs.sort_by_key(key);\n    return xs.max_by(key);\n}\n";
+        let program = bolide_parser::parse_source(ok).unwrap();
+        JitCompiler::new().compile(&program).expect("float key function");
+
+        for (from, to, msg) in [
+            ("-> float {\n    return 1.5;", "-> list<int> {\n    return [];", "sort_by_key key function must return int, float or str"),
+            ("key(s: str)", "key(s: str, t: str)", "sort_by_key key function must take 1 argument, got 2"),
+            ("xs.sort_by_key(key);", "let k: func = key;\n    xs.sort_by_key(k);", "sort_by_key key function needs a declared signature"),
+            ("xs: list<str>", "xs: list<float>", "sort_by_key does not support float lists"),
+        ] {
+            let bad = ok.replace(from, to);
+            let program = bolide_parser::parse_source(&bad).unwrap();
+            let err = JitCompiler::new().compile(&program).err().expect(msg);
+            assert!(err.contains(msg), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_sort_by_comparator() {
+        let ok = "fn cmp(a: str, b: str) -> int {\n    return len(b) - len(a);\n}\nfn f(xs: list<str>) {\n    xs.sort_by(cmp);\n    xs.sort();\n}\n";
+        let program = bolide_parser::parse_source(ok).unwrap();
+        JitCompiler::new().compile(&program).expect("str comparator");
+
+        for (from, to, msg) in [
+            ("cmp(a: str, b: str) -> int {\n    return len(b) - len(a);", "cmp(a: str) -> int {\n    return len(a);", "sort_by comparator must take 2 arguments, got 1"),
+            ("-> int {\n    return len(b) - len(a);", "-> bool {\n    return true;", "sort_by comparator must return int, got bool"),
+            ("xs.sort_by(cmp);", "let c: func = cmp;\n    xs.sort_by(c);", "sort_by comparator needs a declared signature"),
+            ("xs.sort();", "xs.sort(cmp);", "sort expects no arguments"),
+            ("xs: list<str>", "xs: list<float>", "sort_by does not support float lists"),
+            ("xs.sort_by(cmp);\n", "let ys: list<list<int>> = [];\n    ys.sort();\n", "sort() only supports int, bool, float and str lists, got list<list<int>>"),
+        ] {
+            let bad = ok.replace(from, to);
+            let program = bolide_parser::parse_source(&bad).unwrap();
+            let err = JitCompiler::new().compile(&program).err().expect(msg);
+            assert!(err.contains(msg), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_map_filter_reduce() {
+        let source = r#"
+fn double(x: int) -> int {
+    return x * 2;
+}
+fn odd(x: int) -> bool {
+    return x % 2 == 1;
+}
+fn add(acc: int, x: int) -> int {
+    return acc + x;
+}
+fn label(x: int) -> str {
+    return str(x) + "!";
+}
+fn join(acc: str, s: str) -> str {
+    return acc + s;
+}
+fn run() -> int {
+    let xs: list<int> = [1, 2, 3, 10];
+    let doubled: list<int> = map(double, xs);
+    let total: int = reduce(add, map(double, filter(odd, xs)), 0);
+    let labels: list<str> = map(label, xs);
+    let joined: str = reduce(join, labels, ">");
+    let empty: list<int> = [];
+    let fallback: int = reduce(add, empty, 7) + len(map(label, empty));
+    return doubled[3] * 1000 + total * 100 + len(joined) * 10 + fallback;
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        // doubled[3] = 20；(1 + 3) * 2 = 8；">1!2!3!10!" 长 10；空列表 reduce 得到 7
+        assert_eq!(main_fn(), 20 * 1000 + 8 * 100 + 10 * 10 + 7);
+        // 回调返回的字符串和结果列表都在语句结束或作用域结束时释放
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        main_fn();
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+
+        let f = "fn inc(x: int) -> int {\n    return x + 1;\n}\nlet xs: list<int> = [1];\n";
+        for (call, msg) in [
+            ("reduce(inc, xs, 0)", "reduce function must take 2 argument(s), got 1"),
+            ("filter(inc, xs)", "filter function must return bool, got Some(Int)"),
+            ("map(inc, 5)", "map expects a list as its second argument"),
+            ("map(inc)", "map expects 2 arguments"),
+            ("map(inc, [1.5])", "map does not support float lists"),
+        ] {
+            let program = bolide_parser::parse_source(&format!("{}let r = {};\n", f, call)).unwrap();
+            let err = JitCompiler::new().compile(&program).err().expect(msg);
+            assert!(err.contains(msg), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_lambdas() {
+        let source = r#"
+let square = fn(x: int) -> int { return x * x; };
+fn run() -> int {
+    let sum: int = test_callback(fn(a: int, b: int) -> int { return a + b; }, 3, 4);
+    let fs: list<func(int) -> int> = [square, fn(x: int) -> int { return x + 1; }];
+    let total: int = 0;
+    for f in fs {
+        total += f(10);
+    }
+    let odd = fn(x: int) -> bool { return x % 2 == 1; };
+    let kept: list<int> = map(square, filter(odd, [1, 2, 3]));
+    let worker = fn(a: int, b: int) -> int { return a * b; };
+    let h = spawn worker(6, 7);
+    let g = spawn square(5);
+    return sum * 10000 + total * 10 + kept[1] + join(h) + join(g);
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        // 7；100 + 11；kept = [1, 9]；6 * 7；5 * 5
+        assert_eq!(main_fn(), 7 * 10000 + 111 * 10 + 9 + 42 + 25);
+
+        for (source, msg) in [
+            ("fn f(k: int) -> list<int> {\n    return map(fn(x: int) -> int { return x * k; }, [1]);\n}\n",
+             "lambda in 'f' captures 'k' from the enclosing function"),
+            ("fn f(g: func(int) -> int) -> int {\n    let h = spawn g(1);\n    return join(h);\n}\n",
+             "cannot spawn 'g': spawn needs a function name"),
+            ("let f = fn() -> int { return 1; };\nf = fn() -> int { return 2; };\nlet h = spawn f();\n",
+             "cannot spawn 'f'"),
+        ] {
+            let program = bolide_parser::parse_source(source).unwrap();
+            let err = JitCompiler::new().compile(&program).expect_err(msg);
+            assert!(err.contains(msg), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_recursive_classes() {
+        let src = "class Node {\n    value: int;\n    next: Node = Node(0);\n}\n";
+        let program = bolide_parser::parse_source(src).unwrap();
+        let err = JitCompiler::new().compile(&program).err().expect("self-constructing default");
+        assert_eq!(err, "field defaults construct each other without end: Node.next -> Node.next");
+
+        // 字段类型指向自己的类照常编译；成环的实例转字符串、逐字段比较都会结束
+        let source = r#"
+class Node {
+    value: int;
+    next: Node = none;
+}
+fn run() -> int {
+    let a: Node = Node(1);
+    let b: Node = Node(1);
+    a.next = b;
+    b.next = a;
+    let text: str = str(a);
+    let same: bool = a == b;
+    b.next = none;
+    if same {
+        return len(text);
+    }
+    return 0 - len(text);
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), "Node { value: 1, next: Node { value: 1, next: ... } }".len() as i64);
+    }
+
+    #[test]
+    fn test_method_chaining() {
+        let source = r#"
+class Builder {
+    x: int;
+    y: int;
+    name: str;
+    fn set_x(v: int) -> Builder {
+        self.x = v;
+        return self;
+    }
+    fn set_y(v: int) -> Builder {
+        self.y = v;
+        return self;
+    }
+    fn set_name(n: str) -> Builder {
+        self.name = n;
+        return self;
+    }
+}
+fn build(cache: weak_dict<Builder, int>) -> int {
+    let b: Builder = Builder(0, 0, "");
+    b.set_x(1).set_y(2).set_name("pt");
+    let first: int = b.x * 10 + b.y;
+    let c: Builder = b.set_x(3).set_y(4);
+    let d: Builder = Builder(0, 0, "").set_x(5).set_name("q");
+    cache.set(b, 1);
+    cache.set(d, 2);
+    return first * 10000 + c.x * 1000 + c.y * 100 + d.x * 10 + len(b.name);
+}
+fn run() -> int {
+    let cache: weak_dict<Builder, int> = weak_dict();
+    let result: int = build(cache);
+    // 链上每一步返回的 self 都已释放：b 和 d 出了作用域，表项随之失效
+    return result * 10 + cache.len();
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 1234520);
+    }
+
+    #[test]
+    fn test_trace_attribute() {
+        const CHILD_ENV: &str = "BOLIDE_TRACE_TEST_CHILD";
+        let source = r#"
+class Point {
+    x: int;
+    y: int;
+    @trace(ret)
+    fn moved(dx: int) -> Point {
+        return Point(self.x + dx, self.y);
+    }
+}
+@trace
+fn greet(name: str, times: int, p: Point, xs: list<int>) -> int {
+    return times;
+}
+@trace(ret)
+fn classify(n: int) -> str {
+    if n < 0 {
+        return "neg";
+    }
+    for i in range(3) {
+        if i == n {
+            return "small";
+        }
+    }
+    return "big";
+}
+@trace(ret)
+fn bump(ref total: int, by: int) {
+    total = total + by;
+}
+@trace(ret)
+fn twice(n: int) -> int {
+    return n * 2;
+}
+fn run() -> int {
+    let p: Point = Point(1, 2);
+    let n: int = greet("ada", 3, p, [1]);
+    let kinds: str = classify(-1) + classify(2) + classify(9);
+    let t: int = 5;
+    bump(t, 7);
+    let q: Point = p.moved(10);
+    let h: future = spawn twice(21);
+    return n + len(kinds) + t + q.x + join(h);
+}
+return run();
+"#;
+        if std::env::var_os(CHILD_ENV).is_some() {
+            let program = bolide_parser::parse_source(source).unwrap();
+            let mut compiler = JitCompiler::new();
+            let main_ptr = compiler.compile(&program).unwrap();
+            let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+            // 3 + len("negsmallbig") + 12 + 11 + 42
+            assert_eq!(main_fn(), 3 + 11 + 12 + 11 + 42);
+            // 日志拼接产生的字符串都已释放
+            let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+            main_fn();
+            let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+            assert_eq!(alloc_after - alloc_before, free_after - free_before);
+            std::process::exit(0);
+        }
+
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["jit::tests::test_trace_attribute", "--exact", "--nocapture", "--test-threads=1"])
+            .env(CHILD_ENV, "1")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{}\n{}", stdout, String::from_utf8_lossy(&output.stderr));
+        // 第一行跟在测试框架的 `test ... ` 后面
+        let lines: Vec<&str> = stdout.lines().filter_map(|l| l.find("[trace]").map(|i| &l[i..])).collect();
+        assert_eq!(lines[..14], [
+            "[trace] greet(name=ada, times=3, p=Point { x: 1, y: 2 }, xs=<list>)",
+            "[trace] classify(n=-1)",
+            "[trace] classify -> neg",
+            "[trace] classify(n=2)",
+            "[trace] classify -> small",
+            "[trace] classify(n=9)",
+            "[trace] classify -> big",
+            "[trace] bump(total=5, by=7)",
+            "[trace] bump returned",
+            "[trace] Point.moved(dx=10)",
+            "[trace] Point.moved -> Point { x: 11, y: 2 }",
+            "[trace] twice(n=21)",
+            "[trace] twice -> 42",
+            "[trace] greet(name=ada, times=3, p=Point { x: 1, y: 2 }, xs=<list>)",
+        ]);
+        assert_eq!(lines.len(), 26);
+
+        // --no-trace：生成的代码和没有标注时相同
+        let analyze = |source: &str, no_trace: bool| {
+            let program = bolide_parser::parse_source(source).unwrap();
+            let mut compiler = JitCompiler::new();
+            compiler.set_no_trace(no_trace);
+            compiler.analyze(&program).unwrap()
+        };
+        let plain = source.replace("    @trace(ret)\n", "").replace("@trace(ret)\n", "").replace("@trace\n", "");
+        let traced = analyze(source, false);
+        let stripped = analyze(source, true);
+        let untraced = analyze(&plain, false);
+        for name in ["greet", "classify", "bump", "twice", "Point_moved"] {
+            let (t, s, u) = (&traced.functions[name], &stripped.functions[name], &untraced.functions[name]);
+            assert!(t.temps_tracked > u.temps_tracked, "{}", name);
+            assert_eq!(s.temps_tracked, u.temps_tracked, "{}", name);
+            assert_eq!(s.rc_ops, u.rc_ops, "{}", name);
+            assert_eq!(s.allocs, u.allocs, "{}", name);
+            assert_eq!(s.rc_variables.len(), u.rc_variables.len(), "{}", name);
+        }
+        assert_eq!(stripped.to_text(), untraced.to_text());
+    }
+
+    #[test]
+    fn test_int_of_str_is_deprecated() {
+        let src = "fn f(s: str) -> int {\n    return int(s) + int(s) + int(2.5);\n}\nlet x: float = float(\"1.5\") + parse_float(\"2\");\nlet n: int = parse_int(\"ff\", 16);\n";
+        let program = bolide_parser::parse_source(src).unwrap();
+        let mut compiler = JitCompiler::new();
+        compiler.compile(&program).expect("deprecated conversions still compile");
+        // 同一函数里的重复用法只警告一次；parse_int / parse_float 和非 str 参数不警告
+        assert_eq!(compiler.warnings(), [
+            "in function 'f': int() on a str is deprecated; use parse_int(s), which also rejects out-of-range input",
+            "in top-level code: float() on a str is deprecated; use parse_float(s), which also rejects out-of-range input",
+        ]);
+
+        let bad = "let n: int = parse_int(12);\n";
+        let program = bolide_parser::parse_source(bad).unwrap();
+        let err = JitCompiler::new().compile(&program).err().expect("parse_int of an int");
+        assert!(err.contains("parse_int() expects a str, got Int"), "{}", err);
+    }
+
+    #[test]
+    fn test_fstring() {
+        let source = r#"
+class P {
+    x: int;
+    y: int;
+}
+fn check(got: str, want: str, bit: int) -> int {
+    if got == want {
+        return 0;
+    }
+    print(got);
+    return bit;
+}
+fn run() -> int {
+    let name: str = "bo";
+    let n: int = 42;
+    let big: bigint = 12345B;
+    let nums: list<int> = [1, 2];
+    let d: dict<int, bool> = {7: true};
+    let t: (int, int) = (3, 4);
+    let p: P = P(1, 2);
+    let bad: int = 0;
+    bad = bad + check(f"hi {name}, {n + 1}!", "hi bo, 43!", 1);
+    bad = bad + check(f"{2.5} {3.0} {true} {n > 50}", "2.5 3 true false", 2);
+    bad = bad + check(f"{big * big} {19.99D}", "152399025 19.99", 4);
+    bad = bad + check(f"{nums}{d}{t}", "[1, 2]{7: true}(3, 4)", 8);
+    bad = bad + check(f"{p}", "P { x: 1, y: 2 }", 16);
+    bad = bad + check(f"{{{n}}} }} {{", "{42} } {", 32);
+    bad = bad + check(f"a\tb {f"{len(name)}"}", "a\tb 2", 64);
+    bad = bad + check(f"", "", 128);
+    return bad;
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 0);
+        // 除结果外不分配中间字符串，结果在语句结束时释放
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        main_fn();
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+
+        let bad = ["print(f\"{x\");\n", "print(f\"a } b\");\n"];
+        for src in bad {
+            assert!(bolide_parser::parse_source(src).is_err(), "{}", src);
+        }
+        let program = bolide_parser::parse_source("let c: channel<int> = channel();\nprint(f\"{c}\");\n").unwrap();
+        let err = JitCompiler::new().compile(&program).expect_err("f-string of a channel");
+        assert!(err.contains("f-string cannot format a value of type"), "{}", err);
+    }
+
+    #[test]
+    fn test_pure_violations() {
+        let err = |src: &str| {
+            let program = bolide_parser::parse_source(src).unwrap();
+            JitCompiler::new().compile(&program).err().expect("impure @pure function")
+        };
+        let cases = [
+            ("@pure\nfn f(x: int) -> int {\n    print(x);\n    return x;\n}\n", "`print(x)` calls print(), which has side effects"),
+            ("let g: int = 1;\n@pure\nfn f(x: int) -> int {\n    return x + g;\n}\n", "`g` reads global 'g'"),
+            ("let g: int = 1;\n@pure\nfn f(x: int) -> int {\n    g = x;\n    return x;\n}\n", "`g = ...` assigns to global 'g'"),
+            ("@pure\nfn f(ref x: int) -> int {\n    x = 2;\n    return x;\n}\n", "`x = ...` assigns to ref parameter 'x'"),
+            ("@pure\nfn f(xs: list<int>) -> int {\n    xs.push(1);\n    return len(xs);\n}\n", "`xs.push(1)` modifies 'xs', which may be shared with the caller"),
+            ("@pure\nfn f(xs: list<int>) -> int {\n    let ys: list<int> = xs;\n    ys += [1];\n    return len(ys);\n}\n", "`ys += ...` modifies 'ys', which may be shared with the caller"),
+            ("fn log(x: int) -> int {\n    print(x);\n    return x;\n}\n@pure\nfn f(x: int) -> int {\n    return log(x);\n}\n", "`log(x)` calls 'log', which is not pure: `print(x)` calls print()"),
+            ("fn down(x: int) -> int {\n    return down(x - 1);\n}\n@pure\nfn f(x: int) -> int {\n    return down(x);\n}\n", "calls 'down', which is recursive and not marked @pure"),
+            ("@pure\nfn f(x: int) -> int {\n    let c: channel<int> = channel();\n    return x;\n}\n", "`channel()` calls channel(), which has side effects"),
+        ];
+        for (src, reason) in cases {
+            let err = err(src);
+            assert!(err.contains("@pure function 'f' has side effects: ") && err.contains(reason), "{}", err);
+        }
+
+        // 新建的列表可以修改，标注 @pure 的函数可以递归，未标注的纯函数可以调用
+        let ok = "fn inc(x: int) -> int {\n    return x + 1;\n}\n@pure\nfn f(xs: list<int>, n: int) -> list<int> {\n    let ys: list<int> = xs.copy();\n    ys.push(inc(n));\n    ys += [n];\n    let m: int = n;\n    m -= 1;\n    if n > 0 {\n        return f(ys, m);\n    }\n    return ys;\n}\n";
+        let program = bolide_parser::parse_source(ok).unwrap();
+        JitCompiler::new().compile(&program).expect("pure function");
+
+        let parse_err = |src: &str| bolide_parser::parse_source(src).err().expect("misplaced @pure").message;
+        assert!(parse_err("@pure\nlet x: int = 1;\n").contains("@pure only applies to function definitions"));
+        assert!(parse_err("@pure\nasync fn f() -> int {\n    return 1;\n}\n").contains("@pure cannot be applied to async function 'f'"));
+        assert!(parse_err("@pure(unix)\nfn f() -> int {\n    return 1;\n}\n").contains("@pure takes no arguments"));
+    }
+
+    #[test]
+    fn test_analyze_pure_calls() {
+        let src = "@pure\nfn sq(x: int) -> int {\n    return x * x;\n}\n\n@pure\nfn limit(n: int) -> int {\n    let total: int = 0;\n    for i in range(n) {\n        total += i;\n    }\n    return total;\n}\n\nfn folded() -> int {\n    return sq(12) + limit(10);\n}\n\nfn looped(n: int) -> int {\n    let i: int = 0;\n    let total: int = 0;\n    while i < limit(n) and sq(i) < limit(n) {\n        total = total + sq(i) + sq(i);\n        i = i + 1;\n    }\n    return total;\n}\n\nfn changing(n: int) -> int {\n    while limit(n) > 0 {\n        n = n - 1;\n    }\n    return n;\n}\n";
+        let program = bolide_parser::parse_source(src).unwrap();
+        let report = JitCompiler::new().analyze(&program).expect("analyze");
+
+        // 实参都是字面量：编译期求值，不生成调用
+        let folded = report.function("folded").unwrap();
+        assert_eq!(folded.pure_calls_folded, 2);
+        assert!(folded.calls.is_empty(), "{:?}", folded.calls);
+
+        // limit(n) 提到循环之前，条件里第二次用到时复用；循环体里的 sq(i) 只调用一次
+        let looped = report.function("looped").unwrap();
+        assert_eq!((looped.pure_calls_folded, looped.pure_calls_reused, looped.pure_calls_hoisted), (0, 2, 1));
+        assert!(looped.calls.contains(&(CallKind::Call, "limit".to_string())));
+
+        // 实参在循环里被修改，不能提到循环外
+        let changing = report.function("changing").unwrap();
+        assert_eq!(changing.pure_calls_hoisted, 0);
+        assert!(report.to_json().contains("\"pure_calls\": {\"folded\": 2, \"reused\": 0, \"hoisted\": 0}"));
+    }
+
+    #[test]
+    fn test_literal_strings_folded() {
+        let src = "fn labels() -> list<str> {\n    let a: str = \"metric_\" + \"cpu\" + \"_\" + str(0);\n    let b: str = \"metric_\" + \"disk\" + \"_\" + str(2) + \".\" + str(5);\n\
+                   let c: str = f\"{\"net\"}_{3}_{true}\";\n    let d: str = \"a\" + \"b\" + \"c\" + \"d\" + \"e\" + \"f\";\n    return [a, b, c, d];\n}\n";
+        let analyze = |no_opt: bool| {
+            let program = bolide_parser::parse_source(src).unwrap();
+            let mut compiler = JitCompiler::new();
+            compiler.set_no_opt(no_opt);
+            compiler.analyze(&program).unwrap()
+        };
+        let folded = analyze(false);
+        assert_eq!(folded.functions["labels"].allocs.keys().collect::<Vec<_>>(), ["list_new"]);
+        // --no-opt 时照常在运行时拼接
+        let unfolded = analyze(true);
+        assert_eq!(unfolded.functions["labels"].allocs["string_concat"], 13);
+
+        // 折叠出的 float 文本和运行时转换的结果相同（字面量没有指数写法，1e300 按位写出）
+        let big = format!("1{}.0", "0".repeat(300));
+        let values = ["-0.0", "0.1", &big, "1000000000000000000000.0", "123456789.125", "0.000001", "-2.5"];
+        let mut source = String::from("fn run() -> int {\n    let same: int = 0;\n");
+        for value in values {
+            source.push_str(&format!("    let v: float = {};\n    if str(v) == str({}) and f\"{{v}}\" == f\"{{{}}}\" {{\n        same = same + 1;\n    }}\n", value, value, value));
+        }
+        source.push_str("    return same;\n}\nreturn run();\n");
+        let program = bolide_parser::parse_source(&source).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), values.len() as i64);
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        let src = r#"
+fn trace(n: int) -> int {
+    return n;
+}
+
+fn step(n: int) -> int {
+    if 1 > 2 {
+        return trace(n);
+    } elif 2 * 2 == 4 {
+        let k: int = 10 * 10;
+        n = n + k;
+    }
+    while false {
+        n = trace(n);
+    }
+    if true {
+        return n + -(3 - 1);
+    }
+    return trace(n);
+}
+
+fn run() -> int {
+    let total: int = 0;
+    for i in range(0, 5) {
+        total = total + step(i) * (7 % 4);
+        if 1.5 * 2.0 > 2.5 and not false {
+            total = total + 1;
+        }
+    }
+    return total;
+}
+return run();
+"#;
+        let run = |no_opt: bool| {
+            let program = bolide_parser::parse_source(src).unwrap();
+            let mut compiler = JitCompiler::new();
+            compiler.set_no_opt(no_opt);
+            let main_ptr = compiler.compile(&program).unwrap();
+            let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+            main_fn()
+        };
+        assert_eq!(run(false), run(true));
+        assert_eq!(run(false), 1505);
+
+        // 不会执行的分支和 return 之后的语句不生成代码
+        let analyze = |no_opt: bool| {
+            let program = bolide_parser::parse_source(src).unwrap();
+            let mut compiler = JitCompiler::new();
+            compiler.set_no_opt(no_opt);
+            compiler.analyze(&program).unwrap()
+        };
+        let calls = |report: &AnalysisReport| report.functions["step"].calls.iter().map(|(_, name)| name.clone()).collect::<Vec<_>>();
+        assert!(calls(&analyze(false)).is_empty());
+        assert_eq!(calls(&analyze(true)), ["trace"]);
+    }
+
+    #[test]
+    fn test_checked_destructure_allocates_no_tuple() {
+        let src = "fn split(a: int, b: int) -> int {\n    let ok, v = checked_add(a, b);\n    if ok {\n        return v;\n    }\n    return saturating_mul(a, b) + wrapping_sub(a, b);\n}\n\nfn stored(a: int, b: int) -> int {\n    let r = checked_mul(a, b);\n    let ok, v = r;\n    return v;\n}\n";
+        let program = bolide_parser::parse_source(src).unwrap();
+        let report = JitCompiler::new().analyze(&program).expect("analyze");
+
+        // 直接解构：两个结果绑定到变量，内置函数都是内联指令
+        let split = report.function("split").unwrap();
+        assert!(split.allocs.is_empty(), "{:?}", split.allocs);
+        assert!(split.calls.is_empty(), "{:?}", split.calls);
+
+        // 结果先存成值：分配一个元组
+        let stored = report.function("stored").unwrap();
+        assert_eq!(stored.allocs.get("tuple_new"), Some(&1));
+        assert!(report.to_json().contains("\"allocs\": {\"tuple_new\": 1}"));
+
+        for (call, msg) in [
+            ("checked_add(a)", "checked_add expects 2 int arguments, got 1"),
+            ("wrapping_mul(a, 1.5)", "wrapping_mul expects int arguments, but argument 2 is float"),
+            ("saturating_sub(\"x\", a)", "saturating_sub expects int arguments, but argument 1 is str"),
+        ] {
+            let bad = format!("fn f(a: int) -> int {{\n    let r = {};\n    return 0;\n}}\n", call);
+            let program = bolide_parser::parse_source(&bad).unwrap();
+            let err = JitCompiler::new().compile(&program).err().expect(call);
+            assert!(err.contains(msg), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_globals_shared_and_released() {
+        let source = r#"
+let counter: int = 0;
+let names: list<str> = [];
+let last = "";
+
+fn inc() {
+    counter = counter + 1;
+    last = str(counter) + "!";
+    names.push(last);
+}
+
+inc();
+inc();
+inc();
+return counter * 100 + names.len() * 10 + len(last);
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+
+        // 函数读写的是同一个全局变量
+        assert_eq!(main_fn(), 332);
+        compiler.release_globals();
+
+        // 释放后全局变量置零，再次运行重新初始化；第一次运行驻留了字符串字面量，第二次运行才统计
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        assert_eq!(main_fn(), 332);
+        compiler.release_globals();
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+    }
+
+    #[test]
+    fn test_threadlocal_globals() {
+        let source = r#"
+threadlocal let counter: int = 0;
+threadlocal let tag: str = str(0);
+let total: int = 0;
+
+fn bump(n: int) -> int {
+    for i in range(n) {
+        counter = counter + 1;
+        total = total + 1;
+    }
+    tag = tag + str(n);
+    return counter * 10 + len(tag);
+}
+
+let a: future = spawn bump(3);
+let r1: int = join(a);
+let b: future = spawn bump(5);
+let r2: int = join(b);
+return r1 * 1000 + r2 * 10 + counter * 100 + total;
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+
+        // 每个线程从 0 开始计数，主线程的 counter 没有被修改；普通全局变量累加了两个线程
+        assert_eq!(main_fn(), 32 * 1000 + 52 * 10 + 8);
+        compiler.release_globals();
+
+        // 每个线程初始化自己的 tag，在线程结束时释放（第二次运行才统计，见上一个测试）
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        assert_eq!(main_fn(), 32 * 1000 + 52 * 10 + 8);
+        compiler.release_globals();
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+
+        assert!(bolide_parser::parse_source("fn f() {\n    threadlocal let x: int = 0;\n}\n").is_err());
+    }
+
+    #[test]
+    fn test_share_containers() {
+        // 每个绑定位置得到 a 后追加一个元素；复制时只有传参和列表字面量与 a 共享
+        let source = r#"
+class Box {
+    items: list<str>;
+}
+
+let g: list<str> = [];
+
+fn add_one(xs: list<str>) {
+    xs.push("param");
+}
+
+fn give(b: Box) -> list<str> {
+    return b.items;
+}
+
+fn pick(xs: list<str>, ys: list<str>) -> list<str> {
+    if len(xs) >= len(ys) {
+        return xs;
+    }
+    return ys;
+}
+
+fn run() -> int {
+    let a: list<str> = [str(1), str(2)];
+    let b = a;
+    b.push("decl");
+    let c: list<str> = [];
+    c = a;
+    c.push("assign");
+    g = a;
+    g.push("global");
+    let box = Box(a);
+    box.items.push("constructor");
+    let other = Box([]);
+    other.items = a;
+    other.items.push("member");
+    let r = give(box);
+    r.push("return");
+    let p = pick(a, b);
+    p.push("return param");
+    let t: (list<str>, int) = (a, 1);
+    let u, n = t;
+    u.push("tuple");
+    let outer: list<list<str>> = [a];
+    outer[0].push("literal");
+    let copy: list<str> = a.copy();
+    copy.push("copy");
+    add_one(a);
+    let d: dict<str, int> = {"x": 1};
+    let e = d;
+    e[str(2)] = 2;
+    g = [];
+    return len(a) * 10 + len(d);
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        for (share, expected) in [(false, 41), (true, 122)] {
+            let mut compiler = JitCompiler::new();
+            compiler.set_share_containers(share);
+            let main_ptr = compiler.compile(&program).unwrap();
+            let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+            assert_eq!(main_fn(), expected, "share_containers = {}", share);
+            // 共享的列表只在最后一个引用释放时释放一次（第一次运行驻留了字面量，不计入）
+            let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+            assert_eq!(main_fn(), expected, "share_containers = {}", share);
+            let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+            assert_eq!(alloc_after - alloc_before, free_after - free_before, "share_containers = {}", share);
+            compiler.release_globals();
+        }
+    }
+
+    #[test]
+    fn test_float_list_elements() {
+        let source = r#"
+fn mean(xs: list<float>) -> float {
+    let total: float = 0.0;
+    for x in xs {
+        total += x;
+    }
+    return total / float(len(xs));
+}
+
+let xs: list<float> = [2.5, -0.25];
+xs.push(1);
+xs.push(0.75);
+xs[0] = xs[0] * 2.0;
+xs.sort();
+let first: float = xs.first();
+let last: float = xs.pop();
+return int(first * 100.0) * 10000 + int(last * 100.0) * 10 + int(mean(xs) * 4.0);
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        // [-0.25, 0.75, 1, 5]：first -0.25，pop 5，剩下的平均值 0.5
+        assert_eq!(main_fn(), -25 * 10000 + 500 * 10 + 2);
+    }
+
+    #[test]
+    fn test_module_qualified_members() {
+        let dir = std::env::temp_dir().join(format!("bolide_qualified_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let module = dir.join("geom.bl");
+        std::fs::write(&module, r#"
+let SCALE: int = 10;
+class Point {
+    x: int;
+    y: int;
+}
+fn make(x: int) -> Point {
+    return Point(x, x + 1);
+}
+"#).unwrap();
+        let import = format!("import \"{}\";\n", module.display());
+
+        let source = format!(r#"{}
+fn norm(p: geom.Point) -> int {{
+    return p.x + p.y;
+}}
+fn run() -> int {{
+    let p = geom.Point(1, 2);
+    let q = geom.make(4);
+    return norm(p) * 1000 + q.y * geom.SCALE;
+}}
+return run();
+"#, import);
+        let program = bolide_parser::parse_source(&source).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 3050);
+
+        let compile_err = |body: &str| {
+            let program = bolide_parser::parse_source(&format!("{}{}", import, body)).unwrap();
+            JitCompiler::new().compile(&program).err().expect("should fail to compile")
+        };
+        assert!(compile_err("print(geom.nope);\n").contains(
+            "module 'geom' has no member 'nope'; it exports functions: make; classes: Point; globals: SCALE"));
+        assert!(compile_err("fn f(p: geom.Line) {\n}\n").contains("module 'geom' has no member 'Line'"));
+        assert!(compile_err("print(geom.SCALE(1));\n").contains("'geom.SCALE' is not a function"));
+        assert!(compile_err("fn f() {\n    geom.SCALE = 1;\n}\n").contains("cannot assign to 'geom.SCALE'"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_module_parse_error_has_path() {
+        let dir = std::env::temp_dir().join(format!("bolide_module_error_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let module = dir.join("broken.bl");
+        std::fs::write(&module, "fn ok() -> int {\n    return 1;\n}\nlet x: int = ;\nlet y: int = 2 $ 3;\n").unwrap();
+        let program = bolide_parser::parse_source(&format!("import \"{}\";\n", module.display())).unwrap();
+        let err = JitCompiler::new().compile(&program).err().expect("broken module");
+        // 每处错误一行，都以模块路径开头
+        let path = module.display().to_string();
+        let lines: Vec<&str> = err.lines().skip(1).collect();
+        assert_eq!(lines.len(), 2, "{}", err);
+        assert!(lines[0].starts_with(&format!("{}:4:14: ", path)), "{}", err);
+        assert!(lines[1].starts_with(&format!("{}:5:16: ", path)), "{}", err);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_async_fan_out_on_worker_pool() {
+        let source = r#"
+async fn sum_range(lo: int, hi: int) -> int {
+    if hi - lo == 1 {
+        return lo;
+    }
+    let mid: int = (lo + hi) / 2;
+    let left: future = sum_range(lo, mid);
+    let right: future = sum_range(mid, hi);
+    let a: int = await left;
+    let b: int = await right;
+    return a + b;
+}
+fn run() -> int {
+    set_coroutine_workers(2);
+    let f: future = sum_range(0, 5000);
+    return await f;
+}
+return run();
+"#;
+        let limit = bolide_runtime::coroutine_worker_limit();
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        // 约 1 万个协程共用工作线程
+        assert_eq!(main_fn(), (0..5000).sum::<i64>());
+        assert_eq!(bolide_runtime::coroutine_worker_limit(), 2);
+        bolide_runtime::set_coroutine_workers(limit);
+    }
+
+    #[test]
+    fn test_async_call_owns_rc_arguments() {
+        // trampoline 调用结束后释放 RC 参数：列表传给 async 函数后调用方仍持有自己的引用
+        let source = r#"
+async fn fetch(x: int) -> int {
+    return x * 2;
+}
+async fn sum_fetched(items: list<int>) -> int {
+    let total: int = 0;
+    for item in items {
+        let r: int = await fetch(item);
+        total = total + r;
+    }
+    return total;
+}
+async fn fib(n: int) -> int {
+    if n < 2 {
+        return n;
+    }
+    let a: int = await fib(n - 1);
+    let b: int = await fib(n - 2);
+    return a + b;
+}
+fn run() -> int {
+    let items: list<int> = [1, 2, 3, 4, 5];
+    let first: int = await sum_fetched(items);
+    let second: int = await sum_fetched(items);
+    let f: int = await fib(10);
+    return (first + second) * 1000 + f + len(items);
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 60 * 1000 + 55 + 5);
+    }
+
+    #[test]
+    fn test_join_ok_takes_task_error() {
+        let source = r#"
+fn parse(s: str) -> int {
+    return parse_int(s);
+}
+fn label(n: int) -> str {
+    return f"n{n}";
+}
+fn run() -> int {
+    let good = spawn parse("40");
+    let bad = spawn parse("x");
+    let ok, v = join_ok(good);
+    let failed, w = join_ok(bad);
+    let total: int = v + w;
+    if ok and not failed {
+        total = total + 1;
+    }
+    let code: int = error();
+    let named = spawn label(2);
+    let ok2, text = join_ok(named);
+    if ok2 and text == "n2" and error() == code {
+        total = total + 1;
+    }
+    return total;
+}
+return run();
+"#;
+        bolide_runtime::bolide_error_clear();
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 42);
+        // 失败任务的错误记录在等待方（这里是测试线程），带上任务名；成功的 join_ok 不清除它
+        let (code, message) = bolide_runtime::last_error().unwrap();
+        assert_eq!(code, bolide_runtime::ERROR_VALUE);
+        assert!(message.starts_with("error in spawned task 'parse': "), "{}", message);
+        bolide_runtime::bolide_error_clear();
+
+        let program = bolide_parser::parse_source("let h = spawn parse(\"1\");\nlet v = join_ok(h + 1);\nfn parse(s: str) -> int {\n    return parse_int(s);\n}\n").unwrap();
+        let err = JitCompiler::new().compile(&program).unwrap_err();
+        assert!(err.contains("join_ok expects a variable"), "{}", err);
+    }
+
+    #[test]
+    fn test_join_and_await_twice() {
+        let source = r#"
+fn word(n: int) -> str {
+    return str(n);
+}
+async fn later(n: int) -> str {
+    return str(n);
+}
+fn count(n: int) -> int {
+    return n;
+}
+fn run() -> int {
+    let t = spawn word(7);
+    let f = later(8);
+    let total: int = 0;
+    let i: int = 0;
+    while i < 2 {
+        let s: str = join(t);
+        let w: str = await f;
+        if error() == 0 {
+            total = total + parse_int(s) * 10 + parse_int(w);
+        } else {
+            total = total + 100 * error();
+            clear_error();
+        }
+        i = i + 1;
+    }
+    let n = spawn count(5);
+    let j: int = 0;
+    while j < 2 {
+        total = total + join(n) * 1000;
+        j = j + 1;
+    }
+    pool(2) {
+        let p = spawn word(3);
+        let k: int = 0;
+        while k < 2 {
+            let q: str = join(p);
+            if error() == 0 {
+                total = total + parse_int(q) * 100000;
+            } else {
+                clear_error();
+            }
+            k = k + 1;
+        }
+    }
+    return total;
+}
+return run();
+"#;
+        bolide_runtime::bolide_error_clear();
+        let program = bolide_parser::parse_source(source).unwrap();
+        // 测试用 str(n) 得到运行时新建的字符串
+        let mut compiler = JitCompiler::new();
+        compiler.set_no_opt(true);
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        // 字符串结果只交给第一次 join / await，之后记录 ERROR_VALUE；int 结果每次都返回缓存的值
+        let expected = 78 + 100 + 10000 + 300000;
+        // 第一次运行驻留任务名等字面量，第二次运行检查泄漏
+        assert_eq!(main_fn(), expected);
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        assert_eq!(main_fn(), expected);
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+
+        let cases = [
+            ("fn work() -> int {\n    return 1;\n}\nfn main() {\n    let t = spawn work();\n    let a = join(t);\n    print(join(t) + a);\n}\n",
+             "in function 'main': handle 't' was already joined in this block and cannot be joined again"),
+            ("async fn work() -> int {\n    return 1;\n}\nlet f = work();\nlet a = await f;\nlet b = await f;\n",
+             "future 'f' was already awaited in this block"),
+        ];
+        for (source, expected) in cases {
+            let program = bolide_parser::parse_source(source).unwrap();
+            let err = JitCompiler::new().compile(&program).expect_err(source);
+            assert!(err.contains(expected), "{}: {}", source, err);
+        }
+    }
+
+    #[test]
+    fn test_read_file_in_async_functions() {
+        let path = std::env::temp_dir().join(format!("bolide_read_file_{}.txt", std::process::id()));
+        std::fs::write(&path, "hello\nworld\n").unwrap();
+        let source = format!(r#"
+async fn load() -> int {{
+    let text: str = read_file("{path}");
+    return len(text);
+}}
+fn run() -> int {{
+    let direct: str = read_file("{path}");
+    let loaded: int = await load();
+    clear_error();
+    let missing: str = read_file("{path}.missing");
+    return loaded * 1000 + len(direct) * 10 + error() + len(missing);
+}}
+return run();
+"#, path = path.display());
+        let program = bolide_parser::parse_source(&source).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 12 * 1000 + 12 * 10 + bolide_runtime::ERROR_IO);
+        // async 函数里的读取经过 I/O 线程
+        assert!(bolide_runtime::io_workers() >= 1);
+        std::fs::remove_file(&path).unwrap();
+
+        let program = bolide_parser::parse_source("let n: int = len(read_file(3));\n").unwrap();
+        let err = JitCompiler::new().compile(&program).err().expect("non-str path should fail");
+        assert!(err.contains("read_file expects a str argument"), "{}", err);
+    }
+
+    #[test]
+    fn test_format_row_and_write_lines() {
+        let path = std::env::temp_dir().join(format!("bolide_write_lines_{}.csv", std::process::id()));
+        let source = format!(r#"
+fn run() -> int {{
+    let rows: list<str> = [];
+    for i in range(3) {{
+        let fields: list<int> = [i, i * i];
+        rows.push(format_row(fields));
+    }}
+    let names: list<str> = [str(1) + ",x", "plain", ""];
+    rows.push(format_row(names, ";"));
+    let empty: list<float> = [];
+    rows.push(format_row(empty));
+    write_lines("{path}", rows);
+    clear_error();
+    write_lines("{path}.missing/out.csv", rows);
+    return error();
+}}
+return run();
+"#, path = path.display());
+        let program = bolide_parser::parse_source(&source).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), bolide_runtime::ERROR_IO);
+        // 每行的字符串都由列表释放（第一次运行驻留了字面量，不计入）
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        assert_eq!(main_fn(), bolide_runtime::ERROR_IO);
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "0,0\n1,1\n2,4\n1,x;plain;\n\n");
+        std::fs::remove_file(&path).unwrap();
+        bolide_runtime::bolide_error_clear();
+
+        let cases = [
+            ("let s: str = format_row();\n", "format_row expects 1 or 2 arguments"),
+            ("let s: str = format_row([1], 2);\n", "format_row expects a str separator, got Int"),
+            ("let xs: list<list<int>> = [[1]];\nlet s: str = format_row(xs);\n", "format_row expects a list of int"),
+            ("write_lines(\"a.txt\");\n", "write_lines expects 2 arguments"),
+            ("write_lines(1, [\"a\"]);\n", "write_lines expects a str path, got Int"),
+            ("write_lines(\"a.txt\", [1]);\n", "write_lines expects a list<str>, got"),
+        ];
+        for (source, expected) in cases {
+            let program = bolide_parser::parse_source(source).unwrap();
+            let err = JitCompiler::new().compile(&program).expect_err(source);
+            assert!(err.contains(expected), "{}: {}", source, err);
+        }
+    }
+
+    #[test]
+    fn test_write_append_read_lines() {
+        let path = std::env::temp_dir().join(format!("bolide_file_io_{}.txt", std::process::id()));
+        let source = format!(r#"
+fn run() -> int {{
+    let path: str = "{path}";
+    let fresh: bool = file_exists(path);
+    if not write_file(path, "a,b\n") {{
+        return -1;
+    }}
+    for i in range(3) {{
+        append_file(path, str(i) + "\n");
+    }}
+    let fields: list<str> = read_file(path).split("\n");
+    let lines: list<str> = read_lines(path);
+    let total: int = 0;
+    for line in lines {{
+        total = total + len(line);
+    }}
+    // 读不存在的文件不中止程序：空列表、错误码 7
+    clear_error();
+    let missing: list<str> = read_lines(path + ".missing");
+    let code: int = error();
+    let ok: bool = write_file(path + ".missing/x.txt", "x");
+    if fresh or ok or not file_exists(path) {{
+        return -2;
+    }}
+    return len(fields) * 1000 + len(lines) * 100 + total * 10 + len(missing) + code * 10000;
+}}
+return run();
+"#, path = path.display());
+        let _ = std::fs::remove_file(&path);
+        let program = bolide_parser::parse_source(&source).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        let expected = bolide_runtime::ERROR_IO * 10000 + 5 * 1000 + 4 * 100 + 6 * 10;
+        assert_eq!(main_fn(), expected);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a,b\n0\n1\n2\n");
+        // 读到的行由列表释放（第一次运行驻留了字面量，不计入）
+        std::fs::remove_file(&path).unwrap();
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        assert_eq!(main_fn(), expected);
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+        std::fs::remove_file(&path).unwrap();
+        bolide_runtime::bolide_error_clear();
+
+        let cases = [
+            ("let ok: bool = write_file(\"a.txt\");\n", "write_file expects 2 arguments"),
+            ("let ok: bool = append_file(\"a.txt\", 3);\n", "append_file expects a str contents, got Int"),
+            ("let ok: bool = file_exists(1);\n", "file_exists expects a str argument, got Int"),
+            ("let xs: list<str> = read_lines();\n", "read_lines expects 1 argument"),
+        ];
+        for (source, expected) in cases {
+            let program = bolide_parser::parse_source(source).unwrap();
+            let err = JitCompiler::new().compile(&program).expect_err(source);
+            assert!(err.contains(expected), "{}: {}", source, err);
+        }
+    }
+
+    #[test]
+    fn test_assertions() {
+        let source = r#"
+fn grid() -> list<list<int>> {
+    let rows: list<list<int>> = [];
+    for i in range(2) {
+        let row: list<int> = [i, i + 1];
+        rows.push(row);
+    }
+    return rows;
+}
+test "skipped by compile" {
+    assert(false);
+}
+fn run() -> int {
+    assert(1 < 2);
+    assert_eq(2 + 2, 4);
+    assert_eq(1.0 / 3.0 * 3.0, 1);
+    assert_eq(true, 1 == 1);
+    assert_eq(str(12), "12");
+    assert_eq(grid(), grid());
+    assert(1 > 2, "order");
+    assert_eq(1.5, 2);
+    let a: list<list<int>> = grid();
+    a[1].push(9);
+    assert_eq(a, grid());
+    return 7;
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        bolide_runtime::test_begin("jit");
+        assert_eq!(main_fn(), 7);
+        let messages: Vec<String> = bolide_runtime::test_end().into_iter().map(|f| f.message).collect();
+        // 测试块没有编译进程序；嵌套列表按内容比较
+        assert_eq!(messages.len(), 3, "{:?}", messages);
+        assert_eq!(messages[0], "assert failed: order");
+        assert_eq!(messages[1], "assert_eq failed: left = 1.5, right = 2");
+        assert!(messages[2].starts_with("assert_eq failed: left = ["), "{}", messages[2]);
+
+        let cases = [
+            ("assert();\n", "assert expects 1 or 2 arguments"),
+            ("assert(\"yes\");\n", "assert expects a bool condition, got Str"),
+            ("assert(true, 1);\n", "assert expects a str message, got Int"),
+            ("assert_eq(1);\n", "assert_eq expects 2 arguments"),
+            ("assert_eq(1, \"1\");\n", "assert_eq compares two int, bool, float, str or list values of the same type, got Int and Str"),
+            ("let a: list<int> = [1];\nlet b: list<str> = [\"1\"];\nassert_eq(a, b);\n", "got List(Int) and List(Str)"),
+        ];
+        for (source, expected) in cases {
+            let program = bolide_parser::parse_source(source).unwrap();
+            let err = JitCompiler::new().compile(&program).expect_err(source);
+            assert!(err.contains(expected), "{}: {}", source, err);
+        }
+    }
+
+    #[test]
+    fn test_debug_trace() {
+        let source = r#"
+fn pick(xs: list<int>, i: int) -> int {
+    return xs[i];
+}
+
+fn scan(xs: list<int>) -> int {
+    for i in range(5) {
+        if i == 4 {
+            return pick(xs, i);
+        }
+    }
+    return 0;
+}
+
+fn run() -> int {
+    let xs: list<int> = [1, 2, 3];
+    return scan(xs) + pick(xs, 1);
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+
+        // 调试模式关闭时不生成影子调用栈的调用
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 2);
+        assert!(bolide_runtime::last_error_trace().is_empty());
+        bolide_runtime::bolide_error_clear();
+
+        let mut compiler = JitCompiler::new();
+        compiler.set_debug(true);
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 2);
+        assert_eq!(bolide_runtime::last_error_trace(), [
+            "    at pick (line 2)",
+            "    at scan (line 6)",
+            "    at run (line 15)",
+            "    at <main>",
+        ]);
+        // 每条返回路径（循环里的 return、函数末尾）都弹出了自己的帧
+        assert_eq!(bolide_runtime::trace_depth(), 0);
+        bolide_runtime::bolide_error_clear();
+    }
+
+    #[test]
+    fn test_sampling_profile() {
+        const CHILD_ENV: &str = "BOLIDE_SAMPLE_TEST_CHILD";
+        if std::env::var_os(CHILD_ENV).is_none() {
+            // 采样线程和登记的调用栈是进程全局的，在子进程里运行，不受并行的其他测试影响
+            let output = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["jit::tests::test_sampling_profile", "--exact", "--nocapture", "--test-threads=1"])
+                .env(CHILD_ENV, "1")
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{}\n{}",
+                String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+            return;
+        }
+
+        let source = "fn leaf(n: int) -> int {\n    let acc: int = 0;\n    for i in range(n) {\n        acc = acc + i % 7;\n    }\n    return acc;\n}\n\
+                      fn light(n: int) -> int {\n    return n + 1;\n}\n\
+                      fn run() -> int {\n    let total: int = 0;\n    for k in range(200) {\n        total = total + leaf(200000) + light(k);\n    }\n    return total;\n}\nreturn run();\n";
+        let program = bolide_parser::parse_source(source).unwrap();
+
+        // 不采样、不调试时：没有采样线程，也没有线程压过影子调用栈
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        let expected = main_fn();
+        assert!(!bolide_runtime::sampler_running());
+        assert_eq!(bolide_runtime::traced_threads(), 0);
+
+        let path = std::env::temp_dir().join(format!("bolide_jit_sample_{}.folded", std::process::id()));
+        let mut compiler = JitCompiler::new();
+        compiler.set_debug(true);
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert!(bolide_runtime::start_sampler(997, path.clone()));
+        assert_eq!(main_fn(), expected);
+        let profile = bolide_runtime::stop_sampler().unwrap();
+
+        // 热点叶子函数占大多数样本，调用栈从 <main> 开始
+        let self_time = profile.self_time();
+        assert_eq!(self_time[0].0, "leaf", "{:?}", profile);
+        assert!(self_time[0].1 * 10 >= profile.total() * 8, "{:?}", profile);
+        assert!(profile.stacks.iter().any(|(stack, _)| stack == &["<main>", "run", "leaf"]), "{:?}", profile);
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(bolide_runtime::SampleProfile::parse_folded(&written).unwrap(), profile);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_extern_c_types() {
+        let source = r#"
+extern "libc.so.6" {
+    fn strlen(s: cstr) -> u64;
+    fn strchr(s: cstr, c: i32) -> cstr;
+    fn strtoul(s: cstr, end: ptr, base: i32) -> u32;
+    fn abs(x: i32) -> i32;
+}
+extern "libm.so.6" {
+    fn sqrtf(x: f32) -> f32;
+}
+fn run() -> int {
+    let n = strlen("hello, world");
+    let root = sqrtf(2.25);
+    if root != 1.5 or sqrtf(16) != 4.0 {
+        return -1;
+    }
+    if strtoul("4294967295", 0, 10) != 4294967295 or abs(-7) != 7 {
+        return -2;
+    }
+    let rest = strchr("key=value", 61);
+    if rest != "=value" or strchr("abc", 122) != "" {
+        return -3;
+    }
+    return n;
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 12);
+
+        let program = bolide_parser::parse_source("extern \"libc.so.6\" {\n    fn abs(x: i32) -> i32;\n}\nabs(1.5);\n").unwrap();
+        let err = JitCompiler::new().compile(&program).err().expect("float passed to i32");
+        assert!(err.contains("extern function 'abs' parameter 'x': expects an integer, got float"), "{}", err);
+    }
+
+    #[test]
+    fn test_default_and_keyword_args() {
+        let source = r#"
+fn greet(name: str, punct: str = str(1) + "!", times: int = 1) -> int {
+    let text: str = name + punct;
+    return len(text) * times;
+}
+fn run() -> int {
+    let a: int = greet("bob");
+    let b: int = greet("al", times: 10);
+    let c: int = greet(times: 100, name: "x", punct: "??");
+    return a + b + c;
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+
+        // "bob1!" 5；"al1!" 4 * 10；"x??" 3 * 100
+        assert_eq!(main_fn(), 5 + 40 + 300);
+        // 默认值在调用处分配的字符串在语句结束时释放（第一次运行驻留了字面量，不计入）
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        assert_eq!(main_fn(), 5 + 40 + 300);
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+
+        // 构造函数省略的字段取初始值，字段接管默认值分配的字符串
+        let source = r#"
+class Tag {
+    name: str;
+    weight: int = 3;
+    label: str = str(7) + "x";
+}
+fn run() -> int {
+    let t: Tag = Tag("t");
+    let u: Tag = Tag(label: "long-label", name: "u");
+    let n: int = len(t.label) * 10 + len(u.label) * 100;
+    return t.weight + n;
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 3 + 20 + 1000);
+
+        let compile_err = |src: &str| {
+            let program = bolide_parser::parse_source(src).unwrap();
+            JitCompiler::new().compile(&program).unwrap_err()
+        };
+        let f = "fn f(a: int, b: int = 2) -> int {\n    return a + b;\n}\n";
+        assert!(compile_err(&format!("{}return f(a: 1, 2);\n", f)).contains("positional argument after keyword"));
+        assert!(compile_err(&format!("{}return f(b: 1);\n", f)).contains("missing argument 'a'"));
+        assert!(compile_err(&format!("{}return f(1, c: 3);\n", f)).contains("no parameter named 'c'"));
+
+        // 只有一个列表实参的 max(xs) 仍是内置聚合，不按同名用户函数的参数整理
+        let source = "fn max(a: int, b: int) -> int {\n    if a > b { return a; }\n    return b;\n}\nlet xs: list<int> = [3, 8, 1];\nreturn max(2, 5) * 10 + max(xs);\n";
+        let program = bolide_parser::parse_source(source).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 58);
+    }
+
+    #[test]
+    fn test_float_range() {
+        let source = r#"
+fn run() -> int {
+    let tenths: int = 0;
+    let exact: bool = true;
+    for t in range(0.0, 1.0, 0.1) {
+        let err: float = t - float(tenths) / 10.0;
+        if err > 0.000000000001 or err < -0.000000000001 { exact = false; }
+        tenths = tenths + 1;
+    }
+    let down: float = 0.0;
+    for t in range(1.0, 0.0, -0.25) {
+        down = down * 10.0 + t * 4.0;
+    }
+    let mixed: float = 0.0;
+    for t in range(0, 2, 0.5) {
+        mixed = mixed + t;
+    }
+    let score: int = tenths * 100000 + int(down) * 10 + int(mixed);
+    if exact { score = score + 1000000000; }
+    return score;
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        // 10 次迭代且都在 1e-12 之内；1.0 -> 0.0 依次 4 3 2 1；0 + 0.5 + 1 + 1.5 = 3
+        assert_eq!(main_fn(), 1000000000 + 10 * 100000 + 4321 * 10 + 3);
+
+        let lossy = "fn f() -> int {\n    let n: int = 0;\n    for i: int in range(0.0, 1.0, 0.5) {\n        n = n + i;\n    }\n    return n;\n}\n";
+        let program = bolide_parser::parse_source(lossy).unwrap();
+        let err = JitCompiler::new().compile(&program).unwrap_err();
+        assert!(err.contains("cannot bind int loop variable 'i'"), "{}", err);
+    }
+
+    #[test]
+    fn test_decimal_rounding_builtins() {
+        let source = r#"
+fn run() -> int {
+    let third: decimal = round(decimal("1") / decimal("3"), 10);
+    let score: int = 0;
+    if third == decimal("0.3333333333") { score = score + 1; }
+    if decimal("7.5") % decimal("2") == decimal("1.5") { score = score + 10; }
+    if decimal("-7.5") % decimal("2") == decimal("-1.5") { score = score + 100; }
+    let prev: int = set_div_precision(10);
+    if decimal("1") / decimal("3") == third { score = score + 1000; }
+    set_div_precision(prev);
+    if decimal("1") / decimal("3") > third { score = score + 10000; }
+    if floor(-2.5) == -3.0 and ceil(2.25d) == 3d and round(2.675, 2) == 2.68 { score = score + 100000; }
+    if floor(7) == 7 and decimal("2") <= decimal("2") and decimal("2") >= decimal("2") { score = score + 1000000; }
+    return score;
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 1111111);
+
+        // 同名的用户函数优先
+        let shadowed = "fn round(x: int) -> int {\n    return x + 1;\n}\nfn f() -> int {\n    return round(1);\n}\nreturn f();\n";
+        let program = bolide_parser::parse_source(shadowed).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 2);
+
+        let bad = "fn f() -> str {\n    return floor(\"x\");\n}\n";
+        let program = bolide_parser::parse_source(bad).unwrap();
+        let err = JitCompiler::new().compile(&program).unwrap_err();
+        assert!(err.contains("floor expects an int, float or decimal argument"), "{}", err);
+    }
+
+    #[test]
+    fn test_int_intrinsics_ignore_overflow_checks() {
+        let source = r#"
+fn run() -> int {
+    let max: int = 9223372036854775807;
+    let min: int = -max - 1;
+    let ok, v = checked_mul(min, -1);
+    let score: int = 0;
+    if wrapping_add(max, 1) == min { score = score + 1; }
+    if saturating_sub(min, 1) == min { score = score + 10; }
+    if saturating_mul(min, -1) == max { score = score + 100; }
+    if not ok and v == min { score = score + 1000; }
+    return score;
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+
+        // 内联指令不读取溢出检查开关：打开前后结果相同，也不报运行时错误
+        assert_eq!(main_fn(), 1111);
+        bolide_runtime::bolide_set_overflow_checks(1);
+        let checked = main_fn();
+        bolide_runtime::bolide_set_overflow_checks(0);
+        assert_eq!(checked, 1111);
+    }
+
+    #[test]
+    fn test_loop_count_outside_loop() {
+        let ok = "fn f() -> int {\n    let n: int = 0;\n    while loop_count() < 3 {\n        for x in range(2) {\n            n = n + loop_count();\n        }\n    }\n    return n + 0;\n}\n";
+        let program = bolide_parser::parse_source(ok).unwrap();
+        JitCompiler::new().compile(&program).expect("loop_count inside loops");
+
+        let bad = ok.replace("return n + 0;", "return n + loop_count();");
+        let program = bolide_parser::parse_source(&bad).unwrap();
+        let err = JitCompiler::new().compile(&program).err().expect("loop_count outside a loop");
+        assert!(err.contains("loop_count() can only be used inside a while or for loop"), "{}", err);
+    }
+
+    #[test]
+    fn test_len_rejects_numbers() {
+        let ok = "fn f(s: str, xs: list<int>) -> int {\n    return len(s) + len(xs);\n}\n";
+        let program = bolide_parser::parse_source(ok).unwrap();
+        JitCompiler::new().compile(&program).expect("len of str and list");
+
+        for (decl, ty) in [("x: int", "Int"), ("x: float", "Float")] {
+            let bad = format!("fn f({}) -> int {{\n    return len(x);\n}}\n", decl);
+            let program = bolide_parser::parse_source(&bad).unwrap();
+            let err = JitCompiler::new().compile(&program).err().expect("len of a number");
+            assert!(err.contains(&format!("len() is not supported for {}", ty)), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_analyze_counts_clones() {
+        let src = "fn copies(s: str, xs: list<int>) -> str {\n    let a: str = s;\n    let b: str = s;\n    let ys: list<int> = xs;\n    return a + b;\n}\n\nfn take(owned s: str) {\n}\n\nasync fn fetch(n: int) -> int {\n    return n;\n}\n\nfn caller() {\n    let xs: list<int> = [1, 2];\n    print(copies(\"x\", xs));\n    let t: str = str(1);\n    take(t);\n    let f = fetch(1);\n}\n";
+        let program = bolide_parser::parse_source(src).unwrap();
+        // `str(1)` 得到新建的字符串，移交给 owned 参数时不需要克隆
+        let mut compiler = JitCompiler::new();
+        compiler.set_no_opt(true);
+        let report = compiler.analyze(&program).expect("analyze");
+
+        // 借用参数赋给局部变量时各克隆一次
+        let copies = report.function("copies").unwrap();
+        assert_eq!(copies.clones(), 3);
+        assert_eq!(copies.rc_ops.get("string_clone"), Some(&2));
+        assert_eq!(copies.rc_ops.get("list_clone"), Some(&1));
+        let vars: Vec<&str> = copies.rc_variables.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(vars, ["a", "b", "ys"]);
+
+        let caller = report.function("caller").unwrap();
+        assert_eq!(caller.clones(), 0);
+        assert!(caller.calls.contains(&(CallKind::Call, "copies".to_string())));
+        assert!(caller.calls.contains(&(CallKind::Call, "take".to_string())));
+        assert!(caller.calls.contains(&(CallKind::Async, "fetch".to_string())));
+        assert!(caller.moved_variables.contains("t"));
+        assert!(report.to_json().contains("\"copies\": {\"calls\": []"));
+    }
+
+    #[test]
+    fn test_default_dict_needs_container_type() {
+        let ok = "fn f() -> int {\n    let d: dict<str, list<int>> = default_dict();\n    d[\"a\"].push(1);\n    return d[\"a\"].len();\n}\n";
+        let program = bolide_parser::parse_source(ok).unwrap();
+        JitCompiler::new().compile(&program).expect("default_dict of lists");
+
+        for (src, msg) in [
+            ("fn f() {\n    let d = default_dict();\n}\n", "needs a declared dict type"),
+            ("fn f() {\n    let d: dict<int, str> = default_dict();\n}\n", "values must be lists or dicts"),
+        ] {
+            let program = bolide_parser::parse_source(src).unwrap();
+            let err = JitCompiler::new().compile(&program).err().expect(src);
+            assert!(err.contains(msg), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_break_continue_targets() {
+        let ok = "fn f() -> int {\n    let n: int = 0;\n    while true {\n        for i in range(5) {\n            if i == 1 { continue; }\n            if i == 3 { break; }\n            n = n + i;\n        }\n        break\n    }\n    return n;\n}\n";
+        let program = bolide_parser::parse_source(ok).unwrap();
+        JitCompiler::new().compile(&program).expect("break/continue inside loops");
+
+        let outside = "fn f() {\n    if true {\n        break;\n    }\n}\n";
+        let program = bolide_parser::parse_source(outside).unwrap();
+        let err = JitCompiler::new().compile(&program).err().expect("break outside a loop");
+        assert!(err.contains("'break' can only be used inside a while or for loop"), "{}", err);
+
+        let in_scope = "fn f() {\n    while true {\n        await scope {\n            continue;\n        }\n    }\n}\n";
+        let program = bolide_parser::parse_source(in_scope).unwrap();
+        let err = JitCompiler::new().compile(&program).expect_err("continue out of an await scope block");
+        assert!(err.contains("'continue' cannot jump out of a select or await scope block"), "{}", err);
+    }
+
+    #[test]
+    fn test_pool_early_exit() {
+        let source = r#"
+let done: int = 0;
+fn busy(n: int) -> int {
+    let start: int = timer_start();
+    while timer_elapsed_ms(start) < 5.0 {
+    }
+    let tag: str = "task " + str(n);
+    done = done + len(tag) - 5;
+    return n;
+}
+fn tid() -> int {
+    return thread_id();
+}
+fn early() -> int {
+    let label: str = "pool" + str(1);
+    pool(1) {
+        let inner: str = label + "!";
+        for i in range(4) {
+            let h = spawn busy(i);
+        }
+        if len(inner) > 0 {
+            return len(inner);
+        }
+    }
+    return 0;
+}
+fn skip() -> int {
+    let rounds: int = 0;
+    for r in range(3) {
+        pool(1) {
+            let h = spawn busy(r);
+            rounds += 1;
+            if r == 1 {
+                break;
+            }
+        }
+    }
+    return rounds;
+}
+fn inner_early() -> int {
+    pool(2) {
+        let h = spawn busy(9);
+        return 1;
+    }
+    return 0;
+}
+fn nested() -> int {
+    pool(1) {
+        let a = spawn tid();
+        let first: int = join(a);
+        inner_early();
+        let b = spawn tid();
+        if join(b) == first {
+            return 1;
+        }
+    }
+    return 0;
+}
+fn run() -> int {
+    done = 0;
+    let e: int = early();
+    let s: int = skip();
+    let n: int = nested();
+    return e * 10000 + s * 1000 + n * 100 + done;
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        // early 返回前 4 个任务都已完成；skip 在第二轮 break；内层 pool 提前返回后
+        // 外层的任务仍在同一个工作线程上（上下文已恢复）；一共完成 4 + 2 + 1 个任务
+        assert_eq!(main_fn(), 6 * 10000 + 2 * 1000 + 100 + 7);
+        assert_eq!(bolide_runtime::bolide_pool_is_active(), 0);
+        // 提前离开时块内的字符串也都释放了
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        main_fn();
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+    }
+
+    #[test]
+    fn test_incremental_compile() {
+        // 运行一次输入，返回这次新声明的函数个数
+        fn eval(compiler: &mut JitCompiler, src: &str) -> Result<usize, String> {
+            let before = compiler.module.declarations().get_functions().count();
+            let program = bolide_parser::parse_source(src).unwrap();
+            let main: fn() -> i64 = unsafe { std::mem::transmute(compiler.compile_incremental(&program)?) };
+            main();
+            Ok(compiler.module.declarations().get_functions().count() - before)
+        }
+        fn call(compiler: &JitCompiler, name: &str) -> i64 {
+            let (ptr, _, _) = compiler.entry_point(name).unwrap();
+            let f: fn() -> i64 = unsafe { std::mem::transmute(ptr) };
+            f()
+        }
+
+        let mut compiler = JitCompiler::new();
+        for i in 0..100 {
+            eval(&mut compiler, &format!("fn f{}(x: int) -> int {{\n    return x + {};\n}}\n", i, i)).unwrap();
+        }
+        // 之前的函数直接复用，只编译新的入口
+        assert_eq!(eval(&mut compiler, "let total: int = f99(1);\n").unwrap(), 1);
+        // 全局变量跨输入保留，初始化不会重新执行
+        eval(&mut compiler, "total = total + f1(0);\n").unwrap();
+        eval(&mut compiler, "fn get_total() -> int {\n    return total;\n}\n").unwrap();
+        assert_eq!(call(&compiler, "get_total"), 101);
+        eval(&mut compiler, "let total: int = 7;\n").unwrap();
+        assert_eq!(call(&compiler, "get_total"), 7);
+
+        let err = eval(&mut compiler, "fn f1(x: int) -> int {\n    return x;\n}\n").unwrap_err();
+        assert_eq!(err, "'f1' is already defined");
+        let err = eval(&mut compiler, "let total: str = \"x\";\n").unwrap_err();
+        assert_eq!(err, "'total' is already declared as int");
+
+        // 出错的输入整体撤销：g 已经生成了调用 h 的代码，h 没有定义
+        let failed = "fn g() -> int {\n    return h();\n}\nfn h() -> int {\n    return missing;\n}\n";
+        assert!(eval(&mut compiler, failed).is_err());
+        eval(&mut compiler, "fn g() -> int {\n    return total + 1;\n}\n").unwrap();
+        assert_eq!(call(&compiler, "g"), 8);
+    }
+
+    #[test]
+    fn test_hot_reload() {
+        const V1: &str = r#"
+let counter: int = 0;
+fn step() -> int {
+    return 1;
+}
+fn tick() -> bool {
+    counter = counter + step();
+    return counter < 1000;
+}
+fn total() -> int {
+    return counter;
+}
+fn apply(f: func(int) -> int, x: int) -> int {
+    return f(x);
+}
+fn twice(x: int) -> int {
+    return x * 2;
+}
+fn via_value() -> int {
+    return apply(twice, 10);
+}
+"#;
+        fn call(compiler: &JitCompiler, name: &str) -> i64 {
+            let f: fn() -> i64 = unsafe { std::mem::transmute(compiler.hot_function(name).unwrap()) };
+            f()
+        }
+        fn reload(compiler: &mut JitCompiler, source: &str) -> Result<Vec<String>, String> {
+            compiler.hot_reload(&bolide_parser::parse_source(source).unwrap())
+        }
+
+        let mut compiler = JitCompiler::new();
+        compiler.set_hot(true);
+        let main: fn() -> i64 = unsafe { std::mem::transmute(compiler.compile(&bolide_parser::parse_source(V1).unwrap()).unwrap()) };
+        main();
+        assert_eq!(compiler.hot_entry(), Some(("tick", true)));
+        for _ in 0..3 {
+            assert_eq!(call(&compiler, "tick"), 1);
+        }
+        assert_eq!(call(&compiler, "total"), 3);
+        assert_eq!(call(&compiler, "via_value"), 20);
+
+        // 换掉 tick 的函数体：全局变量的值保留
+        let v2 = V1.replace("counter = counter + step();", "counter = counter + step() * 100;");
+        assert_eq!(reload(&mut compiler, &v2), Ok(vec!["tick".to_string()]));
+        call(&compiler, "tick");
+        assert_eq!(call(&compiler, "total"), 103);
+
+        // 只换 step：没有改变的 tick 经分派槽调用到新版本；函数作为值取到的也是新版本
+        let v3 = v2.replace("return 1;", "return 5;").replace("return x * 2;", "return x * 3;");
+        assert_eq!(reload(&mut compiler, &v3), Ok(vec!["step".to_string(), "twice".to_string()]));
+        call(&compiler, "tick");
+        assert_eq!(call(&compiler, "total"), 603);
+        assert_eq!(call(&compiler, "via_value"), 30);
+        assert_eq!(reload(&mut compiler, &v3), Ok(vec![]));
+
+        // 签名改变和编译错误都不替换任何函数，之前的版本继续运行
+        let err = reload(&mut compiler, &v3.replace("fn step() -> int", "fn step(k: int) -> int")).unwrap_err();
+        assert!(err.contains("function 'step' changed its signature") && err.contains("restart"), "{}", err);
+        let err = reload(&mut compiler, &v3.replace("return 5;", "return 6;").replace("return counter;", "return missing;")).unwrap_err();
+        assert!(err.contains("missing"), "{}", err);
+        call(&compiler, "tick");
+        assert_eq!(call(&compiler, "total"), 1103);
+        assert_eq!(call(&compiler, "tick"), 0);
+
+        let err = reload(&mut compiler, &v3.replace("let counter: int = 0;", "let counter: int = 0;\nlet extra: int = 1;")).unwrap_err();
+        assert!(err.contains("global 'extra' added"), "{}", err);
+        let mut plain = JitCompiler::new();
+        assert!(plain.hot_reload(&bolide_parser::parse_source(V1).unwrap()).is_err());
+        let mut missing = JitCompiler::new();
+        missing.set_hot(true);
+        let err = missing.compile(&bolide_parser::parse_source("fn frame() {}\n").unwrap()).unwrap_err();
+        assert!(err.contains("named 'tick' or marked @hot_entry"), "{}", err);
+    }
+
+    #[test]
+    fn test_duration_arithmetic() {
+        let source = r#"
+fn scaled(d: duration, n: int) -> duration {
+    return d * n;
+}
+
+fn run() -> int {
+    let a: duration = 1.5s + 250ms;
+    let checks: list<bool> = [
+        a == 1750ms,
+        a - 2s == -250ms,
+        scaled(30us, 4) == 120us,
+        2 * 1h == 120m,
+        a * 0.5 == 875ms,
+        a / 7 == 250ms,
+        a % 1s == 750ms,
+        -a < 0s,
+        1ns < 1us and 1us < 1ms and 1ms < 1s,
+        str(a) == "1.75s",
+        f"{30us} {1.5h}" == "30us 5400s"
+    ];
+    let passed: int = 0;
+    for ok in checks {
+        if ok {
+            passed = passed + 1;
+        }
+    }
+    // d / d 是 float：以毫秒计的 a
+    let ms: float = a / 1ms;
+    return passed * 10000 + int(ms);
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let main_ptr = JitCompiler::new().compile(&program).unwrap();
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 11 * 10000 + 1750);
+
+        for (bad, expected) in [
+            ("let d: duration = 5ms + 3;\n", "duration + int; give the number a unit"),
+            ("let b: bool = 5ms > 0;\n", "duration > int"),
+            ("let d: deadline = deadline(5);\n", "deadline expects 1 duration argument"),
+        ] {
+            let program = bolide_parser::parse_source(bad).unwrap();
+            let err = JitCompiler::new().compile(&program).err().expect(bad);
+            assert!(err.contains(expected), "{}: {}", bad, err);
+        }
+    }
+
+    #[test]
+    fn test_select_timeout_units() {
+        // timeout(200ms) 和 timeout(0.2s) 与以前的 timeout(200) 等待同样长；deadline 等到时间点为止
+        let source = r#"
+fn wait_int() -> int {
+    let ch: channel<int> = channel();
+    let t: int = timer_start();
+    select {
+        v <- ch => { t = 0; }
+        timeout(200) => {}
+    }
+    return timer_elapsed_ns(t);
+}
+
+fn wait_duration(d: duration) -> int {
+    let ch: channel<int> = channel();
+    let t: int = timer_start();
+    select {
+        v <- ch => { t = 0; }
+        timeout(d) => {}
+    }
+    return timer_elapsed_ns(t);
+}
+
+fn wait_deadline(dl: deadline) -> int {
+    let chans: list<channel<int>> = [channel()];
+    let t: int = timer_start();
+    select from chans {
+        v, i => { t = 0; }
+        timeout(dl) => {}
+    }
+    return timer_elapsed_ns(t);
+}
+
+fn run() -> int {
+    let waits: list<int> = [wait_int(), wait_duration(200ms), wait_duration(0.2s), wait_deadline(deadline(200ms))];
+    let ok: int = 0;
+    for ns in waits {
+        if ns >= 190000000 and ns < 2000000000 {
+            ok = ok + 1;
+        }
+    }
+    // 负的 duration 立即超时，而不是当作“无超时”
+    if wait_duration(-1s) < 190000000 {
+        ok = ok + 1;
+    }
+    return ok;
+}
+return run();
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        let mut compiler = JitCompiler::new();
+        let main_ptr = compiler.compile(&program).unwrap();
+        assert_eq!(compiler.warnings(), [
+            "in function 'wait_int': bare int timeout is deprecated; it is interpreted as milliseconds, write a duration such as 100ms instead",
+        ]);
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(main_ptr) };
+        assert_eq!(main_fn(), 5);
+    }
+}
@@ -0,0 +1,451 @@
+//! 匿名函数的提升
+//!
+//! `fn(x: int) -> int { return x * 2; }` 在合并导入的模块之前提升为顶层的命名函数 `__lambdaN`，
+//! 表达式换成这个名字：之后和把函数名当作值完全一样（函数地址，类型为带签名的 `func(...)`），
+//! `map` / `filter`、`test_callback` 这类回调和函数值调用都不需要额外处理。
+//!
+//! 暂不支持捕获：匿名函数体用到外层函数的参数或局部变量（包括方法里的 `self`）时报错，
+//! 顶层的全局变量照常可用。嵌套的匿名函数先提升，外层匿名函数的参数和局部变量对它同样算外层。
+//!
+//! `spawn` 只接受函数名：`let f = fn(...) { ... };` 之后从未重新赋值的 `f`，在 `spawn f(args)`
+//! 里换成提升出的函数名，和命名函数一样经 trampoline 传参。
+
+use std::collections::{HashMap, HashSet};
+
+use bolide_parser::{AsyncSelectBranch, Expr, FuncDef, Program, SelectBranch, Statement};
+
+use crate::purity;
+
+/// 提升出的函数名前缀
+pub(crate) const LAMBDA_PREFIX: &str = "__lambda";
+
+/// 提升程序里的所有匿名函数；函数名从 `__lambda{first}` 开始编号（REPL 的多次输入不重名）
+pub(crate) fn lift_lambdas(program: &mut Program, first: usize) -> Result<(), String> {
+    let mut lifter = Lifter { next: first, lifted: Vec::new() };
+
+    let mut scopes = Vec::new();
+    for stmt in &mut program.statements {
+        match stmt {
+            Statement::FuncDef(func) => {
+                let owner = func.name.clone();
+                scopes.push(lifter.lift_function(func, &owner, false)?);
+            }
+            Statement::ClassDef(class) => {
+                for method in &mut class.methods {
+                    let owner = format!("{}.{}", class.name, method.name);
+                    scopes.push(lifter.lift_function(method, &owner, true)?);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // 顶层语句：顶层的 `let` 是全局变量，块里声明的名字才是入口函数的局部变量
+    let globals: HashSet<String> = program.statements.iter()
+        .filter_map(|stmt| match stmt {
+            Statement::VarDecl(decl) => Some(decl.name.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut scope = locals_of(&program.statements);
+    scope.retain(|name| !globals.contains(name));
+    lifter.lift_body(&mut program.statements, &scope, "<main>", 0)?;
+
+    // 全局的 `let f = fn ...`：没有同名局部变量遮住的地方都可以 spawn
+    let assigned = assigned_names(program);
+    let mut aliases = lambda_aliases(&program.statements, &assigned);
+    resolve_spawns(&mut program.statements, &aliases, &HashSet::new());
+    aliases.retain(|name, _| globals.contains(name));
+    if !aliases.is_empty() {
+        let mut scopes = scopes.into_iter();
+        for stmt in &mut program.statements {
+            match stmt {
+                Statement::FuncDef(func) => resolve_spawns(&mut func.body, &aliases, &scopes.next().unwrap()),
+                Statement::ClassDef(class) => {
+                    for method in &mut class.methods {
+                        resolve_spawns(&mut method.body, &aliases, &scopes.next().unwrap());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    program.statements.extend(lifter.lifted.into_iter().map(Statement::FuncDef));
+    Ok(())
+}
+
+struct Lifter {
+    next: usize,
+    lifted: Vec<FuncDef>,
+}
+
+impl Lifter {
+    /// 提升函数体里的匿名函数，返回函数的参数和局部变量
+    fn lift_function(&mut self, func: &mut FuncDef, owner: &str, is_method: bool) -> Result<HashSet<String>, String> {
+        let mut scope = locals_of(&func.body);
+        scope.extend(func.params.iter().map(|p| p.name.clone()));
+        if is_method {
+            scope.insert("self".to_string());
+        }
+        self.lift_body(&mut func.body, &scope, owner, func.line)?;
+
+        let assigned = body_assigned_names(&func.body);
+        let aliases = lambda_aliases(&func.body, &assigned);
+        resolve_spawns(&mut func.body, &aliases, &HashSet::new());
+        Ok(scope)
+    }
+
+    /// `scope` 是外层的参数和局部变量，匿名函数体不能用到
+    fn lift_body(&mut self, body: &mut [Statement], scope: &HashSet<String>, owner: &str, line: usize) -> Result<(), String> {
+        walk_exprs(body, &mut |expr| {
+            let Expr::Lambda(params, return_type, lambda_body) = expr else { return Ok(()) };
+            let mut own = locals_of(lambda_body);
+            own.extend(params.iter().map(|p| p.name.clone()));
+            self.lift_body(lambda_body, &own, owner, line)?;
+
+            let mut used: Vec<String> = names_used(lambda_body).into_iter()
+                .filter(|name| scope.contains(name) && !own.contains(name))
+                .collect();
+            used.sort();
+            if let Some(name) = used.first() {
+                return Err(format!(
+                    "lambda in '{}' captures '{}' from the enclosing function; lambdas cannot capture variables yet, \
+                     pass it as a parameter (or use bind)",
+                    owner, name
+                ));
+            }
+
+            let name = format!("{}{}", LAMBDA_PREFIX, self.next);
+            self.next += 1;
+            self.lifted.push(FuncDef {
+                name: name.clone(),
+                is_async: false,
+                params: std::mem::take(params),
+                return_type: return_type.take(),
+                lifetime_deps: None,
+                body: std::mem::take(lambda_body),
+                pure: false,
+                trace: None,
+                hot_entry: false,
+                line,
+            });
+            *expr = Expr::Ident(name);
+            Ok(())
+        })
+    }
+}
+
+/// 函数体里声明的名字（`let`、for 循环变量、解构、select 的接收变量）
+fn locals_of(body: &[Statement]) -> HashSet<String> {
+    let mut locals = HashSet::new();
+    purity::collect_locals(body, &mut locals, &mut HashSet::new(), &mut HashMap::new());
+    locals.into_iter().map(str::to_string).collect()
+}
+
+/// 函数体用到的名字：标识符、通道和 spawn 的目标
+fn names_used(body: &[Statement]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    purity::for_each_expr(body, &mut |expr| match expr {
+        Expr::Ident(name) | Expr::Recv(name) | Expr::Spawn(name, _) => {
+            names.insert(name.clone());
+        }
+        _ => {}
+    });
+    for_each_stmt(body, &mut |stmt| match stmt {
+        Statement::Send(send) => {
+            names.insert(send.channel.clone());
+        }
+        Statement::Select(select) => {
+            for branch in &select.branches {
+                if let SelectBranch::Recv { channel, .. } = branch {
+                    names.insert(channel.clone());
+                }
+            }
+        }
+        _ => {}
+    });
+    names
+}
+
+/// `let f = __lambdaN;`（由匿名函数提升而来）中只声明一次、从未重新赋值的 `f` -> `__lambdaN`
+fn lambda_aliases(body: &[Statement], assigned: &HashSet<String>) -> HashMap<String, String> {
+    let mut aliases = HashMap::new();
+    let mut declared = HashSet::new();
+    for_each_stmt(body, &mut |stmt| {
+        if let Statement::VarDecl(decl) = stmt {
+            if !declared.insert(decl.name.clone()) {
+                aliases.remove(&decl.name);
+                return;
+            }
+            if let Some(Expr::Ident(target)) = &decl.value {
+                if target.starts_with(LAMBDA_PREFIX) && !assigned.contains(&decl.name) {
+                    aliases.insert(decl.name.clone(), target.clone());
+                }
+            }
+        }
+    });
+    aliases
+}
+
+/// `spawn f(args)` 中的 `f` 换成它绑定的函数名；`shadowed` 里的名字是局部变量，不换
+fn resolve_spawns(body: &mut [Statement], aliases: &HashMap<String, String>, shadowed: &HashSet<String>) {
+    if aliases.is_empty() {
+        return;
+    }
+    let _ = walk_exprs(body, &mut |expr| {
+        if let Expr::Spawn(name, _) = expr {
+            if let Some(target) = aliases.get(name.as_str()).filter(|_| !shadowed.contains(name.as_str())) {
+                *name = target.clone();
+            }
+        }
+        Ok(())
+    });
+}
+
+/// 被整体赋值过的名字（函数体内）
+fn body_assigned_names(body: &[Statement]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for_each_stmt(body, &mut |stmt| match stmt {
+        Statement::Assign(assign) => {
+            if let Expr::Ident(name) = &assign.target {
+                names.insert(name.clone());
+            }
+        }
+        Statement::Destructure(d) if !d.declare => names.extend(d.targets.iter().flatten().cloned()),
+        _ => {}
+    });
+    names
+}
+
+/// 被整体赋值过的名字（整个程序，全局变量可能在任何函数里被赋值）
+fn assigned_names(program: &Program) -> HashSet<String> {
+    let mut names = body_assigned_names(&program.statements);
+    for stmt in &program.statements {
+        match stmt {
+            Statement::FuncDef(func) => names.extend(body_assigned_names(&func.body)),
+            Statement::ClassDef(class) => {
+                for method in &class.methods {
+                    names.extend(body_assigned_names(&method.body));
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// 依次访问语句及其嵌套块里的语句（不进入函数定义）
+fn for_each_stmt(body: &[Statement], f: &mut impl FnMut(&Statement)) {
+    for stmt in body {
+        f(stmt);
+        match stmt {
+            Statement::If(s) => {
+                for_each_stmt(&s.then_body, f);
+                for (_, body) in &s.elif_branches {
+                    for_each_stmt(body, f);
+                }
+                if let Some(body) = &s.else_body {
+                    for_each_stmt(body, f);
+                }
+            }
+            Statement::While(s) => for_each_stmt(&s.body, f),
+            Statement::For(s) => for_each_stmt(&s.body, f),
+            Statement::Pool(s) => for_each_stmt(&s.body, f),
+            Statement::AwaitScope(s) => for_each_stmt(&s.body, f),
+            Statement::Measure(s) => for_each_stmt(&s.body, f),
+            Statement::Match(s) => {
+                for arm in &s.arms {
+                    for_each_stmt(&arm.body, f);
+                }
+                if let Some(body) = &s.default {
+                    for_each_stmt(body, f);
+                }
+            }
+            Statement::Select(s) => {
+                for branch in &s.branches {
+                    match branch {
+                        SelectBranch::Recv { body, .. } | SelectBranch::RecvAny { body, .. } | SelectBranch::Closed { body }
+                        | SelectBranch::Timeout { body, .. } | SelectBranch::Default { body } => {
+                            for_each_stmt(body, f)
+                        }
+                    }
+                }
+            }
+            Statement::AsyncSelect(s) => {
+                for branch in &s.branches {
+                    match branch {
+                        AsyncSelectBranch::Bind { body, .. } | AsyncSelectBranch::Expr { body, .. } => for_each_stmt(body, f),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 依次访问语句里的表达式（先外后内，不进入函数定义和匿名函数体），可以原地改写
+fn walk_exprs(body: &mut [Statement], f: &mut impl FnMut(&mut Expr) -> Result<(), String>) -> Result<(), String> {
+    for stmt in body {
+        match stmt {
+            Statement::VarDecl(decl) => {
+                if let Some(value) = &mut decl.value {
+                    walk_expr(value, f)?;
+                }
+            }
+            Statement::Assign(assign) => {
+                walk_expr(&mut assign.target, f)?;
+                walk_expr(&mut assign.value, f)?;
+            }
+            Statement::Destructure(d) => walk_expr(&mut d.value, f)?,
+            Statement::If(s) => {
+                walk_expr(&mut s.condition, f)?;
+                walk_exprs(&mut s.then_body, f)?;
+                for (cond, body) in &mut s.elif_branches {
+                    walk_expr(cond, f)?;
+                    walk_exprs(body, f)?;
+                }
+                if let Some(body) = &mut s.else_body {
+                    walk_exprs(body, f)?;
+                }
+            }
+            Statement::While(s) => {
+                walk_expr(&mut s.condition, f)?;
+                walk_exprs(&mut s.body, f)?;
+            }
+            Statement::For(s) => {
+                walk_expr(&mut s.iter, f)?;
+                walk_exprs(&mut s.body, f)?;
+            }
+            Statement::Pool(s) => {
+                walk_expr(&mut s.size, f)?;
+                walk_exprs(&mut s.body, f)?;
+            }
+            Statement::AwaitScope(s) => walk_exprs(&mut s.body, f)?,
+            Statement::Measure(s) => walk_exprs(&mut s.body, f)?,
+            Statement::Match(s) => {
+                walk_expr(&mut s.subject, f)?;
+                for arm in &mut s.arms {
+                    walk_exprs(&mut arm.body, f)?;
+                }
+                if let Some(body) = &mut s.default {
+                    walk_exprs(body, f)?;
+                }
+            }
+            Statement::Select(s) => {
+                if let Some(channels) = &mut s.channels {
+                    walk_expr(channels, f)?;
+                }
+                for branch in &mut s.branches {
+                    match branch {
+                        SelectBranch::Recv { body, .. } | SelectBranch::RecvAny { body, .. } | SelectBranch::Closed { body }
+                        | SelectBranch::Default { body } => walk_exprs(body, f)?,
+                        SelectBranch::Timeout { duration, body } => {
+                            walk_expr(duration, f)?;
+                            walk_exprs(body, f)?;
+                        }
+                    }
+                }
+            }
+            Statement::AsyncSelect(s) => {
+                for branch in &mut s.branches {
+                    match branch {
+                        AsyncSelectBranch::Bind { expr, body, .. } | AsyncSelectBranch::Expr { expr, body } => {
+                            walk_expr(expr, f)?;
+                            walk_exprs(body, f)?;
+                        }
+                    }
+                }
+            }
+            Statement::Send(s) => walk_expr(&mut s.value, f)?,
+            Statement::Return(Some(value)) | Statement::Expr(value) => walk_expr(value, f)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn walk_expr(expr: &mut Expr, f: &mut impl FnMut(&mut Expr) -> Result<(), String>) -> Result<(), String> {
+    f(expr)?;
+    match expr {
+        Expr::BinOp(l, _, r) | Expr::Index(l, r) => {
+            walk_expr(l, f)?;
+            walk_expr(r, f)
+        }
+        Expr::CompareChain(first, rest) => {
+            walk_expr(first, f)?;
+            rest.iter_mut().try_for_each(|(_, e)| walk_expr(e, f))
+        }
+        Expr::UnaryOp(_, e) | Expr::Member(e, _) | Expr::Await(e) | Expr::NamedArg(_, e) => walk_expr(e, f),
+        Expr::Slice(base, start, end) => {
+            walk_expr(base, f)?;
+            for bound in [start, end].into_iter().flatten() {
+                walk_expr(bound, f)?;
+            }
+            Ok(())
+        }
+        Expr::Call(callee, args) => {
+            walk_expr(callee, f)?;
+            args.iter_mut().try_for_each(|arg| walk_expr(arg, f))
+        }
+        Expr::List(items) | Expr::Tuple(items) | Expr::FString(items) | Expr::AwaitAll(items) | Expr::Spawn(_, items) => {
+            items.iter_mut().try_for_each(|item| walk_expr(item, f))
+        }
+        Expr::Dict(entries) => entries.iter_mut().try_for_each(|(k, v)| {
+            walk_expr(k, f)?;
+            walk_expr(v, f)
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lift(src: &str) -> Result<Program, String> {
+        let mut program = bolide_parser::parse_source(src).unwrap();
+        lift_lambdas(&mut program, 0)?;
+        Ok(program)
+    }
+
+    #[test]
+    fn test_lambdas_become_functions() {
+        let program = lift("let double = fn(x: int) -> int {\n    return x * 2;\n};\n\
+            fn run(n: int) -> int {\n    let twice = fn(x: int) -> int {\n        let inc = fn(y: int) -> int { return y + 1; };\n        return inc(x) * 2;\n    };\n    let t = spawn twice(n);\n    return await t;\n}\n").unwrap();
+        let names: Vec<&str> = program.statements.iter()
+            .filter_map(|stmt| match stmt {
+                Statement::FuncDef(func) => Some(func.name.as_str()),
+                _ => None,
+            })
+            .collect();
+        // 内层的匿名函数先提升
+        assert_eq!(names, ["run", "__lambda0", "__lambda1", "__lambda2"]);
+        let Statement::FuncDef(run) = &program.statements[1] else { panic!() };
+        let Statement::VarDecl(t) = &run.body[1] else { panic!() };
+        assert!(matches!(&t.value, Some(Expr::Spawn(name, _)) if name == "__lambda1"), "{:?}", t.value);
+    }
+
+    #[test]
+    fn test_captures_rejected() {
+        let err = lift("fn scale(k: int) -> list<int> {\n    return map(fn(x: int) -> int { return x * k; }, [1, 2]);\n}\n").unwrap_err();
+        assert!(err.starts_with("lambda in 'scale' captures 'k' from the enclosing function"), "{}", err);
+
+        let err = lift("class C {\n    n: int;\n    fn f() -> int {\n        let g = fn() -> int { return self.n; };\n        return g();\n    }\n}\n").unwrap_err();
+        assert!(err.starts_with("lambda in 'C.f' captures 'self'"), "{}", err);
+
+        let err = lift("let f = fn(a: int) -> int {\n    let g = fn() -> int { return a; };\n    return g();\n};\n").unwrap_err();
+        assert!(err.contains("captures 'a'"), "{}", err);
+
+        // 全局变量、自己的参数和局部变量、同名遮住外层的名字都不算捕获
+        lift("let base: int = 10;\nfn f(x: int) -> int {\n    let g = fn(x: int) -> int { let y: int = x + base; return y; };\n    return g(x);\n}\n").unwrap();
+    }
+
+    #[test]
+    fn test_reassigned_alias_not_spawned() {
+        let program = lift("fn run() -> int {\n    let f = fn() -> int { return 1; };\n    f = fn() -> int { return 2; };\n    let t = spawn f();\n    return await t;\n}\n").unwrap();
+        let Statement::FuncDef(run) = &program.statements[0] else { panic!() };
+        let Statement::VarDecl(t) = &run.body[2] else { panic!() };
+        assert!(matches!(&t.value, Some(Expr::Spawn(name, _)) if name == "f"));
+    }
+}
@@ -0,0 +1,205 @@
+//! AOT 函数排布
+//!
+//! 目标文件中函数按定义顺序排列。按调用图深度优先排列（调用者后紧跟它的被调用者），
+//! 热路径上的函数在代码段中彼此靠近，对指令缓存更友好；trampoline 由调用方统一放在最后。
+//! 顺序只取决于源码，同一程序每次编译得到相同的布局。
+
+use std::collections::HashSet;
+use bolide_parser::{AsyncSelectBranch, Expr, FuncDef, SelectBranch, Statement};
+
+/// 函数的输出顺序：从顶层代码出发按首次调用的先后深度优先，未被调用到的函数按源码顺序排在后面
+pub(crate) fn call_graph_order(funcs: &[&FuncDef], toplevel: &[Statement]) -> Vec<String> {
+    let mut order = Vec::with_capacity(funcs.len());
+    let mut visited = HashSet::new();
+    visit(&callees(toplevel), funcs, &mut visited, &mut order);
+    for func in funcs {
+        visit(std::slice::from_ref(&func.name), funcs, &mut visited, &mut order);
+    }
+    order
+}
+
+/// 深度优先（显式栈，避免深调用链递归过深）
+fn visit(roots: &[String], funcs: &[&FuncDef], visited: &mut HashSet<String>, order: &mut Vec<String>) {
+    let mut stack: Vec<String> = roots.iter().rev().cloned().collect();
+    while let Some(name) = stack.pop() {
+        let Some(func) = funcs.iter().find(|f| f.name == name) else { continue };
+        if !visited.insert(name) {
+            continue;
+        }
+        order.push(func.name.clone());
+        stack.extend(callees(&func.body).into_iter().rev());
+    }
+}
+
+/// 语句中直接调用（或 spawn）的函数名，按首次出现顺序去重
+fn callees(stmts: &[Statement]) -> Vec<String> {
+    let mut names = Vec::new();
+    for stmt in stmts {
+        stmt_callees(stmt, &mut names);
+    }
+    let mut seen = HashSet::new();
+    names.retain(|name| seen.insert(name.clone()));
+    names
+}
+
+fn body_callees(stmts: &[Statement], names: &mut Vec<String>) {
+    for stmt in stmts {
+        stmt_callees(stmt, names);
+    }
+}
+
+fn stmt_callees(stmt: &Statement, names: &mut Vec<String>) {
+    match stmt {
+        Statement::VarDecl(decl) => {
+            if let Some(ref value) = decl.value {
+                expr_callees(value, names);
+            }
+        }
+        Statement::Assign(assign) => {
+            expr_callees(&assign.target, names);
+            expr_callees(&assign.value, names);
+        }
+        Statement::Destructure(destructure) => expr_callees(&destructure.value, names),
+        Statement::If(if_stmt) => {
+            expr_callees(&if_stmt.condition, names);
+            body_callees(&if_stmt.then_body, names);
+            for (cond, body) in &if_stmt.elif_branches {
+                expr_callees(cond, names);
+                body_callees(body, names);
+            }
+            if let Some(ref else_body) = if_stmt.else_body {
+                body_callees(else_body, names);
+            }
+        }
+        Statement::While(while_stmt) => {
+            expr_callees(&while_stmt.condition, names);
+            body_callees(&while_stmt.body, names);
+        }
+        Statement::For(for_stmt) => {
+            expr_callees(&for_stmt.iter, names);
+            body_callees(&for_stmt.body, names);
+        }
+        Statement::Pool(pool_stmt) => {
+            expr_callees(&pool_stmt.size, names);
+            body_callees(&pool_stmt.body, names);
+        }
+        Statement::Select(select_stmt) => {
+            if let Some(channels) = &select_stmt.channels {
+                expr_callees(channels, names);
+            }
+            for branch in &select_stmt.branches {
+                match branch {
+                    SelectBranch::Recv { body, .. } | SelectBranch::RecvAny { body, .. } | SelectBranch::Closed { body }
+                    | SelectBranch::Default { body } => body_callees(body, names),
+                    SelectBranch::Timeout { duration, body } => {
+                        expr_callees(duration, names);
+                        body_callees(body, names);
+                    }
+                }
+            }
+        }
+        Statement::AwaitScope(scope_stmt) => body_callees(&scope_stmt.body, names),
+        Statement::Measure(measure_stmt) => body_callees(&measure_stmt.body, names),
+        Statement::Match(match_stmt) => {
+            expr_callees(&match_stmt.subject, names);
+            for arm in &match_stmt.arms {
+                body_callees(&arm.body, names);
+            }
+            if let Some(ref default) = match_stmt.default {
+                body_callees(default, names);
+            }
+        }
+        Statement::AsyncSelect(select_stmt) => {
+            for branch in &select_stmt.branches {
+                let (AsyncSelectBranch::Bind { expr, body, .. } | AsyncSelectBranch::Expr { expr, body }) = branch;
+                expr_callees(expr, names);
+                body_callees(body, names);
+            }
+        }
+        Statement::Send(send) => expr_callees(&send.value, names),
+        Statement::Return(Some(expr)) | Statement::Expr(expr) => expr_callees(expr, names),
+        _ => {}
+    }
+}
+
+fn expr_callees(expr: &Expr, names: &mut Vec<String>) {
+    match expr {
+        Expr::Call(callee, args) => {
+            if let Expr::Ident(name) = callee.as_ref() {
+                names.push(name.clone());
+            } else {
+                expr_callees(callee, names);
+            }
+            for arg in args {
+                expr_callees(arg, names);
+            }
+        }
+        Expr::Spawn(name, args) => {
+            names.push(name.clone());
+            for arg in args {
+                expr_callees(arg, names);
+            }
+        }
+        Expr::BinOp(left, _, right) | Expr::Index(left, right) => {
+            expr_callees(left, names);
+            expr_callees(right, names);
+        }
+        Expr::Slice(base, start, end) => {
+            expr_callees(base, names);
+            for bound in [start, end].into_iter().flatten() {
+                expr_callees(bound, names);
+            }
+        }
+        Expr::CompareChain(first, rest) => {
+            expr_callees(first, names);
+            for (_, operand) in rest {
+                expr_callees(operand, names);
+            }
+        }
+        Expr::UnaryOp(_, inner) | Expr::Member(inner, _) | Expr::Await(inner) | Expr::NamedArg(_, inner) => {
+            expr_callees(inner, names)
+        }
+        Expr::List(items) | Expr::AwaitAll(items) | Expr::Tuple(items) | Expr::FString(items) => {
+            for item in items {
+                expr_callees(item, names);
+            }
+        }
+        Expr::Dict(pairs) => {
+            for (key, value) in pairs {
+                expr_callees(key, names);
+                expr_callees(value, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_callees_follow_callers() {
+        let source = "\
+fn unused() -> int { return 0; }
+fn leaf() -> int { return 1; }
+fn helper() -> int { return leaf(); }
+fn worker(n: int) -> int { return n; }
+fn run() -> int {
+    let h = spawn worker(2);
+    return helper() + join(h);
+}
+print(run());
+";
+        let program = bolide_parser::parse_source(source).unwrap();
+        let funcs: Vec<&FuncDef> = program.statements.iter()
+            .filter_map(|s| if let Statement::FuncDef(f) = s { Some(f) } else { None })
+            .collect();
+        let toplevel: Vec<Statement> = program.statements.iter()
+            .filter(|s| !matches!(s, Statement::FuncDef(_)))
+            .cloned()
+            .collect();
+        let order = call_graph_order(&funcs, &toplevel);
+        assert_eq!(order, ["run", "worker", "helper", "leaf", "unused"]);
+    }
+}
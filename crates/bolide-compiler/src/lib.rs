@@ -3,9 +3,45 @@
 //! 使用 Cranelift 进行代码生成
 
 mod jit;
+mod ice;
 mod aot;
+mod builtins;
+mod modules;
+mod layout;
+mod formatter;
+mod call_trace;
+mod lambda;
+mod equality;
+mod class_graph;
+mod packed;
+mod list_literal;
+mod analysis;
+mod engine;
+mod purity;
+mod comptime;
+mod compound;
+mod call_args;
+mod features;
+mod overflow;
+mod c_header;
+mod test_blocks;
+mod optimize;
+mod string_fold;
+mod rc_peephole;
+mod duration;
+mod hot;
+mod handles;
+mod bind;
 
 pub use jit::JitCompiler;
+pub use analysis::{AnalysisReport, CallKind, FunctionReport};
+pub use engine::{BolideEngine, BolideError, CancelHandle};
 pub use aot::AotCompiler;
 pub use aot::AotCompileResult;
 pub use aot::RUNTIME_SYMBOLS;
+pub use aot::SUPPORTED_TARGETS;
+pub use c_header::c_header;
+pub use builtins::{BuiltinDoc, BuiltinKind, BUILTINS, BUILTIN_CATEGORIES};
+pub use modules::import_files;
+pub use features::FEATURES;
+pub use test_blocks::{test_programs, TestCase};
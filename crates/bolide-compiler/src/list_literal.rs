@@ -0,0 +1,155 @@
+//! 大列表字面量
+//!
+//! 列表字面量默认逐个元素 `list_push`，上万个元素的字面量会让所在函数的 IR 同样膨胀，
+//! 后端编译时间随之超线性增长。元素超过 `CHUNK_SIZE` 个时改用：
+//!
+//! - 元素全是 int/float/bool/str 字面量：编译期把值序列化成只读数据对象，运行时一次
+//!   `list_from_const_array(elem_type, data, count)` 建出整个列表（布局见该运行时函数）；
+//! - 否则，元素不引用局部变量时每 `CHUNK_SIZE` 个元素合成一个返回子列表的辅助函数
+//!   `__list_chunk_*`，按顺序调用并 `list_extend` 到结果中，元素的求值顺序不变；
+//! - 引用了局部变量的字面量仍逐个 push（辅助函数看不到调用方的变量）。
+
+use bolide_parser::{Expr, FuncDef, Statement, Type as BolideType, UnaryOp};
+
+/// 超过这个元素数的字面量分块编译，也是每个辅助函数包含的元素数
+pub(crate) const CHUNK_SIZE: usize = 256;
+
+/// 序列化后的常量元素
+pub(crate) struct ConstArray {
+    /// 运行时元素类型标签
+    pub elem_type: u8,
+    pub bytes: Vec<u8>,
+}
+
+/// 元素全是 `elem` 类型的字面量时序列化它们（int 字面量可提升为 float），否则返回 None
+pub(crate) fn const_array(items: &[Expr], elem: &BolideType) -> Option<ConstArray> {
+    let (elem_type, bytes) = match elem {
+        BolideType::Int => (0, words(items, |e| int_literal(e))?),
+        BolideType::Float => (1, words(items, |e| float_literal(e).map(|f| f.to_bits() as i64))?),
+        BolideType::Bool => (2, words(items, |e| match e {
+            Expr::Bool(b) => Some(*b as i64),
+            _ => None,
+        })?),
+        BolideType::Str => (3, strings(items)?),
+        _ => return None,
+    };
+    Some(ConstArray { elem_type, bytes })
+}
+
+fn words(items: &[Expr], value: impl Fn(&Expr) -> Option<i64>) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(items.len() * 8);
+    for item in items {
+        bytes.extend_from_slice(&value(item)?.to_ne_bytes());
+    }
+    Some(bytes)
+}
+
+/// 偏移表（`count + 1` 个 u64）后接字节块
+fn strings(items: &[Expr]) -> Option<Vec<u8>> {
+    let mut offsets = Vec::with_capacity(items.len() + 1);
+    let mut blob = Vec::new();
+    offsets.push(0u64);
+    for item in items {
+        let Expr::String(s) = item else { return None };
+        blob.extend_from_slice(s.as_bytes());
+        offsets.push(blob.len() as u64);
+    }
+    let mut bytes: Vec<u8> = offsets.iter().flat_map(|o| o.to_ne_bytes()).collect();
+    bytes.extend_from_slice(&blob);
+    Some(bytes)
+}
+
+fn int_literal(expr: &Expr) -> Option<i64> {
+    match expr {
+        Expr::Int(n) => Some(*n),
+        Expr::UnaryOp(UnaryOp::Neg, inner) => match inner.as_ref() {
+            Expr::Int(n) => Some(n.wrapping_neg()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn float_literal(expr: &Expr) -> Option<f64> {
+    match expr {
+        Expr::Float(f) => Some(*f),
+        Expr::UnaryOp(UnaryOp::Neg, inner) => float_literal(inner).map(|f| -f),
+        _ => int_literal(expr).map(|n| n as f64),
+    }
+}
+
+/// 表达式能否搬进辅助函数求值：只由字面量、运算和按名字的调用构成，
+/// 被调用的名字不是当前函数的局部变量（`is_local`）
+pub(crate) fn is_closed(expr: &Expr, is_local: &impl Fn(&str) -> bool) -> bool {
+    match expr {
+        Expr::Int(_) | Expr::Float(_) | Expr::Bool(_) | Expr::String(_)
+        | Expr::BigInt(_) | Expr::Decimal(_) | Expr::Duration(_) | Expr::None => true,
+        Expr::UnaryOp(_, inner) | Expr::NamedArg(_, inner) => is_closed(inner, is_local),
+        Expr::BinOp(left, _, right) => is_closed(left, is_local) && is_closed(right, is_local),
+        Expr::List(items) | Expr::Tuple(items) => items.iter().all(|e| is_closed(e, is_local)),
+        Expr::Dict(pairs) => pairs.iter().all(|(k, v)| is_closed(k, is_local) && is_closed(v, is_local)),
+        Expr::Call(callee, args) => matches!(callee.as_ref(), Expr::Ident(name) if !is_local(name))
+            && args.iter().all(|e| is_closed(e, is_local)),
+        _ => false,
+    }
+}
+
+/// 每 `CHUNK_SIZE` 个元素一个 `fn <prefix>_<i>() -> list<elem> { return [...]; }`
+pub(crate) fn chunk_functions(prefix: &str, items: &[Expr], elem: &BolideType) -> Vec<FuncDef> {
+    items.chunks(CHUNK_SIZE).enumerate().map(|(i, chunk)| FuncDef {
+        name: format!("{}_{}", prefix, i),
+        is_async: false,
+        params: vec![],
+        return_type: Some(BolideType::List(Box::new(elem.clone()))),
+        lifetime_deps: None,
+        body: vec![Statement::Return(Some(Expr::List(chunk.to_vec())))],
+        pure: false,
+        trace: None,
+        hot_entry: false,
+        line: 0,
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_const_array_layout() {
+        let ints = [Expr::Int(3), Expr::UnaryOp(UnaryOp::Neg, Box::new(Expr::Int(4)))];
+        let array = const_array(&ints, &BolideType::Int).unwrap();
+        assert_eq!(array.elem_type, 0);
+        assert_eq!(array.bytes, [3i64.to_ne_bytes(), (-4i64).to_ne_bytes()].concat());
+
+        // int 字面量在 float 列表中提升
+        let array = const_array(&ints, &BolideType::Float).unwrap();
+        assert_eq!(array.bytes[8..], (-4.0f64).to_bits().to_ne_bytes());
+
+        let strs = [Expr::String("ab".into()), Expr::String("c".into())];
+        let array = const_array(&strs, &BolideType::Str).unwrap();
+        assert_eq!(array.elem_type, 3);
+        let header: Vec<u8> = [0u64, 2, 3].iter().flat_map(|o| o.to_ne_bytes()).collect();
+        assert_eq!(array.bytes, [header, b"abc".to_vec()].concat());
+
+        // 非字面量或类型不符时不能序列化
+        assert!(const_array(&[Expr::Int(1), Expr::Ident("x".into())], &BolideType::Int).is_none());
+        assert!(const_array(&strs, &BolideType::Int).is_none());
+    }
+
+    #[test]
+    fn test_closed_elements() {
+        let is_local = |name: &str| name == "n";
+        let call = |name: &str, args: Vec<Expr>| Expr::Call(Box::new(Expr::Ident(name.into())), args);
+        assert!(is_closed(&call("Point", vec![Expr::Int(1), Expr::Float(2.0)]), &is_local));
+        assert!(is_closed(&Expr::List(vec![Expr::String("a".into())]), &is_local));
+        assert!(!is_closed(&Expr::Ident("n".into()), &is_local));
+        assert!(!is_closed(&call("n", vec![]), &is_local));
+        assert!(!is_closed(&call("f", vec![Expr::Ident("g".into())]), &is_local));
+
+        let items: Vec<Expr> = (0..CHUNK_SIZE as i64 + 1).map(Expr::Int).collect();
+        let chunks = chunk_functions("__list_chunk_f_0", &items, &BolideType::Int);
+        let names: Vec<&str> = chunks.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["__list_chunk_f_0_0", "__list_chunk_f_0_1"]);
+        assert!(matches!(&chunks[1].body[0], Statement::Return(Some(Expr::List(rest))) if rest.len() == 1));
+    }
+}
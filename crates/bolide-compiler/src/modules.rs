@@ -0,0 +1,438 @@
+//! import 解析
+//!
+//! JIT、AOT 和 `bolide run --cache` 共用同一套规则，保证缓存键覆盖的文件
+//! 正是编译器实际加载的文件。
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use bolide_parser::{ClassDef, Expr, FuncDef, ParseError, Program, Statement, Type as BolideType, VarDecl};
+
+/// 程序直接导入的模块文件，按出现顺序去重
+///
+/// 路径原样返回（相对当前工作目录），与编译器加载模块时一致。
+pub fn import_files(program: &Program) -> Vec<String> {
+    let mut files: Vec<String> = Vec::new();
+    for stmt in &program.statements {
+        if let Statement::Import(import) = stmt {
+            if let Some(ref file_path) = import.file_path {
+                if !files.contains(file_path) {
+                    files.push(file_path.clone());
+                }
+            }
+        }
+    }
+    files
+}
+
+/// 模块文件的解析错误：每处错误一行，带上模块路径（`util.bl:3:5: ...`）
+pub(crate) fn parse_error(file_path: &str, error: ParseError) -> String {
+    let error = error.with_path(file_path);
+    let lines: Vec<String> = error.errors().map(ToString::to_string).collect();
+    format!("Failed to parse module '{}':\n{}", file_path, lines.join("\n"))
+}
+
+/// 导入的模块：文件路径和顶层定义
+#[derive(Debug, Clone)]
+pub(crate) struct ImportedModule {
+    pub path: String,
+    /// 顶层函数、类和全局变量的原名（不带 `@模块_` 前缀），按定义顺序
+    functions: Vec<String>,
+    classes: Vec<String>,
+    globals: Vec<String>,
+}
+
+/// `模块.名字` 指向的定义，名字已按导入规则改成 `@模块_名字`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Member {
+    Function(String),
+    Class(String),
+    Global(String),
+}
+
+impl ImportedModule {
+    pub(crate) fn new(path: &str, program: &Program) -> Self {
+        let mut module = Self { path: path.to_string(), functions: Vec::new(), classes: Vec::new(), globals: Vec::new() };
+        for stmt in &program.statements {
+            match stmt {
+                Statement::FuncDef(func) => module.functions.push(func.name.clone()),
+                Statement::ClassDef(class) => module.classes.push(class.name.clone()),
+                Statement::VarDecl(decl) => module.globals.push(decl.name.clone()),
+                _ => {}
+            }
+        }
+        module
+    }
+
+    /// 在模块的命名空间里查找 `member`；找不到时的错误信息列出模块导出的名字
+    pub(crate) fn resolve(&self, module_name: &str, member: &str) -> Result<Member, String> {
+        let has = |names: &[String]| names.iter().any(|n| n == member);
+        let mangled = mangle(module_name, member);
+        if has(&self.functions) {
+            Ok(Member::Function(mangled))
+        } else if has(&self.classes) {
+            Ok(Member::Class(mangled))
+        } else if has(&self.globals) {
+            Ok(Member::Global(mangled))
+        } else {
+            Err(format!("module '{}' has no member '{}'; {}", module_name, member, self.describe_exports()))
+        }
+    }
+
+    fn describe_exports(&self) -> String {
+        let groups: Vec<String> = [("functions", &self.functions), ("classes", &self.classes), ("globals", &self.globals)]
+            .into_iter()
+            .filter(|(_, names)| !names.is_empty())
+            .map(|(kind, names)| format!("{}: {}", kind, names.join(", ")))
+            .collect();
+        if groups.is_empty() {
+            "it exports nothing".to_string()
+        } else {
+            format!("it exports {}", groups.join("; "))
+        }
+    }
+}
+
+/// `base.member` 的 `base` 是导入的模块名时在模块里查找 `member`，否则返回 `None`（对象成员）
+pub(crate) fn resolve_member(modules: &HashMap<String, ImportedModule>, base: &Expr, member: &str) -> Option<Result<Member, String>> {
+    let Expr::Ident(module_name) = base else { return None };
+    modules.get(module_name).map(|module| module.resolve(module_name, member))
+}
+
+/// 错误信息里的 `模块.名字`
+pub(crate) fn qualified_name(base: &Expr, member: &str) -> String {
+    match base {
+        Expr::Ident(module_name) => format!("{}.{}", module_name, member),
+        _ => member.to_string(),
+    }
+}
+
+/// 导入模块的定义改名后的名字：`@模块_名字`
+pub(crate) fn mangle(module_name: &str, name: &str) -> String {
+    format!("@{}_{}", module_name, name)
+}
+
+/// 对模块成员赋值时的错误：模块的全局变量在模块外只读
+pub(crate) fn global_write_error(base: &Expr, member: &str) -> String {
+    let qualified = qualified_name(base, member);
+    let module_name = qualified.split_once('.').map_or("", |(m, _)| m);
+    format!("cannot assign to '{}': globals of module '{}' are read-only outside the module", qualified, module_name)
+}
+
+/// 模块的定义按导入规则改名：函数、类和（`with_globals` 时）全局变量加上 `@模块_` 前缀，
+/// 定义内部对模块自己的类的引用（类型标注、父类、构造函数调用）一并改写
+///
+/// extern 块原样保留（C 函数名不能改），其他顶层代码忽略。
+pub(crate) fn module_items(imported: Program, module_name: &str, with_globals: bool) -> Vec<Statement> {
+    let class_names: HashSet<String> = imported.statements.iter()
+        .filter_map(|stmt| match stmt {
+            Statement::ClassDef(class) => Some(class.name.clone()),
+            _ => None,
+        })
+        .collect();
+    let rename = |name: &str| class_names.contains(name).then(|| mangle(module_name, name));
+
+    let mut items = Vec::new();
+    for stmt in imported.statements {
+        match stmt {
+            Statement::FuncDef(mut func) => {
+                func.name = mangle(module_name, &func.name);
+                rewrite_func_class_refs(&mut func, &rename);
+                items.push(Statement::FuncDef(func));
+            }
+            Statement::ClassDef(mut class) => {
+                class.name = mangle(module_name, &class.name);
+                rewrite_class_def_refs(&mut class, &rename);
+                items.push(Statement::ClassDef(class));
+            }
+            Statement::ExternBlock(ext) => items.push(Statement::ExternBlock(ext)),
+            Statement::VarDecl(mut decl) if with_globals => {
+                decl.name = mangle(module_name, &decl.name);
+                rewrite_var_decl_class_refs(&mut decl, &rename);
+                items.push(Statement::VarDecl(decl));
+            }
+            _ => {}
+        }
+    }
+    items
+}
+
+/// 把主程序里模块限定的类名（类型标注 `utils.Point`）改成导入后的名字 `@utils_Point`
+///
+/// 模块存在但没有这个类时报错，错误信息列出模块导出的名字。
+pub(crate) fn resolve_qualified_types(statements: &mut [Statement], modules: &HashMap<String, ImportedModule>) -> Result<(), String> {
+    let error = RefCell::new(None);
+    let rename = |name: &str| {
+        let (module_name, member) = name.split_once('.')?;
+        let module = modules.get(module_name)?;
+        match module.resolve(module_name, member) {
+            Ok(Member::Class(mangled)) => Some(mangled),
+            Ok(_) => {
+                error.borrow_mut().get_or_insert_with(|| format!("'{}' is not a class", name));
+                None
+            }
+            Err(e) => {
+                error.borrow_mut().get_or_insert(e);
+                None
+            }
+        }
+    };
+    for stmt in statements {
+        match stmt {
+            Statement::FuncDef(func) => rewrite_func_class_refs(func, &rename),
+            Statement::ClassDef(class) => rewrite_class_def_refs(class, &rename),
+            stmt => rewrite_stmt_class_refs(stmt, &rename),
+        }
+    }
+    match error.into_inner() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// 重写类定义里的类引用：父类、字段类型和初始值、方法
+fn rewrite_class_def_refs(class: &mut ClassDef, rename: &dyn Fn(&str) -> Option<String>) {
+    if let Some(parent) = &mut class.parent {
+        if let Some(renamed) = rename(parent) {
+            *parent = renamed;
+        }
+    }
+    for field in &mut class.fields {
+        rewrite_type_class_refs(&mut field.ty, rename);
+        if let Some(value) = &mut field.default_value {
+            rewrite_expr_class_refs(value, rename);
+        }
+    }
+    for method in &mut class.methods {
+        rewrite_func_class_refs(method, rename);
+    }
+}
+
+/// 重写函数内部的类引用：签名里的类型、函数体里的类型标注和构造函数调用
+fn rewrite_func_class_refs(func: &mut FuncDef, rename: &dyn Fn(&str) -> Option<String>) {
+    // 重写返回类型
+    if let Some(ref mut ret_ty) = func.return_type {
+        rewrite_type_class_refs(ret_ty, rename);
+    }
+    // 重写参数类型
+    for param in &mut func.params {
+        rewrite_type_class_refs(&mut param.ty, rename);
+    }
+    // 重写函数体内的语句
+    for stmt in &mut func.body {
+        rewrite_stmt_class_refs(stmt, rename);
+    }
+}
+
+/// 重写类型中的类引用
+fn rewrite_type_class_refs(ty: &mut BolideType, rename: &dyn Fn(&str) -> Option<String>) {
+    match ty {
+        BolideType::Custom(name) => {
+            if let Some(renamed) = rename(name) {
+                *name = renamed;
+            }
+        }
+        BolideType::List(inner) => rewrite_type_class_refs(inner, rename),
+        BolideType::Dict(k, v) | BolideType::WeakDict(k, v) => {
+            rewrite_type_class_refs(k, rename);
+            rewrite_type_class_refs(v, rename);
+        }
+        BolideType::Channel(inner) | BolideType::PackedList(inner) | BolideType::PackedRef(inner) => {
+            rewrite_type_class_refs(inner, rename);
+        }
+        BolideType::Tuple(types) => {
+            for t in types {
+                rewrite_type_class_refs(t, rename);
+            }
+        }
+        BolideType::Weak(inner) | BolideType::Unowned(inner) => {
+            rewrite_type_class_refs(inner, rename);
+        }
+        BolideType::FuncSig(params, ret) => {
+            for p in params {
+                rewrite_type_class_refs(p, rename);
+            }
+            if let Some(r) = ret {
+                rewrite_type_class_refs(r, rename);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 重写变量声明中的类引用
+fn rewrite_var_decl_class_refs(decl: &mut VarDecl, rename: &dyn Fn(&str) -> Option<String>) {
+    if let Some(ref mut ty) = decl.ty {
+        rewrite_type_class_refs(ty, rename);
+    }
+    if let Some(ref mut val) = decl.value {
+        rewrite_expr_class_refs(val, rename);
+    }
+}
+
+/// 重写语句中的类引用
+fn rewrite_stmt_class_refs(stmt: &mut Statement, rename: &dyn Fn(&str) -> Option<String>) {
+    match stmt {
+        Statement::VarDecl(decl) => {
+            rewrite_var_decl_class_refs(decl, rename);
+        }
+        Statement::Assign(assign) => {
+            rewrite_expr_class_refs(&mut assign.value, rename);
+        }
+        Statement::Destructure(destructure) => {
+            rewrite_expr_class_refs(&mut destructure.value, rename);
+        }
+        Statement::Expr(expr) => {
+            rewrite_expr_class_refs(expr, rename);
+        }
+        Statement::Return(Some(expr)) => {
+            rewrite_expr_class_refs(expr, rename);
+        }
+        Statement::If(if_stmt) => {
+            rewrite_expr_class_refs(&mut if_stmt.condition, rename);
+            for s in &mut if_stmt.then_body {
+                rewrite_stmt_class_refs(s, rename);
+            }
+            for (cond, body) in &mut if_stmt.elif_branches {
+                rewrite_expr_class_refs(cond, rename);
+                for s in body {
+                    rewrite_stmt_class_refs(s, rename);
+                }
+            }
+            if let Some(else_body) = &mut if_stmt.else_body {
+                for s in else_body {
+                    rewrite_stmt_class_refs(s, rename);
+                }
+            }
+        }
+        Statement::While(while_stmt) => {
+            rewrite_expr_class_refs(&mut while_stmt.condition, rename);
+            for s in &mut while_stmt.body {
+                rewrite_stmt_class_refs(s, rename);
+            }
+        }
+        Statement::For(for_stmt) => {
+            rewrite_expr_class_refs(&mut for_stmt.iter, rename);
+            for s in &mut for_stmt.body {
+                rewrite_stmt_class_refs(s, rename);
+            }
+        }
+        Statement::Measure(measure_stmt) => {
+            for s in &mut measure_stmt.body {
+                rewrite_stmt_class_refs(s, rename);
+            }
+        }
+        Statement::Match(match_stmt) => {
+            rewrite_expr_class_refs(&mut match_stmt.subject, rename);
+            for arm in &mut match_stmt.arms {
+                for s in &mut arm.body {
+                    rewrite_stmt_class_refs(s, rename);
+                }
+            }
+            if let Some(default) = &mut match_stmt.default {
+                for s in default {
+                    rewrite_stmt_class_refs(s, rename);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 重写表达式中的类引用（主要是构造函数调用）
+fn rewrite_expr_class_refs(expr: &mut Expr, rename: &dyn Fn(&str) -> Option<String>) {
+    match expr {
+        Expr::Call(callee, args) => {
+            // 检查是否是类构造函数调用: ClassName(args)
+            if let Expr::Ident(name) = callee.as_mut() {
+                if let Some(renamed) = rename(name) {
+                    *name = renamed;
+                }
+            }
+            rewrite_expr_class_refs(callee, rename);
+            for arg in args {
+                rewrite_expr_class_refs(arg, rename);
+            }
+        }
+        Expr::BinOp(left, _, right) => {
+            rewrite_expr_class_refs(left, rename);
+            rewrite_expr_class_refs(right, rename);
+        }
+        Expr::CompareChain(first, rest) => {
+            rewrite_expr_class_refs(first, rename);
+            for (_, operand) in rest {
+                rewrite_expr_class_refs(operand, rename);
+            }
+        }
+        Expr::UnaryOp(_, operand) | Expr::NamedArg(_, operand) => {
+            rewrite_expr_class_refs(operand, rename);
+        }
+        Expr::Index(base, idx) => {
+            rewrite_expr_class_refs(base, rename);
+            rewrite_expr_class_refs(idx, rename);
+        }
+        Expr::Slice(base, start, end) => {
+            rewrite_expr_class_refs(base, rename);
+            for bound in [start, end].into_iter().flatten() {
+                rewrite_expr_class_refs(bound, rename);
+            }
+        }
+        Expr::Member(base, _) => {
+            rewrite_expr_class_refs(base, rename);
+        }
+        Expr::List(items) => {
+            for item in items {
+                rewrite_expr_class_refs(item, rename);
+            }
+        }
+        Expr::Dict(entries) => {
+            for (k, v) in entries {
+                rewrite_expr_class_refs(k, rename);
+                rewrite_expr_class_refs(v, rename);
+            }
+        }
+        Expr::Tuple(items) | Expr::FString(items) => {
+            for item in items {
+                rewrite_expr_class_refs(item, rename);
+            }
+        }
+        Expr::Await(inner) => {
+            rewrite_expr_class_refs(inner, rename);
+        }
+        Expr::AwaitAll(exprs) => {
+            for e in exprs {
+                rewrite_expr_class_refs(e, rename);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_module_members() {
+        let program = bolide_parser::parse_source(r#"
+let MAX_RETRIES: int = 3;
+class Point {
+    x: int;
+}
+fn add(a: int, b: int) -> int {
+    return a + b;
+}
+"#).unwrap();
+        let module = ImportedModule::new("utils.bl", &program);
+        assert_eq!(module.resolve("utils", "add"), Ok(Member::Function("@utils_add".to_string())));
+        assert_eq!(module.resolve("utils", "Point"), Ok(Member::Class("@utils_Point".to_string())));
+        assert_eq!(module.resolve("utils", "MAX_RETRIES"), Ok(Member::Global("@utils_MAX_RETRIES".to_string())));
+        assert_eq!(
+            module.resolve("utils", "nope").unwrap_err(),
+            "module 'utils' has no member 'nope'; it exports functions: add; classes: Point; globals: MAX_RETRIES"
+        );
+
+        let empty = ImportedModule::new("empty.bl", &Program { statements: vec![] });
+        assert_eq!(empty.resolve("empty", "x").unwrap_err(), "module 'empty' has no member 'x'; it exports nothing");
+    }
+}
@@ -0,0 +1,328 @@
+//! 代码生成前的常量折叠和死分支消除（`-O1`，默认开启；`-O0` / `--no-opt` 时不调用）
+//!
+//! - int / float / bool 字面量之间的算术、比较和逻辑运算（含链式比较）换成结果字面量，
+//!   语义与生成的代码相同（见 `comptime::binop`）；int 溢出、除以零等运行时才有定论的运算不折叠
+//! - 条件为常量的 `if` / `elif` 只保留会执行的分支：分支体没有声明变量时直接展开到外层，
+//!   否则保留成 `if true { ... }`，变量的作用域不变；条件为 `false` 的 `while` 整个删除
+//! - 函数体（含方法、lambda、测试块）里，一定会执行的 `return` / `break` / `continue`
+//!   之后的语句删除；顶层代码不删，其中的定义在声明阶段就要用到
+//!
+//! 字符串字面量的拼接由随后的 `string_fold` 折叠，`str(2 * 3)` 这类调用在这里先变成 `str(6)`。
+//! `and` / `or` 两边都会求值，只有两边都是常量时才折叠。
+
+use bolide_parser::{AsyncSelectBranch, ClassDef, Expr, FuncDef, IfStmt, Program, SelectBranch, Statement, UnaryOp};
+
+use crate::comptime::{self, Const};
+
+/// 折叠整个程序，返回折叠后的副本
+pub(crate) fn fold_program(program: &Program) -> Program {
+    let mut program = program.clone();
+    fold_body(&mut program.statements, false);
+    program
+}
+
+/// 折叠一个语句块；`in_func` 为 true 时删除不可达的语句
+fn fold_body(body: &mut Vec<Statement>, in_func: bool) {
+    let mut folded = Vec::with_capacity(body.len());
+    for mut stmt in body.drain(..) {
+        fold_stmt(&mut stmt, in_func);
+        match stmt {
+            Statement::If(if_stmt) => folded.extend(fold_if(if_stmt)),
+            Statement::While(s) if matches!(s.condition, Expr::Bool(false)) => {}
+            other => folded.push(other),
+        }
+        if in_func && folded.last().is_some_and(terminates) {
+            break;
+        }
+    }
+    *body = folded;
+}
+
+fn fold_func(func: &mut FuncDef) {
+    for param in &mut func.params {
+        if let Some(default) = &mut param.default {
+            fold_expr(default);
+        }
+    }
+    fold_body(&mut func.body, true);
+}
+
+fn fold_class(class: &mut ClassDef) {
+    for field in &mut class.fields {
+        if let Some(default) = &mut field.default_value {
+            fold_expr(default);
+        }
+    }
+    for method in &mut class.methods {
+        fold_func(method);
+    }
+}
+
+fn fold_stmt(stmt: &mut Statement, in_func: bool) {
+    match stmt {
+        Statement::VarDecl(decl) => {
+            if let Some(value) = &mut decl.value {
+                fold_expr(value);
+            }
+        }
+        Statement::Assign(assign) => {
+            fold_expr(&mut assign.target);
+            fold_expr(&mut assign.value);
+        }
+        Statement::Destructure(d) => fold_expr(&mut d.value),
+        Statement::FuncDef(func) => fold_func(func),
+        Statement::ClassDef(class) => fold_class(class),
+        Statement::If(s) => {
+            fold_expr(&mut s.condition);
+            fold_body(&mut s.then_body, in_func);
+            for (cond, body) in &mut s.elif_branches {
+                fold_expr(cond);
+                fold_body(body, in_func);
+            }
+            if let Some(body) = &mut s.else_body {
+                fold_body(body, in_func);
+            }
+        }
+        Statement::While(s) => {
+            fold_expr(&mut s.condition);
+            fold_body(&mut s.body, in_func);
+        }
+        Statement::For(s) => {
+            fold_expr(&mut s.iter);
+            fold_body(&mut s.body, in_func);
+        }
+        Statement::Pool(s) => {
+            fold_expr(&mut s.size);
+            fold_body(&mut s.body, in_func);
+        }
+        Statement::AwaitScope(s) => fold_body(&mut s.body, in_func),
+        Statement::Measure(s) => fold_body(&mut s.body, in_func),
+        Statement::Match(s) => {
+            fold_expr(&mut s.subject);
+            for arm in &mut s.arms {
+                fold_body(&mut arm.body, in_func);
+            }
+            if let Some(body) = &mut s.default {
+                fold_body(body, in_func);
+            }
+        }
+        Statement::Select(s) => {
+            if let Some(channels) = &mut s.channels {
+                fold_expr(channels);
+            }
+            for branch in &mut s.branches {
+                match branch {
+                    SelectBranch::Recv { body, .. } | SelectBranch::RecvAny { body, .. } | SelectBranch::Closed { body }
+                    | SelectBranch::Default { body } => fold_body(body, in_func),
+                    SelectBranch::Timeout { duration, body } => {
+                        fold_expr(duration);
+                        fold_body(body, in_func);
+                    }
+                }
+            }
+        }
+        Statement::AsyncSelect(s) => {
+            for branch in &mut s.branches {
+                match branch {
+                    AsyncSelectBranch::Bind { expr, body, .. } | AsyncSelectBranch::Expr { expr, body } => {
+                        fold_expr(expr);
+                        fold_body(body, in_func);
+                    }
+                }
+            }
+        }
+        Statement::Send(s) => fold_expr(&mut s.value),
+        Statement::Test(test) => fold_body(&mut test.body, true),
+        Statement::Return(Some(value)) | Statement::Expr(value) => fold_expr(value),
+        _ => {}
+    }
+}
+
+/// 去掉条件为常量的分支，返回替换原 `if` 的语句
+fn fold_if(if_stmt: IfStmt) -> Vec<Statement> {
+    let mut branches = Vec::with_capacity(1 + if_stmt.elif_branches.len());
+    let mut else_body = if_stmt.else_body;
+    for (cond, body) in std::iter::once((if_stmt.condition, if_stmt.then_body)).chain(if_stmt.elif_branches) {
+        match cond {
+            Expr::Bool(false) => {}
+            // 之后的分支不会执行
+            Expr::Bool(true) => {
+                else_body = Some(body);
+                break;
+            }
+            cond => branches.push((cond, body)),
+        }
+    }
+    if branches.is_empty() {
+        let Some(body) = else_body else {
+            return Vec::new();
+        };
+        if body.iter().any(declares) {
+            return vec![Statement::If(IfStmt {
+                condition: Expr::Bool(true),
+                then_body: body,
+                elif_branches: Vec::new(),
+                else_body: None,
+            })];
+        }
+        return body;
+    }
+    let (condition, then_body) = branches.remove(0);
+    vec![Statement::If(IfStmt { condition, then_body, elif_branches: branches, else_body })]
+}
+
+/// 语句在当前作用域里声明变量
+fn declares(stmt: &Statement) -> bool {
+    matches!(stmt, Statement::VarDecl(_)) || matches!(stmt, Statement::Destructure(d) if d.declare)
+}
+
+/// 执行到这条语句后一定不会继续执行同一块里的下一条
+fn terminates(stmt: &Statement) -> bool {
+    match stmt {
+        Statement::Return(_) | Statement::Break | Statement::Continue => true,
+        Statement::If(s) => {
+            s.else_body.as_ref().is_some_and(|body| body.last().is_some_and(terminates))
+                && s.then_body.last().is_some_and(terminates)
+                && s.elif_branches.iter().all(|(_, body)| body.last().is_some_and(terminates))
+        }
+        _ => false,
+    }
+}
+
+/// 先折叠子表达式，再看自身能否折叠
+fn fold_expr(expr: &mut Expr) {
+    match expr {
+        Expr::BinOp(l, _, r) | Expr::Index(l, r) => {
+            fold_expr(l);
+            fold_expr(r);
+        }
+        Expr::CompareChain(first, rest) => {
+            fold_expr(first);
+            for (_, e) in rest {
+                fold_expr(e);
+            }
+        }
+        Expr::UnaryOp(_, e) | Expr::Member(e, _) | Expr::Await(e) | Expr::NamedArg(_, e) => fold_expr(e),
+        Expr::Slice(base, start, end) => {
+            fold_expr(base);
+            for bound in [start, end].into_iter().flatten() {
+                fold_expr(bound);
+            }
+        }
+        Expr::Call(callee, args) => {
+            fold_expr(callee);
+            for arg in args {
+                fold_expr(arg);
+            }
+        }
+        Expr::List(items) | Expr::Tuple(items) | Expr::FString(items) | Expr::AwaitAll(items) | Expr::Spawn(_, items) => {
+            for item in items {
+                fold_expr(item);
+            }
+        }
+        Expr::Dict(entries) => {
+            for (k, v) in entries {
+                fold_expr(k);
+                fold_expr(v);
+            }
+        }
+        Expr::Lambda(_, _, body) => fold_body(body, true),
+        _ => {}
+    }
+    if let Some(value) = constant(expr) {
+        *expr = literal(value);
+    }
+}
+
+/// 子表达式已经折叠过的表达式的常量值
+fn constant(expr: &Expr) -> Option<Const> {
+    match expr {
+        Expr::UnaryOp(op, inner) => match (op, Const::from_literal(inner)?) {
+            (UnaryOp::Neg, Const::Int(n)) => n.checked_neg().map(Const::Int),
+            (UnaryOp::Neg, Const::Float(f)) => Some(Const::Float(-f)),
+            (UnaryOp::Not, Const::Bool(b)) => Some(Const::Bool(!b)),
+            _ => None,
+        },
+        Expr::BinOp(l, op, r) => binop(Const::from_literal(l)?, op, Const::from_literal(r)?),
+        Expr::CompareChain(first, rest) => {
+            let mut left = Const::from_literal(first)?;
+            let mut result = true;
+            for (op, expr) in rest {
+                let right = Const::from_literal(expr)?;
+                result &= binop(left, op, right)? == Const::Bool(true);
+                left = right;
+            }
+            Some(Const::Bool(result))
+        }
+        _ => None,
+    }
+}
+
+/// 与生成的代码相同的运算结果；int 溢出时的行为取决于运行时的 `--overflow-checks`，不折叠
+fn binop(l: Const, op: &bolide_parser::BinOp, r: Const) -> Option<Const> {
+    use bolide_parser::BinOp::{Add, Mul, Sub};
+    if let (Const::Int(a), Const::Int(b)) = (l, r) {
+        let exact = match op {
+            Add => a.checked_add(b),
+            Sub => a.checked_sub(b),
+            Mul => a.checked_mul(b),
+            _ => Some(0),
+        };
+        exact?;
+    }
+    comptime::binop(l, op, r)
+}
+
+fn literal(value: Const) -> Expr {
+    match value {
+        Const::Int(n) => Expr::Int(n),
+        Const::Float(f) => Expr::Float(f),
+        Const::Bool(b) => Expr::Bool(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folded(source: &str) -> String {
+        let program = bolide_parser::parse_source(source).unwrap();
+        format!("{:?}", fold_program(&program).statements)
+    }
+
+    fn parsed(source: &str) -> String {
+        format!("{:?}", bolide_parser::parse_source(source).unwrap().statements)
+    }
+
+    #[test]
+    fn test_constant_expressions() {
+        assert_eq!(
+            folded("let a: int = 2 * 3 + 4;\nlet b: float = 1.5 * 2.0 - 0.5;\nlet c: bool = 1 < 2 <= 2 and not false;\nlet d: int = -(3 - 5);\n"),
+            parsed("let a: int = 10;\nlet b: float = 2.5;\nlet c: bool = true;\nlet d: int = 2;\n"),
+        );
+        // 运行时才有定论的运算、有变量参与的运算不折叠
+        let kept = "let x: int = 1;\nlet a: int = 1 / 0;\nlet b: int = 9223372036854775807 + 1;\nlet c: int = x + 2 * 3;\nlet d: bool = 1 == 1.0;\n";
+        assert_eq!(folded(kept), parsed(kept.replace("2 * 3", "6").as_str()));
+    }
+
+    #[test]
+    fn test_constant_branches() {
+        assert_eq!(
+            folded("if 1 == 1 {\n    print(1);\n} else {\n    print(2);\n}\nif false {\n    print(3);\n} elif x {\n    print(4);\n} elif true {\n    print(5);\n} else {\n    print(6);\n}\nwhile 2 < 1 {\n    print(7);\n}\n"),
+            parsed("print(1);\nif x {\n    print(4);\n} else {\n    print(5);\n}\n"),
+        );
+        // 声明了变量的分支保留自己的作用域
+        assert_eq!(
+            folded("if true {\n    let y: int = 1;\n    print(y);\n}\n"),
+            parsed("if true {\n    let y: int = 1;\n    print(y);\n}\n"),
+        );
+    }
+
+    #[test]
+    fn test_unreachable_statements() {
+        assert_eq!(
+            folded("fn f(x: int) -> int {\n    if x > 0 {\n        return 1;\n    } else {\n        return 2;\n    }\n    print(x);\n}\nfn g() {\n    while true {\n        if true {\n            break;\n        }\n        print(1);\n    }\n    return;\n    print(2);\n}\n"),
+            parsed("fn f(x: int) -> int {\n    if x > 0 {\n        return 1;\n    } else {\n        return 2;\n    }\n}\n\nfn g() {\n    while true {\n        break;\n    }\n    return;\n}\n"),
+        );
+    }
+}
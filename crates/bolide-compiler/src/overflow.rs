@@ -0,0 +1,215 @@
+//! 显式指定溢出行为的整数内置函数
+//!
+//! - `saturating_add/sub/mul(a, b)`：溢出时取 i64 的最大/最小值
+//! - `wrapping_add/sub/mul(a, b)`：按补码回绕
+//! - `checked_add/sub/mul(a, b)`：返回 `(ok, value)`，溢出时 ok 为 false，value 为回绕后的值
+//!
+//! 行为不受 `--overflow-checks` 影响。JIT 和 AOT 都把它们编译成几条内联指令
+//! （带溢出标志的加减乘，饱和时再用 select 选边界值），不调用运行时；
+//! `checked_*` 只有结果被当作元组使用时才分配元组，`let ok, v = checked_add(a, b);` 直接绑定两个值。
+
+use bolide_parser::{BinOp, Expr, Type as BolideType};
+use cranelift::prelude::*;
+
+/// 溢出时的行为
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Mode {
+    Saturating,
+    Wrapping,
+    Checked,
+}
+
+/// 一个内置函数：行为 + 运算（Add / Sub / Mul）
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct IntOp {
+    pub mode: Mode,
+    pub op: BinOp,
+}
+
+/// 按函数名查找，不是这组内置函数时返回 None
+pub(crate) fn lookup(name: &str) -> Option<IntOp> {
+    let (mode, op) = name.split_once('_')?;
+    let mode = match mode {
+        "saturating" => Mode::Saturating,
+        "wrapping" => Mode::Wrapping,
+        "checked" => Mode::Checked,
+        _ => return None,
+    };
+    let op = match op {
+        "add" => BinOp::Add,
+        "sub" => BinOp::Sub,
+        "mul" => BinOp::Mul,
+        _ => return None,
+    };
+    Some(IntOp { mode, op })
+}
+
+/// 返回类型：`checked_*` 是 `(bool, int)`，其余是 int
+pub(crate) fn result_type(op: IntOp) -> BolideType {
+    match op.mode {
+        Mode::Checked => BolideType::Tuple(vec![BolideType::Bool, BolideType::Int]),
+        _ => BolideType::Int,
+    }
+}
+
+/// 表达式是 `checked_*(a, b)` 调用时返回函数名和实参（解构时不分配元组）
+pub(crate) fn checked_call(expr: &Expr) -> Option<(&str, &[Expr])> {
+    let Expr::Call(callee, args) = expr else { return None };
+    let Expr::Ident(name) = callee.as_ref() else { return None };
+    lookup(name).filter(|op| op.mode == Mode::Checked).map(|_| (name.as_str(), args.as_slice()))
+}
+
+/// 检查实参个数和类型，错误信息里带上函数名
+pub(crate) fn check_args(name: &str, arg_types: &[Option<BolideType>]) -> Result<(), String> {
+    if arg_types.len() != 2 {
+        return Err(format!("{} expects 2 int arguments, got {}", name, arg_types.len()));
+    }
+    for (i, ty) in arg_types.iter().enumerate() {
+        match ty {
+            Some(BolideType::Int) => {}
+            Some(other) => {
+                return Err(format!(
+                    "{} expects int arguments, but argument {} is {}",
+                    name, i + 1, crate::analysis::type_name(other)
+                ));
+            }
+            None => return Err(format!("{}: cannot infer the type of argument {}", name, i + 1)),
+        }
+    }
+    Ok(())
+}
+
+/// 生成运算，返回 (结果, 是否溢出)；`wrapping_*` 不需要溢出标志，返回 None
+///
+/// 溢出标志是 i8（0 / 1）。
+pub(crate) fn emit(builder: &mut FunctionBuilder, op: IntOp, a: Value, b: Value) -> (Value, Option<Value>) {
+    if op.mode == Mode::Wrapping {
+        let value = match op.op {
+            BinOp::Add => builder.ins().iadd(a, b),
+            BinOp::Sub => builder.ins().isub(a, b),
+            _ => builder.ins().imul(a, b),
+        };
+        return (value, None);
+    }
+
+    let (wrapped, overflowed) = match op.op {
+        BinOp::Add => builder.ins().sadd_overflow(a, b),
+        BinOp::Sub => builder.ins().ssub_overflow(a, b),
+        _ => builder.ins().smul_overflow(a, b),
+    };
+    if op.mode == Mode::Checked {
+        return (wrapped, Some(overflowed));
+    }
+
+    // 加减溢出时真实结果与 a 同号；乘法溢出时真实结果的符号是 a、b 符号的异或
+    let sign_source = match op.op {
+        BinOp::Mul => builder.ins().bxor(a, b),
+        _ => a,
+    };
+    let negative = builder.ins().icmp_imm(IntCC::SignedLessThan, sign_source, 0);
+    let min = builder.ins().iconst(types::I64, i64::MIN);
+    let max = builder.ins().iconst(types::I64, i64::MAX);
+    let bound = builder.ins().select(negative, min, max);
+    (builder.ins().select(overflowed, bound, wrapped), Some(overflowed))
+}
+
+/// 编译期求值（`@pure` 折叠用）：返回 (结果, 是否溢出)
+pub(crate) fn fold(op: IntOp, a: i64, b: i64) -> (i64, bool) {
+    let (wrapped, overflowed) = match op.op {
+        BinOp::Add => a.overflowing_add(b),
+        BinOp::Sub => a.overflowing_sub(b),
+        _ => a.overflowing_mul(b),
+    };
+    let value = match (op.mode, op.op) {
+        (Mode::Saturating, BinOp::Add) => a.saturating_add(b),
+        (Mode::Saturating, BinOp::Sub) => a.saturating_sub(b),
+        (Mode::Saturating, _) => a.saturating_mul(b),
+        _ => wrapped,
+    };
+    (value, overflowed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::ir::{Function, UserFuncName};
+    use cranelift_codegen::settings;
+    use cranelift_jit::{JITBuilder, JITModule};
+    use cranelift_module::{default_libcall_names, Linkage, Module};
+
+    const NAMES: [&str; 9] = [
+        "saturating_add", "saturating_sub", "saturating_mul",
+        "wrapping_add", "wrapping_sub", "wrapping_mul",
+        "checked_add", "checked_sub", "checked_mul",
+    ];
+
+    /// 把一个内置函数编译成 fn(a, b) -> (value, overflowed)，overflowed 通过指针写回
+    fn compile(module: &mut JITModule, name: &str) -> extern "C" fn(i64, i64, *mut i64) -> i64 {
+        let op = lookup(name).unwrap();
+        let ptr = module.target_config().pointer_type();
+        let mut sig = module.make_signature();
+        sig.params.extend([AbiParam::new(types::I64), AbiParam::new(types::I64), AbiParam::new(ptr)]);
+        sig.returns.push(AbiParam::new(types::I64));
+        let id = module.declare_function(name, Linkage::Local, &sig).unwrap();
+
+        let mut ctx = module.make_context();
+        ctx.func = Function::with_name_signature(UserFuncName::default(), sig);
+        let mut fctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut ctx.func, &mut fctx);
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
+        let params = builder.block_params(entry).to_vec();
+        let (value, overflowed) = emit(&mut builder, op, params[0], params[1]);
+        let flag = match overflowed {
+            Some(flag) => builder.ins().uextend(types::I64, flag),
+            None => builder.ins().iconst(types::I64, -1),
+        };
+        builder.ins().store(MemFlags::new(), flag, params[2], 0);
+        builder.ins().return_(&[value]);
+        builder.finalize();
+
+        module.define_function(id, &mut ctx).unwrap();
+        module.finalize_definitions().unwrap();
+        unsafe { std::mem::transmute(module.get_finalized_function(id)) }
+    }
+
+    #[test]
+    fn test_emit_matches_fold_at_boundaries() {
+        let isa = cranelift_native::builder().unwrap().finish(settings::Flags::new(settings::builder())).unwrap();
+        let mut module = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+        let values = [i64::MAX, i64::MIN, -1, 0, 1, 2, i64::MAX / 2 + 1];
+        for name in NAMES {
+            let op = lookup(name).unwrap();
+            let f = compile(&mut module, name);
+            for a in values {
+                for b in values {
+                    let mut flag = 0;
+                    let value = f(a, b, &mut flag);
+                    let (expected, overflowed) = fold(op, a, b);
+                    assert_eq!(value, expected, "{}({}, {})", name, a, b);
+                    if op.mode != Mode::Wrapping {
+                        assert_eq!(flag, overflowed as i64, "{}({}, {}) overflow flag", name, a, b);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_lookup_and_args() {
+        assert!(NAMES.iter().all(|n| lookup(n).is_some()));
+        assert!(lookup("saturating_div").is_none() && lookup("checked").is_none() && lookup("len").is_none());
+        assert_eq!(fold(lookup("saturating_mul").unwrap(), i64::MIN, -1), (i64::MAX, true));
+        assert_eq!(fold(lookup("checked_sub").unwrap(), i64::MIN, 1), (i64::MAX, true));
+
+        let int = Some(BolideType::Int);
+        assert!(check_args("wrapping_add", &[int.clone(), int.clone()]).is_ok());
+        assert_eq!(check_args("checked_add", &[int.clone()]).unwrap_err(), "checked_add expects 2 int arguments, got 1");
+        assert_eq!(
+            check_args("saturating_mul", &[int, Some(BolideType::Float)]).unwrap_err(),
+            "saturating_mul expects int arguments, but argument 2 is float"
+        );
+    }
+}
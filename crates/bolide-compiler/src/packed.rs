@@ -0,0 +1,133 @@
+//! `list<packed T>` 的元素布局
+//!
+//! packed 列表把类实例按值存放在列表缓冲区里（见运行时的 `packed_list`）。元素没有对象头，
+//! 每个 int/float/bool 字段占 8 字节，类字段原地展开成它自己的 packed 布局，
+//! 所以只有不含 RC 字段（字符串、容器、函数值……）的类能被 packed。
+//!
+//! 字段只有 int/float/bool 的类，packed 布局和对象数据部分的布局一致。
+
+use bolide_parser::Type as BolideType;
+use crate::analysis::type_name;
+
+/// packed 元素中的一个字段
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PackedField {
+    pub name: String,
+    /// int / float / bool，或内联的类 `Custom(name)`
+    pub ty: BolideType,
+    /// 相对元素起点的字节偏移
+    pub offset: usize,
+}
+
+/// 一个类的 packed 布局
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PackedLayout {
+    pub fields: Vec<PackedField>,
+    /// 元素的字节数
+    pub size: usize,
+    /// 交给运行时打印用的描述，如 `Vec2{x:f,y:f}`
+    pub descriptor: String,
+}
+
+impl PackedLayout {
+    pub fn field(&self, name: &str) -> Option<&PackedField> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+/// 计算类的 packed 布局
+///
+/// `fields_of` 返回类的全部字段（含继承的，按对象布局顺序），类不存在时返回 None；
+/// `display_name` 给出打印时的类名。
+pub(crate) fn packed_layout(
+    class_name: &str,
+    fields_of: &dyn Fn(&str) -> Option<Vec<(String, BolideType)>>,
+    display_name: &dyn Fn(&str) -> String,
+) -> Result<PackedLayout, String> {
+    layout_of(class_name, fields_of, display_name, &mut Vec::new())
+}
+
+fn layout_of(
+    class_name: &str,
+    fields_of: &dyn Fn(&str) -> Option<Vec<(String, BolideType)>>,
+    display_name: &dyn Fn(&str) -> String,
+    visiting: &mut Vec<String>,
+) -> Result<PackedLayout, String> {
+    let fields = fields_of(class_name).ok_or_else(|| format!("Class not found: {}", class_name))?;
+    if visiting.iter().any(|c| c == class_name) {
+        return Err(format!("Class '{}' cannot be packed: it contains itself", visiting[0]));
+    }
+    visiting.push(class_name.to_string());
+
+    let mut layout = PackedLayout { fields: Vec::new(), size: 0, descriptor: format!("{}{{", display_name(class_name)) };
+    for (i, (name, ty)) in fields.into_iter().enumerate() {
+        if i > 0 {
+            layout.descriptor.push(',');
+        }
+        layout.descriptor.push_str(&name);
+        layout.descriptor.push(':');
+        let size = match &ty {
+            BolideType::Int => { layout.descriptor.push('i'); 8 }
+            BolideType::Float => { layout.descriptor.push('f'); 8 }
+            BolideType::Bool => { layout.descriptor.push('b'); 8 }
+            BolideType::Custom(inner) => {
+                let inner = layout_of(inner, fields_of, display_name, visiting)?;
+                layout.descriptor.push_str(&inner.descriptor);
+                inner.size
+            }
+            other => {
+                let field = if visiting.len() > 1 { format!("{}.{}", class_name, name) } else { name };
+                return Err(format!(
+                    "Class '{}' cannot be packed: field '{}' has type {}; \
+                     packed classes may only contain int, float, bool and other packable classes",
+                    visiting[0], field, type_name(other)
+                ));
+            }
+        };
+        layout.fields.push(PackedField { name, ty, offset: layout.size });
+        layout.size += size;
+    }
+    layout.descriptor.push('}');
+
+    visiting.pop();
+    Ok(layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields_of(name: &str) -> Option<Vec<(String, BolideType)>> {
+        let field = |n: &str, ty: BolideType| (n.to_string(), ty);
+        let vec2 = || BolideType::Custom("Vec2".to_string());
+        Some(match name {
+            "Vec2" => vec![field("x", BolideType::Float), field("y", BolideType::Float)],
+            "Particle" => vec![field("pos", vec2()), field("vel", vec2()), field("alive", BolideType::Bool)],
+            "Named" => vec![field("id", BolideType::Int), field("name", BolideType::Str)],
+            "Holder" => vec![field("inner", BolideType::Custom("Named".to_string()))],
+            "Chain" => vec![field("next", BolideType::Custom("Chain".to_string()))],
+            _ => return None,
+        })
+    }
+
+    fn layout(name: &str) -> Result<PackedLayout, String> {
+        packed_layout(name, &fields_of, &|n| n.to_string())
+    }
+
+    #[test]
+    fn test_nested_classes_are_inlined() {
+        let particle = layout("Particle").unwrap();
+        assert_eq!(particle.size, 40);
+        let offsets: Vec<(&str, usize)> = particle.fields.iter().map(|f| (f.name.as_str(), f.offset)).collect();
+        assert_eq!(offsets, [("pos", 0), ("vel", 16), ("alive", 32)]);
+        assert_eq!(particle.descriptor, "Particle{pos:Vec2{x:f,y:f},vel:Vec2{x:f,y:f},alive:b}");
+    }
+
+    #[test]
+    fn test_rc_fields_are_rejected() {
+        let err = layout("Holder").unwrap_err();
+        assert!(err.contains("'Holder' cannot be packed: field 'Named.name' has type str"), "{}", err);
+        assert!(layout("Chain").unwrap_err().contains("'Chain' cannot be packed: it contains itself"));
+        assert_eq!(layout("Missing").unwrap_err(), "Class not found: Missing");
+    }
+}
@@ -0,0 +1,744 @@
+//! `@pure` 函数的验证
+//!
+//! 标注 `@pure` 的函数必须没有副作用，结果只由参数决定：不做 I/O、不调用 extern 函数、
+//! 不 spawn、不收发通道、不读写全局变量、不给 ref 参数赋值、不修改可能与调用者共享的列表/字典/对象，
+//! 只调用内置的纯函数和纯的用户函数。没有标注的用户函数按同样的规则推断；推断遇到调用环时
+//! 当作不纯（标注了 `@pure` 的函数可以递归）。检查是保守的：拿不准的写法一律拒绝。
+//!
+//! 运行时错误（`error()` 记录的错误）不算副作用：合并或提前的调用只记录一次错误。
+//!
+//! 验证通过的函数才参与优化（见 JIT 的 `compile_pure_call`），标注错了是编译错误而不是未定义行为。
+
+use std::collections::{HashMap, HashSet};
+
+use bolide_parser::{
+    AsyncSelectBranch, BinOp, ClassDef, Expr, FuncDef, ParamMode, Program, SelectBranch, Statement, Type, UnaryOp,
+};
+
+use crate::compound;
+
+/// 没有副作用的内置函数
+const PURE_BUILTINS: &[&str] = &[
+    "len", "int", "float", "parse_int", "parse_float", "str", "bigint", "decimal", "hash",
+    "sum", "min", "max", "avg", "default_dict", "range", "round", "floor", "ceil",
+    "saturating_add", "saturating_sub", "saturating_mul", "wrapping_add", "wrapping_sub", "wrapping_mul",
+    "checked_add", "checked_sub", "checked_mul",
+];
+
+/// 只读取接收者、返回新值的内置方法
+const READ_ONLY_METHODS: &[&str] = &[
+    "split", "trim", "strip", "to_upper", "upper", "to_lower", "lower", "contains", "includes", "replace",
+    "encode", "round", "len", "length", "size", "get", "index_of", "index", "find", "count", "slice",
+    "unique", "is_empty", "empty", "first", "last", "copy", "clone", "decode", "keys", "values",
+];
+
+/// 原地修改接收者的内置方法：只能用在函数自己新建的列表/字典上
+const MUTATING_METHODS: &[&str] = &[
+    "push", "append", "pop", "set", "insert", "remove", "clear", "reverse", "extend", "sort", "sort_by", "dedup",
+];
+
+/// 检查所有 `@pure` 函数，返回名字 -> 定义（按名字索引，供编译期求值使用）
+pub(crate) fn check_program(program: &Program) -> Result<HashMap<String, FuncDef>, String> {
+    let mut checker = Checker::new(program);
+    let mut pure = HashMap::new();
+    for stmt in &program.statements {
+        if let Statement::FuncDef(func) = stmt {
+            if func.pure {
+                checker.check_function(func)
+                    .map_err(|reason| format!("@pure function '{}' has side effects: {}", func.name, reason))?;
+                pure.insert(func.name.clone(), func.clone());
+            }
+        }
+    }
+    Ok(pure)
+}
+
+struct Checker<'p> {
+    functions: HashMap<&'p str, &'p FuncDef>,
+    classes: HashMap<&'p str, &'p ClassDef>,
+    /// 所有类方法名（方法调用不检查纯度）
+    methods: HashSet<&'p str>,
+    globals: HashSet<&'p str>,
+    externs: HashSet<&'p str>,
+    /// 未标注函数的推断结果；`None` 表示正在检查（遇到即为调用环）
+    inferred: HashMap<String, Option<Result<(), String>>>,
+}
+
+/// 单个函数的作用域信息
+struct Scope<'f> {
+    /// 参数和函数里声明过的所有名字
+    locals: HashSet<&'f str>,
+    ref_params: HashSet<&'f str>,
+    /// 只保存函数自己新建的列表/字典的局部变量，可以原地修改
+    fresh: HashSet<&'f str>,
+    /// 声明为数值/字符串的参数和局部变量：`x += v` 只是重新赋值，不会原地扩展列表
+    scalars: HashSet<&'f str>,
+}
+
+impl<'p> Checker<'p> {
+    fn new(program: &'p Program) -> Self {
+        let mut checker = Checker {
+            functions: HashMap::new(),
+            classes: HashMap::new(),
+            methods: HashSet::new(),
+            globals: HashSet::new(),
+            externs: HashSet::new(),
+            inferred: HashMap::new(),
+        };
+        for stmt in &program.statements {
+            match stmt {
+                Statement::FuncDef(func) => {
+                    checker.functions.insert(&func.name, func);
+                }
+                Statement::ClassDef(class) => {
+                    checker.classes.insert(&class.name, class);
+                    checker.methods.extend(class.methods.iter().map(|m| m.name.as_str()));
+                }
+                Statement::VarDecl(decl) => {
+                    checker.globals.insert(&decl.name);
+                }
+                Statement::Destructure(d) if d.declare => {
+                    checker.globals.extend(d.targets.iter().flatten().map(|t| t.as_str()));
+                }
+                Statement::ExternBlock(block) => {
+                    for decl in &block.declarations {
+                        if let bolide_parser::ExternDecl::Function(f) = decl {
+                            checker.externs.insert(&f.name);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        checker
+    }
+
+    /// 用户函数是否纯：标注了 `@pure` 的视为纯（单独验证），其余按函数体推断
+    fn callee_is_pure(&mut self, name: &str) -> Result<(), String> {
+        let func = self.functions[name];
+        if func.pure {
+            return Ok(());
+        }
+        match self.inferred.get(name) {
+            Some(Some(result)) => return result.clone(),
+            Some(None) => return Err(format!("'{}', which is recursive and not marked @pure", name)),
+            None => {}
+        }
+        self.inferred.insert(name.to_string(), None);
+        let result = if func.is_async {
+            Err(format!("'{}' is async", name))
+        } else {
+            self.check_function(func).map_err(|reason| format!("'{}', which is not pure: {}", name, reason))
+        };
+        self.inferred.insert(name.to_string(), Some(result.clone()));
+        result
+    }
+
+    fn check_function(&mut self, func: &FuncDef) -> Result<(), String> {
+        let mut scope = Scope {
+            locals: func.params.iter().map(|p| p.name.as_str()).collect(),
+            ref_params: func.params.iter()
+                .filter(|p| p.mode == ParamMode::Ref)
+                .map(|p| p.name.as_str())
+                .collect(),
+            fresh: HashSet::new(),
+            scalars: func.params.iter()
+                .filter(|p| is_scalar_type(&p.ty))
+                .map(|p| p.name.as_str())
+                .collect(),
+        };
+        let mut writes: HashMap<&str, bool> = HashMap::new();
+        collect_locals(&func.body, &mut scope.locals, &mut scope.scalars, &mut writes);
+        scope.fresh = writes.into_iter()
+            .filter(|(name, fresh)| *fresh && !func.params.iter().any(|p| p.name == *name))
+            .map(|(name, _)| name)
+            .collect();
+        self.check_block(&func.body, &scope)
+    }
+
+    fn check_block(&mut self, body: &[Statement], scope: &Scope) -> Result<(), String> {
+        body.iter().try_for_each(|stmt| self.check_stmt(stmt, scope))
+    }
+
+    fn check_stmt(&mut self, stmt: &Statement, scope: &Scope) -> Result<(), String> {
+        match stmt {
+            Statement::VarDecl(decl) => {
+                if let Some(value) = &decl.value {
+                    self.check_expr(value, scope)?;
+                }
+                Ok(())
+            }
+            Statement::Assign(assign) => {
+                let op = assign.op.map_or("=", compound::op_text);
+                let text = format!("`{} {} ...`", expr_text(&assign.target), op);
+                match &assign.target {
+                    // 列表的 `+=` 原地扩展，和 push 一样只能用在新建的列表上
+                    Expr::Ident(name) if matches!(assign.op, Some(BinOp::Add)) && !scope.scalars.contains(name.as_str()) => {
+                        self.check_write(name, &text, scope)?;
+                        self.check_mutation(&assign.target, &text, scope)?;
+                    }
+                    Expr::Ident(name) => self.check_write(name, &text, scope)?,
+                    Expr::Index(base, index) => {
+                        self.check_mutation(base, &text, scope)?;
+                        self.check_expr(index, scope)?;
+                    }
+                    Expr::Member(..) => return Err(format!("{} modifies a field", text)),
+                    _ => return Err(format!("{} is not a local assignment", text)),
+                }
+                self.check_expr(&assign.value, scope)
+            }
+            Statement::Destructure(d) => {
+                if !d.declare {
+                    for name in d.targets.iter().flatten() {
+                        self.check_write(name, &format!("`{} = ...`", name), scope)?;
+                    }
+                }
+                self.check_expr(&d.value, scope)
+            }
+            Statement::If(s) => {
+                self.check_expr(&s.condition, scope)?;
+                self.check_block(&s.then_body, scope)?;
+                for (cond, body) in &s.elif_branches {
+                    self.check_expr(cond, scope)?;
+                    self.check_block(body, scope)?;
+                }
+                if let Some(body) = &s.else_body {
+                    self.check_block(body, scope)?;
+                }
+                Ok(())
+            }
+            Statement::While(s) => {
+                self.check_expr(&s.condition, scope)?;
+                self.check_block(&s.body, scope)
+            }
+            Statement::For(s) => {
+                self.check_expr(&s.iter, scope)?;
+                self.check_block(&s.body, scope)
+            }
+            Statement::Match(s) => {
+                self.check_expr(&s.subject, scope)?;
+                for arm in &s.arms {
+                    self.check_block(&arm.body, scope)?;
+                }
+                if let Some(body) = &s.default {
+                    self.check_block(body, scope)?;
+                }
+                Ok(())
+            }
+            Statement::Return(value) => match value {
+                Some(value) => self.check_expr(value, scope),
+                None => Ok(()),
+            },
+            Statement::Break | Statement::Continue => Ok(()),
+            Statement::Expr(expr) => self.check_expr(expr, scope),
+            Statement::Send(send) => Err(format!("`{} <- ...` sends on a channel", send.channel)),
+            Statement::Pool(_) => Err("`pool` block runs code on other threads".to_string()),
+            Statement::Select(_) => Err("`select` receives from channels".to_string()),
+            Statement::AsyncSelect(_) => Err("`select` awaits coroutines".to_string()),
+            Statement::AwaitScope(_) => Err("`await scope` starts coroutines".to_string()),
+            Statement::Measure(m) => Err(format!("`measure \"{}\"` prints timings", m.label)),
+            Statement::FuncDef(_) | Statement::ClassDef(_) | Statement::Import(_) | Statement::ExternBlock(_) | Statement::Test(_) => {
+                Err("nested definitions are not allowed".to_string())
+            }
+        }
+    }
+
+    /// 给名字赋值：只能是局部变量，不能是 ref 参数
+    fn check_write(&self, name: &str, text: &str, scope: &Scope) -> Result<(), String> {
+        if scope.ref_params.contains(name) {
+            return Err(format!("{} assigns to ref parameter '{}'", text, name));
+        }
+        if !scope.locals.contains(name) {
+            return Err(format!("{} assigns to global '{}'", text, name));
+        }
+        Ok(())
+    }
+
+    /// 原地修改 `target`：只能是函数自己新建的列表/字典
+    fn check_mutation(&mut self, target: &Expr, text: &str, scope: &Scope) -> Result<(), String> {
+        match target {
+            Expr::Ident(name) if scope.fresh.contains(name.as_str()) => Ok(()),
+            Expr::Ident(name) if scope.locals.contains(name.as_str()) => {
+                Err(format!("{} modifies '{}', which may be shared with the caller", text, name))
+            }
+            _ => {
+                self.check_expr(target, scope)?;
+                Err(format!("{} modifies a value that may be shared with the caller", text))
+            }
+        }
+    }
+
+    fn check_expr(&mut self, expr: &Expr, scope: &Scope) -> Result<(), String> {
+        match expr {
+            Expr::Int(_) | Expr::Float(_) | Expr::Bool(_) | Expr::String(_)
+            | Expr::BigInt(_) | Expr::Decimal(_) | Expr::Duration(_) | Expr::None => Ok(()),
+            Expr::Ident(name) => {
+                if !scope.locals.contains(name.as_str()) && self.globals.contains(name.as_str()) {
+                    return Err(format!("`{}` reads global '{}'", name, name));
+                }
+                Ok(())
+            }
+            Expr::BinOp(l, _, r) => {
+                self.check_expr(l, scope)?;
+                self.check_expr(r, scope)
+            }
+            Expr::CompareChain(first, rest) => {
+                self.check_expr(first, scope)?;
+                rest.iter().try_for_each(|(_, e)| self.check_expr(e, scope))
+            }
+            Expr::UnaryOp(_, e) | Expr::Member(e, _) | Expr::NamedArg(_, e) => self.check_expr(e, scope),
+            Expr::Index(base, index) => {
+                self.check_expr(base, scope)?;
+                self.check_expr(index, scope)
+            }
+            Expr::Slice(base, start, end) => {
+                self.check_expr(base, scope)?;
+                for bound in [start, end].into_iter().flatten() {
+                    self.check_expr(bound, scope)?;
+                }
+                Ok(())
+            }
+            Expr::List(items) | Expr::Tuple(items) | Expr::FString(items) => items.iter().try_for_each(|e| self.check_expr(e, scope)),
+            Expr::Dict(entries) => entries.iter().try_for_each(|(k, v)| {
+                self.check_expr(k, scope)?;
+                self.check_expr(v, scope)
+            }),
+            Expr::Call(callee, args) => {
+                self.check_call(expr, callee, scope)?;
+                args.iter().try_for_each(|e| self.check_expr(e, scope))
+            }
+            Expr::Spawn(..) => Err(format!("`{}` starts a thread", expr_text(expr))),
+            // 匿名函数在检查之前已提升为命名函数，剩下的出现在不支持的位置，由编译器报告
+            Expr::Lambda(..) => Ok(()),
+            Expr::Recv(ch) => Err(format!("`<- {}` receives from a channel", ch)),
+            Expr::Await(_) | Expr::AwaitAll(_) => Err(format!("`{}` waits for a coroutine", expr_text(expr))),
+        }
+    }
+
+    fn check_call(&mut self, call: &Expr, callee: &Expr, scope: &Scope) -> Result<(), String> {
+        let text = expr_text(call);
+        match callee {
+            Expr::Ident(name) if scope.locals.contains(name.as_str()) => {
+                Err(format!("`{}` calls a function value; only direct calls to pure functions are allowed", text))
+            }
+            Expr::Ident(name) if self.functions.contains_key(name.as_str()) => {
+                self.callee_is_pure(name).map_err(|reason| format!("`{}` calls {}", text, reason))
+            }
+            Expr::Ident(name) if self.classes.contains_key(name.as_str()) => self.check_constructor(name, &text),
+            Expr::Ident(name) if self.externs.contains(name.as_str()) => {
+                Err(format!("`{}` calls extern function '{}'", text, name))
+            }
+            Expr::Ident(name) if PURE_BUILTINS.contains(&name.as_str()) => Ok(()),
+            Expr::Ident(name) => Err(format!("`{}` calls {}(), which has side effects", text, name)),
+            Expr::Member(receiver, method) => {
+                // 模块函数: math.square(x)
+                if let Expr::Ident(module) = receiver.as_ref() {
+                    let qualified = format!("@{}_{}", module, method);
+                    if !scope.locals.contains(module.as_str()) && self.functions.contains_key(qualified.as_str()) {
+                        return self.callee_is_pure(&qualified).map_err(|reason| format!("`{}` calls {}", text, reason));
+                    }
+                }
+                if self.methods.contains(method.as_str()) {
+                    return Err(format!("`{}` calls method '{}', which is not checked for purity", text, method));
+                }
+                if MUTATING_METHODS.contains(&method.as_str()) {
+                    return self.check_mutation(receiver, &format!("`{}`", text), scope);
+                }
+                if READ_ONLY_METHODS.contains(&method.as_str()) {
+                    return self.check_expr(receiver, scope);
+                }
+                Err(format!("`{}` calls method '{}', which has side effects", text, method))
+            }
+            _ => Err(format!("`{}` calls a computed function value", text)),
+        }
+    }
+
+    /// 构造对象只分配内存；字段默认值也要是纯的
+    fn check_constructor(&mut self, class_name: &str, text: &str) -> Result<(), String> {
+        let mut class = self.classes.get(class_name).copied();
+        let empty = Scope { locals: HashSet::new(), ref_params: HashSet::new(), fresh: HashSet::new(), scalars: HashSet::new() };
+        while let Some(c) = class {
+            for field in &c.fields {
+                if let Some(default) = &field.default_value {
+                    self.check_expr(default, &empty).map_err(|reason| {
+                        format!("`{}` evaluates the default of {}.{}: {}", text, c.name, field.name, reason)
+                    })?;
+                }
+            }
+            class = c.parent.as_deref().and_then(|p| self.classes.get(p).copied());
+        }
+        Ok(())
+    }
+}
+
+/// 收集函数体里声明的名字，并记录每个名字是否只被赋过新建的列表/字典
+pub(crate) fn collect_locals<'f>(
+    body: &'f [Statement],
+    locals: &mut HashSet<&'f str>,
+    scalars: &mut HashSet<&'f str>,
+    writes: &mut HashMap<&'f str, bool>,
+) {
+    let record = |name: &'f str, value: Option<&Expr>, writes: &mut HashMap<&'f str, bool>| {
+        let fresh = value.is_some_and(is_fresh);
+        *writes.entry(name).or_insert(true) &= fresh;
+    };
+    for stmt in body {
+        match stmt {
+            Statement::VarDecl(decl) => {
+                locals.insert(&decl.name);
+                record(&decl.name, decl.value.as_ref(), writes);
+                let scalar = match &decl.ty {
+                    Some(ty) => is_scalar_type(ty),
+                    None => decl.value.as_ref().is_some_and(is_scalar_expr),
+                };
+                if scalar {
+                    scalars.insert(&decl.name);
+                }
+            }
+            Statement::Assign(assign) => {
+                if let Expr::Ident(name) = &assign.target {
+                    if assign.op.is_some() {
+                        // 复合赋值不换掉列表本身，不影响是否新建
+                        writes.entry(name).or_insert(true);
+                    } else {
+                        record(name, Some(&assign.value), writes);
+                    }
+                }
+            }
+            Statement::Destructure(d) => {
+                for name in d.targets.iter().flatten() {
+                    if d.declare {
+                        locals.insert(name);
+                    }
+                    record(name, None, writes);
+                }
+            }
+            Statement::If(s) => {
+                collect_locals(&s.then_body, locals, scalars, writes);
+                for (_, body) in &s.elif_branches {
+                    collect_locals(body, locals, scalars, writes);
+                }
+                if let Some(body) = &s.else_body {
+                    collect_locals(body, locals, scalars, writes);
+                }
+            }
+            Statement::While(s) => collect_locals(&s.body, locals, scalars, writes),
+            Statement::For(s) => {
+                for var in &s.vars {
+                    locals.insert(var);
+                    record(var, None, writes);
+                }
+                collect_locals(&s.body, locals, scalars, writes);
+            }
+            Statement::Match(s) => {
+                for arm in &s.arms {
+                    collect_locals(&arm.body, locals, scalars, writes);
+                }
+                if let Some(body) = &s.default {
+                    collect_locals(body, locals, scalars, writes);
+                }
+            }
+            Statement::Select(s) => {
+                for branch in &s.branches {
+                    match branch {
+                        SelectBranch::Recv { var, .. } => {
+                            locals.insert(var);
+                        }
+                        SelectBranch::RecvAny { var, index, .. } => {
+                            locals.insert(var);
+                            locals.insert(index);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Statement::AsyncSelect(s) => {
+                for branch in &s.branches {
+                    if let AsyncSelectBranch::Bind { var, .. } = branch {
+                        locals.insert(var);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_scalar_type(ty: &Type) -> bool {
+    matches!(ty, Type::Int | Type::Float | Type::Bool | Type::Str | Type::BigInt | Type::Decimal | Type::Duration | Type::Deadline)
+}
+
+/// 结果一定是数值/字符串的初始化表达式
+fn is_scalar_expr(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Int(_) | Expr::Float(_) | Expr::Bool(_) | Expr::String(_) | Expr::BigInt(_) | Expr::Decimal(_)
+            | Expr::Duration(_) | Expr::BinOp(..) | Expr::UnaryOp(..) | Expr::CompareChain(..)
+    )
+}
+
+/// 新建的、不与任何其他值共享的列表/字典
+fn is_fresh(expr: &Expr) -> bool {
+    match expr {
+        Expr::List(_) | Expr::Dict(_) => true,
+        Expr::Call(callee, _) => matches!(callee.as_ref(), Expr::Member(_, m) if m == "copy" || m == "clone"),
+        _ => false,
+    }
+}
+
+/// 错误信息里引用的源码片段
+pub(crate) fn expr_text(expr: &Expr) -> String {
+    let list = |items: &[Expr]| items.iter().map(expr_text).collect::<Vec<_>>().join(", ");
+    match expr {
+        Expr::Int(n) => n.to_string(),
+        Expr::Float(f) => format!("{:?}", f),
+        Expr::Bool(b) => b.to_string(),
+        Expr::String(s) => format!("{:?}", s),
+        Expr::BigInt(s) => format!("{}B", s),
+        Expr::Decimal(s) => format!("{}D", s),
+        Expr::Duration(ns) => bolide_runtime::format_duration(*ns),
+        Expr::Ident(name) => name.clone(),
+        Expr::None => "none".to_string(),
+        Expr::BinOp(l, op, r) => format!("{} {} {}", expr_text(l), binop_text(op), expr_text(r)),
+        Expr::CompareChain(first, rest) => {
+            let mut text = expr_text(first);
+            for (op, e) in rest {
+                text.push_str(&format!(" {} {}", binop_text(op), expr_text(e)));
+            }
+            text
+        }
+        Expr::UnaryOp(UnaryOp::Neg, e) => format!("-{}", expr_text(e)),
+        Expr::UnaryOp(UnaryOp::Not, e) => format!("not {}", expr_text(e)),
+        Expr::Call(callee, args) => format!("{}({})", expr_text(callee), list(args)),
+        Expr::Index(base, index) => format!("{}[{}]", expr_text(base), expr_text(index)),
+        Expr::Slice(base, start, end) => format!(
+            "{}[{}:{}]",
+            expr_text(base),
+            start.as_deref().map(expr_text).unwrap_or_default(),
+            end.as_deref().map(expr_text).unwrap_or_default()
+        ),
+        Expr::Member(base, name) => format!("{}.{}", expr_text(base), name),
+        Expr::NamedArg(name, value) => format!("{}: {}", name, expr_text(value)),
+        Expr::List(items) => format!("[{}]", list(items)),
+        Expr::Tuple(items) => format!("({})", list(items)),
+        Expr::FString(parts) => {
+            let text: String = parts.iter().map(|part| match part {
+                Expr::String(s) => s.replace('{', "{{").replace('}', "}}"),
+                e => format!("{{{}}}", expr_text(e)),
+            }).collect();
+            format!("f{:?}", text)
+        }
+        Expr::Dict(entries) => format!(
+            "{{{}}}",
+            entries.iter().map(|(k, v)| format!("{}: {}", expr_text(k), expr_text(v))).collect::<Vec<_>>().join(", ")
+        ),
+        Expr::Spawn(name, args) => format!("spawn {}({})", name, list(args)),
+        Expr::Recv(ch) => format!("<- {}", ch),
+        Expr::Await(e) => format!("await {}", expr_text(e)),
+        Expr::AwaitAll(items) => format!("await all {{ {} }}", list(items)),
+        Expr::Lambda(params, _, _) => format!(
+            "fn({}) {{ ... }}",
+            params.iter().map(|p| p.name.as_str()).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+pub(crate) fn binop_text(op: &BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Sub => "-",
+        BinOp::Mul => "*",
+        BinOp::Div => "/",
+        BinOp::Mod => "%",
+        BinOp::Eq => "==",
+        BinOp::Ne => "!=",
+        BinOp::Lt => "<",
+        BinOp::Le => "<=",
+        BinOp::Gt => ">",
+        BinOp::Ge => ">=",
+        BinOp::And => "and",
+        BinOp::Or => "or",
+        BinOp::Is => "is",
+        BinOp::IsNot => "is not",
+    }
+}
+
+/// 条件里每次都会求值的直接调用（链式比较第一个操作数之后的部分会短路，不算）
+pub(crate) fn unconditional_calls<'e>(expr: &'e Expr, calls: &mut Vec<(&'e str, &'e [Expr])>) {
+    match expr {
+        Expr::Call(callee, args) => {
+            match callee.as_ref() {
+                Expr::Ident(name) => calls.push((name, args)),
+                Expr::Member(receiver, _) => unconditional_calls(receiver, calls),
+                _ => {}
+            }
+            for arg in args {
+                unconditional_calls(arg, calls);
+            }
+        }
+        Expr::BinOp(l, _, r) | Expr::Index(l, r) => {
+            unconditional_calls(l, calls);
+            unconditional_calls(r, calls);
+        }
+        Expr::CompareChain(first, _) | Expr::UnaryOp(_, first) | Expr::Member(first, _) => {
+            unconditional_calls(first, calls);
+        }
+        Expr::List(items) | Expr::Tuple(items) | Expr::FString(items) => {
+            for item in items {
+                unconditional_calls(item, calls);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 循环里可能被改写的变量：赋值、声明、循环变量，以及传给 ref / owned 参数（`by_ref(函数, 下标)`）
+/// 或方法调用的变量（用于把 `@pure` 调用提到循环之前）
+pub(crate) fn loop_written_vars(cond: &Expr, body: &[Statement], by_ref: impl Fn(&str, usize) -> bool) -> HashSet<String> {
+    let mut written = HashSet::new();
+    let mut locals = HashSet::new();
+    let mut writes = HashMap::new();
+    collect_locals(body, &mut locals, &mut HashSet::new(), &mut writes);
+    written.extend(writes.into_keys().map(str::to_string));
+    written.extend(locals.into_iter().map(str::to_string));
+
+    let mut visit = |expr: &Expr| {
+        if let Expr::Call(callee, args) = expr {
+            for (i, arg) in args.iter().enumerate() {
+                let Expr::Ident(var) = arg else { continue };
+                // 方法和模块函数的参数模式这里查不到，按可能被改写处理
+                let passed_by_ref = match callee.as_ref() {
+                    Expr::Ident(name) => by_ref(name, i),
+                    _ => true,
+                };
+                if passed_by_ref {
+                    written.insert(var.clone());
+                }
+            }
+        }
+    };
+    visit_expr(cond, &mut visit);
+    for_each_expr(body, &mut visit);
+    written
+}
+
+/// 访问语句里的所有表达式（含子表达式）
+pub(crate) fn for_each_expr(body: &[Statement], f: &mut impl FnMut(&Expr)) {
+    for stmt in body {
+        match stmt {
+            Statement::VarDecl(decl) => {
+                if let Some(value) = &decl.value {
+                    visit_expr(value, f);
+                }
+            }
+            Statement::Assign(assign) => {
+                visit_expr(&assign.target, f);
+                visit_expr(&assign.value, f);
+            }
+            Statement::Destructure(d) => visit_expr(&d.value, f),
+            Statement::If(s) => {
+                visit_expr(&s.condition, f);
+                for_each_expr(&s.then_body, f);
+                for (cond, body) in &s.elif_branches {
+                    visit_expr(cond, f);
+                    for_each_expr(body, f);
+                }
+                if let Some(body) = &s.else_body {
+                    for_each_expr(body, f);
+                }
+            }
+            Statement::While(s) => {
+                visit_expr(&s.condition, f);
+                for_each_expr(&s.body, f);
+            }
+            Statement::For(s) => {
+                visit_expr(&s.iter, f);
+                for_each_expr(&s.body, f);
+            }
+            Statement::Match(s) => {
+                visit_expr(&s.subject, f);
+                for arm in &s.arms {
+                    for_each_expr(&arm.body, f);
+                }
+                if let Some(body) = &s.default {
+                    for_each_expr(body, f);
+                }
+            }
+            Statement::Pool(s) => {
+                visit_expr(&s.size, f);
+                for_each_expr(&s.body, f);
+            }
+            Statement::Select(s) => {
+                if let Some(channels) = &s.channels {
+                    visit_expr(channels, f);
+                }
+                for branch in &s.branches {
+                    match branch {
+                        SelectBranch::Recv { body, .. } | SelectBranch::RecvAny { body, .. } | SelectBranch::Closed { body }
+                        | SelectBranch::Default { body } => for_each_expr(body, f),
+                        SelectBranch::Timeout { duration, body } => {
+                            visit_expr(duration, f);
+                            for_each_expr(body, f);
+                        }
+                    }
+                }
+            }
+            Statement::AsyncSelect(s) => {
+                for branch in &s.branches {
+                    match branch {
+                        AsyncSelectBranch::Bind { expr, body, .. } | AsyncSelectBranch::Expr { expr, body } => {
+                            visit_expr(expr, f);
+                            for_each_expr(body, f);
+                        }
+                    }
+                }
+            }
+            Statement::AwaitScope(s) => for_each_expr(&s.body, f),
+            Statement::Measure(s) => for_each_expr(&s.body, f),
+            Statement::Send(s) => visit_expr(&s.value, f),
+            Statement::Return(Some(value)) | Statement::Expr(value) => visit_expr(value, f),
+            _ => {}
+        }
+    }
+}
+
+pub(crate) fn visit_expr(expr: &Expr, f: &mut impl FnMut(&Expr)) {
+    f(expr);
+    match expr {
+        Expr::BinOp(l, _, r) | Expr::Index(l, r) => {
+            visit_expr(l, f);
+            visit_expr(r, f);
+        }
+        Expr::CompareChain(first, rest) => {
+            visit_expr(first, f);
+            for (_, e) in rest {
+                visit_expr(e, f);
+            }
+        }
+        Expr::UnaryOp(_, e) | Expr::Member(e, _) | Expr::Await(e) | Expr::NamedArg(_, e) => visit_expr(e, f),
+        Expr::Slice(base, start, end) => {
+            visit_expr(base, f);
+            for bound in [start, end].into_iter().flatten() {
+                visit_expr(bound, f);
+            }
+        }
+        Expr::Call(callee, args) => {
+            visit_expr(callee, f);
+            for arg in args {
+                visit_expr(arg, f);
+            }
+        }
+        Expr::List(items) | Expr::Tuple(items) | Expr::FString(items) | Expr::AwaitAll(items) | Expr::Spawn(_, items) => {
+            for item in items {
+                visit_expr(item, f);
+            }
+        }
+        Expr::Dict(entries) => {
+            for (k, v) in entries {
+                visit_expr(k, f);
+                visit_expr(v, f);
+            }
+        }
+        _ => {}
+    }
+}
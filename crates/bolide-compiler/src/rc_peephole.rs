@@ -0,0 +1,292 @@
+//! 窥孔优化：删除互相抵消的 `string_clone` / `string_release`
+//!
+//! 在函数的 IR 生成完之后运行。`c = string_clone(src)` 之后 `c` 只被借用（传给只读取字符串的
+//! 运行时函数、按借用模式传给用户函数、再被 clone），最后由 `string_release(c)` 释放时，这次深拷贝
+//! 没有被观察到：删掉 clone 和 release，把 `c` 的使用换成 `src`。
+//!
+//! 只在能证明 `src` 在 `c` 的最后一次使用之前一直有效时才改写，其余情况保持原样：
+//!
+//! - clone、`c` 的全部使用和它的 release 在同一个基本块里，`c` 在 release 之后不再使用；
+//!   `c` 被返回、存入内存、传给 owned 参数或其他运行时函数、作为跳转参数时不改写。
+//! - `src` 是入口块里的函数参数（借用参数在整个调用期间有效，和直接使用参数的假设相同），
+//!   或同一块里刚分配的新值（用户函数的返回值、拼接、转换、clone 的结果）。
+//! - 从 `src` 定义到 `c` 最后一次使用之间，`src` 也只被借用：没有被释放、移交或存入别处，
+//!   中间的调用无法通过别名释放它。
+//!
+//! 删掉一对之后可能出现新的一对（`let a = s; let b = a;`），重复到没有可删的为止。
+//! `--no-opt` 时不改写，`--analyze` 报告还能删掉的对数。
+
+use std::collections::HashMap;
+
+use cranelift_codegen::ir::{Block, ExternalName, Function, Inst, InstructionData, Value, ValueDef};
+use cranelift_module::FuncId;
+
+use bolide_parser::{Param, ParamMode};
+
+use crate::analysis;
+
+/// 只读取字符串参数、不保存指针的运行时函数
+const STRING_READERS: &[&str] = &[
+    "print_string", "print_string_inline",
+    "string_len", "string_eq", "string_cmp", "string_concat", "string_contains",
+    "string_char_at", "string_slice", "string_split", "string_trim", "string_upper",
+    "string_lower", "string_replace", "string_encode", "string_to_int", "string_to_float",
+    "string_parse_int", "string_parse_float", "string_builder_append_str", "hash_string",
+    "string_clone",
+];
+
+/// 返回新字符串（ref_count = 1）的运行时函数，`analysis::is_alloc` 之外的
+const STRING_PRODUCERS: &[&str] = &[
+    "string_slice", "string_trim", "string_upper", "string_lower", "string_replace",
+    "string_builder_finish",
+];
+
+/// 一对可以删掉的 clone / release
+struct Pair {
+    clone: Inst,
+    release: Inst,
+    copy: Value,
+    src: Value,
+}
+
+struct Callees<'a> {
+    names: HashMap<u32, &'a str>,
+    params: &'a HashMap<String, Vec<Param>>,
+}
+
+impl<'a> Callees<'a> {
+    fn new(functions: &'a HashMap<String, FuncId>, params: &'a HashMap<String, Vec<Param>>) -> Self {
+        let names = functions.iter().map(|(name, id)| (id.as_u32(), name.as_str())).collect();
+        Callees { names, params }
+    }
+
+    /// 直接调用的目标函数名
+    fn target(&self, func: &Function, inst: Inst) -> Option<&'a str> {
+        let InstructionData::Call { func_ref, .. } = func.dfg.insts[inst] else {
+            return None;
+        };
+        match &func.dfg.ext_funcs[func_ref].name {
+            ExternalName::User(r) => self.names.get(&func.params.user_named_funcs()[*r].index).copied(),
+            _ => None,
+        }
+    }
+
+    /// `inst` 对 `value` 的使用是否只是借用
+    fn borrows(&self, func: &Function, inst: Inst, value: Value) -> bool {
+        let Some(target) = self.target(func, inst) else {
+            return false;
+        };
+        if STRING_READERS.contains(&target) {
+            return true;
+        }
+        let Some(params) = self.params.get(target) else {
+            return false;
+        };
+        let args = func.dfg.inst_args(inst);
+        args.len() == params.len()
+            && args.iter().zip(params).all(|(&arg, param)| {
+                func.dfg.resolve_aliases(arg) != value || param.mode == ParamMode::Borrow
+            })
+    }
+
+    /// `inst` 的结果是否是调用者拥有的新值
+    fn produces_fresh(&self, func: &Function, inst: Inst) -> bool {
+        self.target(func, inst).is_some_and(|target| {
+            self.params.contains_key(target)
+                || target.ends_with("_clone")
+                || analysis::is_alloc(target)
+                || STRING_PRODUCERS.contains(&target)
+        })
+    }
+}
+
+/// 删掉所有能证明互相抵消的 clone / release 对，返回删掉的对数
+pub(crate) fn remove_cancelled_clones(
+    func: &mut Function,
+    functions: &HashMap<String, FuncId>,
+    params: &HashMap<String, Vec<Param>>,
+) -> usize {
+    let callees = Callees::new(functions, params);
+    let mut removed = 0;
+    while let Some(pair) = find_pairs(func, &callees).into_iter().next() {
+        apply(func, &pair);
+        removed += 1;
+    }
+    removed
+}
+
+/// 不改写，统计当前能删掉的 clone / release 对数
+pub(crate) fn cancelled_clones(
+    func: &Function,
+    functions: &HashMap<String, FuncId>,
+    params: &HashMap<String, Vec<Param>>,
+) -> usize {
+    find_pairs(func, &Callees::new(functions, params)).len()
+}
+
+fn find_pairs(func: &Function, callees: &Callees) -> Vec<Pair> {
+    // 每个值被哪些指令使用（含跳转参数）
+    let mut uses: HashMap<Value, Vec<Inst>> = HashMap::new();
+    for block in func.layout.blocks() {
+        for inst in func.layout.block_insts(block) {
+            for value in func.dfg.inst_values(inst) {
+                uses.entry(func.dfg.resolve_aliases(value)).or_default().push(inst);
+            }
+        }
+    }
+    let entry = func.layout.entry_block();
+    let mut pairs = Vec::new();
+    for block in func.layout.blocks() {
+        let insts: Vec<Inst> = func.layout.block_insts(block).collect();
+        let position: HashMap<Inst, usize> = insts.iter().enumerate().map(|(i, &inst)| (inst, i)).collect();
+        for (at, &inst) in insts.iter().enumerate() {
+            if callees.target(func, inst) != Some("string_clone") {
+                continue;
+            }
+            let copy = func.dfg.first_result(inst);
+            let src = func.dfg.resolve_aliases(func.dfg.inst_args(inst)[0]);
+            if let Some(pair) = check_pair(func, callees, &uses, &position, block, entry, at, inst, copy, src) {
+                pairs.push(pair);
+            }
+        }
+    }
+    pairs
+}
+
+#[allow(clippy::too_many_arguments)]
+fn check_pair(
+    func: &Function,
+    callees: &Callees,
+    uses: &HashMap<Value, Vec<Inst>>,
+    position: &HashMap<Inst, usize>,
+    block: Block,
+    entry: Option<Block>,
+    at: usize,
+    clone: Inst,
+    copy: Value,
+    src: Value,
+) -> Option<Pair> {
+    // src 必须在这一块里一直归本函数（或调用者）持有
+    let src_start = match func.dfg.value_def(src) {
+        ValueDef::Param(def_block, _) if Some(def_block) == entry && block == def_block => 0,
+        ValueDef::Result(def, 0) if func.layout.inst_block(def) == Some(block) && callees.produces_fresh(func, def) => {
+            position[&def] + 1
+        }
+        _ => return None,
+    };
+
+    // copy 只在这一块里被借用，最后恰好释放一次
+    let mut release = None;
+    let mut last_use = at;
+    for &user in uses.get(&copy).map(Vec::as_slice).unwrap_or(&[]) {
+        let &pos = position.get(&user)?;
+        if callees.target(func, user) == Some("string_release") {
+            if release.is_some() {
+                return None;
+            }
+            release = Some((user, pos));
+        } else if pos > at && callees.borrows(func, user, copy) {
+            last_use = last_use.max(pos);
+        } else {
+            return None;
+        }
+    }
+    let (release, release_pos) = release?;
+    if release_pos < last_use {
+        return None;
+    }
+
+    // 到 copy 最后一次使用为止，src 也只被借用
+    for &user in uses.get(&src).map(Vec::as_slice).unwrap_or(&[]) {
+        let Some(&pos) = position.get(&user) else { continue };
+        if pos >= src_start && pos <= last_use && user != clone && !callees.borrows(func, user, src) {
+            return None;
+        }
+    }
+    Some(Pair { clone, release, copy, src })
+}
+
+fn apply(func: &mut Function, pair: &Pair) {
+    func.layout.remove_inst(pair.clone);
+    func.layout.remove_inst(pair.release);
+    let users: Vec<Inst> = func.layout.blocks()
+        .flat_map(|block| func.layout.block_insts(block))
+        .collect();
+    for inst in users {
+        let values: Vec<Value> = func.dfg.inst_values(inst)
+            .map(|value| if func.dfg.resolve_aliases(value) == pair.copy { pair.src } else { value })
+            .collect();
+        func.dfg.overwrite_inst_values(inst, values.into_iter());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::JitCompiler;
+
+    fn analyze(src: &str, no_opt: bool) -> crate::AnalysisReport {
+        let program = bolide_parser::parse_source(src).unwrap();
+        let mut compiler = JitCompiler::new();
+        compiler.set_no_opt(no_opt);
+        compiler.analyze(&program).unwrap()
+    }
+
+    const SRC: &str = "fn look(s: str) -> int {\n    return len(s);\n}\n\n\
+fn show(s: str) -> int {\n    let t: str = s;\n    let n: int = look(t) + look(t);\n    print(t);\n    return n;\n}\n\n\
+fn twice(s: str) -> str {\n    let a: str = s;\n    let b: str = a;\n    return b;\n}\n\n\
+fn joined(s: str) -> int {\n    let t: str = s + \"!\";\n    let u: str = t;\n    return look(u);\n}\n\n\
+fn keep(s: str) -> list<str> {\n    let t: str = s;\n    let xs: list<str> = [];\n    xs.push(t);\n    print(t);\n    return xs;\n}\n";
+
+    #[test]
+    fn test_cancelled_pairs_removed() {
+        let report = analyze(SRC, false);
+        // 参数复制到局部变量、只被借用：没有任何 RC 操作
+        let show = report.function("show").unwrap();
+        assert!(show.rc_ops.is_empty(), "{:?}", show.rc_ops);
+        assert_eq!(show.rc_pairs_removed, 1);
+        // 链式复制只剩返回值需要的一次 clone
+        let twice = report.function("twice").unwrap();
+        assert_eq!(twice.rc_ops.get("string_clone"), Some(&1));
+        assert_eq!(twice.releases(), 0);
+        // 拼接结果被复制：只释放拼接结果本身
+        let joined = report.function("joined").unwrap();
+        assert_eq!(joined.clones(), 0);
+        assert_eq!(joined.rc_ops.get("string_release"), Some(&1));
+        for f in report.functions.values() {
+            assert_eq!(f.rc_pairs_cancellable, 0);
+        }
+
+        // 存入列表的副本不能删
+        let keep = report.function("keep").unwrap();
+        assert_eq!(keep.rc_pairs_removed, 0);
+        assert_eq!(keep.clones(), 1);
+
+        // --no-opt 只报告
+        let plain = analyze(SRC, true);
+        let show = plain.function("show").unwrap();
+        assert_eq!((show.clones(), show.releases()), (1, 1));
+        assert_eq!((show.rc_pairs_removed, show.rc_pairs_cancellable), (0, 1));
+        assert!(plain.to_text().contains("cancellable x1"));
+    }
+
+    #[test]
+    fn test_removed_pairs_keep_semantics() {
+        let src = "fn grow(s: str) -> str {\n    let t: str = s;\n    let u: str = t + \"?\";\n    let v: str = u;\n    return v + t;\n}\n\n\
+fn pick(a: str, b: str) -> str {\n    let x: str = a;\n    let y: str = b;\n    if len(x) > len(y) {\n        return x;\n    }\n    return y;\n}\n\n\
+fn run() -> int {\n    let total: int = 0;\n    let kept: list<str> = [];\n    for i in range(50) {\n        let s: str = grow(str(i));\n        let c: str = s;\n        kept.push(c);\n        let d: str = pick(c, grow(c));\n        total = total + len(c) + len(d);\n    }\n    return total * 100 + len(kept);\n}\n\n\
+return run();\n";
+        let program = bolide_parser::parse_source(src).unwrap();
+        let mut plain = JitCompiler::new();
+        plain.set_no_opt(true);
+        let plain_fn: fn() -> i64 = unsafe { std::mem::transmute(plain.compile(&program).unwrap()) };
+        let main_fn: fn() -> i64 = unsafe { std::mem::transmute(JitCompiler::new().compile(&program).unwrap()) };
+        let expected = plain_fn();
+        assert_eq!(main_fn(), expected);
+
+        // 第一次运行驻留字符串字面量，第二次运行才统计
+        let (alloc_before, free_before) = bolide_runtime::string_alloc_stats();
+        assert_eq!(main_fn(), expected);
+        let (alloc_after, free_after) = bolide_runtime::string_alloc_stats();
+        assert_eq!(alloc_after - alloc_before, free_after - free_before);
+    }
+}
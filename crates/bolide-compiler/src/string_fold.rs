@@ -0,0 +1,283 @@
+//! 编译期折叠只由字面量组成的字符串表达式
+//!
+//! `"a" + "b"`、`str(42)` / `str(1.5)` / `str(true)`、字面量之间的字符串比较，以及每一段都是
+//! 字面量的 f-string，在编译前换成结果字面量：生成的代码直接取驻留的字符串，运行时不做拼接或转换，
+//! 相同的结果只占一个数据对象。由内向外折叠，`"a" + "b" + str(1)` 整体成为 `"ab1"`；
+//! `"a" + x + "b"` 按左结合只有部分是字面量，不折叠。float 的文本用运行时的 `format_float`，
+//! 与运行时转换的结果一致。`--no-opt` 时不调用。
+
+use bolide_parser::{
+    AsyncSelectBranch, BinOp, ClassDef, Expr, FuncDef, Program, SelectBranch, Statement,
+};
+
+use crate::comptime::Const;
+
+/// 折叠整个程序（含函数体、方法、测试块、参数和字段的默认值）
+pub(crate) fn fold_program(program: &mut Program) {
+    // 用户定义了同名函数时 `str(...)` 不是内置转换
+    let fold_str_calls = !program.statements.iter()
+        .any(|stmt| matches!(stmt, Statement::FuncDef(func) if func.name == "str"));
+    let folder = Folder { fold_str_calls };
+    folder.body(&mut program.statements);
+}
+
+struct Folder {
+    fold_str_calls: bool,
+}
+
+impl Folder {
+    fn body(&self, body: &mut [Statement]) {
+        for stmt in body {
+            self.stmt(stmt);
+        }
+    }
+
+    fn func(&self, func: &mut FuncDef) {
+        for param in &mut func.params {
+            if let Some(default) = &mut param.default {
+                self.expr(default);
+            }
+        }
+        self.body(&mut func.body);
+    }
+
+    fn class(&self, class: &mut ClassDef) {
+        for field in &mut class.fields {
+            if let Some(default) = &mut field.default_value {
+                self.expr(default);
+            }
+        }
+        for method in &mut class.methods {
+            self.func(method);
+        }
+    }
+
+    fn stmt(&self, stmt: &mut Statement) {
+        match stmt {
+            Statement::VarDecl(decl) => {
+                if let Some(value) = &mut decl.value {
+                    self.expr(value);
+                }
+            }
+            Statement::Assign(assign) => {
+                self.expr(&mut assign.target);
+                self.expr(&mut assign.value);
+            }
+            Statement::Destructure(d) => self.expr(&mut d.value),
+            Statement::FuncDef(func) => self.func(func),
+            Statement::ClassDef(class) => self.class(class),
+            Statement::If(s) => {
+                self.expr(&mut s.condition);
+                self.body(&mut s.then_body);
+                for (cond, body) in &mut s.elif_branches {
+                    self.expr(cond);
+                    self.body(body);
+                }
+                if let Some(body) = &mut s.else_body {
+                    self.body(body);
+                }
+            }
+            Statement::While(s) => {
+                self.expr(&mut s.condition);
+                self.body(&mut s.body);
+            }
+            Statement::For(s) => {
+                self.expr(&mut s.iter);
+                self.body(&mut s.body);
+            }
+            Statement::Pool(s) => {
+                self.expr(&mut s.size);
+                self.body(&mut s.body);
+            }
+            Statement::AwaitScope(s) => self.body(&mut s.body),
+            Statement::Measure(s) => self.body(&mut s.body),
+            Statement::Match(s) => {
+                self.expr(&mut s.subject);
+                for arm in &mut s.arms {
+                    self.body(&mut arm.body);
+                }
+                if let Some(body) = &mut s.default {
+                    self.body(body);
+                }
+            }
+            Statement::Select(s) => {
+                if let Some(channels) = &mut s.channels {
+                    self.expr(channels);
+                }
+                for branch in &mut s.branches {
+                    match branch {
+                        SelectBranch::Recv { body, .. } | SelectBranch::RecvAny { body, .. } | SelectBranch::Closed { body }
+                        | SelectBranch::Default { body } => self.body(body),
+                        SelectBranch::Timeout { duration, body } => {
+                            self.expr(duration);
+                            self.body(body);
+                        }
+                    }
+                }
+            }
+            Statement::AsyncSelect(s) => {
+                for branch in &mut s.branches {
+                    match branch {
+                        AsyncSelectBranch::Bind { expr, body, .. } | AsyncSelectBranch::Expr { expr, body } => {
+                            self.expr(expr);
+                            self.body(body);
+                        }
+                    }
+                }
+            }
+            Statement::Send(s) => self.expr(&mut s.value),
+            Statement::Test(test) => self.body(&mut test.body),
+            Statement::Return(Some(value)) | Statement::Expr(value) => self.expr(value),
+            _ => {}
+        }
+    }
+
+    /// 先折叠子表达式，再看自身能否折叠
+    fn expr(&self, expr: &mut Expr) {
+        match expr {
+            Expr::BinOp(l, _, r) | Expr::Index(l, r) => {
+                self.expr(l);
+                self.expr(r);
+            }
+            Expr::CompareChain(first, rest) => {
+                self.expr(first);
+                for (_, e) in rest {
+                    self.expr(e);
+                }
+            }
+            Expr::UnaryOp(_, e) | Expr::Member(e, _) | Expr::Await(e) | Expr::NamedArg(_, e) => self.expr(e),
+            Expr::Slice(base, start, end) => {
+                self.expr(base);
+                for bound in [start, end].into_iter().flatten() {
+                    self.expr(bound);
+                }
+            }
+            Expr::Call(callee, args) => {
+                self.expr(callee);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::List(items) | Expr::Tuple(items) | Expr::FString(items) | Expr::AwaitAll(items) | Expr::Spawn(_, items) => {
+                for item in items {
+                    self.expr(item);
+                }
+            }
+            Expr::Dict(entries) => {
+                for (k, v) in entries {
+                    self.expr(k);
+                    self.expr(v);
+                }
+            }
+            Expr::Lambda(_, _, body) => self.body(body),
+            _ => {}
+        }
+        if let Some(folded) = self.fold(expr) {
+            *expr = folded;
+        }
+    }
+
+    fn fold(&self, expr: &Expr) -> Option<Expr> {
+        match expr {
+            Expr::BinOp(l, op, r) => {
+                let (Expr::String(a), Expr::String(b)) = (l.as_ref(), r.as_ref()) else {
+                    return None;
+                };
+                match op {
+                    BinOp::Add => Some(Expr::String(format!("{}{}", a, b))),
+                    BinOp::Eq => Some(Expr::Bool(a == b)),
+                    BinOp::Ne => Some(Expr::Bool(a != b)),
+                    // 与 string_cmp 相同的字典序
+                    BinOp::Lt => Some(Expr::Bool(a < b)),
+                    BinOp::Le => Some(Expr::Bool(a <= b)),
+                    BinOp::Gt => Some(Expr::Bool(a > b)),
+                    BinOp::Ge => Some(Expr::Bool(a >= b)),
+                    _ => None,
+                }
+            }
+            Expr::Call(callee, args) if self.fold_str_calls && args.len() == 1
+                && matches!(callee.as_ref(), Expr::Ident(name) if name == "str") => {
+                constant_text(&args[0]).map(Expr::String)
+            }
+            Expr::FString(parts) => {
+                let mut text = String::new();
+                for part in parts {
+                    text.push_str(&constant_text(part)?);
+                }
+                Some(Expr::String(text))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// 字面量转成字符串的结果，与运行时的 `string_from_*` / `string_builder_append_*` 相同
+fn constant_text(expr: &Expr) -> Option<String> {
+    if let Expr::String(text) = expr {
+        return Some(text.clone());
+    }
+    match Const::from_literal(expr)? {
+        Const::Int(n) => Some(n.to_string()),
+        Const::Float(f) => Some(bolide_runtime::format_float(f)),
+        Const::Bool(b) => Some(if b { "true" } else { "false" }.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folded_decls(source: &str) -> Vec<Expr> {
+        let mut program = bolide_parser::parse_source(source).unwrap();
+        fold_program(&mut program);
+        program.statements.into_iter()
+            .filter_map(|stmt| match stmt {
+                Statement::VarDecl(decl) => decl.value,
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn string_of(expr: &Expr) -> &str {
+        match expr {
+            Expr::String(text) => text,
+            other => panic!("not folded: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_literal_strings_fold() {
+        let values = folded_decls("let a: str = \"prefix_\" + \"name\" + str(42);\n\
+            let b: str = f\"{1}-{\"x\"}-{true}\";\n\
+            let c: bool = \"abc\" < \"abd\";\n\
+            let d: bool = \"a\" + \"b\" == \"ab\";\n\
+            let e: str = str(-7);\n\
+            let x: str = \"x\";\n\
+            let f: str = \"a\" + x + \"b\";\n\
+            let g: str = f\"{x}!\";\n");
+        assert_eq!(string_of(&values[0]), "prefix_name42");
+        assert_eq!(string_of(&values[1]), "1-x-true");
+        assert!(matches!(values[2], Expr::Bool(true)));
+        assert!(matches!(values[3], Expr::Bool(true)));
+        assert_eq!(string_of(&values[4]), "-7");
+        // 有变量参与的部分不折叠
+        assert!(matches!(values[6], Expr::BinOp(..)));
+        assert!(matches!(values[7], Expr::FString(..)));
+    }
+
+    #[test]
+    fn test_user_str_function_not_folded() {
+        let values = folded_decls("fn str(x: int) -> str {\n    return \"n\";\n}\nlet a: str = str(1);\n");
+        assert!(matches!(values[0], Expr::Call(..)));
+    }
+
+    #[test]
+    fn test_float_text_matches_runtime() {
+        for value in [-0.0, 1e300, 0.1, 1.0, -2.5, 1e-7, 123456789.125, f64::MIN_POSITIVE] {
+            let folded = constant_text(&Expr::Float(value)).unwrap();
+            let runtime = bolide_runtime::bolide_string_from_float(value);
+            assert_eq!(folded, unsafe { (*runtime).as_str() }, "{}", value);
+            bolide_runtime::bolide_string_release(runtime);
+        }
+    }
+}
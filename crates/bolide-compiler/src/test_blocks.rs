@@ -0,0 +1,80 @@
+//! 测试块: test "name" { ... }
+//!
+//! `bolide run` 和 AOT 编译去掉全部测试块（`without_tests`）。`bolide test` 把每个测试块单独
+//! 组成一个程序（`test_programs`）：文件里的定义（函数、类、extern 块、import）和顶层变量声明
+//! 原样保留，测试块的语句作为顶层代码，其余顶层语句不执行。每个程序用新的编译器编译运行，
+//! 测试之间不共享全局变量。
+
+use bolide_parser::{Program, Statement};
+
+/// 一个测试块组成的程序
+#[derive(Debug, Clone)]
+pub struct TestCase {
+    pub name: String,
+    /// 测试块所在的行（从 1 开始）
+    pub line: usize,
+    pub program: Program,
+}
+
+/// 去掉顶层的测试块
+pub(crate) fn without_tests(program: &Program) -> Program {
+    let statements = program.statements.iter()
+        .filter(|stmt| !matches!(stmt, Statement::Test(_)))
+        .cloned()
+        .collect();
+    Program { statements }
+}
+
+/// 按出现顺序为每个测试块生成独立的程序
+pub fn test_programs(program: &Program) -> Vec<TestCase> {
+    let definitions: Vec<Statement> = program.statements.iter()
+        .filter(|stmt| matches!(stmt,
+            Statement::FuncDef(_) | Statement::ClassDef(_) | Statement::ExternBlock(_)
+            | Statement::Import(_) | Statement::VarDecl(_)))
+        .cloned()
+        .collect();
+    program.statements.iter()
+        .filter_map(|stmt| match stmt {
+            Statement::Test(test) => {
+                let mut statements = definitions.clone();
+                statements.extend(test.body.iter().cloned());
+                Some(TestCase { name: test.name.clone(), line: test.line, program: Program { statements } })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_programs_keep_definitions_only() {
+        let source = r#"
+let base: int = 10;
+fn add(a: int, b: int) -> int {
+    return a + b;
+}
+print("top level");
+test "adds" {
+    assert_eq(add(base, 1), 11);
+}
+test "second" {
+    assert(true);
+    print("x");
+}
+"#;
+        let program = bolide_parser::parse_source(source).unwrap();
+        assert_eq!(without_tests(&program).statements.len(), 3);
+
+        let cases = test_programs(&program);
+        let summary: Vec<(&str, usize, usize)> = cases.iter()
+            .map(|case| (case.name.as_str(), case.line, case.program.statements.len()))
+            .collect();
+        // 顶层的 print 不进入测试程序
+        assert_eq!(summary, [("adds", 7, 3), ("second", 10, 4)]);
+        assert!(matches!(cases[0].program.statements[0], Statement::VarDecl(_)));
+        assert!(matches!(cases[0].program.statements[2], Statement::Expr(_)));
+    }
+}
@@ -11,6 +11,7 @@ pub struct Program {
 pub enum Statement {
     VarDecl(VarDecl),
     Assign(Assign),
+    Destructure(Destructure),
     FuncDef(FuncDef),
     ClassDef(ClassDef),
     If(IfStmt),
@@ -19,18 +20,28 @@ pub enum Statement {
     Pool(PoolStmt),
     Select(SelectStmt),
     AwaitScope(AwaitScopeStmt),
+    Measure(MeasureStmt),
+    Match(MatchStmt),
     AsyncSelect(AsyncSelectStmt),
     Send(SendStmt),
     Return(Option<Expr>),
+    /// 跳出最内层 while/for 循环
+    Break,
+    /// 进入最内层 while/for 循环的下一次迭代
+    Continue,
     Expr(Expr),
     Import(Import),
     ExternBlock(ExternBlock),
+    /// 顶层测试块，`bolide run` 跳过
+    Test(TestBlock),
 }
 
 /// 赋值语句
 #[derive(Debug, Clone)]
 pub struct Assign {
     pub target: Expr,  // 可以是 Ident 或 Member
+    /// 复合赋值 `target op= value` 的运算符，普通赋值为 None
+    pub op: Option<BinOp>,
     pub value: Expr,
 }
 
@@ -40,6 +51,19 @@ pub struct VarDecl {
     pub name: String,
     pub ty: Option<Type>,
     pub value: Option<Expr>,
+    /// `threadlocal let`：每个线程一份的全局变量（只出现在顶层）
+    pub thread_local: bool,
+}
+
+/// 解构语句: let a, b = expr; 或 a, b = expr;
+#[derive(Debug, Clone)]
+pub struct Destructure {
+    /// 目标变量，`_` 为 None（丢弃该槽位）
+    pub targets: Vec<Option<String>>,
+    /// 右侧写多个表达式时为元组字面量
+    pub value: Expr,
+    /// let 声明新变量；否则赋值给已有变量
+    pub declare: bool,
 }
 
 /// 函数定义
@@ -53,6 +77,23 @@ pub struct FuncDef {
     /// 当指定时，跳过 ARC 并执行生命周期检查
     pub lifetime_deps: Option<Vec<String>>,
     pub body: Vec<Statement>,
+    /// `@pure`：声明没有副作用，编译器验证后才据此优化
+    pub pure: bool,
+    /// `@trace` / `@trace(ret)`：调用时打印函数名和参数（以及返回值）
+    pub trace: Option<TraceMode>,
+    /// `@hot_entry`：`bolide run --hot` 反复调用的入口函数（不标注时是名为 `tick` 的函数）
+    pub hot_entry: bool,
+    /// 定义所在的行（从 1 开始）；编译器生成的函数为 0
+    pub line: usize,
+}
+
+/// `@trace` 记录的内容
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceMode {
+    /// `@trace`：每次调用打印函数名和参数
+    Calls,
+    /// `@trace(ret)`：还在每条返回路径上打印返回值
+    Returns,
 }
 
 /// 参数传递模式
@@ -62,7 +103,22 @@ pub enum ParamMode {
     Borrow,
     /// 接收所有权：传递裸指针，调用者置空本地变量
     Owned,
-    /// 引用修改：传递指针的地址 (Object**)
+    /// 引用修改：传递调用者变量的地址 (Object**)
+    ///
+    /// 所有权约定（RC 类型，如 `ref xs: list<int>`、`ref s: str`）：
+    ///
+    /// - 调用者变量所在的槽位在调用前后都恰好持有值的一个引用，调用本身不改变引用计数；
+    ///   调用者在调用后从槽位读回当前值，不释放旧值。
+    /// - 原地修改（`xs.push(4)`）：槽位不变，没有 RC 操作。
+    /// - 替换（`xs = [1, 2]`）：新值被槽位拥有，旧值立即释放——第一次替换释放的就是
+    ///   调用者原来的对象；多次替换时每个中间值各释放一次。
+    /// - 每次赋值都立即写入槽位（store-through），所以替换后提前 `return`、从循环里返回
+    ///   都不需要额外的写回；被调用者读取同一个全局变量看到的也是新值。
+    /// - 被调用者因运行时错误中止时进程直接退出，槽位里总是最后一次赋值的值，不会重复释放。
+    /// - `return xs` 返回新的引用（retain 一次），槽位仍持有原来那个引用。
+    /// - 实参必须是拥有其值的变量：局部变量、全局变量或外层的 ref 参数；借用参数
+    ///   不能再按 ref 传递，ref 参数不能移交给 owned 参数，同一变量不能在一次调用中
+    ///   既按 ref 传递又作为其他实参。
     Ref,
 }
 
@@ -72,6 +128,8 @@ pub struct Param {
     pub name: String,
     pub ty: Type,
     pub mode: ParamMode,
+    /// 默认值，调用时省略该参数则在调用处求值
+    pub default: Option<Expr>,
 }
 
 /// 类定义
@@ -111,6 +169,8 @@ pub struct WhileStmt {
 #[derive(Debug, Clone)]
 pub struct ForStmt {
     pub vars: Vec<String>,
+    /// 循环变量的类型标注：`for t: float in range(...)`
+    pub var_type: Option<Type>,
     pub iter: Expr,
     pub body: Vec<Statement>,
 }
@@ -122,9 +182,11 @@ pub struct PoolStmt {
     pub body: Vec<Statement>,
 }
 
-/// Select 语句: select { x <- ch => { ... } }
+/// Select 语句: select { x <- ch => { ... } }，或 select from chans { x, i => { ... } }
 #[derive(Debug, Clone)]
 pub struct SelectStmt {
+    /// 动态形式等待的 list<channel<T>>；静态形式为 None
+    pub channels: Option<Expr>,
     pub branches: Vec<SelectBranch>,
 }
 
@@ -137,7 +199,17 @@ pub enum SelectBranch {
         channel: String,
         body: Vec<Statement>,
     },
-    /// 超时分支: timeout(ms) => { body }
+    /// 动态形式的接收分支: var, index => { body }，index 是收到值的 channel 在列表中的位置
+    RecvAny {
+        var: String,
+        index: String,
+        body: Vec<Statement>,
+    },
+    /// 动态形式中所有 channel 都已关闭（或列表为空）: closed => { body }
+    Closed {
+        body: Vec<Statement>,
+    },
+    /// 超时分支: timeout(d) => { body }，d 为 duration 或 deadline
     Timeout {
         duration: Expr,
         body: Vec<Statement>,
@@ -148,12 +220,56 @@ pub enum SelectBranch {
     },
 }
 
-/// await scope 语句: await scope { ... }
+/// await scope 语句: await scope { ... } / await scope fail_fast { ... }
 #[derive(Debug, Clone)]
 pub struct AwaitScopeStmt {
+    /// 第一个失败的子任务取消其他子任务
+    pub fail_fast: bool,
+    pub body: Vec<Statement>,
+}
+
+/// 计时块: measure "label" { ... }
+#[derive(Debug, Clone)]
+pub struct MeasureStmt {
+    pub label: String,
     pub body: Vec<Statement>,
 }
 
+/// 测试块: test "name" { ... }
+///
+/// 只出现在顶层。`bolide run` 和 AOT 编译跳过测试块；`bolide test` 把每个测试块
+/// 单独编译运行（见编译器的 `test_programs`）。
+#[derive(Debug, Clone)]
+pub struct TestBlock {
+    pub name: String,
+    pub body: Vec<Statement>,
+    /// 所在的行（从 1 开始）
+    pub line: usize,
+}
+
+/// 分支语句: match x { 1 => { ... } 2, 3 => { ... } _ => { ... } }
+#[derive(Debug, Clone)]
+pub struct MatchStmt {
+    pub subject: Expr,
+    pub arms: Vec<MatchArm>,
+    /// `_` 分支；没有时不匹配任何分支就什么都不执行
+    pub default: Option<Vec<Statement>>,
+}
+
+/// match 分支: 一个或多个字面量模式共用一个代码块
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub patterns: Vec<MatchPattern>,
+    pub body: Vec<Statement>,
+}
+
+/// match 模式（同一个 match 中只能全是整数或全是字符串）
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchPattern {
+    Int(i64),
+    Str(String),
+}
+
 /// 协程 select 语句
 #[derive(Debug, Clone)]
 pub struct AsyncSelectStmt {
@@ -200,17 +316,27 @@ pub enum Expr {
     String(String),
     BigInt(String),     // 存储原始字符串以支持任意大数
     Decimal(String),    // 存储原始字符串以支持任意精度
+    /// duration 字面量（`100ms`、`1.5s`），已换算成纳秒
+    Duration(i64),
     Ident(String),
     BinOp(Box<Expr>, BinOp, Box<Expr>),
+    /// 链式比较: a < b <= c，等价于 a < b and b <= c，每个操作数只求值一次，短路
+    CompareChain(Box<Expr>, Vec<(BinOp, Expr)>),
     UnaryOp(UnaryOp, Box<Expr>),
     Call(Box<Expr>, Vec<Expr>),
+    /// 关键字实参 `name: value`，只出现在调用的实参列表里
+    NamedArg(String, Box<Expr>),
     Index(Box<Expr>, Box<Expr>),
+    /// 切片: base[start:end]，省略的边界为 None
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
     Member(Box<Expr>, String),
     List(Vec<Expr>),
     /// 字典字面量: {key: value, ...}
     Dict(Vec<(Expr, Expr)>),
     /// spawn func(args) - 在新线程执行函数
     Spawn(String, Vec<Expr>),
+    /// 匿名函数 fn(params) -> ret { body }：编译时提升为命名函数，不能捕获外层的局部变量
+    Lambda(Vec<Param>, Option<Type>, Vec<Statement>),
     /// <- ch - 从通道接收
     Recv(String),
     /// await expr - 等待异步结果
@@ -219,6 +345,8 @@ pub enum Expr {
     AwaitAll(Vec<Expr>),
     /// 元组字面量: (expr, expr, ...)
     Tuple(Vec<Expr>),
+    /// f-string: f"x = {x}"，按顺序保存字面量片段（Expr::String）和嵌入的表达式
+    FString(Vec<Expr>),
     None,
 }
 
@@ -228,6 +356,8 @@ pub enum BinOp {
     Add, Sub, Mul, Div, Mod,
     Eq, Ne, Lt, Le, Gt, Ge,
     And, Or,
+    /// 引用同一性: a is b / a is not b
+    Is, IsNot,
 }
 
 /// 一元运算符
@@ -253,10 +383,15 @@ pub enum Type {
     FuncSig(Vec<Type>, Option<Box<Type>>),  // 带签名的函数类型: func(params) -> return_type
     List(Box<Type>),
     Dict(Box<Type>, Box<Type>),  // dict<K, V>
+    WeakDict(Box<Type>, Box<Type>),  // weak_dict<K, V>：K 是类，只持有键的弱引用
     Tuple(Vec<Type>),  // 元组类型: (T1, T2, ...)
     Custom(String),
     Weak(Box<Type>),    // 弱引用: weak T
     Unowned(Box<Type>), // 无主引用: unowned T
+    PackedList(Box<Type>),  // 内联存储的类实例列表: list<packed T>
+    PackedRef(Box<Type>),   // packed 列表元素的借用引用（ps[i] 的类型，只由编译器推导）
+    Duration,  // 时间长度，内部是 i64 纳秒
+    Deadline,  // 单调时钟上的时间点（`deadline(d)`），内部是 i64 纳秒
 }
 
 /// FFI extern 块
@@ -0,0 +1,148 @@
+//! 条件编译
+//!
+//! 顶层语句（函数、extern 块、类、普通语句）前可以写 `@cfg(...)`，条件对编译目标求值
+//! （JIT 是本机，AOT 是 `--target` 指定的三元组），不成立的语句在转换阶段直接丢弃，
+//! 不会进入编译器：被排除的配置里引用了不存在的函数或库也不报错。
+//!
+//! - `@cfg(windows)` / `@cfg(unix)`：平台族
+//! - `@cfg(linux)` / `@cfg(macos)`：操作系统
+//! - `@cfg(target = "x86_64")`：架构
+//! - `@cfg(os = "freebsd")`：任意操作系统名（取值同 Rust 的 `std::env::consts::OS`）
+//!
+//! 同一语句前的多个 `@cfg` 须全部成立。拼错的条件是错误而不是恒假。
+
+use std::str::FromStr;
+use target_lexicon::{OperatingSystem, Triple};
+
+/// 合法的条件，用于错误信息
+pub const VALID_CFG_KEYS: &str = "windows, unix, linux, macos, target = \"<arch>\", os = \"<os>\"";
+
+/// 条件编译的目标平台
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CfgTarget {
+    /// 操作系统："windows"、"linux"、"macos"、"freebsd" ...
+    pub os: String,
+    /// 平台族："windows" 或 "unix"，裸机等目标为空
+    pub family: String,
+    /// 架构："x86_64"、"aarch64" ...
+    pub arch: String,
+}
+
+impl CfgTarget {
+    /// 本机（JIT 的目标）
+    pub fn host() -> Self {
+        Self {
+            os: std::env::consts::OS.to_string(),
+            family: std::env::consts::FAMILY.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+        }
+    }
+
+    /// 由目标三元组得到，如 `x86_64-pc-windows-msvc`、`aarch64-apple-darwin`
+    pub fn from_triple(triple: &str) -> Result<Self, String> {
+        let parsed = Triple::from_str(triple)
+            .map_err(|e| format!("Invalid target '{}': {}", triple, e))?;
+        let os = match parsed.operating_system {
+            OperatingSystem::Darwin(_) | OperatingSystem::MacOSX(_) => "macos".to_string(),
+            OperatingSystem::Unknown | OperatingSystem::None_ => "none".to_string(),
+            other => other.to_string(),
+        };
+        let family = match os.as_str() {
+            "windows" => "windows",
+            "none" | "uefi" | "wasi" | "wasip1" | "wasip2" | "cuda" | "amdhsa" => "",
+            _ => "unix",
+        };
+        Ok(Self {
+            os,
+            family: family.to_string(),
+            arch: parsed.architecture.to_string(),
+        })
+    }
+
+    /// 单个条件 `key` 或 `key = "value"` 是否成立
+    pub fn matches(&self, key: &str, value: Option<&str>) -> Result<bool, String> {
+        match (key, value) {
+            ("windows" | "unix", None) => Ok(self.family == key),
+            ("linux" | "macos", None) => Ok(self.os == key),
+            ("target", Some(arch)) => Ok(self.arch == arch),
+            ("os", Some(os)) => Ok(self.os == os),
+            ("windows" | "unix" | "linux" | "macos", Some(_)) => {
+                Err(format!("cfg key '{}' takes no value", key))
+            }
+            ("target" | "os", None) => {
+                Err(format!("cfg key '{}' needs a value, e.g. @cfg({} = \"...\")", key, key))
+            }
+            _ => Err(format!("Unknown cfg key '{}' (valid keys: {})", key, VALID_CFG_KEYS)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_triple() {
+        let windows = CfgTarget::from_triple("x86_64-pc-windows-msvc").unwrap();
+        assert_eq!((windows.os.as_str(), windows.family.as_str(), windows.arch.as_str()), ("windows", "windows", "x86_64"));
+
+        let mac = CfgTarget::from_triple("aarch64-apple-darwin").unwrap();
+        assert_eq!((mac.os.as_str(), mac.family.as_str(), mac.arch.as_str()), ("macos", "unix", "aarch64"));
+
+        let linux = CfgTarget::from_triple("x86_64-unknown-linux-gnu").unwrap();
+        assert!(linux.matches("unix", None).unwrap());
+        assert!(linux.matches("linux", None).unwrap());
+        assert!(!linux.matches("windows", None).unwrap());
+        assert!(linux.matches("target", Some("x86_64")).unwrap());
+        assert!(!linux.matches("os", Some("macos")).unwrap());
+
+        assert!(CfgTarget::from_triple("not-a-triple").is_err());
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let host = CfgTarget::host();
+        let err = host.matches("widows", None).unwrap_err();
+        assert!(err.contains("'widows'") && err.contains(VALID_CFG_KEYS), "{}", err);
+        assert!(host.matches("target", None).is_err());
+        assert!(host.matches("unix", Some("yes")).is_err());
+    }
+
+    #[test]
+    fn test_filter_statements() {
+        let source = r#"
+@cfg(windows)
+extern "kernel32.dll" {
+    fn GetTickCount() -> u32;
+}
+@cfg(unix)
+extern "libc.so.6" {
+    fn getpid() -> c_int;
+}
+@cfg(windows)
+fn sep() -> str { return "\\"; }
+@cfg(unix)
+@cfg(target = "x86_64")
+fn sep() -> str { return "/"; }
+print(sep());
+"#;
+        let names = |triple: &str| -> Vec<String> {
+            let target = CfgTarget::from_triple(triple).unwrap();
+            crate::parse_for_target(source, &target).unwrap().statements.iter().map(|s| match s {
+                crate::Statement::ExternBlock(block) => block.lib_path.clone(),
+                crate::Statement::FuncDef(f) => f.name.clone(),
+                _ => "stmt".to_string(),
+            }).collect()
+        };
+        assert_eq!(names("x86_64-pc-windows-msvc"), ["kernel32.dll", "sep", "stmt"]);
+        assert_eq!(names("x86_64-unknown-linux-gnu"), ["libc.so.6", "sep", "stmt"]);
+        // 两个 @cfg 都要成立
+        assert_eq!(names("aarch64-unknown-linux-gnu"), ["libc.so.6", "stmt"]);
+
+        // 拼错的条件在不会被选中的语句上也报错，并给出行号
+        let err = crate::parse("@cfg(widows)\nfn f() {}\n").unwrap_err();
+        assert!(err.line == 1 && err.message.contains(VALID_CFG_KEYS), "{}", err);
+        let err = crate::parse("@inline\nfn f() {}\n").unwrap_err();
+        assert!(err.message.contains("Unknown attribute '@inline'"), "{}", err);
+    }
+}
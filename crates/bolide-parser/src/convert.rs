@@ -4,30 +4,203 @@ use pest::Parser;
 use pest::iterators::Pair;
 use crate::{BolideParser, Rule};
 use crate::ast::*;
+use crate::cfg::{CfgTarget, VALID_CFG_KEYS};
+use crate::error::ParseError;
+use crate::recover::{self, MAX_PARSE_ERRORS};
 
-/// 解析源代码为 AST
-pub fn parse(source: &str) -> Result<Program, String> {
-    let pairs = BolideParser::parse(Rule::program, source)
-        .map_err(|e| format!("Parse error: {}", e))?;
+/// 解析源代码为 AST，`@cfg` 对本机求值
+pub fn parse(source: &str) -> Result<Program, Box<ParseError>> {
+    parse_for_target(source, &CfgTarget::host())
+}
+
+/// 解析源代码为 AST，`@cfg` 条件不成立的顶层语句被丢弃
+///
+/// 有多处错误时第一处之外的放在 `ParseError::related` 里（见 `parse_collecting_errors`）。
+pub fn parse_for_target(source: &str, target: &CfgTarget) -> Result<Program, Box<ParseError>> {
+    parse_collecting_errors(source, target).map_err(|errors| Box::new(ParseError::combine(errors)))
+}
+
+/// 解析源代码为 AST，出错时返回全部错误（最多 `MAX_PARSE_ERRORS` 个）
+///
+/// 语法错误不会在第一处停下：按顶层语句逐条重新解析，跳过出错的语句继续（见 `recover`）；
+/// 各条语句的转换错误（未知属性等）同样逐条收集。有任何错误都不返回 AST。
+pub fn parse_collecting_errors(source: &str, target: &CfgTarget) -> Result<Program, Vec<ParseError>> {
+    let pairs = match BolideParser::parse(Rule::program, source) {
+        Ok(pairs) => pairs,
+        Err(e) => {
+            let mut errors = recover::collect_errors(source, target);
+            if errors.is_empty() {
+                errors.push(ParseError::from_pest(source, &e));
+            }
+            return Err(errors);
+        }
+    };
 
     let mut statements = Vec::new();
+    let mut errors = Vec::new();
     for pair in pairs {
         if pair.as_rule() == Rule::program {
             for inner in pair.into_inner() {
-                match inner.as_rule() {
-                    Rule::statement => {
-                        if let Some(stmt) = parse_statement(inner)? {
-                            statements.push(stmt);
-                        }
-                    }
-                    Rule::EOI => {}
-                    _ => {}
+                if !matches!(inner.as_rule(), Rule::statement | Rule::attributed_stmt | Rule::test_block | Rule::threadlocal_decl) {
+                    continue;
+                }
+                match convert_item(inner, target) {
+                    Ok(Some(stmt)) => statements.push(stmt),
+                    Ok(None) => {}
+                    Err(e) if errors.len() < MAX_PARSE_ERRORS => errors.push(ParseError::from_message(source, &e)),
+                    Err(_) => break,
                 }
             }
         }
     }
 
-    Ok(Program { statements })
+    if errors.is_empty() {
+        Ok(Program { statements })
+    } else {
+        Err(errors)
+    }
+}
+
+/// 转换一条顶层语句（`statement`、`attributed_stmt`、`threadlocal_decl` 或 `test_block`）；`@cfg` 条件不成立时返回 None
+pub(crate) fn convert_item(pair: Pair<Rule>, target: &CfgTarget) -> Result<Option<Statement>, String> {
+    if pair.as_rule() == Rule::test_block {
+        return Ok(Some(Statement::Test(parse_test_block(pair)?)));
+    }
+    if pair.as_rule() == Rule::threadlocal_decl {
+        return parse_threadlocal_decl(pair).map(Some);
+    }
+    if pair.as_rule() != Rule::attributed_stmt {
+        return parse_statement(pair);
+    }
+    let mut parts = pair.into_inner();
+    let stmt = parts.next_back().unwrap();
+    let (line, _) = stmt.line_col();
+    let mut enabled = true;
+    let mut pure = false;
+    let mut trace = None;
+    let mut hot_entry = false;
+    for attr in parts {
+        match eval_attribute(attr, target)? {
+            Attribute::Cfg(matched) => enabled &= matched,
+            Attribute::Pure => pure = true,
+            Attribute::Trace(mode) => trace = Some(mode),
+            Attribute::HotEntry => hot_entry = true,
+        }
+    }
+    if !enabled {
+        return Ok(None);
+    }
+    if pure && trace.is_some() {
+        return Err(format!("line {}: @pure and @trace cannot be combined: tracing prints on every call", line));
+    }
+    let mut stmt = match stmt.as_rule() {
+        Rule::threadlocal_decl => Some(parse_threadlocal_decl(stmt)?),
+        _ => parse_statement(stmt)?,
+    };
+    if let (true, Some(stmt)) = (pure, stmt.as_mut()) {
+        mark_pure(stmt, line)?;
+    }
+    if let (Some(mode), Some(stmt)) = (trace, stmt.as_mut()) {
+        match stmt {
+            Statement::FuncDef(func) => func.trace = Some(mode),
+            _ => return Err(format!("line {}: @trace only applies to function definitions", line)),
+        }
+    }
+    if let (true, Some(stmt)) = (hot_entry, stmt.as_mut()) {
+        match stmt {
+            Statement::FuncDef(func) if func.is_async => {
+                return Err(format!("line {}: @hot_entry cannot be applied to async function '{}'", line, func.name));
+            }
+            Statement::FuncDef(func) => func.hot_entry = true,
+            _ => return Err(format!("line {}: @hot_entry only applies to function definitions", line)),
+        }
+    }
+    Ok(stmt)
+}
+
+/// 语句前的属性
+enum Attribute {
+    /// `@cfg(...)`：条件是否对编译目标成立
+    Cfg(bool),
+    /// `@pure`：函数声明为无副作用，由编译器验证
+    Pure,
+    /// `@trace` / `@trace(ret)`：记录函数的调用
+    Trace(TraceMode),
+    /// `@hot_entry`：`bolide run --hot` 的入口函数
+    HotEntry,
+}
+
+/// 求值语句前的属性；所有条件都会检查，拼错的条件在任何目标上都报错
+fn eval_attribute(pair: Pair<Rule>, target: &CfgTarget) -> Result<Attribute, String> {
+    let (line, _) = pair.line_col();
+    let mut inner = pair.into_inner();
+    let name = inner.next().unwrap().as_str();
+    if name == "pure" {
+        if inner.next().is_some() {
+            return Err(format!("line {}: @pure takes no arguments", line));
+        }
+        return Ok(Attribute::Pure);
+    }
+    if name == "trace" {
+        return trace_mode(inner.next(), line).map(Attribute::Trace);
+    }
+    if name == "hot_entry" {
+        if inner.next().is_some() {
+            return Err(format!("line {}: @hot_entry takes no arguments", line));
+        }
+        return Ok(Attribute::HotEntry);
+    }
+    if name != "cfg" {
+        return Err(format!("line {}: Unknown attribute '@{}'", line, name));
+    }
+    let Some(arg) = inner.next() else {
+        return Err(format!("line {}: @cfg needs a condition (valid keys: {})", line, VALID_CFG_KEYS));
+    };
+    let mut parts = arg.into_inner();
+    let key = parts.next().unwrap().as_str();
+    let value = parts.next().map(|v| unescape_string(&v.as_str()[1..v.as_str().len() - 1]));
+    target.matches(key, value.as_deref())
+        .map(Attribute::Cfg)
+        .map_err(|e| format!("line {}: {}", line, e))
+}
+
+/// `@trace` 的参数：不写或 `ret`
+fn trace_mode(arg: Option<Pair<Rule>>, line: usize) -> Result<TraceMode, String> {
+    match arg.map(|a| a.as_str().to_string()) {
+        None => Ok(TraceMode::Calls),
+        Some(arg) if arg == "ret" => Ok(TraceMode::Returns),
+        Some(arg) => Err(format!("line {}: unknown @trace option '{}' (expected `@trace` or `@trace(ret)`)", line, arg)),
+    }
+}
+
+/// 方法定义前的属性：只支持 `@trace`
+fn parse_method_def(pair: Pair<Rule>) -> Result<FuncDef, String> {
+    let mut parts = pair.into_inner();
+    let mut func = parse_func_def(parts.next_back().unwrap())?;
+    for attr in parts {
+        let (line, _) = attr.line_col();
+        let mut inner = attr.into_inner();
+        let name = inner.next().unwrap().as_str();
+        if name != "trace" {
+            return Err(format!("line {}: @{} cannot be applied to methods; only @trace can", line, name));
+        }
+        func.trace = Some(trace_mode(inner.next(), line)?);
+    }
+    Ok(func)
+}
+
+/// 给 `@pure` 标注的函数定义打上标记（是否真的无副作用由编译器检查）
+fn mark_pure(stmt: &mut Statement, line: usize) -> Result<(), String> {
+    match stmt {
+        Statement::FuncDef(func) if func.is_async => {
+            Err(format!("line {}: @pure cannot be applied to async function '{}'", line, func.name))
+        }
+        Statement::FuncDef(func) => {
+            func.pure = true;
+            Ok(())
+        }
+        _ => Err(format!("line {}: @pure only applies to function definitions", line)),
+    }
 }
 
 fn parse_statement(pair: Pair<Rule>) -> Result<Option<Statement>, String> {
@@ -40,15 +213,21 @@ fn parse_statement(pair: Pair<Rule>) -> Result<Option<Statement>, String> {
         Rule::func_def => Ok(Some(Statement::FuncDef(parse_func_def(pair)?))),
         Rule::var_decl => Ok(Some(Statement::VarDecl(parse_var_decl(pair)?))),
         Rule::assign_stmt => Ok(Some(Statement::Assign(parse_assign(pair)?))),
+        Rule::destructure_decl => Ok(Some(Statement::Destructure(parse_destructure(pair, true)?))),
+        Rule::destructure_assign => Ok(Some(Statement::Destructure(parse_destructure(pair, false)?))),
         Rule::if_stmt => Ok(Some(Statement::If(parse_if_stmt(pair)?))),
         Rule::while_stmt => Ok(Some(Statement::While(parse_while_stmt(pair)?))),
         Rule::for_stmt => Ok(Some(Statement::For(parse_for_stmt(pair)?))),
         Rule::pool_stmt => Ok(Some(Statement::Pool(parse_pool_stmt(pair)?))),
         Rule::select_stmt => Ok(Some(Statement::Select(parse_select_stmt(pair)?))),
         Rule::await_scope_stmt => Ok(Some(Statement::AwaitScope(parse_await_scope_stmt(pair)?))),
+        Rule::measure_stmt => Ok(Some(Statement::Measure(parse_measure_stmt(pair)?))),
+        Rule::match_stmt => Ok(Some(Statement::Match(parse_match_stmt(pair)?))),
         Rule::async_select_stmt => Ok(Some(Statement::AsyncSelect(parse_async_select_stmt(pair)?))),
         Rule::send_stmt => Ok(Some(Statement::Send(parse_send_stmt(pair)?))),
         Rule::return_stmt => Ok(Some(parse_return_stmt(pair)?)),
+        Rule::break_stmt => Ok(Some(Statement::Break)),
+        Rule::continue_stmt => Ok(Some(Statement::Continue)),
         Rule::expr_stmt => Ok(Some(Statement::Expr(parse_expr_stmt(pair)?))),
         Rule::import_stmt => Ok(Some(Statement::Import(parse_import(pair)?))),
         Rule::class_def => Ok(Some(Statement::ClassDef(parse_class_def(pair)?))),
@@ -62,8 +241,43 @@ fn parse_assign(pair: Pair<Rule>) -> Result<Assign, String> {
     let mut inner = pair.into_inner();
     let target_pair = inner.next().unwrap();
     let target = parse_assign_target(target_pair)?;
+    let op = match inner.next().unwrap().as_str() {
+        "+=" => Some(BinOp::Add),
+        "-=" => Some(BinOp::Sub),
+        "*=" => Some(BinOp::Mul),
+        "/=" => Some(BinOp::Div),
+        "%=" => Some(BinOp::Mod),
+        _ => None,
+    };
     let value = parse_expr(inner.next().unwrap())?;
-    Ok(Assign { target, value })
+    Ok(Assign { target, op, value })
+}
+
+fn parse_destructure(pair: Pair<Rule>, declare: bool) -> Result<Destructure, String> {
+    let mut targets = Vec::new();
+    let mut values = Vec::new();
+    for item in pair.into_inner() {
+        match item.as_rule() {
+            Rule::ident if item.as_str() == "_" => targets.push(None),
+            Rule::ident => {
+                let name = item.as_str().to_string();
+                if targets.contains(&Some(name.clone())) {
+                    return Err(format!("Duplicate name '{}' in destructuring", name));
+                }
+                targets.push(Some(name));
+            }
+            Rule::expr => values.push(parse_expr(item)?),
+            _ => {}
+        }
+    }
+
+    // a, b = b, a: 右侧多个表达式先组成元组，全部求值后再赋值
+    let value = if values.len() == 1 {
+        values.pop().unwrap()
+    } else {
+        Expr::Tuple(values)
+    };
+    Ok(Destructure { targets, value, declare })
 }
 
 fn parse_assign_target(pair: Pair<Rule>) -> Result<Expr, String> {
@@ -98,6 +312,7 @@ fn parse_assign_target(pair: Pair<Rule>) -> Result<Expr, String> {
 
 
 fn parse_func_def(pair: Pair<Rule>) -> Result<FuncDef, String> {
+    let line = pair.as_span().start_pos().line_col().0;
     let mut inner = pair.into_inner();
     let mut is_async = false;
 
@@ -139,7 +354,7 @@ fn parse_func_def(pair: Pair<Rule>) -> Result<FuncDef, String> {
         }
     }
 
-    Ok(FuncDef { name, is_async, params, return_type, lifetime_deps, body })
+    Ok(FuncDef { name, is_async, params, return_type, lifetime_deps, body, pure: false, trace: None, hot_entry: false, line })
 }
 
 fn parse_param(pair: Pair<Rule>) -> Result<Param, String> {
@@ -160,7 +375,8 @@ fn parse_param(pair: Pair<Rule>) -> Result<Param, String> {
     };
 
     let ty = parse_type(inner.next().unwrap())?;
-    Ok(Param { name, ty, mode })
+    let default = inner.next().map(parse_expr).transpose()?;
+    Ok(Param { name, ty, mode, default })
 }
 
 fn parse_type(pair: Pair<Rule>) -> Result<Type, String> {
@@ -184,8 +400,14 @@ fn parse_type(pair: Pair<Rule>) -> Result<Type, String> {
             Type::Tuple(types?)
         }
         Rule::list_type => {
-            let elem_type = parse_type(type_pair.into_inner().next().unwrap())?;
-            Type::List(Box::new(elem_type))
+            let mut inner = type_pair.into_inner();
+            let first = inner.next().unwrap();
+            if first.as_rule() == Rule::packed_mode {
+                let elem_type = parse_type(inner.next().unwrap())?;
+                Type::PackedList(Box::new(elem_type))
+            } else {
+                Type::List(Box::new(parse_type(first)?))
+            }
         }
         Rule::dict_type => {
             let mut inner = type_pair.into_inner();
@@ -193,6 +415,15 @@ fn parse_type(pair: Pair<Rule>) -> Result<Type, String> {
             let value_type = parse_type(inner.next().unwrap())?;
             Type::Dict(Box::new(key_type), Box::new(value_type))
         }
+        Rule::weak_dict_type => {
+            let mut inner = type_pair.into_inner();
+            let key_type = parse_type(inner.next().unwrap())?;
+            let value_type = parse_type(inner.next().unwrap())?;
+            if !matches!(key_type, Type::Custom(_)) {
+                return Err(format!("weak_dict keys must be class instances, got {:?}", key_type));
+            }
+            Type::WeakDict(Box::new(key_type), Box::new(value_type))
+        }
         Rule::channel_type => {
             let elem_type = parse_type(type_pair.into_inner().next().unwrap())?;
             Type::Channel(Box::new(elem_type))
@@ -238,6 +469,8 @@ fn parse_type(pair: Pair<Rule>) -> Result<Type, String> {
                 "ptr" => Type::Ptr,
                 "future" => Type::Future,
                 "func" => Type::Func,
+                "duration" => Type::Duration,
+                "deadline" => Type::Deadline,
                 _ => Type::Custom(clean_s),
             }
         }
@@ -281,7 +514,13 @@ fn parse_var_decl(pair: Pair<Rule>) -> Result<VarDecl, String> {
         }
     }
 
-    Ok(VarDecl { name, ty, value })
+    Ok(VarDecl { name, ty, value, thread_local: false })
+}
+
+fn parse_threadlocal_decl(pair: Pair<Rule>) -> Result<Statement, String> {
+    let mut decl = parse_var_decl(pair.into_inner().next().unwrap())?;
+    decl.thread_local = true;
+    Ok(Statement::VarDecl(decl))
 }
 
 fn parse_if_stmt(pair: Pair<Rule>) -> Result<IfStmt, String> {
@@ -320,13 +559,14 @@ fn parse_while_stmt(pair: Pair<Rule>) -> Result<WhileStmt, String> {
 fn parse_for_stmt(pair: Pair<Rule>) -> Result<ForStmt, String> {
     let mut inner = pair.into_inner();
     let mut vars = Vec::new();
+    let mut var_type = None;
     
-    // Collect loop variables
+    // Collect loop variables (the first one may carry a type annotation)
     while let Some(p) = inner.peek() {
-        if p.as_rule() == Rule::ident {
-            vars.push(inner.next().unwrap().as_str().to_string());
-        } else {
-            break;
+        match p.as_rule() {
+            Rule::ident => vars.push(inner.next().unwrap().as_str().to_string()),
+            Rule::type_expr => var_type = Some(parse_type(inner.next().unwrap())?),
+            _ => break,
         }
     }
     
@@ -342,7 +582,7 @@ fn parse_for_stmt(pair: Pair<Rule>) -> Result<ForStmt, String> {
     let block_pair = inner.next().ok_or("Missing loop body")?;
     let body = parse_block(block_pair)?;
 
-    Ok(ForStmt { vars, iter, body })
+    Ok(ForStmt { vars, var_type, iter, body })
 }
 
 fn parse_pool_stmt(pair: Pair<Rule>) -> Result<PoolStmt, String> {
@@ -353,12 +593,28 @@ fn parse_pool_stmt(pair: Pair<Rule>) -> Result<PoolStmt, String> {
 }
 
 fn parse_select_stmt(pair: Pair<Rule>) -> Result<SelectStmt, String> {
+    let mut channels = None;
     let mut branches = Vec::new();
-    for branch_pair in pair.into_inner() {
-        let branch = parse_select_branch(branch_pair)?;
-        branches.push(branch);
+    for inner in pair.into_inner() {
+        if inner.as_rule() == Rule::select_source {
+            channels = Some(parse_expr(inner.into_inner().next().unwrap())?);
+            continue;
+        }
+        branches.push(parse_select_branch(inner)?);
     }
-    Ok(SelectStmt { branches })
+    // 静态形式按变量名指定 channel，动态形式从列表里接收并绑定索引
+    let recv_any = branches.iter().filter(|b| matches!(b, SelectBranch::RecvAny { .. })).count();
+    if channels.is_some() {
+        if branches.iter().any(|b| matches!(b, SelectBranch::Recv { .. })) {
+            return Err("`select from` branches receive as `value, index => { ... }`, not `x <- ch`".to_string());
+        }
+        if recv_any != 1 {
+            return Err("`select from` needs exactly one `value, index => { ... }` branch".to_string());
+        }
+    } else if recv_any > 0 || branches.iter().any(|b| matches!(b, SelectBranch::Closed { .. })) {
+        return Err("`value, index =>` and `closed =>` branches need `select from <list of channels>`".to_string());
+    }
+    Ok(SelectStmt { channels, branches })
 }
 
 fn parse_select_branch(pair: Pair<Rule>) -> Result<SelectBranch, String> {
@@ -371,6 +627,17 @@ fn parse_select_branch(pair: Pair<Rule>) -> Result<SelectBranch, String> {
             let body = parse_block(recv_inner.next().unwrap())?;
             Ok(SelectBranch::Recv { var, channel, body })
         }
+        Rule::select_recv_any => {
+            let mut recv_inner = inner.into_inner();
+            let var = recv_inner.next().unwrap().as_str().to_string();
+            let index = recv_inner.next().unwrap().as_str().to_string();
+            let body = parse_block(recv_inner.next().unwrap())?;
+            Ok(SelectBranch::RecvAny { var, index, body })
+        }
+        Rule::select_closed => {
+            let body = parse_block(inner.into_inner().next().unwrap())?;
+            Ok(SelectBranch::Closed { body })
+        }
         Rule::select_timeout => {
             let mut timeout_inner = inner.into_inner();
             let duration = parse_expr(timeout_inner.next().unwrap())?;
@@ -393,8 +660,96 @@ fn parse_send_stmt(pair: Pair<Rule>) -> Result<SendStmt, String> {
 }
 
 fn parse_await_scope_stmt(pair: Pair<Rule>) -> Result<AwaitScopeStmt, String> {
-    let body = parse_block(pair.into_inner().next().unwrap())?;
-    Ok(AwaitScopeStmt { body })
+    let mut inner = pair.into_inner().peekable();
+    let fail_fast = inner.next_if(|p| p.as_rule() == Rule::fail_fast_keyword).is_some();
+    let body = parse_block(inner.next().unwrap())?;
+    Ok(AwaitScopeStmt { fail_fast, body })
+}
+
+fn parse_measure_stmt(pair: Pair<Rule>) -> Result<MeasureStmt, String> {
+    let mut inner = pair.into_inner();
+    let s = inner.next().unwrap().as_str();
+    let label = unescape_string(&s[1..s.len()-1]);
+    let body = parse_block(inner.next().unwrap())?;
+    Ok(MeasureStmt { label, body })
+}
+
+fn parse_test_block(pair: Pair<Rule>) -> Result<TestBlock, String> {
+    let line = pair.as_span().start_pos().line_col().0;
+    let mut inner = pair.into_inner();
+    let s = inner.next().unwrap().as_str();
+    let name = unescape_string(&s[1..s.len()-1]);
+    let body = parse_block(inner.next().unwrap())?;
+    Ok(TestBlock { name, body, line })
+}
+
+fn parse_match_stmt(pair: Pair<Rule>) -> Result<MatchStmt, String> {
+    let line = pair.as_span().start_pos().line_col().0;
+    let mut inner = pair.into_inner();
+    inner.next(); // match_kw
+    let subject = parse_expr(inner.next().unwrap())?;
+    let mut arms = Vec::new();
+    let mut default = None;
+    let mut seen: Vec<MatchPattern> = Vec::new();
+    for arm_pair in inner {
+        if default.is_some() {
+            return Err(format!("line {}: '_' must be the last match arm", line));
+        }
+        let mut patterns = Vec::new();
+        let mut body = Vec::new();
+        let mut wildcard = false;
+        for item in arm_pair.into_inner() {
+            match item.as_rule() {
+                Rule::match_wildcard => wildcard = true,
+                Rule::match_pattern => {
+                    let pattern = parse_match_pattern(item.into_inner().next().unwrap())?;
+                    if seen.contains(&pattern) {
+                        return Err(format!("line {}: duplicate match pattern {}", line, match_pattern_text(&pattern)));
+                    }
+                    if seen.first().is_some_and(|first| std::mem::discriminant(first) != std::mem::discriminant(&pattern)) {
+                        return Err(format!("line {}: match patterns must be all int or all str literals", line));
+                    }
+                    seen.push(pattern.clone());
+                    patterns.push(pattern);
+                }
+                Rule::block => body = parse_block(item)?,
+                _ => {}
+            }
+        }
+        if wildcard {
+            default = Some(body);
+        } else {
+            arms.push(MatchArm { patterns, body });
+        }
+    }
+    Ok(MatchStmt { subject, arms, default })
+}
+
+fn parse_match_pattern(pair: Pair<Rule>) -> Result<MatchPattern, String> {
+    let s = pair.as_str();
+    match pair.as_rule() {
+        Rule::string_lit => Ok(MatchPattern::Str(unescape_string(&s[1..s.len()-1]))),
+        _ => {
+            let (negative, digits) = match s.strip_prefix('-') {
+                Some(rest) => (true, rest),
+                None => (false, s),
+            };
+            let magnitude = match digits.strip_prefix("0x") {
+                Some(hex) => i128::from_str_radix(hex, 16),
+                None => digits.parse::<i128>(),
+            }.map_err(|_| format!("match pattern {} is out of range for int", s))?;
+            let value = if negative { -magnitude } else { magnitude };
+            i64::try_from(value).map(MatchPattern::Int)
+                .map_err(|_| format!("match pattern {} is out of range for int", s))
+        }
+    }
+}
+
+fn match_pattern_text(pattern: &MatchPattern) -> String {
+    match pattern {
+        MatchPattern::Int(n) => n.to_string(),
+        MatchPattern::Str(s) => format!("{:?}", s),
+    }
 }
 
 fn parse_async_select_stmt(pair: Pair<Rule>) -> Result<AsyncSelectStmt, String> {
@@ -485,7 +840,7 @@ fn parse_class_def(pair: Pair<Rule>) -> Result<ClassDef, String> {
                             fields.push(ClassField { name: fname, ty: fty, default_value });
                         }
                         Rule::method_def => {
-                            methods.push(parse_func_def(member_inner.into_inner().next().unwrap())?);
+                            methods.push(parse_method_def(member_inner)?);
                         }
                         _ => {}
                     }
@@ -573,7 +928,8 @@ fn parse_unary_expr(pair: Pair<Rule>) -> Result<Expr, String> {
 
 fn parse_cmp_expr(pair: Pair<Rule>) -> Result<Expr, String> {
     let mut inner = pair.into_inner();
-    let mut left = parse_add_expr(inner.next().unwrap())?;
+    let first = parse_add_expr(inner.next().unwrap())?;
+    let mut rest = Vec::new();
     while let Some(op_pair) = inner.next() {
         let op = match op_pair.as_str() {
             "==" => BinOp::Eq,
@@ -582,12 +938,35 @@ fn parse_cmp_expr(pair: Pair<Rule>) -> Result<Expr, String> {
             "<=" => BinOp::Le,
             ">" => BinOp::Gt,
             ">=" => BinOp::Ge,
+            "is" => BinOp::Is,
+            s if s.starts_with("is") => BinOp::IsNot,
             _ => return Err(format!("Unknown cmp op: {}", op_pair.as_str())),
         };
         let right = parse_add_expr(inner.next().unwrap())?;
-        left = Expr::BinOp(Box::new(left), op, Box::new(right));
+        rest.push((op, right));
     }
-    Ok(left)
+    // 不加括号的 a < b < c 是链式比较，而不是 (a < b) < c
+    if rest.len() > 1 {
+        if rest.iter().any(|(op, _)| matches!(op, BinOp::Is | BinOp::IsNot)) {
+            return Err("`is` cannot be chained with other comparisons; join them with `and`".to_string());
+        }
+        return Ok(Expr::CompareChain(Box::new(first), rest));
+    }
+    Ok(match rest.pop() {
+        Some((op, right)) => Expr::BinOp(Box::new(first), op, Box::new(right)),
+        None => first,
+    })
+}
+
+/// 调用的一个实参：表达式或关键字实参 `name: value`
+fn parse_call_arg(pair: Pair<Rule>) -> Result<Expr, String> {
+    if pair.as_rule() != Rule::named_arg {
+        return parse_expr(pair);
+    }
+    let mut inner = pair.into_inner();
+    let name = inner.next().unwrap().as_str().to_string();
+    let value = parse_expr(inner.next().unwrap())?;
+    Ok(Expr::NamedArg(name, Box::new(value)))
 }
 
 fn parse_postfix_expr(pair: Pair<Rule>) -> Result<Expr, String> {
@@ -598,13 +977,26 @@ fn parse_postfix_expr(pair: Pair<Rule>) -> Result<Expr, String> {
         match item.as_rule() {
             Rule::call_args => {
                 let args: Result<Vec<_>, _> = item.into_inner()
-                    .map(parse_expr).collect();
+                    .map(parse_call_arg).collect();
                 expr = Expr::Call(Box::new(expr), args?);
             }
             Rule::index => {
                 let idx = parse_expr(item.into_inner().next().unwrap())?;
                 expr = Expr::Index(Box::new(expr), Box::new(idx));
             }
+            Rule::slice => {
+                let mut start = None;
+                let mut end = None;
+                for bound in item.into_inner() {
+                    let rule = bound.as_rule();
+                    let value = Box::new(parse_expr(bound.into_inner().next().unwrap())?);
+                    match rule {
+                        Rule::slice_start => start = Some(value),
+                        _ => end = Some(value),
+                    }
+                }
+                expr = Expr::Slice(Box::new(expr), start, end);
+            }
             Rule::member => {
                 let name = item.into_inner().next().unwrap().as_str().to_string();
                 expr = Expr::Member(Box::new(expr), name);
@@ -638,6 +1030,142 @@ fn unescape_string(s: &str) -> String {
     res
 }
 
+/// 三引号字符串去缩进时要删掉的字节区间（相对引号内的原文，升序）
+///
+/// 只在开头的 `"""` 后紧跟换行时去缩进：
+/// - 去掉开头这个换行；
+/// - 最后一行（结束的 `"""` 之前）只有空白时，连同它前面的换行一起去掉；
+/// - 公共缩进是所有非空白行、以及上面这种结束行的行首空白（空格、tab 逐字符比较）的最长公共前缀，
+///   每个非空白行去掉这段前缀，只有空白的行变成空行。
+///
+/// 按转义之前的原文计算：`\n` 转义不算换行。
+fn dedent_cuts(content: &str) -> Vec<std::ops::Range<usize>> {
+    if !content.starts_with('\n') {
+        return Vec::new();
+    }
+    let mut cuts = vec![0..1];
+    // (行首偏移, 行内容)
+    let mut lines = Vec::new();
+    let mut start = 1;
+    for line in content[1..].split('\n') {
+        lines.push((start, line));
+        start += line.len() + 1;
+    }
+    let is_blank = |line: &str| line.chars().all(|c| c == ' ' || c == '\t');
+    let indent = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+    let closing = match lines.last() {
+        Some(&(_, line)) if is_blank(line) => lines.pop(),
+        _ => None,
+    };
+    let mut prefix: Option<&str> = None;
+    for &(_, line) in lines.iter().filter(|(_, line)| !is_blank(line)).chain(closing.iter()) {
+        let lead = &line[..indent(line)];
+        prefix = Some(match prefix {
+            None => lead,
+            Some(prev) => {
+                let common = prev.bytes().zip(lead.bytes()).take_while(|(a, b)| a == b).count();
+                &prev[..common]
+            }
+        });
+    }
+    let prefix_len = prefix.map_or(0, str::len);
+    for &(offset, line) in &lines {
+        let len = if is_blank(line) { line.len() } else { prefix_len };
+        if len > 0 {
+            cuts.push(offset..offset + len);
+        }
+    }
+    if let Some((offset, line)) = closing {
+        // 连同前面的换行；内容只有一个换行时它就是开头那个，已经删掉了
+        cuts.push(offset.saturating_sub(1).max(1)..offset + line.len());
+    }
+    cuts
+}
+
+/// 从 `text`（位于引号内原文的 `offset` 处）删掉落在 `cuts` 里的字节
+fn apply_cuts(text: &str, offset: usize, cuts: &[std::ops::Range<usize>]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut next = cuts.partition_point(|cut| cut.end <= offset);
+    for (i, c) in text.char_indices() {
+        let pos = offset + i;
+        while next < cuts.len() && cuts[next].end <= pos {
+            next += 1;
+        }
+        if next < cuts.len() && cuts[next].contains(&pos) {
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// f-string 拆成字面量片段和表达式；相邻的字面量合并，没有表达式时就是普通字符串。
+/// 三引号 f-string 的字面量片段先按整个字面量的缩进去缩进（`dedent_cuts`）再处理转义。
+fn parse_fstring(pair: Pair<Rule>) -> Result<Expr, String> {
+    let (content_start, cuts) = if pair.as_rule() == Rule::ftriple_string_lit {
+        let s = pair.as_str();
+        (pair.as_span().start() + 4, dedent_cuts(&s[4..s.len()-3]))
+    } else {
+        (pair.as_span().start() + 2, Vec::new())
+    };
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    for part in pair.into_inner() {
+        match part.as_rule() {
+            Rule::fstring_lbrace => text.push('{'),
+            Rule::fstring_rbrace => text.push('}'),
+            Rule::fstring_text | Rule::ftriple_text => {
+                let raw = apply_cuts(part.as_str(), part.as_span().start() - content_start, &cuts);
+                text.push_str(&unescape_string(&raw));
+            }
+            Rule::fstring_expr => {
+                if !text.is_empty() {
+                    parts.push(Expr::String(std::mem::take(&mut text)));
+                }
+                parts.push(parse_expr(part.into_inner().next().unwrap())?);
+            }
+            _ => return Err(format!("Unexpected f-string part: {:?}", part.as_rule())),
+        }
+    }
+    if parts.is_empty() {
+        return Ok(Expr::String(text));
+    }
+    if !text.is_empty() {
+        parts.push(Expr::String(text));
+    }
+    Ok(Expr::FString(parts))
+}
+
+/// duration 字面量换算成纳秒：小数部分按整数精确换算，不经过浮点数；
+/// 换算结果不是整数纳秒（`1.5ns`）或超出 i64 时报错
+fn parse_duration(text: &str) -> Result<i64, String> {
+    let split = text.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let scale: i128 = match unit {
+        "ns" => 1,
+        "us" => 1_000,
+        "ms" => 1_000_000,
+        "s" => 1_000_000_000,
+        "m" => 60_000_000_000,
+        "h" => 3_600_000_000_000,
+        _ => return Err(format!("Unknown duration unit '{}' in '{}'", unit, text)),
+    };
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let out_of_range = || format!("Duration literal '{}' is out of range", text);
+    let whole: i128 = whole.parse().map_err(|_| out_of_range())?;
+    let mut nanos = whole.checked_mul(scale).ok_or_else(out_of_range)?;
+    if !fraction.is_empty() {
+        let digits = u32::try_from(fraction.len()).map_err(|_| out_of_range())?;
+        let denominator = 10i128.checked_pow(digits).ok_or_else(out_of_range)?;
+        let numerator = fraction.parse::<i128>().map_err(|_| out_of_range())?.checked_mul(scale).ok_or_else(out_of_range)?;
+        if numerator % denominator != 0 {
+            return Err(format!("Duration literal '{}' is not a whole number of nanoseconds", text));
+        }
+        nanos += numerator / denominator;
+    }
+    i64::try_from(nanos).map_err(|_| out_of_range())
+}
+
 fn parse_primary(pair: Pair<Rule>) -> Result<Expr, String> {
     let inner = pair.into_inner().next().unwrap();
     match inner.as_rule() {
@@ -654,6 +1182,7 @@ fn parse_primary(pair: Pair<Rule>) -> Result<Expr, String> {
             let f: f64 = inner.as_str().parse().unwrap();
             Ok(Expr::Float(f))
         }
+        Rule::duration_lit => parse_duration(inner.as_str()).map(Expr::Duration),
         Rule::bigint_lit => {
             // 去掉后缀 B/b
             let s = inner.as_str();
@@ -670,6 +1199,23 @@ fn parse_primary(pair: Pair<Rule>) -> Result<Expr, String> {
             let s = inner.as_str();
             Ok(Expr::String(unescape_string(&s[1..s.len()-1])))
         }
+        Rule::fstring_lit => parse_fstring(inner),
+        Rule::triple_string_lit => {
+            let s = inner.as_str();
+            let content = &s[3..s.len()-3];
+            Ok(Expr::String(unescape_string(&apply_cuts(content, 0, &dedent_cuts(content)))))
+        }
+        Rule::raw_string_lit => {
+            let s = inner.as_str();
+            match s.strip_prefix("r\"\"\"") {
+                Some(rest) => {
+                    let content = &rest[..rest.len()-3];
+                    Ok(Expr::String(apply_cuts(content, 0, &dedent_cuts(content))))
+                }
+                None => Ok(Expr::String(s[2..s.len()-1].to_string())),
+            }
+        }
+        Rule::ftriple_string_lit => parse_fstring(inner),
         Rule::bool_lit => {
             Ok(Expr::Bool(inner.as_str() == "true"))
         }
@@ -698,10 +1244,28 @@ fn parse_primary(pair: Pair<Rule>) -> Result<Expr, String> {
             let func_name = spawn_inner.next().unwrap().as_str().to_string();
             let args: Result<Vec<_>, _> = spawn_inner.next().unwrap()
                 .into_inner()
-                .map(parse_expr)
+                .map(parse_call_arg)
                 .collect();
             Ok(Expr::Spawn(func_name, args?))
         }
+        Rule::lambda_expr => {
+            let mut params = Vec::new();
+            let mut return_type = None;
+            let mut body = Vec::new();
+            for item in inner.into_inner() {
+                match item.as_rule() {
+                    Rule::param_list => {
+                        for param_pair in item.into_inner() {
+                            params.push(parse_param(param_pair)?);
+                        }
+                    }
+                    Rule::type_expr => return_type = Some(parse_type(item)?),
+                    Rule::block => body = parse_block(item)?,
+                    _ => {}
+                }
+            }
+            Ok(Expr::Lambda(params, return_type, body))
+        }
         Rule::recv_expr => {
             let channel = inner.into_inner().next().unwrap().as_str().to_string();
             Ok(Expr::Recv(channel))
@@ -873,8 +1437,233 @@ fn parse_c_basic_type(pair: Pair<Rule>) -> Result<CType, String> {
         "u32" => CType::U32,
         "i64" => CType::I64,
         "u64" => CType::U64,
+        "f32" => CType::Float,
+        "f64" => CType::Double,
+        // cstr 是以 0 结尾的 char*：传入时取 str 的字节，返回时复制成 str
+        "cstr" => CType::Ptr(Box::new(CType::Char)),
         "size_t" => CType::SizeT,
         "ptrdiff_t" => CType::PtrDiffT,
         _ => CType::Struct(s.to_string()),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, Statement, TraceMode};
+
+    #[test]
+    fn test_trace_attribute() {
+        let program = parse("@trace\nfn f() {}\n@trace(ret)\nfn g() {}\nclass A {\n    @trace(ret)\n    fn m() {}\n    fn n() {}\n}\n").unwrap();
+        let traces: Vec<Option<TraceMode>> = program.statements.iter().flat_map(|stmt| match stmt {
+            Statement::FuncDef(func) => vec![func.trace],
+            Statement::ClassDef(class) => class.methods.iter().map(|m| m.trace).collect(),
+            _ => vec![],
+        }).collect();
+        assert_eq!(traces, [Some(TraceMode::Calls), Some(TraceMode::Returns), Some(TraceMode::Returns), None]);
+
+        for (source, message) in [
+            ("@trace(args)\nfn f() {}\n", "1:1: unknown @trace option 'args'"),
+            ("@trace\nlet x: int = 1;\n", "2:1: @trace only applies to function definitions"),
+            ("@pure\n@trace\nfn f() -> int { return 1; }\n", "3:1: @pure and @trace cannot be combined"),
+            ("class A {\n    @pure\n    fn m() {}\n}\n", "2:5: @pure cannot be applied to methods"),
+        ] {
+            let err = parse(source).unwrap_err().to_string();
+            assert!(err.starts_with(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_hot_entry_attribute() {
+        let program = parse("@hot_entry\nfn frame() {}\nfn tick() {}\n").unwrap();
+        let entries: Vec<bool> = program.statements.iter().filter_map(|stmt| match stmt {
+            Statement::FuncDef(func) => Some(func.hot_entry),
+            _ => None,
+        }).collect();
+        assert_eq!(entries, [true, false]);
+
+        for (source, message) in [
+            ("@hot_entry(fast)\nfn f() {}\n", "1:1: @hot_entry takes no arguments"),
+            ("@hot_entry\nlet x: int = 1;\n", "2:1: @hot_entry only applies to function definitions"),
+            ("@hot_entry\nasync fn f() {}\n", "2:1: @hot_entry cannot be applied to async function 'f'"),
+            ("class A {\n    @hot_entry\n    fn m() {}\n}\n", "2:5: @hot_entry cannot be applied to methods"),
+        ] {
+            let err = parse(source).unwrap_err().to_string();
+            assert!(err.starts_with(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_select_from() {
+        let program = parse("select from chans {\n    v, i => { print(v); }\n    closed => { }\n    timeout(100ms) => { }\n}\n").unwrap();
+        let Statement::Select(select) = &program.statements[0] else {
+            panic!("expected select");
+        };
+        assert!(matches!(select.channels, Some(crate::Expr::Ident(ref name)) if name == "chans"));
+        assert!(matches!(&select.branches[0], crate::SelectBranch::RecvAny { var, index, .. } if var == "v" && index == "i"));
+        assert!(matches!(select.branches[1], crate::SelectBranch::Closed { .. }));
+
+        for (source, message) in [
+            ("select from chans {\n    x <- ch => { }\n}\n", "not `x <- ch`"),
+            ("select from chans {\n    closed => { }\n}\n", "exactly one"),
+            ("select {\n    v, i => { }\n}\n", "need `select from"),
+        ] {
+            let err = parse(source).unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_await_scope_fail_fast() {
+        let program = parse("await scope {\n}\nawait scope fail_fast {\n    let fail_fast = 1;\n}\n").unwrap();
+        let flags: Vec<(bool, usize)> = program.statements.iter().map(|stmt| match stmt {
+            Statement::AwaitScope(scope) => (scope.fail_fast, scope.body.len()),
+            other => panic!("expected await scope, got {:?}", other),
+        }).collect();
+        assert_eq!(flags, [(false, 0), (true, 1)]);
+        assert!(parse("await scope fail_fastx {\n}\n").is_err());
+    }
+
+    #[test]
+    fn test_threadlocal_decl() {
+        let program = parse("threadlocal let hits: int = 0;
+@cfg(linux)
+threadlocal let name: str = \"x\";
+let total: int = 0;
+").unwrap();
+        let flags: Vec<(&str, bool)> = program.statements.iter().filter_map(|stmt| match stmt {
+            Statement::VarDecl(decl) => Some((decl.name.as_str(), decl.thread_local)),
+            _ => None,
+        }).collect();
+        let expected: &[(&str, bool)] = if cfg!(target_os = "linux") {
+            &[("hits", true), ("name", true), ("total", false)]
+        } else {
+            &[("hits", true), ("total", false)]
+        };
+        assert_eq!(flags, expected);
+
+        // 只能写在顶层
+        for source in [
+            "fn f() {\n    threadlocal let x: int = 0;\n}\n",
+            "if true {\n    threadlocal let x: int = 0;\n}\n",
+            "threadlocal x = 1;\n",
+        ] {
+            assert!(parse(source).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_extern_c_types() {
+        let program = parse("extern \"libc.so.6\" {\n    fn f(a: i32, b: u32, c: f32, d: f64, s: cstr) -> cstr;\n}\n").unwrap();
+        let Statement::ExternBlock(block) = &program.statements[0] else {
+            panic!("expected extern block");
+        };
+        let crate::ExternDecl::Function(func) = &block.declarations[0] else {
+            panic!("expected extern fn");
+        };
+        let cstr = crate::CType::Ptr(Box::new(crate::CType::Char));
+        let params: Vec<&crate::CType> = func.params.iter().map(|p| &p.ty).collect();
+        assert_eq!(params, [&crate::CType::I32, &crate::CType::U32, &crate::CType::Float, &crate::CType::Double, &cstr]);
+        assert_eq!(func.return_type, Some(cstr));
+    }
+
+    #[test]
+    fn test_duration_literals() {
+        let program = parse("let a = [30us, 100ms, 2s, 1.5h, 0.25ms, 1m, 7ns, 0.000001s];\nlet d: duration = 5s;\nlet t: deadline = deadline(d);\n").unwrap();
+        let Statement::VarDecl(decl) = &program.statements[0] else {
+            panic!("expected let");
+        };
+        let Some(crate::Expr::List(items)) = &decl.value else {
+            panic!("expected list");
+        };
+        let nanos: Vec<i64> = items.iter().map(|item| match item {
+            crate::Expr::Duration(n) => *n,
+            other => panic!("not a duration: {:?}", other),
+        }).collect();
+        assert_eq!(nanos, [30_000, 100_000_000, 2_000_000_000, 5_400_000_000_000, 250_000, 60_000_000_000, 7, 1_000]);
+        let types: Vec<Option<&crate::Type>> = program.statements[1..].iter().map(|stmt| match stmt {
+            Statement::VarDecl(decl) => decl.ty.as_ref(),
+            _ => None,
+        }).collect();
+        assert!(matches!(types[..], [Some(crate::Type::Duration), Some(crate::Type::Deadline)]));
+
+        for (source, message) in [
+            ("let a = 1.5ns;\n", "not a whole number of nanoseconds"),
+            ("let a = 0.0000000001s;\n", "not a whole number of nanoseconds"),
+            ("let a = 9999999999h;\n", "out of range"),
+        ] {
+            let err = parse(source).unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+        // 单位后面紧跟字母不是 duration 字面量
+        assert!(parse("let a = 5sec;\n").is_err());
+    }
+
+    /// 取第一条 `let` 语句的初值
+    fn first_value(source: &str) -> crate::Expr {
+        let program = parse(source).unwrap();
+        match &program.statements[0] {
+            Statement::VarDecl(decl) => decl.value.clone().unwrap(),
+            other => panic!("expected let, got {:?}", other),
+        }
+    }
+
+    /// 第一条 `let` 语句的字符串字面量初值
+    fn first_string(source: &str) -> String {
+        match first_value(source) {
+            crate::Expr::String(s) => s,
+            other => panic!("expected string literal, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_triple_quoted_dedent() {
+        let source = "fn f() {\n    let sql: str = \"\"\"\n        SELECT name, age\n          FROM users\n\n        WHERE id = 1;\\t-- \"done\"\n        \"\"\";\n}\nlet s: str = \"\";\n";
+        let program = parse(source).unwrap();
+        let Statement::FuncDef(func) = &program.statements[0] else {
+            panic!("expected fn");
+        };
+        let Statement::VarDecl(decl) = &func.body[0] else {
+            panic!("expected let");
+        };
+        assert!(
+            matches!(&decl.value, Some(crate::Expr::String(s)) if s == "SELECT name, age\n  FROM users\n\nWHERE id = 1;\t-- \"done\""),
+            "{:?}", decl.value
+        );
+
+        for (source, expected) in [
+            // 开头不换行：原样保留
+            ("let s: str = \"\"\"  a\n  b\"\"\";", "  a\n  b"),
+            // 结束行的缩进参与公共前缀
+            ("let s: str = \"\"\"\n    a\n  \"\"\";", "  a"),
+            // 结束的 """ 跟在内容后面：最后一行不是空白行，保留内容
+            ("let s: str = \"\"\"\n  a\n  b\"\"\";", "a\nb"),
+            ("let s: str = \"\"\"\n\"\"\";", ""),
+            ("let s: str = \"\"\"\"\"\";", ""),
+        ] {
+            assert_eq!(first_string(source), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_raw_strings() {
+        assert_eq!(first_string(r#"let s: str = r"C:\new\{x}\t";"#), r"C:\new\{x}\t");
+        assert_eq!(first_string("let s: str = r\"\"\"\n    {\"a\": \"\\n\"}\n    \"\"\";"), "{\"a\": \"\\n\"}");
+        // r 仍然可以作变量名
+        assert!(matches!(first_value("let s: int = r+1;"), crate::Expr::BinOp(..)));
+    }
+
+    #[test]
+    fn test_triple_quoted_fstring() {
+        let value = first_value("let s: str = f\"\"\"\n    id = {id}\n      {{x}}\\t{n + 1}\n    \"\"\";");
+        let crate::Expr::FString(parts) = value else {
+            panic!("expected f-string, got {:?}", value);
+        };
+        assert_eq!(parts.len(), 4);
+        assert!(matches!(&parts[0], crate::Expr::String(s) if s == "id = "), "{:?}", parts);
+        assert!(matches!(&parts[2], crate::Expr::String(s) if s == "\n  {x}\t"), "{:?}", parts);
+
+        // 插值表达式里的语法错误指向原文中的位置
+        let err = parse("let n: int = 1;\nlet s: str = f\"\"\"\n    a {n +} b\n    \"\"\";\n").unwrap_err();
+        assert_eq!((err.line, err.col), (3, 11), "{}", err);
+    }
+}
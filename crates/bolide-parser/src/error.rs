@@ -0,0 +1,226 @@
+//! 解析错误
+//!
+//! 语法错误（pest）、转换错误（未知属性等）和预处理错误统一成 `ParseError`：
+//! 行列号（从 1 开始，列按字符计）、期望的语法规则、出错的那一行源码。
+//! `Display` 只有一行 `文件:行:列: 说明`；实现了 `miette::Diagnostic`，
+//! CLI 附上源码后渲染出错行并在出错位置下划线。一次解析的多处错误，
+//! 第一处之外的挂在 `related` 上，由 `errors()` 依次取出。
+
+use std::fmt;
+
+use miette::{Diagnostic, LabeledSpan};
+
+use crate::{Rule, SourceMap};
+
+/// 带位置的解析错误
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// 行号，从 1 开始
+    pub line: usize,
+    /// 列号，从 1 开始，按字符计
+    pub col: usize,
+    /// 出错位置期望的语法规则（只有语法错误有）
+    pub expected: Vec<String>,
+    /// 出错的那一行源码（不含换行）
+    pub snippet: String,
+    /// 错误说明
+    pub message: String,
+    /// 源文件路径（`with_path` 设置）
+    pub path: Option<String>,
+    /// 同一次解析中的其他错误
+    pub related: Vec<ParseError>,
+    /// 出错位置在源码中的字节偏移和下划线的字节长度
+    offset: usize,
+    len: usize,
+}
+
+impl ParseError {
+    /// 源码 `source` 中第 `line` 行第 `col` 列处的错误
+    pub fn new(source: &str, line: usize, col: usize, message: impl Into<String>) -> Self {
+        let (offset, snippet) = locate(source, line, col);
+        let len = token_len(&source[offset..]);
+        ParseError {
+            line,
+            col,
+            expected: Vec::new(),
+            snippet,
+            message: message.into(),
+            path: None,
+            related: Vec::new(),
+            offset,
+            len,
+        }
+    }
+
+    /// pest 的语法错误；行列号和期望的规则取自 pest
+    pub(crate) fn from_pest(source: &str, error: &pest::error::Error<Rule>) -> Self {
+        let (line, col) = match error.line_col {
+            pest::error::LineColLocation::Pos(pos) | pest::error::LineColLocation::Span(pos, _) => pos,
+        };
+        let mut parse_error = ParseError::new(source, line, col, error.variant.message());
+        if let pest::error::ErrorVariant::ParsingError { positives, .. } = &error.variant {
+            parse_error.expected = positives.iter().map(|rule| format!("{:?}", rule)).collect();
+        }
+        parse_error
+    }
+
+    /// 带位置前缀的错误文本：转换错误的 `line 3: ...`，预处理错误的 `3:5: ...`
+    ///
+    /// 只有行号时列指向该行第一个非空白字符；没有位置前缀时指向开头。
+    pub(crate) fn from_message(source: &str, message: &str) -> Self {
+        if let Some((line, rest)) = message.strip_prefix("line ").and_then(|m| m.split_once(": ")) {
+            if let Ok(line) = line.parse::<usize>() {
+                let (_, snippet) = locate(source, line, 1);
+                let col = snippet.chars().take_while(|c| c.is_whitespace()).count() + 1;
+                return ParseError::new(source, line, col, rest);
+            }
+        }
+        let mut parts = message.splitn(3, ':');
+        if let (Some(line), Some(col), Some(rest)) = (parts.next(), parts.next(), parts.next()) {
+            if let (Ok(line), Ok(col)) = (line.parse::<usize>(), col.parse::<usize>()) {
+                return ParseError::new(source, line, col, rest.trim_start());
+            }
+        }
+        ParseError::new(source, 1, 1, message)
+    }
+
+    /// 把一组错误合成一个：第一处在前，其余挂在 `related` 上
+    pub(crate) fn combine(errors: Vec<ParseError>) -> Self {
+        let mut errors = errors.into_iter();
+        let mut first = errors.next().expect("at least one parse error");
+        first.related.extend(errors);
+        first
+    }
+
+    /// 换算到预处理前的原文：行号和列号不变，字节偏移经 `map` 换算（BOM、去掉的 `\r`）
+    pub(crate) fn relocate(mut self, source: &str, map: &SourceMap) -> Self {
+        self.offset = map.original_offset(self.offset);
+        self.len = token_len(&source[self.offset..]);
+        self.snippet = locate(source, self.line, self.col).1;
+        self.related = self.related.into_iter().map(|e| e.relocate(source, map)).collect();
+        self
+    }
+
+    /// 附上源文件路径（包括 `related` 里的错误）
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        let path = path.into();
+        self.related = self.related.into_iter().map(|e| e.with_path(path.clone())).collect();
+        self.path = Some(path);
+        self
+    }
+
+    /// 依次取出全部错误：自身，然后是 `related`
+    pub fn errors(&self) -> impl Iterator<Item = &ParseError> {
+        std::iter::once(self).chain(&self.related)
+    }
+
+    /// 出错位置在源码中的字节范围（下划线覆盖的部分；行尾时为空）
+    pub fn span(&self) -> std::ops::Range<usize> {
+        self.offset..self.offset + self.len
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path)?;
+        }
+        write!(f, "{}:{}: {}", self.line, self.col, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Diagnostic for ParseError {
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let label = match self.expected.as_slice() {
+            [] => "here".to_string(),
+            expected => format!("expected {}", expected.join(", ")),
+        };
+        Some(Box::new(std::iter::once(LabeledSpan::at(self.span(), label))))
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        if self.related.is_empty() {
+            return None;
+        }
+        Some(Box::new(self.related.iter().map(|e| e as &dyn Diagnostic)))
+    }
+}
+
+/// 第 `line` 行第 `col` 列的字节偏移和该行文本；越界时落在最近的位置
+///
+/// 开头的 BOM 和行尾的 `\r` 不算列（与预处理一致）。
+fn locate(source: &str, line: usize, col: usize) -> (usize, String) {
+    let body_start = if source.starts_with('\u{FEFF}') { '\u{FEFF}'.len_utf8() } else { 0 };
+    let mut line_start = body_start;
+    for _ in 1..line {
+        match source[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => break,
+        }
+    }
+    let rest = &source[line_start..];
+    let text = rest.split('\n').next().unwrap_or("");
+    let text = text.strip_suffix('\r').unwrap_or(text);
+    let offset = line_start + text.char_indices().nth(col.saturating_sub(1)).map_or(text.len(), |(i, _)| i);
+    (offset, text.to_string())
+}
+
+/// 下划线的长度：标识符或数字整个划出，其他字符划一个；行尾不划
+fn token_len(rest: &str) -> usize {
+    let word = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+    match rest.chars().next() {
+        _ if word > 0 => word,
+        Some('\n') | Some('\r') | None => 0,
+        Some(c) => c.len_utf8(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_missing_closing_brace() {
+        let source = "fn f() -> int {\n    return 1;\n\nprint(f());\n";
+        let err = crate::parse_source(source).unwrap_err();
+        assert_eq!((err.line, err.col), (5, 1), "{}", err);
+        assert!(err.expected.iter().any(|rule| rule == "statement"), "{:?}", err.expected);
+        assert_eq!(err.snippet, "");
+        assert_eq!(err.span(), source.len()..source.len());
+        assert_eq!(err.with_path("main.bl").to_string().split(": ").next(), Some("main.bl:5:1"));
+    }
+
+    #[test]
+    fn test_invalid_token_mid_file() {
+        let source = "let a: int = 1;\nlet b: int = 2;\nlet c: int = a $ b;\nprint(c);\n";
+        let err = crate::parse_source(source).unwrap_err();
+        assert_eq!((err.line, err.col), (3, 16), "{}", err);
+        assert_eq!(err.snippet, "let c: int = a $ b;");
+        assert_eq!(&source[err.span()], "$");
+        assert!(err.related.is_empty());
+
+        // 预处理前的原文（BOM、CRLF）上定位
+        let crlf = format!("\u{FEFF}{}", source.replace('\n', "\r\n"));
+        let err = crate::parse_source(&crlf).unwrap_err();
+        assert_eq!((err.line, err.col), (3, 16), "{}", err);
+        assert_eq!(err.snippet, "let c: int = a $ b;");
+        assert_eq!(&crlf[err.span()], "$");
+    }
+
+    #[test]
+    fn test_convert_and_preprocess_errors() {
+        let err = crate::parse_source("let x: int = 1;\n  @nope\n  fn f() {}\n").unwrap_err();
+        assert_eq!((err.line, err.col), (2, 3));
+        assert_eq!(err.message, "Unknown attribute '@nope'");
+        assert!(err.expected.is_empty());
+
+        let err = crate::parse_source("let x: int = 1; // \u{202E}\n").unwrap_err();
+        assert_eq!((err.line, err.col), (1, 20));
+        assert!(err.message.contains("U+202E"), "{}", err);
+
+        // 多处错误：第一处在前，其余在 related 里
+        let err = crate::parse_source("let a: int = ;\nlet b: int = 1;\nlet c: int = ;\n").unwrap_err();
+        let lines: Vec<usize> = err.errors().map(|e| e.line).collect();
+        assert_eq!(lines, [1, 3]);
+    }
+}
@@ -3,19 +3,42 @@
 //! 使用 pest 进行语法分析
 
 mod ast;
+mod cfg;
 mod convert;
+mod error;
+mod recover;
+mod source;
 
 use pest_derive::Parser;
 
 pub use ast::*;
-pub use convert::parse;
+pub use cfg::{CfgTarget, VALID_CFG_KEYS};
+pub use convert::{parse, parse_collecting_errors, parse_for_target};
+pub use error::ParseError;
+pub use recover::MAX_PARSE_ERRORS;
+pub use source::{preprocess, Preprocessed, SourceMap};
 
 #[derive(Parser)]
 #[grammar = "bolide.pest"]
 pub struct BolideParser;
 
 /// 解析源代码为 AST
-pub fn parse_source(source: &str) -> Result<Program, String> {
-    let ast = parse(source)?;
+///
+/// 错误的行列号和源码片段都对应传入的原文（BOM、CRLF 去掉之前），见 `ParseError`。
+/// 错误装箱返回，`Result` 本身保持小巧。
+pub fn parse_source(source: &str) -> Result<Program, Box<ParseError>> {
+    let (ast, _warnings) = parse_source_with_warnings(source)?;
     Ok(ast)
 }
+
+/// 解析源代码为 AST，同时返回预处理阶段的警告
+pub fn parse_source_with_warnings(source: &str) -> Result<(Program, Vec<String>), Box<ParseError>> {
+    parse_source_for_target(source, &CfgTarget::host())
+}
+
+/// 解析源代码为 AST，`@cfg` 对给定目标求值（AOT 交叉编译）
+pub fn parse_source_for_target(source: &str, target: &CfgTarget) -> Result<(Program, Vec<String>), Box<ParseError>> {
+    let pre = preprocess(source).map_err(|e| Box::new(ParseError::from_message(source, &e)))?;
+    let ast = parse_for_target(&pre.text, target).map_err(|e| Box::new(e.relocate(source, &pre.map)))?;
+    Ok((ast, pre.warnings))
+}
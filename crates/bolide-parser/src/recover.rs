@@ -0,0 +1,259 @@
+//! 语法错误恢复
+//!
+//! 整个文件解析失败时，从头逐条解析顶层语句（`top_item`），一次报告多处错误：
+//! 解析成功的语句照常转换（收集转换错误），失败的语句记下错误后跳到下一个同步点继续。
+//!
+//! 同步点按括号深度找（跳过字符串和注释）：
+//! - 深度 0 的 `;`，或回到深度 0 的 `}`（后面紧跟 `else` 时语句还没结束）
+//! - 深度 0 时，行首的语句关键字（`fn` / `class` / `let` / `if` / `while` / `for` / `return` / `import` / `extern` 等）
+//! - 任意深度时，顶格写的 `fn` / `class` / `extern` / `import` / `async` / `test` / `@`：
+//!   函数少了 `}` 时不会把后面的函数都吞进同一条语句，只多报这一处
+//!
+//! 已处理的部分换成空白（保留换行）再交给 pest，错误和转换时的行列号与原文一致。
+//! 恢复只用于报错，有错误时不产生 AST。
+
+use pest::Parser;
+
+use crate::cfg::CfgTarget;
+use crate::convert::convert_item;
+use crate::error::ParseError;
+use crate::{BolideParser, Rule};
+
+/// 一次最多报告的错误数
+pub const MAX_PARSE_ERRORS: usize = 20;
+
+/// 深度 0 时在行首结束上一条语句的关键字
+const STMT_KEYWORDS: &[&str] = &[
+    "fn", "class", "async", "extern", "import", "let", "if", "while", "for", "return",
+    "break", "continue", "match", "select", "pool", "measure", "await", "test", "threadlocal",
+];
+
+/// 顶格写时在任意深度结束上一条语句的关键字
+const ITEM_KEYWORDS: &[&str] = &["fn", "class", "async", "extern", "import", "test", "threadlocal"];
+
+/// 逐条解析顶层语句，收集语法错误和转换错误
+pub(crate) fn collect_errors(source: &str, target: &CfgTarget) -> Vec<ParseError> {
+    let mut errors = Vec::new();
+    // 已处理部分的替身：换行保留，其余每个字符换成一个空格
+    let mut masked = String::new();
+    let mut pos = 0;
+
+    while errors.len() < MAX_PARSE_ERRORS {
+        let start = skip_trivia(source, pos);
+        mask(&mut masked, &source[pos..start]);
+        pos = start;
+        if pos >= source.len() {
+            break;
+        }
+
+        let input = format!("{}{}", masked, &source[pos..]);
+        let end = match BolideParser::parse(Rule::top_item, &input) {
+            Ok(mut pairs) => {
+                let item = pairs.next().unwrap().into_inner().next().unwrap();
+                let end = pos + item.as_span().end() - masked.len();
+                if let Err(e) = convert_item(item, target) {
+                    errors.push(ParseError::from_message(source, &e));
+                }
+                end
+            }
+            Err(e) => {
+                // 只解析到同步点，错误不会落在后面的语句里
+                let end = item_end(source, pos);
+                let chunk = format!("{}{}", masked, &source[pos..end]);
+                let error = BolideParser::parse(Rule::top_item, &chunk).err().unwrap_or(e);
+                errors.push(ParseError::from_pest(source, &error));
+                end
+            }
+        };
+        mask(&mut masked, &source[pos..end]);
+        pos = end;
+    }
+    errors
+}
+
+/// 把 `text` 换成空白追加到 `masked`：换行保留，每个字符一个空格（列号按字符计）
+fn mask(masked: &mut String, text: &str) {
+    masked.extend(text.chars().map(|c| if c == '\n' { '\n' } else { ' ' }));
+}
+
+/// 跳过空白和注释
+fn skip_trivia(text: &str, mut pos: usize) -> usize {
+    loop {
+        let rest = &text[pos..];
+        let trimmed = rest.trim_start();
+        pos += rest.len() - trimmed.len();
+        if trimmed.starts_with("//") {
+            pos += trimmed.find('\n').unwrap_or(trimmed.len());
+        } else if let Some(body) = trimmed.strip_prefix("/*") {
+            pos += body.find("*/").map_or(trimmed.len(), |i| i + 4);
+        } else {
+            return pos;
+        }
+    }
+}
+
+/// `text` 是否以关键字 `word` 开头（后面不是标识符字符）
+fn starts_with_word(text: &str, word: &str) -> bool {
+    text.strip_prefix(word)
+        .is_some_and(|rest| !rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_'))
+}
+
+/// 从 `start` 开始的语句在哪里结束（下一个同步点）
+pub(crate) fn item_end(text: &str, start: usize) -> usize {
+    let bytes = text.as_bytes();
+    let mut depth = 0usize;
+    let mut i = start;
+    // 当前行到目前为止是否只有缩进，以及缩进是否为空
+    let mut line_blank = false;
+    let mut indented = false;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c == b'\n' {
+            line_blank = true;
+            indented = false;
+            i += 1;
+            continue;
+        }
+        if line_blank && (c == b' ' || c == b'\t') {
+            indented = true;
+            i += 1;
+            continue;
+        }
+        if line_blank {
+            line_blank = false;
+            let rest = &text[i..];
+            let is_stmt = rest.starts_with('@') || STMT_KEYWORDS.iter().any(|k| starts_with_word(rest, k));
+            let is_item = rest.starts_with('@') || ITEM_KEYWORDS.iter().any(|k| starts_with_word(rest, k));
+            if (depth == 0 && is_stmt) || (!indented && is_item) {
+                return i;
+            }
+        }
+
+        match c {
+            b'"' if text[i..].starts_with("\"\"\"") => {
+                // 三引号字符串里可以有换行、单个引号和括号；停在结束的最后一个引号上
+                i += text[i + 3..].find("\"\"\"").map_or(text.len() - i - 1, |j| j + 5);
+            }
+            b'"' => {
+                i += 1 + text[i + 1..].find('"').unwrap_or(text.len() - i - 1);
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i += text[i..].find('\n').unwrap_or(text.len() - i);
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += text[i + 2..].find("*/").map_or(text.len() - i, |j| j + 4);
+                continue;
+            }
+            b'{' | b'(' | b'[' => depth += 1,
+            b'}' | b')' | b']' => {
+                // 多出来的右括号自成一条语句
+                if depth == 0 {
+                    return i + 1;
+                }
+                depth -= 1;
+                if depth == 0 && c == b'}' {
+                    let next = skip_trivia(text, i + 1);
+                    if starts_with_word(&text[next..], "else") {
+                        i = next;
+                        continue;
+                    }
+                    return if text[next..].starts_with(';') { next + 1 } else { i + 1 };
+                }
+            }
+            b';' if depth == 0 => return i + 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 错误信息里 pest 报告的位置（` --> 行:列`）
+    fn positions(errors: &[ParseError]) -> Vec<String> {
+        errors.iter().map(|e| format!("{}:{}", e.line, e.col)).collect()
+    }
+
+    #[test]
+    fn test_item_end_sync_points() {
+        let text = "let d = {\"a\": 1};\nif x { a(); } else { b(); }\nlet y = 2 +\nlet z = 3;\n";
+        let first = item_end(text, 0);
+        assert_eq!(&text[..first], "let d = {\"a\": 1};");
+        let second = item_end(text, first + 1);
+        assert_eq!(&text[first + 1..second], "if x { a(); } else { b(); }");
+        let third = item_end(text, second + 1);
+        assert_eq!(&text[second + 1..third], "let y = 2 +\n");
+
+        // 少了 `}` 的函数在下一个顶格的 fn 处结束
+        let text = "fn a() {\n    let s = \"}\";\n\nfn b() {\n}\n";
+        assert_eq!(&text[..item_end(text, 0)], "fn a() {\n    let s = \"}\";\n\n");
+
+        // 三引号字符串里的引号、括号和顶格的关键字都不是同步点
+        let text = "let q = \"\"\"\nfn \"x\" {\n\"\"\";\nlet z = 3;\n";
+        assert_eq!(&text[..item_end(text, 0)], "let q = \"\"\"\nfn \"x\" {\n\"\"\";");
+    }
+
+    #[test]
+    fn test_errors_in_separate_functions() {
+        let source = "\
+fn a() -> int {
+    let x: int = ;
+    return x;
+}
+
+fn b() {
+    print(1 +);
+}
+
+let ok: int = 1;
+
+fn c() {
+    let = 5;
+    return;
+}
+";
+        let errors = crate::parse_collecting_errors(source, &CfgTarget::host()).unwrap_err();
+        assert_eq!(positions(&errors), ["2:18", "7:14", "13:9"], "{:#?}", errors);
+    }
+
+    #[test]
+    fn test_unclosed_brace_is_bounded() {
+        let source = "\
+fn a() {
+    if true {
+        print(1);
+}
+
+fn b() -> int {
+    return 2;
+}
+
+print(b());
+";
+        let errors = crate::parse_collecting_errors(source, &CfgTarget::host()).unwrap_err();
+        assert_eq!(errors.len(), 1, "{:#?}", errors);
+        assert_eq!(positions(&errors), ["6:1"]);
+    }
+
+    #[test]
+    fn test_error_limit_and_convert_errors() {
+        let mut source = String::from("@nope\nfn f() {}\n");
+        for i in 0..30 {
+            source.push_str(&format!("let v{}: int = ;\n", i));
+        }
+        let errors = crate::parse_collecting_errors(&source, &CfgTarget::host()).unwrap_err();
+        assert_eq!(errors.len(), MAX_PARSE_ERRORS);
+        assert_eq!(errors[0].to_string(), "1:1: Unknown attribute '@nope'");
+        assert_eq!(positions(&errors[1..2]), ["3:15"]);
+
+        // 没有语法错误时转换错误同样逐条收集
+        let errors = crate::parse_collecting_errors("@nope\nfn f() {}\n@pure\nlet x: int = 1;\n", &CfgTarget::host())
+            .unwrap_err();
+        assert_eq!(errors.len(), 2, "{:#?}", errors);
+    }
+}
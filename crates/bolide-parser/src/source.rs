@@ -0,0 +1,317 @@
+//! 源码预处理（交给 pest 之前）
+//!
+//! - 去掉开头的 UTF-8 BOM
+//! - 把 CRLF 统一成 LF
+//! - 拒绝字符串字面量之外的 Unicode 行分隔符和双向控制字符
+//! - 对行首缩进混用 tab 和空格给出警告
+//! - 没有结束的字符串字面量报错，位置指向开头的引号（`r"`、`f"""` 指向前缀）
+//!
+//! 解析错误在预处理后文本中的字节偏移通过 `SourceMap` 换算回原文，
+//! 行号不变，列号按字符计（BOM 和被去掉的 `\r` 不占列）。
+
+/// 预处理结果
+pub struct Preprocessed {
+    /// 交给 pest 的文本
+    pub text: String,
+    /// 偏移映射
+    pub map: SourceMap,
+    /// 警告（不阻止编译）
+    pub warnings: Vec<String>,
+}
+
+/// 预处理文本到原文的偏移映射
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    /// 开头 BOM 的字节数（0 或 3）
+    bom_len: usize,
+    /// 每个被去掉的 `\r` 在预处理文本中对应的位置（即其后 `\n` 的位置），递增
+    removed_cr: Vec<usize>,
+}
+
+impl SourceMap {
+    /// 原文是否以 BOM 开头
+    pub fn had_bom(&self) -> bool {
+        self.bom_len > 0
+    }
+
+    /// 预处理文本中的字节偏移 -> 原文中的字节偏移
+    pub fn original_offset(&self, offset: usize) -> usize {
+        let removed = self.removed_cr.partition_point(|&pos| pos <= offset);
+        offset + self.bom_len + removed
+    }
+}
+
+/// 扫描状态
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Code,
+    Str,
+    /// 三引号字符串 `"""..."""`（包括 `r"""`、`f"""`），里面的单个 `"` 不结束字符串
+    TripleStr,
+    LineComment,
+    BlockComment,
+}
+
+/// 双向控制字符（见 CVE-2021-42574 "Trojan Source"）
+fn is_bidi_control(c: char) -> bool {
+    matches!(c,
+        '\u{061C}' | '\u{200E}' | '\u{200F}' |
+        '\u{202A}'..='\u{202E}' |
+        '\u{2066}'..='\u{2069}')
+}
+
+/// 除 `\n` 外的 Unicode 行分隔符
+fn is_line_separator(c: char) -> bool {
+    matches!(c, '\u{0085}' | '\u{2028}' | '\u{2029}')
+}
+
+/// 引号前面的 `r` / `f` 前缀长度（前缀本身不能是更长标识符的结尾）
+fn string_prefix_len(before: &str) -> usize {
+    let mut rev = before.chars().rev();
+    match (rev.next(), rev.next()) {
+        (Some('r' | 'f'), prev) if !prev.is_some_and(|c| c.is_alphanumeric() || c == '_') => 1,
+        _ => 0,
+    }
+}
+
+/// 预处理源码
+pub fn preprocess(source: &str) -> Result<Preprocessed, String> {
+    let (body, bom_len) = match source.strip_prefix('\u{FEFF}') {
+        Some(rest) => (rest, '\u{FEFF}'.len_utf8()),
+        None => (source, 0),
+    };
+
+    let mut text = String::with_capacity(body.len());
+    let mut removed_cr = Vec::new();
+    let mut warnings = Vec::new();
+
+    let mut state = State::Code;
+    let mut line = 1usize;
+    let mut col = 1usize;
+    let mut at_line_start = true;
+    // 当前字符串字面量开头的行列（含 r / f 前缀）
+    let mut str_start = (0, 0);
+    let mut chars = body.char_indices().peekable();
+
+    while let Some((pos, c)) = chars.next() {
+        // 行首缩进检查（只检查从代码状态开始的行）
+        if at_line_start {
+            at_line_start = false;
+            if state == State::Code {
+                let rest = &body[pos..];
+                let indent = &rest[..rest.len() - rest.trim_start_matches([' ', '\t']).len()];
+                if indent.contains(' ') && indent.contains('\t') {
+                    warnings.push(format!(
+                        "{}:1: indentation mixes tabs and spaces; use one or the other",
+                        line
+                    ));
+                }
+            }
+        }
+
+        if c == '\r' {
+            if matches!(chars.peek(), Some((_, '\n'))) {
+                removed_cr.push(text.len());
+                continue;
+            }
+            if !matches!(state, State::Str | State::TripleStr) {
+                return Err(format!(
+                    "{}:{}: bare carriage return (U+000D) is not a line break; save the file with LF or CRLF line endings",
+                    line, col
+                ));
+            }
+        }
+
+        if !matches!(state, State::Str | State::TripleStr) {
+            if is_bidi_control(c) {
+                return Err(format!(
+                    "{}:{}: bidirectional control character U+{:04X} is not allowed outside string literals; \
+                     it can make code display differently from how it compiles (CVE-2021-42574)",
+                    line, col, c as u32
+                ));
+            }
+            if is_line_separator(c) {
+                return Err(format!(
+                    "{}:{}: Unicode line separator U+{:04X} is not allowed outside string literals; use a plain newline",
+                    line, col, c as u32
+                ));
+            }
+        }
+
+        // 状态转移（与 bolide.pest 中的 string_lit / triple_string_lit / COMMENT 保持一致）
+        match state {
+            State::Code => match c {
+                '"' if body[pos..].starts_with("\"\"\"") => {
+                    str_start = (line, col - string_prefix_len(&text));
+                    text.push_str("\"\"\"");
+                    chars.nth(1);
+                    col += 3;
+                    state = State::TripleStr;
+                    continue;
+                }
+                '"' => {
+                    str_start = (line, col - string_prefix_len(&text));
+                    state = State::Str;
+                }
+                '/' if matches!(chars.peek(), Some((_, '/'))) => state = State::LineComment,
+                '/' if matches!(chars.peek(), Some((_, '*'))) => {
+                    text.push(c);
+                    text.push(chars.next().unwrap().1);
+                    col += 2;
+                    state = State::BlockComment;
+                    continue;
+                }
+                _ => {}
+            },
+            State::Str => {
+                if c == '"' {
+                    state = State::Code;
+                }
+            }
+            State::TripleStr => {
+                if body[pos..].starts_with("\"\"\"") {
+                    text.push_str("\"\"\"");
+                    chars.nth(1);
+                    col += 3;
+                    state = State::Code;
+                    continue;
+                }
+            }
+            State::LineComment => {
+                if c == '\n' {
+                    state = State::Code;
+                }
+            }
+            State::BlockComment => {
+                if c == '*' && matches!(chars.peek(), Some((_, '/'))) {
+                    text.push(c);
+                    text.push(chars.next().unwrap().1);
+                    col += 2;
+                    state = State::Code;
+                    continue;
+                }
+            }
+        }
+
+        text.push(c);
+        if c == '\n' {
+            line += 1;
+            col = 1;
+            at_line_start = true;
+        } else {
+            col += 1;
+        }
+    }
+
+    match state {
+        State::Str => return Err(format!(
+            "{}:{}: unterminated string literal; close it with \"",
+            str_start.0, str_start.1
+        )),
+        State::TripleStr => return Err(format!(
+            "{}:{}: unterminated triple-quoted string literal; close it with \"\"\"",
+            str_start.0, str_start.1
+        )),
+        _ => {}
+    }
+
+    Ok(Preprocessed {
+        text,
+        map: SourceMap { bom_len, removed_cr },
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bom_stripped() {
+        let src = "\u{FEFF}let x: int = 1;\n";
+        let out = preprocess(src).unwrap();
+        assert_eq!(out.text, "let x: int = 1;\n");
+        assert!(out.map.had_bom());
+        // "x" 在预处理文本中的偏移 4，对应原文偏移 7
+        assert_eq!(out.map.original_offset(4), 7);
+        assert_eq!(&src[7..8], "x");
+    }
+
+    #[test]
+    fn test_crlf_offsets() {
+        let src = "let a = 1;\r\nlet b = 2;\r\nlet c = 3;\r\n";
+        let out = preprocess(src).unwrap();
+        assert_eq!(out.text, "let a = 1;\nlet b = 2;\nlet c = 3;\n");
+        for (i, ch) in out.text.char_indices() {
+            let orig = out.map.original_offset(i);
+            assert_eq!(src[orig..].chars().next(), Some(ch), "offset {}", i);
+        }
+    }
+
+    #[test]
+    fn test_bidi_rejected() {
+        let src = "let ok: bool = true;\nlet x: int = 1; // \u{202E} evil\n";
+        let err = preprocess(src).err().unwrap();
+        assert!(err.starts_with("2:20:"), "{}", err);
+        assert!(err.contains("U+202E"), "{}", err);
+    }
+
+    #[test]
+    fn test_bidi_allowed_in_string() {
+        let src = "let s: str = \"\u{202E}abc\u{2028}\";\n";
+        assert!(preprocess(src).is_ok());
+    }
+
+    #[test]
+    fn test_line_separator_rejected() {
+        let err = preprocess("let x: int = 1;\u{2028}let y: int = 2;").err().unwrap();
+        assert!(err.contains("U+2028"), "{}", err);
+    }
+
+    #[test]
+    fn test_mixed_indent_warning() {
+        let src = "fn f() {\n \tlet x: int = 1;\n    let y: int = 2;\n\tlet z: int = 3;\n}\n";
+        let out = preprocess(src).unwrap();
+        assert_eq!(out.warnings.len(), 1);
+        assert!(out.warnings[0].starts_with("2:1:"), "{}", out.warnings[0]);
+    }
+
+    #[test]
+    fn test_unterminated_string_position() {
+        // 指向开头的引号（或 r / f 前缀），不是文件末尾
+        for (source, expected) in [
+            ("let a: int = 1;\nlet s: str = \"\"\"\n    abc \"x\"\nlet b: int = 2;\n", "2:14: unterminated triple-quoted"),
+            ("let s: str = f\"\"\"{a}\n", "1:14: unterminated triple-quoted"),
+            ("let a: int = 1;\n    let s: str = r\"C:\\;\n", "2:18: unterminated string literal"),
+        ] {
+            let err = crate::parse_source(source).err().unwrap();
+            assert!(err.to_string().starts_with(expected), "{}", err);
+        }
+        assert!(preprocess("let s: str = \"\"\"a\"b\"\"\";\nlet t: str = \"\";\n").is_ok());
+    }
+
+    #[test]
+    fn test_crlf_error_position() {
+        // CRLF 文件里的语法错误仍指向正确的行列
+        let src = "let a: int = 1;\r\nlet b: int = 2;\r\nlet c: int = ;\r\n";
+        let err = crate::parse_source(src).err().unwrap();
+        assert_eq!((err.line, err.col), (3, 14), "{}", err);
+    }
+
+    #[test]
+    fn test_crlf_tab_error_span() {
+        // 行首的 tab 按一列计；下划线的字节范围经 SourceMap 换算回原文（BOM 和每行的 \r）
+        let src = "\u{FEFF}fn f() {\r\n\tlet a: int = 1;\r\n\tlet b: int = a $ 2;\r\n}\r\n";
+        let err = crate::parse_source(src).err().unwrap();
+        assert_eq!((err.line, err.col), (3, 17), "{}", err);
+        assert_eq!(&src[err.span()], "$");
+        assert_eq!(err.snippet, "\tlet b: int = a $ 2;");
+    }
+
+    #[test]
+    fn test_bom_file_parses() {
+        let src = "\u{FEFF}let a: int = 1;\r\nprint(a);\r\n";
+        let program = crate::parse_source(src).unwrap();
+        assert_eq!(program.statements.len(), 2);
+    }
+}
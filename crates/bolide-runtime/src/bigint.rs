@@ -4,10 +4,12 @@
 
 use num_bigint::BigInt;
 use num_traits::{Zero, Signed, ToPrimitive};
+use std::borrow::Cow;
 use std::cell::Cell;
 use std::sync::atomic::{AtomicI64, Ordering};
 
 use crate::rc::{TypeTag, flags};
+use crate::output::outln;
 
 // Debug: 跟踪分配和释放
 static BIGINT_ALLOC_COUNT: AtomicI64 = AtomicI64::new(0);
@@ -126,7 +128,10 @@ pub extern "C" fn bolide_bigint_from_i64(value: i64) -> *mut BolideBigInt {
 pub extern "C" fn bolide_bigint_from_str(s: *const i8, len: usize) -> *mut BolideBigInt {
     let slice = unsafe { std::slice::from_raw_parts(s as *const u8, len) };
     let s = std::str::from_utf8(slice).unwrap_or("");
-    BolideBigInt::from_str(s).unwrap_or(std::ptr::null_mut())
+    BolideBigInt::from_str(s).unwrap_or_else(|| {
+        crate::set_error(crate::ERROR_VALUE, &format!("bigint(): invalid literal '{}'", s));
+        std::ptr::null_mut()
+    })
 }
 
 /// 增加引用计数
@@ -205,11 +210,17 @@ pub extern "C" fn bolide_bigint_mul(a: *const BolideBigInt, b: *const BolideBigI
     BolideBigInt::from_bigint(&a.inner * &b.inner)
 }
 
+/// 除以零时记录 `ERROR_ZERO_DIVISION` 并返回空指针
+fn division_by_zero(op: &str) -> *mut BolideBigInt {
+    crate::set_error(crate::ERROR_ZERO_DIVISION, &format!("bigint {}: division by zero", op));
+    std::ptr::null_mut()
+}
+
 #[no_mangle]
 pub extern "C" fn bolide_bigint_div(a: *const BolideBigInt, b: *const BolideBigInt) -> *mut BolideBigInt {
     if a.is_null() || b.is_null() { return std::ptr::null_mut(); }
     let (a, b) = unsafe { (&*a, &*b) };
-    if b.is_zero() { return std::ptr::null_mut(); }
+    if b.is_zero() { return division_by_zero("/"); }
     BolideBigInt::from_bigint(&a.inner / &b.inner)
 }
 
@@ -217,7 +228,7 @@ pub extern "C" fn bolide_bigint_div(a: *const BolideBigInt, b: *const BolideBigI
 pub extern "C" fn bolide_bigint_rem(a: *const BolideBigInt, b: *const BolideBigInt) -> *mut BolideBigInt {
     if a.is_null() || b.is_null() { return std::ptr::null_mut(); }
     let (a, b) = unsafe { (&*a, &*b) };
-    if b.is_zero() { return std::ptr::null_mut(); }
+    if b.is_zero() { return division_by_zero("%"); }
     BolideBigInt::from_bigint(&a.inner % &b.inner)
 }
 
@@ -272,6 +283,85 @@ pub extern "C" fn bolide_bigint_ge(a: *const BolideBigInt, b: *const BolideBigIn
     if a.inner >= b.inner { 1 } else { 0 }
 }
 
+// ==================== 原地运算（累加循环优化）====================
+//
+// `v = v <op> rhs` 由编译器改写为 `v = bolide_bigint_<op>_assign(v, rhs)`。
+// 调用者把 dst 的一个引用交给函数，并接收返回的对象：
+// - dst 的引用计数为 1 时直接修改并返回 dst，不分配
+// - 否则释放调用者持有的那个引用，返回新分配的结果，其他持有者看到的值不变
+
+/// 原地修改 dst（共享时先复制）
+unsafe fn bigint_assign_with(dst: *mut BolideBigInt, op: impl FnOnce(&mut BigInt)) -> *mut BolideBigInt {
+    if (*dst).ref_count() == 1 {
+        op(&mut (*dst).inner);
+        return dst;
+    }
+    let mut value = (*dst).inner.clone();
+    op(&mut value);
+    bolide_bigint_release(dst);
+    BolideBigInt::from_bigint(value)
+}
+
+/// 右操作数；src 就是 dst 时（v = v + v）先复制一份，避免读写别名
+unsafe fn assign_operand<'a>(dst: *mut BolideBigInt, src: *const BolideBigInt) -> Cow<'a, BigInt> {
+    if std::ptr::eq(dst, src) {
+        Cow::Owned((*src).inner.clone())
+    } else {
+        Cow::Borrowed(&(*src).inner)
+    }
+}
+
+/// dst += src，返回新的 dst
+#[no_mangle]
+pub extern "C" fn bolide_bigint_add_assign(dst: *mut BolideBigInt, src: *const BolideBigInt) -> *mut BolideBigInt {
+    if dst.is_null() || src.is_null() { return dst; }
+    unsafe {
+        let rhs = assign_operand(dst, src);
+        bigint_assign_with(dst, |v| *v += rhs.as_ref())
+    }
+}
+
+/// dst += n，返回新的 dst
+#[no_mangle]
+pub extern "C" fn bolide_bigint_add_assign_i64(dst: *mut BolideBigInt, n: i64) -> *mut BolideBigInt {
+    if dst.is_null() { return dst; }
+    unsafe { bigint_assign_with(dst, |v| *v += n) }
+}
+
+/// dst -= src，返回新的 dst
+#[no_mangle]
+pub extern "C" fn bolide_bigint_sub_assign(dst: *mut BolideBigInt, src: *const BolideBigInt) -> *mut BolideBigInt {
+    if dst.is_null() || src.is_null() { return dst; }
+    unsafe {
+        let rhs = assign_operand(dst, src);
+        bigint_assign_with(dst, |v| *v -= rhs.as_ref())
+    }
+}
+
+/// dst -= n，返回新的 dst
+#[no_mangle]
+pub extern "C" fn bolide_bigint_sub_assign_i64(dst: *mut BolideBigInt, n: i64) -> *mut BolideBigInt {
+    if dst.is_null() { return dst; }
+    unsafe { bigint_assign_with(dst, |v| *v -= n) }
+}
+
+/// dst *= src，返回新的 dst
+#[no_mangle]
+pub extern "C" fn bolide_bigint_mul_assign(dst: *mut BolideBigInt, src: *const BolideBigInt) -> *mut BolideBigInt {
+    if dst.is_null() || src.is_null() { return dst; }
+    unsafe {
+        let rhs = assign_operand(dst, src);
+        bigint_assign_with(dst, |v| *v *= rhs.as_ref())
+    }
+}
+
+/// dst *= n，返回新的 dst
+#[no_mangle]
+pub extern "C" fn bolide_bigint_mul_assign_i64(dst: *mut BolideBigInt, n: i64) -> *mut BolideBigInt {
+    if dst.is_null() { return dst; }
+    unsafe { bigint_assign_with(dst, |v| *v *= n) }
+}
+
 // ==================== Debug Stats ====================
 
 /// 打印 BigInt 内存统计
@@ -279,7 +369,12 @@ pub extern "C" fn bolide_bigint_ge(a: *const BolideBigInt, b: *const BolideBigIn
 pub extern "C" fn bolide_bigint_debug_stats() {
     let alloc = BIGINT_ALLOC_COUNT.load(Ordering::SeqCst);
     let free = BIGINT_FREE_COUNT.load(Ordering::SeqCst);
-    println!("[BigInt Stats] alloc: {}, free: {}, leak: {}", alloc, free, alloc - free);
+    outln!("[BigInt Stats] alloc: {}, free: {}, leak: {}", alloc, free, alloc - free);
+}
+
+/// 累计分配次数（measure 块统计分配增量用）
+pub(crate) fn bigint_alloc_count() -> i64 {
+    BIGINT_ALLOC_COUNT.load(Ordering::SeqCst)
 }
 
 /// 重置统计计数器
@@ -325,4 +420,43 @@ mod tests {
             bolide_bigint_release(c);
         }
     }
+
+    #[test]
+    fn test_bigint_add_assign_in_place() {
+        let total = BolideBigInt::new(0);
+        let mut acc = total;
+        for i in 1..=100 {
+            acc = bolide_bigint_add_assign_i64(acc, i);
+        }
+        // 引用计数为 1 时不分配新对象
+        assert_eq!(acc, total);
+        let two = BolideBigInt::new(2);
+        acc = bolide_bigint_mul_assign(acc, two);
+        acc = bolide_bigint_add_assign(acc, acc);
+        unsafe {
+            assert_eq!((*acc).to_i64(), Some(20200));
+        }
+        bolide_bigint_release(two);
+        bolide_bigint_release(acc);
+    }
+
+    #[test]
+    fn test_bigint_assign_shared_copies() {
+        let acc = BolideBigInt::new(10);
+        // 另一个持有者
+        let other = bolide_bigint_retain(acc);
+
+        let acc2 = bolide_bigint_add_assign_i64(acc, 5);
+        assert_ne!(acc2, other);
+        let acc3 = bolide_bigint_sub_assign_i64(acc2, 1);
+        // 第二次已是独占，原地修改
+        assert_eq!(acc3, acc2);
+        unsafe {
+            assert_eq!((*acc3).to_i64(), Some(14));
+            assert_eq!((*other).to_i64(), Some(10));
+            assert_eq!((*other).ref_count(), 1);
+        }
+        bolide_bigint_release(acc3);
+        bolide_bigint_release(other);
+    }
 }
@@ -1,14 +1,26 @@
 //! Bolide 通道运行时
 //!
 //! 提供线程安全的通道实现，用于线程间通信
+//!
+//! 阻塞的收发经过 `executor::blocking`：在协程工作线程上等待时执行器补一个线程，
+//! 对面的协程即使还在排队也能运行。
+//!
+//! 通道只搬运 i64。RC 值（str、list、对象等）经 `bolide_channel_send_ptr` / `send_all`
+//! 入队时 retain 一次，这份引用由通道持有，接收方取走后归接收方；
+//! 通道释放时仍在队列中的值按记录的类型标签释放。
 
 use std::sync::{Arc, Mutex, Condvar};
 use std::collections::VecDeque;
 
+use crate::executor;
+use crate::list::{bolide_list_new, BolideList};
+
 /// 通道内部状态（单个 Mutex 保护，保证原子性）
 struct ChannelInner {
     queue: VecDeque<i64>,
     closed: bool,
+    /// 队列中 RC 值的类型标签（见 `closure::retain_tagged`），只搬运整数时为 None
+    payload_tag: Option<u8>,
 }
 
 /// Select 通知器（用于事件驱动的 select）
@@ -34,7 +46,7 @@ impl SelectNotifier {
     /// 等待通知（带超时）
     pub fn wait_timeout(&self, timeout: std::time::Duration) -> bool {
         let guard = self.mutex.lock().unwrap();
-        let result = self.condvar.wait_timeout(guard, timeout).unwrap();
+        let result = executor::blocking(|| self.condvar.wait_timeout(guard, timeout)).unwrap();
         !result.1.timed_out()
     }
 }
@@ -68,6 +80,7 @@ impl BolideChannel {
             inner: Mutex::new(ChannelInner {
                 queue: VecDeque::new(),
                 closed: false,
+                payload_tag: None,
             }),
             condvar: Condvar::new(),
             capacity: 0,
@@ -81,6 +94,7 @@ impl BolideChannel {
             inner: Mutex::new(ChannelInner {
                 queue: VecDeque::with_capacity(capacity),
                 closed: false,
+                payload_tag: None,
             }),
             condvar: Condvar::new(),
             capacity,
@@ -103,7 +117,7 @@ impl BolideChannel {
                 if inner.closed {
                     return false;
                 }
-                inner = self.condvar.wait(inner).unwrap();
+                inner = executor::blocking(|| self.condvar.wait(inner)).unwrap();
             }
         }
 
@@ -127,7 +141,7 @@ impl BolideChannel {
                 return None;
             }
 
-            inner = self.condvar.wait(inner).unwrap();
+            inner = executor::blocking(|| self.condvar.wait(inner)).unwrap();
         }
     }
 
@@ -141,6 +155,68 @@ impl BolideChannel {
         value
     }
 
+    /// 批量发送（阻塞）
+    ///
+    /// 整批在一次加锁中入队；有容量限制时按剩余空间分块，满了就等待接收方腾出空间。
+    /// 返回实际入队的数量，通道关闭时停止，小于 `values.len()` 即表示关闭
+    pub fn send_all(&self, values: &[i64]) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let mut sent = 0;
+
+        while sent < values.len() {
+            if inner.closed {
+                break;
+            }
+
+            let room = if self.capacity > 0 {
+                self.capacity.saturating_sub(inner.queue.len())
+            } else {
+                values.len() - sent
+            };
+            if room == 0 {
+                inner = executor::blocking(|| self.condvar.wait(inner)).unwrap();
+                continue;
+            }
+
+            let chunk = &values[sent..(sent + room).min(values.len())];
+            inner.queue.extend(chunk.iter().copied());
+            sent += chunk.len();
+            // 同一个条件变量上可能同时有接收方和其他发送方在等，整块入队后全部唤醒
+            self.condvar.notify_all();
+            self.select_notifier.notify();
+        }
+
+        sent
+    }
+
+    /// 批量接收（阻塞）
+    ///
+    /// 等到至少有一条消息或通道关闭，然后在同一次加锁中取走最多 `n` 条交给 `sink`。
+    /// 返回取到的数量，0 表示通道已关闭且为空（`n` 为 0 时也立即返回 0）
+    pub fn recv_n(&self, n: usize, mut sink: impl FnMut(i64)) -> usize {
+        if n == 0 {
+            return 0;
+        }
+        let mut inner = self.inner.lock().unwrap();
+
+        loop {
+            if !inner.queue.is_empty() {
+                let count = n.min(inner.queue.len());
+                for value in inner.queue.drain(..count) {
+                    sink(value);
+                }
+                self.condvar.notify_all();
+                return count;
+            }
+
+            if inner.closed {
+                return 0;
+            }
+
+            inner = executor::blocking(|| self.condvar.wait(inner)).unwrap();
+        }
+    }
+
     /// 关闭通道
     pub fn close(&self) {
         let mut inner = self.inner.lock().unwrap();
@@ -153,6 +229,23 @@ impl BolideChannel {
     pub fn is_closed(&self) -> bool {
         self.inner.lock().unwrap().closed
     }
+
+    /// 记录队列中 RC 值的类型，通道释放时据此释放未取走的值
+    fn set_payload_tag(&self, tag: u8) {
+        self.inner.lock().unwrap().payload_tag = Some(tag);
+    }
+}
+
+impl Drop for BolideChannel {
+    /// 释放仍在队列中的 RC 值（关闭不会丢弃消息，接收方可以继续取完）
+    fn drop(&mut self) {
+        let inner = self.inner.get_mut().unwrap_or_else(|e| e.into_inner());
+        if let Some(tag) = inner.payload_tag {
+            for value in inner.queue.drain(..) {
+                unsafe { crate::closure::release_tagged(value, tag); }
+            }
+        }
+    }
 }
 
 impl Default for BolideChannel {
@@ -187,6 +280,34 @@ pub extern "C" fn bolide_channel_send(channel: *mut BolideChannel, value: i64) -
     if channel.send(value) { 1 } else { 0 }
 }
 
+/// 发送 RC 值（阻塞）：入队前按 `tag` retain 一次，这份引用由通道持有、接收方取走
+///
+/// 返回 1 表示成功，0 表示通道已关闭（此时撤销 retain，值不受影响）
+#[no_mangle]
+pub extern "C" fn bolide_channel_send_ptr(channel: *mut BolideChannel, value: i64, tag: u8) -> i64 {
+    if channel.is_null() {
+        return 0;
+    }
+
+    let channel = unsafe { &*channel };
+    channel.set_payload_tag(tag);
+    // 先 retain 再入队：入队后接收方随时可能取走并释放
+    unsafe { crate::closure::retain_tagged(value, tag); }
+    if channel.send(value) {
+        1
+    } else {
+        unsafe { crate::closure::release_tagged(value, tag); }
+        0
+    }
+}
+
+/// 接收 `bolide_channel_send_ptr` 发送的 RC 值（阻塞），返回的引用归调用者
+/// 如果通道已关闭且为空，返回 0（空指针）
+#[no_mangle]
+pub extern "C" fn bolide_channel_recv_ptr(channel: *mut BolideChannel) -> i64 {
+    bolide_channel_recv(channel)
+}
+
 /// 从通道接收消息（阻塞）
 /// 如果通道已关闭且为空，返回 0
 #[no_mangle]
@@ -199,6 +320,30 @@ pub extern "C" fn bolide_channel_recv(channel: *mut BolideChannel) -> i64 {
     channel.recv().unwrap_or(0)
 }
 
+/// 从通道接收消息（阻塞），用于 `for msg in ch`：收到值时写入 `*out` 并返回 1，
+/// 通道已关闭且为空时返回 0（`*out` 不变）
+#[no_mangle]
+pub extern "C" fn bolide_channel_recv_opt(channel: *mut BolideChannel, out: *mut i64) -> i64 {
+    if channel.is_null() {
+        return 0;
+    }
+
+    let channel = unsafe { &*channel };
+    match channel.recv() {
+        Some(value) => {
+            unsafe { *out = value; }
+            1
+        }
+        None => 0,
+    }
+}
+
+/// `bolide_channel_recv_opt` 的 RC 版本：写入的引用归调用者
+#[no_mangle]
+pub extern "C" fn bolide_channel_recv_opt_ptr(channel: *mut BolideChannel, out: *mut i64) -> i64 {
+    bolide_channel_recv_opt(channel, out)
+}
+
 /// 尝试从通道接收消息（非阻塞）
 /// 成功时 *success = 1，失败时 *success = 0
 #[no_mangle]
@@ -230,6 +375,55 @@ pub extern "C" fn bolide_channel_try_recv(
     }
 }
 
+/// 批量发送列表的全部元素（阻塞）
+///
+/// 列表是借用的：RC 元素（按列表的元素类型）入队前各 retain 一次，由通道持有，
+/// 接收方取走后归接收方。返回入队的数量，小于列表长度表示中途遇到通道关闭，
+/// 未入队的元素不受影响
+#[no_mangle]
+pub extern "C" fn bolide_channel_send_all(channel: *mut BolideChannel, list: *const BolideList) -> i64 {
+    if channel.is_null() || list.is_null() {
+        return 0;
+    }
+
+    let channel = unsafe { &*channel };
+    let list = unsafe { &*list };
+    let values = list.as_slice();
+    channel.set_payload_tag(list.elem_type() as u8);
+    // 先 retain 再入队：入队后接收方随时可能取走并释放
+    for &value in values {
+        unsafe { list.retain_element(value); }
+    }
+    let sent = channel.send_all(values);
+    for &value in &values[sent..] {
+        unsafe { list.release_element(value); }
+    }
+    sent as i64
+}
+
+/// 批量接收最多 n 条消息追加到 out_list（阻塞到至少有一条或通道关闭）
+///
+/// 取出的消息带着发送时的引用，直接交给 out_list 持有。
+/// 返回接收的数量，0 表示通道已关闭且为空
+#[no_mangle]
+pub extern "C" fn bolide_channel_recv_n(channel: *mut BolideChannel, n: i64, out_list: *mut BolideList) -> i64 {
+    if channel.is_null() || out_list.is_null() || n <= 0 {
+        return 0;
+    }
+
+    let channel = unsafe { &*channel };
+    let out = unsafe { &mut *out_list };
+    channel.recv_n(n as usize, |value| out.push_owned(value)) as i64
+}
+
+/// 接收直到通道关闭且为空，返回包含全部消息的新列表（元素类型为 elem_type）
+#[no_mangle]
+pub extern "C" fn bolide_channel_drain(channel: *mut BolideChannel, elem_type: u8) -> *mut BolideList {
+    let list = bolide_list_new(elem_type);
+    while bolide_channel_recv_n(channel, i64::MAX, list) > 0 {}
+    list
+}
+
 /// 关闭通道
 #[no_mangle]
 pub extern "C" fn bolide_channel_close(channel: *mut BolideChannel) {
@@ -264,81 +458,65 @@ pub extern "C" fn bolide_channel_free(channel: *mut BolideChannel) {
 
 use std::time::{Duration, Instant};
 
-/// Select 操作：同时等待多个 channel
-/// channels: channel 指针数组
-/// count: channel 数量
-/// timeout_ms: 超时时间（毫秒），-1 表示无超时，-2 表示有 default（非阻塞）
-/// value: 输出参数，接收到的值
-/// 返回值: 选中的 channel 索引，-1 表示超时，-2 表示 default 被选中
-#[no_mangle]
-pub extern "C" fn bolide_channel_select(
-    channels: *const *mut BolideChannel,
-    count: i64,
-    timeout_ms: i64,
-    value: *mut i64,
-) -> i64 {
-    if channels.is_null() || count <= 0 {
-        return -1;
-    }
-
-    let channel_slice = unsafe {
-        std::slice::from_raw_parts(channels, count as usize)
-    };
-
-    // 收集有效的 channel 引用
-    let channel_refs: Vec<&BolideChannel> = channel_slice
-        .iter()
-        .filter_map(|&ptr| {
-            if ptr.is_null() { None } else { Some(unsafe { &*ptr }) }
-        })
-        .collect();
+/// 动态 select（`select from chans`）在所有 channel 都已关闭（或列表为空）时的返回值
+pub const SELECT_ALL_CLOSED: i64 = -3;
+
+/// 一次 select 的结果
+enum Selected {
+    /// 第几个 channel 收到了值
+    Received(usize, i64),
+    Timeout,
+    Default,
+    /// 所有 channel 都已关闭且为空
+    Closed,
+}
 
-    if channel_refs.is_empty() {
-        return -1;
+/// 等待 `channels` 中任意一个收到值；`timeout_ns` 的含义见 `bolide_channel_select`
+///
+/// 索引是 `channels` 里的 `usize`，空指针已经由调用方去掉。
+fn select_on(channels: &[(usize, &BolideChannel)], timeout_ns: i64) -> Selected {
+    if channels.is_empty() {
+        return Selected::Closed;
     }
 
-    let has_default = timeout_ms == -2;
-    let has_timeout = timeout_ms >= 0;
+    let has_default = timeout_ns == -2;
+    let has_timeout = timeout_ns >= 0;
     let deadline = if has_timeout {
-        Some(Instant::now() + Duration::from_millis(timeout_ms as u64))
+        Some(Instant::now() + Duration::from_nanos(timeout_ns as u64))
     } else {
         None
     };
 
     loop {
         // 尝试从每个 channel 非阻塞接收
-        for (idx, ch) in channel_refs.iter().enumerate() {
+        for &(idx, ch) in channels {
             if let Some(val) = ch.try_recv() {
-                if !value.is_null() {
-                    unsafe { *value = val; }
-                }
-                return idx as i64;
+                return Selected::Received(idx, val);
             }
         }
 
         // 如果有 default 分支，立即返回
         if has_default {
-            return -2;
+            return Selected::Default;
         }
 
         // 检查超时
         if let Some(dl) = deadline {
             if Instant::now() >= dl {
-                return -1;  // 超时
+                return Selected::Timeout;
             }
         }
 
         // 检查是否所有 channel 都已关闭
-        let all_closed = channel_refs.iter().all(|ch| ch.is_closed());
-        if all_closed {
-            return -1;
+        if channels.iter().all(|(_, ch)| ch.is_closed()) {
+            return Selected::Closed;
         }
 
         // 事件驱动等待：等待任意 channel 有数据
         let wait_duration = if let Some(dl) = deadline {
             let remaining = dl.saturating_duration_since(Instant::now());
             if remaining.is_zero() {
-                return -1;  // 超时
+                return Selected::Timeout;
             }
             remaining
         } else {
@@ -348,3 +526,355 @@ pub extern "C" fn bolide_channel_select(
         GLOBAL_SELECT_NOTIFIER.wait_timeout(wait_duration);
     }
 }
+
+/// 把 select 的结果写到输出参数并换算成返回值；`closed` 是所有 channel 都关闭时的返回值
+fn selected_index(selected: Selected, value: *mut i64, closed: i64) -> i64 {
+    match selected {
+        Selected::Received(idx, val) => {
+            if !value.is_null() {
+                unsafe { *value = val; }
+            }
+            idx as i64
+        }
+        Selected::Timeout => -1,
+        Selected::Default => -2,
+        Selected::Closed => closed,
+    }
+}
+
+/// Select 操作：同时等待多个 channel
+/// channels: channel 指针数组
+/// count: channel 数量
+/// timeout_ns: 超时时间（纳秒，即 `duration` 的表示），-1 表示无超时，-2 表示有 default（非阻塞）
+/// value: 输出参数，接收到的值
+/// 返回值: 选中的 channel 索引，-1 表示超时或所有 channel 都已关闭，-2 表示 default 被选中
+#[no_mangle]
+pub extern "C" fn bolide_channel_select(
+    channels: *const *mut BolideChannel,
+    count: i64,
+    timeout_ns: i64,
+    value: *mut i64,
+) -> i64 {
+    if channels.is_null() || count <= 0 {
+        return -1;
+    }
+
+    let channel_slice = unsafe {
+        std::slice::from_raw_parts(channels, count as usize)
+    };
+
+    // 收集有效的 channel 引用
+    let channel_refs: Vec<(usize, &BolideChannel)> = channel_slice
+        .iter()
+        .filter_map(|&ptr| unsafe { ptr.as_ref() })
+        .enumerate()
+        .collect();
+
+    selected_index(select_on(&channel_refs, timeout_ns), value, -1)
+}
+
+/// 动态 select：等待 `list<channel<T>>` 中任意一个 channel
+///
+/// 每次调用时读取列表当前的元素，两次 select 之间可以增删 channel。
+/// 返回收到值的 channel 在列表中的索引；-1 超时，-2 default，
+/// 所有 channel 都已关闭且为空（或列表为空）时返回 `SELECT_ALL_CLOSED`。
+#[no_mangle]
+pub extern "C" fn bolide_channel_select_list(
+    channels: *const BolideList,
+    timeout_ns: i64,
+    value: *mut i64,
+) -> i64 {
+    let Some(list) = (unsafe { channels.as_ref() }) else {
+        return SELECT_ALL_CLOSED;
+    };
+    let channel_refs: Vec<(usize, &BolideChannel)> = list.as_slice()
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, &ptr)| unsafe { (ptr as *const BolideChannel).as_ref() }.map(|ch| (idx, ch)))
+        .collect();
+
+    selected_index(select_on(&channel_refs, timeout_ns), value, SELECT_ALL_CLOSED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::list::{bolide_list_get, bolide_list_len, bolide_list_push, bolide_list_release, ElementType};
+    use crate::{bolide_string_from_int, bolide_string_ref_count, bolide_string_release};
+
+    fn int_list(values: &[i64]) -> *mut BolideList {
+        let list = bolide_list_new(ElementType::Int as u8);
+        for &v in values {
+            bolide_list_push(list, v);
+        }
+        list
+    }
+
+    fn contents(list: *mut BolideList) -> Vec<i64> {
+        (0..bolide_list_len(list)).map(|i| bolide_list_get(list, i)).collect()
+    }
+
+    #[test]
+    fn test_partial_receive() {
+        let ch = bolide_channel_create();
+        let xs = int_list(&[1, 2, 3, 4, 5]);
+        assert_eq!(bolide_channel_send_all(ch, xs), 5);
+
+        let out = bolide_list_new(ElementType::Int as u8);
+        assert_eq!(bolide_channel_recv_n(ch, 2, out), 2);
+        assert_eq!(contents(out), [1, 2]);
+        // 只取当前可用的，不等凑满 n 条
+        assert_eq!(bolide_channel_recv_n(ch, 10, out), 3);
+        assert_eq!(contents(out), [1, 2, 3, 4, 5]);
+        assert_eq!(bolide_channel_recv_n(ch, 0, out), 0);
+
+        bolide_channel_close(ch);
+        assert_eq!(bolide_channel_recv_n(ch, 10, out), 0);
+
+        bolide_list_release(xs);
+        bolide_list_release(out);
+        bolide_channel_free(ch);
+    }
+
+    #[test]
+    fn test_capacity_chunking() {
+        let ch = bolide_channel_create_buffered(3);
+        let addr = ch as usize;
+        let values: Vec<i64> = (0..100).collect();
+        let xs = int_list(&values);
+        let xs_addr = xs as usize;
+
+        let sender = std::thread::spawn(move || {
+            bolide_channel_send_all(addr as *mut BolideChannel, xs_addr as *const BolideList)
+        });
+
+        let out = bolide_list_new(ElementType::Int as u8);
+        while bolide_list_len(out) < 100 {
+            let got = bolide_channel_recv_n(ch, 7, out);
+            // 队列里永远不会超过容量
+            assert!((1..=3).contains(&got), "got {}", got);
+        }
+        assert_eq!(sender.join().unwrap(), 100);
+        assert_eq!(contents(out), values);
+
+        bolide_list_release(xs);
+        bolide_list_release(out);
+        bolide_channel_free(ch);
+    }
+
+    #[test]
+    fn test_recv_opt_until_closed() {
+        let ch = bolide_channel_create_buffered(4);
+        let addr = ch as usize;
+        let sender = std::thread::spawn(move || {
+            let ch = addr as *mut BolideChannel;
+            for i in 0..100 {
+                bolide_channel_send(ch, i);
+            }
+            bolide_channel_close(ch);
+        });
+
+        let mut sum = 0;
+        let mut value = -1;
+        while bolide_channel_recv_opt(ch, &mut value) == 1 {
+            sum += value;
+        }
+        assert_eq!(sum, 4950);
+        // 关闭并取空后不再写入
+        value = -1;
+        assert_eq!(bolide_channel_recv_opt(ch, &mut value), 0);
+        assert_eq!(value, -1);
+        sender.join().unwrap();
+        bolide_channel_free(ch);
+    }
+
+    #[test]
+    fn test_close_wakes_blocked_receivers() {
+        let ch = bolide_channel_create();
+        let addr = ch as usize;
+        let receivers: Vec<_> = (0..3).map(|_| std::thread::spawn(move || {
+            let mut value = 0;
+            bolide_channel_recv_opt(addr as *mut BolideChannel, &mut value)
+        })).collect();
+
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        bolide_channel_close(ch);
+        for receiver in receivers {
+            assert_eq!(receiver.join().unwrap(), 0);
+        }
+        bolide_channel_free(ch);
+    }
+
+    #[test]
+    fn test_closed_mid_batch() {
+        let ch = bolide_channel_create_buffered(4);
+        let addr = ch as usize;
+        let strings: Vec<i64> = (0..10).map(|i| bolide_string_from_int(i) as i64).collect();
+        let xs = bolide_list_new(ElementType::String as u8);
+        for &s in &strings {
+            bolide_list_push(xs, s);
+            bolide_string_release(s as *mut _);
+        }
+        let xs_addr = xs as usize;
+
+        let sender = std::thread::spawn(move || {
+            bolide_channel_send_all(addr as *mut BolideChannel, xs_addr as *const BolideList)
+        });
+
+        // 取走第一块后关闭：发送方等空间时发现关闭，停在已入队的位置
+        let out = bolide_list_new(ElementType::String as u8);
+        while bolide_list_len(out) < 4 {
+            bolide_channel_recv_n(ch, 4, out);
+        }
+        bolide_channel_close(ch);
+        let sent = sender.join().unwrap() as usize;
+        assert!((4..10).contains(&sent), "sent {}", sent);
+
+        // 关闭前已入队的仍可取走；每个字符串恰好由 xs 和 out 各持有一次，未发送的只在 xs 里
+        let drained = bolide_channel_drain(ch, ElementType::String as u8);
+        assert_eq!(bolide_list_len(out) + bolide_list_len(drained), sent);
+        for (i, &s) in strings.iter().enumerate() {
+            let expected = if i < sent { 2 } else { 1 };
+            assert_eq!(bolide_string_ref_count(s as *const _), expected, "string {}", i);
+        }
+
+        bolide_list_release(out);
+        bolide_list_release(drained);
+        for &s in &strings[..sent] {
+            assert_eq!(bolide_string_ref_count(s as *const _), 1);
+        }
+        bolide_list_release(xs);
+        bolide_channel_free(ch);
+    }
+
+    #[test]
+    fn test_send_ptr_hands_ownership_to_receiver() {
+        let ch = bolide_channel_create_buffered(8);
+        let addr = ch as usize;
+        let tag = ElementType::String as u8;
+
+        // 发送方发完就释放自己的引用，接收方拿到的仍然有效
+        let sender = std::thread::spawn(move || {
+            let ch = addr as *mut BolideChannel;
+            for i in 0..1000 {
+                let s = bolide_string_from_int(i);
+                assert_eq!(bolide_channel_send_ptr(ch, s as i64, tag), 1);
+                bolide_string_release(s);
+            }
+        });
+
+        let received: Vec<_> = (0..1000)
+            .map(|_| bolide_channel_recv_ptr(ch) as *mut crate::BolideString)
+            .collect();
+        sender.join().unwrap();
+        for (i, &s) in received.iter().enumerate() {
+            assert_eq!(unsafe { (*s).as_str() }, i.to_string());
+            assert_eq!(bolide_string_ref_count(s), 1);
+            bolide_string_release(s);
+        }
+
+        // 关闭后发送失败，值不受影响
+        bolide_channel_close(ch);
+        let s = bolide_string_from_int(7);
+        assert_eq!(bolide_channel_send_ptr(ch, s as i64, tag), 0);
+        assert_eq!(bolide_string_ref_count(s), 1);
+        bolide_string_release(s);
+        bolide_channel_free(ch);
+    }
+
+    #[test]
+    fn test_free_releases_unreceived_payloads() {
+        let ch = bolide_channel_create();
+        let strings: Vec<_> = (0..3).map(|i| bolide_string_from_int(i)).collect();
+        for &s in &strings {
+            bolide_channel_send_ptr(ch, s as i64, ElementType::String as u8);
+            assert_eq!(bolide_string_ref_count(s), 2);
+        }
+        // 关闭不丢弃消息
+        bolide_channel_close(ch);
+        let first = bolide_channel_recv_ptr(ch) as *mut crate::BolideString;
+        assert_eq!(first, strings[0]);
+        bolide_string_release(first);
+
+        bolide_channel_free(ch);
+        for &s in &strings {
+            assert_eq!(bolide_string_ref_count(s), 1);
+            bolide_string_release(s);
+        }
+    }
+
+    #[test]
+    fn test_send_all_after_close() {
+        let ch = bolide_channel_create();
+        bolide_channel_close(ch);
+        let xs = int_list(&[1, 2]);
+        assert_eq!(bolide_channel_send_all(ch, xs), 0);
+        let drained = bolide_channel_drain(ch, ElementType::Int as u8);
+        assert_eq!(bolide_list_len(drained), 0);
+        bolide_list_release(xs);
+        bolide_list_release(drained);
+        bolide_channel_free(ch);
+    }
+
+    #[test]
+    fn test_select_list_fan_in() {
+        const WORKERS: i64 = 5;
+        let chans = bolide_list_new(ElementType::Int as u8);
+        let mut handles = Vec::new();
+        for i in 0..WORKERS {
+            let ch = bolide_channel_create_buffered(1);
+            bolide_list_push(chans, ch as i64);
+            let addr = ch as usize;
+            // 后面的 worker 先发送
+            handles.push(std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(((WORKERS - i) * 5) as u64));
+                let ch = addr as *mut BolideChannel;
+                bolide_channel_send(ch, i * 10);
+                bolide_channel_close(ch);
+            }));
+        }
+
+        let mut received = Vec::new();
+        let mut value = 0;
+        loop {
+            match bolide_channel_select_list(chans, 5_000_000_000, &mut value) {
+                SELECT_ALL_CLOSED => break,
+                -1 => panic!("timed out"),
+                idx => received.push((idx, value)),
+            }
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        received.sort();
+        assert_eq!(received, (0..WORKERS).map(|i| (i, i * 10)).collect::<Vec<_>>());
+
+        for &ch in contents(chans).iter() {
+            bolide_channel_free(ch as *mut BolideChannel);
+        }
+        bolide_list_release(chans);
+    }
+
+    #[test]
+    fn test_select_list_timeout_default_and_empty() {
+        let ch = bolide_channel_create_buffered(1);
+        let chans = int_list(&[ch as i64]);
+        let mut value = 0;
+        assert_eq!(bolide_channel_select_list(chans, 10_000_000, &mut value), -1);
+        assert_eq!(bolide_channel_select_list(chans, -2, &mut value), -2);
+        bolide_channel_send(ch, 7);
+        assert_eq!(bolide_channel_select_list(chans, -1, &mut value), 0);
+        assert_eq!(value, 7);
+        // 静态 select 在所有 channel 关闭时仍返回 -1
+        bolide_channel_close(ch);
+        assert_eq!(bolide_channel_select_list(chans, 10_000_000, &mut value), SELECT_ALL_CLOSED);
+        let array = [ch];
+        assert_eq!(bolide_channel_select(array.as_ptr(), 1, 10_000_000, &mut value), -1);
+
+        let empty = bolide_list_new(ElementType::Int as u8);
+        assert_eq!(bolide_channel_select_list(empty, -1, &mut value), SELECT_ALL_CLOSED);
+        bolide_list_release(empty);
+        bolide_list_release(chans);
+        bolide_channel_free(ch);
+    }
+}
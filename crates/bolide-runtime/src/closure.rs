@@ -0,0 +1,333 @@
+//! 绑定函数（偏函数应用）
+//!
+//! `bind(f, a, b)` 生成一个新的函数值：调用时把预先绑定的参数放在实参前面，
+//! 再调用原函数。
+//!
+//! 函数值的表示：
+//! - 普通函数值就是函数地址（JIT 生成的函数不保证对齐，不能用低位做标记）
+//! - 绑定函数值是 `BolideClosure` 指针，最高位置 1 作为标记（用户态地址最高位总是 0）
+//!
+//! 编译器在间接调用时检查标记位，带标记的函数值通过 `bolide_closure_call` 分发。
+//! 绑定函数是 RC 类型：绑定时 retain 参数，最后一次 release 时释放参数。
+//! 目前只支持整数/指针类参数（所有实参按 i64 传递）；返回值为整数/指针，运行时内部（`sort_by_key` 等）也能调用返回 float 的函数。
+
+use std::cell::Cell;
+
+use crate::rc::TypeTag;
+use crate::list::ElementType;
+
+/// 绑定函数值的标记位
+pub const CLOSURE_TAG: i64 = i64::MIN;
+
+/// 参数标签: 函数值（可能是另一个绑定函数）
+pub const ARG_TAG_FUNC: u8 = 10;
+/// 参数标签: 类实例
+pub const ARG_TAG_OBJECT: u8 = 11;
+
+/// 支持的最大总参数个数（绑定参数 + 调用参数）
+pub const MAX_CLOSURE_ARITY: usize = 8;
+
+/// RC 对象头
+#[repr(C)]
+struct RcHeader {
+    strong_count: Cell<u32>,
+    weak_count: Cell<u32>,
+    type_tag: TypeTag,
+    flags: Cell<u8>,
+    _padding: [u8; 6],
+}
+
+/// 绑定函数记录
+#[repr(C)]
+pub struct BolideClosure {
+    header: RcHeader,
+    /// 原函数地址
+    func: *const u8,
+    /// 绑定的参数（按顺序放在调用参数前面）
+    bound: Vec<i64>,
+    /// 每个绑定参数的类型标签（与列表元素标签一致，另加函数/对象）
+    tags: Vec<u8>,
+}
+
+impl BolideClosure {
+    /// 绑定的参数个数
+    pub fn bound_count(&self) -> usize {
+        self.bound.len()
+    }
+
+    #[inline]
+    pub fn ref_count(&self) -> u32 {
+        self.header.strong_count.get()
+    }
+}
+
+/// 函数值是否为绑定函数
+#[inline]
+fn is_closure(f: i64) -> bool {
+    f & CLOSURE_TAG != 0
+}
+
+/// 去掉标记位，得到记录指针
+#[inline]
+fn as_closure(f: i64) -> *mut BolideClosure {
+    (f & !CLOSURE_TAG) as *mut BolideClosure
+}
+
+/// 按标签 retain 一个值（列表元素标签，另加函数/对象）；绑定参数和通道里的 RC 值共用
+pub(crate) unsafe fn retain_tagged(value: i64, tag: u8) {
+    if value == 0 {
+        return;
+    }
+    let ptr = value as *mut u8;
+    match tag {
+        t if t == ElementType::String as u8 => { crate::bolide_string_retain(ptr as *mut crate::BolideString); }
+        t if t == ElementType::BigInt as u8 => { crate::bolide_bigint_retain(ptr as *mut crate::BolideBigInt); }
+        t if t == ElementType::Decimal as u8 => { crate::bolide_decimal_retain(ptr as *mut crate::BolideDecimal); }
+        t if t == ElementType::List as u8 => { crate::bolide_list_retain(ptr as *mut crate::BolideList); }
+        t if t == ElementType::Dict as u8 => { crate::bolide_dict_retain(ptr as *mut crate::dict::BolideDict); }
+        t if t == ElementType::Dynamic as u8 => { crate::bolide_dynamic_retain(ptr as *mut crate::dynamic::BolideDynamic); }
+        ARG_TAG_FUNC => { bolide_func_retain(value); }
+        ARG_TAG_OBJECT => { crate::object_retain(ptr); }
+        _ => {}
+    }
+}
+
+/// 按标签 release 一个值
+pub(crate) unsafe fn release_tagged(value: i64, tag: u8) {
+    if value == 0 {
+        return;
+    }
+    let ptr = value as *mut u8;
+    match tag {
+        t if t == ElementType::String as u8 => { crate::bolide_string_release(ptr as *mut crate::BolideString); }
+        t if t == ElementType::BigInt as u8 => { crate::bolide_bigint_release(ptr as *mut crate::BolideBigInt); }
+        t if t == ElementType::Decimal as u8 => { crate::bolide_decimal_release(ptr as *mut crate::BolideDecimal); }
+        t if t == ElementType::List as u8 => { crate::bolide_list_release(ptr as *mut crate::BolideList); }
+        t if t == ElementType::Dict as u8 => { crate::bolide_dict_release(ptr as *mut crate::dict::BolideDict); }
+        t if t == ElementType::Dynamic as u8 => { crate::bolide_dynamic_release(ptr as *mut crate::dynamic::BolideDynamic); }
+        ARG_TAG_FUNC => { bolide_func_release(value); }
+        ARG_TAG_OBJECT => { crate::object_release(ptr); }
+        _ => {}
+    }
+}
+
+/// 生成以 i64 实参调用函数地址的函数，`$ret` 是函数的返回类型
+macro_rules! define_invoke {
+    ($name:ident, $ret:ty) => {
+        unsafe fn $name(func: *const u8, args: &[i64]) -> $ret {
+            use std::mem::transmute;
+            type F0 = extern "C" fn() -> $ret;
+            type F1 = extern "C" fn(i64) -> $ret;
+            type F2 = extern "C" fn(i64, i64) -> $ret;
+            type F3 = extern "C" fn(i64, i64, i64) -> $ret;
+            type F4 = extern "C" fn(i64, i64, i64, i64) -> $ret;
+            type F5 = extern "C" fn(i64, i64, i64, i64, i64) -> $ret;
+            type F6 = extern "C" fn(i64, i64, i64, i64, i64, i64) -> $ret;
+            type F7 = extern "C" fn(i64, i64, i64, i64, i64, i64, i64) -> $ret;
+            type F8 = extern "C" fn(i64, i64, i64, i64, i64, i64, i64, i64) -> $ret;
+
+            let a = args;
+            match a.len() {
+                0 => transmute::<*const u8, F0>(func)(),
+                1 => transmute::<*const u8, F1>(func)(a[0]),
+                2 => transmute::<*const u8, F2>(func)(a[0], a[1]),
+                3 => transmute::<*const u8, F3>(func)(a[0], a[1], a[2]),
+                4 => transmute::<*const u8, F4>(func)(a[0], a[1], a[2], a[3]),
+                5 => transmute::<*const u8, F5>(func)(a[0], a[1], a[2], a[3], a[4]),
+                6 => transmute::<*const u8, F6>(func)(a[0], a[1], a[2], a[3], a[4], a[5]),
+                7 => transmute::<*const u8, F7>(func)(a[0], a[1], a[2], a[3], a[4], a[5], a[6]),
+                8 => transmute::<*const u8, F8>(func)(a[0], a[1], a[2], a[3], a[4], a[5], a[6], a[7]),
+                n => panic!("bound function called with {} arguments (max {})", n, MAX_CLOSURE_ARITY),
+            }
+        }
+    };
+}
+
+define_invoke!(invoke, i64);
+define_invoke!(invoke_f64, f64);
+
+/// 调用函数值：绑定函数先展开绑定参数；总参数个数超限时记录错误并返回 None
+fn dispatch<R>(func: i64, call_args: &[i64], call: unsafe fn(*const u8, &[i64]) -> R) -> Option<R> {
+    let bound = if is_closure(func) { unsafe { (*as_closure(func)).bound.len() } } else { 0 };
+    if bound + call_args.len() > MAX_CLOSURE_ARITY {
+        crate::set_error(crate::ERROR_VALUE, &format!(
+            "bound function called with {} arguments (max {})", bound + call_args.len(), MAX_CLOSURE_ARITY));
+        return None;
+    }
+
+    if !is_closure(func) {
+        return Some(unsafe { call(func as *const u8, call_args) });
+    }
+
+    let closure = unsafe { &*as_closure(func) };
+    let mut full = Vec::with_capacity(closure.bound.len() + call_args.len());
+    full.extend_from_slice(&closure.bound);
+    full.extend_from_slice(call_args);
+    Some(unsafe { call(closure.func, &full) })
+}
+
+/// 调用返回 float 的函数值（`sort_by_key` 等的 float 键函数）
+pub(crate) fn closure_call_f64(func: i64, args: &[i64]) -> f64 {
+    dispatch(func, args, invoke_f64).unwrap_or(0.0)
+}
+
+// ==================== FFI 导出 ====================
+
+/// 绑定参数，返回带标记的函数值
+///
+/// `args`/`tags` 是编译器在栈上准备的数组，长度为 `count`。
+/// 如果 `func` 本身是绑定函数，新记录会直接展开它的绑定参数。
+#[no_mangle]
+pub extern "C" fn bolide_bind(func: i64, args: *const i64, tags: *const u8, count: i64) -> i64 {
+    let count = count.max(0) as usize;
+    let (target, mut bound, mut bound_tags) = if is_closure(func) {
+        let inner = unsafe { &*as_closure(func) };
+        (inner.func, inner.bound.clone(), inner.tags.clone())
+    } else {
+        (func as *const u8, Vec::new(), Vec::new())
+    };
+
+    if count > 0 {
+        let new_args = unsafe { std::slice::from_raw_parts(args, count) };
+        let new_tags = unsafe { std::slice::from_raw_parts(tags, count) };
+        bound.extend_from_slice(new_args);
+        bound_tags.extend_from_slice(new_tags);
+    }
+
+    // 记录持有全部绑定参数（包括从内层记录展开的）
+    for (value, tag) in bound.iter().zip(bound_tags.iter()) {
+        unsafe { retain_tagged(*value, *tag); }
+    }
+
+    let closure = Box::into_raw(Box::new(BolideClosure {
+        header: RcHeader {
+            strong_count: Cell::new(1),
+            weak_count: Cell::new(1),
+            type_tag: TypeTag::Closure,
+            flags: Cell::new(0),
+            _padding: [0; 6],
+        },
+        func: target,
+        bound,
+        tags: bound_tags,
+    }));
+    closure as i64 | CLOSURE_TAG
+}
+
+/// 调用绑定函数：绑定参数在前，`args` 在后；参数总数超过 `MAX_CLOSURE_ARITY` 时记录 `ERROR_VALUE` 并返回 0
+#[no_mangle]
+pub extern "C" fn bolide_closure_call(func: i64, args: *const i64, argc: i64) -> i64 {
+    let argc = argc.max(0) as usize;
+    let call_args: &[i64] = if argc == 0 {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(args, argc) }
+    };
+
+    dispatch(func, call_args, invoke).unwrap_or(0)
+}
+
+/// 增加函数值引用计数（普通函数地址不做任何事）
+#[no_mangle]
+pub extern "C" fn bolide_func_retain(func: i64) -> i64 {
+    if is_closure(func) {
+        let closure = unsafe { &*as_closure(func) };
+        let count = closure.header.strong_count.get();
+        closure.header.strong_count.set(count + 1);
+    }
+    func
+}
+
+/// 减少函数值引用计数，归零时释放绑定参数
+#[no_mangle]
+pub extern "C" fn bolide_func_release(func: i64) {
+    if !is_closure(func) {
+        return;
+    }
+    let ptr = as_closure(func);
+    unsafe {
+        let count = (*ptr).header.strong_count.get();
+        (*ptr).header.strong_count.set(count - 1);
+        if count == 1 {
+            let closure = Box::from_raw(ptr);
+            for (value, tag) in closure.bound.iter().zip(closure.tags.iter()) {
+                release_tagged(*value, *tag);
+            }
+        }
+    }
+}
+
+// ==================== 测试 ====================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn add3(a: i64, b: i64, c: i64) -> i64 {
+        a * 100 + b * 10 + c
+    }
+
+    extern "C" fn str_len_plus(s: i64, n: i64) -> i64 {
+        let s = unsafe { &*(s as *const crate::BolideString) };
+        s.len() as i64 + n
+    }
+
+    #[test]
+    fn test_bind_ints() {
+        let args = [1i64];
+        let tags = [ElementType::Int as u8];
+        let f = bolide_bind(add3 as *const u8 as i64, args.as_ptr(), tags.as_ptr(), 1);
+        assert!(is_closure(f));
+
+        let rest = [2i64, 3];
+        assert_eq!(bolide_closure_call(f, rest.as_ptr(), 2), 123);
+        bolide_func_release(f);
+    }
+
+    #[test]
+    fn test_bind_of_bind() {
+        let a = [1i64];
+        let b = [2i64];
+        let tags = [ElementType::Int as u8];
+        let f = bolide_bind(add3 as *const u8 as i64, a.as_ptr(), tags.as_ptr(), 1);
+        let g = bolide_bind(f, b.as_ptr(), tags.as_ptr(), 1);
+        unsafe {
+            assert_eq!((*as_closure(g)).bound_count(), 2);
+        }
+
+        // 释放内层后外层仍可调用
+        bolide_func_release(f);
+        let rest = [7i64];
+        assert_eq!(bolide_closure_call(g, rest.as_ptr(), 1), 127);
+        bolide_func_release(g);
+    }
+
+    #[test]
+    fn test_bind_string_retained() {
+        let s = crate::BolideString::new("hello");
+        let args = [s as i64];
+        let tags = [ElementType::String as u8];
+        let f = bolide_bind(str_len_plus as *const u8 as i64, args.as_ptr(), tags.as_ptr(), 1);
+        unsafe {
+            assert_eq!((*s).ref_count(), 2);
+        }
+
+        let rest = [10i64];
+        assert_eq!(bolide_closure_call(f, rest.as_ptr(), 1), 15);
+
+        bolide_func_release(f);
+        unsafe {
+            assert_eq!((*s).ref_count(), 1);
+        }
+        crate::bolide_string_release(s);
+    }
+
+    #[test]
+    fn test_plain_func_value_is_not_rc() {
+        let f = add3 as *const u8 as i64;
+        assert_eq!(bolide_func_retain(f), f);
+        bolide_func_release(f);
+        let args = [4i64, 5, 6];
+        assert_eq!(bolide_closure_call(f, args.as_ptr(), 3), 456);
+    }
+}
@@ -1,11 +1,17 @@
 //! Bolide 协程运行时
 //!
 //! 提供 Hot Future 风格的协程支持
+//!
+//! 协程的栈和调度见 `executor` 模块：协程在工作线程池上运行，`await` 是挂起点。
 
 use std::sync::{Arc, Mutex, Condvar};
-use std::thread;
 use std::os::raw::c_void;
 
+use crate::error::{run_task, task_error_message, TaskError};
+use crate::executor::{self, CoroutineTurn, Job, TaskSlot};
+use crate::string::BolideString;
+use crate::thread::{consumed_twice, current_pool, take_name, LiveThread, PoolScope};
+
 /// 协程状态
 #[derive(Clone, Copy, PartialEq)]
 enum CoroutineState {
@@ -29,12 +35,25 @@ unsafe impl Sync for CoroutineResult {}
 /// 完成回调类型
 type CompletionCallback = Box<dyn Fn() + Send + Sync>;
 
+/// 协程的名字、结束时留下的错误和所属的 await 作用域
+#[derive(Default)]
+struct TaskOutcome {
+    name: String,
+    error: TaskError,
+    scope: Option<Arc<AwaitScope>>,
+}
+
 /// 协程 Future
 pub struct BolideFuture {
     state: Arc<Mutex<CoroutineState>>,
     result: Arc<Mutex<Option<CoroutineResult>>>,
     condvar: Arc<Condvar>,
     on_complete: Arc<Mutex<Option<CompletionCallback>>>,
+    /// 还没开始执行的任务（见 `executor::TaskSlot`）
+    task: TaskSlot,
+    outcome: Arc<Mutex<TaskOutcome>>,
+    /// 已经 await 过（见 `await_future`）
+    consumed: Arc<AtomicBool>,
 }
 
 unsafe impl Send for BolideFuture {}
@@ -48,6 +67,22 @@ impl BolideFuture {
             result: Arc::new(Mutex::new(None)),
             condvar: Arc::new(Condvar::new()),
             on_complete: Arc::new(Mutex::new(None)),
+            task: Arc::new(Mutex::new(None)),
+            outcome: Arc::new(Mutex::new(TaskOutcome::default())),
+            consumed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// 共享同一状态的另一个句柄：在其他线程上完成 Future，不依赖调用方何时释放它
+    pub(crate) fn share(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            result: self.result.clone(),
+            condvar: self.condvar.clone(),
+            on_complete: self.on_complete.clone(),
+            task: self.task.clone(),
+            outcome: self.outcome.clone(),
+            consumed: self.consumed.clone(),
         }
     }
 
@@ -94,20 +129,59 @@ impl BolideFuture {
 
     /// 等待结果
     pub fn await_result(&self) -> Option<CoroutineResult> {
-        let mut state = self.state.lock().unwrap();
-        while *state == CoroutineState::Running {
-            state = self.condvar.wait(state).unwrap();
+        // threaded 模式下还没开始的任务直接在当前线程上执行；single 模式照常在挂起点交出执行权
+        if !executor::single_threaded() {
+            executor::run_inline(&self.task);
+        }
+        let running = *self.state.lock().unwrap() == CoroutineState::Running;
+        if running {
+            // 挂起点：等待期间其他协程可以执行
+            executor::suspend(|| {
+                let mut state = self.state.lock().unwrap();
+                while *state == CoroutineState::Running {
+                    state = self.condvar.wait(state).unwrap();
+                }
+            });
         }
         self.result.lock().unwrap().clone()
     }
 
-    /// 取消协程
+    /// 取消协程：还没开始的任务不再执行，正在执行的任务可以通过 `cancelled()` 看到
     pub fn cancel(&self) {
-        let mut state = self.state.lock().unwrap();
-        if *state == CoroutineState::Running {
+        {
+            let mut state = self.state.lock().unwrap();
+            if *state != CoroutineState::Running {
+                return;
+            }
             *state = CoroutineState::Cancelled;
             self.condvar.notify_all();
         }
+        // 在锁外丢弃任务
+        let task = self.task.lock().unwrap().take();
+        drop(task);
+    }
+
+    /// 记录协程留下的错误；所属的作用域是 fail_fast 时取消其他子任务
+    fn record_error(&self, error: (i64, String)) {
+        let scope = {
+            let mut outcome = self.outcome.lock().unwrap();
+            outcome.error = Some(error);
+            outcome.scope.clone()
+        };
+        if let Some(scope) = scope {
+            scope.child_failed(self);
+        }
+    }
+
+    /// 协程留下的错误，已按 `await` 的格式加上协程名
+    fn error_message(&self) -> Option<String> {
+        let outcome = self.outcome.lock().unwrap();
+        let (_, message) = outcome.error.as_ref()?;
+        Some(task_error_message("async", &outcome.name, message))
+    }
+
+    fn same_task(&self, other: &BolideFuture) -> bool {
+        Arc::ptr_eq(&self.outcome, &other.outcome)
     }
 
     /// 检查是否完成
@@ -134,43 +208,72 @@ impl Default for BolideFuture {
 struct SendFnPtr(*const c_void);
 unsafe impl Send for SendFnPtr {}
 
-/// 启动协程（返回 int）
-#[no_mangle]
-pub extern "C" fn bolide_coroutine_spawn_int(
-    func_ptr: extern "C" fn() -> i64
-) -> *mut BolideFuture {
-    let future = Box::new(BolideFuture::new());
-    let future_ptr = Box::into_raw(future);
+impl SendFnPtr {
+    /// 通过方法取出，让闭包捕获整个 `SendFnPtr` 而不是其中的裸指针字段
+    fn get(self) -> *mut c_void {
+        self.0 as *mut c_void
+    }
+}
 
-    let send_fn = SendFnPtr(func_ptr as *const c_void);
-    let state = unsafe { (*future_ptr).state.clone() };
-    let result = unsafe { (*future_ptr).result.clone() };
-    let condvar = unsafe { (*future_ptr).condvar.clone() };
-    let on_complete = unsafe { (*future_ptr).on_complete.clone() };
+thread_local! {
+    /// 当前线程上正在执行的协程（被 `await` 直接执行的任务叠在外层协程上）
+    static CURRENT: std::cell::RefCell<Vec<BolideFuture>> = const { std::cell::RefCell::new(Vec::new()) };
+}
 
-    thread::spawn(move || {
-        let f: extern "C" fn() -> i64 = unsafe { std::mem::transmute(send_fn) };
-        let val = f();
+/// 当前协程是否已被取消；不在协程里时返回 false
+pub fn cancelled() -> bool {
+    CURRENT.with(|current| current.borrow().last().is_some_and(|f| f.is_cancelled()))
+}
 
-        let callback;
-        {
-            let mut on_complete_guard = on_complete.lock().unwrap();
-            let mut s = state.lock().unwrap();
-            if *s == CoroutineState::Running {
-                *result.lock().unwrap() = Some(CoroutineResult { int_val: val });
-                *s = CoroutineState::Completed;
-                condvar.notify_all();
-                callback = on_complete_guard.take();
-            } else {
-                callback = None;
-            }
+/// 当前协程是否已被取消
+#[no_mangle]
+pub extern "C" fn bolide_coroutine_cancelled() -> i64 {
+    cancelled() as i64
+}
+
+/// 把协程体放进执行器的队列，返回它的 Future
+fn spawn(body: impl FnOnce() -> CoroutineResult + Send + 'static) -> *mut BolideFuture {
+    let future = Box::new(BolideFuture::new());
+    let handle = future.share();
+
+    let live = LiveThread::start();
+    let pool = current_pool();
+    let job: Job = Box::new(move || {
+        let _live = live;
+        // 协程继承启动它的线程的线程池上下文
+        let _pool = PoolScope::enter(pool);
+        let _turn = CoroutineTurn::start();
+        // 排队等待执行权期间已被取消（例如 fail_fast 作用域里的兄弟任务失败）：不再执行
+        if handle.is_cancelled() {
+            return;
         }
-        if let Some(cb) = callback {
-            cb();
+        CURRENT.with(|current| current.borrow_mut().push(handle.share()));
+        let (result, error) = run_task(body);
+        CURRENT.with(|current| current.borrow_mut().pop());
+        // 先记录错误再完成：等待方醒来时一定能看到它
+        if let Some(error) = error {
+            handle.record_error(error);
         }
+        handle.complete(result);
     });
+    *future.task.lock().unwrap() = Some(job);
 
-    future_ptr
+    // single 模式下启动协程的线程加入执行器（已持有执行权时不变）
+    executor::acquire();
+    executor::spawn(future.task.clone());
+    Box::into_raw(future)
+}
+
+/// 启动协程（返回 int）
+#[no_mangle]
+pub extern "C" fn bolide_coroutine_spawn_int(
+    func_ptr: extern "C" fn() -> i64
+) -> *mut BolideFuture {
+    let f = SendFnPtr(func_ptr as *const c_void);
+    spawn(move || {
+        let f: extern "C" fn() -> i64 = unsafe { std::mem::transmute(f) };
+        CoroutineResult { int_val: f() }
+    })
 }
 
 /// 启动协程（返回 float）
@@ -178,101 +281,83 @@ pub extern "C" fn bolide_coroutine_spawn_int(
 pub extern "C" fn bolide_coroutine_spawn_float(
     func_ptr: extern "C" fn() -> f64
 ) -> *mut BolideFuture {
-    let future = Box::new(BolideFuture::new());
-    let future_ptr = Box::into_raw(future);
-
-    let send_fn = SendFnPtr(func_ptr as *const c_void);
-    let state = unsafe { (*future_ptr).state.clone() };
-    let result = unsafe { (*future_ptr).result.clone() };
-    let condvar = unsafe { (*future_ptr).condvar.clone() };
-    let on_complete = unsafe { (*future_ptr).on_complete.clone() };
-
-    thread::spawn(move || {
-        let f: extern "C" fn() -> f64 = unsafe { std::mem::transmute(send_fn) };
-        let val = f();
-
-        let callback;
-        {
-            let mut on_complete_guard = on_complete.lock().unwrap();
-            let mut s = state.lock().unwrap();
-            if *s == CoroutineState::Running {
-                *result.lock().unwrap() = Some(CoroutineResult { float_val: val });
-                *s = CoroutineState::Completed;
-                condvar.notify_all();
-                callback = on_complete_guard.take();
-            } else {
-                callback = None;
-            }
-        }
-        if let Some(cb) = callback {
-            cb();
-        }
-    });
-
-    future_ptr
+    let f = SendFnPtr(func_ptr as *const c_void);
+    spawn(move || {
+        let f: extern "C" fn() -> f64 = unsafe { std::mem::transmute(f) };
+        CoroutineResult { float_val: f() }
+    })
 }
 
-/// 启动协程（返回指针）
+/// 启动协程（返回 ptr）
 #[no_mangle]
 pub extern "C" fn bolide_coroutine_spawn_ptr(
     func_ptr: extern "C" fn() -> *mut c_void
 ) -> *mut BolideFuture {
-    let future = Box::new(BolideFuture::new());
-    let future_ptr = Box::into_raw(future);
-
-    let send_fn = SendFnPtr(func_ptr as *const c_void);
-    let state = unsafe { (*future_ptr).state.clone() };
-    let result = unsafe { (*future_ptr).result.clone() };
-    let condvar = unsafe { (*future_ptr).condvar.clone() };
-    let on_complete = unsafe { (*future_ptr).on_complete.clone() };
-
-    thread::spawn(move || {
-        let f: extern "C" fn() -> *mut c_void = unsafe { std::mem::transmute(send_fn) };
-        let val = f();
+    let f = SendFnPtr(func_ptr as *const c_void);
+    spawn(move || {
+        let f: extern "C" fn() -> *mut c_void = unsafe { std::mem::transmute(f) };
+        CoroutineResult { ptr_val: f() }
+    })
+}
 
-        let callback;
-        {
-            let mut on_complete_guard = on_complete.lock().unwrap();
-            let mut s = state.lock().unwrap();
-            if *s == CoroutineState::Running {
-                *result.lock().unwrap() = Some(CoroutineResult { ptr_val: val });
-                *s = CoroutineState::Completed;
-                condvar.notify_all();
-                callback = on_complete_guard.take();
-            } else {
-                callback = None;
-            }
-        }
-        if let Some(cb) = callback {
-            cb();
-        }
-    });
+/// 等待协程结果；协程留下了错误时报告并结束程序
+fn await_checked(future: &BolideFuture) -> Option<CoroutineResult> {
+    let result = future.await_result();
+    if let Some(message) = future.error_message() {
+        crate::runtime_error(&message);
+    }
+    result
+}
 
-    future_ptr
+/// 等待协程并返回结果和之前是否已经 await 过
+///
+/// 编译器 await 变量里的 Future 后释放它并把变量置空，再次 await 同一个变量时这里拿到空指针，
+/// 记录错误（见 `thread::consumed_twice`）并返回 None；没有释放的 Future 再次 await 返回缓存的结果。
+fn await_future(future: *mut BolideFuture) -> Option<(CoroutineResult, String, bool)> {
+    if future.is_null() {
+        consumed_twice("async", "", "awaited");
+        return None;
+    }
+    let future = unsafe { &*future };
+    let repeated = future.consumed.swap(true, Ordering::SeqCst);
+    let result = await_checked(future)?;
+    Some((result, future.outcome.lock().unwrap().name.clone(), repeated))
 }
 
 /// 等待协程结果（int）
 #[no_mangle]
 pub extern "C" fn bolide_coroutine_await_int(future: *mut BolideFuture) -> i64 {
-    if future.is_null() { return 0; }
-    let future = unsafe { &*future };
-    future.await_result().map(|r| unsafe { r.int_val }).unwrap_or(0)
+    await_future(future).map(|(r, _, _)| unsafe { r.int_val }).unwrap_or(0)
 }
 
 /// 等待协程结果（float）
 #[no_mangle]
 pub extern "C" fn bolide_coroutine_await_float(future: *mut BolideFuture) -> f64 {
-    if future.is_null() { return 0.0; }
-    let future = unsafe { &*future };
-    future.await_result().map(|r| unsafe { r.float_val }).unwrap_or(0.0)
+    await_future(future).map(|(r, _, _)| unsafe { r.float_val }).unwrap_or(0.0)
 }
 
-/// 等待协程结果（指针）
+/// 等待协程结果（指针）；只有第一次 await 拿到结果，同 `bolide_thread_join_ptr`
 #[no_mangle]
 pub extern "C" fn bolide_coroutine_await_ptr(future: *mut BolideFuture) -> *mut c_void {
-    if future.is_null() { return std::ptr::null_mut(); }
-    let future = unsafe { &*future };
-    future.await_result().map(|r| unsafe { r.ptr_val }).unwrap_or(std::ptr::null_mut())
+    match await_future(future) {
+        Some((_, name, true)) => {
+            consumed_twice("async", &name, "awaited");
+            std::ptr::null_mut()
+        }
+        Some((r, _, false)) => unsafe { r.ptr_val },
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// 记录协程对应的函数名（编译器在启动协程后调用，name 交给 Future）
+///
+/// # Safety
+/// `future` 为空指针或还没有释放的 Future；`name` 为空指针或有效的 `BolideString`，调用方不再使用它
+#[no_mangle]
+pub unsafe extern "C" fn bolide_coroutine_set_name(future: *mut BolideFuture, name: *mut BolideString) {
+    let name = take_name(name);
+    let Some(future) = future.as_ref() else { return; };
+    future.outcome.lock().unwrap().name = name;
 }
 
 /// 取消协程
@@ -284,7 +369,7 @@ pub extern "C" fn bolide_coroutine_cancel(future: *mut BolideFuture) {
     }
 }
 
-/// 释放 Future
+/// 释放 Future；空指针什么也不做，同 `bolide_thread_handle_free`
 #[no_mangle]
 pub extern "C" fn bolide_coroutine_free(future: *mut BolideFuture) {
     if !future.is_null() {
@@ -300,40 +385,11 @@ pub extern "C" fn bolide_coroutine_spawn_int_with_env(
     func_ptr: extern "C" fn(*mut c_void) -> i64,
     env: *mut c_void,
 ) -> *mut BolideFuture {
-    let future = Box::new(BolideFuture::new());
-    let future_ptr = Box::into_raw(future);
-
-    let send_fn = SendFnPtr(func_ptr as *const c_void);
-    let send_env = SendFnPtr(env);
-    let state = unsafe { (*future_ptr).state.clone() };
-    let result = unsafe { (*future_ptr).result.clone() };
-    let condvar = unsafe { (*future_ptr).condvar.clone() };
-    let on_complete = unsafe { (*future_ptr).on_complete.clone() };
-
-    thread::spawn(move || {
-        let f: extern "C" fn(*mut c_void) -> i64 = unsafe { std::mem::transmute(send_fn) };
-        let e: *mut c_void = unsafe { std::mem::transmute(send_env) };
-        let val = f(e);
-
-        let callback;
-        {
-            let mut on_complete_guard = on_complete.lock().unwrap();
-            let mut s = state.lock().unwrap();
-            if *s == CoroutineState::Running {
-                *result.lock().unwrap() = Some(CoroutineResult { int_val: val });
-                *s = CoroutineState::Completed;
-                condvar.notify_all();
-                callback = on_complete_guard.take();
-            } else {
-                callback = None;
-            }
-        }
-        if let Some(cb) = callback {
-            cb();
-        }
-    });
-
-    future_ptr
+    let (f, env) = (SendFnPtr(func_ptr as *const c_void), SendFnPtr(env));
+    spawn(move || {
+        let f: extern "C" fn(*mut c_void) -> i64 = unsafe { std::mem::transmute(f) };
+        CoroutineResult { int_val: f(env.get()) }
+    })
 }
 
 /// 启动协程（带环境，返回 float）
@@ -342,40 +398,11 @@ pub extern "C" fn bolide_coroutine_spawn_float_with_env(
     func_ptr: extern "C" fn(*mut c_void) -> f64,
     env: *mut c_void,
 ) -> *mut BolideFuture {
-    let future = Box::new(BolideFuture::new());
-    let future_ptr = Box::into_raw(future);
-
-    let send_fn = SendFnPtr(func_ptr as *const c_void);
-    let send_env = SendFnPtr(env);
-    let state = unsafe { (*future_ptr).state.clone() };
-    let result = unsafe { (*future_ptr).result.clone() };
-    let condvar = unsafe { (*future_ptr).condvar.clone() };
-    let on_complete = unsafe { (*future_ptr).on_complete.clone() };
-
-    thread::spawn(move || {
-        let f: extern "C" fn(*mut c_void) -> f64 = unsafe { std::mem::transmute(send_fn) };
-        let e: *mut c_void = unsafe { std::mem::transmute(send_env) };
-        let val = f(e);
-
-        let callback;
-        {
-            let mut on_complete_guard = on_complete.lock().unwrap();
-            let mut s = state.lock().unwrap();
-            if *s == CoroutineState::Running {
-                *result.lock().unwrap() = Some(CoroutineResult { float_val: val });
-                *s = CoroutineState::Completed;
-                condvar.notify_all();
-                callback = on_complete_guard.take();
-            } else {
-                callback = None;
-            }
-        }
-        if let Some(cb) = callback {
-            cb();
-        }
-    });
-
-    future_ptr
+    let (f, env) = (SendFnPtr(func_ptr as *const c_void), SendFnPtr(env));
+    spawn(move || {
+        let f: extern "C" fn(*mut c_void) -> f64 = unsafe { std::mem::transmute(f) };
+        CoroutineResult { float_val: f(env.get()) }
+    })
 }
 
 /// 启动协程（带环境，返回 ptr）
@@ -384,82 +411,132 @@ pub extern "C" fn bolide_coroutine_spawn_ptr_with_env(
     func_ptr: extern "C" fn(*mut c_void) -> *mut c_void,
     env: *mut c_void,
 ) -> *mut BolideFuture {
-    let future = Box::new(BolideFuture::new());
-    let future_ptr = Box::into_raw(future);
+    let (f, env) = (SendFnPtr(func_ptr as *const c_void), SendFnPtr(env));
+    spawn(move || {
+        let f: extern "C" fn(*mut c_void) -> *mut c_void = unsafe { std::mem::transmute(f) };
+        CoroutineResult { ptr_val: f(env.get()) }
+    })
+}
 
-    let send_fn = SendFnPtr(func_ptr as *const c_void);
-    let send_env = SendFnPtr(env);
-    let state = unsafe { (*future_ptr).state.clone() };
-    let result = unsafe { (*future_ptr).result.clone() };
-    let condvar = unsafe { (*future_ptr).condvar.clone() };
-    let on_complete = unsafe { (*future_ptr).on_complete.clone() };
+// ==================== Scope 管理 ====================
+//
+// await 作用域结束时等待其中启动的全部协程。没有被 `await` 过的协程留下的错误在这里报告：
+// 默认作用域等全部子任务结束后列出所有错误；fail_fast 作用域在第一个错误出现时取消其他子任务，
+// 结束时只报告这个错误。
 
-    thread::spawn(move || {
-        let f: extern "C" fn(*mut c_void) -> *mut c_void = unsafe { std::mem::transmute(send_fn) };
-        let e: *mut c_void = unsafe { std::mem::transmute(send_env) };
-        let val = f(e);
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 一个 await 作用域
+struct AwaitScope {
+    fail_fast: bool,
+    /// 注册到作用域的协程（共享状态的句柄，不依赖调用方何时释放 Future）
+    children: Mutex<Vec<BolideFuture>>,
+    /// fail_fast 作用域已经有子任务失败
+    failed: AtomicBool,
+    /// fail_fast 作用域里第一个失败的子任务的错误，作用域结束时报告
+    first_error: Mutex<Option<String>>,
+}
 
-        let callback;
-        {
-            let mut on_complete_guard = on_complete.lock().unwrap();
-            let mut s = state.lock().unwrap();
-            if *s == CoroutineState::Running {
-                *result.lock().unwrap() = Some(CoroutineResult { ptr_val: val });
-                *s = CoroutineState::Completed;
-                condvar.notify_all();
-                callback = on_complete_guard.take();
-            } else {
-                callback = None;
-            }
+impl AwaitScope {
+    /// 子任务失败：fail_fast 时取消其他子任务（只在第一个错误时进行）
+    fn child_failed(&self, child: &BolideFuture) {
+        if !self.fail_fast || self.failed.swap(true, Ordering::SeqCst) {
+            return;
         }
-        if let Some(cb) = callback {
-            cb();
+        *self.first_error.lock().unwrap() = child.error_message();
+        let siblings: Vec<BolideFuture> = self.children.lock().unwrap()
+            .iter()
+            .filter(|f| !f.same_task(child))
+            .map(|f| f.share())
+            .collect();
+        for sibling in siblings {
+            sibling.cancel();
         }
-    });
-
-    future_ptr
+    }
 }
 
-// ==================== Scope 管理 ====================
-
-use std::cell::RefCell;
-
 thread_local! {
-    static SCOPE_FUTURES: RefCell<Vec<Vec<*mut BolideFuture>>> = RefCell::new(Vec::new());
+    static SCOPES: RefCell<Vec<Arc<AwaitScope>>> = const { RefCell::new(Vec::new()) };
+}
+
+fn scope_enter(fail_fast: bool) {
+    let scope = Arc::new(AwaitScope {
+        fail_fast,
+        children: Mutex::new(Vec::new()),
+        failed: AtomicBool::new(false),
+        first_error: Mutex::new(None),
+    });
+    SCOPES.with(|stack| stack.borrow_mut().push(scope));
 }
 
 /// 进入新的 await scope
 #[no_mangle]
 pub extern "C" fn bolide_scope_enter() {
-    SCOPE_FUTURES.with(|stack| {
-        stack.borrow_mut().push(Vec::new());
-    });
+    scope_enter(false);
+}
+
+/// 进入新的 `await scope fail_fast`：第一个失败的子任务取消其他子任务
+#[no_mangle]
+pub extern "C" fn bolide_scope_enter_fail_fast() {
+    scope_enter(true);
 }
 
 /// 注册 Future 到当前 scope
 #[no_mangle]
 pub extern "C" fn bolide_scope_register(future: *mut BolideFuture) {
     if future.is_null() { return; }
-    SCOPE_FUTURES.with(|stack| {
-        if let Some(current) = stack.borrow_mut().last_mut() {
-            current.push(future);
-        }
-    });
+    let Some(scope) = SCOPES.with(|stack| stack.borrow().last().cloned()) else {
+        return;
+    };
+    let future = unsafe { &*future };
+    scope.children.lock().unwrap().push(future.share());
+    // 与 `record_error` 在同一把锁下交接：注册前就失败的协程在这里补上通知
+    let failed = {
+        let mut outcome = future.outcome.lock().unwrap();
+        outcome.scope = Some(scope.clone());
+        outcome.error.is_some()
+    };
+    if failed {
+        scope.child_failed(future);
+    } else if scope.failed.load(Ordering::SeqCst) {
+        future.cancel();
+    }
 }
 
-/// 退出 scope 并等待所有未完成的 Future
+/// 退出 scope 并等待所有未完成的 Future；子任务留下了错误时报告并结束程序
 #[no_mangle]
 pub extern "C" fn bolide_scope_exit() {
-    SCOPE_FUTURES.with(|stack| {
-        if let Some(futures) = stack.borrow_mut().pop() {
-            for future_ptr in futures {
-                if !future_ptr.is_null() {
-                    let future = unsafe { &*future_ptr };
-                    let _ = future.await_result();
-                }
-            }
-        }
-    });
+    if let Some(message) = scope_finish() {
+        crate::runtime_error(&message);
+    }
+}
+
+/// 退出当前 scope，等待其中的 Future，返回要报告的错误
+pub(crate) fn scope_finish() -> Option<String> {
+    // 先出栈再等待：等待时可能在当前线程上直接执行子任务，它们也会进出作用域
+    let scope = SCOPES.with(|stack| stack.borrow_mut().pop())?;
+    // 子任务留在作用域里，等待期间失败的子任务仍能取消其他子任务
+    let children: Vec<BolideFuture> = scope.children.lock().unwrap().iter().map(|f| f.share()).collect();
+    for future in &children {
+        let _ = future.await_result();
+    }
+    // 子任务也引用作用域，清空后两边才能释放
+    scope.children.lock().unwrap().clear();
+    scope_error(&scope, &children)
+}
+
+/// 作用域结束时要报告的错误：fail_fast 只报告第一个（被取消的子任务随后的错误不再报告），默认列出全部
+fn scope_error(scope: &AwaitScope, children: &[BolideFuture]) -> Option<String> {
+    if scope.fail_fast {
+        return scope.first_error.lock().unwrap().take();
+    }
+    let mut errors: Vec<String> = children.iter().filter_map(|f| f.error_message()).collect();
+    match errors.len() {
+        0 => None,
+        1 => errors.pop(),
+        n => Some(format!("{} async tasks failed in await scope:\n  {}", n, errors.join("\n  "))),
+    }
 }
 
 // ==================== Select 支持 ====================
@@ -551,6 +628,6 @@ pub extern "C" fn bolide_select_wait_first(
     }
 
     // 等待第一个完成（零轮询，纯事件驱动）
-    ctx.wait_winner() as i64
+    executor::suspend(|| ctx.wait_winner()) as i64
 }
 
@@ -1,13 +1,150 @@
 //! Bolide Decimal type with reference counting
 //!
 //! BolideDecimal 使用引用计数管理内存
+//!
+//! ## 舍入模式
+//! 除法、乘法（结果超出 28 位小数时）和 `round` 都按舍入模式处理被舍去的部分。
+//! 当前线程的模式用 `bolide_decimal_set_rounding` 设置，默认 half-even（银行家舍入）；
+//! `bolide_decimal_round_dp_mode` 可以按次指定模式，不依赖线程状态。
+//!
+//! ## 除法精度
+//! 除法默认保留 rust_decimal 能表示的全部小数位（最多 28 位）。`bolide_decimal_set_div_precision`
+//! 为当前线程设置最多保留的小数位数，商直接按舍入模式舍入到该位数（只舍入一次）。
 
+use num_bigint::BigInt;
+use num_traits::Signed;
 use rust_decimal::Decimal;
 use rust_decimal::prelude::*;
 use std::cell::Cell;
+use std::cmp::Ordering;
 
 use crate::rc::{TypeTag, flags};
 
+// ==================== 舍入模式 ====================
+
+/// 四舍五入（0.5 远离零）
+pub const ROUND_HALF_UP: i64 = 0;
+/// 银行家舍入（0.5 取偶）
+pub const ROUND_HALF_EVEN: i64 = 1;
+/// 向零截断
+pub const ROUND_DOWN: i64 = 2;
+/// 远离零
+pub const ROUND_UP: i64 = 3;
+/// 向负无穷
+pub const ROUND_FLOOR: i64 = 4;
+/// 向正无穷
+pub const ROUND_CEILING: i64 = 5;
+
+thread_local! {
+    /// 当前线程的舍入模式
+    static ROUNDING_MODE: Cell<i64> = const { Cell::new(ROUND_HALF_EVEN) };
+    /// 当前线程除法最多保留的小数位数，-1 表示不限制
+    static DIV_PRECISION: Cell<i64> = const { Cell::new(-1) };
+}
+
+/// 是否为合法的舍入模式
+fn is_valid_mode(mode: i64) -> bool {
+    (ROUND_HALF_UP..=ROUND_CEILING).contains(&mode)
+}
+
+/// 当前线程的舍入模式
+fn current_mode() -> i64 {
+    ROUNDING_MODE.with(|m| m.get())
+}
+
+/// 舍入模式名 -> 模式常量（编译器解析 `round(places, "half_even")` 时使用）
+pub fn rounding_mode_from_name(name: &str) -> Option<i64> {
+    match name {
+        "half_up" => Some(ROUND_HALF_UP),
+        "half_even" => Some(ROUND_HALF_EVEN),
+        "down" => Some(ROUND_DOWN),
+        "up" => Some(ROUND_UP),
+        "floor" => Some(ROUND_FLOOR),
+        "ceiling" => Some(ROUND_CEILING),
+        _ => None,
+    }
+}
+
+/// 模式常量 -> rust_decimal 的舍入策略
+fn rounding_strategy(mode: i64) -> RoundingStrategy {
+    match mode {
+        ROUND_HALF_UP => RoundingStrategy::MidpointAwayFromZero,
+        ROUND_DOWN => RoundingStrategy::ToZero,
+        ROUND_UP => RoundingStrategy::AwayFromZero,
+        ROUND_FLOOR => RoundingStrategy::ToNegativeInfinity,
+        ROUND_CEILING => RoundingStrategy::ToPositiveInfinity,
+        _ => RoundingStrategy::MidpointNearestEven,
+    }
+}
+
+/// 按模式计算 n / d 的整数商（d != 0）
+fn round_quotient(n: &BigInt, d: &BigInt, mode: i64) -> BigInt {
+    let q = n / d;
+    let r = n % d;
+    if r.sign() == num_bigint::Sign::NoSign {
+        return q;
+    }
+    // 真实结果的符号（商可能被截断为 0，不能看 q）
+    let positive = n.is_positive() == d.is_positive();
+    let away = match mode {
+        ROUND_DOWN => false,
+        ROUND_UP => true,
+        ROUND_FLOOR => !positive,
+        ROUND_CEILING => positive,
+        _ => match (r.abs() * 2u32).cmp(&d.abs()) {
+            Ordering::Less => false,
+            Ordering::Greater => true,
+            Ordering::Equal => mode == ROUND_HALF_UP || (&q % 2u32).sign() != num_bigint::Sign::NoSign,
+        },
+    };
+    if !away {
+        q
+    } else if positive {
+        q + 1u32
+    } else {
+        q - 1u32
+    }
+}
+
+/// 把精确值 n / d 舍入到 `scale` 位小数；尾数放不下时返回 None
+fn decimal_from_ratio(n: &BigInt, d: &BigInt, scale: u32, mode: i64) -> Option<Decimal> {
+    let m = round_quotient(n, d, mode);
+    Decimal::try_from_i128_with_scale(m.to_i128()?, scale).ok()
+}
+
+/// 10^exp
+fn pow10(exp: u32) -> BigInt {
+    num_traits::pow(BigInt::from(10u32), exp as usize)
+}
+
+/// 按模式做除法：精度与 rust_decimal 的结果相同（`max_scale` 限制时取较小的位数），最后一位按模式舍入
+fn div_with_mode(a: Decimal, b: Decimal, mode: i64, max_scale: Option<u32>) -> Option<Decimal> {
+    let q = a.checked_div(b)?;
+    let s = max_scale.map_or(q.scale(), |max| q.scale().min(max));
+    // a / b = (ma * 10^(sb + s)) / (mb * 10^sa) * 10^-s
+    let n = BigInt::from(a.mantissa()) * pow10(b.scale() + s);
+    let d = BigInt::from(b.mantissa()) * pow10(a.scale());
+    Some(decimal_from_ratio(&n, &d, s, mode).unwrap_or_else(|| q.round_dp_with_strategy(s, rounding_strategy(mode))))
+}
+
+/// 当前线程的除法精度
+fn div_precision() -> Option<u32> {
+    let digits = DIV_PRECISION.with(|p| p.get());
+    (digits >= 0).then_some(digits as u32)
+}
+
+/// 按模式做乘法：结果超出 28 位小数被截短时，按模式舍入
+fn mul_with_mode(a: Decimal, b: Decimal, mode: i64) -> Option<Decimal> {
+    let p = a.checked_mul(b)?;
+    let exact_scale = a.scale() + b.scale();
+    if p.scale() >= exact_scale {
+        return Some(p);
+    }
+    let n = BigInt::from(a.mantissa()) * BigInt::from(b.mantissa());
+    let d = pow10(exact_scale - p.scale());
+    Some(decimal_from_ratio(&n, &d, p.scale(), mode).unwrap_or(p))
+}
+
 /// RC 对象头
 #[repr(C)]
 struct RcHeader {
@@ -145,7 +282,20 @@ pub extern "C" fn bolide_decimal_from_f64(value: f64) -> *mut BolideDecimal {
 pub extern "C" fn bolide_decimal_from_str(s: *const i8, len: usize) -> *mut BolideDecimal {
     let slice = unsafe { std::slice::from_raw_parts(s as *const u8, len) };
     let s = std::str::from_utf8(slice).unwrap_or("");
-    BolideDecimal::from_str(s).unwrap_or(std::ptr::null_mut())
+    BolideDecimal::from_str(s).unwrap_or_else(|| {
+        crate::set_error(crate::ERROR_VALUE, &format!("decimal(): invalid literal '{}'", s));
+        std::ptr::null_mut()
+    })
+}
+
+/// `decimal(s)`：解析字符串（允许首尾空白），无法解析时记录 `ERROR_VALUE` 并返回空指针
+#[no_mangle]
+pub extern "C" fn bolide_decimal_from_string(s: *const crate::string::BolideString) -> *mut BolideDecimal {
+    let text = if s.is_null() { "" } else { unsafe { (*s).as_str() } };
+    BolideDecimal::from_str(text.trim()).unwrap_or_else(|| {
+        crate::set_error(crate::ERROR_VALUE, &format!("decimal(): invalid literal '{}'", text));
+        std::ptr::null_mut()
+    })
 }
 
 /// 增加引用计数
@@ -201,42 +351,60 @@ pub extern "C" fn bolide_decimal_to_f64(a: *const BolideDecimal) -> f64 {
 }
 
 // ==================== 算术运算（返回新对象，ref_count = 1）====================
+//
+// 溢出记录 `ERROR_OVERFLOW`、除以零记录 `ERROR_ZERO_DIVISION`，都返回空指针。
+
+/// 包装运算结果；None（溢出）时记录错误并返回空指针
+fn decimal_result(op: &str, result: Option<Decimal>) -> *mut BolideDecimal {
+    match result {
+        Some(v) => BolideDecimal::from_decimal(v),
+        None => {
+            crate::set_error(crate::ERROR_OVERFLOW, &format!("decimal {}: result out of range", op));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+fn division_by_zero(op: &str) -> *mut BolideDecimal {
+    crate::set_error(crate::ERROR_ZERO_DIVISION, &format!("decimal {}: division by zero", op));
+    std::ptr::null_mut()
+}
 
 #[no_mangle]
 pub extern "C" fn bolide_decimal_add(a: *const BolideDecimal, b: *const BolideDecimal) -> *mut BolideDecimal {
     if a.is_null() || b.is_null() { return std::ptr::null_mut(); }
     let (a, b) = unsafe { (&*a, &*b) };
-    BolideDecimal::from_decimal(a.inner + b.inner)
+    decimal_result("+", a.inner.checked_add(b.inner))
 }
 
 #[no_mangle]
 pub extern "C" fn bolide_decimal_sub(a: *const BolideDecimal, b: *const BolideDecimal) -> *mut BolideDecimal {
     if a.is_null() || b.is_null() { return std::ptr::null_mut(); }
     let (a, b) = unsafe { (&*a, &*b) };
-    BolideDecimal::from_decimal(a.inner - b.inner)
+    decimal_result("-", a.inner.checked_sub(b.inner))
 }
 
 #[no_mangle]
 pub extern "C" fn bolide_decimal_mul(a: *const BolideDecimal, b: *const BolideDecimal) -> *mut BolideDecimal {
     if a.is_null() || b.is_null() { return std::ptr::null_mut(); }
     let (a, b) = unsafe { (&*a, &*b) };
-    BolideDecimal::from_decimal(a.inner * b.inner)
+    decimal_result("*", mul_with_mode(a.inner, b.inner, current_mode()))
 }
 
 #[no_mangle]
 pub extern "C" fn bolide_decimal_div(a: *const BolideDecimal, b: *const BolideDecimal) -> *mut BolideDecimal {
     if a.is_null() || b.is_null() { return std::ptr::null_mut(); }
     let (a, b) = unsafe { (&*a, &*b) };
-    if b.is_zero() { return std::ptr::null_mut(); }
-    BolideDecimal::from_decimal(a.inner / b.inner)
+    if b.is_zero() { return division_by_zero("/"); }
+    decimal_result("/", div_with_mode(a.inner, b.inner, current_mode(), div_precision()))
 }
 
 #[no_mangle]
 pub extern "C" fn bolide_decimal_rem(a: *const BolideDecimal, b: *const BolideDecimal) -> *mut BolideDecimal {
     if a.is_null() || b.is_null() { return std::ptr::null_mut(); }
     let (a, b) = unsafe { (&*a, &*b) };
-    if b.is_zero() { return std::ptr::null_mut(); }
-    BolideDecimal::from_decimal(a.inner % b.inner)
+    if b.is_zero() { return division_by_zero("%"); }
+    decimal_result("%", a.inner.checked_rem(b.inner))
 }
 
 #[no_mangle]
@@ -246,6 +414,68 @@ pub extern "C" fn bolide_decimal_neg(a: *const BolideDecimal) -> *mut BolideDeci
     BolideDecimal::from_decimal(-a.inner)
 }
 
+// ==================== 原地运算（累加循环优化）====================
+//
+// 约定与 bigint 的 `*_assign` 相同：调用者交出 dst 的一个引用，接收返回的对象。
+
+/// 原地修改 dst（共享时复制）；运算失败（溢出）时记录 `ERROR_OVERFLOW` 并返回空指针
+unsafe fn decimal_assign_with(dst: *mut BolideDecimal, op: impl FnOnce(Decimal) -> Option<Decimal>) -> *mut BolideDecimal {
+    let result = op((*dst).inner);
+    if (*dst).ref_count() == 1 {
+        if let Some(v) = result {
+            (*dst).inner = v;
+            return dst;
+        }
+    }
+    bolide_decimal_release(dst);
+    decimal_result("assignment", result)
+}
+
+/// dst += src，返回新的 dst
+#[no_mangle]
+pub extern "C" fn bolide_decimal_add_assign(dst: *mut BolideDecimal, src: *const BolideDecimal) -> *mut BolideDecimal {
+    if dst.is_null() || src.is_null() { return dst; }
+    let rhs = unsafe { (*src).inner };
+    unsafe { decimal_assign_with(dst, |v| v.checked_add(rhs)) }
+}
+
+/// dst += n，返回新的 dst
+#[no_mangle]
+pub extern "C" fn bolide_decimal_add_assign_i64(dst: *mut BolideDecimal, n: i64) -> *mut BolideDecimal {
+    if dst.is_null() { return dst; }
+    unsafe { decimal_assign_with(dst, |v| v.checked_add(Decimal::from(n))) }
+}
+
+/// dst -= src，返回新的 dst
+#[no_mangle]
+pub extern "C" fn bolide_decimal_sub_assign(dst: *mut BolideDecimal, src: *const BolideDecimal) -> *mut BolideDecimal {
+    if dst.is_null() || src.is_null() { return dst; }
+    let rhs = unsafe { (*src).inner };
+    unsafe { decimal_assign_with(dst, |v| v.checked_sub(rhs)) }
+}
+
+/// dst -= n，返回新的 dst
+#[no_mangle]
+pub extern "C" fn bolide_decimal_sub_assign_i64(dst: *mut BolideDecimal, n: i64) -> *mut BolideDecimal {
+    if dst.is_null() { return dst; }
+    unsafe { decimal_assign_with(dst, |v| v.checked_sub(Decimal::from(n))) }
+}
+
+/// dst *= src，返回新的 dst（按当前舍入模式）
+#[no_mangle]
+pub extern "C" fn bolide_decimal_mul_assign(dst: *mut BolideDecimal, src: *const BolideDecimal) -> *mut BolideDecimal {
+    if dst.is_null() || src.is_null() { return dst; }
+    let rhs = unsafe { (*src).inner };
+    unsafe { decimal_assign_with(dst, |v| mul_with_mode(v, rhs, current_mode())) }
+}
+
+/// dst *= n，返回新的 dst
+#[no_mangle]
+pub extern "C" fn bolide_decimal_mul_assign_i64(dst: *mut BolideDecimal, n: i64) -> *mut BolideDecimal {
+    if dst.is_null() { return dst; }
+    unsafe { decimal_assign_with(dst, |v| mul_with_mode(v, Decimal::from(n), current_mode())) }
+}
+
 // ==================== 比较运算 ====================
 
 #[no_mangle]
@@ -313,18 +543,61 @@ pub extern "C" fn bolide_decimal_ceil(a: *const BolideDecimal) -> *mut BolideDec
     BolideDecimal::from_decimal(a.inner.ceil())
 }
 
+/// 按当前线程的舍入模式保留 `digits` 位小数（负数按 0 处理）
 #[no_mangle]
-pub extern "C" fn bolide_decimal_round(a: *const BolideDecimal) -> *mut BolideDecimal {
-    if a.is_null() { return std::ptr::null_mut(); }
-    let a = unsafe { &*a };
-    BolideDecimal::from_decimal(a.inner.round())
+pub extern "C" fn bolide_decimal_round(a: *const BolideDecimal, digits: i64) -> *mut BolideDecimal {
+    bolide_decimal_round_dp_mode(a, digits, -1)
 }
 
+/// 按当前线程的舍入模式保留 `dp` 位小数
 #[no_mangle]
 pub extern "C" fn bolide_decimal_round_dp(a: *const BolideDecimal, dp: u32) -> *mut BolideDecimal {
+    bolide_decimal_round_dp_mode(a, dp as i64, -1)
+}
+
+/// 按指定模式保留 `dp` 位小数（`mode` 为 -1 时使用当前线程的模式）
+///
+/// 位数不少于现有小数位时原样返回，不会补出新的数字。
+#[no_mangle]
+pub extern "C" fn bolide_decimal_round_dp_mode(a: *const BolideDecimal, dp: i64, mode: i64) -> *mut BolideDecimal {
     if a.is_null() { return std::ptr::null_mut(); }
     let a = unsafe { &*a };
-    BolideDecimal::from_decimal(a.inner.round_dp(dp))
+    let mode = if is_valid_mode(mode) { mode } else { current_mode() };
+    let dp = dp.clamp(0, u32::MAX as i64) as u32;
+    BolideDecimal::from_decimal(a.inner.round_dp_with_strategy(dp, rounding_strategy(mode)))
+}
+
+/// 设置当前线程的舍入模式，返回之前的模式；非法模式不做修改并返回 -1
+#[no_mangle]
+pub extern "C" fn bolide_decimal_set_rounding(mode: i64) -> i64 {
+    if !is_valid_mode(mode) {
+        return -1;
+    }
+    ROUNDING_MODE.with(|m| m.replace(mode))
+}
+
+/// 获取当前线程的舍入模式
+#[no_mangle]
+pub extern "C" fn bolide_decimal_get_rounding() -> i64 {
+    current_mode()
+}
+
+/// 设置当前线程除法最多保留的小数位数，返回之前的设置；负数恢复默认（不限制，返回值为 -1）
+#[no_mangle]
+pub extern "C" fn bolide_decimal_set_div_precision(digits: i64) -> i64 {
+    let digits = if digits < 0 { -1 } else { digits.min(u32::MAX as i64) };
+    DIV_PRECISION.with(|p| p.replace(digits))
+}
+
+/// `round(x, digits)` 的 float 版本：按 `decimal(x)` 的值和当前线程的舍入模式舍入，
+/// 结果与先转成 decimal 再舍入一致（`round(2.675, 2)` 为 2.68）；NaN、无穷和超出 decimal 范围的值原样返回
+#[no_mangle]
+pub extern "C" fn bolide_float_round(value: f64, digits: i64) -> f64 {
+    let Some(d) = Decimal::from_f64(value) else {
+        return value;
+    };
+    let digits = digits.clamp(0, u32::MAX as i64) as u32;
+    d.round_dp_with_strategy(digits, rounding_strategy(current_mode())).to_f64().unwrap_or(value)
 }
 
 // ==================== 测试 ====================
@@ -364,6 +637,107 @@ mod tests {
         }
     }
 
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_round_modes_table() {
+        // (值, 位数, 模式, 期望)
+        let cases = [
+            ("2.345", 2, ROUND_HALF_UP, "2.35"),
+            ("2.345", 2, ROUND_HALF_EVEN, "2.34"),
+            ("2.355", 2, ROUND_HALF_EVEN, "2.36"),
+            ("-2.345", 2, ROUND_HALF_UP, "-2.35"),
+            ("-2.345", 2, ROUND_HALF_EVEN, "-2.34"),
+            ("2.5", 0, ROUND_HALF_EVEN, "2"),
+            ("3.5", 0, ROUND_HALF_EVEN, "4"),
+            ("-2.5", 0, ROUND_HALF_UP, "-3"),
+            ("2.349", 2, ROUND_DOWN, "2.34"),
+            ("-2.349", 2, ROUND_DOWN, "-2.34"),
+            ("2.341", 2, ROUND_UP, "2.35"),
+            ("-2.341", 2, ROUND_UP, "-2.35"),
+            ("2.349", 2, ROUND_FLOOR, "2.34"),
+            ("-2.341", 2, ROUND_FLOOR, "-2.35"),
+            ("2.341", 2, ROUND_CEILING, "2.35"),
+            ("-2.349", 2, ROUND_CEILING, "-2.34"),
+            ("1.00000005", 7, ROUND_HALF_EVEN, "1.0000000"),
+            ("1.00000015", 7, ROUND_HALF_EVEN, "1.0000002"),
+            // 增加位数不能补出新数字
+            ("1.5", 4, ROUND_HALF_UP, "1.5"),
+            ("-0.125", 6, ROUND_CEILING, "-0.125"),
+        ];
+        for (value, places, mode, expected) in cases {
+            let a = BolideDecimal::from_decimal(dec(value));
+            let r = bolide_decimal_round_dp_mode(a, places, mode);
+            unsafe {
+                assert_eq!((*r).to_string(), expected, "{} round({}) mode {}", value, places, mode);
+                bolide_decimal_release(a);
+                bolide_decimal_release(r);
+            }
+        }
+    }
+
+    #[test]
+    fn test_div_respects_mode() {
+        // 2 / 3 在第 28 位小数处舍入
+        let two = BolideDecimal::new(2);
+        let three = BolideDecimal::new(3);
+        let minus_two = BolideDecimal::new(-2);
+
+        let prev = bolide_decimal_set_rounding(ROUND_DOWN);
+        let down = bolide_decimal_div(two, three);
+        let neg_down = bolide_decimal_div(minus_two, three);
+        bolide_decimal_set_rounding(ROUND_UP);
+        let up = bolide_decimal_div(two, three);
+        bolide_decimal_set_rounding(ROUND_FLOOR);
+        let neg_floor = bolide_decimal_div(minus_two, three);
+        bolide_decimal_set_rounding(prev);
+
+        unsafe {
+            assert!((*down).to_string().ends_with("6666"));
+            assert!((*up).to_string().ends_with("6667"));
+            assert!((*neg_down).to_string().ends_with("6666"));
+            assert!((*neg_floor).to_string().ends_with("6667"));
+            assert!((*neg_floor).is_negative());
+            for d in [two, three, minus_two, down, up, neg_down, neg_floor] {
+                bolide_decimal_release(d);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rounding_mode_is_per_thread() {
+        let prev = bolide_decimal_set_rounding(ROUND_HALF_UP);
+        let handle = std::thread::spawn(|| {
+            // 新线程使用默认模式
+            let mode = bolide_decimal_get_rounding();
+            bolide_decimal_set_rounding(ROUND_FLOOR);
+            mode
+        });
+        assert_eq!(handle.join().unwrap(), ROUND_HALF_EVEN);
+        assert_eq!(bolide_decimal_get_rounding(), ROUND_HALF_UP);
+        assert_eq!(bolide_decimal_set_rounding(42), -1);
+        bolide_decimal_set_rounding(prev);
+    }
+
+    #[test]
+    fn test_decimal_assign_shared_copies() {
+        let acc = BolideDecimal::from_decimal(dec("1.5"));
+        let acc = bolide_decimal_add_assign_i64(acc, 1);
+        let other = bolide_decimal_retain(acc);
+
+        let acc2 = bolide_decimal_mul_assign_i64(acc, 2);
+        assert_ne!(acc2, other);
+        unsafe {
+            assert_eq!((*acc2).to_string(), "5.0");
+            assert_eq!((*other).to_string(), "2.5");
+            assert_eq!((*other).ref_count(), 1);
+        }
+        bolide_decimal_release(acc2);
+        bolide_decimal_release(other);
+    }
+
     #[test]
     fn test_decimal_from_f64() {
         let d = BolideDecimal::from_f64(3.14);
@@ -373,4 +747,76 @@ mod tests {
             bolide_decimal_release(d);
         }
     }
+
+    #[test]
+    fn test_div_precision_and_rem() {
+        let one = BolideDecimal::new(1);
+        let three = BolideDecimal::new(3);
+        let quarter_den = BolideDecimal::new(4);
+
+        let prev = bolide_decimal_set_div_precision(10);
+        assert_eq!(prev, -1);
+        let third = bolide_decimal_div(one, three);
+        // 位数多于商的精确位数时不补零
+        let quarter = bolide_decimal_div(one, quarter_den);
+        bolide_decimal_set_rounding(ROUND_UP);
+        let third_up = bolide_decimal_div(one, three);
+        bolide_decimal_set_rounding(ROUND_HALF_EVEN);
+        assert_eq!(bolide_decimal_set_div_precision(-5), 10);
+        let full = bolide_decimal_div(one, three);
+
+        let a = BolideDecimal::from_decimal(dec("7.5"));
+        let b = BolideDecimal::from_decimal(dec("-2"));
+        let rem = bolide_decimal_rem(a, b);
+        let neg_rem = bolide_decimal_rem(b, a);
+        unsafe {
+            assert_eq!((*third).to_string(), "0.3333333333");
+            assert_eq!((*quarter).to_string(), "0.25");
+            assert_eq!((*third_up).to_string(), "0.3333333334");
+            assert_eq!((*full).to_string(), "0.3333333333333333333333333333");
+            // 余数的符号与被除数相同
+            assert_eq!((*rem).to_string(), "1.5");
+            assert_eq!((*neg_rem).to_string(), "-2");
+            for d in [one, three, quarter_den, third, quarter, third_up, full, a, b, rem, neg_rem] {
+                bolide_decimal_release(d);
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_floor_ceil() {
+        let a = BolideDecimal::from_decimal(dec("-2.675"));
+        let rounded = bolide_decimal_round(a, 2);
+        let floor = bolide_decimal_floor(a);
+        let ceil = bolide_decimal_ceil(a);
+        unsafe {
+            assert_eq!((*rounded).to_string(), "-2.68");
+            assert_eq!((*floor).to_string(), "-3");
+            assert_eq!((*ceil).to_string(), "-2");
+            for d in [a, rounded, floor, ceil] {
+                bolide_decimal_release(d);
+            }
+        }
+
+        assert_eq!(bolide_float_round(2.675, 2), 2.68);
+        assert_eq!(bolide_float_round(2.5, 0), 2.0);
+        assert_eq!(bolide_float_round(-1.25, 1), -1.2);
+        assert!(bolide_float_round(f64::NAN, 2).is_nan());
+        assert_eq!(bolide_float_round(1e300, 2), 1e300);
+    }
+
+    #[test]
+    fn test_decimal_from_string() {
+        let s = crate::string::BolideString::new(" 12.50 ");
+        let bad = crate::string::BolideString::new("abc");
+        let d = bolide_decimal_from_string(s);
+        unsafe {
+            assert_eq!((*d).to_string(), "12.50");
+        }
+        assert!(bolide_decimal_from_string(bad).is_null());
+        crate::bolide_error_clear();
+        bolide_decimal_release(d);
+        crate::bolide_string_release(s);
+        crate::bolide_string_release(bad);
+    }
 }
@@ -2,14 +2,20 @@
 //!
 //! BolideDict 使用引用计数管理内存
 //! 键值以 i64 存储（可以是值或指针）
+//!
+//! 字符串键按内容哈希和比较，字典持有每个键的一个引用：插入新键时 retain，
+//! 删除、清空和释放字典时 release。键是同一个实例（例如都是驻留的字面量）时不比较内容。
 
 use std::cell::Cell;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::os::raw::c_void;
+use std::fmt::Write as _;
 
 use crate::rc::{TypeTag, flags};
 use crate::{BolideString, BolideBigInt, BolideDecimal, BolideList};
 use crate::list::ElementType;
+use crate::output::outln;
 
 /// RC 对象头
 #[repr(C)]
@@ -21,11 +27,47 @@ struct RcHeader {
     _padding: [u8; 6],
 }
 
+/// 哈希表中的键：字符串键按内容哈希和比较（空指针等同于空串），其他键按值
+#[derive(Clone, Copy)]
+struct DictKey {
+    bits: i64,
+    string: bool,
+}
+
+impl DictKey {
+    fn as_str(&self) -> Option<&str> {
+        if !self.string {
+            return None;
+        }
+        if self.bits == 0 {
+            return Some("");
+        }
+        Some(unsafe { (*(self.bits as *const BolideString)).as_str() })
+    }
+}
+
+impl Hash for DictKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.as_str() {
+            Some(s) => s.hash(state),
+            None => self.bits.hash(state),
+        }
+    }
+}
+
+impl PartialEq for DictKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits == other.bits || matches!((self.as_str(), other.as_str()), (Some(a), Some(b)) if a == b)
+    }
+}
+
+impl Eq for DictKey {}
+
 /// Bolide 字典类型（带引用计数）
 #[repr(C)]
 pub struct BolideDict {
     header: RcHeader,
-    data: *mut HashMap<i64, i64>,  // 使用 Box 管理的 HashMap
+    data: *mut HashMap<DictKey, i64>,  // 使用 Box 管理的 HashMap
     len: usize,
     key_type: ElementType,
     value_type: ElementType,
@@ -70,26 +112,38 @@ impl BolideDict {
         count == 1
     }
 
+    fn key(&self, bits: i64) -> DictKey {
+        DictKey { bits, string: self.key_type == ElementType::String }
+    }
+
     /// 设置键值对
+    ///
+    /// 新键由字典 retain 一份；覆盖已有的键时保留原来的键实例。
     pub fn set(&mut self, key: i64, value: i64) {
+        let key = self.key(key);
+        // 先 retain 新值再释放旧值：两者是同一个对象时不会提前释放
+        self.retain_value(value);
         unsafe {
             let map = &mut *self.data;
-            // 如果是覆盖，需要释放旧值
-            if let Some(old_value) = map.insert(key, value) {
-                self.release_value(old_value);
-            } else {
-                self.len += 1;
+            match map.get_mut(&key) {
+                Some(slot) => {
+                    let old_value = std::mem::replace(slot, value);
+                    self.release_value(old_value);
+                }
+                None => {
+                    self.retain_key(key.bits);
+                    map.insert(key, value);
+                    self.len += 1;
+                }
             }
-            // 增加新值的引用计数
-            self.retain_value(value);
         }
     }
 
-    /// 获取值（不存在返回 0）
+    /// 获取值
     pub fn get(&self, key: i64) -> Option<i64> {
         unsafe {
             let map = &*self.data;
-            map.get(&key).copied()
+            map.get(&self.key(key)).copied()
         }
     }
 
@@ -97,21 +151,18 @@ impl BolideDict {
     pub fn contains(&self, key: i64) -> bool {
         unsafe {
             let map = &*self.data;
-            map.contains_key(&key)
+            map.contains_key(&self.key(key))
         }
     }
 
-    /// 移除键值对，返回值
+    /// 移除键值对，返回值（归调用者所有）；字典持有的键被释放
     pub fn remove(&mut self, key: i64) -> Option<i64> {
         unsafe {
             let map = &mut *self.data;
-            if let Some(value) = map.remove(&key) {
-                self.len -= 1;
-                // 注意：不释放值，因为我们返回它
-                Some(value)
-            } else {
-                None
-            }
+            let (stored, value) = map.remove_entry(&self.key(key))?;
+            self.len -= 1;
+            self.release_key(stored.bits);
+            Some(value)
         }
     }
 
@@ -131,19 +182,20 @@ impl BolideDict {
     pub fn clear(&mut self) {
         unsafe {
             let map = &mut *self.data;
-            // 释放所有值的引用
-            for (_, value) in map.drain() {
+            // 释放所有键和值的引用
+            for (key, value) in map.drain() {
+                self.release_key(key.bits);
                 self.release_value(value);
             }
             self.len = 0;
         }
     }
 
-    /// 获取所有键
+    /// 获取所有键（借用，不 retain）
     pub fn keys(&self) -> Vec<i64> {
         unsafe {
             let map = &*self.data;
-            map.keys().copied().collect()
+            map.keys().map(|key| key.bits).collect()
         }
     }
 
@@ -177,6 +229,59 @@ impl BolideDict {
         self.header.flags.set(self.header.flags.get() | flags::MOVED);
     }
 
+    /// 是否由 default_dict() 创建（缺失的容器值自动插入空容器）
+    pub fn is_default_empty(&self) -> bool {
+        self.header.flags.get() & flags::DEFAULT_EMPTY != 0
+    }
+
+    /// 读取容器值：返回字典里存放的那一份（借用，不 retain），方法调用直接修改它
+    ///
+    /// 键不存在时，default_dict() 创建的字典插入值类型的空容器并返回它；
+    /// 其他字典报运行时错误，不会把 0 当作容器指针使用。
+    /// `elem_tag` 描述空容器：列表值是元素类型，字典值低 4 位是键类型、高 4 位是值类型。
+    pub fn get_or_insert_empty(&mut self, key: i64, elem_tag: u8) -> i64 {
+        if let Some(value) = self.get(key) {
+            return value;
+        }
+        if !self.is_default_empty() {
+            crate::runtime_error(&format!("key {} not found in dict", self.describe_key(key)));
+        }
+        let empty = match self.value_type {
+            ElementType::List => crate::bolide_list_new(elem_tag) as i64,
+            ElementType::Dict => bolide_dict_new(elem_tag & 0x0f, elem_tag >> 4) as i64,
+            other => crate::runtime_error(&format!("default_dict() values must be lists or dicts, not {:?}", other)),
+        };
+        self.set(key, empty);
+        // 字典持有 set 时 retain 的那一份
+        self.release_value(empty);
+        empty
+    }
+
+    /// 错误信息里显示的键
+    fn describe_key(&self, key: i64) -> String {
+        match self.key_type {
+            ElementType::String if key != 0 => {
+                let s = unsafe { &*(key as *const BolideString) };
+                format!("\"{}\"", s.as_str())
+            }
+            ElementType::Bool => (key != 0).to_string(),
+            _ => key.to_string(),
+        }
+    }
+
+    /// 字符串键：字典持有的引用
+    fn retain_key(&self, key: i64) {
+        if self.key_type == ElementType::String {
+            crate::bolide_string_retain(key as *mut BolideString);
+        }
+    }
+
+    fn release_key(&self, key: i64) {
+        if self.key_type == ElementType::String {
+            crate::bolide_string_release(key as *mut BolideString);
+        }
+    }
+
     /// 增加值的引用计数
     fn retain_value(&self, value: i64) {
         let ptr = value as *mut c_void;
@@ -194,6 +299,9 @@ impl BolideDict {
             ElementType::List => unsafe {
                 crate::bolide_list_retain(ptr as *mut BolideList);
             },
+            ElementType::Dict => {
+                bolide_dict_retain(ptr as *mut BolideDict);
+            }
             ElementType::Dynamic => unsafe {
                 crate::bolide_dynamic_retain(ptr as *mut crate::dynamic::BolideDynamic);
             },
@@ -218,6 +326,9 @@ impl BolideDict {
             ElementType::List => unsafe {
                 crate::bolide_list_release(ptr as *mut BolideList);
             },
+            ElementType::Dict => {
+                bolide_dict_release(ptr as *mut BolideDict);
+            }
             ElementType::Dynamic => unsafe {
                 crate::bolide_dynamic_release(ptr as *mut crate::dynamic::BolideDynamic);
             },
@@ -229,10 +340,11 @@ impl BolideDict {
 impl Drop for BolideDict {
     fn drop(&mut self) {
         unsafe {
-            // 释放所有值
+            // 释放所有键和值
             if !self.data.is_null() {
                 let map = &*self.data;
-                for (_, &value) in map.iter() {
+                for (key, &value) in map.iter() {
+                    self.release_key(key.bits);
                     self.release_value(value);
                 }
                 // 释放 HashMap
@@ -252,6 +364,14 @@ pub extern "C" fn bolide_dict_new(key_type: u8, value_type: u8) -> *mut BolideDi
     BolideDict::new(kt, vt)
 }
 
+/// default_dict()：读取不存在的容器值时插入空容器的字典
+#[no_mangle]
+pub extern "C" fn bolide_dict_new_default(key_type: u8, value_type: u8) -> *mut BolideDict {
+    let dict = bolide_dict_new(key_type, value_type);
+    unsafe { (*dict).header.flags.set(flags::DEFAULT_EMPTY); }
+    dict
+}
+
 /// 增加引用计数
 #[no_mangle]
 pub extern "C" fn bolide_dict_retain(dict: *mut BolideDict) {
@@ -279,10 +399,13 @@ pub extern "C" fn bolide_dict_clone(dict: *const BolideDict) -> *mut BolideDict
         let src = &*dict;
         let new_dict = BolideDict::new(src.key_type, src.value_type);
         let dst = &mut *new_dict;
+        if src.is_default_empty() {
+            dst.header.flags.set(flags::DEFAULT_EMPTY);
+        }
         
         let src_map = &*src.data;
-        for (&key, &value) in src_map.iter() {
-            dst.set(key, value);
+        for (key, &value) in src_map.iter() {
+            dst.set(key.bits, value);
         }
         
         new_dict
@@ -296,11 +419,22 @@ pub extern "C" fn bolide_dict_set(dict: *mut BolideDict, key: i64, value: i64) {
     unsafe { (*dict).set(key, value); }
 }
 
-/// 获取值（不存在返回 0）
+/// 获取值（借用）；键不存在时记录 `ERROR_KEY` 并返回 0（指针类型的值为空指针）
 #[no_mangle]
 pub extern "C" fn bolide_dict_get(dict: *const BolideDict, key: i64) -> i64 {
     if dict.is_null() { return 0; }
-    unsafe { (*dict).get(key).unwrap_or(0) }
+    let d = unsafe { &*dict };
+    d.get(key).unwrap_or_else(|| {
+        crate::set_error(crate::ERROR_KEY, &format!("key {} not found in dict", d.describe_key(key)));
+        0
+    })
+}
+
+/// 读取容器值（`d[k]`），见 [`BolideDict::get_or_insert_empty`]
+#[no_mangle]
+pub extern "C" fn bolide_dict_get_or_insert_empty(dict: *mut BolideDict, key: i64, elem_tag: u8) -> i64 {
+    if dict.is_null() { return 0; }
+    unsafe { (*dict).get_or_insert_empty(key, elem_tag) }
 }
 
 /// 检查键是否存在
@@ -338,7 +472,7 @@ pub extern "C" fn bolide_dict_clear(dict: *mut BolideDict) {
     unsafe { (*dict).clear(); }
 }
 
-/// 获取所有键（返回新列表）
+/// 获取所有键（返回新列表，`push` 为列表 retain 每个字符串键）
 #[no_mangle]
 pub extern "C" fn bolide_dict_keys(dict: *const BolideDict) -> *mut BolideList {
     if dict.is_null() { return std::ptr::null_mut(); }
@@ -362,9 +496,8 @@ pub extern "C" fn bolide_dict_values(dict: *const BolideDict) -> *mut BolideList
         let values = d.values();
         let list = crate::list::BolideList::new(d.value_type);
         for value in values {
+            // push 为列表 retain 每个值
             crate::bolide_list_push(list, value);
-            // 增加值的引用计数（因为 values() 不增加）
-            d.retain_value(value);
         }
         list
     }
@@ -399,56 +532,61 @@ pub extern "C" fn bolide_dict_mark_moved(dict: *mut BolideDict) {
     }
 }
 
-/// 打印字典
-#[no_mangle]
-pub extern "C" fn bolide_print_dict(dict: *const BolideDict) {
-    if dict.is_null() {
-        println!("{{}}");
-        return;
-    }
-    unsafe {
-        let d = &*dict;
-        let map = &*d.data;
-        print!("{{");
-        let mut first = true;
-        for (&key, &value) in map.iter() {
-            if !first { print!(", "); }
-            first = false;
-            
-            // 打印键
-            match d.key_type {
-                ElementType::Int => print!("{}", key),
-                ElementType::String => {
-                    let s = key as *const BolideString;
-                    if !s.is_null() {
-                        print!("\"{}\"", (*s).as_str());
-                    } else {
-                        print!("null");
+/// 字典的打印形式：`{"a": 1, "b": 2}`，与 `print` 的输出一致
+pub(crate) fn format_dict(dict: *const BolideDict) -> String {
+    let mut text = String::from("{");
+    if !dict.is_null() {
+        unsafe {
+            let d = &*dict;
+            let map = &*d.data;
+            let mut first = true;
+            for (key, &value) in map.iter() {
+                let key = key.bits;
+                if !first { text.push_str(", "); }
+                first = false;
+
+                // 键
+                let _ = match d.key_type {
+                    ElementType::Int => write!(text, "{}", key),
+                    ElementType::String => {
+                        let s = key as *const BolideString;
+                        if !s.is_null() {
+                            write!(text, "\"{}\"", (*s).as_str())
+                        } else {
+                            write!(text, "null")
+                        }
                     }
-                }
-                _ => print!("{}", key),
-            }
-            
-            print!(": ");
-            
-            // 打印值
-            match d.value_type {
-                ElementType::Int => print!("{}", value),
-                ElementType::Float => print!("{}", f64::from_bits(value as u64)),
-                ElementType::Bool => print!("{}", if value != 0 { "true" } else { "false" }),
-                ElementType::String => {
-                    let s = value as *const BolideString;
-                    if !s.is_null() {
-                        print!("\"{}\"", (*s).as_str());
-                    } else {
-                        print!("null");
+                    _ => write!(text, "{}", key),
+                };
+
+                text.push_str(": ");
+
+                // 值
+                let _ = match d.value_type {
+                    ElementType::Int => write!(text, "{}", value),
+                    ElementType::Float => write!(text, "{}", f64::from_bits(value as u64)),
+                    ElementType::Bool => write!(text, "{}", if value != 0 { "true" } else { "false" }),
+                    ElementType::String => {
+                        let s = value as *const BolideString;
+                        if !s.is_null() {
+                            write!(text, "\"{}\"", (*s).as_str())
+                        } else {
+                            write!(text, "null")
+                        }
                     }
-                }
-                _ => print!("{}", value),
+                    _ => write!(text, "{}", value),
+                };
             }
         }
-        println!("}}");
     }
+    text.push('}');
+    text
+}
+
+/// 打印字典
+#[no_mangle]
+pub extern "C" fn bolide_print_dict(dict: *const BolideDict) {
+    outln!("{}", format_dict(dict));
 }
 
 // ==================== 迭代器支持 (for 循环) ====================
@@ -506,4 +644,138 @@ mod tests {
             bolide_dict_release(cloned);
         }
     }
+
+    #[test]
+    fn test_dict_string_keys_by_content() {
+        let dict = BolideDict::new(ElementType::String, ElementType::Int);
+        let literal = crate::bolide_string_literal("dict-key-a".as_ptr() as *const i8, 10);
+        let built = crate::bolide_string_concat(BolideString::new("dict-key-"), BolideString::new("a"));
+        let missing = BolideString::new("dict-key-never-set");
+
+        bolide_dict_set(dict, built as i64, 1);
+        // 字面量和运行时拼出的字符串是同一个键
+        assert_eq!(bolide_dict_get(dict, literal as i64), 1);
+        bolide_dict_set(dict, literal as i64, 2);
+        assert_eq!(bolide_dict_len(dict), 1);
+        assert_eq!(bolide_dict_get(dict, built as i64), 2);
+        assert_eq!(bolide_dict_contains(dict, missing as i64), 0);
+        assert_eq!(bolide_dict_remove(dict, built as i64), 2);
+        assert_eq!(bolide_dict_len(dict), 0);
+
+        bolide_dict_release(dict);
+        crate::bolide_string_release(literal);
+        crate::bolide_string_release(built);
+        crate::bolide_string_release(missing);
+    }
+
+    #[test]
+    fn test_dict_owns_string_keys() {
+        let dict = BolideDict::new(ElementType::String, ElementType::Int);
+        let keys: Vec<*mut BolideString> = (0..10_000).map(|i| BolideString::new(&format!("k{}", i))).collect();
+        for (i, &key) in keys.iter().enumerate() {
+            bolide_dict_set(dict, key as i64, i as i64);
+        }
+        // 调用者释放自己的引用后，键仍由字典持有
+        for &key in &keys {
+            unsafe { assert_eq!((*key).ref_count(), 2); }
+            crate::bolide_string_release(key);
+        }
+        assert_eq!(bolide_dict_len(dict), 10_000);
+        for i in (0..10_000).step_by(997) {
+            let probe = BolideString::new(&format!("k{}", i));
+            assert_eq!(bolide_dict_get(dict, probe as i64), i);
+            crate::bolide_string_release(probe);
+        }
+
+        // 覆盖已有的键：保留原来的键实例，不增加长度
+        let first = keys[0];
+        let again = BolideString::new("k0");
+        bolide_dict_set(dict, again as i64, -1);
+        assert_eq!(bolide_dict_len(dict), 10_000);
+        unsafe {
+            assert_eq!((*again).ref_count(), 1);
+            assert_eq!((*first).ref_count(), 1);
+        }
+
+        // keys() 返回的列表持有自己的引用
+        let list = bolide_dict_keys(dict);
+        assert_eq!(crate::bolide_list_len(list), 10_000);
+        unsafe { assert_eq!((*first).ref_count(), 2); }
+        crate::bolide_list_release(list);
+        unsafe { assert_eq!((*first).ref_count(), 1); }
+        // values() 的列表同样只持有一份引用
+        let values = BolideDict::new(ElementType::Int, ElementType::String);
+        bolide_dict_set(values, 0, first as i64);
+        let list = bolide_dict_values(values);
+        crate::bolide_list_release(list);
+        bolide_dict_release(values);
+        unsafe { assert_eq!((*first).ref_count(), 1); }
+
+        // 删除时返回值，字典持有的键被释放
+        crate::bolide_string_retain(first);
+        assert_eq!(bolide_dict_remove(dict, again as i64), -1);
+        unsafe { assert_eq!((*first).ref_count(), 1); }
+        assert_eq!(bolide_dict_len(dict), 9_999);
+
+        bolide_dict_release(dict);
+        crate::bolide_string_release(first);
+        crate::bolide_string_release(again);
+    }
+
+    #[test]
+    fn test_dict_get_missing_key_sets_error() {
+        crate::bolide_error_clear();
+        let dict = BolideDict::new(ElementType::String, ElementType::String);
+        let key = BolideString::new("absent");
+        assert_eq!(bolide_dict_get(dict, key as i64), 0);
+        assert_eq!(crate::last_error(), Some((crate::ERROR_KEY, "key \"absent\" not found in dict".to_string())));
+        crate::bolide_error_clear();
+
+        // contains 不记录错误
+        assert_eq!(bolide_dict_contains(dict, key as i64), 0);
+        assert_eq!(crate::last_error(), None);
+        bolide_dict_release(dict);
+        crate::bolide_string_release(key);
+    }
+
+    #[test]
+    fn test_default_dict_inserts_empty_containers() {
+        let dict = bolide_dict_new_default(ElementType::Int as u8, ElementType::List as u8);
+        unsafe {
+            for i in 0..100 {
+                let group = bolide_dict_get_or_insert_empty(dict, i % 3, ElementType::Int as u8) as *mut BolideList;
+                crate::bolide_list_push(group, i);
+            }
+            assert_eq!(bolide_dict_len(dict), 3);
+            let group = bolide_dict_get_or_insert_empty(dict, 1, ElementType::Int as u8) as *mut BolideList;
+            // 返回的是字典里存放的列表本身，只有字典持有引用
+            assert_eq!(crate::bolide_list_len(group), 33);
+            assert_eq!(crate::bolide_list_get(group, 0), 1);
+            assert_eq!((*group).ref_count(), 1);
+
+            // 克隆保留 default_dict 行为
+            let cloned = bolide_dict_clone(dict);
+            assert!((*cloned).is_default_empty());
+            assert_eq!((*group).ref_count(), 2);
+            bolide_dict_release(cloned);
+            assert_eq!((*group).ref_count(), 1);
+            bolide_dict_release(dict);
+        }
+
+        // 字典值：elem_tag 低 4 位是键类型，高 4 位是值类型
+        let nested = bolide_dict_new_default(ElementType::Int as u8, ElementType::Dict as u8);
+        let tag = ElementType::String as u8 | (ElementType::Int as u8) << 4;
+        unsafe {
+            let inner = bolide_dict_get_or_insert_empty(nested, 7, tag) as *mut BolideDict;
+            assert_eq!((*inner).key_type(), ElementType::String);
+            assert_eq!((*inner).value_type(), ElementType::Int);
+            assert_eq!((*inner).ref_count(), 1);
+            bolide_dict_retain(inner);
+            // 清空外层字典释放内层字典
+            bolide_dict_clear(nested);
+            assert_eq!((*inner).ref_count(), 1);
+            bolide_dict_release(inner);
+            bolide_dict_release(nested);
+        }
+    }
 }
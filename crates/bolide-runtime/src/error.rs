@@ -0,0 +1,196 @@
+//! 可恢复的运行时错误（`error()` / `error_message()` / `clear_error()`）
+//!
+//! 运行时函数遇到错误输入（`int("abc")`、列表下标越界、字典缺少键、除以零等）时记录错误码和信息，
+//! 然后返回约定的哨兵值（0、空指针或空串）继续执行，不在 FFI 边界上 panic。
+//! 错误按线程记录，和 C 的 errno 一样不会被成功的调用清除，需要时用 `clear_error()` 复位。
+//!
+//! `runtime_error` 仍然用于无法继续执行的错误（例如 `min()` 空列表、strict 解码失败），
+//! 它打印信息后退出进程。
+//!
+//! spawn 的线程、线程池任务和协程结束时还没清除的错误保存在它们的句柄里（见 `run_task`）：
+//! `join` / `await` 在等待的线程里报告它并结束程序，`join_ok` 把它记录为等待方的错误，
+//! await 作用域在结束时报告没有被等待过的子任务的错误。
+
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+use crate::string::BolideString;
+
+/// 错误码（`error()` 的返回值）
+pub const ERROR_NONE: i64 = 0;
+/// 无法解析的输入，例如 `int("abc")`
+pub const ERROR_VALUE: i64 = 1;
+/// 列表下标越界或从空列表取元素
+pub const ERROR_INDEX: i64 = 2;
+/// bigint / decimal 除以零
+pub const ERROR_ZERO_DIVISION: i64 = 3;
+/// 结果超出类型的表示范围
+pub const ERROR_OVERFLOW: i64 = 4;
+/// 运行时内部的 panic，被入口处的 `catch_panic` 拦截
+pub const ERROR_INTERNAL: i64 = 5;
+/// 字典里没有要读取的键
+pub const ERROR_KEY: i64 = 6;
+/// 文件读写失败，例如 `read_file()` 的文件不存在
+pub const ERROR_IO: i64 = 7;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<(i64, String)>> = const { RefCell::new(None) };
+    /// 正在 `catch_panic` 内执行：panic hook 不打印信息
+    static CATCHING: RefCell<bool> = const { RefCell::new(false) };
+}
+
+/// 记录当前线程的错误，覆盖之前的错误
+///
+/// 以调试模式运行（影子调用栈不为空）时同时把错误和调用栈打印到标准错误。
+pub fn set_error(code: i64, message: &str) {
+    if crate::trace::trace_depth() > 0 {
+        crate::output::flush_stdout();
+        eprintln!("Recoverable error {}: {}", code, message);
+        crate::trace::report_error_trace();
+    }
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some((code, message.to_string())));
+}
+
+/// 当前线程最近一次错误的错误码和信息
+pub fn last_error() -> Option<(i64, String)> {
+    LAST_ERROR.with(|e| e.borrow().clone())
+}
+
+/// 任务（spawn 的线程、线程池任务、协程）结束时留下的错误：错误码和信息
+pub type TaskError = Option<(i64, String)>;
+
+/// 执行一个任务体，返回结果和任务结束时还没清除的错误
+///
+/// 任务开始时看不到当前线程之前的错误，结束后恢复原来的错误：线程池和执行器的工作线程会被复用，
+/// 等待其他任务时也可能直接在当前线程上执行它们。
+pub(crate) fn run_task<T>(body: impl FnOnce() -> T) -> (T, TaskError) {
+    let outer = LAST_ERROR.with(|e| e.borrow_mut().take());
+    let value = body();
+    let error = LAST_ERROR.with(|e| std::mem::replace(&mut *e.borrow_mut(), outer));
+    (value, error)
+}
+
+/// 任务错误在等待它的线程里报告时的信息：`error in spawned task 'worker': ...`
+pub(crate) fn task_error_message(kind: &str, name: &str, message: &str) -> String {
+    if name.is_empty() {
+        format!("error in {} task: {}", kind, message)
+    } else {
+        format!("error in {} task '{}': {}", kind, name, message)
+    }
+}
+
+/// 执行运行时入口的函数体；函数体 panic 时记录 `ERROR_INTERNAL` 并返回 `sentinel`
+///
+/// panic 不能穿过 `extern "C"` 边界展开，可能 panic 的入口都经过这里。
+pub(crate) fn catch_panic<T>(entry: &str, sentinel: T, body: impl FnOnce() -> T) -> T {
+    static QUIET_HOOK: Once = Once::new();
+    QUIET_HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !CATCHING.with(|c| *c.borrow()) {
+                default_hook(info);
+            }
+        }));
+    });
+
+    let outer = CATCHING.with(|c| c.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(body));
+    CATCHING.with(|c| *c.borrow_mut() = outer);
+    result.unwrap_or_else(|payload| {
+        let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        set_error(ERROR_INTERNAL, &format!("{}: {}", entry, message));
+        sentinel
+    })
+}
+
+/// 从宿主或 FFI 代码记录错误；`msg` 为 UTF-8 字节，长度为 `len`
+#[no_mangle]
+pub extern "C" fn bolide_error_set(code: i64, msg: *const u8, len: usize) {
+    let message = if msg.is_null() {
+        String::new()
+    } else {
+        let bytes = unsafe { std::slice::from_raw_parts(msg, len) };
+        String::from_utf8_lossy(bytes).into_owned()
+    };
+    set_error(code, &message);
+}
+
+/// `error()`：当前线程最近一次错误的错误码，没有错误时为 0
+#[no_mangle]
+pub extern "C" fn bolide_error_code() -> i64 {
+    last_error().map_or(ERROR_NONE, |(code, _)| code)
+}
+
+/// `error_message()`：最近一次错误的信息（新字符串），没有错误时为空串
+#[no_mangle]
+pub extern "C" fn bolide_error_message() -> *mut BolideString {
+    BolideString::new(&last_error().map(|(_, message)| message).unwrap_or_default())
+}
+
+/// `clear_error()`：清除当前线程的错误
+#[no_mangle]
+pub extern "C" fn bolide_error_clear() {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_set_and_clear() {
+        bolide_error_clear();
+        assert_eq!(bolide_error_code(), ERROR_NONE);
+        let msg = "bad input";
+        bolide_error_set(ERROR_VALUE, msg.as_ptr(), msg.len());
+        assert_eq!(bolide_error_code(), ERROR_VALUE);
+        let s = bolide_error_message();
+        assert_eq!(unsafe { (*s).as_str() }, "bad input");
+        crate::bolide_string_release(s);
+
+        // 错误按线程记录
+        let other = std::thread::spawn(|| bolide_error_code()).join().unwrap();
+        assert_eq!(other, ERROR_NONE);
+
+        bolide_error_clear();
+        assert_eq!(bolide_error_code(), ERROR_NONE);
+    }
+
+    #[test]
+    fn test_runtime_functions_record_errors() {
+        bolide_error_clear();
+        let text = crate::BolideString::new("12abc");
+        assert_eq!(crate::bolide_string_to_int(text), 0);
+        assert_eq!(last_error(), Some((ERROR_VALUE, "int(): invalid literal '12abc'".to_string())));
+        crate::bolide_string_release(text);
+
+        // 溢出过去会在 FFI 边界上 panic
+        let max = crate::BolideDecimal::from_decimal(rust_decimal::Decimal::MAX);
+        assert!(crate::bolide_decimal_add(max, max).is_null());
+        assert_eq!(bolide_error_code(), ERROR_OVERFLOW);
+        let zero = crate::bolide_decimal_from_i64(0);
+        assert!(crate::bolide_decimal_rem(max, zero).is_null());
+        assert_eq!(bolide_error_code(), ERROR_ZERO_DIVISION);
+        crate::bolide_decimal_release(max);
+        crate::bolide_decimal_release(zero);
+
+        let list = crate::BolideList::new(crate::ElementType::Int);
+        assert_eq!(crate::bolide_list_get(list, usize::MAX), 0);
+        assert_eq!(last_error(), Some((ERROR_INDEX, "get: index -1 out of range for list of length 0".to_string())));
+        crate::bolide_list_release(list);
+        bolide_error_clear();
+    }
+
+    #[test]
+    fn test_catch_panic_records_internal_error() {
+        bolide_error_clear();
+        let value = catch_panic("entry", -1, || -> i64 { panic!("boom {}", 7) });
+        assert_eq!(value, -1);
+        assert_eq!(last_error(), Some((ERROR_INTERNAL, "entry: boom 7".to_string())));
+        assert_eq!(catch_panic("entry", -1, || 3), 3);
+        bolide_error_clear();
+    }
+}
@@ -0,0 +1,459 @@
+//! 协程执行器
+//!
+//! 协程是有栈的任务：`spawn` 把任务放进全局队列，由固定数量的工作线程取出执行，
+//! 任务在工作线程的栈上运行到结束。挂起时局部变量（循环下标、列表指针等）原样留在栈上，
+//! 所以 `await` 可以出现在循环、条件分支和递归调用里，恢复后从原处继续。
+//! 栈大小就是工作线程的栈大小：默认 2 MiB，可用环境变量 `RUST_MIN_STACK`（字节）调整；
+//! 协程内递归过深会栈溢出。
+//!
+//! 工作线程数默认等于 CPU 核数，可用 `set_coroutine_workers(n)` 调整。任务在挂起点
+//! （`await` 等）或阻塞的 channel 收发上等待时占着所在的工作线程，这时执行器补一个线程，
+//! 保证同时在跑的任务数不少于上限，避免所有线程都在等排队中的任务；等待结束后多出的线程退出。
+//! threaded 模式下 `await` 一个还没开始的任务时直接在当前线程上执行它，不占用额外的线程。
+//!
+//! 两种调度模式：
+//! - `threaded`（默认）：工作线程并行运行协程
+//! - `single`：同一时刻只有一个协程（或启动它们的线程）在执行 Bolide 代码。
+//!   执行权按 FIFO 交给就绪的协程，切换点只有 `await`（以及 `await all`、
+//!   async select、await 作用域结束时的等待和 async 函数里的 I/O，见 `io` 模块）。channel 收发、`join`
+//!   等其他阻塞操作不会交出执行权。
+//!
+//! 模式由 `bolide run --executor single` 或环境变量 `BOLIDE_EXECUTOR=single` 选择。
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+/// 0 = 未初始化，1 = threaded，2 = single
+static MODE: AtomicU8 = AtomicU8::new(0);
+
+/// 是否为单线程调度模式
+pub fn single_threaded() -> bool {
+    match MODE.load(Ordering::Relaxed) {
+        0 => {
+            let single = std::env::var("BOLIDE_EXECUTOR").is_ok_and(|v| v == "single");
+            MODE.store(if single { 2 } else { 1 }, Ordering::Relaxed);
+            single
+        }
+        mode => mode == 2,
+    }
+}
+
+/// 选择调度模式（覆盖环境变量），在启动任何协程之前调用
+#[no_mangle]
+pub extern "C" fn bolide_set_executor_single(single: i64) {
+    MODE.store(if single != 0 { 2 } else { 1 }, Ordering::Relaxed);
+}
+
+/// 执行权：持有者可以执行 Bolide 代码，其他线程按排队顺序等待
+struct Turn {
+    held: bool,
+    next_ticket: u64,
+    queue: VecDeque<u64>,
+}
+
+static TURN: Mutex<Turn> = Mutex::new(Turn { held: false, next_ticket: 0, queue: VecDeque::new() });
+static TURN_CHANGED: Condvar = Condvar::new();
+
+thread_local! {
+    /// 当前线程是否持有执行权
+    static HOLDS_TURN: Cell<bool> = const { Cell::new(false) };
+}
+
+/// 排队取得执行权（threaded 模式或已持有时什么都不做）
+pub(crate) fn acquire() {
+    if !single_threaded() || HOLDS_TURN.with(|h| h.get()) {
+        return;
+    }
+    let mut turn = TURN.lock().unwrap();
+    let ticket = turn.next_ticket;
+    turn.next_ticket += 1;
+    turn.queue.push_back(ticket);
+    while turn.held || turn.queue.front() != Some(&ticket) {
+        turn = TURN_CHANGED.wait(turn).unwrap();
+    }
+    turn.queue.pop_front();
+    turn.held = true;
+    HOLDS_TURN.with(|h| h.set(true));
+}
+
+/// 交出执行权（未持有时什么都不做）
+pub(crate) fn release() {
+    if !HOLDS_TURN.with(|h| h.replace(false)) {
+        return;
+    }
+    TURN.lock().unwrap().held = false;
+    TURN_CHANGED.notify_all();
+}
+
+/// 挂起点：等待期间交出执行权，等待结束后重新排队
+pub(crate) fn suspend<R>(wait: impl FnOnce() -> R) -> R {
+    if !HOLDS_TURN.with(|h| h.get()) {
+        return blocking(wait);
+    }
+    release();
+    let result = blocking(wait);
+    acquire();
+    result
+}
+
+/// 协程的执行权守卫：开始时排队，结束（包括 panic）时交出
+///
+/// 在已持有执行权的线程上开始（被 `await` 的线程直接执行）时不改变执行权。
+pub(crate) struct CoroutineTurn(bool);
+
+impl CoroutineTurn {
+    pub(crate) fn start() -> Self {
+        let held = HOLDS_TURN.with(|h| h.get());
+        acquire();
+        CoroutineTurn(!held)
+    }
+}
+
+impl Drop for CoroutineTurn {
+    fn drop(&mut self) {
+        if self.0 {
+            release();
+        }
+    }
+}
+
+// ==================== 工作线程池 ====================
+
+/// 一个协程任务：包装好的协程体，完成时自己写回 Future
+pub(crate) type Job = Box<dyn FnOnce() + Send>;
+
+/// 任务槽：排队时放在队列里，Future 也持有一份。先取出任务的一方（工作线程、
+/// 等待它的线程或取消）负责执行或丢弃，之后槽为空
+pub(crate) type TaskSlot = Arc<Mutex<Option<Job>>>;
+
+struct Workers {
+    queue: VecDeque<TaskSlot>,
+    /// 存活的工作线程数，其中空闲（等待任务）的和阻塞在挂起点上的个数
+    workers: usize,
+    idle: usize,
+    blocked: usize,
+    /// 同时在跑的任务数上限，0 表示还没设置（取 CPU 核数）
+    max_workers: usize,
+}
+
+impl Workers {
+    fn limit(&mut self) -> usize {
+        if self.max_workers == 0 {
+            self.max_workers = thread::available_parallelism().map_or(4, |n| n.get());
+        }
+        self.max_workers
+    }
+
+    /// 有排队的任务、没有空闲线程、在跑的线程未到上限时启动一个
+    fn spawn_if_needed(&mut self) {
+        if self.queue.is_empty() || self.idle > 0 || self.workers - self.blocked >= self.limit() {
+            return;
+        }
+        if thread::Builder::new().name("bolide-coroutine".to_string()).spawn(worker).is_ok() {
+            self.workers += 1;
+        }
+    }
+}
+
+static WORKERS: Mutex<Workers> = Mutex::new(Workers {
+    queue: VecDeque::new(),
+    workers: 0,
+    idle: 0,
+    blocked: 0,
+    max_workers: 0,
+});
+static TASK_READY: Condvar = Condvar::new();
+
+thread_local! {
+    /// 当前线程是否为协程工作线程
+    static IS_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+fn lock_workers() -> MutexGuard<'static, Workers> {
+    WORKERS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 把任务放进队列
+pub(crate) fn spawn(task: TaskSlot) {
+    let mut pool = lock_workers();
+    pool.queue.push_back(task);
+    pool.spawn_if_needed();
+    TASK_READY.notify_one();
+}
+
+/// 任务还没被取走时在当前线程上执行它，返回是否执行了
+pub(crate) fn run_inline(task: &TaskSlot) -> bool {
+    let job = task.lock().unwrap().take();
+    match job {
+        Some(job) => {
+            job();
+            true
+        }
+        None => false,
+    }
+}
+
+fn worker() {
+    let _tls = crate::tls::ThreadExit;
+    IS_WORKER.with(|w| w.set(true));
+    let mut pool = lock_workers();
+    loop {
+        if let Some(task) = pool.queue.pop_front() {
+            drop(pool);
+            run_inline(&task);
+            pool = lock_workers();
+            continue;
+        }
+        // 等待结束后在跑的线程可能超过上限，队列空了时多出的退出
+        if pool.workers - pool.blocked > pool.limit() {
+            break;
+        }
+        pool.idle += 1;
+        pool = TASK_READY.wait(pool).unwrap_or_else(|e| e.into_inner());
+        pool.idle -= 1;
+    }
+    pool.workers -= 1;
+}
+
+/// 阻塞等待 `wait`：在工作线程上时把本线程记为阻塞，必要时补一个工作线程
+///
+/// 不交出执行权（挂起点用 `suspend`）。channel 收发等可能等待其他协程的操作经过这里。
+pub(crate) fn blocking<R>(wait: impl FnOnce() -> R) -> R {
+    if !IS_WORKER.with(|w| w.get()) {
+        return wait();
+    }
+    {
+        let mut pool = lock_workers();
+        pool.blocked += 1;
+        pool.spawn_if_needed();
+    }
+    let result = wait();
+    lock_workers().blocked -= 1;
+    result
+}
+
+/// 同时在跑的协程数上限（至少 1）；调小后多出的线程在手上的任务结束后退出
+pub fn set_coroutine_workers(n: usize) {
+    let mut pool = lock_workers();
+    pool.max_workers = n.max(1);
+    pool.spawn_if_needed();
+    TASK_READY.notify_all();
+}
+
+/// 同时在跑的协程数上限
+pub fn coroutine_worker_limit() -> usize {
+    lock_workers().limit()
+}
+
+/// 存活的协程工作线程数（包括阻塞和空闲的）
+pub fn coroutine_workers() -> usize {
+    lock_workers().workers
+}
+
+/// set_coroutine_workers(n)：协程工作线程数（至少 1）
+#[no_mangle]
+pub extern "C" fn bolide_coroutine_set_workers(n: i64) {
+    set_coroutine_workers(n.max(1) as usize);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bolide_coroutine_await_int, bolide_coroutine_cancel, bolide_coroutine_free, bolide_coroutine_spawn_int, bolide_coroutine_spawn_int_with_env};
+    use std::os::raw::c_void;
+    use std::sync::atomic::{AtomicBool, AtomicI64, AtomicUsize};
+    use std::time::{Duration, Instant};
+
+    /// 正在执行的协程数；single 模式下任何时刻最多为 1
+    static RUNNING: AtomicI64 = AtomicI64::new(0);
+    static LOG: Mutex<Vec<i64>> = Mutex::new(Vec::new());
+    /// 本模块的测试共用工作线程池，依次执行，免得大量任务影响交替次数的统计
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn serial() -> std::sync::MutexGuard<'static, ()> {
+        SERIAL.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn enter() {
+        assert_eq!(RUNNING.fetch_add(1, Ordering::SeqCst), 0, "two coroutines ran at once");
+    }
+
+    fn leave() {
+        RUNNING.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    extern "C" fn step() -> i64 {
+        enter();
+        leave();
+        1
+    }
+
+    /// 在 1000 次循环里逐次 await，每次记录自己的标签
+    extern "C" fn looper(env: *mut c_void) -> i64 {
+        let tag = env as i64;
+        let mut total = 0;
+        for _ in 0..1000 {
+            enter();
+            LOG.lock().unwrap().push(tag);
+            leave();
+            let f = bolide_coroutine_spawn_int(step);
+            total += bolide_coroutine_await_int(f);
+            bolide_coroutine_free(f);
+        }
+        total
+    }
+
+    extern "C" fn fib(env: *mut c_void) -> i64 {
+        let n = env as i64;
+        if n < 2 {
+            return n;
+        }
+        let a = bolide_coroutine_spawn_int_with_env(fib, (n - 1) as *mut c_void);
+        let b = bolide_coroutine_spawn_int_with_env(fib, (n - 2) as *mut c_void);
+        let r = bolide_coroutine_await_int(a) + bolide_coroutine_await_int(b);
+        bolide_coroutine_free(a);
+        bolide_coroutine_free(b);
+        r
+    }
+
+    #[test]
+    fn test_single_executor_interleaves_loops() {
+        let _serial = serial();
+        bolide_set_executor_single(1);
+
+        let a = bolide_coroutine_spawn_int_with_env(looper, 1 as *mut c_void);
+        let b = bolide_coroutine_spawn_int_with_env(looper, 2 as *mut c_void);
+        assert_eq!(bolide_coroutine_await_int(a), 1000);
+        assert_eq!(bolide_coroutine_await_int(b), 1000);
+        bolide_coroutine_free(a);
+        bolide_coroutine_free(b);
+
+        // 每次 await 都让出执行权，两个循环交替推进
+        let log = LOG.lock().unwrap();
+        assert_eq!(log.len(), 2000);
+        let switches = log.windows(2).filter(|w| w[0] != w[1]).count();
+        assert!(switches >= 1000, "only {} switches between the two loops", switches);
+
+        // 递归的 async 调用：每层都在自己的栈上挂起
+        let f = bolide_coroutine_spawn_int_with_env(fib, 12 as *mut c_void);
+        assert_eq!(bolide_coroutine_await_int(f), 144);
+        bolide_coroutine_free(f);
+
+        release();
+    }
+
+    static PEAK_WORKERS: AtomicUsize = AtomicUsize::new(0);
+
+    extern "C" fn trivial(env: *mut c_void) -> i64 {
+        PEAK_WORKERS.fetch_max(coroutine_workers(), Ordering::SeqCst);
+        env as i64
+    }
+
+    #[test]
+    fn test_many_tasks_share_bounded_workers() {
+        let _serial = serial();
+        // 调度模式是全局的，和本模块其他测试一致，避免并行的测试中途切换模式
+        bolide_set_executor_single(1);
+        let limit = coroutine_worker_limit();
+        set_coroutine_workers(4);
+        let futures: Vec<_> = (0..50_000i64)
+            .map(|i| bolide_coroutine_spawn_int_with_env(trivial, i as *mut c_void))
+            .collect();
+        let mut total = 0;
+        for f in futures {
+            total += bolide_coroutine_await_int(f);
+            bolide_coroutine_free(f);
+        }
+        release();
+        assert_eq!(total, (0..50_000i64).sum::<i64>());
+        // 线程数与任务数无关（其他测试阻塞的协程也可能让执行器补线程）
+        let peak = PEAK_WORKERS.load(Ordering::SeqCst);
+        assert!(peak < 1_000, "{} worker threads for 50k tasks", peak);
+        assert_eq!(coroutine_worker_limit(), 4);
+        set_coroutine_workers(limit);
+    }
+
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    static STOPPED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn until_cancelled() -> i64 {
+        STARTED.store(true, Ordering::SeqCst);
+        while !crate::coroutine::cancelled() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        STOPPED.store(true, Ordering::SeqCst);
+        1
+    }
+
+    #[test]
+    fn test_running_task_observes_cancel() {
+        let _serial = serial();
+        bolide_set_executor_single(1);
+        let f = bolide_coroutine_spawn_int(until_cancelled);
+        release();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !STARTED.load(Ordering::SeqCst) {
+            assert!(Instant::now() < deadline, "task never started");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        bolide_coroutine_cancel(f);
+        while !STOPPED.load(Ordering::SeqCst) {
+            assert!(Instant::now() < deadline, "task did not observe the cancel");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        // 取消的 Future 没有结果
+        assert_eq!(bolide_coroutine_await_int(f), 0);
+        bolide_coroutine_free(f);
+        release();
+    }
+
+    static SIBLING_RAN: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn failing() -> i64 {
+        crate::error::set_error(crate::error::ERROR_VALUE, "bad input");
+        1
+    }
+
+    extern "C" fn sibling() -> i64 {
+        SIBLING_RAN.store(true, Ordering::SeqCst);
+        1
+    }
+
+    fn spawn_in_scope(func: extern "C" fn() -> i64, name: &str) -> *mut crate::BolideFuture {
+        let f = bolide_coroutine_spawn_int(func);
+        if !name.is_empty() {
+            let name = crate::string::BolideString::new(name);
+            unsafe { crate::bolide_coroutine_set_name(f, name) };
+        }
+        crate::bolide_scope_register(f);
+        f
+    }
+
+    #[test]
+    fn test_await_scope_reports_child_errors() {
+        let _serial = serial();
+        bolide_set_executor_single(1);
+
+        // 默认作用域：等全部子任务结束，列出所有错误
+        crate::bolide_scope_enter();
+        let futures = [spawn_in_scope(failing, "parse"), spawn_in_scope(sibling, ""), spawn_in_scope(failing, "")];
+        let error = crate::coroutine::scope_finish().unwrap();
+        assert_eq!(error, "2 async tasks failed in await scope:\n  error in async task 'parse': bad input\n  error in async task: bad input");
+        assert!(SIBLING_RAN.swap(false, Ordering::SeqCst));
+        futures.into_iter().for_each(|f| bolide_coroutine_free(f));
+
+        // fail_fast：第一个错误取消还没开始的兄弟任务
+        crate::bolide_scope_enter_fail_fast();
+        let futures = [spawn_in_scope(failing, "check"), spawn_in_scope(sibling, "")];
+        assert_eq!(crate::coroutine::scope_finish().as_deref(), Some("error in async task 'check': bad input"));
+        assert!(!SIBLING_RAN.load(Ordering::SeqCst));
+        futures.into_iter().for_each(|f| bolide_coroutine_free(f));
+
+        // 子任务的错误不会留在执行它的线程上
+        assert_eq!(crate::error::last_error(), None);
+        release();
+    }
+}
@@ -6,6 +6,9 @@ use std::sync::Mutex;
 use libloading::Library;
 
 /// 全局库缓存
+///
+/// JIT 和 AOT 程序都通过这里加载 extern 库，库的生命周期与进程相同，
+/// 不随编译器释放：编译好的代码（可能在分离的线程中）会直接调用库中的函数。
 static LOADED_LIBS: Mutex<Option<HashMap<String, Library>>> = Mutex::new(None);
 
 /// 初始化库缓存
@@ -17,36 +20,40 @@ fn init_libs() {
 }
 
 /// 加载动态库并返回句柄
+///
+/// 库缓存的锁中毒时会 panic，这里的 FFI 入口都经过 `catch_panic`。
 #[no_mangle]
 pub extern "C" fn bolide_ffi_load_library(path_ptr: *const i8) -> i64 {
-    init_libs();
-
-    let path = unsafe {
-        std::ffi::CStr::from_ptr(path_ptr)
-            .to_str()
-            .unwrap_or("")
-            .to_string()
-    };
-
-    let mut libs = LOADED_LIBS.lock().unwrap();
-    let libs = libs.as_mut().unwrap();
-
-    // 如果已加载，返回成功
-    if libs.contains_key(&path) {
-        return 1;
-    }
-
-    // 加载库
-    match unsafe { Library::new(&path) } {
-        Ok(lib) => {
-            libs.insert(path, lib);
-            1 // 成功
+    crate::catch_panic("ffi_load_library", 0, || {
+        init_libs();
+
+        let path = unsafe {
+            std::ffi::CStr::from_ptr(path_ptr)
+                .to_str()
+                .unwrap_or("")
+                .to_string()
+        };
+
+        let mut libs = LOADED_LIBS.lock().unwrap();
+        let libs = libs.as_mut().unwrap();
+
+        // 如果已加载，返回成功
+        if libs.contains_key(&path) {
+            return 1;
         }
-        Err(e) => {
-            eprintln!("[FFI] Failed to load library: {}", e);
-            0 // 失败
+
+        // 加载库
+        match unsafe { Library::new(&path) } {
+            Ok(lib) => {
+                libs.insert(path, lib);
+                1 // 成功
+            }
+            Err(e) => {
+                eprintln!("[FFI] Failed to load library: {}", e);
+                0 // 失败
+            }
         }
-    }
+    })
 }
 
 /// 获取函数指针
@@ -55,45 +62,55 @@ pub extern "C" fn bolide_ffi_get_symbol(
     lib_path_ptr: *const i8,
     symbol_name_ptr: *const i8,
 ) -> *const c_void {
-    init_libs();
-
-    let lib_path = unsafe {
-        std::ffi::CStr::from_ptr(lib_path_ptr)
-            .to_str()
-            .unwrap_or("")
-            .to_string()
-    };
-
-    let symbol_name = unsafe {
-        std::ffi::CStr::from_ptr(symbol_name_ptr)
-            .to_str()
-            .unwrap_or("")
-    };
-
-    let libs = LOADED_LIBS.lock().unwrap();
-    let libs = libs.as_ref().unwrap();
-
-    if let Some(lib) = libs.get(&lib_path) {
-        unsafe {
-            match lib.get::<*const c_void>(symbol_name.as_bytes()) {
-                Ok(sym) => *sym,
-                Err(e) => {
-                    eprintln!("[FFI] Symbol '{}' not found: {}", symbol_name, e);
-                    std::ptr::null()
+    crate::catch_panic("ffi_get_symbol", std::ptr::null(), || {
+        init_libs();
+
+        let lib_path = unsafe {
+            std::ffi::CStr::from_ptr(lib_path_ptr)
+                .to_str()
+                .unwrap_or("")
+                .to_string()
+        };
+
+        let symbol_name = unsafe {
+            std::ffi::CStr::from_ptr(symbol_name_ptr)
+                .to_str()
+                .unwrap_or("")
+        };
+
+        let libs = LOADED_LIBS.lock().unwrap();
+        let libs = libs.as_ref().unwrap();
+
+        if let Some(lib) = libs.get(&lib_path) {
+            unsafe {
+                match lib.get::<*const c_void>(symbol_name.as_bytes()) {
+                    Ok(sym) => *sym,
+                    Err(e) => {
+                        eprintln!("[FFI] Symbol '{}' not found: {}", symbol_name, e);
+                        std::ptr::null()
+                    }
                 }
             }
+        } else {
+            eprintln!("[FFI] Library not loaded: {}", lib_path);
+            std::ptr::null()
         }
-    } else {
-        eprintln!("[FFI] Library not loaded: {}", lib_path);
-        std::ptr::null()
-    }
+    })
 }
 
 /// 释放所有加载的库
+///
+/// 只在程序有序退出时调用。仍有用户线程在运行时不卸载（它们可能正在调用库中的函数），
+/// 留给进程退出时由操作系统回收。
 #[no_mangle]
 pub extern "C" fn bolide_ffi_cleanup() {
-    let mut libs = LOADED_LIBS.lock().unwrap();
-    *libs = None;
+    crate::catch_panic("ffi_cleanup", (), || {
+        if crate::live_threads() > 0 {
+            return;
+        }
+        let mut libs = LOADED_LIBS.lock().unwrap();
+        *libs = None;
+    })
 }
 
 // ============ 回调测试函数 ============
@@ -0,0 +1,194 @@
+//! 值哈希
+//!
+//! `hash(x)` 和需要按值哈希的容器共用这里的实现，每种类型只有一个哈希函数。
+//!
+//! - int / bool：整数位模式混合（true 与 1 相同）
+//! - float：位模式混合，`-0.0` 归一为 `0.0`，所有 NaN 归一为同一个值
+//!   （NaN 与自身不相等，所以仍然无法作为键查到）
+//! - str：UTF-8 字节的 FNV-1a，再做一次混合
+//! - bigint / decimal：能精确表示为 int 的值与对应 int 哈希相同，
+//!   decimal 先规范化（`1.50d` 与 `1.5d` 相同）
+//! - tuple：编译器按槽位类型逐个哈希后用 `bolide_hash_combine` 依次合并（与顺序有关）
+//!
+//! 哈希值在同一进程内稳定，不保证跨版本稳定，不要持久化。
+
+use num_traits::ToPrimitive;
+
+use crate::dynamic::{BolideDynamic, DynamicType};
+use crate::{BolideBigInt, BolideDecimal, BolideString};
+
+/// 元组哈希的初始值
+pub const TUPLE_HASH_SEED: i64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+/// splitmix64 的最终混合步骤
+#[inline]
+fn mix64(mut x: u64) -> u64 {
+    x ^= x >> 30;
+    x = x.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x ^= x >> 27;
+    x = x.wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// 字节序列哈希（FNV-1a + 混合）
+pub fn hash_bytes(bytes: &[u8]) -> i64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in bytes {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    mix64(h) as i64
+}
+
+/// int 哈希
+pub fn hash_i64(value: i64) -> i64 {
+    mix64(value as u64) as i64
+}
+
+/// float 哈希（-0.0 与 0.0 相同，所有 NaN 相同）
+pub fn hash_f64(value: f64) -> i64 {
+    let bits = if value == 0.0 {
+        0u64
+    } else if value.is_nan() {
+        f64::NAN.to_bits()
+    } else {
+        value.to_bits()
+    };
+    mix64(bits) as i64
+}
+
+/// 字符串哈希
+pub fn hash_str(s: &str) -> i64 {
+    hash_bytes(s.as_bytes())
+}
+
+/// 合并两个哈希（与顺序有关）
+pub fn hash_combine(seed: i64, value: i64) -> i64 {
+    let seed = seed as u64;
+    let mixed = seed ^ (value as u64)
+        .wrapping_add(0x9e37_79b9_7f4a_7c15)
+        .wrapping_add(seed << 6)
+        .wrapping_add(seed >> 2);
+    mix64(mixed) as i64
+}
+
+// ==================== FFI 导出 ====================
+
+#[no_mangle]
+pub extern "C" fn bolide_hash_int(value: i64) -> i64 {
+    hash_i64(value)
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_hash_float(value: f64) -> i64 {
+    hash_f64(value)
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_hash_string(s: *const BolideString) -> i64 {
+    if s.is_null() {
+        return hash_str("");
+    }
+    unsafe { hash_str((*s).as_str()) }
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_hash_bigint(b: *const BolideBigInt) -> i64 {
+    if b.is_null() {
+        return hash_i64(0);
+    }
+    let value = unsafe { (*b).inner() };
+    match value.to_i64() {
+        Some(v) => hash_i64(v),
+        None => hash_bytes(&value.to_signed_bytes_le()),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_hash_decimal(d: *const BolideDecimal) -> i64 {
+    if d.is_null() {
+        return hash_i64(0);
+    }
+    let value = unsafe { (*d).inner() }.normalize();
+    if value.scale() == 0 {
+        if let Some(v) = value.to_i64() {
+            return hash_i64(v);
+        }
+    }
+    hash_combine(hash_bytes(&value.mantissa().to_le_bytes()), value.scale() as i64)
+}
+
+/// dynamic 按运行时标签分发；list 不可哈希，记录 `ERROR_VALUE` 并返回 0 的哈希
+#[no_mangle]
+pub extern "C" fn bolide_hash_dynamic(d: *const BolideDynamic) -> i64 {
+    if d.is_null() {
+        return hash_i64(0);
+    }
+    unsafe {
+        let d = &*d;
+        match d.tag {
+            DynamicType::None => hash_i64(0),
+            DynamicType::Bool => hash_i64(d.data.bool_val),
+            DynamicType::Int => hash_i64(d.data.int_val),
+            DynamicType::Float => hash_f64(d.data.float_val),
+            DynamicType::BigInt => bolide_hash_bigint(d.data.bigint_ptr),
+            DynamicType::Decimal => bolide_hash_decimal(d.data.decimal_ptr),
+            DynamicType::String => bolide_hash_string(d.data.string_ptr),
+            DynamicType::List => {
+                crate::set_error(crate::ERROR_VALUE, "hash(): list values are not hashable");
+                hash_i64(0)
+            }
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_hash_combine(seed: i64, value: i64) -> i64 {
+    hash_combine(seed, value)
+}
+
+// ==================== 测试 ====================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_float_normalization() {
+        assert_eq!(hash_f64(0.0), hash_f64(-0.0));
+        assert_eq!(hash_f64(f64::NAN), hash_f64(-f64::NAN));
+        assert_eq!(hash_f64(f64::NAN), hash_f64(f64::from_bits(0x7ff8_0000_0000_0001)));
+        assert_ne!(hash_f64(1.0), hash_f64(-1.0));
+    }
+
+    #[test]
+    fn test_string_construction_paths() {
+        let a = BolideString::new("abc");
+        let b = crate::bolide_string_concat(BolideString::new("ab"), BolideString::new("c"));
+        assert_eq!(bolide_hash_string(a), bolide_hash_string(b));
+        assert_ne!(bolide_hash_string(a), hash_str("acb"));
+    }
+
+    #[test]
+    fn test_numeric_consistency() {
+        let big = crate::bolide_bigint_from_i64(42);
+        assert_eq!(bolide_hash_bigint(big), hash_i64(42));
+
+        let d1 = BolideDecimal::new(3);
+        let d2 = crate::bolide_decimal_from_str("3.00".as_ptr() as *const i8, 4);
+        assert_eq!(bolide_hash_decimal(d1), hash_i64(3));
+        assert_eq!(bolide_hash_decimal(d1), bolide_hash_decimal(d2));
+
+        let dyn_int = BolideDynamic::from_int(42);
+        assert_eq!(bolide_hash_dynamic(dyn_int), hash_i64(42));
+    }
+
+    #[test]
+    fn test_combine_order_sensitive() {
+        let a = hash_i64(1);
+        let b = hash_i64(2);
+        let ab = hash_combine(hash_combine(TUPLE_HASH_SEED, a), b);
+        let ba = hash_combine(hash_combine(TUPLE_HASH_SEED, b), a);
+        assert_ne!(ab, ba);
+    }
+}
@@ -0,0 +1,171 @@
+//! 嵌入时的协作式中断（`BolideEngine::call_with_budget` / `request_cancel`）
+//!
+//! 开启中断检查编译的 JIT 代码在每条循环回边把 `fuel` 减一，减到 0 才调用
+//! `bolide_interrupt_poll` 对照截止时间和取消请求——平时只是一次内存读写和比较，不读时钟。
+//! poll 按上一段的实际耗时调整下一次的 fuel，使两次读时钟之间大约间隔 `POLL_SLICE`。
+//!
+//! 中断后 `pending` 非 0：当前函数执行提前返回的清理（释放 RC 变量、语句临时值和
+//! 列表迭代器，与 `return` 相同）后返回零值，调用者在每次调用用户函数后检查 `pending`，
+//! 一层层返回到宿主。每返回一层 `frames_unwound` 加一。
+
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 两次读时钟之间的目标间隔
+const POLL_SLICE: Duration = Duration::from_micros(500);
+/// fuel 的初值和上限
+const INITIAL_FUEL: i64 = 1024;
+const MAX_FUEL: i64 = 1 << 20;
+
+/// 中断原因（`pending` 的取值）
+pub const INTERRUPT_NONE: i64 = 0;
+pub const INTERRUPT_TIMEOUT: i64 = 1;
+pub const INTERRUPT_CANCELLED: i64 = 2;
+
+/// 一个引擎的中断状态，JIT 代码把它的地址编译为常量
+///
+/// 前两个字段的偏移是 JIT 代码的约定：`fuel` 在 0，`pending` 在 8。
+#[repr(C)]
+pub struct InterruptState {
+    fuel: AtomicI64,
+    pending: AtomicI64,
+    cancel_requested: AtomicBool,
+    frames_unwound: AtomicU64,
+    clock: Mutex<Clock>,
+}
+
+struct Clock {
+    deadline: Option<Instant>,
+    last_poll: Instant,
+    refill: i64,
+}
+
+/// `fuel` 字段的偏移
+pub const INTERRUPT_FUEL_OFFSET: i32 = 0;
+/// `pending` 字段的偏移
+pub const INTERRUPT_PENDING_OFFSET: i32 = 8;
+
+impl InterruptState {
+    pub fn new() -> Self {
+        Self {
+            fuel: AtomicI64::new(INITIAL_FUEL),
+            pending: AtomicI64::new(INTERRUPT_NONE),
+            cancel_requested: AtomicBool::new(false),
+            frames_unwound: AtomicU64::new(0),
+            clock: Mutex::new(Clock {
+                deadline: None,
+                last_poll: Instant::now(),
+                refill: INITIAL_FUEL,
+            }),
+        }
+    }
+
+    /// 开始一次调用：清除上一次的中断和取消请求，设置截止时间（None 为不限时）
+    pub fn arm(&self, budget: Option<Duration>) {
+        let now = Instant::now();
+        let mut clock = self.clock.lock().unwrap();
+        clock.deadline = budget.map(|b| now + b);
+        clock.last_poll = now;
+        self.cancel_requested.store(false, Ordering::SeqCst);
+        self.pending.store(INTERRUPT_NONE, Ordering::SeqCst);
+        self.fuel.store(clock.refill, Ordering::SeqCst);
+    }
+
+    /// 请求中断正在进行的调用（可以从其他线程调用）：下一条回边就会进入 poll
+    pub fn request_cancel(&self) {
+        self.cancel_requested.store(true, Ordering::SeqCst);
+        self.fuel.store(0, Ordering::SeqCst);
+    }
+
+    /// 中断原因，`INTERRUPT_*` 之一
+    pub fn pending(&self) -> i64 {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    /// 累计因中断而提前返回的函数帧数
+    pub fn frames_unwound(&self) -> u64 {
+        self.frames_unwound.load(Ordering::Relaxed)
+    }
+
+    fn poll(&self) -> i64 {
+        if self.pending() != INTERRUPT_NONE {
+            return 1;
+        }
+        let now = Instant::now();
+        let mut clock = self.clock.lock().unwrap();
+        let reason = if self.cancel_requested.load(Ordering::SeqCst) {
+            INTERRUPT_CANCELLED
+        } else if clock.deadline.is_some_and(|d| now >= d) {
+            INTERRUPT_TIMEOUT
+        } else {
+            INTERRUPT_NONE
+        };
+        if reason != INTERRUPT_NONE {
+            self.pending.store(reason, Ordering::SeqCst);
+            return 1;
+        }
+        // 按上一段的耗时调整 fuel：回边之间的代码越慢，读时钟越频繁
+        let elapsed = now.duration_since(clock.last_poll).as_nanos().max(1);
+        let scaled = clock.refill as u128 * POLL_SLICE.as_nanos() / elapsed;
+        clock.refill = (scaled as i64).clamp(1, MAX_FUEL);
+        clock.last_poll = now;
+        self.fuel.store(clock.refill, Ordering::SeqCst);
+        0
+    }
+}
+
+impl Default for InterruptState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 回边的 fuel 用完时调用：需要中断返回 1，否则补充 fuel 返回 0
+#[no_mangle]
+pub extern "C" fn bolide_interrupt_poll(state: *const InterruptState) -> i64 {
+    if state.is_null() {
+        return 0;
+    }
+    unsafe { (*state).poll() }
+}
+
+/// 中断时每个提前返回的函数帧调用一次（计数）
+#[no_mangle]
+pub extern "C" fn bolide_interrupt_unwind(state: *const InterruptState) {
+    if !state.is_null() {
+        unsafe { (*state).frames_unwound.fetch_add(1, Ordering::Relaxed) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_offsets() {
+        let state = InterruptState::new();
+        let base = &state as *const InterruptState as usize;
+        assert_eq!(&state.fuel as *const AtomicI64 as usize - base, INTERRUPT_FUEL_OFFSET as usize);
+        assert_eq!(&state.pending as *const AtomicI64 as usize - base, INTERRUPT_PENDING_OFFSET as usize);
+    }
+
+    #[test]
+    fn test_poll_deadline_and_cancel() {
+        let state = InterruptState::new();
+        state.arm(None);
+        assert_eq!(bolide_interrupt_poll(&state), 0);
+        assert!(state.fuel.load(Ordering::SeqCst) > 0);
+
+        state.arm(Some(Duration::ZERO));
+        assert_eq!(bolide_interrupt_poll(&state), 1);
+        assert_eq!(state.pending(), INTERRUPT_TIMEOUT);
+
+        state.arm(Some(Duration::from_secs(60)));
+        assert_eq!(state.pending(), INTERRUPT_NONE);
+        state.request_cancel();
+        assert_eq!(state.fuel.load(Ordering::SeqCst), 0);
+        assert_eq!(bolide_interrupt_poll(&state), 1);
+        assert_eq!(state.pending(), INTERRUPT_CANCELLED);
+    }
+}
@@ -0,0 +1,532 @@
+//! 阻塞 I/O 的专用线程池（async 函数里的 `input()` / `read_file()`）
+//!
+//! 协程里直接读标准输入或文件会占住执行它的线程：single 调度模式下整个执行器都停下来等 I/O。
+//! 编译器在 async 函数里把这两个内置函数换成 `bolide_input_async` / `bolide_file_read_async`
+//! 加上 `bolide_io_await`：读取在这里的线程上进行，调用方在 `bolide_io_await` 处挂起（交出执行权），
+//! 读完后恢复。用户代码不变，拿到的仍是 `str`。
+//!
+//! 线程按需启动，最多 `set_io_workers(n)` 个（默认 2），空闲的线程等待下一个请求。
+//! 读到的内容以 Rust `String` 交回，字符串对象和可恢复错误都在等待的线程上创建和记录。
+//! `shutdown_io_workers` 让线程处理完已提交的请求后退出，`bolide run` 在程序结束时调用它。
+//!
+//! 同步的文件读写（`read_file`、`read_lines`、`write_file`、`append_file`、`write_lines`、`file_exists`、`remove_file`）
+//! 也在这里：失败时记录 `ERROR_IO` 并返回空串、空列表或 0，不中止程序。
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::os::raw::c_void;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::coroutine::{BolideFuture, CoroutineResult};
+use crate::error::{set_error, ERROR_IO};
+use crate::list::BolideList;
+use crate::string::{bolide_string_release, BolideString};
+
+/// 默认的线程数上限
+pub const DEFAULT_IO_WORKERS: usize = 2;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// 一次读取的结果：内容或错误信息
+type IoOutcome = Result<String, String>;
+
+struct IoPool {
+    queue: VecDeque<Job>,
+    /// 存活的线程数和其中空闲（等待请求）的个数
+    workers: usize,
+    idle: usize,
+    max_workers: usize,
+    /// 正在关闭：队列清空后线程退出
+    shutdown: bool,
+    handles: Vec<JoinHandle<()>>,
+}
+
+static POOL: Mutex<IoPool> = Mutex::new(IoPool {
+    queue: VecDeque::new(),
+    workers: 0,
+    idle: 0,
+    max_workers: DEFAULT_IO_WORKERS,
+    shutdown: false,
+    handles: Vec::new(),
+});
+static WORK_READY: Condvar = Condvar::new();
+static WORKER_EXITED: Condvar = Condvar::new();
+
+fn lock_pool() -> MutexGuard<'static, IoPool> {
+    POOL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 提交一个请求；没有空闲线程且未到上限时启动一个
+fn submit(job: Job) {
+    let mut pool = lock_pool();
+    pool.queue.push_back(job);
+    if pool.idle == 0 && pool.workers < pool.max_workers {
+        match thread::Builder::new().name("bolide-io".to_string()).spawn(worker) {
+            Ok(handle) => {
+                pool.workers += 1;
+                pool.handles.push(handle);
+            }
+            // 启动不了新线程时由已有的线程处理；一个都没有就在当前线程上执行
+            Err(_) if pool.workers == 0 => {
+                let job = pool.queue.pop_back();
+                drop(pool);
+                if let Some(job) = job {
+                    job();
+                }
+                return;
+            }
+            Err(_) => {}
+        }
+    }
+    WORK_READY.notify_one();
+}
+
+fn worker() {
+    let mut pool = lock_pool();
+    loop {
+        if let Some(job) = pool.queue.pop_front() {
+            drop(pool);
+            job();
+            pool = lock_pool();
+            continue;
+        }
+        if pool.shutdown || pool.workers > pool.max_workers {
+            break;
+        }
+        pool.idle += 1;
+        pool = WORK_READY.wait(pool).unwrap_or_else(|e| e.into_inner());
+        pool.idle -= 1;
+    }
+    pool.workers -= 1;
+    WORKER_EXITED.notify_all();
+}
+
+/// 在线程池上执行 `read`，返回完成时带结果的 future（由 `bolide_io_await` 等待）
+fn spawn_read(read: impl FnOnce() -> IoOutcome + Send + 'static) -> *mut BolideFuture {
+    let future = Box::new(BolideFuture::new());
+    let completer = future.share();
+    submit(Box::new(move || {
+        let outcome = Box::into_raw(Box::new(read()));
+        completer.complete(CoroutineResult { ptr_val: outcome as *mut c_void });
+        // 已被取消的 future 不接收结果
+        if !completer.is_completed() {
+            drop(unsafe { Box::from_raw(outcome) });
+        }
+    }));
+    Box::into_raw(future)
+}
+
+/// 线程数上限；调小后多出的空闲线程退出
+pub fn set_io_workers(n: usize) {
+    let mut pool = lock_pool();
+    pool.max_workers = n.max(1);
+    WORK_READY.notify_all();
+}
+
+/// 存活的 I/O 线程数
+pub fn io_workers() -> usize {
+    lock_pool().workers
+}
+
+/// 关闭线程池：已提交的请求处理完后线程退出，最多等待 `timeout`
+///
+/// 所有线程都已退出并回收时返回 true，之后的请求会重新启动线程。
+/// 超时（例如还有线程阻塞在读标准输入）返回 false，剩下的线程读完后自行退出。
+pub fn shutdown_io_workers(timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    let mut pool = lock_pool();
+    pool.shutdown = true;
+    WORK_READY.notify_all();
+    while pool.workers > 0 {
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        pool = WORKER_EXITED.wait_timeout(pool, deadline - now).unwrap_or_else(|e| e.into_inner()).0;
+    }
+    pool.shutdown = false;
+    let handles = std::mem::take(&mut pool.handles);
+    drop(pool);
+    for handle in handles {
+        let _ = handle.join();
+    }
+    true
+}
+
+/// read_file(path) -> str：读取整个文本文件；失败时记录 `ERROR_IO` 并返回空串
+///
+/// # Safety
+/// `path` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_file_read(path: *const BolideString) -> *mut BolideString {
+    let path = path.as_ref().map_or("", |path| path.as_str());
+    finish(read_file(path))
+}
+
+fn read_file(path: &str) -> IoOutcome {
+    std::fs::read_to_string(path).map_err(|e| format!("read_file: '{}': {}", path, e))
+}
+
+/// write_lines(path, lines)：创建（或清空）文件，每个字符串后加换行写入；失败时记录 `ERROR_IO`
+///
+/// 整个列表经同一个文件句柄和缓冲写出，不逐行打开文件。
+///
+/// # Safety
+/// `path` 为空指针或有效的 `BolideString`，`lines` 为空指针或元素都是字符串的有效列表
+#[no_mangle]
+pub unsafe extern "C" fn bolide_file_write_lines(path: *const BolideString, lines: *const BolideList) {
+    let path = path.as_ref().map_or("", |path| path.as_str());
+    if let Err(e) = write_lines(path, lines.as_ref()) {
+        set_error(ERROR_IO, &format!("write_lines: '{}': {}", path, e));
+    }
+}
+
+fn write_lines(path: &str, lines: Option<&BolideList>) -> std::io::Result<()> {
+    let mut out = BufWriter::with_capacity(1 << 16, File::create(path)?);
+    for i in 0..lines.map_or(0, BolideList::len) {
+        let line = lines.and_then(|list| list.get(i)).and_then(|ptr| unsafe { (ptr as *const BolideString).as_ref() });
+        if let Some(line) = line {
+            out.write_all(line.as_bytes())?;
+        }
+        out.write_all(b"\n")?;
+    }
+    out.flush()
+}
+
+/// write_file(path, contents) -> bool：创建（或清空）文件并写入；成功返回 1，失败记录 `ERROR_IO` 并返回 0
+///
+/// # Safety
+/// `path`、`contents` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_file_write(path: *const BolideString, contents: *const BolideString) -> i64 {
+    write_to("write_file", path, contents, false)
+}
+
+/// append_file(path, contents) -> bool：追加到文件末尾，文件不存在时创建；失败记录 `ERROR_IO` 并返回 0
+///
+/// # Safety
+/// `path`、`contents` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_file_append(path: *const BolideString, contents: *const BolideString) -> i64 {
+    write_to("append_file", path, contents, true)
+}
+
+/// `write_file` / `append_file` 的实现
+///
+/// # Safety
+/// `path`、`contents` 为空指针或有效的 `BolideString`
+unsafe fn write_to(name: &str, path: *const BolideString, contents: *const BolideString, append: bool) -> i64 {
+    let path = path.as_ref().map_or("", |path| path.as_str());
+    let contents = contents.as_ref().map_or(&[][..], |contents| contents.as_bytes());
+    let result = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .and_then(|mut file| file.write_all(contents));
+    match result {
+        Ok(()) => 1,
+        Err(e) => {
+            set_error(ERROR_IO, &format!("{}: '{}': {}", name, path, e));
+            0
+        }
+    }
+}
+
+/// file_exists(path) -> bool：路径存在（文件或目录）时返回 1，不记录错误
+///
+/// # Safety
+/// `path` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_file_exists(path: *const BolideString) -> i64 {
+    let path = path.as_ref().map_or("", |path| path.as_str());
+    std::path::Path::new(path).exists() as i64
+}
+
+/// remove_file(path) -> bool：删除文件；成功返回 1，失败（不存在、是目录等）记录 `ERROR_IO` 并返回 0
+///
+/// # Safety
+/// `path` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_file_remove(path: *const BolideString) -> i64 {
+    let path = path.as_ref().map_or("", |path| path.as_str());
+    match std::fs::remove_file(path) {
+        Ok(()) => 1,
+        Err(e) => {
+            set_error(ERROR_IO, &format!("remove_file: '{}': {}", path, e));
+            0
+        }
+    }
+}
+
+/// temp_dir() -> str：系统临时目录（`TMPDIR` / `%TEMP%`），末尾不带分隔符
+#[no_mangle]
+pub extern "C" fn bolide_temp_dir() -> *mut BolideString {
+    let dir = std::env::temp_dir();
+    let text = dir.to_string_lossy();
+    let trimmed = text.trim_end_matches(['/', '\\']);
+    BolideString::new(if trimmed.is_empty() { &text } else { trimmed })
+}
+
+/// read_lines(path) -> list<str>：按行读取文本文件（去掉 `\n` / `\r\n`）；失败时记录 `ERROR_IO` 并返回空列表
+///
+/// # Safety
+/// `path` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_file_lines(path: *const BolideString) -> *mut BolideList {
+    let path = path.as_ref().map_or("", |path| path.as_str());
+    let list = crate::bolide_list_new(crate::ElementType::String as u8);
+    match std::fs::read_to_string(path) {
+        Ok(text) => {
+            for line in text.lines() {
+                (*list).push_owned(BolideString::new(line) as i64);
+            }
+        }
+        Err(e) => set_error(ERROR_IO, &format!("read_lines: '{}': {}", path, e)),
+    }
+    list
+}
+
+fn finish(outcome: IoOutcome) -> *mut BolideString {
+    match outcome {
+        Ok(text) => BolideString::new(&text),
+        Err(message) => {
+            set_error(ERROR_IO, &message);
+            BolideString::new("")
+        }
+    }
+}
+
+/// 在 I/O 线程上读取文件，返回 future
+///
+/// # Safety
+/// `path` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_file_read_async(path: *const BolideString) -> *mut BolideFuture {
+    let path = path.as_ref().map_or(String::new(), |path| path.as_str().to_string());
+    spawn_read(move || read_file(&path))
+}
+
+/// 在 I/O 线程上读一行输入，返回 future；`prompt` 为空指针时不显示提示
+///
+/// 经过和 `input()` 相同的读取函数（见 `bolide_set_input_handler`）。
+///
+/// # Safety
+/// `prompt` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_input_async(prompt: *const BolideString) -> *mut BolideFuture {
+    let prompt = prompt.as_ref().map(|prompt| prompt.as_str().to_string());
+    // 之前的输出先于提示出现
+    crate::output::flush_stdout();
+    spawn_read(move || {
+        let prompt = prompt.map_or(std::ptr::null_mut(), |p| BolideString::new(&p));
+        let line = crate::print::read_input(prompt);
+        let text = unsafe { (*line).as_str().to_string() };
+        bolide_string_release(line);
+        bolide_string_release(prompt);
+        Ok(text)
+    })
+}
+
+/// 等待 `bolide_input_async` / `bolide_file_read_async` 的结果并释放 future
+///
+/// 等待是挂起点：single 调度模式下其他协程在此期间执行。读取失败时记录 `ERROR_IO` 并返回空串。
+///
+/// # Safety
+/// `future` 为空指针，或 `bolide_input_async` / `bolide_file_read_async` 返回且还没有等待过的 future
+#[no_mangle]
+pub unsafe extern "C" fn bolide_io_await(future: *mut BolideFuture) -> *mut BolideString {
+    if future.is_null() {
+        return BolideString::new("");
+    }
+    let outcome = Box::from_raw(future).await_result()
+        .map(|r| *Box::from_raw(r.ptr_val as *mut IoOutcome))
+        .unwrap_or_else(|| Err("I/O request was cancelled".to_string()));
+    finish(outcome)
+}
+
+/// set_io_workers(n)：I/O 线程数上限（至少 1）
+#[no_mangle]
+pub extern "C" fn bolide_set_io_workers(n: i64) {
+    set_io_workers(n.max(1) as usize);
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::executor::{self, bolide_set_executor_single};
+    use crate::{bolide_coroutine_await_int, bolide_coroutine_free, bolide_coroutine_spawn_int, bolide_coroutine_spawn_int_with_env};
+    use std::io::Write;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static LOG: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+    static FIFO: Mutex<String> = Mutex::new(String::new());
+    static READ_STARTED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn step() -> i64 {
+        1
+    }
+
+    fn yield_now() -> i64 {
+        let f = bolide_coroutine_spawn_int(step);
+        let r = bolide_coroutine_await_int(f);
+        bolide_coroutine_free(f);
+        r
+    }
+
+    /// 读 FIFO：写端打开并写入之前读取一直阻塞
+    extern "C" fn reader() -> i64 {
+        LOG.lock().unwrap().push("read start");
+        READ_STARTED.store(true, Ordering::SeqCst);
+        let path = BolideString::new(&FIFO.lock().unwrap());
+        let text = unsafe { bolide_io_await(bolide_file_read_async(path)) };
+        LOG.lock().unwrap().push("read done");
+        let len = unsafe { (*text).as_str().len() } as i64;
+        bolide_string_release(text);
+        bolide_string_release(path);
+        len
+    }
+
+    /// 等读取开始后逐次 await 计数，最后写 FIFO 让读取结束
+    extern "C" fn counter(env: *mut c_void) -> i64 {
+        while !READ_STARTED.load(Ordering::SeqCst) {
+            yield_now();
+        }
+        let mut total = 0;
+        for _ in 0..env as i64 {
+            LOG.lock().unwrap().push("count");
+            total += yield_now();
+        }
+        let path = FIFO.lock().unwrap().clone();
+        std::fs::OpenOptions::new().write(true).open(path).unwrap().write_all(b"hello").unwrap();
+        total
+    }
+
+    #[test]
+    fn test_async_read_does_not_stall_single_executor() {
+        // 读取失败：错误记录在等待的线程上
+        let missing = BolideString::new("/nonexistent/bolide.txt");
+        let text = unsafe { bolide_io_await(bolide_file_read_async(missing)) };
+        assert_eq!(unsafe { (*text).as_str() }, "");
+        let (code, message) = crate::last_error().unwrap();
+        assert_eq!(code, ERROR_IO);
+        assert!(message.starts_with("read_file: '/nonexistent/bolide.txt'"), "{}", message);
+        bolide_string_release(text);
+        bolide_string_release(missing);
+
+        let dir = std::env::temp_dir().join(format!("bolide_io_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fifo = dir.join("slow");
+        assert!(std::process::Command::new("mkfifo").arg(&fifo).status().unwrap().success());
+        *FIFO.lock().unwrap() = fifo.to_str().unwrap().to_string();
+
+        // 单线程调度：读取若占住执行权，计数的协程永远跑不了，写端也就不会打开
+        bolide_set_executor_single(1);
+        let read = bolide_coroutine_spawn_int(reader);
+        let count = bolide_coroutine_spawn_int_with_env(counter, 50 as *mut c_void);
+        assert_eq!(bolide_coroutine_await_int(count), 50);
+        assert_eq!(bolide_coroutine_await_int(read), 5);
+        bolide_coroutine_free(read);
+        bolide_coroutine_free(count);
+        executor::release();
+
+        let log = LOG.lock().unwrap();
+        let start = log.iter().position(|e| *e == "read start").unwrap();
+        let done = log.iter().position(|e| *e == "read done").unwrap();
+        assert_eq!(log[start + 1..done].iter().filter(|e| **e == "count").count(), 50, "{:?}", log);
+
+        // 关闭后不留下 I/O 线程
+        assert!(io_workers() >= 1);
+        assert!(shutdown_io_workers(Duration::from_secs(5)));
+        assert_eq!(io_workers(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_lines() {
+        let dir = std::env::temp_dir().join(format!("bolide_write_lines_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("out.csv");
+        let path = BolideString::new(file.to_str().unwrap());
+
+        let lines = crate::bolide_list_new(crate::ElementType::String as u8);
+        for line in ["a,b", "\"x\ny\",数据", ""] {
+            crate::bolide_list_push(lines, BolideString::new(line) as i64);
+        }
+        unsafe { bolide_file_write_lines(path, lines) };
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "a,b\n\"x\ny\",数据\n\n");
+
+        // 已有的文件被覆盖；空列表得到空文件
+        let empty = crate::bolide_list_new(crate::ElementType::String as u8);
+        unsafe { bolide_file_write_lines(path, empty) };
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "");
+
+        // 写不了的路径记录 ERROR_IO
+        let missing = BolideString::new("/nonexistent/dir/out.csv");
+        unsafe { bolide_file_write_lines(missing, lines) };
+        let (code, message) = crate::last_error().unwrap();
+        assert_eq!(code, ERROR_IO);
+        assert!(message.starts_with("write_lines: '/nonexistent/dir/out.csv'"), "{}", message);
+
+        crate::bolide_list_release(lines);
+        crate::bolide_list_release(empty);
+        bolide_string_release(missing);
+        bolide_string_release(path);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_append_and_read_lines() {
+        let dir = std::env::temp_dir().join(format!("bolide_file_io_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("notes.txt");
+        let path = BolideString::new(file.to_str().unwrap());
+        let first = BolideString::new("one\r\ntwo\n");
+        let more = BolideString::new("三");
+
+        assert_eq!(unsafe { bolide_file_exists(path) }, 0);
+        assert_eq!(unsafe { bolide_file_append(path, first) }, 1);
+        assert_eq!(unsafe { bolide_file_append(path, more) }, 1);
+        assert_eq!(unsafe { bolide_file_exists(path) }, 1);
+        let lines = unsafe { bolide_file_lines(path) };
+        let read: Vec<String> = (0..unsafe { (*lines).len() })
+            .map(|i| unsafe { (*(((*lines).get(i).unwrap()) as *const BolideString)).as_str().to_string() })
+            .collect();
+        assert_eq!(read, ["one", "two", "三"]);
+        crate::bolide_list_release(lines);
+
+        // write_file 覆盖已有内容
+        assert_eq!(unsafe { bolide_file_write(path, more) }, 1);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "三");
+
+        // 失败返回 0 / 空列表并记录 ERROR_IO
+        let missing = BolideString::new("/nonexistent/dir/notes.txt");
+        assert_eq!(unsafe { bolide_file_write(missing, first) }, 0);
+        let (code, message) = crate::last_error().unwrap();
+        assert_eq!(code, ERROR_IO);
+        assert!(message.starts_with("write_file: '/nonexistent/dir/notes.txt'"), "{}", message);
+        let empty = unsafe { bolide_file_lines(missing) };
+        assert_eq!(unsafe { (*empty).len() }, 0);
+        assert!(crate::last_error().unwrap().1.starts_with("read_lines: "));
+        assert_eq!(unsafe { bolide_file_exists(missing) }, 0);
+
+        // remove_file 删除后文件不再存在；再删一次失败
+        assert_eq!(unsafe { bolide_file_remove(path) }, 1);
+        assert_eq!(unsafe { bolide_file_exists(path) }, 0);
+        assert_eq!(unsafe { bolide_file_remove(path) }, 0);
+        assert!(crate::last_error().unwrap().1.starts_with("remove_file: "));
+        let tmp = bolide_temp_dir();
+        assert_eq!(unsafe { bolide_file_exists(tmp) }, 1);
+        bolide_string_release(tmp);
+
+        crate::bolide_list_release(empty);
+        for s in [path, first, more, missing] {
+            bolide_string_release(s);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -8,11 +8,22 @@
 //! - `bigint`: 任意精度整数
 //! - `decimal`: 任意精度小数
 //! - `dynamic`: 动态类型
-//! - `list`: 列表类型
+//! - `list`: 列表类型（含 sum/min/max/avg 聚合）
 //! - `dict`: 字典类型
+//! - `weak_dict`: 键为弱引用的字典（键是类实例，键释放后表项失效）
+//! - `packed_list`: 类实例内联存储的列表（`list<packed T>`）
 //! - `print`: 统一打印功能
+//! - `output`: 标准输出（读端关闭时安静退出）
 //! - `thread`: 线程和线程池
 //! - `channel`: 线程安全通道
+//! - `closure`: 绑定函数（偏函数应用）
+//! - `timer`: 计时器和 measure 块
+//! - `hash`: 值哈希（`hash()` 内置和字典共用）
+//! - `executor`: 协程工作线程池、协程栈与调度模式（threaded / single）
+//! - `interrupt`: 嵌入时的协作式中断（时间预算和取消）
+//! - `error`: 可恢复的运行时错误（`error()` / `error_message()`）
+//! - `io`: 阻塞 I/O 的专用线程池（async 函数里的 `input()` / `read_file()`）
+//! - `testing`: 测试块的断言（`assert` / `assert_eq`），供 `bolide test` 汇总失败
 
 mod rc;
 mod string;
@@ -21,13 +32,28 @@ mod decimal;
 mod dynamic;
 pub mod list;
 pub mod dict;
+mod weak_dict;
+mod packed_list;
 mod print;
+mod output;
 mod thread;
 mod channel;
 mod object;
 mod coroutine;
 mod tuple;
 mod ffi;
+mod closure;
+mod timer;
+mod hash;
+mod executor;
+mod interrupt;
+mod error;
+mod io;
+mod version;
+mod trace;
+mod sample;
+mod testing;
+mod tls;
 
 pub use rc::*;
 pub use string::*;
@@ -36,17 +62,40 @@ pub use decimal::*;
 pub use dynamic::*;
 pub use list::*;
 pub use dict::*;
+pub use weak_dict::*;
+pub use packed_list::*;
 pub use print::*;
+pub use output::{write_stdout, flush_stdout};
 pub use thread::*;
 pub use channel::*;
 pub use object::*;
 pub use coroutine::*;
 pub use tuple::*;
 pub use ffi::*;
+pub use closure::*;
+pub use timer::*;
+pub use hash::*;
+pub use executor::*;
+pub use interrupt::*;
+pub use error::*;
+pub use io::*;
+pub use version::*;
+pub use trace::*;
+pub use sample::*;
+pub use testing::*;
+pub use tls::*;
 
+/// 版本标记的前缀：`bolide toolchain` 在下载的预编译运行时库里查找它来确认库的版本
+pub const VERSION_TAG_PREFIX: &str = "bolide-runtime-version:";
+
+/// 嵌入静态库的版本标记（前缀 + 版本号 + NUL），与 CLI 版本不同的库不能链接
+#[no_mangle]
+#[used]
+pub static BOLIDE_RUNTIME_VERSION: &str = concat!("bolide-runtime-version:", env!("CARGO_PKG_VERSION"), "\0");
 
 use std::alloc::{alloc, dealloc, Layout};
 use std::os::raw::c_void;
+use std::sync::atomic::{AtomicU8, Ordering};
 
 /// 分配内存（用于 spawn 环境块）
 #[no_mangle]
@@ -67,3 +116,105 @@ pub extern "C" fn bolide_free(ptr: *mut c_void, size: i64) {
     let layout = Layout::from_size_align(size as usize, 8).unwrap();
     unsafe { dealloc(ptr as *mut u8, layout) }
 }
+
+// ==================== 运行时错误 ====================
+
+/// 报告运行时错误并以状态码 1 结束程序
+///
+/// 不能用 panic：FFI 函数无法展开，panic 会直接 abort 并打印 Rust 内部的回溯。
+/// 以调试模式运行时接着打印 Bolide 的调用栈（见 `trace`）。
+pub fn runtime_error(message: &str) -> ! {
+    output::flush_stdout_quietly();
+    eprintln!("Runtime error: {}", message);
+    trace::bolide_trace_dump();
+    std::process::exit(1)
+}
+
+// ==================== 退出 ====================
+
+/// 整数退出码（`exit(code)`、顶层代码的返回值）对应的进程退出状态
+///
+/// Unix 上状态只有 0..255：按 C 的 `exit` 取低 8 位（`-1` → 255），
+/// 但非零的值不会变成 0（`256` → 255），失败不会被当成成功。
+pub fn exit_status(code: i64) -> i32 {
+    if cfg!(unix) {
+        match code & 0xFF {
+            0 if code != 0 => 255,
+            low => low as i32,
+        }
+    } else {
+        code.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+}
+
+/// 等待分离线程结束的最长时间
+pub const THREAD_EXIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// 有序退出：等待仍在运行的用户线程（有超时），关闭 I/O 线程，然后卸载 extern 库
+///
+/// `bolide run` 和编译出的可执行文件在顶层代码返回后调用；超时后不卸载库，直接让进程退出结束这些线程。
+#[no_mangle]
+pub extern "C" fn bolide_shutdown() {
+    flush_stdout();
+    // 已被 await / join 的线程可能还在执行收尾，先安静地等一小会儿
+    if !wait_for_threads(std::time::Duration::from_millis(100)) {
+        eprintln!(
+            "warning: {} thread(s) still running after main returned; waiting up to {}s for them to finish",
+            live_threads(),
+            THREAD_EXIT_TIMEOUT.as_secs()
+        );
+        if !wait_for_threads(THREAD_EXIT_TIMEOUT) {
+            eprintln!("warning: {} thread(s) did not finish; exiting anyway", live_threads());
+        }
+    }
+    // async 函数里的 input() / read_file() 使用的 I/O 线程处理完已提交的读取后退出
+    if !shutdown_io_workers(std::time::Duration::from_millis(100)) {
+        eprintln!("warning: {} I/O thread(s) still blocked in a read; exiting anyway", io_workers());
+    }
+    bolide_ffi_cleanup();
+}
+
+/// `exit(code)`：刷新标准输出后立即结束进程，状态见 `exit_status`
+///
+/// 不等待仍在运行的线程，也不释放还活着的对象。
+#[no_mangle]
+pub extern "C" fn bolide_exit(code: i64) -> ! {
+    flush_stdout();
+    std::process::exit(exit_status(code))
+}
+
+// ==================== 平台 ====================
+
+/// platform()：运行程序的操作系统，"windows"、"linux"、"macos" 等
+///
+/// 返回驻留字符串，与字符串字面量一样不会被释放。
+#[no_mangle]
+pub extern "C" fn bolide_platform() -> *mut BolideString {
+    intern_str(std::env::consts::OS)
+}
+
+// ==================== 溢出检查 ====================
+
+/// 0 = 未初始化，1 = 关闭，2 = 开启
+static OVERFLOW_CHECKS: AtomicU8 = AtomicU8::new(0);
+
+/// 是否开启整数溢出检查
+///
+/// 默认关闭（溢出时回绕）。`bolide run --overflow-checks` 会打开它；
+/// 编译出的可执行文件读取环境变量 `BOLIDE_OVERFLOW_CHECKS=1`。
+pub fn overflow_checks_enabled() -> bool {
+    match OVERFLOW_CHECKS.load(Ordering::Relaxed) {
+        0 => {
+            let enabled = std::env::var("BOLIDE_OVERFLOW_CHECKS").is_ok_and(|v| v == "1");
+            OVERFLOW_CHECKS.store(if enabled { 2 } else { 1 }, Ordering::Relaxed);
+            enabled
+        }
+        state => state == 2,
+    }
+}
+
+/// 开启或关闭整数溢出检查（覆盖环境变量）
+#[no_mangle]
+pub extern "C" fn bolide_set_overflow_checks(enabled: i64) {
+    OVERFLOW_CHECKS.store(if enabled != 0 { 2 } else { 1 }, Ordering::Relaxed);
+}
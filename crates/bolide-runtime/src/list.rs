@@ -5,9 +5,11 @@
 
 use std::cell::Cell;
 use std::os::raw::c_void;
+use std::fmt::Write as _;
 
 use crate::rc::{TypeTag, flags};
 use crate::{BolideString, BolideBigInt, BolideDecimal};
+use crate::output::outln;
 
 /// RC 对象头
 #[repr(C)]
@@ -35,6 +37,53 @@ pub enum ElementType {
     Dynamic = 9, // 动态类型
 }
 
+impl ElementType {
+    /// 编译器传来的类型标签（未知标签按 Int 处理）
+    pub(crate) fn from_tag(tag: u8) -> ElementType {
+        match tag {
+            1 => ElementType::Float,
+            2 => ElementType::Bool,
+            3 => ElementType::String,
+            4 => ElementType::BigInt,
+            5 => ElementType::Decimal,
+            6 => ElementType::List,
+            7 => ElementType::Ptr,
+            8 => ElementType::Dict,
+            9 => ElementType::Dynamic,
+            _ => ElementType::Int,
+        }
+    }
+
+    /// 增加这种类型的一个值的引用（值类型和指针类型不做任何事）
+    pub(crate) unsafe fn retain_value(self, value: i64) {
+        let ptr = value as *mut c_void;
+        if ptr.is_null() { return; }
+        match self {
+            ElementType::String => { crate::bolide_string_retain(ptr as *mut BolideString); }
+            ElementType::BigInt => { crate::bolide_bigint_retain(ptr as *mut BolideBigInt); }
+            ElementType::Decimal => { crate::bolide_decimal_retain(ptr as *mut BolideDecimal); }
+            ElementType::List => { bolide_list_retain(ptr as *mut BolideList); }
+            ElementType::Dict => { crate::bolide_dict_retain(ptr as *mut crate::dict::BolideDict); }
+            ElementType::Dynamic => { crate::bolide_dynamic_retain(ptr as *mut crate::dynamic::BolideDynamic); }
+            _ => {}
+        }
+    }
+
+    /// 释放这种类型的一个值的引用
+    pub(crate) unsafe fn release_value(self, value: i64) {
+        let ptr = value as *mut c_void;
+        if ptr.is_null() { return; }
+        match self {
+            ElementType::String => { crate::bolide_string_release(ptr as *mut BolideString); }
+            ElementType::BigInt => { crate::bolide_bigint_release(ptr as *mut BolideBigInt); }
+            ElementType::Decimal => { crate::bolide_decimal_release(ptr as *mut BolideDecimal); }
+            ElementType::List => { bolide_list_release(ptr as *mut BolideList); }
+            ElementType::Dict => { crate::bolide_dict_release(ptr as *mut crate::dict::BolideDict); }
+            ElementType::Dynamic => { crate::bolide_dynamic_release(ptr as *mut crate::dynamic::BolideDynamic); }
+            _ => {}
+        }
+    }
+}
 
 /// Bolide 列表类型（带引用计数）
 #[repr(C)]
@@ -126,6 +175,26 @@ impl BolideList {
         self.len += 1;
     }
 
+    /// 追加一个调用者已持有引用的元素（引用转交给列表，不再 retain）
+    pub(crate) fn push_owned(&mut self, value: i64) {
+        if self.len >= self.capacity {
+            self.reserve(1);
+        }
+        unsafe {
+            *self.data.add(self.len) = value;
+        }
+        self.len += 1;
+    }
+
+    /// 全部元素
+    pub(crate) fn as_slice(&self) -> &[i64] {
+        if self.data.is_null() {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(self.data, self.len) }
+        }
+    }
+
     pub fn pop(&mut self) -> Option<i64> {
         if self.len == 0 {
             None
@@ -195,32 +264,67 @@ impl BolideList {
     }
 
     /// 增加单个元素引用
-    unsafe fn retain_element(&self, value: i64) {
-        let ptr = value as *mut c_void;
-        if ptr.is_null() { return; }
+    pub(crate) unsafe fn retain_element(&self, value: i64) {
+        self.elem_type.retain_value(value);
+    }
+
+    /// 释放单个元素引用
+    pub(crate) unsafe fn release_element(&self, value: i64) {
+        self.elem_type.release_value(value);
+    }
+
+    /// 按元素类型比较两个元素：int/float/bool 按值，str/bigint/decimal/dynamic 按内容，
+    /// 嵌套列表、字典和指针按同一性
+    pub(crate) unsafe fn elements_equal(&self, a: i64, b: i64) -> bool {
+        if a == b && self.elem_type != ElementType::Float {
+            return true;
+        }
         match self.elem_type {
-            ElementType::String => { crate::bolide_string_retain(ptr as *mut BolideString); }
-            ElementType::BigInt => { crate::bolide_bigint_retain(ptr as *mut BolideBigInt); }
-            ElementType::Decimal => { crate::bolide_decimal_retain(ptr as *mut BolideDecimal); }
-            ElementType::List => { bolide_list_retain(ptr as *mut BolideList); }
-            ElementType::Dict => { crate::bolide_dict_retain(ptr as *mut crate::dict::BolideDict); }
-            ElementType::Dynamic => { crate::bolide_dynamic_retain(ptr as *mut crate::dynamic::BolideDynamic); }
-            _ => {}
+            ElementType::Float => f64::from_bits(a as u64) == f64::from_bits(b as u64),
+            ElementType::String => crate::bolide_string_eq(a as *const BolideString, b as *const BolideString) != 0,
+            ElementType::BigInt => crate::bolide_bigint_eq(a as *const BolideBigInt, b as *const BolideBigInt) != 0,
+            ElementType::Decimal => crate::bolide_decimal_eq(a as *const BolideDecimal, b as *const BolideDecimal) != 0,
+            ElementType::Dynamic => crate::bolide_dynamic_eq(a as *const crate::dynamic::BolideDynamic, b as *const crate::dynamic::BolideDynamic) != 0,
+            _ => false,
         }
     }
 
-    /// 释放单个元素引用
-    unsafe fn release_element(&self, value: i64) {
-        let ptr = value as *mut c_void;
-        if ptr.is_null() { return; }
+    /// 逐元素比较两个列表的内容：长度相同且对应元素相等（按本列表的元素类型），
+    /// 嵌套列表递归比较内容而不是同一性
+    pub(crate) unsafe fn contents_equal(&self, other: &BolideList) -> bool {
+        if self.len != other.len {
+            return false;
+        }
+        (0..self.len).all(|i| {
+            let (a, b) = (*self.data.add(i), *other.data.add(i));
+            if self.elem_type == ElementType::List && a != b {
+                match ((a as *const BolideList).as_ref(), (b as *const BolideList).as_ref()) {
+                    (Some(x), Some(y)) => x.contents_equal(y),
+                    _ => false,
+                }
+            } else {
+                self.elements_equal(a, b)
+            }
+        })
+    }
+
+    /// 与 `elements_equal` 一致的元素哈希（相等的元素哈希相同）
+    pub(crate) unsafe fn element_hash(&self, value: i64) -> i64 {
         match self.elem_type {
-            ElementType::String => { crate::bolide_string_release(ptr as *mut BolideString); }
-            ElementType::BigInt => { crate::bolide_bigint_release(ptr as *mut BolideBigInt); }
-            ElementType::Decimal => { crate::bolide_decimal_release(ptr as *mut BolideDecimal); }
-            ElementType::List => { bolide_list_release(ptr as *mut BolideList); }
-            ElementType::Dict => { crate::bolide_dict_release(ptr as *mut crate::dict::BolideDict); }
-            ElementType::Dynamic => { crate::bolide_dynamic_release(ptr as *mut crate::dynamic::BolideDynamic); }
-            _ => {}
+            ElementType::Float => crate::hash::hash_f64(f64::from_bits(value as u64)),
+            ElementType::String => crate::bolide_hash_string(value as *const BolideString),
+            ElementType::BigInt => crate::bolide_hash_bigint(value as *const BolideBigInt),
+            ElementType::Decimal => crate::bolide_hash_decimal(value as *const BolideDecimal),
+            ElementType::Dynamic => {
+                let d = value as *const crate::dynamic::BolideDynamic;
+                if d.is_null() || (*d).tag != crate::dynamic::DynamicType::List {
+                    crate::bolide_hash_dynamic(d)
+                } else {
+                    // 动态列表按同一性比较，哈希地址即可
+                    crate::hash::hash_i64(value)
+                }
+            }
+            _ => crate::hash::hash_i64(value),
         }
     }
 
@@ -231,14 +335,6 @@ impl BolideList {
             self.release_element(val);
         }
     }
-
-    /// 增加所有元素的引用计数（用于 clone）
-    unsafe fn retain_elements(&self) {
-        for i in 0..self.len {
-            let val = *self.data.add(i);
-            self.retain_element(val);
-        }
-    }
 }
 
 // ==================== FFI 导出 ====================
@@ -246,20 +342,7 @@ impl BolideList {
 /// 创建新列表
 #[no_mangle]
 pub extern "C" fn bolide_list_new(elem_type: u8) -> *mut BolideList {
-    let elem_type = match elem_type {
-        0 => ElementType::Int,
-        1 => ElementType::Float,
-        2 => ElementType::Bool,
-        3 => ElementType::String,
-        4 => ElementType::BigInt,
-        5 => ElementType::Decimal,
-        6 => ElementType::List,
-        7 => ElementType::Ptr,
-        8 => ElementType::Dict,
-        9 => ElementType::Dynamic,
-        _ => ElementType::Int,
-    };
-    BolideList::new(elem_type)
+    BolideList::new(ElementType::from_tag(elem_type))
 }
 
 /// 创建带初始容量的列表
@@ -278,6 +361,38 @@ pub extern "C" fn bolide_list_with_capacity(elem_type: u8, capacity: usize) -> *
     BolideList::with_capacity(elem_type, capacity)
 }
 
+/// 由编译期序列化的常量数据创建列表（大列表字面量）
+///
+/// int/float/bool 列表的 `data` 是 `count` 个 i64（float 为位模式）；str 列表的 `data` 是
+/// `count + 1` 个 u64 偏移，后面紧跟所有字符串拼接成的字节块，第 i 个字符串占
+/// `[offsets[i], offsets[i + 1])`，与字符串字面量一样驻留。
+#[no_mangle]
+pub extern "C" fn bolide_list_from_const_array(elem_type: u8, data: *const u8, count: usize) -> *mut BolideList {
+    let list = bolide_list_with_capacity(elem_type, count);
+    if data.is_null() || count == 0 {
+        return list;
+    }
+    unsafe {
+        let target = &mut *list;
+        if target.elem_type == ElementType::String {
+            let offsets = std::slice::from_raw_parts(data as *const u64, count + 1);
+            let blob = data.add((count + 1) * std::mem::size_of::<u64>());
+            for bounds in offsets.windows(2) {
+                let (start, end) = (bounds[0] as usize, bounds[1] as usize);
+                let s = crate::bolide_string_literal(blob.add(start) as *const i8, end - start);
+                // push 会 retain，创建时的引用在这里交还
+                target.push(s as i64);
+                crate::bolide_string_release(s);
+            }
+        } else {
+            for &value in std::slice::from_raw_parts(data as *const i64, count) {
+                target.push(value);
+            }
+        }
+    }
+    list
+}
+
 /// 增加引用计数
 #[no_mangle]
 pub extern "C" fn bolide_list_retain(list: *mut BolideList) -> *mut BolideList {
@@ -323,15 +438,12 @@ pub extern "C" fn bolide_list_clone(list: *const BolideList) -> *mut BolideList
         let new_list = BolideList::with_capacity(src.elem_type, src.len);
         let dst = &mut *new_list;
 
-        // 复制元素
+        // 复制元素（push 会增加元素的引用计数）
         for i in 0..src.len {
             let value = *src.data.add(i);
             dst.push(value);
         }
 
-        // 增加所有元素的引用计数
-        dst.retain_elements();
-
         new_list
     }
 }
@@ -357,25 +469,64 @@ pub extern "C" fn bolide_list_push(list: *mut BolideList, value: i64) {
     unsafe { (*list).push(value); }
 }
 
-/// 弹出最后一个元素
+/// 记录下标越界（`ERROR_INDEX`），下标按有符号数显示
+fn index_error(func: &str, index: usize, len: usize) {
+    crate::set_error(crate::ERROR_INDEX, &format!("{}: index {} out of range for list of length {}", func, index as i64, len));
+}
+
+/// 弹出最后一个元素；空列表记录 `ERROR_INDEX` 并返回 0
 #[no_mangle]
 pub extern "C" fn bolide_list_pop(list: *mut BolideList) -> i64 {
     if list.is_null() { return 0; }
-    unsafe { (*list).pop().unwrap_or(0) }
+    unsafe { (*list).pop() }.unwrap_or_else(|| {
+        crate::set_error(crate::ERROR_INDEX, "pop: empty list");
+        0
+    })
 }
 
-/// 获取指定位置的元素
+/// 获取指定位置的元素；越界时记录 `ERROR_INDEX` 并返回 0
 #[no_mangle]
 pub extern "C" fn bolide_list_get(list: *const BolideList, index: usize) -> i64 {
     if list.is_null() { return 0; }
-    unsafe { (*list).get(index).unwrap_or(0) }
+    let list = unsafe { &*list };
+    list.get(index).unwrap_or_else(|| {
+        index_error("get", index, list.len);
+        0
+    })
 }
 
-/// 设置指定位置的元素
+/// 设置指定位置的元素；越界时记录 `ERROR_INDEX` 并返回 0
 #[no_mangle]
 pub extern "C" fn bolide_list_set(list: *mut BolideList, index: usize, value: i64) -> i64 {
     if list.is_null() { return 0; }
-    unsafe { if (*list).set(index, value) { 1 } else { 0 } }
+    let list = unsafe { &mut *list };
+    if list.set(index, value) {
+        1
+    } else {
+        index_error("set", index, list.len);
+        0
+    }
+}
+
+// float 元素在槽位里存 f64 的位模式；编译后的代码通过下面三个入口按 f64 传值，
+// 其余操作（pop、remove、insert、contains 等）由编译器在调用处转换位模式
+
+/// 追加 float 元素
+#[no_mangle]
+pub extern "C" fn bolide_list_push_f64(list: *mut BolideList, value: f64) {
+    bolide_list_push(list, value.to_bits() as i64);
+}
+
+/// 获取 float 元素；越界时记录 `ERROR_INDEX` 并返回 0.0
+#[no_mangle]
+pub extern "C" fn bolide_list_get_f64(list: *const BolideList, index: usize) -> f64 {
+    f64::from_bits(bolide_list_get(list, index) as u64)
+}
+
+/// 设置 float 元素；越界时记录 `ERROR_INDEX` 并返回 0
+#[no_mangle]
+pub extern "C" fn bolide_list_set_f64(list: *mut BolideList, index: usize, value: f64) -> i64 {
+    bolide_list_set(list, index, value.to_bits() as i64)
 }
 
 /// 获取元素类型
@@ -431,13 +582,14 @@ pub extern "C" fn bolide_list_insert(list: *mut BolideList, index: usize, value:
     }
 }
 
-/// 移除并返回指定位置的元素
+/// 移除并返回指定位置的元素；越界时记录 `ERROR_INDEX` 并返回 0
 #[no_mangle]
 pub extern "C" fn bolide_list_remove(list: *mut BolideList, index: usize) -> i64 {
     if list.is_null() { return 0; }
     unsafe {
         let list = &mut *list;
         if index >= list.len {
+            index_error("remove", index, list.len);
             return 0;
         }
         
@@ -490,20 +642,21 @@ pub extern "C" fn bolide_list_reverse(list: *mut BolideList) {
 }
 
 /// 扩展列表（用另一个列表的元素）
+///
+/// `other` 可以就是 `list`（`xs += xs`）：先记下元素个数，扩容之后再读源数据。
 #[no_mangle]
 pub extern "C" fn bolide_list_extend(list: *mut BolideList, other: *const BolideList) {
     if list.is_null() || other.is_null() { return; }
     unsafe {
-        let list = &mut *list;
-        let other = &*other;
-        
+        let count = (*other).len;
+
         // 确保有足够空间
-        list.reserve(other.len);
-        
+        (*list).reserve(count);
+
         // 复制元素
-        for i in 0..other.len {
-            let value = *other.data.add(i);
-            list.push(value);
+        for i in 0..count {
+            let value = *(*other).data.add(i);
+            (*list).push(value);
         }
     }
 }
@@ -554,32 +707,343 @@ pub extern "C" fn bolide_list_count(list: *const BolideList, value: i64) -> i64
     }
 }
 
-/// 原地排序（仅支持 Int 和 Float 类型）
+/// 原地移除相邻的重复元素，只保留每段相等元素中的第一个
+///
+/// 相等按元素类型判断（见 `elements_equal`），被移除的 RC 元素会被释放。
 #[no_mangle]
-pub extern "C" fn bolide_list_sort(list: *mut BolideList) {
+pub extern "C" fn bolide_list_dedup(list: *mut BolideList) {
     if list.is_null() { return; }
     unsafe {
         let list = &mut *list;
         if list.len <= 1 { return; }
-        
-        match list.elem_type {
-            ElementType::Int => {
-                // 转换为 slice 并排序
-                let slice = std::slice::from_raw_parts_mut(list.data, list.len);
-                slice.sort();
+
+        let mut kept = 1;
+        for i in 1..list.len {
+            let value = *list.data.add(i);
+            if list.elements_equal(*list.data.add(kept - 1), value) {
+                list.release_element(value);
+            } else {
+                *list.data.add(kept) = value;
+                kept += 1;
+            }
+        }
+        list.len = kept;
+    }
+}
+
+/// 返回只含每个值第一次出现的新列表（保持首次出现的顺序）
+///
+/// 按元素哈希分桶，整体 O(n)；新列表持有各元素的引用。
+#[no_mangle]
+pub extern "C" fn bolide_list_unique(list: *const BolideList) -> *mut BolideList {
+    if list.is_null() { return std::ptr::null_mut(); }
+    unsafe {
+        let src = &*list;
+        let result = BolideList::new(src.elem_type);
+        let dst = &mut *result;
+        let mut seen: std::collections::HashMap<i64, Vec<i64>> = std::collections::HashMap::new();
+
+        for &value in src.as_slice() {
+            let bucket = seen.entry(src.element_hash(value)).or_default();
+            if bucket.iter().any(|&other| src.elements_equal(other, value)) {
+                continue;
+            }
+            bucket.push(value);
+            dst.push(value);
+        }
+        result
+    }
+}
+
+/// 按键函数分组：`key_fn(elem)` 的结果作为键，值为该键下元素组成的列表（保持原顺序）
+///
+/// `key_type` 是键函数返回值的类型标签：
+/// - int / bool：按值作为键
+/// - str：字典 retain 新键，键函数返回的字符串随后释放
+/// - dynamic：键函数返回 dynamic 值，按内容合并相等的键；字典不释放键，
+///   每个不同的键由字典一直持有
+///
+/// 键函数按借用方式接收元素，通过 `bolide_closure_call` 调用（支持 bind 生成的函数值）。
+/// 分组列表持有各元素的引用。
+#[no_mangle]
+pub extern "C" fn bolide_list_group_by(list: *const BolideList, key_fn: i64, key_type: u8) -> *mut crate::dict::BolideDict {
+    let key_type = match key_type {
+        2 => ElementType::Bool,
+        3 => ElementType::String,
+        9 => ElementType::Dynamic,
+        _ => ElementType::Int,
+    };
+    let dict = crate::dict::BolideDict::new(key_type, ElementType::List);
+    if list.is_null() { return dict; }
+    unsafe {
+        let src = &*list;
+        let groups = &mut *dict;
+        // dynamic 键：哈希 -> 已作为键的 dynamic 值
+        let mut dynamic_keys: std::collections::HashMap<i64, Vec<i64>> = std::collections::HashMap::new();
+
+        for &value in src.as_slice() {
+            let mut key = crate::bolide_closure_call(key_fn, &value, 1);
+            if key_type == ElementType::Dynamic {
+                let d = key as *mut crate::dynamic::BolideDynamic;
+                let bucket = dynamic_keys.entry(crate::bolide_hash_dynamic(d)).or_default();
+                match bucket.iter().find(|&&k| crate::bolide_dynamic_eq(k as *const _, d) != 0) {
+                    Some(&existing) => {
+                        crate::bolide_dynamic_release(d);
+                        key = existing;
+                    }
+                    None => bucket.push(key),
+                }
+            }
+
+            match groups.get(key) {
+                Some(group) => (*(group as *mut BolideList)).push(value),
+                None => {
+                    let group = BolideList::new(src.elem_type);
+                    (*group).push(value);
+                    groups.set(key, group as i64);
+                    bolide_list_release(group);
+                }
             }
-            ElementType::Float => {
-                let slice = std::slice::from_raw_parts_mut(list.data, list.len);
-                slice.sort_by(|a, b| {
-                    let fa = f64::from_bits(*a as u64);
-                    let fb = f64::from_bits(*b as u64);
-                    fa.partial_cmp(&fb).unwrap_or(std::cmp::Ordering::Equal)
-                });
+
+            if key_type == ElementType::String {
+                crate::bolide_string_release(key as *mut BolideString);
             }
-            _ => {
-                // 其他类型不支持排序
+        }
+        dict
+    }
+}
+
+/// 按位存入 i64 的两个 float 的全序：-0.0 排在 0.0 前，所有 NaN（不论符号位）排在最后且彼此相等
+fn float_order(a: i64, b: i64) -> std::cmp::Ordering {
+    let (a, b) = (f64::from_bits(a as u64), f64::from_bits(b as u64));
+    match (a.is_nan(), b.is_nan()) {
+        (false, false) => a.total_cmp(&b),
+        (nan_a, nan_b) => nan_a.cmp(&nan_b),
+    }
+}
+
+/// 键函数结果的比较方式（`sort_by_key` / `min_by` / `max_by` 的 `key_type`）
+#[derive(Clone, Copy, PartialEq)]
+enum KeyKind {
+    /// int / bool：按有符号整数比较
+    Int,
+    /// float：按 `float_order` 比较（-0.0 < 0.0，NaN 排在最后）
+    Float,
+    /// str：按字节序比较；键函数返回新字符串，用完释放
+    Str,
+}
+
+impl KeyKind {
+    fn from_tag(key_type: u8) -> KeyKind {
+        match key_type {
+            1 => KeyKind::Float,
+            3 => KeyKind::Str,
+            _ => KeyKind::Int,
+        }
+    }
+
+    /// 调用键函数；float 键按位存入 i64
+    fn key_of(self, key_fn: i64, value: i64) -> i64 {
+        match self {
+            KeyKind::Float => crate::closure::closure_call_f64(key_fn, &[value]).to_bits() as i64,
+            _ => crate::bolide_closure_call(key_fn, &value, 1),
+        }
+    }
+
+    fn compare(self, a: i64, b: i64) -> std::cmp::Ordering {
+        match self {
+            KeyKind::Int => a.cmp(&b),
+            KeyKind::Float => float_order(a, b),
+            KeyKind::Str => unsafe {
+                (*(a as *const BolideString)).as_str().cmp((*(b as *const BolideString)).as_str())
+            },
+        }
+    }
+
+    fn release(self, key: i64) {
+        if self == KeyKind::Str {
+            crate::bolide_string_release(key as *mut BolideString);
+        }
+    }
+}
+
+/// 按键函数原地排序（稳定：键相等的元素保持原来的相对顺序）
+///
+/// 每个元素只调用一次键函数，键缓存后再排序；`key_type` 是键函数返回值的类型标签
+/// （int / bool / float / str）。键函数按借用方式接收元素，不能修改列表本身。
+#[no_mangle]
+pub extern "C" fn bolide_list_sort_by_key(list: *mut BolideList, key_fn: i64, key_type: u8) {
+    if list.is_null() { return; }
+    let kind = KeyKind::from_tag(key_type);
+    unsafe {
+        let values = (*list).as_slice().to_vec();
+        let mut keyed: Vec<(i64, i64)> = values.iter().map(|&value| (kind.key_of(key_fn, value), value)).collect();
+        if (*list).len != values.len() {
+            crate::runtime_error("sort_by_key: list modified by the key function");
+        }
+        keyed.sort_by(|a, b| kind.compare(a.0, b.0));
+        for (i, &(key, value)) in keyed.iter().enumerate() {
+            *(*list).data.add(i) = value;
+            kind.release(key);
+        }
+    }
+}
+
+/// 键最小（`want` 为 Less）或最大（Greater）的元素；键相等时取第一个，空列表是运行时错误
+fn extreme_by(list: *const BolideList, key_fn: i64, key_type: u8, want: std::cmp::Ordering, func: &str) -> i64 {
+    let kind = KeyKind::from_tag(key_type);
+    let values = unsafe { elements(list) }.to_vec();
+    let (&first, rest) = values.split_first().unwrap_or_else(|| empty_list_error(func));
+    let mut best = (kind.key_of(key_fn, first), first);
+    for &value in rest {
+        let key = kind.key_of(key_fn, value);
+        if kind.compare(key, best.0) == want {
+            kind.release(std::mem::replace(&mut best, (key, value)).0);
+        } else {
+            kind.release(key);
+        }
+    }
+    kind.release(best.0);
+    best.1
+}
+
+/// `min_by(key_fn)`：键最小的元素（借用，不增加引用计数）
+#[no_mangle]
+pub extern "C" fn bolide_list_min_by(list: *const BolideList, key_fn: i64, key_type: u8) -> i64 {
+    extreme_by(list, key_fn, key_type, std::cmp::Ordering::Less, "min_by")
+}
+
+/// `max_by(key_fn)`：键最大的元素（借用，不增加引用计数）
+#[no_mangle]
+pub extern "C" fn bolide_list_max_by(list: *const BolideList, key_fn: i64, key_type: u8) -> i64 {
+    extreme_by(list, key_fn, key_type, std::cmp::Ordering::Greater, "max_by")
+}
+
+/// 回调之后检查列表没有被改动（元素按借用方式传给回调，改动会让后面的元素失效）
+fn check_unmodified(list: *const BolideList, len: usize, func: &str) {
+    if unsafe { (*list).len } != len {
+        crate::runtime_error(&format!("{}: list modified by the callback", func));
+    }
+}
+
+/// map(f, xs) -> list：依次用每个元素调用 `func`，返回值组成新列表
+///
+/// `result_type` 是 `func` 返回值的类型标签（float 返回值按位存入 i64）。
+/// 返回的 RC 值是新引用，直接交给结果列表。
+#[no_mangle]
+pub extern "C" fn bolide_list_map(list: *const BolideList, func: i64, result_type: u8) -> *mut BolideList {
+    let result_type = ElementType::from_tag(result_type);
+    let values = unsafe { elements(list) }.to_vec();
+    let result = BolideList::with_capacity(result_type, values.len());
+    for value in values.iter() {
+        let mapped = match result_type {
+            ElementType::Float => crate::closure::closure_call_f64(func, &[*value]).to_bits() as i64,
+            _ => crate::bolide_closure_call(func, value, 1),
+        };
+        unsafe { (*result).push_owned(mapped) };
+        check_unmodified(list, values.len(), "map");
+    }
+    result
+}
+
+/// filter(f, xs) -> list：`func` 返回真的元素组成新列表（保持原顺序，持有各元素的引用）
+#[no_mangle]
+pub extern "C" fn bolide_list_filter(list: *const BolideList, func: i64) -> *mut BolideList {
+    if list.is_null() { return BolideList::new(ElementType::Int); }
+    let values = unsafe { elements(list) }.to_vec();
+    let result = BolideList::new(unsafe { (*list).elem_type });
+    for value in values.iter() {
+        let keep = crate::bolide_closure_call(func, value, 1) != 0;
+        check_unmodified(list, values.len(), "filter");
+        if keep {
+            unsafe { (*result).push(*value) };
+        }
+    }
+    result
+}
+
+/// reduce(f, xs, init) -> T：`acc = func(acc, x)` 依次折叠每个元素，空列表返回 `init`
+///
+/// `acc_type` 是累加值的类型标签。`init` 是借用的，结果总是新引用；
+/// 每一步的返回值是新引用，上一步的累加值在调用后释放。
+#[no_mangle]
+pub extern "C" fn bolide_list_reduce(list: *const BolideList, func: i64, init: i64, acc_type: u8) -> i64 {
+    let acc_type = ElementType::from_tag(acc_type);
+    let values = unsafe { elements(list) }.to_vec();
+    let mut acc = init;
+    unsafe { acc_type.retain_value(acc) };
+    for &value in values.iter() {
+        let next = crate::bolide_closure_call(func, [acc, value].as_ptr(), 2);
+        unsafe { acc_type.release_value(acc) };
+        acc = next;
+        check_unmodified(list, values.len(), "reduce");
+    }
+    acc
+}
+
+/// 原地升序排序（稳定：相等的元素保持原来的相对顺序），按元素类型比较：
+/// int / bool 按数值，float 按 `float_order`（NaN 排在最后），str 按内容的字节序；
+/// 其他类型不排序
+#[no_mangle]
+pub extern "C" fn bolide_list_sort(list: *mut BolideList) {
+    if list.is_null() { return; }
+    unsafe {
+        let list = &mut *list;
+        if list.len <= 1 { return; }
+        let slice = std::slice::from_raw_parts_mut(list.data, list.len);
+        match list.elem_type {
+            ElementType::Int | ElementType::Bool => slice.sort(),
+            ElementType::Float => slice.sort_by(|&a, &b| float_order(a, b)),
+            ElementType::String => slice.sort_by(|&a, &b| {
+                crate::bolide_string_cmp(a as *const BolideString, b as *const BolideString).cmp(&0)
+            }),
+            _ => {}
+        }
+    }
+}
+
+/// 稳定的归并排序；`less_or_equal(a, b)` 为真时 a 排在 b 前面。
+/// 比较函数不满足全序（用户的比较函数写错）时结果顺序不确定，但不会 panic，也不会丢失或重复元素
+fn merge_sort(values: &mut Vec<i64>, less_or_equal: &mut dyn FnMut(i64, i64) -> bool) {
+    let len = values.len();
+    let mut buffer = values.clone();
+    let mut width = 1;
+    while width < len {
+        for start in (0..len).step_by(2 * width) {
+            let mid = (start + width).min(len);
+            let end = (start + 2 * width).min(len);
+            let (mut i, mut j) = (start, mid);
+            for slot in &mut buffer[start..end] {
+                // 右半段的元素严格更小时才先取，保证稳定
+                if i < mid && (j >= end || less_or_equal(values[i], values[j])) {
+                    *slot = values[i];
+                    i += 1;
+                } else {
+                    *slot = values[j];
+                    j += 1;
+                }
             }
         }
+        std::mem::swap(values, &mut buffer);
+        width *= 2;
+    }
+}
+
+/// 用比较函数原地排序（稳定）：`cmp_fn(a, b)` 返回负数表示 a 在前，正数表示 b 在前，0 表示相等
+///
+/// 比较函数按借用方式接收元素，不能修改列表本身；不支持 float 列表（元素按 i64 传递）。
+#[no_mangle]
+pub extern "C" fn bolide_list_sort_by(list: *mut BolideList, cmp_fn: i64) {
+    if list.is_null() { return; }
+    let mut values = unsafe { (*list).as_slice() }.to_vec();
+    let len = values.len();
+    merge_sort(&mut values, &mut |a, b| {
+        let order = crate::bolide_closure_call(cmp_fn, [a, b].as_ptr(), 2);
+        check_unmodified(list, len, "sort_by");
+        order <= 0
+    });
+    unsafe {
+        std::ptr::copy_nonoverlapping(values.as_ptr(), (*list).data, values.len());
     }
 }
 
@@ -603,14 +1067,12 @@ pub extern "C" fn bolide_list_slice(list: *const BolideList, start: i64, end: i6
         let new_list = BolideList::with_capacity(src.elem_type, slice_len);
         let dst = &mut *new_list;
         
+        // push 会增加元素的引用计数
         for i in start..end {
             let value = *src.data.add(i);
             dst.push(value);
         }
         
-        // 增加元素引用计数
-        dst.retain_elements();
-        
         new_list
     }
 }
@@ -622,58 +1084,429 @@ pub extern "C" fn bolide_list_is_empty(list: *const BolideList) -> i64 {
     unsafe { if (*list).len == 0 { 1 } else { 0 } }
 }
 
-/// 获取第一个元素
+/// 获取第一个元素；空列表记录 `ERROR_INDEX` 并返回 0
 #[no_mangle]
 pub extern "C" fn bolide_list_first(list: *const BolideList) -> i64 {
     if list.is_null() { return 0; }
     unsafe {
         let list = &*list;
-        if list.len == 0 { return 0; }
+        if list.len == 0 {
+            crate::set_error(crate::ERROR_INDEX, "first: empty list");
+            return 0;
+        }
         *list.data
     }
 }
 
-/// 获取最后一个元素
+/// 获取最后一个元素；空列表记录 `ERROR_INDEX` 并返回 0
 #[no_mangle]
 pub extern "C" fn bolide_list_last(list: *const BolideList) -> i64 {
     if list.is_null() { return 0; }
     unsafe {
         let list = &*list;
-        if list.len == 0 { return 0; }
+        if list.len == 0 {
+            crate::set_error(crate::ERROR_INDEX, "last: empty list");
+            return 0;
+        }
         *list.data.add(list.len - 1)
     }
 }
 
+/// 列表的打印形式：`[1, 2, "a"]`，与 `print` 的输出一致
+pub(crate) fn format_list(list: *const BolideList) -> String {
+    let mut text = String::from("[");
+    if !list.is_null() {
+        unsafe {
+            let list = &*list;
+            for i in 0..list.len {
+                if i > 0 { text.push_str(", "); }
+                let val = *list.data.add(i);
+                let _ = match list.elem_type {
+                    ElementType::Int => write!(text, "{}", val),
+                    ElementType::Float => write!(text, "{}", f64::from_bits(val as u64)),
+                    ElementType::Bool => write!(text, "{}", if val != 0 { "true" } else { "false" }),
+                    ElementType::String => {
+                        let s = val as *const crate::BolideString;
+                        if !s.is_null() {
+                            write!(text, "\"{}\"", (*s).as_str())
+                        } else {
+                            write!(text, "null")
+                        }
+                    }
+                    ElementType::BigInt => match (val as *const BolideBigInt).as_ref() {
+                        Some(bigint) => text.write_str(&bigint.to_string()),
+                        None => write!(text, "null"),
+                    },
+                    ElementType::Decimal => match (val as *const BolideDecimal).as_ref() {
+                        Some(decimal) => text.write_str(&decimal.to_string()),
+                        None => write!(text, "null"),
+                    },
+                    _ => write!(text, "0x{:x}", val),
+                };
+            }
+        }
+    }
+    text.push(']');
+    text
+}
+
 /// 打印列表
 #[no_mangle]
 pub extern "C" fn bolide_print_list(list: *const BolideList) {
-    if list.is_null() {
-        println!("[]");
+    outln!("{}", format_list(list));
+}
+
+// ==================== 表格行 ====================
+
+/// 追加一个 CSV/TSV 字段：含分隔符、引号或换行时按 RFC 4180 加引号，内部的引号写两遍
+fn push_row_field(row: &mut String, field: &str, sep: &str) {
+    let needs_quotes = (!sep.is_empty() && field.contains(sep)) || field.contains(['"', '\n', '\r']);
+    if !needs_quotes {
+        row.push_str(field);
         return;
     }
-    unsafe {
-        let list = &*list;
-        print!("[");
-        for i in 0..list.len {
-            if i > 0 { print!(", "); }
-            let val = *list.data.add(i);
-            match list.elem_type {
-                ElementType::Int => print!("{}", val),
-                ElementType::Float => print!("{}", f64::from_bits(val as u64)),
-                ElementType::Bool => print!("{}", if val != 0 { "true" } else { "false" }),
-                ElementType::String => {
-                    let s = val as *const crate::BolideString;
-                    if !s.is_null() {
-                        print!("\"{}\"", (*s).as_str());
-                    } else {
-                        print!("null");
-                    }
-                }
-                _ => print!("0x{:x}", val),
-            }
+    row.push('"');
+    for part in field.split_inclusive('"') {
+        row.push_str(part);
+        if part.ends_with('"') {
+            row.push('"');
         }
-        println!("]");
     }
+    row.push('"');
+}
+
+/// 一行的预估长度：字符串按内容长度（加引号的余量），其他元素按 8 字节
+unsafe fn row_capacity(list: &BolideList, sep: &str) -> usize {
+    let mut capacity = sep.len() * list.len.saturating_sub(1) + 1;
+    for i in 0..list.len {
+        let val = *list.data.add(i);
+        capacity += match list.elem_type {
+            ElementType::String if val != 0 => (*(val as *const BolideString)).len() + 2,
+            _ => 8,
+        };
+    }
+    capacity
+}
+
+/// format_row(fields, sep) -> str：把列表的元素用 `sep` 连接成 CSV/TSV 的一行
+///
+/// 数值和布尔值的格式与 `str()` 相同，字符串按需加引号（`push_row_field`），
+/// none 元素是空字段；内容在一个缓冲里一次拼好。
+#[no_mangle]
+pub extern "C" fn bolide_format_row(list: *const BolideList, sep: *const BolideString) -> *mut BolideString {
+    let sep = if sep.is_null() { "," } else { unsafe { (*sep).as_str() } };
+    let Some(list) = (unsafe { list.as_ref() }) else {
+        return BolideString::new("");
+    };
+    let mut row = String::with_capacity(unsafe { row_capacity(list, sep) });
+    let mut field = String::new();
+    for i in 0..list.len {
+        if i > 0 {
+            row.push_str(sep);
+        }
+        let val = unsafe { *list.data.add(i) };
+        field.clear();
+        let _ = match list.elem_type {
+            ElementType::Int => write!(field, "{}", val),
+            ElementType::Float => write!(field, "{}", f64::from_bits(val as u64)),
+            ElementType::Bool => write!(field, "{}", val != 0),
+            ElementType::String => match unsafe { (val as *const BolideString).as_ref() } {
+                Some(s) => {
+                    push_row_field(&mut row, s.as_str(), sep);
+                    continue;
+                }
+                None => Ok(()),
+            },
+            ElementType::BigInt => match unsafe { (val as *const BolideBigInt).as_ref() } {
+                Some(bigint) => field.write_str(&bigint.to_string()),
+                None => Ok(()),
+            },
+            ElementType::Decimal => match unsafe { (val as *const BolideDecimal).as_ref() } {
+                Some(decimal) => field.write_str(&decimal.to_string()),
+                None => Ok(()),
+            },
+            ElementType::Dynamic => match unsafe { (val as *const crate::BolideDynamic).as_ref() } {
+                Some(value) => field.write_str(&value.to_string_repr()),
+                None => Ok(()),
+            },
+            _ => write!(field, "0x{:x}", val),
+        };
+        push_row_field(&mut row, &field, sep);
+    }
+    BolideString::from_string(row)
+}
+
+// ==================== 迭代器 ====================
+
+/// 列表迭代器（for 循环遍历列表变量时使用）
+///
+/// 创建时 retain 列表并记录长度，循环体给变量重新赋值不会让列表在遍历中被释放。
+/// 遍历中修改列表时：追加的元素不会被遍历到；删除元素后，位置超出当前长度时直接结束
+/// （截断，不报错），不会读到越界的数据。元素是借用的，不 retain。
+pub struct BolideListIter {
+    list: *mut BolideList,
+    index: usize,
+    len: usize,
+}
+
+/// 创建列表迭代器（list 为 null 时得到空迭代）
+#[no_mangle]
+pub extern "C" fn bolide_list_iter_new(list: *mut c_void) -> *mut c_void {
+    let list = list as *mut BolideList;
+    let len = if list.is_null() {
+        0
+    } else {
+        unsafe {
+            (*list).retain();
+            (*list).len
+        }
+    };
+    Box::into_raw(Box::new(BolideListIter { list, index: 0, len })) as *mut c_void
+}
+
+/// 取下一个元素写入 *out，返回 1；遍历结束返回 0
+#[no_mangle]
+pub extern "C" fn bolide_list_iter_next(iter: *mut c_void, out: *mut i64) -> i64 {
+    if iter.is_null() { return 0; }
+    unsafe {
+        let iter = &mut *(iter as *mut BolideListIter);
+        if iter.index >= iter.len || iter.index >= (*iter.list).len {
+            return 0;
+        }
+        *out = *(*iter.list).data.add(iter.index);
+        iter.index += 1;
+        1
+    }
+}
+
+/// 释放迭代器，同时释放它持有的列表引用
+#[no_mangle]
+pub extern "C" fn bolide_list_iter_free(iter: *mut c_void) {
+    if iter.is_null() { return; }
+    let iter = unsafe { Box::from_raw(iter as *mut BolideListIter) };
+    if !iter.list.is_null() {
+        bolide_list_release(iter.list);
+    }
+}
+
+// ==================== 聚合: sum / min / max / avg ====================
+//
+// 直接遍历底层缓冲区，不经过逐元素的 FFI 调用。int 的求和/最值和 float 的最值
+// 是简单的折叠，LLVM 会自动向量化；float 求和用 4 路累加器打破加法依赖链，
+// 因此结果可能与顺序累加在最后几位上不同。
+//
+// 空列表：sum 为 0，min/max/avg 是运行时错误（`runtime_error`）。
+
+/// 列表元素切片（空列表或空指针得到空切片）
+unsafe fn elements<'a>(list: *const BolideList) -> &'a [i64] {
+    if list.is_null() || (*list).len == 0 {
+        return &[];
+    }
+    std::slice::from_raw_parts((*list).data, (*list).len)
+}
+
+/// 回绕求和
+fn sum_i64_wrapping(xs: &[i64]) -> i64 {
+    xs.iter().fold(0i64, |acc, &x| acc.wrapping_add(x))
+}
+
+/// 检查溢出的求和，溢出时返回 None
+fn sum_i64_checked(xs: &[i64]) -> Option<i64> {
+    // 先用 i128 累加（可向量化），最后再判断是否落在 i64 范围内；
+    // 2^64 个 i64 之和才可能让 i128 溢出
+    let wide = xs.iter().fold(0i128, |acc, &x| acc + x as i128);
+    i64::try_from(wide).ok()
+}
+
+/// float 求和（4 路累加器）
+fn sum_f64(xs: &[i64]) -> f64 {
+    let mut lanes = [0.0f64; 4];
+    let chunks = xs.chunks_exact(4);
+    let rest = chunks.remainder();
+    for chunk in chunks {
+        for (lane, &bits) in lanes.iter_mut().zip(chunk) {
+            *lane += f64::from_bits(bits as u64);
+        }
+    }
+    let mut total = (lanes[0] + lanes[1]) + (lanes[2] + lanes[3]);
+    for &bits in rest {
+        total += f64::from_bits(bits as u64);
+    }
+    total
+}
+
+/// float 最值：NaN 被忽略，全部为 NaN 时结果为 NaN
+fn fold_f64(xs: &[i64], pick: fn(f64, f64) -> f64) -> Option<f64> {
+    let (&first, rest) = xs.split_first()?;
+    Some(rest.iter().fold(f64::from_bits(first as u64), |acc, &bits| pick(acc, f64::from_bits(bits as u64))))
+}
+
+/// int 平均值（i128 累加，不会溢出）
+fn mean_i64(xs: &[i64]) -> Option<f64> {
+    if xs.is_empty() {
+        return None;
+    }
+    let total = xs.iter().fold(0i128, |acc, &x| acc + x as i128);
+    Some(total as f64 / xs.len() as f64)
+}
+
+/// 空列表上调用 min/max/avg
+fn empty_list_error(func: &str) -> ! {
+    crate::runtime_error(&format!("{}() of an empty list", func))
+}
+
+/// sum(list[int])：默认溢出回绕，开启溢出检查时溢出是运行时错误
+#[no_mangle]
+pub extern "C" fn bolide_list_sum_int(list: *const BolideList) -> i64 {
+    let xs = unsafe { elements(list) };
+    if crate::overflow_checks_enabled() {
+        match sum_i64_checked(xs) {
+            Some(total) => total,
+            None => crate::runtime_error("sum() overflowed int; use list<bigint> for larger totals"),
+        }
+    } else {
+        sum_i64_wrapping(xs)
+    }
+}
+
+/// sum(list[float])
+#[no_mangle]
+pub extern "C" fn bolide_list_sum_float(list: *const BolideList) -> f64 {
+    sum_f64(unsafe { elements(list) })
+}
+
+/// min(list[int])
+#[no_mangle]
+pub extern "C" fn bolide_list_min_int(list: *const BolideList) -> i64 {
+    let xs = unsafe { elements(list) };
+    let (&first, rest) = xs.split_first().unwrap_or_else(|| empty_list_error("min"));
+    rest.iter().fold(first, |acc, &x| acc.min(x))
+}
+
+/// max(list[int])
+#[no_mangle]
+pub extern "C" fn bolide_list_max_int(list: *const BolideList) -> i64 {
+    let xs = unsafe { elements(list) };
+    let (&first, rest) = xs.split_first().unwrap_or_else(|| empty_list_error("max"));
+    rest.iter().fold(first, |acc, &x| acc.max(x))
+}
+
+/// min(list[float])
+#[no_mangle]
+pub extern "C" fn bolide_list_min_float(list: *const BolideList) -> f64 {
+    fold_f64(unsafe { elements(list) }, f64::min).unwrap_or_else(|| empty_list_error("min"))
+}
+
+/// max(list[float])
+#[no_mangle]
+pub extern "C" fn bolide_list_max_float(list: *const BolideList) -> f64 {
+    fold_f64(unsafe { elements(list) }, f64::max).unwrap_or_else(|| empty_list_error("max"))
+}
+
+/// avg(list[int])，结果为 float
+#[no_mangle]
+pub extern "C" fn bolide_list_mean(list: *const BolideList) -> f64 {
+    mean_i64(unsafe { elements(list) }).unwrap_or_else(|| empty_list_error("avg"))
+}
+
+/// avg(list[float])
+#[no_mangle]
+pub extern "C" fn bolide_list_mean_float(list: *const BolideList) -> f64 {
+    let xs = unsafe { elements(list) };
+    if xs.is_empty() {
+        empty_list_error("avg");
+    }
+    sum_f64(xs) / xs.len() as f64
+}
+
+/// avg(list[bigint])，结果为 float
+#[no_mangle]
+pub extern "C" fn bolide_list_mean_bigint(list: *const BolideList) -> f64 {
+    let len = unsafe { elements(list) }.len();
+    if len == 0 {
+        empty_list_error("avg");
+    }
+    let total = bolide_list_sum_bigint(list);
+    let mean = crate::bolide_bigint_to_f64(total) / len as f64;
+    crate::bolide_bigint_release(total);
+    mean
+}
+
+/// sum(list[bigint])，用 bigint 的原地加法逐个累加
+#[no_mangle]
+pub extern "C" fn bolide_list_sum_bigint(list: *const BolideList) -> *mut BolideBigInt {
+    let mut total = crate::bolide_bigint_from_i64(0);
+    for &x in unsafe { elements(list) } {
+        total = crate::bolide_bigint_add_assign(total, x as *const BolideBigInt);
+    }
+    total
+}
+
+/// sum(list[decimal])
+#[no_mangle]
+pub extern "C" fn bolide_list_sum_decimal(list: *const BolideList) -> *mut BolideDecimal {
+    let mut total = crate::bolide_decimal_from_i64(0);
+    for &x in unsafe { elements(list) } {
+        total = crate::bolide_decimal_add_assign(total, x as *const BolideDecimal);
+    }
+    total
+}
+
+/// avg(list[decimal])，结果为 decimal（按当前舍入模式相除）
+#[no_mangle]
+pub extern "C" fn bolide_list_mean_decimal(list: *const BolideList) -> *mut BolideDecimal {
+    let len = unsafe { elements(list) }.len();
+    if len == 0 {
+        empty_list_error("avg");
+    }
+    let total = bolide_list_sum_decimal(list);
+    let count = crate::bolide_decimal_from_i64(len as i64);
+    let mean = crate::bolide_decimal_div(total, count);
+    crate::bolide_decimal_release(total);
+    crate::bolide_decimal_release(count);
+    mean
+}
+
+/// 按比较函数选出元素（借用，调用方负责 retain）
+unsafe fn pick_rc(list: *const BolideList, func: &str, better: impl Fn(i64, i64) -> bool) -> i64 {
+    let (&first, rest) = elements(list).split_first().unwrap_or_else(|| empty_list_error(func));
+    rest.iter().fold(first, |best, &x| if better(x, best) { x } else { best })
+}
+
+/// min(list[bigint])
+#[no_mangle]
+pub extern "C" fn bolide_list_min_bigint(list: *const BolideList) -> *mut BolideBigInt {
+    let best = unsafe {
+        pick_rc(list, "min", |a, b| crate::bolide_bigint_lt(a as *const BolideBigInt, b as *const BolideBigInt) != 0)
+    };
+    crate::bolide_bigint_retain(best as *mut BolideBigInt)
+}
+
+/// max(list[bigint])
+#[no_mangle]
+pub extern "C" fn bolide_list_max_bigint(list: *const BolideList) -> *mut BolideBigInt {
+    let best = unsafe {
+        pick_rc(list, "max", |a, b| crate::bolide_bigint_gt(a as *const BolideBigInt, b as *const BolideBigInt) != 0)
+    };
+    crate::bolide_bigint_retain(best as *mut BolideBigInt)
+}
+
+/// min(list[decimal])
+#[no_mangle]
+pub extern "C" fn bolide_list_min_decimal(list: *const BolideList) -> *mut BolideDecimal {
+    let best = unsafe {
+        pick_rc(list, "min", |a, b| crate::bolide_decimal_lt(a as *const BolideDecimal, b as *const BolideDecimal) != 0)
+    };
+    crate::bolide_decimal_retain(best as *mut BolideDecimal)
+}
+
+/// max(list[decimal])
+#[no_mangle]
+pub extern "C" fn bolide_list_max_decimal(list: *const BolideList) -> *mut BolideDecimal {
+    let best = unsafe {
+        pick_rc(list, "max", |a, b| crate::bolide_decimal_gt(a as *const BolideDecimal, b as *const BolideDecimal) != 0)
+    };
+    crate::bolide_decimal_retain(best as *mut BolideDecimal)
 }
 
 // ==================== 测试 ====================
@@ -699,6 +1532,50 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_list_iter() {
+        let list = BolideList::new(ElementType::Int);
+        for v in [10, 20, 30] {
+            bolide_list_push(list, v);
+        }
+        let collect = |iter: *mut c_void| {
+            let mut out = 0i64;
+            let mut seen = Vec::new();
+            while bolide_list_iter_next(iter, &mut out) == 1 {
+                seen.push(out);
+            }
+            seen
+        };
+        unsafe {
+            let iter = bolide_list_iter_new(list as *mut c_void);
+            assert_eq!((*list).ref_count(), 2);
+            assert_eq!(collect(iter), [10, 20, 30]);
+            assert_eq!(bolide_list_iter_next(iter, &mut 0), 0);
+            bolide_list_iter_free(iter);
+            assert_eq!((*list).ref_count(), 1);
+
+            // 长度在创建时固定：追加的不遍历，删除后截断
+            let iter = bolide_list_iter_new(list as *mut c_void);
+            let mut out = 0i64;
+            assert_eq!(bolide_list_iter_next(iter, &mut out), 1);
+            bolide_list_push(list, 40);
+            bolide_list_pop(list);
+            bolide_list_pop(list);
+            assert_eq!(collect(iter), [20]);
+            bolide_list_iter_free(iter);
+
+            // 迭代器持有列表：调用者释放后仍可遍历完
+            let iter = bolide_list_iter_new(list as *mut c_void);
+            bolide_list_release(list);
+            assert_eq!(collect(iter), [10, 20]);
+            bolide_list_iter_free(iter);
+
+            let empty = bolide_list_iter_new(std::ptr::null_mut());
+            assert_eq!(collect(empty), Vec::<i64>::new());
+            bolide_list_iter_free(empty);
+        }
+    }
+
     #[test]
     fn test_list_operations() {
         let list = BolideList::new(ElementType::Int);
@@ -718,6 +1595,10 @@ mod tests {
             assert_eq!(bolide_list_pop(list), 30);
             assert_eq!((*list).len(), 2);
 
+            // 扩展自身：只追加扩展前的元素
+            bolide_list_extend(list, list);
+            assert_eq!((*list).as_slice(), [10, 25, 10, 25]);
+
             bolide_list_release(list);
         }
     }
@@ -743,6 +1624,377 @@ mod tests {
         }
     }
 
+    /// 用给定内容创建字符串列表（列表持有唯一引用），同时返回各字符串
+    fn string_list(items: &[&str]) -> (*mut BolideList, Vec<*mut BolideString>) {
+        let list = BolideList::new(ElementType::String);
+        let strings: Vec<_> = items.iter().map(|s| BolideString::new(s)).collect();
+        for &s in &strings {
+            unsafe { (*list).push_owned(s as i64); }
+        }
+        (list, strings)
+    }
+
+    fn string_items(list: *const BolideList) -> Vec<String> {
+        unsafe {
+            (*list).as_slice().iter()
+                .map(|&v| (*(v as *const BolideString)).as_str().to_string())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_format_list_numbers() {
+        // bigint、decimal 元素按数值打印，和 str 一样由列表释放
+        let bigs = BolideList::new(ElementType::BigInt);
+        for v in [7, -12] {
+            let big = crate::bolide_bigint_from_i64(v);
+            bolide_list_push(bigs, big as i64);
+            crate::bolide_bigint_release(big);
+        }
+        assert_eq!(format_list(bigs), "[7, -12]");
+        bolide_list_release(bigs);
+
+        let decimals = BolideList::new(ElementType::Decimal);
+        let d = crate::bolide_decimal_from_i64(3);
+        bolide_list_push(decimals, d as i64);
+        crate::bolide_decimal_release(d);
+        assert_eq!(format_list(decimals), "[3]");
+        bolide_list_release(decimals);
+    }
+
+    #[test]
+    fn test_format_row() {
+        fn row(list: *const BolideList, sep: &str) -> String {
+            let sep = BolideString::new(sep);
+            let s = bolide_format_row(list, sep);
+            let text = unsafe { (*s).as_str().to_string() };
+            crate::bolide_string_release(s);
+            crate::bolide_string_release(sep);
+            text
+        }
+
+        // 含分隔符、引号、换行的字段加引号，引号写两遍；其他字段（包括 unicode）原样
+        let (list, _) = string_list(&["plain", "a,b", "say \"hi\"", "two\nlines", "cr\r", "", "数据 ✓", "\""]);
+        assert_eq!(row(list, ","), "plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",\"cr\r\",,数据 ✓,\"\"\"\"");
+        // 分隔符换成制表符后逗号不再需要引号
+        assert_eq!(row(list, "\t"), "plain\ta,b\t\"say \"\"hi\"\"\"\t\"two\nlines\"\t\"cr\r\"\t\t数据 ✓\t\"\"\"\"");
+        bolide_list_release(list);
+
+        let ints = BolideList::new(ElementType::Int);
+        for v in [1, -2, 30] {
+            bolide_list_push(ints, v);
+        }
+        assert_eq!(row(ints, ","), "1,-2,30");
+        // 负号与分隔符相同时也加引号
+        assert_eq!(row(ints, "-"), "1-\"-2\"-30");
+        bolide_list_release(ints);
+
+        let floats = BolideList::new(ElementType::Float);
+        for v in [2.5, 1.0, -0.125] {
+            bolide_list_push(floats, f64::to_bits(v) as i64);
+        }
+        assert_eq!(row(floats, ";"), "2.5;1;-0.125");
+        bolide_list_release(floats);
+
+        let bools = BolideList::new(ElementType::Bool);
+        bolide_list_push(bools, 1);
+        bolide_list_push(bools, 0);
+        assert_eq!(row(bools, ","), "true,false");
+        bolide_list_release(bools);
+
+        // 空列表和只有空字段的行
+        let empty = BolideList::new(ElementType::String);
+        assert_eq!(row(empty, ","), "");
+        bolide_list_release(empty);
+        let (blanks, _) = string_list(&["", "", ""]);
+        assert_eq!(row(blanks, ","), ",,");
+        bolide_list_release(blanks);
+        assert_eq!(row(std::ptr::null(), ","), "");
+    }
+
+    #[test]
+    fn test_dedup_vs_unique() {
+        let list = BolideList::new(ElementType::Int);
+        for v in [1, 1, 2, 1, 3, 3, 3, 2] {
+            bolide_list_push(list, v);
+        }
+        let unique = bolide_list_unique(list);
+        bolide_list_dedup(list);
+        unsafe {
+            // dedup 只合并相邻的重复，unique 去掉所有重复
+            assert_eq!((*list).as_slice(), [1, 2, 1, 3, 2]);
+            assert_eq!((*unique).as_slice(), [1, 2, 3]);
+        }
+        bolide_list_release(list);
+        bolide_list_release(unique);
+
+        // float 按值比较：-0.0 与 0.0 相等，NaN 与自身不相等
+        let floats = BolideList::new(ElementType::Float);
+        for v in [0.0f64, -0.0, f64::NAN, f64::NAN, 1.5] {
+            bolide_list_push(floats, v.to_bits() as i64);
+        }
+        let unique = bolide_list_unique(floats);
+        bolide_list_dedup(floats);
+        unsafe {
+            assert_eq!((*floats).len(), 4);
+            assert_eq!((*unique).len(), 4);
+        }
+        bolide_list_release(floats);
+        bolide_list_release(unique);
+    }
+
+    #[test]
+    fn test_dedup_unique_strings_rc() {
+        // 内容相同但实例不同的字符串按内容比较
+        let (list, strings) = string_list(&["a", "a", "b", "a", "b", "b"]);
+        unsafe {
+            for &s in &strings {
+                (*s).retain();
+            }
+
+            let unique = bolide_list_unique(list);
+            assert_eq!(string_items(unique), ["a", "b"]);
+            // 保留首次出现的实例
+            assert_eq!((*unique).as_slice(), [strings[0] as i64, strings[2] as i64]);
+            assert_eq!((*strings[0]).ref_count(), 3);
+            assert_eq!((*strings[1]).ref_count(), 2);
+
+            bolide_list_dedup(list);
+            assert_eq!(string_items(list), ["a", "b", "a", "b"]);
+            // 被移除的元素（下标 1 和 5）已释放
+            assert_eq!((*strings[1]).ref_count(), 1);
+            assert_eq!((*strings[5]).ref_count(), 1);
+            assert_eq!((*strings[4]).ref_count(), 2);
+
+            bolide_list_release(unique);
+            bolide_list_release(list);
+            for &s in &strings {
+                assert_eq!((*s).ref_count(), 1);
+                crate::bolide_string_release(s);
+            }
+        }
+    }
+
+    extern "C" fn key_len(s: i64) -> i64 {
+        unsafe { (*(s as *const BolideString)).len() as i64 }
+    }
+
+    extern "C" fn key_first_char(s: i64) -> i64 {
+        let s = unsafe { (*(s as *const BolideString)).as_str() };
+        BolideString::new(&s[..1]) as i64
+    }
+
+    extern "C" fn key_dynamic(n: i64) -> i64 {
+        if n > 2 {
+            crate::bolide_dynamic_from_string(BolideString::new("big")) as i64
+        } else {
+            crate::bolide_dynamic_from_int(n) as i64
+        }
+    }
+
+    #[test]
+    fn test_group_by() {
+        let (list, strings) = string_list(&["kiwi", "fig", "pear", "apple", "plum", "yam"]);
+        unsafe {
+            for &s in &strings {
+                (*s).retain();
+            }
+
+            let by_len = bolide_list_group_by(list, key_len as *const u8 as i64, ElementType::Int as u8);
+            assert_eq!((*by_len).len(), 3);
+            let group = |dict: *mut crate::dict::BolideDict, key: i64| {
+                string_items((*dict).get(key).unwrap() as *const BolideList)
+            };
+            // 组内保持原列表中的顺序
+            assert_eq!(group(by_len, 4), ["kiwi", "pear", "plum"]);
+            assert_eq!(group(by_len, 3), ["fig", "yam"]);
+            assert_eq!(group(by_len, 5), ["apple"]);
+            assert_eq!((*strings[0]).ref_count(), 3);
+
+            let by_first = bolide_list_group_by(list, key_first_char as *const u8 as i64, ElementType::String as u8);
+            let p = BolideString::new("p");
+            assert_eq!(group(by_first, p as i64), ["pear", "plum"]);
+            crate::bolide_string_release(p);
+            assert_eq!((*by_first).len(), 5);
+
+            // dynamic 键按内容合并
+            let nums = BolideList::new(ElementType::Int);
+            for v in [1, 5, 2, 7, 1] {
+                bolide_list_push(nums, v);
+            }
+            let by_kind = bolide_list_group_by(nums, key_dynamic as *const u8 as i64, ElementType::Dynamic as u8);
+            assert_eq!((*by_kind).len(), 3);
+            let groups: Vec<Vec<i64>> = (*by_kind).values().iter()
+                .map(|&g| (*(g as *const BolideList)).as_slice().to_vec())
+                .collect();
+            assert!(groups.contains(&vec![1, 1]));
+            assert!(groups.contains(&vec![5, 7]));
+            crate::bolide_dict_release(by_kind);
+            bolide_list_release(nums);
+
+            crate::bolide_dict_release(by_len);
+            crate::bolide_dict_release(by_first);
+            bolide_list_release(list);
+            for &s in &strings {
+                assert_eq!((*s).ref_count(), 1);
+                crate::bolide_string_release(s);
+            }
+        }
+    }
+
+    extern "C" fn key_last_char(s: i64) -> i64 {
+        let s = unsafe { (*(s as *const BolideString)).as_str() };
+        BolideString::new(&s[s.len() - 1..]) as i64
+    }
+
+    extern "C" fn key_neg_half(n: i64) -> f64 {
+        -(n as f64) / 2.0
+    }
+
+    extern "C" fn cmp_desc(a: i64, b: i64) -> i64 {
+        b - a
+    }
+
+    extern "C" fn cmp_len(a: i64, b: i64) -> i64 {
+        let len = |v: i64| unsafe { (*(v as *const BolideString)).as_str().len() as i64 };
+        len(a) - len(b)
+    }
+
+    #[test]
+    fn test_sort_by_element_type() {
+        let (list, strings) = string_list(&["b", "a", "c", "ab", ""]);
+        bolide_list_sort(list);
+        assert_eq!(string_items(list), ["", "a", "ab", "b", "c"]);
+        for &s in &strings {
+            assert_eq!(unsafe { (*s).ref_count() }, 1);
+        }
+        bolide_list_release(list);
+
+        // 符号位为 1 的 NaN（x86 上 0.0 / 0.0 的结果）也排在最后
+        let floats = BolideList::new(ElementType::Float);
+        for x in [3.5f64, -f64::NAN, 1.25, f64::NAN, -2.0] {
+            bolide_list_push_f64(floats, x);
+        }
+        bolide_list_sort(floats);
+        let sorted: Vec<f64> = (0..5).map(|i| bolide_list_get_f64(floats, i)).collect();
+        assert_eq!(sorted[..3], [-2.0, 1.25, 3.5]);
+        assert!(sorted[3].is_nan() && sorted[4].is_nan());
+        bolide_list_release(floats);
+    }
+
+    #[test]
+    fn test_sort_by_comparator_is_stable() {
+        let nums = BolideList::new(ElementType::Int);
+        for v in [3, -1, 4, 1, -5, 9, 2, 6] {
+            bolide_list_push(nums, v);
+        }
+        bolide_list_sort_by(nums, cmp_desc as *const u8 as i64);
+        unsafe {
+            assert_eq!((*nums).as_slice(), [9, 6, 4, 3, 2, 1, -1, -5]);
+        }
+        bolide_list_release(nums);
+
+        // 比较结果为 0 的元素保持原顺序
+        let (list, strings) = string_list(&["kiwi", "fig", "pear", "apple", "plum", "yam", "date"]);
+        bolide_list_sort_by(list, cmp_len as *const u8 as i64);
+        assert_eq!(string_items(list), ["fig", "yam", "kiwi", "pear", "plum", "date", "apple"]);
+        for &s in &strings {
+            assert_eq!(unsafe { (*s).ref_count() }, 1);
+        }
+        bolide_list_release(list);
+    }
+
+    #[test]
+    fn test_sort_by_key_is_stable() {
+        let (list, strings) = string_list(&["kiwi", "fig", "pear", "apple", "plum", "yam"]);
+        unsafe {
+            bolide_list_sort_by_key(list, key_len as *const u8 as i64, ElementType::Int as u8);
+            // 等长的元素保持原顺序
+            assert_eq!(string_items(list), ["fig", "yam", "kiwi", "pear", "plum", "apple"]);
+
+            // str 键用完即释放，元素引用计数不变
+            bolide_list_sort_by_key(list, key_last_char as *const u8 as i64, ElementType::String as u8);
+            assert_eq!(string_items(list), ["apple", "fig", "kiwi", "yam", "plum", "pear"]);
+            for &s in &strings {
+                assert_eq!((*s).ref_count(), 1);
+            }
+
+            let item = |v: i64| (*(v as *const BolideString)).as_str();
+            // 键相等时取第一个：fig 和 yam 长度都是 3
+            assert_eq!(item(bolide_list_min_by(list, key_len as *const u8 as i64, 0)), "fig");
+            bolide_list_sort_by_key(list, key_len as *const u8 as i64, ElementType::Int as u8);
+            assert_eq!(item(bolide_list_max_by(list, key_len as *const u8 as i64, 0)), "apple");
+            assert_eq!(item(bolide_list_max_by(list, key_last_char as *const u8 as i64, ElementType::String as u8)), "pear");
+            bolide_list_release(list);
+        }
+
+        let nums = BolideList::new(ElementType::Int);
+        for v in [3, -1, 4, 1, -5] {
+            bolide_list_push(nums, v);
+        }
+        bolide_list_sort_by_key(nums, key_neg_half as *const u8 as i64, ElementType::Float as u8);
+        unsafe {
+            assert_eq!((*nums).as_slice(), [4, 3, 1, -1, -5]);
+        }
+        assert_eq!(bolide_list_min_by(nums, key_neg_half as *const u8 as i64, ElementType::Float as u8), 4);
+        bolide_list_release(nums);
+    }
+
+    extern "C" fn is_odd(n: i64) -> i64 {
+        (n % 2 != 0) as i64
+    }
+
+    extern "C" fn digits(n: i64) -> i64 {
+        BolideString::new(&n.to_string()) as i64
+    }
+
+    /// 字符串累加：返回新字符串，参数都是借用的
+    extern "C" fn concat(acc: i64, s: i64) -> i64 {
+        let (acc, s) = unsafe { ((*(acc as *const BolideString)).as_str(), (*(s as *const BolideString)).as_str()) };
+        BolideString::new(&format!("{}{}", acc, s)) as i64
+    }
+
+    #[test]
+    fn test_map_filter_reduce() {
+        let nums = BolideList::new(ElementType::Int);
+        for v in [1, 2, 3, 4, 5] {
+            bolide_list_push(nums, v);
+        }
+        let odd = bolide_list_filter(nums, is_odd as *const u8 as i64);
+        let halves = bolide_list_map(odd, key_neg_half as *const u8 as i64, ElementType::Float as u8);
+        let texts = bolide_list_map(odd, digits as *const u8 as i64, ElementType::String as u8);
+        unsafe {
+            assert_eq!((*odd).as_slice(), [1, 3, 5]);
+            let halves: Vec<f64> = (*halves).as_slice().iter().map(|&b| f64::from_bits(b as u64)).collect();
+            assert_eq!(halves, [-0.5, -1.5, -2.5]);
+            // map 返回的字符串由结果列表持有，不再额外 retain
+            assert_eq!(string_items(texts), ["1", "3", "5"]);
+            assert_eq!((*((*texts).as_slice()[0] as *const BolideString)).ref_count(), 1);
+        }
+
+        // 字符串累加：init 借用，中间结果逐步释放
+        let empty = BolideString::new("");
+        let joined = bolide_list_reduce(texts, concat as *const u8 as i64, empty as i64, ElementType::String as u8);
+        unsafe {
+            assert_eq!((*(joined as *const BolideString)).as_str(), "135");
+            assert_eq!((*(joined as *const BolideString)).ref_count(), 1);
+            // 空列表返回 init 的新引用
+            let none = BolideList::new(ElementType::String);
+            assert_eq!(bolide_list_reduce(none, concat as *const u8 as i64, empty as i64, ElementType::String as u8), empty as i64);
+            assert_eq!((*empty).ref_count(), 2);
+            crate::bolide_string_release(empty);
+            let mapped = bolide_list_map(none, digits as *const u8 as i64, ElementType::String as u8);
+            assert_eq!((*mapped).len(), 0);
+            bolide_list_release(mapped);
+            bolide_list_release(none);
+        }
+        crate::bolide_string_release(empty);
+        crate::bolide_string_release(joined as *mut BolideString);
+        for list in [nums, odd, halves, texts] {
+            bolide_list_release(list);
+        }
+    }
+
     #[test]
     fn test_list_clone() {
         let list = BolideList::new(ElementType::Int);
@@ -760,4 +2012,142 @@ mod tests {
             bolide_list_release(cloned);
         }
     }
+
+    #[test]
+    fn test_clone_and_slice_string_rc() {
+        // 复制出的列表各持有元素一次引用，释放后元素回到原来的计数
+        let (list, strings) = string_list(&["a", "b", "c"]);
+        unsafe {
+            for &s in &strings {
+                (*s).retain();
+            }
+
+            let cloned = bolide_list_clone(list);
+            let sliced = bolide_list_slice(list, 1, i64::MAX);
+            assert_eq!(string_items(sliced), ["b", "c"]);
+            assert_eq!((*strings[0]).ref_count(), 3);
+            assert_eq!((*strings[1]).ref_count(), 4);
+
+            bolide_list_release(cloned);
+            bolide_list_release(sliced);
+            bolide_list_release(list);
+            for &s in &strings {
+                assert_eq!((*s).ref_count(), 1);
+                crate::bolide_string_release(s);
+            }
+        }
+    }
+
+    #[test]
+    fn test_list_from_const_array() {
+        let words: [i64; 3] = [7, -1, 2.5f64.to_bits() as i64];
+        let list = bolide_list_from_const_array(0, words.as_ptr() as *const u8, 3);
+        unsafe {
+            assert_eq!((*list).len(), 3);
+            assert_eq!(bolide_list_get(list, 1), -1);
+            assert_eq!(f64::from_bits(bolide_list_get(list, 2) as u64), 2.5);
+        }
+        bolide_list_release(list);
+
+        // 偏移表 [0, 2, 2, 5] + 字节块 "hi" "" "abc"
+        let mut data: Vec<u8> = [0u64, 2, 2, 5].iter().flat_map(|o| o.to_ne_bytes()).collect();
+        data.extend_from_slice(b"hiabc");
+        let words: Vec<u64> = data.chunks(8)
+            .map(|c| { let mut b = [0u8; 8]; b[..c.len()].copy_from_slice(c); u64::from_ne_bytes(b) })
+            .collect();
+        let list = bolide_list_from_const_array(3, words.as_ptr() as *const u8, 3);
+        unsafe {
+            let texts: Vec<&str> = (0..3)
+                .map(|i| (*(bolide_list_get(list, i) as *const crate::BolideString)).as_str())
+                .collect();
+            assert_eq!(texts, ["hi", "", "abc"]);
+        }
+        bolide_list_release(list);
+    }
+
+    #[test]
+    fn test_aggregate_int() {
+        let list = BolideList::new(ElementType::Int);
+        assert_eq!(bolide_list_sum_int(list), 0);
+        bolide_list_push(list, 7);
+        assert_eq!(bolide_list_min_int(list), 7);
+        assert_eq!(bolide_list_max_int(list), 7);
+        for x in [3, -2, 10, 4] {
+            bolide_list_push(list, x);
+        }
+        assert_eq!(bolide_list_sum_int(list), 22);
+        assert_eq!(bolide_list_min_int(list), -2);
+        assert_eq!(bolide_list_max_int(list), 10);
+        assert_eq!(bolide_list_mean(list), 4.4);
+        bolide_list_release(list);
+    }
+
+    #[test]
+    fn test_aggregate_float() {
+        let list = BolideList::new(ElementType::Float);
+        assert_eq!(bolide_list_sum_float(list), 0.0);
+        for x in [1.5f64, f64::NAN, -0.5, 2.0, 1.0, 0.5] {
+            bolide_list_push(list, x.to_bits() as i64);
+        }
+        // NaN 不参与最值
+        assert_eq!(bolide_list_min_float(list), -0.5);
+        assert_eq!(bolide_list_max_float(list), 2.0);
+        bolide_list_set(list, 1, 0.0f64.to_bits() as i64);
+        assert_eq!(bolide_list_sum_float(list), 4.5);
+        assert_eq!(bolide_list_mean_float(list), 0.75);
+        bolide_list_release(list);
+    }
+
+    #[test]
+    fn test_float_elements() {
+        let list = BolideList::new(ElementType::Float);
+        for x in [2.5f64, -0.25, f64::NAN, 1e300, 0.0] {
+            bolide_list_push_f64(list, x);
+        }
+        assert_eq!(bolide_list_get_f64(list, 0), 2.5);
+        assert_eq!(bolide_list_set_f64(list, 4, -0.0), 1);
+        assert_eq!(bolide_list_set_f64(list, 9, 1.0), 0);
+
+        bolide_list_sort(list);
+        let sorted: Vec<f64> = (0..5).map(|i| bolide_list_get_f64(list, i)).collect();
+        assert_eq!(sorted[..4], [-0.25, -0.0, 2.5, 1e300]);
+        assert!(sorted[3].is_finite() && sorted[4].is_nan());
+        assert!(sorted[1].is_sign_negative());
+        assert_eq!(bolide_list_contains(list, 2.5f64.to_bits() as i64), 1);
+        bolide_list_release(list);
+    }
+
+    #[test]
+    fn test_sum_overflow() {
+        let xs = [i64::MAX, 1];
+        assert_eq!(sum_i64_wrapping(&xs), i64::MIN);
+        assert_eq!(sum_i64_checked(&xs), None);
+        // 中间结果溢出但最终落回范围内不算溢出
+        assert_eq!(sum_i64_checked(&[i64::MAX, 1, -2]), Some(i64::MAX - 1));
+        assert_eq!(mean_i64(&[i64::MAX, i64::MAX]), Some(i64::MAX as f64));
+        assert_eq!(mean_i64(&[]), None);
+    }
+
+    #[test]
+    fn test_aggregate_bigint() {
+        let list = BolideList::new(ElementType::BigInt);
+        for x in [5, -3, 12] {
+            let b = crate::bolide_bigint_from_i64(x);
+            bolide_list_push(list, b as i64);
+            crate::bolide_bigint_release(b);
+        }
+        let total = bolide_list_sum_bigint(list);
+        let min = bolide_list_min_bigint(list);
+        let max = bolide_list_max_bigint(list);
+        assert_eq!(crate::bolide_bigint_to_f64(total), 14.0);
+        assert_eq!(crate::bolide_bigint_to_f64(min), -3.0);
+        assert_eq!(crate::bolide_bigint_to_f64(max), 12.0);
+        assert!((bolide_list_mean_bigint(list) - 14.0 / 3.0).abs() < 1e-12);
+        // 返回值持有自己的引用
+        unsafe { assert_eq!((*max).ref_count(), 2); }
+        for b in [total, min, max] {
+            crate::bolide_bigint_release(b);
+        }
+        bolide_list_release(list);
+    }
 }
@@ -1,26 +1,72 @@
 //! 对象运行时支持
 //!
-//! 提供类实例的内存管理
+//! 提供类实例的内存管理、弱句柄和字符串表示
 
 use std::alloc::{alloc, dealloc, Layout};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread::ThreadId;
 
-/// 对象头部结构（每个对象都有）
+use crate::BolideString;
+use crate::output::outln;
+
+/// 类实例的对象头（布局版本 `OBJECT_HEADER_VERSION`）
+///
+/// 内存布局（64 位目标）：
+///
+/// ```text
+/// offset  0  ref_count   AtomicUsize  引用计数，object_alloc 时为 1
+/// offset  8  data_size   usize        数据部分的字节数（不含头部）
+/// offset 16  weak        AtomicPtr    弱句柄的控制块，第一次 downgrade 时创建，没有时为空
+/// offset 24  字段 0                   ← 对象指针指向这里
+/// offset 32  字段 1 ...               每个字段 OBJECT_FIELD_SIZE 字节
+/// ```
+///
+/// 编译后的代码只持有数据部分的指针，字段偏移从 0 开始，所以头部对生成的
+/// 代码不可见；只有运行时通过 `data_ptr - OBJECT_HEADER_SIZE` 访问它。
+/// 编译器的字段偏移和 `ClassInfo::size` 使用这里导出的常量计算。
+///
+/// 修改头部（增加析构函数、vtable、类 id 等字段）时必须递增
+/// `OBJECT_HEADER_VERSION` 并更新上面的布局表。
+///
+/// - 版本 1：ref_count + data_size
+/// - 版本 2：增加 weak（`weak_dict` 的键）
 #[repr(C)]
-pub struct ObjectHeader {
+pub struct BolideObjectHeader {
     pub ref_count: AtomicUsize,
-    pub data_size: usize,  // 数据部分大小
+    pub data_size: usize,
+    pub weak: AtomicPtr<ObjectWeakCell>,
 }
 
-const HEADER_SIZE: usize = std::mem::size_of::<ObjectHeader>();
+/// 对象头布局版本，见 `BolideObjectHeader`
+pub const OBJECT_HEADER_VERSION: u32 = 2;
+/// 对象头字节数；对象指针之前的这段内存属于头部
+pub const OBJECT_HEADER_SIZE: usize = std::mem::size_of::<BolideObjectHeader>();
+/// 对象（头部和数据部分）的对齐
+pub const OBJECT_ALIGN: usize = std::mem::align_of::<BolideObjectHeader>();
+/// 每个字段占用的字节数（int/float/bool 和各种指针都存成 8 字节）
+pub const OBJECT_FIELD_SIZE: usize = 8;
+
+// 头部之后的数据部分必须满足字段的对齐
+const _: () = assert!(OBJECT_HEADER_SIZE.is_multiple_of(OBJECT_ALIGN) && OBJECT_ALIGN >= OBJECT_FIELD_SIZE);
+
+/// 数据指针对应的对象头
+///
+/// # Safety
+/// `data_ptr` 必须是 `object_alloc` 返回且尚未释放的指针
+unsafe fn header_of(data_ptr: *mut u8) -> *mut BolideObjectHeader {
+    data_ptr.sub(OBJECT_HEADER_SIZE) as *mut BolideObjectHeader
+}
 
 /// 分配对象内存
 /// size: 对象数据大小（不含头部）
 /// 返回: 指向对象数据的指针（头部在前面）
 #[no_mangle]
 pub extern "C" fn object_alloc(size: usize) -> *mut u8 {
-    let total_size = HEADER_SIZE + size;
-    let layout = Layout::from_size_align(total_size, 8).unwrap();
+    let total_size = OBJECT_HEADER_SIZE + size;
+    let layout = Layout::from_size_align(total_size, OBJECT_ALIGN).unwrap();
 
     unsafe {
         let ptr = alloc(layout);
@@ -29,12 +75,19 @@ pub extern "C" fn object_alloc(size: usize) -> *mut u8 {
         }
 
         // 初始化头部
-        let header = ptr as *mut ObjectHeader;
-        (*header).ref_count = AtomicUsize::new(1);
-        (*header).data_size = size;
+        let header = ptr as *mut BolideObjectHeader;
+        header.write(BolideObjectHeader {
+            ref_count: AtomicUsize::new(1),
+            data_size: size,
+            weak: AtomicPtr::new(std::ptr::null_mut()),
+        });
 
         // 返回数据部分的指针
-        ptr.add(HEADER_SIZE)
+        let data_ptr = ptr.add(OBJECT_HEADER_SIZE);
+        if OBJECT_DEBUG.with(Cell::get) {
+            register_live(data_ptr, size);
+        }
+        data_ptr
     }
 }
 
@@ -45,8 +98,7 @@ pub extern "C" fn object_retain(data_ptr: *mut u8) {
         return;
     }
     unsafe {
-        let header = (data_ptr as *mut u8).sub(HEADER_SIZE) as *mut ObjectHeader;
-        (*header).ref_count.fetch_add(1, Ordering::SeqCst);
+        (*header_of(data_ptr)).ref_count.fetch_add(1, Ordering::SeqCst);
     }
 }
 
@@ -57,16 +109,21 @@ pub extern "C" fn object_release(data_ptr: *mut u8) {
         return;
     }
     unsafe {
-        let header_ptr = (data_ptr as *mut u8).sub(HEADER_SIZE);
-        let header = header_ptr as *mut ObjectHeader;
+        let header = header_of(data_ptr);
 
         let old_count = (*header).ref_count.fetch_sub(1, Ordering::SeqCst);
         if old_count == 1 {
+            // 弱句柄从此升级失败；控制块在最后一个句柄释放时回收
+            let cell = (*header).weak.load(Ordering::SeqCst);
+            if !cell.is_null() {
+                (*cell).alive.store(false, Ordering::SeqCst);
+                bolide_object_weak_release(cell);
+            }
+            unregister_live(data_ptr);
             // 引用计数为0，释放内存
-            let data_size = (*header).data_size;
-            let total_size = HEADER_SIZE + data_size;
-            let layout = Layout::from_size_align(total_size, 8).unwrap();
-            dealloc(header_ptr, layout);
+            let total_size = OBJECT_HEADER_SIZE + (*header).data_size;
+            let layout = Layout::from_size_align(total_size, OBJECT_ALIGN).unwrap();
+            dealloc(header as *mut u8, layout);
         }
     }
 }
@@ -79,3 +136,529 @@ pub extern "C" fn object_clone(data_ptr: *mut u8) -> *mut u8 {
     }
     data_ptr
 }
+
+// ==================== 内省 ====================
+
+/// 对象数据部分的字节数（`ClassInfo::size`，不含头部）；nil 为 0
+///
+/// 供调试器、序列化器按 `OBJECT_FIELD_SIZE` 遍历字段。
+#[no_mangle]
+pub extern "C" fn bolide_object_size(data_ptr: *mut u8) -> usize {
+    if data_ptr.is_null() {
+        return 0;
+    }
+    unsafe { (*header_of(data_ptr)).data_size }
+}
+
+/// 对象当前的引用计数；nil 为 0
+#[no_mangle]
+pub extern "C" fn bolide_object_refcount(data_ptr: *mut u8) -> usize {
+    if data_ptr.is_null() {
+        return 0;
+    }
+    unsafe { (*header_of(data_ptr)).ref_count.load(Ordering::SeqCst) }
+}
+
+// ==================== 存活对象登记 ====================
+
+thread_local! {
+    /// 当前线程分配的对象是否登记（`bolide_object_debug_enable`）
+    static OBJECT_DEBUG: Cell<bool> = const { Cell::new(false) };
+}
+
+/// 登记的存活对象：数据指针 -> (数据字节数, 分配它的线程)
+///
+/// 只登记开启了调试的线程分配的对象，任何线程释放对象时都会移除，
+/// 所以并发运行的其他代码不会影响一个线程看到的存活数。
+static LIVE_OBJECTS: Mutex<BTreeMap<usize, (usize, ThreadId)>> = Mutex::new(BTreeMap::new());
+/// 登记表的条目数；为 0 时释放对象不需要加锁
+static LIVE_OBJECT_ENTRIES: AtomicUsize = AtomicUsize::new(0);
+
+fn live_objects() -> std::sync::MutexGuard<'static, BTreeMap<usize, (usize, ThreadId)>> {
+    LIVE_OBJECTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn register_live(data_ptr: *mut u8, size: usize) {
+    let mut live = live_objects();
+    live.insert(data_ptr as usize, (size, std::thread::current().id()));
+    LIVE_OBJECT_ENTRIES.fetch_add(1, Ordering::SeqCst);
+}
+
+fn unregister_live(data_ptr: *mut u8) {
+    if LIVE_OBJECT_ENTRIES.load(Ordering::SeqCst) == 0 {
+        return;
+    }
+    let mut live = live_objects();
+    if live.remove(&(data_ptr as usize)).is_some() {
+        LIVE_OBJECT_ENTRIES.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// 当前线程登记的存活对象（按地址排序）
+fn live_objects_of_current_thread() -> Vec<(usize, usize)> {
+    let me = std::thread::current().id();
+    live_objects()
+        .iter()
+        .filter(|(_, (_, owner))| *owner == me)
+        .map(|(&ptr, &(size, _))| (ptr, size))
+        .collect()
+}
+
+/// 开启（非 0）或关闭当前线程的存活对象登记，返回之前的状态（1/0）
+///
+/// 开启后当前线程分配的对象在释放前都算存活；关闭时丢弃当前线程的登记。
+#[no_mangle]
+pub extern "C" fn bolide_object_debug_enable(on: i64) -> i64 {
+    let previous = OBJECT_DEBUG.with(|flag| flag.replace(on != 0));
+    if on == 0 {
+        let me = std::thread::current().id();
+        let mut live = live_objects();
+        let before = live.len();
+        live.retain(|_, (_, owner)| *owner != me);
+        LIVE_OBJECT_ENTRIES.fetch_sub(before - live.len(), Ordering::SeqCst);
+    }
+    previous as i64
+}
+
+/// 当前线程开启登记后分配、尚未释放的对象个数
+#[no_mangle]
+pub extern "C" fn bolide_object_live_count() -> i64 {
+    live_objects_of_current_thread().len() as i64
+}
+
+/// 打印当前线程登记的存活对象：地址、数据字节数和引用计数
+#[no_mangle]
+pub extern "C" fn bolide_object_dump_live() {
+    let live = live_objects_of_current_thread();
+    outln!("[Object Live] {} object(s)", live.len());
+    for (ptr, size) in live {
+        let refcount = bolide_object_refcount(ptr as *mut u8);
+        outln!("  {:#x} size: {}, refcount: {}", ptr, size, refcount);
+    }
+}
+
+// ==================== 弱句柄 ====================
+
+/// 对象弱句柄的控制块
+///
+/// 每个对象最多一个，由对象头的 `weak` 指向；句柄就是控制块指针。对象释放时标记为失效，
+/// 控制块本身在对象和所有句柄都释放后回收，所以失效的句柄仍可以安全地检查和释放。
+///
+/// 升级只在持有句柄的线程与释放对象的线程不并发时可靠（`weak_dict` 不跨线程共享）。
+pub struct ObjectWeakCell {
+    object: *mut u8,
+    alive: AtomicBool,
+    /// 句柄数，对象存活时另加 1
+    count: AtomicUsize,
+}
+
+impl ObjectWeakCell {
+    /// 对象是否还活着
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    /// 对象指针（对象已释放时只能用来比较，不能解引用）
+    pub fn object(&self) -> *mut u8 {
+        self.object
+    }
+}
+
+static WEAK_CELL_ALLOC_COUNT: AtomicUsize = AtomicUsize::new(0);
+static WEAK_CELL_FREE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// 创建对象的弱句柄（控制块的句柄数 +1）；nil 返回空指针
+#[no_mangle]
+pub extern "C" fn bolide_object_downgrade(data_ptr: *mut u8) -> *mut ObjectWeakCell {
+    if data_ptr.is_null() {
+        return std::ptr::null_mut();
+    }
+    unsafe {
+        let header = header_of(data_ptr);
+        let mut cell = (*header).weak.load(Ordering::SeqCst);
+        if cell.is_null() {
+            let fresh = Box::into_raw(Box::new(ObjectWeakCell {
+                object: data_ptr,
+                alive: AtomicBool::new(true),
+                count: AtomicUsize::new(1),
+            }));
+            cell = match (*header).weak.compare_exchange(std::ptr::null_mut(), fresh, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => {
+                    WEAK_CELL_ALLOC_COUNT.fetch_add(1, Ordering::SeqCst);
+                    fresh
+                }
+                Err(existing) => {
+                    drop(Box::from_raw(fresh));
+                    existing
+                }
+            };
+        }
+        (*cell).count.fetch_add(1, Ordering::SeqCst);
+        cell
+    }
+}
+
+/// 升级弱句柄：对象还活着时返回它并增加引用计数，否则返回空指针
+#[no_mangle]
+pub extern "C" fn bolide_object_weak_upgrade(cell: *mut ObjectWeakCell) -> *mut u8 {
+    if cell.is_null() {
+        return std::ptr::null_mut();
+    }
+    unsafe {
+        if !(*cell).is_alive() {
+            return std::ptr::null_mut();
+        }
+        // 计数已经归零的对象不能复活
+        let count = &(*header_of((*cell).object)).ref_count;
+        let mut current = count.load(Ordering::SeqCst);
+        while current > 0 {
+            match count.compare_exchange(current, current + 1, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => return (*cell).object,
+                Err(actual) => current = actual,
+            }
+        }
+        std::ptr::null_mut()
+    }
+}
+
+/// 释放弱句柄；对象已释放且这是最后一个句柄时回收控制块
+#[no_mangle]
+pub extern "C" fn bolide_object_weak_release(cell: *mut ObjectWeakCell) {
+    if cell.is_null() {
+        return;
+    }
+    unsafe {
+        if (*cell).count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            drop(Box::from_raw(cell));
+            WEAK_CELL_FREE_COUNT.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// 到目前为止创建和回收的控制块个数（泄漏检查用）
+pub fn weak_cell_stats() -> (usize, usize) {
+    (WEAK_CELL_ALLOC_COUNT.load(Ordering::SeqCst), WEAK_CELL_FREE_COUNT.load(Ordering::SeqCst))
+}
+
+/// 打印弱句柄控制块统计
+#[no_mangle]
+pub extern "C" fn bolide_weak_debug_stats() {
+    let (alloc, free) = weak_cell_stats();
+    outln!("[Weak Stats] alloc: {}, free: {}, leak: {}", alloc, free, alloc - free);
+}
+
+// ==================== 递归保护 ====================
+
+/// 沿对象图递归的运行时辅助（`object_to_string`、`object_equals`）共用的保护：
+/// 每种递归一个线程局部栈，记录正在处理的对象（由外到内），
+/// 同一个键再次进入是循环引用，栈深到达上限是嵌套过深。
+type ActiveStack<K> = std::thread::LocalKey<RefCell<Vec<K>>>;
+
+/// 不能进入的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reentry {
+    /// 键已经在栈上（循环引用）
+    Cycle,
+    /// 栈深达到上限
+    TooDeep,
+}
+
+/// 占住栈上的一项，结束（包括 panic）时弹出
+struct RecursionGuard<K: 'static> {
+    active: &'static ActiveStack<K>,
+}
+
+impl<K: PartialEq + 'static> RecursionGuard<K> {
+    /// 把 `key` 压入 `active`；已在栈上或栈深达到 `max_depth` 时不压入
+    fn enter(active: &'static ActiveStack<K>, key: K, max_depth: usize) -> Result<Self, Reentry> {
+        active.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            if stack.contains(&key) {
+                return Err(Reentry::Cycle);
+            }
+            if stack.len() >= max_depth {
+                return Err(Reentry::TooDeep);
+            }
+            stack.push(key);
+            Ok(())
+        })?;
+        Ok(RecursionGuard { active })
+    }
+}
+
+impl<K: 'static> Drop for RecursionGuard<K> {
+    fn drop(&mut self) {
+        self.active.with(|stack| stack.borrow_mut().pop());
+    }
+}
+
+// ==================== 字符串表示 ====================
+
+/// 嵌套对象格式化的最大深度，更深的对象输出 `...`
+pub const FORMAT_MAX_DEPTH: usize = 16;
+
+thread_local! {
+    /// 当前线程正在格式化的对象（由外到内）
+    static FORMATTING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 类实例转字符串（`print`/`str()`）
+///
+/// `format` 是编译器为该类选出的格式化函数：用户定义的 `__str__`，
+/// 没有时为合成的 `__default_str__`。nil 输出 `nil`；对象已在格式化中
+/// （循环引用）或嵌套超过 `FORMAT_MAX_DEPTH` 时输出 `...`。
+#[no_mangle]
+pub extern "C" fn object_to_string(
+    data_ptr: *mut u8,
+    format: extern "C" fn(*mut u8) -> *mut BolideString,
+) -> *mut BolideString {
+    if data_ptr.is_null() {
+        return BolideString::new("nil");
+    }
+    match RecursionGuard::enter(&FORMATTING, data_ptr as usize, FORMAT_MAX_DEPTH) {
+        Ok(_guard) => format(data_ptr),
+        Err(_) => BolideString::new("..."),
+    }
+}
+
+// ==================== 结构相等 ====================
+
+/// 嵌套对象逐字段比较的最大深度，更深处只比较引用
+pub const EQUALS_MAX_DEPTH: usize = 1024;
+
+thread_local! {
+    /// 当前线程正在比较的对象对（由外到内）
+    static COMPARING: RefCell<Vec<(usize, usize)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 类实例的 `==`（类没有定义 `__eq__` 时）
+///
+/// `eq` 是编译器为该类合成的 `__default_eq__`。同一对象（包括两个 nil）相等，
+/// 只有一边是 nil 时不等。同一对对象已在比较中（循环引用）时视为相等，
+/// 由外层正在进行的比较决定结果；嵌套超过 `EQUALS_MAX_DEPTH` 时按引用比较。
+#[no_mangle]
+pub extern "C" fn object_equals(
+    a: *mut u8,
+    b: *mut u8,
+    eq: extern "C" fn(*mut u8, *mut u8) -> i64,
+) -> i64 {
+    if a == b {
+        return 1;
+    }
+    if a.is_null() || b.is_null() {
+        return 0;
+    }
+    match RecursionGuard::enter(&COMPARING, (a as usize, b as usize), EQUALS_MAX_DEPTH) {
+        Ok(_guard) => eq(a, b),
+        Err(Reentry::Cycle) => 1,
+        Err(Reentry::TooDeep) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bolide_string_concat, bolide_string_release};
+
+    /// 测试用的两字段对象：[next 指针, 值]
+    extern "C" fn format_node(obj: *mut u8) -> *mut BolideString {
+        unsafe {
+            let next = *(obj as *const *mut u8);
+            let value = *(obj as *const i64).add(1);
+            let inner = object_to_string(next, format_node);
+            let head = BolideString::new(&format!("Node({}) -> ", value));
+            let s = bolide_string_concat(head, inner);
+            bolide_string_release(head);
+            bolide_string_release(inner);
+            s
+        }
+    }
+
+    /// 测试用的比较函数：值相等且 next 递归相等
+    extern "C" fn eq_node(a: *mut u8, b: *mut u8) -> i64 {
+        unsafe {
+            if *(a as *const i64).add(1) != *(b as *const i64).add(1) {
+                return 0;
+            }
+            object_equals(*(a as *const *mut u8), *(b as *const *mut u8), eq_node)
+        }
+    }
+
+    fn render(obj: *mut u8) -> String {
+        let s = object_to_string(obj, format_node);
+        let text = unsafe { (*s).as_str().to_string() };
+        bolide_string_release(s);
+        text
+    }
+
+    thread_local! {
+        static TEST_ACTIVE: RefCell<Vec<u32>> = const { RefCell::new(Vec::new()) };
+    }
+
+    #[test]
+    fn test_recursion_guard() {
+        let outer = RecursionGuard::enter(&TEST_ACTIVE, 1, 2).unwrap();
+        assert_eq!(RecursionGuard::enter(&TEST_ACTIVE, 1, 2).err(), Some(Reentry::Cycle));
+        let inner = RecursionGuard::enter(&TEST_ACTIVE, 2, 2).unwrap();
+        assert_eq!(RecursionGuard::enter(&TEST_ACTIVE, 3, 2).err(), Some(Reentry::TooDeep));
+        drop(inner);
+        // 离开后同一个键可以再次进入
+        drop(RecursionGuard::enter(&TEST_ACTIVE, 2, 2).unwrap());
+        drop(outer);
+        assert!(TEST_ACTIVE.with(|a| a.borrow().is_empty()));
+    }
+
+    #[test]
+    fn test_object_header_introspection() {
+        assert_eq!(OBJECT_HEADER_SIZE, 3 * std::mem::size_of::<usize>());
+        assert_eq!(std::mem::offset_of!(BolideObjectHeader, ref_count), 0);
+        assert_eq!(std::mem::offset_of!(BolideObjectHeader, data_size), std::mem::size_of::<usize>());
+        assert_eq!(std::mem::offset_of!(BolideObjectHeader, weak), 2 * std::mem::size_of::<usize>());
+
+        let obj = object_alloc(3 * OBJECT_FIELD_SIZE);
+        assert_eq!(obj as usize % OBJECT_ALIGN, 0);
+        assert_eq!(bolide_object_size(obj), 24);
+        assert_eq!(bolide_object_refcount(obj), 1);
+        object_retain(obj);
+        assert_eq!(bolide_object_refcount(obj), 2);
+        object_release(obj);
+        assert_eq!(bolide_object_refcount(obj), 1);
+        object_release(obj);
+
+        assert_eq!(bolide_object_size(std::ptr::null_mut()), 0);
+        assert_eq!(bolide_object_refcount(std::ptr::null_mut()), 0);
+    }
+
+    #[test]
+    fn test_weak_handles() {
+        let obj = object_alloc(OBJECT_FIELD_SIZE);
+        let a = bolide_object_downgrade(obj);
+        let b = bolide_object_downgrade(obj);
+        // 同一个对象共用一个控制块
+        assert_eq!(a, b);
+        assert_eq!(bolide_object_refcount(obj), 1);
+
+        let strong = bolide_object_weak_upgrade(a);
+        assert_eq!(strong, obj);
+        assert_eq!(bolide_object_refcount(obj), 2);
+        object_release(strong);
+
+        bolide_object_weak_release(b);
+        object_release(obj);
+        unsafe { assert!(!(*a).is_alive()); }
+        assert!(bolide_object_weak_upgrade(a).is_null());
+        bolide_object_weak_release(a);
+
+        assert!(bolide_object_downgrade(std::ptr::null_mut()).is_null());
+        assert!(bolide_object_weak_upgrade(std::ptr::null_mut()).is_null());
+    }
+
+    #[test]
+    fn test_live_object_registry() {
+        // 开启之前分配的对象不登记
+        let before = object_alloc(OBJECT_FIELD_SIZE);
+        assert_eq!(bolide_object_debug_enable(1), 0);
+        assert_eq!(bolide_object_live_count(), 0);
+
+        let a = object_alloc(OBJECT_FIELD_SIZE);
+        let b = object_alloc(2 * OBJECT_FIELD_SIZE);
+        assert_eq!(bolide_object_live_count(), 2);
+        object_retain(a);
+        object_release(a);
+        assert_eq!(bolide_object_live_count(), 2);
+        object_release(a);
+        assert_eq!(bolide_object_live_count(), 1);
+        object_release(before);
+        assert_eq!(bolide_object_live_count(), 1);
+
+        // 其他线程分配的对象不算在当前线程里，但在这里释放的会移除
+        let other = std::thread::spawn(|| {
+            bolide_object_debug_enable(1);
+            let obj = object_alloc(OBJECT_FIELD_SIZE);
+            assert_eq!(bolide_object_live_count(), 1);
+            obj as usize
+        })
+        .join()
+        .unwrap() as *mut u8;
+        assert_eq!(bolide_object_live_count(), 1);
+        object_release(other);
+        assert!(!live_objects().contains_key(&(other as usize)));
+
+        // 关闭时丢弃登记，之后释放不受影响
+        assert_eq!(bolide_object_debug_enable(0), 1);
+        assert_eq!(bolide_object_live_count(), 0);
+        object_release(b);
+    }
+
+    #[test]
+    fn test_object_to_string_guards() {
+        unsafe {
+            let a = object_alloc(16);
+            let b = object_alloc(16);
+            *(a as *mut *mut u8) = b;
+            *(a as *mut i64).add(1) = 1;
+            *(b as *mut *mut u8) = std::ptr::null_mut();
+            *(b as *mut i64).add(1) = 2;
+            assert_eq!(render(a), "Node(1) -> Node(2) -> nil");
+
+            // 环：a -> b -> a
+            *(b as *mut *mut u8) = a;
+            assert_eq!(render(a), "Node(1) -> Node(2) -> ...");
+
+            // 长链在深度限制处截断
+            let nodes: Vec<*mut u8> = (0..FORMAT_MAX_DEPTH + 4).map(|_| object_alloc(16)).collect();
+            for (i, &node) in nodes.iter().enumerate() {
+                *(node as *mut *mut u8) = nodes.get(i + 1).copied().unwrap_or(std::ptr::null_mut());
+                *(node as *mut i64).add(1) = i as i64;
+            }
+            let text = render(nodes[0]);
+            assert_eq!(text.matches("Node(").count(), FORMAT_MAX_DEPTH);
+            assert!(text.ends_with("-> ..."));
+
+            for node in nodes.into_iter().chain([a, b]) {
+                object_release(node);
+            }
+        }
+    }
+
+    #[test]
+    fn test_object_equals_guards() {
+        unsafe {
+            let chain = |values: &[i64]| -> Vec<*mut u8> {
+                let nodes: Vec<*mut u8> = values.iter().map(|_| object_alloc(16)).collect();
+                for (i, &node) in nodes.iter().enumerate() {
+                    *(node as *mut *mut u8) = nodes.get(i + 1).copied().unwrap_or(std::ptr::null_mut());
+                    *(node as *mut i64).add(1) = values[i];
+                }
+                nodes
+            };
+            let a = chain(&[1, 2, 3]);
+            let b = chain(&[1, 2, 3]);
+            let c = chain(&[1, 2, 4]);
+            assert_eq!(object_equals(a[0], b[0], eq_node), 1);
+            assert_eq!(object_equals(a[0], c[0], eq_node), 0);
+            assert_eq!(object_equals(a[0], a[0], eq_node), 1);
+            assert_eq!(object_equals(a[0], std::ptr::null_mut(), eq_node), 0);
+            assert_eq!(object_equals(std::ptr::null_mut(), std::ptr::null_mut(), eq_node), 1);
+
+            // 环：a0 -> a1 -> a2 -> a0 与 b0 -> b1 -> b2 -> b0
+            *(a[2] as *mut *mut u8) = a[0];
+            *(b[2] as *mut *mut u8) = b[0];
+            assert_eq!(object_equals(a[0], b[0], eq_node), 1);
+            *(b[1] as *mut i64).add(1) = 9;
+            assert_eq!(object_equals(a[0], b[0], eq_node), 0);
+            COMPARING.with(|c| assert!(c.borrow().is_empty()));
+
+            // 超过深度限制的部分只比较引用
+            let values: Vec<i64> = (0..EQUALS_MAX_DEPTH as i64 + 8).collect();
+            let long_a = chain(&values);
+            let long_b = chain(&values);
+            assert_eq!(object_equals(long_a[0], long_b[0], eq_node), 0);
+            assert_eq!(object_equals(long_a[10], long_b[10], eq_node), 1);
+
+            for node in a.into_iter().chain(b).chain(c).chain(long_a).chain(long_b) {
+                object_release(node);
+            }
+        }
+    }
+}
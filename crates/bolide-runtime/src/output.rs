@@ -0,0 +1,232 @@
+//! 标准输出
+//!
+//! 运行时的所有输出（print 系列、列表/字典/元组的打印、input 的提示、measure 报告、
+//! 内存统计）都经过 `write_stdout`，写进进程共享的缓冲区 `STDOUT`。每次调用只加一次锁，
+//! 一次 print 的整行在锁内写完，多个线程同时打印时各行不会交错。
+//!
+//! 标准输出是终端时，写入的内容含换行就刷新（和行缓冲一样，交互时及时看到输出）；
+//! 重定向到文件或管道时缓冲区满了才写出，循环里打印大量数据时系统调用少得多。
+//! 缓冲区在这些时候刷新：`flush()`、读取输入之前、`__main__` 返回时、`exit` 和运行时错误，
+//! 以及进程退出时（`atexit`，覆盖嵌入方和 AOT 库直接退出的情况）。
+//!
+//! 读端关闭时（`bolide run prog.bl | head -5`）写入返回 EPIPE（Windows 上管道关闭也映射为
+//! BrokenPipe）：不再输出，以状态码 0 结束程序，与常见的命令行工具一致。其余写入错误走运行时错误。
+//!
+//! Unix 上首次输出前忽略 SIGPIPE，否则编译出的可执行文件会在写入时被信号直接杀死
+//! （Rust 程序启动时已经忽略它，AOT 程序的入口不经过 Rust）。
+
+use std::fmt;
+use std::io::{self, BufWriter, IsTerminal, Stdout, Write};
+use std::sync::{Mutex, MutexGuard, Once};
+
+/// `print!` 的替代，经过 `write_stdout`
+macro_rules! out {
+    ($($arg:tt)*) => {
+        $crate::output::write_stdout(format_args!($($arg)*))
+    };
+}
+
+/// `println!` 的替代，经过 `write_stdout`
+macro_rules! outln {
+    () => {
+        $crate::output::write_stdout(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::write_stdout(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+pub(crate) use out;
+pub(crate) use outln;
+
+/// 缓冲区大小
+const BUFFER_CAPACITY: usize = 64 * 1024;
+
+/// 带缓冲的输出：`line_buffered` 时写入的内容含换行就刷新
+struct Output<W: Write> {
+    writer: BufWriter<W>,
+    line_buffered: bool,
+}
+
+impl<W: Write> Output<W> {
+    fn new(inner: W, line_buffered: bool) -> Self {
+        Output { writer: BufWriter::with_capacity(BUFFER_CAPACITY, inner), line_buffered }
+    }
+
+    fn write(&mut self, args: fmt::Arguments) -> io::Result<()> {
+        self.writer.write_fmt(args)?;
+        if self.line_buffered && self.writer.buffer().contains(&b'\n') {
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+static STDOUT: Mutex<Option<Output<Stdout>>> = Mutex::new(None);
+
+/// 取得输出缓冲区（首次使用时创建，并忽略 SIGPIPE、注册退出时的刷新）
+fn stdout_buffer() -> MutexGuard<'static, Option<Output<Stdout>>> {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        ignore_sigpipe();
+        register_exit_flush();
+    });
+    let mut guard = STDOUT.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.is_none() {
+        let stdout = io::stdout();
+        let line_buffered = stdout.is_terminal();
+        *guard = Some(Output::new(stdout, line_buffered));
+    }
+    guard
+}
+
+/// 写到标准输出
+pub fn write_stdout(args: fmt::Arguments) {
+    let result = stdout_buffer().as_mut().map_or(Ok(()), |out| out.write(args));
+    if let Err(e) = result {
+        write_failed(e);
+    }
+}
+
+/// 刷新标准输出（input 的提示之后、`flush()`、程序结束前）
+pub fn flush_stdout() {
+    let result = stdout_buffer().as_mut().map_or(Ok(()), |out| out.flush());
+    if let Err(e) = result {
+        write_failed(e);
+    }
+}
+
+/// 退出前刷新，忽略错误（运行时错误的路径上不能再报错）
+pub(crate) fn flush_stdout_quietly() {
+    let _ = stdout_buffer().as_mut().map(|out| out.flush());
+}
+
+fn write_failed(e: io::Error) {
+    if e.kind() == io::ErrorKind::BrokenPipe {
+        // 读端已经走了，剩下的输出没人看；退出时刷新缓冲区再遇到 EPIPE 会被忽略
+        std::process::exit(0);
+    }
+    crate::runtime_error(&format!("cannot write to standard output: {}", e));
+}
+
+/// 进程退出时（`exit`、从 C 的 `main` 返回）刷新缓冲区
+fn register_exit_flush() {
+    extern "C" fn flush_at_exit() {
+        // 退出可能发生在别的线程正在写的时候，拿不到锁就放弃，不在退出时卡住
+        if let Ok(mut guard) = STDOUT.try_lock() {
+            if let Some(out) = guard.as_mut() {
+                let _ = out.flush();
+            }
+        }
+    }
+    extern "C" {
+        fn atexit(callback: extern "C" fn()) -> i32;
+    }
+    unsafe { atexit(flush_at_exit); }
+}
+
+#[cfg(unix)]
+fn ignore_sigpipe() {
+    extern "C" {
+        fn signal(signum: i32, handler: usize) -> usize;
+    }
+    const SIGPIPE: i32 = 13;
+    const SIG_IGN: usize = 1;
+    unsafe { signal(SIGPIPE, SIG_IGN); }
+}
+
+#[cfg(not(unix))]
+fn ignore_sigpipe() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+
+    const CHILD_ENV: &str = "BOLIDE_OUTPUT_TEST_CHILD";
+
+    /// 记录底层写入次数（相当于 write 系统调用）的输出目标
+    #[derive(Default)]
+    struct CountingSink {
+        writes: usize,
+        data: Vec<u8>,
+    }
+
+    impl Write for CountingSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_buffered_output_batches_writes() {
+        const COUNT: usize = 1_000_000;
+        let mut out = Output::new(CountingSink::default(), false);
+        for i in 0..COUNT {
+            out.write(format_args!("{}\n", i)).unwrap();
+        }
+        out.flush().unwrap();
+        let sink = out.writer.get_ref();
+        // 逐行写出（原来的行缓冲）每行一次写入
+        assert!(sink.writes * 5 <= COUNT, "{} writes", sink.writes);
+        assert!(sink.data.starts_with(b"0\n1\n2\n"));
+        assert!(sink.data.ends_with(b"999999\n"));
+    }
+
+    #[test]
+    fn test_line_buffered_output_flushes_lines() {
+        let mut out = Output::new(CountingSink::default(), true);
+        out.write(format_args!("a")).unwrap();
+        out.write(format_args!("b")).unwrap();
+        assert_eq!(out.writer.get_ref().writes, 0);
+        out.write(format_args!("c\n")).unwrap();
+        assert_eq!(out.writer.get_ref().writes, 1);
+        assert_eq!(out.writer.get_ref().data, b"abc\n");
+    }
+
+    #[test]
+    fn test_closed_pipe_exits_cleanly() {
+        if std::env::var_os(CHILD_ENV).is_some() {
+            // 子进程：输出到读端提前关闭的管道
+            for i in 0..1_000_000 {
+                outln!("line {}", i);
+            }
+            std::process::exit(3);
+        }
+
+        let mut child = Command::new(std::env::current_exe().unwrap())
+            .args(["output::tests::test_closed_pipe_exits_cleanly", "--exact", "--nocapture", "--test-threads=1"])
+            .env(CHILD_ENV, "1")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let started = Instant::now();
+        let mut reader = BufReader::new(child.stdout.take().unwrap());
+        let mut seen = 0;
+        let mut line = String::new();
+        while seen < 10 && reader.read_line(&mut line).unwrap() > 0 {
+            if line.starts_with("line ") {
+                seen += 1;
+            }
+            line.clear();
+        }
+        drop(reader);
+
+        let status = child.wait().unwrap();
+        assert_eq!(status.code(), Some(0), "{:?}", status);
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,433 @@
+//! packed 列表：类实例按值内联存储的列表（`list<packed T>`）
+//!
+//! 元素是缓冲区里连续的定长记录，没有对象头和引用计数。每个字段占 8 字节，
+//! 内联的类字段原地展开成它的全部字段。编译后的代码从列表头读出 `data` 和 `len`，
+//! 用 `data + i * elem_size + 字段偏移` 直接读写字段，越界时才调用 `bolide_packed_list_at`。
+//!
+//! 列表本身带引用计数，赋值时和普通列表一样复制整个缓冲区（`bolide_packed_list_clone`）。
+//! push / resize 可能重新分配缓冲区，指向元素的借用由编译器在调用处作废。
+//!
+//! 运行时不知道字段类型，创建列表时编译器传入元素布局的描述（见 `PackedClass::parse`），
+//! 只用于打印。
+
+use std::alloc::{alloc_zeroed, dealloc, realloc, Layout};
+use std::cell::RefCell;
+use std::fmt::Write as _;
+
+use crate::BolideString;
+use crate::output::outln;
+
+/// 元素布局描述里的字段
+#[derive(Debug, Clone, PartialEq)]
+enum PackedField {
+    Int,
+    Float,
+    Bool,
+    Class(PackedClass),
+}
+
+/// 元素布局：`Vec2{x:f,y:f}`，字段类型 `i` / `f` / `b` 或内联的类 `Name{...}`
+#[derive(Debug, Clone, PartialEq)]
+struct PackedClass {
+    name: String,
+    fields: Vec<(String, PackedField)>,
+}
+
+impl PackedClass {
+    fn parse(desc: &str) -> Option<Self> {
+        let (class, rest) = Self::parse_class(desc)?;
+        rest.is_empty().then_some(class)
+    }
+
+    fn parse_class(s: &str) -> Option<(Self, &str)> {
+        let open = s.find('{')?;
+        let name = s[..open].to_string();
+        let mut rest = &s[open + 1..];
+        let mut fields = Vec::new();
+        while !rest.starts_with('}') {
+            let colon = rest.find(':')?;
+            let field_name = rest[..colon].to_string();
+            let (field, remaining) = Self::parse_field(&rest[colon + 1..])?;
+            fields.push((field_name, field));
+            rest = remaining.strip_prefix(',').unwrap_or(remaining);
+        }
+        Some((PackedClass { name, fields }, &rest[1..]))
+    }
+
+    fn parse_field(s: &str) -> Option<(PackedField, &str)> {
+        let scalar = match s.get(..2).map(|head| head.as_bytes()) {
+            Some([b'i', b',' | b'}']) => Some(PackedField::Int),
+            Some([b'f', b',' | b'}']) => Some(PackedField::Float),
+            Some([b'b', b',' | b'}']) => Some(PackedField::Bool),
+            _ => None,
+        };
+        match scalar {
+            Some(field) => Some((field, &s[1..])),
+            None => Self::parse_class(s).map(|(class, rest)| (PackedField::Class(class), rest)),
+        }
+    }
+
+    /// 记录的字节数
+    fn size(&self) -> usize {
+        self.fields.iter().map(|(_, field)| match field {
+            PackedField::Class(class) => class.size(),
+            _ => 8,
+        }).sum()
+    }
+
+    /// 按类实例的默认格式输出：`Vec2 { x: 1, y: 2.5 }`
+    ///
+    /// # Safety
+    /// `elem` 指向一条按本布局存放的记录
+    unsafe fn format(&self, elem: *const u8, out: &mut String) {
+        let _ = write!(out, "{} {{", self.name);
+        let mut offset = 0;
+        for (i, (name, field)) in self.fields.iter().enumerate() {
+            let _ = write!(out, "{}{}: ", if i == 0 { " " } else { ", " }, name);
+            let slot = elem.add(offset);
+            match field {
+                PackedField::Int => { let _ = write!(out, "{}", *(slot as *const i64)); }
+                PackedField::Float => { let _ = write!(out, "{}", *(slot as *const f64)); }
+                PackedField::Bool => out.push_str(if *(slot as *const i64) != 0 { "true" } else { "false" }),
+                PackedField::Class(class) => class.format(slot, out),
+            }
+            offset += match field {
+                PackedField::Class(class) => class.size(),
+                _ => 8,
+            };
+        }
+        out.push_str(if self.fields.is_empty() { "}" } else { " }" });
+    }
+}
+
+/// 编译器传入的布局描述（`ptr` / `len` 指向 UTF-8 文本）
+fn layout_from_raw(desc: *const u8, desc_len: usize) -> PackedClass {
+    let text = if desc.is_null() {
+        ""
+    } else {
+        unsafe { std::str::from_utf8(std::slice::from_raw_parts(desc, desc_len)).unwrap_or("") }
+    };
+    PackedClass::parse(text).unwrap_or_else(|| PackedClass { name: "?".to_string(), fields: Vec::new() })
+}
+
+/// packed 列表头
+///
+/// 编译后的代码按 `PACKED_LIST_DATA_OFFSET` / `PACKED_LIST_LEN_OFFSET` 直接读取
+/// `data` 和 `len`，调整字段顺序时要同步这两个常量。
+#[repr(C)]
+pub struct BolidePackedList {
+    data: *mut u8,
+    len: usize,
+    capacity: usize,
+    elem_size: usize,
+    ref_count: usize,
+    layout: Box<PackedClass>,
+}
+
+/// 列表头里 `data`（元素缓冲区）的偏移
+pub const PACKED_LIST_DATA_OFFSET: i32 = std::mem::offset_of!(BolidePackedList, data) as i32;
+/// 列表头里 `len`（元素个数）的偏移
+pub const PACKED_LIST_LEN_OFFSET: i32 = std::mem::offset_of!(BolidePackedList, len) as i32;
+
+impl BolidePackedList {
+    fn buffer_layout(elem_size: usize, capacity: usize) -> Layout {
+        Layout::from_size_align((elem_size * capacity).max(1), 8).unwrap()
+    }
+
+    /// 容量至少为 `needed`，新增的部分清零
+    fn reserve(&mut self, needed: usize) {
+        if needed <= self.capacity {
+            return;
+        }
+        let new_cap = needed.max(self.capacity * 2).max(8);
+        let new_layout = Self::buffer_layout(self.elem_size, new_cap);
+        unsafe {
+            self.data = if self.data.is_null() {
+                alloc_zeroed(new_layout)
+            } else {
+                let old_layout = Self::buffer_layout(self.elem_size, self.capacity);
+                let data = realloc(self.data, old_layout, new_layout.size());
+                let old_bytes = self.elem_size * self.capacity;
+                std::ptr::write_bytes(data.add(old_bytes), 0, new_layout.size() - old_bytes);
+                data
+            };
+        }
+        self.capacity = new_cap;
+    }
+
+    /// 调整长度：截掉的元素清零（重新增长时仍是零值），新增的元素为零值
+    fn resize(&mut self, len: usize) {
+        if len > self.len {
+            self.reserve(len);
+        } else if len < self.len {
+            unsafe {
+                std::ptr::write_bytes(self.elem_ptr(len), 0, (self.len - len) * self.elem_size);
+            }
+        }
+        self.len = len;
+    }
+
+    fn elem_ptr(&self, index: usize) -> *mut u8 {
+        unsafe { self.data.add(index * self.elem_size) }
+    }
+}
+
+impl Drop for BolidePackedList {
+    fn drop(&mut self) {
+        if !self.data.is_null() {
+            unsafe { dealloc(self.data, Self::buffer_layout(self.elem_size, self.capacity)) };
+        }
+    }
+}
+
+thread_local! {
+    /// 越界访问返回的零值记录：读到 0，写入被丢弃
+    static SCRATCH: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+// ==================== 创建和销毁 ====================
+
+/// 创建 `len` 个零值元素的列表（ref_count = 1）
+///
+/// `elem_size` 是一条记录的字节数，`desc` / `desc_len` 是元素布局的描述。
+#[no_mangle]
+pub extern "C" fn bolide_packed_list_new(elem_size: usize, len: i64, desc: *const u8, desc_len: usize) -> *mut BolidePackedList {
+    let mut list = BolidePackedList {
+        data: std::ptr::null_mut(),
+        len: 0,
+        capacity: 0,
+        elem_size,
+        ref_count: 1,
+        layout: Box::new(layout_from_raw(desc, desc_len)),
+    };
+    list.resize(len.max(0) as usize);
+    Box::into_raw(Box::new(list))
+}
+
+/// 增加引用计数
+#[no_mangle]
+pub extern "C" fn bolide_packed_list_retain(list: *mut BolidePackedList) -> *mut BolidePackedList {
+    if !list.is_null() {
+        unsafe { (*list).ref_count += 1 };
+    }
+    list
+}
+
+/// 减少引用计数，为 0 时释放列表和缓冲区
+#[no_mangle]
+pub extern "C" fn bolide_packed_list_release(list: *mut BolidePackedList) {
+    if list.is_null() {
+        return;
+    }
+    unsafe {
+        (*list).ref_count -= 1;
+        if (*list).ref_count == 0 {
+            drop(Box::from_raw(list));
+        }
+    }
+}
+
+/// 复制列表（整个缓冲区按字节复制）
+#[no_mangle]
+pub extern "C" fn bolide_packed_list_clone(list: *const BolidePackedList) -> *mut BolidePackedList {
+    if list.is_null() {
+        return std::ptr::null_mut();
+    }
+    let src = unsafe { &*list };
+    let mut copy = BolidePackedList {
+        data: std::ptr::null_mut(),
+        len: 0,
+        capacity: 0,
+        elem_size: src.elem_size,
+        ref_count: 1,
+        layout: src.layout.clone(),
+    };
+    copy.resize(src.len);
+    unsafe {
+        std::ptr::copy_nonoverlapping(src.data, copy.data, src.len * src.elem_size);
+    }
+    Box::into_raw(Box::new(copy))
+}
+
+// ==================== 访问和修改 ====================
+
+/// 元素个数
+#[no_mangle]
+pub extern "C" fn bolide_packed_list_len(list: *const BolidePackedList) -> i64 {
+    if list.is_null() {
+        return 0;
+    }
+    unsafe { (*list).len as i64 }
+}
+
+/// 第 `index` 个元素的地址（内联访问越界时的慢路径）
+///
+/// 越界时记录 `ERROR_INDEX`，返回一条零值的临时记录，读到 0，写入不影响列表。
+#[no_mangle]
+pub extern "C" fn bolide_packed_list_at(list: *mut BolidePackedList, index: i64) -> *mut u8 {
+    let (len, elem_size) = if list.is_null() {
+        (0, 0)
+    } else {
+        unsafe { ((*list).len, (*list).elem_size) }
+    };
+    if index >= 0 && (index as usize) < len {
+        return unsafe { (*list).elem_ptr(index as usize) };
+    }
+    crate::set_error(crate::ERROR_INDEX, &format!("get: index {} out of range for list of length {}", index, len));
+    SCRATCH.with(|scratch| {
+        let mut scratch = scratch.borrow_mut();
+        scratch.clear();
+        scratch.resize(elem_size.div_ceil(8).max(1), 0);
+        scratch.as_mut_ptr() as *mut u8
+    })
+}
+
+/// 追加一个零值元素，返回它的地址（由编译器写入各字段）
+#[no_mangle]
+pub extern "C" fn bolide_packed_list_push(list: *mut BolidePackedList) -> *mut u8 {
+    let list = unsafe { &mut *list };
+    let index = list.len;
+    list.resize(index + 1);
+    list.elem_ptr(index)
+}
+
+/// 调整元素个数：新增的元素为零值；负数按 0 处理
+#[no_mangle]
+pub extern "C" fn bolide_packed_list_resize(list: *mut BolidePackedList, len: i64) {
+    if list.is_null() {
+        return;
+    }
+    unsafe { (*list).resize(len.max(0) as usize) };
+}
+
+/// 交换两个元素；越界时记录 `ERROR_INDEX`，列表不变
+#[no_mangle]
+pub extern "C" fn bolide_packed_list_swap(list: *mut BolidePackedList, i: i64, j: i64) {
+    if list.is_null() {
+        return;
+    }
+    let list = unsafe { &mut *list };
+    for index in [i, j] {
+        if index < 0 || index as usize >= list.len {
+            crate::set_error(crate::ERROR_INDEX, &format!("swap: index {} out of range for list of length {}", index, list.len));
+            return;
+        }
+    }
+    if i != j {
+        unsafe {
+            std::ptr::swap_nonoverlapping(list.elem_ptr(i as usize), list.elem_ptr(j as usize), list.elem_size);
+        }
+    }
+}
+
+// ==================== 打印 ====================
+
+/// 列表的打印形式：`[Vec2 { x: 1, y: 2 }, ...]`
+pub(crate) fn format_packed_list(list: *const BolidePackedList) -> String {
+    let mut text = String::from("[");
+    if !list.is_null() {
+        let list = unsafe { &*list };
+        for i in 0..list.len {
+            if i > 0 {
+                text.push_str(", ");
+            }
+            unsafe { list.layout.format(list.elem_ptr(i), &mut text) };
+        }
+    }
+    text.push(']');
+    text
+}
+
+/// 打印列表：`[Vec2 { x: 1, y: 2 }, ...]`
+#[no_mangle]
+pub extern "C" fn bolide_print_packed_list(list: *const BolidePackedList) {
+    outln!("{}", format_packed_list(list));
+}
+
+/// 单个元素（`ps[i]`）转字符串，格式同打印列表
+#[no_mangle]
+pub extern "C" fn bolide_packed_to_string(elem: *const u8, desc: *const u8, desc_len: usize) -> *mut BolideString {
+    let layout = layout_from_raw(desc, desc_len);
+    let mut text = String::new();
+    unsafe { layout.format(elem, &mut text) };
+    BolideString::new(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARTICLE: &str = "Particle{pos:Vec2{x:f,y:f},id:i,alive:b}";
+
+    fn new_list(len: i64) -> *mut BolidePackedList {
+        bolide_packed_list_new(32, len, PARTICLE.as_ptr(), PARTICLE.len())
+    }
+
+    unsafe fn write_particle(elem: *mut u8, x: f64, id: i64) {
+        *(elem as *mut f64) = x;
+        *(elem.add(16) as *mut i64) = id;
+        *(elem.add(24) as *mut i64) = 1;
+    }
+
+    unsafe fn id_at(list: *mut BolidePackedList, index: i64) -> i64 {
+        *(bolide_packed_list_at(list, index).add(16) as *const i64)
+    }
+
+    #[test]
+    fn test_layout_parse_and_format() {
+        let layout = PackedClass::parse(PARTICLE).unwrap();
+        assert_eq!(layout.size(), 32);
+        assert_eq!(PackedClass::parse("Empty{}").unwrap().size(), 0);
+        assert!(PackedClass::parse("Broken{x:f").is_none());
+
+        let record: [u64; 4] = [1.5f64.to_bits(), (-2.0f64).to_bits(), 7, 0];
+        let mut text = String::new();
+        unsafe { layout.format(record.as_ptr() as *const u8, &mut text) };
+        assert_eq!(text, "Particle { pos: Vec2 { x: 1.5, y: -2 }, id: 7, alive: false }");
+    }
+
+    #[test]
+    fn test_push_resize_swap_clone() {
+        let list = new_list(0);
+        for id in 0..100 {
+            unsafe { write_particle(bolide_packed_list_push(list), id as f64, id) };
+        }
+        assert_eq!(bolide_packed_list_len(list), 100);
+        unsafe {
+            assert_eq!(id_at(list, 99), 99);
+
+            bolide_packed_list_swap(list, 0, 99);
+            assert_eq!(id_at(list, 0), 99);
+            assert_eq!(id_at(list, 99), 0);
+
+            // 缩短后再增长，新元素是零值
+            bolide_packed_list_resize(list, 10);
+            bolide_packed_list_resize(list, 20);
+            assert_eq!(bolide_packed_list_len(list), 20);
+            assert_eq!(id_at(list, 15), 0);
+
+            // clone 复制缓冲区，之后互不影响
+            let copy = bolide_packed_list_clone(list);
+            write_particle(bolide_packed_list_at(list, 1), 0.0, -1);
+            assert_eq!(id_at(copy, 1), 1);
+            bolide_packed_list_release(copy);
+        }
+        bolide_packed_list_release(list);
+    }
+
+    #[test]
+    fn test_out_of_range_sets_error() {
+        let list = new_list(2);
+        crate::bolide_error_clear();
+        unsafe {
+            let scratch = bolide_packed_list_at(list, 5);
+            write_particle(scratch, 3.0, 42);
+            assert_eq!(crate::bolide_error_code(), crate::ERROR_INDEX);
+            // 每次越界拿到的都是零值
+            assert_eq!(id_at(list, 5), 0);
+        }
+        bolide_packed_list_swap(list, 0, -1);
+        assert_eq!(bolide_packed_list_len(list), 2);
+        bolide_packed_list_release(list);
+    }
+}
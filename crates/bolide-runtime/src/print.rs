@@ -5,25 +5,27 @@
 //! - 内部使用各类型的 to_string 方法
 
 use crate::{BolideBigInt, BolideDecimal, BolideDynamic, BolideString};
+use crate::output::{out, outln};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 // ==================== 基本类型打印 ====================
 
 /// 打印整数
 #[no_mangle]
 pub extern "C" fn bolide_print_int(value: i64) {
-    println!("{}", value);
+    outln!("{}", value);
 }
 
 /// 打印浮点数
 #[no_mangle]
 pub extern "C" fn bolide_print_float(value: f64) {
-    println!("{}", value);
+    outln!("{}", value);
 }
 
 /// 打印布尔值
 #[no_mangle]
 pub extern "C" fn bolide_print_bool(value: i64) {
-    println!("{}", if value != 0 { "true" } else { "false" });
+    outln!("{}", if value != 0 { "true" } else { "false" });
 }
 
 // ==================== 复合类型打印 ====================
@@ -32,44 +34,44 @@ pub extern "C" fn bolide_print_bool(value: i64) {
 #[no_mangle]
 pub extern "C" fn bolide_print_bigint(ptr: *const BolideBigInt) {
     if ptr.is_null() {
-        println!("null");
+        outln!("null");
         return;
     }
     let value = unsafe { &*ptr };
-    println!("{}", value.to_string());
+    outln!("{}", value.to_string());
 }
 
 /// 打印 Decimal
 #[no_mangle]
 pub extern "C" fn bolide_print_decimal(ptr: *const BolideDecimal) {
     if ptr.is_null() {
-        println!("null");
+        outln!("null");
         return;
     }
     let value = unsafe { &*ptr };
-    println!("{}", value.to_string());
+    outln!("{}", value.to_string());
 }
 
 /// 打印 String
 #[no_mangle]
 pub extern "C" fn bolide_print_string(ptr: *const BolideString) {
     if ptr.is_null() {
-        println!("null");
+        outln!("null");
         return;
     }
     let value = unsafe { &*ptr };
-    println!("{}", value.as_str());
+    outln!("{}", value.as_str());
 }
 
 /// 打印 Dynamic (自动识别类型)
 #[no_mangle]
 pub extern "C" fn bolide_print_dynamic(ptr: *const BolideDynamic) {
     if ptr.is_null() {
-        println!("null");
+        outln!("null");
         return;
     }
     let value = unsafe { &*ptr };
-    println!("{}", value.to_string_repr());
+    outln!("{}", value.to_string_repr());
 }
 
 // ==================== 辅助函数 ====================
@@ -77,28 +79,105 @@ pub extern "C" fn bolide_print_dynamic(ptr: *const BolideDynamic) {
 /// 打印换行
 #[no_mangle]
 pub extern "C" fn bolide_println() {
-    println!();
+    outln!();
 }
 
 /// 打印整数不换行
 #[no_mangle]
 pub extern "C" fn bolide_print_int_inline(value: i64) {
-    print!("{}", value);
+    out!("{}", value);
 }
 
 /// 打印浮点数不换行
 #[no_mangle]
 pub extern "C" fn bolide_print_float_inline(value: f64) {
-    print!("{}", value);
+    out!("{}", value);
+}
+
+/// 打印布尔值不换行
+#[no_mangle]
+pub extern "C" fn bolide_print_bool_inline(value: i64) {
+    out!("{}", if value != 0 { "true" } else { "false" });
+}
+
+/// 打印 BigInt 不换行
+#[no_mangle]
+pub extern "C" fn bolide_print_bigint_inline(ptr: *const BolideBigInt) {
+    match unsafe { ptr.as_ref() } {
+        Some(value) => out!("{}", value.to_string()),
+        None => out!("null"),
+    }
+}
+
+/// 打印 Decimal 不换行
+#[no_mangle]
+pub extern "C" fn bolide_print_decimal_inline(ptr: *const BolideDecimal) {
+    match unsafe { ptr.as_ref() } {
+        Some(value) => out!("{}", value.to_string()),
+        None => out!("null"),
+    }
+}
+
+/// 打印 String 不换行
+#[no_mangle]
+pub extern "C" fn bolide_print_string_inline(ptr: *const BolideString) {
+    match unsafe { ptr.as_ref() } {
+        Some(value) => out!("{}", value.as_str()),
+        None => out!("null"),
+    }
+}
+
+/// 打印 Dynamic 不换行
+#[no_mangle]
+pub extern "C" fn bolide_print_dynamic_inline(ptr: *const BolideDynamic) {
+    match unsafe { ptr.as_ref() } {
+        Some(value) => out!("{}", value.to_string_repr()),
+        None => out!("null"),
+    }
+}
+
+/// 把缓冲的输出写到标准输出（`flush()`，`__main__` 返回前）
+#[no_mangle]
+pub extern "C" fn bolide_print_flush() {
+    crate::output::flush_stdout();
 }
 
 // ==================== 输入函数 ====================
 
-/// 读取用户输入（无提示）
+/// `input()` 的读取函数：`prompt` 是提示（无提示时为空指针，由读取函数负责显示），
+/// 返回新分配的一行输入（不含换行符）；返回空指针按空字符串处理。
+///
+/// 默认打印提示后从标准输入读一行。REPL 换成从自己的行读取器取行，嵌入方可以用它以程序方式提供输入。
+pub type InputHandler = extern "C" fn(prompt: *const BolideString) -> *mut BolideString;
+
+/// 当前的读取函数，0 表示默认
+static INPUT_HANDLER: AtomicUsize = AtomicUsize::new(0);
+
+/// 设置 `input()` 的读取函数；传空指针恢复为从标准输入读取
 #[no_mangle]
-pub extern "C" fn bolide_input() -> *mut BolideString {
-    use std::io::{self, BufRead, Write};
-    io::stdout().flush().ok();
+pub extern "C" fn bolide_set_input_handler(handler: Option<InputHandler>) {
+    INPUT_HANDLER.store(handler.map_or(0, |h| h as usize), Ordering::SeqCst);
+}
+
+pub(crate) fn read_input(prompt: *const BolideString) -> *mut BolideString {
+    let handler = INPUT_HANDLER.load(Ordering::SeqCst);
+    if handler != 0 {
+        // 只会存入 InputHandler
+        let handler: InputHandler = unsafe { std::mem::transmute::<usize, InputHandler>(handler) };
+        let line = handler(prompt);
+        return if line.is_null() { BolideString::new("") } else { line };
+    }
+    read_stdin_line(prompt)
+}
+
+/// 默认的读取函数：打印提示，从标准输入读一行
+fn read_stdin_line(prompt: *const BolideString) -> *mut BolideString {
+    use std::io::{self, BufRead};
+    if !prompt.is_null() {
+        let prompt_str = unsafe { &*prompt };
+        out!("{}", prompt_str.as_str());
+    }
+    crate::output::flush_stdout();
     let mut input = String::new();
     io::stdin().lock().read_line(&mut input).ok();
     // 移除末尾的换行符
@@ -106,17 +185,14 @@ pub extern "C" fn bolide_input() -> *mut BolideString {
     BolideString::new(trimmed)
 }
 
+/// 读取用户输入（无提示）
+#[no_mangle]
+pub extern "C" fn bolide_input() -> *mut BolideString {
+    read_input(std::ptr::null())
+}
+
 /// 读取用户输入（带提示）
 #[no_mangle]
 pub extern "C" fn bolide_input_prompt(prompt: *const BolideString) -> *mut BolideString {
-    use std::io::{self, BufRead, Write};
-    if !prompt.is_null() {
-        let prompt_str = unsafe { &*prompt };
-        print!("{}", prompt_str.as_str());
-        io::stdout().flush().ok();
-    }
-    let mut input = String::new();
-    io::stdin().lock().read_line(&mut input).ok();
-    let trimmed = input.trim_end_matches(&['\r', '\n'][..]);
-    BolideString::new(trimmed)
+    read_input(prompt)
 }
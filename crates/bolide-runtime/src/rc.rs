@@ -23,6 +23,7 @@ pub enum TypeTag {
     Closure = 6,   // 闭包
     Future = 7,    // Future/Promise
     Dict = 8,      // 字典/哈希表
+    WeakDict = 9,  // 键为弱引用的字典
 }
 
 
@@ -55,6 +56,7 @@ pub struct RcHeader {
     /// 标志位
     /// - bit 0: 是否已标记为待释放
     /// - bit 1: 是否被 spawn move
+    /// - bit 2: 是否为驻留字符串（跨线程共享，引用计数用原子操作）
     pub flags: Cell<u8>,
     /// 填充对齐
     _padding: [u8; 6],
@@ -64,6 +66,9 @@ pub struct RcHeader {
 pub mod flags {
     pub const DROPPING: u8 = 0b0000_0001;
     pub const MOVED: u8 = 0b0000_0010;
+    pub const INTERNED: u8 = 0b0000_0100;
+    /// 字典由 default_dict() 创建：读取不存在的容器值时插入空容器
+    pub const DEFAULT_EMPTY: u8 = 0b0000_1000;
 }
 
 impl RcHeader {
@@ -0,0 +1,325 @@
+//! 采样分析（`bolide run --sample 997hz`）
+//!
+//! 采样线程按给定频率读取每个线程的影子调用栈（见 `trace.rs`，需要以调试模式编译），
+//! 相同的调用栈合并计数。停止时（程序结束、`runtime_error` 退出）以及收到 SIGUSR1 时，
+//! 把结果按 flamegraph 工具使用的折叠格式（每行 `外层;内层;最内层 次数`）写入文件，
+//! 并在标准错误打印自身时间最多的函数。
+//!
+//! 采样按墙钟时间：阻塞在 `join`、channel 上的线程同样计入当时所在的函数；调用栈为空的线程
+//! （空闲的工作线程、没有以调试模式编译的代码）不计入。协程在工作线程上运行，记在它自己的调用栈里；
+//! 线程池的工作线程记在正在执行的任务里。运行时函数的时间算作调用它的 Bolide 函数的自身时间。
+//! 不开启采样时没有采样线程，也不生成任何额外的调用。
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Once};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::trace::for_each_thread_trace;
+
+/// 频率的上限：再高时读取调用栈本身的开销就不可忽略了
+pub const MAX_SAMPLE_HZ: u32 = 10_000;
+/// 停止时打印的自身时间最多的函数个数
+const SUMMARY_TOP: usize = 10;
+
+/// 采样结果：每种调用栈（最外层在前）和采到的次数
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SampleProfile {
+    pub stacks: Vec<(Vec<String>, u64)>,
+}
+
+impl SampleProfile {
+    /// 采到的调用栈总数
+    pub fn total(&self) -> u64 {
+        self.stacks.iter().map(|(_, count)| count).sum()
+    }
+
+    /// 折叠格式：每种调用栈一行，函数名用 `;` 连接，最后是次数
+    pub fn folded(&self) -> String {
+        let mut out = String::new();
+        for (stack, count) in &self.stacks {
+            out.push_str(&stack.join(";"));
+            out.push(' ');
+            out.push_str(&count.to_string());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// 解析折叠格式，空行忽略
+    pub fn parse_folded(text: &str) -> Result<Self, String> {
+        let mut stacks = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (stack, count) = line.rsplit_once(' ')
+                .ok_or_else(|| format!("line {}: expected `stack count`", i + 1))?;
+            let count = count.parse::<u64>()
+                .map_err(|_| format!("line {}: invalid sample count '{}'", i + 1, count))?;
+            if stack.is_empty() {
+                return Err(format!("line {}: empty stack", i + 1));
+            }
+            stacks.push((stack.split(';').map(str::to_string).collect(), count));
+        }
+        Ok(SampleProfile { stacks })
+    }
+
+    /// 每个函数作为最内层帧被采到的次数，从多到少
+    pub fn self_time(&self) -> Vec<(String, u64)> {
+        let mut counts: HashMap<&str, u64> = HashMap::new();
+        for (stack, count) in &self.stacks {
+            if let Some(leaf) = stack.last() {
+                *counts.entry(leaf.as_str()).or_default() += count;
+            }
+        }
+        let mut counts: Vec<(String, u64)> = counts.into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// 自身时间最多的 `top` 个函数，每行 `百分比 次数 函数名`
+    pub fn summary(&self, top: usize) -> String {
+        let total = self.total().max(1) as f64;
+        let mut out = String::from("self time:\n");
+        for (name, count) in self.self_time().into_iter().take(top) {
+            out.push_str(&format!("  {:5.1}%  {:>8}  {}\n", count as f64 * 100.0 / total, count, name));
+        }
+        out
+    }
+}
+
+/// 采样线程累计的结果；函数名第一次出现时复制下来，之后按名字的地址查找
+#[derive(Default)]
+struct Aggregate {
+    names: Vec<String>,
+    ids: HashMap<(usize, usize), u32>,
+    stacks: HashMap<Vec<u32>, u64>,
+}
+
+impl Aggregate {
+    fn name_id(&mut self, name: *const u8, len: usize) -> u32 {
+        let names = &mut self.names;
+        *self.ids.entry((name as usize, len)).or_insert_with(|| {
+            let bytes = unsafe { std::slice::from_raw_parts(name, len) };
+            names.push(String::from_utf8_lossy(bytes).into_owned());
+            (names.len() - 1) as u32
+        })
+    }
+
+    /// 读取每个线程的调用栈，各计一次
+    fn sample(&mut self) {
+        let mut frames = Vec::new();
+        let mut ids = Vec::new();
+        for_each_thread_trace(|trace| {
+            trace.snapshot(&mut frames);
+            if frames.is_empty() {
+                return;
+            }
+            ids.clear();
+            for &(name, len) in &frames {
+                let id = self.name_id(name, len);
+                ids.push(id);
+            }
+            match self.stacks.get_mut(ids.as_slice()) {
+                Some(count) => *count += 1,
+                None => {
+                    self.stacks.insert(ids.clone(), 1);
+                }
+            }
+        });
+    }
+
+    fn profile(&self) -> SampleProfile {
+        let mut stacks: Vec<(Vec<String>, u64)> = self.stacks.iter()
+            .map(|(ids, &count)| (ids.iter().map(|&id| self.names[id as usize].clone()).collect(), count))
+            .collect();
+        stacks.sort();
+        SampleProfile { stacks }
+    }
+}
+
+struct Shared {
+    hz: u32,
+    path: PathBuf,
+    stop: AtomicBool,
+    aggregate: Mutex<Aggregate>,
+}
+
+impl Shared {
+    fn profile(&self) -> SampleProfile {
+        self.aggregate.lock().unwrap_or_else(|e| e.into_inner()).profile()
+    }
+
+    /// 写入折叠格式的文件，并在标准错误打印摘要
+    fn report(&self) -> SampleProfile {
+        let profile = self.profile();
+        match std::fs::write(&self.path, profile.folded()) {
+            Ok(()) => eprintln!(
+                "sampled {} stacks at {} Hz; folded stacks written to {}",
+                profile.total(), self.hz, self.path.display()
+            ),
+            Err(e) => eprintln!("warning: failed to write {}: {}", self.path.display(), e),
+        }
+        eprint!("{}", profile.summary(SUMMARY_TOP));
+        profile
+    }
+}
+
+struct Sampler {
+    shared: Arc<Shared>,
+    thread: JoinHandle<()>,
+}
+
+static SAMPLER: Mutex<Option<Sampler>> = Mutex::new(None);
+/// SIGUSR1 到达后置位，采样线程在下一次采样时写出当前结果
+static DUMP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+fn sampler_loop(shared: Arc<Shared>) {
+    let period = Duration::from_secs_f64(1.0 / shared.hz as f64);
+    let mut next = Instant::now() + period;
+    while !shared.stop.load(Ordering::Acquire) {
+        let now = Instant::now();
+        if now < next {
+            thread::park_timeout(next - now);
+            continue;
+        }
+        // 落后超过一个周期（线程被挂起）时不补采
+        next = (next + period).max(now);
+        shared.aggregate.lock().unwrap_or_else(|e| e.into_inner()).sample();
+        if DUMP_REQUESTED.swap(false, Ordering::AcqRel) {
+            shared.report();
+        }
+    }
+}
+
+/// 启动采样线程，每秒采样 `hz` 次，停止时把结果写入 `path`
+///
+/// 已经在采样或 `hz` 不在 1..=`MAX_SAMPLE_HZ` 内时返回 false。
+pub fn start_sampler(hz: u32, path: PathBuf) -> bool {
+    if hz == 0 || hz > MAX_SAMPLE_HZ {
+        return false;
+    }
+    let mut sampler = SAMPLER.lock().unwrap_or_else(|e| e.into_inner());
+    if sampler.is_some() {
+        return false;
+    }
+    let shared = Arc::new(Shared { hz, path, stop: AtomicBool::new(false), aggregate: Mutex::default() });
+    let worker = Arc::clone(&shared);
+    let Ok(thread) = thread::Builder::new().name("bolide-sampler".to_string()).spawn(move || sampler_loop(worker)) else {
+        return false;
+    };
+    *sampler = Some(Sampler { shared, thread });
+
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        install_dump_signal();
+        register_exit_report();
+    });
+    true
+}
+
+/// 是否有采样线程在运行
+pub fn sampler_running() -> bool {
+    SAMPLER.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+/// 停止采样线程，写出结果并返回；没有在采样时返回 None
+pub fn stop_sampler() -> Option<SampleProfile> {
+    let sampler = SAMPLER.lock().unwrap_or_else(|e| e.into_inner()).take()?;
+    sampler.shared.stop.store(true, Ordering::Release);
+    sampler.thread.thread().unpark();
+    let _ = sampler.thread.join();
+    Some(sampler.shared.report())
+}
+
+/// `runtime_error`、`exit` 等直接结束进程时也写出结果
+fn register_exit_report() {
+    extern "C" fn report_at_exit() {
+        stop_sampler();
+    }
+    extern "C" {
+        fn atexit(callback: extern "C" fn()) -> i32;
+    }
+    unsafe { atexit(report_at_exit); }
+}
+
+/// 收到 SIGUSR1 时写出到目前为止的结果，采样继续
+#[cfg(unix)]
+fn install_dump_signal() {
+    extern "C" fn on_dump_signal(_signum: i32) {
+        DUMP_REQUESTED.store(true, Ordering::Release);
+    }
+    extern "C" {
+        fn signal(signum: i32, handler: usize) -> usize;
+    }
+    const SIGUSR1: i32 = if cfg!(target_os = "linux") { 10 } else { 30 };
+    unsafe { signal(SIGUSR1, on_dump_signal as extern "C" fn(i32) as usize); }
+}
+
+#[cfg(not(unix))]
+fn install_dump_signal() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bolide_trace_pop, bolide_trace_push};
+
+    #[test]
+    fn test_folded_round_trip() {
+        let profile = SampleProfile {
+            stacks: vec![
+                (vec!["<main>".to_string(), "run".to_string()], 3),
+                (vec!["<main>".to_string(), "run".to_string(), "hot".to_string()], 40),
+                (vec!["worker".to_string(), "hot".to_string()], 7),
+            ],
+        };
+        let text = profile.folded();
+        assert_eq!(text, "<main>;run 3\n<main>;run;hot 40\nworker;hot 7\n");
+        assert_eq!(SampleProfile::parse_folded(&text).unwrap(), profile);
+        assert_eq!(profile.total(), 50);
+        assert_eq!(profile.self_time(), [("hot".to_string(), 47), ("run".to_string(), 3)]);
+        assert!(profile.summary(1).contains(" 94.0%        47  hot\n"), "{}", profile.summary(1));
+
+        assert!(SampleProfile::parse_folded("a;b\n").unwrap_err().contains("line 1"));
+        assert!(SampleProfile::parse_folded("a;b x\n").unwrap_err().contains("invalid sample count 'x'"));
+    }
+
+    #[test]
+    fn test_sampler_reads_other_threads() {
+        let path = std::env::temp_dir().join(format!("bolide_sample_{}.folded", std::process::id()));
+        assert!(!start_sampler(0, path.clone()));
+        assert!(start_sampler(1000, path.clone()));
+        assert!(sampler_running());
+        assert!(!start_sampler(1000, path.clone()));
+
+        let busy = thread::spawn(|| {
+            let (outer, leaf) = ("outer", "spin");
+            bolide_trace_push(outer.as_ptr(), outer.len(), 1);
+            bolide_trace_push(leaf.as_ptr(), leaf.len(), 2);
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_millis(100) {
+                std::hint::spin_loop();
+            }
+            bolide_trace_pop();
+            bolide_trace_pop();
+        });
+        busy.join().unwrap();
+
+        let profile = stop_sampler().unwrap();
+        assert!(!sampler_running());
+        let spin = profile.stacks.iter()
+            .find(|(stack, _)| stack == &["outer", "spin"])
+            .map(|(_, count)| *count)
+            .unwrap_or(0);
+        assert!(spin >= 20, "{:?}", profile);
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(SampleProfile::parse_folded(&written).unwrap(), profile);
+        let _ = std::fs::remove_file(&path);
+        assert!(stop_sampler().is_none());
+    }
+}
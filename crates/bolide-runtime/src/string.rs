@@ -7,15 +7,46 @@
 
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
-use std::cell::{Cell, RefCell};
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, AtomicU32, Ordering};
+use std::sync::{Mutex, OnceLock};
 
-thread_local! {
-    // String interner for literals (stores raw pointers with Strong RC=1 owned by interner)
-    static STRING_LITERALS: RefCell<HashMap<String, *mut BolideString>> = RefCell::new(HashMap::new());
+/// 驻留表的分片数
+const INTERN_SHARDS: usize = 16;
+
+type InternShard = Mutex<HashMap<Box<str>, usize>>;
+
+/// 字符串驻留表：内容 -> 规范实例地址
+///
+/// 全进程共享，按内容的哈希分成 `INTERN_SHARDS` 片，各片一把锁，多个线程驻留不同内容时互不阻塞。
+/// 表为每个实例持有一个引用，驻留字符串在 `bolide_string_intern_clear()` 之前不会释放；
+/// 驻留字符串会被多个线程同时持有，引用计数改用原子操作（见 `flags::INTERNED`）。
+static STRING_INTERNER: OnceLock<[InternShard; INTERN_SHARDS]> = OnceLock::new();
+
+fn interner() -> &'static [InternShard; INTERN_SHARDS] {
+    STRING_INTERNER.get_or_init(|| std::array::from_fn(|_| Mutex::new(HashMap::new())))
+}
+
+/// 内容所在的分片
+///
+/// 只看长度和末尾最多 8 个字节（`"user-42"` 这类键变化的部分），常数时间，
+/// 避免在表本身的哈希之外再完整哈希一遍。
+fn intern_shard(s: &str) -> &'static InternShard {
+    let bytes = s.as_bytes();
+    let mut tail = [0u8; 8];
+    let n = bytes.len().min(8);
+    tail[..n].copy_from_slice(&bytes[bytes.len() - n..]);
+    let hash = (u64::from_le_bytes(tail) ^ bytes.len() as u64).wrapping_mul(0x9e3779b97f4a7c15);
+    &interner()[(hash >> 60) as usize % INTERN_SHARDS]
 }
 
 use crate::rc::{TypeTag, flags};
+use crate::output::outln;
+
+// Debug: 跟踪 String 分配和释放（驻留的字面量不会释放，计入 leak）
+static STRING_ALLOC_COUNT: AtomicI64 = AtomicI64::new(0);
+static STRING_FREE_COUNT: AtomicI64 = AtomicI64::new(0);
 
 /// RC 对象头（与 rc.rs 中保持一致）
 #[repr(C)]
@@ -51,9 +82,28 @@ pub struct BolideString {
 
 impl BolideString {
     /// 创建新字符串（strong_count = 1）
+    ///
+    /// 内容以 NUL 结尾，s 中含 NUL 时截断到第一个 NUL 并记录 `ERROR_VALUE`。
     pub fn new(s: &str) -> *mut Self {
-        let c_string = CString::new(s).unwrap();
-        let len = s.len();
+        Self::from_c_string(CString::new(s))
+    }
+
+    /// 用已经拼好的内容创建字符串，沿用 `buf` 的缓冲而不复制
+    ///
+    /// `buf` 的容量比长度多至少一个字节时不重新分配（结尾的 NUL 放在这里）。NUL 的处理同 `new`。
+    pub fn from_string(buf: String) -> *mut Self {
+        Self::from_c_string(CString::new(buf))
+    }
+
+    fn from_c_string(c_string: Result<CString, std::ffi::NulError>) -> *mut Self {
+        let c_string = c_string.unwrap_or_else(|e| {
+            let offset = e.nul_position();
+            crate::set_error(crate::ERROR_VALUE, &format!("string contains a NUL character at byte offset {}", offset));
+            let mut bytes = e.into_vec();
+            bytes.truncate(offset);
+            CString::new(bytes).unwrap_or_default()
+        });
+        let len = c_string.as_bytes().len();
         let string = Self {
             header: RcHeader {
                 strong_count: Cell::new(1),
@@ -66,6 +116,7 @@ impl BolideString {
             len,
             capacity: len + 1,
         };
+        STRING_ALLOC_COUNT.fetch_add(1, Ordering::SeqCst);
         Box::into_raw(Box::new(string))
     }
 
@@ -80,6 +131,14 @@ impl BolideString {
         }
     }
 
+    /// 获取字符串字节（不扫描结尾的 NUL）
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.data.is_null() {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.data as *const u8, self.len) }
+    }
+
     pub fn len(&self) -> usize {
         self.len
     }
@@ -93,6 +152,10 @@ impl BolideString {
     /// 增加引用计数
     #[inline]
     pub fn retain(&self) {
+        if self.is_interned() {
+            self.atomic_count().fetch_add(1, Ordering::Relaxed);
+            return;
+        }
         let count = self.header.strong_count.get();
         debug_assert!(count > 0, "retain on dropped string");
         self.header.strong_count.set(count + 1);
@@ -101,6 +164,9 @@ impl BolideString {
     /// 减少引用计数，返回是否应该释放
     #[inline]
     pub fn release(&self) -> bool {
+        if self.is_interned() {
+            return self.atomic_count().fetch_sub(1, Ordering::AcqRel) == 1;
+        }
         let count = self.header.strong_count.get();
         debug_assert!(count > 0, "release underflow");
         self.header.strong_count.set(count - 1);
@@ -110,9 +176,24 @@ impl BolideString {
     /// 获取引用计数
     #[inline]
     pub fn ref_count(&self) -> u32 {
+        if self.is_interned() {
+            return self.atomic_count().load(Ordering::Relaxed);
+        }
         self.header.strong_count.get()
     }
 
+    /// 是否为驻留字符串
+    #[inline]
+    pub fn is_interned(&self) -> bool {
+        self.header.flags.get() & flags::INTERNED != 0
+    }
+
+    /// 驻留字符串的引用计数（与 `Cell<u32>` 布局相同）
+    #[inline]
+    fn atomic_count(&self) -> &AtomicU32 {
+        unsafe { AtomicU32::from_ptr(self.header.strong_count.as_ptr()) }
+    }
+
     /// 检查是否已被 move
     #[inline]
     pub fn is_moved(&self) -> bool {
@@ -154,28 +235,86 @@ pub extern "C" fn bolide_string_from_slice(s: *const i8, len: usize) -> *mut Bol
     BolideString::new(s)
 }
 
-/// 获取字符串字面量（带 Interning）
+/// 返回内容为 `s` 的驻留实例（新引用）
+pub fn intern_str(s: &str) -> *mut BolideString {
+    let mut table = intern_shard(s).lock().unwrap();
+    if let Some(&addr) = table.get(s) {
+        let ptr = addr as *mut BolideString;
+        unsafe { (*ptr).retain(); }
+        return ptr;
+    }
+    // 发布到表之前打上标记，之后所有引用计数操作都是原子的
+    let ptr = BolideString::new(s);
+    unsafe {
+        let header = &(*ptr).header;
+        header.flags.set(header.flags.get() | flags::INTERNED);
+        // 表持有初始引用，调用方再得到一个
+        (*ptr).retain();
+    }
+    table.insert(s.into(), ptr as usize);
+    ptr
+}
+
+/// 查找内容为 `s` 的驻留实例（不增加引用、不插入）
+pub fn lookup_interned(s: &str) -> Option<*mut BolideString> {
+    intern_shard(s).lock().unwrap().get(s).map(|&addr| addr as *mut BolideString)
+}
+
+/// 驻留表中的条目数
+#[no_mangle]
+pub extern "C" fn bolide_string_intern_stats() -> i64 {
+    interner().iter().map(|shard| shard.lock().unwrap().len() as i64).sum()
+}
+
+/// 移除只被驻留表引用的条目并释放实例，返回移除的个数
+///
+/// 别处仍持有的实例保留在表中，所以同一内容不会同时存在两个驻留实例。
+#[no_mangle]
+pub extern "C" fn bolide_string_intern_clear() -> i64 {
+    intern_clear_matching(|_| true)
+}
+
+/// 同 `bolide_string_intern_clear`，只考虑内容满足 `filter` 的条目
+fn intern_clear_matching(filter: impl Fn(&str) -> bool) -> i64 {
+    let mut removed = 0;
+    for shard in interner() {
+        let mut table = shard.lock().unwrap();
+        // 持有分片锁时没有线程能从表中取得新引用；计数为 1 说明只有表持有它
+        table.retain(|key, &mut addr| {
+            let ptr = addr as *mut BolideString;
+            if !filter(key) || unsafe { (*ptr).atomic_count().load(Ordering::Acquire) } != 1 {
+                return true;
+            }
+            bolide_string_release(ptr);
+            removed += 1;
+            false
+        });
+    }
+    removed
+}
+
+/// 获取字符串字面量（驻留）
 #[no_mangle]
 pub extern "C" fn bolide_string_literal(s: *const i8, len: usize) -> *mut BolideString {
     let slice = unsafe { std::slice::from_raw_parts(s as *const u8, len) };
-    let s_str = std::str::from_utf8(slice).unwrap_or("");
-    
-    STRING_LITERALS.with(|interner| {
-        let mut map = interner.borrow_mut();
-        if let Some(&ptr) = map.get(s_str) {
-             // Found. Retain and return a NEW reference.
-             unsafe { (*ptr).retain(); }
-             ptr
-        } else {
-             // Not found. Create (RC=1).
-             let ptr = BolideString::new(s_str);
-             // Interner keeps the original RC=1.
-             // We retain to give caller their own reference (RC=2).
-             unsafe { (*ptr).retain(); }
-             map.insert(s_str.to_string(), ptr);
-             ptr
-        }
-    })
+    intern_str(std::str::from_utf8(slice).unwrap_or(""))
+}
+
+/// `intern(s)`：返回与 s 内容相同的驻留实例（新引用）
+///
+/// 驻留实例由表持有，直到 `intern_clear()`；相同内容总是得到同一个指针，
+/// 所以驻留过的字符串之间比较、作为字典键查找都只需比较地址。
+#[no_mangle]
+pub extern "C" fn bolide_string_intern(s: *const BolideString) -> *mut BolideString {
+    if s.is_null() {
+        return intern_str("");
+    }
+    let s = unsafe { &*s };
+    if s.is_interned() {
+        s.retain();
+        return s as *const BolideString as *mut BolideString;
+    }
+    intern_str(s.as_str())
 }
 
 /// 增加引用计数（浅拷贝）
@@ -201,6 +340,7 @@ pub extern "C" fn bolide_string_release(s: *mut BolideString) {
             // 引用计数归零，释放数据
             (*s).drop_data();
             let _ = Box::from_raw(s);
+            STRING_FREE_COUNT.fetch_add(1, Ordering::SeqCst);
         }
     }
 }
@@ -221,13 +361,66 @@ pub extern "C" fn bolide_string_free(s: *mut BolideString) {
     bolide_string_release(s);
 }
 
-/// 获取字符串长度
+/// `len(s)`：字符（Unicode 标量）个数，不是字节数
 #[no_mangle]
 pub extern "C" fn bolide_string_len(s: *const BolideString) -> usize {
     if s.is_null() {
         return 0;
     }
-    unsafe { (*s).len() }
+    let s = unsafe { &*s };
+    let bytes = s.as_bytes();
+    if bytes.is_ascii() {
+        return bytes.len();
+    }
+    s.as_str().chars().count()
+}
+
+/// `s[index]`：第 index 个字符（Unicode 标量）的码点
+///
+/// 负下标从末尾数起（-1 为最后一个字符），越界返回 -1。纯 ASCII 字符串直接按字节取。
+#[no_mangle]
+pub extern "C" fn bolide_string_char_at(s: *const BolideString, index: i64) -> i64 {
+    if s.is_null() {
+        return -1;
+    }
+    let s = unsafe { &*s };
+    let bytes = s.as_bytes();
+    if bytes.is_ascii() {
+        let len = bytes.len() as i64;
+        let i = if index < 0 { index + len } else { index };
+        return if (0..len).contains(&i) { bytes[i as usize] as i64 } else { -1 };
+    }
+    let ch = if index >= 0 {
+        s.as_str().chars().nth(index as usize)
+    } else {
+        s.as_str().chars().rev().nth((-(index + 1)) as usize)
+    };
+    ch.map_or(-1, |c| c as i64)
+}
+
+/// `s[start:end]`：第 start 到 end（不含）个字符组成的新字符串（ref_count = 1）
+///
+/// 与列表切片一致：负下标从末尾数起，越界的边界收拢到两端，start 不小于 end 时为空串。
+/// 省略的 start 传 0，省略的 end 传 i64::MAX。
+#[no_mangle]
+pub extern "C" fn bolide_string_slice(s: *const BolideString, start: i64, end: i64) -> *mut BolideString {
+    if s.is_null() {
+        return BolideString::new("");
+    }
+    let s = unsafe { &*s };
+    let text = s.as_str();
+    let ascii = text.is_ascii();
+    let len = if ascii { text.len() } else { text.chars().count() } as i64;
+    let clamp = |i: i64| if i < 0 { len.saturating_add(i).max(0) } else { i.min(len) } as usize;
+    let (start, end) = (clamp(start), clamp(end));
+    if start >= end {
+        return BolideString::new("");
+    }
+    if ascii {
+        return BolideString::new(&text[start..end]);
+    }
+    let byte_at = |n: usize| text.char_indices().nth(n).map_or(text.len(), |(i, _)| i);
+    BolideString::new(&text[byte_at(start)..byte_at(end)])
 }
 
 /// 获取引用计数
@@ -248,10 +441,10 @@ pub extern "C" fn bolide_string_concat(a: *const BolideString, b: *const BolideS
     BolideString::new(&result)
 }
 
-/// 字符串比较
+/// 字符串比较：同一实例直接相等，长度不同直接不等，最后才比较字节
 #[no_mangle]
 pub extern "C" fn bolide_string_eq(a: *const BolideString, b: *const BolideString) -> i64 {
-    if a.is_null() && b.is_null() {
+    if std::ptr::eq(a, b) {
         return 1;
     }
     if a.is_null() || b.is_null() {
@@ -259,7 +452,239 @@ pub extern "C" fn bolide_string_eq(a: *const BolideString, b: *const BolideStrin
     }
     let a = unsafe { &*a };
     let b = unsafe { &*b };
-    if a.as_str() == b.as_str() { 1 } else { 0 }
+    if a.len() != b.len() {
+        return 0;
+    }
+    // 两个不同的驻留实例内容一定不同
+    if a.is_interned() && b.is_interned() {
+        return 0;
+    }
+    if a.as_bytes() == b.as_bytes() { 1 } else { 0 }
+}
+
+/// 字符串排序比较（按字节序），返回 -1 / 0 / 1
+#[no_mangle]
+pub extern "C" fn bolide_string_cmp(a: *const BolideString, b: *const BolideString) -> i64 {
+    let a_str = if a.is_null() { "" } else { unsafe { (*a).as_str() } };
+    let b_str = if b.is_null() { "" } else { unsafe { (*b).as_str() } };
+    a_str.cmp(b_str) as i64
+}
+
+/// 字符串参数的文本（空指针为空串）
+///
+/// # Safety
+/// `s` 为空指针或有效的 `BolideString`，返回的引用使用期间不被释放
+unsafe fn str_or_empty<'a>(s: *const BolideString) -> &'a str {
+    s.as_ref().map_or("", |s| s.as_str())
+}
+
+/// `s.split(sep)`：按 sep 拆分为新的 `list<str>`，与 Python 的 `str.split` 一致
+///
+/// 空串得到 `[""]`，首尾的分隔符产生空串元素；sep 为空指针（`s.split()`）时按空白拆分并丢弃空串。
+/// sep 为空串是运行时错误。
+///
+/// # Safety
+/// `s`、`sep` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_string_split(s: *const BolideString, sep: *const BolideString) -> *mut crate::BolideList {
+    let text = str_or_empty(s);
+    let list = crate::BolideList::new(crate::ElementType::String);
+    let push = |part: &str| (*list).push_owned(BolideString::new(part) as i64);
+    if sep.is_null() {
+        text.split_whitespace().for_each(push);
+    } else {
+        let sep = str_or_empty(sep);
+        if sep.is_empty() {
+            crate::runtime_error("split: empty separator");
+        }
+        text.split(sep).for_each(push);
+    }
+    list
+}
+
+/// `s.trim()`：去掉首尾空白的新字符串
+///
+/// # Safety
+/// `s` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_string_trim(s: *const BolideString) -> *mut BolideString {
+    BolideString::new(str_or_empty(s).trim())
+}
+
+/// `s.to_upper()`：转为大写的新字符串（按 Unicode 规则）
+///
+/// # Safety
+/// `s` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_string_upper(s: *const BolideString) -> *mut BolideString {
+    BolideString::new(&str_or_empty(s).to_uppercase())
+}
+
+/// `s.to_lower()`：转为小写的新字符串（按 Unicode 规则）
+///
+/// # Safety
+/// `s` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_string_lower(s: *const BolideString) -> *mut BolideString {
+    BolideString::new(&str_or_empty(s).to_lowercase())
+}
+
+/// `s.contains(needle)`：是否包含子串，空串总是包含
+///
+/// # Safety
+/// `s`、`needle` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_string_contains(s: *const BolideString, needle: *const BolideString) -> i64 {
+    str_or_empty(s).contains(str_or_empty(needle)) as i64
+}
+
+/// `s.replace(from, to)`：替换所有 from 的新字符串
+///
+/// from 为空串时与 Python 一致，在每个字符前后插入 to。
+///
+/// # Safety
+/// `s`、`from`、`to` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_string_replace(s: *const BolideString, from: *const BolideString, to: *const BolideString) -> *mut BolideString {
+    BolideString::new(&str_or_empty(s).replace(str_or_empty(from), str_or_empty(to)))
+}
+
+/// 文本编码（字节序列用元素为 0..=255 的 `list<int>` 表示）
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Utf8,
+    Utf16Le,
+    Latin1,
+}
+
+fn parse_encoding(name: &str) -> Result<Encoding, String> {
+    match name.to_ascii_lowercase().replace('_', "-").as_str() {
+        "utf-8" | "utf8" => Ok(Encoding::Utf8),
+        "utf-16le" | "utf16le" | "utf-16-le" => Ok(Encoding::Utf16Le),
+        "latin-1" | "latin1" | "iso-8859-1" => Ok(Encoding::Latin1),
+        _ => Err(format!("unknown encoding '{}' (supported: utf-8, utf-16le, latin-1)", name)),
+    }
+}
+
+fn encode_str(text: &str, encoding: Encoding) -> Result<Vec<u8>, String> {
+    match encoding {
+        Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+        Encoding::Utf16Le => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+        Encoding::Latin1 => text.chars().enumerate()
+            .map(|(i, c)| u8::try_from(c as u32)
+                .map_err(|_| format!("encode: character U+{:04X} at index {} is not representable in latin-1", c as u32, i)))
+            .collect(),
+    }
+}
+
+/// 解码字节序列；strict 模式下的错误给出第一个非法序列的字节偏移，lossy 模式替换为 U+FFFD
+///
+/// 字符串以 NUL 结尾，解码出 U+0000 在两种模式下都是错误。
+fn decode_bytes(bytes: &[u8], encoding: Encoding, lossy: bool) -> Result<String, String> {
+    let nul = match encoding {
+        Encoding::Utf16Le => bytes.chunks_exact(2).position(|pair| pair == [0, 0]).map(|i| 2 * i),
+        Encoding::Utf8 | Encoding::Latin1 => bytes.iter().position(|&b| b == 0),
+    };
+    if let Some(offset) = nul {
+        return Err(format!("decode: NUL character at byte offset {} cannot be stored in a string", offset));
+    }
+    match encoding {
+        Encoding::Utf8 if lossy => Ok(String::from_utf8_lossy(bytes).into_owned()),
+        Encoding::Utf8 => std::str::from_utf8(bytes)
+            .map(str::to_string)
+            .map_err(|e| format!("decode: invalid utf-8 sequence at byte offset {}", e.valid_up_to())),
+        Encoding::Utf16Le => {
+            let units = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]]));
+            let mut text = String::with_capacity(bytes.len() / 2);
+            let mut offset = 0;
+            for c in char::decode_utf16(units) {
+                match c {
+                    Ok(c) => {
+                        text.push(c);
+                        offset += 2 * c.len_utf16();
+                    }
+                    Err(_) if lossy => {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        offset += 2;
+                    }
+                    Err(e) => return Err(format!("decode: unpaired utf-16 surrogate 0x{:04X} at byte offset {}", e.unpaired_surrogate(), offset)),
+                }
+            }
+            if !bytes.len().is_multiple_of(2) {
+                if !lossy {
+                    return Err(format!("decode: truncated utf-16le code unit at byte offset {}", bytes.len() - 1));
+                }
+                text.push(char::REPLACEMENT_CHARACTER);
+            }
+            Ok(text)
+        }
+        Encoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+    }
+}
+
+/// 取出 `list<int>` 中的字节，元素不在 0..=255 内是运行时错误
+///
+/// # Safety
+/// `list` 为空指针或有效的 `BolideList`
+unsafe fn list_bytes(list: *const crate::BolideList) -> Vec<u8> {
+    if list.is_null() {
+        return Vec::new();
+    }
+    (*list).as_slice().iter().enumerate()
+        .map(|(i, &v)| u8::try_from(v).unwrap_or_else(|_| {
+            crate::runtime_error(&format!("decode: element {} at index {} is not a byte (0-255)", v, i))
+        }))
+        .collect()
+}
+
+/// `s.encode(encoding)`：编码为新的字节列表（`list<int>`）
+///
+/// 支持 utf-8、utf-16le、latin-1；latin-1 无法表示的字符和未知编码是运行时错误。
+///
+/// # Safety
+/// `s`、`encoding` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_string_encode(s: *const BolideString, encoding: *const BolideString) -> *mut crate::BolideList {
+    let bytes = parse_encoding(str_or_empty(encoding))
+        .and_then(|enc| encode_str(str_or_empty(s), enc))
+        .unwrap_or_else(|e| crate::runtime_error(&e));
+    let list = crate::BolideList::new(crate::ElementType::Int);
+    for b in bytes {
+        (*list).push(b as i64);
+    }
+    list
+}
+
+/// `b.decode(encoding, mode)`：把字节列表解码为新字符串
+///
+/// mode 为 "strict"（默认，mode 为空指针）或 "lossy"：strict 遇到非法序列是运行时错误，
+/// 错误信息给出字节偏移；lossy 替换为 U+FFFD，与 Rust 的 `String::from_utf8_lossy` 一致。
+///
+/// # Safety
+/// `bytes` 为空指针或有效的 `BolideList`，`encoding`、`mode` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_bytes_decode(bytes: *const crate::BolideList, encoding: *const BolideString, mode: *const BolideString) -> *mut BolideString {
+    let mode = if mode.is_null() { "strict" } else { str_or_empty(mode) };
+    let lossy = match mode {
+        "strict" => false,
+        "lossy" => true,
+        other => crate::runtime_error(&format!("decode: unknown mode '{}' (expected \"strict\" or \"lossy\")", other)),
+    };
+    let text = parse_encoding(str_or_empty(encoding))
+        .and_then(|enc| decode_bytes(&list_bytes(bytes), enc, lossy))
+        .unwrap_or_else(|e| crate::runtime_error(&e));
+    BolideString::new(&text)
+}
+
+/// `b.decode_latin1()`：按 latin-1 解码，每个字节对应一个字符（只有 NUL 字节会出错）
+///
+/// # Safety
+/// `bytes` 为空指针或有效的 `BolideList`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_bytes_decode_latin1(bytes: *const crate::BolideList) -> *mut BolideString {
+    let text = decode_bytes(&list_bytes(bytes), Encoding::Latin1, false)
+        .unwrap_or_else(|e| crate::runtime_error(&e));
+    BolideString::new(&text)
 }
 
 /// 检查是否已被 move
@@ -281,10 +706,29 @@ pub extern "C" fn bolide_string_mark_moved(s: *mut BolideString) {
     }
 }
 
+// ==================== Debug Stats ====================
+
+/// 打印 String 内存统计
+#[no_mangle]
+pub extern "C" fn bolide_string_debug_stats() {
+    let (alloc, free) = string_alloc_stats();
+    outln!("[String Stats] alloc: {}, free: {}, leak: {}", alloc, free, alloc - free);
+}
+
+/// 到目前为止创建和释放的字符串个数（泄漏检查用）
+pub fn string_alloc_stats() -> (i64, i64) {
+    (STRING_ALLOC_COUNT.load(Ordering::SeqCst), STRING_FREE_COUNT.load(Ordering::SeqCst))
+}
+
 // ==================== 类型转换 ====================
 
 // --- 转为字符串 ---
 
+/// `str(x)` 和 f-string 里 float 的文本；编译器折叠常量时用同一个函数，结果与运行时一致
+pub fn format_float(value: f64) -> String {
+    value.to_string()
+}
+
 #[no_mangle]
 pub extern "C" fn bolide_string_from_int(value: i64) -> *mut BolideString {
     BolideString::new(&value.to_string())
@@ -292,7 +736,7 @@ pub extern "C" fn bolide_string_from_int(value: i64) -> *mut BolideString {
 
 #[no_mangle]
 pub extern "C" fn bolide_string_from_float(value: f64) -> *mut BolideString {
-    BolideString::new(&value.to_string())
+    BolideString::new(&format_float(value))
 }
 
 #[no_mangle]
@@ -321,26 +765,195 @@ pub extern "C" fn bolide_string_from_decimal(ptr: *const crate::BolideDecimal) -
     BolideString::new(&decimal.to_string())
 }
 
+// --- f-string 拼接 ---
+
+/// f-string 的拼接缓冲：各段按 `print` 的格式依次追加，`finish` 时只分配一次结果字符串
+pub struct BolideStringBuilder {
+    buf: String,
+}
+
+/// 创建拼接缓冲，`capacity` 是预估的字节数（字面量片段的总长）
+#[no_mangle]
+pub extern "C" fn bolide_string_builder_new(capacity: i64) -> *mut BolideStringBuilder {
+    Box::into_raw(Box::new(BolideStringBuilder { buf: String::with_capacity(capacity.max(0) as usize) }))
+}
+
+/// 向缓冲追加一段文本；缓冲为空指针时忽略
+fn builder_push(builder: *mut BolideStringBuilder, text: &str) {
+    if let Some(builder) = unsafe { builder.as_mut() } {
+        builder.buf.push_str(text);
+    }
+}
+
+/// 追加字符串（借用，不改变引用计数）
+#[no_mangle]
+pub extern "C" fn bolide_string_builder_append_str(builder: *mut BolideStringBuilder, s: *const BolideString) {
+    if !s.is_null() {
+        builder_push(builder, unsafe { (*s).as_str() });
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_string_builder_append_int(builder: *mut BolideStringBuilder, value: i64) {
+    builder_push(builder, &value.to_string());
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_string_builder_append_float(builder: *mut BolideStringBuilder, value: f64) {
+    builder_push(builder, &format_float(value));
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_string_builder_append_bool(builder: *mut BolideStringBuilder, value: i64) {
+    builder_push(builder, if value != 0 { "true" } else { "false" });
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_string_builder_append_bigint(builder: *mut BolideStringBuilder, ptr: *const crate::BolideBigInt) {
+    match unsafe { ptr.as_ref() } {
+        Some(bigint) => builder_push(builder, &bigint.to_string()),
+        None => builder_push(builder, "null"),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_string_builder_append_decimal(builder: *mut BolideStringBuilder, ptr: *const crate::BolideDecimal) {
+    match unsafe { ptr.as_ref() } {
+        Some(decimal) => builder_push(builder, &decimal.to_string()),
+        None => builder_push(builder, "null"),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_string_builder_append_dynamic(builder: *mut BolideStringBuilder, ptr: *const crate::BolideDynamic) {
+    match unsafe { ptr.as_ref() } {
+        Some(value) => builder_push(builder, &value.to_string_repr()),
+        None => builder_push(builder, "null"),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_string_builder_append_list(builder: *mut BolideStringBuilder, list: *const crate::BolideList) {
+    builder_push(builder, &crate::list::format_list(list));
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_string_builder_append_dict(builder: *mut BolideStringBuilder, dict: *const crate::BolideDict) {
+    builder_push(builder, &crate::dict::format_dict(dict));
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_string_builder_append_tuple(builder: *mut BolideStringBuilder, tuple: *const crate::BolideTuple) {
+    builder_push(builder, &crate::tuple::format_tuple(tuple));
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_string_builder_append_packed_list(builder: *mut BolideStringBuilder, list: *const crate::BolidePackedList) {
+    builder_push(builder, &crate::packed_list::format_packed_list(list));
+}
+
+/// 结束拼接：释放缓冲并返回新字符串（strong_count = 1）
+#[no_mangle]
+pub extern "C" fn bolide_string_builder_finish(builder: *mut BolideStringBuilder) -> *mut BolideString {
+    if builder.is_null() {
+        return BolideString::new("");
+    }
+    let builder = unsafe { Box::from_raw(builder) };
+    BolideString::from_string(builder.buf)
+}
+
 // --- 从字符串转换 ---
 
-/// 字符串转 int
+/// 字符串转 int，无法解析时记录 `ERROR_VALUE` 并返回 0
 #[no_mangle]
 pub extern "C" fn bolide_string_to_int(s: *const BolideString) -> i64 {
     if s.is_null() {
         return 0;
     }
     let str_val = unsafe { (*s).as_str() };
-    str_val.trim().parse::<i64>().unwrap_or(0)
+    str_val.trim().parse::<i64>().unwrap_or_else(|_| {
+        crate::set_error(crate::ERROR_VALUE, &format!("int(): invalid literal '{}'", str_val));
+        0
+    })
 }
 
-/// 字符串转 float
+/// 字符串转 float，无法解析时记录 `ERROR_VALUE` 并返回 0.0
 #[no_mangle]
 pub extern "C" fn bolide_string_to_float(s: *const BolideString) -> f64 {
     if s.is_null() {
         return 0.0;
     }
     let str_val = unsafe { (*s).as_str() };
-    str_val.trim().parse::<f64>().unwrap_or(0.0)
+    str_val.trim().parse::<f64>().unwrap_or_else(|_| {
+        crate::set_error(crate::ERROR_VALUE, &format!("float(): invalid literal '{}'", str_val));
+        0.0
+    })
+}
+
+/// 错误信息里引用的输入最多保留的字符数
+const PARSE_ECHO_CHARS: usize = 32;
+
+/// 错误信息里引用的输入：过长时截断并加省略号
+fn parse_echo(text: &str) -> String {
+    match text.char_indices().nth(PARSE_ECHO_CHARS) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// `parse_int(s, radix)`：按 `radix`（2–36）解析整数
+///
+/// 忽略首尾空白，允许一个 `+` / `-` 号，其余字符都必须是该进制的数字。
+/// 失败时记录错误并返回 0：格式不对是 `ERROR_VALUE`，超出 int 范围是 `ERROR_OVERFLOW`。
+#[no_mangle]
+pub extern "C" fn bolide_string_parse_int(s: *const BolideString, radix: i64) -> i64 {
+    use std::num::IntErrorKind;
+
+    if !(2..=36).contains(&radix) {
+        crate::set_error(crate::ERROR_VALUE, &format!("parse_int(): radix must be between 2 and 36, got {}", radix));
+        return 0;
+    }
+    let text = if s.is_null() { "" } else { unsafe { (*s).as_str() } };
+    let digits = text.trim();
+    i64::from_str_radix(digits, radix as u32).unwrap_or_else(|e| {
+        // 溢出在扫描到非法字符之前就会报告，非法字符优先
+        let unsigned = digits.strip_prefix(['+', '-']).unwrap_or(digits);
+        let all_digits = unsigned.chars().all(|c| c.is_digit(radix as u32));
+        let (code, reason) = match e.kind() {
+            IntErrorKind::Empty => (crate::ERROR_VALUE, "empty string".to_string()),
+            IntErrorKind::PosOverflow | IntErrorKind::NegOverflow if all_digits => {
+                (crate::ERROR_OVERFLOW, "out of range for int, use bigint() for larger values".to_string())
+            }
+            _ if radix == 10 => (crate::ERROR_VALUE, "invalid digit".to_string()),
+            _ => (crate::ERROR_VALUE, format!("invalid digit for radix {}", radix)),
+        };
+        crate::set_error(code, &format!("parse_int(): '{}': {}", parse_echo(text), reason));
+        0
+    })
+}
+
+/// `parse_float(s)`：解析浮点数，格式同 Rust 的 `f64` 字面量（也接受 `inf` / `nan`）
+///
+/// 忽略首尾空白。失败时记录错误并返回 0.0：格式不对是 `ERROR_VALUE`，
+/// 有限的写法超出 float 范围（如 `1e400`）是 `ERROR_OVERFLOW`。
+#[no_mangle]
+pub extern "C" fn bolide_string_parse_float(s: *const BolideString) -> f64 {
+    let text = if s.is_null() { "" } else { unsafe { (*s).as_str() } };
+    let digits = text.trim();
+    let fail = |code: i64, reason: &str| {
+        crate::set_error(code, &format!("parse_float(): '{}': {}", parse_echo(text), reason));
+        0.0
+    };
+    if digits.is_empty() {
+        return fail(crate::ERROR_VALUE, "empty string");
+    }
+    match digits.parse::<f64>() {
+        Ok(value) if value.is_infinite() && !digits.to_ascii_lowercase().contains("inf") => {
+            fail(crate::ERROR_OVERFLOW, "out of range for float")
+        }
+        Ok(value) => value,
+        Err(_) => fail(crate::ERROR_VALUE, "invalid float literal"),
+    }
 }
 
 /// 从 Rust String 创建 BolideString（内部使用）
@@ -396,6 +1009,218 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_string_builder() {
+        let name = BolideString::new("bo");
+        let builder = bolide_string_builder_new(8);
+        bolide_string_builder_append_str(builder, name);
+        bolide_string_builder_append_int(builder, -3);
+        bolide_string_builder_append_float(builder, 2.5);
+        bolide_string_builder_append_float(builder, 1.0);
+        bolide_string_builder_append_bool(builder, 1);
+        bolide_string_builder_append_list(builder, std::ptr::null());
+        let result = bolide_string_builder_finish(builder);
+        unsafe {
+            assert_eq!((*result).as_str(), "bo-32.51true[]");
+            bolide_string_release(result);
+            bolide_string_release(name);
+        }
+    }
+
+    #[test]
+    fn test_string_char_at() {
+        let ascii = BolideString::new("hello");
+        assert_eq!(bolide_string_char_at(ascii, 0), 'h' as i64);
+        assert_eq!(bolide_string_char_at(ascii, 4), 'o' as i64);
+        assert_eq!(bolide_string_char_at(ascii, -1), 'o' as i64);
+        assert_eq!(bolide_string_char_at(ascii, -5), 'h' as i64);
+        assert_eq!(bolide_string_char_at(ascii, 5), -1);
+        assert_eq!(bolide_string_char_at(ascii, -6), -1);
+        assert_eq!(bolide_string_char_at(ascii, i64::MIN), -1);
+
+        // 多字节 UTF-8 按字符而不是字节计数
+        let utf8 = BolideString::new("aé中😀");
+        assert_eq!(bolide_string_char_at(utf8, 1), 'é' as i64);
+        assert_eq!(bolide_string_char_at(utf8, 2), '中' as i64);
+        assert_eq!(bolide_string_char_at(utf8, 3), '😀' as i64);
+        assert_eq!(bolide_string_char_at(utf8, 4), -1);
+        assert_eq!(bolide_string_char_at(utf8, -1), '😀' as i64);
+        assert_eq!(bolide_string_char_at(utf8, -4), 'a' as i64);
+        assert_eq!(bolide_string_char_at(utf8, -5), -1);
+
+        let empty = BolideString::new("");
+        assert_eq!(bolide_string_len(ascii), 5);
+        assert_eq!(bolide_string_len(utf8), 4);
+        assert_eq!(bolide_string_len(empty), 0);
+        assert_eq!(bolide_string_char_at(empty, 0), -1);
+        assert_eq!(bolide_string_char_at(empty, -1), -1);
+        assert_eq!(bolide_string_char_at(std::ptr::null(), 0), -1);
+
+        for s in [ascii, utf8, empty] {
+            bolide_string_release(s);
+        }
+    }
+
+    #[test]
+    fn test_string_slice() {
+        let ascii = BolideString::new("hello");
+        let utf8 = BolideString::new("aé中😀");
+        let slice = |s: *const BolideString, start: i64, end: i64| {
+            let out = bolide_string_slice(s, start, end);
+            let text = unsafe { (*out).as_str().to_string() };
+            bolide_string_release(out);
+            text
+        };
+        assert_eq!(slice(ascii, 1, 3), "el");
+        assert_eq!(slice(ascii, 0, 2), "he");
+        assert_eq!(slice(ascii, -3, i64::MAX), "llo");
+        assert_eq!(slice(ascii, -100, 100), "hello");
+        assert_eq!(slice(ascii, 3, 1), "");
+        assert_eq!(slice(ascii, i64::MIN, -4), "h");
+        assert_eq!(slice(utf8, 1, 3), "é中");
+        assert_eq!(slice(utf8, -2, i64::MAX), "中😀");
+        assert_eq!(slice(utf8, 2, -1), "中");
+        bolide_string_release(ascii);
+        bolide_string_release(utf8);
+    }
+
+    fn split_items(text: &str, sep: Option<&str>) -> Vec<String> {
+        let s = BolideString::new(text);
+        let sep = sep.map_or(std::ptr::null_mut(), BolideString::new);
+        let list = unsafe { bolide_string_split(s, sep) };
+        let items = unsafe {
+            (*list).as_slice().iter()
+                .map(|&p| (*(p as *const BolideString)).as_str().to_string())
+                .collect()
+        };
+        crate::bolide_list_release(list);
+        bolide_string_release(s);
+        if !sep.is_null() {
+            bolide_string_release(sep);
+        }
+        items
+    }
+
+    #[test]
+    fn test_string_split_like_python() {
+        assert_eq!(split_items("a,b,c", Some(",")), ["a", "b", "c"]);
+        assert_eq!(split_items("", Some(",")), [""]);
+        assert_eq!(split_items("a,b,", Some(",")), ["a", "b", ""]);
+        assert_eq!(split_items(",a,,b", Some(",")), ["", "a", "", "b"]);
+        assert_eq!(split_items("a::b", Some("::")), ["a", "b"]);
+        assert_eq!(split_items("  one  two\tthree\n", None), ["one", "two", "three"]);
+        assert!(split_items("   ", None).is_empty());
+    }
+
+    #[test]
+    fn test_string_transforms() {
+        let text = |s: *mut BolideString| {
+            let out = unsafe { (*s).as_str().to_string() };
+            bolide_string_release(s);
+            out
+        };
+        let s = BolideString::new("  Héllo World \n");
+        let from = BolideString::new("o");
+        let to = BolideString::new("0");
+        let empty = BolideString::new("");
+        let dash = BolideString::new("-");
+        unsafe {
+            assert_eq!(text(bolide_string_trim(s)), "Héllo World");
+            assert_eq!(text(bolide_string_upper(s)), "  HÉLLO WORLD \n");
+            assert_eq!(text(bolide_string_lower(s)), "  héllo world \n");
+            assert_eq!(text(bolide_string_replace(s, from, to)), "  Héll0 W0rld \n");
+            assert_eq!(text(bolide_string_replace(from, empty, dash)), "-o-");
+            assert_eq!(bolide_string_contains(s, from), 1);
+            assert_eq!(bolide_string_contains(s, dash), 0);
+            assert_eq!(bolide_string_contains(s, empty), 1);
+        }
+        for p in [s, from, to, empty, dash] {
+            assert_eq!(unsafe { (*p).ref_count() }, 1);
+            bolide_string_release(p);
+        }
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        // 含 BMP 以外的字符（U+1F600 在 utf-16 中是代理对）
+        let text = "héllo, 世界 😀";
+        for (name, len) in [("utf-8", 19), ("UTF-16LE", 24)] {
+            let enc = parse_encoding(name).unwrap();
+            let bytes = encode_str(text, enc).unwrap();
+            assert_eq!(bytes.len(), len, "{}", name);
+            assert_eq!(decode_bytes(&bytes, enc, false).unwrap(), text);
+        }
+        assert_eq!(encode_str("😀", Encoding::Utf16Le).unwrap(), [0x3D, 0xD8, 0x00, 0xDE]);
+
+        let latin = "café ÿ";
+        let bytes = encode_str(latin, Encoding::Latin1).unwrap();
+        assert_eq!(bytes, [b'c', b'a', b'f', 0xE9, b' ', 0xFF]);
+        assert_eq!(decode_bytes(&bytes, Encoding::Latin1, false).unwrap(), latin);
+        assert_eq!(encode_str("a😀", Encoding::Latin1).unwrap_err(),
+            "encode: character U+1F600 at index 1 is not representable in latin-1");
+
+        assert_eq!(parse_encoding("latin1"), Ok(Encoding::Latin1));
+        assert_eq!(parse_encoding("utf_8"), Ok(Encoding::Utf8));
+        assert_eq!(parse_encoding("ascii").unwrap_err(),
+            "unknown encoding 'ascii' (supported: utf-8, utf-16le, latin-1)");
+    }
+
+    #[test]
+    fn test_decode_strict_error_offsets() {
+        let utf8 = |b: &[u8]| decode_bytes(b, Encoding::Utf8, false).unwrap_err();
+        assert_eq!(utf8(b"ab\xFFcd"), "decode: invalid utf-8 sequence at byte offset 2");
+        // 截断的多字节序列：偏移指向序列开头
+        assert_eq!(utf8("é世".as_bytes().split_last().unwrap().1), "decode: invalid utf-8 sequence at byte offset 2");
+        assert_eq!(utf8(b"\xC0\x80"), "decode: invalid utf-8 sequence at byte offset 0");
+
+        let utf16 = |b: &[u8]| decode_bytes(b, Encoding::Utf16Le, false).unwrap_err();
+        // "a" 后面跟一个孤立的低代理
+        assert_eq!(utf16(&[0x61, 0x00, 0x00, 0xDC, 0x62, 0x00]), "decode: unpaired utf-16 surrogate 0xDC00 at byte offset 2");
+        // 代理对之后的孤立高代理
+        assert_eq!(utf16(&[0x3D, 0xD8, 0x00, 0xDE, 0x3D, 0xD8]), "decode: unpaired utf-16 surrogate 0xD83D at byte offset 4");
+        assert_eq!(utf16(&[0x61, 0x00, 0x62]), "decode: truncated utf-16le code unit at byte offset 2");
+        assert_eq!(utf8(b"a\0"), "decode: NUL character at byte offset 1 cannot be stored in a string");
+        assert_eq!(utf16(&[0x00, 0x01, 0x00, 0x00]), "decode: NUL character at byte offset 2 cannot be stored in a string");
+    }
+
+    #[test]
+    fn test_decode_lossy_matches_from_utf8_lossy() {
+        let mut corrupted = "ok 世界 😀 end".as_bytes().to_vec();
+        corrupted[4] = 0xFF;            // 破坏“世”的第二个字节
+        corrupted.truncate(corrupted.len() - 1);
+        corrupted.push(0xF0);           // 末尾截断的四字节序列
+        corrupted.insert(0, 0x80);      // 开头孤立的续字节
+        assert_eq!(decode_bytes(&corrupted, Encoding::Utf8, true).unwrap(), String::from_utf8_lossy(&corrupted));
+
+        assert_eq!(decode_bytes(&[0x61, 0x00, 0x00, 0xDC, 0x62], Encoding::Utf16Le, true).unwrap(), "a\u{FFFD}\u{FFFD}");
+    }
+
+    #[test]
+    fn test_encode_decode_ffi() {
+        let s = BolideString::new("Grüße 😀");
+        let enc = BolideString::new("utf-16le");
+        let lossy = BolideString::new("lossy");
+        let utf8 = BolideString::new("utf-8");
+        let (back, back_lossy, latin) = unsafe {
+            let bytes = bolide_string_encode(s, enc);
+            assert_eq!((*bytes).len(), 16);
+            let back = bolide_bytes_decode(bytes, enc, std::ptr::null());
+            assert_eq!((*back).as_str(), "Grüße 😀");
+            let back_lossy = bolide_bytes_decode(bytes, enc, lossy);
+            assert_eq!((*back_lossy).as_str(), "Grüße 😀");
+            crate::bolide_list_release(bytes);
+            let bytes = bolide_string_encode(s, utf8);
+            let latin = bolide_bytes_decode_latin1(bytes);
+            assert_eq!((*latin).as_str(), "GrÃ¼Ã\u{9f}e ð\u{9f}\u{98}\u{80}");
+            crate::bolide_list_release(bytes);
+            (back, back_lossy, latin)
+        };
+        bolide_string_release(utf8);
+        for p in [s, enc, lossy, back, back_lossy, latin] {
+            bolide_string_release(p);
+        }
+    }
+
     #[test]
     fn test_string_concat() {
         let a = BolideString::new("hello ");
@@ -411,6 +1236,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_string_cmp() {
+        let a = BolideString::new("apple");
+        let b = BolideString::new("banana");
+        let c = BolideString::new("apple");
+        assert_eq!(bolide_string_cmp(a, b), -1);
+        assert_eq!(bolide_string_cmp(b, a), 1);
+        assert_eq!(bolide_string_cmp(a, c), 0);
+        unsafe {
+            bolide_string_release(a);
+            bolide_string_release(b);
+            bolide_string_release(c);
+        }
+    }
+
     #[test]
     fn test_string_move_flag() {
         let s = BolideString::new("movable");
@@ -421,4 +1261,208 @@ mod tests {
             bolide_string_release(s);
         }
     }
+
+    #[test]
+    fn test_string_eq_fast_paths() {
+        let a = BolideString::new("same");
+        let b = BolideString::new("same");
+        let c = BolideString::new("longer");
+        assert_eq!(bolide_string_eq(a, a), 1);
+        assert_eq!(bolide_string_eq(a, b), 1);
+        assert_eq!(bolide_string_eq(a, c), 0);
+        assert_eq!(bolide_string_eq(std::ptr::null(), std::ptr::null()), 1);
+        assert_eq!(bolide_string_eq(a, std::ptr::null()), 0);
+        bolide_string_release(a);
+        bolide_string_release(b);
+        bolide_string_release(c);
+    }
+
+    #[test]
+    fn test_intern_returns_canonical_instance() {
+        let runtime = bolide_string_concat(BolideString::new("inter"), BolideString::new("ned-key"));
+        let literal = bolide_string_literal("interned-key".as_ptr() as *const i8, 12);
+        let interned = bolide_string_intern(runtime);
+        assert!(std::ptr::eq(interned, literal));
+        assert!(std::ptr::eq(bolide_string_intern(interned), literal));
+        assert_eq!(lookup_interned("interned-key"), Some(literal));
+        assert_eq!(lookup_interned("never-interned-key"), None);
+
+        // 调用方释放自己的引用后，驻留实例仍然有效
+        bolide_string_release(interned);
+        bolide_string_release(interned);
+        bolide_string_release(literal);
+        unsafe {
+            assert_eq!((*literal).as_str(), "interned-key");
+            assert!((*literal).ref_count() >= 1);
+        }
+        bolide_string_release(runtime);
+    }
+
+    #[test]
+    fn test_intern_shared_across_threads() {
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    let mut addr = 0;
+                    for _ in 0..1000 {
+                        let s = intern_str("shared-literal");
+                        addr = s as usize;
+                        bolide_string_retain(s);
+                        bolide_string_release(s);
+                        bolide_string_release(s);
+                    }
+                    addr
+                })
+            })
+            .collect();
+        let addrs: Vec<usize> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert!(addrs.iter().all(|&a| a == addrs[0]));
+        // 只剩驻留表持有的引用
+        assert_eq!(unsafe { (*(addrs[0] as *mut BolideString)).ref_count() }, 1);
+    }
+
+    #[test]
+    fn test_intern_clear_drops_unreferenced() {
+        let is_ours = |s: &str| s.starts_with("clear-test-");
+        let held = intern_str("clear-test-held");
+        let dropped = intern_str("clear-test-dropped");
+        bolide_string_release(dropped);
+        assert!(bolide_string_intern_stats() >= 2);
+
+        // 只有表持有的条目被移除，别处持有的保留
+        assert_eq!(intern_clear_matching(is_ours), 1);
+        assert_eq!(lookup_interned("clear-test-dropped"), None);
+        assert_eq!(lookup_interned("clear-test-held"), Some(held));
+        assert_eq!(intern_clear_matching(is_ours), 0);
+
+        // 再次驻留得到新实例；原来的实例释放后同样可以清理
+        let again = intern_str("clear-test-dropped");
+        assert!(unsafe { (*again).is_interned() });
+        bolide_string_release(again);
+        bolide_string_release(held);
+        assert_eq!(intern_clear_matching(is_ours), 2);
+        assert_eq!(lookup_interned("clear-test-held"), None);
+    }
+
+    /// 驻留表的吞吐：分片表单线程不慢于单锁表，多线程时各线程的内容落在不同分片
+    #[test]
+    fn test_intern_sharded_throughput() {
+        use std::time::Instant;
+
+        const ROUNDS: usize = 20;
+        const THREADS: usize = 4;
+        let keys: Vec<String> = (0..512).map(|i| format!("bench-intern-{}", i)).collect();
+        for key in &keys {
+            bolide_string_release(intern_str(key));
+        }
+
+        // 对照：改动之前的单锁表，同样查找并 retain / release
+        let single: Mutex<HashMap<Box<str>, usize>> = Mutex::new(
+            keys.iter().map(|k| (k.as_str().into(), lookup_interned(k).unwrap() as usize)).collect()
+        );
+        let single_lookup = |key: &str| {
+            let ptr = *single.lock().unwrap().get(key).unwrap() as *mut BolideString;
+            bolide_string_retain(ptr);
+            bolide_string_release(ptr);
+        };
+        let sharded_lookup = |key: &str| bolide_string_release(intern_str(key));
+
+        // 单线程：取三次中最快的一次，减少调度噪声
+        let time = |lookup: &dyn Fn(&str)| {
+            (0..3).map(|_| {
+                let started = Instant::now();
+                for _ in 0..ROUNDS {
+                    keys.iter().for_each(|k| lookup(k));
+                }
+                started.elapsed()
+            }).min().unwrap()
+        };
+        let single_time = time(&single_lookup);
+        let sharded_time = time(&sharded_lookup);
+
+        // 多线程：每个线程驻留各自的一段内容
+        let threaded = |lookup: &(dyn Fn(&str) + Sync)| {
+            let started = Instant::now();
+            std::thread::scope(|scope| {
+                for chunk in keys.chunks(keys.len() / THREADS) {
+                    scope.spawn(move || {
+                        for _ in 0..ROUNDS * THREADS {
+                            chunk.iter().for_each(|k| lookup(k));
+                        }
+                    });
+                }
+            });
+            started.elapsed()
+        };
+        let single_threaded = threaded(&single_lookup);
+        let sharded_threaded = threaded(&sharded_lookup);
+        println!(
+            "intern x{}: single lock {:?} / sharded {:?}; {} threads: single lock {:?} / sharded {:?}",
+            ROUNDS * keys.len(), single_time, sharded_time, THREADS, single_threaded, sharded_threaded
+        );
+
+        // 其他测试并行运行，留足余量；只防止单线程路径明显变慢
+        assert!(sharded_time < single_time * 3, "{:?} vs {:?}", sharded_time, single_time);
+        for key in &keys {
+            assert_eq!(unsafe { (*lookup_interned(key).unwrap()).ref_count() }, 1);
+        }
+        assert_eq!(intern_clear_matching(|s| s.starts_with("bench-intern-")), keys.len() as i64);
+    }
+
+    fn parse_int_str(text: &str, radix: i64) -> (i64, Option<(i64, String)>) {
+        crate::bolide_error_clear();
+        let s = BolideString::new(text);
+        let value = bolide_string_parse_int(s, radix);
+        bolide_string_release(s);
+        (value, crate::last_error())
+    }
+
+    #[test]
+    fn test_parse_int_radix_and_errors() {
+        assert_eq!(parse_int_str("  -42\n", 10), (-42, None));
+        assert_eq!(parse_int_str("+ff", 16), (255, None));
+        assert_eq!(parse_int_str("-1010", 2), (-10, None));
+        assert_eq!(parse_int_str("zz", 36), (1295, None));
+        assert_eq!(parse_int_str("9223372036854775807", 10), (i64::MAX, None));
+        assert_eq!(parse_int_str("-9223372036854775808", 10), (i64::MIN, None));
+
+        let (value, err) = parse_int_str("12abc", 10);
+        assert_eq!(value, 0);
+        assert_eq!(err, Some((crate::ERROR_VALUE, "parse_int(): '12abc': invalid digit".to_string())));
+        assert_eq!(parse_int_str("102", 2).1.unwrap().1, "parse_int(): '102': invalid digit for radix 2");
+        assert_eq!(parse_int_str("   ", 10).1.unwrap().1, "parse_int(): '   ': empty string");
+        assert_eq!(parse_int_str("-", 10).1.unwrap().0, crate::ERROR_VALUE);
+        assert_eq!(parse_int_str("0x10", 16).1.unwrap().0, crate::ERROR_VALUE);
+        assert_eq!(parse_int_str("10", 37).1.unwrap().1, "parse_int(): radix must be between 2 and 36, got 37");
+
+        let (value, err) = parse_int_str("9223372036854775808", 10);
+        assert_eq!(value, 0);
+        assert_eq!(err, Some((crate::ERROR_OVERFLOW,
+            "parse_int(): '9223372036854775808': out of range for int, use bigint() for larger values".to_string())));
+
+        // 过长的输入在错误信息里截断
+        let long = "1".repeat(40) + "x";
+        assert_eq!(parse_int_str(&long, 10).1.unwrap().1,
+            format!("parse_int(): '{}...': invalid digit", "1".repeat(32)));
+        crate::bolide_error_clear();
+    }
+
+    #[test]
+    fn test_parse_float_errors() {
+        let parse = |text: &str| {
+            crate::bolide_error_clear();
+            let s = BolideString::new(text);
+            let value = bolide_string_parse_float(s);
+            bolide_string_release(s);
+            (value, crate::last_error())
+        };
+        assert_eq!(parse(" 2.5e3 "), (2500.0, None));
+        assert_eq!(parse("-.5"), (-0.5, None));
+        assert_eq!(parse("-inf"), (f64::NEG_INFINITY, None));
+        assert_eq!(parse("1.5x"), (0.0, Some((crate::ERROR_VALUE, "parse_float(): '1.5x': invalid float literal".to_string()))));
+        assert_eq!(parse("").1.unwrap().1, "parse_float(): '': empty string");
+        assert_eq!(parse("1e400"), (0.0, Some((crate::ERROR_OVERFLOW, "parse_float(): '1e400': out of range for float".to_string()))));
+        crate::bolide_error_clear();
+    }
 }
+
@@ -0,0 +1,192 @@
+//! 测试块的断言：`assert(cond)` / `assert_eq(a, b)`
+//!
+//! 断言失败不中止程序：失败信息连同当前测试名记下来，`bolide test` 在每个测试结束后取出汇总
+//! （`test_begin` / `test_end`）。不在测试中（`bolide run`、编译出的程序）时失败信息直接打印到标准错误。
+//!
+//! 失败记录是进程级的，测试里启动的线程中的断言也记在当前测试名下。
+
+use std::sync::Mutex;
+
+use crate::list::BolideList;
+use crate::string::BolideString;
+
+/// `assert_eq` 比较 float 的相对误差（两边绝对值都小于 1 时按绝对误差）
+pub const ASSERT_FLOAT_EPSILON: f64 = 1e-9;
+
+/// 一次失败的断言
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssertFailure {
+    /// 所在的测试块名
+    pub test: String,
+    pub message: String,
+}
+
+/// 正在运行的测试名和已记录的失败
+struct TestState {
+    current: Option<String>,
+    failures: Vec<AssertFailure>,
+}
+
+static TEST_STATE: Mutex<TestState> = Mutex::new(TestState { current: None, failures: Vec::new() });
+
+/// 开始记录名为 `name` 的测试的断言失败（清空之前的记录）
+pub fn test_begin(name: &str) {
+    let mut state = TEST_STATE.lock().unwrap_or_else(|e| e.into_inner());
+    state.current = Some(name.to_string());
+    state.failures.clear();
+}
+
+/// 结束当前测试，返回它的断言失败；之后的失败重新直接打印
+pub fn test_end() -> Vec<AssertFailure> {
+    let mut state = TEST_STATE.lock().unwrap_or_else(|e| e.into_inner());
+    state.current = None;
+    std::mem::take(&mut state.failures)
+}
+
+/// 记录一次失败；不在测试中时打印到标准错误
+fn record_failure(message: String) {
+    let mut state = TEST_STATE.lock().unwrap_or_else(|e| e.into_inner());
+    match state.current.clone() {
+        Some(test) => state.failures.push(AssertFailure { test, message }),
+        None => {
+            drop(state);
+            crate::output::flush_stdout();
+            eprintln!("Assertion failed: {}", message);
+        }
+    }
+}
+
+/// `assert_eq` 对 float 的比较
+fn floats_close(a: f64, b: f64) -> bool {
+    a == b || (a - b).abs() <= ASSERT_FLOAT_EPSILON * a.abs().max(b.abs()).max(1.0)
+}
+
+/// 字符串参数的文本（空指针为 `None`）
+///
+/// # Safety
+/// `s` 为空指针或有效的 `BolideString`，返回的引用使用期间不被释放
+unsafe fn str_of<'a>(s: *const BolideString) -> Option<&'a str> {
+    s.as_ref().map(|s| s.as_str())
+}
+
+// ==================== FFI 导出 ====================
+
+/// `assert(cond)` / `assert(cond, message)`：`message` 可以为空指针
+///
+/// # Safety
+/// `message` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_assert(cond: i64, message: *const BolideString) {
+    if cond != 0 {
+        return;
+    }
+    match str_of(message) {
+        Some(message) => record_failure(format!("assert failed: {}", message)),
+        None => record_failure("assert failed".to_string()),
+    }
+}
+
+/// `assert_eq(a, b)`：int / bool
+#[no_mangle]
+pub extern "C" fn bolide_assert_eq_int(a: i64, b: i64) {
+    if a != b {
+        record_failure(format!("assert_eq failed: left = {}, right = {}", a, b));
+    }
+}
+
+/// `assert_eq(a, b)`：float，允许 `ASSERT_FLOAT_EPSILON` 的相对误差
+#[no_mangle]
+pub extern "C" fn bolide_assert_eq_float(a: f64, b: f64) {
+    if !floats_close(a, b) {
+        record_failure(format!("assert_eq failed: left = {}, right = {}", a, b));
+    }
+}
+
+/// `assert_eq(a, b)`：str，按内容比较
+///
+/// # Safety
+/// `a`、`b` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_assert_eq_str(a: *const BolideString, b: *const BolideString) {
+    if crate::bolide_string_eq(a, b) == 0 {
+        record_failure(format!("assert_eq failed: left = {:?}, right = {:?}", str_of(a).unwrap_or(""), str_of(b).unwrap_or("")));
+    }
+}
+
+/// `assert_eq(a, b)`：列表，长度相同且元素逐个相等（嵌套列表比较内容）
+///
+/// # Safety
+/// `a`、`b` 为空指针或有效的 `BolideList`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_assert_eq_list(a: *const BolideList, b: *const BolideList) {
+    let equal = match (a.as_ref(), b.as_ref()) {
+        (Some(x), Some(y)) => std::ptr::eq(x, y) || x.contents_equal(y),
+        (x, y) => x.is_none() && y.is_none(),
+    };
+    if !equal {
+        record_failure(format!(
+            "assert_eq failed: left = {}, right = {}",
+            crate::list::format_list(a),
+            crate::list::format_list(b)
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::list::{bolide_list_new, bolide_list_push, bolide_list_release};
+
+    fn int_list(values: &[i64]) -> *mut BolideList {
+        let list = bolide_list_new(0);
+        for &v in values {
+            bolide_list_push(list, v);
+        }
+        list
+    }
+
+    #[test]
+    fn test_assertions_record_failures() {
+        let a = BolideString::new("abc");
+        let b = BolideString::new("abd");
+        let xs = int_list(&[1, 2, 3]);
+        let ys = int_list(&[1, 2, 3]);
+        let zs = int_list(&[1, 2]);
+
+        test_begin("sample");
+        unsafe {
+            bolide_assert(1, std::ptr::null());
+            bolide_assert_eq_int(3, 3);
+            bolide_assert_eq_float(0.1 + 0.2, 0.3);
+            bolide_assert_eq_str(a, a);
+            bolide_assert_eq_list(xs, ys);
+        }
+        assert!(test_end().is_empty());
+
+        test_begin("sample");
+        unsafe {
+            bolide_assert(0, std::ptr::null());
+            bolide_assert_eq_int(3, 4);
+            bolide_assert_eq_float(1.0, 1.001);
+            bolide_assert_eq_str(a, b);
+            bolide_assert_eq_list(xs, zs);
+        }
+        let messages: Vec<String> = test_end().into_iter()
+            .inspect(|f| assert_eq!(f.test, "sample"))
+            .map(|f| f.message)
+            .collect();
+        assert_eq!(messages, [
+            "assert failed",
+            "assert_eq failed: left = 3, right = 4",
+            "assert_eq failed: left = 1, right = 1.001",
+            "assert_eq failed: left = \"abc\", right = \"abd\"",
+            "assert_eq failed: left = [1, 2, 3], right = [1, 2]",
+        ]);
+
+        for list in [xs, ys, zs] {
+            bolide_list_release(list);
+        }
+        crate::bolide_string_release(a);
+        crate::bolide_string_release(b);
+    }
+}
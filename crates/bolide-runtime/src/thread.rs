@@ -4,16 +4,85 @@
 //! 使用 trampoline 方案，运行时只处理无参函数
 
 use std::sync::{Arc, Mutex, Condvar};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicUsize, Ordering};
 use std::thread::{self, JoinHandle};
 use std::collections::VecDeque;
 use std::os::raw::c_void;
+use std::time::{Duration, Instant};
+
+use crate::error::{run_task, task_error_message, TaskError};
+use crate::string::BolideString;
 
 /// 包装函数指针使其可跨线程发送
 #[derive(Clone, Copy)]
 struct SendFnPtr(*const c_void);
 unsafe impl Send for SendFnPtr {}
 
+// ==================== 存活线程计数 ====================
+
+/// 仍在运行的用户线程数（spawn、线程池外的 pool_spawn、协程；不含线程池工作线程）
+static LIVE_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// 存活线程计数守卫：spawn 前创建（避免线程还没启动就被当成已结束），
+/// 移入线程闭包，线程结束（包括 panic）时计数减一
+pub(crate) struct LiveThread(());
+
+impl LiveThread {
+    pub(crate) fn start() -> Self {
+        LIVE_THREADS.fetch_add(1, Ordering::SeqCst);
+        LiveThread(())
+    }
+}
+
+impl Drop for LiveThread {
+    fn drop(&mut self) {
+        LIVE_THREADS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// 仍在运行的用户线程数
+pub fn live_threads() -> usize {
+    LIVE_THREADS.load(Ordering::SeqCst)
+}
+
+/// 等待所有用户线程结束，超时返回 false
+pub fn wait_for_threads(timeout: Duration) -> bool {
+    // single 模式下主线程交出执行权，排队的协程才能跑完
+    crate::executor::release();
+    let deadline = Instant::now() + timeout;
+    while live_threads() > 0 {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(2));
+    }
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn bolide_live_threads() -> i64 {
+    live_threads() as i64
+}
+
+// ==================== 线程编号 ====================
+
+static NEXT_THREAD_ID: AtomicI64 = AtomicI64::new(1);
+
+thread_local! {
+    static THREAD_ID: std::cell::Cell<i64> = const { std::cell::Cell::new(0) };
+}
+
+/// 当前 OS 线程的编号：首次调用时按顺序分配，从 1 开始，进程内不复用
+#[no_mangle]
+pub extern "C" fn bolide_thread_id() -> i64 {
+    THREAD_ID.with(|id| {
+        if id.get() == 0 {
+            id.set(NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed));
+        }
+        id.get()
+    })
+}
+
 /// 线程结果联合体
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -29,10 +98,16 @@ unsafe impl Sync for ThreadResult {}
 /// 线程句柄
 #[repr(C)]
 pub struct BolideThreadHandle {
-    handle: Option<JoinHandle<ThreadResult>>,
+    handle: Option<JoinHandle<(ThreadResult, TaskError)>>,
     result: ThreadResult,
     has_result: bool,
     cancelled: Arc<AtomicBool>,
+    /// spawn 的函数名，报告任务错误时使用
+    name: String,
+    /// 任务结束时留下的错误，join 时报告
+    error: TaskError,
+    /// 已经 join 过：之后的 join 返回缓存的结果（指针结果见 `bolide_thread_join_ptr`）
+    consumed: AtomicBool,
 }
 
 unsafe impl Send for BolideThreadHandle {}
@@ -41,9 +116,7 @@ unsafe impl Sync for BolideThreadHandle {}
 /// 线程池
 pub struct BolideThreadPool {
     workers: Vec<Worker>,
-    sender: Arc<Mutex<VecDeque<Job>>>,
-    condvar: Arc<Condvar>,
-    shutdown: Arc<Mutex<bool>>,
+    ctx: PoolContext,
 }
 
 type Job = Box<dyn FnOnce() -> ThreadResult + Send + 'static>;
@@ -52,33 +125,109 @@ struct Worker {
     thread: Option<JoinHandle<()>>,
 }
 
+/// 线程池的任务队列，线程池、任务句柄和任务上下文共享
+#[derive(Clone)]
+pub(crate) struct PoolContext {
+    queue: Arc<Mutex<VecDeque<Job>>>,
+    condvar: Arc<Condvar>,
+    shutdown: Arc<Mutex<bool>>,
+}
+
+impl PoolContext {
+    fn same_pool(&self, other: &PoolContext) -> bool {
+        Arc::ptr_eq(&self.queue, &other.queue)
+    }
+
+    /// 入队；线程池已关闭且当前线程不是它的工作线程时交还任务，由调用方改用普通线程
+    ///
+    /// 工作线程在退出前会取完队列，所以工作线程自己提交的任务总会被执行。
+    fn submit(&self, job: Job) -> Result<(), Job> {
+        {
+            let mut queue = self.queue.lock().unwrap();
+            if *self.shutdown.lock().unwrap() && !is_worker_of(self) {
+                return Err(job);
+            }
+            queue.push_back(job);
+        }
+        self.condvar.notify_one();
+        Ok(())
+    }
+
+    fn try_pop(&self) -> Option<Job> {
+        self.queue.lock().unwrap().pop_front()
+    }
+}
+
+thread_local! {
+    /// 当前线程的线程池上下文栈：pool 块和线程池任务执行期间压栈，结束时恢复
+    static POOL_STACK: std::cell::RefCell<Vec<PoolContext>> = const { std::cell::RefCell::new(Vec::new()) };
+    /// 本线程所属的线程池（仅工作线程）
+    static WORKER_OF: std::cell::RefCell<Option<PoolContext>> = const { std::cell::RefCell::new(None) };
+}
+
+/// 当前线程的线程池上下文（spawn 协程时捕获，在协程线程里恢复）
+pub(crate) fn current_pool() -> Option<PoolContext> {
+    POOL_STACK.with(|stack| stack.borrow().last().cloned())
+}
+
+fn is_worker_of(ctx: &PoolContext) -> bool {
+    WORKER_OF.with(|w| w.borrow().as_ref().is_some_and(|own| own.same_pool(ctx)))
+}
+
+/// 线程池上下文作用域：进入时压栈，离开（包括 panic）时出栈
+pub(crate) struct PoolScope(bool);
+
+impl PoolScope {
+    pub(crate) fn enter(ctx: Option<PoolContext>) -> Self {
+        match ctx {
+            Some(ctx) => {
+                POOL_STACK.with(|stack| stack.borrow_mut().push(ctx));
+                PoolScope(true)
+            }
+            None => PoolScope(false),
+        }
+    }
+}
+
+impl Drop for PoolScope {
+    fn drop(&mut self) {
+        if self.0 {
+            POOL_STACK.with(|stack| stack.borrow_mut().pop());
+        }
+    }
+}
+
 impl BolideThreadPool {
     pub fn new(size: usize) -> Self {
-        let sender: Arc<Mutex<VecDeque<Job>>> = Arc::new(Mutex::new(VecDeque::new()));
-        let condvar = Arc::new(Condvar::new());
-        let shutdown = Arc::new(Mutex::new(false));
+        let ctx = PoolContext {
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+            condvar: Arc::new(Condvar::new()),
+            shutdown: Arc::new(Mutex::new(false)),
+        };
 
         let mut workers = Vec::with_capacity(size);
 
         for _ in 0..size {
-            let sender = Arc::clone(&sender);
-            let condvar = Arc::clone(&condvar);
-            let shutdown = Arc::clone(&shutdown);
+            let ctx = ctx.clone();
 
             let thread = thread::spawn(move || {
+                let _tls = crate::tls::ThreadExit;
+                WORKER_OF.with(|w| *w.borrow_mut() = Some(ctx.clone()));
                 loop {
                     let job = {
-                        let mut queue = sender.lock().unwrap();
+                        let mut queue = ctx.queue.lock().unwrap();
                         while queue.is_empty() {
-                            if *shutdown.lock().unwrap() {
+                            if *ctx.shutdown.lock().unwrap() {
                                 return;
                             }
-                            queue = condvar.wait(queue).unwrap();
+                            queue = ctx.condvar.wait(queue).unwrap();
                         }
                         queue.pop_front()
                     };
 
                     if let Some(job) = job {
+                        // 任务内的 spawn 留在本线程池
+                        let _scope = PoolScope::enter(Some(ctx.clone()));
                         job();
                     }
                 }
@@ -89,17 +238,12 @@ impl BolideThreadPool {
             });
         }
 
-        BolideThreadPool {
-            workers,
-            sender,
-            condvar,
-            shutdown,
-        }
+        BolideThreadPool { workers, ctx }
     }
 
     pub fn shutdown(&mut self) {
-        *self.shutdown.lock().unwrap() = true;
-        self.condvar.notify_all();
+        *self.ctx.shutdown.lock().unwrap() = true;
+        self.ctx.condvar.notify_all();
 
         for worker in &mut self.workers {
             if let Some(thread) = worker.thread.take() {
@@ -119,7 +263,15 @@ impl Drop for BolideThreadPool {
 #[repr(C)]
 pub struct BolidePoolHandle {
     result: Arc<Mutex<Option<ThreadResult>>>,
+    /// 任务结束时留下的错误，join 时报告
+    error: Arc<Mutex<TaskError>>,
     completed: Arc<(Mutex<bool>, Condvar)>,
+    /// 任务所在的线程池（回退为普通线程时为 None）
+    pool: Option<PoolContext>,
+    /// spawn 的函数名，报告任务错误时使用
+    name: String,
+    /// 已经 join 过，同 `BolideThreadHandle::consumed`
+    consumed: AtomicBool,
 }
 
 unsafe impl Send for BolidePoolHandle {}
@@ -133,9 +285,12 @@ pub extern "C" fn bolide_thread_spawn_int(func_ptr: extern "C" fn() -> i64) -> *
     let send_fn = SendFnPtr(func_ptr as *const c_void);
     let cancelled = Arc::new(AtomicBool::new(false));
 
+    let live = LiveThread::start();
     let handle = thread::spawn(move || {
+        let _live = live;
+        let _tls = crate::tls::ThreadExit;
         let f: extern "C" fn() -> i64 = unsafe { std::mem::transmute(send_fn) };
-        ThreadResult { int_val: f() }
+        run_task(|| ThreadResult { int_val: f() })
     });
 
     Box::into_raw(Box::new(BolideThreadHandle {
@@ -143,6 +298,9 @@ pub extern "C" fn bolide_thread_spawn_int(func_ptr: extern "C" fn() -> i64) -> *
         result: ThreadResult { int_val: 0 },
         has_result: false,
         cancelled,
+        name: String::new(),
+        error: None,
+        consumed: AtomicBool::new(false),
     }))
 }
 
@@ -152,9 +310,12 @@ pub extern "C" fn bolide_thread_spawn_float(func_ptr: extern "C" fn() -> f64) ->
     let send_fn = SendFnPtr(func_ptr as *const c_void);
     let cancelled = Arc::new(AtomicBool::new(false));
 
+    let live = LiveThread::start();
     let handle = thread::spawn(move || {
+        let _live = live;
+        let _tls = crate::tls::ThreadExit;
         let f: extern "C" fn() -> f64 = unsafe { std::mem::transmute(send_fn) };
-        ThreadResult { float_val: f() }
+        run_task(|| ThreadResult { float_val: f() })
     });
 
     Box::into_raw(Box::new(BolideThreadHandle {
@@ -162,6 +323,9 @@ pub extern "C" fn bolide_thread_spawn_float(func_ptr: extern "C" fn() -> f64) ->
         result: ThreadResult { float_val: 0.0 },
         has_result: false,
         cancelled,
+        name: String::new(),
+        error: None,
+        consumed: AtomicBool::new(false),
     }))
 }
 
@@ -171,9 +335,12 @@ pub extern "C" fn bolide_thread_spawn_ptr(func_ptr: extern "C" fn() -> *mut c_vo
     let send_fn = SendFnPtr(func_ptr as *const c_void);
     let cancelled = Arc::new(AtomicBool::new(false));
 
+    let live = LiveThread::start();
     let handle = thread::spawn(move || {
+        let _live = live;
+        let _tls = crate::tls::ThreadExit;
         let f: extern "C" fn() -> *mut c_void = unsafe { std::mem::transmute(send_fn) };
-        ThreadResult { ptr_val: f() }
+        run_task(|| ThreadResult { ptr_val: f() })
     });
 
     Box::into_raw(Box::new(BolideThreadHandle {
@@ -181,6 +348,9 @@ pub extern "C" fn bolide_thread_spawn_ptr(func_ptr: extern "C" fn() -> *mut c_vo
         result: ThreadResult { ptr_val: std::ptr::null_mut() },
         has_result: false,
         cancelled,
+        name: String::new(),
+        error: None,
+        consumed: AtomicBool::new(false),
     }))
 }
 
@@ -196,10 +366,13 @@ pub extern "C" fn bolide_thread_spawn_int_with_env(
     let env_addr = env as usize;
     let cancelled = Arc::new(AtomicBool::new(false));
 
+    let live = LiveThread::start();
     let handle = thread::spawn(move || {
+        let _live = live;
+        let _tls = crate::tls::ThreadExit;
         let f: extern "C" fn(*mut c_void) -> i64 = unsafe { std::mem::transmute(send_fn) };
         let env_ptr = env_addr as *mut c_void;
-        ThreadResult { int_val: f(env_ptr) }
+        run_task(|| ThreadResult { int_val: f(env_ptr) })
     });
 
     Box::into_raw(Box::new(BolideThreadHandle {
@@ -207,6 +380,9 @@ pub extern "C" fn bolide_thread_spawn_int_with_env(
         result: ThreadResult { int_val: 0 },
         has_result: false,
         cancelled,
+        name: String::new(),
+        error: None,
+        consumed: AtomicBool::new(false),
     }))
 }
 
@@ -220,10 +396,13 @@ pub extern "C" fn bolide_thread_spawn_float_with_env(
     let env_addr = env as usize;
     let cancelled = Arc::new(AtomicBool::new(false));
 
+    let live = LiveThread::start();
     let handle = thread::spawn(move || {
+        let _live = live;
+        let _tls = crate::tls::ThreadExit;
         let f: extern "C" fn(*mut c_void) -> f64 = unsafe { std::mem::transmute(send_fn) };
         let env_ptr = env_addr as *mut c_void;
-        ThreadResult { float_val: f(env_ptr) }
+        run_task(|| ThreadResult { float_val: f(env_ptr) })
     });
 
     Box::into_raw(Box::new(BolideThreadHandle {
@@ -231,6 +410,9 @@ pub extern "C" fn bolide_thread_spawn_float_with_env(
         result: ThreadResult { float_val: 0.0 },
         has_result: false,
         cancelled,
+        name: String::new(),
+        error: None,
+        consumed: AtomicBool::new(false),
     }))
 }
 
@@ -244,10 +426,13 @@ pub extern "C" fn bolide_thread_spawn_ptr_with_env(
     let env_addr = env as usize;
     let cancelled = Arc::new(AtomicBool::new(false));
 
+    let live = LiveThread::start();
     let handle = thread::spawn(move || {
+        let _live = live;
+        let _tls = crate::tls::ThreadExit;
         let f: extern "C" fn(*mut c_void) -> *mut c_void = unsafe { std::mem::transmute(send_fn) };
         let env_ptr = env_addr as *mut c_void;
-        ThreadResult { ptr_val: f(env_ptr) }
+        run_task(|| ThreadResult { ptr_val: f(env_ptr) })
     });
 
     Box::into_raw(Box::new(BolideThreadHandle {
@@ -255,82 +440,148 @@ pub extern "C" fn bolide_thread_spawn_ptr_with_env(
         result: ThreadResult { ptr_val: std::ptr::null_mut() },
         has_result: false,
         cancelled,
+        name: String::new(),
+        error: None,
+        consumed: AtomicBool::new(false),
     }))
 }
 
-/// 等待线程完成并获取 int 类型结果
-#[no_mangle]
-pub extern "C" fn bolide_thread_join_int(handle: *mut BolideThreadHandle) -> i64 {
-    if handle.is_null() {
-        return 0;
-    }
-
-    let handle = unsafe { &mut *handle };
+/// 等待线程结束并保存结果和错误；句柄为空或线程 panic 时返回 None
+///
+/// # Safety
+/// `handle` 为空指针或还没有释放的线程句柄，返回的引用使用期间句柄不被释放
+unsafe fn thread_wait<'a>(handle: *mut BolideThreadHandle) -> Option<&'a mut BolideThreadHandle> {
+    let handle = handle.as_mut()?;
 
     if !handle.has_result {
-        if let Some(join_handle) = handle.handle.take() {
-            match join_handle.join() {
-                Ok(result) => {
-                    handle.result = result;
-                    handle.has_result = true;
-                }
-                Err(_) => return 0,
-            }
-        }
+        let join_handle = handle.handle.take()?;
+        let (result, error) = crate::executor::blocking(|| join_handle.join()).ok()?;
+        handle.result = result;
+        handle.error = error;
+        handle.has_result = true;
     }
 
-    unsafe { handle.result.int_val }
+    Some(handle)
 }
 
-/// 等待线程完成并获取 float 类型结果
-#[no_mangle]
-pub extern "C" fn bolide_thread_join_float(handle: *mut BolideThreadHandle) -> f64 {
-    if handle.is_null() {
-        return 0.0;
+/// 在等待方报告任务留下的错误并结束程序
+fn raise_task_error(kind: &str, name: &str, error: TaskError) {
+    if let Some((_, message)) = error {
+        crate::runtime_error(&task_error_message(kind, name, &message));
     }
+}
 
-    let handle = unsafe { &mut *handle };
+/// 再次等待结果是指针的任务时记录的错误
+///
+/// 指针结果（字符串、列表等）的所有权交给了第一次 join / await，调用方可能已经释放了它，
+/// 之后的等待不能再交出同一个指针，返回空指针并记录这个错误。
+pub(crate) fn consumed_twice(kind: &str, name: &str, op: &str) {
+    let task = if name.is_empty() { format!("{} task", kind) } else { format!("{} task '{}'", kind, name) };
+    crate::error::set_error(
+        crate::error::ERROR_VALUE,
+        &format!("{} was already {}; only the first {} gets its result", task, op, op.trim_end_matches("ed")),
+    );
+}
 
-    if !handle.has_result {
-        if let Some(join_handle) = handle.handle.take() {
-            match join_handle.join() {
-                Ok(result) => {
-                    handle.result = result;
-                    handle.has_result = true;
-                }
-                Err(_) => return 0.0,
-            }
+/// 把任务留下的错误记录为当前线程的错误，返回任务是否失败
+fn adopt_task_error(kind: &str, name: &str, error: TaskError) -> i64 {
+    match error {
+        Some((code, message)) => {
+            crate::error::set_error(code, &task_error_message(kind, name, &message));
+            1
         }
+        None => 0,
     }
+}
 
-    unsafe { handle.result.float_val }
+/// 取出任务名的文本并释放编译器交来的字符串（`*_set_name` 接管 name 的引用）
+///
+/// # Safety
+/// `name` 为空指针或有效的 `BolideString`，调用方不再使用它
+pub(crate) unsafe fn take_name(name: *mut BolideString) -> String {
+    if name.is_null() {
+        return String::new();
+    }
+    let text = (*name).as_str().to_string();
+    crate::string::bolide_string_release(name);
+    text
 }
 
-/// 等待线程完成并获取指针类型结果
+/// 记录线程句柄对应的函数名（编译器在 spawn 后调用，name 交给句柄）
+///
+/// # Safety
+/// `handle` 为空指针或还没有释放的线程句柄；`name` 为空指针或有效的 `BolideString`，调用方不再使用它
 #[no_mangle]
-pub extern "C" fn bolide_thread_join_ptr(handle: *mut BolideThreadHandle) -> *mut c_void {
-    if handle.is_null() {
-        return std::ptr::null_mut();
+pub unsafe extern "C" fn bolide_thread_set_name(handle: *mut BolideThreadHandle, name: *mut BolideString) {
+    let name = take_name(name);
+    if let Some(handle) = handle.as_mut() {
+        handle.name = name;
     }
+}
 
-    let handle = unsafe { &mut *handle };
-
-    if !handle.has_result {
-        if let Some(join_handle) = handle.handle.take() {
-            match join_handle.join() {
-                Ok(result) => {
-                    handle.result = result;
-                    handle.has_result = true;
-                }
-                Err(_) => return std::ptr::null_mut(),
-            }
-        }
+/// `join_ok`：等待线程结束；任务留下了错误时把它记录为当前线程的错误并返回 1，
+/// 之后的 join 只取结果，不再报告这个错误
+///
+/// # Safety
+/// `handle` 为空指针或还没有释放的线程句柄
+#[no_mangle]
+pub unsafe extern "C" fn bolide_thread_take_error(handle: *mut BolideThreadHandle) -> i64 {
+    match thread_wait(handle) {
+        Some(handle) => adopt_task_error("spawned", &handle.name, handle.error.take()),
+        None => 0,
     }
+}
+
+/// 等待线程完成并获取 int 类型结果
+///
+/// # Safety
+/// `handle` 为空指针或还没有释放的线程句柄
+#[no_mangle]
+pub unsafe extern "C" fn bolide_thread_join_int(handle: *mut BolideThreadHandle) -> i64 {
+    let Some(handle) = thread_wait(handle) else {
+        return 0;
+    };
+    raise_task_error("spawned", &handle.name, handle.error.take());
+    handle.consumed.store(true, Ordering::SeqCst);
+    handle.result.int_val
+}
 
-    unsafe { handle.result.ptr_val }
+/// 等待线程完成并获取 float 类型结果
+///
+/// # Safety
+/// `handle` 为空指针或还没有释放的线程句柄
+#[no_mangle]
+pub unsafe extern "C" fn bolide_thread_join_float(handle: *mut BolideThreadHandle) -> f64 {
+    let Some(handle) = thread_wait(handle) else {
+        return 0.0;
+    };
+    raise_task_error("spawned", &handle.name, handle.error.take());
+    handle.consumed.store(true, Ordering::SeqCst);
+    handle.result.float_val
+}
+
+/// 等待线程完成并获取指针类型结果
+///
+/// 只有第一次 join 拿到结果；之后的 join 返回空指针并记录错误（见 `consumed_twice`）
+///
+/// # Safety
+/// `handle` 为空指针或还没有释放的线程句柄
+#[no_mangle]
+pub unsafe extern "C" fn bolide_thread_join_ptr(handle: *mut BolideThreadHandle) -> *mut c_void {
+    let Some(handle) = thread_wait(handle) else {
+        return std::ptr::null_mut();
+    };
+    raise_task_error("spawned", &handle.name, handle.error.take());
+    if handle.consumed.swap(true, Ordering::SeqCst) {
+        consumed_twice("spawned", &handle.name, "joined");
+        return std::ptr::null_mut();
+    }
+    handle.result.ptr_val
 }
 
-/// 释放线程句柄
+/// 释放线程句柄；空句柄什么也不做
+///
+/// 编译器释放句柄后把变量置空，同一个变量上重复的释放因此是空操作。
 #[no_mangle]
 pub extern "C" fn bolide_thread_handle_free(handle: *mut BolideThreadHandle) {
     if !handle.is_null() {
@@ -362,12 +613,10 @@ pub extern "C" fn bolide_thread_is_cancelled(handle: *const BolideThreadHandle)
 }
 
 // ==================== 线程池 FFI ====================
-
-struct SendPtr(*mut BolideThreadPool);
-unsafe impl Send for SendPtr {}
-unsafe impl Sync for SendPtr {}
-
-static POOL_CONTEXT: Mutex<Option<SendPtr>> = Mutex::new(None);
+//
+// 线程池上下文是线程局部的：pool 块在当前线程上进入，工作线程执行任务期间进入所属线程池，
+// 协程继承启动它的线程的上下文。因此线程池任务（以及任务里启动的协程）中的 spawn
+// 仍提交到同一个线程池，不会另开线程。
 
 /// 创建线程池
 #[no_mangle]
@@ -376,158 +625,124 @@ pub extern "C" fn bolide_pool_create(size: i64) -> *mut BolideThreadPool {
     Box::into_raw(Box::new(pool))
 }
 
-/// 设置当前线程池上下文
+/// 进入线程池上下文（pool 块开始）
 #[no_mangle]
 pub extern "C" fn bolide_pool_enter(pool: *mut BolideThreadPool) {
-    let mut ctx = POOL_CONTEXT.lock().unwrap();
-    *ctx = Some(SendPtr(pool));
+    if pool.is_null() {
+        return;
+    }
+    let ctx = unsafe { (*pool).ctx.clone() };
+    POOL_STACK.with(|stack| stack.borrow_mut().push(ctx));
 }
 
-/// 清除当前线程池上下文
+/// 退出线程池上下文（pool 块结束），恢复外层的上下文
+///
+/// 旧版本编译的代码使用；现在的编译器生成 `bolide_pool_exit_checked`。
 #[no_mangle]
 pub extern "C" fn bolide_pool_exit() {
-    let mut ctx = POOL_CONTEXT.lock().unwrap();
-    *ctx = None;
+    if POOL_STACK.with(|stack| stack.borrow_mut().pop()).is_none() {
+        eprintln!("Warning: pool_exit without a matching pool_enter, ignored");
+    }
 }
 
-/// 检查是否在线程池上下文中
+/// 退出 `pool` 的上下文（pool 块结束，或 return / break 提前离开块）
+///
+/// 栈顶应当就是 `pool`；不是时记录后恢复：`pool` 在栈里，就连同它上面没有退出的上下文一起弹出
+/// （例如旧版本编译的代码从内层块提前返回，漏掉了 pool_exit）；不在栈里则保持不变。
 #[no_mangle]
-pub extern "C" fn bolide_pool_is_active() -> i64 {
-    let ctx = POOL_CONTEXT.lock().unwrap();
-    if ctx.is_some() { 1 } else { 0 }
+pub extern "C" fn bolide_pool_exit_checked(pool: *mut BolideThreadPool) {
+    if pool.is_null() {
+        return;
+    }
+    let ctx = unsafe { &(*pool).ctx };
+    POOL_STACK.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        match stack.iter().rposition(|c| c.same_pool(ctx)) {
+            Some(i) => {
+                if i + 1 != stack.len() {
+                    eprintln!("Warning: pool_exit found {} pool context(s) that were never exited, discarding them", stack.len() - i - 1);
+                }
+                stack.truncate(i);
+            }
+            None => eprintln!("Warning: pool_exit for a pool that is not the current pool context, ignored"),
+        }
+    });
 }
 
-/// 在线程池中执行返回 int 的任务
+/// 检查当前线程是否在线程池上下文中
 #[no_mangle]
-pub extern "C" fn bolide_pool_spawn_int(func_ptr: extern "C" fn() -> i64) -> *mut BolidePoolHandle {
-    let send_fn = SendFnPtr(func_ptr as *const c_void);
+pub extern "C" fn bolide_pool_is_active() -> i64 {
+    if current_pool().is_some() { 1 } else { 0 }
+}
 
+/// 提交到当前线程池；不在线程池上下文中（或线程池已关闭）时用普通线程执行
+fn pool_spawn(task: impl FnOnce() -> ThreadResult + Send + 'static) -> *mut BolidePoolHandle {
     let result: Arc<Mutex<Option<ThreadResult>>> = Arc::new(Mutex::new(None));
     let completed = Arc::new((Mutex::new(false), Condvar::new()));
 
+    let error: Arc<Mutex<TaskError>> = Arc::new(Mutex::new(None));
+
     let result_clone = Arc::clone(&result);
+    let error_clone = Arc::clone(&error);
     let completed_clone = Arc::clone(&completed);
 
-    let ctx = POOL_CONTEXT.lock().unwrap();
-    if let Some(ref send_ptr) = *ctx {
-        let pool = unsafe { &*send_ptr.0 };
-
-        let job = Box::new(move || {
-            let f: extern "C" fn() -> i64 = unsafe { std::mem::transmute(send_fn) };
-            let res = ThreadResult { int_val: f() };
-            *result_clone.lock().unwrap() = Some(res);
-            let (lock, cvar) = &*completed_clone;
-            *lock.lock().unwrap() = true;
-            cvar.notify_all();
-            res
-        });
+    let job: Job = Box::new(move || {
+        let (res, task_error) = run_task(task);
+        *error_clone.lock().unwrap() = task_error;
+        *result_clone.lock().unwrap() = Some(res);
+        let (lock, cvar) = &*completed_clone;
+        *lock.lock().unwrap() = true;
+        cvar.notify_all();
+        res
+    });
 
-        {
-            let mut queue = pool.sender.lock().unwrap();
-            queue.push_back(job);
-        }
-        pool.condvar.notify_one();
-    } else {
+    let mut pool = current_pool();
+    let rejected = match &pool {
+        Some(ctx) => ctx.submit(job).err(),
+        None => Some(job),
+    };
+    if let Some(job) = rejected {
         // 不在线程池上下文中，创建普通线程
+        pool = None;
+        let live = LiveThread::start();
         thread::spawn(move || {
-            let f: extern "C" fn() -> i64 = unsafe { std::mem::transmute(send_fn) };
-            let res = ThreadResult { int_val: f() };
-            *result_clone.lock().unwrap() = Some(res);
-            let (lock, cvar) = &*completed_clone;
-            *lock.lock().unwrap() = true;
-            cvar.notify_all();
+            let _live = live;
+            let _tls = crate::tls::ThreadExit;
+            job();
         });
     }
 
-    Box::into_raw(Box::new(BolidePoolHandle { result, completed }))
+    Box::into_raw(Box::new(BolidePoolHandle { result, error, completed, pool, name: String::new(), consumed: AtomicBool::new(false) }))
+}
+
+/// 在线程池中执行返回 int 的任务
+#[no_mangle]
+pub extern "C" fn bolide_pool_spawn_int(func_ptr: extern "C" fn() -> i64) -> *mut BolidePoolHandle {
+    let send_fn = SendFnPtr(func_ptr as *const c_void);
+    pool_spawn(move || {
+        let f: extern "C" fn() -> i64 = unsafe { std::mem::transmute(send_fn) };
+        ThreadResult { int_val: f() }
+    })
 }
 
 /// 在线程池中执行返回 float 的任务
 #[no_mangle]
 pub extern "C" fn bolide_pool_spawn_float(func_ptr: extern "C" fn() -> f64) -> *mut BolidePoolHandle {
     let send_fn = SendFnPtr(func_ptr as *const c_void);
-
-    let result: Arc<Mutex<Option<ThreadResult>>> = Arc::new(Mutex::new(None));
-    let completed = Arc::new((Mutex::new(false), Condvar::new()));
-
-    let result_clone = Arc::clone(&result);
-    let completed_clone = Arc::clone(&completed);
-
-    let ctx = POOL_CONTEXT.lock().unwrap();
-    if let Some(ref send_ptr) = *ctx {
-        let pool = unsafe { &*send_ptr.0 };
-
-        let job = Box::new(move || {
-            let f: extern "C" fn() -> f64 = unsafe { std::mem::transmute(send_fn) };
-            let res = ThreadResult { float_val: f() };
-            *result_clone.lock().unwrap() = Some(res);
-            let (lock, cvar) = &*completed_clone;
-            *lock.lock().unwrap() = true;
-            cvar.notify_all();
-            res
-        });
-
-        {
-            let mut queue = pool.sender.lock().unwrap();
-            queue.push_back(job);
-        }
-        pool.condvar.notify_one();
-    } else {
-        thread::spawn(move || {
-            let f: extern "C" fn() -> f64 = unsafe { std::mem::transmute(send_fn) };
-            let res = ThreadResult { float_val: f() };
-            *result_clone.lock().unwrap() = Some(res);
-            let (lock, cvar) = &*completed_clone;
-            *lock.lock().unwrap() = true;
-            cvar.notify_all();
-        });
-    }
-
-    Box::into_raw(Box::new(BolidePoolHandle { result, completed }))
+    pool_spawn(move || {
+        let f: extern "C" fn() -> f64 = unsafe { std::mem::transmute(send_fn) };
+        ThreadResult { float_val: f() }
+    })
 }
 
 /// 在线程池中执行返回指针的任务
 #[no_mangle]
 pub extern "C" fn bolide_pool_spawn_ptr(func_ptr: extern "C" fn() -> *mut c_void) -> *mut BolidePoolHandle {
     let send_fn = SendFnPtr(func_ptr as *const c_void);
-
-    let result: Arc<Mutex<Option<ThreadResult>>> = Arc::new(Mutex::new(None));
-    let completed = Arc::new((Mutex::new(false), Condvar::new()));
-
-    let result_clone = Arc::clone(&result);
-    let completed_clone = Arc::clone(&completed);
-
-    let ctx = POOL_CONTEXT.lock().unwrap();
-    if let Some(ref send_ptr) = *ctx {
-        let pool = unsafe { &*send_ptr.0 };
-
-        let job = Box::new(move || {
-            let f: extern "C" fn() -> *mut c_void = unsafe { std::mem::transmute(send_fn) };
-            let res = ThreadResult { ptr_val: f() };
-            *result_clone.lock().unwrap() = Some(res);
-            let (lock, cvar) = &*completed_clone;
-            *lock.lock().unwrap() = true;
-            cvar.notify_all();
-            res
-        });
-
-        {
-            let mut queue = pool.sender.lock().unwrap();
-            queue.push_back(job);
-        }
-        pool.condvar.notify_one();
-    } else {
-        thread::spawn(move || {
-            let f: extern "C" fn() -> *mut c_void = unsafe { std::mem::transmute(send_fn) };
-            let res = ThreadResult { ptr_val: f() };
-            *result_clone.lock().unwrap() = Some(res);
-            let (lock, cvar) = &*completed_clone;
-            *lock.lock().unwrap() = true;
-            cvar.notify_all();
-        });
-    }
-
-    Box::into_raw(Box::new(BolidePoolHandle { result, completed }))
+    pool_spawn(move || {
+        let f: extern "C" fn() -> *mut c_void = unsafe { std::mem::transmute(send_fn) };
+        ThreadResult { ptr_val: f() }
+    })
 }
 
 // ==================== 带环境的线程池 spawn FFI ====================
@@ -540,46 +755,10 @@ pub extern "C" fn bolide_pool_spawn_int_with_env(
 ) -> *mut BolidePoolHandle {
     let send_fn = SendFnPtr(func_ptr as *const c_void);
     let env_addr = env as usize;
-
-    let result: Arc<Mutex<Option<ThreadResult>>> = Arc::new(Mutex::new(None));
-    let completed = Arc::new((Mutex::new(false), Condvar::new()));
-
-    let result_clone = Arc::clone(&result);
-    let completed_clone = Arc::clone(&completed);
-
-    let ctx = POOL_CONTEXT.lock().unwrap();
-    if let Some(ref send_ptr) = *ctx {
-        let pool = unsafe { &*send_ptr.0 };
-
-        let job = Box::new(move || {
-            let f: extern "C" fn(*mut c_void) -> i64 = unsafe { std::mem::transmute(send_fn) };
-            let env_ptr = env_addr as *mut c_void;
-            let res = ThreadResult { int_val: f(env_ptr) };
-            *result_clone.lock().unwrap() = Some(res);
-            let (lock, cvar) = &*completed_clone;
-            *lock.lock().unwrap() = true;
-            cvar.notify_all();
-            res
-        });
-
-        {
-            let mut queue = pool.sender.lock().unwrap();
-            queue.push_back(job);
-        }
-        pool.condvar.notify_one();
-    } else {
-        thread::spawn(move || {
-            let f: extern "C" fn(*mut c_void) -> i64 = unsafe { std::mem::transmute(send_fn) };
-            let env_ptr = env_addr as *mut c_void;
-            let res = ThreadResult { int_val: f(env_ptr) };
-            *result_clone.lock().unwrap() = Some(res);
-            let (lock, cvar) = &*completed_clone;
-            *lock.lock().unwrap() = true;
-            cvar.notify_all();
-        });
-    }
-
-    Box::into_raw(Box::new(BolidePoolHandle { result, completed }))
+    pool_spawn(move || {
+        let f: extern "C" fn(*mut c_void) -> i64 = unsafe { std::mem::transmute(send_fn) };
+        ThreadResult { int_val: f(env_addr as *mut c_void) }
+    })
 }
 
 /// 在线程池中执行带环境的返回 float 的任务
@@ -590,46 +769,10 @@ pub extern "C" fn bolide_pool_spawn_float_with_env(
 ) -> *mut BolidePoolHandle {
     let send_fn = SendFnPtr(func_ptr as *const c_void);
     let env_addr = env as usize;
-
-    let result: Arc<Mutex<Option<ThreadResult>>> = Arc::new(Mutex::new(None));
-    let completed = Arc::new((Mutex::new(false), Condvar::new()));
-
-    let result_clone = Arc::clone(&result);
-    let completed_clone = Arc::clone(&completed);
-
-    let ctx = POOL_CONTEXT.lock().unwrap();
-    if let Some(ref send_ptr) = *ctx {
-        let pool = unsafe { &*send_ptr.0 };
-
-        let job = Box::new(move || {
-            let f: extern "C" fn(*mut c_void) -> f64 = unsafe { std::mem::transmute(send_fn) };
-            let env_ptr = env_addr as *mut c_void;
-            let res = ThreadResult { float_val: f(env_ptr) };
-            *result_clone.lock().unwrap() = Some(res);
-            let (lock, cvar) = &*completed_clone;
-            *lock.lock().unwrap() = true;
-            cvar.notify_all();
-            res
-        });
-
-        {
-            let mut queue = pool.sender.lock().unwrap();
-            queue.push_back(job);
-        }
-        pool.condvar.notify_one();
-    } else {
-        thread::spawn(move || {
-            let f: extern "C" fn(*mut c_void) -> f64 = unsafe { std::mem::transmute(send_fn) };
-            let env_ptr = env_addr as *mut c_void;
-            let res = ThreadResult { float_val: f(env_ptr) };
-            *result_clone.lock().unwrap() = Some(res);
-            let (lock, cvar) = &*completed_clone;
-            *lock.lock().unwrap() = true;
-            cvar.notify_all();
-        });
-    }
-
-    Box::into_raw(Box::new(BolidePoolHandle { result, completed }))
+    pool_spawn(move || {
+        let f: extern "C" fn(*mut c_void) -> f64 = unsafe { std::mem::transmute(send_fn) };
+        ThreadResult { float_val: f(env_addr as *mut c_void) }
+    })
 }
 
 /// 在线程池中执行带环境的返回指针的任务
@@ -640,112 +783,118 @@ pub extern "C" fn bolide_pool_spawn_ptr_with_env(
 ) -> *mut BolidePoolHandle {
     let send_fn = SendFnPtr(func_ptr as *const c_void);
     let env_addr = env as usize;
+    pool_spawn(move || {
+        let f: extern "C" fn(*mut c_void) -> *mut c_void = unsafe { std::mem::transmute(send_fn) };
+        ThreadResult { ptr_val: f(env_addr as *mut c_void) }
+    })
+}
 
-    let result: Arc<Mutex<Option<ThreadResult>>> = Arc::new(Mutex::new(None));
-    let completed = Arc::new((Mutex::new(false), Condvar::new()));
-
-    let result_clone = Arc::clone(&result);
-    let completed_clone = Arc::clone(&completed);
-
-    let ctx = POOL_CONTEXT.lock().unwrap();
-    if let Some(ref send_ptr) = *ctx {
-        let pool = unsafe { &*send_ptr.0 };
-
-        let job = Box::new(move || {
-            let f: extern "C" fn(*mut c_void) -> *mut c_void = unsafe { std::mem::transmute(send_fn) };
-            let env_ptr = env_addr as *mut c_void;
-            let res = ThreadResult { ptr_val: f(env_ptr) };
-            *result_clone.lock().unwrap() = Some(res);
-            let (lock, cvar) = &*completed_clone;
-            *lock.lock().unwrap() = true;
-            cvar.notify_all();
-            res
-        });
+/// 等待线程池任务完成
+///
+/// 在同一线程池的工作线程上等待时，先帮忙执行队列里的任务：
+/// 否则所有工作线程都在等自己提交的子任务时会死锁。
+fn pool_wait_completed(handle: &BolidePoolHandle) {
+    let (lock, cvar) = &*handle.completed;
+    let helping = handle.pool.as_ref().filter(|ctx| is_worker_of(ctx));
 
-        {
-            let mut queue = pool.sender.lock().unwrap();
-            queue.push_back(job);
+    let mut completed = lock.lock().unwrap();
+    while !*completed {
+        match helping.and_then(|ctx| ctx.try_pop()) {
+            Some(job) => {
+                drop(completed);
+                job();
+                completed = lock.lock().unwrap();
+            }
+            // 队列空了：任务正在别的工作线程上运行，短暂等待后再看队列
+            None if helping.is_some() => {
+                completed = cvar.wait_timeout(completed, Duration::from_millis(1)).unwrap().0;
+            }
+            None => {
+                completed = crate::executor::blocking(|| cvar.wait(completed)).unwrap();
+            }
         }
-        pool.condvar.notify_one();
-    } else {
-        thread::spawn(move || {
-            let f: extern "C" fn(*mut c_void) -> *mut c_void = unsafe { std::mem::transmute(send_fn) };
-            let env_ptr = env_addr as *mut c_void;
-            let res = ThreadResult { ptr_val: f(env_ptr) };
-            *result_clone.lock().unwrap() = Some(res);
-            let (lock, cvar) = &*completed_clone;
-            *lock.lock().unwrap() = true;
-            cvar.notify_all();
-        });
     }
+}
 
-    Box::into_raw(Box::new(BolidePoolHandle { result, completed }))
+/// 等待线程池任务完成并返回结果和之前是否已经 join 过；任务留下了错误时报告并结束程序
+///
+/// 结果留在句柄里，再次 join 时返回同一个结果。
+///
+/// # Safety
+/// `handle` 为空指针或还没有释放的线程池任务句柄，返回的引用使用期间句柄不被释放
+unsafe fn pool_wait<'a>(handle: *mut BolidePoolHandle) -> Option<(ThreadResult, &'a BolidePoolHandle, bool)> {
+    let handle = handle.as_ref()?;
+    pool_wait_completed(handle);
+    raise_task_error("spawned", &handle.name, handle.error.lock().unwrap().take());
+    let result = (*handle.result.lock().unwrap())?;
+    Some((result, handle, handle.consumed.swap(true, Ordering::SeqCst)))
 }
 
-/// 等待线程池任务完成并获取 int 结果
+/// 记录线程池任务句柄对应的函数名（编译器在 spawn 后调用，name 交给句柄）
+///
+/// # Safety
+/// `handle` 为空指针或还没有释放的线程池任务句柄；`name` 为空指针或有效的 `BolideString`，调用方不再使用它
 #[no_mangle]
-pub extern "C" fn bolide_pool_join_int(handle: *mut BolidePoolHandle) -> i64 {
+pub unsafe extern "C" fn bolide_pool_set_name(handle: *mut BolidePoolHandle, name: *mut BolideString) {
+    let name = take_name(name);
+    if let Some(handle) = handle.as_mut() {
+        handle.name = name;
+    }
+}
+
+/// `join_ok`：等待线程池任务完成，同 `bolide_thread_take_error`
+#[no_mangle]
+pub extern "C" fn bolide_pool_take_error(handle: *mut BolidePoolHandle) -> i64 {
     if handle.is_null() {
         return 0;
     }
-
     let handle = unsafe { &*handle };
-    let (lock, cvar) = &*handle.completed;
-
-    let mut completed = lock.lock().unwrap();
-    while !*completed {
-        completed = cvar.wait(completed).unwrap();
-    }
+    pool_wait_completed(handle);
+    let error = handle.error.lock().unwrap().take();
+    adopt_task_error("spawned", &handle.name, error)
+}
 
-    match handle.result.lock().unwrap().take() {
-        Some(res) => unsafe { res.int_val },
+/// 等待线程池任务完成并获取 int 结果
+///
+/// # Safety
+/// `handle` 为空指针或还没有释放的线程池任务句柄
+#[no_mangle]
+pub unsafe extern "C" fn bolide_pool_join_int(handle: *mut BolidePoolHandle) -> i64 {
+    match pool_wait(handle) {
+        Some((res, _, _)) => res.int_val,
         None => 0,
     }
 }
 
 /// 等待线程池任务完成并获取 float 结果
+///
+/// # Safety
+/// `handle` 为空指针或还没有释放的线程池任务句柄
 #[no_mangle]
-pub extern "C" fn bolide_pool_join_float(handle: *mut BolidePoolHandle) -> f64 {
-    if handle.is_null() {
-        return 0.0;
-    }
-
-    let handle = unsafe { &*handle };
-    let (lock, cvar) = &*handle.completed;
-
-    let mut completed = lock.lock().unwrap();
-    while !*completed {
-        completed = cvar.wait(completed).unwrap();
-    }
-
-    match handle.result.lock().unwrap().take() {
-        Some(res) => unsafe { res.float_val },
+pub unsafe extern "C" fn bolide_pool_join_float(handle: *mut BolidePoolHandle) -> f64 {
+    match pool_wait(handle) {
+        Some((res, _, _)) => res.float_val,
         None => 0.0,
     }
 }
 
-/// 等待线程池任务完成并获取指针结果
+/// 等待线程池任务完成并获取指针结果；再次 join 同 `bolide_thread_join_ptr`
+///
+/// # Safety
+/// `handle` 为空指针或还没有释放的线程池任务句柄
 #[no_mangle]
-pub extern "C" fn bolide_pool_join_ptr(handle: *mut BolidePoolHandle) -> *mut c_void {
-    if handle.is_null() {
-        return std::ptr::null_mut();
-    }
-
-    let handle = unsafe { &*handle };
-    let (lock, cvar) = &*handle.completed;
-
-    let mut completed = lock.lock().unwrap();
-    while !*completed {
-        completed = cvar.wait(completed).unwrap();
-    }
-
-    match handle.result.lock().unwrap().take() {
-        Some(res) => unsafe { res.ptr_val },
+pub unsafe extern "C" fn bolide_pool_join_ptr(handle: *mut BolidePoolHandle) -> *mut c_void {
+    match pool_wait(handle) {
+        Some((_, handle, true)) => {
+            consumed_twice("spawned", &handle.name, "joined");
+            std::ptr::null_mut()
+        }
+        Some((res, _, false)) => res.ptr_val,
         None => std::ptr::null_mut(),
     }
 }
 
-/// 释放线程池任务句柄
+/// 释放线程池任务句柄；空句柄什么也不做，同 `bolide_thread_handle_free`
 #[no_mangle]
 pub extern "C" fn bolide_pool_handle_free(handle: *mut BolidePoolHandle) {
     if !handle.is_null() {
@@ -759,9 +908,181 @@ pub extern "C" fn bolide_pool_handle_free(handle: *mut BolidePoolHandle) {
 #[no_mangle]
 pub extern "C" fn bolide_pool_destroy(pool: *mut BolideThreadPool) {
     if !pool.is_null() {
-        unsafe {
-            let mut pool = Box::from_raw(pool);
-            pool.shutdown();
+        let mut pool = unsafe { Box::from_raw(pool) };
+        // 仍是当前上下文时移出：之后的 spawn 不能再提交到已销毁的线程池
+        POOL_STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            let before = stack.len();
+            stack.retain(|c| !c.same_pool(&pool.ctx));
+            if stack.len() != before {
+                eprintln!("Warning: pool destroyed while still the current pool context, leaving it");
+            }
+        });
+        pool.shutdown();
+    }
+}
+
+// ==================== 测试 ====================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn slow_task() -> i64 {
+        thread::sleep(Duration::from_millis(50));
+        7
+    }
+
+    #[test]
+    fn test_live_threads_tracks_detached_spawn() {
+        let handle = bolide_thread_spawn_int(slow_task);
+        // 计数在 spawn 返回前就已增加
+        assert!(live_threads() >= 1);
+        assert!(wait_for_threads(Duration::from_secs(5)));
+        assert_eq!(unsafe { bolide_thread_join_int(handle) }, 7);
+        bolide_thread_handle_free(handle);
+    }
+
+    static TASK_THREADS: Mutex<Vec<i64>> = Mutex::new(Vec::new());
+
+    extern "C" fn leaf_task() -> i64 {
+        TASK_THREADS.lock().unwrap().push(bolide_thread_id());
+        thread::sleep(Duration::from_millis(5));
+        bolide_pool_is_active()
+    }
+
+    extern "C" fn parent_task() -> i64 {
+        TASK_THREADS.lock().unwrap().push(bolide_thread_id());
+        let handles: Vec<_> = (0..3).map(|_| bolide_pool_spawn_int(leaf_task)).collect();
+        let mut active = bolide_pool_is_active();
+        for handle in handles {
+            active += unsafe { bolide_pool_join_int(handle) };
+            bolide_pool_handle_free(handle);
+        }
+        active
+    }
+
+    extern "C" fn failing_task() -> i64 {
+        crate::error::set_error(crate::error::ERROR_VALUE, "bad input");
+        3
+    }
+
+    #[test]
+    fn test_take_error_adopts_task_error() {
+        let name = BolideString::new("parse");
+        let handle = bolide_thread_spawn_int(failing_task);
+        unsafe { bolide_thread_set_name(handle, name) };
+        assert_eq!(unsafe { bolide_thread_take_error(handle) }, 1);
+        assert_eq!(
+            crate::error::last_error(),
+            Some((crate::error::ERROR_VALUE, "error in spawned task 'parse': bad input".to_string()))
+        );
+        // 错误已经交给等待方，join 只取结果
+        assert_eq!(unsafe { bolide_thread_take_error(handle) }, 0);
+        assert_eq!(unsafe { bolide_thread_join_int(handle) }, 3);
+        bolide_thread_handle_free(handle);
+        crate::bolide_error_clear();
+
+        // 不在线程池上下文中：回退为普通线程
+        let handle = bolide_pool_spawn_int(failing_task);
+        assert_eq!(bolide_pool_take_error(handle), 1);
+        assert_eq!(crate::error::last_error().unwrap().1, "error in spawned task: bad input");
+        assert_eq!(unsafe { bolide_pool_join_int(handle) }, 3);
+        bolide_pool_handle_free(handle);
+
+        // 任务看不到 spawn 方的错误，spawn 方的错误也不受任务影响
+        let handle = bolide_pool_spawn_int(slow_task);
+        assert_eq!(bolide_pool_take_error(handle), 0);
+        assert_eq!(crate::error::last_error().unwrap().1, "error in spawned task: bad input");
+        bolide_pool_handle_free(handle);
+        crate::bolide_error_clear();
+    }
+
+    extern "C" fn ptr_task() -> *mut c_void {
+        BolideString::new("done") as *mut c_void
+    }
+
+    #[test]
+    fn test_join_twice() {
+        crate::bolide_error_clear();
+        // int 结果：再次 join 返回缓存的结果
+        let handle = bolide_thread_spawn_int(slow_task);
+        assert_eq!(unsafe { bolide_thread_join_int(handle) }, 7);
+        assert_eq!(unsafe { bolide_thread_join_int(handle) }, 7);
+        bolide_thread_handle_free(handle);
+        let handle = bolide_pool_spawn_int(slow_task);
+        assert_eq!(unsafe { bolide_pool_join_int(handle) }, 7);
+        assert_eq!(unsafe { bolide_pool_join_int(handle) }, 7);
+        bolide_pool_handle_free(handle);
+        assert_eq!(crate::error::last_error(), None);
+
+        // 指针结果只交给第一次 join
+        let name = BolideString::new("make");
+        let handle = bolide_thread_spawn_ptr(ptr_task);
+        unsafe { bolide_thread_set_name(handle, name) };
+        let result = unsafe { bolide_thread_join_ptr(handle) };
+        assert!(!result.is_null());
+        assert!(unsafe { bolide_thread_join_ptr(handle) }.is_null());
+        assert_eq!(
+            crate::error::last_error(),
+            Some((crate::error::ERROR_VALUE, "spawned task 'make' was already joined; only the first join gets its result".to_string()))
+        );
+        crate::bolide_string_release(result as *mut BolideString);
+        bolide_thread_handle_free(handle);
+        crate::bolide_error_clear();
+
+        let handle = bolide_pool_spawn_ptr(ptr_task);
+        let result = unsafe { bolide_pool_join_ptr(handle) };
+        assert!(unsafe { bolide_pool_join_ptr(handle) }.is_null());
+        assert_eq!(crate::error::last_error().unwrap().1, "spawned task was already joined; only the first join gets its result");
+        crate::bolide_string_release(result as *mut BolideString);
+        bolide_pool_handle_free(handle);
+        crate::bolide_error_clear();
+
+        // 释放过的句柄由编译器置空，空句柄的释放什么也不做
+        bolide_thread_handle_free(std::ptr::null_mut());
+        bolide_pool_handle_free(std::ptr::null_mut());
+    }
+
+    #[test]
+    fn test_pool_exit_recovers_from_unbalanced_stack() {
+        let outer = bolide_pool_create(1);
+        let inner = bolide_pool_create(1);
+        bolide_pool_enter(outer);
+        bolide_pool_enter(inner);
+        // 漏掉了 inner 的退出：退出 outer 时一起弹出
+        bolide_pool_exit_checked(outer);
+        assert_eq!(bolide_pool_is_active(), 0);
+        // 不在栈里的线程池：保持不变
+        bolide_pool_enter(outer);
+        bolide_pool_exit_checked(inner);
+        assert_eq!(bolide_pool_is_active(), 1);
+        // 销毁仍是当前上下文的线程池时移出，之后的 spawn 不会提交给它
+        bolide_pool_destroy(outer);
+        assert_eq!(bolide_pool_is_active(), 0);
+        bolide_pool_destroy(inner);
+    }
+
+    #[test]
+    fn test_pool_tasks_inherit_context() {
+        let pool = bolide_pool_create(2);
+        bolide_pool_enter(pool);
+        let handles: Vec<_> = (0..4).map(|_| bolide_pool_spawn_int(parent_task)).collect();
+        for handle in handles {
+            // 每个父任务和它的 3 个子任务都看到线程池上下文
+            assert_eq!(unsafe { bolide_pool_join_int(handle) }, 4);
+            bolide_pool_handle_free(handle);
         }
+        bolide_pool_exit();
+        assert_eq!(bolide_pool_is_active(), 0);
+        bolide_pool_destroy(pool);
+
+        let ids = TASK_THREADS.lock().unwrap();
+        assert_eq!(ids.len(), 4 + 4 * 3);
+        let mut distinct = ids.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert!(distinct.len() <= 2, "tasks ran on {} threads", distinct.len());
+        assert!(!distinct.contains(&bolide_thread_id()));
     }
 }
@@ -0,0 +1,206 @@
+//! 计时器和 measure 块
+//!
+//! - `timer_start()` / `timer_elapsed_ns(t)` / `timer_elapsed_ms(t)`：单调时钟，单位纳秒
+//! - `duration`：`100ms`、`1.5s` 等字面量，表示为 i64 纳秒；打印时选最大的能整除到小数的单位
+//! - `deadline(d)` / `remaining(dl)`：单调时钟上的绝对时间点（纳秒），超时 API 用它表示截止时间
+//! - `measure "label" { ... }`：编译器在块前调用 `bolide_measure_begin`，
+//!   块结束（包括块内提前 return）时调用 `bolide_measure_end` 打印耗时和分配增量
+//!
+//! 分配增量来自 bigint / tuple 的调试计数器。嵌套深度按线程记录，用于缩进输出。
+
+use std::cell::Cell;
+use std::sync::OnceLock;
+use std::time::Instant;
+use crate::output::outln;
+use crate::string::BolideString;
+
+/// 进程内的计时零点（第一次取时间时确定）
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+thread_local! {
+    /// 当前线程正在执行的 measure 块层数
+    static MEASURE_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// 自零点以来的纳秒数（单调）
+fn now_ns() -> i64 {
+    let epoch = EPOCH.get_or_init(Instant::now);
+    epoch.elapsed().as_nanos() as i64
+}
+
+/// 格式化 duration：取 s / ms / us / ns 中不超过它的最大单位，小数部分精确并去掉末尾的 0
+pub fn format_duration(ns: i64) -> String {
+    const UNITS: [(i128, &str, usize); 3] = [(1_000_000_000, "s", 9), (1_000_000, "ms", 6), (1_000, "us", 3)];
+    let value = ns as i128;
+    let sign = if value < 0 { "-" } else { "" };
+    let abs = value.abs();
+    for (scale, unit, digits) in UNITS {
+        if abs >= scale {
+            let whole = abs / scale;
+            let frac = abs % scale;
+            if frac == 0 {
+                return format!("{}{}{}", sign, whole, unit);
+            }
+            let frac = format!("{:0width$}", frac, width = digits);
+            return format!("{}{}.{}{}", sign, whole, frac.trim_end_matches('0'), unit);
+        }
+    }
+    if abs == 0 {
+        "0s".to_string()
+    } else {
+        format!("{}{}ns", sign, abs)
+    }
+}
+
+/// 格式化一行 measure 输出
+fn format_measure(depth: usize, label: &str, elapsed_ns: i64, allocs: i64, early: bool) -> String {
+    format!(
+        "{}[measure] {}: {:.3} ms, {} allocs{}",
+        "  ".repeat(depth),
+        label,
+        elapsed_ns as f64 / 1_000_000.0,
+        allocs,
+        if early { " (early return)" } else { "" }
+    )
+}
+
+// ==================== FFI 导出 ====================
+
+/// 开始计时，返回单调时钟读数（纳秒）
+#[no_mangle]
+pub extern "C" fn bolide_timer_start() -> i64 {
+    now_ns()
+}
+
+/// 自 `start` 以来经过的纳秒数
+#[no_mangle]
+pub extern "C" fn bolide_timer_elapsed_ns(start: i64) -> i64 {
+    now_ns() - start
+}
+
+/// 自 `start` 以来经过的毫秒数
+#[no_mangle]
+pub extern "C" fn bolide_timer_elapsed_ms(start: i64) -> f64 {
+    bolide_timer_elapsed_ns(start) as f64 / 1_000_000.0
+}
+
+/// `deadline(d)`：从现在起 `d` 纳秒后的时间点（超出 i64 时饱和）
+#[no_mangle]
+pub extern "C" fn bolide_deadline(duration_ns: i64) -> i64 {
+    now_ns().saturating_add(duration_ns)
+}
+
+/// `remaining(dl)`：距离时间点 `deadline` 还剩的纳秒数，已过期时为 0
+#[no_mangle]
+pub extern "C" fn bolide_deadline_remaining(deadline: i64) -> i64 {
+    deadline.saturating_sub(now_ns()).max(0)
+}
+
+/// duration 的字符串形式，见 `format_duration`
+#[no_mangle]
+pub extern "C" fn bolide_string_from_duration(ns: i64) -> *mut BolideString {
+    BolideString::new(&format_duration(ns))
+}
+
+/// 分配计数快照（bigint + tuple）
+#[no_mangle]
+pub extern "C" fn bolide_alloc_snapshot() -> i64 {
+    crate::bigint::bigint_alloc_count() + crate::tuple::tuple_alloc_count()
+}
+
+/// 进入 measure 块，返回开始时间
+#[no_mangle]
+pub extern "C" fn bolide_measure_begin() -> i64 {
+    MEASURE_DEPTH.with(|d| d.set(d.get() + 1));
+    now_ns()
+}
+
+/// 离开 measure 块并打印结果
+///
+/// `label` 指向 UTF-8 字节（编译器常量），`early` 非 0 表示从块内提前 return。
+#[no_mangle]
+pub extern "C" fn bolide_measure_end(label: *const u8, label_len: i64, start: i64, alloc_start: i64, early: i64) {
+    let elapsed = now_ns() - start;
+    let allocs = bolide_alloc_snapshot() - alloc_start;
+    let depth = MEASURE_DEPTH.with(|d| {
+        let depth = d.get().saturating_sub(1);
+        d.set(depth);
+        depth
+    });
+    let label = if label.is_null() || label_len <= 0 {
+        ""
+    } else {
+        let bytes = unsafe { std::slice::from_raw_parts(label, label_len as usize) };
+        std::str::from_utf8(bytes).unwrap_or("?")
+    };
+    outln!("{}", format_measure(depth, label, elapsed, allocs, early != 0));
+}
+
+// ==================== 测试 ====================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timer_monotonic() {
+        let mut last = bolide_timer_start();
+        for _ in 0..1000 {
+            let now = bolide_timer_start();
+            assert!(now >= last);
+            last = now;
+        }
+    }
+
+    #[test]
+    fn test_timer_scale() {
+        let t = bolide_timer_start();
+        std::thread::sleep(Duration::from_millis(20));
+        let ns = bolide_timer_elapsed_ns(t);
+        assert!(ns >= 20_000_000, "elapsed {} ns", ns);
+        assert!(ns < 2_000_000_000, "elapsed {} ns", ns);
+        let ms = bolide_timer_elapsed_ms(t);
+        assert!((20.0..2000.0).contains(&ms), "elapsed {} ms", ms);
+    }
+
+    #[test]
+    fn test_measure_depth() {
+        let outer = bolide_measure_begin();
+        let inner = bolide_measure_begin();
+        MEASURE_DEPTH.with(|d| assert_eq!(d.get(), 2));
+        bolide_measure_end(b"inner".as_ptr(), 5, inner, bolide_alloc_snapshot(), 0);
+        bolide_measure_end(b"outer".as_ptr(), 5, outer, bolide_alloc_snapshot(), 1);
+        MEASURE_DEPTH.with(|d| assert_eq!(d.get(), 0));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(30_000), "30us");
+        assert_eq!(format_duration(100_000_000), "100ms");
+        assert_eq!(format_duration(1_500_000_000), "1.5s");
+        assert_eq!(format_duration(5_400_000_000_000), "5400s");
+        assert_eq!(format_duration(1_000_001), "1.000001ms");
+        assert_eq!(format_duration(-250), "-250ns");
+        assert_eq!(format_duration(i64::MIN), "-9223372036.854775808s");
+    }
+
+    #[test]
+    fn test_deadline_remaining() {
+        let dl = bolide_deadline(50_000_000);
+        let left = bolide_deadline_remaining(dl);
+        assert!(left > 0 && left <= 50_000_000, "remaining {} ns", left);
+        assert_eq!(bolide_deadline_remaining(bolide_deadline(-1)), 0);
+        assert_eq!(bolide_deadline(i64::MAX), i64::MAX);
+    }
+
+    #[test]
+    fn test_format_measure() {
+        assert_eq!(
+            format_measure(2, "phase", 1_500_000, 3, true),
+            "    [measure] phase: 1.500 ms, 3 allocs (early return)"
+        );
+        assert_eq!(format_measure(0, "x", 0, 0, false), "[measure] x: 0.000 ms, 0 allocs");
+    }
+}
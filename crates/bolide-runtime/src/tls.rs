@@ -0,0 +1,153 @@
+//! 线程局部的全局变量：`threadlocal let x: T = init;`
+//!
+//! 程序开始时编译器为每个 threadlocal 全局变量分配一个槽（`bolide_tls_alloc`），槽号存在该全局变量
+//! 的数据段里；读写先用 `bolide_tls_get` 取得当前线程的 8 字节单元，再直接 load / store。
+//!
+//! 单元在线程第一次访问时创建（初值为 0），随后调用槽的初始化函数：它是编译后的初始化表达式，
+//! 像普通赋值一样通过 `bolide_tls_get` 把初值写进刚创建的单元。线程结束时按槽号的逆序调用析构函数
+//! 释放单元里的值：spawn 出的线程、线程池和协程的工作线程在退出时自动执行（`ThreadExit`），
+//! 主线程在释放全局变量时调用 `bolide_tls_thread_exit`。
+
+use std::cell::RefCell;
+use std::sync::Mutex;
+
+/// 初始化函数：把初值写入当前线程的单元
+pub type TlsInit = extern "C" fn();
+/// 析构函数：释放单元里的值（非 RC 类型没有）
+pub type TlsDtor = extern "C" fn(i64);
+
+#[derive(Clone, Copy)]
+struct Slot {
+    init: TlsInit,
+    dtor: Option<TlsDtor>,
+}
+
+static SLOTS: Mutex<Vec<Slot>> = Mutex::new(Vec::new());
+
+thread_local! {
+    /// 当前线程已创建的单元，按槽号索引；Box 保证单元地址不随 Vec 扩容变化
+    static CELLS: RefCell<Vec<Option<Box<i64>>>> = const { RefCell::new(Vec::new()) };
+}
+
+fn slots() -> std::sync::MutexGuard<'static, Vec<Slot>> {
+    SLOTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 运行当前线程全部单元的析构函数并清空单元；之后再访问会重新初始化
+pub fn tls_thread_exit() {
+    let cells = CELLS.with(|cells| std::mem::take(&mut *cells.borrow_mut()));
+    if cells.is_empty() {
+        return;
+    }
+    let dtors: Vec<Option<TlsDtor>> = slots().iter().map(|slot| slot.dtor).collect();
+    for (key, cell) in cells.into_iter().enumerate().rev() {
+        if let (Some(cell), Some(Some(dtor))) = (cell, dtors.get(key)) {
+            dtor(*cell);
+        }
+    }
+}
+
+/// 线程结束守卫：放在线程闭包开头，闭包返回（包括 panic）时释放本线程的单元
+pub(crate) struct ThreadExit;
+
+impl Drop for ThreadExit {
+    fn drop(&mut self) {
+        tls_thread_exit();
+    }
+}
+
+// ==================== FFI 导出 ====================
+
+/// 分配一个槽，返回槽号；`dtor` 可以为空
+#[no_mangle]
+pub extern "C" fn bolide_tls_alloc(init: TlsInit, dtor: Option<TlsDtor>) -> i64 {
+    let mut slots = slots();
+    slots.push(Slot { init, dtor });
+    (slots.len() - 1) as i64
+}
+
+/// 当前线程在槽 `key` 的单元，第一次访问时创建并初始化
+#[no_mangle]
+pub extern "C" fn bolide_tls_get(key: i64) -> *mut i64 {
+    let index = key as usize;
+    let existing = CELLS.with(|cells| {
+        cells.borrow_mut().get_mut(index)
+            .and_then(|cell| cell.as_mut())
+            .map(|cell| &mut **cell as *mut i64)
+    });
+    if let Some(cell) = existing {
+        return cell;
+    }
+    let Some(init) = slots().get(index).map(|slot| slot.init) else {
+        return std::ptr::null_mut();
+    };
+    // 先登记单元，初始化函数再次取得的是同一个单元
+    let mut cell = Box::new(0i64);
+    let ptr = &mut *cell as *mut i64;
+    CELLS.with(|cells| {
+        let mut cells = cells.borrow_mut();
+        if cells.len() <= index {
+            cells.resize_with(index + 1, || None);
+        }
+        cells[index] = Some(cell);
+    });
+    // 初始化表达式可能读取其他 threadlocal 变量，调用时不持有 CELLS 的借用
+    init();
+    ptr
+}
+
+/// 释放当前线程的全部单元（主线程在程序结束时调用）
+#[no_mangle]
+pub extern "C" fn bolide_tls_thread_exit() {
+    tls_thread_exit();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    static KEY: AtomicI64 = AtomicI64::new(-1);
+    static INITS: AtomicI64 = AtomicI64::new(0);
+    static DROPPED: AtomicI64 = AtomicI64::new(0);
+
+    extern "C" fn init_counter() {
+        INITS.fetch_add(1, Ordering::SeqCst);
+        unsafe { *bolide_tls_get(KEY.load(Ordering::SeqCst)) = 100 };
+    }
+
+    extern "C" fn drop_counter(value: i64) {
+        DROPPED.fetch_add(value, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_cells_are_per_thread() {
+        let key = bolide_tls_alloc(init_counter, Some(drop_counter));
+        KEY.store(key, Ordering::SeqCst);
+        let bump = move |n: i64| {
+            let _exit = ThreadExit;
+            for _ in 0..n {
+                let cell = bolide_tls_get(key);
+                unsafe { *cell += 1 };
+            }
+            unsafe { *bolide_tls_get(key) }
+        };
+        let a = std::thread::spawn(move || bump(3));
+        let b = std::thread::spawn(move || bump(5));
+        assert_eq!(a.join().unwrap(), 103);
+        assert_eq!(b.join().unwrap(), 105);
+        assert_eq!(INITS.load(Ordering::SeqCst), 2);
+        // 线程结束时析构函数收到各自的值
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 208);
+
+        // 同一线程内单元地址不变；退出后重新初始化
+        let first = bolide_tls_get(key);
+        assert_eq!(first, bolide_tls_get(key));
+        unsafe { *first = 7 };
+        tls_thread_exit();
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 215);
+        assert_eq!(unsafe { *bolide_tls_get(key) }, 100);
+        tls_thread_exit();
+        assert!(bolide_tls_get(i64::MAX).is_null());
+    }
+}
@@ -0,0 +1,241 @@
+//! 影子调用栈（`bolide run --debug`）
+//!
+//! 以调试模式编译的函数在入口调用 `bolide_trace_push`，每条返回路径上调用 `bolide_trace_pop`；
+//! 非调试模式不生成这些调用，没有任何开销。调用栈按线程记录，运行时错误的报告路径
+//! （`runtime_error`、可恢复错误的 `set_error`）打印 `at foo (line 12)`，最内层的函数在前；
+//! 可恢复错误的调用栈同时记下，由 `last_error_trace` 读取。栈为空（没有以调试模式编译的代码在运行）时不打印。
+//!
+//! 函数名指向编译出的代码里的只读数据，和代码活得一样久；行号是函数定义所在的行，0 表示编译器生成的函数。
+//!
+//! 采样线程（`bolide run --sample`，见 `sample.rs`）要跨线程读取调用栈。完整的栈只由本线程读写；
+//! 另外把最内层 `SHARED_DEPTH` 帧的函数名镜像到一个环形数组里，用单写者的序号锁保护：
+//! 压栈、弹栈只是几次普通的原子写，不加锁；采样线程读到一半被改动时重读。
+//! 线程第一次压栈时登记到全局列表。
+
+use std::cell::RefCell;
+use std::sync::atomic::{fence, AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+/// 一帧：函数名（UTF-8 字节）和定义所在的行
+struct Frame {
+    name: *const u8,
+    len: usize,
+    line: i64,
+}
+
+impl Frame {
+    fn name(&self) -> String {
+        let bytes = unsafe { std::slice::from_raw_parts(self.name, self.len) };
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+/// 跨线程可读的帧数：更深的调用栈只保留最内层的这些帧
+const SHARED_DEPTH: usize = 256;
+/// 采样线程读到被改动的栈时重读的次数，超过后放弃这一次
+const SNAPSHOT_RETRIES: usize = 8;
+
+#[derive(Default)]
+struct SharedFrame {
+    name: AtomicPtr<u8>,
+    len: AtomicUsize,
+}
+
+/// 一个线程的调用栈镜像，别的线程只读
+pub(crate) struct ThreadTrace {
+    /// 序号锁：写入期间为奇数
+    seq: AtomicU64,
+    depth: AtomicUsize,
+    frames: Box<[SharedFrame]>,
+}
+
+impl ThreadTrace {
+    fn new() -> Self {
+        Self {
+            seq: AtomicU64::new(0),
+            depth: AtomicUsize::new(0),
+            frames: (0..SHARED_DEPTH).map(|_| SharedFrame::default()).collect(),
+        }
+    }
+
+    /// 本线程写入：`depth` 层时最内层是 `top`（弹栈时为 None）
+    fn publish(&self, depth: usize, top: Option<(*const u8, usize)>) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        self.seq.store(seq + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        if let Some((name, len)) = top {
+            let slot = &self.frames[(depth - 1) % SHARED_DEPTH];
+            slot.name.store(name as *mut u8, Ordering::Relaxed);
+            slot.len.store(len, Ordering::Relaxed);
+        }
+        self.depth.store(depth, Ordering::Relaxed);
+        self.seq.store(seq + 2, Ordering::Release);
+    }
+
+    /// 当前的调用栈，最外层在前；每帧是函数名的地址和长度。读不到一致的栈时清空 `out`
+    pub(crate) fn snapshot(&self, out: &mut Vec<(*const u8, usize)>) {
+        for _ in 0..SNAPSHOT_RETRIES {
+            out.clear();
+            let before = self.seq.load(Ordering::Acquire);
+            if before % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let depth = self.depth.load(Ordering::Relaxed);
+            for i in depth.saturating_sub(SHARED_DEPTH)..depth {
+                let slot = &self.frames[i % SHARED_DEPTH];
+                out.push((slot.name.load(Ordering::Relaxed) as *const u8, slot.len.load(Ordering::Relaxed)));
+            }
+            fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == before {
+                return;
+            }
+        }
+        out.clear();
+    }
+}
+
+/// 压过栈的线程，线程结束后对应的项失效，`for_each_thread_trace` 时清掉
+static THREADS: Mutex<Vec<Weak<ThreadTrace>>> = Mutex::new(Vec::new());
+
+/// 本线程的完整调用栈和它的镜像
+struct LocalTrace {
+    frames: RefCell<Vec<Frame>>,
+    shared: Arc<ThreadTrace>,
+}
+
+impl LocalTrace {
+    fn new() -> Self {
+        let shared = Arc::new(ThreadTrace::new());
+        THREADS.lock().unwrap_or_else(|e| e.into_inner()).push(Arc::downgrade(&shared));
+        Self { frames: RefCell::new(Vec::new()), shared }
+    }
+}
+
+/// 对每个还活着、压过栈的线程调用 `f`
+pub(crate) fn for_each_thread_trace(mut f: impl FnMut(&ThreadTrace)) {
+    let live: Vec<Arc<ThreadTrace>> = {
+        let mut threads = THREADS.lock().unwrap_or_else(|e| e.into_inner());
+        threads.retain(|t| t.strong_count() > 0);
+        threads.iter().filter_map(Weak::upgrade).collect()
+    };
+    for trace in &live {
+        f(trace);
+    }
+}
+
+/// 压过影子调用栈、还活着的线程数；没有以调试模式编译的代码运行过时为 0
+pub fn traced_threads() -> usize {
+    let mut count = 0;
+    for_each_thread_trace(|_| count += 1);
+    count
+}
+
+thread_local! {
+    static TRACE: LocalTrace = LocalTrace::new();
+    /// 最近一次可恢复错误时的调用栈
+    static ERROR_TRACE: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 进入函数：压入一帧
+#[no_mangle]
+pub extern "C" fn bolide_trace_push(name: *const u8, len: usize, line: i64) {
+    if name.is_null() {
+        return;
+    }
+    TRACE.with(|t| {
+        let mut frames = t.frames.borrow_mut();
+        frames.push(Frame { name, len, line });
+        t.shared.publish(frames.len(), Some((name, len)));
+    });
+}
+
+/// 离开函数：弹出最内层的一帧
+#[no_mangle]
+pub extern "C" fn bolide_trace_pop() {
+    TRACE.with(|t| {
+        let mut frames = t.frames.borrow_mut();
+        if frames.pop().is_some() {
+            t.shared.publish(frames.len(), None);
+        }
+    });
+}
+
+/// 把当前线程的调用栈打印到标准错误，最内层在前
+#[no_mangle]
+pub extern "C" fn bolide_trace_dump() {
+    for line in trace_lines() {
+        eprintln!("{}", line);
+    }
+}
+
+/// 可恢复错误的报告路径：打印调用栈，并记下供 `last_error_trace` 读取
+pub(crate) fn report_error_trace() {
+    let lines = trace_lines();
+    for line in &lines {
+        eprintln!("{}", line);
+    }
+    ERROR_TRACE.with(|t| *t.borrow_mut() = lines);
+}
+
+/// 当前线程最近一次以调试模式报告的可恢复错误发生时的调用栈，格式同 `trace_lines`
+pub fn last_error_trace() -> Vec<String> {
+    ERROR_TRACE.with(|t| t.borrow().clone())
+}
+
+/// 当前线程调用栈的文本，每帧一行，最内层在前
+pub fn trace_lines() -> Vec<String> {
+    TRACE.with(|t| {
+        t.frames.borrow().iter().rev().map(|frame| match frame.line {
+            0 => format!("    at {}", frame.name()),
+            line => format!("    at {} (line {})", frame.name(), line),
+        }).collect()
+    })
+}
+
+/// 当前线程的调用栈深度
+pub fn trace_depth() -> usize {
+    TRACE.with(|t| t.frames.borrow().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_push_pop() {
+        let (outer, inner) = ("main", "helper");
+        bolide_trace_push(outer.as_ptr(), outer.len(), 3);
+        bolide_trace_push(inner.as_ptr(), inner.len(), 0);
+        assert_eq!(trace_lines(), ["    at helper", "    at main (line 3)"]);
+
+        bolide_trace_pop();
+        assert_eq!(trace_lines(), ["    at main (line 3)"]);
+        bolide_trace_pop();
+        assert_eq!(trace_depth(), 0);
+        // 多余的 pop 不出错
+        bolide_trace_pop();
+        assert!(trace_lines().is_empty());
+    }
+
+    #[test]
+    fn test_shared_snapshot_keeps_innermost_frames() {
+        let names: Vec<String> = (0..SHARED_DEPTH + 3).map(|i| format!("f{}", i)).collect();
+        for name in &names {
+            bolide_trace_push(name.as_ptr(), name.len(), 0);
+        }
+        let mut frames = Vec::new();
+        TRACE.with(|t| t.shared.snapshot(&mut frames));
+        let read: Vec<String> = frames.iter()
+            .map(|&(name, len)| String::from_utf8_lossy(unsafe { std::slice::from_raw_parts(name, len) }).into_owned())
+            .collect();
+        // 超过 SHARED_DEPTH 层时镜像只有最内层的帧
+        assert_eq!(read, names[3..]);
+
+        for _ in &names {
+            bolide_trace_pop();
+        }
+        TRACE.with(|t| t.shared.snapshot(&mut frames));
+        assert!(frames.is_empty());
+    }
+}
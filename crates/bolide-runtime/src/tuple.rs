@@ -34,6 +34,7 @@ impl BolideTuple {
 }
 
 use std::sync::atomic::{AtomicI64, Ordering};
+use crate::output::outln;
 
 // Debug: 跟踪 Tuple 分配和释放
 static TUPLE_ALLOC_COUNT: AtomicI64 = AtomicI64::new(0);
@@ -99,7 +100,12 @@ pub extern "C" fn bolide_tuple_free(ptr: *mut BolideTuple) {
 pub extern "C" fn bolide_tuple_debug_stats() {
     let alloc = TUPLE_ALLOC_COUNT.load(Ordering::SeqCst);
     let free = TUPLE_FREE_COUNT.load(Ordering::SeqCst);
-    println!("[Tuple Stats] alloc: {}, free: {}, leak: {}", alloc, free, alloc - free);
+    outln!("[Tuple Stats] alloc: {}, free: {}, leak: {}", alloc, free, alloc - free);
+}
+
+/// 累计分配次数（measure 块统计分配增量用）
+pub(crate) fn tuple_alloc_count() -> i64 {
+    TUPLE_ALLOC_COUNT.load(Ordering::SeqCst)
 }
 
 // ==================== 元素访问 ====================
@@ -153,25 +159,27 @@ pub extern "C" fn bolide_tuple_len(ptr: *const BolideTuple) -> usize {
 
 // ==================== 打印 ====================
 
-/// 打印元组 (简单版本，所有元素作为 i64 打印)
-#[no_mangle]
-pub extern "C" fn bolide_print_tuple(ptr: *const BolideTuple) {
-    if ptr.is_null() {
-        println!("()");
-        return;
-    }
-
-    unsafe {
-        let len = (*ptr).len;
-        let data = (*ptr).data_ptr();
-
-        print!("(");
-        for i in 0..len {
-            if i > 0 {
-                print!(", ");
+/// 元组的打印形式 (简单版本，所有元素作为 i64)
+pub(crate) fn format_tuple(ptr: *const BolideTuple) -> String {
+    let mut text = String::from("(");
+    if !ptr.is_null() {
+        unsafe {
+            let len = (*ptr).len;
+            let data = (*ptr).data_ptr();
+            for i in 0..len {
+                if i > 0 {
+                    text.push_str(", ");
+                }
+                text.push_str(&(*data.add(i)).to_string());
             }
-            print!("{}", *data.add(i));
         }
-        println!(")");
     }
+    text.push(')');
+    text
+}
+
+/// 打印元组 (简单版本，所有元素作为 i64 打印)
+#[no_mangle]
+pub extern "C" fn bolide_print_tuple(ptr: *const BolideTuple) {
+    outln!("{}", format_tuple(ptr));
 }
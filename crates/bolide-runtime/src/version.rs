@@ -0,0 +1,225 @@
+//! 版本和特性检测（`version()` / `version_at_least()` / `has_feature()`）
+//!
+//! 版本号是运行时 crate 的语义化版本。`version_at_least` 按 SemVer 2.0 的优先级比较：
+//! 主版本、次版本、修订号按数值比较；带预发布标签的版本低于对应的正式版本，
+//! 预发布标签逐段比较（数字段按数值、数字段低于字母段、段数少的更低）；构建元数据不参与比较。
+//!
+//! 特性表由编译器生成并在启动时注册：JIT 直接调用 `register_features`，
+//! AOT 把特性名写进数据段，由入口 `main` 调用 `bolide_register_features`。
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::sync::RwLock;
+
+use crate::error::{set_error, ERROR_VALUE};
+use crate::string::{intern_str, BolideString};
+
+/// 运行时的版本号
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// 解析后的语义化版本
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    /// 预发布标签按 `.` 分开的各段，正式版本为空
+    pub pre: Vec<String>,
+}
+
+impl Version {
+    /// 解析 `MAJOR.MINOR.PATCH[-PRE][+BUILD]`，格式不对时返回 `None`
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.split_once('+').map_or(text, |(core, build)| {
+            if build.is_empty() { "" } else { core }
+        });
+        let (core, pre) = match text.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (text, None),
+        };
+
+        let mut numbers = core.split('.').map(parse_numeric);
+        let (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) =
+            (numbers.next(), numbers.next(), numbers.next(), numbers.next())
+        else {
+            return None;
+        };
+
+        let pre = match pre {
+            None => Vec::new(),
+            Some(pre) => {
+                let fields: Vec<String> = pre.split('.').map(str::to_string).collect();
+                let valid = fields.iter().all(|f| {
+                    !f.is_empty()
+                        && f.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+                        && (!f.bytes().all(|b| b.is_ascii_digit()) || parse_numeric(f).is_some())
+                });
+                if !valid {
+                    return None;
+                }
+                fields
+            }
+        };
+        Some(Self { major, minor, patch, pre })
+    }
+}
+
+/// 数字段：不能为空，不能有前导零
+fn parse_numeric(field: &str) -> Option<u64> {
+    if field.is_empty() || !field.bytes().all(|b| b.is_ascii_digit()) || (field.len() > 1 && field.starts_with('0')) {
+        return None;
+    }
+    field.parse().ok()
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch).cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => compare_pre(&self.pre, &other.pre),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn compare_pre(a: &[String], b: &[String]) -> Ordering {
+    for (x, y) in a.iter().zip(b) {
+        let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => x.cmp(y),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// 当前运行时版本是否不低于 `required`；`required` 不是合法的语义化版本时返回 `None`
+pub fn version_at_least(required: &str) -> Option<bool> {
+    let current = Version::parse(VERSION).expect("crate version is valid semver");
+    Version::parse(required).map(|required| current >= required)
+}
+
+static FEATURES: RwLock<Option<HashSet<String>>> = RwLock::new(None);
+
+/// 注册编译器的特性表，重复注册时合并
+pub fn register_features<'a>(names: impl IntoIterator<Item = &'a str>) {
+    let mut features = FEATURES.write().unwrap_or_else(|e| e.into_inner());
+    features.get_or_insert_with(HashSet::new)
+        .extend(names.into_iter().filter(|n| !n.is_empty()).map(str::to_string));
+}
+
+/// 特性 `name` 是否在已注册的特性表里
+pub fn has_feature(name: &str) -> bool {
+    let features = FEATURES.read().unwrap_or_else(|e| e.into_inner());
+    features.as_ref().is_some_and(|f| f.contains(name))
+}
+
+/// version() -> str：运行时的语义化版本
+///
+/// 返回驻留字符串，与字符串字面量一样不会被释放。
+#[no_mangle]
+pub extern "C" fn bolide_version() -> *mut BolideString {
+    intern_str(VERSION)
+}
+
+/// version_at_least(v) -> bool；`v` 不是合法的语义化版本时记录 `ERROR_VALUE` 并返回 false
+///
+/// # Safety
+/// `required` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_version_at_least(required: *const BolideString) -> i64 {
+    let required = required.as_ref().map_or("", |s| s.as_str());
+    match version_at_least(required) {
+        Some(at_least) => at_least as i64,
+        None => {
+            set_error(ERROR_VALUE, &format!("version_at_least: invalid version '{}'", required));
+            0
+        }
+    }
+}
+
+/// 注册特性表：`names` 指向 `len` 字节、以 `\n` 分隔的特性名（AOT 入口调用）
+///
+/// # Safety
+/// `names` 为空指针，或指向至少 `len` 个可读字节
+#[no_mangle]
+pub unsafe extern "C" fn bolide_register_features(names: *const u8, len: usize) {
+    if names.is_null() {
+        return;
+    }
+    let bytes = std::slice::from_raw_parts(names, len);
+    register_features(String::from_utf8_lossy(bytes).split('\n'));
+}
+
+/// has_feature(name) -> bool
+///
+/// # Safety
+/// `name` 为空指针或有效的 `BolideString`
+#[no_mangle]
+pub unsafe extern "C" fn bolide_has_feature(name: *const BolideString) -> i64 {
+    name.as_ref().is_some_and(|name| has_feature(name.as_str())) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(text: &str) -> Version {
+        Version::parse(text).unwrap_or_else(|| panic!("invalid version {}", text))
+    }
+
+    #[test]
+    fn test_version_parse() {
+        assert_eq!(v("1.2.3"), Version { major: 1, minor: 2, patch: 3, pre: vec![] });
+        assert_eq!(v("1.0.0-rc.1+build.5").pre, ["rc", "1"]);
+        for bad in ["", "1", "1.2", "1.2.3.4", "01.2.3", "1.2.x", "1.2.3-", "1.2.3-rc..1", "1.2.3-01", "1.2.3+"] {
+            assert!(Version::parse(bad).is_none(), "{} should be rejected", bad);
+        }
+        assert!(Version::parse(VERSION).is_some());
+    }
+
+    #[test]
+    fn test_version_precedence() {
+        // SemVer 2.0 规范里的示例顺序
+        let ordered = ["1.0.0-alpha", "1.0.0-alpha.1", "1.0.0-alpha.beta", "1.0.0-beta", "1.0.0-beta.2",
+            "1.0.0-beta.11", "1.0.0-rc.1", "1.0.0", "1.0.1", "1.1.0", "2.0.0", "10.0.0"];
+        for pair in ordered.windows(2) {
+            assert!(v(pair[0]) < v(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+        assert_eq!(v("1.0.0+a").cmp(&v("1.0.0+b")), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_version_at_least_boundaries() {
+        let current = v(VERSION);
+        let at_least = |text: &str| version_at_least(text).unwrap();
+
+        assert!(at_least(VERSION));
+        assert!(at_least("0.0.0"));
+        assert!(at_least(&format!("{}.{}.{}-rc.1", current.major, current.minor, current.patch)));
+        assert!(!at_least(&format!("{}.{}.{}", current.major, current.minor, current.patch + 1)));
+        assert!(!at_least(&format!("{}.0.0", current.major + 1)));
+        assert_eq!(version_at_least("0.4"), None);
+    }
+
+    #[test]
+    fn test_has_feature() {
+        register_features("test-feature-a\ntest-feature-b".split('\n'));
+        assert!(has_feature("test-feature-a"));
+        assert!(has_feature("test-feature-b"));
+        assert!(!has_feature("test-feature-c"));
+        assert!(!has_feature(""));
+    }
+}
@@ -0,0 +1,321 @@
+//! 弱键字典 `weak_dict<K, V>`
+//!
+//! 键是类实例，字典只持有键的弱句柄（见 `object::ObjectWeakCell`），不会让键一直活着；
+//! 值和普通字典一样由字典持有（按值类型 retain / release）。
+//!
+//! 查找用探测键的地址在表中定位，再确认表项的句柄仍然存活：键对象释放后它的地址可能被新对象复用，
+//! 这时旧表项已经失效，不会和新对象混淆。
+//!
+//! 键失效的表项惰性清理：查找、插入和删除碰到失效表项时当场移除；`len()` 和 `compact()`
+//! 先清理所有失效表项，所以 `len()` 总是当前存活的表项数。清理时释放字典持有的值和键的句柄。
+
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use crate::list::ElementType;
+use crate::object::{bolide_object_downgrade, bolide_object_weak_release, ObjectWeakCell};
+use crate::rc::TypeTag;
+
+/// RC 对象头
+#[repr(C)]
+struct RcHeader {
+    strong_count: Cell<u32>,
+    weak_count: Cell<u32>,
+    type_tag: TypeTag,
+    flags: Cell<u8>,
+    _padding: [u8; 6],
+}
+
+/// 一个表项：键的弱句柄和字典持有的值
+struct Entry {
+    key: *mut ObjectWeakCell,
+    value: i64,
+}
+
+impl Entry {
+    fn is_alive(&self) -> bool {
+        unsafe { (*self.key).is_alive() }
+    }
+}
+
+/// Bolide 弱键字典（带引用计数）
+#[repr(C)]
+pub struct BolideWeakDict {
+    header: RcHeader,
+    /// 键对象地址 -> 表项
+    entries: HashMap<usize, Entry>,
+    value_type: ElementType,
+}
+
+impl BolideWeakDict {
+    /// 创建新字典（ref_count = 1）
+    pub fn new(value_type: ElementType) -> *mut Self {
+        Box::into_raw(Box::new(Self {
+            header: RcHeader {
+                strong_count: Cell::new(1),
+                weak_count: Cell::new(1),
+                type_tag: TypeTag::WeakDict,
+                flags: Cell::new(0),
+                _padding: [0; 6],
+            },
+            entries: HashMap::new(),
+            value_type,
+        }))
+    }
+
+    /// 增加引用计数
+    pub fn retain(&self) {
+        self.header.strong_count.set(self.header.strong_count.get() + 1);
+    }
+
+    /// 减少引用计数，返回是否应该释放
+    pub fn release(&self) -> bool {
+        let count = self.header.strong_count.get();
+        debug_assert!(count > 0, "release on already freed weak_dict");
+        self.header.strong_count.set(count - 1);
+        count == 1
+    }
+
+    /// 释放表项持有的句柄和值
+    fn drop_entry(&self, entry: Entry) {
+        bolide_object_weak_release(entry.key);
+        unsafe { self.value_type.release_value(entry.value) };
+    }
+
+    /// 键 `key` 的存活表项；表项的键已失效时顺便移除
+    fn live_entry(&mut self, key: *mut u8) -> Option<&mut Entry> {
+        let addr = key as usize;
+        if self.entries.get(&addr).is_some_and(|e| !e.is_alive()) {
+            let stale = self.entries.remove(&addr).unwrap();
+            self.drop_entry(stale);
+        }
+        self.entries.get_mut(&addr)
+    }
+
+    /// 设置键值对：字典 retain 新值，覆盖时释放旧值
+    pub fn set(&mut self, key: *mut u8, value: i64) {
+        if key.is_null() {
+            crate::runtime_error("weak_dict key is nil");
+        }
+        let value_type = self.value_type;
+        // 先 retain 新值再释放旧值：两者是同一个对象时不会提前释放
+        unsafe { value_type.retain_value(value) };
+        match self.live_entry(key) {
+            Some(entry) => {
+                let old = std::mem::replace(&mut entry.value, value);
+                unsafe { value_type.release_value(old) };
+            }
+            None => {
+                let entry = Entry { key: bolide_object_downgrade(key), value };
+                self.entries.insert(key as usize, entry);
+            }
+        }
+    }
+
+    /// 获取值（借用）
+    pub fn get(&mut self, key: *mut u8) -> Option<i64> {
+        self.live_entry(key).map(|e| e.value)
+    }
+
+    /// 移除键值对，返回是否存在；字典持有的值被释放
+    pub fn remove(&mut self, key: *mut u8) -> bool {
+        if self.live_entry(key).is_none() {
+            return false;
+        }
+        let entry = self.entries.remove(&(key as usize)).unwrap();
+        self.drop_entry(entry);
+        true
+    }
+
+    /// 移除所有键已失效的表项，返回移除的个数
+    pub fn compact(&mut self) -> usize {
+        let dead: Vec<usize> = self.entries.iter()
+            .filter(|(_, e)| !e.is_alive())
+            .map(|(&addr, _)| addr)
+            .collect();
+        for addr in &dead {
+            let entry = self.entries.remove(addr).unwrap();
+            self.drop_entry(entry);
+        }
+        dead.len()
+    }
+
+    /// 存活的表项数（先清理失效的表项）
+    pub fn len(&mut self) -> usize {
+        self.compact();
+        self.entries.len()
+    }
+
+    /// 是否没有存活的表项
+    pub fn is_empty(&mut self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for BolideWeakDict {
+    fn drop(&mut self) {
+        for (_, entry) in std::mem::take(&mut self.entries) {
+            self.drop_entry(entry);
+        }
+    }
+}
+
+// ==================== FFI 接口 ====================
+
+/// 创建弱键字典，`value_type` 是值的 ElementType
+#[no_mangle]
+pub extern "C" fn bolide_weakdict_new(value_type: u8) -> *mut BolideWeakDict {
+    let vt = unsafe { std::mem::transmute::<u8, ElementType>(value_type) };
+    BolideWeakDict::new(vt)
+}
+
+/// 增加引用计数
+#[no_mangle]
+pub extern "C" fn bolide_weakdict_retain(dict: *mut BolideWeakDict) {
+    if !dict.is_null() {
+        unsafe { (*dict).retain(); }
+    }
+}
+
+/// 减少引用计数，归零时释放所有表项
+#[no_mangle]
+pub extern "C" fn bolide_weakdict_release(dict: *mut BolideWeakDict) {
+    if dict.is_null() { return; }
+    unsafe {
+        if (*dict).release() {
+            let _ = Box::from_raw(dict);
+        }
+    }
+}
+
+/// 克隆字典：新字典有自己的句柄，值各 retain 一份；失效的表项不复制
+#[no_mangle]
+pub extern "C" fn bolide_weakdict_clone(dict: *const BolideWeakDict) -> *mut BolideWeakDict {
+    if dict.is_null() { return std::ptr::null_mut(); }
+    unsafe {
+        let src = &*dict;
+        let copy = BolideWeakDict::new(src.value_type);
+        for entry in src.entries.values().filter(|e| e.is_alive()) {
+            (*copy).set((*entry.key).object(), entry.value);
+        }
+        copy
+    }
+}
+
+/// 设置键值对
+#[no_mangle]
+pub extern "C" fn bolide_weakdict_set(dict: *mut BolideWeakDict, key: *mut u8, value: i64) {
+    if dict.is_null() { return; }
+    unsafe { (*dict).set(key, value); }
+}
+
+/// 获取值（借用）；键不存在时记录 `ERROR_KEY` 并返回 0（指针类型的值为空指针）
+#[no_mangle]
+pub extern "C" fn bolide_weakdict_get(dict: *mut BolideWeakDict, key: *mut u8) -> i64 {
+    if dict.is_null() { return 0; }
+    unsafe { (*dict).get(key) }.unwrap_or_else(|| {
+        crate::set_error(crate::ERROR_KEY, "key not found in weak_dict");
+        0
+    })
+}
+
+/// 检查键是否存在
+#[no_mangle]
+pub extern "C" fn bolide_weakdict_contains(dict: *mut BolideWeakDict, key: *mut u8) -> i64 {
+    if dict.is_null() { return 0; }
+    unsafe { (*dict).get(key).is_some() as i64 }
+}
+
+/// 移除键值对，返回是否存在
+#[no_mangle]
+pub extern "C" fn bolide_weakdict_remove(dict: *mut BolideWeakDict, key: *mut u8) -> i64 {
+    if dict.is_null() { return 0; }
+    unsafe { (*dict).remove(key) as i64 }
+}
+
+/// 存活的表项数（先清理失效的表项）
+#[no_mangle]
+pub extern "C" fn bolide_weakdict_len(dict: *mut BolideWeakDict) -> i64 {
+    if dict.is_null() { return 0; }
+    unsafe { (*dict).len() as i64 }
+}
+
+/// 是否没有存活的表项
+#[no_mangle]
+pub extern "C" fn bolide_weakdict_is_empty(dict: *mut BolideWeakDict) -> i64 {
+    if dict.is_null() { return 1; }
+    unsafe { (*dict).is_empty() as i64 }
+}
+
+/// 清理键已失效的表项，返回清理的个数
+#[no_mangle]
+pub extern "C" fn bolide_weakdict_compact(dict: *mut BolideWeakDict) -> i64 {
+    if dict.is_null() { return 0; }
+    unsafe { (*dict).compact() as i64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::{object_alloc, object_release};
+    use crate::{bolide_string_release, BolideString};
+
+    fn text(value: i64) -> String {
+        unsafe { (*(value as *const BolideString)).as_str().to_string() }
+    }
+
+    #[test]
+    fn test_entries_vanish_with_keys() {
+        let dict = bolide_weakdict_new(ElementType::String as u8);
+        let keys: Vec<*mut u8> = (0..6).map(|_| object_alloc(8)).collect();
+        let values: Vec<*mut BolideString> = (0..6).map(|i| BolideString::new(&format!("node {}", i))).collect();
+        for (&key, &value) in keys.iter().zip(&values) {
+            bolide_weakdict_set(dict, key, value as i64);
+        }
+        // 字典持有每个值的一份引用
+        assert!(values.iter().all(|&v| unsafe { (*v).ref_count() } == 2));
+        assert_eq!(bolide_weakdict_len(dict), 6);
+
+        // 覆盖同一个键不增加表项，旧值被释放
+        let again = BolideString::new("again");
+        bolide_weakdict_set(dict, keys[0], again as i64);
+        bolide_string_release(again);
+        assert_eq!(unsafe { (*values[0]).ref_count() }, 1);
+        assert_eq!(text(bolide_weakdict_get(dict, keys[0])), "again");
+        assert_eq!(bolide_weakdict_len(dict), 6);
+
+        // 释放一半的键：表项还在表里，compact() 清理它们并释放值
+        for &key in &keys[..3] {
+            object_release(key);
+        }
+        assert_eq!(unsafe { (*dict).entries.len() }, 6);
+        assert_eq!(bolide_weakdict_compact(dict), 3);
+        assert_eq!(bolide_weakdict_compact(dict), 0);
+        assert!(values[..3].iter().all(|&v| unsafe { (*v).ref_count() } == 1));
+        assert_eq!(bolide_weakdict_len(dict), 3);
+        assert_eq!(text(bolide_weakdict_get(dict, keys[4])), "node 4");
+
+        // len() 同样先清理；失效键地址上的新对象不会命中旧表项
+        object_release(keys[3]);
+        assert_eq!(bolide_weakdict_len(dict), 2);
+        assert_eq!(unsafe { (*values[3]).ref_count() }, 1);
+        let fresh = object_alloc(8);
+        assert_eq!(bolide_weakdict_contains(dict, fresh), 0);
+        assert_eq!(bolide_weakdict_get(dict, fresh), 0);
+        crate::bolide_error_clear();
+
+        assert_eq!(bolide_weakdict_remove(dict, keys[4]), 1);
+        assert_eq!(bolide_weakdict_remove(dict, keys[4]), 0);
+        assert_eq!(bolide_weakdict_len(dict), 1);
+
+        // 释放字典时释放剩下的值
+        bolide_weakdict_release(dict);
+        assert!(values.iter().all(|&v| unsafe { (*v).ref_count() } == 1));
+        for key in [keys[4], keys[5], fresh] {
+            object_release(key);
+        }
+        for value in values {
+            bolide_string_release(value);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+/* 调用 calc.bl 编译出的静态库（libcalc.a + calc.h）：
+ *   bolide compile tests/c_library/calc.bl --emit lib -o build/libcalc.a
+ *   cc tests/c_library/main.c -Ibuild build/libcalc.a target/debug/libbolide_runtime.a -lm -lpthread -ldl
+ */
+#include <stdio.h>
+
+#include "calc.h"
+
+void bolide_print_string(const void *s);
+
+int main(void) {
+    printf("add: %lld\n", (long long)add(40, 2));
+    printf("fib: %lld\n", (long long)fib(50));
+    printf("hypot2: %g\n", hypot2(3.0, 4.0));
+    printf("is_even: %lld %lld\n", (long long)is_even(10), (long long)is_even(7));
+
+    /* 传入的字符串由被调用的函数释放；之后还要用的值先 retain */
+    void *name = bolide_string_new("C");
+    void *greeting = greet(bolide_string_retain(name));
+    printf("greeting length: %zu\n", bolide_string_len(greeting));
+    fflush(stdout);
+    bolide_print_string(greeting);
+    /* 标准输出不是终端时 Bolide 的输出有缓冲，接着用 printf 前先刷新 */
+    bolide_print_flush();
+    bolide_string_release(greeting);
+    printf("name length: %zu\n", bolide_string_len(name));
+    bolide_string_release(name);
+
+    printf("words: %lld\n", (long long)count_words(bolide_string_new("one two three")));
+
+    fflush(stdout);
+    report(add(40, 2));
+    return 0;
+}